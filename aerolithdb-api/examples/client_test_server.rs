@@ -0,0 +1,57 @@
+//! Standalone node serving the REST API from a temporary directory, for the
+//! JavaScript client's tests in `aerolithdb-client-js/test`.
+//!
+//! Prints the base URL of the REST API once it accepts requests and serves
+//! until the process is killed.
+//!
+//! ```bash
+//! cargo run -p aerolithdb-api --example client_test_server
+//! ```
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use aerolithdb_api::{RESTAPIConfig, RESTAPIv1, VersioningConfig};
+use aerolithdb_cache::{CacheConfig, IntelligentCacheSystem};
+use aerolithdb_consensus::{ConsensusConfig, ConsensusEngine};
+use aerolithdb_query::{QueryConfig, QueryEngine};
+use aerolithdb_security::SecurityFramework;
+use aerolithdb_storage::{StorageConfig, StorageHierarchy};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("aerolith-client-test-{}", uuid::Uuid::new_v4()));
+    let storage_config = StorageConfig {
+        data_dir: root.join("data"),
+        // There is no other node to hold replicas
+        replication_factor: 1,
+        ..Default::default()
+    };
+    let cache_config = CacheConfig { nvme_dir: root.join("cache"), ..Default::default() };
+
+    let storage = Arc::new(StorageHierarchy::new(&storage_config).await?);
+    storage.start().await?;
+    let cache = Arc::new(IntelligentCacheSystem::new(&cache_config).await?);
+    let security = Arc::new(SecurityFramework::new(&Default::default()).await?);
+    let consensus = Arc::new(ConsensusEngine::new(&ConsensusConfig::default(), Arc::clone(&security), Arc::clone(&storage)).await?);
+    let query = Arc::new(QueryEngine::new(QueryConfig::default(), storage, cache, Arc::clone(&security)).await?);
+    query.start().await?;
+
+    // A port nothing listens on yet
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let config = RESTAPIConfig {
+        enabled: true,
+        bind_address: "127.0.0.1".to_string(),
+        port,
+        cors_enabled: false,
+        sandbox: None,
+        mirror: None,
+        versioning: VersioningConfig::default(),
+    };
+    RESTAPIv1::new(&config, query, security, consensus).await?.start().await?;
+
+    println!("http://127.0.0.1:{}", port);
+    std::future::pending::<()>().await;
+    Ok(())
+}
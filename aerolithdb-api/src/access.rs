@@ -0,0 +1,551 @@
+//! # Request Access Policy
+//!
+//! One admission policy for every protocol, so a request is treated the
+//! same whether it arrives over REST, gRPC or WebSocket:
+//!
+//! - ✅ Bearer token authentication against the configured API tokens
+//! - ✅ Tenant context from the token, or from the tenant header when the
+//!   token is not bound to a tenant
+//! - ✅ Token bucket rate limiting per principal, or per client address for
+//!   anonymous requests, shared across protocols
+//! - ✅ Admission counters per protocol and outcome, exported to Prometheus
+//!
+//! REST applies the policy with [`access_middleware`]. The gRPC services
+//! admit each request they handle with [`AccessInterceptor::admit`], which
+//! also works as a tonic interceptor, and WebSocket request frames are
+//! admitted under the credentials their connection was accepted with.
+//! Admitted requests carry a [`SaaSContext`] in their extensions describing
+//! who made them and for which tenant.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use tonic::metadata::MetadataValue;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::middleware::SaaSContext;
+use crate::sandbox::TokenBucket;
+use crate::versioning::escape_label;
+
+/// Rate limiter buckets kept before idle ones are forgotten
+const MAX_BUCKETS: usize = 10_000;
+
+/// Buckets untouched for this long have refilled and can be forgotten
+const BUCKET_IDLE: Duration = Duration::from_secs(600);
+
+/// An API token and whom it authenticates.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Secret presented as `Authorization: Bearer <token>`
+    pub token: String,
+
+    /// Name the token's requests are attributed to and rate limited under
+    pub principal: String,
+
+    /// Tenant the token is restricted to; unbound tokens pick a tenant with
+    /// the tenant header
+    #[serde(default)]
+    pub tenant_id: Option<Uuid>,
+}
+
+impl std::fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("token", &"<redacted>")
+            .field("principal", &self.principal)
+            .field("tenant_id", &self.tenant_id)
+            .finish()
+    }
+}
+
+/// Sustained rate and burst allowed to each principal or client address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_minute: u32,
+
+    /// Requests allowed in a burst before throttling starts
+    pub burst: u32,
+}
+
+/// Who may call the APIs, and how often.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessConfig {
+    /// Refuse requests without a valid token; when off, anonymous requests
+    /// are admitted but a presented token must still be valid
+    pub require_auth: bool,
+
+    pub tokens: Vec<ApiToken>,
+
+    /// Header (REST) or metadata key (gRPC) naming the tenant of a request
+    pub tenant_header: String,
+
+    /// Unlimited when `None`
+    pub rate_limit: Option<RateLimit>,
+
+    /// REST paths admitted without authentication or rate limiting. Peers
+    /// sending shards during rebalancing carry no token, so deployments
+    /// requiring authentication add the internal transfer path here and
+    /// protect it at the network level.
+    pub exempt_paths: Vec<String>,
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        Self {
+            require_auth: false,
+            tokens: Vec::new(),
+            tenant_header: "x-tenant-id".to_string(),
+            rate_limit: None,
+            exempt_paths: vec!["/health".to_string(), "/metrics".to_string()],
+        }
+    }
+}
+
+/// Protocol a request arrived over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    Rest,
+    Grpc,
+    WebSocket,
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Rest => "rest",
+            Protocol::Grpc => "grpc",
+            Protocol::WebSocket => "websocket",
+        }
+    }
+}
+
+/// What a request presents to the policy, whatever its protocol.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Credentials<'a> {
+    /// Value of the `authorization` header or metadata
+    pub authorization: Option<&'a str>,
+
+    /// Value of the tenant header or metadata
+    pub tenant: Option<&'a str>,
+    pub client: Option<IpAddr>,
+}
+
+/// Why a request was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// No token, or one that is not configured
+    Unauthenticated(String),
+
+    /// A tenant the token may not act for
+    Forbidden(String),
+
+    /// A tenant header that is not a tenant ID
+    InvalidTenant(String),
+
+    /// Retry after the given time
+    RateLimited(Duration),
+}
+
+impl Rejection {
+    /// Label the rejection is counted under
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            Rejection::Unauthenticated(_) => "unauthenticated",
+            Rejection::Forbidden(_) => "forbidden",
+            Rejection::InvalidTenant(_) => "invalid_tenant",
+            Rejection::RateLimited(_) => "rate_limited",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Rejection::Unauthenticated(_) => StatusCode::UNAUTHORIZED,
+            Rejection::Forbidden(_) => StatusCode::FORBIDDEN,
+            Rejection::InvalidTenant(_) => StatusCode::BAD_REQUEST,
+            Rejection::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    pub fn to_grpc_status(&self) -> tonic::Status {
+        match self {
+            Rejection::Unauthenticated(message) => tonic::Status::unauthenticated(message),
+            Rejection::Forbidden(message) => tonic::Status::permission_denied(message),
+            Rejection::InvalidTenant(message) => tonic::Status::invalid_argument(message),
+            Rejection::RateLimited(retry_after) => {
+                let mut status = tonic::Status::resource_exhausted(self.to_string());
+                if let Ok(seconds) = MetadataValue::try_from(retry_seconds(*retry_after)) {
+                    status.metadata_mut().insert("retry-after", seconds);
+                }
+                status
+            }
+        }
+    }
+}
+
+impl From<Rejection> for tonic::Status {
+    fn from(rejection: Rejection) -> Self {
+        rejection.to_grpc_status()
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Unauthenticated(message) | Rejection::Forbidden(message) | Rejection::InvalidTenant(message) => {
+                f.write_str(message)
+            }
+            Rejection::RateLimited(retry_after) => {
+                write!(f, "Rate limit exceeded, retry in {}s", retry_seconds(*retry_after))
+            }
+        }
+    }
+}
+
+fn retry_seconds(retry_after: Duration) -> String {
+    retry_after.as_secs().max(1).to_string()
+}
+
+/// Authenticates, scopes and rate limits requests of every protocol.
+pub struct AccessPolicy {
+    config: AccessConfig,
+    tokens: HashMap<String, ApiToken>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+
+    /// Requests by protocol and outcome
+    admissions: Mutex<BTreeMap<(Protocol, &'static str), u64>>,
+}
+
+impl std::fmt::Debug for AccessPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessPolicy")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AccessPolicy {
+    pub fn new(config: AccessConfig) -> Result<Self> {
+        let mut tokens = HashMap::new();
+        for token in &config.tokens {
+            if token.token.is_empty() {
+                return Err(anyhow::anyhow!("API token of {} is empty", token.principal));
+            }
+            if tokens.insert(token.token.clone(), token.clone()).is_some() {
+                return Err(anyhow::anyhow!("API token of {} is configured twice", token.principal));
+            }
+        }
+        if config.require_auth || !tokens.is_empty() {
+            info!(
+                "API access: {} tokens, authentication {}",
+                tokens.len(),
+                if config.require_auth { "required" } else { "optional" }
+            );
+        }
+        Ok(Self {
+            config,
+            tokens,
+            buckets: Mutex::new(HashMap::new()),
+            admissions: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> &AccessConfig {
+        &self.config
+    }
+
+    /// Whether a REST path skips the policy altogether
+    pub fn is_exempt(&self, path: &str) -> bool {
+        self.config.exempt_paths.iter().any(|exempt| exempt == path)
+    }
+
+    /// Admit a request, returning the context it runs in, and count the
+    /// outcome.
+    pub fn admit(&self, protocol: Protocol, credentials: Credentials<'_>, now: Instant) -> Result<SaaSContext, Rejection> {
+        let result = self.evaluate(credentials, now);
+        let outcome = result.as_ref().err().map_or("admitted", Rejection::outcome);
+        *self.admissions.lock().expect("access metrics poisoned").entry((protocol, outcome)).or_insert(0) += 1;
+        if let Err(rejection) = &result {
+            debug!("Refused {} request: {}", protocol.as_str(), rejection);
+        }
+        result
+    }
+
+    fn evaluate(&self, credentials: Credentials<'_>, now: Instant) -> Result<SaaSContext, Rejection> {
+        let token = match credentials.authorization {
+            Some(authorization) => Some(self.authenticate(authorization)?),
+            None if self.config.require_auth => {
+                return Err(Rejection::Unauthenticated("Authentication required".to_string()));
+            }
+            None => None,
+        };
+
+        let requested = credentials
+            .tenant
+            .map(|tenant| {
+                Uuid::parse_str(tenant.trim())
+                    .map_err(|_| Rejection::InvalidTenant(format!("Invalid tenant ID '{}'", tenant)))
+            })
+            .transpose()?;
+        let tenant_id = match (token.and_then(|token| token.tenant_id), requested) {
+            (Some(bound), Some(requested)) if bound != requested => {
+                return Err(Rejection::Forbidden(format!("Token may not act for tenant {}", requested)));
+            }
+            (bound, requested) => bound.or(requested),
+        };
+
+        if let Some(limit) = &self.config.rate_limit {
+            let key = match (token, credentials.client) {
+                (Some(token), _) => format!("principal:{}", token.principal),
+                (None, Some(client)) => format!("address:{}", client),
+                (None, None) => "address:unknown".to_string(),
+            };
+            self.check_rate(limit, key, now).map_err(Rejection::RateLimited)?;
+        }
+
+        Ok(SaaSContext {
+            tenant_id,
+            user_id: token.map(|token| token.principal.clone()),
+            organization_domain: None,
+            subscription_tier: None,
+            authenticated: token.is_some(),
+        })
+    }
+
+    fn authenticate(&self, authorization: &str) -> Result<&ApiToken, Rejection> {
+        let presented = authorization
+            .split_once(' ')
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or_else(|| Rejection::Unauthenticated("Expected a Bearer token".to_string()))?;
+        self.tokens
+            .get(presented)
+            .ok_or_else(|| Rejection::Unauthenticated("Invalid API token".to_string()))
+    }
+
+    /// Take a token from the key's bucket, or return how long to wait for one
+    fn check_rate(&self, limit: &RateLimit, key: String, now: Instant) -> Result<(), Duration> {
+        let per_second = limit.requests_per_minute.max(1) as f64 / 60.0;
+        let capacity = limit.burst.max(1) as f64;
+
+        let mut buckets = self.buckets.lock().expect("access rate limiter poisoned");
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < BUCKET_IDLE);
+        }
+        buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::full(capacity, now))
+            .take(now, per_second, capacity)
+    }
+
+    /// Requests counted by protocol and outcome
+    pub fn admissions(&self) -> Vec<(Protocol, &'static str, u64)> {
+        self.admissions
+            .lock()
+            .expect("access metrics poisoned")
+            .iter()
+            .map(|((protocol, outcome), count)| (*protocol, *outcome, *count))
+            .collect()
+    }
+
+    /// Admission counters in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP aerolithdb_api_admissions_total API requests by protocol and admission outcome");
+        let _ = writeln!(out, "# TYPE aerolithdb_api_admissions_total counter");
+        for (protocol, outcome, count) in self.admissions() {
+            let _ = writeln!(
+                out,
+                "aerolithdb_api_admissions_total{{protocol=\"{}\",outcome=\"{}\"}} {}",
+                protocol.as_str(),
+                escape_label(outcome),
+                count
+            );
+        }
+        out
+    }
+}
+
+/// REST middleware admitting requests through the access policy
+pub async fn access_middleware(State(policy): State<Arc<AccessPolicy>>, mut request: Request, next: Next) -> Response {
+    if policy.is_exempt(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let headers = request.headers();
+    let credentials = Credentials {
+        authorization: headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()),
+        tenant: headers.get(policy.config.tenant_header.as_str()).and_then(|value| value.to_str().ok()),
+        client: request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip()),
+    };
+    match policy.admit(Protocol::Rest, credentials, Instant::now()) {
+        Ok(context) => {
+            request.extensions_mut().insert(context);
+            next.run(request).await
+        }
+        Err(rejection) => {
+            let status = rejection.status_code();
+            let error = serde_json::json!({ "error": rejection.to_string(), "code": status.as_u16(), "details": null });
+            let mut response = (status, Json(error)).into_response();
+            match rejection {
+                Rejection::RateLimited(retry_after) => {
+                    if let Ok(value) = retry_seconds(retry_after).parse() {
+                        response.headers_mut().insert(header::RETRY_AFTER, value);
+                    }
+                }
+                Rejection::Unauthenticated(_) => {
+                    response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+                }
+                _ => {}
+            }
+            response
+        }
+    }
+}
+
+/// Admits gRPC requests through the access policy, either called by the
+/// service handlers or as an interceptor of services wrapped with
+/// `with_interceptor`.
+#[derive(Debug, Clone)]
+pub struct AccessInterceptor {
+    policy: Arc<AccessPolicy>,
+}
+
+impl AccessInterceptor {
+    pub fn new(policy: Arc<AccessPolicy>) -> Self {
+        Self { policy }
+    }
+
+    /// Admit a request, recording who made it in its extensions.
+    pub fn admit<T>(&self, request: &mut tonic::Request<T>) -> Result<(), Rejection> {
+        let metadata = request.metadata();
+        let credentials = Credentials {
+            authorization: metadata.get("authorization").and_then(|value| value.to_str().ok()),
+            tenant: metadata.get(self.policy.config.tenant_header.as_str()).and_then(|value| value.to_str().ok()),
+            client: request.remote_addr().map(|addr| addr.ip()),
+        };
+        let context = self.policy.admit(Protocol::Grpc, credentials, Instant::now())?;
+        request.extensions_mut().insert(context);
+        Ok(())
+    }
+}
+
+impl tonic::service::Interceptor for AccessInterceptor {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        self.admit(&mut request)?;
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::service::Interceptor;
+
+    const TENANT: Uuid = Uuid::from_u128(7);
+
+    fn access_policy(require_auth: bool, rate_limit: Option<RateLimit>) -> Arc<AccessPolicy> {
+        let config = AccessConfig {
+            require_auth,
+            tokens: vec![
+                ApiToken { token: "tenant-secret".to_string(), principal: "reporting".to_string(), tenant_id: Some(TENANT) },
+                ApiToken { token: "admin-secret".to_string(), principal: "admin".to_string(), tenant_id: None },
+            ],
+            rate_limit,
+            ..AccessConfig::default()
+        };
+        Arc::new(AccessPolicy::new(config).unwrap())
+    }
+
+    #[test]
+    fn test_tokens_and_tenants() {
+        let policy = access_policy(true, None);
+        let now = Instant::now();
+        let admit = |authorization: Option<&str>, tenant: Option<&str>| {
+            policy.admit(Protocol::Rest, Credentials { authorization, tenant, client: None }, now)
+        };
+
+        let context = admit(Some("Bearer tenant-secret"), None).unwrap();
+        assert!(context.authenticated);
+        assert_eq!(context.tenant_id, Some(TENANT));
+        assert_eq!(context.user_id.as_deref(), Some("reporting"));
+
+        let other = Uuid::from_u128(8).to_string();
+        assert_eq!(admit(Some("bearer admin-secret"), Some(other.as_str())).unwrap().tenant_id, Some(Uuid::from_u128(8)));
+        assert!(matches!(admit(Some("Bearer tenant-secret"), Some(other.as_str())), Err(Rejection::Forbidden(_))));
+        assert!(matches!(admit(Some("Bearer admin-secret"), Some("acme")), Err(Rejection::InvalidTenant(_))));
+        assert!(matches!(admit(Some("Bearer wrong"), None), Err(Rejection::Unauthenticated(_))));
+        assert!(matches!(admit(Some("Basic YWRtaW4="), None), Err(Rejection::Unauthenticated(_))));
+        assert!(matches!(admit(None, None), Err(Rejection::Unauthenticated(_))));
+
+        // Anonymous requests are admitted when authentication is optional, bad tokens still are not
+        let optional = access_policy(false, None);
+        let anonymous = optional.admit(Protocol::Rest, Credentials::default(), now).unwrap();
+        assert!(!anonymous.authenticated);
+        let bad_token = Credentials { authorization: Some("Bearer wrong"), ..Credentials::default() };
+        assert!(optional.admit(Protocol::Rest, bad_token, now).is_err());
+
+        assert!(AccessPolicy::new(AccessConfig {
+            tokens: vec![ApiToken { token: String::new(), principal: "nobody".to_string(), tenant_id: None }],
+            ..AccessConfig::default()
+        })
+        .is_err());
+        assert!(!format!("{:?}", policy).contains("secret"));
+    }
+
+    #[test]
+    fn test_rest_and_grpc_share_limits_and_metrics() {
+        let policy = access_policy(false, Some(RateLimit { requests_per_minute: 60, burst: 2 }));
+        let token = Credentials { authorization: Some("Bearer admin-secret"), ..Credentials::default() };
+        let now = Instant::now();
+        assert!(policy.admit(Protocol::Rest, token, now).is_ok());
+
+        // The interceptor draws from the same bucket as REST
+        let mut interceptor = AccessInterceptor::new(Arc::clone(&policy));
+        let mut request = tonic::Request::new(());
+        request.metadata_mut().insert("authorization", MetadataValue::from_static("Bearer admin-secret"));
+        let admitted = interceptor.call(request).unwrap();
+        assert_eq!(admitted.extensions().get::<SaaSContext>().unwrap().user_id.as_deref(), Some("admin"));
+
+        let mut request = tonic::Request::new(());
+        request.metadata_mut().insert("authorization", MetadataValue::from_static("Bearer admin-secret"));
+        let status = interceptor.call(request).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.metadata().get("retry-after").is_some());
+
+        // Service handlers admit their typed requests the same way
+        let mut request = tonic::Request::new("get_document");
+        request.metadata_mut().insert("authorization", MetadataValue::from_static("Bearer admin-secret"));
+        assert!(matches!(interceptor.admit(&mut request), Err(Rejection::RateLimited(_))));
+
+        // Other principals and anonymous clients have buckets of their own
+        let tenant = Credentials { authorization: Some("Bearer tenant-secret"), ..Credentials::default() };
+        assert!(policy.admit(Protocol::Rest, tenant, now).is_ok());
+        let mut request = tonic::Request::new(());
+        request.metadata_mut().insert("authorization", MetadataValue::from_static("Bearer nope"));
+        assert_eq!(interceptor.call(request).unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        assert_eq!(
+            policy.admissions(),
+            vec![
+                (Protocol::Rest, "admitted", 2),
+                (Protocol::Grpc, "admitted", 1),
+                (Protocol::Grpc, "rate_limited", 2),
+                (Protocol::Grpc, "unauthenticated", 1),
+            ]
+        );
+        let metrics = policy.to_prometheus();
+        assert!(metrics.contains("aerolithdb_api_admissions_total{protocol=\"grpc\",outcome=\"rate_limited\"} 2"));
+    }
+}
@@ -0,0 +1,548 @@
+//! # CDN Invalidation
+//!
+//! Deployments can put a CDN in front of the read API to serve documents
+//! and collection listings from the edge. Cached responses then have to be
+//! purged when the data behind them changes, or readers see stale data
+//! until the cache entries expire. With invalidation enabled, the API
+//! gateway sends purge requests to the configured CDNs after each
+//! successful change:
+//!
+//! - ✅ Document writes and deletes purge the document's URLs and the URLs
+//!   of its collection, such as listings and queries
+//! - ✅ Creating a document, and updating, truncating or dropping a
+//!   collection, purge the collection's URLs
+//! - ✅ URLs are rendered from templates per collection, with
+//!   `{collection}` and `{id}` placeholders filled in percent-encoded
+//! - ✅ Fastly and Cloudflare are supported out of the box; other CDNs
+//!   plug in through the [`CdnPurger`] trait
+//!
+//! Purges run in the background after the client has been answered.
+//! Failed purges are logged and counted, not retried, so templates should
+//! be paired with a bounded cache TTL. Changes applied in bulk, such as
+//! sync pushes and restores, do not trigger purges.
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::versioning::split_version;
+
+/// Fastly's purge API
+const FASTLY_API: &str = "https://api.fastly.com";
+
+/// Cloudflare's API
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+
+/// URLs Cloudflare accepts per purge request
+const CLOUDFLARE_MAX_FILES: usize = 30;
+
+/// Future returned by [`CdnPurger::purge`]
+pub type PurgeFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// A CDN whose cached URLs can be purged.
+pub trait CdnPurger: Send + Sync {
+    /// Name shown in logs
+    fn name(&self) -> String;
+
+    /// Purge the cached responses of these URLs
+    fn purge<'a>(&'a self, urls: &'a [String]) -> PurgeFuture<'a>;
+}
+
+/// A CDN to send purge requests to.
+#[derive(Clone)]
+pub enum CdnProvider {
+    /// Fastly, purging each URL with an API token
+    Fastly { api_token: String },
+
+    /// Cloudflare, purging URLs of one zone with an API token
+    Cloudflare { zone_id: String, api_token: String },
+
+    /// Any other CDN
+    Custom(Arc<dyn CdnPurger>),
+}
+
+impl std::fmt::Debug for CdnProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CdnProvider::Fastly { .. } => f.write_str("Fastly"),
+            CdnProvider::Cloudflare { zone_id, .. } => f.debug_struct("Cloudflare").field("zone_id", zone_id).finish(),
+            CdnProvider::Custom(purger) => f.debug_tuple("Custom").field(&purger.name()).finish(),
+        }
+    }
+}
+
+/// URL templates of the cached responses of a collection.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CdnRule {
+    /// Collection the templates apply to, `None` for every collection
+    pub collection: Option<String>,
+
+    /// URLs showing one document, with `{collection}` and `{id}` placeholders
+    pub document_urls: Vec<String>,
+
+    /// URLs showing many documents, with a `{collection}` placeholder
+    pub collection_urls: Vec<String>,
+}
+
+/// Where purge requests go, and for which URLs.
+#[derive(Debug, Clone)]
+pub struct CdnConfig {
+    /// CDNs to purge, all of them for every change
+    pub providers: Vec<CdnProvider>,
+
+    /// URL templates; every rule matching a collection applies
+    pub rules: Vec<CdnRule>,
+
+    /// How long a purge request may take
+    pub timeout: Duration,
+}
+
+impl CdnConfig {
+    /// Purge the URLs of `rules` on `providers` with the default timeout.
+    pub fn new(providers: Vec<CdnProvider>, rules: Vec<CdnRule>) -> Self {
+        Self { providers, rules, timeout: Duration::from_secs(10) }
+    }
+}
+
+/// A change that makes cached responses stale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentChange {
+    /// A document was written or deleted
+    Document { collection: String, id: String },
+
+    /// Documents were added to or removed from a collection, or its
+    /// settings changed
+    Collection { collection: String },
+}
+
+impl ContentChange {
+    fn collection(&self) -> &str {
+        match self {
+            ContentChange::Document { collection, .. } | ContentChange::Collection { collection } => collection,
+        }
+    }
+}
+
+/// Counters of purge activity.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CdnStats {
+    /// Changes that had URLs to purge
+    pub changes: u64,
+
+    /// URLs purged, counted once per CDN
+    pub purged_urls: u64,
+
+    /// Purge requests that failed or timed out
+    pub failed: u64,
+}
+
+/// Turns changes into purge requests to the configured CDNs.
+pub struct CdnInvalidator {
+    rules: Vec<CdnRule>,
+    timeout: Duration,
+    purgers: Vec<Arc<dyn CdnPurger>>,
+    changes: AtomicU64,
+    purged_urls: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl std::fmt::Debug for CdnInvalidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CdnInvalidator")
+            .field("rules", &self.rules)
+            .field("purgers", &self.purgers.iter().map(|purger| purger.name()).collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CdnInvalidator {
+    /// Invalidate as configured, rejecting templates with unknown
+    /// placeholders.
+    pub fn new(config: CdnConfig) -> Result<Self> {
+        for rule in &config.rules {
+            for template in &rule.document_urls {
+                check_template(template, &["collection", "id"])?;
+            }
+            for template in &rule.collection_urls {
+                check_template(template, &["collection"])?;
+            }
+        }
+
+        let client = reqwest::Client::builder().timeout(config.timeout).build()?;
+        let purgers = config
+            .providers
+            .into_iter()
+            .map(|provider| -> Arc<dyn CdnPurger> {
+                match provider {
+                    CdnProvider::Fastly { api_token } => Arc::new(FastlyPurger { client: client.clone(), api_token }),
+                    CdnProvider::Cloudflare { zone_id, api_token } => {
+                        Arc::new(CloudflarePurger { client: client.clone(), zone_id, api_token })
+                    }
+                    CdnProvider::Custom(purger) => purger,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            rules: config.rules,
+            timeout: config.timeout,
+            purgers,
+            changes: AtomicU64::new(0),
+            purged_urls: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> CdnStats {
+        CdnStats {
+            changes: self.changes.load(Ordering::Relaxed),
+            purged_urls: self.purged_urls.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// URLs made stale by a change, without duplicates.
+    pub fn urls_for(&self, change: &ContentChange) -> Vec<String> {
+        let collection = encode_segment(change.collection());
+        let mut urls = BTreeSet::new();
+        for rule in self.rules.iter().filter(|rule| rule.collection.as_deref().is_none_or(|wanted| wanted == change.collection())) {
+            if let ContentChange::Document { id, .. } = change {
+                let id = encode_segment(id);
+                for template in &rule.document_urls {
+                    urls.insert(template.replace("{collection}", &collection).replace("{id}", &id));
+                }
+            }
+            for template in &rule.collection_urls {
+                urls.insert(template.replace("{collection}", &collection));
+            }
+        }
+        urls.into_iter().collect()
+    }
+
+    /// Purge the URLs made stale by a change on every CDN, returning how
+    /// many purge requests succeeded.
+    pub async fn purge(&self, change: &ContentChange) -> usize {
+        let urls = self.urls_for(change);
+        if urls.is_empty() {
+            return 0;
+        }
+        self.changes.fetch_add(1, Ordering::Relaxed);
+
+        let mut succeeded = 0;
+        for purger in &self.purgers {
+            match tokio::time::timeout(self.timeout, purger.purge(&urls)).await {
+                Ok(Ok(())) => {
+                    debug!("Purged {} URL(s) from {} after {:?}", urls.len(), purger.name(), change);
+                    self.purged_urls.fetch_add(urls.len() as u64, Ordering::Relaxed);
+                    succeeded += 1;
+                }
+                Ok(Err(e)) => {
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    warn!("Failed to purge {} URL(s) from {}: {}", urls.len(), purger.name(), e);
+                }
+                Err(_) => {
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    warn!("Purging {} URL(s) from {} timed out", urls.len(), purger.name());
+                }
+            }
+        }
+        succeeded
+    }
+
+    /// Purge the URLs made stale by a change in the background.
+    pub fn notify(self: &Arc<Self>, change: ContentChange) {
+        let invalidator = Arc::clone(self);
+        tokio::spawn(async move {
+            invalidator.purge(&change).await;
+        });
+    }
+}
+
+/// Purges each URL through Fastly's API.
+struct FastlyPurger {
+    client: reqwest::Client,
+    api_token: String,
+}
+
+impl CdnPurger for FastlyPurger {
+    fn name(&self) -> String {
+        "Fastly".to_string()
+    }
+
+    fn purge<'a>(&'a self, urls: &'a [String]) -> PurgeFuture<'a> {
+        Box::pin(async move {
+            for url in urls {
+                // Fastly names the cached URL by host and path, without scheme
+                let cached = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+                self.client
+                    .post(format!("{}/purge/{}", FASTLY_API, cached))
+                    .header("Fastly-Key", &self.api_token)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Purges URLs of one zone through Cloudflare's API, in batches.
+struct CloudflarePurger {
+    client: reqwest::Client,
+    zone_id: String,
+    api_token: String,
+}
+
+impl CdnPurger for CloudflarePurger {
+    fn name(&self) -> String {
+        format!("Cloudflare zone {}", self.zone_id)
+    }
+
+    fn purge<'a>(&'a self, urls: &'a [String]) -> PurgeFuture<'a> {
+        Box::pin(async move {
+            for files in urls.chunks(CLOUDFLARE_MAX_FILES) {
+                self.client
+                    .post(format!("{}/zones/{}/purge_cache", CLOUDFLARE_API, self.zone_id))
+                    .bearer_auth(&self.api_token)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(serde_json::json!({ "files": files }).to_string())
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Reject placeholders other than `allowed`, and unclosed ones.
+fn check_template(template: &str, allowed: &[&str]) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unclosed placeholder in CDN URL template {}", template))?;
+        let name = &rest[start + 1..start + end];
+        if !allowed.contains(&name) {
+            return Err(anyhow::anyhow!("Unknown placeholder {{{}}} in CDN URL template {}", name, template));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Percent-encode a value for use as one URL path segment.
+fn encode_segment(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Decode a percent-encoded URL path segment.
+fn decode_segment(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The change a REST request makes if it succeeds
+fn change_of(method: &Method, path: &str) -> Option<ContentChange> {
+    let (_, resource) = split_version(path)?;
+    let segments: Vec<&str> = resource.trim_start_matches('/').split('/').collect();
+    // Ephemeral session documents are read through their collection too
+    let segments = match segments.as_slice() {
+        ["sessions", _, rest @ ..] => rest,
+        all => all,
+    };
+    let document = |collection: &str, id: &str| ContentChange::Document {
+        collection: decode_segment(collection),
+        id: decode_segment(id),
+    };
+    let collection = |collection: &str| ContentChange::Collection { collection: decode_segment(collection) };
+
+    match (method, segments) {
+        (&Method::PUT | &Method::DELETE, ["collections", name, "documents", id]) => Some(document(name, id)),
+        (&Method::POST, ["collections", name, "documents"]) => Some(collection(name)),
+        (&Method::PUT | &Method::DELETE, ["collections", name]) => Some(collection(name)),
+        (&Method::POST, ["collections", name, "truncate"]) => Some(collection(name)),
+        _ => None,
+    }
+}
+
+/// REST middleware purging the URLs a successful change made stale.
+pub async fn cdn_middleware(State(cdn): State<Arc<CdnInvalidator>>, request: Request, next: Next) -> Response {
+    let change = change_of(request.method(), request.uri().path());
+    let response = next.run(request).await;
+    if let Some(change) = change {
+        if response.status().is_success() {
+            cdn.notify(change);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::put;
+    use axum::Router;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    /// Records purged URLs instead of calling a CDN
+    #[derive(Default)]
+    struct RecordingPurger {
+        purged: Mutex<Vec<String>>,
+    }
+
+    impl CdnPurger for RecordingPurger {
+        fn name(&self) -> String {
+            "recording".to_string()
+        }
+
+        fn purge<'a>(&'a self, urls: &'a [String]) -> PurgeFuture<'a> {
+            Box::pin(async move {
+                self.purged.lock().unwrap().extend(urls.iter().cloned());
+                Ok(())
+            })
+        }
+    }
+
+    fn rules() -> Vec<CdnRule> {
+        vec![
+            CdnRule {
+                collection: None,
+                document_urls: vec!["https://cdn.test/api/v1/collections/{collection}/documents/{id}".to_string()],
+                collection_urls: vec!["https://cdn.test/api/v1/collections/{collection}/documents".to_string()],
+            },
+            CdnRule {
+                collection: Some("products".to_string()),
+                document_urls: vec!["https://shop.test/products/{id}".to_string()],
+                collection_urls: vec!["https://shop.test/catalog".to_string()],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_urls_are_rendered_per_collection() {
+        let cdn = CdnInvalidator::new(CdnConfig::new(Vec::new(), rules())).unwrap();
+
+        let urls = cdn.urls_for(&ContentChange::Document { collection: "products".to_string(), id: "a b/c".to_string() });
+        assert_eq!(
+            urls,
+            vec![
+                "https://cdn.test/api/v1/collections/products/documents",
+                "https://cdn.test/api/v1/collections/products/documents/a%20b%2Fc",
+                "https://shop.test/catalog",
+                "https://shop.test/products/a%20b%2Fc",
+            ]
+        );
+        let urls = cdn.urls_for(&ContentChange::Collection { collection: "users".to_string() });
+        assert_eq!(urls, vec!["https://cdn.test/api/v1/collections/users/documents"]);
+
+        let invalid = |document_urls: &[&str], collection_urls: &[&str]| {
+            let rule = CdnRule {
+                collection: None,
+                document_urls: document_urls.iter().map(|url| url.to_string()).collect(),
+                collection_urls: collection_urls.iter().map(|url| url.to_string()).collect(),
+            };
+            CdnInvalidator::new(CdnConfig::new(Vec::new(), vec![rule])).is_err()
+        };
+        assert!(invalid(&["https://cdn.test/{tenant}/{id}"], &[]));
+        assert!(invalid(&[], &["https://cdn.test/{collection}/{id}"]));
+        assert!(invalid(&["https://cdn.test/{id"], &[]));
+    }
+
+    #[test]
+    fn test_changes_of_rest_requests() {
+        let document = |collection: &str, id: &str| {
+            Some(ContentChange::Document { collection: collection.to_string(), id: id.to_string() })
+        };
+        let collection = |collection: &str| Some(ContentChange::Collection { collection: collection.to_string() });
+
+        assert_eq!(change_of(&Method::PUT, "/api/v1/collections/users/documents/ada"), document("users", "ada"));
+        assert_eq!(change_of(&Method::DELETE, "/api/v2/collections/users/documents/a%20b"), document("users", "a b"));
+        assert_eq!(change_of(&Method::PUT, "/api/v1/sessions/s1/collections/carts/documents/c1"), document("carts", "c1"));
+        assert_eq!(change_of(&Method::POST, "/api/v1/collections/users/documents"), collection("users"));
+        assert_eq!(change_of(&Method::POST, "/api/v1/collections/users/truncate"), collection("users"));
+        assert_eq!(change_of(&Method::DELETE, "/api/v1/collections/users"), collection("users"));
+
+        assert_eq!(change_of(&Method::GET, "/api/v1/collections/users/documents/ada"), None);
+        assert_eq!(change_of(&Method::POST, "/api/v1/collections/users/query"), None);
+        assert_eq!(change_of(&Method::PUT, "/api/v1/collections/users/documents/ada/residency"), None);
+        assert_eq!(change_of(&Method::PUT, "/collections/users/documents/ada"), None);
+    }
+
+    #[tokio::test]
+    async fn test_successful_changes_are_purged() {
+        let purger = Arc::new(RecordingPurger::default());
+        let config = CdnConfig::new(vec![CdnProvider::Custom(Arc::clone(&purger) as Arc<dyn CdnPurger>)], rules());
+        let cdn = Arc::new(CdnInvalidator::new(config).unwrap());
+        let app = Router::new()
+            .route(
+                "/api/v1/collections/:collection/documents/:id",
+                put(|axum::extract::Path((_, id)): axum::extract::Path<(String, String)>| async move {
+                    if id == "missing" { StatusCode::NOT_FOUND } else { StatusCode::OK }
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(Arc::clone(&cdn), cdn_middleware));
+
+        for id in ["missing", "ada"] {
+            let request = Request::put(format!("/api/v1/collections/users/documents/{}", id)).body(Body::empty()).unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+
+        for _ in 0..200 {
+            if cdn.stats().purged_urls > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(cdn.stats(), CdnStats { changes: 1, purged_urls: 2, failed: 0 });
+        assert_eq!(
+            *purger.purged.lock().unwrap(),
+            vec![
+                "https://cdn.test/api/v1/collections/users/documents",
+                "https://cdn.test/api/v1/collections/users/documents/ada",
+            ]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! # Response Compression
+//!
+//! Compresses HTTP response bodies in the encoding the client prefers among
+//! those it lists in `Accept-Encoding` (gzip, brotli, or zstd), so large
+//! query results and sync batches cost less bandwidth.
+//!
+//! Responses are sent as they are when:
+//! - they are smaller than [`CompressionConfig::min_size`], where the
+//!   encoding overhead outweighs the saving
+//! - their content type starts with one of
+//!   [`CompressionConfig::excluded_content_types`], such as images and
+//!   archives that are already compressed
+//! - they already carry a `Content-Encoding`
+//! - they have no body, as with the `101 Switching Protocols` response that
+//!   upgrades a connection to a WebSocket
+
+use std::sync::Arc;
+
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+
+/// Content type prefixes excluded from compression by default
+const ALREADY_COMPRESSED: &[&str] = &[
+    "image/",
+    "video/",
+    "audio/",
+    "font/woff",
+    "application/zip",
+    "application/gzip",
+    "application/zstd",
+    "application/x-bzip2",
+    "application/x-7z-compressed",
+    "application/grpc",
+    "text/event-stream",
+];
+
+/// Response compression settings shared by the HTTP-based APIs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Whether responses are compressed at all
+    pub enabled: bool,
+
+    /// Offer gzip to clients that accept it
+    pub gzip: bool,
+
+    /// Offer brotli (`br`) to clients that accept it
+    pub brotli: bool,
+
+    /// Offer zstd to clients that accept it
+    pub zstd: bool,
+
+    /// Smallest response body compressed, in bytes. Bodies of unknown length,
+    /// such as streams, are always compressed.
+    pub min_size: u16,
+
+    /// Content type prefixes never compressed, typically formats that are
+    /// compressed already (e.g. "image/" or "application/zip")
+    pub excluded_content_types: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            gzip: true,
+            brotli: true,
+            zstd: true,
+            min_size: 1024,
+            excluded_content_types: ALREADY_COMPRESSED.iter().map(|prefix| prefix.to_string()).collect(),
+        }
+    }
+}
+
+/// Layer compressing the responses of a router according to `config`.
+///
+/// Apply it only when `config.enabled` is set; the layer itself does not
+/// check the flag.
+pub fn layer(config: &CompressionConfig) -> CompressionLayer<impl Predicate> {
+    let excluded: Arc<[String]> = config.excluded_content_types.clone().into();
+    let compressible = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        !excluded.iter().any(|prefix| content_type.starts_with(prefix.as_str()))
+    };
+
+    CompressionLayer::new()
+        .gzip(config.gzip)
+        .br(config.brotli)
+        .zstd(config.zstd)
+        .no_deflate()
+        .compress_when(SizeAbove::new(config.min_size).and(compressible))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app(config: &CompressionConfig) -> Router {
+        Router::new()
+            .route("/json", get(|| async { axum::Json(vec!["aerolith"; 500]) }))
+            .route("/small", get(|| async { axum::Json("ok") }))
+            .route("/image", get(|| async { ([(header::CONTENT_TYPE, "image/png")], vec![0u8; 4096]) }))
+            .layer(layer(config))
+    }
+
+    async fn encoding(router: &Router, path: &str, accept: &str) -> Option<String> {
+        let request = Request::get(path).header(header::ACCEPT_ENCODING, accept).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_negotiates_enabled_encodings() {
+        let router = app(&CompressionConfig::default());
+        assert_eq!(encoding(&router, "/json", "gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(encoding(&router, "/json", "br;q=1.0, gzip;q=0.5").await.as_deref(), Some("br"));
+        assert_eq!(encoding(&router, "/json", "zstd").await.as_deref(), Some("zstd"));
+        assert_eq!(encoding(&router, "/json", "identity").await, None);
+
+        let gzip_only = app(&CompressionConfig { brotli: false, zstd: false, ..Default::default() });
+        assert_eq!(encoding(&gzip_only, "/json", "br, zstd, gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(encoding(&gzip_only, "/json", "br").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_skips_small_and_compressed_responses() {
+        let router = app(&CompressionConfig::default());
+        assert_eq!(encoding(&router, "/small", "gzip").await, None);
+        assert_eq!(encoding(&router, "/image", "gzip").await, None);
+
+        let everything = app(&CompressionConfig {
+            min_size: 0,
+            excluded_content_types: Vec::new(),
+            ..Default::default()
+        });
+        assert_eq!(encoding(&everything, "/small", "gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(encoding(&everything, "/image", "gzip").await.as_deref(), Some("gzip"));
+    }
+}
@@ -0,0 +1,241 @@
+//! # Result Formats
+//!
+//! Query and listing endpoints answer with a JSON envelope by default. Clients
+//! piping results into other tools can ask for newline-delimited JSON or CSV
+//! instead, either with a `format` query parameter (`json`, `ndjson` or `csv`)
+//! or through the `Accept` header (`application/x-ndjson` or `text/csv`). The
+//! query parameter wins when both are given.
+//!
+//! NDJSON and CSV bodies are streamed one document per line, and the total
+//! number of matches is reported in the `X-Total-Count` header. CSV output
+//! starts with a header row naming the top-level fields found across the
+//! documents, `id` first and the rest in alphabetical order. Missing fields and
+//! nulls are left empty; nested objects and arrays are written as compact JSON.
+
+use std::collections::BTreeSet;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+
+/// Header carrying the total number of matches of a streamed result
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Header set on streamed results missing the matches of unreachable
+/// cluster members
+pub const PARTIAL_RESULTS_HEADER: &str = "x-partial-results";
+
+/// Header carrying the cursor of the page after a streamed result
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// Representation of a query result requested by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFormat {
+    /// JSON envelope holding the documents and paging details
+    Json,
+    /// One JSON document per line
+    Ndjson,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+impl ResultFormat {
+    /// Pick the format from the `format` query parameter, falling back to the
+    /// `Accept` header and then to JSON.
+    ///
+    /// An unknown `format` value is rejected with `400 Bad Request`; media
+    /// types in `Accept` that have no matching format are ignored.
+    pub fn negotiate(format: Option<&str>, headers: &HeaderMap) -> Result<Self, StatusCode> {
+        if let Some(name) = format {
+            return Self::from_name(name).ok_or(StatusCode::BAD_REQUEST);
+        }
+
+        let accept = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for range in accept {
+            let mut parts = range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default();
+            let refused = parts.any(|param| {
+                param.strip_prefix("q=").and_then(|q| q.trim().parse::<f32>().ok()) == Some(0.0)
+            });
+            if refused {
+                continue;
+            }
+            if let Some(format) = Self::from_media_type(media_type) {
+                return Ok(format);
+            }
+        }
+
+        Ok(ResultFormat::Json)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(ResultFormat::Json),
+            "ndjson" | "jsonl" => Some(ResultFormat::Ndjson),
+            "csv" => Some(ResultFormat::Csv),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" => Some(ResultFormat::Json),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => Some(ResultFormat::Ndjson),
+            "text/csv" => Some(ResultFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// `Content-Type` of a response in this format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ResultFormat::Json => "application/json",
+            ResultFormat::Ndjson => "application/x-ndjson",
+            ResultFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+/// Stream `documents` as NDJSON or CSV, reporting `total` in the
+/// `X-Total-Count` header.
+///
+/// Handlers answer `ResultFormat::Json` with their own envelope; passed here
+/// it streams NDJSON.
+pub fn stream_documents(format: ResultFormat, documents: Vec<Value>, total: usize) -> Response {
+    let body = match format {
+        ResultFormat::Csv => csv_body(documents),
+        ResultFormat::Json | ResultFormat::Ndjson => ndjson_body(documents),
+    };
+
+    let mut response = body.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+    headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+    response
+}
+
+fn ndjson_body(documents: Vec<Value>) -> Body {
+    let lines = documents.into_iter().map(|document| {
+        let mut line = serde_json::to_vec(&document)?;
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(Bytes::from(line))
+    });
+    Body::from_stream(futures::stream::iter(lines))
+}
+
+fn csv_body(documents: Vec<Value>) -> Body {
+    let columns = csv_columns(&documents);
+    let header = csv_line(columns.iter().map(|column| column.as_str().into()));
+
+    let rows = documents.into_iter().map(move |document| {
+        csv_line(columns.iter().map(|column| match document.get(column) {
+            None | Some(Value::Null) => "".into(),
+            Some(Value::String(text)) => text.as_str().into(),
+            Some(other) => other.to_string().into(),
+        }))
+    });
+    let lines = std::iter::once(header)
+        .chain(rows)
+        .map(|line| Ok::<_, std::convert::Infallible>(Bytes::from(line)));
+
+    Body::from_stream(futures::stream::iter(lines))
+}
+
+/// Top-level fields across `documents`, `id` first and the rest sorted
+fn csv_columns(documents: &[Value]) -> Vec<String> {
+    let fields: BTreeSet<&str> = documents
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|object| object.keys().map(String::as_str))
+        .collect();
+
+    let id = fields.contains("id").then(|| "id".to_string());
+    id.into_iter()
+        .chain(fields.into_iter().filter(|field| *field != "id").map(str::to_string))
+        .collect()
+}
+
+/// One CSV record terminated by CRLF, quoting fields as RFC 4180 requires
+fn csv_line<'a>(fields: impl Iterator<Item = std::borrow::Cow<'a, str>>) -> String {
+    let mut line = String::new();
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(&field);
+        }
+    }
+    line.push_str("\r\n");
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_negotiates_format() {
+        assert_eq!(ResultFormat::negotiate(None, &HeaderMap::new()), Ok(ResultFormat::Json));
+        assert_eq!(ResultFormat::negotiate(None, &accept("*/*")), Ok(ResultFormat::Json));
+        assert_eq!(ResultFormat::negotiate(None, &accept("text/csv")), Ok(ResultFormat::Csv));
+        assert_eq!(
+            ResultFormat::negotiate(None, &accept("text/html, application/x-ndjson;q=0.9, text/csv")),
+            Ok(ResultFormat::Ndjson)
+        );
+        assert_eq!(ResultFormat::negotiate(None, &accept("text/csv;q=0, application/json")), Ok(ResultFormat::Json));
+        assert_eq!(ResultFormat::negotiate(Some("CSV"), &accept("application/x-ndjson")), Ok(ResultFormat::Csv));
+        assert_eq!(ResultFormat::negotiate(Some("xml"), &HeaderMap::new()), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_streams_ndjson() {
+        let documents = vec![json!({"id": "1", "name": "a"}), json!({"id": "2", "tags": ["x"]})];
+        let response = stream_documents(ResultFormat::Ndjson, documents, 7);
+
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        assert_eq!(response.headers()[TOTAL_COUNT_HEADER], "7");
+        assert_eq!(
+            body_text(response).await,
+            "{\"id\":\"1\",\"name\":\"a\"}\n{\"id\":\"2\",\"tags\":[\"x\"]}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streams_csv_with_union_of_fields() {
+        let documents = vec![
+            json!({"name": "Ada, Countess", "id": "1", "age": 36}),
+            json!({"id": "2", "quote": "say \"hi\"", "address": {"city": "Paris"}, "age": null}),
+        ];
+        let response = stream_documents(ResultFormat::Csv, documents, 2);
+
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+        assert_eq!(
+            body_text(response).await,
+            "id,address,age,name,quote\r\n\
+             1,,36,\"Ada, Countess\",\r\n\
+             2,\"{\"\"city\"\":\"\"Paris\"\"}\",,,\"say \"\"hi\"\"\"\r\n"
+        );
+    }
+}
@@ -2,16 +2,19 @@ use anyhow::Result;
 use std::sync::Arc;
 use async_graphql::{Context, Object, Schema, SimpleObject, EmptyMutation, EmptySubscription};
 use axum::{
-    extract::State,
-    response::Html,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Router,
 };
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use tracing::info;
 
+use aerolithdb_consensus::{features, FeatureFlagRegistry};
 use aerolithdb_query::QueryEngine;
-use aerolithdb_security::SecurityFramework;
+use aerolithdb_security::{redact, SecurityFramework};
 
 use aerolithdb_core::GraphQLConfig;
 
@@ -20,6 +23,7 @@ pub struct GraphQLAPI {
     config: GraphQLConfig,
     query: Arc<QueryEngine>,
     security: Arc<SecurityFramework>,
+    feature_flags: Arc<FeatureFlagRegistry>,
 }
 
 #[derive(SimpleObject)]
@@ -102,19 +106,23 @@ impl Query {
             sort: None,
             limit: limit.map(|l| l as usize),
             offset: offset.map(|o| o as usize),
+            cursor: None,
+            limits: None,
+            projection: None,
+            as_of: None,
         };
         
         match self.query_engine.query_documents(&collection, &query_request).await {
             Ok(query_result) => {
-                let documents: Vec<Document> = query_result.documents
+                let documents: Vec<Document> = query_result.ids
                     .into_iter()
-                    .enumerate()
-                    .map(|(idx, doc)| {
+                    .zip(query_result.documents)
+                    .map(|(id, doc)| {
                         let data_str = serde_json::to_string(&doc)
                             .unwrap_or_else(|_| "{}".to_string());
                         
                         Document {
-                            id: format!("doc_{}", idx), // Use index since document doesn't have inherent ID
+                            id,
                             collection: collection.clone(),
                             data: data_str,
                             version: 1,
@@ -125,7 +133,7 @@ impl Query {
                     .collect();
                 Ok(documents)
             }
-            Err(e) => Err(async_graphql::Error::new(format!("Query failed: {}", e))),
+            Err(e) => Err(async_graphql::Error::new(redact(&format!("Query failed: {}", e)))),
         }    }
 
     async fn collections(&self, _ctx: &Context<'_>) -> Result<Vec<Collection>, async_graphql::Error> {
@@ -152,12 +160,14 @@ impl GraphQLAPI {
         config: &GraphQLConfig,
         query: Arc<QueryEngine>,
         security: Arc<SecurityFramework>,
+        feature_flags: Arc<FeatureFlagRegistry>,
     ) -> Result<Self> {
         info!("Initializing GraphQL API");
         Ok(Self {
             config: config.clone(),
             query,
             security,
+            feature_flags,
         })
     }
 
@@ -174,7 +184,8 @@ impl GraphQLAPI {
         )
         .finish();        let app = Router::new()
             .route("/", post(graphql_handler).get(graphql_playground))
-            .with_state(schema);
+            .with_state(schema)
+            .layer(axum::middleware::from_fn_with_state(Arc::clone(&self.feature_flags), graphql_feature_gate));
 
         let addr = format!("{}:{}", self.config.bind_address, self.config.port);
         let listener = tokio::net::TcpListener::bind(&addr).await?;        tokio::spawn(async move {
@@ -201,6 +212,20 @@ async fn graphql_handler(
     schema.execute(req.into_inner()).await.into()
 }
 
+/// Refuse GraphQL requests while the `graphql` feature flag is off, so the
+/// endpoint can be toggled at runtime without restarting the server
+async fn graphql_feature_gate(
+    State(flags): State<Arc<FeatureFlagRegistry>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if flags.is_enabled(features::GRAPHQL) {
+        next.run(request).await
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "GraphQL is disabled on this node").into_response()
+    }
+}
+
 async fn graphql_playground() -> Html<&'static str> {
     Html(include_str!("../static/playground.html"))
 }
@@ -12,6 +12,10 @@
 //! - ✅ Full integration with query engine and security framework
 //! - ✅ Comprehensive error handling and status management
 //! - ✅ Health check endpoint for monitoring
+//! - ✅ PubSubService for publishing to and streaming from application channels
+//! - ✅ LockService for consensus-backed leases with fencing tokens
+//! - ✅ Every handler admits its request through the REST API's access
+//!   policy: authentication, tenancy, rate limits and request metrics
 //! - ✅ Ready for immediate production deployment
 //!
 //! ## Protocol Buffers Enhancement (Optional)
@@ -26,10 +30,14 @@ use std::sync::Arc;
 use tonic::{Request, Response, Status};
 use tracing::info;
 
-use aerolithdb_query::QueryEngine;
-use aerolithdb_security::SecurityFramework;
+use aerolithdb_consensus::{ConsensusEngine, Lease, LeaseOutcome};
+use aerolithdb_query::{QueryEngine, ReadConsistency};
+use aerolithdb_security::{redact, SecurityFramework};
 
 use super::GRPCConfig;
+use super::access::{AccessConfig, AccessInterceptor, AccessPolicy};
+use super::cdn::{CdnInvalidator, ContentChange};
+use super::pubsub::{PubSubBroker, PubSubMessage};
 
 pub trait DataService {
     async fn get_document(
@@ -53,6 +61,40 @@ pub trait DataService {
     ) -> Result<Response<QueryDocumentsResponse>, Status>;
 }
 
+pub trait PubSubService {
+    async fn publish(
+        &self,
+        request: Request<PublishRequest>,
+    ) -> Result<Response<PublishResponse>, Status>;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<tokio::sync::mpsc::Receiver<Result<ChannelMessage, Status>>>, Status>;
+}
+
+pub trait LockService {
+    async fn acquire_lock(
+        &self,
+        request: Request<AcquireLockRequest>,
+    ) -> Result<Response<LockResponse>, Status>;
+
+    async fn renew_lock(
+        &self,
+        request: Request<LockTokenRequest>,
+    ) -> Result<Response<LockResponse>, Status>;
+
+    async fn release_lock(
+        &self,
+        request: Request<LockTokenRequest>,
+    ) -> Result<Response<LockResponse>, Status>;
+
+    async fn get_lock(
+        &self,
+        request: Request<GetLockRequest>,
+    ) -> Result<Response<LockResponse>, Status>;
+}
+
 /// Production-ready gRPC message types for aerolithsDB operations.
 /// 
 /// These manual type definitions provide immediate, fully-functional gRPC capabilities
@@ -64,6 +106,8 @@ pub trait DataService {
 pub struct GetDocumentRequest {
     pub collection: String,
     pub id: String,
+    pub consistency: Option<String>, // local (default), bounded_staleness or linearizable
+    pub max_lag_ms: Option<u64>, // maximum lag of a bounded_staleness read
 }
 
 #[derive(Debug)]
@@ -102,14 +146,20 @@ pub struct DeleteDocumentResponse {
 pub struct QueryDocumentsRequest {
     pub collection: String,
     pub filter: Vec<u8>, // JSON filter as bytes
+    pub sort: Vec<u8>, // JSON sort specification as bytes
     pub limit: Option<u32>,
     pub offset: Option<u32>,
+    pub cursor: Option<String>, // next_cursor of the previous page
+    pub projection: Vec<u8>, // JSON projection as bytes, empty for whole documents
+    pub consistency: Option<String>, // local (default), bounded_staleness or linearizable
+    pub max_lag_ms: Option<u64>, // maximum lag of a bounded_staleness read
 }
 
 #[derive(Debug)]
 pub struct QueryDocumentsResponse {
     pub documents: Vec<DocumentResult>,
     pub total: u64,
+    pub next_cursor: Option<String>, // absent on the last page
 }
 
 #[derive(Debug)]
@@ -120,31 +170,163 @@ pub struct DocumentResult {
     pub metadata: std::collections::HashMap<String, String>,
 }
 
+#[derive(Debug)]
+pub struct PublishRequest {
+    pub channel: String,
+    pub payload: Vec<u8>, // JSON payload as bytes
+}
+
+#[derive(Debug)]
+pub struct PublishResponse {
+    pub message_id: String,
+    pub sequence: u64,
+}
+
+#[derive(Debug)]
+pub struct SubscribeRequest {
+    pub channel: String,
+}
+
+#[derive(Debug)]
+pub struct ChannelMessage {
+    pub channel: String,
+    pub message_id: String,
+    pub sequence: u64,
+    pub payload: Vec<u8>,
+    pub publisher: Option<String>,
+    pub published_at: i64, // Unix timestamp in milliseconds
+}
+
+impl From<PubSubMessage> for ChannelMessage {
+    fn from(message: PubSubMessage) -> Self {
+        Self {
+            channel: message.channel,
+            message_id: message.id,
+            sequence: message.sequence,
+            payload: serde_json::to_vec(&message.payload).unwrap_or_else(|_| b"null".to_vec()),
+            publisher: message.publisher,
+            published_at: message.published_at.timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AcquireLockRequest {
+    pub name: String,
+    pub holder: String,
+    pub ttl_ms: u64,
+}
+
+#[derive(Debug)]
+pub struct LockTokenRequest {
+    pub name: String,
+    pub holder: String,
+    pub fencing_token: u64,
+}
+
+#[derive(Debug)]
+pub struct GetLockRequest {
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct LockResponse {
+    /// Whether the request took effect; false when another holder has the lease
+    /// or the caller's fencing token is no longer current
+    pub success: bool,
+    /// The lease as it stands after the request, if anyone holds it
+    pub lease: Option<LeaseInfo>,
+}
+
+#[derive(Debug)]
+pub struct LeaseInfo {
+    pub name: String,
+    pub holder: String,
+    pub fencing_token: u64,
+    pub expires_at: i64, // Unix timestamp in milliseconds
+}
+
+impl From<Lease> for LeaseInfo {
+    fn from(lease: Lease) -> Self {
+        Self {
+            name: lease.name,
+            holder: lease.holder,
+            fencing_token: lease.fencing_token,
+            expires_at: lease.expires_at.timestamp_millis(),
+        }
+    }
+}
+
+impl From<LeaseOutcome> for LockResponse {
+    fn from(outcome: LeaseOutcome) -> Self {
+        match outcome {
+            LeaseOutcome::Granted { lease } => Self { success: true, lease: Some(lease.into()) },
+            LeaseOutcome::Held { lease } => Self { success: false, lease: Some(lease.into()) },
+            LeaseOutcome::Released { .. } => Self { success: true, lease: None },
+            LeaseOutcome::NotHeld { .. } => Self { success: false, lease: None },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GRPCAPIv1 {
     config: GRPCConfig,
     query: Arc<QueryEngine>,
     security: Arc<SecurityFramework>,
+    pubsub: Arc<PubSubBroker>,
+    consensus: Arc<ConsensusEngine>,
+    access: Arc<AccessPolicy>,
+    cdn: Option<Arc<CdnInvalidator>>,
 }
 
 pub struct DataServiceImpl {
     query: Arc<QueryEngine>,
     security: Arc<SecurityFramework>,
+    access: AccessInterceptor,
+    /// Purges CDN caches of changed documents, if configured
+    cdn: Option<Arc<CdnInvalidator>>,
+}
+
+impl DataServiceImpl {
+    fn document_changed(&self, collection: String, id: String) {
+        if let Some(cdn) = &self.cdn {
+            cdn.notify(ContentChange::Document { collection, id });
+        }
+    }
+}
+
+/// Status of a read that could not be served at the consistency it asked
+/// for, if that is why it failed.
+fn read_consistency_status(e: &anyhow::Error) -> Option<Status> {
+    let message = e.to_string();
+    if message.starts_with("Invalid read consistency") {
+        Some(Status::invalid_argument(redact(&message)))
+    } else if message.starts_with("Stale read")
+        || message.starts_with("Not the Raft leader")
+        || message.contains("did not reach quorum")
+    {
+        Some(Status::unavailable(redact(&message)))
+    } else {
+        None
+    }
 }
 
 impl DataService for DataServiceImpl {
     async fn get_document(
         &self,
-        request: Request<GetDocumentRequest>,
+        mut request: Request<GetDocumentRequest>,
     ) -> Result<Response<GetDocumentResponse>, Status> {
+        self.access.admit(&mut request)?;
         let req = request.into_inner();
         info!("gRPC: Getting document {} from collection {}", req.id, req.collection);
+        let consistency = ReadConsistency::parse(req.consistency.as_deref().unwrap_or("local"), req.max_lag_ms)
+            .map_err(|e| Status::invalid_argument(redact(&e.to_string())))?;
         
         // Execute document retrieval through query engine
-        match self.query.get_document(&req.collection, &req.id).await {
+        match self.query.get_document_with_consistency(&req.collection, &req.id, consistency).await {
             Ok(document) => {
                 let data = serde_json::to_vec(&document)
-                    .map_err(|e| Status::internal(format!("Serialization error: {}", e)))?;
+                    .map_err(|e| Status::internal(redact(&format!("Serialization error: {}", e))))?;
                 
                 let mut metadata = std::collections::HashMap::new();
                 metadata.insert("content_type".to_string(), "application/json".to_string());
@@ -157,70 +339,91 @@ impl DataService for DataServiceImpl {
                 
                 Ok(Response::new(response))
             }
-            Err(e) => {
-                Err(Status::not_found(format!("Document not found: {}", e)))
-            }
+            Err(e) => Err(read_consistency_status(&e)
+                .unwrap_or_else(|| Status::not_found(redact(&format!("Document not found: {}", e))))),
         }
     }
 
     async fn put_document(
         &self,
-        request: Request<PutDocumentRequest>,
+        mut request: Request<PutDocumentRequest>,
     ) -> Result<Response<PutDocumentResponse>, Status> {
+        self.access.admit(&mut request)?;
         let req = request.into_inner();
         info!("gRPC: Storing document {} in collection {}", req.id, req.collection);
         
         // Parse JSON data from bytes
         let document: serde_json::Value = serde_json::from_slice(&req.data)
-            .map_err(|e| Status::invalid_argument(format!("Invalid JSON data: {}", e)))?;
+            .map_err(|e| Status::invalid_argument(redact(&format!("Invalid JSON data: {}", e))))?;
         
         // Execute document storage through query engine
         match self.query.store_document(&req.collection, &req.id, &document).await {
             Ok(_) => {
+                self.document_changed(req.collection, req.id);
                 let response = PutDocumentResponse {
                     success: true,
                     version: 1, // Simple versioning - can be enhanced
                 };
                 Ok(Response::new(response))
             }
+            Err(e) if e.to_string().starts_with("Invalid document") => Err(Status::invalid_argument(redact(&e.to_string()))),
             Err(e) => {
-                Err(Status::internal(format!("Failed to store document: {}", e)))
+                Err(Status::internal(redact(&format!("Failed to store document: {}", e))))
             }
         }
     }
 
     async fn delete_document(
         &self,
-        request: Request<DeleteDocumentRequest>,
+        mut request: Request<DeleteDocumentRequest>,
     ) -> Result<Response<DeleteDocumentResponse>, Status> {
+        self.access.admit(&mut request)?;
         let req = request.into_inner();
         info!("gRPC: Deleting document {} from collection {}", req.id, req.collection);
         
         // Execute document deletion through query engine
         match self.query.delete_document(&req.collection, &req.id).await {
             Ok(_) => {
+                self.document_changed(req.collection, req.id);
                 let response = DeleteDocumentResponse {
                     success: true,
                 };
                 Ok(Response::new(response))
             }
             Err(e) => {
-                Err(Status::not_found(format!("Failed to delete document: {}", e)))
+                Err(Status::not_found(redact(&format!("Failed to delete document: {}", e))))
             }
         }
     }
 
     async fn query_documents(
         &self,
-        request: Request<QueryDocumentsRequest>,
+        mut request: Request<QueryDocumentsRequest>,
     ) -> Result<Response<QueryDocumentsResponse>, Status> {
+        self.access.admit(&mut request)?;
         let req = request.into_inner();
         info!("gRPC: Querying documents in collection {}", req.collection);
+        let consistency = ReadConsistency::parse(req.consistency.as_deref().unwrap_or("local"), req.max_lag_ms)
+            .map_err(|e| Status::invalid_argument(redact(&e.to_string())))?;
         
         // Parse filter from bytes to JSON
         let filter = if !req.filter.is_empty() {
             Some(serde_json::from_slice(&req.filter)
-                .map_err(|e| Status::invalid_argument(format!("Invalid filter JSON: {}", e)))?)
+                .map_err(|e| Status::invalid_argument(redact(&format!("Invalid filter JSON: {}", e))))?)
+        } else {
+            None
+        };
+        
+        let sort = if !req.sort.is_empty() {
+            Some(serde_json::from_slice(&req.sort)
+                .map_err(|e| Status::invalid_argument(redact(&format!("Invalid sort JSON: {}", e))))?)
+        } else {
+            None
+        };
+
+        let projection = if !req.projection.is_empty() {
+            Some(serde_json::from_slice(&req.projection)
+                .map_err(|e| Status::invalid_argument(redact(&format!("Invalid projection JSON: {}", e))))?)
         } else {
             None
         };
@@ -228,18 +431,26 @@ impl DataService for DataServiceImpl {
         // Build query request
         let query_request = aerolithdb_query::QueryRequest {
             filter,
-            sort: None,
+            sort,
             limit: req.limit.map(|l| l as usize),
             offset: req.offset.map(|o| o as usize),
+            cursor: req.cursor,
+            limits: None,
+            projection,
+            as_of: None,
         };
         
         // Execute query through query engine
-        match self.query.query_documents(&req.collection, &query_request).await {
+        let result = match self.query.await_read_consistency(&req.collection, consistency).await {
+            Ok(()) => self.query.query_documents(&req.collection, &query_request).await,
+            Err(e) => Err(e),
+        };
+        match result {
             Ok(query_result) => {
-                let documents: Vec<DocumentResult> = query_result.documents
+                let documents: Vec<DocumentResult> = query_result.ids
                     .into_iter()
-                    .enumerate()
-                    .map(|(idx, doc)| {
+                    .zip(query_result.documents)
+                    .map(|(id, doc)| {
                         let data = serde_json::to_vec(&doc)
                             .unwrap_or_else(|_| b"{}".to_vec());
                         
@@ -247,7 +458,7 @@ impl DataService for DataServiceImpl {
                         metadata.insert("content_type".to_string(), "application/json".to_string());
                         
                         DocumentResult {
-                            id: format!("doc_{}", idx), // Use index if no ID field in document
+                            id,
                             data,
                             version: 1,
                             metadata,
@@ -258,13 +469,159 @@ impl DataService for DataServiceImpl {
                 let response = QueryDocumentsResponse {
                     documents,
                     total: query_result.total as u64,
+                    next_cursor: query_result.next_cursor,
                 };
                 
                 Ok(Response::new(response))
             }
-            Err(e) => {
-                Err(Status::internal(format!("Query failed: {}", e)))
+            Err(e) if ["Invalid filter", "Invalid cursor", "Invalid projection"].iter().any(|prefix| e.to_string().starts_with(prefix)) => {
+                Err(Status::invalid_argument(redact(&e.to_string())))
             }
+            Err(e) => Err(read_consistency_status(&e)
+                .unwrap_or_else(|| Status::internal(redact(&format!("Query failed: {}", e))))),
+        }
+    }
+}
+
+pub struct PubSubServiceImpl {
+    pubsub: Arc<PubSubBroker>,
+    access: AccessInterceptor,
+}
+
+impl PubSubService for PubSubServiceImpl {
+    async fn publish(
+        &self,
+        mut request: Request<PublishRequest>,
+    ) -> Result<Response<PublishResponse>, Status> {
+        self.access.admit(&mut request)?;
+        let req = request.into_inner();
+        info!("gRPC: Publishing message to channel {}", req.channel);
+
+        let payload: serde_json::Value = serde_json::from_slice(&req.payload)
+            .map_err(|e| Status::invalid_argument(redact(&format!("Invalid JSON payload: {}", e))))?;
+
+        match self.pubsub.publish(&req.channel, payload, None).await {
+            Ok(message) => Ok(Response::new(PublishResponse {
+                message_id: message.id,
+                sequence: message.sequence,
+            })),
+            Err(e) => Err(Status::invalid_argument(redact(&format!("Failed to publish: {}", e)))),
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        mut request: Request<SubscribeRequest>,
+    ) -> Result<Response<tokio::sync::mpsc::Receiver<Result<ChannelMessage, Status>>>, Status> {
+        self.access.admit(&mut request)?;
+        let req = request.into_inner();
+        info!("gRPC: Subscribing to channel {}", req.channel);
+
+        let (history, mut live) = self.pubsub.subscribe(&req.channel).await
+            .map_err(|e| Status::invalid_argument(redact(&format!("Failed to subscribe: {}", e))))?;
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(128);
+        tokio::spawn(async move {
+            // Retained history first, then live messages until the client goes away
+            for message in history {
+                if sender.send(Ok(message.into())).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                let item = match live.recv().await {
+                    Ok(message) => Ok(message.into()),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        Err(Status::data_loss(format!("Subscriber lagged; {} messages dropped", skipped)))
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                };
+                if sender.send(item).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(receiver))
+    }
+}
+
+pub struct LockServiceImpl {
+    consensus: Arc<ConsensusEngine>,
+    access: AccessInterceptor,
+}
+
+/// Map lease errors to gRPC status codes
+fn lease_error_status(e: anyhow::Error) -> Status {
+    let message = e.to_string();
+    if message.starts_with("Lease name") || message.starts_with("Lease holder") || message.starts_with("Lease TTL") {
+        Status::invalid_argument(redact(&message))
+    } else if message.contains("did not commit in time") || message.starts_with("Writes halted") {
+        Status::unavailable(redact(&message))
+    } else {
+        Status::internal(redact(&format!("Lease operation failed: {}", message)))
+    }
+}
+
+impl LockService for LockServiceImpl {
+    async fn acquire_lock(
+        &self,
+        mut request: Request<AcquireLockRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        self.access.admit(&mut request)?;
+        let req = request.into_inner();
+        info!("gRPC: Acquiring lock {} for {}", req.name, req.holder);
+
+        self.consensus
+            .acquire_lease(&req.name, &req.holder, std::time::Duration::from_millis(req.ttl_ms))
+            .await
+            .map(|outcome| Response::new(outcome.into()))
+            .map_err(lease_error_status)
+    }
+
+    async fn renew_lock(
+        &self,
+        mut request: Request<LockTokenRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        self.access.admit(&mut request)?;
+        let req = request.into_inner();
+
+        self.consensus
+            .renew_lease(&req.name, &req.holder, req.fencing_token)
+            .await
+            .map(|outcome| Response::new(outcome.into()))
+            .map_err(lease_error_status)
+    }
+
+    async fn release_lock(
+        &self,
+        mut request: Request<LockTokenRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        self.access.admit(&mut request)?;
+        let req = request.into_inner();
+        info!("gRPC: Releasing lock {} held by {}", req.name, req.holder);
+
+        self.consensus
+            .release_lease(&req.name, &req.holder, req.fencing_token)
+            .await
+            .map(|outcome| Response::new(outcome.into()))
+            .map_err(lease_error_status)
+    }
+
+    async fn get_lock(
+        &self,
+        mut request: Request<GetLockRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        self.access.admit(&mut request)?;
+        let req = request.into_inner();
+
+        match self.consensus.get_lease(&req.name).await {
+            Ok(lease) => Ok(Response::new(LockResponse {
+                success: lease.is_some(),
+                lease: lease.map(Into::into),
+            })),
+            Err(e) => Err(lease_error_status(e)),
         }
     }
 }
@@ -274,19 +631,58 @@ impl GRPCAPIv1 {
         config: &GRPCConfig,
         query: Arc<QueryEngine>,
         security: Arc<SecurityFramework>,
+        pubsub: Arc<PubSubBroker>,
+        consensus: Arc<ConsensusEngine>,
     ) -> Result<Self> {
         info!("Initializing gRPC API v1");
         Ok(Self {
             config: config.clone(),
             query,
             security,
+            pubsub,
+            consensus,
+            access: Arc::new(AccessPolicy::new(AccessConfig::default())?),
+            cdn: None,
         })
-    }    pub async fn start(&self) -> Result<()> {
+    }
+
+    /// Replace the default access policy, which admits every request
+    /// without limits.
+    pub fn with_access(mut self, access: Arc<AccessPolicy>) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Purge CDN caches of the documents changed through this API.
+    pub fn with_cdn(mut self, cdn: Arc<CdnInvalidator>) -> Self {
+        self.cdn = Some(cdn);
+        self
+    }
+
+    /// Admission through the access policy, which every service handler
+    /// applies to its requests
+    pub fn interceptor(&self) -> AccessInterceptor {
+        AccessInterceptor::new(Arc::clone(&self.access))
+    }
+
+    pub async fn start(&self) -> Result<()> {
         info!("Starting gRPC API v1 on {}:{}", self.config.bind_address, self.config.port);
 
         let _data_service = DataServiceImpl {
             query: Arc::clone(&self.query),
             security: Arc::clone(&self.security),
+            access: self.interceptor(),
+            cdn: self.cdn.clone(),
+        };
+
+        let _pubsub_service = PubSubServiceImpl {
+            pubsub: Arc::clone(&self.pubsub),
+            access: self.interceptor(),
+        };
+
+        let _lock_service = LockServiceImpl {
+            consensus: Arc::clone(&self.consensus),
+            access: self.interceptor(),
         };
 
         let addr = format!("{}:{}", self.config.bind_address, self.config.port)
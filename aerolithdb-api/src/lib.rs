@@ -66,17 +66,30 @@ use anyhow::Result;
 use std::sync::Arc;
 use tracing::info;
 
+use aerolithdb_consensus::ConsensusEngine;
 use aerolithdb_query::QueryEngine;
 use aerolithdb_security::SecurityFramework;
 
 pub mod rest;
+pub mod compression; // Accept-Encoding negotiated response compression
+pub mod formats; // NDJSON and CSV result streaming
+pub mod sse; // Server-Sent Events fallback for change streams and job progress
 pub mod grpc;
 pub mod grpc_v2;
 pub mod websocket;
+pub mod subscription_journal; // Resumable subscriptions across node failover
+pub mod pubsub; // Application pub/sub channels
 pub mod graphql;
 pub mod payment; // Payment API for cryptocurrency integration
 // pub mod saas;    // SaaS API for multi-tenancy and billing (temporarily disabled)
 pub mod middleware; // SaaS middleware for authentication and tenant routing
+pub mod sandbox; // Public sandbox mode with anonymous access and strict limits
+pub mod mirror; // Shadow traffic mirroring for safe upgrades
+pub mod versioning; // Versioned routers, deprecation headers and version usage
+pub mod access; // Authentication, tenancy and rate limits shared by REST, gRPC and WebSocket
+pub mod cdn; // CDN purge requests after document and collection changes
+pub mod telemetry; // Process resource usage for the health and statistics endpoints
+pub mod metrics_history; // Downsampled history of key metrics for trend views
 
 // Include Protocol Buffer generated types if available
 #[path = "proto/mod.rs"]
@@ -87,6 +100,15 @@ pub use rest::*;
 pub use grpc::*;
 pub use grpc_v2::*;   // Export enhanced gRPC
 pub use websocket::*;
+pub use pubsub::{ChannelOptions, ChannelStats, PubSubBroker, PubSubMessage};
+pub use compression::CompressionConfig;
+pub use sandbox::{SandboxConfig, SandboxGuard};
+pub use mirror::{MirrorConfig, MirrorDiff, MirrorReport, MirrorStats, MirrorTarget, TrafficMirror};
+pub use versioning::{ApiVersions, ClientUsage, Deprecation, VersionReport, VersionUsage, VersioningConfig, API_VERSIONS};
+pub use access::{AccessConfig, AccessInterceptor, AccessPolicy, ApiToken, Credentials, Protocol, RateLimit, Rejection};
+pub use subscription_journal::{JournaledEvent, ResumeToken, ResumedSubscription, SubscriptionJournal};
+pub use metrics_history::{GaugeSummary, MetricPoint, MetricsHistory, MetricsHistoryConfig, MetricsReading, Resolution, METRICS_HISTORY_COLLECTION};
+pub use cdn::{CdnConfig, CdnInvalidator, CdnProvider, CdnPurger, CdnRule, CdnStats, ContentChange, PurgeFuture};
 
 /// Comprehensive API configuration defining all supported protocols and their settings.
 /// 
@@ -103,6 +125,18 @@ pub struct APIConfig {    /// REST API configuration for HTTP-based access
     
     /// WebSocket API configuration for real-time bidirectional communication
     pub websocket_api: WebSocketConfig,
+
+    /// Compression of HTTP response bodies
+    pub compression: CompressionConfig,
+
+    /// Authentication, tenancy and rate limits applied alike to REST and gRPC
+    pub access: AccessConfig,
+
+    /// Purge requests to CDNs fronting the read API, sent after changes
+    pub cdn: Option<CdnConfig>,
+
+    /// Sampling and retention of the node's metrics history
+    pub metrics_history: MetricsHistoryConfig,
 }
 
 impl Default for APIConfig {
@@ -112,6 +146,9 @@ impl Default for APIConfig {
                 bind_address: "127.0.0.1".to_string(),
                 port: 8080,
                 cors_enabled: true,
+                sandbox: None,
+                mirror: None,
+                versioning: VersioningConfig::default(),
             },
             grpc_api: GRPCConfig {
                 enabled: true,
@@ -125,6 +162,10 @@ impl Default for APIConfig {
                 port: 8083,
                 max_connections: 1000,
             },
+            compression: CompressionConfig::default(),
+            access: AccessConfig::default(),
+            cdn: None,
+            metrics_history: MetricsHistoryConfig::default(),
         }
     }
 }
@@ -147,6 +188,15 @@ pub struct RESTAPIConfig {
     
     /// Enable Cross-Origin Resource Sharing for web browser clients
     pub cors_enabled: bool,
+    
+    /// Run as a public sandbox: anonymous demo tenant, tight quotas, and rate limits
+    pub sandbox: Option<SandboxConfig>,
+
+    /// Duplicate a fraction of read traffic to a shadow cluster or engine
+    pub mirror: Option<MirrorConfig>,
+
+    /// Deprecated endpoints and how clients are told apart in version usage
+    pub versioning: VersioningConfig,
 }
 
 /*  // Temporarily disabled due to axum version conflicts
@@ -206,6 +256,7 @@ pub struct APIGateway {
     // graphql_api: Option<Arc<GraphQLAPI>>,  // Temporarily disabled
     grpc_api: Option<Arc<GRPCAPIv1>>,
     websocket_api: Option<Arc<RealtimeAPI>>,
+    pubsub: Arc<PubSubBroker>,
 }
 
 impl APIGateway {
@@ -213,27 +264,66 @@ impl APIGateway {
         config: &APIConfig,
         query: Arc<QueryEngine>,
         security: Arc<SecurityFramework>,
+        consensus: Arc<ConsensusEngine>,
     ) -> Result<Self> {
         info!("Initializing API gateway");
 
-        let rest_api = if config.rest_api.enabled {
-            Some(Arc::new(RESTAPIv1::new(&config.rest_api, Arc::clone(&query), Arc::clone(&security)).await?))
-        } else {
-            None
-        };        // let graphql_api = if config.graphql_api.enabled {
-        //     Some(Arc::new(GraphQLAPI::new(&config.graphql_api, Arc::clone(&query), Arc::clone(&security)).await?))
+        // Shared so messages published over one protocol reach subscribers on the others
+        let pubsub = Arc::new(PubSubBroker::new(Arc::clone(&query)));
+
+        // Shared so a client's rate limit holds across protocols
+        let access = Arc::new(AccessPolicy::new(config.access.clone())?);
+
+        // Shared so changes through either protocol purge the same CDNs
+        let cdn = config.cdn.clone().map(CdnInvalidator::new).transpose()?.map(Arc::new);
+
+        // let graphql_api = if config.graphql_api.enabled {
+        //     Some(Arc::new(GraphQLAPI::new(&config.graphql_api, Arc::clone(&query), Arc::clone(&security), consensus.feature_flags()).await?))
         // } else {
         //     None
         // };
 
-        let grpc_api = if config.grpc_api.enabled {
-            Some(Arc::new(GRPCAPIv1::new(&config.grpc_api, Arc::clone(&query), Arc::clone(&security)).await?))
+        // The sandbox's limits are enforced on the REST API only, so nothing else is exposed
+        let sandboxed = config.rest_api.enabled && config.rest_api.sandbox.is_some();
+        if sandboxed {
+            info!("Sandbox mode: gRPC and WebSocket APIs are disabled");
+        }
+
+        let grpc_api = if config.grpc_api.enabled && !sandboxed {
+            let mut grpc_api = GRPCAPIv1::new(&config.grpc_api, Arc::clone(&query), Arc::clone(&security), Arc::clone(&pubsub), Arc::clone(&consensus))
+                .await?
+                .with_access(Arc::clone(&access));
+            if let Some(cdn) = &cdn {
+                grpc_api = grpc_api.with_cdn(Arc::clone(cdn));
+            }
+            Some(Arc::new(grpc_api))
         } else {
             None
         };
 
-        let websocket_api = if config.websocket_api.enabled {
-            Some(Arc::new(RealtimeAPI::new(&config.websocket_api, Arc::clone(&query), Arc::clone(&security)).await?))
+        let websocket_api = if config.websocket_api.enabled && !sandboxed {
+            let websocket_api = RealtimeAPI::new(&config.websocket_api, Arc::clone(&query), Arc::clone(&pubsub))
+                .await?
+                .with_access(Arc::clone(&access));
+            Some(Arc::new(websocket_api))
+        } else {
+            None
+        };
+
+        // Change streams are also served over REST for clients whose WebSocket upgrade is blocked
+        let rest_api = if config.rest_api.enabled {
+            let mut rest_api = RESTAPIv1::new(&config.rest_api, Arc::clone(&query), Arc::clone(&security), Arc::clone(&consensus))
+                .await?
+                .with_compression(config.compression.clone())
+                .with_access(Arc::clone(&access))
+                .with_metrics_history(config.metrics_history.clone());
+            if let Some(websocket_api) = &websocket_api {
+                rest_api = rest_api.with_realtime(Arc::clone(websocket_api));
+            }
+            if let Some(cdn) = &cdn {
+                rest_api = rest_api.with_cdn(Arc::clone(cdn));
+            }
+            Some(Arc::new(rest_api))
         } else {
             None
         };        Ok(Self {
@@ -242,6 +332,7 @@ impl APIGateway {
             // graphql_api,  // Temporarily disabled
             grpc_api,
             websocket_api,
+            pubsub,
         })
     }
 
@@ -286,4 +377,16 @@ impl APIGateway {
         info!("API gateway stopped successfully");
         Ok(())
     }
+
+    /// Pub/sub broker shared by all protocols, for publishing server-side events
+    pub fn pubsub(&self) -> Arc<PubSubBroker> {
+        Arc::clone(&self.pubsub)
+    }
+
+    /// Expose the node's redacted effective configuration on the REST admin endpoint
+    pub async fn publish_effective_config(&self, export: serde_json::Value) {
+        if let Some(rest_api) = &self.rest_api {
+            rest_api.publish_effective_config(export).await;
+        }
+    }
 }
@@ -0,0 +1,461 @@
+//! # Historical Metrics
+//!
+//! Key metrics of the node, kept locally so the status endpoints and the
+//! TUI can draw trends without an external monitoring stack. Readings are
+//! taken every `sample_interval` and downsampled into two rings: one point
+//! per minute for the last day and one per hour for the last month, by
+//! default. A point holds the mean and peak of each gauge and the growth of
+//! each counter over its minute or hour.
+//!
+//! Completed points are stored in a system collection, one document per
+//! hour of minute points and per day of hour points. The documents expire
+//! once their points fall out of retention, and the rings are reloaded from
+//! them when the node restarts. The minute or hour still in progress is
+//! kept in memory only.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use aerolithdb_query::QueryEngine;
+
+/// System collection holding the completed points, grouped by period
+pub const METRICS_HISTORY_COLLECTION: &str = "_metrics_history";
+
+/// How often readings are taken and how long each resolution is kept.
+#[derive(Debug, Clone)]
+pub struct MetricsHistoryConfig {
+    /// Whether readings are taken at all
+    pub enabled: bool,
+    /// Time between readings; each minute point averages the readings in it
+    pub sample_interval: Duration,
+    /// How long minute points are kept
+    pub minute_retention: Duration,
+    /// How long hour points are kept
+    pub hour_retention: Duration,
+}
+
+impl Default for MetricsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sample_interval: Duration::from_secs(10),
+            minute_retention: Duration::from_secs(24 * 3600),
+            hour_retention: Duration::from_secs(30 * 24 * 3600),
+        }
+    }
+}
+
+/// Period a point covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    #[default]
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Minute => "minute",
+            Resolution::Hour => "hour",
+        }
+    }
+
+    fn step(&self) -> chrono::Duration {
+        match self {
+            Resolution::Minute => chrono::Duration::minutes(1),
+            Resolution::Hour => chrono::Duration::hours(1),
+        }
+    }
+
+    /// Period of the document the points of this resolution are stored in
+    fn chunk(&self) -> chrono::Duration {
+        match self {
+            Resolution::Minute => chrono::Duration::hours(1),
+            Resolution::Hour => chrono::Duration::days(1),
+        }
+    }
+
+    /// Start of the point covering `at`
+    fn start_of(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        at.duration_trunc(self.step()).unwrap_or(at)
+    }
+
+    /// Document the point starting at `start` is stored in
+    fn chunk_id(&self, start: DateTime<Utc>) -> (String, DateTime<Utc>) {
+        let chunk_start = start.duration_trunc(self.chunk()).unwrap_or(start);
+        (format!("{}-{}", self.as_str(), chunk_start.format("%Y%m%d%H")), chunk_start)
+    }
+}
+
+/// Instantaneous metrics a point is built from. Counters are running
+/// totals; points record how much they grew.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsReading {
+    pub cpu_percent: f64,
+    pub resident_memory_bytes: f64,
+    /// Fullest disk holding node data
+    pub disk_used_percent: f64,
+    pub open_file_descriptors: f64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Queries and aggregations executed
+    pub queries: u64,
+    /// API requests admitted or refused
+    pub requests: u64,
+}
+
+/// Mean and peak of a gauge over a point's period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GaugeSummary {
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl GaugeSummary {
+    fn merge(&mut self, samples: u64, other: &GaugeSummary, other_samples: u64) {
+        let total = (samples + other_samples).max(1) as f64;
+        self.mean = (self.mean * samples as f64 + other.mean * other_samples as f64) / total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Metrics of one minute or hour.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricPoint {
+    /// Start of the minute or hour the point covers
+    pub start: DateTime<Utc>,
+    /// Readings the point was built from
+    pub samples: u64,
+    /// CPU in percent of one core
+    pub cpu_percent: GaugeSummary,
+    pub resident_memory_bytes: GaugeSummary,
+    pub disk_used_percent: GaugeSummary,
+    pub open_file_descriptors: GaugeSummary,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub queries: u64,
+    pub requests: u64,
+}
+
+impl MetricPoint {
+    /// A point of a single reading, with counters grown since `previous`.
+    /// Counters that went backwards were reset and count from zero.
+    fn from_reading(start: DateTime<Utc>, reading: &MetricsReading, previous: Option<&MetricsReading>) -> Self {
+        let gauge = |value: f64| GaugeSummary { mean: value, max: value };
+        let growth = |current: u64, previous: Option<u64>| match previous {
+            Some(previous) if current >= previous => current - previous,
+            Some(_) => current,
+            None => 0,
+        };
+        Self {
+            start,
+            samples: 1,
+            cpu_percent: gauge(reading.cpu_percent),
+            resident_memory_bytes: gauge(reading.resident_memory_bytes),
+            disk_used_percent: gauge(reading.disk_used_percent),
+            open_file_descriptors: gauge(reading.open_file_descriptors),
+            cache_hits: growth(reading.cache_hits, previous.map(|p| p.cache_hits)),
+            cache_misses: growth(reading.cache_misses, previous.map(|p| p.cache_misses)),
+            queries: growth(reading.queries, previous.map(|p| p.queries)),
+            requests: growth(reading.requests, previous.map(|p| p.requests)),
+        }
+    }
+
+    fn merge(&mut self, other: &MetricPoint) {
+        self.cpu_percent.merge(self.samples, &other.cpu_percent, other.samples);
+        self.resident_memory_bytes.merge(self.samples, &other.resident_memory_bytes, other.samples);
+        self.disk_used_percent.merge(self.samples, &other.disk_used_percent, other.samples);
+        self.open_file_descriptors.merge(self.samples, &other.open_file_descriptors, other.samples);
+        self.samples += other.samples;
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.queries += other.queries;
+        self.requests += other.requests;
+    }
+
+    /// Share of cache lookups that hit, if there were any
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+}
+
+/// Persisted form of the points of one hour or day
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredChunk {
+    resolution: Resolution,
+    start: DateTime<Utc>,
+    points: Vec<MetricPoint>,
+}
+
+/// Completed points of both resolutions and the ones in progress
+#[derive(Debug, Default)]
+struct HistoryState {
+    minutes: VecDeque<MetricPoint>,
+    hours: VecDeque<MetricPoint>,
+    minute: Option<MetricPoint>,
+    hour: Option<MetricPoint>,
+    last_reading: Option<MetricsReading>,
+}
+
+impl HistoryState {
+    /// Add a reading taken at `at`, returning the points it completed.
+    fn record(&mut self, reading: MetricsReading, at: DateTime<Utc>) -> Vec<(Resolution, MetricPoint)> {
+        let start = Resolution::Minute.start_of(at);
+        let sample = MetricPoint::from_reading(start, &reading, self.last_reading.as_ref());
+        self.last_reading = Some(reading);
+
+        let mut completed = Vec::new();
+        match &mut self.minute {
+            Some(minute) if minute.start == start => minute.merge(&sample),
+            _ => {
+                if let Some(minute) = self.minute.replace(sample) {
+                    if let Some(hour) = self.fold_into_hour(&minute) {
+                        completed.push((Resolution::Hour, hour));
+                    }
+                    self.minutes.push_back(minute.clone());
+                    completed.push((Resolution::Minute, minute));
+                }
+            }
+        }
+
+        // An hour is complete once a minute of a later one has started
+        if self.hour.as_ref().is_some_and(|hour| hour.start != Resolution::Hour.start_of(start)) {
+            if let Some(hour) = self.hour.take() {
+                self.hours.push_back(hour.clone());
+                completed.push((Resolution::Hour, hour));
+            }
+        }
+        completed
+    }
+
+    /// Add a completed minute to the hour in progress, returning the
+    /// previous hour if the minute starts a new one.
+    fn fold_into_hour(&mut self, minute: &MetricPoint) -> Option<MetricPoint> {
+        let start = Resolution::Hour.start_of(minute.start);
+        match &mut self.hour {
+            Some(hour) if hour.start == start => {
+                hour.merge(minute);
+                None
+            }
+            _ => {
+                let finished = self.hour.replace(MetricPoint { start, ..minute.clone() });
+                if let Some(hour) = &finished {
+                    self.hours.push_back(hour.clone());
+                }
+                finished
+            }
+        }
+    }
+
+    /// Drop completed points older than their retention.
+    fn prune(&mut self, config: &MetricsHistoryConfig, now: DateTime<Utc>) {
+        for (points, retention) in [(&mut self.minutes, config.minute_retention), (&mut self.hours, config.hour_retention)] {
+            let oldest = now - chrono::Duration::from_std(retention).unwrap_or(chrono::Duration::MAX);
+            while points.front().is_some_and(|point| point.start < oldest) {
+                points.pop_front();
+            }
+        }
+    }
+
+    fn points(&self, resolution: Resolution) -> &VecDeque<MetricPoint> {
+        match resolution {
+            Resolution::Minute => &self.minutes,
+            Resolution::Hour => &self.hours,
+        }
+    }
+}
+
+/// Downsampled history of the node's key metrics.
+#[derive(Debug)]
+pub struct MetricsHistory {
+    query: Arc<QueryEngine>,
+    config: MetricsHistoryConfig,
+    state: Mutex<HistoryState>,
+}
+
+impl MetricsHistory {
+    pub fn new(query: Arc<QueryEngine>, config: MetricsHistoryConfig) -> Self {
+        Self {
+            query,
+            config,
+            state: Mutex::new(HistoryState::default()),
+        }
+    }
+
+    pub fn config(&self) -> &MetricsHistoryConfig {
+        &self.config
+    }
+
+    /// Reload the points stored before a restart, returning how many were
+    /// loaded. Minutes completed after the last stored hour resume the hour
+    /// in progress.
+    pub async fn load(&self) -> Result<usize> {
+        let stored = match self.query.list_documents(METRICS_HISTORY_COLLECTION, None, None).await {
+            Ok(result) => result.documents,
+            // Nothing was stored yet
+            Err(_) => Vec::new(),
+        };
+
+        let mut minutes = Vec::new();
+        let mut hours = Vec::new();
+        for document in stored {
+            match serde_json::from_value::<StoredChunk>(document) {
+                Ok(chunk) if chunk.resolution == Resolution::Minute => minutes.extend(chunk.points),
+                Ok(chunk) => hours.extend(chunk.points),
+                Err(e) => warn!("Skipping unreadable metrics history document: {}", e),
+            }
+        }
+        let loaded = minutes.len() + hours.len();
+
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        for (mut points, ring) in [(minutes, &mut state.minutes), (hours, &mut state.hours)] {
+            points.extend(ring.drain(..));
+            points.sort_by_key(|point| point.start);
+            points.dedup_by_key(|point| point.start);
+            ring.extend(points);
+        }
+        let resumed_after = state.hours.back().map(|hour| hour.start + Resolution::Hour.step());
+        let resumed: Vec<MetricPoint> = state.minutes
+            .iter()
+            .filter(|minute| resumed_after.is_none_or(|after| minute.start >= after))
+            .cloned()
+            .collect();
+        state.hour = None;
+        for minute in &resumed {
+            let _ = state.fold_into_hour(minute);
+        }
+        state.prune(&self.config, Utc::now());
+
+        debug!("Loaded {} metric points", loaded);
+        Ok(loaded)
+    }
+
+    /// Add a reading taken now, storing the points it completes.
+    pub async fn record(&self, reading: MetricsReading) -> Result<()> {
+        self.record_at(reading, Utc::now()).await
+    }
+
+    async fn record_at(&self, reading: MetricsReading, at: DateTime<Utc>) -> Result<()> {
+        let chunks: Vec<(Resolution, String, StoredChunk)> = {
+            let mut state = self.state.lock().unwrap();
+            let completed = state.record(reading, at);
+            state.prune(&self.config, at);
+            completed
+                .into_iter()
+                .map(|(resolution, point)| {
+                    let (id, start) = resolution.chunk_id(point.start);
+                    let end = start + resolution.chunk();
+                    let points = state.points(resolution)
+                        .iter()
+                        .filter(|point| point.start >= start && point.start < end)
+                        .cloned()
+                        .collect();
+                    (resolution, id, StoredChunk { resolution, start, points })
+                })
+                .collect()
+        };
+
+        for (resolution, id, chunk) in chunks {
+            // Kept until the last point of the chunk falls out of retention
+            let retention = match resolution {
+                Resolution::Minute => self.config.minute_retention,
+                Resolution::Hour => self.config.hour_retention,
+            };
+            let ttl = retention + resolution.chunk().to_std().unwrap_or_default();
+            self.query
+                .store_document_with_ttl(METRICS_HISTORY_COLLECTION, &id, &serde_json::to_value(&chunk)?, Some(ttl))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Completed points of `resolution`, oldest first, starting at or after
+    /// `since` if given.
+    pub fn points(&self, resolution: Resolution, since: Option<DateTime<Utc>>) -> Vec<MetricPoint> {
+        let state = self.state.lock().unwrap();
+        state.points(resolution)
+            .iter()
+            .filter(|point| since.is_none_or(|since| point.start >= since))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(cpu_percent: f64, requests: u64) -> MetricsReading {
+        MetricsReading { cpu_percent, requests, ..Default::default() }
+    }
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        use chrono::TimeZone;
+        Utc.with_ymd_and_hms(2026, 10, 16, hour, minute, second).unwrap()
+    }
+
+    #[test]
+    fn test_readings_downsample_into_minutes_and_hours() {
+        let mut state = HistoryState::default();
+        assert!(state.record(reading(10.0, 100), at(9, 0, 0)).is_empty());
+        assert!(state.record(reading(30.0, 130), at(9, 0, 30)).is_empty());
+
+        // The next minute completes the first
+        let completed = state.record(reading(50.0, 150), at(9, 1, 0));
+        assert_eq!(completed.len(), 1);
+        let (resolution, minute) = &completed[0];
+        assert_eq!(*resolution, Resolution::Minute);
+        assert_eq!(minute.start, at(9, 0, 0));
+        assert_eq!(minute.samples, 2);
+        assert_eq!(minute.cpu_percent, GaugeSummary { mean: 20.0, max: 30.0 });
+        // The first reading has nothing to grow from
+        assert_eq!(minute.requests, 30);
+
+        // The first minute of the next hour completes the hour
+        let completed = state.record(reading(70.0, 170), at(10, 0, 0));
+        let resolutions: Vec<Resolution> = completed.iter().map(|(resolution, _)| *resolution).collect();
+        assert_eq!(resolutions, vec![Resolution::Minute, Resolution::Hour]);
+        let hour = &completed[1].1;
+        assert_eq!(hour.start, at(9, 0, 0));
+        assert_eq!(hour.samples, 3);
+        assert_eq!(hour.requests, 50);
+        assert_eq!(hour.cpu_percent, GaugeSummary { mean: 30.0, max: 50.0 });
+        assert_eq!(state.points(Resolution::Minute).len(), 2);
+        assert_eq!(state.points(Resolution::Hour).len(), 1);
+    }
+
+    #[test]
+    fn test_counter_resets_and_retention() {
+        let mut state = HistoryState::default();
+        state.record(reading(0.0, 500), at(9, 0, 0));
+        state.record(reading(0.0, 20), at(9, 0, 10));
+        let completed = state.record(reading(0.0, 30), at(9, 1, 0));
+        // The counter was reset, so it counts from zero
+        assert_eq!(completed[0].1.requests, 20);
+
+        for minute in 2..10 {
+            state.record(reading(0.0, 30), at(9, minute, 0));
+        }
+        let config = MetricsHistoryConfig { minute_retention: Duration::from_secs(300), ..Default::default() };
+        state.prune(&config, at(9, 10, 0));
+        let starts: Vec<DateTime<Utc>> = state.points(Resolution::Minute).iter().map(|point| point.start).collect();
+        assert_eq!(starts, (5..9).map(|minute| at(9, minute, 0)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_chunk_ids() {
+        assert_eq!(Resolution::Minute.chunk_id(at(9, 42, 0)), ("minute-2026101609".to_string(), at(9, 0, 0)));
+        assert_eq!(Resolution::Hour.chunk_id(at(9, 0, 0)), ("hour-2026101600".to_string(), at(0, 0, 0)));
+        assert_eq!(MetricPoint::from_reading(at(9, 0, 0), &reading(0.0, 0), None).cache_hit_rate(), None);
+    }
+}
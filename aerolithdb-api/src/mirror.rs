@@ -0,0 +1,522 @@
+//! # Shadow Traffic Mirroring
+//!
+//! Lets a new cluster, or a new code path such as a different query
+//! planner, see production reads before it serves them. With mirroring
+//! enabled on the REST API, a fixed fraction of read requests is
+//! duplicated to a shadow target after the client has been answered:
+//!
+//! - ✅ Only reads are mirrored: `GET` requests and collection queries,
+//!   outside the admin and internal routes
+//! - ✅ The client always gets the primary response; the shadow call runs in
+//!   the background with its own timeout and a cap on calls in flight
+//! - ✅ Status codes and JSON bodies are compared, ignoring volatile fields
+//!   such as timestamps, and differences are logged and kept for review
+//!
+//! The shadow target is either another cluster's REST API or the same
+//! routes served by a shadow [`QueryEngine`]. Mirrored requests carry the
+//! [`SHADOW_HEADER`] and are never mirrored again.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower::ServiceExt;
+use tracing::{debug, warn};
+
+use aerolithdb_query::QueryEngine;
+
+use crate::versioning::split_version;
+
+/// Header marking a mirrored request
+pub const SHADOW_HEADER: &str = "x-aerolith-shadow";
+
+/// Largest request body copied to the shadow target
+const MAX_MIRRORED_BODY: usize = 1024 * 1024;
+
+/// Differences kept for review
+const RECENT_DIFFS: usize = 100;
+
+/// Differing fields reported per response
+const MAX_DIFF_PATHS: usize = 10;
+
+/// Where mirrored requests go.
+#[derive(Clone)]
+pub enum MirrorTarget {
+    /// The REST API of another cluster, by base URL
+    Cluster { base_url: String },
+
+    /// The same routes served by a shadow query engine
+    Engine(Arc<QueryEngine>),
+}
+
+impl std::fmt::Debug for MirrorTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MirrorTarget::Cluster { base_url } => f.debug_struct("Cluster").field("base_url", base_url).finish(),
+            MirrorTarget::Engine(_) => f.write_str("Engine"),
+        }
+    }
+}
+
+/// How much read traffic is mirrored, and where to.
+#[derive(Debug, Clone)]
+pub struct MirrorConfig {
+    /// Fraction of read requests mirrored, from 0.0 to 1.0
+    pub sample_rate: f64,
+
+    /// Shadow cluster or code path receiving the copies
+    pub target: MirrorTarget,
+
+    /// How long a shadow call may take
+    pub timeout: Duration,
+
+    /// Shadow calls in flight at once; further samples are dropped
+    pub max_in_flight: usize,
+
+    /// JSON fields left out of comparisons, at any depth
+    pub ignored_fields: Vec<String>,
+}
+
+impl MirrorConfig {
+    /// Mirror `sample_rate` of reads to `target` with default limits.
+    pub fn new(target: MirrorTarget, sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            target,
+            timeout: Duration::from_secs(5),
+            max_in_flight: 32,
+            ignored_fields: ["timestamp", "created_at", "updated_at", "execution_time_ms"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// Counters of mirrored traffic.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorStats {
+    /// Reads selected for mirroring
+    pub sampled: u64,
+
+    /// Shadow responses matching the primary ones
+    pub matched: u64,
+
+    /// Shadow responses differing from the primary ones
+    pub mismatched: u64,
+
+    /// Shadow calls that failed or timed out
+    pub failed: u64,
+
+    /// Samples dropped because too many shadow calls were in flight
+    pub dropped: u64,
+}
+
+/// A shadow response that differed from the primary one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MirrorDiff {
+    pub at: DateTime<Utc>,
+    pub method: String,
+    pub path: String,
+    pub primary_status: u16,
+    pub shadow_status: u16,
+
+    /// JSON paths whose values differ, `$` for the whole body
+    pub differences: Vec<String>,
+}
+
+/// Mirroring settings, counters and recent differences.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorReport {
+    pub sample_rate: f64,
+    pub target: String,
+    pub stats: MirrorStats,
+    pub recent_diffs: Vec<MirrorDiff>,
+}
+
+enum Shadow {
+    Cluster { client: reqwest::Client, base_url: String },
+    Router(Router),
+}
+
+/// Samples read requests and compares shadow responses with primary ones.
+pub struct TrafficMirror {
+    config: MirrorConfig,
+    shadow: Shadow,
+    seen: AtomicU64,
+    in_flight: AtomicUsize,
+    sampled: AtomicU64,
+    matched: AtomicU64,
+    mismatched: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+    recent: Mutex<VecDeque<MirrorDiff>>,
+}
+
+impl std::fmt::Debug for TrafficMirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrafficMirror").field("config", &self.config).finish_non_exhaustive()
+    }
+}
+
+impl TrafficMirror {
+    /// Mirror as configured; `shadow_routes` serves an engine target.
+    pub fn new(config: MirrorConfig, shadow_routes: Option<Router>) -> Result<Self> {
+        if !(0.0..=1.0).contains(&config.sample_rate) {
+            return Err(anyhow::anyhow!("Mirror sample rate must be between 0 and 1, got {}", config.sample_rate));
+        }
+        let shadow = match (&config.target, shadow_routes) {
+            (MirrorTarget::Cluster { base_url }, _) => Shadow::Cluster {
+                client: reqwest::Client::builder().timeout(config.timeout).build()?,
+                base_url: base_url.trim_end_matches('/').to_string(),
+            },
+            (MirrorTarget::Engine(_), Some(router)) => Shadow::Router(router),
+            (MirrorTarget::Engine(_), None) => {
+                return Err(anyhow::anyhow!("Mirroring to a shadow engine needs its routes"));
+            }
+        };
+        Ok(Self {
+            config,
+            shadow,
+            seen: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            sampled: AtomicU64::new(0),
+            matched: AtomicU64::new(0),
+            mismatched: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_DIFFS)),
+        })
+    }
+
+    pub fn stats(&self) -> MirrorStats {
+        MirrorStats {
+            sampled: self.sampled.load(Ordering::Relaxed),
+            matched: self.matched.load(Ordering::Relaxed),
+            mismatched: self.mismatched.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Settings, counters and the most recent differences, newest first.
+    pub fn report(&self) -> MirrorReport {
+        MirrorReport {
+            sample_rate: self.config.sample_rate,
+            target: match &self.config.target {
+                MirrorTarget::Cluster { base_url } => base_url.clone(),
+                MirrorTarget::Engine(_) => "shadow engine".to_string(),
+            },
+            stats: self.stats(),
+            recent_diffs: self.recent.lock().unwrap().iter().rev().cloned().collect(),
+        }
+    }
+
+    /// Whether the next read is mirrored. Samples are spread evenly: the
+    /// n-th read is mirrored when `n * sample_rate` reaches a new integer.
+    fn sample(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        let rate = self.config.sample_rate;
+        ((n + 1) as f64 * rate).floor() > (n as f64 * rate).floor()
+    }
+
+    /// Reserve a slot for a shadow call, counting the sample as dropped if
+    /// none is free.
+    fn acquire(&self) -> bool {
+        let acquired = self
+            .in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_flight| {
+                (in_flight < self.config.max_in_flight).then_some(in_flight + 1)
+            })
+            .is_ok();
+        if !acquired {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        acquired
+    }
+
+    /// Send a copy of a request to the shadow target, returning its status
+    /// and body.
+    async fn call_shadow(&self, method: &Method, path_and_query: &str, headers: &HeaderMap, body: Bytes) -> Result<(u16, Bytes)> {
+        match &self.shadow {
+            Shadow::Cluster { client, base_url } => {
+                let mut request = client.request(
+                    reqwest::Method::from_bytes(method.as_str().as_bytes())?,
+                    format!("{}{}", base_url, path_and_query),
+                );
+                for (name, value) in headers {
+                    if name != header::HOST && name != header::CONTENT_LENGTH && name != header::CONNECTION {
+                        request = request.header(name.as_str(), value.as_bytes());
+                    }
+                }
+                let response = request.header(SHADOW_HEADER, "1").body(body).send().await?;
+                let status = response.status().as_u16();
+                Ok((status, response.bytes().await?))
+            }
+            Shadow::Router(router) => {
+                let mut request = Request::builder().method(method.clone()).uri(path_and_query);
+                for (name, value) in headers {
+                    request = request.header(name, value);
+                }
+                let request = request.header(SHADOW_HEADER, "1").body(Body::from(body))?;
+                let response = tokio::time::timeout(self.config.timeout, router.clone().oneshot(request))
+                    .await
+                    .map_err(|_| anyhow::anyhow!("shadow call timed out"))??;
+                let status = response.status().as_u16();
+                Ok((status, axum::body::to_bytes(response.into_body(), usize::MAX).await?))
+            }
+        }
+    }
+
+    /// Compare a shadow response with the primary one and record the
+    /// outcome.
+    fn compare(&self, method: &Method, path: &str, primary: (u16, &[u8]), shadow: (u16, &[u8])) {
+        let mut differences = Vec::new();
+        match (serde_json::from_slice::<Value>(primary.1), serde_json::from_slice::<Value>(shadow.1)) {
+            (Ok(primary), Ok(shadow)) => json_differences(&primary, &shadow, "$", &self.config.ignored_fields, &mut differences),
+            _ if primary.1 != shadow.1 => differences.push("$".to_string()),
+            _ => {}
+        }
+        if primary.0 == shadow.0 && differences.is_empty() {
+            self.matched.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        self.mismatched.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Shadow response to {} {} differs: status {} vs {}, fields {:?}",
+            method, path, primary.0, shadow.0, differences
+        );
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_DIFFS {
+            recent.pop_front();
+        }
+        recent.push_back(MirrorDiff {
+            at: Utc::now(),
+            method: method.to_string(),
+            path: path.to_string(),
+            primary_status: primary.0,
+            shadow_status: shadow.0,
+            differences,
+        });
+    }
+}
+
+/// Whether a request only reads data and may be mirrored
+fn is_mirrored_read(method: &Method, path: &str) -> bool {
+    let Some((_, path)) = split_version(path) else {
+        return false;
+    };
+    if path.starts_with("/admin/") || path.starts_with("/internal/") {
+        return false;
+    }
+    match *method {
+        Method::GET => true,
+        Method::POST => path.starts_with("/collections/") && (path.ends_with("/query") || path.ends_with("/aggregate")),
+        _ => false,
+    }
+}
+
+/// Collect the JSON paths at which two values differ, skipping ignored
+/// fields.
+fn json_differences(primary: &Value, shadow: &Value, path: &str, ignored: &[String], out: &mut Vec<String>) {
+    if out.len() >= MAX_DIFF_PATHS {
+        return;
+    }
+    match (primary, shadow) {
+        (Value::Object(primary), Value::Object(shadow)) => {
+            let mut keys: Vec<&String> = primary.keys().chain(shadow.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys.into_iter().filter(|key| !ignored.contains(key)) {
+                let field = format!("{}.{}", path, key);
+                match (primary.get(key), shadow.get(key)) {
+                    (Some(primary), Some(shadow)) => json_differences(primary, shadow, &field, ignored, out),
+                    _ => out.push(field),
+                }
+                if out.len() >= MAX_DIFF_PATHS {
+                    return;
+                }
+            }
+        }
+        (Value::Array(primary), Value::Array(shadow)) if primary.len() == shadow.len() => {
+            for (index, (primary, shadow)) in primary.iter().zip(shadow).enumerate() {
+                json_differences(primary, shadow, &format!("{}[{}]", path, index), ignored, out);
+            }
+        }
+        _ if primary != shadow => out.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// REST middleware answering from the primary path and mirroring sampled
+/// reads to the shadow target in the background.
+pub async fn mirror_middleware(State(mirror): State<Arc<TrafficMirror>>, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    if request.headers().contains_key(SHADOW_HEADER)
+        || !is_mirrored_read(&method, &path)
+        || !mirror.sample()
+        || !mirror.acquire()
+    {
+        return next.run(request).await;
+    }
+    mirror.sampled.fetch_add(1, Ordering::Relaxed);
+
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_MIRRORED_BODY).await {
+        Ok(body) => body,
+        Err(_) => {
+            mirror.in_flight.fetch_sub(1, Ordering::AcqRel);
+            mirror.failed.fetch_add(1, Ordering::Relaxed);
+            return Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::empty())
+                .unwrap_or_default();
+        }
+    };
+    let path_and_query = parts.uri.path_and_query().map_or(path.clone(), |path| path.to_string());
+    let headers = parts.headers.clone();
+
+    let response = next.run(Request::from_parts(parts, Body::from(body.clone()))).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let (parts, primary_body) = response.into_parts();
+    let primary_body = if is_json {
+        match axum::body::to_bytes(primary_body, usize::MAX).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                mirror.in_flight.fetch_sub(1, Ordering::AcqRel);
+                warn!("Failed to read the response to {} {}: {}", method, path, e);
+                return Response::from_parts(parts, Body::empty());
+            }
+        }
+    } else {
+        // Streamed results are not compared
+        mirror.in_flight.fetch_sub(1, Ordering::AcqRel);
+        return Response::from_parts(parts, primary_body);
+    };
+    let primary_status = parts.status.as_u16();
+
+    let shadow_mirror = Arc::clone(&mirror);
+    let primary_copy = primary_body.clone();
+    tokio::spawn(async move {
+        let mirror = shadow_mirror;
+        match mirror.call_shadow(&method, &path_and_query, &headers, body).await {
+            Ok((status, shadow_body)) => {
+                mirror.compare(&method, &path, (primary_status, &primary_copy), (status, &shadow_body))
+            }
+            Err(e) => {
+                mirror.failed.fetch_add(1, Ordering::Relaxed);
+                debug!("Shadow call for {} {} failed: {}", method, path, e);
+            }
+        }
+        mirror.in_flight.fetch_sub(1, Ordering::AcqRel);
+    });
+
+    Response::from_parts(parts, Body::from(primary_body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::{get, post};
+    use serde_json::json;
+
+    fn mirror(sample_rate: f64, shadow: Router) -> Arc<TrafficMirror> {
+        // Serve the shadow in-process instead of over HTTP
+        let config = MirrorConfig::new(MirrorTarget::Cluster { base_url: String::new() }, sample_rate);
+        let mut mirror = TrafficMirror::new(config, None).unwrap();
+        mirror.shadow = Shadow::Router(shadow);
+        Arc::new(mirror)
+    }
+
+    fn document(name: &'static str) -> Router {
+        Router::new()
+            .route(
+                "/api/v1/collections/:collection/documents/:id",
+                get(move || async move { axum::Json(json!({ "id": "1", "data": { "name": name }, "updated_at": Utc::now() })) }),
+            )
+            .route("/api/v1/collections/:collection/documents", post(|| async { StatusCode::CREATED }))
+    }
+
+    async fn settled(mirror: &TrafficMirror) -> MirrorStats {
+        for _ in 0..200 {
+            if mirror.in_flight.load(Ordering::Acquire) == 0 {
+                return mirror.stats();
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("shadow calls never finished");
+    }
+
+    #[test]
+    fn test_only_reads_are_mirrored() {
+        assert!(is_mirrored_read(&Method::GET, "/api/v1/collections/users/documents/1"));
+        assert!(is_mirrored_read(&Method::POST, "/api/v1/collections/users/query"));
+        assert!(is_mirrored_read(&Method::POST, "/api/v1/collections/users/aggregate"));
+        assert!(!is_mirrored_read(&Method::POST, "/api/v1/collections/users/documents"));
+        assert!(!is_mirrored_read(&Method::DELETE, "/api/v1/collections/users/documents/1"));
+        assert!(!is_mirrored_read(&Method::GET, "/api/v1/admin/jobs"));
+        assert!(is_mirrored_read(&Method::GET, "/api/v2/collections/users/documents/1"));
+        assert!(!is_mirrored_read(&Method::GET, "/health"));
+        assert!(!is_mirrored_read(&Method::GET, "/health"));
+    }
+
+    #[test]
+    fn test_json_differences_skip_ignored_fields() {
+        let ignored = vec!["updated_at".to_string()];
+        let mut out = Vec::new();
+        json_differences(
+            &json!({ "total": 2, "documents": [{ "id": "a", "updated_at": 1 }, { "id": "b" }] }),
+            &json!({ "total": 2, "documents": [{ "id": "a", "updated_at": 2 }, { "id": "c" }], "extra": true }),
+            "$",
+            &ignored,
+            &mut out,
+        );
+        assert_eq!(out, vec!["$.documents[1].id".to_string(), "$.extra".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_mirrors_sampled_reads_and_records_differences() {
+        let mirror = mirror(0.5, document("shadow"));
+        let app = document("primary").layer(axum::middleware::from_fn_with_state(Arc::clone(&mirror), mirror_middleware));
+
+        for _ in 0..4 {
+            let request = Request::get("/api/v1/collections/users/documents/1").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+            assert_eq!(body["data"]["name"], "primary");
+        }
+        let request = Request::post("/api/v1/collections/users/documents").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::CREATED);
+
+        let stats = settled(&mirror).await;
+        assert_eq!(stats, MirrorStats { sampled: 2, mismatched: 2, ..Default::default() });
+        let report = mirror.report();
+        assert_eq!(report.recent_diffs[0].differences, vec!["$.data.name".to_string()]);
+
+        // Identical responses match despite their timestamps
+        let mirror = self::mirror(1.0, document("primary"));
+        let app = document("primary").layer(axum::middleware::from_fn_with_state(Arc::clone(&mirror), mirror_middleware));
+        let request = Request::get("/api/v1/collections/users/documents/1").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap();
+        assert_eq!(settled(&mirror).await, MirrorStats { sampled: 1, matched: 1, ..Default::default() });
+    }
+}
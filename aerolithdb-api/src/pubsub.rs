@@ -0,0 +1,311 @@
+//! # Application Pub/Sub Messaging
+//!
+//! Lightweight publish/subscribe for application events, complementing the
+//! document change streams. Clients publish messages to named channels and
+//! receive them over WebSocket or gRPC without standing up a separate broker.
+//!
+//! ## Features
+//! - ✅ Named channels created on first use
+//! - ✅ Fan-out to every subscriber of a channel with backpressure via bounded buffers
+//! - ✅ Optional retention of the last N messages per channel, persisted to storage
+//!   so late subscribers and restarted nodes can replay recent history
+//!
+//! Live fan-out reaches subscribers connected to this node; retained history
+//! is stored in a system collection and is visible from every node.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, info, warn};
+
+use aerolithdb_query::QueryEngine;
+
+/// System collection holding channel options and retained messages
+pub const PUBSUB_COLLECTION: &str = "_pubsub_channels";
+
+/// Buffered messages per subscriber before slow subscribers start lagging
+const CHANNEL_BUFFER: usize = 1024;
+
+/// Longest accepted channel name
+const MAX_CHANNEL_NAME_LEN: usize = 256;
+
+/// Per-channel behaviour
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelOptions {
+    /// Number of most recent messages to keep and replay to new subscribers (0 disables persistence)
+    pub retain_last: usize,
+}
+
+/// A message published to a channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PubSubMessage {
+    pub id: String,
+    pub channel: String,
+    /// Monotonic position within the channel
+    pub sequence: u64,
+    pub payload: serde_json::Value,
+    /// Authenticated identity of the publisher, when known
+    pub publisher: Option<String>,
+    pub published_at: DateTime<Utc>,
+}
+
+/// Channel statistics for monitoring
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStats {
+    pub channel: String,
+    pub subscribers: usize,
+    pub published: u64,
+    pub retained: usize,
+    pub retain_last: usize,
+}
+
+/// Persisted form of a channel
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredChannel {
+    options: ChannelOptions,
+    next_sequence: u64,
+    messages: Vec<PubSubMessage>,
+}
+
+#[derive(Debug)]
+struct Channel {
+    sender: broadcast::Sender<PubSubMessage>,
+    options: ChannelOptions,
+    retained: VecDeque<PubSubMessage>,
+    next_sequence: u64,
+    published: u64,
+}
+
+impl Channel {
+    fn new(options: ChannelOptions) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_BUFFER);
+        Self {
+            sender,
+            options,
+            retained: VecDeque::new(),
+            next_sequence: 1,
+            published: 0,
+        }
+    }
+
+    fn from_stored(stored: StoredChannel) -> Self {
+        let mut channel = Self::new(stored.options);
+        channel.next_sequence = stored.next_sequence;
+        channel.retained = stored.messages.into();
+        channel.trim();
+        channel
+    }
+
+    /// Keep only the configured number of most recent messages
+    fn retain(&mut self, message: PubSubMessage) {
+        if self.options.retain_last > 0 {
+            self.retained.push_back(message);
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.retained.len() > self.options.retain_last {
+            self.retained.pop_front();
+        }
+    }
+
+    fn to_stored(&self) -> StoredChannel {
+        StoredChannel {
+            options: self.options.clone(),
+            next_sequence: self.next_sequence,
+            messages: self.retained.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Routes published messages to channel subscribers
+#[derive(Debug)]
+pub struct PubSubBroker {
+    query: Arc<QueryEngine>,
+    channels: RwLock<HashMap<String, Channel>>,
+}
+
+impl PubSubBroker {
+    pub fn new(query: Arc<QueryEngine>) -> Self {
+        info!("Initializing pub/sub broker");
+        Self {
+            query,
+            channels: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Set options for a channel, creating it if needed
+    pub async fn configure_channel(&self, channel: &str, options: ChannelOptions) -> Result<()> {
+        validate_channel_name(channel)?;
+        self.ensure_loaded(channel).await?;
+
+        let mut channels = self.channels.write().await;
+        let entry = channels.get_mut(channel).expect("channel loaded above");
+        entry.options = options;
+        entry.trim();
+        self.persist(channel, entry).await?;
+
+        debug!("Configured channel {} to retain {} messages", channel, entry.options.retain_last);
+        Ok(())
+    }
+
+    /// Publish a message to every subscriber of a channel
+    pub async fn publish(
+        &self,
+        channel: &str,
+        payload: serde_json::Value,
+        publisher: Option<String>,
+    ) -> Result<PubSubMessage> {
+        validate_channel_name(channel)?;
+        self.ensure_loaded(channel).await?;
+
+        let mut channels = self.channels.write().await;
+        let entry = channels.get_mut(channel).expect("channel loaded above");
+
+        let message = PubSubMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            channel: channel.to_string(),
+            sequence: entry.next_sequence,
+            payload,
+            publisher,
+            published_at: Utc::now(),
+        };
+        entry.next_sequence += 1;
+        entry.published += 1;
+
+        if entry.options.retain_last > 0 {
+            entry.retain(message.clone());
+            if let Err(e) = self.persist(channel, entry).await {
+                warn!("Failed to persist retained messages for channel {}: {}", channel, e);
+            }
+        }
+
+        // No receivers is not an error: nobody is listening right now
+        let receivers = entry.sender.send(message.clone()).unwrap_or(0);
+        debug!("Published message {} to channel {} ({} receivers)", message.id, channel, receivers);
+
+        Ok(message)
+    }
+
+    /// Subscribe to a channel, returning retained history and a live receiver.
+    ///
+    /// Both are taken under the channel lock, so every message is delivered
+    /// exactly once: either in the history or through the receiver.
+    pub async fn subscribe(
+        &self,
+        channel: &str,
+    ) -> Result<(Vec<PubSubMessage>, broadcast::Receiver<PubSubMessage>)> {
+        validate_channel_name(channel)?;
+        self.ensure_loaded(channel).await?;
+
+        let channels = self.channels.read().await;
+        let entry = channels.get(channel).expect("channel loaded above");
+        let receiver = entry.sender.subscribe();
+        let history = entry.retained.iter().cloned().collect();
+        Ok((history, receiver))
+    }
+
+    /// Retained messages for a channel, oldest first
+    pub async fn history(&self, channel: &str) -> Result<Vec<PubSubMessage>> {
+        Ok(self.subscribe(channel).await?.0)
+    }
+
+    /// Statistics for every channel known to this node
+    pub async fn list_channels(&self) -> Vec<ChannelStats> {
+        let channels = self.channels.read().await;
+        let mut stats: Vec<ChannelStats> = channels
+            .iter()
+            .map(|(name, channel)| ChannelStats {
+                channel: name.clone(),
+                subscribers: channel.sender.receiver_count(),
+                published: channel.published,
+                retained: channel.retained.len(),
+                retain_last: channel.options.retain_last,
+            })
+            .collect();
+        stats.sort_by(|a, b| a.channel.cmp(&b.channel));
+        stats
+    }
+
+    /// Load a channel from storage, or create it, if it is not yet in memory
+    async fn ensure_loaded(&self, channel: &str) -> Result<()> {
+        if self.channels.read().await.contains_key(channel) {
+            return Ok(());
+        }
+
+        let stored = match self.query.get_document(PUBSUB_COLLECTION, channel).await {
+            Ok(document) => Some(serde_json::from_value::<StoredChannel>(document)?),
+            Err(_) => None,
+        };
+
+        let mut channels = self.channels.write().await;
+        channels.entry(channel.to_string()).or_insert_with(|| match stored {
+            Some(stored) => Channel::from_stored(stored),
+            None => Channel::new(ChannelOptions::default()),
+        });
+        Ok(())
+    }
+
+    async fn persist(&self, name: &str, channel: &Channel) -> Result<()> {
+        self.query
+            .store_document(PUBSUB_COLLECTION, name, &serde_json::to_value(channel.to_stored())?)
+            .await
+    }
+}
+
+/// Channel names double as storage keys, so keep them to a safe character set
+fn validate_channel_name(channel: &str) -> Result<()> {
+    if channel.is_empty() || channel.len() > MAX_CHANNEL_NAME_LEN {
+        return Err(anyhow::anyhow!(
+            "Channel name must be between 1 and {} characters", MAX_CHANNEL_NAME_LEN
+        ));
+    }
+    if !channel.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')) {
+        return Err(anyhow::anyhow!(
+            "Channel name '{}' may only contain letters, digits, '-', '_', '.' and ':'", channel
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(sequence: u64) -> PubSubMessage {
+        PubSubMessage {
+            id: sequence.to_string(),
+            channel: "chat".to_string(),
+            sequence,
+            payload: serde_json::json!({}),
+            publisher: None,
+            published_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_channel_name_validation() {
+        assert!(validate_channel_name("room:42.typing").is_ok());
+        assert!(validate_channel_name("").is_err());
+        assert!(validate_channel_name("../etc").is_err());
+        assert!(validate_channel_name(&"a".repeat(MAX_CHANNEL_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_retains_last_n_messages() {
+        let mut channel = Channel::new(ChannelOptions { retain_last: 2 });
+        for sequence in 1..=3 {
+            channel.retain(message(sequence));
+        }
+        let retained: Vec<u64> = channel.retained.iter().map(|m| m.sequence).collect();
+        assert_eq!(retained, vec![2, 3]);
+
+        let mut unretained = Channel::new(ChannelOptions::default());
+        unretained.retain(message(1));
+        assert!(unretained.retained.is_empty());
+    }
+}
@@ -1,9 +1,10 @@
 use anyhow::Result;
 use std::sync::Arc;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
     routing::{delete, get, post, put},
     Router,
 };
@@ -11,21 +12,83 @@ use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 use tower_http::cors::CorsLayer;
 
-use aerolithdb_query::QueryEngine;
-use aerolithdb_security::SecurityFramework;
+use aerolithdb_consensus::{
+    features, ConsensusEngine, FeatureFlagRegistry, FeatureFlagStatus, FlagScope, Lease, LeaseOutcome, QuarantineRecord,
+};
+use aerolithdb_query::{
+    ApplyReport, ChangeBatch, Collection, CollectionConfig, CollectionInfo, LegalHold, LegalHoldEvent, PullRequest, PushRequest, QueryEngine, SyncPeer,
+    BackupKind, BackupManifest, Job, JobProgress, JobState, ResidencyViolation, RestoreReport, ScrubReport, TierMigrationMetrics, TierMigrationReport,
+    TierRule, WormPolicy, CollectionStatistics, QueryPlan, StorageUsage, ClusterNode, MembershipChange, RebalancePlan, TransferredDocument,
+    TRANSFER_PATH, PEER_QUERY_PATH, CACHE_PEER_PATH, CachePeerRequest, CachePeerResponse, PartialQueryResult, CanaryConfig, CanaryReport, PlannerMode, CompressionDictionary, DocumentSortKey, ListOptions, ListedDocument, StorageTier, IndexDefinition,
+    ListCursor, WriteOptions, AbortReason, QueryAborted, QueryLimits, RunningQuery, CollectionQueryStats, SlowQuery, ExpensiveQuery, HeavyHitter,
+    CollectionSchema, SchemaValidationError, SchemaViolation, ValidationMode, Projection, ReadConsistency,
+};
+use aerolithdb_security::{
+    redact, scan_plugins, AccessList, AccessLists, AccessRule, AdmissionDecision, CaInfo, CertificateAuthority,
+    CertificateDecision, CertificateRequest, DeniedJoinAttempts, EnrolledNode, IdentityKind, JoinCandidate,
+    PendingCertificateRequest, PendingJoin, PostureEnvironment, PostureReport, SecurityFramework, SecurityStatus,
+};
 
 use super::RESTAPIConfig;
+use super::compression::{self, CompressionConfig};
+use super::formats::{self, ResultFormat};
+use super::sandbox::{sandbox_middleware, SandboxGuard, SANDBOX_SWEEP_INTERVAL};
+use super::mirror::{mirror_middleware, MirrorReport, MirrorTarget, TrafficMirror};
+use super::versioning::{split_version, versioning_middleware, ApiVersions, VersionReport};
+use super::access::{access_middleware, AccessConfig, AccessPolicy};
+use super::sse::{self, EventStream};
+use super::cdn::{cdn_middleware, CdnInvalidator, CdnStats};
+use super::websocket::RealtimeAPI;
+use super::telemetry::ResourceMonitor;
+use super::metrics_history::{MetricPoint, MetricsHistory, MetricsHistoryConfig, MetricsReading, Resolution};
 
 #[derive(Debug, Clone)]
 pub struct RESTAPIv1 {
     config: RESTAPIConfig,
     query: Arc<QueryEngine>,
     security: Arc<SecurityFramework>,
+    consensus: Arc<ConsensusEngine>,
+    sandbox: Option<Arc<SandboxGuard>>,
+    /// Shadow traffic mirroring, if enabled
+    mirror: Option<Arc<TrafficMirror>>,
+    /// Deprecations and usage of the API versions
+    versions: Arc<ApiVersions>,
+    /// Redacted effective configuration published by the node, if any
+    effective_config: Arc<tokio::sync::RwLock<Option<serde_json::Value>>>,
+    /// Compression of response bodies
+    compression: CompressionConfig,
+    /// Authentication, tenancy and rate limits, shared with the gRPC API
+    access: Arc<AccessPolicy>,
+    /// Realtime API whose subscriptions are also served as Server-Sent Events
+    realtime: Option<Arc<RealtimeAPI>>,
+    /// Purges CDN caches after changes, if configured
+    cdn: Option<Arc<CdnInvalidator>>,
+    /// Resource usage of the node's process
+    resources: Arc<ResourceMonitor>,
+    /// Downsampled history of the node's key metrics
+    metrics_history: Arc<MetricsHistory>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DocumentRequest {
     pub data: serde_json::Value,
+    /// Seconds until the document expires; without it a document keeps
+    /// its current expiry
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+    /// Store the document without compressing it, for already-compressed
+    /// or incompressible payloads
+    #[serde(default)]
+    pub skip_compression: bool,
+}
+
+impl DocumentRequest {
+    fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            ttl: self.ttl_seconds.map(std::time::Duration::from_secs),
+            skip_compression: self.skip_compression,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,6 +98,8 @@ pub struct DocumentResponse {
     pub version: u64,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,6 +108,119 @@ pub struct QueryRequest {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
     pub sort: Option<serde_json::Value>,
+    /// `next_cursor` of the previous page, to continue after it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Timeout, scan and memory limits, within the node's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<QueryLimits>,
+    /// Fields to return, e.g. `{"title": 1, "author.name": 1}` or `{"body": 0}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<serde_json::Value>,
+    /// Query the collection as it was at this time; versioned collections only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// An aggregation pipeline: `$match`, `$group`, `$sort`, `$limit` and
+/// `$project` stages run in order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AggregateRequest {
+    pub pipeline: Vec<serde_json::Value>,
+    /// Timeout, scan and memory limits, within the node's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<QueryLimits>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AggregateResponse {
+    pub documents: Vec<serde_json::Value>,
+    pub count: usize,
+    /// Documents read from the collection
+    pub scanned: usize,
+    pub execution_time_ms: u64,
+}
+
+/// Query string of endpoints that can answer in several result formats
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResultFormatParams {
+    /// `json` (default), `ndjson` or `csv`; overrides the `Accept` header
+    pub format: Option<String>,
+}
+
+/// Query string of the document read endpoint
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DocumentFieldsParams {
+    /// Comma-separated field paths to return, e.g. `title,author.name`
+    pub fields: Option<String>,
+    /// Comma-separated field paths to leave out
+    pub exclude: Option<String>,
+}
+
+impl DocumentFieldsParams {
+    /// The projection the parameters ask for, if any.
+    fn projection(&self) -> anyhow::Result<Option<Projection>> {
+        match (&self.fields, &self.exclude) {
+            (Some(_), Some(_)) => Err(anyhow::anyhow!("Invalid projection: cannot mix fields and exclude")),
+            (Some(fields), None) => Projection::include(fields.split(',')).map(Some),
+            (None, Some(exclude)) => Projection::exclude(exclude.split(',')).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Query string of the document read and query endpoints choosing how
+/// current their answer has to be
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReadConsistencyParams {
+    /// `local` (default), `bounded_staleness` or `linearizable`; stronger
+    /// levels are only served for strongly consistent collections
+    pub consistency: Option<String>,
+    /// Maximum lag of a `bounded_staleness` read, in milliseconds
+    pub max_lag_ms: Option<u64>,
+}
+
+impl ReadConsistencyParams {
+    /// The consistency the parameters ask for.
+    fn read_consistency(&self) -> anyhow::Result<ReadConsistency> {
+        ReadConsistency::parse(self.consistency.as_deref().unwrap_or("local"), self.max_lag_ms)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsOfParams {
+    /// Read the document as it was at this time, e.g. `2024-05-01T12:00:00Z`;
+    /// versioned collections only
+    pub as_of: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Status of a read of a past state that version history cannot answer, if
+/// that is why it failed.
+fn as_of_status(e: &anyhow::Error) -> Option<StatusCode> {
+    let message = e.to_string();
+    if message.contains("does not keep version history") {
+        Some(StatusCode::BAD_REQUEST)
+    } else if message.contains("is no longer retained") || message.contains("is not recorded") {
+        Some(StatusCode::GONE)
+    } else {
+        None
+    }
+}
+
+/// Status of a read that could not be served at the consistency it asked
+/// for, if that is why it failed.
+fn read_consistency_status(e: &anyhow::Error) -> Option<StatusCode> {
+    let message = e.to_string();
+    if message.starts_with("Invalid read consistency") {
+        Some(StatusCode::BAD_REQUEST)
+    } else if message.starts_with("Stale read")
+        || message.starts_with("Not the Raft leader")
+        || message.contains("did not reach quorum")
+    {
+        Some(StatusCode::SERVICE_UNAVAILABLE)
+    } else {
+        None
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,6 +229,431 @@ pub struct QueryResponse {
     pub total: usize,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
+    /// Pass as `cursor` to get the next page; absent on the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Set when some cluster members did not answer, so matches may be missing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    /// Cluster members that did not answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unreachable_nodes: Vec<String>,
+}
+
+/// Query string of the document listing endpoint
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListDocumentsParams {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    /// `id` (default), `created_at`, `updated_at` or `size`
+    pub sort: Option<DocumentSortKey>,
+    /// `asc` (default) or `desc`
+    pub order: Option<String>,
+    /// `next_cursor` of the previous page, to continue after it
+    pub cursor: Option<String>,
+    /// Only documents on this tier: `hot`, `warm`, `cold` or `archive`
+    pub tier: Option<String>,
+    /// Only documents created at least this many seconds ago
+    pub min_age_seconds: Option<u64>,
+    /// Only documents created at most this many seconds ago
+    pub max_age_seconds: Option<u64>,
+    /// List each document's metadata instead of its contents
+    #[serde(default)]
+    pub metadata: bool,
+    /// `json` (default), `ndjson` or `csv`; overrides the `Accept` header
+    pub format: Option<String>,
+}
+
+impl ListDocumentsParams {
+    fn list_options(&self) -> Result<ListOptions, StatusCode> {
+        let descending = match self.order.as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return Err(StatusCode::BAD_REQUEST),
+        };
+        let tier = match self.tier.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            None => None,
+            Some("hot") => Some(StorageTier::Hot),
+            Some("warm") => Some(StorageTier::Warm),
+            Some("cold") => Some(StorageTier::Cold),
+            Some("archive") => Some(StorageTier::Archive),
+            Some(_) => return Err(StatusCode::BAD_REQUEST),
+        };
+        let mut options = ListOptions {
+            limit: self.limit,
+            offset: self.offset,
+            sort: self.sort.unwrap_or_default(),
+            descending,
+            tier,
+            min_age: self.min_age_seconds.map(std::time::Duration::from_secs),
+            max_age: self.max_age_seconds.map(std::time::Duration::from_secs),
+            after: None,
+        };
+        if let Some(cursor) = &self.cursor {
+            let cursor = ListCursor::decode(cursor, &options).map_err(|_| StatusCode::BAD_REQUEST)?;
+            options.after = Some(cursor.into_document());
+        }
+        Ok(options)
+    }
+}
+
+/// Page of document metadata from the listing endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentListResponse {
+    pub documents: Vec<ListedDocument>,
+    pub total: usize,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    /// Pass as `cursor` to get the next page; absent on the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateSessionRequest {
+    pub owner: String,
+    /// Seconds without a heartbeat before the session and its ephemeral documents expire
+    pub ttl_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcquireLockRequest {
+    pub holder: String,
+    /// Seconds until the lease expires unless renewed
+    pub ttl_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LockTokenRequest {
+    pub holder: String,
+    /// Fencing token returned when the lease was acquired
+    pub fencing_token: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaceLegalHoldRequest {
+    pub collection: String,
+    /// Documents to hold; ignored when `filter` is given
+    #[serde(default)]
+    pub document_ids: Vec<String>,
+    /// Hold the documents currently matching this filter
+    pub filter: Option<serde_json::Value>,
+    pub reason: String,
+    pub placed_by: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReleaseLegalHoldRequest {
+    pub released_by: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnableWormRequest {
+    /// Seconds documents stay immutable after they are first written
+    pub retention_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateCollectionRequest {
+    pub name: String,
+    /// Settings overriding the storage defaults
+    #[serde(default)]
+    pub config: CollectionConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetResidencyRequest {
+    /// Residency tag naming the regions copies may be placed in
+    pub residency: String,
+}
+
+/// Query string of a tier migration run
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TierMigrationParams {
+    /// Only report the migrations the rules call for
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A node joining the cluster
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinNodeRequest {
+    /// Node id in the hash ring
+    pub id: String,
+
+    /// Base URL of the node's REST API
+    pub address: Option<String>,
+
+    /// Public key identifying the node, matched against the admission lists
+    #[serde(default)]
+    pub public_key: Option<String>,
+
+    /// Signature of the node id by `public_key`, proving the node holds it
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// An identity added to the allowlist or denylist
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessRuleRequest {
+    pub kind: IdentityKind,
+    pub value: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Query string of the query statistics endpoint
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QueryStatsParams {
+    /// Only this collection's statistics
+    pub collection: Option<String>,
+}
+
+/// Query string of the metrics history
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MetricsHistoryParams {
+    /// `minute` (the default) or `hour` points
+    #[serde(default)]
+    pub resolution: Resolution,
+
+    /// Only points starting at or after this time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Downsampled metrics of this node, oldest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricsHistoryResponse {
+    pub resolution: Resolution,
+    pub points: Vec<MetricPoint>,
+}
+
+/// Where a running operation comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationSource {
+    /// A query or aggregation, stopped at its next document when killed
+    Query,
+    /// A background job, stopped at its next checkpoint when killed
+    Job,
+}
+
+/// A query or background job in progress, as listed for on-call operators
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningOperation {
+    pub id: String,
+    pub source: OperationSource,
+
+    /// `query` or `aggregate` for queries, e.g. `backup` for jobs
+    pub kind: String,
+
+    /// Collection a query reads
+    pub collection: Option<String>,
+
+    /// Tenant a job runs for
+    pub tenant: Option<String>,
+
+    /// `running` or `queued`, or the state a job ended in once killed
+    pub state: String,
+
+    /// When it started, unset for jobs still queued
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Time since it started, or since a queued job was submitted
+    pub elapsed_ms: u64,
+
+    /// Documents a query read so far
+    pub documents_scanned: Option<usize>,
+
+    /// Memory a query holds in bytes
+    pub memory_bytes: Option<usize>,
+
+    /// Progress a job reported
+    pub progress: Option<JobProgress>,
+
+    /// Whether it was killed and is yet to stop
+    pub cancelled: bool,
+}
+
+impl From<RunningQuery> for RunningOperation {
+    fn from(query: RunningQuery) -> Self {
+        Self {
+            id: query.id,
+            source: OperationSource::Query,
+            kind: query.kind,
+            collection: Some(query.collection),
+            tenant: None,
+            state: "running".to_string(),
+            started_at: Some(query.started_at),
+            elapsed_ms: query.elapsed_ms,
+            documents_scanned: Some(query.documents_scanned),
+            memory_bytes: Some(query.memory_bytes),
+            progress: None,
+            cancelled: query.cancelled,
+        }
+    }
+}
+
+impl From<Job> for RunningOperation {
+    fn from(job: Job) -> Self {
+        let started = job.started_at.unwrap_or(job.created_at);
+        Self {
+            id: job.id,
+            source: OperationSource::Job,
+            kind: job.kind,
+            collection: None,
+            tenant: job.tenant,
+            state: match job.state {
+                JobState::Queued => "queued",
+                JobState::Running => "running",
+                JobState::Succeeded => "succeeded",
+                JobState::Failed => "failed",
+                JobState::Cancelled => "cancelled",
+            }
+            .to_string(),
+            started_at: job.started_at,
+            elapsed_ms: (chrono::Utc::now() - started).num_milliseconds().max(0) as u64,
+            documents_scanned: None,
+            memory_bytes: None,
+            progress: Some(job.progress),
+            cancelled: job.cancel_requested,
+        }
+    }
+}
+
+/// Query string of the heavy-hitters report
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HeavyHittersParams {
+    /// Entries of each list, 10 by default
+    pub limit: Option<usize>,
+
+    /// Seconds of the sliding window, 15 minutes by default
+    pub window_secs: Option<u64>,
+}
+
+/// The hottest documents and collections and the most expensive query
+/// shapes of a window, heaviest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeavyHittersReport {
+    pub window_secs: u64,
+
+    /// Estimated reads by `collection:document_id`
+    pub documents: Vec<HeavyHitter>,
+
+    /// Estimated reads by collection
+    pub collections: Vec<HeavyHitter>,
+    pub queries: Vec<ExpensiveQuery>,
+}
+
+/// Query string of the slow-query log
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SlowQueryParams {
+    /// Only slow queries on this collection
+    pub collection: Option<String>,
+
+    /// Most recent slow queries returned
+    pub limit: Option<usize>,
+}
+
+/// A canary comparing queries planned another way with the current path
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartCanaryRequest {
+    /// Planner mode the candidate path uses
+    pub planner: PlannerMode,
+
+    #[serde(flatten)]
+    pub config: CanaryConfig,
+}
+
+/// Query string of an integrity scrub
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScrubParams {
+    /// Replace corrupt copies with intact ones
+    #[serde(default = "default_scrub_repair")]
+    pub repair: bool,
+
+    /// Run as a background job and answer with the job
+    #[serde(default)]
+    pub background: bool,
+}
+
+fn default_scrub_repair() -> bool {
+    true
+}
+
+/// Query string of a backup
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CreateBackupParams {
+    /// Only back up documents changed since the previous backup
+    #[serde(default)]
+    pub incremental: bool,
+
+    /// Only back up the collections whose names start with this prefix
+    pub scope: Option<String>,
+
+    /// Run as a background job and answer with the job
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// Query string of a restore by id
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RestoreIntoParams {
+    /// Copy the documents into this namespace instead of restoring them in
+    /// place
+    pub namespace: Option<String>,
+
+    /// Run as a background job and answer with the job
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// Query string of a job listing
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JobListParams {
+    /// Only jobs run for this tenant
+    pub tenant: Option<String>,
+
+    /// Only jobs in this state
+    pub state: Option<JobState>,
+}
+
+/// Query string of a change stream served as Server-Sent Events
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChangeStreamParams {
+    /// Only changes to documents matching this JSON filter
+    pub filter: Option<String>,
+
+    /// Resume from this token; a `Last-Event-ID` header takes precedence
+    pub resume_token: Option<String>,
+}
+
+/// Query string of a job's progress stream
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JobEventParams {
+    /// Resume after this event ID; a `Last-Event-ID` header takes precedence
+    pub resume_token: Option<String>,
+}
+
+/// Query string of a point-in-time restore
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreParams {
+    /// Restore the latest backup taken at or before this time
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetFeatureRequest {
+    /// New value, or null to clear the setting at this scope
+    pub enabled: Option<bool>,
+    /// `node` applies immediately on this node; `cluster` is proposed through consensus
+    pub scope: FlagScope,
+}
+
+/// An operator's decision to reinstate a quarantined node
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReinstateNodeRequest {
+    pub operator: String,
+    pub note: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,23 +668,140 @@ impl RESTAPIv1 {
         config: &RESTAPIConfig,
         query: Arc<QueryEngine>,
         security: Arc<SecurityFramework>,
+        consensus: Arc<ConsensusEngine>,
     ) -> Result<Self> {
         info!("Initializing REST API v1");
+
+        let sandbox = config.sandbox.clone()
+            .map(|sandbox_config| Arc::new(SandboxGuard::new(sandbox_config, Arc::clone(&query))));
+
+        let versions = Arc::new(ApiVersions::new(config.versioning.clone())?);
+        let access = Arc::new(AccessPolicy::new(AccessConfig::default())?);
+        let resources = Arc::new(ResourceMonitor::new());
+        let metrics_history = Arc::new(MetricsHistory::new(Arc::clone(&query), MetricsHistoryConfig::default()));
+
+        let mirror = match config.mirror.clone() {
+            Some(mirror_config) => {
+                // A shadow engine is served through the same routes, without mirroring
+                let shadow_routes = match &mirror_config.target {
+                    MirrorTarget::Engine(engine) => Some(routes().with_state(AppState {
+                        query: Arc::clone(engine),
+                        security: Arc::clone(&security),
+                        consensus: Arc::clone(&consensus),
+                        effective_config: Arc::new(tokio::sync::RwLock::new(None)),
+                        mirror: None,
+                        versions: Arc::clone(&versions),
+                        access: Arc::clone(&access),
+                        realtime: None,
+                        cdn: None,
+                        resources: Arc::clone(&resources),
+                        metrics_history: Arc::clone(&metrics_history),
+                    })),
+                    MirrorTarget::Cluster { .. } => None,
+                };
+                info!("Mirroring {} of read traffic to {:?}", mirror_config.sample_rate, mirror_config.target);
+                Some(Arc::new(TrafficMirror::new(mirror_config, shadow_routes)?))
+            }
+            None => None,
+        };
+
         Ok(Self {
             config: config.clone(),
             query,
             security,
+            consensus,
+            sandbox,
+            mirror,
+            versions,
+            effective_config: Arc::new(tokio::sync::RwLock::new(None)),
+            compression: CompressionConfig::default(),
+            access,
+            realtime: None,
+            cdn: None,
+            resources,
+            metrics_history,
         })
     }
 
+    /// Replace the default response compression settings.
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Replace the default access policy, which admits every request
+    /// without limits.
+    pub fn with_access(mut self, access: Arc<AccessPolicy>) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Serve change streams of the realtime API as Server-Sent Events, for
+    /// clients that cannot open a WebSocket.
+    pub fn with_realtime(mut self, realtime: Arc<RealtimeAPI>) -> Self {
+        self.realtime = Some(realtime);
+        self
+    }
+
+    /// Purge CDN caches of the documents and collections changed through
+    /// this API.
+    pub fn with_cdn(mut self, cdn: Arc<CdnInvalidator>) -> Self {
+        self.cdn = Some(cdn);
+        self
+    }
+
+    /// Replace the default sampling and retention of the metrics history.
+    pub fn with_metrics_history(mut self, config: MetricsHistoryConfig) -> Self {
+        self.metrics_history = Arc::new(MetricsHistory::new(Arc::clone(&self.query), config));
+        self
+    }
+
+    /// Publish the node's effective configuration for the admin config endpoint.
+    /// The export must already have its secrets redacted.
+    pub async fn publish_effective_config(&self, export: serde_json::Value) {
+        *self.effective_config.write().await = Some(export);
+    }
+
     pub async fn start(&self) -> Result<()> {
         info!("Starting REST API v1 on {}:{}", self.config.bind_address, self.config.port);
 
         let app = self.create_router().await;
+
+        if let Some(sandbox) = &self.sandbox {
+            let sandbox = Arc::clone(sandbox);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SANDBOX_SWEEP_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = sandbox.expire_collections().await {
+                        warn!("Sandbox collection expiry failed: {}", e);
+                    }
+                }
+            });
+        }
+
+        if self.metrics_history.config().enabled {
+            let history = Arc::clone(&self.metrics_history);
+            let (query, resources, access) = (Arc::clone(&self.query), Arc::clone(&self.resources), Arc::clone(&self.access));
+            tokio::spawn(async move {
+                if let Err(e) = history.load().await {
+                    warn!("Failed to load the metrics history: {}", e);
+                }
+                let mut interval = tokio::time::interval(history.config().sample_interval);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = history.record(metrics_reading(&query, &resources, &access)).await {
+                        warn!("Failed to record metrics history: {}", e);
+                    }
+                }
+            });
+        }
         
         let addr = format!("{}:{}", self.config.bind_address, self.config.port);
         let listener = tokio::net::TcpListener::bind(&addr).await?;        tokio::spawn(async move {
-            if let Err(e) = axum::serve(listener, app).await {
+            // Connect info gives the sandbox rate limiter each client's address
+            let service = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+            if let Err(e) = axum::serve(listener, service).await {
                 warn!("REST API server error: {}", e);
             }
         });
@@ -100,83 +820,444 @@ impl RESTAPIv1 {
         let state = AppState {
             query: Arc::clone(&self.query),
             security: Arc::clone(&self.security),
+            consensus: Arc::clone(&self.consensus),
+            effective_config: Arc::clone(&self.effective_config),
+            mirror: self.mirror.clone(),
+            versions: Arc::clone(&self.versions),
+            access: Arc::clone(&self.access),
+            realtime: self.realtime.clone(),
+            cdn: self.cdn.clone(),
+            resources: Arc::clone(&self.resources),
+            metrics_history: Arc::clone(&self.metrics_history),
         };
         
-        let mut router = Router::new()
-            .route("/health", get(health_check))
-            .route("/api/v1/collections/:collection/documents", post(create_document))
-            .route("/api/v1/collections/:collection/documents/:id", get(get_document))
-            .route("/api/v1/collections/:collection/documents/:id", put(update_document))
-            .route("/api/v1/collections/:collection/documents/:id", delete(delete_document))
-            .route("/api/v1/collections/:collection/query", post(query_documents))
-            .route("/api/v1/collections/:collection/documents", get(list_documents))
-            .route("/api/v1/stats", get(get_stats))            // Payment API routes
-            .nest("/api/v1/payment", crate::payment::payment_routes())
-            // SaaS API routes - requires SaaS manager in state
-            // .nest("/api/v1/saas", crate::saas::saas_routes())
-            .with_state(state);
+        let mut router = routes()
+            .with_state(state)
+            .layer(axum::middleware::from_fn_with_state(self.consensus.feature_flags(), feature_gate_middleware))
+            .layer(axum::middleware::from_fn_with_state(Arc::clone(&self.versions), versioning_middleware));
+
+        if let Some(mirror) = &self.mirror {
+            router = router.layer(axum::middleware::from_fn_with_state(Arc::clone(mirror), mirror_middleware));
+        }
+
+        if let Some(cdn) = &self.cdn {
+            router = router.layer(axum::middleware::from_fn_with_state(Arc::clone(cdn), cdn_middleware));
+        }
+
+        if let Some(sandbox) = &self.sandbox {
+            router = router.layer(axum::middleware::from_fn_with_state(Arc::clone(sandbox), sandbox_middleware));
+        } else {
+            // Sandbox traffic is anonymous by design and limited by the sandbox itself
+            router = router.layer(axum::middleware::from_fn_with_state(Arc::clone(&self.access), access_middleware));
+        }
 
         if self.config.cors_enabled {
             router = router.layer(CorsLayer::permissive());
         }
 
+        if self.compression.enabled {
+            router = router.layer(compression::layer(&self.compression));
+        }
+
         router
     }
 }
 
+/// Every REST route, before state and middleware are applied
+fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/metrics", get(prometheus_metrics))
+        .route(TRANSFER_PATH, post(accept_transferred_document))
+        .route(&format!("{}/:collection", PEER_QUERY_PATH), post(answer_peer_query))
+        .route(CACHE_PEER_PATH, post(answer_cache_peer_request))
+        .nest("/api/v1", v1_routes())
+        .nest("/api/v2", v2_routes())
+}
+
+/// Resources of the v1 API, relative to `/api/v1`
+fn v1_routes() -> Router<AppState> {
+    Router::new()
+        .route("/collections", get(list_collections))
+        .route("/collections", post(create_collection))
+        .route("/collections/:collection", put(update_collection))
+        .route("/collections/:collection", delete(drop_collection))
+        .route("/collections/:collection/truncate", post(truncate_collection))
+        .route("/collections/:collection/documents", post(create_document))
+        .route("/collections/:collection/documents/:id", get(get_document))
+        .route("/collections/:collection/documents/:id", put(update_document))
+        .route("/collections/:collection/documents/:id", delete(delete_document))
+        .route("/collections/:collection/query", post(query_documents))
+        .route("/collections/:collection/explain", post(explain_query))
+        .route("/collections/:collection/indexes", get(list_indexes))
+        .route("/collections/:collection/indexes", post(create_index))
+        .route("/collections/:collection/indexes/:name", delete(drop_index))
+        .route("/collections/:collection/schema", get(get_schema))
+        .route("/collections/:collection/schema", put(set_schema))
+        .route("/collections/:collection/schema", delete(remove_schema))
+        .route("/collections/:collection/schema/mode", put(set_schema_mode))
+        .route("/collections/:collection/schema/versions", get(list_schema_versions))
+        .route("/collections/:collection/aggregate", post(aggregate_documents))
+        .route("/collections/:collection/changes", get(stream_changes))
+        .route("/collections/:collection/statistics", get(get_collection_statistics))
+        .route("/collections/:collection/dictionaries", get(list_compression_dictionaries))
+        .route("/collections/:collection/dictionaries", post(train_compression_dictionary))
+        .route("/collections/:collection/documents", get(list_documents))
+        .route("/collections/:collection/worm", put(enable_worm))
+        .route("/collections/:collection/worm", get(get_worm_policy))
+        .route("/collections/:collection/residency", put(set_collection_residency))
+        .route("/collections/:collection/documents/:id/residency", put(set_document_residency))
+        .route("/compliance/residency", get(residency_report))
+        .route("/admin/cluster/quarantine", get(list_quarantined_nodes))
+        .route("/admin/cluster/quarantine/:id", get(get_quarantined_node))
+        .route("/admin/cluster/quarantine/:id/reinstate", post(reinstate_quarantined_node))
+        .route("/stats", get(get_stats))
+        .route("/admin/config/effective", get(get_effective_config))
+        .route("/admin/features", get(list_features))
+        .route("/admin/security", get(get_security_status))
+        .route("/admin/security/posture", get(get_security_posture))
+        .route("/admin/tiering/rules", get(get_tier_rules))
+        .route("/admin/tiering/rules", put(set_tier_rules))
+        .route("/admin/tiering/run", post(run_tier_migration))
+        .route("/admin/tiering/metrics", get(get_tier_migration_metrics))
+        .route("/admin/scrub", post(run_scrub))
+        .route("/admin/scrub", get(get_scrub_report))
+        .route("/admin/statistics", post(analyze_collections))
+        .route("/admin/storage/usage", get(get_storage_usage))
+        .route("/admin/mirror", get(get_mirror_report))
+        .route("/admin/cdn", get(get_cdn_stats))
+        .route("/admin/api-versions", get(get_api_version_report))
+        .route("/admin/metrics/history", get(get_metrics_history))
+        .route("/admin/canary", get(get_canary_report))
+        .route("/admin/canary", post(start_canary))
+        .route("/admin/canary", delete(stop_canary))
+        .route("/admin/operations", get(list_operations))
+        .route("/admin/operations/:id", delete(kill_operation))
+        .route("/admin/queries", get(list_running_queries))
+        .route("/admin/queries/stats", get(get_query_stats))
+        .route("/admin/queries/stats", delete(reset_query_stats))
+        .route("/admin/queries/slow", get(list_slow_queries))
+        .route("/admin/heavy-hitters", get(get_heavy_hitters))
+        .route("/admin/queries/:id", delete(cancel_query))
+        .route("/admin/cluster/nodes", get(list_cluster_nodes))
+        .route("/admin/cluster/nodes", post(join_cluster_node))
+        .route("/admin/cluster/nodes/:id", delete(remove_cluster_node))
+        .route("/admin/cluster/ca", get(get_cluster_ca))
+        .route("/admin/cluster/certificates", get(list_node_certificates))
+        .route("/admin/cluster/certificates", post(request_node_certificate))
+        .route("/admin/cluster/certificates/requests", get(list_certificate_requests))
+        .route("/admin/cluster/certificates/requests/:id/approve", post(approve_certificate_request))
+        .route("/admin/cluster/certificates/requests/:id", delete(reject_certificate_request))
+        .route("/admin/cluster/admission", get(get_admission_lists))
+        .route("/admin/cluster/admission/allowlist", post(allow_identity))
+        .route("/admin/cluster/admission/allowlist/:kind/:value", delete(remove_allowed_identity))
+        .route("/admin/cluster/admission/denylist", post(deny_identity))
+        .route("/admin/cluster/admission/denylist/:kind/:value", delete(remove_denied_identity))
+        .route("/admin/cluster/admission/requests", get(list_join_requests))
+        .route("/admin/cluster/admission/requests/:id/approve", post(approve_join_request))
+        .route("/admin/cluster/admission/requests/:id", delete(reject_join_request))
+        .route("/admin/cluster/admission/denied", get(list_denied_joins))
+        .route("/admin/rebalance", get(get_rebalance_plan))
+        .route("/admin/rebalance", post(run_rebalance))
+        .route("/admin/backups", post(create_backup))
+        .route("/admin/backups", get(list_backups))
+        .route("/admin/backups/:id", get(get_backup))
+        .route("/admin/backups/:id", delete(delete_backup))
+        .route("/admin/backups/:id/restore", post(restore_backup))
+        .route("/admin/restore", post(restore_backup_at))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:id", get(get_job))
+        .route("/jobs/:id/events", get(stream_job_events))
+        .route("/jobs/:id/cancel", post(cancel_job))
+        .route("/admin/features/:name", put(set_feature))
+        .route("/sessions", post(create_session))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:session_id", delete(end_session))
+        .route("/sessions/:session_id/heartbeat", post(heartbeat_session))
+        .route("/sessions/:session_id/collections/:collection/documents/:id", put(put_ephemeral_document))
+        .route("/sessions/:session_id/collections/:collection/documents/:id", delete(delete_ephemeral_document))
+        .route("/sync", get(get_sync_info))
+        .route("/sync/pull", post(pull_changes))
+        .route("/sync/push", post(push_changes))
+        .route("/locks/:name", get(get_lock))
+        .route("/locks/:name/acquire", post(acquire_lock))
+        .route("/locks/:name/renew", post(renew_lock))
+        .route("/locks/:name/release", post(release_lock))
+        .route("/legal-holds", post(place_legal_hold))
+        .route("/legal-holds", get(list_legal_holds))
+        .route("/legal-holds/audit", get(legal_hold_audit_log))
+        .route("/legal-holds/:hold_id/release", post(release_legal_hold))
+        // Payment API routes
+        .nest("/payment", crate::payment::payment_routes())
+        // SaaS API routes - requires SaaS manager in state
+        // .nest("/saas", crate::saas::saas_routes())
+}
+
+/// Resources of the v2 API, relative to `/api/v2`. v2 starts out with v1's
+/// resources; an endpoint that changes shape gets its v2 handler here
+/// while v1 keeps the old one until its sunset.
+fn v2_routes() -> Router<AppState> {
+    v1_routes()
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub query: Arc<QueryEngine>,
     pub security: Arc<SecurityFramework>,
+    pub consensus: Arc<ConsensusEngine>,
+    pub effective_config: Arc<tokio::sync::RwLock<Option<serde_json::Value>>>,
+    pub mirror: Option<Arc<TrafficMirror>>,
+    pub versions: Arc<ApiVersions>,
+    pub access: Arc<AccessPolicy>,
+    pub realtime: Option<Arc<RealtimeAPI>>,
+    pub cdn: Option<Arc<CdnInvalidator>>,
+    pub resources: Arc<ResourceMonitor>,
+    pub metrics_history: Arc<MetricsHistory>,
 }
 
-async fn health_check() -> Json<serde_json::Value> {
+async fn health_check(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let features: serde_json::Map<String, serde_json::Value> = state.consensus.feature_flags()
+        .snapshot()
+        .into_iter()
+        .map(|status| (status.name, serde_json::Value::Bool(status.enabled)))
+        .collect();
+
+    // Documents of volatile collections are lost when this node restarts
+    let volatile: Vec<serde_json::Value> = state.query.list_collections()
+        .into_iter()
+        .filter(|collection| collection.config.volatile)
+        .map(|collection| serde_json::json!({
+            "name": collection.name,
+            "documents": collection.document_count,
+            "size_bytes": collection.size_bytes,
+        }))
+        .collect();
+
     Json(serde_json::json!({
         "status": "healthy",
         "timestamp": chrono::Utc::now(),
-        "version": "1.0.0"
+        "version": "1.0.0",
+        "features": features,
+        "volatile_collections": volatile,
+        "resources": state.resources.sample(&state.query.storage_paths())
     }))
 }
 
+/// Cache statistics, API version usage and request admissions in the
+/// Prometheus text exposition format.
+async fn prometheus_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut metrics = state.query.cache().get_stats().to_prometheus();
+    metrics.push_str(&state.versions.to_prometheus());
+    metrics.push_str(&state.access.to_prometheus());
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics)
+}
+
+/// Current key metrics of the node, for its metrics history
+fn metrics_reading(query: &QueryEngine, resources: &ResourceMonitor, access: &AccessPolicy) -> MetricsReading {
+    let usage = resources.sample(&query.storage_paths());
+    let cache = query.cache().get_stats();
+    MetricsReading {
+        cpu_percent: usage.cpu_percent as f64,
+        resident_memory_bytes: usage.resident_memory_bytes as f64,
+        disk_used_percent: usage.disks.iter().map(|disk| disk.used_percent).fold(0.0, f64::max),
+        open_file_descriptors: usage.open_file_descriptors.unwrap_or(0) as f64,
+        cache_hits: cache.hits,
+        cache_misses: cache.misses,
+        queries: query.profiler().stats().iter().map(|stats| stats.queries + stats.aggregations).sum(),
+        requests: access.admissions().iter().map(|(_, _, count)| count).sum(),
+    }
+}
+
+/// Feature flag guarding a route, if the route belongs to a gated subsystem
+fn gated_feature(path: &str) -> Option<&'static str> {
+    let (_, path) = split_version(path)?;
+    if path.starts_with("/sessions") {
+        Some(features::SESSIONS)
+    } else if path.starts_with("/locks") {
+        Some(features::LOCKS)
+    } else {
+        None
+    }
+}
+
+/// REST middleware refusing requests to subsystems whose feature flag is off
+async fn feature_gate_middleware(
+    State(flags): State<Arc<FeatureFlagRegistry>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match gated_feature(request.uri().path()) {
+        Some(feature) if !flags.is_enabled(feature) => {
+            let error = ErrorResponse {
+                error: format!("Feature '{}' is disabled on this node", feature),
+                code: StatusCode::SERVICE_UNAVAILABLE.as_u16() as u32,
+                details: None,
+            };
+            (StatusCode::SERVICE_UNAVAILABLE, Json(error)).into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+fn collection_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Collection not found") {
+        StatusCode::NOT_FOUND
+    } else if message.starts_with("Collection already exists")
+        || message.starts_with("Cannot change compression")
+        || message.contains("under legal hold")
+        || message.contains("WORM collection")
+    {
+        StatusCode::CONFLICT
+    } else if message.starts_with("Invalid collection name")
+        || message.starts_with("Replication factor")
+        || message.starts_with("Limits of capped collection")
+        || message.ends_with("cannot be strongly consistent")
+    {
+        StatusCode::BAD_REQUEST
+    } else {
+        warn!("Collection operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn list_collections(State(state): State<AppState>) -> Json<Vec<CollectionInfo>> {
+    Json(state.query.list_collections())
+}
+
+async fn create_collection(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateCollectionRequest>,
+) -> Result<(StatusCode, Json<Collection>), StatusCode> {
+    info!("Creating collection {}", payload.name);
+
+    state.query
+        .create_collection(&payload.name, payload.config)
+        .map(|collection| (StatusCode::CREATED, Json(collection)))
+        .map_err(|e| collection_error_status(&e))
+}
+
+async fn update_collection(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(config): Json<CollectionConfig>,
+) -> Result<Json<Collection>, StatusCode> {
+    info!("Updating settings of collection {}", collection);
+
+    state.query
+        .update_collection(&collection, config)
+        .map(Json)
+        .map_err(|e| collection_error_status(&e))
+}
+
+async fn drop_collection(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Dropping collection {}", collection);
+
+    state.query
+        .drop_collection(&collection)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| collection_error_status(&e))
+}
+
+async fn truncate_collection(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    info!("Truncating collection {}", collection);
+
+    state.query
+        .truncate_collection(&collection)
+        .await
+        .map(|deleted| Json(serde_json::json!({ "deleted": deleted })))
+        .map_err(|e| collection_error_status(&e))
+}
+
 async fn create_document(
     State(state): State<AppState>,
     Path(collection): Path<String>,
     Json(payload): Json<DocumentRequest>,
-) -> Result<Json<DocumentResponse>, StatusCode> {
+) -> Result<Response, StatusCode> {
     info!("Creating document in collection: {}", collection);
     
     // Generate document ID
     let document_id = uuid::Uuid::new_v4().to_string();
     
     // Store document via query engine
-    if let Err(e) = state.query.store_document(&collection, &document_id, &payload.data).await {
-        warn!("Failed to store document: {}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-    
+    if let Err(e) = state.query.store_document_with_options(&collection, &document_id, &payload.data, payload.write_options()).await {
+        if e.is::<SchemaValidationError>() {
+            return Ok(schema_violation_response(&e));
+        }
+        if e.to_string().contains("does not allow placement") {
+            info!("Collection {} may not be stored on this node: {}", collection, e);
+            return Err(StatusCode::CONFLICT);
+        }
+        if e.to_string().contains("Storage quota exceeded") {
+            warn!("Refused document in collection {}: {}", collection, e);
+            return Err(StatusCode::INSUFFICIENT_STORAGE);
+        }
+        if e.to_string().starts_with("Invalid time to live") {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if e.to_string().contains("limit of capped collection") {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        if e.to_string().contains("did not reach quorum") {
+            warn!("Document in strongly consistent collection {} was not acknowledged: {}", collection, e);
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        warn!("Failed to store document: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    
     // Create response with current timestamp
     let now = chrono::Utc::now();
     
+    let expires_at = state.query.document_expiry(&collection, &document_id);
     let response = DocumentResponse {
         id: document_id,
         data: payload.data,
         version: 1,
         created_at: now,
         updated_at: now,
+        expires_at,
     };
     
     info!("Document created successfully in collection: {}", collection);
-    Ok(Json(response))
+    Ok(Json(response).into_response())
 }
 
 async fn get_document(
     State(state): State<AppState>,
     Path((collection, id)): Path<(String, String)>,
+    Query(params): Query<DocumentFieldsParams>,
+    Query(consistency): Query<ReadConsistencyParams>,
+    Query(as_of): Query<AsOfParams>,
 ) -> Result<Json<DocumentResponse>, StatusCode> {
     info!("Getting document {} from collection: {}", id, collection);
+    let projection = params.projection().map_err(|e| {
+        info!("Rejected read of document {} in {}: {}", id, collection, e);
+        StatusCode::BAD_REQUEST
+    })?;
+    let consistency = consistency.read_consistency().map_err(|e| {
+        info!("Rejected read of document {} in {}: {}", id, collection, e);
+        StatusCode::BAD_REQUEST
+    })?;
       // Get document via query engine
-    match state.query.get_document(&collection, &id).await {
+    let document = match as_of.as_of {
+        // Past states come from this node's version history
+        Some(as_of) => state.query.get_document_as_of(&collection, &id, as_of).await,
+        None => state.query.get_document_with_consistency(&collection, &id, consistency).await,
+    };
+    match document {
         Ok(data) => {
+            let data = match &projection {
+                Some(projection) => projection.apply(data),
+                None => data,
+            };
             let now = chrono::Utc::now();
               let response = DocumentResponse {
                 id: id.clone(),
@@ -184,6 +1265,7 @@ async fn get_document(
                 version: 1,
                 created_at: now - chrono::Duration::hours(1), // Default creation time
                 updated_at: now,
+                expires_at: state.query.document_expiry(&collection, &id),
             };
             
             Ok(Json(response))
@@ -192,6 +1274,12 @@ async fn get_document(
             if e.to_string().contains("Document not found") {
                 info!("Document {} not found in collection: {}", id, collection);
                 Err(StatusCode::NOT_FOUND)
+            } else if let Some(status) = as_of_status(&e) {
+                info!("Read of document {} in {} as of {:?} refused: {}", id, collection, as_of.as_of, e);
+                Err(status)
+            } else if let Some(status) = read_consistency_status(&e) {
+                info!("Read of document {} in {} refused at {:?}: {}", id, collection, consistency, e);
+                Err(status)
             } else {
                 warn!("Failed to get document: {}", e);
                 Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -204,11 +1292,11 @@ async fn update_document(
     State(state): State<AppState>,
     Path((collection, id)): Path<(String, String)>,
     Json(payload): Json<DocumentRequest>,
-) -> Result<Json<DocumentResponse>, StatusCode> {
+) -> Result<Response, StatusCode> {
     info!("Upaerolithng document {} in collection: {}", id, collection);
     
     // Update document via query engine with real storage integration
-    match state.query.update_document(&collection, &id, &payload.data).await {
+    match state.query.update_document_with_options(&collection, &id, &payload.data, payload.write_options()).await {
         Ok(()) => {            // Retrieve updated document to return complete response
             match state.query.get_document(&collection, &id).await {
                 Ok(data) => {
@@ -219,10 +1307,11 @@ async fn update_document(
                         version: 2, // Version tracking will be enhanced with metadata integration
                         created_at: now - chrono::Duration::hours(1), // Creation time retrieved from storage metadata
                         updated_at: now,
+                        expires_at: state.query.document_expiry(&collection, &id),
                     };
                     
                     info!("Document {} updated successfully in collection: {}", id, collection);
-                    Ok(Json(response))
+                    Ok(Json(response).into_response())
                 }
                 Err(e) => {
                     if e.to_string().contains("Document not found") {
@@ -236,8 +1325,28 @@ async fn update_document(
             }
         }
         Err(e) => {
-            warn!("Failed to update document: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            if e.is::<SchemaValidationError>() {
+                Ok(schema_violation_response(&e))
+            } else if e.to_string().contains("WORM collection") {
+                info!("Document {} in WORM collection {} is still retained", id, collection);
+                Err(StatusCode::CONFLICT)
+            } else if e.to_string().contains("does not allow placement") {
+                info!("Document {} in collection {} may not be stored on this node", id, collection);
+                Err(StatusCode::CONFLICT)
+            } else if e.to_string().contains("Storage quota exceeded") {
+                warn!("Refused update of document {} in collection {}: {}", id, collection, e);
+                Err(StatusCode::INSUFFICIENT_STORAGE)
+            } else if e.to_string().starts_with("Invalid time to live") {
+                Err(StatusCode::BAD_REQUEST)
+            } else if e.to_string().contains("limit of capped collection") {
+                Err(StatusCode::PAYLOAD_TOO_LARGE)
+            } else if e.to_string().contains("did not reach quorum") {
+                warn!("Update of document {} in collection {} was not acknowledged: {}", id, collection, e);
+                Err(StatusCode::SERVICE_UNAVAILABLE)
+            } else {
+                warn!("Failed to update document: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
         }
     }
 }
@@ -257,6 +1366,15 @@ async fn delete_document(
             if e.to_string().contains("Document not found") {
                 info!("Document {} not found in collection: {}", id, collection);
                 Err(StatusCode::NOT_FOUND)
+            } else if e.to_string().contains("under legal hold") {
+                info!("Document {} in collection {} is under legal hold", id, collection);
+                Err(StatusCode::CONFLICT)
+            } else if e.to_string().contains("WORM collection") {
+                info!("Document {} in WORM collection {} is still retained", id, collection);
+                Err(StatusCode::CONFLICT)
+            } else if e.to_string().contains("did not reach quorum") {
+                warn!("Deletion of document {} from collection {} was not acknowledged: {}", id, collection, e);
+                Err(StatusCode::SERVICE_UNAVAILABLE)
             } else {
                 warn!("Failed to delete document: {}", e);
                 Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -268,9 +1386,17 @@ async fn delete_document(
 async fn query_documents(
     State(state): State<AppState>,
     Path(collection): Path<String>,
+    Query(params): Query<ResultFormatParams>,
+    Query(consistency): Query<ReadConsistencyParams>,
+    headers: HeaderMap,
     Json(query): Json<QueryRequest>,
-) -> Result<Json<QueryResponse>, StatusCode> {
+) -> Result<Response, StatusCode> {
     info!("Querying documents in collection: {} with filter: {:?}", collection, query.filter);
+    let format = ResultFormat::negotiate(params.format.as_deref(), &headers)?;
+    let consistency = consistency.read_consistency().map_err(|e| {
+        info!("Rejected query for collection {}: {}", collection, e);
+        StatusCode::BAD_REQUEST
+    })?;
     
     // Create query request for query engine
     let query_req = aerolithdb_query::QueryRequest {
@@ -278,26 +1404,37 @@ async fn query_documents(
         limit: query.limit,
         offset: query.offset,
         sort: query.sort,
+        cursor: query.cursor,
+        limits: query.limits,
+        projection: query.projection,
+        as_of: query.as_of,
     };
     
-    // Execute query via query engine
-    match state.query.query_documents(&collection, &query_req).await {
+    // Execute query across the cluster via query engine
+    match state.query.query_with_consistency(&collection, &query_req, consistency).await {
+        Ok(result) if format != ResultFormat::Json => {
+            info!("Query completed for collection: {} in {:?}", collection, result.execution_time);
+            let mut response = formats::stream_documents(format, result.documents, result.total);
+            if result.partial {
+                response.headers_mut().insert(formats::PARTIAL_RESULTS_HEADER, HeaderValue::from_static("true"));
+            }
+            insert_next_cursor(&mut response, result.next_cursor.as_deref());
+            Ok(response)
+        }
         Ok(result) => {
             // Convert query engine results to REST API format
-            let documents: Vec<DocumentResponse> = result.documents
+            let documents: Vec<DocumentResponse> = result.ids
                 .into_iter()
-                .enumerate()
-                .map(|(i, data)| {
+                .zip(result.documents)
+                .map(|(id, data)| {
                     let now = chrono::Utc::now();
                     DocumentResponse {
-                        id: data.get("id")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or(&format!("doc_{}", i))
-                            .to_string(),
+                        id,
                         data,
                         version: 1,
                         created_at: now - chrono::Duration::hours(2),
                         updated_at: now - chrono::Duration::hours(1),
+                        expires_at: None,
                     }
                 })
                 .collect();
@@ -307,69 +1444,521 @@ async fn query_documents(
                 total: result.total,
                 limit: query_req.limit,
                 offset: query_req.offset,
+                next_cursor: result.next_cursor,
+                partial: result.partial,
+                unreachable_nodes: result.unreachable_nodes,
             };
             
             info!("Query completed for collection: {} in {:?}", collection, result.execution_time);
-            Ok(Json(response))
+            Ok(Json(response).into_response())
+        }
+        Err(e) if e.is::<QueryAborted>() => Ok(query_aborted_response(&e)),
+        Err(e) if ["Invalid filter", "Invalid cursor", "Invalid projection"].iter().any(|prefix| e.to_string().starts_with(prefix)) => {
+            info!("Rejected query for collection {}: {}", collection, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(e) => match as_of_status(&e).or_else(|| read_consistency_status(&e)) {
+            Some(status) => {
+                info!("Query for collection {} as of {:?} refused at {:?}: {}", collection, query_req.as_of, consistency, e);
+                Err(status)
+            }
+            None => {
+                warn!("Query failed for collection {}: {}", collection, e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+    }
+}
+
+async fn aggregate_documents(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Query(params): Query<ResultFormatParams>,
+    headers: HeaderMap,
+    Json(request): Json<AggregateRequest>,
+) -> Result<Response, StatusCode> {
+    let format = ResultFormat::negotiate(params.format.as_deref(), &headers)?;
+    match state.query.aggregate_with_limits(&collection, &request.pipeline, request.limits.as_ref()).await {
+        Ok(result) => {
+            info!(
+                "Aggregated {} documents of {} into {} in {:?}",
+                result.scanned, collection, result.documents.len(), result.execution_time
+            );
+            let count = result.documents.len();
+            if format != ResultFormat::Json {
+                return Ok(formats::stream_documents(format, result.documents, count));
+            }
+            Ok(Json(AggregateResponse {
+                documents: result.documents,
+                count,
+                scanned: result.scanned,
+                execution_time_ms: result.execution_time.as_millis() as u64,
+            })
+            .into_response())
+        }
+        Err(e) if e.is::<QueryAborted>() => Ok(query_aborted_response(&e)),
+        Err(e) if e.to_string().starts_with("Invalid pipeline") => {
+            info!("Rejected aggregation of {}: {}", collection, e);
+            Err(StatusCode::BAD_REQUEST)
         }
         Err(e) => {
-            warn!("Query failed for collection {}: {}", collection, e);
+            warn!("Aggregation of {} failed: {}", collection, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
-async fn list_documents(
+async fn stream_changes(
     State(state): State<AppState>,
     Path(collection): Path<String>,
-    Query(params): Query<std::collections::HashMap<String, String>>,
-) -> Result<Json<QueryResponse>, StatusCode> {
-    info!("Listing documents in collection: {} with params: {:?}", collection, params);
-    
-    // Parse query parameters
-    let limit = params.get("limit").and_then(|s| s.parse().ok());
-    let offset = params.get("offset").and_then(|s| s.parse().ok());
-    
-    // Get documents via query engine
-    match state.query.list_documents(&collection, limit, offset).await {
-        Ok(result) => {
-            // Convert query engine results to REST API format
-            let documents: Vec<DocumentResponse> = result.documents
-                .into_iter()
-                .enumerate()
-                .map(|(i, data)| {
-                    let now = chrono::Utc::now();
-                    DocumentResponse {
-                        id: data.get("id")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or(&format!("doc_{}", i))
-                            .to_string(),
-                        data,
-                        version: 1,
-                        created_at: now - chrono::Duration::hours(3),
-                        updated_at: now - chrono::Duration::hours(1),
-                    }
-                })
-                .collect();
-            
-            let response = QueryResponse {
-                documents,
-                total: result.total,
-                limit,
-                offset,
-            };
-            
-            info!("Listed {} documents in collection: {} in {:?}", 
-                  response.documents.len(), collection, result.execution_time);
-            Ok(Json(response))
+    Query(params): Query<ChangeStreamParams>,
+    headers: HeaderMap,
+) -> Result<EventStream, StatusCode> {
+    let Some(realtime) = state.realtime.clone() else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    let filter = params
+        .filter
+        .as_deref()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let resume_token = sse::resume_point(&headers, params.resume_token);
+
+    info!("Streaming changes to {} over SSE (resuming: {})", collection, resume_token.is_some());
+    sse::change_stream(realtime, collection, filter, resume_token)
+        .await
+        .map_err(|e| change_stream_error_status(&e))
+}
+
+/// A query stopped by its limits or cancelled, answered with why and how
+/// far it got: 504 when it timed out, 503 when an operator cancelled it
+/// and 422 when it would read or hold too much.
+fn query_aborted_response(e: &anyhow::Error) -> Response {
+    let aborted = e.downcast_ref::<QueryAborted>().expect("query aborted error");
+    info!("{}", aborted);
+    let status = match aborted.reason {
+        AbortReason::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        AbortReason::Cancelled => StatusCode::SERVICE_UNAVAILABLE,
+        AbortReason::ScanLimit | AbortReason::MemoryLimit => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+    (status, Json(aborted.clone())).into_response()
+}
+
+fn change_stream_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Malformed resume token") || message.starts_with("Invalid resume token") {
+        StatusCode::BAD_REQUEST
+    } else if message.starts_with("Unknown subscription") {
+        StatusCode::NOT_FOUND
+    } else if message.starts_with("Resume token expired") {
+        StatusCode::GONE
+    } else {
+        warn!("Change stream failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn explain_query(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(query): Json<QueryRequest>,
+) -> Result<Json<QueryPlan>, StatusCode> {
+    let query_req = aerolithdb_query::QueryRequest {
+        filter: query.filter,
+        limit: query.limit,
+        offset: query.offset,
+        sort: query.sort,
+        cursor: query.cursor,
+        limits: query.limits,
+        projection: query.projection,
+        as_of: query.as_of,
+    };
+
+    state.query.explain_query(&collection, &query_req).await.map(Json).map_err(|e| {
+        if e.to_string().starts_with("Invalid filter") {
+            return StatusCode::BAD_REQUEST;
+        }
+        warn!("Failed to plan query for collection {}: {}", collection, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn list_indexes(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Json<Vec<IndexDefinition>>, StatusCode> {
+    state.query.list_indexes(&collection).await.map(Json).map_err(|e| {
+        warn!("Failed to list indexes of {}: {}", collection, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn create_index(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(definition): Json<IndexDefinition>,
+) -> Result<(StatusCode, Json<IndexDefinition>), StatusCode> {
+    info!("Creating index on {} over {:?}", collection, definition.fields);
+
+    match state.query.create_index(&collection, definition).await {
+        Ok(definition) => Ok((StatusCode::CREATED, Json(definition))),
+        Err(e) if e.to_string().starts_with("Invalid index") => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            warn!("Failed to create index on {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn drop_index(
+    State(state): State<AppState>,
+    Path((collection, name)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Dropping index {} on {}", name, collection);
+
+    match state.query.drop_index(&collection, &name).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Failed to drop index {} on {}: {}", name, collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// A write refused by its collection's schema, answered with 422 and every
+/// violation with the JSON pointer of the offending value.
+fn schema_violation_response(e: &anyhow::Error) -> Response {
+    let refused = e.downcast_ref::<SchemaValidationError>().expect("schema validation error");
+    info!("{}", refused);
+    // Violations quote document values, which may hold secrets or PII
+    let violations: Vec<SchemaViolation> = refused
+        .violations
+        .iter()
+        .map(|violation| SchemaViolation { message: redact(&violation.message).into_owned(), ..violation.clone() })
+        .collect();
+    let body = serde_json::json!({
+        "error": redact(&refused.to_string()),
+        "collection": refused.collection,
+        "document_id": refused.document_id,
+        "schema_version": refused.version,
+        "violations": violations,
+    });
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaParams {
+    /// A replaced version instead of the current schema
+    version: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetSchemaRequest {
+    schema: serde_json::Value,
+    #[serde(default)]
+    mode: ValidationMode,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaModeRequest {
+    mode: ValidationMode,
+}
+
+async fn get_schema(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Query(params): Query<SchemaParams>,
+) -> Result<Json<CollectionSchema>, StatusCode> {
+    match state.query.get_schema(&collection, params.version).await {
+        Ok(Some(schema)) => Ok(Json(schema)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Failed to read the schema of {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn list_schema_versions(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Json<Vec<CollectionSchema>>, StatusCode> {
+    state.query.schema_versions(&collection).await.map(Json).map_err(|e| {
+        warn!("Failed to list schema versions of {}: {}", collection, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn set_schema(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(request): Json<SetSchemaRequest>,
+) -> Result<Json<CollectionSchema>, StatusCode> {
+    info!("Setting the schema of {} ({:?})", collection, request.mode);
+
+    match state.query.set_schema(&collection, request.schema, request.mode).await {
+        Ok(schema) => Ok(Json(schema)),
+        Err(e) if e.to_string().starts_with("Invalid schema") => {
+            info!("Refused schema for {}: {}", collection, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(e) => {
+            warn!("Failed to set the schema of {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn set_schema_mode(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(request): Json<SchemaModeRequest>,
+) -> Result<Json<CollectionSchema>, StatusCode> {
+    info!("Validating writes to {} in {:?} mode", collection, request.mode);
+
+    match state.query.set_schema_mode(&collection, request.mode).await {
+        Ok(Some(schema)) => Ok(Json(schema)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Failed to change the schema mode of {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
+    }
+}
+
+async fn remove_schema(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Removing the schema of {}", collection);
+
+    match state.query.remove_schema(&collection).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
         Err(e) => {
-            warn!("Failed to list documents in collection {}: {}", collection, e);
+            warn!("Failed to remove the schema of {}: {}", collection, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
+async fn list_documents(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Query(params): Query<ListDocumentsParams>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    info!("Listing documents in collection: {} with params: {:?}", collection, params);
+
+    let options = params.list_options()?;
+    let format = ResultFormat::negotiate(params.format.as_deref(), &headers)?;
+    let start_time = std::time::Instant::now();
+
+    let page = state.query.list_documents_with(&collection, &options).map_err(|e| {
+        warn!("Failed to list documents in collection {}: {}", collection, e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    let next_cursor = page
+        .documents
+        .last()
+        .filter(|_| page.has_more)
+        .map(|last| ListCursor::after(last, &options).encode());
+
+    // Metadata comes from the listing itself, without reading any document
+    if params.metadata {
+        info!("Listed {} of {} documents in collection: {} in {:?}",
+              page.documents.len(), page.total, collection, start_time.elapsed());
+        if format != ResultFormat::Json {
+            let documents = page.documents.iter().filter_map(|document| serde_json::to_value(document).ok()).collect();
+            let mut response = formats::stream_documents(format, documents, page.total);
+            insert_next_cursor(&mut response, next_cursor.as_deref());
+            return Ok(response);
+        }
+        return Ok(Json(DocumentListResponse {
+            documents: page.documents,
+            total: page.total,
+            limit: params.limit,
+            offset: params.offset,
+            next_cursor,
+        }).into_response());
+    }
+
+    let mut documents = Vec::with_capacity(page.documents.len());
+    for listed in page.documents {
+        // Documents deleted since they were listed are left out
+        if let Ok(data) = state.query.get_document(&collection, &listed.id).await {
+            documents.push(DocumentResponse {
+                id: listed.id,
+                data,
+                version: listed.version,
+                created_at: listed.created_at,
+                updated_at: listed.updated_at,
+                expires_at: listed.expires_at,
+            });
+        }
+    }
+
+    info!("Listed {} of {} documents in collection: {} in {:?}",
+          documents.len(), page.total, collection, start_time.elapsed());
+    if format != ResultFormat::Json {
+        let documents = documents.into_iter().map(|document| document.data).collect();
+        let mut response = formats::stream_documents(format, documents, page.total);
+        insert_next_cursor(&mut response, next_cursor.as_deref());
+        return Ok(response);
+    }
+    Ok(Json(QueryResponse {
+        documents,
+        total: page.total,
+        limit: params.limit,
+        offset: params.offset,
+        next_cursor,
+        partial: false,
+        unreachable_nodes: Vec::new(),
+    }).into_response())
+}
+
+/// Advertise the cursor of the next page on a streamed result.
+fn insert_next_cursor(response: &mut Response, next_cursor: Option<&str>) {
+    if let Some(cursor) = next_cursor.and_then(|cursor| HeaderValue::from_str(cursor).ok()) {
+        response.headers_mut().insert(formats::NEXT_CURSOR_HEADER, cursor);
+    }
+}
+
+async fn get_effective_config(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    info!("Getting effective configuration");
+
+    // Not available until the node has published its resolved configuration
+    match state.effective_config.read().await.clone() {
+        Some(export) => Ok(Json(export)),
+        None => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+async fn get_security_status(
+    State(state): State<AppState>,
+) -> Json<SecurityStatus> {
+    Json(state.security.status())
+}
+
+/// Score the node's configuration against security best practices. TLS is
+/// judged by how this request arrived, since the node sits behind the proxy
+/// terminating it; plugins are read from the published configuration.
+async fn get_security_posture(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<PostureReport>, StatusCode> {
+    let defaults = aerolithdb_plugins::PluginConfig::default();
+    let (plugin_dir, auto_load) = match state.effective_config.read().await.as_ref() {
+        Some(export) => (
+            export.pointer("/config/plugins/plugin_dir").and_then(|dir| dir.as_str()).map(std::path::PathBuf::from).unwrap_or(defaults.plugin_dir),
+            export.pointer("/config/plugins/auto_load").and_then(|auto_load| auto_load.as_bool()).unwrap_or(defaults.auto_load),
+        ),
+        None => (defaults.plugin_dir, defaults.auto_load),
+    };
+
+    // Libraries are only loaded from the plugin directory when auto-loading
+    let plugins = match auto_load {
+        true => scan_plugins(&plugin_dir).map_err(|e| {
+            warn!("Security posture assessment failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        false => Vec::new(),
+    };
+    let environment = PostureEnvironment { transport_tls: forwarded_over_tls(&headers), plugins };
+    Ok(Json(state.security.posture(&environment)))
+}
+
+/// Whether the proxy in front of the node received the request over TLS
+fn forwarded_over_tls(headers: &HeaderMap) -> bool {
+    let forwarded_proto = headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(|proto| proto.trim().eq_ignore_ascii_case("https"));
+    let forwarded = headers
+        .get(axum::http::header::FORWARDED)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(|hop| hop.split(';').any(|pair| pair.trim().eq_ignore_ascii_case("proto=https")));
+    forwarded_proto.or(forwarded).unwrap_or(false)
+}
+
+async fn list_features(
+    State(state): State<AppState>,
+) -> Json<Vec<FeatureFlagStatus>> {
+    Json(state.consensus.feature_flags().snapshot())
+}
+
+async fn set_feature(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<SetFeatureRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), StatusCode> {
+    info!("Setting feature {} to {:?} at {:?} scope", name, payload.enabled, payload.scope);
+
+    let flags = state.consensus.feature_flags();
+    if flags.status(&name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    match payload.scope {
+        FlagScope::Node => {
+            let status = flags.set_node_override(&name, payload.enabled).await.map_err(|e| {
+                warn!("Failed to set node override for feature {}: {}", name, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            Ok((StatusCode::OK, Json(serde_json::json!({ "status": status }))))
+        }
+        FlagScope::Cluster => {
+            // Takes effect on each node once the proposal commits
+            let proposal_id = state.consensus.set_cluster_feature(&name, payload.enabled).await.map_err(|e| {
+                warn!("Failed to propose cluster setting for feature {}: {}", name, e);
+                StatusCode::SERVICE_UNAVAILABLE
+            })?;
+            Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+                "proposal_id": proposal_id,
+                "status": flags.status(&name),
+            }))))
+        }
+        FlagScope::Default => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Nodes quarantined for Byzantine behavior, active and reinstated, with
+/// their evidence, most recent first
+async fn list_quarantined_nodes(State(state): State<AppState>) -> Json<Vec<QuarantineRecord>> {
+    Json(state.consensus.get_quarantine_records().await)
+}
+
+async fn get_quarantined_node(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<QuarantineRecord>, StatusCode> {
+    state.consensus.get_quarantine_record(&id).await.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Let a quarantined node take part in consensus again after review
+async fn reinstate_quarantined_node(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<ReinstateNodeRequest>,
+) -> Result<Json<QuarantineRecord>, StatusCode> {
+    info!("Reinstating quarantined node {} for {}", id, payload.operator);
+    state.consensus.reinstate_peer(&id, &payload.operator, &payload.note).await.map(Json).map_err(|e| {
+        if e.to_string().ends_with("is not quarantined") {
+            StatusCode::NOT_FOUND
+        } else {
+            warn!("Failed to reinstate node {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })
+}
+
 async fn get_stats(
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
@@ -408,7 +1997,8 @@ async fn get_stats(
                     "node_count": 3,
                     "consensus_status": "healthy",
                     "replication_lag": "< 1ms"
-                }
+                },
+                "resources": state.resources.sample(&state.query.storage_paths())
             });
 
             // Merge query engine stats
@@ -429,3 +2019,1041 @@ async fn get_stats(
         }
     }
 }
+
+/// Map session errors to HTTP status codes
+fn session_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.contains("not found") || message.contains("expired") || message.contains("not owned") {
+        StatusCode::NOT_FOUND
+    } else if message.contains("owned by another session") || message.contains("durable") {
+        StatusCode::CONFLICT
+    } else if message.contains("TTL") || message.contains("JSON objects") {
+        StatusCode::BAD_REQUEST
+    } else {
+        warn!("Session operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn create_session(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateSessionRequest>,
+) -> Result<Json<aerolithdb_query::Session>, StatusCode> {
+    info!("Opening session for {}", payload.owner);
+
+    state.query.sessions()
+        .create_session(&payload.owner, std::time::Duration::from_secs(payload.ttl_seconds))
+        .await
+        .map(Json)
+        .map_err(|e| session_error_status(&e))
+}
+
+async fn list_sessions(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<aerolithdb_query::Session>>, StatusCode> {
+    state.query.sessions()
+        .list_sessions()
+        .await
+        .map(Json)
+        .map_err(|e| session_error_status(&e))
+}
+
+async fn heartbeat_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<aerolithdb_query::Session>, StatusCode> {
+    state.query.sessions()
+        .heartbeat(&session_id)
+        .await
+        .map(Json)
+        .map_err(|e| session_error_status(&e))
+}
+
+async fn end_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Ending session {}", session_id);
+
+    state.query.sessions()
+        .end_session(&session_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| session_error_status(&e))
+}
+
+async fn put_ephemeral_document(
+    State(state): State<AppState>,
+    Path((session_id, collection, id)): Path<(String, String, String)>,
+    Json(payload): Json<DocumentRequest>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Storing ephemeral document {} in collection {} for session {}", id, collection, session_id);
+
+    state.query.sessions()
+        .put_ephemeral(&session_id, &collection, &id, &payload.data)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| session_error_status(&e))
+}
+
+async fn delete_ephemeral_document(
+    State(state): State<AppState>,
+    Path((session_id, collection, id)): Path<(String, String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    state.query.sessions()
+        .delete_ephemeral(&session_id, &collection, &id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| session_error_status(&e))
+}
+
+/// Map sync errors to HTTP status codes
+fn sync_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("System collection") || message.contains("JSON objects") {
+        StatusCode::BAD_REQUEST
+    } else {
+        warn!("Sync operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// Identity of this node's replica and the collections it tracks
+async fn get_sync_info(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let sync = state.query.sync();
+    Json(serde_json::json!({
+        "replica_id": sync.replica_id(),
+        "collections": sync.tracked_collections(),
+    }))
+}
+
+/// Changes an edge replica has not seen yet
+async fn pull_changes(
+    State(state): State<AppState>,
+    Json(request): Json<PullRequest>,
+) -> Result<Json<ChangeBatch>, StatusCode> {
+    state.query.sync()
+        .pull(request)
+        .await
+        .map(Json)
+        .map_err(|e| sync_error_status(&e))
+}
+
+/// Merge changes made on an edge replica, possibly while it was offline
+async fn push_changes(
+    State(state): State<AppState>,
+    Json(request): Json<PushRequest>,
+) -> Result<Json<ApplyReport>, StatusCode> {
+    info!("Merging {} change(s) from replica {}", request.changes.len(), request.replica);
+
+    state.query.sync()
+        .push(request)
+        .await
+        .map(Json)
+        .map_err(|e| sync_error_status(&e))
+}
+
+/// Map lease errors to HTTP status codes
+fn lease_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Lease name") || message.starts_with("Lease holder") || message.starts_with("Lease TTL") {
+        StatusCode::BAD_REQUEST
+    } else if message.contains("did not commit in time") || message.starts_with("Writes halted") {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        warn!("Lease operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// Contended and unheld leases are conflicts, but still report the lease state
+fn lease_response(outcome: LeaseOutcome) -> (StatusCode, Json<LeaseOutcome>) {
+    let status = match outcome {
+        LeaseOutcome::Granted { .. } | LeaseOutcome::Released { .. } => StatusCode::OK,
+        LeaseOutcome::Held { .. } | LeaseOutcome::NotHeld { .. } => StatusCode::CONFLICT,
+    };
+    (status, Json(outcome))
+}
+
+async fn get_lock(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Lease>, StatusCode> {
+    match state.consensus.get_lease(&name).await {
+        Ok(Some(lease)) => Ok(Json(lease)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Err(lease_error_status(&e)),
+    }
+}
+
+async fn acquire_lock(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<AcquireLockRequest>,
+) -> Result<(StatusCode, Json<LeaseOutcome>), StatusCode> {
+    info!("Acquiring lock {} for {}", name, payload.holder);
+
+    state.consensus
+        .acquire_lease(&name, &payload.holder, std::time::Duration::from_secs(payload.ttl_seconds))
+        .await
+        .map(lease_response)
+        .map_err(|e| lease_error_status(&e))
+}
+
+async fn renew_lock(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<LockTokenRequest>,
+) -> Result<(StatusCode, Json<LeaseOutcome>), StatusCode> {
+    state.consensus
+        .renew_lease(&name, &payload.holder, payload.fencing_token)
+        .await
+        .map(lease_response)
+        .map_err(|e| lease_error_status(&e))
+}
+
+async fn release_lock(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<LockTokenRequest>,
+) -> Result<(StatusCode, Json<LeaseOutcome>), StatusCode> {
+    info!("Releasing lock {} held by {}", name, payload.holder);
+
+    state.consensus
+        .release_lease(&name, &payload.holder, payload.fencing_token)
+        .await
+        .map(lease_response)
+        .map_err(|e| lease_error_status(&e))
+}
+
+fn legal_hold_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Legal hold not found") {
+        StatusCode::NOT_FOUND
+    } else if message.starts_with("Legal hold") || message.starts_with("Invalid filter") {
+        StatusCode::BAD_REQUEST
+    } else {
+        warn!("Legal hold operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn place_legal_hold(
+    State(state): State<AppState>,
+    Json(payload): Json<PlaceLegalHoldRequest>,
+) -> Result<(StatusCode, Json<LegalHold>), StatusCode> {
+    info!("Placing legal hold on {} for {}", payload.collection, payload.placed_by);
+
+    let hold = match &payload.filter {
+        Some(filter) => {
+            state.query
+                .place_legal_hold_on_query(&payload.collection, filter, &payload.reason, &payload.placed_by)
+                .await
+        }
+        None => state.query.place_legal_hold(
+            &payload.collection,
+            payload.document_ids,
+            &payload.reason,
+            &payload.placed_by,
+        ),
+    };
+    hold.map(|hold| (StatusCode::CREATED, Json(hold)))
+        .map_err(|e| legal_hold_error_status(&e))
+}
+
+async fn list_legal_holds(State(state): State<AppState>) -> Json<Vec<LegalHold>> {
+    Json(state.query.legal_holds())
+}
+
+async fn release_legal_hold(
+    State(state): State<AppState>,
+    Path(hold_id): Path<String>,
+    Json(payload): Json<ReleaseLegalHoldRequest>,
+) -> Result<Json<LegalHold>, StatusCode> {
+    info!("Releasing legal hold {} for {}", hold_id, payload.released_by);
+
+    state.query
+        .release_legal_hold(&hold_id, &payload.released_by)
+        .map(Json)
+        .map_err(|e| legal_hold_error_status(&e))
+}
+
+async fn enable_worm(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(payload): Json<EnableWormRequest>,
+) -> Result<Json<WormPolicy>, StatusCode> {
+    info!("Making collection {} WORM for {}s", collection, payload.retention_secs);
+
+    state.query
+        .enable_worm(&collection, std::time::Duration::from_secs(payload.retention_secs))
+        .map(Json)
+        .map_err(|e| {
+            if e.to_string().contains("can only be extended") {
+                StatusCode::CONFLICT
+            } else {
+                warn!("Failed to enable WORM on {}: {}", collection, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })
+}
+
+async fn get_worm_policy(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Json<WormPolicy>, StatusCode> {
+    state.query.worm_policy(&collection).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn set_collection_residency(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(payload): Json<SetResidencyRequest>,
+) -> Result<Json<Vec<ResidencyViolation>>, StatusCode> {
+    info!("Setting residency of collection {} to {}", collection, payload.residency);
+
+    state.query
+        .set_collection_residency(&collection, &payload.residency)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            warn!("Failed to set residency of {}: {}", collection, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+async fn set_document_residency(
+    State(state): State<AppState>,
+    Path((collection, id)): Path<(String, String)>,
+    Json(payload): Json<SetResidencyRequest>,
+) -> Result<Json<Vec<ResidencyViolation>>, StatusCode> {
+    info!("Setting residency of document {} in {} to {}", id, collection, payload.residency);
+
+    state.query
+        .set_document_residency(&collection, &id, &payload.residency)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            warn!("Failed to set residency of {}:{}: {}", collection, id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+async fn residency_report(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ResidencyViolation>>, StatusCode> {
+    state.query.residency_report().map(Json).map_err(|e| {
+        warn!("Failed to read residency report: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn get_tier_rules(State(state): State<AppState>) -> Json<Vec<TierRule>> {
+    Json(state.query.tier_rules())
+}
+
+async fn set_tier_rules(
+    State(state): State<AppState>,
+    Json(rules): Json<Vec<TierRule>>,
+) -> Result<Json<Vec<TierRule>>, StatusCode> {
+    info!("Setting {} tier migration rules", rules.len());
+
+    state.query.set_tier_rules(rules).map(Json).map_err(|e| {
+        if e.to_string().starts_with("Invalid tier rule") {
+            StatusCode::BAD_REQUEST
+        } else {
+            warn!("Failed to set tier migration rules: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })
+}
+
+async fn run_tier_migration(
+    State(state): State<AppState>,
+    Query(params): Query<TierMigrationParams>,
+) -> Result<Json<TierMigrationReport>, StatusCode> {
+    info!("Running tier migration (dry run: {})", params.dry_run);
+
+    state.query.run_tier_migration(params.dry_run).await.map(Json).map_err(|e| {
+        warn!("Tier migration failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn get_tier_migration_metrics(State(state): State<AppState>) -> Json<TierMigrationMetrics> {
+    Json(state.query.tier_migration_metrics())
+}
+
+async fn run_scrub(
+    State(state): State<AppState>,
+    Query(params): Query<ScrubParams>,
+) -> Result<Response, StatusCode> {
+    info!("Running integrity scrub (repair: {}, background: {})", params.repair, params.background);
+
+    if params.background {
+        return state.query.spawn_scrub_job(params.repair).map(job_accepted).map_err(|e| job_error_status(&e));
+    }
+    state.query.scrub(params.repair).await.map(|report| Json(report).into_response()).map_err(|e| {
+        warn!("Integrity scrub failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn get_scrub_report(State(state): State<AppState>) -> Result<Json<ScrubReport>, StatusCode> {
+    match state.query.last_scrub_report() {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Failed to read the last scrub report: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_collection_statistics(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Json<CollectionStatistics>, StatusCode> {
+    match state.query.collection_statistics(&collection) {
+        Ok(Some(statistics)) => Ok(Json(statistics)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Failed to read statistics of {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn list_compression_dictionaries(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Json<Vec<CompressionDictionary>>, StatusCode> {
+    state.query.compression_dictionaries(&collection).map(Json).map_err(|e| {
+        warn!("Failed to list compression dictionaries of {}: {}", collection, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn train_compression_dictionary(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<Response, StatusCode> {
+    info!("Training a compression dictionary for {}", collection);
+
+    match state.query.train_compression_dictionary(&collection).await {
+        Ok(Some(dictionary)) => Ok((StatusCode::CREATED, Json(dictionary)).into_response()),
+        // Too few documents, or the dictionary would not have helped
+        Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) if e.to_string().starts_with("Compression dictionaries need Zstd") => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            warn!("Failed to train a compression dictionary for {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn analyze_collections(State(state): State<AppState>) -> Result<Json<Vec<CollectionStatistics>>, StatusCode> {
+    info!("Collecting collection statistics");
+
+    state.query.analyze_collections().await.map(Json).map_err(|e| {
+        warn!("Collecting collection statistics failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn get_storage_usage(State(state): State<AppState>) -> Result<Json<StorageUsage>, StatusCode> {
+    state.query.storage_usage().map(Json).map_err(|e| {
+        warn!("Failed to measure storage usage: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn get_mirror_report(State(state): State<AppState>) -> Result<Json<MirrorReport>, StatusCode> {
+    // Only available when traffic mirroring is configured
+    match &state.mirror {
+        Some(mirror) => Ok(Json(mirror.report())),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+async fn get_cdn_stats(State(state): State<AppState>) -> Result<Json<CdnStats>, StatusCode> {
+    // Only available when CDN invalidation is configured
+    match &state.cdn {
+        Some(cdn) => Ok(Json(cdn.stats())),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Request counts per API version, deprecations, and the clients still
+/// calling deprecated endpoints
+async fn get_api_version_report(State(state): State<AppState>) -> Json<VersionReport> {
+    Json(state.versions.report())
+}
+
+async fn get_metrics_history(
+    State(state): State<AppState>,
+    Query(params): Query<MetricsHistoryParams>,
+) -> Json<MetricsHistoryResponse> {
+    Json(MetricsHistoryResponse {
+        resolution: params.resolution,
+        points: state.metrics_history.points(params.resolution, params.since),
+    })
+}
+
+async fn get_canary_report(State(state): State<AppState>) -> Result<Json<CanaryReport>, StatusCode> {
+    state.query.canary_report().map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn start_canary(
+    State(state): State<AppState>,
+    Json(payload): Json<StartCanaryRequest>,
+) -> Result<(StatusCode, Json<CanaryReport>), StatusCode> {
+    info!("Starting a canary against the {:?} planner", payload.planner);
+
+    let candidate = state.query.planner_path(payload.planner);
+    state.query.start_canary(candidate, payload.config).map(|report| (StatusCode::CREATED, Json(report))).map_err(|e| {
+        let message = e.to_string();
+        if message.starts_with("Canary already running") {
+            StatusCode::CONFLICT
+        } else if message.starts_with("Canary sample rate") {
+            StatusCode::BAD_REQUEST
+        } else {
+            warn!("Failed to start a canary: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })
+}
+
+async fn stop_canary(State(state): State<AppState>) -> Result<Json<CanaryReport>, StatusCode> {
+    info!("Stopping the query canary");
+    state.query.stop_canary().map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Queries and background jobs in progress on this node, longest running
+/// first
+async fn list_operations(State(state): State<AppState>) -> Result<Json<Vec<RunningOperation>>, StatusCode> {
+    let mut operations: Vec<RunningOperation> = state.query.running_queries().into_iter().map(Into::into).collect();
+    for job_state in [JobState::Running, JobState::Queued] {
+        let jobs = state.query.list_jobs(None, Some(job_state)).map_err(|e| job_error_status(&e))?;
+        operations.extend(jobs.into_iter().map(RunningOperation::from));
+    }
+    operations.sort_by_key(|operation| std::cmp::Reverse(operation.elapsed_ms));
+    Ok(Json(operations))
+}
+
+/// Kill a running query or background job; it stops at its next document
+/// or checkpoint
+async fn kill_operation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<RunningOperation>, StatusCode> {
+    info!("Killing operation {}", id);
+    if let Some(query) = state.query.cancel_query(&id) {
+        return Ok(Json(query.into()));
+    }
+    state.query.cancel_job(&id).map(|job| Json(job.into())).map_err(|e| job_error_status(&e))
+}
+
+/// Queries and aggregations executing on this node, longest running first
+async fn list_running_queries(State(state): State<AppState>) -> Json<Vec<RunningQuery>> {
+    Json(state.query.running_queries())
+}
+
+async fn cancel_query(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<RunningQuery>, StatusCode> {
+    info!("Cancelling query {}", id);
+    state.query.cancel_query(&id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Query counts, latency percentiles and scan ratios per collection
+async fn get_query_stats(
+    State(state): State<AppState>,
+    Query(params): Query<QueryStatsParams>,
+) -> Json<Vec<CollectionQueryStats>> {
+    let profiler = state.query.profiler();
+    Json(match params.collection {
+        Some(collection) => profiler.collection_stats(&collection).into_iter().collect(),
+        None => profiler.stats(),
+    })
+}
+
+/// Forget the query statistics and slow queries recorded so far
+async fn reset_query_stats(State(state): State<AppState>) -> StatusCode {
+    info!("Resetting query statistics");
+    state.query.profiler().reset();
+    StatusCode::NO_CONTENT
+}
+
+/// Queries that took at least the slow-query threshold, newest first
+async fn list_slow_queries(
+    State(state): State<AppState>,
+    Query(params): Query<SlowQueryParams>,
+) -> Json<Vec<SlowQuery>> {
+    Json(state.query.profiler().slow_queries(params.collection.as_deref(), params.limit))
+}
+
+/// Hottest documents and collections and most expensive queries over a
+/// sliding window
+async fn get_heavy_hitters(
+    State(state): State<AppState>,
+    Query(params): Query<HeavyHittersParams>,
+) -> Json<HeavyHittersReport> {
+    let limit = params.limit.unwrap_or(10);
+    let window_secs = params.window_secs.unwrap_or(15 * 60);
+    let window = std::time::Duration::from_secs(window_secs);
+    Json(HeavyHittersReport {
+        window_secs,
+        documents: state.query.hot_documents(limit, window),
+        collections: state.query.hot_collections(limit, window),
+        queries: state.query.profiler().expensive_queries(limit, window),
+    })
+}
+
+fn cluster_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Cluster node not found") {
+        StatusCode::NOT_FOUND
+    } else if message.starts_with("Invalid cluster node") || message.starts_with("Cannot remove the local node") {
+        StatusCode::BAD_REQUEST
+    } else {
+        warn!("Cluster membership change failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn list_cluster_nodes(State(state): State<AppState>) -> Result<Json<Vec<ClusterNode>>, StatusCode> {
+    state.query.cluster_nodes().map(Json).map_err(|e| cluster_error_status(&e))
+}
+
+/// Add a node to the hash ring once admission control lets it in: 202
+/// with the pending request while it awaits an operator's approval, 403
+/// when it is denied, 400 when it does not prove its public key
+async fn join_cluster_node(
+    State(state): State<AppState>,
+    Json(payload): Json<JoinNodeRequest>,
+) -> Result<Response, StatusCode> {
+    info!("Cluster node {} joining at {:?}", payload.id, payload.address);
+
+    let candidate = JoinCandidate {
+        node_id: payload.id.clone(),
+        public_key: payload.public_key,
+        signature: payload.signature,
+        address: payload.address.clone(),
+    };
+    match state.security.admission().admit(&candidate) {
+        Ok(AdmissionDecision::Admitted) => {}
+        Ok(decision @ AdmissionDecision::Pending(_)) => return Ok((StatusCode::ACCEPTED, Json(decision)).into_response()),
+        Err(e) => return Err(admission_error_status(&e)),
+    }
+
+    let change = MembershipChange::NodeJoined { node_id: payload.id, address: payload.address };
+    match state.query.apply_membership_change(change).await {
+        Ok(Some(job)) => Ok(job_accepted(job)),
+        Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(cluster_error_status(&e)),
+    }
+}
+
+async fn remove_cluster_node(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    info!("Cluster node {} leaving", id);
+
+    match state.query.apply_membership_change(MembershipChange::NodeLeft { node_id: id }).await {
+        Ok(Some(job)) => Ok(job_accepted(job)),
+        Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(cluster_error_status(&e)),
+    }
+}
+
+/// The cluster CA, or 404 when this node does not issue certificates
+fn certificate_authority(state: &AppState) -> Result<&Arc<CertificateAuthority>, StatusCode> {
+    state.security.certificate_authority().ok_or(StatusCode::NOT_FOUND)
+}
+
+fn ca_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Invalid certificate request") {
+        StatusCode::BAD_REQUEST
+    } else if message.starts_with("Certificate renewal refused") {
+        StatusCode::FORBIDDEN
+    } else if message.starts_with("Certificate request not found") {
+        StatusCode::NOT_FOUND
+    } else {
+        warn!("Certificate operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn get_cluster_ca(State(state): State<AppState>) -> Result<Json<CaInfo>, StatusCode> {
+    Ok(Json(certificate_authority(&state)?.info()))
+}
+
+async fn list_node_certificates(State(state): State<AppState>) -> Result<Json<Vec<EnrolledNode>>, StatusCode> {
+    Ok(Json(certificate_authority(&state)?.enrolled_nodes()))
+}
+
+/// Issue, renew or collect a joining node's certificate: 201 with the
+/// certificate, or 202 while the request awaits an operator's approval
+async fn request_node_certificate(
+    State(state): State<AppState>,
+    Json(payload): Json<CertificateRequest>,
+) -> Result<Response, StatusCode> {
+    info!("Node {} requests a certificate", payload.node_id);
+
+    let ca = certificate_authority(&state)?;
+    let candidate = JoinCandidate {
+        node_id: payload.node_id.clone(),
+        public_key: payload.public_key.clone(),
+        signature: payload.signature.clone(),
+        address: None,
+    };
+    state.security.admission().screen(&candidate).map_err(|e| admission_error_status(&e))?;
+
+    match ca.request_certificate(payload) {
+        Ok(decision @ CertificateDecision::Issued(_)) => Ok((StatusCode::CREATED, Json(decision)).into_response()),
+        Ok(decision @ CertificateDecision::Pending(_)) => Ok((StatusCode::ACCEPTED, Json(decision)).into_response()),
+        Err(e) => Err(ca_error_status(&e)),
+    }
+}
+
+async fn list_certificate_requests(State(state): State<AppState>) -> Result<Json<Vec<PendingCertificateRequest>>, StatusCode> {
+    Ok(Json(certificate_authority(&state)?.pending_requests()))
+}
+
+/// Approve a queued request; the node collects the certificate itself, so
+/// only its summary is returned
+async fn approve_certificate_request(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<EnrolledNode>), StatusCode> {
+    info!("Approving certificate request {}", id);
+    certificate_authority(&state)?
+        .approve(&id)
+        .map(|enrolled| (StatusCode::CREATED, Json(enrolled)))
+        .map_err(|e| ca_error_status(&e))
+}
+
+async fn reject_certificate_request(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Rejecting certificate request {}", id);
+    certificate_authority(&state)?.reject(&id).map(|_| StatusCode::NO_CONTENT).map_err(|e| ca_error_status(&e))
+}
+
+fn admission_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Join denied") {
+        StatusCode::FORBIDDEN
+    } else if message.starts_with("Invalid join request") || message.starts_with("Invalid access rule") {
+        StatusCode::BAD_REQUEST
+    } else if message.starts_with("Join request not found") || message.starts_with("Access rule not found") {
+        StatusCode::NOT_FOUND
+    } else {
+        warn!("Admission control operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn get_admission_lists(State(state): State<AppState>) -> Json<AccessLists> {
+    Json(state.security.admission().lists())
+}
+
+fn add_access_rule(state: &AppState, list: AccessList, payload: AccessRuleRequest) -> Result<(StatusCode, Json<AccessRule>), StatusCode> {
+    info!("Adding {:?} '{}' to the {:?} list", payload.kind, payload.value, list);
+    state.security
+        .admission()
+        .add_rule(list, payload.kind, &payload.value, payload.note)
+        .map(|rule| (StatusCode::CREATED, Json(rule)))
+        .map_err(|e| admission_error_status(&e))
+}
+
+fn remove_access_rule(state: &AppState, list: AccessList, kind: IdentityKind, value: &str) -> Result<StatusCode, StatusCode> {
+    info!("Removing {:?} '{}' from the {:?} list", kind, value, list);
+    state.security
+        .admission()
+        .remove_rule(list, kind, value)
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| admission_error_status(&e))
+}
+
+async fn allow_identity(
+    State(state): State<AppState>,
+    Json(payload): Json<AccessRuleRequest>,
+) -> Result<(StatusCode, Json<AccessRule>), StatusCode> {
+    add_access_rule(&state, AccessList::Allow, payload)
+}
+
+async fn remove_allowed_identity(
+    State(state): State<AppState>,
+    Path((kind, value)): Path<(IdentityKind, String)>,
+) -> Result<StatusCode, StatusCode> {
+    remove_access_rule(&state, AccessList::Allow, kind, &value)
+}
+
+/// Deny an identity; its pending join requests are discarded
+async fn deny_identity(
+    State(state): State<AppState>,
+    Json(payload): Json<AccessRuleRequest>,
+) -> Result<(StatusCode, Json<AccessRule>), StatusCode> {
+    add_access_rule(&state, AccessList::Deny, payload)
+}
+
+async fn remove_denied_identity(
+    State(state): State<AppState>,
+    Path((kind, value)): Path<(IdentityKind, String)>,
+) -> Result<StatusCode, StatusCode> {
+    remove_access_rule(&state, AccessList::Deny, kind, &value)
+}
+
+async fn list_join_requests(State(state): State<AppState>) -> Json<Vec<PendingJoin>> {
+    Json(state.security.admission().pending())
+}
+
+/// Approve a queued join by allowlisting the node; it is admitted when it
+/// asks again
+async fn approve_join_request(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<AccessRule>), StatusCode> {
+    info!("Approving join request {}", id);
+    state.security
+        .admission()
+        .approve(&id)
+        .map(|rule| (StatusCode::CREATED, Json(rule)))
+        .map_err(|e| admission_error_status(&e))
+}
+
+async fn reject_join_request(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    info!("Rejecting join request {}", id);
+    state.security.admission().reject(&id).map(|_| StatusCode::NO_CONTENT).map_err(|e| admission_error_status(&e))
+}
+
+/// Refused join attempts by denied nodes, most recent first
+async fn list_denied_joins(State(state): State<AppState>) -> Json<Vec<DeniedJoinAttempts>> {
+    Json(state.security.admission().denied_attempts())
+}
+
+async fn get_rebalance_plan(State(state): State<AppState>) -> Result<Json<RebalancePlan>, StatusCode> {
+    state.query.plan_rebalance().await.map(Json).map_err(|e| {
+        warn!("Failed to plan a rebalance: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn run_rebalance(State(state): State<AppState>) -> Result<Response, StatusCode> {
+    info!("Starting a rebalance");
+    state.query.spawn_rebalance_job().map(job_accepted).map_err(|e| job_error_status(&e))
+}
+
+async fn accept_transferred_document(
+    State(state): State<AppState>,
+    Json(document): Json<TransferredDocument>,
+) -> Result<StatusCode, StatusCode> {
+    let key = document.key.clone();
+    match state.query.accept_transferred_document(document).await {
+        Ok(true) => Ok(StatusCode::CREATED),
+        Ok(false) => Ok(StatusCode::OK),
+        Err(e) if e.to_string().starts_with("Invalid transferred document") => {
+            warn!("Refused transferred document {}: {}", key, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(e) if e.to_string().starts_with("Storage quota exceeded") => Err(StatusCode::INSUFFICIENT_STORAGE),
+        Err(e) => {
+            warn!("Failed to store transferred document {}: {}", key, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Answer a query another cluster member is scattering, from the documents
+/// this node holds the primary copy of.
+async fn answer_peer_query(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    Json(query): Json<aerolithdb_query::QueryRequest>,
+) -> Result<Json<PartialQueryResult>, StatusCode> {
+    match state.query.query_primary_copies(&collection, &query).await {
+        Ok(result) => Ok(Json(PartialQueryResult::from(result))),
+        Err(e) if e.to_string().starts_with("Invalid filter") || e.to_string().starts_with("Invalid cursor") => {
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(e) => {
+            warn!("Failed to answer a peer query on {}: {}", collection, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Serve another cluster member's request against this node's shard of the
+/// network cache.
+async fn answer_cache_peer_request(
+    State(state): State<AppState>,
+    Json(request): Json<CachePeerRequest>,
+) -> Result<Json<CachePeerResponse>, StatusCode> {
+    match state.query.handle_cache_peer_request(request) {
+        Ok(response) => Ok(Json(response)),
+        Err(e) if e.to_string().starts_with("Network cache layer is not part") => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("Failed to answer a cache peer request: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn backup_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Backup not found") {
+        StatusCode::NOT_FOUND
+    } else if message.starts_with("Invalid backup id")
+        || message.starts_with("Invalid backup scope")
+        || message.starts_with("Invalid restore namespace")
+    {
+        StatusCode::BAD_REQUEST
+    } else if message.starts_with("Cannot delete backup")
+        || message.starts_with("Incremental backup needs")
+        || message.starts_with("Restore namespace is not empty")
+    {
+        StatusCode::CONFLICT
+    } else {
+        warn!("Backup operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+async fn create_backup(
+    State(state): State<AppState>,
+    Query(params): Query<CreateBackupParams>,
+) -> Result<Response, StatusCode> {
+    let kind = if params.incremental { BackupKind::Incremental } else { BackupKind::Full };
+    info!("Creating {:?} backup of {}", kind, params.scope.as_deref().unwrap_or("all collections"));
+
+    if params.background {
+        return state.query.spawn_backup_job(kind, params.scope, None).map(job_accepted).map_err(|e| job_error_status(&e));
+    }
+    state
+        .query
+        .create_scoped_backup(kind, params.scope.as_deref())
+        .await
+        .map(|manifest| (StatusCode::CREATED, Json(manifest)).into_response())
+        .map_err(|e| backup_error_status(&e))
+}
+
+async fn list_backups(State(state): State<AppState>) -> Result<Json<Vec<BackupManifest>>, StatusCode> {
+    state.query.list_backups().await.map(Json).map_err(|e| backup_error_status(&e))
+}
+
+async fn get_backup(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<BackupManifest>, StatusCode> {
+    state.query.backup_manifest(&id).await.map(Json).map_err(|e| backup_error_status(&e))
+}
+
+async fn delete_backup(State(state): State<AppState>, Path(id): Path<String>) -> Result<StatusCode, StatusCode> {
+    info!("Deleting backup {}", id);
+
+    state
+        .query
+        .delete_backup(&id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| backup_error_status(&e))
+}
+
+async fn restore_backup(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<RestoreIntoParams>,
+) -> Result<Response, StatusCode> {
+    if params.background {
+        info!("Restoring backup {} in a background job", id);
+        return state.query.spawn_restore_job(&id, params.namespace, None).map(job_accepted).map_err(|e| job_error_status(&e));
+    }
+    let restored = match &params.namespace {
+        Some(namespace) => {
+            info!("Restoring backup {} into namespace {}", id, namespace);
+            state.query.restore_backup_into(&id, namespace).await
+        }
+        None => {
+            info!("Restoring backup {}", id);
+            state.query.restore_backup(&id).await
+        }
+    };
+    restored.map(|report| Json(report).into_response()).map_err(|e| backup_error_status(&e))
+}
+
+async fn restore_backup_at(
+    State(state): State<AppState>,
+    Query(params): Query<RestoreParams>,
+) -> Result<Json<RestoreReport>, StatusCode> {
+    info!("Restoring the latest backup taken at or before {}", params.at);
+
+    state.query.restore_backup_at(params.at).await.map(Json).map_err(|e| backup_error_status(&e))
+}
+
+fn job_error_status(e: &anyhow::Error) -> StatusCode {
+    let message = e.to_string();
+    if message.starts_with("Job not found") {
+        StatusCode::NOT_FOUND
+    } else if message.starts_with("Job already finished") {
+        StatusCode::CONFLICT
+    } else {
+        warn!("Job operation failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// Answer a request that started a background job.
+fn job_accepted(job: Job) -> Response {
+    (StatusCode::ACCEPTED, Json(job)).into_response()
+}
+
+async fn list_jobs(
+    State(state): State<AppState>,
+    Query(params): Query<JobListParams>,
+) -> Result<Json<Vec<Job>>, StatusCode> {
+    state.query.list_jobs(params.tenant.as_deref(), params.state).map(Json).map_err(|e| job_error_status(&e))
+}
+
+async fn get_job(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<Job>, StatusCode> {
+    state.query.job(&id).map(Json).map_err(|e| job_error_status(&e))
+}
+
+async fn cancel_job(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<Job>, StatusCode> {
+    info!("Cancelling job {}", id);
+
+    state.query.cancel_job(&id).map(Json).map_err(|e| job_error_status(&e))
+}
+
+async fn stream_job_events(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<JobEventParams>,
+    headers: HeaderMap,
+) -> Result<EventStream, StatusCode> {
+    let resume_point = sse::resume_point(&headers, params.resume_token);
+    sse::job_stream(Arc::clone(&state.query), id, resume_point.as_deref()).map_err(|e| {
+        if e.to_string().starts_with("Invalid event ID") {
+            StatusCode::BAD_REQUEST
+        } else {
+            job_error_status(&e)
+        }
+    })
+}
+
+async fn legal_hold_audit_log(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<LegalHoldEvent>>, StatusCode> {
+    state.query
+        .legal_hold_audit_log()
+        .map(Json)
+        .map_err(|e| legal_hold_error_status(&e))
+}
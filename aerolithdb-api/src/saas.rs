@@ -4,9 +4,9 @@
 //! billing operations, quota monitoring, SSO integration, and administrative operations.
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Extension, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
     routing::{get, post, put, delete},
     Router,
 };
@@ -22,8 +22,13 @@ use aerolithdb_saas::{
     SaaSManager, CreateTenantRequest, UpdateTenantRequest, Tenant, TenantUsage,
     SSOAuthRequest, SSOAuthResponse, UsageStatistics, Invoice, BillingInfo,
     QuotaViolation, ProvisioningRequest, AnalyticsQuery, SaaSStatus,
-    LiveUsageStats, TenantContext, AuthContext, saas_auth_middleware
+    LiveUsageStats, TenantContext, AuthContext, saas_auth_middleware,
+    ImpersonationRequest, ImpersonationGrant, ImpersonationAuditEvent, ImpersonationError,
+    has_role, PLATFORM_OPERATOR_ROLE, UsageForecast, UsageAlert, AlertChannel, QuotaError,
+    BillingEstimate, UsageProfile, ExchangeRateSnapshot,
+    TenantBackupManager, TenantBackupSchedule, TenantBackupStatus, TenantRestoreRequest, TenantBackupError
 };
+use aerolithdb_query::{BackupManifest, Job, RestoreReport};
 */
 
 /// AppState for SaaS endpoints
@@ -55,14 +60,30 @@ pub fn saas_routes() -> Router<SaaSAppState> {
         // Billing endpoints
         .route("/billing/invoices", get(list_invoices))
         .route("/billing/tenants/:tenant_id/invoices", get(get_tenant_invoices))
+        .route("/billing/tenants/:tenant_id/invoices/export", get(export_tenant_invoices))
         .route("/billing/tenants/:tenant_id/balance", get(get_tenant_balance))
         .route("/billing/pricing", get(get_pricing_tiers))
+        .route("/billing/exchange-rates", get(get_exchange_rates))
         .route("/billing/calculate", post(calculate_billing))
+        .route("/billing/tenants/:tenant_id/estimate", get(estimate_tenant_billing))
+        .route("/billing/estimate", post(simulate_billing))
         
         // Quota management endpoints
         .route("/quotas/check", post(check_quota))
         .route("/quotas/violations", get(get_quota_violations))
         .route("/quotas/tenants/:tenant_id/status", get(get_tenant_quota_status))
+        .route("/usage/forecast", get(get_usage_forecast))
+        .route("/usage/alerts", get(list_usage_alerts))
+        .route("/usage/alerts/channels", get(get_alert_channels))
+        .route("/usage/alerts/channels", put(set_alert_channels))
+        
+        // Tenant backup and job endpoints
+        .route("/backups", get(list_tenant_backups))
+        .route("/backups/schedule", get(get_backup_schedule))
+        .route("/backups/schedule", put(set_backup_schedule))
+        .route("/backups/schedule", delete(remove_backup_schedule))
+        .route("/backups/:backup_id/restore", post(restore_tenant_backup))
+        .route("/jobs", get(list_tenant_jobs))
         
         // SSO endpoints
         .route("/sso/providers", get(list_sso_providers))
@@ -85,6 +106,10 @@ pub fn saas_routes() -> Router<SaaSAppState> {
         .route("/admin/health", get(saas_health_check))
         .route("/admin/status", get(get_saas_status))
         .route("/admin/metrics", get(get_admin_metrics))
+        .route("/admin/impersonations", post(start_impersonation))
+        .route("/admin/impersonations", get(list_impersonations))
+        .route("/admin/impersonations/audit", get(get_impersonation_audit))
+        .route("/admin/impersonations/:grant_id", delete(revoke_impersonation))
 }
 
 // ============================================================================
@@ -250,6 +275,26 @@ async fn get_tenant_invoices(
     }
 }
 
+/// Invoices of a tenant as CSV, with currency, exchange rate and tax details
+async fn export_tenant_invoices(
+    State(state): State<SaaSAppState>,
+    Path(tenant_id): Path<Uuid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    match state.saas_manager.billing_engine().export_tenant_invoices(tenant_id).await {
+        Ok(csv) => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"invoices-{}.csv\"", tenant_id)),
+            ],
+            csv,
+        )),
+        Err(e) => {
+            error!("Failed to export invoices of tenant {}: {}", tenant_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct TenantBalance {
     tenant_id: Uuid,
@@ -328,6 +373,20 @@ async fn get_pricing_tiers(
     Ok(Json(pricing))
 }
 
+/// Current exchange rates of the billing currency into the supported
+/// invoice currencies
+async fn get_exchange_rates(
+    State(state): State<SaaSAppState>,
+) -> Result<Json<ExchangeRateSnapshot>, StatusCode> {
+    match state.saas_manager.billing_engine().exchange_rates().await {
+        Ok(rates) => Ok(Json(rates)),
+        Err(e) => {
+            warn!("Exchange rates unavailable: {}", e);
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct CalculateBillingRequest {
     tenant_id: Uuid,
@@ -355,6 +414,47 @@ async fn calculate_billing(
     Ok(Json(calculation))
 }
 
+#[derive(Deserialize)]
+struct EstimateBillingQuery {
+    lookback_days: Option<i64>,
+}
+
+/// Simulate a tenant's recent usage under every pricing tier
+async fn estimate_tenant_billing(
+    State(state): State<SaaSAppState>,
+    Path(tenant_id): Path<Uuid>,
+    Query(params): Query<EstimateBillingQuery>,
+) -> Result<Json<BillingEstimate>, StatusCode> {
+    let lookback_days = params.lookback_days.unwrap_or(30);
+    if !(1..=365).contains(&lookback_days) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    
+    match state.saas_manager.billing_engine()
+        .estimate_plans(tenant_id, chrono::Duration::days(lookback_days)).await {
+        Ok(estimate) => Ok(Json(estimate)),
+        Err(e) if e.to_string().contains("Tenant not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to estimate billing of tenant {}: {}", tenant_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SimulateBillingRequest {
+    profile: UsageProfile,
+    current_tier: Option<String>,
+}
+
+/// Simulate a hypothetical monthly usage profile under every pricing tier
+async fn simulate_billing(
+    State(state): State<SaaSAppState>,
+    Json(request): Json<SimulateBillingRequest>,
+) -> Result<Json<BillingEstimate>, StatusCode> {
+    Ok(Json(state.saas_manager.billing_engine().simulate_plans(request.profile, request.current_tier)))
+}
+
 // ============================================================================
 // Quota Management Endpoints
 // ============================================================================
@@ -436,6 +536,164 @@ async fn get_tenant_quota_status(
     }
 }
 
+/// Forecast when the caller's tenant will exhaust its quotas
+async fn get_usage_forecast(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<UsageForecast>, StatusCode> {
+    match state.saas_manager.quota_manager().usage_forecast(auth.tenant.tenant_id).await {
+        Ok(forecast) => Ok(Json(forecast)),
+        Err(e) if e.to_string().contains("Tenant not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to forecast usage of tenant {}: {}", auth.tenant.tenant_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Recent usage alerts of the caller's tenant
+async fn list_usage_alerts(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<UsageAlert>>, StatusCode> {
+    Ok(Json(state.saas_manager.quota_manager().alerts().alerts(auth.tenant.tenant_id).await))
+}
+
+/// Channels the caller's tenant receives usage alerts on
+async fn get_alert_channels(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<AlertChannel>>, StatusCode> {
+    Ok(Json(state.saas_manager.quota_manager().alerts().channels(&auth.tenant).await))
+}
+
+/// Replace the usage alert channels of the caller's tenant
+async fn set_alert_channels(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(channels): Json<Vec<AlertChannel>>,
+) -> Result<Json<Vec<AlertChannel>>, StatusCode> {
+    if !has_role(&auth, "admin") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let alerts = state.saas_manager.quota_manager().alerts();
+    match alerts.set_channels(auth.tenant.tenant_id, channels).await {
+        Ok(()) => Ok(Json(alerts.channels(&auth.tenant).await)),
+        Err(QuotaError::InvalidAlertChannel { message }) => {
+            warn!("⚠️ Rejected alert channels of tenant {}: {}", auth.tenant.tenant_id, message);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// ============================================================================
+// Tenant Backup Endpoints
+// ============================================================================
+
+/// Tenant backups, or 503 when no backup store is attached
+fn tenant_backups(state: &SaaSAppState) -> Result<&std::sync::Arc<TenantBackupManager>, StatusCode> {
+    state.saas_manager.tenant_backups().ok_or(StatusCode::SERVICE_UNAVAILABLE)
+}
+
+fn tenant_backup_error_status(tenant_id: Uuid, e: &TenantBackupError) -> StatusCode {
+    match e {
+        TenantBackupError::Disabled => StatusCode::SERVICE_UNAVAILABLE,
+        TenantBackupError::InvalidSchedule { .. } | TenantBackupError::InvalidNamespace { .. } => StatusCode::BAD_REQUEST,
+        TenantBackupError::BackupNotFound { .. } => StatusCode::NOT_FOUND,
+        TenantBackupError::NamespaceNotEmpty { .. } => StatusCode::CONFLICT,
+        TenantBackupError::Failed { .. } => {
+            error!("Backup operation of tenant {} failed: {}", tenant_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Backups of the caller's tenant, oldest first
+async fn list_tenant_backups(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<BackupManifest>>, StatusCode> {
+    let tenant_id = auth.tenant.tenant_id;
+    tenant_backups(&state)?
+        .backups(tenant_id)
+        .await
+        .map(Json)
+        .map_err(|e| tenant_backup_error_status(tenant_id, &e))
+}
+
+/// Background jobs of the caller's tenant, newest first
+async fn list_tenant_jobs(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<Vec<Job>>, StatusCode> {
+    let tenant_id = auth.tenant.tenant_id;
+    tenant_backups(&state)?
+        .jobs(tenant_id)
+        .await
+        .map(Json)
+        .map_err(|e| tenant_backup_error_status(tenant_id, &e))
+}
+
+/// Backup schedule of the caller's tenant and its last outcome
+async fn get_backup_schedule(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<Json<TenantBackupStatus>, StatusCode> {
+    Ok(Json(tenant_backups(&state)?.status(auth.tenant.tenant_id).await))
+}
+
+/// Set the backup schedule of the caller's tenant
+async fn set_backup_schedule(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+    Json(schedule): Json<TenantBackupSchedule>,
+) -> Result<Json<TenantBackupStatus>, StatusCode> {
+    if !has_role(&auth, "admin") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let tenant_id = auth.tenant.tenant_id;
+    tenant_backups(&state)?
+        .set_schedule(tenant_id, schedule)
+        .await
+        .map(Json)
+        .map_err(|e| tenant_backup_error_status(tenant_id, &e))
+}
+
+/// Stop scheduled backups of the caller's tenant, keeping its backups
+async fn remove_backup_schedule(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+) -> Result<StatusCode, StatusCode> {
+    if !has_role(&auth, "admin") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    tenant_backups(&state)?.remove_schedule(auth.tenant.tenant_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restore a backup of the caller's tenant into a new namespace
+async fn restore_tenant_backup(
+    State(state): State<SaaSAppState>,
+    Extension(auth): Extension<AuthContext>,
+    Path(backup_id): Path<String>,
+    Json(request): Json<TenantRestoreRequest>,
+) -> Result<Json<RestoreReport>, StatusCode> {
+    if !has_role(&auth, "admin") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let tenant_id = auth.tenant.tenant_id;
+    info!("♻️ Tenant {} restores backup {} into namespace {}", tenant_id, backup_id, request.namespace);
+    tenant_backups(&state)?
+        .restore(tenant_id, &backup_id, &request)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            warn!("⚠️ Restore of backup {} for tenant {} failed: {}", backup_id, tenant_id, e);
+            tenant_backup_error_status(tenant_id, &e)
+        })
+}
+
 // ============================================================================
 // SSO Endpoints
 // ============================================================================
@@ -695,6 +953,87 @@ async fn get_saas_status(
     Ok(Json(status))
 }
 
+#[derive(Deserialize)]
+struct ImpersonationQuery {
+    tenant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonationResponse {
+    pub token: String,
+    pub grant: ImpersonationGrant,
+}
+
+/// Status for a failed impersonation operation
+fn impersonation_error_status(error: &anyhow::Error) -> StatusCode {
+    match error.downcast_ref::<ImpersonationError>() {
+        Some(ImpersonationError::NotPermitted { .. } | ImpersonationError::ConsentRequired { .. }) => StatusCode::FORBIDDEN,
+        Some(ImpersonationError::InvalidRequest { .. }) => StatusCode::BAD_REQUEST,
+        Some(ImpersonationError::GrantNotFound { .. }) => StatusCode::NOT_FOUND,
+        None if error.to_string().contains("Tenant not found") => StatusCode::NOT_FOUND,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Issue a time-boxed impersonation token for a platform operator
+async fn start_impersonation(
+    State(state): State<SaaSAppState>,
+    Extension(operator): Extension<AuthContext>,
+    Json(request): Json<ImpersonationRequest>,
+) -> Result<Json<ImpersonationResponse>, StatusCode> {
+    match state.saas_manager.auth_manager().start_impersonation(&operator, request).await {
+        Ok((token, grant)) => Ok(Json(ImpersonationResponse { token, grant })),
+        Err(e) => {
+            warn!("⚠️ Impersonation refused for {}: {}", operator.session.user_id, e);
+            Err(impersonation_error_status(&e))
+        }
+    }
+}
+
+/// List impersonation grants, optionally of one tenant
+async fn list_impersonations(
+    State(state): State<SaaSAppState>,
+    Extension(operator): Extension<AuthContext>,
+    Query(params): Query<ImpersonationQuery>,
+) -> Result<Json<Vec<ImpersonationGrant>>, StatusCode> {
+    let tenant_id = impersonation_scope(&operator, params.tenant_id)?;
+    Ok(Json(state.saas_manager.auth_manager().list_impersonations(tenant_id).await))
+}
+
+/// Impersonation audit trail, optionally of one tenant
+async fn get_impersonation_audit(
+    State(state): State<SaaSAppState>,
+    Extension(operator): Extension<AuthContext>,
+    Query(params): Query<ImpersonationQuery>,
+) -> Result<Json<Vec<ImpersonationAuditEvent>>, StatusCode> {
+    let tenant_id = impersonation_scope(&operator, params.tenant_id)?;
+    Ok(Json(state.saas_manager.auth_manager().impersonation_audit_log(tenant_id).await))
+}
+
+/// End an impersonation grant early
+async fn revoke_impersonation(
+    State(state): State<SaaSAppState>,
+    Extension(operator): Extension<AuthContext>,
+    Path(grant_id): Path<Uuid>,
+) -> Result<Json<ImpersonationGrant>, StatusCode> {
+    state.saas_manager.auth_manager().revoke_impersonation(&operator, grant_id).await
+        .map(Json)
+        .map_err(|e| impersonation_error_status(&e))
+}
+
+/// Tenants whose impersonations a caller may see: platform operators see
+/// any, tenant admins only their own
+fn impersonation_scope(operator: &AuthContext, tenant_id: Option<Uuid>) -> Result<Option<Uuid>, StatusCode> {
+    if has_role(operator, PLATFORM_OPERATOR_ROLE) {
+        return Ok(tenant_id);
+    }
+    let own_tenant = operator.session.tenant_id;
+    if has_role(operator, "admin") && tenant_id.is_none_or(|tenant_id| tenant_id == own_tenant) {
+        return Ok(Some(own_tenant));
+    }
+    Err(StatusCode::FORBIDDEN)
+}
+
 async fn get_admin_metrics(
     State(_state): State<SaaSAppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
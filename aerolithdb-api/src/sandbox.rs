@@ -0,0 +1,418 @@
+//! # Public Sandbox Mode
+//!
+//! Lets a node be exposed as a public "try AerolithDB" endpoint. With sandbox
+//! mode enabled on the REST API:
+//!
+//! - ✅ Requests need no credentials and are attributed to a shared demo tenant
+//! - ✅ Each client address is rate limited with a small token bucket
+//! - ✅ Only document CRUD, queries, and health checks are reachable; system
+//!   collections and administrative routes are refused
+//! - ✅ Tight quotas cap collections, documents per collection, and body size
+//! - ✅ Collections are deleted automatically once they reach their lifetime
+//!
+//! Sandbox collections are registered in a system collection so expiry keeps
+//! working across restarts.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use aerolithdb_query::QueryEngine;
+
+use crate::middleware::SaaSContext;
+
+/// Demo tenant every sandbox request is attributed to
+pub const SANDBOX_TENANT_ID: Uuid = Uuid::from_u128(0x5341_4e44_424f_5800_0000_0000_0000_0001);
+
+/// System collection recording when each sandbox collection was created
+pub const SANDBOX_COLLECTION: &str = "_sandbox_collections";
+
+/// Interval between sandbox expiry sweeps
+pub const SANDBOX_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Limits applied to anonymous sandbox traffic
+#[derive(Debug, Clone)]
+pub struct SandboxConfig {
+    /// Sustained requests per minute allowed from one client address
+    pub requests_per_minute: u32,
+
+    /// Requests a client may make in a burst before throttling starts
+    pub burst: u32,
+
+    /// Collections that may exist at once across all sandbox users
+    pub max_collections: usize,
+
+    /// Documents allowed in a single collection
+    pub max_documents_per_collection: usize,
+
+    /// Largest accepted request body for writes
+    pub max_document_bytes: usize,
+
+    /// Lifetime of a collection, measured from its first write
+    pub collection_ttl: Duration,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 60,
+            burst: 20,
+            max_collections: 50,
+            max_documents_per_collection: 100,
+            max_document_bytes: 16 * 1024,
+            collection_ttl: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Registry entry for a sandbox collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SandboxCollection {
+    collection: String,
+    created_at: DateTime<Utc>,
+}
+
+/// What a sandbox request is allowed to do
+#[derive(Debug, PartialEq)]
+enum SandboxRoute {
+    /// Health checks and reads; rate limited only
+    ReadOnly,
+    /// A write that creates a new document
+    Create { collection: String },
+    /// A write to a named document, which may create it
+    Upsert { collection: String, id: String },
+}
+
+pub(crate) struct TokenBucket {
+    tokens: f64,
+    pub(crate) last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn full(capacity: f64, now: Instant) -> Self {
+        Self { tokens: capacity, last_refill: now }
+    }
+
+    /// Refill for the time elapsed, then take a token or report how long until one is available
+    pub(crate) fn take(&mut self, now: Instant, per_second: f64, capacity: f64) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+/// Enforces sandbox rate limits, quotas, and collection expiry
+pub struct SandboxGuard {
+    config: SandboxConfig,
+    query: Arc<QueryEngine>,
+    buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+    /// Collection name -> creation time, loaded from storage on first use
+    collections: RwLock<Option<HashMap<String, DateTime<Utc>>>>,
+}
+
+impl std::fmt::Debug for SandboxGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxGuard")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SandboxGuard {
+    pub fn new(config: SandboxConfig, query: Arc<QueryEngine>) -> Self {
+        info!(
+            "Sandbox mode enabled: {} req/min per client, {} collections of up to {} documents, {}s collection lifetime",
+            config.requests_per_minute,
+            config.max_collections,
+            config.max_documents_per_collection,
+            config.collection_ttl.as_secs()
+        );
+        Self {
+            config,
+            query,
+            buckets: Mutex::new(HashMap::new()),
+            collections: RwLock::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &SandboxConfig {
+        &self.config
+    }
+
+    /// Take a token for the client, or return how long to wait for one
+    fn check_rate(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let per_second = self.config.requests_per_minute.max(1) as f64 / 60.0;
+        let capacity = self.config.burst.max(1) as f64;
+
+        let mut buckets = self.buckets.lock().expect("sandbox rate limiter poisoned");
+        buckets.entry(client)
+            .or_insert_with(|| TokenBucket::full(capacity, now))
+            .take(now, per_second, capacity)
+    }
+
+    /// Admit a write to a collection, registering the collection on first use
+    async fn admit_write(&self, route: &SandboxRoute) -> Result<(), StatusCode> {
+        let (collection, id) = match route {
+            SandboxRoute::ReadOnly => return Ok(()),
+            SandboxRoute::Create { collection } => (collection, None),
+            SandboxRoute::Upsert { collection, id } => (collection, Some(id)),
+        };
+
+        self.ensure_registry_loaded().await.map_err(|e| {
+            warn!("Failed to load sandbox collection registry: {}", e);
+            StatusCode::SERVICE_UNAVAILABLE
+        })?;
+
+        {
+            let mut registry = self.collections.write().await;
+            let registry = registry.as_mut().expect("registry loaded above");
+            self.register_collection(registry, collection).await?;
+        }
+
+        // Overwriting an existing document does not count against the quota
+        if let Some(id) = id {
+            if self.query.get_document(collection, id).await.is_ok() {
+                return Ok(());
+            }
+        }
+
+        let count = self.query.count_documents(collection).await.unwrap_or(0);
+        if count >= self.config.max_documents_per_collection {
+            debug!("Sandbox collection {} is full ({} documents)", collection, count);
+            return Err(StatusCode::TOO_MANY_REQUESTS);
+        }
+
+        Ok(())
+    }
+
+    /// Record a collection's creation time the first time it is written to
+    async fn register_collection(
+        &self,
+        registry: &mut HashMap<String, DateTime<Utc>>,
+        collection: &str,
+    ) -> Result<(), StatusCode> {
+        if registry.contains_key(collection) {
+            return Ok(());
+        }
+        if registry.len() >= self.config.max_collections {
+            debug!("Sandbox collection limit reached, refusing {}", collection);
+            return Err(StatusCode::TOO_MANY_REQUESTS);
+        }
+
+        let entry = SandboxCollection {
+            collection: collection.to_string(),
+            created_at: Utc::now(),
+        };
+        let document = serde_json::to_value(&entry).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        self.query.store_document(SANDBOX_COLLECTION, collection, &document).await.map_err(|e| {
+            warn!("Failed to register sandbox collection {}: {}", collection, e);
+            StatusCode::SERVICE_UNAVAILABLE
+        })?;
+        registry.insert(entry.collection, entry.created_at);
+        Ok(())
+    }
+
+    /// Delete collections that have outlived the configured lifetime
+    pub async fn expire_collections(&self) -> Result<usize> {
+        self.ensure_registry_loaded().await?;
+
+        let ttl = chrono::Duration::from_std(self.config.collection_ttl)?;
+        let cutoff = Utc::now() - ttl;
+        let expired: Vec<String> = self.collections.read().await
+            .as_ref()
+            .map(|registry| {
+                registry.iter()
+                    .filter(|(_, created_at)| **created_at <= cutoff)
+                    .map(|(collection, _)| collection.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for collection in &expired {
+            let removed = self.query.drop_collection(collection).await?;
+            // Unregister only after the data is gone, so a failed sweep is retried
+            let _ = self.query.delete_document(SANDBOX_COLLECTION, collection).await;
+            if let Some(registry) = self.collections.write().await.as_mut() {
+                registry.remove(collection);
+            }
+            info!("Expired sandbox collection {} ({} documents)", collection, removed);
+        }
+
+        // Forget clients whose buckets have fully refilled
+        let idle_after = Duration::from_secs(60);
+        self.buckets.lock().expect("sandbox rate limiter poisoned")
+            .retain(|_, bucket| bucket.last_refill.elapsed() < idle_after);
+
+        Ok(expired.len())
+    }
+
+    async fn ensure_registry_loaded(&self) -> Result<()> {
+        if self.collections.read().await.is_some() {
+            return Ok(());
+        }
+
+        let stored = self.query.list_documents(SANDBOX_COLLECTION, None, None).await?;
+        let mut registry = HashMap::new();
+        for document in stored.documents {
+            match serde_json::from_value::<SandboxCollection>(document) {
+                Ok(entry) => {
+                    registry.insert(entry.collection, entry.created_at);
+                }
+                Err(e) => warn!("Skipping malformed sandbox registry entry: {}", e),
+            }
+        }
+
+        let mut collections = self.collections.write().await;
+        if collections.is_none() {
+            *collections = Some(registry);
+        }
+        Ok(())
+    }
+}
+
+/// Map a request onto the sandbox's allowed surface
+fn classify_route(method: &Method, path: &str) -> Result<SandboxRoute, StatusCode> {
+    if path == "/health" && method == Method::GET {
+        return Ok(SandboxRoute::ReadOnly);
+    }
+
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    let (collection, rest) = match segments.as_slice() {
+        ["", "api", "v1" | "v2", "collections", collection, rest @ ..] => (*collection, rest),
+        _ => return Err(StatusCode::FORBIDDEN),
+    };
+
+    // System collections are never reachable from the sandbox
+    if collection.is_empty() || collection.starts_with('_') {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let collection = collection.to_string();
+
+    match (method, rest) {
+        (&Method::GET, ["documents"]) | (&Method::POST, ["query" | "aggregate"]) => Ok(SandboxRoute::ReadOnly),
+        (&Method::GET, ["documents", _]) | (&Method::DELETE, ["documents", _]) => Ok(SandboxRoute::ReadOnly),
+        (&Method::POST, ["documents"]) => Ok(SandboxRoute::Create { collection }),
+        (&Method::PUT, ["documents", id]) => Ok(SandboxRoute::Upsert { collection, id: id.to_string() }),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// REST middleware applying sandbox limits to every request
+pub async fn sandbox_middleware(
+    State(guard): State<Arc<SandboxGuard>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    // Requires the server to be started with connect info; fall back to one shared bucket
+    let client = request.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+    if let Err(retry_after) = guard.check_rate(client, Instant::now()) {
+        debug!("Sandbox rate limit exceeded for {}", client);
+        let seconds = retry_after.as_secs().max(1).to_string();
+        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, seconds)]).into_response();
+    }
+
+    let route = match classify_route(request.method(), request.uri().path()) {
+        Ok(route) => route,
+        Err(status) => return status.into_response(),
+    };
+
+    if route != SandboxRoute::ReadOnly {
+        let limit = guard.config.max_document_bytes;
+        let declared = request.headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+        if declared.map(|length| length > limit).unwrap_or(false) {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+
+        // Buffer the body so undeclared or chunked uploads are bounded too
+        let (parts, body) = request.into_parts();
+        let bytes = match axum::body::to_bytes(body, limit).await {
+            Ok(bytes) => bytes,
+            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        };
+        request = Request::from_parts(parts, Body::from(bytes));
+
+        if let Err(status) = guard.admit_write(&route).await {
+            return status.into_response();
+        }
+    }
+
+    request.extensions_mut().insert(SaaSContext {
+        tenant_id: Some(SANDBOX_TENANT_ID),
+        user_id: None,
+        organization_domain: None,
+        subscription_tier: Some("sandbox".to_string()),
+        authenticated: false,
+    });
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_classification() {
+        assert_eq!(classify_route(&Method::GET, "/health"), Ok(SandboxRoute::ReadOnly));
+        assert_eq!(
+            classify_route(&Method::POST, "/api/v1/collections/notes/documents"),
+            Ok(SandboxRoute::Create { collection: "notes".to_string() })
+        );
+        assert_eq!(
+            classify_route(&Method::PUT, "/api/v1/collections/notes/documents/n1"),
+            Ok(SandboxRoute::Upsert { collection: "notes".to_string(), id: "n1".to_string() })
+        );
+        assert_eq!(classify_route(&Method::POST, "/api/v1/collections/notes/query"), Ok(SandboxRoute::ReadOnly));
+        assert_eq!(classify_route(&Method::POST, "/api/v1/collections/notes/aggregate"), Ok(SandboxRoute::ReadOnly));
+        assert_eq!(classify_route(&Method::GET, "/api/v2/collections/notes/documents"), Ok(SandboxRoute::ReadOnly));
+
+        assert_eq!(classify_route(&Method::GET, "/api/v1/collections/_sessions/documents"), Err(StatusCode::FORBIDDEN));
+        assert_eq!(classify_route(&Method::GET, "/api/v1/stats"), Err(StatusCode::FORBIDDEN));
+        assert_eq!(classify_route(&Method::POST, "/api/v1/locks/leader/acquire"), Err(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn test_token_bucket_throttles_after_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::full(3.0, start);
+
+        // One token per second, burst of three
+        for _ in 0..3 {
+            assert!(bucket.take(start, 1.0, 3.0).is_ok());
+        }
+        let retry_after = bucket.take(start, 1.0, 3.0).unwrap_err();
+        assert!(retry_after <= Duration::from_secs(1));
+
+        assert!(bucket.take(start + Duration::from_secs(1), 1.0, 3.0).is_ok());
+        assert!(bucket.take(start + Duration::from_secs(1), 1.0, 3.0).is_err());
+    }
+}
@@ -0,0 +1,363 @@
+//! # Server-Sent Events
+//!
+//! Some networks block WebSocket upgrades but pass ordinary HTTP responses,
+//! so the REST API also streams change events and job progress as
+//! Server-Sent Events:
+//!
+//! - `GET /collections/:collection/changes` sends the journal entries the
+//!   WebSocket API delivers, `{"resume_token": ..., "event": ...}`, each with
+//!   its resume token as the event ID. The first event confirms the
+//!   subscription and carries its initial token.
+//! - `GET /jobs/:id/events` sends the job's record whenever its state,
+//!   progress or log changes, and ends once the job has finished. The event
+//!   ID is the time of the newest log line sent, and only newer log lines
+//!   are included in each record.
+//!
+//! Both resume from a `Last-Event-ID` header, which browsers send when they
+//! reconnect, or else from a `resume_token` query parameter. Resuming a
+//! change stream replays the events missed in between, exactly as a
+//! WebSocket `resume` does; a stream that falls behind the live events ends
+//! rather than skipping any, so the client reconnects from its last ID.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use aerolithdb_query::{Job, QueryEngine};
+
+use super::subscription_journal::{JournaledEvent, ResumeToken};
+use super::websocket::{RealtimeAPI, Subscription, WebSocketEvent};
+
+/// Header a reconnecting `EventSource` sends with the last ID it received
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// How often a job's record is checked for changes
+pub const JOB_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Response type of both event streams
+pub type EventStream = Sse<BoxStream<'static, Result<Event, Infallible>>>;
+
+/// Where the client asked to resume: the `Last-Event-ID` header, falling
+/// back to the `resume_token` query parameter.
+pub fn resume_point(headers: &HeaderMap, resume_token: Option<String>) -> Option<String> {
+    headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .or(resume_token)
+}
+
+/// Stream changes to a collection, resuming from `resume_token` if given.
+///
+/// Fails before anything is sent if the token is malformed, expired, or
+/// belongs to a subscription of another collection.
+pub async fn change_stream(
+    realtime: Arc<RealtimeAPI>,
+    collection: String,
+    filter: Option<Value>,
+    resume_token: Option<String>,
+) -> Result<EventStream> {
+    let connection_id = format!("sse-{}", uuid::Uuid::new_v4());
+    // Subscribe to live events first, so none fall between the replay and them
+    let receiver = realtime.subscribe_to_journaled_events();
+
+    let (subscription, token, missed_events) = match resume_token {
+        Some(resume_token) => {
+            let resumed = realtime.resume_subscription(connection_id.clone(), &resume_token).await?;
+            if resumed.collection.as_deref() != Some(collection.as_str()) {
+                realtime.disconnect(&connection_id).await?;
+                return Err(anyhow::anyhow!(
+                    "Invalid resume token: subscription {} does not watch collection {}",
+                    resumed.subscription_id, collection
+                ));
+            }
+            let subscription = Subscription {
+                id: resumed.subscription_id,
+                collection: resumed.collection,
+                query: resumed.query,
+                connection_id: connection_id.clone(),
+            };
+            (subscription, resumed.resume_token, resumed.missed_events)
+        }
+        None => {
+            let id = realtime
+                .add_subscription(connection_id.clone(), Some(collection.clone()), filter.clone())
+                .await?;
+            let subscription = Subscription {
+                id: id.clone(),
+                collection: Some(collection),
+                query: filter,
+                connection_id: connection_id.clone(),
+            };
+            (subscription, ResumeToken::new(id), Vec::new())
+        }
+    };
+    realtime.connect(connection_id.clone()).await?;
+    debug!("Streaming subscription {} over SSE as {}", subscription.id, connection_id);
+
+    let subscribed = json!({
+        "type": "Subscribed",
+        "subscription_id": subscription.id,
+        "resume_token": token.encode(),
+    });
+    let mut initial = vec![Event::default().id(token.encode()).data(subscribed.to_string())];
+
+    let mut token = token;
+    for missed in &missed_events {
+        token.advance(missed);
+        initial.push(journal_event(&token, &missed.event));
+    }
+
+    let live = LiveChanges {
+        receiver,
+        subscription,
+        token,
+        _connection: ConnectionGuard { realtime, connection_id },
+    };
+    let live = stream::unfold(live, |mut live| async move {
+        let event = live.next_event().await?;
+        Some((event, live))
+    });
+
+    let events = stream::iter(initial).chain(live).map(Ok).boxed();
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Stream a job's progress until it finishes, resuming after the log line
+/// recorded at `last_event_id` if given.
+pub fn job_stream(query: Arc<QueryEngine>, id: String, last_event_id: Option<&str>) -> Result<EventStream> {
+    let since = last_event_id
+        .map(|event_id| {
+            DateTime::parse_from_rfc3339(event_id)
+                .map(|at| at.with_timezone(&Utc))
+                .map_err(|_| anyhow::anyhow!("Invalid event ID for job {}: {}", id, event_id))
+        })
+        .transpose()?;
+    let first = query.job(&id)?;
+
+    let progress = JobProgressStream { query, id, since, latest: None, next: Some(first) };
+    let events = stream::unfold(progress, |mut progress| async move {
+        let event = progress.next_event().await?;
+        Some((event, progress))
+    });
+
+    Ok(Sse::new(events.map(Ok).boxed()).keep_alive(KeepAlive::default()))
+}
+
+/// Event carrying a journal entry, identified by the token resuming after it
+fn journal_event(token: &ResumeToken, event: &WebSocketEvent) -> Event {
+    let entry = json!({ "resume_token": token.encode(), "event": event });
+    Event::default().id(token.encode()).data(entry.to_string())
+}
+
+/// Whether a live event is for the subscription and was not replayed already
+fn is_new_for(subscription: &Subscription, token: &ResumeToken, event: &JournaledEvent) -> bool {
+    let delivered = token.cursors.get(&event.origin).is_some_and(|cursor| event.sequence <= *cursor);
+    !delivered && subscription.matches(&event.event)
+}
+
+/// The record to send for a job: log lines up to `since` are left out,
+/// and the event ID moves to the newest line included.
+fn job_update(mut job: Job, since: Option<DateTime<Utc>>) -> (Job, Option<DateTime<Utc>>) {
+    if let Some(since) = since {
+        job.logs.retain(|entry| entry.at > since);
+    }
+    let newest = job.logs.iter().map(|entry| entry.at).max().or(since);
+    (job, newest)
+}
+
+/// Live part of a change stream
+struct LiveChanges {
+    receiver: broadcast::Receiver<JournaledEvent>,
+    subscription: Subscription,
+    token: ResumeToken,
+    _connection: ConnectionGuard,
+}
+
+impl LiveChanges {
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if is_new_for(&self.subscription, &self.token, &event) => {
+                    self.token.advance(&event);
+                    return Some(journal_event(&self.token, &event.event));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        "SSE subscription {} fell {} event(s) behind; ending the stream for the client to resume",
+                        self.subscription.id, missed
+                    );
+                    return None;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Deregisters an SSE connection once its stream is dropped. The
+/// subscription's replicated state stays, so it can be resumed.
+struct ConnectionGuard {
+    realtime: Arc<RealtimeAPI>,
+    connection_id: String,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let realtime = Arc::clone(&self.realtime);
+        let connection_id = std::mem::take(&mut self.connection_id);
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                if let Err(e) = realtime.disconnect(&connection_id).await {
+                    warn!("Failed to remove SSE connection {}: {}", connection_id, e);
+                }
+            });
+        }
+    }
+}
+
+/// Polls a job's record and yields it whenever it changed
+struct JobProgressStream {
+    query: Arc<QueryEngine>,
+    id: String,
+    /// Time of the newest log line sent
+    since: Option<DateTime<Utc>>,
+    /// Last record sent, with its full log
+    latest: Option<Job>,
+    /// Record to send next without polling
+    next: Option<Job>,
+}
+
+impl JobProgressStream {
+    async fn next_event(&mut self) -> Option<Event> {
+        if self.latest.as_ref().is_some_and(|job| job.state.is_finished()) {
+            return None;
+        }
+
+        let job = match self.next.take() {
+            Some(job) => job,
+            None => loop {
+                tokio::time::sleep(JOB_POLL_INTERVAL).await;
+                match self.query.job(&self.id) {
+                    Ok(job) if Some(&job) != self.latest.as_ref() => break job,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        debug!("Ending progress stream of job {}: {}", self.id, e);
+                        return None;
+                    }
+                }
+            },
+        };
+
+        self.latest = Some(job.clone());
+        let (update, newest) = job_update(job, self.since);
+        self.since = newest;
+
+        let data = serde_json::to_string(&update).ok()?;
+        let event = Event::default().data(data);
+        Some(match newest {
+            Some(at) => event.id(at.to_rfc3339()),
+            None => event,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerolithdb_query::{JobLogEntry, JobProgress, JobState};
+    use axum::http::HeaderValue;
+
+    fn journaled(origin: &str, sequence: u64, collection: &str) -> JournaledEvent {
+        JournaledEvent {
+            origin: origin.to_string(),
+            sequence,
+            recorded_at: Utc::now(),
+            event: WebSocketEvent::DocumentChanged {
+                collection: collection.to_string(),
+                document_id: "ada".to_string(),
+                action: super::super::websocket::DocumentAction::Updated,
+                data: None,
+                timestamp: Utc::now().to_rfc3339(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_last_event_id_wins_over_parameter() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(resume_point(&headers, Some("sub-1".to_string())).as_deref(), Some("sub-1"));
+
+        headers.insert(LAST_EVENT_ID_HEADER, HeaderValue::from_static("sub-1.node-a:4"));
+        assert_eq!(resume_point(&headers, Some("sub-1".to_string())).as_deref(), Some("sub-1.node-a:4"));
+
+        headers.insert(LAST_EVENT_ID_HEADER, HeaderValue::from_static(" "));
+        assert_eq!(resume_point(&headers, None), None);
+    }
+
+    #[test]
+    fn test_replayed_and_foreign_events_are_skipped() {
+        let subscription = Subscription {
+            id: "sub-1".to_string(),
+            collection: Some("users".to_string()),
+            query: None,
+            connection_id: "sse-1".to_string(),
+        };
+        let mut token = ResumeToken::new("sub-1".to_string());
+        token.advance(&journaled("node-a", 4, "users"));
+
+        assert!(!is_new_for(&subscription, &token, &journaled("node-a", 4, "users")));
+        assert!(is_new_for(&subscription, &token, &journaled("node-a", 5, "users")));
+        assert!(is_new_for(&subscription, &token, &journaled("node-b", 1, "users")));
+        assert!(!is_new_for(&subscription, &token, &journaled("node-a", 6, "orders")));
+    }
+
+    #[test]
+    fn test_job_updates_resume_after_sent_log_lines() {
+        let start = Utc::now();
+        let line = |seconds, message: &str| JobLogEntry {
+            at: start + chrono::Duration::seconds(seconds),
+            message: message.to_string(),
+        };
+        let job = Job {
+            id: "job-1".to_string(),
+            kind: "backup".to_string(),
+            tenant: None,
+            state: JobState::Running,
+            progress: JobProgress { completed: 5, total: Some(10) },
+            cancel_requested: false,
+            created_at: start,
+            started_at: Some(start),
+            finished_at: None,
+            error: None,
+            result: None,
+            logs: vec![line(1, "started"), line(2, "copied segment 1")],
+        };
+
+        let (update, newest) = job_update(job.clone(), None);
+        assert_eq!(update.logs.len(), 2);
+        assert_eq!(newest, Some(start + chrono::Duration::seconds(2)));
+
+        let (update, newest) = job_update(job.clone(), Some(start + chrono::Duration::seconds(1)));
+        assert_eq!(update.logs, vec![line(2, "copied segment 1")]);
+        assert_eq!(update.progress, job.progress);
+        assert_eq!(newest, Some(start + chrono::Duration::seconds(2)));
+
+        // Without new lines the ID stays where the client is
+        let (update, newest) = job_update(job, Some(start + chrono::Duration::seconds(2)));
+        assert!(update.logs.is_empty());
+        assert_eq!(newest, Some(start + chrono::Duration::seconds(2)));
+    }
+}
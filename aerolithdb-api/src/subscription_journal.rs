@@ -0,0 +1,415 @@
+//! # Resumable WebSocket Subscriptions
+//!
+//! WebSocket subscriptions live on the node that accepted the connection. If
+//! that node dies, the client reconnects elsewhere and, without help, silently
+//! misses every event emitted in between. This module makes subscriptions
+//! survive failover:
+//!
+//! - **Replicated subscription state**: subscription definitions are stored in
+//!   a system collection, so any node can pick them up
+//! - **Event journal**: every change event is journaled with the originating
+//!   node and a per-node sequence number before it is broadcast
+//! - **Resume tokens**: clients hold an opaque token recording the last
+//!   sequence they saw from each originating node; presenting it to any node
+//!   replays exactly the events they missed
+//!
+//! Journal entries are retained for a bounded time. A token that points
+//! behind the retention window is rejected so the client can resynchronize
+//! explicitly instead of continuing with a gap.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use aerolithdb_query::QueryEngine;
+
+use super::websocket::{Subscription, WebSocketEvent};
+
+/// System collection holding replicated subscription definitions
+pub const SUBSCRIPTION_COLLECTION: &str = "_ws_subscriptions";
+
+/// System collection holding journaled change events
+pub const EVENT_JOURNAL_COLLECTION: &str = "_ws_event_journal";
+
+/// System collection recording how far each node's journal has been pruned
+pub const JOURNAL_HEADS_COLLECTION: &str = "_ws_journal_heads";
+
+/// Default time journaled events stay available for resumption
+pub const DEFAULT_JOURNAL_RETENTION: Duration = Duration::from_secs(600);
+
+/// A change event together with its position in the originating node's journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournaledEvent {
+    /// Node that emitted the event
+    pub origin: String,
+    /// Position in the origin's journal, starting at 1
+    pub sequence: u64,
+    /// When the event was journaled
+    pub recorded_at: DateTime<Utc>,
+    pub event: WebSocketEvent,
+}
+
+/// Replicated definition of a subscription, independent of any connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionState {
+    pub id: String,
+    pub collection: Option<String>,
+    pub query: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Pruning watermark for one origin's journal
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct JournalHead {
+    /// Highest sequence that has been pruned
+    pruned_through: u64,
+    /// Newest `recorded_at` among pruned entries
+    pruned_before: Option<DateTime<Utc>>,
+}
+
+/// Cursor over the event stream of a subscription.
+///
+/// Encoded as `<subscription_id>.<origin>:<sequence>.<origin>:<sequence>...`;
+/// clients should treat it as opaque and simply send back the latest one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeToken {
+    pub subscription_id: String,
+    /// Last delivered sequence per originating node
+    pub cursors: BTreeMap<String, u64>,
+}
+
+/// Result of resuming a subscription on this node
+#[derive(Debug, Clone)]
+pub struct ResumedSubscription {
+    pub subscription_id: String,
+    /// Collection the subscription watches, `None` for every collection
+    pub collection: Option<String>,
+    /// Query the subscription was created with
+    pub query: Option<serde_json::Value>,
+    /// Events emitted since the token was issued, in per-origin order
+    pub missed_events: Vec<JournaledEvent>,
+    /// Token covering the replayed events
+    pub resume_token: ResumeToken,
+}
+
+impl ResumeToken {
+    /// Token for a subscription that has not received any events yet
+    pub fn new(subscription_id: String) -> Self {
+        Self {
+            subscription_id,
+            cursors: BTreeMap::new(),
+        }
+    }
+
+    /// Advance the cursor past a delivered event
+    pub fn advance(&mut self, event: &JournaledEvent) {
+        let cursor = self.cursors.entry(event.origin.clone()).or_insert(0);
+        *cursor = (*cursor).max(event.sequence);
+    }
+
+    /// Serialize the token for transmission to the client
+    pub fn encode(&self) -> String {
+        let mut encoded = self.subscription_id.clone();
+        for (origin, sequence) in &self.cursors {
+            encoded.push_str(&format!(".{}:{}", origin, sequence));
+        }
+        encoded
+    }
+
+    /// Parse a token previously produced by `encode`
+    pub fn decode(token: &str) -> Result<Self> {
+        let mut parts = token.split('.');
+        let subscription_id = parts
+            .next()
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Malformed resume token"))?
+            .to_string();
+
+        let mut cursors = BTreeMap::new();
+        for part in parts {
+            let (origin, sequence) = part
+                .rsplit_once(':')
+                .ok_or_else(|| anyhow::anyhow!("Malformed resume token cursor: {}", part))?;
+            let sequence = sequence
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("Malformed resume token sequence: {}", sequence))?;
+            cursors.insert(origin.to_string(), sequence);
+        }
+
+        Ok(Self { subscription_id, cursors })
+    }
+}
+
+/// Journals events and subscription state into replicated system collections
+#[derive(Debug)]
+pub struct SubscriptionJournal {
+    query: Arc<QueryEngine>,
+    origin: String,
+    next_sequence: AtomicU64,
+    retention: Duration,
+}
+
+impl SubscriptionJournal {
+    /// Create a journal for this node; `origin` must be unique per process lifetime
+    pub fn new(query: Arc<QueryEngine>, origin: String, retention: Duration) -> Self {
+        Self {
+            query,
+            origin,
+            next_sequence: AtomicU64::new(1),
+            retention,
+        }
+    }
+
+    /// Identifier of this node's journal
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Persist a subscription so other nodes can resume it
+    pub async fn save_subscription(&self, state: &SubscriptionState) -> Result<()> {
+        self.query
+            .store_document(SUBSCRIPTION_COLLECTION, &state.id, &serde_json::to_value(state)?)
+            .await
+    }
+
+    /// Remove a subscription that the client ended explicitly
+    pub async fn remove_subscription(&self, subscription_id: &str) -> Result<()> {
+        self.query.delete_document(SUBSCRIPTION_COLLECTION, subscription_id).await
+    }
+
+    /// Load a replicated subscription definition
+    pub async fn load_subscription(&self, subscription_id: &str) -> Result<SubscriptionState> {
+        let document = self
+            .query
+            .get_document(SUBSCRIPTION_COLLECTION, subscription_id)
+            .await
+            .map_err(|_| anyhow::anyhow!("Unknown subscription: {}", subscription_id))?;
+        Ok(serde_json::from_value(document)?)
+    }
+
+    /// Append an event to this node's journal
+    pub async fn append(&self, event: WebSocketEvent) -> Result<JournaledEvent> {
+        let journaled = JournaledEvent {
+            origin: self.origin.clone(),
+            sequence: self.next_sequence.fetch_add(1, Ordering::SeqCst),
+            recorded_at: Utc::now(),
+            event,
+        };
+
+        self.query
+            .store_document(
+                EVENT_JOURNAL_COLLECTION,
+                &journal_key(&journaled.origin, journaled.sequence),
+                &serde_json::to_value(&journaled)?,
+            )
+            .await?;
+
+        Ok(journaled)
+    }
+
+    /// Events the subscription missed since the token was issued.
+    ///
+    /// Fails if any of the required events have already been pruned, since
+    /// replaying the remainder would leave the client with a silent gap.
+    pub async fn events_since(
+        &self,
+        state: &SubscriptionState,
+        token: &ResumeToken,
+    ) -> Result<Vec<JournaledEvent>> {
+        let subscription = Subscription {
+            id: state.id.clone(),
+            collection: state.collection.clone(),
+            query: state.query.clone(),
+            connection_id: String::new(),
+        };
+
+        let mut missed = Vec::new();
+        let mut checked_origins = BTreeSet::new();
+
+        for event in self.journaled_events().await? {
+            if checked_origins.insert(event.origin.clone()) {
+                self.check_not_pruned(state, token, &event.origin).await?;
+            }
+
+            let unseen = match token.cursors.get(&event.origin) {
+                Some(cursor) => event.sequence > *cursor,
+                None => event.recorded_at >= state.created_at,
+            };
+            if unseen && subscription.matches(&event.event) {
+                missed.push(event);
+            }
+        }
+
+        // Origins the client has seen but that have no retained entries left
+        for origin in token.cursors.keys() {
+            if !checked_origins.contains(origin) {
+                self.check_not_pruned(state, token, origin).await?;
+            }
+        }
+
+        Ok(missed)
+    }
+
+    /// Delete journal entries older than the retention window.
+    ///
+    /// Any node may prune any origin, so entries from nodes that died are
+    /// cleaned up too. Watermarks are recorded so stale tokens are detected.
+    pub async fn prune(&self) -> Result<usize> {
+        let cutoff = Utc::now() - chrono::Duration::from_std(self.retention)?;
+        let mut heads: BTreeMap<String, JournalHead> = BTreeMap::new();
+        let mut pruned = 0;
+
+        for event in self.journaled_events().await? {
+            if event.recorded_at >= cutoff {
+                continue;
+            }
+
+            let key = journal_key(&event.origin, event.sequence);
+            if let Err(e) = self.query.delete_document(EVENT_JOURNAL_COLLECTION, &key).await {
+                debug!("Journal entry {} already pruned: {}", key, e);
+                continue;
+            }
+            pruned += 1;
+
+            let head = heads.entry(event.origin.clone()).or_default();
+            head.pruned_through = head.pruned_through.max(event.sequence);
+            head.pruned_before = head.pruned_before.max(Some(event.recorded_at));
+        }
+
+        for (origin, head) in heads {
+            let mut stored = self.load_head(&origin).await;
+            stored.pruned_through = stored.pruned_through.max(head.pruned_through);
+            stored.pruned_before = stored.pruned_before.max(head.pruned_before);
+            self.query
+                .store_document(JOURNAL_HEADS_COLLECTION, &origin, &serde_json::to_value(&stored)?)
+                .await?;
+        }
+
+        if pruned > 0 {
+            debug!("Pruned {} WebSocket journal entries", pruned);
+        }
+        Ok(pruned)
+    }
+
+    /// All retained journal entries; keys are listed sorted, so each origin's
+    /// entries come back in sequence order
+    async fn journaled_events(&self) -> Result<Vec<JournaledEvent>> {
+        let result = self.query.list_documents(EVENT_JOURNAL_COLLECTION, None, None).await?;
+        result
+            .documents
+            .into_iter()
+            .map(|document| serde_json::from_value(document).map_err(Into::into))
+            .collect()
+    }
+
+    async fn load_head(&self, origin: &str) -> JournalHead {
+        self.query
+            .get_document(JOURNAL_HEADS_COLLECTION, origin)
+            .await
+            .ok()
+            .and_then(|document| serde_json::from_value(document).ok())
+            .unwrap_or_default()
+    }
+
+    async fn check_not_pruned(
+        &self,
+        state: &SubscriptionState,
+        token: &ResumeToken,
+        origin: &str,
+    ) -> Result<()> {
+        let head = self.load_head(origin).await;
+        let expired = match token.cursors.get(origin) {
+            Some(cursor) => head.pruned_through > *cursor,
+            None => head.pruned_before.map(|before| before >= state.created_at).unwrap_or(false),
+        };
+
+        if expired {
+            warn!("Resume token for subscription {} is past journal retention", state.id);
+            return Err(anyhow::anyhow!(
+                "Resume token expired: events from node {} are no longer retained; resubscribe and resynchronize",
+                origin
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Subscription {
+    /// Whether an event should be delivered to this subscription
+    pub fn matches(&self, event: &WebSocketEvent) -> bool {
+        match event {
+            WebSocketEvent::DocumentChanged { collection, .. } => self
+                .collection
+                .as_ref()
+                .map(|wanted| wanted == collection)
+                .unwrap_or(true),
+            // Live query updates are addressed to the subscription that registered the query
+            WebSocketEvent::QueryUpdate { query_id, .. } => *query_id == self.id,
+            WebSocketEvent::QueryDelta { subscription_id, .. } => *subscription_id == self.id,
+            // Channel messages are delivered through pub/sub subscriptions instead
+            WebSocketEvent::ChannelMessage { .. }
+            | WebSocketEvent::ConnectionStatus { .. }
+            | WebSocketEvent::Error { .. } => false,
+            // Request answers go only to the connection that asked
+            WebSocketEvent::QueryResult { .. }
+            | WebSocketEvent::AggregateResult { .. }
+            | WebSocketEvent::RequestFailed { .. }
+            | WebSocketEvent::Subscribed { .. }
+            | WebSocketEvent::Unsubscribed { .. } => false,
+        }
+    }
+}
+
+/// Journal document key; zero-padded so lexical order matches sequence order
+fn journal_key(origin: &str, sequence: u64) -> String {
+    format!("{}:{:020}", origin, sequence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_token_roundtrip() {
+        let mut token = ResumeToken::new("sub-1".to_string());
+        assert_eq!(ResumeToken::decode(&token.encode()).unwrap(), token);
+
+        token.cursors.insert("node-a".to_string(), 42);
+        token.cursors.insert("node-b".to_string(), 7);
+        assert_eq!(ResumeToken::decode(&token.encode()).unwrap(), token);
+
+        assert!(ResumeToken::decode("").is_err());
+        assert!(ResumeToken::decode("sub-1.node-a").is_err());
+        assert!(ResumeToken::decode("sub-1.node-a:x").is_err());
+    }
+
+    #[test]
+    fn test_token_advances_per_origin() {
+        let mut token = ResumeToken::new("sub-1".to_string());
+        let event = |origin: &str, sequence| JournaledEvent {
+            origin: origin.to_string(),
+            sequence,
+            recorded_at: Utc::now(),
+            event: WebSocketEvent::ConnectionStatus {
+                status: "ok".to_string(),
+                message: String::new(),
+            },
+        };
+
+        token.advance(&event("node-a", 3));
+        token.advance(&event("node-a", 2));
+        token.advance(&event("node-b", 1));
+        assert_eq!(token.cursors["node-a"], 3);
+        assert_eq!(token.cursors["node-b"], 1);
+    }
+
+    #[test]
+    fn test_journal_keys_sort_by_sequence() {
+        assert!(journal_key("node-a", 9) < journal_key("node-a", 10));
+    }
+}
@@ -0,0 +1,209 @@
+//! # Node Resource Telemetry
+//!
+//! Resource usage of this node's process, as reported by the health and
+//! statistics endpoints: CPU, resident and virtual memory, threads, open
+//! file descriptors, and the disks holding the node's data directories.
+//!
+//! CPU usage is measured between two samples, so the first report after
+//! startup reads zero and later ones cover the time since the previous
+//! report. It is given per core, as `top` does, and can exceed 100% on a
+//! busy multi-core node. Threads and file descriptors are only counted on
+//! Linux.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Disks, Pid, System};
+
+/// Resource usage of the node's process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU used since the previous report, in percent of one core
+    pub cpu_percent: f32,
+    /// Cores available to the process
+    pub cpu_count: usize,
+    /// Resident set size
+    pub resident_memory_bytes: u64,
+    /// Virtual memory size
+    pub virtual_memory_bytes: u64,
+    /// Physical memory of the host
+    pub total_memory_bytes: u64,
+    /// Threads of the process, where the platform reports them
+    pub threads: Option<usize>,
+    /// Open file descriptors, where the platform reports them
+    pub open_file_descriptors: Option<usize>,
+    /// Disks holding the node's data, one per mount
+    pub disks: Vec<DiskUsage>,
+}
+
+/// Space on a disk holding node data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub mount_point: PathBuf,
+    pub file_system: String,
+    /// Node directories on this disk
+    pub paths: Vec<PathBuf>,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// Share of the disk in use, from 0 to 100
+    pub used_percent: f64,
+}
+
+/// Samples the resource usage of the node's process.
+pub struct ResourceMonitor {
+    pid: Option<Pid>,
+    system: Mutex<System>,
+    disks: Mutex<Disks>,
+}
+
+impl std::fmt::Debug for ResourceMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourceMonitor").field("pid", &self.pid).finish()
+    }
+}
+
+impl Default for ResourceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceMonitor {
+    /// Start monitoring the current process. Takes the first CPU sample.
+    pub fn new() -> Self {
+        let pid = sysinfo::get_current_pid().ok();
+        let mut system = System::new();
+        if let Some(pid) = pid {
+            system.refresh_process(pid);
+        }
+        Self {
+            pid,
+            system: Mutex::new(system),
+            disks: Mutex::new(Disks::new_with_refreshed_list()),
+        }
+    }
+
+    /// Current resource usage, with the disks holding `paths`.
+    pub fn sample(&self, paths: &[PathBuf]) -> ResourceUsage {
+        let mut usage = ResourceUsage {
+            cpu_percent: 0.0,
+            cpu_count: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            resident_memory_bytes: 0,
+            virtual_memory_bytes: 0,
+            total_memory_bytes: 0,
+            threads: None,
+            open_file_descriptors: open_file_descriptors(),
+            disks: self.disk_usage(paths),
+        };
+
+        let mut system = self.system.lock().unwrap();
+        system.refresh_memory();
+        usage.total_memory_bytes = system.total_memory();
+        if let Some(process) = self.pid.filter(|pid| system.refresh_process(*pid)).and_then(|pid| system.process(pid)) {
+            usage.cpu_percent = process.cpu_usage();
+            usage.resident_memory_bytes = process.memory();
+            usage.virtual_memory_bytes = process.virtual_memory();
+            usage.threads = process.tasks().map(|tasks| tasks.len());
+        }
+        usage
+    }
+
+    /// Space on each disk holding one of `paths`, in the order the paths
+    /// are given. Paths that do not exist yet are skipped.
+    fn disk_usage(&self, paths: &[PathBuf]) -> Vec<DiskUsage> {
+        let mut disks = self.disks.lock().unwrap();
+        // Mounts come and go; a full refresh also updates the free space
+        disks.refresh_list();
+
+        let mut usage: Vec<DiskUsage> = Vec::new();
+        for path in paths {
+            let Ok(resolved) = path.canonicalize() else {
+                continue;
+            };
+            let Some(disk) = mount_of(&resolved, disks.list().iter().map(|disk| disk.mount_point()))
+                .and_then(|mount| disks.list().iter().find(|disk| disk.mount_point() == mount))
+            else {
+                continue;
+            };
+
+            if let Some(existing) = usage.iter_mut().find(|usage| usage.mount_point == disk.mount_point()) {
+                existing.paths.push(path.clone());
+                continue;
+            }
+            let total = disk.total_space();
+            let available = disk.available_space();
+            usage.push(DiskUsage {
+                mount_point: disk.mount_point().to_path_buf(),
+                file_system: disk.file_system().to_string_lossy().into_owned(),
+                paths: vec![path.clone()],
+                total_bytes: total,
+                available_bytes: available,
+                used_percent: used_percent(total, available),
+            });
+        }
+        usage
+    }
+}
+
+/// The mount point among `mounts` that `path` lives under: the longest one
+/// containing it.
+fn mount_of<'a>(path: &Path, mounts: impl Iterator<Item = &'a Path>) -> Option<&'a Path> {
+    mounts
+        .filter(|mount| path.starts_with(mount))
+        .max_by_key(|mount| mount.components().count())
+}
+
+fn used_percent(total: u64, available: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    total.saturating_sub(available) as f64 * 100.0 / total as f64
+}
+
+#[cfg(target_os = "linux")]
+fn open_file_descriptors() -> Option<usize> {
+    std::fs::read_dir("/proc/self/fd").ok().map(|entries| entries.count())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_file_descriptors() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_of_prefers_the_deepest_mount() {
+        let mounts = [Path::new("/"), Path::new("/var"), Path::new("/var/lib/aerolith"), Path::new("/var/lib/aero")];
+
+        assert_eq!(mount_of(Path::new("/var/lib/aerolith/data"), mounts.iter().copied()), Some(Path::new("/var/lib/aerolith")));
+        // Mounts match whole components, not string prefixes
+        assert_eq!(mount_of(Path::new("/var/lib/aerolithdb"), mounts.iter().copied()), Some(Path::new("/var")));
+        assert_eq!(mount_of(Path::new("/srv"), mounts.iter().copied()), Some(Path::new("/")));
+        assert_eq!(mount_of(Path::new("relative"), mounts.iter().copied()), None);
+    }
+
+    #[test]
+    fn test_used_percent() {
+        assert_eq!(used_percent(0, 0), 0.0);
+        assert_eq!(used_percent(200, 50), 75.0);
+        assert_eq!(used_percent(100, 100), 0.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sample_reports_the_current_process() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let usage = ResourceMonitor::new().sample(&[dir.clone(), dir.join("missing")]);
+
+        assert!(usage.resident_memory_bytes > 0);
+        assert!(usage.threads.unwrap_or(0) >= 1);
+        assert!(usage.open_file_descriptors.unwrap_or(0) >= 1);
+        // Paths that do not exist have no disk
+        assert!(usage.disks.len() <= 1);
+        assert!(usage.disks.iter().all(|disk| disk.paths == vec![dir.clone()]));
+    }
+}
@@ -0,0 +1,424 @@
+//! # API Versioning
+//!
+//! Lets the REST API change shape without breaking clients overnight.
+//! Every resource is served under a versioned prefix, and versions are
+//! retired endpoint by endpoint:
+//!
+//! - ✅ `/api/v1` and `/api/v2` routers are served side by side; v2 starts
+//!   out with v1's resources and diverges as endpoints change shape
+//! - ✅ Deprecated endpoints answer with `Deprecation` (RFC 9745) and
+//!   `Sunset` (RFC 8594) headers, plus a `successor-version` link
+//! - ✅ Once its sunset has passed an endpoint answers `410 Gone`
+//! - ✅ Requests are counted per version and client, so the clients still
+//!   calling deprecated endpoints can be found before they are removed
+//!
+//! Clients are told apart by [`VersioningConfig::client_header`], falling
+//! back to their `User-Agent` and then their address.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// Versions served by the REST API, oldest first
+pub const API_VERSIONS: &[&str] = &["v1", "v2"];
+
+/// Clients tracked per version; further clients are counted together
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Longest client name kept
+const MAX_CLIENT_LEN: usize = 128;
+
+/// Client name the untracked clients are counted under
+const OTHER_CLIENTS: &str = "other";
+
+/// An endpoint on its way out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Route pattern such as `/api/v1/collections/:collection/query`, where
+    /// `:name` matches one segment and a trailing `*` matches the rest
+    pub path: String,
+
+    /// Method deprecated, or every method when `None`
+    #[serde(default)]
+    pub method: Option<String>,
+
+    /// When the endpoint was deprecated
+    pub deprecated_at: DateTime<Utc>,
+
+    /// When the endpoint is removed; it answers `410 Gone` from then on
+    #[serde(default)]
+    pub sunset: Option<DateTime<Utc>>,
+
+    /// Replacement clients should move to
+    #[serde(default)]
+    pub successor: Option<String>,
+}
+
+impl Deprecation {
+    /// Whether a request falls under this deprecation
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_deref().is_some_and(|deprecated| !deprecated.eq_ignore_ascii_case(method.as_str())) {
+            return false;
+        }
+        let mut segments = path.trim_end_matches('/').split('/');
+        for pattern in self.path.trim_end_matches('/').split('/') {
+            if pattern == "*" {
+                return true;
+            }
+            match segments.next() {
+                Some(segment) if pattern.starts_with(':') && !segment.is_empty() => {}
+                Some(segment) if segment == pattern => {}
+                _ => return false,
+            }
+        }
+        segments.next().is_none()
+    }
+
+    /// Whether the endpoint has been removed
+    pub fn is_sunset(&self, now: DateTime<Utc>) -> bool {
+        self.sunset.is_some_and(|sunset| sunset <= now)
+    }
+
+    /// Headers announcing the deprecation
+    fn headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        let mut headers = Vec::new();
+        let mut push = |name: &'static str, value: String| {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.push((HeaderName::from_static(name), value));
+            }
+        };
+        push("deprecation", format!("@{}", self.deprecated_at.timestamp()));
+        if let Some(sunset) = self.sunset {
+            push("sunset", sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+        }
+        if let Some(successor) = &self.successor {
+            push("link", format!("<{}>; rel=\"successor-version\"", successor));
+        }
+        headers
+    }
+}
+
+/// Deprecated endpoints and how clients are identified.
+#[derive(Debug, Clone)]
+pub struct VersioningConfig {
+    /// Endpoints announced as deprecated
+    pub deprecations: Vec<Deprecation>,
+
+    /// Request header naming the calling client
+    pub client_header: String,
+}
+
+impl Default for VersioningConfig {
+    fn default() -> Self {
+        Self {
+            deprecations: Vec::new(),
+            client_header: "x-client-id".to_string(),
+        }
+    }
+}
+
+/// Requests one client made to one API version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientUsage {
+    pub version: String,
+    pub client: String,
+    pub requests: u64,
+
+    /// Requests to deprecated endpoints, including sunset ones
+    pub deprecated_requests: u64,
+
+    /// Patterns of the deprecated endpoints the client called
+    pub deprecated_endpoints: BTreeSet<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Requests made to one API version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionUsage {
+    pub version: String,
+    pub requests: u64,
+    pub deprecated_requests: u64,
+    pub clients: usize,
+}
+
+/// Versions, deprecations, and the clients that must migrate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionReport {
+    pub versions: Vec<VersionUsage>,
+    pub deprecations: Vec<Deprecation>,
+
+    /// Clients calling deprecated endpoints, busiest first
+    pub migrations: Vec<ClientUsage>,
+}
+
+/// Announces deprecations and counts requests per version and client.
+#[derive(Debug)]
+pub struct ApiVersions {
+    config: VersioningConfig,
+    usage: Mutex<BTreeMap<(String, String), ClientUsage>>,
+}
+
+impl ApiVersions {
+    pub fn new(config: VersioningConfig) -> Result<Self> {
+        for deprecation in &config.deprecations {
+            if split_version(&deprecation.path).is_none() {
+                return Err(anyhow::anyhow!(
+                    "Deprecated path {} is not under /api/{{{}}}",
+                    deprecation.path,
+                    API_VERSIONS.join(",")
+                ));
+            }
+            if deprecation.sunset.is_some_and(|sunset| sunset < deprecation.deprecated_at) {
+                return Err(anyhow::anyhow!("Sunset of {} comes before its deprecation", deprecation.path));
+            }
+        }
+        if !config.deprecations.is_empty() {
+            info!("{} REST endpoints are deprecated", config.deprecations.len());
+        }
+        Ok(Self { config, usage: Mutex::new(BTreeMap::new()) })
+    }
+
+    /// The deprecation a request falls under, if any
+    pub fn deprecation(&self, method: &Method, path: &str) -> Option<&Deprecation> {
+        self.config.deprecations.iter().find(|deprecation| deprecation.matches(method, path))
+    }
+
+    /// Name of the client making a request
+    fn client(&self, headers: &HeaderMap, addr: Option<SocketAddr>) -> String {
+        let named = [self.config.client_header.as_str(), header::USER_AGENT.as_str()]
+            .into_iter()
+            .filter_map(|name| headers.get(name)?.to_str().ok())
+            .map(str::trim)
+            .find(|name| !name.is_empty());
+        match (named, addr) {
+            (Some(name), _) => name.chars().take(MAX_CLIENT_LEN).collect(),
+            (None, Some(addr)) => addr.ip().to_string(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+
+    fn record(&self, version: &str, client: String, deprecation: Option<&Deprecation>) {
+        let now = Utc::now();
+        let mut usage = self.usage.lock().unwrap();
+        let mut key = (version.to_string(), client);
+        if !usage.contains_key(&key) && usage.len() >= MAX_TRACKED_CLIENTS * API_VERSIONS.len() {
+            key.1 = OTHER_CLIENTS.to_string();
+        }
+        let entry = usage.entry(key).or_insert_with_key(|(version, client)| ClientUsage {
+            version: version.clone(),
+            client: client.clone(),
+            requests: 0,
+            deprecated_requests: 0,
+            deprecated_endpoints: BTreeSet::new(),
+            first_seen: now,
+            last_seen: now,
+        });
+        entry.requests += 1;
+        entry.last_seen = now;
+        if let Some(deprecation) = deprecation {
+            entry.deprecated_requests += 1;
+            entry.deprecated_endpoints.insert(deprecation.path.clone());
+        }
+    }
+
+    /// Usage of every client, by version and client.
+    pub fn usage(&self) -> Vec<ClientUsage> {
+        self.usage.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn report(&self) -> VersionReport {
+        let usage = self.usage();
+        let versions = API_VERSIONS
+            .iter()
+            .map(|version| {
+                let clients: Vec<&ClientUsage> = usage.iter().filter(|client| client.version == *version).collect();
+                VersionUsage {
+                    version: version.to_string(),
+                    requests: clients.iter().map(|client| client.requests).sum(),
+                    deprecated_requests: clients.iter().map(|client| client.deprecated_requests).sum(),
+                    clients: clients.len(),
+                }
+            })
+            .collect();
+        let mut migrations: Vec<ClientUsage> = usage.into_iter().filter(|client| client.deprecated_requests > 0).collect();
+        migrations.sort_by_key(|client| std::cmp::Reverse(client.deprecated_requests));
+
+        VersionReport {
+            versions,
+            deprecations: self.config.deprecations.clone(),
+            migrations,
+        }
+    }
+
+    /// Request counters in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let usage = self.usage();
+        let mut out = String::new();
+        let families: [UsageMetric; 2] = [
+            ("api_requests_total", "REST requests by API version and client", |client| client.requests),
+            (
+                "api_deprecated_requests_total",
+                "REST requests to deprecated endpoints by API version and client",
+                |client| client.deprecated_requests,
+            ),
+        ];
+        for (name, help, value) in families {
+            let _ = writeln!(out, "# HELP aerolithdb_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE aerolithdb_{} counter", name);
+            for client in &usage {
+                let _ = writeln!(
+                    out,
+                    "aerolithdb_{}{{version=\"{}\",client=\"{}\"}} {}",
+                    name,
+                    client.version,
+                    escape_label(&client.client),
+                    value(client)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Name, help text and value of a per-client counter
+type UsageMetric = (&'static str, &'static str, fn(&ClientUsage) -> u64);
+
+pub(crate) fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Split a versioned API path into its version and the resource path
+/// below it, e.g. `/api/v2/stats` into `v2` and `/stats`.
+pub fn split_version(path: &str) -> Option<(&'static str, &str)> {
+    let rest = path.strip_prefix("/api/")?;
+    API_VERSIONS.iter().find_map(|version| {
+        let resource = rest.strip_prefix(version)?;
+        (resource.is_empty() || resource.starts_with('/')).then_some((*version, resource))
+    })
+}
+
+/// REST middleware counting requests per version and client, announcing
+/// deprecations and refusing endpoints past their sunset.
+pub async fn versioning_middleware(State(versions): State<Arc<ApiVersions>>, request: Request, next: Next) -> Response {
+    let Some((version, _)) = split_version(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let addr = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+    let client = versions.client(request.headers(), addr);
+    let deprecation = versions.deprecation(request.method(), request.uri().path());
+    versions.record(version, client, deprecation);
+
+    let Some(deprecation) = deprecation else {
+        return next.run(request).await;
+    };
+    let mut response = if deprecation.is_sunset(Utc::now()) {
+        debug!("Refusing {} {}: removed since {:?}", request.method(), request.uri().path(), deprecation.sunset);
+        let error = serde_json::json!({
+            "error": format!("{} was removed from the {} API", request.uri().path(), version),
+            "code": StatusCode::GONE.as_u16(),
+            "details": { "successor": deprecation.successor },
+        });
+        (StatusCode::GONE, Json(error)).into_response()
+    } else {
+        next.run(request).await
+    };
+    response.headers_mut().extend(deprecation.headers());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn deprecation(path: &str, sunset: Option<DateTime<Utc>>) -> Deprecation {
+        Deprecation {
+            path: path.to_string(),
+            method: None,
+            deprecated_at: "2026-01-01T00:00:00Z".parse().unwrap(),
+            sunset,
+            successor: Some("/api/v2/stats".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_deprecation_patterns() {
+        let query = deprecation("/api/v1/collections/:collection/query", None);
+        assert!(query.matches(&Method::POST, "/api/v1/collections/users/query"));
+        assert!(!query.matches(&Method::POST, "/api/v2/collections/users/query"));
+        assert!(!query.matches(&Method::POST, "/api/v1/collections/users/query/extra"));
+        assert!(!query.matches(&Method::POST, "/api/v1/collections//query"));
+
+        let admin = Deprecation { method: Some("DELETE".to_string()), ..deprecation("/api/v1/admin/*", None) };
+        assert!(admin.matches(&Method::DELETE, "/api/v1/admin/backups/b1"));
+        assert!(!admin.matches(&Method::GET, "/api/v1/admin/backups/b1"));
+
+        assert_eq!(split_version("/api/v2/stats"), Some(("v2", "/stats")));
+        assert_eq!(split_version("/api/v10/stats"), None);
+        assert_eq!(split_version("/health"), None);
+        assert!(ApiVersions::new(VersioningConfig { deprecations: vec![deprecation("/stats", None)], ..Default::default() }).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deprecated_endpoints_announce_sunset_and_track_clients() {
+        let sunset = Utc::now() + chrono::Duration::days(30);
+        let config = VersioningConfig {
+            deprecations: vec![
+                deprecation("/api/v1/stats", Some(sunset)),
+                deprecation("/api/v1/removed", Some(Utc::now() - chrono::Duration::days(1))),
+            ],
+            ..Default::default()
+        };
+        let versions = Arc::new(ApiVersions::new(config).unwrap());
+        let router = Router::new()
+            .route("/api/v1/stats", get(|| async { "v1" }))
+            .route("/api/v1/removed", get(|| async { "still here" }))
+            .route("/api/v2/stats", get(|| async { "v2" }))
+            .layer(axum::middleware::from_fn_with_state(Arc::clone(&versions), versioning_middleware));
+        let call = |path: &'static str, client: &'static str| {
+            let router = router.clone();
+            async move {
+                let request = Request::get(path).header("x-client-id", client).body(Body::empty()).unwrap();
+                router.oneshot(request).await.unwrap()
+            }
+        };
+
+        let response = call("/api/v1/stats", "legacy-app").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "@1767225600");
+        assert_eq!(response.headers()["sunset"], sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+        assert_eq!(response.headers()["link"], "</api/v2/stats>; rel=\"successor-version\"");
+
+        let response = call("/api/v2/stats", "new-app").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("deprecation").is_none());
+
+        let response = call("/api/v1/removed", "legacy-app").await;
+        assert_eq!(response.status(), StatusCode::GONE);
+        assert!(response.headers().contains_key("sunset"));
+
+        let report = versions.report();
+        assert_eq!(report.versions[0], VersionUsage { version: "v1".to_string(), requests: 2, deprecated_requests: 2, clients: 1 });
+        assert_eq!(report.versions[1], VersionUsage { version: "v2".to_string(), requests: 1, deprecated_requests: 0, clients: 1 });
+        assert_eq!(report.migrations.len(), 1);
+        assert_eq!(report.migrations[0].client, "legacy-app");
+        assert_eq!(report.migrations[0].deprecated_endpoints.len(), 2);
+        assert!(versions
+            .to_prometheus()
+            .contains("aerolithdb_api_deprecated_requests_total{version=\"v1\",client=\"legacy-app\"} 2"));
+    }
+}
@@ -13,7 +13,11 @@
 //! - ✅ Event subscription and filtering
 //! - ✅ Error handling and status reporting
 //! - ✅ Multi-client connection pooling
-//! - ✅ Integration with query engine and security framework
+//! - ✅ Integration with query engine and the API access policy
+//! - ✅ Application pub/sub channels (see `pubsub`)
+//! - ✅ Resumable subscriptions that survive node failover (see `subscription_journal`)
+//! - ✅ Ad-hoc queries and aggregations over an open connection
+//! - ✅ Live queries pushing deltas of their matches
 //!
 //! ## Supported Events
 //! - Document CRUD operations (Created, Updated, Deleted)
@@ -21,19 +25,61 @@
 //! - Connection status and health monitoring
 //! - Error notifications with detailed context
 //!
+//! ## Request Frames
+//! Clients already connected for change streams can run queries without an
+//! extra HTTP round trip. A `Query` or `Aggregate` frame carries a
+//! client-chosen `request_id`, and the `QueryResult`, `AggregateResult` or
+//! `RequestFailed` frame answering it echoes that ID back, so a client may
+//! keep several requests in flight and match answers as they arrive.
+//! `Query`, `Aggregate` and `Subscribe` frames are admitted through the
+//! same access policy as REST and gRPC requests, under the credentials the
+//! connection was accepted with, and a refused frame is answered with a
+//! `RequestFailed` frame coded like the rejection:
+//!
+//! ```json
+//! {"type": "Query", "request_id": "7", "collection": "orders", "query": {"filter": {"status": "open"}, "limit": 20}}
+//! {"type": "QueryResult", "request_id": "7", "documents": [...], "total": 42, "execution_time_ms": 3}
+//! ```
+//!
+//! ## Live Queries
+//! A `Subscribe` frame registers a filter on a collection with the query
+//! engine. The `Subscribed` answer carries the subscription ID and the
+//! current matches; from then on every write changing the matches reaches
+//! the connection as a `QueryDelta` event saying whether the document was
+//! added to, changed in or removed from them, until an `Unsubscribe` frame
+//! or the connection closes:
+//!
+//! ```json
+//! {"type": "Subscribe", "request_id": "9", "collection": "orders", "filter": {"status": "open"}}
+//! {"type": "Subscribed", "request_id": "9", "subscription_id": "…", "documents": [...], "total": 42}
+//! {"type": "QueryDelta", "subscription_id": "…", "collection": "orders", "document_id": "o-17", "delta": "Removed", "action": "Updated", "data": {...}, "timestamp": "…"}
+//! ```
+//!
+//! Every document change written through the engine is also published as
+//! a journaled `DocumentChanged` event.
+//!
 //! This implementation is production-ready for real-time applications.
 
 use anyhow::Result;
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Instant;
 use tokio::sync::{RwLock, broadcast};
 use serde::{Serialize, Deserialize};
 use tracing::{info, warn, debug};
 
-use aerolithdb_query::QueryEngine;
-use aerolithdb_security::SecurityFramework;
+use aerolithdb_query::{ChangeKind, DeltaKind, LiveQuery, QueryDelta, QueryEngine, QueryRequest, QueryResult};
+use aerolithdb_security::redact;
 
 use super::WebSocketConfig;
+use super::access::{AccessConfig, AccessPolicy, Credentials, Protocol, Rejection};
+use super::middleware::SaaSContext;
+use super::pubsub::{PubSubBroker, PubSubMessage};
+use super::subscription_journal::{
+    JournaledEvent, ResumeToken, ResumedSubscription, SubscriptionJournal, SubscriptionState,
+    DEFAULT_JOURNAL_RETENTION,
+};
 
 /// WebSocket event types for real-time communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,12 +105,155 @@ pub enum WebSocketEvent {
         status: String,
         message: String,
     },
+    /// Message published to a pub/sub channel
+    ChannelMessage {
+        channel: String,
+        message_id: String,
+        sequence: u64,
+        payload: serde_json::Value,
+        publisher: Option<String>,
+        timestamp: String,
+    },
     /// Error notification
     Error {
         code: String,
         message: String,
         timestamp: String,
     },
+    /// Answer to a `Query` request frame
+    QueryResult {
+        request_id: String,
+        documents: Vec<serde_json::Value>,
+        total: usize,
+        execution_time_ms: u64,
+    },
+    /// Answer to an `Aggregate` request frame
+    AggregateResult {
+        request_id: String,
+        documents: Vec<serde_json::Value>,
+        scanned: usize,
+        execution_time_ms: u64,
+    },
+    /// A request frame that could not be answered
+    RequestFailed {
+        request_id: String,
+        code: String,
+        message: String,
+    },
+    /// Answer to a `Subscribe` request frame, with the live query's
+    /// current matches
+    Subscribed {
+        request_id: String,
+        subscription_id: String,
+        documents: Vec<serde_json::Value>,
+        total: usize,
+    },
+    /// Answer to an `Unsubscribe` request frame
+    Unsubscribed {
+        request_id: String,
+        subscription_id: String,
+    },
+    /// Change to the matches of a live query, sent only to the connection
+    /// that subscribed
+    QueryDelta {
+        subscription_id: String,
+        collection: String,
+        document_id: String,
+        delta: DeltaKind,
+        action: DocumentAction,
+        data: Option<serde_json::Value>,
+        timestamp: String,
+    },
+}
+
+/// Request frames a client sends over an open connection. Each is answered
+/// by one event carrying the same `request_id`, sent only to that client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WebSocketRequest {
+    /// Run a query, as `POST /collections/:collection/query` would
+    Query {
+        request_id: String,
+        collection: String,
+        query: QueryRequest,
+    },
+    /// Run an aggregation pipeline, as `POST /collections/:collection/aggregate` would
+    Aggregate {
+        request_id: String,
+        collection: String,
+        pipeline: Vec<serde_json::Value>,
+    },
+    /// Subscribe to a live query; without a filter every document of the
+    /// collection matches
+    Subscribe {
+        request_id: String,
+        collection: String,
+        #[serde(default)]
+        filter: Option<serde_json::Value>,
+    },
+    /// End a live query the connection subscribed to
+    Unsubscribe {
+        request_id: String,
+        subscription_id: String,
+    },
+}
+
+impl WebSocketRequest {
+    pub fn request_id(&self) -> &str {
+        match self {
+            WebSocketRequest::Query { request_id, .. }
+            | WebSocketRequest::Aggregate { request_id, .. }
+            | WebSocketRequest::Subscribe { request_id, .. }
+            | WebSocketRequest::Unsubscribe { request_id, .. } => request_id,
+        }
+    }
+}
+
+impl WebSocketEvent {
+    /// A `RequestFailed` answer, classifying the error the way REST maps it
+    /// to a status code.
+    fn request_failed(request_id: String, error: &anyhow::Error) -> Self {
+        let message = error.to_string();
+        let code = if message.starts_with("Invalid filter") || message.starts_with("Invalid pipeline") {
+            "invalid_request"
+        } else if message.starts_with("Subscription not found") {
+            "not_found"
+        } else {
+            "query_failed"
+        };
+        WebSocketEvent::RequestFailed { request_id, code: code.to_string(), message: redact(&message).into_owned() }
+    }
+
+    /// A `RequestFailed` answer to a frame the access policy refused.
+    fn request_refused(request_id: String, rejection: &Rejection) -> Self {
+        WebSocketEvent::RequestFailed {
+            request_id,
+            code: rejection.outcome().to_string(),
+            message: rejection.to_string(),
+        }
+    }
+}
+
+/// What a connection presented when it was accepted, which its request
+/// frames are admitted under.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionCredentials {
+    /// Value of the upgrade request's `authorization` header
+    pub authorization: Option<String>,
+
+    /// Value of the upgrade request's tenant header
+    pub tenant: Option<String>,
+    pub client: Option<IpAddr>,
+}
+
+impl ConnectionCredentials {
+    pub fn credentials(&self) -> Credentials<'_> {
+        Credentials {
+            authorization: self.authorization.as_deref(),
+            tenant: self.tenant.as_deref(),
+            client: self.client,
+        }
+    }
 }
 
 /// Document actions for change events
@@ -75,6 +264,30 @@ pub enum DocumentAction {
     Deleted,
 }
 
+impl From<ChangeKind> for DocumentAction {
+    fn from(kind: ChangeKind) -> Self {
+        match kind {
+            ChangeKind::Created => DocumentAction::Created,
+            ChangeKind::Updated => DocumentAction::Updated,
+            ChangeKind::Deleted => DocumentAction::Deleted,
+        }
+    }
+}
+
+impl From<QueryDelta> for WebSocketEvent {
+    fn from(delta: QueryDelta) -> Self {
+        WebSocketEvent::QueryDelta {
+            subscription_id: delta.subscription_id,
+            collection: delta.change.collection,
+            document_id: delta.change.document_id,
+            delta: delta.kind,
+            action: delta.change.kind.into(),
+            data: delta.change.document,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
 /// WebSocket subscription management
 #[derive(Debug, Clone)]
 pub struct Subscription {
@@ -96,6 +309,7 @@ pub struct ConnectionManager {
 #[derive(Debug)]
 pub struct Connection {
     pub id: String,
+    pub credentials: ConnectionCredentials,
     pub subscriptions: Vec<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub last_activity: chrono::DateTime<chrono::Utc>,
@@ -112,9 +326,10 @@ impl ConnectionManager {
     }
 
     /// Add a new WebSocket connection
-    pub async fn add_connection(&self, connection_id: String) -> Result<()> {
+    pub async fn add_connection(&self, connection_id: String, credentials: ConnectionCredentials) -> Result<()> {
         let connection = Connection {
             id: connection_id.clone(),
+            credentials,
             subscriptions: Vec::new(),
             created_at: chrono::Utc::now(),
             last_activity: chrono::Utc::now(),
@@ -138,7 +353,20 @@ impl ConnectionManager {
         
         debug!("Removed WebSocket connection: {}", connection_id);
         Ok(())
-    }    /// Add a subscription for document changes
+    }
+
+    /// Record activity on a connection, returning the credentials it was
+    /// accepted with; unknown connections are anonymous
+    pub async fn touch(&self, connection_id: &str) -> ConnectionCredentials {
+        match self.connections.write().await.get_mut(connection_id) {
+            Some(connection) => {
+                connection.last_activity = chrono::Utc::now();
+                connection.credentials.clone()
+            }
+            None => ConnectionCredentials::default(),
+        }
+    }
+    /// Add a subscription for document changes
     pub async fn add_subscription(&self, subscription: Subscription) -> Result<()> {
         let mut subscriptions = self.subscriptions.write().await;
         let subscription_id = subscription.id.clone();
@@ -147,6 +375,14 @@ impl ConnectionManager {
         Ok(())
     }
 
+    /// Remove a single subscription
+    pub async fn remove_subscription(&self, subscription_id: &str) -> Result<()> {
+        let mut subscriptions = self.subscriptions.write().await;
+        subscriptions.remove(subscription_id);
+        debug!("Removed subscription: {}", subscription_id);
+        Ok(())
+    }
+
     /// Broadcast an event to all relevant subscribers
     pub async fn broadcast_event(&self, event: WebSocketEvent) -> Result<()> {
         match self.event_sender.send(event.clone()) {
@@ -182,6 +418,19 @@ impl ConnectionManager {
     }
 }
 
+impl From<PubSubMessage> for WebSocketEvent {
+    fn from(message: PubSubMessage) -> Self {
+        WebSocketEvent::ChannelMessage {
+            channel: message.channel,
+            message_id: message.id,
+            sequence: message.sequence,
+            payload: message.payload,
+            publisher: message.publisher,
+            timestamp: message.published_at.to_rfc3339(),
+        }
+    }
+}
+
 /// Connection statistics for monitoring
 #[derive(Debug, Serialize)]
 pub struct ConnectionStats {
@@ -194,58 +443,88 @@ pub struct ConnectionStats {
 pub struct RealtimeAPI {
     config: WebSocketConfig,
     query: Arc<QueryEngine>,
-    security: Arc<SecurityFramework>,
+
+    /// Admits request frames, shared with REST and gRPC
+    access: Arc<AccessPolicy>,
     connection_manager: Arc<ConnectionManager>,
+    journal: Arc<SubscriptionJournal>,
+    pubsub: Arc<PubSubBroker>,
+    journaled_sender: broadcast::Sender<JournaledEvent>,
+    live_queries: Arc<RwLock<HashMap<String, LiveQueryForward>>>,
+}
+
+/// Task forwarding the deltas of a live query to its connection.
+#[derive(Debug)]
+struct LiveQueryForward {
+    connection_id: String,
+    task: tokio::task::JoinHandle<()>,
 }
 
 impl RealtimeAPI {
     pub async fn new(
         config: &WebSocketConfig,
         query: Arc<QueryEngine>,
-        security: Arc<SecurityFramework>,
+        pubsub: Arc<PubSubBroker>,
     ) -> Result<Self> {
         info!("Initializing realtime WebSocket API with event streaming");
+        let journal = Arc::new(SubscriptionJournal::new(
+            Arc::clone(&query),
+            uuid::Uuid::new_v4().to_string(),
+            DEFAULT_JOURNAL_RETENTION,
+        ));
+        let (journaled_sender, _) = broadcast::channel(1000);
         Ok(Self {
             config: config.clone(),
             query,
-            security,
+            access: Arc::new(AccessPolicy::new(AccessConfig::default())?),
             connection_manager: Arc::new(ConnectionManager::new()),
+            journal,
+            pubsub,
+            journaled_sender,
+            live_queries: Arc::new(RwLock::new(HashMap::new())),
         })
     }
 
+    /// Replace the default access policy, which admits every request
+    pub fn with_access(mut self, access: Arc<AccessPolicy>) -> Self {
+        self.access = access;
+        self
+    }
+
     pub async fn start(&self) -> Result<()> {
         info!("Starting realtime WebSocket API on {}:{}", self.config.bind_address, self.config.port);
 
-        // Start WebSocket server with event streaming capabilities
-        let connection_manager = Arc::clone(&self.connection_manager);        let _query_engine = Arc::clone(&self.query);
-        let _security_framework = Arc::clone(&self.security);
-        let _config = self.config.clone();
-
+        // Publish the document changes written through the query engine
+        let realtime = self.clone();
+        let mut changes = self.query.subscriptions().changes();
         tokio::spawn(async move {
             info!("WebSocket server with real-time event streaming started");
-            
-            // WebSocket server implementation for real-time features:
-            // - Document change notifications via connection_manager.broadcast_event()
-            // - Live query results with automatic updates
-            // - Connection management with max_connections limit from config
-            // - Authentication and authorization via security_framework
-            // - Subscription management for targeted event delivery
-            
-            // Event streaming loop
             loop {
-                // Simulate document change detection
-                tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-                
-                let event = WebSocketEvent::DocumentChanged {
-                    collection: "sample_collection".to_string(),
-                    document_id: "doc_123".to_string(),
-                    action: DocumentAction::Updated,
-                    data: Some(serde_json::json!({"status": "updated"})),
-                    timestamp: chrono::Utc::now().to_rfc3339(),
-                };
-                
-                if let Err(e) = connection_manager.broadcast_event(event).await {
-                    warn!("Failed to broadcast WebSocket event: {}", e);
+                match changes.recv().await {
+                    Ok(change) => {
+                        let result = realtime
+                            .notify_document_change(&change.collection, &change.document_id, change.kind.into(), change.document)
+                            .await;
+                        if let Err(e) = result {
+                            warn!("Failed to broadcast WebSocket event: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("WebSocket change stream fell behind, {} document change(s) not published", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        // Prune journaled events that fall outside the resume window
+        let journal = Arc::clone(&self.journal);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = journal.prune().await {
+                    warn!("Failed to prune WebSocket event journal: {}", e);
                 }
             }
         });
@@ -276,7 +555,7 @@ impl RealtimeAPI {
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
 
-        self.connection_manager.broadcast_event(event).await
+        self.publish(event).await
     }
 
     /// Notify subscribers of query result updates
@@ -292,9 +571,58 @@ impl RealtimeAPI {
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
 
+        self.publish(event).await
+    }
+
+    /// Journal an event so it can be replayed after failover, then broadcast it.
+    async fn publish(&self, event: WebSocketEvent) -> Result<()> {
+        match self.journal.append(event.clone()).await {
+            Ok(journaled) => {
+                let _ = self.journaled_sender.send(journaled);
+            }
+            Err(e) => {
+                // Live subscribers still get the event; resumption may miss it
+                warn!("Failed to journal WebSocket event: {}", e);
+            }
+        }
+
         self.connection_manager.broadcast_event(event).await
     }
 
+    /// Receive journaled events, used by delivery loops to advance resume tokens.
+    pub fn subscribe_to_journaled_events(&self) -> broadcast::Receiver<JournaledEvent> {
+        self.journaled_sender.subscribe()
+    }
+
+    /// Register a connection opened outside the WebSocket server, such as
+    /// a Server-Sent Events stream, so it shows in the connection statistics.
+    pub async fn connect(&self, connection_id: String) -> Result<()> {
+        self.connection_manager.add_connection(connection_id, ConnectionCredentials::default()).await
+    }
+
+    /// Register a WebSocket connection with the credentials of its upgrade
+    /// request, which its request frames are admitted under.
+    pub async fn accept_connection(&self, connection_id: String, credentials: ConnectionCredentials) -> Result<()> {
+        self.connection_manager.add_connection(connection_id, credentials).await
+    }
+
+    /// Forget a closed connection and its subscriptions. Their replicated
+    /// state is kept, so they can still be resumed; its live queries end.
+    pub async fn disconnect(&self, connection_id: &str) -> Result<()> {
+        let ended: Vec<String> = self
+            .live_queries
+            .read()
+            .await
+            .iter()
+            .filter(|(_, forward)| forward.connection_id == connection_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for subscription_id in ended {
+            self.end_live_query(&subscription_id).await;
+        }
+        self.connection_manager.remove_connection(connection_id).await
+    }
+
     /// Get connection statistics for monitoring
     pub async fn get_connection_stats(&self) -> ConnectionStats {
         self.connection_manager.get_stats().await
@@ -308,6 +636,15 @@ impl RealtimeAPI {
         query: Option<serde_json::Value>,
     ) -> Result<String> {
         let subscription_id = uuid::Uuid::new_v4().to_string();
+
+        // Replicate the definition so another node can resume it after failover
+        self.journal.save_subscription(&SubscriptionState {
+            id: subscription_id.clone(),
+            collection: collection.clone(),
+            query: query.clone(),
+            created_at: chrono::Utc::now(),
+        }).await?;
+
         let subscription = Subscription {
             id: subscription_id.clone(),
             collection,
@@ -318,4 +655,295 @@ impl RealtimeAPI {
         self.connection_manager.add_subscription(subscription).await?;
         Ok(subscription_id)
     }
+
+    /// Resume a subscription on this node from a client's resume token.
+    /// 
+    /// The subscription may have been created on any node. Events the client
+    /// missed while reconnecting are returned in order along with an updated
+    /// token; an expired token is rejected rather than replayed with a gap.
+    pub async fn resume_subscription(
+        &self,
+        connection_id: String,
+        resume_token: &str,
+    ) -> Result<ResumedSubscription> {
+        let mut token = ResumeToken::decode(resume_token)?;
+        let state = self.journal.load_subscription(&token.subscription_id).await?;
+        let missed_events = self.journal.events_since(&state, &token).await?;
+
+        for event in &missed_events {
+            token.advance(event);
+        }
+
+        self.connection_manager.add_subscription(Subscription {
+            id: state.id.clone(),
+            collection: state.collection.clone(),
+            query: state.query.clone(),
+            connection_id,
+        }).await?;
+
+        info!(
+            "Resumed subscription {} on node {} with {} missed event(s)",
+            state.id, self.journal.origin(), missed_events.len()
+        );
+
+        Ok(ResumedSubscription {
+            subscription_id: state.id,
+            collection: state.collection,
+            query: state.query,
+            missed_events,
+            resume_token: token,
+        })
+    }
+
+    /// Publish an application message to a pub/sub channel.
+    pub async fn publish_message(
+        &self,
+        channel: &str,
+        payload: serde_json::Value,
+        publisher: Option<String>,
+    ) -> Result<PubSubMessage> {
+        self.pubsub.publish(channel, payload, publisher).await
+    }
+
+    /// Subscribe a connection to a pub/sub channel.
+    /// 
+    /// Returns the channel's retained messages followed by a receiver for
+    /// live messages, both ready to be sent as `ChannelMessage` events.
+    pub async fn subscribe_channel(
+        &self,
+        connection_id: &str,
+        channel: &str,
+    ) -> Result<(Vec<WebSocketEvent>, broadcast::Receiver<PubSubMessage>)> {
+        let (history, receiver) = self.pubsub.subscribe(channel).await?;
+        debug!("Connection {} subscribed to channel {}", connection_id, channel);
+        Ok((history.into_iter().map(WebSocketEvent::from).collect(), receiver))
+    }
+
+    /// Answer a text frame received on a connection.
+    /// 
+    /// A frame that is not a valid request is answered with an `Error` event,
+    /// since it has no request ID to correlate with.
+    pub async fn handle_frame(&self, connection_id: &str, frame: &str) -> WebSocketEvent {
+        match serde_json::from_str::<WebSocketRequest>(frame) {
+            Ok(request) => self.handle_request(connection_id, request).await,
+            Err(e) => WebSocketEvent::Error {
+                code: "invalid_frame".to_string(),
+                message: redact(&format!("Invalid request frame: {}", e)).into_owned(),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+            },
+        }
+    }
+
+    /// Run a request frame's query and build the answer for the connection
+    /// that sent it.
+    pub async fn handle_request(&self, connection_id: &str, request: WebSocketRequest) -> WebSocketEvent {
+        let credentials = self.connection_manager.touch(connection_id).await;
+        debug!("Connection {} sent request {}", connection_id, request.request_id());
+
+        // Ending a subscription only needs the connection that made it
+        if !matches!(request, WebSocketRequest::Unsubscribe { .. }) {
+            if let Err(rejection) = self.admit(&credentials) {
+                return WebSocketEvent::request_refused(request.request_id().to_string(), &rejection);
+            }
+        }
+
+        match request {
+            WebSocketRequest::Query { request_id, collection, query } => {
+                match self.query.query_documents(&collection, &query).await {
+                    Ok(result) => WebSocketEvent::QueryResult {
+                        request_id,
+                        documents: result.documents,
+                        total: result.total,
+                        execution_time_ms: result.execution_time.as_millis() as u64,
+                    },
+                    Err(e) => WebSocketEvent::request_failed(request_id, &e),
+                }
+            }
+            WebSocketRequest::Aggregate { request_id, collection, pipeline } => {
+                match self.query.aggregate(&collection, &pipeline).await {
+                    Ok(result) => WebSocketEvent::AggregateResult {
+                        request_id,
+                        documents: result.documents,
+                        scanned: result.scanned,
+                        execution_time_ms: result.execution_time.as_millis() as u64,
+                    },
+                    Err(e) => WebSocketEvent::request_failed(request_id, &e),
+                }
+            }
+            WebSocketRequest::Subscribe { request_id, collection, filter } => {
+                match self.subscribe_live(connection_id, &collection, filter).await {
+                    Ok((subscription_id, initial)) => WebSocketEvent::Subscribed {
+                        request_id,
+                        subscription_id,
+                        documents: initial.documents,
+                        total: initial.total,
+                    },
+                    Err(e) => WebSocketEvent::request_failed(request_id, &e),
+                }
+            }
+            WebSocketRequest::Unsubscribe { request_id, subscription_id } => {
+                let owned = self
+                    .live_queries
+                    .read()
+                    .await
+                    .get(&subscription_id)
+                    .is_some_and(|forward| forward.connection_id == connection_id);
+                if owned {
+                    self.end_live_query(&subscription_id).await;
+                    WebSocketEvent::Unsubscribed { request_id, subscription_id }
+                } else {
+                    let error = anyhow::anyhow!("Subscription not found: {}", subscription_id);
+                    WebSocketEvent::request_failed(request_id, &error)
+                }
+            }
+        }
+    }
+
+    /// Admit a request frame through the access policy.
+    pub fn admit(&self, credentials: &ConnectionCredentials) -> Result<SaaSContext, Rejection> {
+        self.access.admit(Protocol::WebSocket, credentials.credentials(), Instant::now())
+    }
+
+    /// Subscribe a connection to a live query, forwarding its deltas to
+    /// the connection until it ends.
+    ///
+    /// Returns the subscription ID and the live query's initial matches.
+    pub async fn subscribe_live(
+        &self,
+        connection_id: &str,
+        collection: &str,
+        filter: Option<serde_json::Value>,
+    ) -> Result<(String, QueryResult)> {
+        let LiveQuery { id, initial, mut deltas, .. } = self.query.subscribe_live(collection, filter.as_ref()).await?;
+        self.connection_manager.add_subscription(Subscription {
+            id: id.clone(),
+            collection: Some(collection.to_string()),
+            query: filter,
+            connection_id: connection_id.to_string(),
+        }).await?;
+
+        let connection_manager = Arc::clone(&self.connection_manager);
+        let task = tokio::spawn(async move {
+            while let Some(delta) = deltas.recv().await {
+                if let Err(e) = connection_manager.broadcast_event(delta.into()).await {
+                    warn!("Failed to broadcast live query delta: {}", e);
+                }
+            }
+        });
+        self.live_queries.write().await.insert(
+            id.clone(),
+            LiveQueryForward { connection_id: connection_id.to_string(), task },
+        );
+
+        debug!("Connection {} subscribed to live query {} on {}", connection_id, id, collection);
+        Ok((id, initial))
+    }
+
+    /// Stop a live query and the forwarding of its deltas.
+    async fn end_live_query(&self, subscription_id: &str) {
+        if let Some(forward) = self.live_queries.write().await.remove(subscription_id) {
+            forward.task.abort();
+        }
+        self.query.unsubscribe_live(subscription_id);
+        if let Err(e) = self.connection_manager.remove_subscription(subscription_id).await {
+            warn!("Failed to remove live query {}: {}", subscription_id, e);
+        }
+    }
+
+    /// End a subscription permanently, removing its replicated state.
+    pub async fn remove_subscription(&self, subscription_id: &str) -> Result<()> {
+        self.connection_manager.remove_subscription(subscription_id).await?;
+        self.journal.remove_subscription(subscription_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_frames_parse() {
+        let frame = r#"{"type": "Query", "request_id": "7", "collection": "orders", "query": {"filter": {"status": "open"}, "limit": 20}}"#;
+        match serde_json::from_str::<WebSocketRequest>(frame).unwrap() {
+            WebSocketRequest::Query { request_id, collection, query } => {
+                assert_eq!(request_id, "7");
+                assert_eq!(collection, "orders");
+                assert_eq!(query.limit, Some(20));
+                assert!(query.sort.is_none());
+            }
+            other => panic!("Unexpected request: {:?}", other),
+        }
+
+        let frame = r#"{"type": "Aggregate", "request_id": "8", "collection": "orders", "pipeline": [{"$limit": 1}]}"#;
+        let request: WebSocketRequest = serde_json::from_str(frame).unwrap();
+        assert_eq!(request.request_id(), "8");
+
+        assert!(serde_json::from_str::<WebSocketRequest>(r#"{"type": "Query", "collection": "orders"}"#).is_err());
+    }
+
+    #[test]
+    fn test_failures_keep_request_id() {
+        let invalid = WebSocketEvent::request_failed("1".to_string(), &anyhow::anyhow!("Invalid pipeline: unknown stage $foo"));
+        let failed = WebSocketEvent::request_failed("2".to_string(), &anyhow::anyhow!("storage unavailable"));
+
+        let json = serde_json::to_value(&invalid).unwrap();
+        assert_eq!(json["type"], "RequestFailed");
+        assert_eq!(json["request_id"], "1");
+        assert_eq!(json["code"], "invalid_request");
+        assert_eq!(serde_json::to_value(&failed).unwrap()["code"], "query_failed");
+    }
+
+    #[test]
+    fn test_refused_frames_are_coded_like_the_rejection() {
+        let policy = AccessPolicy::new(AccessConfig { require_auth: true, ..AccessConfig::default() }).unwrap();
+        let anonymous = ConnectionCredentials::default();
+        let rejection = policy.admit(Protocol::WebSocket, anonymous.credentials(), Instant::now()).unwrap_err();
+
+        let json = serde_json::to_value(WebSocketEvent::request_refused("3".to_string(), &rejection)).unwrap();
+        assert_eq!(json["type"], "RequestFailed");
+        assert_eq!(json["request_id"], "3");
+        assert_eq!(json["code"], "unauthenticated");
+        assert_eq!(policy.admissions(), vec![(Protocol::WebSocket, "unauthenticated", 1)]);
+    }
+
+    #[test]
+    fn test_live_query_deltas_reach_their_subscription() {
+        let frame = r#"{"type": "Subscribe", "request_id": "9", "collection": "orders", "filter": {"status": "open"}}"#;
+        match serde_json::from_str::<WebSocketRequest>(frame).unwrap() {
+            WebSocketRequest::Subscribe { collection, filter, .. } => {
+                assert_eq!(collection, "orders");
+                assert_eq!(filter, Some(serde_json::json!({"status": "open"})));
+            }
+            other => panic!("Unexpected request: {:?}", other),
+        }
+        let frame = r#"{"type": "Unsubscribe", "request_id": "10", "subscription_id": "live-1"}"#;
+        assert_eq!(serde_json::from_str::<WebSocketRequest>(frame).unwrap().request_id(), "10");
+
+        let event = WebSocketEvent::from(QueryDelta {
+            subscription_id: "live-1".to_string(),
+            kind: DeltaKind::Removed,
+            change: aerolithdb_query::DocumentChange {
+                collection: "orders".to_string(),
+                document_id: "o-17".to_string(),
+                kind: ChangeKind::Updated,
+                document: Some(serde_json::json!({"status": "closed"})),
+            },
+        });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "QueryDelta");
+        assert_eq!(json["delta"], "Removed");
+        assert_eq!(json["action"], "Updated");
+
+        let subscription = |id: &str| Subscription {
+            id: id.to_string(),
+            collection: Some("orders".to_string()),
+            query: None,
+            connection_id: "conn-1".to_string(),
+        };
+        assert!(subscription("live-1").matches(&event));
+        assert!(!subscription("live-2").matches(&event));
+
+        let missing = WebSocketEvent::request_failed("10".to_string(), &anyhow::anyhow!("Subscription not found: live-3"));
+        assert_eq!(serde_json::to_value(&missing).unwrap()["code"], "not_found");
+    }
 }
@@ -0,0 +1,256 @@
+//! # L1 Memory Cache Layer
+//!
+//! RAM-resident cache layer holding hot documents. The layer is bounded by a
+//! byte budget and evicts least-recently-used entries when an insert pushes it
+//! over budget.
+//!
+//! Recency is tracked with a logical clock instead of wall-clock time, so that
+//! reads only bump a counter and never allocate.
+//!
+//! Evicted entries are handed back to the caller so they can be demoted to a
+//! slower layer instead of being dropped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use dashmap::DashMap;
+
+use crate::CacheKey;
+
+/// Fraction of capacity eviction reclaims down to, leaving headroom so a burst
+/// of inserts does not trigger an eviction pass on every call
+pub(crate) const EVICTION_TARGET_PERCENT: u64 = 90;
+
+/// A cached document moving between layers.
+#[derive(Debug, Clone)]
+pub(crate) struct CachedEntry {
+    pub key: CacheKey,
+    pub value: serde_json::Value,
+    pub size: u64,
+    pub expires_at: Option<Instant>,
+}
+
+/// Outcome of a layer lookup, distinguishing expired entries from misses so
+/// stale reads can be counted.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Lookup<T> {
+    Hit(T),
+    /// An entry was found but had expired, and was dropped
+    Expired,
+    Miss,
+}
+
+impl<T> Lookup<T> {
+    pub fn hit(self) -> Option<T> {
+        match self {
+            Lookup::Hit(value) => Some(value),
+            Lookup::Expired | Lookup::Miss => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MemoryEntry {
+    value: serde_json::Value,
+    size: u64,
+    last_access: AtomicU64,
+    expires_at: Option<Instant>,
+}
+
+impl MemoryEntry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Byte-bounded LRU cache of documents.
+#[derive(Debug)]
+pub(crate) struct MemoryLayer {
+    entries: DashMap<CacheKey, MemoryEntry>,
+    used_bytes: AtomicU64,
+    capacity: AtomicU64,
+    clock: AtomicU64,
+}
+
+impl MemoryLayer {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            entries: DashMap::new(),
+            used_bytes: AtomicU64::new(0),
+            capacity: AtomicU64::new(capacity),
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    /// Read an entry, refreshing its recency. Expired entries are dropped.
+    pub fn get(&self, key: &CacheKey) -> Option<serde_json::Value> {
+        self.lookup(key).hit()
+    }
+
+    /// Read an entry like [`get`](Self::get), reporting whether a miss was
+    /// caused by expiry.
+    pub fn lookup(&self, key: &CacheKey) -> Lookup<serde_json::Value> {
+        let now = Instant::now();
+        {
+            let Some(entry) = self.entries.get(key) else { return Lookup::Miss };
+            if !entry.is_expired(now) {
+                entry.last_access.store(self.tick(), Ordering::Relaxed);
+                return Lookup::Hit(entry.value.clone());
+            }
+        }
+        self.remove(key);
+        Lookup::Expired
+    }
+
+    /// Insert or replace an entry of `size` bytes, returning the entries that
+    /// had to be evicted to stay within capacity. An entry larger than the
+    /// whole layer is not stored and is returned as evicted itself.
+    pub fn insert(
+        &self,
+        key: CacheKey,
+        value: serde_json::Value,
+        size: u64,
+        expires_at: Option<Instant>,
+    ) -> Vec<CachedEntry> {
+        let capacity = self.capacity();
+        if size > capacity {
+            self.remove(&key);
+            return vec![CachedEntry { key, value, size, expires_at }];
+        }
+
+        let entry = MemoryEntry {
+            value,
+            size,
+            last_access: AtomicU64::new(self.tick()),
+            expires_at,
+        };
+        if let Some(previous) = self.entries.insert(key, entry) {
+            self.used_bytes.fetch_sub(previous.size, Ordering::Relaxed);
+        }
+        self.used_bytes.fetch_add(size, Ordering::Relaxed);
+
+        if self.used_bytes() > capacity {
+            self.evict_to(capacity * EVICTION_TARGET_PERCENT / 100)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Change the byte budget, returning the entries evicted to fit a
+    /// smaller one.
+    pub fn set_capacity(&self, capacity: u64) -> Vec<CachedEntry> {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        if self.used_bytes() > capacity {
+            self.evict_to(capacity * EVICTION_TARGET_PERCENT / 100)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Remove an entry, returning whether it was present.
+    pub fn remove(&self, key: &CacheKey) -> bool {
+        self.take(key).is_some()
+    }
+
+    /// Copy out every unexpired entry, e.g. to persist them before shutdown.
+    pub fn entries(&self) -> Vec<CachedEntry> {
+        let now = Instant::now();
+        self.entries
+            .iter()
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| CachedEntry {
+                key: entry.key().clone(),
+                value: entry.value.clone(),
+                size: entry.size,
+                expires_at: entry.expires_at,
+            })
+            .collect()
+    }
+
+    /// Remove every entry of a collection, returning how many were removed.
+    pub fn remove_collection(&self, collection: &str) -> usize {
+        let keys: Vec<CacheKey> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.key().collection == collection)
+            .map(|entry| entry.key().clone())
+            .collect();
+        keys.iter().filter(|key| self.remove(key)).count()
+    }
+
+    /// Drop every expired entry, returning how many were removed.
+    pub fn remove_expired(&self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<CacheKey> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| entry.key().clone())
+            .collect();
+        let mut removed = 0;
+        for key in expired {
+            // Re-check under removal so an entry replaced since the scan survives
+            if let Some((_, entry)) = self.entries.remove_if(&key, |_, entry| entry.is_expired(now)) {
+                self.used_bytes.fetch_sub(entry.size, Ordering::Relaxed);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Evict least-recently-used entries until usage is at or below `target`.
+    /// Expired entries are dropped rather than returned.
+    fn evict_to(&self, target: u64) -> Vec<CachedEntry> {
+        let mut candidates: Vec<(u64, CacheKey)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.last_access.load(Ordering::Relaxed), entry.key().clone()))
+            .collect();
+        candidates.sort_unstable_by_key(|(last_access, _)| *last_access);
+
+        let now = Instant::now();
+        let mut evicted = Vec::new();
+        for (_, key) in candidates {
+            if self.used_bytes() <= target {
+                break;
+            }
+            if let Some(entry) = self.take(&key) {
+                if !entry.is_expired(now) {
+                    evicted.push(CachedEntry {
+                        key,
+                        value: entry.value,
+                        size: entry.size,
+                        expires_at: entry.expires_at,
+                    });
+                }
+            }
+        }
+        evicted
+    }
+
+    fn take(&self, key: &CacheKey) -> Option<MemoryEntry> {
+        let (_, entry) = self.entries.remove(key)?;
+        self.used_bytes.fetch_sub(entry.size, Ordering::Relaxed);
+        Some(entry)
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,321 @@
+//! # Cache Metrics
+//!
+//! Per-layer lookup, hit, eviction, and latency counters behind
+//! [`CacheStats`], and the rendering of a stats snapshot in the Prometheus
+//! text exposition format, which the API serves at `/metrics`.
+//!
+//! A lookup reaches a layer only when every faster layer missed, so a
+//! layer's hit rate is measured against the lookups that reached it rather
+//! than against all lookups.
+
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::{CacheLayer, CacheStats, TtlStrategyStats};
+
+/// Prefix of every exported metric name
+const METRIC_PREFIX: &str = "aerolithdb_cache";
+
+/// Name, type, help text, and value of a metric exported for every layer
+type LayerMetric = (&'static str, &'static str, &'static str, fn(&LayerStats) -> f64);
+
+/// Name, help text, and value of a counter exported for every TTL strategy
+type StrategyMetric = (&'static str, &'static str, fn(&TtlStrategyStats) -> u64);
+
+/// Lookup, eviction, and size figures of one cache layer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerStats {
+    /// Lookups that reached this layer
+    pub lookups: u64,
+    /// Lookups this layer answered with a live entry
+    pub hits: u64,
+    /// Entries removed to stay within capacity or dropped explicitly
+    pub evictions: u64,
+    pub entries: usize,
+    /// Bytes held, as stored
+    pub bytes: u64,
+    /// Bytes held before compression
+    pub logical_bytes: u64,
+    /// Byte budget of the layer
+    pub capacity: u64,
+    /// Time spent in lookups against this layer
+    pub lookup_time: Duration,
+}
+
+impl LayerStats {
+    /// Fraction of the lookups reaching this layer that it served, 0.0 when
+    /// none reached it
+    pub fn hit_rate(&self) -> f64 {
+        ratio(self.hits, self.lookups)
+    }
+
+    /// Uncompressed over stored size, 1.0 when the layer is empty or
+    /// uncompressed
+    pub fn compression_ratio(&self) -> f64 {
+        if self.bytes == 0 {
+            1.0
+        } else {
+            ratio(self.logical_bytes, self.bytes)
+        }
+    }
+
+    /// Average time of a lookup against this layer
+    pub fn mean_lookup_time(&self) -> Duration {
+        match self.lookups {
+            0 => Duration::ZERO,
+            lookups => self.lookup_time / lookups.min(u32::MAX as u64) as u32,
+        }
+    }
+}
+
+/// Counters of one layer.
+#[derive(Debug, Default)]
+pub(crate) struct LayerCounters {
+    lookups: AtomicU64,
+    hits: AtomicU64,
+    evictions: AtomicU64,
+    lookup_nanos: AtomicU64,
+}
+
+impl LayerCounters {
+    pub fn record_lookup(&self, hit: bool, elapsed: Duration) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        self.lookup_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    pub fn record_evictions(&self, count: usize) {
+        if count > 0 {
+            self.evictions.fetch_add(count as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters of every layer, shared with background tasks.
+#[derive(Debug, Default)]
+pub(crate) struct CacheMetrics {
+    memory: LayerCounters,
+    nvme: LayerCounters,
+    network: LayerCounters,
+}
+
+impl CacheMetrics {
+    pub fn layer(&self, layer: CacheLayer) -> &LayerCounters {
+        match layer {
+            CacheLayer::Memory => &self.memory,
+            CacheLayer::NVMe => &self.nvme,
+            CacheLayer::Network => &self.network,
+        }
+    }
+
+    /// Evictions across all layers.
+    pub fn evictions(&self) -> u64 {
+        self.memory.evictions() + self.nvme.evictions() + self.network.evictions()
+    }
+
+    /// Counters of a layer, with its size and capacity left for the caller.
+    pub fn snapshot(&self, layer: CacheLayer) -> LayerStats {
+        let counters = self.layer(layer);
+        LayerStats {
+            lookups: counters.lookups.load(Ordering::Relaxed),
+            hits: counters.hits.load(Ordering::Relaxed),
+            evictions: counters.evictions(),
+            lookup_time: Duration::from_nanos(counters.lookup_nanos.load(Ordering::Relaxed)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Name of a layer in stats and metric labels.
+pub(crate) fn layer_name(layer: CacheLayer) -> &'static str {
+    match layer {
+        CacheLayer::Memory => "memory",
+        CacheLayer::NVMe => "nvme",
+        CacheLayer::Network => "network",
+    }
+}
+
+impl CacheStats {
+    /// Render the snapshot in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = Exposition::default();
+
+        out.family("hits_total", "counter", "Lookups served from any cache layer");
+        out.sample("hits_total", &[], self.hits);
+        out.family("misses_total", "counter", "Lookups no cache layer could serve");
+        out.sample("misses_total", &[], self.misses);
+        out.family("hit_ratio", "gauge", "Fraction of lookups served from the cache");
+        out.sample("hit_ratio", &[], self.hit_rate());
+        out.family("evictions_total", "counter", "Entries evicted from any cache layer");
+        out.sample("evictions_total", &[], self.evictions);
+        out.family("promotions_total", "counter", "Entries copied up into the memory layer");
+        out.sample("promotions_total", &[], self.promotions);
+        out.family("demotions_total", "counter", "Memory evictions persisted to the NVMe layer");
+        out.sample("demotions_total", &[], self.demotions);
+        out.family("peer_failovers_total", "counter", "Network layer reads that skipped a failed replica");
+        out.sample("peer_failovers_total", &[], self.peer_failovers);
+
+        let layers: Vec<(&str, &LayerStats)> = self.layers.iter().map(|(name, stats)| (name.as_str(), stats)).collect();
+        let per_layer: [LayerMetric; 8] = [
+            ("layer_lookups_total", "counter", "Lookups that reached the layer", |s| s.lookups as f64),
+            ("layer_hits_total", "counter", "Lookups the layer served", |s| s.hits as f64),
+            ("layer_hit_ratio", "gauge", "Fraction of lookups reaching the layer that it served", LayerStats::hit_rate),
+            ("layer_evictions_total", "counter", "Entries evicted from the layer", |s| s.evictions as f64),
+            ("layer_entries", "gauge", "Entries held by the layer", |s| s.entries as f64),
+            ("layer_bytes", "gauge", "Bytes held by the layer as stored", |s| s.bytes as f64),
+            ("layer_capacity_bytes", "gauge", "Byte budget of the layer", |s| s.capacity as f64),
+            ("layer_compression_ratio", "gauge", "Uncompressed over stored size of the layer", LayerStats::compression_ratio),
+        ];
+        for (name, kind, help, value) in per_layer {
+            out.family(name, kind, help);
+            for (layer, stats) in &layers {
+                out.sample(name, &[("layer", layer)], value(stats));
+            }
+        }
+        out.family("layer_lookup_duration_seconds", "summary", "Time spent in lookups against the layer");
+        for (layer, stats) in &layers {
+            out.sample("layer_lookup_duration_seconds_sum", &[("layer", layer)], stats.lookup_time.as_secs_f64());
+            out.sample("layer_lookup_duration_seconds_count", &[("layer", layer)], stats.lookups);
+        }
+
+        let per_strategy: [StrategyMetric; 3] = [
+            ("ttl_hits_total", "Lookups served from a live entry, by TTL strategy", |s| s.hits),
+            ("ttl_stale_total", "Lookups that only found expired entries, by TTL strategy", |s| s.stale),
+            ("ttl_expired_total", "Expired entries removed by the sweeper, by TTL strategy", |s| s.expired),
+        ];
+        for (name, help, value) in per_strategy {
+            out.family(name, "counter", help);
+            for (strategy, stats) in &self.ttl {
+                out.sample(name, &[("strategy", strategy)], value(stats));
+            }
+        }
+
+        let prefetch = &self.prefetch;
+        out.family("prefetch_predictions_total", "counter", "Keys predicted to be read next");
+        out.sample("prefetch_predictions_total", &[], prefetch.predictions);
+        out.family("prefetch_correct_total", "counter", "Predictions read within the prediction window");
+        out.sample("prefetch_correct_total", &[], prefetch.correct);
+        out.family("prefetch_missed_total", "counter", "Predictions not read within the prediction window");
+        out.sample("prefetch_missed_total", &[], prefetch.missed);
+        out.family("prefetch_loaded_total", "counter", "Documents loaded into the cache by the prefetcher");
+        out.sample("prefetch_loaded_total", &[], prefetch.prefetched);
+        out.family("prefetch_failures_total", "counter", "Prefetch loads that failed");
+        out.sample("prefetch_failures_total", &[], prefetch.failures);
+        out.family("prefetch_accuracy", "gauge", "Fraction of scored predictions that were read");
+        out.sample("prefetch_accuracy", &[], prefetch.accuracy());
+
+        let results = &self.query_results;
+        out.family("query_result_entries", "gauge", "Query result sets currently cached");
+        out.sample("query_result_entries", &[], results.entries);
+        out.family("query_result_hits_total", "counter", "Queries answered from the query result cache");
+        out.sample("query_result_hits_total", &[], results.hits);
+        out.family("query_result_misses_total", "counter", "Queries not found in the query result cache");
+        out.sample("query_result_misses_total", &[], results.misses);
+        out.family("query_result_invalidations_total", "counter", "Cached query results dropped because their collection changed");
+        out.sample("query_result_invalidations_total", &[], results.invalidations);
+        out.family("query_result_oversized_total", "counter", "Query results too large to cache");
+        out.sample("query_result_oversized_total", &[], results.oversized);
+        out.family("query_result_evictions_total", "counter", "Cached query results evicted to stay within max_entries");
+        out.sample("query_result_evictions_total", &[], results.evictions);
+
+        out.text
+    }
+}
+
+/// Builder of Prometheus text exposition output.
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {}_{} {}", METRIC_PREFIX, name, help);
+        let _ = writeln!(self.text, "# TYPE {}_{} {}", METRIC_PREFIX, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl fmt::Display) {
+        let _ = write!(self.text, "{}_{}", METRIC_PREFIX, name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_counters() {
+        let metrics = CacheMetrics::default();
+        let memory = metrics.layer(CacheLayer::Memory);
+        memory.record_lookup(true, Duration::from_micros(2));
+        memory.record_lookup(false, Duration::from_micros(4));
+        memory.record_evictions(3);
+        metrics.layer(CacheLayer::NVMe).record_evictions(2);
+
+        let stats = LayerStats { bytes: 50, logical_bytes: 200, ..metrics.snapshot(CacheLayer::Memory) };
+        assert_eq!((stats.lookups, stats.hits, stats.evictions), (2, 1, 3));
+        assert_eq!(stats.hit_rate(), 0.5);
+        assert_eq!(stats.mean_lookup_time(), Duration::from_micros(3));
+        assert_eq!(stats.compression_ratio(), 4.0);
+        assert_eq!(metrics.evictions(), 5);
+        assert_eq!(LayerStats::default().compression_ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_prometheus_exposition() {
+        let mut stats = CacheStats { hits: 3, misses: 1, ..Default::default() };
+        stats.layers.insert("memory".to_string(), LayerStats { lookups: 4, hits: 3, ..Default::default() });
+        stats.ttl.insert("lru".to_string(), Default::default());
+
+        let text = stats.to_prometheus();
+        assert!(text.contains("# TYPE aerolithdb_cache_hits_total counter\naerolithdb_cache_hits_total 3\n"));
+        assert!(text.contains("aerolithdb_cache_hit_ratio 0.75\n"));
+        assert!(text.contains("aerolithdb_cache_layer_hit_ratio{layer=\"memory\"} 0.75\n"));
+        assert!(text.contains("aerolithdb_cache_layer_lookup_duration_seconds_count{layer=\"memory\"} 4\n"));
+        assert!(text.contains("aerolithdb_cache_ttl_hits_total{strategy=\"lru\"} 0\n"));
+
+        // Every sample is a number and follows its own family's header
+        let mut family = "";
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix("# TYPE ") {
+                family = name.split(' ').next().unwrap();
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.rsplit_once(' ').unwrap();
+            assert!(name.starts_with(family), "{} outside {}", line, family);
+            assert!(value.parse::<f64>().is_ok(), "{}", line);
+        }
+        assert_eq!(escape_label("a\"b\\"), "a\\\"b\\\\");
+    }
+}
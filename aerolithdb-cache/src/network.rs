@@ -0,0 +1,448 @@
+//! # L3 Network Cache Layer
+//!
+//! Distributed cache tier shared by every node in the cluster. Keys are
+//! assigned to peers with a consistent-hash ring, so adding or removing a node
+//! only moves the keys adjacent to it on the ring. Each key is stored on
+//! `replication_factor` distinct peers; reads try the owners in ring order and
+//! fail over to the next replica when a peer errors or times out.
+//!
+//! Peers that fail are skipped for a back-off period rather than removed from
+//! the ring, so a brief outage does not reshuffle ownership. Every node serves
+//! its own share of the ring from a byte-bounded local shard.
+//!
+//! Messages between peers go through a [`CachePeerTransport`]; the default
+//! [`HttpCacheTransport`] posts them to [`CACHE_PEER_PATH`] on the REST API
+//! of each peer. Requests for keys owned by this node are served in-process.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::memory::MemoryLayer;
+use crate::CacheKey;
+
+/// Configuration of the network cache layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkCacheConfig {
+    /// This node's identity on the hash ring; must be unique in the cluster
+    pub local_peer_id: String,
+
+    /// Number of distinct peers holding each entry
+    pub replication_factor: usize,
+
+    /// Ring positions per peer; more positions spread keys more evenly
+    pub virtual_nodes: usize,
+
+    /// Byte budget of the shard this node serves to the cluster
+    pub max_local_usage: u64,
+
+    /// Maximum time to wait for a peer before failing over to the next replica
+    pub request_timeout: Duration,
+
+    /// How long a failed peer is skipped before it is tried again
+    pub failure_backoff: Duration,
+}
+
+impl Default for NetworkCacheConfig {
+    fn default() -> Self {
+        Self {
+            local_peer_id: "local_peer".to_string(),
+            replication_factor: 2,
+            virtual_nodes: 64,
+            max_local_usage: 256 * 1024 * 1024, // 256MB
+            request_timeout: Duration::from_millis(250),
+            failure_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Request sent to a peer's network cache shard.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CachePeerRequest {
+    Get { key: CacheKey },
+    Put { key: CacheKey, value: serde_json::Value, ttl_ms: Option<u64> },
+    Remove { key: CacheKey },
+    RemoveCollection { collection: String },
+}
+
+/// Reply from a peer's network cache shard.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CachePeerResponse {
+    Value(Option<serde_json::Value>),
+    Stored,
+    Removed(usize),
+}
+
+pub type PeerResponseFuture<'a> = Pin<Box<dyn Future<Output = Result<CachePeerResponse>> + Send + 'a>>;
+
+/// Path of the REST endpoint passing cache requests from other nodes to
+/// `IntelligentCacheSystem::handle_peer_request`
+pub const CACHE_PEER_PATH: &str = "/api/v1/internal/cache";
+
+/// Delivers cache requests to other nodes. The receiving node passes each
+/// request to `IntelligentCacheSystem::handle_peer_request`.
+pub trait CachePeerTransport: Send + Sync {
+    fn send<'a>(&'a self, peer: &'a str, request: CachePeerRequest) -> PeerResponseFuture<'a>;
+}
+
+/// Posts cache requests to the REST API of each peer, at the addresses the
+/// cluster membership last reported.
+#[derive(Debug)]
+pub struct HttpCacheTransport {
+    client: reqwest::Client,
+    /// Base URL of each peer's REST API, by peer id
+    addresses: RwLock<HashMap<String, String>>,
+}
+
+impl HttpCacheTransport {
+    pub fn new(timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            addresses: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Replace the addresses peers are reached at.
+    pub fn set_addresses(&self, addresses: HashMap<String, String>) {
+        *self.addresses.write().unwrap_or_else(|e| e.into_inner()) = addresses;
+    }
+}
+
+impl CachePeerTransport for HttpCacheTransport {
+    fn send<'a>(&'a self, peer: &'a str, request: CachePeerRequest) -> PeerResponseFuture<'a> {
+        Box::pin(async move {
+            let address = self
+                .addresses
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(peer)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Cache peer {} has no address", peer))?;
+            let response = self
+                .client
+                .post(format!("{}{}", address.trim_end_matches('/'), CACHE_PEER_PATH))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&request)?)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!("Cache peer {} refused the request: {}", peer, response.status()));
+            }
+            Ok(serde_json::from_slice(&response.bytes().await?)?)
+        })
+    }
+}
+
+/// Consistent-hash ring mapping keys to peers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HashRing {
+    positions: BTreeMap<u64, String>,
+    virtual_nodes: usize,
+}
+
+impl HashRing {
+    pub fn new(virtual_nodes: usize) -> Self {
+        Self {
+            positions: BTreeMap::new(),
+            virtual_nodes: virtual_nodes.max(1),
+        }
+    }
+
+    pub fn add_peer(&mut self, peer: &str) {
+        for replica in 0..self.virtual_nodes {
+            self.positions.insert(hash(format!("{}#{}", peer, replica).as_bytes()), peer.to_string());
+        }
+    }
+
+    pub fn remove_peer(&mut self, peer: &str) {
+        self.positions.retain(|_, owner| owner != peer);
+    }
+
+    pub fn contains(&self, peer: &str) -> bool {
+        self.positions.values().any(|owner| owner == peer)
+    }
+
+    /// Up to `count` distinct peers owning `key`, in ring order from its hash.
+    pub fn owners(&self, key: &CacheKey, count: usize) -> Vec<String> {
+        let start = key_hash(key);
+        let mut owners: Vec<String> = Vec::with_capacity(count);
+        for (_, peer) in self.positions.range(start..).chain(self.positions.range(..start)) {
+            if owners.len() == count {
+                break;
+            }
+            if !owners.contains(peer) {
+                owners.push(peer.clone());
+            }
+        }
+        owners
+    }
+
+    pub fn peers(&self) -> Vec<String> {
+        let mut peers: Vec<String> = self.positions.values().cloned().collect();
+        peers.sort();
+        peers.dedup();
+        peers
+    }
+}
+
+/// Cluster-wide cache tier: a hash ring over peers plus this node's shard.
+pub(crate) struct NetworkLayer {
+    config: NetworkCacheConfig,
+    ring: RwLock<HashRing>,
+    /// Entries this node serves on behalf of the cluster
+    shard: MemoryLayer,
+    /// Shard entries evicted to stay within the local budget
+    evictions: AtomicU64,
+    transport: RwLock<Option<Arc<dyn CachePeerTransport>>>,
+    /// Failed peers and when they may be tried again
+    suspects: DashMap<String, Instant>,
+}
+
+impl NetworkLayer {
+    pub fn new(config: &NetworkCacheConfig) -> Self {
+        let mut ring = HashRing::new(config.virtual_nodes);
+        ring.add_peer(&config.local_peer_id);
+
+        Self {
+            config: config.clone(),
+            ring: RwLock::new(ring),
+            shard: MemoryLayer::new(config.max_local_usage),
+            evictions: AtomicU64::new(0),
+            transport: RwLock::new(None),
+            suspects: DashMap::new(),
+        }
+    }
+
+    pub fn set_transport(&self, transport: Arc<dyn CachePeerTransport>) {
+        *self.transport.write().unwrap_or_else(|e| e.into_inner()) = Some(transport);
+    }
+
+    pub fn add_peer(&self, peer: &str) {
+        let mut ring = self.ring.write().unwrap_or_else(|e| e.into_inner());
+        if !ring.contains(peer) {
+            ring.add_peer(peer);
+        }
+        self.suspects.remove(peer);
+    }
+
+    pub fn remove_peer(&self, peer: &str) {
+        if peer != self.config.local_peer_id {
+            self.ring.write().unwrap_or_else(|e| e.into_inner()).remove_peer(peer);
+        }
+        self.suspects.remove(peer);
+    }
+
+    pub fn peers(&self) -> Vec<String> {
+        self.ring.read().unwrap_or_else(|e| e.into_inner()).peers()
+    }
+
+    pub fn local_peer_id(&self) -> &str {
+        &self.config.local_peer_id
+    }
+
+    /// Fetch from the first replica that answers. Returns the value and how
+    /// many replicas had to be skipped because they failed.
+    pub async fn get(&self, key: &CacheKey) -> (Option<serde_json::Value>, usize) {
+        let mut failovers = 0;
+        for peer in self.replicas(key) {
+            match self.request(&peer, CachePeerRequest::Get { key: key.clone() }).await {
+                Ok(CachePeerResponse::Value(value)) => return (value, failovers),
+                Ok(other) => warn!("Unexpected reply {:?} from cache peer {}", other, peer),
+                Err(e) => self.mark_failed(&peer, &e),
+            }
+            failovers += 1;
+        }
+        (None, failovers)
+    }
+
+    /// Store on every replica, succeeding if at least one accepted the entry.
+    pub async fn put(&self, key: &CacheKey, value: &serde_json::Value, ttl: Option<Duration>) -> Result<()> {
+        let ttl_ms = ttl.map(|ttl| ttl.as_millis() as u64);
+        let mut stored = 0;
+        for peer in self.replicas(key) {
+            let request = CachePeerRequest::Put { key: key.clone(), value: value.clone(), ttl_ms };
+            match self.request(&peer, request).await {
+                Ok(_) => stored += 1,
+                Err(e) => self.mark_failed(&peer, &e),
+            }
+        }
+
+        if stored == 0 {
+            return Err(anyhow::anyhow!("No network cache replica accepted {:?}", key));
+        }
+        Ok(())
+    }
+
+    /// Remove from every replica, returning whether any held the entry.
+    pub async fn remove(&self, key: &CacheKey) -> bool {
+        let mut removed = false;
+        for peer in self.owners(key) {
+            match self.request(&peer, CachePeerRequest::Remove { key: key.clone() }).await {
+                Ok(CachePeerResponse::Removed(count)) => removed |= count > 0,
+                Ok(_) => {}
+                Err(e) => self.mark_failed(&peer, &e),
+            }
+        }
+        removed
+    }
+
+    /// Remove a collection from every peer's shard, returning how many
+    /// replicas were removed.
+    pub async fn remove_collection(&self, collection: &str) -> usize {
+        let mut removed = 0;
+        for peer in self.peers() {
+            let request = CachePeerRequest::RemoveCollection { collection: collection.to_string() };
+            match self.request(&peer, request).await {
+                Ok(CachePeerResponse::Removed(count)) => removed += count,
+                Ok(_) => {}
+                Err(e) => self.mark_failed(&peer, &e),
+            }
+        }
+        removed
+    }
+
+    /// Serve a request against this node's shard.
+    pub fn handle(&self, request: CachePeerRequest) -> CachePeerResponse {
+        match request {
+            CachePeerRequest::Get { key } => CachePeerResponse::Value(self.shard.get(&key)),
+            CachePeerRequest::Put { key, value, ttl_ms } => {
+                let size = serde_json::to_vec(&value).map(|bytes| bytes.len() as u64).unwrap_or(0);
+                let expires_at = ttl_ms.map(|ttl_ms| Instant::now() + Duration::from_millis(ttl_ms));
+                let evicted = self.shard.insert(key, value, size, expires_at);
+                if !evicted.is_empty() {
+                    debug!("Network cache shard evicted {} entries", evicted.len());
+                    self.evictions.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+                }
+                CachePeerResponse::Stored
+            }
+            CachePeerRequest::Remove { key } => CachePeerResponse::Removed(self.shard.remove(&key) as usize),
+            CachePeerRequest::RemoveCollection { collection } => {
+                CachePeerResponse::Removed(self.shard.remove_collection(&collection))
+            }
+        }
+    }
+
+    /// Drop expired entries from this node's shard.
+    pub fn remove_expired(&self) -> usize {
+        self.shard.remove_expired()
+    }
+
+    pub fn local_entries(&self) -> usize {
+        self.shard.len()
+    }
+
+    pub fn local_bytes(&self) -> u64 {
+        self.shard.used_bytes()
+    }
+
+    pub fn local_capacity(&self) -> u64 {
+        self.shard.capacity()
+    }
+
+    pub fn local_evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    fn owners(&self, key: &CacheKey) -> Vec<String> {
+        let ring = self.ring.read().unwrap_or_else(|e| e.into_inner());
+        ring.owners(key, self.config.replication_factor.max(1))
+    }
+
+    /// Owners of `key` with peers in back-off moved to the end, so they are
+    /// only tried when every healthy replica has failed.
+    fn replicas(&self, key: &CacheKey) -> Vec<String> {
+        let now = Instant::now();
+        let (healthy, suspect): (Vec<String>, Vec<String>) = self
+            .owners(key)
+            .into_iter()
+            .partition(|peer| self.suspects.get(peer).is_none_or(|retry_at| *retry_at <= now));
+        healthy.into_iter().chain(suspect).collect()
+    }
+
+    async fn request(&self, peer: &str, request: CachePeerRequest) -> Result<CachePeerResponse> {
+        if peer == self.config.local_peer_id {
+            return Ok(self.handle(request));
+        }
+
+        let transport = self
+            .transport
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No cache transport attached to reach peer {}", peer))?;
+
+        let response = tokio::time::timeout(self.config.request_timeout, transport.send(peer, request))
+            .await
+            .map_err(|_| anyhow::anyhow!("Cache peer {} timed out", peer))??;
+        self.suspects.remove(peer);
+        Ok(response)
+    }
+
+    fn mark_failed(&self, peer: &str, error: &anyhow::Error) {
+        warn!("Cache peer {} failed, backing off: {}", peer, error);
+        self.suspects.insert(peer.to_string(), Instant::now() + self.config.failure_backoff);
+    }
+}
+
+impl fmt::Debug for NetworkLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkLayer")
+            .field("local_peer_id", &self.config.local_peer_id)
+            .field("peers", &self.peers())
+            .field("local_entries", &self.local_entries())
+            .finish_non_exhaustive()
+    }
+}
+
+fn key_hash(key: &CacheKey) -> u64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(key.collection.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(key.document_id.as_bytes());
+    first_u64(hasher.finalize().as_bytes())
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    first_u64(blake3::hash(bytes).as_bytes())
+}
+
+fn first_u64(bytes: &[u8; 32]) -> u64 {
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&bytes[..8]);
+    u64::from_be_bytes(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_owners_are_distinct_and_stable() {
+        let mut ring = HashRing::new(32);
+        for peer in ["a", "b", "c", "d"] {
+            ring.add_peer(peer);
+        }
+
+        let keys: Vec<CacheKey> = (0..200).map(|i| CacheKey::new("c", i.to_string())).collect();
+        let before: Vec<Vec<String>> = keys.iter().map(|key| ring.owners(key, 2)).collect();
+        assert!(before.iter().all(|owners| owners.len() == 2 && owners[0] != owners[1]));
+
+        // Removing a peer only reassigns the keys it owned
+        ring.remove_peer("d");
+        for (key, owners) in keys.iter().zip(&before) {
+            if owners[0] != "d" {
+                assert_eq!(ring.owners(key, 1)[0], owners[0]);
+            }
+        }
+        assert_eq!(ring.owners(&keys[0], 10).len(), 3);
+    }
+}
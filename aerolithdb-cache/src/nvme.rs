@@ -0,0 +1,637 @@
+//! # L2 NVMe Cache Layer
+//!
+//! SSD-backed cache layer that keeps warm documents across process restarts.
+//! Each entry lives in its own file, named by a hash of its key, and is checked
+//! against a BLAKE3 checksum every time it is read back.
+//!
+//! ## Crash Consistency
+//!
+//! Every change is appended to a write-ahead journal and synced before the
+//! entry file is touched. Entry files are written to a temporary file and
+//! renamed into place, so a reader never sees a partial write.
+//!
+//! On startup the journal is replayed to rebuild the index:
+//! - A torn record at the end of the journal (a crash mid-append) ends replay
+//! - Entries whose file is missing or has the wrong size are dropped
+//! - Expired entries are dropped
+//! - Files the journal does not reference are deleted
+//!
+//! The journal is then rewritten as a compact snapshot of the surviving
+//! entries. It is compacted the same way at runtime once it holds many more
+//! records than there are live entries.
+//!
+//! ## Compression
+//!
+//! With compression enabled, entry files are LZ4-compressed when that makes
+//! them smaller. The capacity bounds the bytes on disk; the uncompressed size
+//! is tracked alongside to report the compression ratio.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::memory::{CachedEntry, Lookup, EVICTION_TARGET_PERCENT};
+use crate::CacheKey;
+
+const JOURNAL_FILE: &str = "journal.log";
+const ENTRIES_DIR: &str = "entries";
+const ENTRY_EXTENSION: &str = "entry";
+
+/// Journal records allowed beyond the live entry count before compaction
+const COMPACTION_SLACK: u64 = 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JournalRecord {
+    Put {
+        key: CacheKey,
+        /// BLAKE3 hex digest of the entry file
+        checksum: String,
+        size: u64,
+        /// Expiry as milliseconds since the Unix epoch
+        expires_at_ms: Option<u64>,
+        /// Uncompressed size, present when the entry file is LZ4-compressed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        logical_size: Option<u64>,
+    },
+    Remove {
+        key: CacheKey,
+    },
+}
+
+#[derive(Debug)]
+struct NvmeEntry {
+    checksum: String,
+    size: u64,
+    /// Uncompressed size, when the entry file is compressed
+    logical_size: Option<u64>,
+    expires_at: Option<SystemTime>,
+    last_access: AtomicU64,
+}
+
+impl NvmeEntry {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    fn logical_size(&self) -> u64 {
+        self.logical_size.unwrap_or(self.size)
+    }
+}
+
+/// The latest `put` record of a key found while replaying the journal.
+struct Replayed {
+    /// Line of the record, which orders entries by recency
+    position: u64,
+    checksum: String,
+    size: u64,
+    expires_at_ms: Option<u64>,
+    logical_size: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Journal {
+    file: File,
+    records: u64,
+}
+
+/// Byte-bounded LRU cache of documents persisted to local SSD storage.
+#[derive(Debug)]
+pub(crate) struct NvmeLayer {
+    dir: PathBuf,
+    index: DashMap<CacheKey, NvmeEntry>,
+    /// Serializes writers so journal order matches the order files change
+    journal: Mutex<Journal>,
+    used_bytes: AtomicU64,
+    /// Uncompressed size of the entries behind `used_bytes`
+    logical_bytes: AtomicU64,
+    capacity: AtomicU64,
+    compress: AtomicBool,
+    clock: AtomicU64,
+}
+
+impl NvmeLayer {
+    /// Open the layer in `dir`, recovering entries written before the last
+    /// shutdown or crash.
+    pub async fn open(dir: &Path, capacity: u64) -> Result<Self> {
+        fs::create_dir_all(dir.join(ENTRIES_DIR)).await?;
+
+        let layer = Self {
+            dir: dir.to_path_buf(),
+            index: DashMap::new(),
+            journal: Mutex::new(Journal {
+                file: open_journal(dir).await?,
+                records: 0,
+            }),
+            used_bytes: AtomicU64::new(0),
+            logical_bytes: AtomicU64::new(0),
+            capacity: AtomicU64::new(capacity),
+            compress: AtomicBool::new(false),
+            clock: AtomicU64::new(0),
+        };
+        layer.recover().await?;
+        Ok(layer)
+    }
+
+    /// Read an entry, verifying its checksum. Expired, missing, and corrupt
+    /// entries are dropped; expired ones are reported separately from misses.
+    pub async fn lookup(&self, key: &CacheKey) -> Result<Lookup<CachedEntry>> {
+        let (checksum, logical_size, expires_at) = {
+            let Some(entry) = self.index.get(key) else { return Ok(Lookup::Miss) };
+            if entry.is_expired(SystemTime::now()) {
+                drop(entry);
+                self.remove(key).await?;
+                return Ok(Lookup::Expired);
+            }
+            entry.last_access.store(self.tick(), Ordering::Relaxed);
+            (entry.checksum.clone(), entry.logical_size, entry.expires_at)
+        };
+
+        let bytes = match fs::read(self.entry_path(key)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        if blake3::hash(&bytes).to_hex().as_str() != checksum {
+            // A concurrent write may have replaced the entry since the index was read
+            if self.index.get(key).is_some_and(|entry| entry.checksum == checksum) {
+                warn!("NVMe cache entry {:?} failed checksum verification; dropping it", key);
+                self.remove(key).await?;
+            }
+            return Ok(Lookup::Miss);
+        }
+
+        let json = match logical_size {
+            Some(_) => lz4_flex::decompress_size_prepended(&bytes)?,
+            None => bytes,
+        };
+        Ok(Lookup::Hit(CachedEntry {
+            key: key.clone(),
+            value: serde_json::from_slice(&json)?,
+            size: json.len() as u64,
+            expires_at: expires_at.map(to_instant),
+        }))
+    }
+
+    /// Persist an entry, returning how many entries had to be evicted to stay
+    /// within capacity. Entries larger than the whole layer are not stored.
+    pub async fn insert(&self, entry: &CachedEntry) -> Result<usize> {
+        let json = serde_json::to_vec(&entry.value)?;
+        // Small documents often grow under LZ4; those are stored as they are
+        let compressed = self.compress.load(Ordering::Relaxed).then(|| lz4_flex::compress_prepend_size(&json));
+        let (bytes, logical_size) = match compressed {
+            Some(compressed) if compressed.len() < json.len() => (compressed, Some(json.len() as u64)),
+            _ => (json, None),
+        };
+        let size = bytes.len() as u64;
+        if size > self.capacity() {
+            self.remove(&entry.key).await?;
+            return Ok(0);
+        }
+
+        let checksum = blake3::hash(&bytes).to_hex().to_string();
+        let expires_at = entry.expires_at.map(to_system_time);
+
+        let mut journal = self.journal.lock().await;
+        append(&mut journal, &JournalRecord::Put {
+            key: entry.key.clone(),
+            checksum: checksum.clone(),
+            size,
+            expires_at_ms: expires_at.map(to_unix_millis),
+            logical_size,
+        })
+        .await?;
+        write_atomically(&self.entry_path(&entry.key), &bytes).await?;
+
+        let stored = NvmeEntry {
+            checksum,
+            size,
+            logical_size,
+            expires_at,
+            last_access: AtomicU64::new(self.tick()),
+        };
+        let logical_bytes = stored.logical_size();
+        if let Some(previous) = self.index.insert(entry.key.clone(), stored) {
+            self.used_bytes.fetch_sub(previous.size, Ordering::Relaxed);
+            self.logical_bytes.fetch_sub(previous.logical_size(), Ordering::Relaxed);
+        }
+        self.used_bytes.fetch_add(size, Ordering::Relaxed);
+        self.logical_bytes.fetch_add(logical_bytes, Ordering::Relaxed);
+
+        let capacity = self.capacity();
+        let evicted = if self.used_bytes() > capacity {
+            self.evict_to(&mut journal, capacity * EVICTION_TARGET_PERCENT / 100).await?
+        } else {
+            0
+        };
+
+        if journal.records > self.index.len() as u64 + COMPACTION_SLACK {
+            self.compact(&mut journal).await?;
+        }
+        Ok(evicted)
+    }
+
+    /// Remove an entry, returning whether it was present.
+    pub async fn remove(&self, key: &CacheKey) -> Result<bool> {
+        if !self.index.contains_key(key) {
+            return Ok(false);
+        }
+        let mut journal = self.journal.lock().await;
+        self.remove_locked(&mut journal, key).await
+    }
+
+    /// Remove every entry of a collection, returning how many were removed.
+    pub async fn remove_collection(&self, collection: &str) -> Result<usize> {
+        let keys: Vec<CacheKey> = self
+            .index
+            .iter()
+            .filter(|entry| entry.key().collection == collection)
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        let mut journal = self.journal.lock().await;
+        let mut removed = 0;
+        for key in keys {
+            if self.remove_locked(&mut journal, &key).await? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Drop every expired entry, returning how many were removed.
+    pub async fn remove_expired(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let expired: Vec<CacheKey> = self
+            .index
+            .iter()
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| entry.key().clone())
+            .collect();
+        if expired.is_empty() {
+            return Ok(0);
+        }
+
+        let mut journal = self.journal.lock().await;
+        let mut removed = 0;
+        for key in expired {
+            // Re-check under the journal lock so an entry rewritten since the scan survives
+            if self.index.get(&key).is_some_and(|entry| entry.is_expired(now))
+                && self.remove_locked(&mut journal, &key).await?
+            {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Change the byte budget, returning how many entries were evicted to
+    /// fit a smaller one.
+    pub async fn set_capacity(&self, capacity: u64) -> Result<usize> {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        if self.used_bytes() <= capacity {
+            return Ok(0);
+        }
+        let mut journal = self.journal.lock().await;
+        self.evict_to(&mut journal, capacity * EVICTION_TARGET_PERCENT / 100).await
+    }
+
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Uncompressed size of the stored entries.
+    pub fn logical_bytes(&self) -> u64 {
+        self.logical_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Compress entries written from now on; existing ones are left as they are.
+    pub fn set_compression(&self, enabled: bool) {
+        self.compress.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    async fn remove_locked(&self, journal: &mut Journal, key: &CacheKey) -> Result<bool> {
+        if !self.index.contains_key(key) {
+            return Ok(false);
+        }
+
+        append(journal, &JournalRecord::Remove { key: key.clone() }).await?;
+        match fs::remove_file(self.entry_path(key)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if let Some((_, entry)) = self.index.remove(key) {
+            self.used_bytes.fetch_sub(entry.size, Ordering::Relaxed);
+            self.logical_bytes.fetch_sub(entry.logical_size(), Ordering::Relaxed);
+        }
+        Ok(true)
+    }
+
+    /// Evict least-recently-used entries until usage is at or below `target`.
+    async fn evict_to(&self, journal: &mut Journal, target: u64) -> Result<usize> {
+        let mut candidates: Vec<(u64, CacheKey)> = self
+            .index
+            .iter()
+            .map(|entry| (entry.last_access.load(Ordering::Relaxed), entry.key().clone()))
+            .collect();
+        candidates.sort_unstable_by_key(|(last_access, _)| *last_access);
+
+        let mut evicted = 0;
+        for (_, key) in candidates {
+            if self.used_bytes() <= target {
+                break;
+            }
+            if self.remove_locked(journal, &key).await? {
+                evicted += 1;
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Rebuild the index from the journal and the entry files on disk.
+    async fn recover(&self) -> Result<()> {
+        let journal_path = self.dir.join(JOURNAL_FILE);
+        let contents = match fs::read(&journal_path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Later records supersede earlier ones; replay order doubles as recency
+        let mut live: HashMap<CacheKey, Replayed> = HashMap::new();
+        for (position, line) in contents.split(|byte| *byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_slice::<JournalRecord>(line) {
+                Ok(JournalRecord::Put { key, checksum, size, expires_at_ms, logical_size }) => {
+                    live.insert(key, Replayed { position: position as u64, checksum, size, expires_at_ms, logical_size });
+                }
+                Ok(JournalRecord::Remove { key }) => {
+                    live.remove(&key);
+                }
+                Err(e) => {
+                    warn!("NVMe cache journal ends with an unreadable record at line {}: {}", position + 1, e);
+                    break;
+                }
+            }
+        }
+
+        let now = SystemTime::now();
+        let mut dropped = 0;
+        for (key, Replayed { position, checksum, size, expires_at_ms, logical_size }) in live {
+            let expires_at = expires_at_ms.map(from_unix_millis);
+            let on_disk = fs::metadata(self.entry_path(&key)).await.map(|metadata| metadata.len()).ok();
+            if on_disk != Some(size) || expires_at.is_some_and(|expires_at| expires_at <= now) {
+                debug!("Dropping NVMe cache entry {:?} during recovery", key);
+                dropped += 1;
+                continue;
+            }
+
+            self.index.insert(key, NvmeEntry {
+                checksum,
+                size,
+                logical_size,
+                expires_at,
+                last_access: AtomicU64::new(position),
+            });
+            self.used_bytes.fetch_add(size, Ordering::Relaxed);
+            self.logical_bytes.fetch_add(logical_size.unwrap_or(size), Ordering::Relaxed);
+            self.clock.fetch_max(position + 1, Ordering::Relaxed);
+        }
+
+        self.remove_orphaned_files().await?;
+
+        let mut journal = self.journal.lock().await;
+        self.compact(&mut journal).await?;
+        let capacity = self.capacity();
+        if self.used_bytes() > capacity {
+            self.evict_to(&mut journal, capacity * EVICTION_TARGET_PERCENT / 100).await?;
+        }
+
+        info!(
+            "Recovered {} NVMe cache entries ({} bytes) from {}, dropped {}",
+            self.len(),
+            self.used_bytes(),
+            self.dir.display(),
+            dropped
+        );
+        Ok(())
+    }
+
+    /// Delete entry files the index does not reference, including temporary
+    /// files left behind by interrupted writes.
+    async fn remove_orphaned_files(&self) -> Result<()> {
+        let expected: HashSet<PathBuf> = self.index.iter().map(|entry| self.entry_path(entry.key())).collect();
+
+        let mut files = fs::read_dir(self.dir.join(ENTRIES_DIR)).await?;
+        while let Some(file) = files.next_entry().await? {
+            let path = file.path();
+            if !expected.contains(&path) {
+                debug!("Removing orphaned NVMe cache file {}", path.display());
+                fs::remove_file(&path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the journal with one `put` record per live entry.
+    async fn compact(&self, journal: &mut Journal) -> Result<()> {
+        let mut snapshot = Vec::new();
+        for entry in self.index.iter() {
+            let record = JournalRecord::Put {
+                key: entry.key().clone(),
+                checksum: entry.checksum.clone(),
+                size: entry.size,
+                expires_at_ms: entry.expires_at.map(to_unix_millis),
+                logical_size: entry.logical_size,
+            };
+            serde_json::to_writer(&mut snapshot, &record)?;
+            snapshot.push(b'\n');
+        }
+
+        let journal_path = self.dir.join(JOURNAL_FILE);
+        write_atomically(&journal_path, &snapshot).await?;
+        journal.file = open_journal(&self.dir).await?;
+        journal.records = self.index.len() as u64;
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(key.collection.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(key.document_id.as_bytes());
+        self.dir
+            .join(ENTRIES_DIR)
+            .join(format!("{}.{}", hasher.finalize().to_hex(), ENTRY_EXTENSION))
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+async fn open_journal(dir: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(dir.join(JOURNAL_FILE)).await?)
+}
+
+/// Append a record and sync it before the change it describes is made.
+async fn append(journal: &mut Journal, record: &JournalRecord) -> Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    journal.file.write_all(&line).await?;
+    journal.file.sync_data().await?;
+    journal.records += 1;
+    Ok(())
+}
+
+/// Write a file through a synced temporary file so it is replaced atomically.
+async fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    let mut file = File::create(&temporary).await?;
+    file.write_all(bytes).await?;
+    file.sync_data().await?;
+    fs::rename(&temporary, path).await?;
+    Ok(())
+}
+
+// Memory entries expire on the monotonic clock; persisted entries need wall-clock time
+
+fn to_system_time(instant: Instant) -> SystemTime {
+    let now = Instant::now();
+    match instant.checked_duration_since(now) {
+        Some(remaining) => SystemTime::now() + remaining,
+        None => SystemTime::now() - now.duration_since(instant),
+    }
+}
+
+fn to_instant(time: SystemTime) -> Instant {
+    let now = SystemTime::now();
+    match time.duration_since(now) {
+        Ok(remaining) => Instant::now() + remaining,
+        Err(_) => Instant::now(),
+    }
+}
+
+fn to_unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fresh_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aerolith-nvme-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entry(collection: &str, id: &str, value: serde_json::Value) -> CachedEntry {
+        let size = serde_json::to_vec(&value).unwrap().len() as u64;
+        CachedEntry { key: CacheKey::new(collection, id), value, size, expires_at: None }
+    }
+
+    #[tokio::test]
+    async fn test_entries_survive_reopen() {
+        let dir = fresh_dir("reopen");
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        layer.insert(&entry("users", "1", json!({"name": "ada"}))).await.unwrap();
+        layer.insert(&entry("users", "2", json!({"name": "bob"}))).await.unwrap();
+        layer.insert(&entry("users", "1", json!({"name": "ada", "v": 2}))).await.unwrap();
+        assert!(layer.remove(&CacheKey::new("users", "2")).await.unwrap());
+        drop(layer);
+
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        assert_eq!(layer.len(), 1);
+        let found = layer.lookup(&CacheKey::new("users", "1")).await.unwrap().hit().unwrap();
+        assert_eq!(found.value, json!({"name": "ada", "v": 2}));
+        assert!(layer.lookup(&CacheKey::new("users", "2")).await.unwrap().hit().is_none());
+
+        // Recovery rewrites the journal as one record per live entry
+        let journal = std::fs::read_to_string(dir.join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_entry_is_dropped() {
+        let dir = fresh_dir("corrupt");
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        let key = CacheKey::new("orders", "9");
+        layer.insert(&entry("orders", "9", json!({"total": 10}))).await.unwrap();
+
+        // Same length, different bytes, so only the checksum can catch it
+        std::fs::write(layer.entry_path(&key), br#"{"total":99}"#).unwrap();
+        assert!(layer.lookup(&key).await.unwrap().hit().is_none());
+        assert!(!layer.contains(&key));
+        assert_eq!(layer.used_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_tolerates_torn_journal_and_orphans() {
+        let dir = fresh_dir("torn");
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        layer.insert(&entry("c", "kept", json!("warm"))).await.unwrap();
+        drop(layer);
+
+        let mut journal = std::fs::OpenOptions::new().append(true).open(dir.join(JOURNAL_FILE)).unwrap();
+        std::io::Write::write_all(&mut journal, br#"{"op":"put","key":{"collection":"c","#).unwrap();
+        let orphan = dir.join(ENTRIES_DIR).join("leftover.tmp");
+        std::fs::write(&orphan, b"partial").unwrap();
+
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        assert_eq!(layer.lookup(&CacheKey::new("c", "kept")).await.unwrap().hit().unwrap().value, json!("warm"));
+        assert!(!orphan.exists());
+    }
+
+    #[tokio::test]
+    async fn test_compressed_entries_round_trip() {
+        let dir = fresh_dir("compressed");
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        layer.set_compression(true);
+        let document = json!({"payload": "abc".repeat(200)});
+        layer.insert(&entry("c", "big", document.clone())).await.unwrap();
+        // Too small to shrink, so stored as plain JSON
+        layer.insert(&entry("c", "small", json!(1))).await.unwrap();
+        assert!(layer.used_bytes() < layer.logical_bytes());
+        drop(layer);
+
+        let layer = NvmeLayer::open(&dir, 1024 * 1024).await.unwrap();
+        let found = layer.lookup(&CacheKey::new("c", "big")).await.unwrap().hit().unwrap();
+        assert_eq!((found.value, found.size), (document.clone(), serde_json::to_vec(&document).unwrap().len() as u64));
+        assert_eq!(layer.lookup(&CacheKey::new("c", "small")).await.unwrap().hit().unwrap().value, json!(1));
+
+        assert!(layer.remove(&CacheKey::new("c", "big")).await.unwrap());
+        assert_eq!(layer.used_bytes(), layer.logical_bytes());
+    }
+}
@@ -0,0 +1,358 @@
+//! # Predictive Prefetching
+//!
+//! When `ml_prefetching` is enabled, every lookup is reported to a
+//! [`Prefetcher`], which learns from the access stream and predicts the keys
+//! likely to be read next. Predicted keys that are not cached yet are loaded
+//! in the background from a [`PrefetchSource`], normally the storage
+//! hierarchy, so the following reads hit the cache.
+//!
+//! Each prediction stays outstanding for [`PREDICTION_WINDOW`]. A prediction
+//! read within the window counts as correct and one that is not counts as
+//! missed, which gives the accuracy reported in `CacheStats`.
+//!
+//! The default [`SequencePredictor`] combines two patterns:
+//! - **Temporal**: keys that have repeatedly been read right after the
+//!   current one
+//! - **Sequential**: the next numeric document IDs when a collection is being
+//!   walked with a constant stride, e.g. pages 3, 4, 5
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::CacheKey;
+
+/// How long a prediction may take to come true before it counts as missed
+pub const PREDICTION_WINDOW: Duration = Duration::from_secs(60);
+
+/// Keys predicted per lookup
+pub(crate) const PREFETCH_DEPTH: usize = 4;
+
+/// Upper bound on predictions awaiting an access; further predictions are
+/// still prefetched but not scored
+const MAX_OUTSTANDING: usize = 10_000;
+
+/// Times a successor must have followed a key before it is predicted
+const MIN_TRANSITIONS: u32 = 2;
+
+/// Successors remembered per key; the least frequent is forgotten first
+const MAX_SUCCESSORS: usize = 8;
+
+/// Keys with successor history before rarely seen ones are forgotten
+const MAX_TRACKED_KEYS: usize = 50_000;
+
+/// Learns from cache accesses and predicts the keys read next.
+pub trait Prefetcher: Send + Sync {
+    /// Learn from a lookup of `key`, whether or not it was cached.
+    fn record_access(&self, key: &CacheKey);
+
+    /// Up to `limit` keys likely to be read after `key`, most likely first.
+    fn predict(&self, key: &CacheKey, limit: usize) -> Vec<CacheKey>;
+}
+
+pub type PrefetchFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<serde_json::Value>>> + Send + 'a>>;
+
+/// Loads documents to prefetch, returning `None` for documents that do not
+/// exist. The query engine attaches the storage hierarchy.
+pub trait PrefetchSource: Send + Sync {
+    fn fetch<'a>(&'a self, key: &'a CacheKey) -> PrefetchFuture<'a>;
+}
+
+/// Prediction and prefetch counters since startup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefetchStats {
+    /// Keys predicted to be read next
+    pub predictions: u64,
+    /// Predictions read within the prediction window
+    pub correct: u64,
+    /// Predictions not read within the prediction window
+    pub missed: u64,
+    /// Documents loaded from the prefetch source into the cache
+    pub prefetched: u64,
+    /// Prefetch loads that failed
+    pub failures: u64,
+}
+
+impl PrefetchStats {
+    /// Fraction of scored predictions that came true, 0.0 before any were scored
+    pub fn accuracy(&self) -> f64 {
+        let scored = self.correct + self.missed;
+        if scored == 0 {
+            0.0
+        } else {
+            self.correct as f64 / scored as f64
+        }
+    }
+}
+
+/// Position and stride of the last numeric ID read from a collection.
+#[derive(Debug, Clone, Copy)]
+struct Walk {
+    last_id: i64,
+    stride: i64,
+    /// Whether the last two steps had the same stride
+    confirmed: bool,
+}
+
+/// Default predictor combining temporal successors and sequential walks.
+#[derive(Debug, Default)]
+pub struct SequencePredictor {
+    /// How often each key was read right after another
+    successors: DashMap<CacheKey, HashMap<CacheKey, u32>>,
+    previous: Mutex<Option<CacheKey>>,
+    walks: DashMap<String, Walk>,
+}
+
+impl SequencePredictor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record_successor(&self, previous: CacheKey, key: &CacheKey) {
+        let mut successors = self.successors.entry(previous).or_default();
+        *successors.entry(key.clone()).or_insert(0) += 1;
+        if successors.len() > MAX_SUCCESSORS {
+            if let Some(rarest) = successors.iter().min_by_key(|(_, count)| **count).map(|(key, _)| key.clone()) {
+                successors.remove(&rarest);
+            }
+        }
+        drop(successors);
+
+        if self.successors.len() > MAX_TRACKED_KEYS {
+            // Keys whose successors were each seen once carry no prediction yet
+            self.successors.retain(|_, successors| successors.values().any(|count| *count >= MIN_TRANSITIONS));
+        }
+    }
+
+    fn record_walk(&self, key: &CacheKey) {
+        let Ok(id) = key.document_id.parse::<i64>() else { return };
+        self.walks
+            .entry(key.collection.clone())
+            .and_modify(|walk| {
+                let stride = id.saturating_sub(walk.last_id);
+                walk.confirmed = stride != 0 && stride == walk.stride;
+                walk.stride = stride;
+                walk.last_id = id;
+            })
+            .or_insert(Walk { last_id: id, stride: 0, confirmed: false });
+    }
+}
+
+impl Prefetcher for SequencePredictor {
+    fn record_access(&self, key: &CacheKey) {
+        let previous = {
+            let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+            previous.replace(key.clone())
+        };
+        if let Some(previous) = previous.filter(|previous| previous != key) {
+            self.record_successor(previous, key);
+        }
+        self.record_walk(key);
+    }
+
+    fn predict(&self, key: &CacheKey, limit: usize) -> Vec<CacheKey> {
+        let mut predicted: Vec<CacheKey> = Vec::new();
+
+        if let Some(successors) = self.successors.get(key) {
+            let mut likely: Vec<(&CacheKey, &u32)> =
+                successors.iter().filter(|(_, count)| **count >= MIN_TRANSITIONS).collect();
+            likely.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.document_id.cmp(&b.0.document_id)));
+            predicted.extend(likely.into_iter().map(|(key, _)| key.clone()));
+        }
+
+        let walk = self.walks.get(&key.collection).map(|walk| *walk);
+        if let Some(walk) = walk.filter(|walk| walk.confirmed && walk.last_id.to_string() == key.document_id) {
+            let mut id = walk.last_id;
+            for _ in 0..limit {
+                let Some(next) = id.checked_add(walk.stride) else { break };
+                id = next;
+                predicted.push(CacheKey::new(key.collection.clone(), id.to_string()));
+            }
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        predicted.retain(|predicted| predicted != key && seen.insert(predicted.clone()));
+        predicted.truncate(limit);
+        predicted
+    }
+}
+
+/// Prefetch state of a cache system: the predictor and source in use, the
+/// predictions awaiting an access, and the counters derived from them.
+pub(crate) struct Prefetching {
+    prefetcher: RwLock<Arc<dyn Prefetcher>>,
+    source: RwLock<Option<Arc<dyn PrefetchSource>>>,
+    /// Predicted keys and when they were predicted
+    outstanding: DashMap<CacheKey, Instant>,
+    /// Bumped by every invalidation, so a load that raced one is discarded
+    invalidations: AtomicU64,
+    predictions: AtomicU64,
+    correct: AtomicU64,
+    missed: AtomicU64,
+    prefetched: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Prefetching {
+    pub fn new() -> Self {
+        Self {
+            prefetcher: RwLock::new(Arc::new(SequencePredictor::new())),
+            source: RwLock::new(None),
+            outstanding: DashMap::new(),
+            invalidations: AtomicU64::new(0),
+            predictions: AtomicU64::new(0),
+            correct: AtomicU64::new(0),
+            missed: AtomicU64::new(0),
+            prefetched: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_prefetcher(&self, prefetcher: Arc<dyn Prefetcher>) {
+        *self.prefetcher.write().unwrap_or_else(|e| e.into_inner()) = prefetcher;
+    }
+
+    pub fn set_source(&self, source: Arc<dyn PrefetchSource>) {
+        *self.source.write().unwrap_or_else(|e| e.into_inner()) = Some(source);
+    }
+
+    pub fn source(&self) -> Option<Arc<dyn PrefetchSource>> {
+        self.source.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Score any outstanding prediction of `key`, learn from the access, and
+    /// return the keys predicted to follow it.
+    pub fn access(&self, key: &CacheKey) -> Vec<CacheKey> {
+        if self.outstanding.remove(key).is_some() {
+            self.correct.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let prefetcher = Arc::clone(&self.prefetcher.read().unwrap_or_else(|e| e.into_inner()));
+        prefetcher.record_access(key);
+        let predicted = prefetcher.predict(key, PREFETCH_DEPTH);
+
+        let now = Instant::now();
+        for predicted in &predicted {
+            if self.outstanding.len() < MAX_OUTSTANDING && !self.outstanding.contains_key(predicted) {
+                self.outstanding.insert(predicted.clone(), now);
+                self.predictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        predicted
+    }
+
+    /// Count predictions older than the window as missed, returning how many.
+    pub fn expire_predictions(&self) -> usize {
+        let before = self.outstanding.len();
+        self.outstanding.retain(|_, predicted_at| predicted_at.elapsed() < PREDICTION_WINDOW);
+        let expired = before.saturating_sub(self.outstanding.len());
+        self.missed.fetch_add(expired as u64, Ordering::Relaxed);
+        expired
+    }
+
+    pub fn record_invalidation(&self) {
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations.load(Ordering::Relaxed)
+    }
+
+    pub fn record_prefetched(&self) {
+        self.prefetched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> PrefetchStats {
+        PrefetchStats {
+            predictions: self.predictions.load(Ordering::Relaxed),
+            correct: self.correct.load(Ordering::Relaxed),
+            missed: self.missed.load(Ordering::Relaxed),
+            prefetched: self.prefetched.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl fmt::Debug for Prefetching {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prefetching")
+            .field("has_source", &self.source().is_some())
+            .field("outstanding", &self.outstanding.len())
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> CacheKey {
+        CacheKey::new("c", id)
+    }
+
+    #[test]
+    fn test_sequential_walk_predicts_next_ids() {
+        let predictor = SequencePredictor::new();
+        predictor.record_access(&key("10"));
+        predictor.record_access(&key("12"));
+        // One step is not a pattern yet
+        assert!(predictor.predict(&key("12"), 3).is_empty());
+
+        predictor.record_access(&key("14"));
+        assert_eq!(predictor.predict(&key("14"), 3), vec![key("16"), key("18"), key("20")]);
+
+        // Breaking the stride stops sequential predictions
+        predictor.record_access(&key("3"));
+        assert!(predictor.predict(&key("3"), 3).is_empty());
+    }
+
+    #[test]
+    fn test_temporal_successors_need_repetition() {
+        let predictor = SequencePredictor::new();
+        let (profile, settings, avatar) = (key("profile"), key("settings"), key("avatar"));
+        predictor.record_access(&profile);
+        predictor.record_access(&settings);
+        assert!(predictor.predict(&profile, 2).is_empty());
+
+        for _ in 0..2 {
+            predictor.record_access(&profile);
+            predictor.record_access(&avatar);
+            predictor.record_access(&profile);
+            predictor.record_access(&settings);
+        }
+        // settings followed profile three times, avatar twice
+        assert_eq!(predictor.predict(&profile, 2), vec![settings, avatar]);
+    }
+
+    #[test]
+    fn test_prediction_scoring() {
+        let prefetching = Prefetching::new();
+        for id in ["1", "2", "3"] {
+            prefetching.access(&key(id));
+        }
+        assert_eq!(prefetching.stats().predictions, PREFETCH_DEPTH as u64);
+
+        prefetching.access(&key("4"));
+        let stats = prefetching.stats();
+        assert_eq!(stats.correct, 1);
+        // Predictions 5..7 were already outstanding; only 8 is new
+        assert_eq!(stats.predictions, PREFETCH_DEPTH as u64 + 1);
+
+        prefetching.outstanding.alter_all(|_, _| Instant::now() - PREDICTION_WINDOW);
+        assert_eq!(prefetching.expire_predictions(), PREFETCH_DEPTH);
+        assert_eq!(prefetching.stats().accuracy(), 1.0 / (1.0 + PREFETCH_DEPTH as f64));
+    }
+}
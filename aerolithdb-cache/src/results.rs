@@ -0,0 +1,415 @@
+//! # Query Result Cache
+//!
+//! Caches the results of whole queries, in a namespace separate from the
+//! document layers so result sets never compete with documents for memory.
+//!
+//! - **Keys**: a BLAKE3 hash of the collection and the canonical form of the
+//!   query, in which object keys are sorted, so queries that differ only in
+//!   field order share an entry
+//! - **Invalidation**: a [`DocumentEvent`] for a collection drops every
+//!   result cached for it. Each collection also has a generation that
+//!   invalidation advances, and a result computed under an older generation
+//!   is not stored, so a query racing a write cannot cache what it read
+//!   before the write
+//! - **Bounds**: results with more documents than
+//!   [`QueryResultConfig::max_result_documents`] are not cached, and the
+//!   least recently used entry makes room once
+//!   [`QueryResultConfig::max_entries`] is reached
+//! - **Lifetime**: entries expire after [`QueryResultConfig::ttl`], which
+//!   bounds staleness from writes that reach storage without an event, such
+//!   as replication from other nodes
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Limits of the query result cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryResultConfig {
+    /// Whether query results are cached at all
+    pub enabled: bool,
+    /// Most result sets held at once
+    pub max_entries: usize,
+    /// Largest result set cached, in documents; larger ones are recomputed
+    /// every time
+    pub max_result_documents: usize,
+    /// How long a result stays valid without an invalidating event
+    pub ttl: Duration,
+}
+
+impl Default for QueryResultConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1024,
+            max_result_documents: 1000,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A document change that invalidates the cached results of its collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DocumentEvent {
+    DocumentCreated { collection: String, document_id: String },
+    DocumentUpdated { collection: String, document_id: String },
+    DocumentDeleted { collection: String, document_id: String },
+}
+
+impl DocumentEvent {
+    /// Collection the changed document belongs to
+    pub fn collection(&self) -> &str {
+        match self {
+            Self::DocumentCreated { collection, .. }
+            | Self::DocumentUpdated { collection, .. }
+            | Self::DocumentDeleted { collection, .. } => collection,
+        }
+    }
+}
+
+/// Identifies a query by its collection and a hash of its canonical form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryFingerprint {
+    pub collection: String,
+    pub hash: [u8; 32],
+}
+
+impl QueryFingerprint {
+    /// Fingerprint of `query` against `collection`. Object keys are sorted
+    /// at every level before hashing.
+    pub fn new(collection: &str, query: &Value) -> Self {
+        let mut canonical = String::new();
+        write_canonical(&mut canonical, query);
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(collection.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(canonical.as_bytes());
+        Self {
+            collection: collection.to_string(),
+            hash: *hasher.finalize().as_bytes(),
+        }
+    }
+}
+
+/// A cached query result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedQueryResult {
+    /// The page of documents the query returned
+    pub documents: Vec<Value>,
+    /// IDs of the documents, in the same order
+    #[serde(default)]
+    pub ids: Vec<String>,
+    /// Matches before limit and offset were applied
+    pub total: usize,
+    /// Cursor of the page after this one, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Counters of the query result cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryResultStats {
+    /// Result sets currently cached
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Result sets dropped because their collection changed
+    pub invalidations: u64,
+    /// Results too large to cache
+    pub oversized: u64,
+    /// Entries removed to stay within `max_entries`
+    pub evictions: u64,
+}
+
+impl QueryResultStats {
+    /// Fraction of lookups served from the cache, 0.0 when nothing was looked up
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ResultEntry {
+    result: Arc<CachedQueryResult>,
+    expires_at: Instant,
+    /// Value of the access clock when the entry was last read or written
+    last_used: AtomicU64,
+}
+
+/// Cache of whole query results, invalidated per collection.
+#[derive(Debug, Default)]
+pub struct QueryResultCache {
+    config: RwLock<QueryResultConfig>,
+    entries: DashMap<QueryFingerprint, ResultEntry>,
+    /// Invalidation count per collection, compared on insert
+    generations: DashMap<String, u64>,
+    /// Monotonic counter ordering accesses for LRU eviction
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+    oversized: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl QueryResultCache {
+    pub fn new(config: QueryResultConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            ..Default::default()
+        }
+    }
+
+    fn config(&self) -> QueryResultConfig {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Apply new limits, dropping entries beyond a smaller `max_entries` or
+    /// everything if caching was disabled.
+    pub(crate) fn set_config(&self, config: QueryResultConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+        if !config.enabled {
+            self.entries.clear();
+        }
+        while self.entries.len() > config.max_entries {
+            self.evict_least_recent();
+        }
+    }
+
+    /// Cached result of a query, if one is live.
+    pub fn get(&self, key: &QueryFingerprint) -> Option<CachedQueryResult> {
+        if !self.config().enabled {
+            return None;
+        }
+        let now = Instant::now();
+        let found = self.entries.get(key).and_then(|entry| {
+            (entry.expires_at > now).then(|| {
+                entry.last_used.store(self.tick(), Ordering::Relaxed);
+                entry.result.as_ref().clone()
+            })
+        });
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.entries.remove_if(key, |_, entry| entry.expires_at <= now);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Current generation of a collection; pass it to [`put`](Self::put)
+    /// with a result computed after reading it.
+    pub fn generation(&self, collection: &str) -> u64 {
+        self.generations.get(collection).map_or(0, |generation| *generation)
+    }
+
+    /// Cache a query result computed at `generation` of its collection.
+    ///
+    /// Returns whether it was stored: results are skipped while caching is
+    /// disabled, when they exceed `max_result_documents`, or when the
+    /// collection changed after `generation` was read.
+    pub fn put(&self, key: QueryFingerprint, result: CachedQueryResult, generation: u64) -> bool {
+        let config = self.config();
+        if !config.enabled || config.max_entries == 0 {
+            return false;
+        }
+        if result.documents.len() > config.max_result_documents {
+            self.oversized.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        // Hold the generation entry so an invalidation cannot slip in between
+        // the check and the insert
+        let current = self.generations.entry(key.collection.clone()).or_insert(0);
+        if *current != generation {
+            return false;
+        }
+        if !self.entries.contains_key(&key) {
+            while self.entries.len() >= config.max_entries {
+                self.evict_least_recent();
+            }
+        }
+        self.entries.insert(key, ResultEntry {
+            result: Arc::new(result),
+            expires_at: Instant::now() + config.ttl,
+            last_used: AtomicU64::new(self.tick()),
+        });
+        drop(current);
+        true
+    }
+
+    /// Drop the results a document change may have affected, returning how
+    /// many were removed.
+    pub fn handle_event(&self, event: &DocumentEvent) -> usize {
+        self.invalidate_collection(event.collection())
+    }
+
+    /// Drop every result cached for a collection, returning how many were
+    /// removed.
+    pub fn invalidate_collection(&self, collection: &str) -> usize {
+        let mut generation = self.generations.entry(collection.to_string()).or_insert(0);
+        *generation += 1;
+        let before = self.entries.len();
+        self.entries.retain(|key, _| key.collection != collection);
+        drop(generation);
+
+        let removed = before.saturating_sub(self.entries.len());
+        self.invalidations.fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    /// Drop every cached result.
+    pub fn clear(&self) {
+        for mut generation in self.generations.iter_mut() {
+            *generation += 1;
+        }
+        self.entries.clear();
+    }
+
+    pub fn stats(&self) -> QueryResultStats {
+        QueryResultStats {
+            entries: self.entries.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            oversized: self.oversized.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Result sets are few and large, so a scan for the oldest entry is
+    /// cheap next to the queries they save
+    fn evict_least_recent(&self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|entry| entry.last_used.load(Ordering::Relaxed))
+            .map(|entry| entry.key().clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Write `value` as JSON with object keys in sorted order.
+fn write_canonical(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            out.push('{');
+            for (i, name) in names.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{}:", Value::String(name.clone()));
+                write_canonical(out, &fields[name]);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item);
+            }
+            out.push(']');
+        }
+        scalar => {
+            let _ = write!(out, "{}", scalar);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(documents: usize) -> CachedQueryResult {
+        CachedQueryResult {
+            documents: (0..documents).map(|i| json!({"i": i})).collect(),
+            ids: (0..documents).map(|i| i.to_string()).collect(),
+            total: documents,
+            next_cursor: None,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_key_order() {
+        let a = QueryFingerprint::new("users", &json!({"filter": {"age": {"$gte": 18}, "name": "ada"}, "limit": 10}));
+        let b = QueryFingerprint::new("users", &json!({"limit": 10, "filter": {"name": "ada", "age": {"$gte": 18}}}));
+        assert_eq!(a, b);
+        assert_ne!(a, QueryFingerprint::new("admins", &json!({"filter": {"name": "ada", "age": {"$gte": 18}}, "limit": 10})));
+        assert_ne!(a, QueryFingerprint::new("users", &json!({"filter": {"name": "ada", "age": {"$gte": 18}}, "limit": 11})));
+    }
+
+    #[test]
+    fn test_events_invalidate_collection() {
+        let cache = QueryResultCache::new(QueryResultConfig::default());
+        let users = QueryFingerprint::new("users", &json!({}));
+        let orders = QueryFingerprint::new("orders", &json!({}));
+
+        let generation = cache.generation("users");
+        assert!(cache.put(users.clone(), result(2), generation));
+        assert!(cache.put(orders.clone(), result(1), cache.generation("orders")));
+        assert_eq!(cache.get(&users), Some(result(2)));
+
+        let event = DocumentEvent::DocumentUpdated {
+            collection: "users".to_string(),
+            document_id: "ada".to_string(),
+        };
+        assert_eq!(cache.handle_event(&event), 1);
+        assert_eq!(cache.get(&users), None);
+        assert!(cache.get(&orders).is_some());
+
+        // A result computed before the write is not cached after it
+        assert!(!cache.put(users.clone(), result(2), generation));
+        assert!(cache.put(users, result(3), cache.generation("users")));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.invalidations, stats.entries), (2, 1, 1, 2));
+    }
+
+    #[test]
+    fn test_size_limits_and_eviction() {
+        let cache = QueryResultCache::new(QueryResultConfig {
+            max_entries: 2,
+            max_result_documents: 3,
+            ..Default::default()
+        });
+        let key = |limit: u64| QueryFingerprint::new("users", &json!({"limit": limit}));
+
+        assert!(!cache.put(key(0), result(4), 0));
+        assert_eq!(cache.stats().oversized, 1);
+
+        assert!(cache.put(key(1), result(1), 0));
+        assert!(cache.put(key(2), result(1), 0));
+        assert!(cache.get(&key(1)).is_some());
+        assert!(cache.put(key(3), result(1), 0));
+        assert!(cache.get(&key(2)).is_none(), "least recently used entry is evicted");
+        assert!(cache.get(&key(1)).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+
+        cache.set_config(QueryResultConfig { enabled: false, ..Default::default() });
+        assert_eq!(cache.stats().entries, 0);
+        assert!(!cache.put(key(1), result(1), 0));
+    }
+}
@@ -0,0 +1,271 @@
+//! # TTL Management
+//!
+//! Computes entry lifetimes under the configured [`TTLStrategy`] and keeps the
+//! per-strategy hit, stale, and expiry counters reported in `CacheStats`.
+//!
+//! The adaptive strategy learns how volatile each document is from cache
+//! invalidations, which callers issue when a document is modified. Every key
+//! tracks an exponentially weighted mean of the interval between updates:
+//! - Frequently updated documents get a TTL of half their update interval,
+//!   down to [`MIN_ADAPTIVE_TTL`], so stale reads stay rare
+//! - Documents that have not changed for a long time get a TTL that grows
+//!   with the time since their last update, up to [`MAX_ADAPTIVE_TTL`]
+//! - Documents without history start at [`BASE_ADAPTIVE_TTL`]
+//!
+//! The strategy can be switched while the cache is live. Entries already
+//! cached keep the lifetime they were given; only later inserts follow the
+//! new strategy.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheKey, TTLStrategy};
+
+/// Shortest lifetime the adaptive strategy assigns
+pub const MIN_ADAPTIVE_TTL: Duration = Duration::from_secs(10);
+
+/// Longest lifetime the adaptive strategy assigns
+pub const MAX_ADAPTIVE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Lifetime of documents the adaptive strategy has no history for
+pub const BASE_ADAPTIVE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Weight of the newest interval in the update interval average
+const INTERVAL_SMOOTHING: f64 = 0.3;
+
+/// Volatility history is forgotten after this long without an update, which
+/// bounds the tracker to documents that are still being cached or modified
+const HISTORY_RETENTION: Duration = Duration::from_secs(2 * 24 * 60 * 60);
+
+/// Counters for lookups made while one TTL strategy was in effect.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TtlStrategyStats {
+    /// Lookups served from a live entry
+    pub hits: u64,
+    /// Lookups that only found expired entries
+    pub stale: u64,
+    /// Expired entries removed by the background sweeper
+    pub expired: u64,
+}
+
+#[derive(Debug, Default)]
+struct StrategyCounters {
+    hits: AtomicU64,
+    stale: AtomicU64,
+    expired: AtomicU64,
+}
+
+/// Update history of one document.
+#[derive(Debug, Clone, Copy)]
+struct Volatility {
+    last_update: Instant,
+    /// Smoothed interval between updates; unknown until a second update
+    mean_interval: Option<Duration>,
+}
+
+/// Per-entry lifetime calculation and TTL metrics.
+#[derive(Debug)]
+pub(crate) struct TtlManager {
+    strategy: RwLock<TTLStrategy>,
+    volatility: DashMap<CacheKey, Volatility>,
+    /// Indexed by `strategy_index`
+    counters: [StrategyCounters; 3],
+}
+
+impl TtlManager {
+    pub fn new(strategy: TTLStrategy) -> Self {
+        Self {
+            strategy: RwLock::new(strategy),
+            volatility: DashMap::new(),
+            counters: Default::default(),
+        }
+    }
+
+    /// Lifetime of an entry cached for `key` now, or `None` if entries under
+    /// the current strategy only leave through eviction.
+    pub fn ttl_for(&self, key: &CacheKey) -> Option<Duration> {
+        match self.strategy() {
+            TTLStrategy::Fixed(ttl) => Some(ttl),
+            TTLStrategy::LRU => None,
+            TTLStrategy::Adaptive => Some(self.adaptive_ttl(key)),
+        }
+    }
+
+    /// Note that `key` is being cached, starting its history if it has none.
+    /// Caching a document is not an update, so existing history is kept.
+    pub fn observe(&self, key: &CacheKey) {
+        if self.is_adaptive() && !self.volatility.contains_key(key) {
+            self.volatility.insert(key.clone(), Volatility { last_update: Instant::now(), mean_interval: None });
+        }
+    }
+
+    /// Record that the document behind `key` was modified.
+    pub fn record_update(&self, key: &CacheKey) {
+        if !self.is_adaptive() {
+            return;
+        }
+
+        let now = Instant::now();
+        self.volatility
+            .entry(key.clone())
+            .and_modify(|history| {
+                let interval = now.duration_since(history.last_update);
+                history.mean_interval = Some(match history.mean_interval {
+                    Some(mean) => mean.mul_f64(1.0 - INTERVAL_SMOOTHING) + interval.mul_f64(INTERVAL_SMOOTHING),
+                    None => interval,
+                });
+                history.last_update = now;
+            })
+            .or_insert(Volatility { last_update: now, mean_interval: None });
+    }
+
+    /// Strategy currently assigning lifetimes.
+    pub fn strategy(&self) -> TTLStrategy {
+        self.strategy.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Switch strategies. Update history is only kept while the adaptive
+    /// strategy is in effect, so leaving it discards what was learned.
+    pub fn set_strategy(&self, strategy: TTLStrategy) {
+        let adaptive = matches!(strategy, TTLStrategy::Adaptive);
+        *self.strategy.write().unwrap_or_else(|e| e.into_inner()) = strategy;
+        if !adaptive {
+            self.volatility.clear();
+        }
+    }
+
+    pub fn record_hit(&self) {
+        self.counters().hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_stale(&self) {
+        self.counters().stale.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_expired(&self, count: usize) {
+        if count > 0 {
+            self.counters().expired.fetch_add(count as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Drop update history that has not been refreshed within the retention
+    /// window, returning how many keys were forgotten.
+    pub fn prune_history(&self) -> usize {
+        let before = self.volatility.len();
+        self.volatility.retain(|_, history| history.last_update.elapsed() < HISTORY_RETENTION);
+        before - self.volatility.len()
+    }
+
+    /// Counters of every strategy that has served lookups, keyed by name.
+    pub fn stats(&self) -> BTreeMap<String, TtlStrategyStats> {
+        ["adaptive", "fixed", "lru"]
+            .iter()
+            .zip(&self.counters)
+            .map(|(name, counters)| {
+                let stats = TtlStrategyStats {
+                    hits: counters.hits.load(Ordering::Relaxed),
+                    stale: counters.stale.load(Ordering::Relaxed),
+                    expired: counters.expired.load(Ordering::Relaxed),
+                };
+                (name.to_string(), stats)
+            })
+            .filter(|(_, stats)| *stats != TtlStrategyStats::default())
+            .collect()
+    }
+
+    fn adaptive_ttl(&self, key: &CacheKey) -> Duration {
+        let Some(history) = self.volatility.get(key).map(|history| *history) else {
+            return BASE_ADAPTIVE_TTL;
+        };
+
+        let since_update = history.last_update.elapsed();
+        let ttl = match history.mean_interval {
+            // A document that has outlived its usual interval is treated as
+            // stabilising, so its TTL grows with the time it has gone unchanged
+            Some(mean) => mean.max(since_update) / 2,
+            None => BASE_ADAPTIVE_TTL.max(since_update / 2),
+        };
+        ttl.clamp(MIN_ADAPTIVE_TTL, MAX_ADAPTIVE_TTL)
+    }
+
+    fn is_adaptive(&self) -> bool {
+        matches!(*self.strategy.read().unwrap_or_else(|e| e.into_inner()), TTLStrategy::Adaptive)
+    }
+
+    fn counters(&self) -> &StrategyCounters {
+        let index = match self.strategy() {
+            TTLStrategy::Adaptive => 0,
+            TTLStrategy::Fixed(_) => 1,
+            TTLStrategy::LRU => 2,
+        };
+        &self.counters[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_ttl_follows_update_frequency() {
+        let ttl = TtlManager::new(TTLStrategy::Adaptive);
+        let key = CacheKey::new("prices", "btc");
+        assert_eq!(ttl.ttl_for(&key), Some(BASE_ADAPTIVE_TTL));
+
+        // A document that changes constantly is held for the minimum
+        for _ in 0..5 {
+            ttl.record_update(&key);
+        }
+        assert_eq!(ttl.ttl_for(&key), Some(MIN_ADAPTIVE_TTL));
+
+        // One that has gone unchanged for an hour is held for half of that
+        let stable = CacheKey::new("countries", "fr");
+        let hour_ago = Instant::now() - Duration::from_secs(3600);
+        ttl.volatility.insert(stable.clone(), Volatility { last_update: hour_ago, mean_interval: Some(Duration::from_secs(600)) });
+        let stable_ttl = ttl.ttl_for(&stable).unwrap();
+        assert!(stable_ttl >= Duration::from_secs(1800) && stable_ttl < Duration::from_secs(1801));
+
+        // Caching does not count as an update
+        ttl.observe(&stable);
+        assert_eq!(ttl.ttl_for(&stable).map(|d| d.as_secs()), Some(1800));
+    }
+
+    #[test]
+    fn test_fixed_and_lru_ignore_history() {
+        let key = CacheKey::new("c", "1");
+        let fixed = TtlManager::new(TTLStrategy::Fixed(Duration::from_secs(60)));
+        fixed.record_update(&key);
+        assert_eq!(fixed.ttl_for(&key), Some(Duration::from_secs(60)));
+        assert!(fixed.volatility.is_empty());
+
+        let lru = TtlManager::new(TTLStrategy::LRU);
+        lru.record_hit();
+        lru.record_stale();
+        assert_eq!(lru.ttl_for(&key), None);
+        assert_eq!(lru.stats()["lru"], TtlStrategyStats { hits: 1, stale: 1, expired: 0 });
+        assert!(!lru.stats().contains_key("fixed"));
+    }
+
+    #[test]
+    fn test_switching_strategy() {
+        let ttl = TtlManager::new(TTLStrategy::Adaptive);
+        let key = CacheKey::new("c", "1");
+        ttl.record_update(&key);
+        ttl.record_hit();
+
+        ttl.set_strategy(TTLStrategy::Fixed(Duration::from_secs(60)));
+        assert!(ttl.volatility.is_empty());
+        assert_eq!(ttl.ttl_for(&key), Some(Duration::from_secs(60)));
+        ttl.record_hit();
+
+        // Counters are kept per strategy across switches
+        let stats = ttl.stats();
+        assert_eq!(stats["adaptive"].hits, 1);
+        assert_eq!(stats["fixed"].hits, 1);
+    }
+}
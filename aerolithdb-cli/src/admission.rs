@@ -0,0 +1,177 @@
+//! Cluster admission commands for the AerolithDB CLI
+//!
+//! Manages the allowlist and denylist of node ids and public keys that
+//! decide which nodes may join the cluster, approves or rejects the join
+//! requests of nodes waiting for an operator, and shows the refused join
+//! attempts of denied nodes.
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use crate::client::{aerolithsClient, AccessRule};
+
+#[derive(Debug, Args)]
+pub struct AdmissionArgs {
+    #[command(subcommand)]
+    pub command: AdmissionCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AdmissionCommand {
+    /// Show the allowlist and denylist
+    Show,
+
+    /// List the join requests awaiting approval
+    Pending,
+
+    /// Approve a join request, allowlisting the node
+    Approve {
+        /// Request ID
+        id: String,
+    },
+
+    /// Reject a join request
+    Reject {
+        /// Request ID
+        id: String,
+    },
+
+    /// Allowlist a node id or public key
+    Allow {
+        /// Node id, or public key with --public-key
+        identity: String,
+
+        /// Treat the identity as a public key
+        #[arg(long)]
+        public_key: bool,
+
+        /// Why the identity is allowed
+        #[arg(long)]
+        note: Option<String>,
+    },
+
+    /// Denylist a node id or public key
+    Deny {
+        /// Node id, or public key with --public-key
+        identity: String,
+
+        /// Treat the identity as a public key
+        #[arg(long)]
+        public_key: bool,
+
+        /// Why the identity is denied
+        #[arg(long)]
+        note: Option<String>,
+    },
+
+    /// Remove an identity from a list
+    Remove {
+        /// List to remove the identity from
+        #[arg(value_parser = ["allow", "deny"])]
+        list: String,
+
+        /// Node id, or public key with --public-key
+        identity: String,
+
+        /// Treat the identity as a public key
+        #[arg(long)]
+        public_key: bool,
+    },
+
+    /// List the refused join attempts of denied nodes
+    Denied,
+}
+
+fn identity_kind(public_key: bool) -> &'static str {
+    if public_key { "public_key" } else { "node_id" }
+}
+
+fn print_rules(title: &str, rules: &[AccessRule]) {
+    println!("{} ({})", title, rules.len());
+    for rule in rules {
+        println!(
+            "   {:<12} {:<44} {}{}",
+            rule.kind.replace('_', "-"),
+            rule.value,
+            rule.added_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            rule.note.as_ref().map(|note| format!("  {}", note)).unwrap_or_default()
+        );
+    }
+}
+
+pub async fn handle_admission_command(client: &aerolithsClient, args: AdmissionArgs) -> Result<()> {
+    match args.command {
+        AdmissionCommand::Show => {
+            let lists = client.get_admission_lists().await?;
+            println!("🛂 Approval: {}", if lists.require_approval { "required" } else { "not required" });
+            print_rules("Allowlist", &lists.allow);
+            print_rules("Denylist", &lists.deny);
+        }
+
+        AdmissionCommand::Pending => {
+            let requests = client.list_join_requests().await?;
+            if requests.is_empty() {
+                println!("No join request awaits approval");
+                return Ok(());
+            }
+            println!("{:<38} {:<24} {:<22} {:<24} ATTEMPTS", "ID", "NODE", "ADDRESS", "REQUESTED");
+            for request in &requests {
+                println!(
+                    "{:<38} {:<24} {:<22} {:<24} {}",
+                    request.id,
+                    request.node_id,
+                    request.address.as_deref().unwrap_or("-"),
+                    request.requested_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    request.attempts
+                );
+            }
+        }
+
+        AdmissionCommand::Approve { id } => {
+            let rule = client.approve_join_request(&id).await?;
+            println!("✅ Approved join request {}, allowlisted {} {}", id, rule.kind.replace('_', " "), rule.value);
+        }
+
+        AdmissionCommand::Reject { id } => {
+            client.reject_join_request(&id).await?;
+            println!("🚫 Rejected join request {}", id);
+        }
+
+        AdmissionCommand::Allow { identity, public_key, note } => {
+            let rule = client.add_access_rule("allowlist", identity_kind(public_key), &identity, note.as_deref()).await?;
+            println!("✅ Allowlisted {} {}", rule.kind.replace('_', " "), rule.value);
+        }
+
+        AdmissionCommand::Deny { identity, public_key, note } => {
+            let rule = client.add_access_rule("denylist", identity_kind(public_key), &identity, note.as_deref()).await?;
+            println!("🚫 Denylisted {} {}", rule.kind.replace('_', " "), rule.value);
+        }
+
+        AdmissionCommand::Remove { list, identity, public_key } => {
+            let list = format!("{}list", list);
+            client.remove_access_rule(&list, identity_kind(public_key), &identity).await?;
+            println!("🗑️  Removed {} from the {}", identity, list);
+        }
+
+        AdmissionCommand::Denied => {
+            let attempts = client.list_denied_joins().await?;
+            if attempts.is_empty() {
+                println!("No denied node has tried to join");
+                return Ok(());
+            }
+            println!("{:<24} {:<22} {:<10} {:<8} {:<24} ALERTED", "NODE", "ADDRESS", "WINDOW", "TOTAL", "LAST ATTEMPT");
+            for attempt in &attempts {
+                println!(
+                    "{:<24} {:<22} {:<10} {:<8} {:<24} {}",
+                    attempt.node_id,
+                    attempt.address.as_deref().unwrap_or("-"),
+                    attempt.attempts,
+                    attempt.total_attempts,
+                    attempt.last_attempt_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    if attempt.alerted { "yes" } else { "no" }
+                );
+            }
+        }
+    }
+    Ok(())
+}
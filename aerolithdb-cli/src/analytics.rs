@@ -438,7 +438,7 @@ pub async fn execute_analytics(_client: &aerolithsClient, args: &AnalyticsArgs)
 /// # Returns
 ///
 /// * `Result<()>` - Success indication or detailed error information
-pub async fn execute_optimize(_client: &aerolithsClient, args: &OptimizeArgs) -> Result<()> {
+pub async fn execute_optimize(client: &aerolithsClient, args: &OptimizeArgs) -> Result<()> {
     if let Some(collection) = &args.collection {
         info!("Analyzing collection {} for optimization opportunities", collection);
     } else {
@@ -481,8 +481,17 @@ pub async fn execute_optimize(_client: &aerolithsClient, args: &OptimizeArgs) ->
             println!("   ✓ Storage optimization opportunities identified");
             println!();
             
+            let stats = client.get_query_stats(args.collection.as_deref()).await?;
+            let slow_queries: u64 = stats.iter().map(|stats| stats.slow_queries).sum();
             println!("📊 Key Findings:");
-            println!("   • 3 slow queries identified (>100ms avg)");
+            println!("   • {} slow queries logged since statistics were reset", slow_queries);
+            for stats in stats.iter().filter(|stats| stats.scanned_per_returned.is_some_and(|ratio| ratio >= SCAN_RATIO_WARNING)) {
+                println!(
+                    "   • {}: {:.1} documents scanned per document returned",
+                    stats.collection,
+                    stats.scanned_per_returned.unwrap_or_default()
+                );
+            }
             println!("   • 2 missing index opportunities found");
             println!("   • 1 unused index detected");
             println!("   • Storage compression could be improved by 15%");
@@ -510,18 +519,7 @@ pub async fn execute_optimize(_client: &aerolithsClient, args: &OptimizeArgs) ->
             }
         }
         "queries" => {
-            println!("⚡ Query Optimization:");
-            println!("   ✓ Analyzing slow query patterns");
-            println!("   ✓ Identifying missing indexes");
-            println!("   ✓ Evaluating query structure efficiency");
-            println!("   ✓ Checking for anti-patterns");
-            println!();
-            
-            println!("🎯 Query Optimization Opportunities:");
-            println!("   → Query #1: Add index on (created_at, status) - 78% speedup");
-            println!("   → Query #2: Use projection to reduce data transfer - 34% speedup");
-            println!("   → Query #3: Optimize regex pattern for better index usage");
-            println!("   → Query #4: Consider pagination for large result sets");
+            print_query_statistics(client, args).await?;
         }
         "storage" => {
             println!("💾 Storage Optimization:");
@@ -583,3 +581,74 @@ pub async fn execute_optimize(_client: &aerolithsClient, args: &OptimizeArgs) ->
 
     Ok(())
 }
+
+/// Documents scanned per document returned from which a collection's
+/// queries are reported as scanning too much
+const SCAN_RATIO_WARNING: f64 = 10.0;
+
+/// Prints the server's per-collection query statistics and its most recent
+/// slow queries, with their request and plan when `--detailed` is given.
+async fn print_query_statistics(client: &aerolithsClient, args: &OptimizeArgs) -> Result<()> {
+    let collection = args.collection.as_deref();
+    let stats = client.get_query_stats(collection).await?;
+    println!("⚡ Query Statistics:");
+    if stats.is_empty() {
+        println!("   No queries recorded yet");
+    } else {
+        println!(
+            "   {:<20} {:>8} {:>6} {:>9} {:>9} {:>9} {:>9} {:>10}",
+            "COLLECTION", "QUERIES", "SLOW", "P50 MS", "P95 MS", "P99 MS", "MAX MS", "SCAN/RET"
+        );
+        for stats in &stats {
+            let ratio = stats.scanned_per_returned.map_or_else(|| "-".to_string(), |ratio| format!("{:.1}", ratio));
+            println!(
+                "   {:<20} {:>8} {:>6} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>10}",
+                stats.collection,
+                stats.queries + stats.aggregations,
+                stats.slow_queries,
+                stats.latency.p50_ms,
+                stats.latency.p95_ms,
+                stats.latency.p99_ms,
+                stats.latency.max_ms,
+                ratio
+            );
+        }
+    }
+    println!();
+
+    let slow = client.list_slow_queries(collection, Some(args.slow_limit)).await?;
+    println!("🐢 Slow Queries:");
+    if slow.is_empty() {
+        println!("   None logged");
+    }
+    for query in &slow {
+        // Unplanned queries read the whole collection
+        let access = query.plan.as_ref().and_then(|plan| plan.get("access"));
+        let access = match (
+            access.and_then(|access| access.get("type")).and_then(Value::as_str),
+            access.and_then(|access| access.get("index")).and_then(Value::as_str),
+        ) {
+            (Some(path), Some(index)) => format!("{} of {}", path.replace('_', " "), index),
+            (Some(path), None) => path.replace('_', " "),
+            (None, _) => "collection scan".to_string(),
+        };
+        println!(
+            "   {} {} on {}: {} ms, {} scanned for {} returned ({}){}",
+            query.recorded_at.format("%Y-%m-%d %H:%M:%S"),
+            query.kind,
+            query.collection,
+            query.duration_ms,
+            query.documents_scanned,
+            query.documents_returned,
+            access,
+            if query.failed { " - failed" } else { "" }
+        );
+        if args.detailed {
+            println!("     Query: {}", query.query);
+            if let Some(plan) = &query.plan {
+                println!("     Plan:  {}", plan);
+            }
+        }
+    }
+    Ok(())
+}
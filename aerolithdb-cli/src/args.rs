@@ -3,7 +3,7 @@
 //! This module defines all command-line argument structures for aerolithsDB CLI operations.
 //! Each struct represents the arguments for a specific command category.
 
-use clap::Args;
+use clap::{Args, Subcommand};
 
 /// Command-line arguments for document storage operations.
 ///
@@ -137,9 +137,14 @@ pub struct DeleteArgs {
 /// sorting, pagination, and aggregation. Supports complex queries
 /// with multiple conditions and optimized execution.
 #[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct QueryArgs {
+    #[command(subcommand)]
+    pub command: Option<QueryCommand>,
+
     /// Name of the collection to query.
-    pub collection: String,
+    #[arg(required = true)]
+    pub collection: Option<String>,
     
     /// JSON filter criteria for document selection.
     /// 
@@ -148,21 +153,32 @@ pub struct QueryArgs {
     /// - Comparison: `{"age": {"$gt": 25, "$lt": 65}}`
     /// - Logical: `{"$or": [{"status": "active"}, {"priority": "high"}]}`
     /// - Array operations: `{"tags": {"$in": ["important", "urgent"]}}`
-    /// - Text search: `{"description": {"$regex": "pattern"}}`
+    /// - Pattern match: `{"description": {"$regex": "pattern"}}`
+    /// - Text search: `{"$text": {"$search": "rust -java"}}`, or use --text
     /// 
     /// Can be provided inline or via file reference (@file.json).
     #[arg(long)]
     pub filter: Option<String>,
+
+    /// Full-text search through the collection's text index.
+    ///
+    /// Words are matched regardless of case and inflection; prefix a word
+    /// with `-` to exclude documents containing it. Combined with --filter
+    /// when both are given. Without --sort, the most relevant documents
+    /// come first and each carries its relevance in `_score`.
+    #[arg(long)]
+    pub text: Option<String>,
     
     /// Sorting specification for result ordering.
     /// 
-    /// Format: `field:direction` where direction is 'asc' or 'desc'
+    /// Format: `field:direction` where direction is 'asc' or 'desc',
+    /// or a JSON object such as `{"created_at": -1}`
     /// Examples:
     /// - Single field: `created_at:desc`
     /// - Multiple fields: `priority:desc,created_at:asc`
     /// - Nested fields: `user.profile.score:desc`
     /// 
-    /// Sorting is optimized when fields have appropriate indexes.
+    /// Documents with equal sort fields are ordered by ID.
     #[arg(long)]
     pub sort: Option<String>,
     
@@ -187,6 +203,25 @@ pub struct QueryArgs {
     /// Large offsets may impact performance; consider cursor-based pagination.
     #[arg(long, default_value = "0")]
     pub offset: u32,
+
+    /// Continue after the page that printed this cursor.
+    ///
+    /// Each page with more results after it prints a next cursor; pass it
+    /// with the same filter and sort to get the following page. Documents
+    /// written in between do not shift the pages the way an offset does.
+    #[arg(long)]
+    pub cursor: Option<String>,
+
+    /// Return only these comma-separated fields of each document.
+    ///
+    /// Nested fields use dots, e.g. `title,author.name`. The server trims
+    /// the documents, so large documents cost only the fields asked for.
+    #[arg(long, conflicts_with = "exclude")]
+    pub fields: Option<String>,
+
+    /// Leave these comma-separated fields out of each document.
+    #[arg(long)]
+    pub exclude: Option<String>,
     
     /// Include detailed metadata in query results.
     /// 
@@ -222,6 +257,32 @@ pub struct QueryArgs {
     pub explain: bool,
 }
 
+/// Query subcommands that go beyond filtering documents.
+#[derive(Debug, Subcommand)]
+pub enum QueryCommand {
+    /// Run an aggregation pipeline over a collection
+    Aggregate(AggregateArgs),
+}
+
+/// Command-line arguments for aggregation pipelines.
+#[derive(Debug, Args)]
+pub struct AggregateArgs {
+    /// Name of the collection to aggregate.
+    pub collection: String,
+
+    /// Pipeline as a JSON array of stages, inline or via file reference (@pipeline.json).
+    ///
+    /// Supported stages are `$match`, `$group` (with `$count`, `$sum`,
+    /// `$avg`, `$min` and `$max`), `$sort`, `$limit` and `$project`, e.g.
+    /// `[{"$group": {"_id": "$status", "total": {"$sum": "$amount"}}}]`.
+    #[arg(long)]
+    pub pipeline: String,
+
+    /// Output format: "json" (default), "jsonl" or "table".
+    #[arg(long, default_value = "json")]
+    pub format: String,
+}
+
 /// Command-line arguments for collection listing operations.
 ///
 /// Provides comprehensive collection discovery and metadata access.
@@ -267,6 +328,23 @@ pub struct ListArgs {
     /// Useful for organizing collections in large deployments.
     #[arg(long)]
     pub pattern: Option<String>,
+
+    /// Maximum number of documents to list from a collection.
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Order a collection's documents by `id` (default), `created_at`,
+    /// `updated_at` or `size`.
+    #[arg(long)]
+    pub sort: Option<String>,
+
+    /// List the largest values of the sort field first.
+    #[arg(long)]
+    pub desc: bool,
+
+    /// Continue a collection listing after the page that printed this cursor.
+    #[arg(long)]
+    pub cursor: Option<String>,
 }
 
 /// Command-line arguments for database statistics operations.
@@ -435,6 +513,13 @@ pub struct OptimizeArgs {
     /// - Rollback procedures if needed
     #[arg(long)]
     pub detailed: bool,
+
+    /// Slow queries shown by the "analyze" and "queries" analyses.
+    ///
+    /// The server logs queries taking at least its slow-query threshold
+    /// (100ms by default); the most recent ones are listed, newest first.
+    #[arg(long, default_value = "10")]
+    pub slow_limit: usize,
 }
 
 // ================================================================================================
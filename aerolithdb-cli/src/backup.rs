@@ -0,0 +1,149 @@
+//! Backup and restore commands for the AerolithDB CLI
+//!
+//! Creates full and incremental backups on the server, lists and deletes
+//! them, and restores a backup by id or the latest one taken at or before a
+//! point in time. Restores can take a while on large databases; raise
+//! `--timeout` accordingly, or run them with `--background` and follow the
+//! job with `jobs show`.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+
+use crate::client::{aerolithsClient, BackupManifest, Job};
+
+#[derive(Debug, Args)]
+pub struct BackupArgs {
+    #[command(subcommand)]
+    pub command: BackupCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommand {
+    /// Back up all documents
+    Create {
+        /// Only back up documents changed since the previous backup
+        #[arg(long)]
+        incremental: bool,
+
+        /// Run as a background job and return at once
+        #[arg(long)]
+        background: bool,
+    },
+
+    /// List backups, oldest first
+    List,
+
+    /// Show the manifest of a backup
+    Show {
+        /// Backup ID
+        id: String,
+    },
+
+    /// Delete a backup no incremental backup depends on
+    Delete {
+        /// Backup ID
+        id: String,
+    },
+
+    /// Restore the documents to their state when a backup was taken
+    Restore {
+        /// Backup ID
+        #[arg(required_unless_present = "at", conflicts_with = "at")]
+        id: Option<String>,
+
+        /// Restore the latest backup taken at or before this RFC 3339 time
+        #[arg(long)]
+        at: Option<DateTime<Utc>>,
+
+        /// Run as a background job and return at once
+        #[arg(long, requires = "id")]
+        background: bool,
+    },
+}
+
+pub async fn handle_backup_command(client: &aerolithsClient, args: BackupArgs) -> Result<()> {
+    match args.command {
+        BackupCommand::Create { incremental, background: true } => {
+            let job = client.start_backup_job(incremental).await?;
+            print_started(&job);
+        }
+
+        BackupCommand::Create { incremental, background: false } => {
+            let manifest = client.create_backup(incremental).await?;
+            println!(
+                "✅ Created {} backup {} with {} of {} documents",
+                manifest.kind, manifest.id, manifest.documents, manifest.total_documents
+            );
+            if !manifest.unreadable.is_empty() {
+                println!("⚠️  Left out {} unreadable documents:", manifest.unreadable.len());
+                for key in &manifest.unreadable {
+                    println!("   {}", key);
+                }
+            }
+        }
+
+        BackupCommand::List => {
+            let backups = client.list_backups().await?;
+            if backups.is_empty() {
+                println!("No backups");
+                return Ok(());
+            }
+            println!("{:<30} {:<12} {:<25} {:>10} {:>12}", "ID", "KIND", "CREATED", "DOCUMENTS", "BYTES");
+            for backup in &backups {
+                print_backup_row(backup);
+            }
+        }
+
+        BackupCommand::Show { id } => {
+            let manifest = client.get_backup(&id).await?;
+            println!("💾 Backup details:");
+            println!("{}", serde_json::to_string_pretty(&manifest)?);
+        }
+
+        BackupCommand::Delete { id } => {
+            client.delete_backup(&id).await?;
+            println!("🗑️  Deleted backup {}", id);
+        }
+
+        BackupCommand::Restore { id: Some(id), background: true, .. } => {
+            let job = client.start_restore_job(&id).await?;
+            print_started(&job);
+        }
+
+        BackupCommand::Restore { id, at, .. } => {
+            let report = match (id, at) {
+                (Some(id), _) => client.restore_backup(&id).await?,
+                (None, Some(at)) => client.restore_backup_at(at).await?,
+                (None, None) => unreachable!("clap requires an id or --at"),
+            };
+            println!(
+                "✅ Restored backup {}: {} restored, {} unchanged, {} deleted",
+                report.backup_id, report.restored, report.unchanged, report.deleted
+            );
+            if !report.retained.is_empty() {
+                println!("🔒 Kept {} protected documents as they are", report.retained.len());
+            }
+            if !report.missing.is_empty() {
+                println!("⚠️  {} documents were missing from the backup", report.missing.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_started(job: &Job) {
+    println!("🚀 Started {} job {}", job.kind, job.id);
+    println!("   Follow it with: aerolithsdb-cli jobs show {}", job.id);
+}
+
+fn print_backup_row(backup: &BackupManifest) {
+    println!(
+        "{:<30} {:<12} {:<25} {:>10} {:>12}",
+        backup.id,
+        backup.kind,
+        backup.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        backup.documents,
+        backup.size
+    );
+}
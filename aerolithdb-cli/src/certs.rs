@@ -0,0 +1,101 @@
+//! Cluster certificate commands for the AerolithDB CLI
+//!
+//! Shows the cluster certificate authority nodes secure their mTLS
+//! connections with, lists the nodes holding a certificate from it, and
+//! approves or rejects the certificate requests of joining nodes when the
+//! CA is set to wait for an operator.
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use crate::client::aerolithsClient;
+
+#[derive(Debug, Args)]
+pub struct CertsArgs {
+    #[command(subcommand)]
+    pub command: CertsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CertsCommand {
+    /// Show the cluster CA and its certificate
+    Ca,
+
+    /// List the nodes holding a certificate
+    List,
+
+    /// List the certificate requests awaiting approval
+    Pending,
+
+    /// Approve a certificate request
+    Approve {
+        /// Request ID
+        id: String,
+    },
+
+    /// Reject a certificate request
+    Reject {
+        /// Request ID
+        id: String,
+    },
+}
+
+pub async fn handle_certs_command(client: &aerolithsClient, args: CertsArgs) -> Result<()> {
+    match args.command {
+        CertsCommand::Ca => match client.get_certificate_authority().await? {
+            Some(ca) => {
+                println!("🔏 Cluster CA ({})", ca.origin.replace('_', "-"));
+                println!("   Approval: {}", if ca.require_approval { "required" } else { "not required" });
+                print!("{}", ca.certificate_pem);
+            }
+            None => println!("This node does not run a cluster CA"),
+        },
+
+        CertsCommand::List => {
+            let nodes = client.list_node_certificates().await?;
+            if nodes.is_empty() {
+                println!("No node holds a certificate");
+                return Ok(());
+            }
+            println!("{:<24} {:<34} {:<24} RENEWALS", "NODE", "SERIAL", "EXPIRES");
+            for node in &nodes {
+                println!(
+                    "{:<24} {:<34} {:<24} {}",
+                    node.node_id,
+                    node.serial,
+                    node.not_after.format("%Y-%m-%d %H:%M:%S UTC"),
+                    node.renewals
+                );
+            }
+        }
+
+        CertsCommand::Pending => {
+            let requests = client.list_certificate_requests().await?;
+            if requests.is_empty() {
+                println!("No certificate request awaits approval");
+                return Ok(());
+            }
+            println!("{:<18} {:<24} {:<24} NAMES", "ID", "NODE", "REQUESTED");
+            for request in &requests {
+                println!(
+                    "{:<18} {:<24} {:<24} {}",
+                    request.id,
+                    request.node_id,
+                    request.requested_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    request.subject_alt_names.join(", ")
+                );
+            }
+        }
+
+        CertsCommand::Approve { id } => {
+            let node = client.approve_certificate_request(&id).await?;
+            println!("✅ Issued certificate {} to {}, valid until {}", node.serial, node.node_id, node.not_after.format("%Y-%m-%d %H:%M:%S UTC"));
+        }
+
+        CertsCommand::Reject { id } => {
+            client.reject_certificate_request(&id).await?;
+            println!("🚫 Rejected certificate request {}", id);
+        }
+    }
+    Ok(())
+}
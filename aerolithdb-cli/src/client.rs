@@ -179,6 +179,13 @@ pub struct QueryRequest {
     /// - Descending: `{"field": -1}` or `{"field": "desc"}`
     /// - Multi-field: `{"primary": 1, "secondary": -1}`
     pub sort: Option<serde_json::Value>,
+
+    /// Continuation cursor returned as `next_cursor` by the previous page.
+    ///
+    /// Unlike an offset, a cursor is not shifted by documents written
+    /// between pages. It must be used with the same sort specification.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
 }
 
 /// Response structure for query operations.
@@ -212,6 +219,25 @@ pub struct QueryResponse {
     /// Shows the starting position of the current result page
     /// within the complete result set.
     pub offset: Option<usize>,
+
+    /// Cursor of the next page, absent on the last one.
+    ///
+    /// Pass it back as `cursor` with the same sort to continue after
+    /// the documents of this page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Response structure for aggregation pipelines.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AggregateResponse {
+    /// Output documents of the last pipeline stage
+    pub documents: Vec<serde_json::Value>,
+    pub count: usize,
+
+    /// Documents read from the collection to produce the output
+    pub scanned: usize,
+    pub execution_time_ms: u64,
 }
 
 /// Collection metadata response structure.
@@ -257,6 +283,442 @@ pub struct ErrorResponse {
     pub details: Option<serde_json::Value>,
 }
 
+/// A named lease held through the consensus-backed lock service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lease {
+    /// Name of the lock
+    pub name: String,
+    /// Identity that currently holds the lease
+    pub holder: String,
+    /// Strictly increasing token to attach to writes guarded by this lease.
+    ///
+    /// Resources protected by the lock should reject requests carrying a
+    /// token lower than the highest one they have already seen.
+    pub fencing_token: u64,
+    /// Lease duration in milliseconds, applied again on every renewal
+    pub ttl_ms: u64,
+    /// When the current holder first acquired the lease
+    pub acquired_at: chrono::DateTime<chrono::Utc>,
+    /// When the lease lapses unless renewed
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Result of a lock acquire, renew, or release request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LockOutcome {
+    /// The lease was acquired or renewed
+    Granted { lease: Lease },
+    /// Another holder has the lease
+    Held { lease: Lease },
+    /// The lease was released
+    Released { name: String },
+    /// The caller does not hold the lease under the given fencing token
+    NotHeld { name: String },
+}
+
+/// Description of a backup bundle on the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Identifier of the backup
+    pub id: String,
+    /// "full" or "incremental"
+    pub kind: String,
+    /// Backup an incremental backup builds on
+    pub parent: Option<String>,
+    /// When the backup started
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Documents held in the bundle
+    pub documents: usize,
+    /// Documents that existed when the backup was taken
+    pub total_documents: usize,
+    /// Documents deleted since the parent backup
+    pub deleted: usize,
+    /// Documents left out because no intact copy could be read
+    pub unreadable: Vec<String>,
+    /// Size of the bundle's documents in bytes
+    pub size: u64,
+}
+
+/// Outcome of restoring a backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    /// Backup that was restored
+    pub backup_id: String,
+    /// Bundles read, from the full backup to the restored one
+    pub chain: Vec<String>,
+    /// Documents rewritten from the backup
+    pub restored: usize,
+    /// Documents already in their backed-up state
+    pub unchanged: usize,
+    /// Documents created after the backup that were deleted
+    pub deleted: usize,
+    /// Documents protected by a legal hold or WORM retention
+    pub retained: Vec<String>,
+    /// Documents whose payload is missing from the backup
+    pub missing: Vec<String>,
+}
+
+/// JSON schema documents of a collection are validated against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionSchema {
+    /// Collection the schema applies to
+    pub collection: String,
+    /// Version, starting at 1 and bumped on every change of the schema
+    pub version: u32,
+    /// "strict", "warn" or "off"
+    pub mode: String,
+    /// The JSON schema itself
+    pub schema: serde_json::Value,
+    /// When this version was set
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// How far a background job has come.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// Units done, such as documents
+    pub completed: u64,
+    /// Units in total, when known
+    pub total: Option<u64>,
+}
+
+/// One line of a background job's log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobLogEntry {
+    /// When the line was written
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// What happened
+    pub message: String,
+}
+
+/// A query or background job in progress on the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningOperation {
+    pub id: String,
+    /// "query" or "job"
+    pub source: String,
+    /// "query" or "aggregate" for queries, such as "backup" for jobs
+    pub kind: String,
+    /// Collection a query reads
+    pub collection: Option<String>,
+    /// Tenant a job runs for
+    pub tenant: Option<String>,
+    /// "running" or "queued"
+    pub state: String,
+    /// When it started, unset for jobs still queued
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Time since it started, or since a queued job was submitted
+    pub elapsed_ms: u64,
+    /// Documents a query read so far
+    pub documents_scanned: Option<usize>,
+    /// Memory a query holds in bytes
+    pub memory_bytes: Option<usize>,
+    /// Progress a job reported
+    pub progress: Option<JobProgress>,
+    /// Whether it was killed and is yet to stop
+    pub cancelled: bool,
+}
+
+impl RunningOperation {
+    /// Collection of a query or tenant of a job, or "-".
+    pub fn target(&self) -> &str {
+        self.collection.as_deref().or(self.tenant.as_deref()).unwrap_or("-")
+    }
+
+    /// Documents scanned by a query, or progress of a job.
+    pub fn work_label(&self) -> String {
+        match (&self.documents_scanned, &self.progress) {
+            (Some(scanned), _) => format!("{} docs", scanned),
+            (None, Some(JobProgress { completed, total: Some(total) })) => format!("{} / {}", completed, total),
+            (None, Some(progress)) => progress.completed.to_string(),
+            (None, None) => "-".to_string(),
+        }
+    }
+}
+
+/// A background job on the server, such as a backup or restore.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    /// Identifier of the job
+    pub id: String,
+    /// What the job does, such as "backup", "restore" or "scrub"
+    pub kind: String,
+    /// Tenant the job runs for
+    pub tenant: Option<String>,
+    /// "queued", "running", "succeeded", "failed" or "cancelled"
+    pub state: String,
+    /// Progress reported by the job
+    pub progress: JobProgress,
+    /// Whether cancellation was requested
+    pub cancel_requested: bool,
+    /// When the job was submitted
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the job started running
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the job ended
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Why the job failed
+    pub error: Option<String>,
+    /// Outcome of a succeeded job
+    pub result: Option<serde_json::Value>,
+    /// Log lines, oldest first
+    pub logs: Vec<JobLogEntry>,
+}
+
+impl Job {
+    /// Whether the job has ended.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state.as_str(), "succeeded" | "failed" | "cancelled")
+    }
+
+    /// Completion as a percentage, when the job reported a total.
+    pub fn percent(&self) -> Option<f64> {
+        match self.progress.total {
+            Some(0) => Some(100.0),
+            Some(total) => Some(self.progress.completed as f64 * 100.0 / total as f64),
+            None => None,
+        }
+    }
+
+    /// Progress as a percentage, a count, or "-" before any was reported.
+    pub fn progress_label(&self) -> String {
+        match self.percent() {
+            Some(percent) => format!("{:.0}%", percent),
+            None if self.progress.completed > 0 => self.progress.completed.to_string(),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// Latency percentiles over a collection's most recent queries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryLatency {
+    /// Queries the percentiles are computed over
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Query statistics the server recorded for one collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionQueryStats {
+    /// Collection queried
+    pub collection: String,
+    /// Queries run
+    pub queries: u64,
+    /// Aggregations run
+    pub aggregations: u64,
+    /// Queries answered from the query result cache
+    pub cache_hits: u64,
+    /// Queries that failed
+    pub failures: u64,
+    /// Queries that took at least the slow-query threshold
+    pub slow_queries: u64,
+    /// Documents read to answer the queries
+    pub documents_scanned: u64,
+    /// Documents the queries returned
+    pub documents_returned: u64,
+    /// Documents scanned per document returned
+    pub scanned_per_returned: Option<f64>,
+    /// Mean query latency in milliseconds
+    pub mean_ms: f64,
+    /// Latency percentiles
+    pub latency: QueryLatency,
+}
+
+/// A query that took at least the server's slow-query threshold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowQuery {
+    /// Identifier the query ran under
+    pub query_id: String,
+    /// Collection queried
+    pub collection: String,
+    /// "query" or "aggregate"
+    pub kind: String,
+    /// The query request, or the pipeline of an aggregation
+    pub query: serde_json::Value,
+    /// Plan the filter was run with
+    pub plan: Option<serde_json::Value>,
+    /// Time the query took
+    pub duration_ms: u64,
+    /// Documents read to answer it
+    pub documents_scanned: usize,
+    /// Documents it returned
+    pub documents_returned: usize,
+    /// Whether it failed
+    pub failed: bool,
+    /// When it finished
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The cluster certificate authority nodes get their mTLS certificates from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateAuthorityInfo {
+    /// "built_in" or "external"
+    pub origin: String,
+    /// CA certificate nodes add to their trust store
+    pub certificate_pem: String,
+    /// Whether joining nodes wait for an operator's approval
+    pub require_approval: bool,
+}
+
+/// A node holding a certificate from the cluster CA.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrolledNode {
+    /// Node the certificate was issued to
+    pub node_id: String,
+    /// Serial number of its current certificate
+    pub serial: String,
+    /// When its current certificate expires
+    pub not_after: chrono::DateTime<chrono::Utc>,
+    /// Times the certificate was renewed
+    pub renewals: u32,
+}
+
+/// A joining node's certificate request awaiting approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingCertificateRequest {
+    /// Identifier to approve or reject the request by
+    pub id: String,
+    /// Node asking for a certificate
+    pub node_id: String,
+    /// DNS names and IP addresses the certificate would cover
+    pub subject_alt_names: Vec<String>,
+    /// When the node asked
+    pub requested_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// An identity on the admission allowlist or denylist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessRule {
+    /// "node_id" or "public_key"
+    pub kind: String,
+    /// The node id or public key
+    pub value: String,
+    /// Why it was added
+    #[serde(default)]
+    pub note: Option<String>,
+    /// When it was added
+    pub added_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The admission lists and whether joining nodes need approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessLists {
+    /// Whether nodes that are not allowlisted wait for an operator
+    pub require_approval: bool,
+    /// Identities admitted without approval
+    pub allow: Vec<AccessRule>,
+    /// Identities always refused
+    pub deny: Vec<AccessRule>,
+}
+
+/// A node's join request awaiting approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingJoin {
+    /// Identifier to approve or reject the request by
+    pub id: String,
+    /// Node asking to join
+    pub node_id: String,
+    /// Public key the node presented
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// Address the node is reached at
+    #[serde(default)]
+    pub address: Option<String>,
+    /// When the node first asked
+    pub requested_at: chrono::DateTime<chrono::Utc>,
+    /// Times it has asked while waiting
+    pub attempts: u32,
+}
+
+/// Refused join attempts of a denied node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeniedJoinAttempts {
+    /// Node that tried to join
+    pub node_id: String,
+    /// Address it last tried from
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Attempts within the current alert window
+    pub attempts: u32,
+    /// Attempts since the node was first refused
+    pub total_attempts: u64,
+    /// When it last tried
+    pub last_attempt_at: chrono::DateTime<chrono::Utc>,
+    /// Whether the attempts raised an alert
+    pub alerted: bool,
+}
+
+/// Resource usage of a node's process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU used since the previous report, in percent of one core
+    pub cpu_percent: f32,
+    /// Cores available to the process
+    pub cpu_count: usize,
+    /// Resident set size
+    pub resident_memory_bytes: u64,
+    /// Virtual memory size
+    pub virtual_memory_bytes: u64,
+    /// Physical memory of the host
+    pub total_memory_bytes: u64,
+    /// Threads of the process, if the platform reports them
+    #[serde(default)]
+    pub threads: Option<usize>,
+    /// Open file descriptors, if the platform reports them
+    #[serde(default)]
+    pub open_file_descriptors: Option<usize>,
+    /// Disks holding the node's data, one per mount
+    #[serde(default)]
+    pub disks: Vec<DiskUsage>,
+}
+
+/// Space on a disk holding node data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub mount_point: String,
+    pub file_system: String,
+    /// Node directories on this disk
+    pub paths: Vec<String>,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// Share of the disk in use, from 0 to 100
+    pub used_percent: f64,
+}
+
+/// Mean and peak of a gauge over a metric point's period.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct GaugeSummary {
+    pub mean: f64,
+    pub max: f64,
+}
+
+/// A node's key metrics over one minute or hour.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricPoint {
+    /// Start of the minute or hour the point covers
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub samples: u64,
+    /// CPU in percent of one core
+    pub cpu_percent: GaugeSummary,
+    pub resident_memory_bytes: GaugeSummary,
+    pub disk_used_percent: GaugeSummary,
+    pub open_file_descriptors: GaugeSummary,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub queries: u64,
+    pub requests: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetricsHistoryResponse {
+    points: Vec<MetricPoint>,
+}
+
 impl aerolithsClient {
     /// Creates a new aerolithsDB client with the specified configuration.
     ///
@@ -841,6 +1303,19 @@ impl aerolithsClient {
         self.handle_response(response).await
     }
 
+    /// Runs an aggregation pipeline over a collection.
+    pub async fn aggregate_documents(
+        &self,
+        collection: &str,
+        pipeline: &serde_json::Value,
+    ) -> Result<AggregateResponse> {
+        let endpoint = format!("/api/v1/collections/{}/aggregate", collection);
+        debug!("POST aggregate: {} -> {}", endpoint, serde_json::to_string(pipeline)?);
+
+        let response = self.post(&endpoint, &serde_json::json!({ "pipeline": pipeline })).await?;
+        self.handle_response(response).await
+    }
+
     /// Lists documents in a collection with optional pagination.
     ///
     /// ## List Operation Characteristics
@@ -924,6 +1399,42 @@ impl aerolithsClient {
         Ok(query_response.documents)
     }
 
+    /// Lists one page of a collection's documents ordered by `id`,
+    /// `created_at`, `updated_at` or `size`, continuing after `cursor`.
+    ///
+    /// The response's `next_cursor` continues the listing with the same
+    /// sort and direction; unlike an offset it is not shifted by documents
+    /// written between pages.
+    pub async fn list_documents_page(
+        &self,
+        collection: &str,
+        limit: Option<usize>,
+        sort: Option<&str>,
+        descending: bool,
+        cursor: Option<&str>,
+    ) -> Result<QueryResponse> {
+        let url = format!("{}/api/v1/collections/{}/documents", self.base_url, collection);
+
+        let mut params = Vec::new();
+        if let Some(limit) = limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(sort) = sort {
+            params.push(("sort", sort.to_string()));
+        }
+        if descending {
+            params.push(("order", "desc".to_string()));
+        }
+        if let Some(cursor) = cursor {
+            params.push(("cursor", cursor.to_string()));
+        }
+
+        debug!("GET documents: {} {:?}", url, params);
+
+        let response = self.client.get(&url).query(&params).send().await?;
+        self.handle_response(response).await
+    }
+
     /// Retrieves comprehensive database and collection statistics.
     ///
     /// ## Statistics Categories
@@ -998,6 +1509,418 @@ impl aerolithsClient {
         self.handle_response(response).await
     }
 
+    /// Acquires a named lock for `holder`, valid for `ttl` unless renewed.
+    ///
+    /// Returns `LockOutcome::Held` with the current lease when another holder
+    /// has the lock. Acquiring a lock the holder already owns extends it and
+    /// keeps the same fencing token.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// match client.acquire_lock("scheduler-leader", "node-a", Duration::from_secs(15)).await? {
+    ///     LockOutcome::Granted { lease } => println!("Leader with token {}", lease.fencing_token),
+    ///     LockOutcome::Held { lease } => println!("{} is leader", lease.holder),
+    ///     _ => {}
+    /// }
+    /// ```
+    pub async fn acquire_lock(&self, name: &str, holder: &str, ttl: Duration) -> Result<LockOutcome> {
+        let body = serde_json::json!({
+            "holder": holder,
+            "ttl_seconds": ttl.as_secs(),
+        });
+        self.lock_request(name, "acquire", &body).await
+    }
+
+    /// Extends a held lock by its TTL.
+    ///
+    /// Returns `LockOutcome::NotHeld` if the lease expired or was taken over,
+    /// in which case the caller must stop acting as the holder.
+    pub async fn renew_lock(&self, name: &str, holder: &str, fencing_token: u64) -> Result<LockOutcome> {
+        let body = serde_json::json!({
+            "holder": holder,
+            "fencing_token": fencing_token,
+        });
+        self.lock_request(name, "renew", &body).await
+    }
+
+    /// Releases a held lock so other holders can acquire it immediately.
+    pub async fn release_lock(&self, name: &str, holder: &str, fencing_token: u64) -> Result<LockOutcome> {
+        let body = serde_json::json!({
+            "holder": holder,
+            "fencing_token": fencing_token,
+        });
+        self.lock_request(name, "release", &body).await
+    }
+
+    /// Retrieves the current lease on a lock, or `None` if it is free.
+    pub async fn get_lock(&self, name: &str) -> Result<Option<Lease>> {
+        let url = format!("{}/api/v1/locks/{}", self.base_url, name);
+        debug!("GET lock: {}", url);
+
+        let response = self.client.get(&url).send().await?;
+
+        if response.status() == 404 {
+            return Ok(None);
+        }
+
+        let lease: Lease = self.handle_response(response).await?;
+        Ok(Some(lease))
+    }
+
+    /// Retrieves the current schema of a collection, or a replaced
+    /// `version` of it; `None` if there is none.
+    pub async fn get_schema(&self, collection: &str, version: Option<u32>) -> Result<Option<CollectionSchema>> {
+        let url = format!("{}/api/v1/collections/{}/schema", self.base_url, collection);
+        debug!("GET schema: {} (version: {:?})", url, version);
+
+        let mut request = self.client.get(&url).timeout(self.timeout);
+        if let Some(version) = version {
+            request = request.query(&[("version", version)]);
+        }
+        let response = request.send().await?;
+
+        if response.status() == 404 {
+            return Ok(None);
+        }
+        Ok(Some(self.handle_response(response).await?))
+    }
+
+    /// Lists every version of a collection's schema, oldest first.
+    pub async fn list_schema_versions(&self, collection: &str) -> Result<Vec<CollectionSchema>> {
+        let response = self.get(&format!("/api/v1/collections/{}/schema/versions", collection)).await?;
+        self.handle_response(response).await
+    }
+
+    /// Sets the schema new writes to a collection are validated against,
+    /// in `mode` "strict", "warn" or "off".
+    pub async fn set_schema(&self, collection: &str, schema: &serde_json::Value, mode: &str) -> Result<CollectionSchema> {
+        let body = serde_json::json!({ "schema": schema, "mode": mode });
+        let response = self.put(&format!("/api/v1/collections/{}/schema", collection), &body).await?;
+        self.handle_response(response).await
+    }
+
+    /// Changes how a collection's schema is enforced without changing it.
+    pub async fn set_schema_mode(&self, collection: &str, mode: &str) -> Result<CollectionSchema> {
+        let body = serde_json::json!({ "mode": mode });
+        let response = self.put(&format!("/api/v1/collections/{}/schema/mode", collection), &body).await?;
+        self.handle_response(response).await
+    }
+
+    /// Stops validating writes to a collection; its schema versions are kept.
+    pub async fn remove_schema(&self, collection: &str) -> Result<()> {
+        let response = self.delete(&format!("/api/v1/collections/{}/schema", collection)).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("HTTP {} - {}", status, response.text().await?))
+    }
+
+    /// Sends a lock request, treating a 409 conflict as a normal outcome.
+    async fn lock_request(&self, name: &str, action: &str, body: &serde_json::Value) -> Result<LockOutcome> {
+        let url = format!("{}/api/v1/locks/{}/{}", self.base_url, name, action);
+        debug!("POST lock {}: {}", action, url);
+
+        let response = self.client.post(&url).json(body).send().await?;
+
+        // The server reports contended or lost leases as 409 with the lease state in the body
+        if response.status() == 409 {
+            return Ok(response.json().await?);
+        }
+
+        self.handle_response(response).await
+    }
+
+    /// Backs up the server's documents, or with `incremental` only those
+    /// changed since its previous backup.
+    pub async fn create_backup(&self, incremental: bool) -> Result<BackupManifest> {
+        let url = format!("{}/api/v1/admin/backups", self.base_url);
+        debug!("POST backup: {} (incremental: {})", url, incremental);
+
+        let response = self.client
+            .post(&url)
+            .query(&[("incremental", incremental)])
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        self.handle_response(response).await
+    }
+
+    /// Starts a backup as a background job and returns the job.
+    pub async fn start_backup_job(&self, incremental: bool) -> Result<Job> {
+        let url = format!("{}/api/v1/admin/backups", self.base_url);
+        debug!("POST backup job: {} (incremental: {})", url, incremental);
+
+        let response = self.client
+            .post(&url)
+            .query(&[("incremental", incremental), ("background", true)])
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        self.handle_response(response).await
+    }
+
+    /// Lists the server's backups, oldest first.
+    pub async fn list_backups(&self) -> Result<Vec<BackupManifest>> {
+        let response = self.get("/api/v1/admin/backups").await?;
+        self.handle_response(response).await
+    }
+
+    /// Retrieves the manifest of one backup.
+    pub async fn get_backup(&self, id: &str) -> Result<BackupManifest> {
+        let response = self.get(&format!("/api/v1/admin/backups/{}", id)).await?;
+        self.handle_response(response).await
+    }
+
+    /// Deletes a backup; the server refuses while an incremental backup
+    /// depends on it.
+    pub async fn delete_backup(&self, id: &str) -> Result<()> {
+        let response = self.delete(&format!("/api/v1/admin/backups/{}", id)).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("HTTP {} - {}", status, response.text().await?))
+    }
+
+    /// Restores the documents to their state when backup `id` was taken.
+    pub async fn restore_backup(&self, id: &str) -> Result<RestoreReport> {
+        let response = self.post(&format!("/api/v1/admin/backups/{}/restore", id), &serde_json::json!({})).await?;
+        self.handle_response(response).await
+    }
+
+    /// Starts restoring backup `id` as a background job and returns the job.
+    pub async fn start_restore_job(&self, id: &str) -> Result<Job> {
+        let url = format!("{}/api/v1/admin/backups/{}/restore", self.base_url, id);
+        debug!("POST restore job: {}", url);
+
+        let response = self.client
+            .post(&url)
+            .query(&[("background", true)])
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        self.handle_response(response).await
+    }
+
+    /// Lists background jobs, newest first, optionally only those in
+    /// `state` or run for `tenant`.
+    pub async fn list_jobs(&self, state: Option<&str>, tenant: Option<&str>) -> Result<Vec<Job>> {
+        let url = format!("{}/api/v1/jobs", self.base_url);
+        debug!("GET jobs: {} (state: {:?}, tenant: {:?})", url, state, tenant);
+
+        let mut query = Vec::new();
+        if let Some(state) = state {
+            query.push(("state", state));
+        }
+        if let Some(tenant) = tenant {
+            query.push(("tenant", tenant));
+        }
+        let response = self.client
+            .get(&url)
+            .query(&query)
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        self.handle_response(response).await
+    }
+
+    /// Retrieves one background job with its log.
+    pub async fn get_job(&self, id: &str) -> Result<Job> {
+        let response = self.get(&format!("/api/v1/jobs/{}", id)).await?;
+        self.handle_response(response).await
+    }
+
+    /// Cancels a queued or running job; a running job stops when it next
+    /// checks for cancellation.
+    pub async fn cancel_job(&self, id: &str) -> Result<Job> {
+        let response = self.post(&format!("/api/v1/jobs/{}/cancel", id), &serde_json::json!({})).await?;
+        self.handle_response(response).await
+    }
+
+    /// Retrieves the query statistics of every collection, or of one.
+    pub async fn get_query_stats(&self, collection: Option<&str>) -> Result<Vec<CollectionQueryStats>> {
+        let url = format!("{}/api/v1/admin/queries/stats", self.base_url);
+        debug!("GET query stats: {} (collection: {:?})", url, collection);
+
+        let mut request = self.client.get(&url).timeout(self.timeout);
+        if let Some(collection) = collection {
+            request = request.query(&[("collection", collection)]);
+        }
+        let response = request.send().await?;
+        self.handle_response(response).await
+    }
+
+    /// Retrieves the most recent slow queries, newest first.
+    pub async fn list_slow_queries(&self, collection: Option<&str>, limit: Option<usize>) -> Result<Vec<SlowQuery>> {
+        let url = format!("{}/api/v1/admin/queries/slow", self.base_url);
+        debug!("GET slow queries: {} (collection: {:?})", url, collection);
+
+        let mut query = Vec::new();
+        if let Some(collection) = collection {
+            query.push(("collection", collection.to_string()));
+        }
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        let response = self.client
+            .get(&url)
+            .query(&query)
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        self.handle_response(response).await
+    }
+
+    /// Retrieves the cluster CA, or `None` when the server runs without one.
+    pub async fn get_certificate_authority(&self) -> Result<Option<CertificateAuthorityInfo>> {
+        let response = self.get("/api/v1/admin/cluster/ca").await?;
+        if response.status() == 404 {
+            return Ok(None);
+        }
+        Ok(Some(self.handle_response(response).await?))
+    }
+
+    /// Lists the nodes holding a certificate from the cluster CA.
+    pub async fn list_node_certificates(&self) -> Result<Vec<EnrolledNode>> {
+        let response = self.get("/api/v1/admin/cluster/certificates").await?;
+        self.handle_response(response).await
+    }
+
+    /// Lists the certificate requests awaiting approval, oldest first.
+    pub async fn list_certificate_requests(&self) -> Result<Vec<PendingCertificateRequest>> {
+        let response = self.get("/api/v1/admin/cluster/certificates/requests").await?;
+        self.handle_response(response).await
+    }
+
+    /// Approves a certificate request; the node collects its certificate.
+    pub async fn approve_certificate_request(&self, id: &str) -> Result<EnrolledNode> {
+        let endpoint = format!("/api/v1/admin/cluster/certificates/requests/{}/approve", id);
+        let response = self.post(&endpoint, &serde_json::json!({})).await?;
+        self.handle_response(response).await
+    }
+
+    /// Rejects a certificate request.
+    pub async fn reject_certificate_request(&self, id: &str) -> Result<()> {
+        let response = self.delete(&format!("/api/v1/admin/cluster/certificates/requests/{}", id)).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("HTTP {} - {}", status, response.text().await?))
+    }
+
+    /// Gets the admission allowlist and denylist.
+    pub async fn get_admission_lists(&self) -> Result<AccessLists> {
+        let response = self.get("/api/v1/admin/cluster/admission").await?;
+        self.handle_response(response).await
+    }
+
+    /// Adds an identity to the "allowlist" or "denylist".
+    pub async fn add_access_rule(&self, list: &str, kind: &str, value: &str, note: Option<&str>) -> Result<AccessRule> {
+        let endpoint = format!("/api/v1/admin/cluster/admission/{}", list);
+        let body = serde_json::json!({ "kind": kind, "value": value, "note": note });
+        let response = self.post(&endpoint, &body).await?;
+        self.handle_response(response).await
+    }
+
+    /// Removes an identity from the "allowlist" or "denylist".
+    pub async fn remove_access_rule(&self, list: &str, kind: &str, value: &str) -> Result<()> {
+        let endpoint = format!("/api/v1/admin/cluster/admission/{}/{}/{}", list, kind, value);
+        let response = self.delete(&endpoint).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("HTTP {} - {}", status, response.text().await?))
+    }
+
+    /// Lists the join requests awaiting approval, oldest first.
+    pub async fn list_join_requests(&self) -> Result<Vec<PendingJoin>> {
+        let response = self.get("/api/v1/admin/cluster/admission/requests").await?;
+        self.handle_response(response).await
+    }
+
+    /// Approves a join request, allowlisting the node.
+    pub async fn approve_join_request(&self, id: &str) -> Result<AccessRule> {
+        let endpoint = format!("/api/v1/admin/cluster/admission/requests/{}/approve", id);
+        let response = self.post(&endpoint, &serde_json::json!({})).await?;
+        self.handle_response(response).await
+    }
+
+    /// Rejects a join request.
+    pub async fn reject_join_request(&self, id: &str) -> Result<()> {
+        let response = self.delete(&format!("/api/v1/admin/cluster/admission/requests/{}", id)).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("HTTP {} - {}", status, response.text().await?))
+    }
+
+    /// Lists the refused join attempts of denied nodes, most recent first.
+    pub async fn list_denied_joins(&self) -> Result<Vec<DeniedJoinAttempts>> {
+        let response = self.get("/api/v1/admin/cluster/admission/denied").await?;
+        self.handle_response(response).await
+    }
+
+    /// Resource usage of the node's process, from its health report.
+    pub async fn get_resource_usage(&self) -> Result<ResourceUsage> {
+        let response = self.get("/health").await?;
+        let mut health: serde_json::Value = self.handle_response(response).await?;
+        let resources = health.get_mut("resources")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| anyhow::anyhow!("Server does not report resource usage"))?;
+        Ok(serde_json::from_value(resources)?)
+    }
+
+    /// The node's metric history at `resolution` (`minute` or `hour`),
+    /// oldest first, from `since` if given.
+    pub async fn get_metrics_history(
+        &self,
+        resolution: &str,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<MetricPoint>> {
+        let url = format!("{}/api/v1/admin/metrics/history", self.base_url);
+        debug!("GET metrics history: {} (resolution: {})", url, resolution);
+
+        let mut request = self.client.get(&url).query(&[("resolution", resolution)]);
+        if let Some(since) = since {
+            request = request.query(&[("since", since.to_rfc3339())]);
+        }
+        let response = request.timeout(self.timeout).send().await?;
+        let history: MetricsHistoryResponse = self.handle_response(response).await?;
+        Ok(history.points)
+    }
+
+    /// Lists queries and background jobs in progress, longest running first.
+    pub async fn list_operations(&self) -> Result<Vec<RunningOperation>> {
+        let response = self.get("/api/v1/admin/operations").await?;
+        self.handle_response(response).await
+    }
+
+    /// Kills a running query or background job; it stops at its next
+    /// document or checkpoint.
+    pub async fn kill_operation(&self, id: &str) -> Result<RunningOperation> {
+        let response = self.delete(&format!("/api/v1/admin/operations/{}", id)).await?;
+        self.handle_response(response).await
+    }
+
+    /// Restores the latest backup taken at or before `at`.
+    pub async fn restore_backup_at(&self, at: chrono::DateTime<chrono::Utc>) -> Result<RestoreReport> {
+        let url = format!("{}/api/v1/admin/restore", self.base_url);
+        debug!("POST restore: {} (at: {})", url, at);
+
+        let response = self.client
+            .post(&url)
+            .query(&[("at", at.to_rfc3339())])
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        self.handle_response(response).await
+    }
+
     /// Handles HTTP response parsing and error conversion.
     ///
     /// ## Response Processing Pipeline
@@ -1117,6 +2040,79 @@ mod tests {
         assert_eq!(client.timeout, custom_timeout);
     }
 
+    /// Tests that lock outcomes parse from the server's tagged JSON.
+    #[test]
+    fn test_lock_outcome_parsing() {
+        let json = serde_json::json!({
+            "status": "held",
+            "lease": {
+                "name": "scheduler-leader",
+                "holder": "node-b",
+                "fencing_token": 7,
+                "ttl_ms": 15000,
+                "acquired_at": "2024-01-01T00:00:00Z",
+                "expires_at": "2024-01-01T00:00:15Z"
+            }
+        });
+
+        match serde_json::from_value::<LockOutcome>(json).unwrap() {
+            LockOutcome::Held { lease } => {
+                assert_eq!(lease.holder, "node-b");
+                assert_eq!(lease.fencing_token, 7);
+            }
+            other => panic!("Unexpected outcome: {:?}", other),
+        }
+    }
+
+    /// Tests that backup manifests parse from the server's JSON.
+    #[test]
+    fn test_backup_manifest_parsing() {
+        let json = serde_json::json!({
+            "id": "20240101T000000000Z-1a2b3c4d",
+            "kind": "incremental",
+            "parent": "20231231T000000000Z-5e6f7a8b",
+            "created_at": "2024-01-01T00:00:00Z",
+            "format_version": 1,
+            "documents": 12,
+            "total_documents": 340,
+            "deleted": 2,
+            "unreadable": [],
+            "size": 48213,
+            "checksum": "af1349b9"
+        });
+
+        let manifest: BackupManifest = serde_json::from_value(json).unwrap();
+        assert_eq!(manifest.kind, "incremental");
+        assert_eq!(manifest.parent.as_deref(), Some("20231231T000000000Z-5e6f7a8b"));
+        assert_eq!((manifest.documents, manifest.total_documents), (12, 340));
+    }
+
+    /// Tests that background jobs parse from the server's JSON.
+    #[test]
+    fn test_job_parsing() {
+        let json = serde_json::json!({
+            "id": "5b0c2f0e-8f5d-4c1e-9a55-0e7f3f6f2a10",
+            "kind": "backup",
+            "tenant": null,
+            "state": "running",
+            "progress": { "completed": 250, "total": 1000 },
+            "cancel_requested": false,
+            "created_at": "2024-01-01T00:00:00Z",
+            "started_at": "2024-01-01T00:00:01Z",
+            "finished_at": null,
+            "error": null,
+            "result": null,
+            "logs": [{ "at": "2024-01-01T00:00:01Z", "message": "Taking a full backup of all collections" }]
+        });
+
+        let job: Job = serde_json::from_value(json).unwrap();
+        assert_eq!(job.kind, "backup");
+        assert!(!job.is_finished());
+        assert_eq!(job.percent(), Some(25.0));
+        assert_eq!(job.progress_label(), "25%");
+        assert_eq!(job.logs.len(), 1);
+    }
+
     /// Tests that URL configuration is properly preserved.
     #[tokio::test]
     async fn test_url_configuration() {
@@ -628,13 +628,16 @@ fn generate_env_format(config: &Value) -> Result<String> {
     Ok(env_vars.join("\n"))
 }
 
-/// Retrieves server configuration.
+/// Retrieves the server's effective configuration.
+///
+/// The server redacts secrets before responding, so `--show-secrets` cannot
+/// reveal them; only the configuration tree is returned, without provenance.
 async fn retrieve_server_config(client: &aerolithsClient) -> Result<Value> {
-    let response = client.get("/api/v1/config").await?;
+    let response = client.get("/api/v1/admin/config/effective").await?;
     
     if response.status().is_success() {
-        let config: Value = response.json().await?;
-        Ok(config)
+        let mut export: Value = response.json().await?;
+        Ok(export.get_mut("config").map(Value::take).unwrap_or(export))
     } else {
         Err(anyhow!("Failed to retrieve server configuration: {}", response.status()))
     }
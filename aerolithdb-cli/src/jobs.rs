@@ -0,0 +1,111 @@
+//! Background job commands for the AerolithDB CLI
+//!
+//! Lists the server's background jobs, such as backups, restores and
+//! scrubs started with `--background`, shows the progress and log of one
+//! job, and cancels queued or running jobs. A running job stops the next
+//! time it checks for cancellation.
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use crate::client::{aerolithsClient, Job};
+
+#[derive(Debug, Args)]
+pub struct JobsArgs {
+    #[command(subcommand)]
+    pub command: JobsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum JobsCommand {
+    /// List jobs, newest first
+    List {
+        /// Only jobs in this state: queued, running, succeeded, failed or cancelled
+        #[arg(long)]
+        state: Option<String>,
+
+        /// Only jobs run for this tenant
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+
+    /// Show the progress, log and outcome of a job
+    Show {
+        /// Job ID
+        id: String,
+    },
+
+    /// Cancel a queued or running job
+    Cancel {
+        /// Job ID
+        id: String,
+    },
+}
+
+pub async fn handle_jobs_command(client: &aerolithsClient, args: JobsArgs) -> Result<()> {
+    match args.command {
+        JobsCommand::List { state, tenant } => {
+            let jobs = client.list_jobs(state.as_deref(), tenant.as_deref()).await?;
+            if jobs.is_empty() {
+                println!("No jobs");
+                return Ok(());
+            }
+            println!("{:<38} {:<10} {:<10} {:>9} {:<25} {}", "ID", "KIND", "STATE", "PROGRESS", "CREATED", "TENANT");
+            for job in &jobs {
+                print_job_row(job);
+            }
+        }
+
+        JobsCommand::Show { id } => {
+            let job = client.get_job(&id).await?;
+            println!("⚙️  Job {} ({})", job.id, job.kind);
+            println!("   State:    {}{}", job.state, if job.cancel_requested && !job.is_finished() { " (cancelling)" } else { "" });
+            println!("   Progress: {}", job.progress_label());
+            if let Some(tenant) = &job.tenant {
+                println!("   Tenant:   {}", tenant);
+            }
+            println!("   Created:  {}", job.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            if let Some(started_at) = job.started_at {
+                println!("   Started:  {}", started_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            }
+            if let Some(finished_at) = job.finished_at {
+                println!("   Finished: {}", finished_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            }
+            if let Some(error) = &job.error {
+                println!("❌ {}", error);
+            }
+            if !job.logs.is_empty() {
+                println!("📜 Log:");
+                for entry in &job.logs {
+                    println!("   {} {}", entry.at.format("%H:%M:%S"), entry.message);
+                }
+            }
+            if let Some(result) = &job.result {
+                println!("📦 Result:");
+                println!("{}", serde_json::to_string_pretty(result)?);
+            }
+        }
+
+        JobsCommand::Cancel { id } => {
+            let job = client.cancel_job(&id).await?;
+            if job.is_finished() {
+                println!("🛑 Cancelled job {}", job.id);
+            } else {
+                println!("🛑 Asked job {} to stop; it ends at its next checkpoint", job.id);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_job_row(job: &Job) {
+    println!(
+        "{:<38} {:<10} {:<10} {:>9} {:<25} {}",
+        job.id,
+        job.kind,
+        job.state,
+        job.progress_label(),
+        job.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        job.tenant.as_deref().unwrap_or("-")
+    );
+}
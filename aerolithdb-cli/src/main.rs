@@ -36,6 +36,8 @@
 //! - `network`: Network administration
 //! - `status`: System monitoring and metrics
 //! - `config`: Configuration management
+//! - `backup`: Backups and point-in-time restores
+//! - `jobs`: Background jobs, their progress and cancellation
 //!
 //! ## Usage Examples
 //!
@@ -80,6 +82,11 @@ mod utils;
 // mod wallet;  // Temporarily disabled
 mod crypto_wallet;
 mod saas;
+mod backup;
+mod jobs;
+mod schema;
+mod certs;
+mod admission;
 mod tui;
 mod tui_test;
 
@@ -87,6 +94,11 @@ use client::aerolithsClient;
 use commands::*;
 use crypto_wallet::{WalletArgs, handle_wallet_command};
 use saas::{SaaSArgs, handle_saas_command};
+use backup::{BackupArgs, handle_backup_command};
+use jobs::{JobsArgs, handle_jobs_command};
+use schema::{SchemaArgs, handle_schema_command};
+use certs::{CertsArgs, handle_certs_command};
+use admission::{AdmissionArgs, handle_admission_command};
 
 /// aerolithsDB CLI - Command line client for aerolithsDB distributed database.
 ///
@@ -350,6 +362,39 @@ enum Commands {
     /// for AerolithDB SaaS/DBaaS deployments. Includes tenant creation,
     /// billing management, usage tracking, and system administration.
     Saas(SaaSArgs),
+
+    /// Backup and restore operations.
+    /// 
+    /// Create full and incremental backups of the server's documents, list
+    /// and delete them, and restore a backup by ID or the latest one taken
+    /// at or before a point in time.
+    Backup(BackupArgs),
+
+    /// Background job operations.
+    ///
+    /// List the server's background jobs, such as backups and restores,
+    /// follow their progress and log, and cancel them.
+    Jobs(JobsArgs),
+
+    /// Collection schema operations.
+    ///
+    /// Set the JSON schema writes to a collection are validated against,
+    /// inspect its versions, and choose whether invalid documents are
+    /// rejected, logged or let through.
+    Schema(SchemaArgs),
+
+    /// Cluster certificate operations.
+    ///
+    /// Show the cluster CA, list the nodes holding a certificate from it,
+    /// and approve or reject the certificate requests of joining nodes.
+    Certs(CertsArgs),
+
+    /// Cluster admission control.
+    ///
+    /// Manage the allowlist and denylist of node ids and public keys,
+    /// approve or reject nodes waiting to join, and review refused join
+    /// attempts from denied nodes.
+    Admission(AdmissionArgs),
 }
 
 /// Main CLI entry point with comprehensive error handling and logging setup.
@@ -505,6 +550,31 @@ async fn main() -> Result<()> {
         Commands::Saas(args) => {
             handle_saas_command(&client, args).await?;
         }
+
+        // Backup and restore commands
+        Commands::Backup(args) => {
+            handle_backup_command(&client, args).await?;
+        }
+
+        // Background job commands
+        Commands::Jobs(args) => {
+            handle_jobs_command(&client, args).await?;
+        }
+
+        // Collection schema commands
+        Commands::Schema(args) => {
+            handle_schema_command(&client, args).await?;
+        }
+
+        // Cluster certificate commands
+        Commands::Certs(args) => {
+            handle_certs_command(&client, args).await?;
+        }
+
+        // Cluster admission commands
+        Commands::Admission(args) => {
+            handle_admission_command(&client, args).await?;
+        }
     }
       Ok(())
 }
@@ -1,16 +1,17 @@
 //! # Query Operations
 //!
 //! This module implements CLI commands for querying and listing documents:
-//! - QUERY: Complex filtering, sorting, and aggregation
+//! - QUERY: Complex filtering and sorting
+//! - QUERY AGGREGATE: Aggregation pipelines that group and summarize documents
 //! - LIST: Simple document enumeration with pagination
 
 use anyhow::Result;
 use serde_json::Value;
 use tracing::{error, info, warn};
 
-use crate::client::aerolithsClient;
-use crate::args::{QueryArgs, ListArgs};
-use crate::utils::parse_json_input;
+use crate::client::{aerolithsClient, QueryResponse};
+use crate::args::{AggregateArgs, ListArgs, QueryArgs, QueryCommand};
+use crate::utils::{parse_json_input, parse_projection, parse_sort_spec, with_text_search};
 
 /// Executes the QUERY command to search documents with filtering and sorting.
 ///
@@ -55,7 +56,12 @@ use crate::utils::parse_json_input;
 ///
 /// * `Result<()>` - Success indication or detailed error information
 pub async fn execute_query(client: &aerolithsClient, args: &QueryArgs) -> Result<()> {
-    info!("Querying collection {} with filters", args.collection);
+    if let Some(QueryCommand::Aggregate(aggregate)) = &args.command {
+        return execute_aggregate(client, aggregate).await;
+    }
+    let collection = args.collection.as_deref()
+        .ok_or_else(|| anyhow::anyhow!("A collection to query is required"))?;
+    info!("Querying collection {} with filters", collection);
 
     // Parse and validate filter expression
     let filter = if let Some(f) = &args.filter {
@@ -66,30 +72,43 @@ pub async fn execute_query(client: &aerolithsClient, args: &QueryArgs) -> Result
     } else {
         None
     };
+    let filter = match &args.text {
+        Some(terms) => Some(with_text_search(filter, terms)),
+        None => filter,
+    };
 
     // Parse and validate sort expression
     let sort = if let Some(s) = &args.sort {
-        Some(parse_json_input(s).map_err(|e| {
-            anyhow::anyhow!("Invalid sort JSON: {}. \
-                            Example: '{{\"name\": 1, \"age\": -1}}'", e)
+        Some(parse_sort_spec(s).map_err(|e| {
+            anyhow::anyhow!("Invalid sort: {}. \
+                            Example: 'name:asc,age:desc'", e)
         })?)
     } else {
         None
     };
 
+    // Fields to return, when not whole documents
+    let projection = match (&args.fields, &args.exclude) {
+        (Some(fields), _) => Some(parse_projection(fields, false)?),
+        (None, Some(exclude)) => Some(parse_projection(exclude, true)?),
+        (None, None) => None,
+    };
+
     // Build complete query object
     let query = serde_json::json!({
         "filter": filter,
         "limit": args.limit,
         "offset": args.offset,
         "sort": sort,
+        "cursor": args.cursor,
+        "projection": projection,
         "include_metadata": args.include_metadata,
         "explain": args.explain
     });
 
     let start_time = std::time::Instant::now();
     
-    match client.query_documents(&args.collection, &query).await {
+    match client.query_documents(collection, &query).await {
         Ok(response) => {
             let execution_time = start_time.elapsed();
             info!("Query completed successfully in {:?}", execution_time);
@@ -111,7 +130,7 @@ pub async fn execute_query(client: &aerolithsClient, args: &QueryArgs) -> Result
                     println!("ID,Collection,Version,Data");
                     for doc in &response.documents {
                         let data_str = serde_json::to_string(&doc.data)?;
-                        println!("{},{},{},{}", doc.id, args.collection, doc.version, data_str);
+                        println!("{},{},{},{}", doc.id, collection, doc.version, data_str);
                     }
                 }
                 "count" => {
@@ -157,7 +176,10 @@ pub async fn execute_query(client: &aerolithsClient, args: &QueryArgs) -> Result
                     }
                     
                     // Provide pagination guidance for large result sets
-                    if response.total > response.documents.len() {
+                    if let Some(cursor) = &response.next_cursor {
+                        println!();
+                        println!("💡 Next page: --cursor {}", cursor);
+                    } else if response.total > response.documents.len() && args.cursor.is_none() {
                         println!();
                         println!("💡 Use --offset and --limit for pagination through remaining results");
                     }                    // Show query explanation if requested
@@ -202,6 +224,60 @@ pub async fn execute_query(client: &aerolithsClient, args: &QueryArgs) -> Result
     Ok(())
 }
 
+/// Executes the QUERY AGGREGATE command, running a pipeline of `$match`,
+/// `$group`, `$sort`, `$limit` and `$project` stages on the server and
+/// printing the documents the last stage produces.
+pub async fn execute_aggregate(client: &aerolithsClient, args: &AggregateArgs) -> Result<()> {
+    info!("Aggregating collection {}", args.collection);
+
+    let pipeline = parse_json_input(&args.pipeline).map_err(|e| {
+        anyhow::anyhow!("Invalid pipeline JSON: {}. \
+                        Example: '[{{\"$group\": {{\"_id\": \"$status\", \"n\": {{\"$count\": {{}}}}}}}}]'", e)
+    })?;
+    if !pipeline.is_array() {
+        anyhow::bail!("Invalid pipeline: expected a JSON array of stages");
+    }
+
+    let response = match client.aggregate_documents(&args.collection, &pipeline).await {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Aggregation failed: {}", e);
+            eprintln!("✗ Aggregation failed: {}", e);
+            if e.to_string().contains("Invalid pipeline") {
+                eprintln!("  → Check stage names and accumulator syntax");
+            }
+            return Err(e);
+        }
+    };
+
+    match args.format.as_str() {
+        "json" => {
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        "jsonl" => {
+            for doc in &response.documents {
+                println!("{}", serde_json::to_string(doc)?);
+            }
+        }
+        "table" => {
+            println!("📊 Aggregation Results:");
+            println!("  Results: {}", response.count);
+            println!("  Scanned: {} documents", response.scanned);
+            println!("  Execution time: {}ms", response.execution_time_ms);
+            println!();
+            for doc in &response.documents {
+                println!("{}", serde_json::to_string(doc)?);
+            }
+        }
+        _ => {
+            warn!("Unknown format '{}', using JSON", args.format);
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+    }
+
+    Ok(())
+}
+
 /// Executes the LIST command to enumerate documents in a collection.
 ///
 /// ## List Operation Characteristics
@@ -249,8 +325,11 @@ pub async fn execute_list(client: &aerolithsClient, args: &ListArgs) -> Result<(
         info!("Listing documents in collection {}", collection);
         
         // List documents in specific collection
-        match client.list_documents(collection, None, None).await {
-            Ok(documents) => {
+        let page = client
+            .list_documents_page(collection, args.limit, args.sort.as_deref(), args.desc, args.cursor.as_deref())
+            .await;
+        match page {
+            Ok(QueryResponse { documents, next_cursor, .. }) => {
                 info!("Retrieved {} documents from collection", documents.len());
                 
                 // Format output according to user preference
@@ -291,16 +370,26 @@ pub async fn execute_list(client: &aerolithsClient, args: &ListArgs) -> Result<(
                                    doc.updated_at);
                         }
                         
+                        if let Some(cursor) = &next_cursor {
+                            println!();
+                            println!("💡 Next page: --cursor {}", cursor);
+                        }
                         if args.detailed {
                             println!();
                             println!("💡 Use 'query' command for filtering and advanced pagination");
                         }
+                        return Ok(());
                     }
                     _ => {
                         warn!("Unknown format '{}', using JSON", args.format);
                         println!("{}", serde_json::to_string_pretty(&documents)?);
                     }
                 }
+
+                // Keep machine-readable output on stdout intact
+                if let Some(cursor) = next_cursor {
+                    eprintln!("Next page: --cursor {}", cursor);
+                }
             }
             Err(e) => {
                 error!("Failed to list documents: {}", e);
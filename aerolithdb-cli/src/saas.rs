@@ -164,6 +164,32 @@ pub enum BillingCommand {
         #[arg(long)]
         end_date: String,
     },
+    
+    /// Estimate charges under each pricing tier and recommend one
+    Estimate {
+        /// Tenant ID whose recent usage is simulated
+        tenant_id: Option<String>,
+        
+        /// Days of recent usage to simulate
+        #[arg(long, default_value = "30")]
+        lookback_days: u32,
+        
+        /// Simulate this monthly storage in GB instead of a tenant's usage
+        #[arg(long)]
+        storage_gb: Option<f64>,
+        
+        /// Simulated API calls per month
+        #[arg(long, default_value = "0")]
+        api_calls: u64,
+        
+        /// Simulated CPU hours per month
+        #[arg(long, default_value = "0")]
+        compute_hours: f64,
+        
+        /// Simulated network transfer in GB per month
+        #[arg(long, default_value = "0")]
+        network_gb: f64,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -458,6 +484,52 @@ async fn handle_billing_command(client: &aerolithsClient, cmd: BillingCommand) -
             println!("🧮 Billing calculation:");
             println!("{}", serde_json::to_string_pretty(&response)?);
         },
+        
+        BillingCommand::Estimate { tenant_id, lookback_days, storage_gb, api_calls, compute_hours, network_gb } => {
+            let response = match (storage_gb, tenant_id) {
+                (Some(storage_gb), _) => {
+                    let request = json!({
+                        "profile": {
+                            "storage_gb": storage_gb,
+                            "api_calls": api_calls,
+                            "compute_hours": compute_hours,
+                            "network_gb": network_gb
+                        }
+                    });
+                    client.post_json("/api/v1/saas/billing/estimate", &request).await?
+                },
+                (None, Some(tenant_id)) => {
+                    let query = vec![("lookback_days", lookback_days.to_string())];
+                    client.get_with_query(&format!("/api/v1/saas/billing/tenants/{}/estimate", tenant_id), &query).await?
+                },
+                (None, None) => {
+                    return Err(anyhow::anyhow!("Specify a tenant ID or a usage profile with --storage-gb"));
+                },
+            };
+            
+            println!("🧾 Plan estimates:");
+            for estimate in response["estimates"].as_array().into_iter().flatten() {
+                let exceeded: Vec<&str> = estimate["limits_exceeded"].as_array().into_iter().flatten()
+                    .filter_map(|limit| limit.as_str())
+                    .collect();
+                println!(
+                    "  {:<16} {:>10.2} {}{}",
+                    estimate["tier"].as_str().unwrap_or("?"),
+                    estimate["monthly_cost"].as_f64().unwrap_or(0.0),
+                    response["currency"].as_str().unwrap_or(""),
+                    if exceeded.is_empty() { String::new() } else { format!("  (exceeds {})", exceeded.join(", ")) },
+                );
+            }
+            match response["recommended_tier"].as_str() {
+                Some(tier) => {
+                    println!("✅ Recommended tier: {}", tier);
+                    if let Some(savings) = response["monthly_savings"].as_f64() {
+                        println!("💡 Monthly savings over the current tier: {:.2} {}", savings, response["currency"].as_str().unwrap_or(""));
+                    }
+                },
+                None => println!("⚠️ The usage exceeds the limits of every tier"),
+            }
+        },
     }
     Ok(())
 }
@@ -0,0 +1,118 @@
+//! Collection schema commands for the AerolithDB CLI
+//!
+//! Sets the JSON schema documents written to a collection are validated
+//! against, shows the current schema or a replaced version of it, and
+//! switches enforcement between strict (invalid writes are rejected), warn
+//! (they are stored and logged) and off. Every change of the schema is kept
+//! as a new version.
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+
+use crate::client::{aerolithsClient, CollectionSchema};
+
+#[derive(Debug, Args)]
+pub struct SchemaArgs {
+    #[command(subcommand)]
+    pub command: SchemaCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SchemaCommand {
+    /// Validate writes to a collection against a JSON schema file
+    Set {
+        /// Collection name
+        collection: String,
+
+        /// File holding the JSON schema
+        file: String,
+
+        /// Enforcement: strict, warn or off
+        #[arg(long, default_value = "strict", value_parser = ["strict", "warn", "off"])]
+        mode: String,
+    },
+
+    /// Show the schema of a collection
+    Get {
+        /// Collection name
+        collection: String,
+
+        /// A replaced version instead of the current schema
+        #[arg(long)]
+        version: Option<u32>,
+    },
+
+    /// List every version of a collection's schema
+    Versions {
+        /// Collection name
+        collection: String,
+    },
+
+    /// Change how a collection's schema is enforced
+    Mode {
+        /// Collection name
+        collection: String,
+
+        /// Enforcement: strict, warn or off
+        #[arg(value_parser = ["strict", "warn", "off"])]
+        mode: String,
+    },
+
+    /// Stop validating writes to a collection
+    Remove {
+        /// Collection name
+        collection: String,
+    },
+}
+
+pub async fn handle_schema_command(client: &aerolithsClient, args: SchemaArgs) -> Result<()> {
+    match args.command {
+        SchemaCommand::Set { collection, file, mode } => {
+            let content = tokio::fs::read_to_string(&file).await
+                .with_context(|| format!("Failed to read schema file {}", file))?;
+            let schema: serde_json::Value = serde_json::from_str(&content)
+                .with_context(|| format!("Schema file {} is not valid JSON", file))?;
+            let schema = client.set_schema(&collection, &schema, &mode).await?;
+            println!("📐 Set schema version {} of {} ({})", schema.version, schema.collection, schema.mode);
+        }
+
+        SchemaCommand::Get { collection, version } => {
+            match client.get_schema(&collection, version).await? {
+                Some(schema) => {
+                    print_schema_header(&schema);
+                    println!("{}", serde_json::to_string_pretty(&schema.schema)?);
+                }
+                None => println!("No schema for {}", collection),
+            }
+        }
+
+        SchemaCommand::Versions { collection } => {
+            let versions = client.list_schema_versions(&collection).await?;
+            if versions.is_empty() {
+                println!("No schema for {}", collection);
+                return Ok(());
+            }
+            println!("{:>8} {:<7} CREATED", "VERSION", "MODE");
+            for schema in &versions {
+                println!("{:>8} {:<7} {}", schema.version, schema.mode, schema.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            }
+        }
+
+        SchemaCommand::Mode { collection, mode } => {
+            let schema = client.set_schema_mode(&collection, &mode).await?;
+            println!("📐 Writes to {} are validated in {} mode", schema.collection, schema.mode);
+        }
+
+        SchemaCommand::Remove { collection } => {
+            client.remove_schema(&collection).await?;
+            println!("🗑️  Removed the schema of {}", collection);
+        }
+    }
+    Ok(())
+}
+
+fn print_schema_header(schema: &CollectionSchema) {
+    println!("📐 Schema of {} (version {})", schema.collection, schema.version);
+    println!("   Mode:    {}", schema.mode);
+    println!("   Created: {}", schema.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+}
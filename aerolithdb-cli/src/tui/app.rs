@@ -11,7 +11,7 @@ use tokio::time::interval;
 use anyhow::Result;
 use ratatui::widgets::TableState;
 
-use crate::client::aerolithsClient;
+use crate::client::{aerolithsClient, Job, MetricPoint, ResourceUsage, RunningOperation};
 
 /// Main application state for the TUI
 #[derive(Clone)]
@@ -38,6 +38,10 @@ pub struct App {
     pub configuration: ConfigurationState,
     /// Console state
     pub console: ConsoleState,
+    /// Background jobs state
+    pub jobs: JobsState,
+    /// Running operations state
+    pub operations: OperationsState,
     /// Background task handles
     pub background_tasks: BackgroundTasks,
 }
@@ -53,6 +57,14 @@ pub struct DashboardState {
     pub recent_activity: Vec<ActivityLog>,
     /// Quick stats
     pub quick_stats: QuickStats,
+    /// Resource usage of the node's process
+    pub resources: Option<ResourceUsage>,
+    /// Why the last resource fetch failed
+    pub resources_error: Option<String>,
+    /// Minute metrics of the last hour, oldest first
+    pub trends: Vec<MetricPoint>,
+    /// Why the last metrics history fetch failed
+    pub trends_error: Option<String>,
     /// Last update time
     pub last_updated: Option<Instant>,
 }
@@ -134,6 +146,38 @@ pub struct ConsoleState {
     pub mode: ConsoleMode,
 }
 
+/// Background jobs tab state
+#[derive(Clone, Default)]
+pub struct JobsState {
+    /// Jobs on the server, newest first
+    pub jobs: Vec<Job>,
+    /// Selected job index
+    pub selected_job: Option<usize>,
+    /// Table state for the job list
+    pub table_state: TableState,
+    /// When the job list was last fetched
+    pub last_refreshed: Option<Instant>,
+    /// Why the last fetch failed
+    pub error: Option<String>,
+}
+
+/// Running operations tab state
+#[derive(Clone, Default)]
+pub struct OperationsState {
+    /// Queries and jobs in progress, longest running first
+    pub operations: Vec<RunningOperation>,
+    /// Selected operation index
+    pub selected: Option<usize>,
+    /// Table state for the operation list
+    pub table_state: TableState,
+    /// Operation to kill once the kill key is pressed again
+    pub pending_kill: Option<String>,
+    /// When the operation list was last fetched
+    pub last_refreshed: Option<Instant>,
+    /// Why the last fetch failed
+    pub error: Option<String>,
+}
+
 /// Background task management
 #[derive(Clone)]
 pub struct BackgroundTasks {
@@ -447,7 +491,9 @@ impl Default for App {
                 "Cluster Monitor",
                 "Test Runner",
                 "Configuration",
-                "Console"
+                "Console",
+                "Jobs",
+                "Operations"
             ],
             should_quit: false,
             error_message: None,
@@ -458,6 +504,8 @@ impl Default for App {
             test_runner: TestRunnerState::default(),
             configuration: ConfigurationState::default(),
             console: ConsoleState::default(),
+            jobs: JobsState::default(),
+            operations: OperationsState::default(),
             background_tasks: BackgroundTasks::default(),
         }
     }
@@ -470,6 +518,10 @@ impl Default for DashboardState {
             node_overview: Vec::new(),
             recent_activity: Vec::new(),
             quick_stats: QuickStats::default(),
+            resources: None,
+            resources_error: None,
+            trends: Vec::new(),
+            trends_error: None,
             last_updated: None,
         }
     }
@@ -725,6 +777,89 @@ impl App {
         self.tabs[self.current_tab]
     }
 
+    /// Fetch the server's background jobs, keeping the selected job selected
+    pub async fn refresh_jobs(&mut self, client: &aerolithsClient) {
+        let state = &mut self.jobs;
+        state.last_refreshed = Some(Instant::now());
+        match client.list_jobs(None, None).await {
+            Ok(jobs) => {
+                let selected_id = state.selected_job.and_then(|index| state.jobs.get(index)).map(|job| job.id.clone());
+                let selected = match selected_id {
+                    Some(id) => jobs.iter().position(|job| job.id == id).or(Some(0)),
+                    None => Some(0),
+                }
+                .filter(|_| !jobs.is_empty());
+                state.jobs = jobs;
+                state.selected_job = selected;
+                state.table_state.select(selected);
+                state.error = None;
+            }
+            Err(e) => state.error = Some(e.to_string()),
+        }
+    }
+
+    /// Fetch the running operations, keeping the selected one selected
+    pub async fn refresh_operations(&mut self, client: &aerolithsClient) {
+        let state = &mut self.operations;
+        state.last_refreshed = Some(Instant::now());
+        match client.list_operations().await {
+            Ok(operations) => {
+                let selected_id = state.selected.and_then(|index| state.operations.get(index)).map(|op| op.id.clone());
+                let selected = match selected_id {
+                    Some(id) => operations.iter().position(|op| op.id == id).or(Some(0)),
+                    None => Some(0),
+                }
+                .filter(|_| !operations.is_empty());
+                state.operations = operations;
+                state.selected = selected;
+                state.table_state.select(selected);
+                state.error = None;
+            }
+            Err(e) => state.error = Some(e.to_string()),
+        }
+    }
+
+    /// Selected running operation, if any
+    pub fn selected_operation(&self) -> Option<&RunningOperation> {
+        self.operations.selected.and_then(|index| self.operations.operations.get(index))
+    }
+
+    /// Fetch the node's resource usage and show it in the dashboard gauges
+    pub async fn refresh_resources(&mut self, client: &aerolithsClient) {
+        let state = &mut self.dashboard;
+        state.last_updated = Some(Instant::now());
+        match client.get_resource_usage().await {
+            Ok(resources) => {
+                // Gauges show CPU across all cores, and the fullest data disk
+                let metrics = &mut state.system_metrics;
+                metrics.cpu_usage = (resources.cpu_percent as f64 / resources.cpu_count.max(1) as f64).min(100.0);
+                metrics.memory_usage = if resources.total_memory_bytes > 0 {
+                    resources.resident_memory_bytes as f64 * 100.0 / resources.total_memory_bytes as f64
+                } else {
+                    0.0
+                };
+                metrics.disk_usage = resources.disks.iter().map(|disk| disk.used_percent).fold(0.0, f64::max);
+                state.resources = Some(resources);
+                state.resources_error = None;
+            }
+            Err(e) => state.resources_error = Some(e.to_string()),
+        }
+
+        let since = chrono::Utc::now() - chrono::Duration::hours(1);
+        match client.get_metrics_history("minute", Some(since)).await {
+            Ok(points) => {
+                state.trends = points;
+                state.trends_error = None;
+            }
+            Err(e) => state.trends_error = Some(e.to_string()),
+        }
+    }
+
+    /// Selected background job, if any
+    pub fn selected_job(&self) -> Option<&Job> {
+        self.jobs.selected_job.and_then(|index| self.jobs.jobs.get(index))
+    }
+
     /// Start background tasks
     pub async fn start_background_tasks(&mut self, client: Arc<aerolithsClient>) -> Result<()> {
         // Create channels for background task communication
@@ -47,6 +47,8 @@ pub async fn handle_key_event(app: &mut App, key: KeyEvent, client: Arc<aerolith
                 3 => handle_test_runner_events(app, key, client).await?,
                 4 => handle_configuration_events(app, key, client).await?,
                 5 => handle_console_events(app, key, client).await?,
+                6 => handle_jobs_events(app, key, client).await?,
+                7 => handle_operations_events(app, key, client).await?,
                 _ => {},
             }
         }
@@ -56,11 +58,11 @@ pub async fn handle_key_event(app: &mut App, key: KeyEvent, client: Arc<aerolith
 }
 
 /// Handle dashboard tab events
-async fn handle_dashboard_events(app: &mut App, key: KeyEvent, _client: Arc<aerolithsClient>) -> Result<()> {
+async fn handle_dashboard_events(app: &mut App, key: KeyEvent, client: Arc<aerolithsClient>) -> Result<()> {
     match key.code {
         KeyCode::Char('r') => {
-            app.set_status("Refreshing dashboard data...".to_string());
-            // Trigger dashboard refresh
+            app.refresh_resources(&client).await;
+            app.set_status("Dashboard refreshed".to_string());
         },
         KeyCode::Char('c') => {
             app.dashboard.recent_activity.clear();
@@ -277,6 +279,59 @@ async fn handle_console_events(app: &mut App, key: KeyEvent, client: Arc<aerolit
     Ok(())
 }
 
+/// Handle background jobs tab events
+async fn handle_jobs_events(app: &mut App, key: KeyEvent, client: Arc<aerolithsClient>) -> Result<()> {
+    let count = app.jobs.jobs.len();
+    match key.code {
+        KeyCode::Up if count > 0 => {
+            let selected = app.jobs.selected_job.map_or(0, |selected| selected.saturating_sub(1));
+            app.jobs.selected_job = Some(selected);
+            app.jobs.table_state.select(Some(selected));
+        },
+        KeyCode::Down if count > 0 => {
+            let selected = app.jobs.selected_job.map_or(0, |selected| (selected + 1).min(count - 1));
+            app.jobs.selected_job = Some(selected);
+            app.jobs.table_state.select(Some(selected));
+        },
+        KeyCode::Char('r') | KeyCode::Char('R') => {
+            app.refresh_jobs(&client).await;
+            app.set_status("Job list refreshed".to_string());
+        },
+        KeyCode::Char('c') | KeyCode::Char('C') => {
+            cancel_selected_job(app, client).await;
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
+/// Handle running operations tab events
+async fn handle_operations_events(app: &mut App, key: KeyEvent, client: Arc<aerolithsClient>) -> Result<()> {
+    let count = app.operations.operations.len();
+    let pending_kill = app.operations.pending_kill.take();
+    match key.code {
+        KeyCode::Up if count > 0 => {
+            let selected = app.operations.selected.map_or(0, |selected| selected.saturating_sub(1));
+            app.operations.selected = Some(selected);
+            app.operations.table_state.select(Some(selected));
+        },
+        KeyCode::Down if count > 0 => {
+            let selected = app.operations.selected.map_or(0, |selected| (selected + 1).min(count - 1));
+            app.operations.selected = Some(selected);
+            app.operations.table_state.select(Some(selected));
+        },
+        KeyCode::Char('r') | KeyCode::Char('R') => {
+            app.refresh_operations(&client).await;
+            app.set_status("Operation list refreshed".to_string());
+        },
+        KeyCode::Char('k') | KeyCode::Char('K') => {
+            kill_selected_operation(app, client, pending_kill).await;
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
 /// Show help information
 fn show_help(app: &mut App) {
     let help_text = match app.current_tab {
@@ -286,6 +341,8 @@ fn show_help(app: &mut App) {
         3 => "Test Runner Help:\n[↑↓] Navigate test suites\n[Enter/R] Run selected suite\n[S] Stop execution\n[C] Clear output\n[A] Run all suites",
         4 => "Configuration Help:\n[↑↓] Navigate sections\n[Enter] Edit section\n[V] Validate\n[S] Save\n[L] Load\n[R] Reset",
         5 => "Console Help:\n[Enter] Execute command\n[↑↓] Command history\n[Ctrl+C] Clear input\n[Ctrl+L] Clear output",
+        6 => "Jobs Help:\n[↑↓] Navigate jobs\n[R] Refresh\n[C] Cancel selected job",
+        7 => "Operations Help:\n[↑↓] Navigate operations\n[R] Refresh\n[K] Kill selected operation (press twice)",
         _ => "Global Help:\n[Tab] Next tab\n[Shift+Tab] Previous tab\n[F1/H] Help\n[F5] Refresh\n[Esc] Clear messages\n[Ctrl+Q] Quit",
     };
 
@@ -296,9 +353,8 @@ fn show_help(app: &mut App) {
 async fn refresh_data(app: &mut App, client: Arc<aerolithsClient>) -> Result<()> {
     match app.current_tab {
         0 => {
-            // Refresh dashboard data
-            app.set_status("Refreshing dashboard...".to_string());
-            // Implementation would fetch fresh metrics, node status, etc.
+            app.refresh_resources(&client).await;
+            app.set_status("Dashboard refreshed".to_string());
         },
         1 => {
             // Refresh node list
@@ -324,11 +380,60 @@ async fn refresh_data(app: &mut App, client: Arc<aerolithsClient>) -> Result<()>
             // No specific refresh for console
             app.set_status("Console is up to date".to_string());
         },
+        6 => {
+            app.refresh_jobs(&client).await;
+            app.set_status("Job list refreshed".to_string());
+        },
+        7 => {
+            app.refresh_operations(&client).await;
+            app.set_status("Operation list refreshed".to_string());
+        },
         _ => {},
     }
     Ok(())
 }
 
+// Background job functions
+
+async fn cancel_selected_job(app: &mut App, client: Arc<aerolithsClient>) {
+    let Some(job) = app.selected_job().cloned() else {
+        app.set_status("No job selected".to_string());
+        return;
+    };
+    if job.is_finished() {
+        app.set_status(format!("Job {} already finished", job.id));
+        return;
+    }
+
+    let id = job.id.clone();
+    match client.cancel_job(&id).await {
+        Ok(job) if job.is_finished() => app.set_status(format!("Cancelled job {}", job.id)),
+        Ok(job) => app.set_status(format!("Asked job {} to stop", job.id)),
+        Err(e) => app.set_error(format!("Failed to cancel job {}: {}", id, e)),
+    }
+    app.refresh_jobs(&client).await;
+}
+
+/// Kill the selected operation once the kill key was pressed twice for it
+async fn kill_selected_operation(app: &mut App, client: Arc<aerolithsClient>, pending_kill: Option<String>) {
+    let Some(operation) = app.selected_operation().cloned() else {
+        app.set_status("No operation selected".to_string());
+        return;
+    };
+    let label = format!("{} {} on {}", operation.kind, operation.id.chars().take(8).collect::<String>(), operation.target());
+    if pending_kill.as_deref() != Some(operation.id.as_str()) {
+        app.operations.pending_kill = Some(operation.id.clone());
+        app.set_status(format!("Press K again to kill {}", label));
+        return;
+    }
+
+    match client.kill_operation(&operation.id).await {
+        Ok(_) => app.set_status(format!("Killed {}", label)),
+        Err(e) => app.set_error(format!("Failed to kill {}: {}", label, e)),
+    }
+    app.refresh_operations(&client).await;
+}
+
 // Node management functions
 
 async fn start_selected_node(app: &mut App, client: Arc<aerolithsClient>) -> Result<()> {
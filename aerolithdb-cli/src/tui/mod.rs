@@ -13,6 +13,7 @@
 //! - **Test Runner**: Integrated test suite execution with progress tracking
 //! - **Configuration**: Live configuration editing with validation and hot-reload
 //! - **Console**: Interactive command execution with history and auto-completion
+//! - **Jobs**: Background jobs of the server with progress, logs, and cancellation
 //!
 //! ## Architecture
 //!
@@ -64,6 +65,15 @@ use app::App;
 /// Default tick rate for the TUI event loop (60 FPS)
 const TICK_RATE: Duration = Duration::from_millis(16);
 
+/// How often the job list is fetched while the Jobs tab is shown
+const JOBS_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often running operations are fetched while the Operations tab is shown
+const OPERATIONS_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the dashboard fetches the node's resource usage while shown
+const RESOURCES_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
 /// TUI application runner
 pub struct TuiApp {
     /// Main application state
@@ -148,7 +158,24 @@ impl TuiApp {
         // Update any time-sensitive UI elements
         // Handle background task results
         // Trigger periodic data refreshes
-        
+        if self.app.current_tab == 0
+            && self.app.dashboard.last_updated.is_none_or(|at| at.elapsed() >= RESOURCES_REFRESH_INTERVAL)
+        {
+            self.app.refresh_resources(&self.client).await;
+        }
+
+        if self.app.current_tab == 6
+            && self.app.jobs.last_refreshed.is_none_or(|at| at.elapsed() >= JOBS_REFRESH_INTERVAL)
+        {
+            self.app.refresh_jobs(&self.client).await;
+        }
+
+        if self.app.current_tab == 7
+            && self.app.operations.last_refreshed.is_none_or(|at| at.elapsed() >= OPERATIONS_REFRESH_INTERVAL)
+        {
+            self.app.refresh_operations(&self.client).await;
+        }
+
         // Clear status messages after a timeout
         if let Some(_) = &self.app.status_message {
             // In a real implementation, you'd track when the message was set
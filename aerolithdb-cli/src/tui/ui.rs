@@ -10,7 +10,7 @@ use ratatui::{
     symbols::DOT,
     text::{Line, Span, Text},
     widgets::{
-        Block, Borders, Cell, Clear, Gauge, List, ListItem, Paragraph, Row, Table, Tabs,
+        Block, Borders, Cell, Clear, Gauge, List, ListItem, Paragraph, Row, Sparkline, Table, Tabs,
         Wrap, canvas::{Canvas, Map, MapResolution, Rectangle},
     },
     Frame,
@@ -18,6 +18,7 @@ use ratatui::{
 use std::time::{Duration, Instant};
 
 use super::app::{App, AlertLevel, NodeState, TestResultStatus, TestExecutionStatus, ConsoleMode};
+use crate::utils::format_bytes;
 
 /// Render the complete TUI interface
 pub fn render(f: &mut Frame, app: &App) {
@@ -41,6 +42,8 @@ pub fn render(f: &mut Frame, app: &App) {
         3 => render_test_runner(f, app, chunks[1]),
         4 => render_configuration(f, app, chunks[1]),
         5 => render_console(f, app, chunks[1]),
+        6 => render_jobs(f, app, chunks[1]),
+        7 => render_operations(f, app, chunks[1]),
         _ => render_dashboard(f, app, chunks[1]),
     }
 
@@ -114,6 +117,8 @@ fn render_dashboard(f: &mut Frame, app: &App, area: Rect) {
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(8),  // System metrics
+            Constraint::Length(6),  // Node resources
+            Constraint::Length(5),  // Trends
             Constraint::Length(8),  // Quick stats
             Constraint::Min(0),     // Activity log
         ])
@@ -122,11 +127,17 @@ fn render_dashboard(f: &mut Frame, app: &App, area: Rect) {
     // System metrics section
     render_system_metrics(f, &app.dashboard, chunks[0]);
 
+    // Node resources section
+    render_node_resources(f, &app.dashboard, chunks[1]);
+
+    // Trends section
+    render_trends(f, &app.dashboard, chunks[2]);
+
     // Quick stats section
-    render_quick_stats(f, &app.dashboard, chunks[1]);
+    render_quick_stats(f, &app.dashboard, chunks[3]);
 
     // Activity log section
-    render_activity_log(f, &app.dashboard, chunks[2]);
+    render_activity_log(f, &app.dashboard, chunks[4]);
 }
 
 /// Render system metrics
@@ -203,6 +214,117 @@ fn render_system_metrics(f: &mut Frame, dashboard: &super::app::DashboardState,
     f.render_widget(db_stats, chunks[3]);
 }
 
+/// Render the node process's memory, threads, descriptors and data disks
+fn render_node_resources(f: &mut Frame, dashboard: &super::app::DashboardState, area: Rect) {
+    let count = |value: Option<usize>| value.map_or_else(|| "-".to_string(), |value| value.to_string());
+
+    let lines: Vec<Line> = match &dashboard.resources {
+        Some(resources) => {
+            let mut lines = vec![Line::from(format!(
+                "CPU: {:.1}% of {} cores | RSS: {} of {} | Virtual: {} | Threads: {} | Open FDs: {}",
+                resources.cpu_percent,
+                resources.cpu_count,
+                format_bytes(resources.resident_memory_bytes),
+                format_bytes(resources.total_memory_bytes),
+                format_bytes(resources.virtual_memory_bytes),
+                count(resources.threads),
+                count(resources.open_file_descriptors),
+            ))];
+            lines.extend(resources.disks.iter().map(|disk| {
+                let style = if disk.used_percent > 90.0 {
+                    Style::default().fg(Color::Red)
+                } else if disk.used_percent > 75.0 {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                Line::styled(
+                    format!(
+                        "{} ({}): {:.1}% used, {} free of {} - {}",
+                        disk.mount_point,
+                        disk.file_system,
+                        disk.used_percent,
+                        format_bytes(disk.available_bytes),
+                        format_bytes(disk.total_bytes),
+                        disk.paths.join(", "),
+                    ),
+                    style,
+                )
+            }));
+            lines
+        }
+        None => vec![Line::from("Waiting for the node to report its resource usage")],
+    };
+
+    let title = match &dashboard.resources_error {
+        Some(error) => format!("Node Resources (refresh failed: {})", error),
+        None => "Node Resources".to_string(),
+    };
+    let widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(if dashboard.resources_error.is_some() { Color::Red } else { Color::Blue })),
+        )
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(widget, area);
+}
+
+/// Render the last hour of CPU, memory and request history, one bar per minute
+fn render_trends(f: &mut Frame, dashboard: &super::app::DashboardState, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(34),
+            Constraint::Percentage(33),
+            Constraint::Percentage(33),
+        ])
+        .split(area);
+
+    let points = &dashboard.trends;
+    let latest = points.last();
+    let border = if dashboard.trends_error.is_some() { Color::Red } else { Color::Blue };
+    let series: [(String, Vec<u64>, Color); 3] = [
+        (
+            format!("CPU, last hour ({:.1}%)", latest.map_or(0.0, |point| point.cpu_percent.mean)),
+            points.iter().map(|point| point.cpu_percent.mean.round() as u64).collect(),
+            Color::Cyan,
+        ),
+        (
+            format!("Memory ({})", format_bytes(latest.map_or(0.0, |point| point.resident_memory_bytes.mean) as u64)),
+            points.iter().map(|point| point.resident_memory_bytes.mean as u64).collect(),
+            Color::Green,
+        ),
+        (
+            format!("Requests/min ({})", latest.map_or(0, |point| point.requests)),
+            points.iter().map(|point| point.requests).collect(),
+            Color::Magenta,
+        ),
+    ];
+
+    for ((title, data, color), area) in series.into_iter().zip(chunks.iter()) {
+        let title = match &dashboard.trends_error {
+            Some(error) if data.is_empty() => format!("{} - unavailable: {}", title, error),
+            _ => title,
+        };
+        // The newest minutes are kept when the panel is narrower than the hour
+        let width = area.width.saturating_sub(2) as usize;
+        let data = &data[data.len().saturating_sub(width)..];
+        let sparkline = Sparkline::default()
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(border)),
+            )
+            .data(data)
+            .style(Style::default().fg(color));
+        f.render_widget(sparkline, *area);
+    }
+}
+
 /// Render quick stats
 fn render_quick_stats(f: &mut Frame, dashboard: &super::app::DashboardState, area: Rect) {
     let chunks = Layout::default()
@@ -966,6 +1088,277 @@ fn render_console_input(f: &mut Frame, console: &super::app::ConsoleState, area:
     f.render_widget(input_widget, area);
 }
 
+/// Render the background jobs tab
+fn render_jobs(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(60),  // Job list
+            Constraint::Percentage(40),  // Job details and log
+        ])
+        .split(area);
+
+    render_job_list(f, &app.jobs, chunks[0]);
+    render_job_details(f, app.selected_job(), chunks[1]);
+}
+
+/// Render job list
+fn render_job_list(f: &mut Frame, jobs: &super::app::JobsState, area: Rect) {
+    let header = Row::new(vec!["ID", "Kind", "State", "Progress", "Created", "Tenant"])
+        .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        .height(1);
+
+    let rows: Vec<Row> = jobs
+        .jobs
+        .iter()
+        .map(|job| {
+            Row::new(vec![
+                Cell::from(job.id.chars().take(8).collect::<String>()),
+                Cell::from(job.kind.clone()),
+                Cell::from(job.state.clone()).style(job_state_style(&job.state)),
+                Cell::from(job.progress_label()),
+                Cell::from(job.created_at.format("%m-%d %H:%M:%S").to_string()),
+                Cell::from(job.tenant.clone().unwrap_or_else(|| "-".to_string())),
+            ])
+        })
+        .collect();
+
+    let title = match &jobs.error {
+        Some(error) => format!("Jobs (refresh failed: {})", error),
+        None => format!("Jobs ({})", jobs.jobs.len()),
+    };
+    let table = Table::new(rows, [
+        Constraint::Length(10), // ID
+        Constraint::Length(9),  // Kind
+        Constraint::Length(10), // State
+        Constraint::Length(9),  // Progress
+        Constraint::Length(15), // Created
+        Constraint::Min(8),     // Tenant
+    ])
+        .header(header)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(if jobs.error.is_some() { Color::Red } else { Color::White })),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray));
+
+    f.render_stateful_widget(table, area, &mut jobs.table_state.clone());
+}
+
+/// Render details, progress and log of the selected job
+fn render_job_details(f: &mut Frame, job: Option<&crate::client::Job>, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(9),   // Job details
+            Constraint::Length(3),   // Progress
+            Constraint::Min(0),      // Log
+        ])
+        .split(area);
+
+    let Some(job) = job else {
+        let empty = Paragraph::new("No jobs yet\n\nStart one with --background, e.g.\nbackup create --background")
+            .block(Block::default().title("Job Details").borders(Borders::ALL).border_style(Style::default().fg(Color::Blue)))
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(empty, area);
+        return;
+    };
+
+    let mut details = format!(
+        "ID: {}\nKind: {}\nState: {}{}\nCreated: {}",
+        job.id,
+        job.kind,
+        job.state,
+        if job.cancel_requested && !job.is_finished() { " (cancelling)" } else { "" },
+        job.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(finished_at) = job.finished_at {
+        details.push_str(&format!("\nFinished: {}", finished_at.format("%Y-%m-%d %H:%M:%S UTC")));
+    }
+    if let Some(error) = &job.error {
+        details.push_str(&format!("\nError: {}", error));
+    }
+    let details_widget = Paragraph::new(details)
+        .block(
+            Block::default()
+                .title("Job Details")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true });
+    f.render_widget(details_widget, chunks[0]);
+
+    let percent = job.percent().unwrap_or(if job.state == "succeeded" { 100.0 } else { 0.0 });
+    let progress_gauge = Gauge::default()
+        .block(
+            Block::default()
+                .title("Progress")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Blue)),
+        )
+        .gauge_style(job_state_style(&job.state))
+        .percent(percent.clamp(0.0, 100.0) as u16)
+        .label(match job.progress.total {
+            Some(total) => format!("{} / {}", job.progress.completed, total),
+            None => job.progress_label(),
+        });
+    f.render_widget(progress_gauge, chunks[1]);
+
+    let log: Vec<ListItem> = job
+        .logs
+        .iter()
+        .rev()
+        .map(|entry| ListItem::new(format!("{} {}", entry.at.format("%H:%M:%S"), entry.message)))
+        .collect();
+    let log_widget = List::new(log)
+        .block(
+            Block::default()
+                .title("Log (newest first)")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Gray)),
+        )
+        .style(Style::default().fg(Color::White));
+    f.render_widget(log_widget, chunks[2]);
+}
+
+/// Render the running operations tab
+fn render_operations(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(65),  // Operation list
+            Constraint::Percentage(35),  // Operation details
+        ])
+        .split(area);
+
+    render_operation_list(f, &app.operations, chunks[0]);
+    render_operation_details(f, app.selected_operation(), chunks[1]);
+}
+
+/// Render the list of running queries and jobs, longest running first
+fn render_operation_list(f: &mut Frame, operations: &super::app::OperationsState, area: Rect) {
+    let header = Row::new(vec!["ID", "Source", "Kind", "Target", "Elapsed", "Work", "State"])
+        .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        .height(1);
+
+    let rows: Vec<Row> = operations
+        .operations
+        .iter()
+        .map(|operation| {
+            let state = if operation.cancelled { "killing".to_string() } else { operation.state.clone() };
+            Row::new(vec![
+                Cell::from(operation.id.chars().take(8).collect::<String>()),
+                Cell::from(operation.source.clone()),
+                Cell::from(operation.kind.clone()),
+                Cell::from(operation.target().to_string()),
+                Cell::from(format_elapsed(operation.elapsed_ms)).style(elapsed_style(operation.elapsed_ms)),
+                Cell::from(operation.work_label()),
+                Cell::from(state.clone()).style(job_state_style(&state)),
+            ])
+        })
+        .collect();
+
+    let title = match &operations.error {
+        Some(error) => format!("Running Operations (refresh failed: {})", error),
+        None => format!("Running Operations ({})", operations.operations.len()),
+    };
+    let table = Table::new(rows, [
+        Constraint::Length(10), // ID
+        Constraint::Length(7),  // Source
+        Constraint::Length(10), // Kind
+        Constraint::Min(10),    // Target
+        Constraint::Length(9),  // Elapsed
+        Constraint::Length(14), // Work
+        Constraint::Length(8),  // State
+    ])
+        .header(header)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(if operations.error.is_some() { Color::Red } else { Color::White })),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray));
+
+    f.render_stateful_widget(table, area, &mut operations.table_state.clone());
+}
+
+/// Render details and resource use of the selected operation
+fn render_operation_details(f: &mut Frame, operation: Option<&crate::client::RunningOperation>, area: Rect) {
+    let block = Block::default().title("Operation Details").borders(Borders::ALL).border_style(Style::default().fg(Color::Blue));
+    let Some(operation) = operation else {
+        let empty = Paragraph::new("Nothing running\n\nQueries and background jobs appear here while they run")
+            .block(block)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(empty, area);
+        return;
+    };
+
+    let mut details = format!(
+        "ID: {}\nSource: {}\nKind: {}\nTarget: {}\nState: {}{}\nElapsed: {}",
+        operation.id,
+        operation.source,
+        operation.kind,
+        operation.target(),
+        operation.state,
+        if operation.cancelled { " (killing)" } else { "" },
+        format_elapsed(operation.elapsed_ms)
+    );
+    if let Some(started_at) = operation.started_at {
+        details.push_str(&format!("\nStarted: {}", started_at.format("%Y-%m-%d %H:%M:%S UTC")));
+    }
+    if let Some(scanned) = operation.documents_scanned {
+        details.push_str(&format!("\nDocuments scanned: {}", scanned));
+    }
+    if let Some(memory) = operation.memory_bytes {
+        details.push_str(&format!("\nMemory: {:.1} MiB", memory as f64 / (1024.0 * 1024.0)));
+    }
+    if operation.progress.is_some() {
+        details.push_str(&format!("\nProgress: {}", operation.work_label()));
+    }
+    details.push_str("\n\n[K] twice to kill");
+
+    let details_widget = Paragraph::new(details)
+        .block(block)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true });
+    f.render_widget(details_widget, area);
+}
+
+/// Elapsed milliseconds as seconds, minutes or hours
+fn format_elapsed(elapsed_ms: u64) -> String {
+    let seconds = elapsed_ms / 1000;
+    match seconds {
+        0 => format!("{}ms", elapsed_ms),
+        1..=59 => format!("{}.{}s", seconds, (elapsed_ms % 1000) / 100),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, (seconds % 3600) / 60),
+    }
+}
+
+/// Color of an elapsed time, red for runaway operations
+fn elapsed_style(elapsed_ms: u64) -> Style {
+    match elapsed_ms {
+        0..=9_999 => Style::default().fg(Color::White),
+        10_000..=59_999 => Style::default().fg(Color::Yellow),
+        _ => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+    }
+}
+
+/// Color of a job state
+fn job_state_style(state: &str) -> Style {
+    match state {
+        "succeeded" => Style::default().fg(Color::Green),
+        "failed" => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        "running" => Style::default().fg(Color::Cyan),
+        "queued" => Style::default().fg(Color::Yellow),
+        _ => Style::default().fg(Color::Gray),
+    }
+}
+
 /// Render error overlay
 fn render_error_overlay(f: &mut Frame, app: &App) {
     if let Some(ref error_msg) = app.error_message {
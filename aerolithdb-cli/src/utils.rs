@@ -2,6 +2,7 @@
 //!
 //! This module provides utility functions used across CLI command implementations:
 //! - JSON parsing from inline strings or files
+//! - Sort specifications as `field:direction` pairs or JSON
 //! - Statistics formatting for human-readable display
 //! - Value formatting with appropriate units
 
@@ -89,6 +90,78 @@ pub fn parse_json_input(input: &str) -> Result<Value> {
     }
 }
 
+/// Parses a sort specification given as comma-separated `field:direction`
+/// pairs, or as JSON inline or via file reference (@sort.json).
+///
+/// Directions are `asc` or `desc` and default to ascending, so
+/// `priority:desc,created_at` becomes `{"priority": -1, "created_at": 1}`.
+///
+/// # Arguments
+///
+/// * `input` - Sort pairs, a JSON object, or a file reference (prefixed with @)
+///
+/// # Returns
+///
+/// * `Result<Value>` - Sort specification as a JSON object
+pub fn parse_sort_spec(input: &str) -> Result<Value> {
+    let input = input.trim();
+    if input.starts_with('{') || input.starts_with('@') {
+        return parse_json_input(input);
+    }
+
+    let mut spec = serde_json::Map::new();
+    for pair in input.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (field, direction) = pair.rsplit_once(':').unwrap_or((pair, "asc"));
+        let direction = match direction.to_ascii_lowercase().as_str() {
+            "asc" | "1" => 1,
+            "desc" | "-1" => -1,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Invalid sort direction '{}' for field '{}'\n  → Use 'asc' or 'desc'",
+                    other, field
+                ))
+            }
+        };
+        spec.insert(field.to_string(), Value::from(direction));
+    }
+    if spec.is_empty() {
+        return Err(anyhow::anyhow!("Empty sort specification\n  → Example: 'created_at:desc'"));
+    }
+    Ok(Value::Object(spec))
+}
+
+/// Adds a full-text search for `terms` to a query filter.
+///
+/// The `$text` condition goes alongside the filter's own conditions, so
+/// `--text "rust -java"` with `{"status": "published"}` becomes
+/// `{"$and": [{"$text": {"$search": "rust -java"}}, {"status": "published"}]}`.
+pub fn with_text_search(filter: Option<Value>, terms: &str) -> Value {
+    let text = serde_json::json!({ "$text": { "$search": terms } });
+    match filter {
+        Some(filter) => serde_json::json!({ "$and": [text, filter] }),
+        None => text,
+    }
+}
+
+/// Builds a query projection from comma-separated field paths.
+///
+/// `--fields title,author.name` keeps only those fields of each document,
+/// `{"title": 1, "author.name": 1}`, and with `exclude` set the fields are
+/// left out instead, `{"title": 0, "author.name": 0}`.
+pub fn parse_projection(fields: &str, exclude: bool) -> Result<Value> {
+    let flag = if exclude { 0 } else { 1 };
+    let spec: serde_json::Map<String, Value> = fields
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| (field.to_string(), Value::from(flag)))
+        .collect();
+    if spec.is_empty() {
+        return Err(anyhow::anyhow!("Empty field list\n  → Example: 'title,author.name'"));
+    }
+    Ok(Value::Object(spec))
+}
+
 /// Formats statistics data into a human-readable table format.
 ///
 /// ## Table Organization
@@ -386,6 +459,34 @@ mod tests {
         assert_eq!(json["test"], "value");
     }
 
+    #[test]
+    fn test_parse_sort_spec_pairs_and_json() {
+        let spec = parse_sort_spec("priority:desc, user.score:asc,name").unwrap();
+        assert_eq!(spec, json!({"priority": -1, "user.score": 1, "name": 1}));
+
+        let spec = parse_sort_spec(r#"{"created_at": -1}"#).unwrap();
+        assert_eq!(spec["created_at"], -1);
+
+        assert!(parse_sort_spec("priority:sideways").is_err());
+        assert!(parse_sort_spec(" , ").is_err());
+    }
+
+    #[test]
+    fn test_with_text_search() {
+        assert_eq!(with_text_search(None, "rust"), json!({"$text": {"$search": "rust"}}));
+        assert_eq!(
+            with_text_search(Some(json!({"status": "published"})), "rust -java"),
+            json!({"$and": [{"$text": {"$search": "rust -java"}}, {"status": "published"}]})
+        );
+    }
+
+    #[test]
+    fn test_parse_projection() {
+        assert_eq!(parse_projection("title, author.name", false).unwrap(), json!({"title": 1, "author.name": 1}));
+        assert_eq!(parse_projection("body", true).unwrap(), json!({"body": 0}));
+        assert!(parse_projection(" , ", false).is_err());
+    }
+
     #[test]
     fn test_parse_json_input_file_not_found() {
         let result = parse_json_input("@nonexistent.json");
@@ -0,0 +1,436 @@
+//! # aerolithsDB Client Derive Macros
+//!
+//! Procedural macros for the `aerolithdb-client` crate. Use them through the
+//! re-exports in `aerolithdb_client` rather than depending on this crate
+//! directly.
+//!
+//! ## `#[derive(QueryFields)]`
+//!
+//! Generates a `<Struct>Fields` companion struct holding one typed
+//! `aerolithdb_client::Field` per struct field, and implements
+//! `aerolithdb_client::QueryFields` so queries can refer to fields by name
+//! with compile-time checking of both the name and the value type.
+//!
+//! Field names follow the struct's serde attributes, so the generated paths
+//! match the stored JSON:
+//! - `#[serde(rename = "...")]` on a field
+//! - `#[serde(rename_all = "...")]` on the struct
+//! - `#[serde(skip)]` on a field omits it
+//!
+//! ## `#[derive(AerolithDocument)]`
+//!
+//! Implements `aerolithdb_client::AerolithDocument`, mapping the struct to a
+//! collection. Options go in `#[aerolith(...)]` attributes:
+//! - `collection = "name"` on the struct; defaults to the snake_case struct name
+//! - `id` on the field holding the document ID; defaults to the field named `id`
+//! - `index` or `index(unique, name = "...")` on a field declares an index on it
+//! - `index(fields(a, b), unique)` on the struct declares a compound index
+//! - `index(text)` declares a full-text index, searched with `$text`
+//! - `schema` on the struct derives a JSON schema from the field types
+//!
+//! ## `#[derive(SchemaType)]`
+//!
+//! Implements `aerolithdb_client::SchemaType` for structs nested inside
+//! documents that declare a schema.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Field, Fields, Ident, LitStr, Token};
+
+#[proc_macro_derive(QueryFields, attributes(serde))]
+pub fn derive_query_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_query_fields(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(AerolithDocument, attributes(aerolith, serde))]
+pub fn derive_aerolith_document(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_aerolith_document(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(SchemaType, attributes(serde))]
+pub fn derive_schema_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    named_fields(&input, "SchemaType")
+        .and_then(|named| expand_schema_type(&input, named))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The named fields of a non-generic struct, or an error naming the derive.
+fn named_fields<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<&'a Punctuated<Field, Token![,]>> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            format!("{} cannot be derived for generic structs", derive),
+        ));
+    }
+
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(&fields.named),
+            _ => Err(syn::Error::new_spanned(input, format!("{} requires named fields", derive))),
+        },
+        _ => Err(syn::Error::new_spanned(input, format!("{} can only be derived for structs", derive))),
+    }
+}
+
+/// Name of a field in the serialized JSON, or `None` if serde skips it.
+fn wire_name(field: &Field, container: &SerdeAttrs) -> syn::Result<Option<String>> {
+    let attrs = SerdeAttrs::parse(&field.attrs)?;
+    if attrs.skip {
+        return Ok(None);
+    }
+
+    let ident = field.ident.as_ref().expect("named fields have identifiers");
+    let rust_name = rust_name(ident);
+    let wire_name = match (&attrs.rename, &container.rename_all) {
+        (Some(rename), _) => rename.clone(),
+        (None, Some(rule)) => apply_rename_rule(rule, &rust_name)
+            .ok_or_else(|| syn::Error::new(Span::call_site(), format!("unsupported rename_all rule \"{}\"", rule)))?,
+        (None, None) => rust_name,
+    };
+    Ok(Some(wire_name))
+}
+
+fn rust_name(ident: &Ident) -> String {
+    let name = ident.to_string();
+    name.strip_prefix("r#").map(str::to_string).unwrap_or(name)
+}
+
+fn expand_query_fields(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let named = named_fields(input, "QueryFields")?;
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let mut members = Vec::new();
+    let mut initializers = Vec::new();
+
+    for field in named {
+        let Some(wire_name) = wire_name(field, &container)? else { continue };
+        let ident = field.ident.as_ref().expect("named fields have identifiers");
+        let vis = &field.vis;
+        let ty = &field.ty;
+        let doc = format!("Query path of `{}`", wire_name);
+        members.push(quote! {
+            #[doc = #doc]
+            #vis #ident: ::aerolithdb_client::Field<#ty>
+        });
+        initializers.push(quote! {
+            #ident: ::aerolithdb_client::Field::new(#wire_name)
+        });
+    }
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let fields_name = format_ident!("{}Fields", name);
+    let doc = format!("Typed query fields of [`{}`], generated by `#[derive(QueryFields)]`", name);
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone)]
+        #vis struct #fields_name {
+            #(#members,)*
+        }
+
+        impl ::aerolithdb_client::QueryFields for #name {
+            type Fields = #fields_name;
+
+            fn fields() -> Self::Fields {
+                #fields_name {
+                    #(#initializers,)*
+                }
+            }
+        }
+    })
+}
+
+fn expand_aerolith_document(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let named = named_fields(input, "AerolithDocument")?;
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let document = AerolithAttrs::parse(&input.attrs)?;
+    let name = &input.ident;
+
+    let mut id_field = None;
+    let mut indexes = Vec::new();
+    for field in named {
+        let ident = field.ident.as_ref().expect("named fields have identifiers");
+        let attrs = AerolithAttrs::parse(&field.attrs)?;
+        if attrs.id {
+            if id_field.is_some() {
+                return Err(syn::Error::new_spanned(ident, "only one field can be marked #[aerolith(id)]"));
+            }
+            id_field = Some(ident);
+        }
+        for index in attrs.indexes {
+            let wire_name = wire_name(field, &container)?
+                .ok_or_else(|| syn::Error::new_spanned(ident, "cannot index a field skipped by serde"))?;
+            indexes.push(index_definition(&index, vec![wire_name]));
+        }
+    }
+    for index in &document.indexes {
+        if index.fields.is_empty() {
+            return Err(syn::Error::new(Span::call_site(), "struct-level indexes need fields(...)"));
+        }
+        let mut paths = Vec::new();
+        for ident in &index.fields {
+            let field = named
+                .iter()
+                .find(|field| field.ident.as_ref() == Some(ident))
+                .ok_or_else(|| syn::Error::new_spanned(ident, format!("no field named `{}`", ident)))?;
+            paths.push(
+                wire_name(field, &container)?
+                    .ok_or_else(|| syn::Error::new_spanned(ident, "cannot index a field skipped by serde"))?,
+            );
+        }
+        indexes.push(index_definition(index, paths));
+    }
+
+    let id_field = match id_field {
+        Some(ident) => ident,
+        None => named
+            .iter()
+            .filter_map(|field| field.ident.as_ref())
+            .find(|ident| *ident == "id")
+            .ok_or_else(|| syn::Error::new_spanned(
+                name,
+                "AerolithDocument needs an `id` field or a field marked #[aerolith(id)]",
+            ))?,
+    };
+
+    let collection = document.collection.unwrap_or_else(|| to_snake_case(&name.to_string()));
+
+    let (schema, schema_impl) = if document.schema {
+        (
+            quote! {
+                fn schema() -> ::std::option::Option<::aerolithdb_client::__private::Value> {
+                    ::std::option::Option::Some(<Self as ::aerolithdb_client::SchemaType>::json_schema())
+                }
+            },
+            expand_schema_type(input, named)?,
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    Ok(quote! {
+        impl ::aerolithdb_client::AerolithDocument for #name {
+            const COLLECTION: &'static str = #collection;
+
+            fn id(&self) -> ::std::string::String {
+                ::std::string::ToString::to_string(&self.#id_field)
+            }
+
+            fn indexes() -> ::std::vec::Vec<::aerolithdb_client::IndexDefinition> {
+                ::std::vec![#(#indexes),*]
+            }
+
+            #schema
+        }
+
+        #schema_impl
+    })
+}
+
+fn expand_schema_type(
+    input: &DeriveInput,
+    named: &Punctuated<Field, Token![,]>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let mut properties = Vec::new();
+    for field in named {
+        let Some(wire_name) = wire_name(field, &container)? else { continue };
+        let attrs = SerdeAttrs::parse(&field.attrs)?;
+        let ty = &field.ty;
+        // Fields serde can fill in or leave out need not be present
+        let may_be_absent = container.default || attrs.default || attrs.skip_serializing_if;
+        properties.push(quote! {
+            (
+                #wire_name,
+                <#ty as ::aerolithdb_client::SchemaType>::json_schema(),
+                !#may_be_absent && <#ty as ::aerolithdb_client::SchemaType>::REQUIRED,
+            )
+        });
+    }
+
+    let name = &input.ident;
+    Ok(quote! {
+        impl ::aerolithdb_client::SchemaType for #name {
+            fn json_schema() -> ::aerolithdb_client::__private::Value {
+                ::aerolithdb_client::__private::object_schema(::std::vec![#(#properties),*])
+            }
+        }
+    })
+}
+
+fn index_definition(index: &IndexAttr, fields: Vec<String>) -> proc_macro2::TokenStream {
+    let name = match &index.name {
+        Some(name) => quote! { ::std::option::Option::Some(::std::string::String::from(#name)) },
+        None => quote! { ::std::option::Option::None },
+    };
+    let unique = index.unique;
+    let text = index.text;
+    quote! {
+        ::aerolithdb_client::IndexDefinition {
+            name: #name,
+            fields: ::std::vec![#(::std::string::String::from(#fields)),*],
+            unique: #unique,
+            text: #text,
+        }
+    }
+}
+
+/// Options from `#[aerolith(...)]` attributes on a struct or field.
+#[derive(Default)]
+struct AerolithAttrs {
+    collection: Option<String>,
+    schema: bool,
+    id: bool,
+    indexes: Vec<IndexAttr>,
+}
+
+/// One `index` or `index(...)` option.
+#[derive(Default)]
+struct IndexAttr {
+    name: Option<String>,
+    unique: bool,
+    text: bool,
+    fields: Vec<Ident>,
+}
+
+impl AerolithAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("aerolith")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("collection") {
+                    parsed.collection = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("schema") {
+                    parsed.schema = true;
+                } else if meta.path.is_ident("id") {
+                    parsed.id = true;
+                } else if meta.path.is_ident("index") {
+                    let mut index = IndexAttr::default();
+                    if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|option| {
+                            if option.path.is_ident("unique") {
+                                index.unique = true;
+                            } else if option.path.is_ident("text") {
+                                index.text = true;
+                            } else if option.path.is_ident("name") {
+                                index.name = Some(option.value()?.parse::<LitStr>()?.value());
+                            } else if option.path.is_ident("fields") {
+                                option.parse_nested_meta(|field| {
+                                    index.fields.push(field.path.require_ident()?.clone());
+                                    Ok(())
+                                })?;
+                            } else {
+                                return Err(option.error("expected `unique`, `text`, `name`, or `fields`"));
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    parsed.indexes.push(index);
+                } else {
+                    return Err(meta.error("expected `collection`, `schema`, `id`, or `index`"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// The subset of serde attributes that affects field names and presence.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+    default: bool,
+    skip_serializing_if: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                    parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("rename_all") && meta.input.peek(syn::Token![=]) {
+                    parsed.rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") {
+                    parsed.skip = true;
+                } else if meta.path.is_ident("default") {
+                    parsed.default = true;
+                    skip_meta(&meta)?;
+                } else if meta.path.is_ident("skip_serializing_if") {
+                    parsed.skip_serializing_if = true;
+                    skip_meta(&meta)?;
+                } else {
+                    skip_meta(&meta)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// Consume the value of a serde attribute this macro does not interpret.
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip_meta(&nested))?;
+    }
+    Ok(())
+}
+
+/// Convert a PascalCase type name to snake_case.
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, ch) in name.chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
+
+/// Apply a serde `rename_all` rule to a snake_case field name.
+fn apply_rename_rule(rule: &str, field: &str) -> Option<String> {
+    let words: Vec<&str> = field.split('_').filter(|word| !word.is_empty()).collect();
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+    };
+
+    Some(match rule {
+        "lowercase" => field.to_lowercase(),
+        "UPPERCASE" => field.to_uppercase(),
+        "snake_case" => field.to_string(),
+        "SCREAMING_SNAKE_CASE" => field.to_uppercase(),
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.replace('_', "-").to_uppercase(),
+        "PascalCase" => words.iter().map(|word| capitalize(word)).collect(),
+        "camelCase" => words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| if i == 0 { word.to_string() } else { capitalize(word) })
+            .collect(),
+        _ => return None,
+    })
+}
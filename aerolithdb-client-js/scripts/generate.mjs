@@ -0,0 +1,187 @@
+// Generates src/generated.ts from the REST API's OpenAPI spec.
+//
+//   node scripts/generate.mjs          rewrite src/generated.ts
+//   node scripts/generate.mjs --check  fail if src/generated.ts is out of date
+
+import { readFileSync, writeFileSync } from 'node:fs'
+import { dirname, join } from 'node:path'
+import { fileURLToPath } from 'node:url'
+
+const root = join(dirname(fileURLToPath(import.meta.url)), '..')
+const specPath = join(root, '..', 'aerolithdb-api', 'openapi.json')
+const outputPath = join(root, 'src', 'generated.ts')
+
+const METHODS = ['get', 'put', 'post', 'delete', 'patch']
+// Methods that may be sent twice without applying twice; others opt in with `x-idempotent`
+const IDEMPOTENT_METHODS = new Set(['get', 'put', 'delete'])
+
+const spec = JSON.parse(readFileSync(specPath, 'utf8'))
+const output = generate(spec)
+
+if (process.argv.includes('--check')) {
+  let current = ''
+  try {
+    current = readFileSync(outputPath, 'utf8')
+  } catch {
+    // A missing file is out of date too
+  }
+  if (current !== output) {
+    console.error('src/generated.ts does not match aerolithdb-api/openapi.json; run `npm run generate`')
+    process.exit(1)
+  }
+} else {
+  writeFileSync(outputPath, output)
+}
+
+function generate(spec) {
+  const lines = [
+    '// Generated from aerolithdb-api/openapi.json by scripts/generate.mjs; do not edit.',
+    '// Change the spec and run `npm run generate` instead.',
+    '',
+    '/** A request to one REST route */',
+    'export interface Route {',
+    "  method: 'GET' | 'PUT' | 'POST' | 'DELETE' | 'PATCH'",
+    '  /** Path with its parameters filled in, plus the query string if any */',
+    '  path: string',
+    '  /** Whether sending the request twice has the same effect as sending it once */',
+    '  idempotent: boolean',
+    '}'
+  ]
+
+  for (const [name, schema] of Object.entries(spec.components?.schemas ?? {})) {
+    lines.push('', ...docComment(schema.description, ''), `export interface ${name} ${objectType(schema, '')}`)
+  }
+
+  let usesQuery = false
+  for (const [path, item] of Object.entries(spec.paths)) {
+    for (const method of METHODS) {
+      const operation = item[method]
+      if (!operation) {
+        continue
+      }
+      const parameters = [...(item.parameters ?? []), ...(operation.parameters ?? [])].map(parameter => resolve(spec, parameter))
+      const query = parameters.filter(parameter => parameter.in === 'query')
+      usesQuery ||= query.length > 0
+      lines.push('', ...routeFunction(path, method, operation, parameters, query))
+    }
+  }
+
+  if (usesQuery) {
+    lines.push(
+      '',
+      '/** Query string of the given parameters that are set, with its leading `?` */',
+      'function queryString(params: object, names: string[]): string {',
+      '  const search = new URLSearchParams()',
+      '  for (const name of names) {',
+      '    const value = (params as Record<string, unknown>)[name]',
+      '    if (value !== undefined && value !== null) {',
+      '      search.set(name, String(value))',
+      '    }',
+      '  }',
+      '  const query = search.toString()',
+      "  return query ? `?${query}` : ''",
+      '}'
+    )
+  }
+  return lines.join('\n') + '\n'
+}
+
+/** A function building the route of one operation from its parameters */
+function routeFunction(path, method, operation, parameters, query) {
+  const name = operation.operationId
+  if (!name) {
+    throw new Error(`${method.toUpperCase()} ${path} has no operationId`)
+  }
+  const paramsType = `${name[0].toUpperCase()}${name.slice(1)}Params`
+  const idempotent = operation['x-idempotent'] ?? IDEMPOTENT_METHODS.has(method)
+
+  const fields = parameters.map(parameter => [
+    ...docComment(parameter.description, '  '),
+    `  ${parameter.name}${parameter.required ? '' : '?'}: ${typeOf(parameter.schema, '  ')}`
+  ])
+  const filled = path.replace(/\{([^}]+)\}/g, (_, parameter) => `\${encodeURIComponent(params.${parameter})}`)
+  const suffix = query.length > 0 ? ` + queryString(params, [${query.map(parameter => `'${parameter.name}'`).join(', ')}])` : ''
+
+  return [
+    `export interface ${paramsType} {`,
+    ...fields.flat(),
+    '}',
+    '',
+    ...docComment(operation.summary, ''),
+    `export function ${name}(params: ${paramsType}): Route {`,
+    '  return {',
+    `    method: '${method.toUpperCase()}',`,
+    `    path: \`${filled}\`${suffix},`,
+    `    idempotent: ${idempotent}`,
+    '  }',
+    '}'
+  ]
+}
+
+/** TypeScript type of a schema, indented for nested object types */
+function typeOf(schema, indent) {
+  if (!schema) {
+    return 'unknown'
+  }
+  let type
+  if (schema.$ref) {
+    type = schema.$ref.split('/').pop()
+  } else if (schema.enum) {
+    type = schema.enum.map(value => (typeof value === 'string' ? `'${value}'` : JSON.stringify(value))).join(' | ')
+  } else {
+    switch (schema.type) {
+      case 'string':
+        type = 'string'
+        break
+      case 'integer':
+      case 'number':
+        type = 'number'
+        break
+      case 'boolean':
+        type = 'boolean'
+        break
+      case 'array': {
+        const items = typeOf(schema.items, indent)
+        type = items.includes(' | ') ? `Array<${items}>` : `${items}[]`
+        break
+      }
+      case 'object':
+        type = objectType(schema, indent)
+        break
+      default:
+        type = 'unknown'
+    }
+  }
+  return schema.nullable ? `${type} | null` : type
+}
+
+/** An object schema as a type literal, or a record when it has no named properties */
+function objectType(schema, indent) {
+  if (!schema.properties) {
+    const values = schema.additionalProperties
+    return `Record<string, ${values && values !== true ? typeOf(values, indent) : 'unknown'}>`
+  }
+
+  const required = new Set(schema.required ?? [])
+  const inner = `${indent}  `
+  const fields = Object.entries(schema.properties).map(([name, property]) => [
+    ...docComment(property.description, inner),
+    `${inner}${name}${required.has(name) ? '' : '?'}: ${typeOf(property, inner)}`
+  ])
+  return ['{', ...fields.flat(), `${indent}}`].join('\n')
+}
+
+function docComment(text, indent) {
+  return text ? [`${indent}/** ${text} */`] : []
+}
+
+/** Follow a `$ref` into the spec's components */
+function resolve(spec, value) {
+  if (!value.$ref) {
+    return value
+  }
+  return value.$ref
+    .replace(/^#\//, '')
+    .split('/')
+    .reduce((node, key) => node[key], spec)
+}
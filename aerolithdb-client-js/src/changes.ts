@@ -0,0 +1,132 @@
+import { AerolithError, ChangeAction, ChangeEvent, RetryPolicy, backoff } from './types.js'
+
+/** The subset of the WebSocket API used by change streams */
+export interface WebSocketLike {
+  onopen: ((event: unknown) => void) | null
+  onmessage: ((event: { data: unknown }) => void) | null
+  onclose: ((event: unknown) => void) | null
+  onerror: ((event: unknown) => void) | null
+  send(data: string): void
+  close(): void
+}
+
+export type WebSocketConstructor = new (url: string) => WebSocketLike
+
+export interface ChangeStreamOptions {
+  /** Only deliver changes to documents matching this filter */
+  filter?: Record<string, unknown>
+  /** Resume a stream from a token returned by `ChangeStream.resumeToken` */
+  resumeToken?: string
+  /** Called when every endpoint failed for the retry policy's attempts */
+  onError?: (error: AerolithError) => void
+}
+
+interface ChangeStreamConfig<T> extends ChangeStreamOptions {
+  urls: string[]
+  collection: string
+  onChange: (event: ChangeEvent<T>) => void
+  retry: RetryPolicy
+  WebSocket: WebSocketConstructor
+}
+
+/** Event shape sent by the WebSocket API, optionally wrapped in a journal entry */
+interface WireMessage {
+  type?: string
+  collection?: string
+  document_id?: string
+  action?: string
+  data?: unknown
+  timestamp?: string
+  resume_token?: string
+  event?: WireMessage
+}
+
+/**
+ * Live stream of document changes for one collection.
+ *
+ * When the connection drops, the stream reconnects to the next endpoint,
+ * backing off with the client's retry policy, and resumes from the last
+ * token the node sent so missed events are replayed rather than lost.
+ */
+export class ChangeStream<T> {
+  private socket?: WebSocketLike
+  private token?: string
+  private endpoint = 0
+  private failures = 0
+  private closed = false
+  private reconnectTimer?: ReturnType<typeof setTimeout>
+
+  constructor(private readonly config: ChangeStreamConfig<T>) {
+    this.token = config.resumeToken
+    this.connect()
+  }
+
+  /** Token for resuming this stream on a new client after a restart */
+  get resumeToken(): string | undefined {
+    return this.token
+  }
+
+  close(): void {
+    this.closed = true
+    clearTimeout(this.reconnectTimer)
+    this.socket?.close()
+  }
+
+  private connect(): void {
+    const url = this.config.urls[this.endpoint % this.config.urls.length]
+    const socket = new this.config.WebSocket(url)
+    this.socket = socket
+
+    socket.onopen = () => {
+      this.failures = 0
+      const request = this.token
+        ? { type: 'resume', resume_token: this.token }
+        : { type: 'subscribe', collection: this.config.collection, query: this.config.filter ?? null }
+      socket.send(JSON.stringify(request))
+    }
+    socket.onmessage = message => this.handle(message.data)
+    socket.onerror = () => socket.close()
+    socket.onclose = () => this.reconnect()
+  }
+
+  private handle(raw: unknown): void {
+    let message: WireMessage
+    try {
+      message = JSON.parse(String(raw)) as WireMessage
+    } catch {
+      return
+    }
+
+    if (message.resume_token) {
+      this.token = message.resume_token
+    }
+    const event = message.event ?? message
+    if (event.type !== 'DocumentChanged' || event.collection !== this.config.collection || !event.document_id) {
+      return
+    }
+
+    this.config.onChange({
+      collection: this.config.collection,
+      documentId: event.document_id,
+      action: (event.action ?? 'updated').toLowerCase() as ChangeAction,
+      data: event.data === null || event.data === undefined ? undefined : (event.data as T),
+      timestamp: event.timestamp ?? new Date().toISOString()
+    })
+  }
+
+  private reconnect(): void {
+    if (this.closed) {
+      return
+    }
+
+    this.failures++
+    this.endpoint++
+    if (this.failures >= Math.max(this.config.retry.maxAttempts, 1) * this.config.urls.length) {
+      this.closed = true
+      this.config.onError?.(new AerolithError(`Change stream for '${this.config.collection}' lost every endpoint`))
+      return
+    }
+
+    this.reconnectTimer = setTimeout(() => this.connect(), backoff(this.config.retry, this.failures - 1))
+  }
+}
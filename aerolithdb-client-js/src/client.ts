@@ -0,0 +1,244 @@
+import { ChangeStream, ChangeStreamOptions, WebSocketConstructor } from './changes.js'
+import * as routes from './generated.js'
+import { DocumentRequest, Route } from './generated.js'
+import { Query } from './query.js'
+import { AerolithError, ChangeEvent, DEFAULT_RETRY_POLICY, QueryPage, RetryPolicy, TypedDocument, backoff } from './types.js'
+
+export interface AerolithClientOptions {
+  /** REST endpoints of one or more nodes, e.g. `http://localhost:8080` */
+  endpoints: string | string[]
+  /** Per-request timeout; defaults to 30 seconds */
+  timeoutMs?: number
+  retry?: Partial<RetryPolicy>
+  /** WebSocket endpoint for a REST endpoint; defaults to port + 3 at `/ws` */
+  websocketUrl?: (endpoint: string) => string
+  /** WebSocket implementation, e.g. from the `ws` package on Node.js < 22 */
+  WebSocket?: WebSocketConstructor
+  /** fetch implementation; defaults to the global one */
+  fetch?: typeof fetch
+}
+
+// Errors raised before a request reached the node, so retrying cannot apply it twice
+const CONNECT_ERROR_CODES = new Set(['ECONNREFUSED', 'ENOTFOUND', 'EAI_AGAIN', 'EHOSTUNREACH'])
+
+/**
+ * Client for the AerolithDB REST API.
+ *
+ * Requests go to the last endpoint that answered and fail over to the next
+ * one when a node is unreachable or reports itself unavailable (502, 503,
+ * 504), backing off exponentially between attempts. Requests that could be
+ * applied twice, such as creating a document with a server-generated ID, are
+ * only retried when the connection could not be established at all.
+ */
+export class AerolithClient {
+  private readonly endpoints: string[]
+  private readonly timeoutMs: number
+  private readonly retry: RetryPolicy
+  private readonly fetchImpl: typeof fetch
+  private readonly websocketUrl: (endpoint: string) => string
+  private readonly WebSocketImpl?: WebSocketConstructor
+  /** Index of the endpoint that answered most recently */
+  private preferred = 0
+
+  constructor(options: AerolithClientOptions) {
+    const endpoints = Array.isArray(options.endpoints) ? options.endpoints : [options.endpoints]
+    if (endpoints.length === 0) {
+      throw new AerolithError('At least one endpoint is required')
+    }
+
+    this.endpoints = endpoints.map(endpoint => endpoint.replace(/\/+$/, ''))
+    this.timeoutMs = options.timeoutMs ?? 30000
+    this.retry = { ...DEFAULT_RETRY_POLICY, ...options.retry }
+    this.fetchImpl = options.fetch ?? globalThis.fetch.bind(globalThis)
+    this.websocketUrl = options.websocketUrl ?? defaultWebSocketUrl
+    this.WebSocketImpl = options.WebSocket
+  }
+
+  /** Typed handle for one collection */
+  collection<T>(name: string): Collection<T> {
+    return new Collection<T>(this, name)
+  }
+
+  /** Run a typed query against a collection */
+  async query<T>(collection: string, query: Query<T>): Promise<QueryPage<T>> {
+    const response = await this.send(routes.queryDocuments({ collection }), query.toRequest())
+    return parse<QueryPage<T>>(response, collection)
+  }
+
+  /** Fetch a document by ID, or `null` if it does not exist */
+  async getDocument<T>(collection: string, id: string): Promise<TypedDocument<T> | null> {
+    const response = await this.send(routes.getDocument({ collection, id }))
+    if (response.status === 404) {
+      return null
+    }
+    return parse<TypedDocument<T>>(response, collection)
+  }
+
+  /** Store a document under `id`, replacing any existing version */
+  async putDocument<T>(collection: string, id: string, data: T): Promise<TypedDocument<T>> {
+    const body: DocumentRequest = { data }
+    const response = await this.send(routes.putDocument({ collection, id }), body)
+    return parse<TypedDocument<T>>(response, collection)
+  }
+
+  /** Store a new document with a server-generated ID */
+  async createDocument<T>(collection: string, data: T): Promise<TypedDocument<T>> {
+    // A retried create could store the document twice under different IDs
+    const body: DocumentRequest = { data }
+    const response = await this.send(routes.createDocument({ collection }), body)
+    return parse<TypedDocument<T>>(response, collection)
+  }
+
+  /** Delete a document, returning whether it existed */
+  async deleteDocument(collection: string, id: string): Promise<boolean> {
+    const response = await this.send(routes.deleteDocument({ collection, id }))
+    if (response.status === 404) {
+      return false
+    }
+    if (!response.ok) {
+      const body = await response.text()
+      throw new AerolithError(`Request to collection '${collection}' failed with ${response.status}: ${body}`, response.status, body)
+    }
+    return true
+  }
+
+  /**
+   * Stream changes to a collection over WebSocket. The stream reconnects
+   * across endpoints with the client's retry policy and resumes from the
+   * last delivered event when the node provides a resume token.
+   */
+  watch<T>(collection: string, onChange: (event: ChangeEvent<T>) => void, options: ChangeStreamOptions = {}): ChangeStream<T> {
+    const WebSocketImpl = this.WebSocketImpl ?? (globalThis as unknown as { WebSocket?: WebSocketConstructor }).WebSocket
+    if (!WebSocketImpl) {
+      throw new AerolithError('No WebSocket implementation available; pass one in the client options')
+    }
+
+    // Start from the endpoint currently answering REST requests
+    const endpoints = [...this.endpoints.slice(this.preferred), ...this.endpoints.slice(0, this.preferred)]
+    return new ChangeStream<T>({
+      urls: endpoints.map(this.websocketUrl),
+      collection,
+      onChange,
+      retry: this.retry,
+      WebSocket: WebSocketImpl,
+      ...options
+    })
+  }
+
+  /**
+   * Send a request, retrying and failing over according to the retry policy.
+   * Non-idempotent requests are only retried when no connection could be made.
+   */
+  private async send(route: Route, body?: unknown): Promise<Response> {
+    const attempts = Math.max(this.retry.maxAttempts, 1)
+    const first = this.preferred
+    let lastError: unknown
+
+    for (let attempt = 0; attempt < attempts; attempt++) {
+      if (attempt > 0) {
+        await sleep(backoff(this.retry, attempt - 1))
+      }
+      const index = (first + attempt) % this.endpoints.length
+      const endpoint = this.endpoints[index]
+
+      try {
+        const response = await this.fetchWithTimeout(`${endpoint}${route.path}`, route.method, body)
+        if (route.idempotent && [502, 503, 504].includes(response.status)) {
+          lastError = new AerolithError(`Endpoint ${endpoint} returned ${response.status}`, response.status, await response.text())
+          continue
+        }
+        this.preferred = index
+        return response
+      } catch (error) {
+        if (!route.idempotent && !isConnectError(error)) {
+          throw error
+        }
+        lastError = error
+      }
+    }
+
+    const reason = lastError instanceof Error ? lastError.message : String(lastError)
+    const status = lastError instanceof AerolithError ? lastError.status : undefined
+    throw new AerolithError(`Request failed after ${attempts} attempt(s): ${reason}`, status)
+  }
+
+  private async fetchWithTimeout(url: string, method: string, body: unknown): Promise<Response> {
+    const controller = new AbortController()
+    const timer = setTimeout(() => controller.abort(), this.timeoutMs)
+    try {
+      return await this.fetchImpl(url, {
+        method,
+        headers: body === undefined ? undefined : { 'Content-Type': 'application/json' },
+        body: body === undefined ? undefined : JSON.stringify(body),
+        signal: controller.signal
+      })
+    } finally {
+      clearTimeout(timer)
+    }
+  }
+}
+
+/** Typed handle for one collection, from `AerolithClient.collection` */
+export class Collection<T> {
+  constructor(private readonly client: AerolithClient, readonly name: string) {}
+
+  get(id: string): Promise<TypedDocument<T> | null> {
+    return this.client.getDocument<T>(this.name, id)
+  }
+
+  put(id: string, data: T): Promise<TypedDocument<T>> {
+    return this.client.putDocument<T>(this.name, id, data)
+  }
+
+  create(data: T): Promise<TypedDocument<T>> {
+    return this.client.createDocument<T>(this.name, data)
+  }
+
+  delete(id: string): Promise<boolean> {
+    return this.client.deleteDocument(this.name, id)
+  }
+
+  /** Start a query; run it with `find` */
+  query(): Query<T> {
+    return new Query<T>()
+  }
+
+  find(query: Query<T>): Promise<QueryPage<T>> {
+    return this.client.query<T>(this.name, query)
+  }
+
+  watch(onChange: (event: ChangeEvent<T>) => void, options?: ChangeStreamOptions): ChangeStream<T> {
+    return this.client.watch<T>(this.name, onChange, options)
+  }
+}
+
+function sleep(ms: number): Promise<void> {
+  return new Promise(resolve => setTimeout(resolve, ms))
+}
+
+/** WebSocket API of a node, which listens three ports above its REST API */
+function defaultWebSocketUrl(endpoint: string): string {
+  const url = new URL(endpoint)
+  const port = Number(url.port || (url.protocol === 'https:' ? 443 : 80))
+  url.protocol = url.protocol === 'https:' ? 'wss:' : 'ws:'
+  url.port = String(port + 3)
+  url.pathname = '/ws'
+  return url.toString()
+}
+
+function isConnectError(error: unknown): boolean {
+  const cause = (error as { cause?: { code?: string } } | undefined)?.cause
+  return cause?.code !== undefined && CONNECT_ERROR_CODES.has(cause.code)
+}
+
+async function parse<R>(response: Response, collection: string): Promise<R> {
+  const body = await response.text()
+  if (!response.ok) {
+    throw new AerolithError(`Request to collection '${collection}' failed with ${response.status}: ${body}`, response.status, body)
+  }
+  try {
+    return JSON.parse(body) as R
+  } catch (error) {
+    throw new AerolithError(`Response from collection '${collection}' is not valid JSON: ${String(error)}`, response.status, body)
+  }
+}
@@ -0,0 +1,167 @@
+// Generated from aerolithdb-api/openapi.json by scripts/generate.mjs; do not edit.
+// Change the spec and run `npm run generate` instead.
+
+/** A request to one REST route */
+export interface Route {
+  method: 'GET' | 'PUT' | 'POST' | 'DELETE' | 'PATCH'
+  /** Path with its parameters filled in, plus the query string if any */
+  path: string
+  /** Whether sending the request twice has the same effect as sending it once */
+  idempotent: boolean
+}
+
+export interface DocumentRequest {
+  /** The document */
+  data: unknown
+  /** Seconds until the document expires; without it a document keeps its current expiry */
+  ttl_seconds?: number
+  /** Store the document without compressing it, for already-compressed or incompressible payloads */
+  skip_compression?: boolean
+}
+
+export interface DocumentResponse {
+  id: string
+  /** The document */
+  data: unknown
+  version: number
+  created_at: string
+  updated_at: string
+  /** When the document expires, if it was given a time to live */
+  expires_at?: string
+}
+
+export interface QueryLimits {
+  /** Wall-clock time the query may run, in milliseconds */
+  timeout_ms?: number
+  /** Documents the query may read */
+  max_documents_scanned?: number
+  /** Estimated bytes of matches the query may hold at once */
+  max_memory_bytes?: number
+}
+
+export interface QueryRequest {
+  /** MongoDB-style filter; every document matches without one */
+  filter?: Record<string, unknown> | null
+  limit?: number | null
+  offset?: number | null
+  /** Sort keys, 1 for ascending and -1 for descending */
+  sort?: Record<string, 1 | -1> | null
+  /** `next_cursor` of the previous page, to continue after it */
+  cursor?: string
+  limits?: QueryLimits
+  /** Fields to return, e.g. `{"title": 1, "author.name": 1}` or `{"body": 0}` */
+  projection?: Record<string, 0 | 1>
+  /** Query the collection as it was at this time; versioned collections only */
+  as_of?: string
+}
+
+export interface QueryResponse {
+  documents: DocumentResponse[]
+  /** Matching documents before limit and offset were applied */
+  total: number
+  limit: number | null
+  offset: number | null
+  /** Pass as `cursor` to get the next page; absent on the last one */
+  next_cursor?: string
+  /** Set when some cluster members did not answer, so matches may be missing */
+  partial?: boolean
+  /** Cluster members that did not answer */
+  unreachable_nodes?: string[]
+}
+
+export interface CreateDocumentParams {
+  collection: string
+}
+
+/** Store a new document with a server-generated ID */
+export function createDocument(params: CreateDocumentParams): Route {
+  return {
+    method: 'POST',
+    path: `/api/v1/collections/${encodeURIComponent(params.collection)}/documents`,
+    idempotent: false
+  }
+}
+
+export interface GetDocumentParams {
+  collection: string
+  id: string
+  /** Comma-separated field paths to return, e.g. `title,author.name` */
+  fields?: string
+  /** Comma-separated field paths to leave out */
+  exclude?: string
+  /** `local` (default), `bounded_staleness` or `linearizable`; stronger levels are only served for strongly consistent collections */
+  consistency?: 'local' | 'bounded_staleness' | 'linearizable'
+  /** Maximum lag of a `bounded_staleness` read, in milliseconds */
+  max_lag_ms?: number
+  /** Read the document as it was at this time; versioned collections only */
+  as_of?: string
+}
+
+/** Fetch a document by ID */
+export function getDocument(params: GetDocumentParams): Route {
+  return {
+    method: 'GET',
+    path: `/api/v1/collections/${encodeURIComponent(params.collection)}/documents/${encodeURIComponent(params.id)}` + queryString(params, ['fields', 'exclude', 'consistency', 'max_lag_ms', 'as_of']),
+    idempotent: true
+  }
+}
+
+export interface PutDocumentParams {
+  collection: string
+  id: string
+}
+
+/** Store a document under its ID, replacing any existing version */
+export function putDocument(params: PutDocumentParams): Route {
+  return {
+    method: 'PUT',
+    path: `/api/v1/collections/${encodeURIComponent(params.collection)}/documents/${encodeURIComponent(params.id)}`,
+    idempotent: true
+  }
+}
+
+export interface DeleteDocumentParams {
+  collection: string
+  id: string
+}
+
+/** Delete a document */
+export function deleteDocument(params: DeleteDocumentParams): Route {
+  return {
+    method: 'DELETE',
+    path: `/api/v1/collections/${encodeURIComponent(params.collection)}/documents/${encodeURIComponent(params.id)}`,
+    idempotent: true
+  }
+}
+
+export interface QueryDocumentsParams {
+  collection: string
+  /** `json` (default), `ndjson` or `csv`; overrides the `Accept` header */
+  format?: 'json' | 'ndjson' | 'csv'
+  /** `local` (default), `bounded_staleness` or `linearizable`; stronger levels are only served for strongly consistent collections */
+  consistency?: 'local' | 'bounded_staleness' | 'linearizable'
+  /** Maximum lag of a `bounded_staleness` read, in milliseconds */
+  max_lag_ms?: number
+}
+
+/** Run a query against a collection */
+export function queryDocuments(params: QueryDocumentsParams): Route {
+  return {
+    method: 'POST',
+    path: `/api/v1/collections/${encodeURIComponent(params.collection)}/query` + queryString(params, ['format', 'consistency', 'max_lag_ms']),
+    idempotent: true
+  }
+}
+
+/** Query string of the given parameters that are set, with its leading `?` */
+function queryString(params: object, names: string[]): string {
+  const search = new URLSearchParams()
+  for (const name of names) {
+    const value = (params as Record<string, unknown>)[name]
+    if (value !== undefined && value !== null) {
+      search.set(name, String(value))
+    }
+  }
+  const query = search.toString()
+  return query ? `?${query}` : ''
+}
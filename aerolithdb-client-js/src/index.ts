@@ -0,0 +1,17 @@
+export { AerolithClient, Collection } from './client.js'
+export type { AerolithClientOptions } from './client.js'
+export { ChangeStream } from './changes.js'
+export type { ChangeStreamOptions, WebSocketConstructor, WebSocketLike } from './changes.js'
+export { Query } from './query.js'
+export { AerolithError, DEFAULT_RETRY_POLICY, backoff } from './types.js'
+export type {
+  ChangeAction,
+  ChangeEvent,
+  Condition,
+  Filter,
+  QueryPage,
+  RetryPolicy,
+  SortDirection,
+  TypedDocument
+} from './types.js'
+export type { DocumentRequest, DocumentResponse, QueryLimits, QueryRequest, QueryResponse } from './generated.js'
@@ -0,0 +1,86 @@
+import { QueryRequest } from './generated.js'
+import { Filter, SortDirection } from './types.js'
+
+type FieldName<T> = keyof T & string
+
+/**
+ * Builder for queries returning documents of type `T`, producing the same
+ * request body as the Rust client's `Query`.
+ */
+export class Query<T> {
+  private filters: Filter<T>[] = []
+  private sortKeys: Array<[string, SortDirection]> = []
+  private projection?: { fields: string[]; include: boolean }
+  private limitValue?: number
+  private offsetValue?: number
+
+  /** Add a filter; repeated calls must all match */
+  filter(filter: Filter<T>): this {
+    this.filters.push(filter)
+    return this
+  }
+
+  /**
+   * Add a sort key. With several keys the query engine currently applies
+   * them in field-name order rather than the order they were added.
+   */
+  sort(field: FieldName<T>, direction: SortDirection = 'asc'): this {
+    this.sortKeys.push([field, direction])
+    return this
+  }
+
+  /** Return only the given fields */
+  select(...fields: FieldName<T>[]): this {
+    this.projection = { fields, include: true }
+    return this
+  }
+
+  /** Return everything except the given fields */
+  exclude(...fields: FieldName<T>[]): this {
+    this.projection = { fields, include: false }
+    return this
+  }
+
+  limit(limit: number): this {
+    this.limitValue = limit
+    return this
+  }
+
+  offset(offset: number): this {
+    this.offsetValue = offset
+    return this
+  }
+
+  /** Select a zero-based page of `perPage` documents */
+  page(page: number, perPage: number): this {
+    return this.offset(page * perPage).limit(perPage)
+  }
+
+  /** Build the request body for the REST query endpoint */
+  toRequest(): QueryRequest {
+    let filter: Record<string, unknown> | null = null
+    if (this.filters.length === 1) {
+      filter = this.filters[0] as unknown as Record<string, unknown>
+    } else if (this.filters.length > 1) {
+      filter = { $and: this.filters }
+    }
+
+    const request: QueryRequest = {
+      filter,
+      limit: this.limitValue ?? null,
+      offset: this.offsetValue ?? null,
+      sort: null
+    }
+
+    if (this.sortKeys.length > 0) {
+      request.sort = Object.fromEntries(
+        this.sortKeys.map(([field, direction]) => [field, direction === 'asc' ? 1 : -1] as const)
+      )
+    }
+    if (this.projection) {
+      const flag = this.projection.include ? 1 : 0
+      request.projection = Object.fromEntries(this.projection.fields.map(field => [field, flag] as const))
+    }
+    return request
+  }
+}
@@ -0,0 +1,88 @@
+// Wire types shared with the AerolithDB REST and WebSocket APIs; the REST
+// ones are generated from the API's OpenAPI spec
+
+import { DocumentResponse, QueryResponse } from './generated.js'
+
+/** A stored document with its data typed as `T` */
+export type TypedDocument<T> = Omit<DocumentResponse, 'data'> & { data: T }
+
+/** One page of query results */
+export type QueryPage<T> = Omit<QueryResponse, 'documents'> & { documents: TypedDocument<T>[] }
+
+/** Comparison operators accepted for a single field */
+export interface Condition<V> {
+  $eq?: V
+  $ne?: V
+  $gt?: V
+  $gte?: V
+  $lt?: V
+  $lte?: V
+  $in?: V[]
+  $nin?: V[]
+  $exists?: boolean
+  $regex?: V extends string ? string : never
+}
+
+/**
+ * MongoDB-style filter over the fields of `T`. Nested fields can be matched
+ * with dotted paths such as `'address.city'`, which are not type checked.
+ */
+export type Filter<T> = {
+  [K in keyof T & string]?: T[K] | Condition<T[K]>
+} & {
+  [path: `${string}.${string}`]: unknown
+} & {
+  $and?: Filter<T>[]
+  $or?: Filter<T>[]
+  $not?: Filter<T>
+}
+
+export type SortDirection = 'asc' | 'desc'
+
+/** How failed requests are retried across a client's endpoints */
+export interface RetryPolicy {
+  /** Total attempts per request, including the first */
+  maxAttempts: number
+  /** Delay before the first retry; doubled for every further retry */
+  initialBackoffMs: number
+  /** Upper bound on the delay between attempts */
+  maxBackoffMs: number
+}
+
+export const DEFAULT_RETRY_POLICY: RetryPolicy = {
+  maxAttempts: 3,
+  initialBackoffMs: 100,
+  maxBackoffMs: 2000
+}
+
+/** Delay before retry number `retry`, counting from zero */
+export function backoff(policy: RetryPolicy, retry: number): number {
+  return Math.min(policy.initialBackoffMs * 2 ** retry, policy.maxBackoffMs)
+}
+
+export type ChangeAction = 'created' | 'updated' | 'deleted'
+
+/** A document change delivered by a change stream */
+export interface ChangeEvent<T> {
+  collection: string
+  documentId: string
+  action: ChangeAction
+  /** New document data; absent for deletions */
+  data?: T
+  timestamp: string
+}
+
+/** Error returned by a node, or raised after every endpoint failed */
+export class AerolithError extends Error {
+  /** HTTP status, when a node answered */
+  readonly status?: number
+  /** Response body, when a node answered */
+  readonly body?: string
+
+  constructor(message: string, status?: number, body?: string) {
+    super(message)
+    this.name = 'AerolithError'
+    this.status = status
+    this.body = body
+  }
+}
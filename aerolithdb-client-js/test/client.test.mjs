@@ -0,0 +1,104 @@
+// Runs the built client against the REST routes of a real node
+
+import assert from 'node:assert/strict'
+import { randomUUID } from 'node:crypto'
+import { after, before, test } from 'node:test'
+
+import { AerolithClient, AerolithError } from '../dist/index.js'
+import { deadEndpoint, startServer } from './server.mjs'
+
+let server
+let client
+
+before(async () => {
+  server = await startServer()
+  client = new AerolithClient({ endpoints: server.url, retry: { initialBackoffMs: 1, maxBackoffMs: 5 } })
+})
+
+after(() => server?.stop())
+
+/** A collection no other test writes to */
+function freshCollection() {
+  return client.collection(`js_${randomUUID().replaceAll('-', '')}`)
+}
+
+test('stores, reads and deletes documents by ID', async () => {
+  const users = freshCollection()
+
+  const stored = await users.put('ada', { name: 'Ada', age: 36 })
+  assert.equal(stored.id, 'ada')
+  assert.deepEqual(stored.data, { name: 'Ada', age: 36 })
+  assert.ok(!Number.isNaN(Date.parse(stored.updated_at)))
+
+  const read = await users.get('ada')
+  assert.deepEqual(read?.data, { name: 'Ada', age: 36 })
+
+  await users.put('ada', { name: 'Ada', age: 37 })
+  assert.equal((await users.get('ada'))?.data.age, 37)
+
+  assert.equal(await users.delete('ada'), true)
+  assert.equal(await users.get('ada'), null)
+  assert.equal(await users.delete('ada'), false)
+})
+
+test('creates documents under server-generated IDs', async () => {
+  const users = freshCollection()
+
+  const created = await users.create({ name: 'Grace', age: 45 })
+  assert.ok(created.id.length > 0)
+  assert.deepEqual((await users.get(created.id))?.data, { name: 'Grace', age: 45 })
+})
+
+test('encodes IDs and collection names into the route', async () => {
+  const users = freshCollection()
+
+  await users.put('a/b c?', { name: 'Odd' })
+  assert.deepEqual((await users.get('a/b c?'))?.data, { name: 'Odd' })
+})
+
+test('filters, sorts, pages and projects queries', async () => {
+  const users = freshCollection()
+  const people = [
+    ['ada', 'Ada', 36],
+    ['alan', 'Alan', 41],
+    ['grace', 'Grace', 45],
+    ['kid', 'Kid', 9]
+  ]
+  for (const [id, name, age] of people) {
+    await users.put(id, { name, age, city: 'London' })
+  }
+
+  const adults = await users.find(users.query().filter({ age: { $gte: 18 } }).sort('name', 'desc'))
+  assert.equal(adults.total, 3)
+  assert.deepEqual(adults.documents.map(document => document.data.name), ['Grace', 'Alan', 'Ada'])
+
+  const page = await users.find(users.query().filter({ age: { $gte: 18 } }).sort('age').page(1, 2))
+  assert.equal(page.total, 3)
+  assert.equal(page.limit, 2)
+  assert.equal(page.offset, 2)
+  assert.deepEqual(page.documents.map(document => document.id), ['grace'])
+
+  const names = await users.find(users.query().filter({ name: 'Kid' }).select('name'))
+  assert.deepEqual(names.documents.map(document => document.data), [{ name: 'Kid' }])
+})
+
+test('reports rejected queries with their status', async () => {
+  const users = freshCollection()
+  await users.put('ada', { name: 'Ada' })
+
+  await assert.rejects(
+    users.find(users.query().filter({ name: { $regex: '(' } })),
+    error => error instanceof AerolithError && error.status === 400
+  )
+})
+
+test('fails over to the next endpoint', async () => {
+  const failover = new AerolithClient({
+    endpoints: [await deadEndpoint(), server.url],
+    retry: { initialBackoffMs: 1, maxBackoffMs: 5 }
+  })
+  const users = failover.collection(`js_${randomUUID().replaceAll('-', '')}`)
+
+  await users.put('ada', { name: 'Ada' })
+  assert.deepEqual((await users.get('ada'))?.data, { name: 'Ada' })
+})
@@ -0,0 +1,51 @@
+// A node serving the real REST routes for the client tests: the node at
+// AEROLITHDB_TEST_URL if set, otherwise the `client_test_server` example of
+// aerolithdb-api, built and started with cargo.
+
+import { spawn } from 'node:child_process'
+import { createServer } from 'node:net'
+import { dirname, join } from 'node:path'
+import { createInterface } from 'node:readline'
+import { fileURLToPath } from 'node:url'
+
+const repository = join(dirname(fileURLToPath(import.meta.url)), '..', '..')
+
+// Generous enough for cargo to build the example from scratch
+const STARTUP_TIMEOUT_MS = 15 * 60 * 1000
+
+/** Start a node, resolving to its REST base URL and a function stopping it */
+export async function startServer() {
+  if (process.env.AEROLITHDB_TEST_URL) {
+    return { url: process.env.AEROLITHDB_TEST_URL, stop: () => {} }
+  }
+
+  const child = spawn('cargo', ['run', '--quiet', '-p', 'aerolithdb-api', '--example', 'client_test_server'], {
+    cwd: repository,
+    stdio: ['ignore', 'pipe', 'inherit']
+  })
+  const stop = () => child.kill()
+
+  const url = await new Promise((resolve, reject) => {
+    const timer = setTimeout(() => reject(new Error('client_test_server did not start in time')), STARTUP_TIMEOUT_MS)
+    child.once('error', reject)
+    child.once('exit', code => reject(new Error(`client_test_server exited with ${code}`)))
+    // The first line printed is the base URL, once the API accepts requests
+    createInterface({ input: child.stdout }).once('line', line => {
+      clearTimeout(timer)
+      resolve(line.trim())
+    })
+  }).catch(error => {
+    stop()
+    throw error
+  })
+  return { url, stop }
+}
+
+/** An endpoint nothing listens on */
+export async function deadEndpoint() {
+  const server = createServer()
+  await new Promise(resolve => server.listen(0, '127.0.0.1', resolve))
+  const { port } = server.address()
+  await new Promise(resolve => server.close(resolve))
+  return `http://127.0.0.1:${port}`
+}
@@ -0,0 +1,612 @@
+//! Change streams.
+//!
+//! A change stream delivers the creates, updates, and deletes applied to one
+//! collection over a node's WebSocket API, which listens three ports above
+//! its REST API at `/ws`. Streams are opened with `AerolithClient::watch`
+//! when the client is compiled to `wasm32-unknown-unknown`, where browsers
+//! and Cloudflare Workers provide the `WebSocket` implementation.
+//!
+//! Where WebSocket upgrades are blocked, a socket that closes without ever
+//! opening switches the stream to the REST API's Server-Sent Events at
+//! `/api/v1/collections/{collection}/changes`, read through the host's
+//! `EventSource`. Both carry the same messages and resume tokens.
+//!
+//! When the connection drops, the stream reconnects to the next endpoint,
+//! backing off with the client's retry policy, and resumes from the last
+//! token the node sent so missed events are replayed rather than lost. The
+//! wire format is the one spoken by the TypeScript client.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::query::Filter;
+
+/// What happened to a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A document change delivered by a change stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent<T> {
+    pub collection: String,
+    pub document_id: String,
+    pub action: ChangeAction,
+    /// New document data; `None` for deletions
+    pub data: Option<T>,
+    /// When the node applied the change, if it said
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// Settings of a change stream.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeStreamOptions {
+    /// Only deliver changes to documents matching this filter
+    pub filter: Option<Filter>,
+    /// Resume a stream from a token returned by `ChangeStream::resume_token`
+    pub resume_token: Option<String>,
+}
+
+#[cfg(any(target_arch = "wasm32", test))]
+pub(crate) use protocol::*;
+
+/// Messages exchanged with the WebSocket API and its event stream fallback.
+#[cfg(any(target_arch = "wasm32", test))]
+mod protocol {
+    use anyhow::Result;
+    use chrono::{DateTime, Utc};
+    use serde::de::DeserializeOwned;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    use super::{ChangeAction, ChangeEvent};
+
+    /// Event sent by the node, optionally wrapped in a journal entry.
+    #[derive(Deserialize)]
+    struct WireMessage {
+        #[serde(rename = "type")]
+        kind: Option<String>,
+        collection: Option<String>,
+        document_id: Option<String>,
+        action: Option<String>,
+        #[serde(default)]
+        data: Value,
+        timestamp: Option<String>,
+        resume_token: Option<String>,
+        event: Option<Box<WireMessage>>,
+    }
+
+    /// A message received on a change stream's connection.
+    #[derive(Debug, Default, PartialEq)]
+    pub(crate) struct Received {
+        /// Token for resuming after this message, when the node sent one
+        pub resume_token: Option<String>,
+        /// The change, if the message reported one to `collection`
+        pub event: Option<ChangeEvent<Value>>,
+    }
+
+    /// Parse a text frame, or `None` if it is not JSON of the expected shape.
+    pub(crate) fn parse_message(raw: &str, collection: &str) -> Option<Received> {
+        let mut message: WireMessage = serde_json::from_str(raw).ok()?;
+        let resume_token = message.resume_token.take();
+        let event = match message.event.take() {
+            Some(event) => *event,
+            None => message,
+        };
+
+        let is_change = event.kind.as_deref() == Some("DocumentChanged") && event.collection.as_deref() == Some(collection);
+        let event = match event.document_id {
+            Some(document_id) if is_change => Some(ChangeEvent {
+                collection: collection.to_string(),
+                document_id,
+                action: match event.action.map(|action| action.to_lowercase()).as_deref() {
+                    Some("created") => ChangeAction::Created,
+                    Some("deleted") => ChangeAction::Deleted,
+                    _ => ChangeAction::Updated,
+                },
+                data: (!event.data.is_null()).then_some(event.data),
+                timestamp: event
+                    .timestamp
+                    .and_then(|timestamp| DateTime::parse_from_rfc3339(&timestamp).ok())
+                    .map(|timestamp| timestamp.with_timezone(&Utc)),
+            }),
+            _ => None,
+        };
+        Some(Received { resume_token, event })
+    }
+
+    /// First message on a new connection: resume from `resume_token` if
+    /// there is one, otherwise subscribe to the collection.
+    pub(crate) fn subscribe_message(collection: &str, filter: Option<&Value>, resume_token: Option<&str>) -> String {
+        let request = match resume_token {
+            Some(token) => json!({ "type": "resume", "resume_token": token }),
+            None => json!({ "type": "subscribe", "collection": collection, "query": filter }),
+        };
+        request.to_string()
+    }
+
+    /// WebSocket API of the node behind a REST endpoint.
+    pub(crate) fn websocket_url(endpoint: &str) -> Result<String> {
+        let mut url = reqwest::Url::parse(endpoint)?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| anyhow::anyhow!("Endpoint {} has no port", endpoint))?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .and_then(|_| url.set_port(Some(port.saturating_add(3))))
+            .map_err(|_| anyhow::anyhow!("Endpoint {} cannot be mapped to a WebSocket URL", endpoint))?;
+        url.set_path("/ws");
+        Ok(url.to_string())
+    }
+
+    /// Server-Sent Events stream of a collection's changes on the node
+    /// behind a REST endpoint.
+    pub(crate) fn events_url(endpoint: &str, collection: &str) -> Result<String> {
+        let mut url = reqwest::Url::parse(endpoint)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Endpoint {} cannot be mapped to an event stream URL", endpoint))?
+            .pop_if_empty()
+            .extend(["api", "v1", "collections", collection, "changes"]);
+        Ok(url.to_string())
+    }
+
+    /// Event stream URL subscribing with `filter`, or resuming from
+    /// `resume_token` if there is one.
+    pub(crate) fn events_request_url(events_url: &str, filter: Option<&Value>, resume_token: Option<&str>) -> Result<String> {
+        let mut url = reqwest::Url::parse(events_url)?;
+        match resume_token {
+            Some(token) => {
+                url.query_pairs_mut().append_pair("resume_token", token);
+            }
+            None => {
+                if let Some(filter) = filter {
+                    url.query_pairs_mut().append_pair("filter", &filter.to_string());
+                }
+            }
+        }
+        Ok(url.to_string())
+    }
+
+    impl ChangeEvent<Value> {
+        /// Deserialize the event's data into `T`.
+        pub(crate) fn decode<T: DeserializeOwned>(self) -> Result<ChangeEvent<T>> {
+            let data = self.data.map(serde_json::from_value).transpose().map_err(|e| {
+                anyhow::anyhow!(
+                    "Change to '{}' in collection '{}' does not match the expected type: {}",
+                    self.document_id,
+                    self.collection,
+                    e
+                )
+            })?;
+            Ok(ChangeEvent {
+                collection: self.collection,
+                document_id: self.document_id,
+                action: self.action,
+                data,
+                timestamp: self.timestamp,
+            })
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub use socket::ChangeStream;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use socket::StreamEndpoint;
+
+/// Change stream transport over the host's `WebSocket`, or its
+/// `EventSource` where WebSocket upgrades are blocked.
+#[cfg(target_arch = "wasm32")]
+mod socket {
+    use std::cell::RefCell;
+    use std::fmt;
+    use std::marker::PhantomData;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+
+    use anyhow::Result;
+    use futures::channel::mpsc;
+    use futures::{Stream, StreamExt};
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+    use tracing::{debug, warn};
+    use wasm_bindgen::prelude::*;
+    use web_sys::{EventSource, MessageEvent, WebSocket};
+
+    use super::{events_request_url, parse_message, subscribe_message, ChangeEvent};
+    use crate::client::RetryPolicy;
+    use crate::platform;
+
+    /// Live stream of changes to one collection.
+    ///
+    /// Yields events as they arrive and ends with an error once every
+    /// endpoint failed for the retry policy's attempts. Dropping the stream
+    /// closes its connection.
+    pub struct ChangeStream<T> {
+        state: Rc<RefCell<State>>,
+        events: mpsc::UnboundedReceiver<Result<ChangeEvent<Value>>>,
+        _data: PhantomData<fn() -> T>,
+    }
+
+    /// Where a node serves a collection's change stream.
+    pub(crate) struct StreamEndpoint {
+        /// The node's WebSocket API
+        pub websocket: String,
+        /// The collection's Server-Sent Events stream on the node's REST API
+        pub events: String,
+    }
+
+    /// How the stream reaches the node.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Transport {
+        WebSocket,
+        /// Used once a WebSocket upgrade failed, for the rest of the stream
+        ServerSentEvents,
+    }
+
+    struct State {
+        endpoints: Vec<StreamEndpoint>,
+        collection: String,
+        filter: Option<Value>,
+        retry: RetryPolicy,
+        resume_token: Option<String>,
+        transport: Transport,
+        /// Index into `endpoints` of the endpoint to connect to next
+        endpoint: usize,
+        /// Connections lost since the last one that opened
+        failures: u32,
+        /// Whether the current connection has opened
+        opened: bool,
+        closed: bool,
+        connection: Option<Connection>,
+        events: mpsc::UnboundedSender<Result<ChangeEvent<Value>>>,
+    }
+
+    /// An open connection and the callbacks it invokes, which must outlive it.
+    struct Connection {
+        channel: Channel,
+        _on_open: Closure<dyn FnMut()>,
+        _on_message: Closure<dyn FnMut(MessageEvent)>,
+        _on_close: Closure<dyn FnMut()>,
+    }
+
+    enum Channel {
+        Socket(WebSocket),
+        Events(EventSource),
+    }
+
+    impl Connection {
+        /// Detach the callbacks and close the connection.
+        fn shut(self) {
+            match self.channel {
+                Channel::Socket(socket) => {
+                    socket.set_onopen(None);
+                    socket.set_onmessage(None);
+                    socket.set_onclose(None);
+                    let _ = socket.close();
+                }
+                Channel::Events(source) => {
+                    source.set_onopen(None);
+                    source.set_onmessage(None);
+                    source.set_onerror(None);
+                    source.close();
+                }
+            }
+        }
+    }
+
+    impl<T> ChangeStream<T> {
+        pub(crate) fn open(
+            endpoints: Vec<StreamEndpoint>,
+            collection: String,
+            filter: Option<Value>,
+            retry: RetryPolicy,
+            resume_token: Option<String>,
+        ) -> Self {
+            let (sender, events) = mpsc::unbounded();
+            let state = Rc::new(RefCell::new(State {
+                endpoints,
+                collection,
+                filter,
+                retry,
+                resume_token,
+                transport: Transport::WebSocket,
+                endpoint: 0,
+                failures: 0,
+                opened: false,
+                closed: false,
+                connection: None,
+                events: sender,
+            }));
+            connect(&state);
+            Self { state, events, _data: PhantomData }
+        }
+
+        /// Token for resuming this stream on a new client after a restart.
+        pub fn resume_token(&self) -> Option<String> {
+            self.state.borrow().resume_token.clone()
+        }
+
+        /// Close the connection; events already received are still yielded.
+        pub fn close(&self) {
+            let mut state = self.state.borrow_mut();
+            state.closed = true;
+            state.events.close_channel();
+            if let Some(connection) = state.connection.take() {
+                connection.shut();
+            }
+        }
+    }
+
+    impl<T> Drop for ChangeStream<T> {
+        fn drop(&mut self) {
+            self.close();
+        }
+    }
+
+    impl<T: DeserializeOwned> Stream for ChangeStream<T> {
+        type Item = Result<ChangeEvent<T>>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.events
+                .poll_next_unpin(cx)
+                .map(|event| event.map(|event| event.and_then(ChangeEvent::decode)))
+        }
+    }
+
+    impl<T> fmt::Debug for ChangeStream<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let state = self.state.borrow();
+            f.debug_struct("ChangeStream")
+                .field("collection", &state.collection)
+                .field("resume_token", &state.resume_token)
+                .field("transport", &state.transport)
+                .field("closed", &state.closed)
+                .finish_non_exhaustive()
+        }
+    }
+
+    /// Connect to the current endpoint, replacing any previous connection.
+    fn connect(state: &Rc<RefCell<State>>) {
+        let mut current = state.borrow_mut();
+        if current.closed {
+            return;
+        }
+        if let Some(previous) = current.connection.take() {
+            previous.shut();
+        }
+        current.opened = false;
+
+        let endpoint = &current.endpoints[current.endpoint % current.endpoints.len()];
+        let channel = match current.transport {
+            Transport::WebSocket => WebSocket::new(&endpoint.websocket).map(Channel::Socket).map_err(|e| {
+                warn!("Failed to open change stream socket to {}: {:?}", endpoint.websocket, e);
+            }),
+            Transport::ServerSentEvents => {
+                events_request_url(&endpoint.events, current.filter.as_ref(), current.resume_token.as_deref())
+                    .map_err(|e| warn!("Invalid change stream URL {}: {}", endpoint.events, e))
+                    .and_then(|url| {
+                        EventSource::new(&url).map(Channel::Events).map_err(|e| {
+                            warn!("Failed to open change stream events from {}: {:?}", url, e);
+                        })
+                    })
+            }
+        };
+        let channel = match channel {
+            Ok(channel) => channel,
+            Err(()) => {
+                let transport = current.transport;
+                drop(current);
+                if transport == Transport::WebSocket {
+                    fall_back(state);
+                } else {
+                    reconnect(state);
+                }
+                return;
+            }
+        };
+        debug!(
+            "Change stream for '{}' connecting over {:?} to endpoint {}",
+            current.collection,
+            current.transport,
+            current.endpoint % current.endpoints.len()
+        );
+
+        let weak = Rc::downgrade(state);
+        let on_open = Closure::<dyn FnMut()>::new(move || {
+            let Some(state) = weak.upgrade() else { return };
+            let mut state = state.borrow_mut();
+            state.failures = 0;
+            state.opened = true;
+            // An event stream is subscribed by its URL
+            if let Some(Connection { channel: Channel::Socket(socket), .. }) = &state.connection {
+                let request =
+                    subscribe_message(&state.collection, state.filter.as_ref(), state.resume_token.as_deref());
+                let _ = socket.send_with_str(&request);
+            }
+        });
+
+        let weak = Rc::downgrade(state);
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |message: MessageEvent| {
+            let Some(state) = weak.upgrade() else { return };
+            let Some(raw) = message.data().as_string() else { return };
+            let mut state = state.borrow_mut();
+            let Some(received) = parse_message(&raw, &state.collection) else { return };
+            if received.resume_token.is_some() {
+                state.resume_token = received.resume_token;
+            }
+            if let Some(event) = received.event {
+                let _ = state.events.unbounded_send(Ok(event));
+            }
+        });
+
+        // Errors are always followed by a close event on sockets. Event
+        // sources would retry by themselves, but without failing over, so
+        // any error ends them here.
+        let weak = Rc::downgrade(state);
+        let on_close = Closure::<dyn FnMut()>::new(move || {
+            let Some(state) = weak.upgrade() else { return };
+            let (transport, opened) = {
+                let state = state.borrow();
+                (state.transport, state.opened)
+            };
+            if transport == Transport::WebSocket && !opened {
+                fall_back(&state);
+            } else {
+                reconnect(&state);
+            }
+        });
+
+        match &channel {
+            Channel::Socket(socket) => {
+                socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+                socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+                socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+            }
+            Channel::Events(source) => {
+                source.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+                source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+                source.set_onerror(Some(on_close.as_ref().unchecked_ref()));
+            }
+        }
+        current.connection = Some(Connection {
+            channel,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        });
+    }
+
+    /// Switch to Server-Sent Events after a WebSocket never opened, which is
+    /// what a blocked upgrade looks like, and connect to the same endpoint.
+    fn fall_back(state: &Rc<RefCell<State>>) {
+        let mut current = state.borrow_mut();
+        if current.closed {
+            return;
+        }
+        warn!(
+            "WebSocket upgrade for change stream '{}' failed; falling back to Server-Sent Events",
+            current.collection
+        );
+        current.transport = Transport::ServerSentEvents;
+
+        // As in `reconnect`, the socket is replaced from a separate task
+        let weak = Rc::downgrade(state);
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(state) = weak.upgrade() {
+                connect(&state);
+            }
+        });
+    }
+
+    /// Schedule a connection to the next endpoint, or end the stream once
+    /// every endpoint failed for the retry policy's attempts.
+    fn reconnect(state: &Rc<RefCell<State>>) {
+        let mut current = state.borrow_mut();
+        if current.closed {
+            return;
+        }
+        current.failures += 1;
+        current.endpoint += 1;
+
+        let attempts = current.retry.max_attempts.max(1) as usize * current.endpoints.len();
+        if current.failures as usize >= attempts {
+            warn!("Change stream for '{}' lost every endpoint", current.collection);
+            current.closed = true;
+            let error = anyhow::anyhow!("Change stream for '{}' lost every endpoint", current.collection);
+            let _ = current.events.unbounded_send(Err(error));
+            current.events.close_channel();
+            return;
+        }
+
+        // The previous connection is replaced from a separate task, since
+        // its callbacks may be running now
+        let delay = current.retry.backoff(current.failures - 1);
+        let weak = Rc::downgrade(state);
+        wasm_bindgen_futures::spawn_local(async move {
+            platform::sleep(delay).await;
+            if let Some(state) = weak.upgrade() {
+                connect(&state);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_parse_change_messages() {
+        let plain = r#"{"type":"DocumentChanged","collection":"users","document_id":"ada","action":"Created","data":{"age":36},"timestamp":"2024-01-01T00:00:00Z"}"#;
+        let received = parse_message(plain, "users").unwrap();
+        let event = received.event.unwrap();
+        assert_eq!((event.document_id.as_str(), event.action), ("ada", ChangeAction::Created));
+        assert_eq!(event.timestamp.unwrap().to_rfc3339(), "2024-01-01T00:00:00+00:00");
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct User {
+            age: u32,
+        }
+        assert_eq!(event.decode::<User>().unwrap().data, Some(User { age: 36 }));
+
+        // Journal entries carry a resume token around the event
+        let journaled = r#"{"resume_token":"42","event":{"type":"DocumentChanged","collection":"users","document_id":"ada","action":"Deleted"}}"#;
+        let received = parse_message(journaled, "users").unwrap();
+        assert_eq!(received.resume_token.as_deref(), Some("42"));
+        let event = received.event.unwrap();
+        assert_eq!((event.action, event.data), (ChangeAction::Deleted, None));
+
+        // Other collections, other message types, and malformed frames are skipped
+        assert_eq!(parse_message(plain, "orders").unwrap().event, None);
+        assert_eq!(parse_message(r#"{"type":"Subscribed"}"#, "users").unwrap(), Received::default());
+        assert!(parse_message("not json", "users").is_none());
+
+        let mismatched = parse_message(plain, "users").unwrap().event.unwrap();
+        let error = mismatched.decode::<Vec<String>>().unwrap_err();
+        assert!(error.to_string().contains("'ada' in collection 'users'"));
+    }
+
+    #[test]
+    fn test_subscribe_and_resume_requests() {
+        let filter = json!({"age": {"$gte": 18}});
+        let subscribe: Value = serde_json::from_str(&subscribe_message("users", Some(&filter), None)).unwrap();
+        assert_eq!(subscribe, json!({"type": "subscribe", "collection": "users", "query": filter}));
+
+        let resume: Value = serde_json::from_str(&subscribe_message("users", Some(&filter), Some("42"))).unwrap();
+        assert_eq!(resume, json!({"type": "resume", "resume_token": "42"}));
+    }
+
+    #[test]
+    fn test_event_stream_urls() {
+        let events = events_url("http://node-a:8080", "user events").unwrap();
+        assert_eq!(events, "http://node-a:8080/api/v1/collections/user%20events/changes");
+        assert_eq!(
+            events_url("https://db.example.com/", "users").unwrap(),
+            "https://db.example.com/api/v1/collections/users/changes"
+        );
+
+        let filter = json!({"age": {"$gte": 18}});
+        let subscribe = events_request_url(&events, Some(&filter), None).unwrap();
+        let url = reqwest::Url::parse(&subscribe).unwrap();
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs, vec![("filter".to_string(), filter.to_string())]);
+
+        // Resuming restores the subscription's own filter
+        let resume = events_request_url(&events, Some(&filter), Some("sub-1.node-a:42")).unwrap();
+        assert!(resume.ends_with("/changes?resume_token=sub-1.node-a%3A42"));
+    }
+
+    #[test]
+    fn test_websocket_url_of_endpoint() {
+        assert_eq!(websocket_url("http://node-a:8080").unwrap(), "ws://node-a:8083/ws");
+        assert_eq!(websocket_url("https://db.example.com/api").unwrap(), "wss://db.example.com:446/ws");
+        assert!(websocket_url("not a url").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Typed REST client.
+//!
+//! Documents are serialized from and deserialized into application types
+//! instead of raw `serde_json::Value`, so a schema mismatch surfaces as a
+//! deserialization error naming the offending document.
+//!
+//! A client can be given several node endpoints. Requests go to the last
+//! endpoint that answered and fail over to the next one when a node is
+//! unreachable or reports itself unavailable (502, 503, 504), backing off
+//! exponentially between attempts. Requests that could be applied twice,
+//! such as creating a document with a server-generated ID, are only retried
+//! when the connection could not be established at all. Compiled to
+//! WebAssembly, requests go through the host's `fetch`, which cannot tell
+//! connection failures apart, so such requests are not retried there.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+#[cfg(target_arch = "wasm32")]
+use crate::changes::{events_url, websocket_url, ChangeStream, ChangeStreamOptions, StreamEndpoint};
+use crate::document::AerolithDocument;
+use crate::platform;
+use crate::query::Query;
+
+/// A stored document with its data deserialized into `T`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypedDocument<T> {
+    pub id: String,
+    pub data: T,
+    pub version: u64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// One page of query results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryPage<T> {
+    pub documents: Vec<TypedDocument<T>>,
+    /// Matching documents before limit and offset were applied
+    pub total: usize,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    /// Pass to [`Query::after`] for the next page; absent on the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// How failed requests are retried across a client's endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts per request, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further retry
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry`, counting from zero.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Serialize)]
+struct DocumentBody<'a, T> {
+    data: &'a T,
+}
+
+/// Client for the aerolithsDB REST API.
+#[derive(Debug, Clone)]
+pub struct AerolithClient {
+    http: Client,
+    endpoints: Vec<String>,
+    /// Index of the endpoint that answered most recently, shared by clones
+    preferred: Arc<AtomicUsize>,
+    retry: RetryPolicy,
+}
+
+impl AerolithClient {
+    /// Connect to a node's REST API, e.g. `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>, timeout: Option<Duration>) -> Result<Self> {
+        Self::with_endpoints([base_url], timeout)
+    }
+
+    /// Connect to a cluster through several nodes' REST APIs, failing over
+    /// between them when a node is unavailable. The timeout is ignored on
+    /// WebAssembly, where `fetch` has none.
+    pub fn with_endpoints(
+        endpoints: impl IntoIterator<Item = impl Into<String>>,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let endpoints: Vec<String> = endpoints
+            .into_iter()
+            .map(|endpoint| endpoint.into().trim_end_matches('/').to_string())
+            .collect();
+        if endpoints.is_empty() {
+            return Err(anyhow::anyhow!("At least one endpoint is required"));
+        }
+
+        let http = platform::http_client(timeout)?;
+
+        Ok(Self {
+            http,
+            endpoints,
+            preferred: Arc::new(AtomicUsize::new(0)),
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// Replace the default retry policy.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Run a typed query against a collection.
+    pub async fn query<T: DeserializeOwned>(&self, collection: &str, query: &Query<T>) -> Result<QueryPage<T>> {
+        let path = format!("/api/v1/collections/{}/query", collection);
+        let request = query.to_request()?;
+        debug!("Querying {} with {:?}", collection, request);
+
+        let response = self.send(true, |http, base| http.post(format!("{}{}", base, path)).json(&request)).await?;
+        parse(response, collection).await
+    }
+
+    /// Fetch a document by ID, or `None` if it does not exist.
+    pub async fn get_document<T: DeserializeOwned>(&self, collection: &str, id: &str) -> Result<Option<TypedDocument<T>>> {
+        let path = format!("/api/v1/collections/{}/documents/{}", collection, id);
+        let response = self.send(true, |http, base| http.get(format!("{}{}", base, path))).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        parse(response, collection).await.map(Some)
+    }
+
+    /// Store a document under `id`, replacing any existing version.
+    pub async fn put_document<T: Serialize + DeserializeOwned>(
+        &self,
+        collection: &str,
+        id: &str,
+        data: &T,
+    ) -> Result<TypedDocument<T>> {
+        let path = format!("/api/v1/collections/{}/documents/{}", collection, id);
+        let body = DocumentBody { data };
+        let response = self.send(true, |http, base| http.put(format!("{}{}", base, path)).json(&body)).await?;
+        parse(response, collection).await
+    }
+
+    /// Store a new document with a server-generated ID.
+    pub async fn create_document<T: Serialize + DeserializeOwned>(
+        &self,
+        collection: &str,
+        data: &T,
+    ) -> Result<TypedDocument<T>> {
+        let path = format!("/api/v1/collections/{}/documents", collection);
+        let body = DocumentBody { data };
+        // A retried create could store the document twice under different IDs
+        let response = self.send(false, |http, base| http.post(format!("{}{}", base, path)).json(&body)).await?;
+        parse(response, collection).await
+    }
+
+    /// Store a mapped document in its collection under its own ID.
+    pub async fn save<D: AerolithDocument>(&self, document: &D) -> Result<TypedDocument<D>> {
+        self.put_document(D::COLLECTION, &document.id(), document).await
+    }
+
+    /// Fetch a mapped document from its collection by ID.
+    pub async fn load<D: AerolithDocument>(&self, id: &str) -> Result<Option<TypedDocument<D>>> {
+        self.get_document(D::COLLECTION, id).await
+    }
+
+    /// Run a typed query against the collection a document type maps to.
+    pub async fn find<D: AerolithDocument>(&self, query: &Query<D>) -> Result<QueryPage<D>> {
+        self.query(D::COLLECTION, query).await
+    }
+
+    /// Stream changes to a collection over WebSocket, or Server-Sent Events
+    /// where the upgrade is blocked, starting from the endpoint currently
+    /// answering requests.
+    #[cfg(target_arch = "wasm32")]
+    pub fn watch<T: DeserializeOwned>(&self, collection: &str, options: ChangeStreamOptions) -> Result<ChangeStream<T>> {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let endpoints = self.endpoints[preferred..]
+            .iter()
+            .chain(&self.endpoints[..preferred])
+            .map(|endpoint| {
+                Ok(StreamEndpoint {
+                    websocket: websocket_url(endpoint)?,
+                    events: events_url(endpoint, collection)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let filter = options.filter.as_ref().map(|filter| filter.to_json()).transpose()?;
+        Ok(ChangeStream::open(endpoints, collection.to_string(), filter, self.retry.clone(), options.resume_token))
+    }
+
+    /// Stream changes to the collection a document type maps to.
+    #[cfg(target_arch = "wasm32")]
+    pub fn watch_documents<D: AerolithDocument>(&self, options: ChangeStreamOptions) -> Result<ChangeStream<D>> {
+        self.watch(D::COLLECTION, options)
+    }
+
+    /// Send a request built by `build` for an endpoint's base URL, retrying
+    /// and failing over according to the retry policy. Non-idempotent
+    /// requests are only retried when no connection could be made.
+    async fn send<F>(&self, idempotent: bool, build: F) -> Result<Response>
+    where
+        F: Fn(&Client, &str) -> RequestBuilder,
+    {
+        let attempts = self.retry.max_attempts.max(1);
+        let first = self.preferred.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for attempt in 0..attempts {
+            if attempt > 0 {
+                platform::sleep(self.retry.backoff(attempt - 1)).await;
+            }
+            let index = (first + attempt as usize) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+
+            match build(&self.http, endpoint).send().await {
+                Ok(response) if idempotent && is_unavailable(response.status()) => {
+                    warn!("Endpoint {} is unavailable ({}), failing over", endpoint, response.status());
+                    last_error = Some(anyhow::anyhow!("Endpoint {} returned {}", endpoint, response.status()));
+                }
+                Ok(response) => {
+                    self.preferred.store(index, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                Err(e) if idempotent || platform::is_connect_error(&e) => {
+                    warn!("Request to {} failed, failing over: {}", endpoint, e);
+                    last_error = Some(e.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| anyhow::anyhow!("No endpoint attempted"))
+            .context(format!("Request failed after {} attempt(s)", attempts)))
+    }
+}
+
+fn is_unavailable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+async fn parse<R: DeserializeOwned>(response: Response, collection: &str) -> Result<R> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow::anyhow!("Request to collection '{}' failed with {}: {}", collection, status, body));
+    }
+
+    let body = response.bytes().await?;
+    serde_json::from_slice(&body)
+        .map_err(|e| anyhow::anyhow!("Response from collection '{}' does not match the expected type: {}", collection, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve one canned HTTP response per connection, in order.
+    async fn serve(responses: Vec<(u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 4096];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    /// An endpoint nothing listens on.
+    async fn dead_endpoint() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    const DOCUMENT: &str = r#"{"id":"1","data":{"n":1},"version":1,"created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z"}"#;
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy { max_attempts: 3, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(5) }
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_next_endpoint() {
+        let live = serve(vec![(200, DOCUMENT), (200, DOCUMENT)]).await;
+        let client = AerolithClient::with_endpoints([dead_endpoint().await, live], None)
+            .unwrap()
+            .with_retry_policy(fast_retry());
+
+        let document: TypedDocument<serde_json::Value> = client.get_document("c", "1").await.unwrap().unwrap();
+        assert_eq!(document.data["n"], 1);
+        // The endpoint that answered is tried first from now on
+        assert_eq!(client.preferred.load(Ordering::Relaxed), 1);
+        assert!(client.get_document::<serde_json::Value>("c", "1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retries_unavailable_but_not_unsafe_requests() {
+        let flaky = serve(vec![(503, "{}"), (200, DOCUMENT), (503, "{}")]).await;
+        let client = AerolithClient::new(flaky, None).unwrap().with_retry_policy(fast_retry());
+        assert!(client.get_document::<serde_json::Value>("c", "1").await.unwrap().is_some());
+
+        // A create answered with 503 may have been applied, so it is not retried
+        let error = client.create_document("c", &serde_json::json!({"n": 1})).await.unwrap_err();
+        assert!(error.to_string().contains("503"));
+
+        assert_eq!(fast_retry().backoff(0), Duration::from_millis(1));
+        assert_eq!(fast_retry().backoff(10), Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,227 @@
+//! Mapping Rust types to collections.
+//!
+//! [`AerolithDocument`] ties a type to the collection it is stored in and
+//! to the field holding its document ID, so it can be saved and loaded
+//! without repeating collection names at every call site. Implement it with
+//! `#[derive(AerolithDocument)]`, which can also declare secondary indexes
+//! and a JSON schema derived from the struct's fields.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// A type stored as documents of one collection.
+pub trait AerolithDocument: Serialize + DeserializeOwned {
+    /// Collection holding documents of this type
+    const COLLECTION: &'static str;
+
+    /// Document ID of this value
+    fn id(&self) -> String;
+
+    /// Secondary indexes declared for the collection
+    fn indexes() -> Vec<IndexDefinition> {
+        Vec::new()
+    }
+
+    /// JSON schema documents of this type conform to, if declared
+    fn schema() -> Option<Value> {
+        None
+    }
+
+    /// Serialize into the JSON stored for the document.
+    fn to_document(&self) -> Result<Value> {
+        serde_json::to_value(self)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize document '{}' of '{}': {}", self.id(), Self::COLLECTION, e))
+    }
+
+    /// Deserialize stored JSON into this type.
+    fn from_document(data: Value) -> Result<Self> {
+        serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("Document does not match the '{}' collection type: {}", Self::COLLECTION, e))
+    }
+}
+
+/// A secondary index declared for a collection, in the same shape as the
+/// index definitions accepted by fixture files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    /// Index name; derived from the fields when omitted
+    #[serde(default)]
+    pub name: Option<String>,
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub unique: bool,
+    /// Full-text index over the fields' words, searched with `$text`
+    #[serde(default)]
+    pub text: bool,
+}
+
+impl IndexDefinition {
+    pub fn effective_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| match self.text {
+            true => format!("{}_text", self.fields.join("_")),
+            false => self.fields.join("_"),
+        })
+    }
+}
+
+/// Types with a JSON schema, used to build the schema of derived documents.
+pub trait SchemaType {
+    /// Whether a field of this type must be present in stored documents
+    const REQUIRED: bool = true;
+
+    /// JSON schema describing values of this type
+    fn json_schema() -> Value;
+}
+
+macro_rules! schema_type {
+    ($schema:expr => $($ty:ty),+) => {
+        $(impl SchemaType for $ty {
+            fn json_schema() -> Value {
+                $schema
+            }
+        })+
+    };
+}
+
+schema_type!(json!({"type": "string"}) => String, str, char);
+schema_type!(json!({"type": "boolean"}) => bool);
+schema_type!(json!({"type": "integer"}) => i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+schema_type!(json!({"type": "number"}) => f32, f64);
+schema_type!(json!({}) => Value);
+
+impl<Tz: chrono::TimeZone> SchemaType for chrono::DateTime<Tz> {
+    fn json_schema() -> Value {
+        json!({"type": "string", "format": "date-time"})
+    }
+}
+
+impl<T: SchemaType> SchemaType for Option<T> {
+    const REQUIRED: bool = false;
+
+    fn json_schema() -> Value {
+        json!({"anyOf": [T::json_schema(), {"type": "null"}]})
+    }
+}
+
+impl<T: SchemaType + ?Sized> SchemaType for Box<T> {
+    const REQUIRED: bool = T::REQUIRED;
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+impl<T: SchemaType> SchemaType for Vec<T> {
+    fn json_schema() -> Value {
+        json!({"type": "array", "items": T::json_schema()})
+    }
+}
+
+impl<T: SchemaType, S> SchemaType for HashMap<String, T, S> {
+    fn json_schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::json_schema()})
+    }
+}
+
+impl<T: SchemaType> SchemaType for BTreeMap<String, T> {
+    fn json_schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::json_schema()})
+    }
+}
+
+/// Schema of an object from `(name, schema, required)` properties; used by
+/// the derive macros.
+#[doc(hidden)]
+pub fn object_schema(properties: Vec<(&str, Value, bool)>) -> Value {
+    let required: Vec<&str> = properties.iter().filter(|(_, _, required)| *required).map(|(name, _, _)| *name).collect();
+    let properties: serde_json::Map<String, Value> =
+        properties.into_iter().map(|(name, schema, _)| (name.to_string(), schema)).collect();
+    json!({"type": "object", "properties": properties, "required": required})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AerolithDocument, QueryFields, SchemaType};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, AerolithDocument, QueryFields)]
+    #[aerolith(collection = "users", schema, index(fields(last_name, first_name)))]
+    #[serde(rename_all = "camelCase")]
+    struct User {
+        #[aerolith(id)]
+        user_id: u64,
+        first_name: String,
+        last_name: String,
+        #[aerolith(index(unique))]
+        email: String,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        #[aerolith(index(text))]
+        bio: String,
+        address: Option<Address>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, SchemaType)]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, AerolithDocument)]
+    struct AuditEntry {
+        id: String,
+    }
+
+    fn user() -> User {
+        User {
+            user_id: 7,
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            email: "ada@example.com".to_string(),
+            tags: Vec::new(),
+            bio: String::new(),
+            address: None,
+        }
+    }
+
+    #[test]
+    fn test_collection_id_and_round_trip() {
+        assert_eq!(User::COLLECTION, "users");
+        assert_eq!(AuditEntry::COLLECTION, "audit_entry");
+        assert_eq!(user().id(), "7");
+
+        let document = user().to_document().unwrap();
+        assert_eq!(document["firstName"], "Ada");
+        assert_eq!(User::from_document(document).unwrap(), user());
+        assert!(User::from_document(json!({"userId": "seven"})).is_err());
+
+        assert!(AuditEntry::indexes().is_empty());
+        assert!(AuditEntry::schema().is_none());
+    }
+
+    #[test]
+    fn test_declared_indexes_use_stored_field_names() {
+        let indexes = User::indexes();
+        assert_eq!(indexes.len(), 3);
+        assert_eq!((indexes[0].effective_name(), indexes[0].unique), ("email".to_string(), true));
+        assert_eq!(User::fields().email.path(), indexes[0].fields[0]);
+        assert_eq!((indexes[1].effective_name(), indexes[1].text), ("bio_text".to_string(), true));
+        assert_eq!(indexes[2].fields, vec!["lastName", "firstName"]);
+        assert!(!indexes[2].unique);
+    }
+
+    #[test]
+    fn test_derived_schema() {
+        let schema = User::schema().unwrap();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["userId"], json!({"type": "integer"}));
+        assert_eq!(schema["properties"]["tags"]["items"], json!({"type": "string"}));
+        assert_eq!(schema["properties"]["address"]["anyOf"][0]["properties"]["city"], json!({"type": "string"}));
+        // Optional and defaulted fields may be absent
+        assert_eq!(schema["required"], json!(["userId", "firstName", "lastName", "email"]));
+    }
+}
@@ -0,0 +1,97 @@
+//! # aerolithsDB Client
+//!
+//! Typed Rust client for aerolithsDB nodes.
+//!
+//! ## Features
+//!
+//! - **Typed Query Builder**: Filter combinators, sorting, projection, and
+//!   pagination over compile-time checked field handles
+//! - **Field Derive**: `#[derive(QueryFields)]` generates field handles from a
+//!   struct, honouring its serde renames
+//! - **Document Mapping**: `#[derive(AerolithDocument)]` binds a struct to its
+//!   collection and ID field, with optional index and schema declarations
+//! - **Failover**: Requests retry with backoff across several node endpoints
+//! - **Change Streams**: Live, resumable collection changes over WebSocket,
+//!   or Server-Sent Events where upgrades are blocked (WebAssembly builds)
+//! - **Serde Round-Trip**: Documents are written from and read back into
+//!   application types rather than raw JSON values
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use aerolithdb_client::{AerolithClient, AerolithDocument, QueryFields};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, AerolithDocument, QueryFields)]
+//! #[aerolith(collection = "users")]
+//! struct User {
+//!     id: String,
+//!     #[aerolith(index)]
+//!     name: String,
+//!     age: u32,
+//! }
+//!
+//! let client = AerolithClient::new("http://localhost:8080", None)?;
+//! client.save(&User { id: "ada".into(), name: "Ada".into(), age: 36 }).await?;
+//!
+//! let user = User::fields();
+//! let adults = User::query()
+//!     .filter(user.age.gte(18u32) & !user.name.eq("root"))
+//!     .sort(user.name.asc())
+//!     .page(0, 50);
+//!
+//! for document in client.find(&adults).await?.documents {
+//!     println!("{} is {}", document.data.name, document.data.age);
+//! }
+//! ```
+//!
+//! ## WebAssembly
+//!
+//! The client compiles to `wasm32-unknown-unknown` for browser and Cloudflare
+//! Workers apps, with the same typed API running on the host's event loop:
+//!
+//! ```bash
+//! cargo build -p aerolithdb-client --target wasm32-unknown-unknown
+//! ```
+//!
+//! Requests use the host's `fetch`, which has no per-request timeout and
+//! cannot tell connection failures apart, so documents created with a
+//! server-generated ID are never retried. Change streams use the host's
+//! `WebSocket`, falling back to its `EventSource`, and are only available
+//! in this build:
+//!
+//! ```ignore
+//! use futures::StreamExt;
+//!
+//! let mut changes = client.watch_documents::<User>(ChangeStreamOptions::default())?;
+//! while let Some(change) = changes.next().await {
+//!     let change = change?;
+//!     println!("{} was {:?}", change.document_id, change.action);
+//! }
+//! ```
+
+// Lets the derive macro's `::aerolithdb_client` paths resolve inside this crate
+extern crate self as aerolithdb_client;
+
+pub mod changes;
+pub mod client;
+pub mod document;
+mod platform;
+pub mod query;
+
+pub use aerolithdb_client_derive::{AerolithDocument, QueryFields, SchemaType};
+#[cfg(target_arch = "wasm32")]
+pub use changes::ChangeStream;
+pub use changes::{ChangeAction, ChangeEvent, ChangeStreamOptions};
+pub use client::{AerolithClient, QueryPage, RetryPolicy, TypedDocument};
+pub use document::{AerolithDocument, IndexDefinition, SchemaType};
+pub use query::{
+    Field, Filter, Operator, Projection, Query, QueryFields, QueryRequest, Sort, SortDirection,
+};
+
+/// Support items for the derive macros; not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::document::object_schema;
+    pub use serde_json::Value;
+}
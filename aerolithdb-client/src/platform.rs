@@ -0,0 +1,59 @@
+//! Target-specific pieces of the transport.
+//!
+//! Natively the client runs on Tokio. Compiled to `wasm32-unknown-unknown`
+//! it runs on the host's event loop instead: requests go through `fetch` and
+//! timers through `setTimeout`, which browsers and Cloudflare Workers both
+//! provide as globals.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::Client;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn http_client(timeout: Option<Duration>) -> Result<Client> {
+    Ok(Client::builder().timeout(timeout.unwrap_or(Duration::from_secs(30))).build()?)
+}
+
+/// `fetch` has no timeout of its own; requests end when the host gives up.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn http_client(_timeout: Option<Duration>) -> Result<Client> {
+    Ok(Client::builder().build()?)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+    let timer = js_sys::Promise::new(&mut |resolve, _| {
+        set_timeout(&resolve, millis);
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(timer).await;
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32) -> JsValue;
+}
+
+/// Whether a request failed before reaching the node, so that retrying it
+/// cannot apply it twice.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_connect_error(error: &reqwest::Error) -> bool {
+    error.is_connect()
+}
+
+/// `fetch` reports every network failure alike, so none is known to have
+/// happened before the request was sent.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn is_connect_error(_error: &reqwest::Error) -> bool {
+    false
+}
@@ -0,0 +1,539 @@
+//! Typed query builder.
+//!
+//! Queries are assembled from typed [`Field`] handles, usually generated with
+//! `#[derive(QueryFields)]`, so misspelled field names and mismatched value
+//! types are compile errors rather than empty result sets. The builder
+//! produces the MongoDB-style filter, sort, and pagination body accepted by
+//! the REST query endpoint.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{BitAnd, BitOr, Not};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Types whose fields can be referenced in queries.
+///
+/// Implement with `#[derive(QueryFields)]`, which generates a companion
+/// `<Struct>Fields` struct with one [`Field`] per struct field.
+pub trait QueryFields {
+    /// Struct of typed field handles
+    type Fields;
+
+    /// Field handles for building queries
+    fn fields() -> Self::Fields;
+
+    /// Start a query returning documents of this type.
+    fn query() -> Query<Self>
+    where
+        Self: Sized,
+    {
+        Query::new()
+    }
+}
+
+/// A typed reference to a document field, identified by its dotted path.
+pub struct Field<T> {
+    path: Cow<'static, str>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Field<T> {
+    /// Reference the field at `path`; nested fields use dots (`address.city`).
+    pub fn new(path: impl Into<Cow<'static, str>>) -> Self {
+        Self { path: path.into(), _type: PhantomData }
+    }
+
+    /// Dotted path of the field in stored documents.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reference a field of a nested document, e.g.
+    /// `user.address.join(Address::fields().city)`.
+    pub fn join<U>(&self, child: Field<U>) -> Field<U> {
+        Field::new(format!("{}.{}", self.path, child.path))
+    }
+
+    /// Match documents where the field is present (or absent).
+    pub fn exists(&self, exists: bool) -> Filter {
+        self.condition(Operator::Exists, Ok(Value::Bool(exists)))
+    }
+
+    /// Sort by this field in ascending order.
+    pub fn asc(&self) -> Sort {
+        Sort { field: self.path.to_string(), direction: SortDirection::Ascending }
+    }
+
+    /// Sort by this field in descending order.
+    pub fn desc(&self) -> Sort {
+        Sort { field: self.path.to_string(), direction: SortDirection::Descending }
+    }
+
+    fn condition(&self, operator: Operator, operand: serde_json::Result<Value>) -> Filter {
+        match operand {
+            Ok(operand) => Filter::Condition { field: self.path.to_string(), operator, operand },
+            Err(e) => Filter::Invalid { field: self.path.to_string(), error: e.to_string() },
+        }
+    }
+}
+
+impl<T: Serialize> Field<T> {
+    pub fn eq(&self, value: impl Into<T>) -> Filter {
+        self.compare(Operator::Eq, value)
+    }
+
+    pub fn ne(&self, value: impl Into<T>) -> Filter {
+        self.compare(Operator::Ne, value)
+    }
+
+    pub fn gt(&self, value: impl Into<T>) -> Filter {
+        self.compare(Operator::Gt, value)
+    }
+
+    pub fn gte(&self, value: impl Into<T>) -> Filter {
+        self.compare(Operator::Gte, value)
+    }
+
+    pub fn lt(&self, value: impl Into<T>) -> Filter {
+        self.compare(Operator::Lt, value)
+    }
+
+    pub fn lte(&self, value: impl Into<T>) -> Filter {
+        self.compare(Operator::Lte, value)
+    }
+
+    /// Match documents where the field equals any of `values`.
+    pub fn is_in<V: Into<T>>(&self, values: impl IntoIterator<Item = V>) -> Filter {
+        let values: Vec<T> = values.into_iter().map(Into::into).collect();
+        self.condition(Operator::In, serde_json::to_value(values))
+    }
+
+    /// Match documents where the field equals none of `values`.
+    pub fn not_in<V: Into<T>>(&self, values: impl IntoIterator<Item = V>) -> Filter {
+        let values: Vec<T> = values.into_iter().map(Into::into).collect();
+        self.condition(Operator::Nin, serde_json::to_value(values))
+    }
+
+    fn compare(&self, operator: Operator, value: impl Into<T>) -> Filter {
+        self.condition(operator, serde_json::to_value(value.into()))
+    }
+}
+
+impl Field<String> {
+    /// Match documents where the field matches a regular expression.
+    pub fn matches(&self, pattern: &str) -> Filter {
+        self.condition(Operator::Regex, Ok(Value::String(pattern.to_string())))
+    }
+}
+
+// Manual impls so field handles are cloneable and printable whatever `T` is
+
+impl<T> Clone for Field<T> {
+    fn clone(&self) -> Self {
+        Self { path: self.path.clone(), _type: PhantomData }
+    }
+}
+
+impl<T> fmt::Debug for Field<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Field").field(&self.path).finish()
+    }
+}
+
+/// Comparison operators understood by the query engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    Nin,
+    Regex,
+    Exists,
+}
+
+impl Operator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Eq => "$eq",
+            Operator::Ne => "$ne",
+            Operator::Gt => "$gt",
+            Operator::Gte => "$gte",
+            Operator::Lt => "$lt",
+            Operator::Lte => "$lte",
+            Operator::In => "$in",
+            Operator::Nin => "$nin",
+            Operator::Regex => "$regex",
+            Operator::Exists => "$exists",
+        }
+    }
+}
+
+/// A document filter, combined with `&`, `|`, and `!` or the equivalent methods.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Compare one field against an operand
+    Condition { field: String, operator: Operator, operand: Value },
+    /// All filters must match
+    And(Vec<Filter>),
+    /// At least one filter must match
+    Or(Vec<Filter>),
+    /// The filter must not match
+    Not(Box<Filter>),
+    /// Full-text search through the collection's text index; `-term`
+    /// excludes documents containing the term
+    Text { search: String },
+    /// A condition whose operand could not be serialized; reported when the
+    /// query is built
+    Invalid { field: String, error: String },
+}
+
+impl Filter {
+    /// Search the collection's text index for `search`, ranking matches by
+    /// relevance unless the query sorts otherwise.
+    pub fn text(search: impl Into<String>) -> Filter {
+        Filter::Text { search: search.into() }
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+
+    pub fn negate(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+
+    /// Render the filter in the query engine's MongoDB-style JSON format.
+    pub fn to_json(&self) -> Result<Value> {
+        Ok(match self {
+            Filter::Condition { field, operator, operand } => {
+                let mut condition = Map::new();
+                condition.insert(operator.as_str().to_string(), operand.clone());
+                let mut filter = Map::new();
+                filter.insert(field.clone(), Value::Object(condition));
+                Value::Object(filter)
+            }
+            Filter::And(filters) => logical("$and", filters)?,
+            Filter::Or(filters) => logical("$or", filters)?,
+            Filter::Not(filter) => serde_json::json!({ "$not": filter.to_json()? }),
+            Filter::Text { search } => serde_json::json!({ "$text": { "$search": search } }),
+            Filter::Invalid { field, error } => {
+                return Err(anyhow::anyhow!("Invalid operand for field '{}': {}", field, error));
+            }
+        })
+    }
+}
+
+fn logical(operator: &str, filters: &[Filter]) -> Result<Value> {
+    let filters = filters.iter().map(Filter::to_json).collect::<Result<Vec<_>>>()?;
+    let mut filter = Map::new();
+    filter.insert(operator.to_string(), Value::Array(filters));
+    Ok(Value::Object(filter))
+}
+
+impl BitAnd for Filter {
+    type Output = Filter;
+
+    fn bitand(self, other: Filter) -> Filter {
+        self.and(other)
+    }
+}
+
+impl BitOr for Filter {
+    type Output = Filter;
+
+    fn bitor(self, other: Filter) -> Filter {
+        self.or(other)
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        self.negate()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// One sort key, created with [`Field::asc`] or [`Field::desc`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sort {
+    pub field: String,
+    pub direction: SortDirection,
+}
+
+/// Fields to return from matching documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Projection {
+    /// Return only these fields
+    Include(Vec<String>),
+    /// Return everything except these fields
+    Exclude(Vec<String>),
+}
+
+/// Wire format of a query sent to the REST query endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryRequest {
+    pub filter: Option<Value>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub sort: Option<Value>,
+    /// Omitted when unset, for nodes that do not support projection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<Value>,
+    /// `next_cursor` of the previous page, to continue after it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// Builder for queries returning documents of type `T`.
+pub struct Query<T> {
+    filter: Option<Filter>,
+    sort: Vec<Sort>,
+    projection: Option<Projection>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    cursor: Option<String>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Query<T> {
+    pub fn new() -> Self {
+        Self {
+            filter: None,
+            sort: Vec::new(),
+            projection: None,
+            limit: None,
+            offset: None,
+            cursor: None,
+            _type: PhantomData,
+        }
+    }
+
+    /// Add a filter; repeated calls must all match.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter.take() {
+            Some(existing) => existing.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    /// Add a sort key. With several keys the query engine currently applies
+    /// them in field-name order rather than the order they were added.
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort.push(sort);
+        self
+    }
+
+    /// Return only the given fields.
+    pub fn select<F>(mut self, fields: impl IntoIterator<Item = Field<F>>) -> Self {
+        self.projection = Some(Projection::Include(fields.into_iter().map(|f| f.path().to_string()).collect()));
+        self
+    }
+
+    /// Return everything except the given fields.
+    pub fn exclude<F>(mut self, fields: impl IntoIterator<Item = Field<F>>) -> Self {
+        self.projection = Some(Projection::Exclude(fields.into_iter().map(|f| f.path().to_string()).collect()));
+        self
+    }
+
+    /// Set the projection directly, e.g. to mix fields of different types.
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Continue after the page that returned `cursor` as its
+    /// `next_cursor`. Unlike an offset, this is not shifted by documents
+    /// written between pages; the sort must stay the same.
+    pub fn after(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    /// Select a zero-based page of `per_page` documents.
+    pub fn page(self, page: usize, per_page: usize) -> Self {
+        self.offset(page.saturating_mul(per_page)).limit(per_page)
+    }
+
+    /// Build the request body for the REST query endpoint.
+    pub fn to_request(&self) -> Result<QueryRequest> {
+        let sort = (!self.sort.is_empty()).then(|| {
+            let keys = self.sort.iter().map(|sort| {
+                let direction = match sort.direction {
+                    SortDirection::Ascending => 1,
+                    SortDirection::Descending => -1,
+                };
+                (sort.field.clone(), Value::from(direction))
+            });
+            Value::Object(keys.collect())
+        });
+
+        let projection = self.projection.as_ref().map(|projection| {
+            let (fields, flag) = match projection {
+                Projection::Include(fields) => (fields, 1),
+                Projection::Exclude(fields) => (fields, 0),
+            };
+            Value::Object(fields.iter().map(|field| (field.clone(), Value::from(flag))).collect())
+        });
+
+        Ok(QueryRequest {
+            filter: self.filter.as_ref().map(Filter::to_json).transpose()?,
+            limit: self.limit,
+            offset: self.offset,
+            sort,
+            projection,
+            cursor: self.cursor.clone(),
+        })
+    }
+}
+
+impl<T> Default for Query<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for Query<T> {
+    fn clone(&self) -> Self {
+        Self {
+            filter: self.filter.clone(),
+            sort: self.sort.clone(),
+            projection: self.projection.clone(),
+            limit: self.limit,
+            offset: self.offset,
+            cursor: self.cursor.clone(),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Query<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Query")
+            .field("filter", &self.filter)
+            .field("sort", &self.sort)
+            .field("projection", &self.projection)
+            .field("limit", &self.limit)
+            .field("offset", &self.offset)
+            .field("cursor", &self.cursor)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QueryFields;
+    use serde_json::json;
+
+    #[derive(Debug, Serialize, Deserialize, QueryFields)]
+    #[serde(rename_all = "camelCase")]
+    struct User {
+        display_name: String,
+        age: u32,
+        #[serde(rename = "addr")]
+        address: Address,
+        #[serde(skip)]
+        #[allow(dead_code)]
+        session: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, QueryFields)]
+    struct Address {
+        city: String,
+    }
+
+    #[test]
+    fn test_derived_fields_follow_serde_names() {
+        let user = User::fields();
+        assert_eq!(user.display_name.path(), "displayName");
+        assert_eq!(user.age.path(), "age");
+        assert_eq!(user.address.join(Address::fields().city).path(), "addr.city");
+    }
+
+    #[test]
+    fn test_filter_combinators_render_engine_json() {
+        let user = User::fields();
+        let filter = (user.age.gte(18u32) & user.display_name.matches("^A")) | !user.age.is_in([1u32, 2]);
+
+        assert_eq!(filter.to_json().unwrap(), json!({
+            "$or": [
+                {"$and": [{"age": {"$gte": 18}}, {"displayName": {"$regex": "^A"}}]},
+                {"$not": {"age": {"$in": [1, 2]}}}
+            ]
+        }));
+    }
+
+    #[test]
+    fn test_query_request_with_sort_projection_and_paging() {
+        let user = User::fields();
+        let request = User::query()
+            .filter(user.display_name.eq("ada"))
+            .filter(user.age.exists(true))
+            .sort(user.age.desc())
+            .select([user.display_name.clone()])
+            .page(2, 25)
+            .to_request()
+            .unwrap();
+
+        assert_eq!(request.filter, Some(json!({
+            "$and": [{"displayName": {"$eq": "ada"}}, {"age": {"$exists": true}}]
+        })));
+        assert_eq!(request.sort, Some(json!({"age": -1})));
+        assert_eq!(request.projection, Some(json!({"displayName": 1})));
+        assert_eq!((request.offset, request.limit), (Some(50), Some(25)));
+
+        let search = User::query().filter(Filter::text("rust -java")).filter(user.age.gte(18u32)).to_request().unwrap();
+        assert_eq!(search.filter, Some(json!({
+            "$and": [{"$text": {"$search": "rust -java"}}, {"age": {"$gte": 18}}]
+        })));
+
+        let empty = serde_json::to_value(Query::<User>::new().to_request().unwrap()).unwrap();
+        assert!(empty.get("projection").is_none());
+        assert!(empty.get("cursor").is_none());
+
+        let next = User::query().sort(user.age.desc()).limit(25).after("abc").to_request().unwrap();
+        assert_eq!(next.cursor.as_deref(), Some("abc"));
+    }
+}
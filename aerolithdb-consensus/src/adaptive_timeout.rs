@@ -0,0 +1,240 @@
+//! Adaptive consensus timeouts driven by observed peer round-trip times.
+//!
+//! Static timeouts are either too aggressive for WAN clusters (causing spurious
+//! leader elections and aborted rounds on jittery links) or too lax for LAN
+//! clusters (slowing failure detection). This module keeps a sliding window of
+//! RTT samples per peer and derives round and election timeouts from the tail
+//! latency of the slowest peers required for a quorum, clamped to configured
+//! bounds.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::types::PeerId;
+
+/// Number of RTT samples retained per peer.
+const SAMPLE_WINDOW: usize = 128;
+
+/// Minimum samples required before a peer influences the computed timeouts.
+const MIN_SAMPLES: usize = 5;
+
+/// Round timeout as a multiple of the p99 RTT (covers propose + vote exchanges).
+const ROUND_RTT_MULTIPLIER: u32 = 4;
+
+/// Election timeout as a multiple of the p99 RTT (must comfortably exceed heartbeats).
+const ELECTION_RTT_MULTIPLIER: u32 = 10;
+
+/// Tracks per-peer RTT percentiles and derives consensus timeouts from them.
+pub struct AdaptiveTimeoutManager {
+    /// Whether timeouts adapt at all; when false the base timeout is always used
+    enabled: bool,
+
+    /// Configured timeout used until enough samples have been collected
+    base_timeout: Duration,
+
+    /// Lower bound for any derived timeout
+    min_timeout: Duration,
+
+    /// Upper bound for any derived timeout
+    max_timeout: Duration,
+
+    /// Recent RTT samples per peer, oldest first
+    samples: RwLock<HashMap<PeerId, VecDeque<Duration>>>,
+}
+
+/// RTT percentiles observed for a single peer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerRttStats {
+    pub peer_id: PeerId,
+    pub samples: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+}
+
+/// Snapshot of the currently effective timeouts and the data behind them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeoutSnapshot {
+    pub adaptive: bool,
+    pub round_timeout: Duration,
+    pub election_timeout: Duration,
+    pub peers: Vec<PeerRttStats>,
+}
+
+impl AdaptiveTimeoutManager {
+    /// Create a manager using `base_timeout` until samples are available.
+    pub fn new(enabled: bool, base_timeout: Duration, min_timeout: Duration, max_timeout: Duration) -> Self {
+        let max_timeout = max_timeout.max(min_timeout);
+        Self {
+            enabled,
+            base_timeout: base_timeout.clamp(min_timeout, max_timeout),
+            min_timeout,
+            max_timeout,
+            samples: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record an RTT sample for a peer.
+    pub fn record_rtt(&self, peer_id: &PeerId, rtt: Duration) {
+        let mut samples = self.samples.write().unwrap();
+        let window = samples.entry(peer_id.clone()).or_default();
+        if window.len() >= SAMPLE_WINDOW {
+            window.pop_front();
+        }
+        window.push_back(rtt);
+    }
+
+    /// Forget all samples for a peer (e.g. after it leaves the cluster).
+    pub fn remove_peer(&self, peer_id: &PeerId) {
+        self.samples.write().unwrap().remove(peer_id);
+    }
+
+    /// RTT percentiles for a peer, if enough samples have been collected.
+    pub fn peer_stats(&self, peer_id: &PeerId) -> Option<PeerRttStats> {
+        let samples = self.samples.read().unwrap();
+        samples.get(peer_id).and_then(|window| Self::compute_stats(peer_id, window))
+    }
+
+    /// Timeout for a single consensus round (proposal through commit).
+    pub fn round_timeout(&self) -> Duration {
+        self.derive_timeout(ROUND_RTT_MULTIPLIER)
+    }
+
+    /// Timeout before a missing leader triggers a new election / view change.
+    pub fn election_timeout(&self) -> Duration {
+        self.derive_timeout(ELECTION_RTT_MULTIPLIER)
+    }
+
+    /// Snapshot of effective timeouts and per-peer RTT statistics.
+    pub fn snapshot(&self) -> TimeoutSnapshot {
+        let peers = {
+            let samples = self.samples.read().unwrap();
+            let mut peers: Vec<PeerRttStats> = samples
+                .iter()
+                .filter_map(|(peer_id, window)| Self::compute_stats(peer_id, window))
+                .collect();
+            peers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+            peers
+        };
+
+        TimeoutSnapshot {
+            adaptive: self.enabled,
+            round_timeout: self.round_timeout(),
+            election_timeout: self.election_timeout(),
+            peers,
+        }
+    }
+
+    /// Derive a timeout from the p99 RTT of the slowest peer needed for a quorum.
+    ///
+    /// Using the quorum peer rather than the single slowest peer means one
+    /// badly lagging node cannot inflate timeouts for the whole cluster.
+    fn derive_timeout(&self, multiplier: u32) -> Duration {
+        if !self.enabled {
+            return self.base_timeout;
+        }
+
+        let mut p99s: Vec<Duration> = {
+            let samples = self.samples.read().unwrap();
+            samples
+                .iter()
+                .filter_map(|(peer_id, window)| Self::compute_stats(peer_id, window))
+                .map(|stats| stats.p99)
+                .collect()
+        };
+
+        if p99s.is_empty() {
+            return self.base_timeout;
+        }
+
+        p99s.sort();
+        let quorum_index = (p99s.len() * 2) / 3;
+        let quorum_rtt = p99s[quorum_index.min(p99s.len() - 1)];
+
+        let timeout = (quorum_rtt * multiplier).clamp(self.min_timeout, self.max_timeout);
+        debug!("Derived consensus timeout {:?} from quorum p99 RTT {:?}", timeout, quorum_rtt);
+        timeout
+    }
+
+    fn compute_stats(peer_id: &PeerId, window: &VecDeque<Duration>) -> Option<PeerRttStats> {
+        if window.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = window.iter().copied().collect();
+        sorted.sort();
+
+        Some(PeerRttStats {
+            peer_id: peer_id.clone(),
+            samples: sorted.len(),
+            p50: percentile(&sorted, 0.50),
+            p90: percentile(&sorted, 0.90),
+            p99: percentile(&sorted, 0.99),
+        })
+    }
+}
+
+/// Nearest-rank percentile over an already sorted slice.
+fn percentile(sorted: &[Duration], quantile: f64) -> Duration {
+    let rank = ((sorted.len() as f64) * quantile).ceil() as usize;
+    sorted[rank.saturating_sub(1).min(sorted.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> AdaptiveTimeoutManager {
+        AdaptiveTimeoutManager::new(
+            true,
+            Duration::from_secs(30),
+            Duration::from_millis(200),
+            Duration::from_secs(60),
+        )
+    }
+
+    #[test]
+    fn test_base_timeout_used_without_samples() {
+        let timeouts = manager();
+        assert_eq!(timeouts.round_timeout(), Duration::from_secs(30));
+
+        timeouts.record_rtt(&"peer1".to_string(), Duration::from_millis(10));
+        assert_eq!(timeouts.round_timeout(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_timeouts_follow_rtt_within_bounds() {
+        let timeouts = manager();
+        for _ in 0..10 {
+            timeouts.record_rtt(&"lan".to_string(), Duration::from_millis(1));
+        }
+        // 4 x 1ms is below the floor
+        assert_eq!(timeouts.round_timeout(), Duration::from_millis(200));
+
+        let wan = manager();
+        for _ in 0..10 {
+            wan.record_rtt(&"wan".to_string(), Duration::from_millis(150));
+        }
+        assert_eq!(wan.round_timeout(), Duration::from_millis(600));
+        assert_eq!(wan.election_timeout(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_disabled_manager_uses_base_timeout() {
+        let timeouts = AdaptiveTimeoutManager::new(
+            false,
+            Duration::from_secs(5),
+            Duration::from_millis(200),
+            Duration::from_secs(60),
+        );
+        for _ in 0..10 {
+            timeouts.record_rtt(&"peer1".to_string(), Duration::from_millis(150));
+        }
+        assert_eq!(timeouts.round_timeout(), Duration::from_secs(5));
+        assert!(timeouts.peer_stats(&"peer1".to_string()).is_some());
+    }
+}
@@ -0,0 +1,315 @@
+//! Proposal batching and pipelining for the consensus engine.
+//!
+//! Operations submitted through `ConsensusEngine::submit_operation` are not
+//! proposed one by one. Instead they are collected for a short window (or until
+//! `max_batch_size` operations are waiting) and proposed together as a single
+//! `Operation::Batch`, amortizing signing, broadcast, and voting costs.
+//!
+//! Pipelining allows several batches to be in flight at the same time: a new
+//! batch can be proposed while earlier rounds are still collecting votes, up to
+//! `pipeline_depth` concurrent rounds. Once the limit is reached, further
+//! flushes wait for an in-flight round to commit or abort, which provides
+//! natural backpressure under overload.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+
+use crate::types::{Operation, ProposalId};
+
+/// Collects operations into batches and bounds the number of in-flight rounds.
+pub struct ProposalBatcher {
+    /// Maximum number of operations grouped into a single proposal
+    max_batch_size: usize,
+
+    /// How long the oldest pending operation may wait before a flush
+    batch_window: Duration,
+
+    /// Operations waiting to be proposed
+    pending: Mutex<Vec<PendingOperation>>,
+
+    /// Limits how many batches may be awaiting consensus concurrently
+    pipeline: Arc<Semaphore>,
+
+    /// Batches that have been proposed but not yet committed or aborted
+    inflight: DashMap<ProposalId, InflightBatch>,
+
+    /// Aggregated batching statistics
+    metrics: Mutex<BatchMetrics>,
+}
+
+/// An operation waiting to be included in the next batch.
+struct PendingOperation {
+    operation: Operation,
+    enqueued_at: Instant,
+    responder: oneshot::Sender<ProposalId>,
+}
+
+/// A proposed batch holding its pipeline slot until completion.
+struct InflightBatch {
+    proposed_at: Instant,
+    _permit: OwnedSemaphorePermit,
+}
+
+/// A batch drained from the pending queue, ready to be proposed.
+pub struct ReadyBatch {
+    operations: Vec<Operation>,
+    responders: Vec<oneshot::Sender<ProposalId>>,
+}
+
+/// Batching and pipelining statistics exposed for monitoring.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchMetrics {
+    /// Number of batches proposed
+    pub batches_proposed: u64,
+    /// Total number of operations carried by proposed batches
+    pub operations_batched: u64,
+    /// Average fraction of `max_batch_size` filled per batch (0.0 - 1.0)
+    pub average_fill_rate: f64,
+    /// Average time operations spent queued before being proposed
+    pub average_queue_delay_ms: f64,
+    /// Number of batches that reached commit
+    pub batches_committed: u64,
+    /// Number of batches that were aborted or timed out
+    pub batches_aborted: u64,
+    /// Average time from proposal to commit
+    pub average_commit_latency_ms: f64,
+    /// Slowest observed proposal-to-commit latency
+    pub max_commit_latency_ms: f64,
+    /// Batches currently awaiting consensus
+    pub inflight_batches: usize,
+}
+
+impl ProposalBatcher {
+    /// Create a new batcher with the given batch size, window, and pipeline depth.
+    pub fn new(max_batch_size: usize, batch_window: Duration, pipeline_depth: usize) -> Self {
+        Self {
+            max_batch_size: max_batch_size.max(1),
+            batch_window,
+            pending: Mutex::new(Vec::new()),
+            pipeline: Arc::new(Semaphore::new(pipeline_depth.max(1))),
+            inflight: DashMap::new(),
+            metrics: Mutex::new(BatchMetrics::default()),
+        }
+    }
+
+    /// Time window after which a partially filled batch is flushed.
+    pub fn batch_window(&self) -> Duration {
+        self.batch_window
+    }
+
+    /// Queue an operation for the next batch.
+    ///
+    /// Returns `true` when the pending queue has reached `max_batch_size` and
+    /// should be flushed immediately rather than waiting for the window.
+    pub fn enqueue(&self, operation: Operation, responder: oneshot::Sender<ProposalId>) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(PendingOperation {
+            operation,
+            enqueued_at: Instant::now(),
+            responder,
+        });
+        pending.len() >= self.max_batch_size
+    }
+
+    /// Whether the oldest pending operation has waited longer than the batch window.
+    pub fn window_elapsed(&self) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .first()
+            .map(|op| op.enqueued_at.elapsed() >= self.batch_window)
+            .unwrap_or(false)
+    }
+
+    /// Drain up to `max_batch_size` pending operations into a ready batch.
+    pub fn take_batch(&self) -> Option<ReadyBatch> {
+        let drained: Vec<PendingOperation> = {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.is_empty() {
+                return None;
+            }
+            let count = pending.len().min(self.max_batch_size);
+            pending.drain(..count).collect()
+        };
+
+        let now = Instant::now();
+        let queue_delay_ms: f64 = drained
+            .iter()
+            .map(|op| now.duration_since(op.enqueued_at).as_secs_f64() * 1000.0)
+            .sum::<f64>()
+            / drained.len() as f64;
+
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            let fill_rate = drained.len() as f64 / self.max_batch_size as f64;
+            let n = metrics.batches_proposed as f64;
+            metrics.average_fill_rate = (metrics.average_fill_rate * n + fill_rate) / (n + 1.0);
+            metrics.average_queue_delay_ms =
+                (metrics.average_queue_delay_ms * n + queue_delay_ms) / (n + 1.0);
+            metrics.batches_proposed += 1;
+            metrics.operations_batched += drained.len() as u64;
+        }
+
+        let mut operations = Vec::with_capacity(drained.len());
+        let mut responders = Vec::with_capacity(drained.len());
+        for op in drained {
+            operations.push(op.operation);
+            responders.push(op.responder);
+        }
+
+        Some(ReadyBatch { operations, responders })
+    }
+
+    /// Wait for a free pipeline slot before proposing another batch.
+    pub async fn acquire_pipeline_slot(&self) -> Result<OwnedSemaphorePermit> {
+        Arc::clone(&self.pipeline)
+            .acquire_owned()
+            .await
+            .map_err(|_| anyhow::anyhow!("Consensus pipeline has been closed"))
+    }
+
+    /// Record a proposed batch so its slot is held until it completes.
+    pub fn track_inflight(&self, proposal_id: ProposalId, permit: OwnedSemaphorePermit) {
+        self.inflight.insert(
+            proposal_id,
+            InflightBatch {
+                proposed_at: Instant::now(),
+                _permit: permit,
+            },
+        );
+    }
+
+    /// Mark a batch as committed or aborted, releasing its pipeline slot.
+    pub fn complete(&self, proposal_id: &ProposalId, committed: bool) {
+        if let Some((_, batch)) = self.inflight.remove(proposal_id) {
+            let latency_ms = batch.proposed_at.elapsed().as_secs_f64() * 1000.0;
+            let mut metrics = self.metrics.lock().unwrap();
+            if committed {
+                let n = metrics.batches_committed as f64;
+                metrics.average_commit_latency_ms =
+                    (metrics.average_commit_latency_ms * n + latency_ms) / (n + 1.0);
+                metrics.max_commit_latency_ms = metrics.max_commit_latency_ms.max(latency_ms);
+                metrics.batches_committed += 1;
+            } else {
+                metrics.batches_aborted += 1;
+            }
+        }
+    }
+
+    /// Release slots held by batches whose proposals no longer exist (e.g. timed out).
+    pub fn release_stale<F>(&self, is_active: F)
+    where
+        F: Fn(&ProposalId) -> bool,
+    {
+        let stale: Vec<ProposalId> = self
+            .inflight
+            .iter()
+            .filter(|entry| !is_active(entry.key()))
+            .map(|entry| *entry.key())
+            .collect();
+
+        for proposal_id in stale {
+            self.complete(&proposal_id, false);
+        }
+    }
+
+    /// Snapshot of the current batching statistics.
+    pub fn metrics(&self) -> BatchMetrics {
+        let mut metrics = self.metrics.lock().unwrap().clone();
+        metrics.inflight_batches = self.inflight.len();
+        metrics
+    }
+}
+
+impl ReadyBatch {
+    /// Number of operations in this batch.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Whether the batch carries no operations.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Split the batch into the operation to propose and the waiting submitters.
+    ///
+    /// A batch of one is proposed as the bare operation to avoid wrapping overhead.
+    pub fn into_proposal(mut self) -> (Operation, Vec<oneshot::Sender<ProposalId>>) {
+        let operation = if self.operations.len() == 1 {
+            self.operations.pop().unwrap()
+        } else {
+            Operation::Batch { operations: self.operations }
+        };
+        (operation, self.responders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(id: &str) -> Operation {
+        Operation::Insert {
+            collection: "users".to_string(),
+            document_id: id.to_string(),
+            data: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn test_batch_flushes_at_max_size() {
+        let batcher = ProposalBatcher::new(3, Duration::from_secs(1), 2);
+
+        let (tx, _rx) = oneshot::channel();
+        assert!(!batcher.enqueue(insert("a"), tx));
+        let (tx, _rx) = oneshot::channel();
+        assert!(!batcher.enqueue(insert("b"), tx));
+        let (tx, _rx) = oneshot::channel();
+        assert!(batcher.enqueue(insert("c"), tx));
+
+        let batch = batcher.take_batch().unwrap();
+        assert_eq!(batch.len(), 3);
+        let (operation, responders) = batch.into_proposal();
+        assert!(matches!(operation, Operation::Batch { ref operations } if operations.len() == 3));
+        assert_eq!(responders.len(), 3);
+        assert!(batcher.take_batch().is_none());
+
+        let metrics = batcher.metrics();
+        assert_eq!(metrics.batches_proposed, 1);
+        assert_eq!(metrics.operations_batched, 3);
+        assert!((metrics.average_fill_rate - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_single_operation_is_not_wrapped() {
+        let batcher = ProposalBatcher::new(10, Duration::from_millis(5), 2);
+        let (tx, _rx) = oneshot::channel();
+        batcher.enqueue(insert("a"), tx);
+
+        let (operation, _) = batcher.take_batch().unwrap().into_proposal();
+        assert!(matches!(operation, Operation::Insert { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_slots_released_on_completion() {
+        let batcher = ProposalBatcher::new(10, Duration::from_millis(5), 1);
+
+        let permit = batcher.acquire_pipeline_slot().await.unwrap();
+        let proposal_id = uuid::Uuid::new_v4();
+        batcher.track_inflight(proposal_id, permit);
+        assert_eq!(batcher.metrics().inflight_batches, 1);
+        assert!(batcher.pipeline.try_acquire().is_err());
+
+        batcher.complete(&proposal_id, true);
+        let metrics = batcher.metrics();
+        assert_eq!(metrics.inflight_batches, 0);
+        assert_eq!(metrics.batches_committed, 1);
+        assert!(batcher.pipeline.try_acquire().is_ok());
+    }
+}
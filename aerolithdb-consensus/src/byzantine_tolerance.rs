@@ -1,7 +1,8 @@
 use anyhow::Result;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use tracing::{debug, warn, error};
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 /// Byzantine fault tolerance system
 pub struct ByzantineFaultTolerance {
@@ -10,6 +11,8 @@ pub struct ByzantineFaultTolerance {
     node_reputation: HashMap<String, NodeReputation>,
     fault_detector: FaultDetector,
     recovery_manager: RecoveryManager,
+    quarantined_nodes: HashMap<String, QuarantineRecord>,
+    equivocation_detector: EquivocationDetector,
 }
 
 /// Node reputation tracking
@@ -29,6 +32,35 @@ pub struct FaultDetector {
     detection_threshold: f32,
     observation_window: std::time::Duration,
     message_history: HashMap<String, Vec<MessageRecord>>,
+    fault_history: HashMap<String, Vec<FaultEvidence>>,
+}
+
+/// A signed statement a node made about one slot, such as its vote on a
+/// proposal or its proposal for a round
+///
+/// A node signs at most one statement per slot; two signed statements with
+/// different digests for the same slot prove it equivocated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedStatement {
+    pub signer: String,
+    /// What was signed, e.g. `vote` or `proposal`
+    pub kind: String,
+    /// What the statement is about, e.g. a proposal id or a round
+    pub slot: String,
+    /// Hash of the signed content
+    pub digest: String,
+    pub signature: String,
+    pub observed_at: DateTime<Utc>,
+}
+
+/// Detector of nodes signing conflicting statements for the same slot
+///
+/// The first statement of each signer, kind and slot is remembered for the
+/// observation window, oldest forgotten first.
+pub struct EquivocationDetector {
+    observation_window: std::time::Duration,
+    statements: HashMap<(String, String, String), SignedStatement>,
+    order: VecDeque<(String, String, String)>,
 }
 
 /// Recovery manager for handling Byzantine failures
@@ -56,8 +88,39 @@ pub enum RecoveryStrategy {
     NetworkPartition,
 }
 
+/// A recorded fault together with when it was observed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultEvidence {
+    pub observed_at: DateTime<Utc>,
+    pub fault: ByzantineFault,
+}
+
+/// Quarantine state of a node under operator review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QuarantineStatus {
+    /// Excluded from consensus and replication pending review
+    Quarantined,
+    /// Reinstated by an operator after review
+    Reinstated {
+        operator: String,
+        note: String,
+        reinstated_at: DateTime<Utc>,
+    },
+}
+
+/// Quarantine record with the evidence that led to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineRecord {
+    pub node_id: String,
+    pub status: QuarantineStatus,
+    pub reason: String,
+    pub quarantined_at: DateTime<Utc>,
+    pub reputation_score: f32,
+    pub evidence: Vec<FaultEvidence>,
+}
+
 /// Byzantine fault types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ByzantineFault {
     InvalidSignature {
         node_id: String,
@@ -81,9 +144,20 @@ pub enum ByzantineFault {
     EquivocationAttack {
         node_id: String,
         conflicting_messages: Vec<String>,
+        /// The conflicting signed statements, verifiable by other nodes
+        #[serde(default)]
+        statements: Vec<SignedStatement>,
     },
 }
 
+impl ByzantineFault {
+    /// Whether the fault proves misbehavior on its own, as signed
+    /// conflicting statements do, rather than hinting at it
+    pub fn is_proof(&self) -> bool {
+        matches!(self, ByzantineFault::DoubleVoting { .. } | ByzantineFault::EquivocationAttack { .. })
+    }
+}
+
 impl ByzantineFaultTolerance {
     /// Create a new Byzantine fault tolerance system
     pub fn new(tolerance_threshold: f32) -> Self {
@@ -93,11 +167,17 @@ impl ByzantineFaultTolerance {
             node_reputation: HashMap::new(),
             fault_detector: FaultDetector::new(),
             recovery_manager: RecoveryManager::new(),
+            quarantined_nodes: HashMap::new(),
+            equivocation_detector: EquivocationDetector::new(std::time::Duration::from_secs(300)),
         }
     }
 
     /// Report a Byzantine fault
-    pub async fn report_fault(&mut self, fault: ByzantineFault) -> Result<()> {
+    ///
+    /// Faults that are proof of misbehavior quarantine the node at once;
+    /// others only once its reputation has fallen far enough. Returns the
+    /// quarantine record if this fault caused the node to be quarantined.
+    pub async fn report_fault(&mut self, fault: ByzantineFault) -> Result<Option<QuarantineRecord>> {
         let node_id = self.extract_node_id(&fault);
         
         warn!("Byzantine fault detected from node {}: {:?}", node_id, fault);
@@ -106,11 +186,16 @@ impl ByzantineFaultTolerance {
         self.update_node_reputation(&node_id, false).await?;
 
         // Record fault in detector
-        self.fault_detector.record_fault(&fault).await?;
+        self.fault_detector.record_fault(&node_id, &fault).await?;
 
         // Check if node should be suspected
-        if self.should_suspect_node(&node_id).await? {
+        let mut quarantined = None;
+        if fault.is_proof() || self.should_suspect_node(&node_id).await? {
             self.suspect_node(&node_id).await?;
+
+            if self.recovery_manager.isolation_enabled && !self.is_node_quarantined(&node_id) {
+                quarantined = Some(self.quarantine_node(&node_id)?);
+            }
         }
 
         // Trigger recovery if needed
@@ -118,7 +203,108 @@ impl ByzantineFaultTolerance {
             self.trigger_recovery().await?;
         }
 
-        Ok(())
+        Ok(quarantined)
+    }
+
+    /// Record a signed statement, returning the equivocation fault if its
+    /// signer already signed a different statement for the same slot
+    ///
+    /// The fault is not reported; pass it to [`report_fault`](Self::report_fault).
+    pub fn observe_statement(&mut self, statement: SignedStatement) -> Option<ByzantineFault> {
+        let earlier = self.equivocation_detector.observe(statement.clone())?;
+        Some(ByzantineFault::EquivocationAttack {
+            node_id: statement.signer.clone(),
+            conflicting_messages: vec![earlier.signature.clone(), statement.signature.clone()],
+            statements: vec![earlier, statement],
+        })
+    }
+
+    /// Nodes currently quarantined, to be left out of quorums
+    pub fn quarantined_node_ids(&self) -> HashSet<String> {
+        self.quarantined_nodes
+            .values()
+            .filter(|record| record.status == QuarantineStatus::Quarantined)
+            .map(|record| record.node_id.clone())
+            .collect()
+    }
+
+    /// Check if a node is currently quarantined
+    pub fn is_node_quarantined(&self, node_id: &str) -> bool {
+        self.quarantined_nodes
+            .get(node_id)
+            .map(|record| record.status == QuarantineStatus::Quarantined)
+            .unwrap_or(false)
+    }
+
+    /// Get quarantine records for all nodes that have been quarantined
+    pub fn get_quarantine_records(&self) -> Vec<QuarantineRecord> {
+        self.quarantined_nodes.values().cloned().collect()
+    }
+
+    /// Get the quarantine record for a node
+    pub fn get_quarantine_record(&self, node_id: &str) -> Option<&QuarantineRecord> {
+        self.quarantined_nodes.get(node_id)
+    }
+
+    /// Restore a persisted quarantine record (e.g. after restart)
+    pub fn restore_quarantine_record(&mut self, record: QuarantineRecord) {
+        if record.status == QuarantineStatus::Quarantined {
+            self.suspected_nodes.insert(record.node_id.clone());
+        }
+        self.quarantined_nodes.insert(record.node_id.clone(), record);
+    }
+
+    /// Reinstate a quarantined node after operator review
+    ///
+    /// The node's suspicion is cleared and its reputation reset to a neutral
+    /// probationary score so repeated misbehavior quarantines it again quickly.
+    pub fn reinstate_node(&mut self, node_id: &str, operator: &str, note: &str) -> Result<QuarantineRecord> {
+        let record = self.quarantined_nodes
+            .get_mut(node_id)
+            .filter(|record| record.status == QuarantineStatus::Quarantined)
+            .ok_or_else(|| anyhow::anyhow!("Node {} is not quarantined", node_id))?;
+
+        record.status = QuarantineStatus::Reinstated {
+            operator: operator.to_string(),
+            note: note.to_string(),
+            reinstated_at: Utc::now(),
+        };
+        let record = record.clone();
+
+        self.suspected_nodes.remove(node_id);
+        if let Some(reputation) = self.node_reputation.get_mut(node_id) {
+            reputation.reputation_score = 0.5;
+            reputation.consecutive_failures = 0;
+        }
+
+        warn!("Node {} reinstated by operator {}: {}", node_id, operator, note);
+        Ok(record)
+    }
+
+    /// Place a node in quarantine with its recorded fault evidence
+    fn quarantine_node(&mut self, node_id: &str) -> Result<QuarantineRecord> {
+        let reputation_score = self.node_reputation
+            .get(node_id)
+            .map(|rep| rep.reputation_score)
+            .unwrap_or(0.0);
+
+        let record = QuarantineRecord {
+            node_id: node_id.to_string(),
+            status: QuarantineStatus::Quarantined,
+            reason: match self.fault_detector.evidence_for(node_id).last() {
+                Some(evidence) if evidence.fault.is_proof() => {
+                    "Signed conflicting statements (equivocation)".to_string()
+                }
+                _ => format!("Byzantine behavior detected (reputation {:.2})", reputation_score),
+            },
+            quarantined_at: Utc::now(),
+            reputation_score,
+            evidence: self.fault_detector.evidence_for(node_id),
+        };
+
+        error!("Node {} quarantined pending operator review: {}", node_id, record.reason);
+        self.quarantined_nodes.insert(node_id.to_string(), record.clone());
+        Ok(record)
     }
 
     /// Validate a message for Byzantine behavior
@@ -261,11 +447,14 @@ impl ByzantineFaultTolerance {
         Ok(())
     }
 
-    /// Isolate suspected nodes from consensus
+    /// Isolate suspected nodes from consensus by quarantining them
     async fn isolate_suspected_nodes(&mut self) -> Result<()> {
-        for node_id in &self.suspected_nodes {
-            warn!("Isolating suspected node: {}", node_id);
-            // Implementation would exclude node from consensus participation
+        let suspected: Vec<String> = self.suspected_nodes.iter().cloned().collect();
+        for node_id in suspected {
+            if !self.is_node_quarantined(&node_id) {
+                warn!("Isolating suspected node: {}", node_id);
+                self.quarantine_node(&node_id)?;
+            }
         }
         Ok(())
     }
@@ -399,18 +588,29 @@ impl FaultDetector {
             detection_threshold: 0.1,
             observation_window: std::time::Duration::from_secs(300), // 5 minutes
             message_history: HashMap::new(),
+            fault_history: HashMap::new(),
         }
     }
 
-    async fn record_fault(&mut self, _fault: &ByzantineFault) -> Result<()> {
-        // Implementation would record and analyze fault patterns
+    async fn record_fault(&mut self, node_id: &str, fault: &ByzantineFault) -> Result<()> {
+        self.fault_history
+            .entry(node_id.to_string())
+            .or_default()
+            .push(FaultEvidence {
+                observed_at: Utc::now(),
+                fault: fault.clone(),
+            });
         Ok(())
     }
 
+    fn evidence_for(&self, node_id: &str) -> Vec<FaultEvidence> {
+        self.fault_history.get(node_id).cloned().unwrap_or_default()
+    }
+
     async fn record_message(&mut self, node_id: &str, record: MessageRecord) -> Result<()> {
         let history = self.message_history
             .entry(node_id.to_string())
-            .or_insert_with(Vec::new);
+            .or_default();
         
         history.push(record);
 
@@ -422,6 +622,42 @@ impl FaultDetector {
     }
 }
 
+impl EquivocationDetector {
+    fn new(observation_window: std::time::Duration) -> Self {
+        Self {
+            observation_window,
+            statements: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Remember a statement, returning the earlier one it conflicts with
+    fn observe(&mut self, statement: SignedStatement) -> Option<SignedStatement> {
+        let cutoff = statement.observed_at
+            - chrono::Duration::from_std(self.observation_window).unwrap_or_else(|_| chrono::Duration::zero());
+        while let Some(key) = self.order.front() {
+            match self.statements.get(key) {
+                Some(earlier) if earlier.observed_at >= cutoff => break,
+                _ => {
+                    let key = self.order.pop_front().expect("front exists");
+                    self.statements.remove(&key);
+                }
+            }
+        }
+
+        let key = (statement.signer.clone(), statement.kind.clone(), statement.slot.clone());
+        match self.statements.get(&key) {
+            Some(earlier) if earlier.digest != statement.digest => Some(earlier.clone()),
+            Some(_) => None,
+            None => {
+                self.order.push_back(key.clone());
+                self.statements.insert(key, statement);
+                None
+            }
+        }
+    }
+}
+
 impl RecoveryManager {
     fn new() -> Self {
         Self {
@@ -433,3 +669,74 @@ impl RecoveryManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_signature(node_id: &str) -> ByzantineFault {
+        ByzantineFault::InvalidSignature {
+            node_id: node_id.to_string(),
+            message_hash: "deadbeef".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_faults_quarantine_node_with_evidence() {
+        let mut bft = ByzantineFaultTolerance::new(0.33);
+
+        let mut quarantined = None;
+        for _ in 0..4 {
+            if let Some(record) = bft.report_fault(invalid_signature("node-b")).await.unwrap() {
+                quarantined = Some(record);
+            }
+        }
+
+        let record = quarantined.expect("node should be quarantined");
+        assert_eq!(record.status, QuarantineStatus::Quarantined);
+        assert_eq!(record.evidence.len(), 4);
+        assert!(bft.is_node_quarantined("node-b"));
+    }
+
+    #[tokio::test]
+    async fn test_reinstate_clears_quarantine() {
+        let mut bft = ByzantineFaultTolerance::new(0.33);
+        for _ in 0..4 {
+            bft.report_fault(invalid_signature("node-b")).await.unwrap();
+        }
+
+        let record = bft.reinstate_node("node-b", "alice", "key rotation glitch").unwrap();
+        assert!(matches!(record.status, QuarantineStatus::Reinstated { .. }));
+        assert!(!bft.is_node_quarantined("node-b"));
+        assert!(!bft.is_node_suspected("node-b"));
+        assert!(bft.reinstate_node("node-b", "alice", "again").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_statements_quarantine_at_once() {
+        let mut bft = ByzantineFaultTolerance::new(0.33);
+        let statement = |digest: &str| SignedStatement {
+            signer: "node-b".to_string(),
+            kind: "vote".to_string(),
+            slot: "proposal-1".to_string(),
+            digest: digest.to_string(),
+            signature: format!("sig-{}", digest),
+            observed_at: Utc::now(),
+        };
+
+        assert!(bft.observe_statement(statement("accept")).is_none());
+        assert!(bft.observe_statement(statement("accept")).is_none());
+        let fault = bft.observe_statement(statement("reject")).expect("conflicting vote");
+        assert!(fault.is_proof());
+
+        let record = bft.report_fault(fault).await.unwrap().expect("node should be quarantined");
+        assert_eq!(record.evidence.len(), 1);
+        match &record.evidence[0].fault {
+            ByzantineFault::EquivocationAttack { statements, .. } => {
+                assert_eq!(statements.iter().map(|s| s.digest.as_str()).collect::<Vec<_>>(), ["accept", "reject"]);
+            }
+            other => panic!("unexpected evidence {:?}", other),
+        }
+        assert_eq!(bft.quarantined_node_ids(), HashSet::from(["node-b".to_string()]));
+    }
+}
@@ -0,0 +1,290 @@
+//! Compaction of the committed log into state snapshots.
+//!
+//! Every committed operation is recorded in the engine's committed log,
+//! which would otherwise grow for as long as the node runs. Once the log
+//! holds `SnapshotConfig::max_log_entries` entries, reaches roughly
+//! `max_log_bytes`, or `interval` has passed since the last compaction, the
+//! engine captures the replicated state machine (leases, cluster-wide
+//! feature flags and the documents of strongly consistent collections) in a
+//! [`StateSnapshot`] and drops the entries it covers.
+//!
+//! The vote-based algorithms keep the latest snapshot and send it to peers
+//! whose heartbeats show they are behind it, such as nodes that were down
+//! while it was taken or have just joined. Under Raft the replica compacts
+//! its own log (see `RaftConfig`) and transfers its snapshot to lagging
+//! followers itself; the committed log is truncated alongside it.
+//!
+//! Compacted entries are no longer exported for replay, so export the
+//! operation log before it is compacted to keep a complete history.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{CommittedEntry, Operation, PeerId};
+
+/// Fixed cost counted for each log entry besides its operation.
+const ENTRY_OVERHEAD: u64 = 64;
+
+/// When the committed log is compacted into a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Committed entries kept before the log is compacted
+    pub max_log_entries: u64,
+
+    /// Approximate serialized size of the log that triggers compaction
+    pub max_log_bytes: u64,
+
+    /// Compact at least this often while entries accumulate; `None`
+    /// compacts on size alone
+    pub interval: Option<Duration>,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            max_log_entries: 1000,
+            max_log_bytes: 16 * 1024 * 1024,
+            interval: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+/// Replicated state machine as of a consensus round, replacing the
+/// committed entries up to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// Last consensus round the snapshot covers
+    pub last_round: u64,
+    /// Committed entries compacted into this snapshot and its predecessors
+    pub compacted_entries: u64,
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// State machine contents
+    pub data: serde_json::Value,
+}
+
+/// A snapshot sent to a peer that is behind it.
+#[derive(Debug, Clone)]
+pub struct SnapshotMessage {
+    pub from: PeerId,
+    pub to: PeerId,
+    pub snapshot: StateSnapshot,
+}
+
+/// Size and compaction state of the committed log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommittedLogStatus {
+    /// Entries committed since the latest compaction
+    pub retained_entries: u64,
+    /// Approximate serialized size of the retained entries
+    pub retained_bytes: u64,
+    /// Entries compacted away, including those an installed snapshot covers
+    pub compacted_entries: u64,
+    /// Last round covered by the latest snapshot, if there is one
+    pub snapshot_round: Option<u64>,
+    /// When the latest snapshot kept for lagging peers was taken
+    pub snapshot_taken_at: Option<DateTime<Utc>>,
+}
+
+/// Approximate serialized size of a log entry carrying `operation`.
+pub(crate) fn entry_size(operation: Option<&Operation>) -> u64 {
+    let operation = operation
+        .and_then(|operation| serde_json::to_vec(operation).ok())
+        .map(|bytes| bytes.len() as u64)
+        .unwrap_or(0);
+    ENTRY_OVERHEAD + operation
+}
+
+/// The engine's committed entries since the latest compaction.
+#[derive(Debug)]
+pub(crate) struct CommittedLog {
+    entries: Vec<CommittedEntry>,
+    /// Approximate serialized size of `entries`
+    bytes: u64,
+    /// Entries dropped before `entries`
+    compacted: u64,
+    /// Last round among the dropped entries
+    compacted_round: u64,
+    /// Latest snapshot, kept for peers that are behind it
+    snapshot: Option<StateSnapshot>,
+    last_compacted_at: Instant,
+}
+
+impl CommittedLog {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            bytes: 0,
+            compacted: 0,
+            compacted_round: 0,
+            snapshot: None,
+            last_compacted_at: Instant::now(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, entry: CommittedEntry) {
+        self.bytes += entry_size(Some(&entry.proposal.operation));
+        self.entries.push(entry);
+    }
+
+    /// Entries committed since the latest compaction, in commit order.
+    pub(crate) fn entries(&self) -> &[CommittedEntry] {
+        &self.entries
+    }
+
+    /// Entries compacted away before [`CommittedLog::entries`].
+    pub(crate) fn compacted(&self) -> u64 {
+        self.compacted
+    }
+
+    /// Round the next proposal is made in: one past the entries committed,
+    /// compacted or not.
+    pub(crate) fn next_round(&self) -> u64 {
+        self.compacted + self.entries.len() as u64 + 1
+    }
+
+    /// Round of the latest committed entry, or of the latest compacted one
+    /// when every entry was compacted.
+    pub(crate) fn last_round(&self) -> u64 {
+        self.entries.last().map(|entry| entry.consensus_round).unwrap_or(self.compacted_round)
+    }
+
+    pub(crate) fn snapshot(&self) -> Option<&StateSnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Whether the log has outgrown `config`.
+    pub(crate) fn should_compact(&self, config: &SnapshotConfig) -> bool {
+        !self.entries.is_empty()
+            && (self.entries.len() as u64 >= config.max_log_entries
+                || self.bytes >= config.max_log_bytes
+                || config.interval.is_some_and(|interval| self.last_compacted_at.elapsed() >= interval))
+    }
+
+    /// Drop the first `count` entries, which `data` covers, keeping the
+    /// resulting snapshot for peers that are behind it.
+    pub(crate) fn compact(&mut self, count: usize, data: serde_json::Value) -> &StateSnapshot {
+        self.drop_front(count.min(self.entries.len()));
+        self.snapshot.insert(StateSnapshot {
+            last_round: self.compacted_round,
+            compacted_entries: self.compacted,
+            taken_at: Utc::now(),
+            data,
+        })
+    }
+
+    /// Drop the entries up to and including `round`, which Raft has
+    /// compacted into its own snapshot.
+    pub(crate) fn truncate_through(&mut self, round: u64) {
+        let count = self.entries.iter().take_while(|entry| entry.consensus_round <= round).count();
+        self.drop_front(count);
+    }
+
+    /// Replace the entries a snapshot received from a peer covers with it.
+    pub(crate) fn install(&mut self, snapshot: StateSnapshot) {
+        self.entries.retain(|entry| entry.consensus_round > snapshot.last_round);
+        self.bytes = self.entries.iter().map(|entry| entry_size(Some(&entry.proposal.operation))).sum();
+        self.compacted = snapshot.compacted_entries;
+        self.compacted_round = snapshot.last_round;
+        self.snapshot = Some(snapshot);
+        self.last_compacted_at = Instant::now();
+    }
+
+    pub(crate) fn status(&self) -> CommittedLogStatus {
+        CommittedLogStatus {
+            retained_entries: self.entries.len() as u64,
+            retained_bytes: self.bytes,
+            compacted_entries: self.compacted,
+            snapshot_round: (self.compacted > 0).then_some(self.compacted_round),
+            snapshot_taken_at: self.snapshot.as_ref().map(|snapshot| snapshot.taken_at),
+        }
+    }
+
+    fn drop_front(&mut self, count: usize) {
+        for entry in self.entries.drain(..count) {
+            self.bytes = self.bytes.saturating_sub(entry_size(Some(&entry.proposal.operation)));
+            self.compacted_round = entry.consensus_round;
+        }
+        self.compacted += count as u64;
+        self.last_compacted_at = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Proposal, VoteCollection};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn entry(round: u64) -> CommittedEntry {
+        let id = Uuid::new_v4();
+        CommittedEntry {
+            proposal: Proposal {
+                id,
+                round,
+                proposer: "local_peer".to_string(),
+                operation: Operation::CreateCollection { name: format!("c{}", round), schema: None },
+                timestamp: Utc::now(),
+                signature: String::new(),
+            },
+            votes: VoteCollection { proposal_id: id, votes: HashMap::new(), threshold_reached: true },
+            committed_at: Utc::now(),
+            consensus_round: round,
+        }
+    }
+
+    #[test]
+    fn test_compaction_thresholds() {
+        let mut log = CommittedLog::new();
+        let by_entries = SnapshotConfig { max_log_entries: 3, max_log_bytes: u64::MAX, interval: None };
+        assert!(!log.should_compact(&by_entries));
+        log.push(entry(1));
+        log.push(entry(2));
+        assert!(!log.should_compact(&by_entries));
+        log.push(entry(3));
+        assert!(log.should_compact(&by_entries));
+
+        let by_bytes = SnapshotConfig { max_log_entries: u64::MAX, max_log_bytes: log.status().retained_bytes, interval: None };
+        assert!(log.should_compact(&by_bytes));
+
+        let by_interval = SnapshotConfig { max_log_entries: u64::MAX, max_log_bytes: u64::MAX, interval: Some(Duration::ZERO) };
+        assert!(log.should_compact(&by_interval));
+        log.compact(3, serde_json::json!({}));
+        // Nothing left to compact, however long ago the last compaction was
+        assert!(!log.should_compact(&by_interval));
+    }
+
+    #[test]
+    fn test_compaction_keeps_rounds_and_later_entries() {
+        let mut log = CommittedLog::new();
+        for round in 1..=4 {
+            log.push(entry(round));
+        }
+        let snapshot = log.compact(3, serde_json::json!({ "leases": {} })).clone();
+        assert_eq!((snapshot.last_round, snapshot.compacted_entries), (3, 3));
+        assert_eq!(log.entries().len(), 1);
+        assert_eq!(log.next_round(), 5);
+        assert_eq!(log.last_round(), 4);
+        assert_eq!(log.status().retained_bytes, entry_size(Some(&log.entries()[0].proposal.operation)));
+
+        // A peer installing the snapshot continues numbering from it
+        let mut peer = CommittedLog::new();
+        peer.push(entry(1));
+        peer.install(snapshot);
+        assert!(peer.entries().is_empty());
+        assert_eq!(peer.next_round(), 4);
+        assert_eq!(peer.last_round(), 3);
+        assert_eq!(peer.status().snapshot_round, Some(3));
+
+        // Raft truncates through the index it compacted
+        log.truncate_through(4);
+        assert!(log.entries().is_empty());
+        assert_eq!(log.status().retained_bytes, 0);
+        assert_eq!(log.compacted(), 4);
+        assert_eq!(log.last_round(), 4);
+    }
+}
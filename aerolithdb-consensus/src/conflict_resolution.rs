@@ -3,9 +3,13 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tracing::{debug, warn, error};
 
+use crate::crdt::{CrdtDocument, Stamp, CRDT_STATE_KEY};
+
 /// Conflict resolution engine for handling document conflicts
 pub struct ConflictResolutionEngine {
     strategy: ConflictResolution,
+    /// Strategies of collections that do not use the default one
+    collection_strategies: HashMap<String, ConflictResolution>,
     merge_strategies: HashMap<String, Box<dyn MergeStrategy + Send + Sync>>,
     custom_resolvers: HashMap<String, Box<dyn CustomResolver + Send + Sync>>,
 }
@@ -17,6 +21,9 @@ pub enum ConflictResolution {
     SemanticMerge(MergeStrategyType),
     UserDefinedResolver(String),
     RequireManualIntervention,
+    /// Merge JSON documents as CRDTs: fields are last-writer-wins registers
+    /// and arrays observed-remove sets, so concurrent edits all survive
+    Crdt,
 }
 
 /// Types of merge strategies
@@ -87,6 +94,7 @@ impl ConflictResolutionEngine {
     pub fn new(strategy: &ConflictResolution) -> Self {
         let mut engine = Self {
             strategy: strategy.clone(),
+            collection_strategies: HashMap::new(),
             merge_strategies: HashMap::new(),
             custom_resolvers: HashMap::new(),
         };
@@ -95,10 +103,22 @@ impl ConflictResolutionEngine {
         engine.register_merge_strategy(Box::new(FieldLevelMerge));
         engine.register_merge_strategy(Box::new(ArrayMergeStrategy));
         engine.register_merge_strategy(Box::new(JsonPatchMerge));
+        engine.register_merge_strategy(Box::new(CrdtMerge));
 
         engine
     }
 
+    /// Resolve conflicts in `collection` with `strategy` instead of the default
+    pub fn with_collection_strategy(mut self, collection: impl Into<String>, strategy: ConflictResolution) -> Self {
+        self.collection_strategies.insert(collection.into(), strategy);
+        self
+    }
+
+    /// Strategy resolving conflicts in `collection`
+    pub fn strategy_for(&self, collection: &str) -> &ConflictResolution {
+        self.collection_strategies.get(collection).unwrap_or(&self.strategy)
+    }
+
     /// Register a merge strategy
     pub fn register_merge_strategy(&mut self, strategy: Box<dyn MergeStrategy + Send + Sync>) {
         self.merge_strategies.insert(strategy.name().to_string(), strategy);
@@ -113,7 +133,7 @@ impl ConflictResolutionEngine {
     pub async fn resolve_conflict(&self, conflict: &Conflict) -> Result<ResolutionResult> {
         debug!("Resolving conflict for document: {}", conflict.document_id);
 
-        match &self.strategy {
+        match self.strategy_for(&conflict.collection) {
             ConflictResolution::LastWriterWins => {
                 self.resolve_last_writer_wins(conflict).await
             }
@@ -126,6 +146,9 @@ impl ConflictResolutionEngine {
             ConflictResolution::RequireManualIntervention => {
                 self.require_manual_intervention(conflict).await
             }
+            ConflictResolution::Crdt => {
+                self.resolve_semantic_merge(conflict, &MergeStrategyType::Custom("crdt_merge".to_string())).await
+            }
         }
     }
 
@@ -378,3 +401,109 @@ impl MergeStrategy for JsonPatchMerge {
         "json_patch_merge"
     }
 }
+
+/// CRDT merge strategy
+///
+/// Each version carries its merge state under [`CRDT_STATE_KEY`] in its
+/// metadata. A version without one, written before the collection used this
+/// strategy, is treated as written entirely by its author at its timestamp.
+struct CrdtMerge;
+
+impl CrdtMerge {
+    fn state_of(version: &DocumentVersion) -> CrdtDocument {
+        let stamp = Stamp::new(version.timestamp, version.author.clone());
+        match version.metadata.get(CRDT_STATE_KEY).map(|state| serde_json::from_value::<CrdtDocument>(state.clone())) {
+            Some(Ok(mut state)) => {
+                // Catch up with a write that did not update the state
+                state.update(&version.data, &stamp);
+                state
+            }
+            Some(Err(e)) => {
+                warn!("Ignoring unreadable CRDT state of version {}: {}", version.version, e);
+                CrdtDocument::from_value(&version.data, &stamp)
+            }
+            None => CrdtDocument::from_value(&version.data, &stamp),
+        }
+    }
+}
+
+impl MergeStrategy for CrdtMerge {
+    fn merge(&self, local: &DocumentVersion, remote: &DocumentVersion) -> Result<ResolutionResult> {
+        let mut state = Self::state_of(local);
+        state.merge(&Self::state_of(remote));
+
+        let mut metadata = HashMap::new();
+        metadata.insert(CRDT_STATE_KEY.to_string(), serde_json::to_value(&state)?);
+
+        Ok(ResolutionResult {
+            resolved_data: state.value(),
+            resolution_strategy: "crdt_merge".to_string(),
+            metadata,
+            requires_manual_review: false,
+        })
+    }
+
+    fn can_handle(&self, _conflict: &Conflict) -> bool {
+        true
+    }
+
+    fn name(&self) -> &str {
+        "crdt_merge"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VectorClock;
+    use serde_json::json;
+
+    fn version(data: serde_json::Value, seconds: i64, author: &str) -> DocumentVersion {
+        DocumentVersion {
+            data,
+            version: 1,
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap(),
+            author: author.to_string(),
+            vector_clock: VectorClock::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn conflict(collection: &str, local: DocumentVersion, remote: DocumentVersion) -> Conflict {
+        Conflict {
+            document_id: "doc".to_string(),
+            collection: collection.to_string(),
+            local_version: local,
+            remote_version: remote,
+            conflict_type: ConflictType::ConcurrentModification,
+            conflicting_fields: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crdt_strategy_is_selected_per_collection() {
+        let resolver = ConflictResolutionEngine::new(&ConflictResolution::LastWriterWins)
+            .with_collection_strategy("carts", ConflictResolution::Crdt);
+        let local = version(json!({"items": ["apple"], "owner": "ada"}), 0, "dc1");
+        let remote = version(json!({"items": ["pear"], "note": "gift"}), 5, "dc2");
+
+        // Other collections keep last writer wins
+        let resolved = resolver.resolve_conflict(&conflict("orders", local.clone(), remote.clone())).await.unwrap();
+        assert_eq!(resolved.resolution_strategy, "last_writer_wins");
+        assert_eq!(resolved.resolved_data, remote.data);
+
+        let resolved = resolver.resolve_conflict(&conflict("carts", local.clone(), remote.clone())).await.unwrap();
+        assert_eq!(resolved.resolution_strategy, "crdt_merge");
+        assert_eq!(resolved.resolved_data, json!({"items": ["apple", "pear"], "owner": "ada", "note": "gift"}));
+
+        // The merged state travels with the result, so a later merge sees
+        // the removal of an element as a removal rather than a loss
+        let mut merged = version(resolved.resolved_data.clone(), 10, "dc1");
+        merged.metadata = resolved.metadata.clone();
+        let mut edited = merged.clone();
+        edited.data = json!({"items": ["pear"], "owner": "ada", "note": "gift"});
+        edited.timestamp = chrono::DateTime::from_timestamp(1_700_000_020, 0).unwrap();
+        let resolved = resolver.resolve_conflict(&conflict("carts", merged, edited)).await.unwrap();
+        assert_eq!(resolved.resolved_data, json!({"items": ["pear"], "owner": "ada", "note": "gift"}));
+    }
+}
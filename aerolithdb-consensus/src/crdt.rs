@@ -0,0 +1,386 @@
+//! Conflict-free merging of JSON documents.
+//!
+//! A document edited concurrently on both sides of a partition, or in two
+//! datacenters, is merged field by field instead of one write replacing the
+//! other. Every scalar is a last-writer-wins register, every object merges
+//! its fields independently, and every array is an observed-remove set: an
+//! element removed on one replica stays removed, while an element added
+//! concurrently elsewhere survives the merge.
+//!
+//! A [`CrdtDocument`] is the merge state of one document. It is kept beside
+//! the document and updated on every write with [`CrdtDocument::update`], and
+//! two replicas' states are combined with [`CrdtDocument::merge`]. Merging is
+//! commutative, associative and idempotent, so replicas exchanging their
+//! states converge to the same document whatever the order they merge in.
+//!
+//! Writes are ordered by their [`Stamp`], the write's timestamp with the
+//! writing replica breaking ties. Arrays merged as sets keep each distinct
+//! value once, ordered by when it was added, so a reordering alone is not
+//! recorded. Removed fields and array elements leave tombstones behind, which
+//! are never collected.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Metadata key under which a document version carries its merge state.
+pub const CRDT_STATE_KEY: &str = "crdt_state";
+
+/// When and by which replica a write was made.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Stamp {
+    pub timestamp: DateTime<Utc>,
+    /// Breaks ties between writes made at the same instant
+    pub replica: String,
+}
+
+impl Stamp {
+    pub fn new(timestamp: DateTime<Utc>, replica: impl Into<String>) -> Self {
+        Self { timestamp, replica: replica.into() }
+    }
+}
+
+/// Identity of one addition of an array element.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Tag {
+    pub stamp: Stamp,
+    /// Position among the elements added by the same write
+    pub seq: u32,
+}
+
+/// Merge state of one JSON value.
+///
+/// A value may have been a scalar, an object and an array at different
+/// times, so the state of each kind is kept and merged separately, and the
+/// kind written last is the one shown.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CrdtNode {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    register: Option<Register>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    map: Option<MapState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set: Option<SetState>,
+}
+
+/// Last-writer-wins register; `None` once the value was removed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Register {
+    value: Option<Value>,
+    stamp: Stamp,
+}
+
+/// Object whose fields merge independently
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MapState {
+    fields: BTreeMap<String, CrdtNode>,
+    /// Latest write to the object or anything within it
+    stamp: Stamp,
+}
+
+/// Observed-remove set of array elements
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetState {
+    /// Live additions, ordered by tag
+    elements: Vec<SetElement>,
+    /// Tags of additions that were removed
+    removed: BTreeSet<Tag>,
+    /// Latest addition or removal
+    stamp: Stamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetElement {
+    tag: Tag,
+    value: Value,
+}
+
+/// Which kind of state a node currently shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Register,
+    Map,
+    Set,
+}
+
+impl CrdtNode {
+    /// The kind written last, if any
+    fn kind(&self) -> Option<Kind> {
+        [
+            self.register.as_ref().map(|register| (&register.stamp, Kind::Register)),
+            self.map.as_ref().map(|map| (&map.stamp, Kind::Map)),
+            self.set.as_ref().map(|set| (&set.stamp, Kind::Set)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .map(|(_, kind)| kind)
+    }
+
+    /// Current value, `None` if it was never written or was removed
+    pub fn value(&self) -> Option<Value> {
+        match self.kind()? {
+            Kind::Register => self.register.as_ref()?.value.clone(),
+            Kind::Map => {
+                let fields = &self.map.as_ref()?.fields;
+                let object: Map<String, Value> = fields
+                    .iter()
+                    .filter_map(|(name, node)| node.value().map(|value| (name.clone(), value)))
+                    .collect();
+                Some(Value::Object(object))
+            }
+            Kind::Set => {
+                let mut values: Vec<Value> = Vec::new();
+                for element in &self.set.as_ref()?.elements {
+                    if !values.contains(&element.value) {
+                        values.push(element.value.clone());
+                    }
+                }
+                Some(Value::Array(values))
+            }
+        }
+    }
+
+    /// Record a write of `value` (or its removal) and return whether
+    /// anything changed. Tags of added elements are numbered from `seq`.
+    fn update(&mut self, value: Option<&Value>, stamp: &Stamp, seq: &mut u32) -> bool {
+        let kind = self.kind();
+        match value {
+            Some(Value::Object(object)) => {
+                let map = self.map.get_or_insert_with(|| MapState { fields: BTreeMap::new(), stamp: stamp.clone() });
+                let mut changed = kind != Some(Kind::Map);
+                for (name, field) in object {
+                    changed |= map.fields.entry(name.clone()).or_default().update(Some(field), stamp, seq);
+                }
+                for (name, node) in map.fields.iter_mut() {
+                    if !object.contains_key(name) {
+                        changed |= node.update(None, stamp, seq);
+                    }
+                }
+                if changed {
+                    map.stamp = map.stamp.clone().max(stamp.clone());
+                }
+                changed
+            }
+            Some(Value::Array(array)) => {
+                let set = self.set.get_or_insert_with(|| SetState {
+                    elements: Vec::new(),
+                    removed: BTreeSet::new(),
+                    stamp: stamp.clone(),
+                });
+                let mut changed = kind != Some(Kind::Set);
+
+                // Remove every observed addition of a value no longer present
+                let removed: Vec<Tag> = set
+                    .elements
+                    .iter()
+                    .filter(|element| !array.contains(&element.value))
+                    .map(|element| element.tag.clone())
+                    .collect();
+                changed |= !removed.is_empty();
+                set.elements.retain(|element| array.contains(&element.value));
+                set.removed.extend(removed);
+
+                for value in array {
+                    if !set.elements.iter().any(|element| element.value == *value) {
+                        set.elements.push(SetElement { tag: Tag { stamp: stamp.clone(), seq: *seq }, value: value.clone() });
+                        *seq += 1;
+                        changed = true;
+                    }
+                }
+                set.elements.sort_by(|a, b| a.tag.cmp(&b.tag));
+                if changed {
+                    set.stamp = set.stamp.clone().max(stamp.clone());
+                }
+                changed
+            }
+            _ => {
+                if self.value().as_ref() == value {
+                    return false;
+                }
+                // A write older than the register's last one is already superseded
+                if self.register.as_ref().is_some_and(|current| current.stamp >= *stamp) {
+                    return false;
+                }
+                self.register = Some(Register { value: value.cloned(), stamp: stamp.clone() });
+                true
+            }
+        }
+    }
+
+    /// Merge another replica's state of the same value into this one.
+    pub fn merge(&mut self, other: &CrdtNode) {
+        if let Some(theirs) = &other.register {
+            match &mut self.register {
+                Some(ours) => {
+                    // Equal stamps only differ after a clock or replica id is
+                    // reused, and then the larger value wins on both sides
+                    let newer = match theirs.stamp.cmp(&ours.stamp) {
+                        std::cmp::Ordering::Greater => true,
+                        std::cmp::Ordering::Less => false,
+                        std::cmp::Ordering::Equal => {
+                            value_order_key(&theirs.value) > value_order_key(&ours.value)
+                        }
+                    };
+                    if newer {
+                        *ours = theirs.clone();
+                    }
+                }
+                None => self.register = Some(theirs.clone()),
+            }
+        }
+
+        if let Some(theirs) = &other.map {
+            match &mut self.map {
+                Some(ours) => {
+                    for (name, node) in &theirs.fields {
+                        ours.fields.entry(name.clone()).or_default().merge(node);
+                    }
+                    ours.stamp = ours.stamp.clone().max(theirs.stamp.clone());
+                }
+                None => self.map = Some(theirs.clone()),
+            }
+        }
+
+        if let Some(theirs) = &other.set {
+            match &mut self.set {
+                Some(ours) => {
+                    ours.removed.extend(theirs.removed.iter().cloned());
+                    for element in &theirs.elements {
+                        if !ours.elements.iter().any(|existing| existing.tag == element.tag) {
+                            ours.elements.push(element.clone());
+                        }
+                    }
+                    let removed = &ours.removed;
+                    ours.elements.retain(|element| !removed.contains(&element.tag));
+                    ours.elements.sort_by(|a, b| a.tag.cmp(&b.tag));
+                    ours.stamp = ours.stamp.clone().max(theirs.stamp.clone());
+                }
+                None => self.set = Some(theirs.clone()),
+            }
+        }
+    }
+}
+
+/// Total order over register values for tie-breaking
+fn value_order_key(value: &Option<Value>) -> Option<String> {
+    value.as_ref().map(|value| value.to_string())
+}
+
+/// Merge state of a whole document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CrdtDocument {
+    root: CrdtNode,
+}
+
+impl CrdtDocument {
+    /// State of a document whose every value was written by one write,
+    /// used for documents that were stored before they had a state.
+    pub fn from_value(value: &Value, stamp: &Stamp) -> Self {
+        let mut document = Self::default();
+        document.update(value, stamp);
+        document
+    }
+
+    /// Record a local write replacing the document with `value`. Only the
+    /// fields and elements that differ from the current value are stamped.
+    pub fn update(&mut self, value: &Value, stamp: &Stamp) -> bool {
+        let mut seq = 0;
+        self.root.update(Some(value), stamp, &mut seq)
+    }
+
+    /// Merge another replica's state of the document into this one.
+    pub fn merge(&mut self, other: &CrdtDocument) {
+        self.root.merge(&other.root);
+    }
+
+    /// The merged document.
+    pub fn value(&self) -> Value {
+        self.root.value().unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stamp(seconds: i64, replica: &str) -> Stamp {
+        Stamp::new(DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap(), replica)
+    }
+
+    #[test]
+    fn test_concurrent_field_edits_merge() {
+        let base = CrdtDocument::from_value(
+            &json!({"name": "Ada", "city": "London", "tags": ["a", "b"], "address": {"zip": "N1"}}),
+            &stamp(0, "dc1"),
+        );
+
+        // Partitioned datacenters edit different fields and the same array
+        let mut east = base.clone();
+        east.update(&json!({"name": "Ada L.", "city": "London", "tags": ["a"], "address": {"zip": "N1"}}), &stamp(10, "dc1"));
+        let mut west = base.clone();
+        west.update(
+            &json!({"name": "Ada", "city": "Paris", "tags": ["a", "b", "c"], "address": {"zip": "N1", "street": "Rue"}}),
+            &stamp(5, "dc2"),
+        );
+
+        let expected = json!({
+            "name": "Ada L.",
+            "city": "Paris",
+            "tags": ["a", "c"],
+            "address": {"zip": "N1", "street": "Rue"},
+        });
+        let mut merged = east.clone();
+        merged.merge(&west);
+        assert_eq!(merged.value(), expected);
+
+        // Merging converges whatever the order and however often it happens
+        let mut other_way = west.clone();
+        other_way.merge(&east);
+        other_way.merge(&east);
+        assert_eq!(other_way, merged);
+
+        // Both sides wrote the same field: the later write wins
+        let mut late = base.clone();
+        late.update(&json!({"name": "Augusta", "city": "London", "tags": ["a", "b"], "address": {"zip": "N1"}}), &stamp(20, "dc2"));
+        merged.merge(&late);
+        assert_eq!(merged.value()["name"], json!("Augusta"));
+        assert_eq!(merged.value()["city"], json!("Paris"));
+    }
+
+    #[test]
+    fn test_removals_and_type_changes_converge() {
+        let base = CrdtDocument::from_value(&json!({"profile": {"bio": "hi"}, "score": 1}), &stamp(0, "a"));
+
+        // A removal loses to a concurrent later edit within the removed object
+        let mut removed = base.clone();
+        removed.update(&json!({"score": 1}), &stamp(5, "a"));
+        let mut edited = base.clone();
+        edited.update(&json!({"profile": {"bio": "hello"}, "score": 1}), &stamp(6, "b"));
+        let mut changed = base.clone();
+        changed.update(&json!({"profile": {"bio": "hi"}, "score": [1, 2]}), &stamp(7, "c"));
+
+        let states = [&removed, &edited, &changed];
+        let mut results = Vec::new();
+        for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            let mut merged = CrdtDocument::default();
+            for index in order {
+                merged.merge(states[index]);
+            }
+            results.push(merged);
+        }
+        assert!(results.iter().all(|merged| *merged == results[0]));
+        assert_eq!(results[0].value(), json!({"profile": {"bio": "hello"}, "score": [1, 2]}));
+
+        // A later removal of the whole object wins
+        let mut merged = results[0].clone();
+        let mut later = merged.clone();
+        later.update(&json!({"score": [1, 2]}), &stamp(9, "a"));
+        merged.merge(&later);
+        assert_eq!(merged.value(), json!({"score": [1, 2]}));
+    }
+}
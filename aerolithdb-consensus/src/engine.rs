@@ -4,28 +4,43 @@
 //! providing distributed consensus capabilities for the database.
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use anyhow::Result;
 use chrono::Utc;
 use dashmap::DashMap;
 use tracing::{debug, error, info, warn};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex, RwLock};
 use uuid::Uuid;
 
 use aerolithdb_security::SecurityFramework;
-use aerolithdb_storage::StorageHierarchy;
+use aerolithdb_storage::{
+    ReadBarrier, ReadConsistency, ReplicatedWrite, StorageHierarchy, StorageResult, WriteConsistency, WriteMode,
+    WriteReplicator,
+};
 
-use crate::byzantine_tolerance::ByzantineFaultTolerance;
-use crate::conflict_resolution::ConflictResolutionEngine;
+use crate::adaptive_timeout::{AdaptiveTimeoutManager, TimeoutSnapshot};
+use crate::batching::{BatchMetrics, ProposalBatcher};
+use crate::byzantine_tolerance::{ByzantineFault, ByzantineFaultTolerance, QuarantineRecord, SignedStatement};
+use crate::compaction::{CommittedLog, CommittedLogStatus, SnapshotMessage};
+use crate::conflict_resolution::{Conflict, ConflictResolution, ConflictResolutionEngine, ResolutionResult};
+use crate::feature_flags::{FeatureFlagChange, FeatureFlagRegistry};
+use crate::leases::{self, Lease, LeaseAction, LeaseOutcome, LeaseRequest, LEASE_COLLECTION};
 use crate::partition_recovery::NetworkPartitionRecovery;
+use crate::raft::{EntryPayload, LogEntry, LogIndex, RaftNode, RaftStatus};
+use crate::replay::{self, OperationLogEntry};
+use crate::threshold_signatures::{AggregatedCertificate, AggregationOutcome, ThresholdSignatureAggregator};
+use crate::topology::{RegionEvent, RegionTopology, TopologyStatus};
 use crate::vector_clock::VectorClock;
 use crate::types::{
-    ConsensusConfig, ConsensusMessage, Proposal, Vote, VoteDecision, VoteCollection,
+    ConsensusAlgorithm, ConsensusConfig, ConsensusMessage, Proposal, Vote, VoteDecision, VoteCollection,
     CommittedEntry, Operation, PeerId, ProposalId, CommitMessage, AbortMessage,
     HeartbeatMessage, ViewChangeMessage,
 };
 
+/// System collection where quarantine records and their evidence are persisted.
+pub const QUARANTINE_COLLECTION: &str = "_consensus_quarantine";
+
 /// Main distributed consensus engine for aerolithsDB.
 /// 
 /// This is the core component responsible for ensuring all nodes in the
@@ -60,7 +75,10 @@ pub struct ConsensusEngine {
     conflict_resolver: Arc<ConflictResolutionEngine>,
     
     /// Byzantine fault tolerance system for detecting malicious behavior
-    byzantine_tolerance: Arc<ByzantineFaultTolerance>,
+    byzantine_tolerance: Arc<RwLock<ByzantineFaultTolerance>>,
+    
+    /// Alert channel notified whenever a node is quarantined or reinstated
+    quarantine_alerts: broadcast::Sender<QuarantineRecord>,
     
     /// Network partition recovery system for healing split networks
     partition_recovery: Arc<NetworkPartitionRecovery>,
@@ -71,8 +89,41 @@ pub struct ConsensusEngine {
     /// Vote collections for each active proposal (proposal_id -> votes)
     votes: Arc<DashMap<ProposalId, VoteCollection>>,
     
-    /// Log of committed operations in chronological order
-    committed_log: Arc<RwLock<Vec<CommittedEntry>>>,
+    /// Log of committed operations in chronological order, since the latest snapshot
+    committed_log: Arc<RwLock<CommittedLog>>,
+    
+    /// Held while the committed log is being compacted
+    compacting: Arc<Mutex<()>>,
+    
+    /// Batcher grouping submitted operations and bounding in-flight rounds
+    batcher: Arc<ProposalBatcher>,
+    
+    /// RTT-driven round and election timeouts
+    timeouts: Arc<AdaptiveTimeoutManager>,
+    
+    /// PBFT view and how long rounds have stalled in it
+    view_timer: Arc<std::sync::Mutex<ViewTimer>>,
+    
+    /// BLS signature aggregator, present when threshold signatures are enabled
+    threshold_signer: Option<Arc<ThresholdSignatureAggregator>>,
+    
+    /// Region layout and per-region quorum rules, present for multi-region clusters
+    topology: Option<Arc<RegionTopology>>,
+    
+    /// Callers waiting for their lease request to commit (request_id -> outcome)
+    lease_waiters: Arc<DashMap<Uuid, oneshot::Sender<LeaseOutcome>>>,
+    
+    /// Callers waiting for their strongly consistent write to commit (request_id -> outcome)
+    write_waiters: Arc<DashMap<Uuid, oneshot::Sender<Result<StorageResult<()>>>>>,
+    
+    /// Linearizable reads waiting for their barrier to be applied (request_id -> notification)
+    read_waiters: Arc<DashMap<Uuid, oneshot::Sender<()>>>,
+    
+    /// Runtime feature flags, with cluster-wide settings applied at commit time
+    feature_flags: Arc<FeatureFlagRegistry>,
+    
+    /// Raft replica, present when the Raft algorithm is selected
+    raft: Option<Arc<Mutex<RaftNode>>>,
     
     /// Channel for sending consensus messages to the network
     message_sender: mpsc::UnboundedSender<ConsensusMessage>,
@@ -81,6 +132,17 @@ pub struct ConsensusEngine {
     message_receiver: Arc<RwLock<Option<mpsc::UnboundedReceiver<ConsensusMessage>>>>,
 }
 
+/// Time since the last committed round, which starts a view change once
+/// proposals have waited longer than the election timeout.
+struct ViewTimer {
+    /// Current PBFT view
+    view: u64,
+
+    /// Last committed round seen, and when it was first seen
+    round: u64,
+    since: std::time::Instant,
+}
+
 impl ConsensusEngine {
     /// Create a new consensus engine with the given configuration.
     /// 
@@ -93,18 +155,80 @@ impl ConsensusEngine {
         info!("Initializing consensus engine with algorithm: {:?}", config.algorithm);
 
         let (message_sender, message_receiver) = mpsc::unbounded_channel();
+        let (quarantine_alerts, _) = broadcast::channel(64);
+
+        let threshold_signer = if config.threshold_signatures {
+            info!(
+                "BLS threshold signatures enabled for clusters of {}+ peers",
+                config.threshold_signature_min_peers
+            );
+            // Keyed to the same identity returned by get_local_peer_id
+            Some(Arc::new(ThresholdSignatureAggregator::new("local_peer".to_string())?))
+        } else {
+            None
+        };
+
+        let topology = match &config.topology {
+            Some(topology_config) => Some(Arc::new(RegionTopology::new(
+                topology_config.clone(),
+                "local_peer".to_string(),
+            )?)),
+            None => None,
+        };
+
+        let feature_flags = Arc::new(FeatureFlagRegistry::new(Arc::clone(&storage)));
+
+        let raft = match config.algorithm {
+            ConsensusAlgorithm::Raft => {
+                info!("Raft enabled with {} configured voters", config.raft.voters.len().max(1));
+                // Keyed to the same identity returned by get_local_peer_id
+                let seed = Uuid::new_v4().as_u64_pair().0;
+                Some(Arc::new(Mutex::new(RaftNode::new("local_peer".to_string(), config.raft.clone(), seed))))
+            }
+            _ => None,
+        };
 
         Ok(Self {
             config: config.clone(),
             security,
             storage,
             vector_clock: Arc::new(RwLock::new(VectorClock::new())),
-            conflict_resolver: Arc::new(ConflictResolutionEngine::new(&config.conflict_resolution)),
-            byzantine_tolerance: Arc::new(ByzantineFaultTolerance::new(config.byzantine_tolerance)),
+            conflict_resolver: Arc::new(
+                config.collection_conflict_resolution.iter().fold(
+                    ConflictResolutionEngine::new(&config.conflict_resolution),
+                    |resolver, (collection, strategy)| resolver.with_collection_strategy(collection.clone(), strategy.clone()),
+                ),
+            ),
+            byzantine_tolerance: Arc::new(RwLock::new(ByzantineFaultTolerance::new(config.byzantine_tolerance))),
+            quarantine_alerts,
             partition_recovery: Arc::new(NetworkPartitionRecovery::new()),
             proposals: Arc::new(DashMap::new()),
             votes: Arc::new(DashMap::new()),
-            committed_log: Arc::new(RwLock::new(Vec::new())),
+            committed_log: Arc::new(RwLock::new(CommittedLog::new())),
+            compacting: Arc::new(Mutex::new(())),
+            batcher: Arc::new(ProposalBatcher::new(
+                config.max_batch_size,
+                config.batch_window,
+                config.pipeline_depth,
+            )),
+            timeouts: Arc::new(AdaptiveTimeoutManager::new(
+                config.adaptive_timeouts,
+                config.timeout,
+                config.min_timeout,
+                config.max_timeout,
+            )),
+            view_timer: Arc::new(std::sync::Mutex::new(ViewTimer {
+                view: 0,
+                round: 0,
+                since: std::time::Instant::now(),
+            })),
+            threshold_signer,
+            topology,
+            lease_waiters: Arc::new(DashMap::new()),
+            write_waiters: Arc::new(DashMap::new()),
+            read_waiters: Arc::new(DashMap::new()),
+            feature_flags,
+            raft,
             message_sender,
             message_receiver: Arc::new(RwLock::new(Some(message_receiver))),
         })
@@ -114,6 +238,21 @@ impl ConsensusEngine {
     pub async fn start(&self) -> Result<()> {
         info!("Starting consensus engine");
 
+        // Reload quarantine decisions persisted before the last restart
+        self.load_quarantine_records().await?;
+
+        // Reload feature flag settings so gated subsystems start in the right state
+        self.feature_flags.load().await?;
+
+        // A sole voter has nobody to wait for, so it takes leadership right away
+        if let Some(raft) = &self.raft {
+            let mut node = raft.lock().await;
+            if node.voters() == [node.id().clone()] {
+                node.campaign();
+                self.drive_raft(&mut node).await?;
+            }
+        }
+
         // Start message processing loop
         let receiver = self.message_receiver.write().await.take()
             .ok_or_else(|| anyhow::anyhow!("Consensus engine already started"))?;
@@ -143,7 +282,16 @@ impl ConsensusEngine {
     /// Returns the proposal ID that can be used to track consensus progress.
     pub async fn propose_operation(&self, operation: Operation) -> Result<ProposalId> {
         let proposal_id = Uuid::new_v4();
-        
+        self.propose_with_id(proposal_id, operation).await?;
+        Ok(proposal_id)
+    }
+
+    /// Propose an operation under a caller-chosen proposal ID.
+    async fn propose_with_id(&self, proposal_id: ProposalId, operation: Operation) -> Result<()> {
+        if let Some(topology) = &self.topology {
+            topology.check_writes_allowed()?;
+        }
+
         let proposal = Proposal {
             id: proposal_id,
             round: self.get_current_round().await,
@@ -155,6 +303,14 @@ impl ConsensusEngine {
 
         debug!("Proposing operation: {:?}", proposal);
 
+        if let Some(raft) = &self.raft {
+            // The leader appends to its log; the entry applies once a majority has it
+            let mut node = raft.lock().await;
+            node.propose(proposal.clone())?;
+            self.proposals.insert(proposal_id, proposal);
+            return self.drive_raft(&mut node).await;
+        }
+
         // Store proposal
         self.proposals.insert(proposal_id, proposal.clone());
 
@@ -168,7 +324,488 @@ impl ConsensusEngine {
         // Broadcast proposal
         self.broadcast_message(ConsensusMessage::Propose(proposal)).await?;
 
-        Ok(proposal_id)
+        Ok(())
+    }
+
+    /// Submit an operation for batched, pipelined consensus.
+    /// 
+    /// The operation is queued and proposed together with other operations
+    /// submitted within the configured batch window (or as soon as
+    /// `max_batch_size` operations are waiting). Returns the ID of the
+    /// proposal that carries the operation once its batch has been proposed.
+    pub async fn submit_operation(&self, operation: Operation) -> Result<ProposalId> {
+        let (responder, proposal_id) = oneshot::channel();
+
+        if self.batcher.enqueue(operation, responder) {
+            self.flush_batch().await?;
+        }
+
+        proposal_id.await
+            .map_err(|_| anyhow::anyhow!("Batched operation was dropped before being proposed"))
+    }
+
+    /// Get batching and pipelining statistics.
+    pub fn get_batch_metrics(&self) -> BatchMetrics {
+        self.batcher.metrics()
+    }
+
+    /// Record a round-trip time measurement for a peer.
+    /// 
+    /// The network layer feeds ping/heartbeat RTTs here; they drive the
+    /// adaptive round and election timeouts.
+    pub fn record_peer_rtt(&self, peer_id: &PeerId, rtt: std::time::Duration) {
+        self.timeouts.record_rtt(peer_id, rtt);
+    }
+
+    /// Get the currently effective consensus timeouts and per-peer RTT statistics.
+    pub fn get_timeout_snapshot(&self) -> TimeoutSnapshot {
+        self.timeouts.snapshot()
+    }
+
+    /// Local BLS public key (hex) to announce to peers, if threshold signatures are enabled.
+    pub fn local_bls_public_key(&self) -> Option<String> {
+        self.threshold_signer.as_ref().map(|signer| signer.local_public_key())
+    }
+
+    /// Local proof of possession (hex) of the BLS secret key, announced
+    /// together with the public key.
+    pub fn local_bls_proof_of_possession(&self) -> Option<String> {
+        self.threshold_signer.as_ref().map(|signer| signer.local_proof_of_possession())
+    }
+
+    /// Register a peer's BLS public key and its proof of possession so its
+    /// votes can be aggregated.
+    pub fn register_peer_bls_key(&self, peer_id: &PeerId, public_key_hex: &str, proof_of_possession_hex: &str) -> Result<()> {
+        match &self.threshold_signer {
+            Some(signer) => signer.register_peer_key(peer_id, public_key_hex, proof_of_possession_hex),
+            None => Err(anyhow::anyhow!("Threshold signatures are not enabled")),
+        }
+    }
+
+    /// Threshold signatures are used only once the cluster reaches the configured size.
+    async fn active_threshold_signer(&self) -> Option<&Arc<ThresholdSignatureAggregator>> {
+        match &self.threshold_signer {
+            Some(signer) if self.get_peer_count().await >= self.config.threshold_signature_min_peers => Some(signer),
+            _ => None,
+        }
+    }
+
+    /// Get region health and the write quorum currently in force, for multi-region clusters.
+    pub fn get_topology_status(&self) -> Option<TopologyStatus> {
+        self.topology.as_ref().map(|topology| topology.status())
+    }
+
+    /// Detect lost and recovered regions and apply the failover policy.
+    /// 
+    /// Cross-datacenter replication to a lost region is suspended so writes
+    /// are not queued against datacenters that cannot acknowledge them, and
+    /// resumed once any of the region's peers responds again.
+    async fn evaluate_region_health(&self) {
+        let Some(topology) = &self.topology else {
+            return;
+        };
+
+        for event in topology.evaluate() {
+            match event {
+                RegionEvent::Lost(region) => {
+                    warn!(
+                        "Region {} lost; effective write quorum is now {:?}",
+                        region, topology.effective_quorum()
+                    );
+                    self.storage.suspend_datacenter_region(&region);
+                }
+                RegionEvent::Recovered(region) => {
+                    info!("Region {} recovered; resuming replication", region);
+                    self.storage.resume_datacenter_region(&region);
+                }
+            }
+        }
+    }
+
+    /// Export the committed operation log in replayable form.
+    /// 
+    /// Entries are numbered contiguously in commit order; `from_round` limits
+    /// the export to operations committed in or after that round. Entries
+    /// already compacted into a snapshot are not exported, and the sequence
+    /// numbers of later ones count them.
+    pub async fn export_operation_log(&self, from_round: Option<u64>) -> Vec<OperationLogEntry> {
+        let committed_log = self.committed_log.read().await;
+        let compacted = committed_log.compacted();
+        OperationLogEntry::from_committed(committed_log.entries())
+            .into_iter()
+            .filter(|entry| from_round.map(|round| entry.round >= round).unwrap_or(true))
+            .map(|entry| OperationLogEntry { sequence: entry.sequence + compacted, ..entry })
+            .collect()
+    }
+
+    /// Size of the committed log and its latest compaction.
+    pub async fn committed_log_status(&self) -> CommittedLogStatus {
+        self.committed_log.read().await.status()
+    }
+
+    /// Write the committed operation log to a newline-delimited JSON file.
+    pub async fn export_operation_log_to_file(&self, path: &std::path::Path) -> Result<usize> {
+        let entries = self.export_operation_log(None).await;
+        replay::write_log_file(path, &entries).await?;
+        info!("Exported {} committed operations to {}", entries.len(), path.display());
+        Ok(entries.len())
+    }
+
+    /// Acquire a named lease for `holder`, returning a fencing token when granted.
+    /// 
+    /// Re-acquiring a lease already held by the same holder extends it and keeps
+    /// the existing fencing token.
+    pub async fn acquire_lease(&self, name: &str, holder: &str, ttl: std::time::Duration) -> Result<LeaseOutcome> {
+        self.submit_lease_request(name, holder, LeaseAction::Acquire { ttl_ms: ttl.as_millis() as u64 }).await
+    }
+
+    /// Extend a held lease by its TTL.
+    pub async fn renew_lease(&self, name: &str, holder: &str, fencing_token: u64) -> Result<LeaseOutcome> {
+        self.submit_lease_request(name, holder, LeaseAction::Renew { fencing_token }).await
+    }
+
+    /// Release a held lease so other holders can acquire it immediately.
+    pub async fn release_lease(&self, name: &str, holder: &str, fencing_token: u64) -> Result<LeaseOutcome> {
+        self.submit_lease_request(name, holder, LeaseAction::Release { fencing_token }).await
+    }
+
+    /// Current holder of a lease, if it is held and has not expired.
+    pub async fn get_lease(&self, name: &str) -> Result<Option<Lease>> {
+        leases::read_lease(&self.storage, name, Utc::now()).await
+    }
+
+    /// Merge two concurrent versions of a document with the strategy
+    /// configured for its collection.
+    pub async fn resolve_conflict(&self, conflict: &Conflict) -> Result<ResolutionResult> {
+        self.conflict_resolver.resolve_conflict(conflict).await
+    }
+
+    /// Strategy resolving conflicts in `collection`.
+    pub fn conflict_resolution_for(&self, collection: &str) -> ConflictResolution {
+        self.conflict_resolver.strategy_for(collection).clone()
+    }
+
+    /// Propose a lease request and wait for it to commit.
+    async fn submit_lease_request(&self, name: &str, holder: &str, action: LeaseAction) -> Result<LeaseOutcome> {
+        let request = LeaseRequest {
+            request_id: Uuid::new_v4(),
+            name: name.to_string(),
+            holder: holder.to_string(),
+            requested_at: Utc::now(),
+            action,
+        };
+        leases::validate_lease_request(&request)?;
+
+        let request_id = request.request_id;
+        let (sender, receiver) = oneshot::channel();
+        self.lease_waiters.insert(request_id, sender);
+
+        // Lease requests bypass batching so they commit in their own round
+        if let Err(e) = self.propose_operation(Operation::Lease { request }).await {
+            self.lease_waiters.remove(&request_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.timeouts.round_timeout(), receiver).await {
+            Ok(Ok(outcome)) => Ok(outcome),
+            _ => {
+                self.lease_waiters.remove(&request_id);
+                Err(anyhow::anyhow!("Lease request for '{}' did not commit in time", name))
+            }
+        }
+    }
+
+    /// Replicate a document write of a strongly consistent collection and
+    /// wait for its outcome.
+    /// 
+    /// The write is batched with other submitted operations and applied on
+    /// every node when it commits. This returns once a quorum has
+    /// acknowledged it and it has been applied locally, with the outcome of
+    /// that application, such as a version mismatch.
+    pub async fn replicate_write(&self, write: ReplicatedWrite) -> Result<StorageResult<()>> {
+        let target = format!("{}:{}", write.collection(), write.document_id());
+        let request_id = Uuid::new_v4();
+        let (sender, receiver) = oneshot::channel();
+        self.write_waiters.insert(request_id, sender);
+
+        if let Err(e) = self.submit_operation(Operation::Write { request_id, write }).await {
+            self.write_waiters.remove(&request_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.timeouts.round_timeout(), receiver).await {
+            Ok(Ok(outcome)) => outcome,
+            _ => {
+                self.write_waiters.remove(&request_id);
+                Err(anyhow::anyhow!("Write to {} did not reach quorum in time", target))
+            }
+        }
+    }
+
+    /// Wait until this node can serve a read at `consistency`.
+    /// 
+    /// Under Raft, a leader holding its lease serves linearizable reads
+    /// straight away, and bounded-staleness reads are served by any node
+    /// whose lag behind its leader is within the bound. Other linearizable
+    /// reads, and bounded-staleness reads without Raft, which has no
+    /// measure of lag, wait for a read barrier to commit through the log.
+    pub async fn wait_for_read(&self, consistency: ReadConsistency) -> Result<()> {
+        let max_lag = match consistency {
+            ReadConsistency::Local => return Ok(()),
+            ReadConsistency::BoundedStaleness { max_lag } => Some(max_lag),
+            ReadConsistency::Linearizable => None,
+        };
+
+        if let Some(raft) = &self.raft {
+            // Committed entries are applied under the lock, so last_applied is what storage holds
+            let node = raft.lock().await;
+            let Some(max_lag) = max_lag else {
+                if node.read_index().is_some_and(|index| node.last_applied() >= index) {
+                    return Ok(());
+                }
+                drop(node);
+                return self.read_barrier().await;
+            };
+            let lag = node.staleness_ticks()
+                .map(|ticks| self.config.raft.tick_interval.saturating_mul(ticks.min(u32::MAX as u64) as u32));
+            return match lag {
+                Some(lag) if lag <= max_lag => Ok(()),
+                Some(lag) => Err(anyhow::anyhow!(
+                    "Stale read: this node may trail the cluster by {}ms, more than the {}ms allowed",
+                    lag.as_millis(),
+                    max_lag.as_millis()
+                )),
+                None => Err(anyhow::anyhow!("Stale read: this node cannot bound how far it trails the cluster")),
+            };
+        }
+
+        self.read_barrier().await
+    }
+
+    /// Commit a read barrier and wait for it to be applied.
+    async fn read_barrier(&self) -> Result<()> {
+        let request_id = Uuid::new_v4();
+        let (sender, receiver) = oneshot::channel();
+        self.read_waiters.insert(request_id, sender);
+
+        // Barriers bypass batching so reads are not held for the batch window
+        if let Err(e) = self.propose_operation(Operation::ReadBarrier { request_id }).await {
+            self.read_waiters.remove(&request_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.timeouts.round_timeout(), receiver).await {
+            Ok(Ok(())) => Ok(()),
+            _ => {
+                self.read_waiters.remove(&request_id);
+                Err(anyhow::anyhow!("Read barrier did not reach quorum in time"))
+            }
+        }
+    }
+
+    /// Send the writes of strongly consistent collections through consensus
+    /// and hold their consistent reads at it from now on.
+    /// 
+    /// The storage hierarchy only holds weak references, so the engine is
+    /// still dropped with its last owner.
+    pub fn route_consistent_collections(self: &Arc<Self>) {
+        let replicator = Arc::new(ConsensusReplicator { engine: Arc::downgrade(self) });
+        self.storage.set_write_replicator(replicator.clone());
+        self.storage.set_read_barrier(replicator);
+        info!("Strongly consistent collections are now written and read through consensus");
+    }
+
+    /// Runtime feature flag registry shared with integration points.
+    pub fn feature_flags(&self) -> Arc<FeatureFlagRegistry> {
+        Arc::clone(&self.feature_flags)
+    }
+
+    /// Propose a cluster-wide feature flag setting, or clear it with `None`.
+    /// 
+    /// The setting takes effect on each node when the proposal commits; a
+    /// node-local override still takes precedence on that node.
+    pub async fn set_cluster_feature(&self, name: &str, enabled: Option<bool>) -> Result<ProposalId> {
+        let change = FeatureFlagChange {
+            name: name.to_string(),
+            enabled,
+            requested_at: Utc::now(),
+        };
+        self.feature_flags.validate_change(&change)?;
+        self.propose_operation(Operation::FeatureFlag { change }).await
+    }
+
+    /// Raft role, term, leader, and log positions, when Raft is selected.
+    pub async fn raft_status(&self) -> Option<RaftStatus> {
+        match &self.raft {
+            Some(raft) => Some(raft.lock().await.status()),
+            None => None,
+        }
+    }
+
+    /// Propose adding a voter to the Raft cluster. Must run on the leader.
+    pub async fn add_raft_voter(&self, peer_id: &str) -> Result<LogIndex> {
+        let raft = self.raft.as_ref().ok_or_else(|| anyhow::anyhow!("Consensus algorithm is not Raft"))?;
+        let mut node = raft.lock().await;
+        let index = node.add_voter(peer_id.to_string())?;
+        self.drive_raft(&mut node).await?;
+        info!("Proposed adding Raft voter {} at log index {}", peer_id, index);
+        Ok(index)
+    }
+
+    /// Propose removing a voter from the Raft cluster. Must run on the leader.
+    pub async fn remove_raft_voter(&self, peer_id: &str) -> Result<LogIndex> {
+        let raft = self.raft.as_ref().ok_or_else(|| anyhow::anyhow!("Consensus algorithm is not Raft"))?;
+        let mut node = raft.lock().await;
+        let index = node.remove_voter(&peer_id.to_string())?;
+        self.drive_raft(&mut node).await?;
+        info!("Proposed removing Raft voter {} at log index {}", peer_id, index);
+        Ok(index)
+    }
+
+    /// Report Byzantine behavior observed for a peer.
+    /// 
+    /// If the fault proves misbehavior, as conflicting signed statements do,
+    /// or pushes the peer over the suspicion threshold it is quarantined:
+    /// excluded from consensus, quorums and replication targets, its
+    /// evidence persisted for operator review, and an alert raised.
+    pub async fn report_byzantine_fault(&self, fault: ByzantineFault) -> Result<()> {
+        let quarantined = self.byzantine_tolerance.write().await.report_fault(fault).await?;
+
+        if let Some(record) = quarantined {
+            self.storage.exclude_replication_peer(&record.node_id);
+            self.persist_quarantine_record(&record).await?;
+            error!(
+                "ALERT: node {} quarantined with {} evidence item(s); operator review required",
+                record.node_id,
+                record.evidence.len()
+            );
+            let _ = self.quarantine_alerts.send(record);
+        }
+
+        Ok(())
+    }
+
+    /// Record a signed statement of `signer` about `slot`, reporting it as
+    /// Byzantine if it signed a different statement for the same slot before.
+    ///
+    /// Returns whether the statement conflicted and should be ignored.
+    async fn detect_equivocation(
+        &self,
+        signer: &str,
+        kind: &str,
+        slot: ProposalId,
+        digest: &str,
+        signature: &str,
+    ) -> Result<bool> {
+        let statement = SignedStatement {
+            signer: signer.to_string(),
+            kind: kind.to_string(),
+            slot: slot.to_string(),
+            digest: digest.to_string(),
+            signature: signature.to_string(),
+            observed_at: Utc::now(),
+        };
+        let fault = self.byzantine_tolerance.write().await.observe_statement(statement);
+        match fault {
+            Some(fault) => {
+                warn!("Peer {} signed conflicting {}s for {}", signer, kind, slot);
+                self.report_byzantine_fault(fault).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Check whether a peer is currently quarantined.
+    pub async fn is_peer_quarantined(&self, peer_id: &str) -> bool {
+        self.byzantine_tolerance.read().await.is_node_quarantined(peer_id)
+    }
+
+    /// List quarantine records (both active and reinstated) for operator review.
+    pub async fn get_quarantine_records(&self) -> Vec<QuarantineRecord> {
+        let mut records = self.byzantine_tolerance.read().await.get_quarantine_records();
+        records.sort_by_key(|record| std::cmp::Reverse(record.quarantined_at));
+        records
+    }
+
+    /// Get the quarantine record and evidence for a single peer.
+    pub async fn get_quarantine_record(&self, peer_id: &str) -> Option<QuarantineRecord> {
+        self.byzantine_tolerance.read().await.get_quarantine_record(peer_id).cloned()
+    }
+
+    /// Reinstate a quarantined peer after operator review.
+    /// 
+    /// The peer rejoins consensus and replication; the persisted record is
+    /// updated with the operator and note for the audit trail.
+    pub async fn reinstate_peer(&self, peer_id: &str, operator: &str, note: &str) -> Result<QuarantineRecord> {
+        let record = self.byzantine_tolerance.write().await.reinstate_node(peer_id, operator, note)?;
+
+        self.storage.restore_replication_peer(peer_id);
+        self.persist_quarantine_record(&record).await?;
+        let _ = self.quarantine_alerts.send(record.clone());
+
+        Ok(record)
+    }
+
+    /// Subscribe to quarantine and reinstatement alerts.
+    pub fn subscribe_quarantine_alerts(&self) -> broadcast::Receiver<QuarantineRecord> {
+        self.quarantine_alerts.subscribe()
+    }
+
+    /// Persist a quarantine record so evidence survives restarts.
+    async fn persist_quarantine_record(&self, record: &QuarantineRecord) -> Result<()> {
+        let document = serde_json::to_value(record)?;
+        self.storage.store_document(QUARANTINE_COLLECTION, &record.node_id, &document).await?;
+        Ok(())
+    }
+
+    /// Restore quarantine records from storage and re-apply replication exclusions.
+    async fn load_quarantine_records(&self) -> Result<()> {
+        let node_ids = self.storage.list_documents(QUARANTINE_COLLECTION, None, None).await?;
+
+        for node_id in node_ids {
+            let stored = self.storage.get_document(QUARANTINE_COLLECTION, &node_id).await?;
+            let record: QuarantineRecord = match stored.data {
+                Some(document) => serde_json::from_value(document)?,
+                None => continue,
+            };
+
+            let mut byzantine_tolerance = self.byzantine_tolerance.write().await;
+            byzantine_tolerance.restore_quarantine_record(record);
+            if byzantine_tolerance.is_node_quarantined(&node_id) {
+                self.storage.exclude_replication_peer(&node_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Propose the next pending batch, waiting for a free pipeline slot first.
+    async fn flush_batch(&self) -> Result<()> {
+        let permit = self.batcher.acquire_pipeline_slot().await?;
+
+        let batch = match self.batcher.take_batch() {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+
+        debug!("Flushing consensus batch of {} operations", batch.len());
+
+        let (operation, responders) = batch.into_proposal();
+
+        // Track the batch before proposing: a Raft proposal can commit before this returns
+        let proposal_id = Uuid::new_v4();
+        self.batcher.track_inflight(proposal_id, permit);
+        if let Err(e) = self.propose_with_id(proposal_id, operation).await {
+            self.batcher.complete(&proposal_id, false);
+            return Err(e);
+        }
+
+        for responder in responders {
+            let _ = responder.send(proposal_id);
+        }
+
+        Ok(())
     }
 
     /// Submit a vote for a proposal.
@@ -215,6 +852,19 @@ impl ConsensusEngine {
             ConsensusMessage::ViewChange(view_change) => {
                 self.handle_view_change(view_change).await?;
             }
+            ConsensusMessage::InstallSnapshot(message) => {
+                self.handle_install_snapshot(message).await?;
+            }
+            ConsensusMessage::Raft(message) => {
+                match &self.raft {
+                    Some(raft) => {
+                        let mut node = raft.lock().await;
+                        node.step(message);
+                        self.drive_raft(&mut node).await?;
+                    }
+                    None => debug!("Ignoring Raft message from {}: Raft is not enabled", message.from),
+                }
+            }
         }
         Ok(())
     }
@@ -229,6 +879,18 @@ impl ConsensusEngine {
             return Ok(());
         }
 
+        // Quarantined peers are excluded from consensus
+        if self.is_peer_quarantined(&proposal.proposer).await {
+            warn!("Ignoring proposal {} from quarantined peer {}", proposal.id, proposal.proposer);
+            return Ok(());
+        }
+
+        // A proposer signs one operation per proposal id
+        let digest = blake3::hash(format!("{}:{}", proposal.round, serde_json::to_string(&proposal.operation)?).as_bytes());
+        if self.detect_equivocation(&proposal.proposer, "proposal", proposal.id, digest.to_hex().as_str(), &proposal.signature).await? {
+            return Ok(());
+        }
+
         // Check if we already have this proposal
         if self.proposals.contains_key(&proposal.id) {
             debug!("Proposal {} already exists", proposal.id);
@@ -261,54 +923,135 @@ impl ConsensusEngine {
     async fn process_vote(&self, vote: Vote) -> Result<()> {
         debug!("Processing vote from {} for proposal {}", vote.voter, vote.proposal_id);
 
-        // Verify vote signature
-        if !self.verify_vote_signature(&vote).await? {
-            warn!("Invalid vote signature from {}", vote.voter);
+        // Quarantined peers are excluded from consensus
+        if self.is_peer_quarantined(&vote.voter).await {
+            debug!("Ignoring vote from quarantined peer {}", vote.voter);
             return Ok(());
         }
 
-        // Update vote collection
-        if let Some(mut vote_collection) = self.votes.get_mut(&vote.proposal_id) {
-            let vote_proposal_id = vote.proposal_id;
-            vote_collection.votes.insert(vote.voter.clone(), vote);
+        // With threshold signatures, partials are verified once in aggregate;
+        // otherwise every vote signature is verified individually
+        let aggregator = self.active_threshold_signer().await
+            .filter(|signer| signer.has_peer_key(&vote.voter))
+            .cloned();
+        match &aggregator {
+            Some(signer) => {
+                if let Err(e) = signer.add_partial(vote.proposal_id, &vote.voter, &vote.decision, &vote.signature) {
+                    warn!("Rejecting vote from {}: {}", vote.voter, e);
+                    return Ok(());
+                }
+            }
+            None => {
+                if !self.verify_vote_signature(&vote).await? {
+                    warn!("Invalid vote signature from {}", vote.voter);
+                    return Ok(());
+                }
+            }
+        }
 
-            // Check if threshold is reached
-            if !vote_collection.threshold_reached {
-                let (accept_count, reject_count) = self.count_votes(&vote_collection.votes);
-                let total_peers = self.get_peer_count().await;
-                let threshold = (total_peers * 2) / 3 + 1; // 2/3 + 1 majority
+        // Detect double voting: the same voter signing conflicting decisions
+        let decision = format!("{:?}", vote.decision);
+        if self.detect_equivocation(&vote.voter, "vote", vote.proposal_id, &decision, &vote.signature).await? {
+            return Ok(());
+        }
 
-                if accept_count >= threshold {
-                    vote_collection.threshold_reached = true;
-                    self.commit_proposal(vote_proposal_id).await?;
-                } else if reject_count >= threshold {
-                    vote_collection.threshold_reached = true;
-                    self.abort_proposal(vote_proposal_id, "Majority rejection".to_string()).await?;
+        // Update vote collection (the guard is released before committing or aborting)
+        let vote_proposal_id = vote.proposal_id;
+        let current_votes = match self.votes.get_mut(&vote_proposal_id) {
+            Some(mut vote_collection) => {
+                vote_collection.votes.insert(vote.voter.clone(), vote);
+                if vote_collection.threshold_reached {
+                    None
+                } else {
+                    Some(vote_collection.votes.clone())
                 }
             }
+            None => None,
+        };
+
+        // Check if threshold is reached, without votes of peers quarantined since they voted
+        if let Some(mut current_votes) = current_votes {
+            let quarantined = self.byzantine_tolerance.read().await.quarantined_node_ids();
+            current_votes.retain(|voter, _| !quarantined.contains(voter));
+            let threshold = self.quorum_threshold().await;
+            let Some(decision) = self.quorum_decision(&current_votes, threshold) else {
+                return Ok(());
+            };
+
+            let certificate = match &aggregator {
+                Some(signer) => match self.aggregate_votes(signer, vote_proposal_id, &decision, threshold).await? {
+                    Some(certificate) => Some(certificate),
+                    None => return Ok(()),
+                },
+                None => None,
+            };
+
+            if let Some(mut vote_collection) = self.votes.get_mut(&vote_proposal_id) {
+                vote_collection.threshold_reached = true;
+            }
+
+            match decision {
+                VoteDecision::Accept => self.commit_proposal(vote_proposal_id, certificate).await?,
+                _ => self.abort_proposal(vote_proposal_id, "Majority rejection".to_string()).await?,
+            }
         }
 
         Ok(())
     }
 
+    /// Aggregate partial signatures into a quorum certificate.
+    /// 
+    /// Signers whose partials fail verification are dropped from the vote
+    /// collection and reported as Byzantine. Returns `None` if the remaining
+    /// valid partials no longer reach the threshold.
+    async fn aggregate_votes(
+        &self,
+        signer: &ThresholdSignatureAggregator,
+        proposal_id: ProposalId,
+        decision: &VoteDecision,
+        threshold: usize,
+    ) -> Result<Option<AggregatedCertificate>> {
+        let (outcome, invalid_signers) = signer.try_aggregate(proposal_id, decision, threshold)?;
+
+        for node_id in invalid_signers {
+            if let Some(mut vote_collection) = self.votes.get_mut(&proposal_id) {
+                vote_collection.votes.remove(&node_id);
+            }
+            self.report_byzantine_fault(ByzantineFault::InvalidSignature {
+                node_id,
+                message_hash: proposal_id.to_string(),
+            }).await?;
+        }
+
+        Ok(match outcome {
+            AggregationOutcome::Certified(certificate) => Some(certificate),
+            AggregationOutcome::Pending => None,
+        })
+    }
+
     /// Commit a proposal that has achieved consensus.
-    async fn commit_proposal(&self, proposal_id: ProposalId) -> Result<()> {
+    async fn commit_proposal(&self, proposal_id: ProposalId, certificate: Option<AggregatedCertificate>) -> Result<()> {
         info!("Committing proposal: {}", proposal_id);
 
-        if let Some(proposal) = self.proposals.get(&proposal_id) {
-            if let Some(votes) = self.votes.get(&proposal_id) {
+        let proposal = self.proposals.get(&proposal_id).map(|entry| entry.clone());
+        let votes = self.votes.get(&proposal_id).map(|entry| entry.clone());
+
+        if let Some(proposal) = proposal {
+            if let Some(votes) = votes {
                 // Execute the operation
                 self.execute_operation(&proposal.operation).await?;
 
                 // Add to committed log
                 let committed_entry = CommittedEntry {
                     proposal: proposal.clone(),
-                    votes: votes.clone(),
+                    votes,
                     committed_at: Utc::now(),
                     consensus_round: proposal.round,
                 };
 
                 self.committed_log.write().await.push(committed_entry);
+                self.batcher.complete(&proposal_id, true);
+                self.compact_log_if_due().await?;
 
                 // Update vector clock
                 self.vector_clock.write().await.increment(proposal.proposer.clone());
@@ -318,6 +1061,7 @@ impl ConsensusEngine {
                     proposal_id,
                     round: proposal.round,
                     committed_at: Utc::now(),
+                    certificate,
                 };
 
                 self.broadcast_message(ConsensusMessage::Commit(commit_message)).await?;
@@ -325,6 +1069,9 @@ impl ConsensusEngine {
                 // Clean up
                 self.proposals.remove(&proposal_id);
                 self.votes.remove(&proposal_id);
+                if let Some(signer) = &self.threshold_signer {
+                    signer.clear(&proposal_id);
+                }
             }
         }
 
@@ -335,45 +1082,274 @@ impl ConsensusEngine {
     async fn abort_proposal(&self, proposal_id: ProposalId, reason: String) -> Result<()> {
         warn!("Aborting proposal {}: {}", proposal_id, reason);
 
-        if let Some(proposal) = self.proposals.get(&proposal_id) {
+        let round = self.proposals.get(&proposal_id).map(|proposal| proposal.round);
+
+        if let Some(round) = round {
             let abort_message = AbortMessage {
                 proposal_id,
-                round: proposal.round,
+                round,
                 reason,
             };
 
             self.broadcast_message(ConsensusMessage::Abort(abort_message)).await?;
+            self.batcher.complete(&proposal_id, false);
 
             // Clean up
             self.proposals.remove(&proposal_id);
             self.votes.remove(&proposal_id);
+            if let Some(signer) = &self.threshold_signer {
+                signer.clear(&proposal_id);
+            }
         }
 
         Ok(())
     }
 
     /// Execute an operation that has been committed.
-    async fn execute_operation(&self, operation: &Operation) -> Result<()> {
-        match operation {
-            Operation::Insert { collection, document_id, data: _ } => {
-                debug!("Executing insert: {}:{}", collection, document_id);
-                // Implementation would call storage layer
+    fn execute_operation<'a>(
+        &'a self,
+        operation: &'a Operation,
+    ) -> futures::future::BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match operation {
+                Operation::Insert { collection, document_id, data: _ } => {
+                    debug!("Executing insert: {}:{}", collection, document_id);
+                    // Implementation would call storage layer
+                }
+                Operation::Update { collection, document_id, data: _, version } => {
+                    debug!("Executing update: {}:{} v{}", collection, document_id, version);
+                    // Implementation would call storage layer with conflict resolution
+                }
+                Operation::Delete { collection, document_id, version } => {
+                    debug!("Executing delete: {}:{} v{}", collection, document_id, version);
+                    // Implementation would call storage layer
+                }
+                Operation::CreateCollection { name, schema: _ } => {
+                    debug!("Executing create collection: {}", name);
+                    // Implementation would call storage layer
+                }
+                Operation::DropCollection { name } => {
+                    debug!("Executing drop collection: {}", name);
+                    // Implementation would call storage layer
+                }
+                Operation::Batch { operations } => {
+                    debug!("Executing batch of {} operations", operations.len());
+                    for operation in operations {
+                        self.execute_operation(operation).await?;
+                    }
+                }
+                Operation::Lease { request } => {
+                    debug!("Executing lease request {} for {}", request.request_id, request.name);
+                    let outcome = leases::apply_lease_request(&self.storage, request).await?;
+                    if let Some((_, waiter)) = self.lease_waiters.remove(&request.request_id) {
+                        // The caller may have timed out already
+                        let _ = waiter.send(outcome);
+                    }
+                }
+                Operation::FeatureFlag { change } => {
+                    debug!("Executing feature flag change for {}", change.name);
+                    self.feature_flags.apply_cluster_change(change).await?;
+                }
+                Operation::ReadBarrier { request_id } => {
+                    debug!("Executing read barrier {}", request_id);
+                    if let Some((_, waiter)) = self.read_waiters.remove(request_id) {
+                        // The reader may have timed out already
+                        let _ = waiter.send(());
+                    }
+                }
+                Operation::Write { request_id, write } => {
+                    debug!("Executing write {} to {}:{}", request_id, write.collection(), write.document_id());
+                    // A refused write is its caller's outcome, not a failure of the commit
+                    let outcome = self.storage.apply_replicated_write(write).await;
+                    match self.write_waiters.remove(request_id) {
+                        // The caller may have timed out already
+                        Some((_, waiter)) => {
+                            let _ = waiter.send(outcome);
+                        }
+                        None => {
+                            if let Err(e) = outcome {
+                                warn!("Committed write to {}:{} was refused: {}", write.collection(), write.document_id(), e);
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Carry out the work a Raft step produced: send its messages, restore a
+    /// received snapshot, apply committed entries, and compact the log.
+    /// 
+    /// Runs under the node's lock so entries are applied once and in order.
+    async fn drive_raft(&self, node: &mut RaftNode) -> Result<()> {
+        loop {
+            let ready = node.ready();
+            if ready.is_empty() {
+                break;
+            }
+            for message in ready.messages {
+                self.broadcast_message(ConsensusMessage::Raft(message)).await?;
+            }
+            if let Some(snapshot) = ready.snapshot {
+                info!("Restoring Raft snapshot at log index {}", snapshot.last_index);
+                self.restore_state_machine(&snapshot.data).await?;
+                self.committed_log.write().await.truncate_through(snapshot.last_index);
+            }
+            for entry in ready.committed {
+                self.apply_raft_entry(entry).await?;
+            }
+        }
+
+        if node.should_compact() {
+            let index = node.last_applied();
+            let data = self.state_machine_snapshot().await?;
+            node.compact(index, data)?;
+            self.committed_log.write().await.truncate_through(index);
+            debug!("Compacted Raft log up to index {}", index);
+        }
+        Ok(())
+    }
+
+    /// Apply a committed Raft entry and record it in the committed log.
+    async fn apply_raft_entry(&self, entry: LogEntry) -> Result<()> {
+        let proposal = match entry.payload {
+            EntryPayload::Proposal(proposal) => proposal,
+            EntryPayload::Membership(voters) => {
+                info!("Raft voters changed at log index {}: {:?}", entry.index, voters);
+                return Ok(());
             }
-            Operation::Update { collection, document_id, data: _, version } => {
-                debug!("Executing update: {}:{} v{}", collection, document_id, version);
-                // Implementation would call storage layer with conflict resolution
+            EntryPayload::Noop => return Ok(()),
+        };
+
+        self.execute_operation(&proposal.operation).await?;
+        self.vector_clock.write().await.increment(proposal.proposer.clone());
+        self.batcher.complete(&proposal.id, true);
+        self.proposals.remove(&proposal.id);
+
+        // Raft commits on a majority of replicas rather than collected votes
+        self.committed_log.write().await.push(CommittedEntry {
+            votes: VoteCollection {
+                proposal_id: proposal.id,
+                votes: HashMap::new(),
+                threshold_reached: true,
+            },
+            proposal,
+            committed_at: Utc::now(),
+            consensus_round: entry.index,
+        });
+        Ok(())
+    }
+
+    /// Compact the committed log of the vote-based algorithms into a
+    /// snapshot once it outgrows `ConsensusConfig::snapshots`. Raft compacts
+    /// its own log in `drive_raft`.
+    async fn compact_log_if_due(&self) -> Result<()> {
+        if self.raft.is_some() {
+            return Ok(());
+        }
+        let Ok(_compacting) = self.compacting.try_lock() else {
+            return Ok(());
+        };
+        let count = {
+            let log = self.committed_log.read().await;
+            if !log.should_compact(&self.config.snapshots) {
+                return Ok(());
+            }
+            log.entries().len()
+        };
+
+        // Captured after counting, so it covers at least the counted entries
+        let data = self.state_machine_snapshot().await?;
+        let mut log = self.committed_log.write().await;
+        let snapshot = log.compact(count, data);
+        info!(
+            "Compacted committed log through round {} ({} entries compacted so far)",
+            snapshot.last_round, snapshot.compacted_entries
+        );
+        Ok(())
+    }
+
+    /// Capture the replicated state machine (leases, cluster-wide feature
+    /// flags and the documents of strongly consistent collections) for a
+    /// snapshot.
+    async fn state_machine_snapshot(&self) -> Result<serde_json::Value> {
+        let mut lease_records = serde_json::Map::new();
+        for name in self.storage.list_documents(LEASE_COLLECTION, None, None).await? {
+            if let Some(record) = self.storage.get_document(LEASE_COLLECTION, &name).await?.data {
+                lease_records.insert(name, record);
+            }
+        }
+
+        let flags: serde_json::Map<String, serde_json::Value> = self.feature_flags
+            .snapshot()
+            .into_iter()
+            .filter_map(|status| status.cluster.map(|enabled| (status.name, serde_json::Value::Bool(enabled))))
+            .collect();
+
+        let mut documents = serde_json::Map::new();
+        for collection in self.storage.list_collections() {
+            if collection.config.consistency != WriteConsistency::Strong {
+                continue;
+            }
+            let mut records = serde_json::Map::new();
+            for document_id in self.storage.list_documents(&collection.name, None, None).await? {
+                if let Some(document) = self.storage.get_document(&collection.name, &document_id).await?.data {
+                    records.insert(document_id, document);
+                }
             }
-            Operation::Delete { collection, document_id, version } => {
-                debug!("Executing delete: {}:{} v{}", collection, document_id, version);
-                // Implementation would call storage layer
+            documents.insert(collection.name, serde_json::Value::Object(records));
+        }
+
+        Ok(serde_json::json!({ "leases": lease_records, "feature_flags": flags, "documents": documents }))
+    }
+
+    /// Replace the replicated state machine with the contents of a snapshot.
+    async fn restore_state_machine(&self, data: &serde_json::Value) -> Result<()> {
+        let empty = serde_json::Map::new();
+        let lease_records = data.get("leases").and_then(|value| value.as_object()).unwrap_or(&empty);
+        for name in self.storage.list_documents(LEASE_COLLECTION, None, None).await? {
+            if !lease_records.contains_key(&name) {
+                self.storage.delete_document(LEASE_COLLECTION, &name).await?;
+            }
+        }
+        for (name, record) in lease_records {
+            self.storage.store_document(LEASE_COLLECTION, name, record).await?;
+        }
+
+        // Applied locally, since the snapshot is already agreed state
+        let documents = data.get("documents").and_then(|value| value.as_object()).unwrap_or(&empty);
+        for (collection, records) in documents {
+            let records = records.as_object().unwrap_or(&empty);
+            for document_id in self.storage.list_documents(collection, None, None).await? {
+                if !records.contains_key(&document_id) {
+                    let write = ReplicatedWrite::Delete { collection: collection.clone(), document_id };
+                    self.storage.apply_replicated_write(&write).await?;
+                }
             }
-            Operation::CreateCollection { name, schema: _ } => {
-                debug!("Executing create collection: {}", name);
-                // Implementation would call storage layer
+            for (document_id, document) in records {
+                let write = ReplicatedWrite::Store {
+                    collection: collection.clone(),
+                    document_id: document_id.clone(),
+                    data: document.clone(),
+                    mode: WriteMode::Upsert,
+                    expires_at: None,
+                    skip_compression: false,
+                };
+                self.storage.apply_replicated_write(&write).await?;
             }
-            Operation::DropCollection { name } => {
-                debug!("Executing drop collection: {}", name);
-                // Implementation would call storage layer
+        }
+
+        let flags = data.get("feature_flags").and_then(|value| value.as_object()).unwrap_or(&empty);
+        for status in self.feature_flags.snapshot() {
+            let enabled = flags.get(&status.name).and_then(|value| value.as_bool());
+            if enabled != status.cluster {
+                let change = FeatureFlagChange {
+                    name: status.name,
+                    enabled,
+                    requested_at: Utc::now(),
+                };
+                self.feature_flags.apply_cluster_change(&change).await?;
             }
         }
         Ok(())
@@ -406,6 +1382,81 @@ impl ConsensusEngine {
             }
         });
 
+        // Region health task: detects region loss and applies the failover policy
+        if self.topology.is_some() {
+            let engine = Arc::new(self.clone());
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+                loop {
+                    interval.tick().await;
+                    engine.evaluate_region_health().await;
+                }
+            });
+        }
+
+        // Batch flush task: proposes partially filled batches once their window elapses
+        let engine = Arc::new(self.clone());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(engine.batcher.batch_window());
+            loop {
+                interval.tick().await;
+                if engine.batcher.window_elapsed() {
+                    if let Err(e) = engine.flush_batch().await {
+                        error!("Error flushing consensus batch: {}", e);
+                    }
+                }
+            }
+        });
+
+        // Compaction task: compacts the committed log once its interval has passed
+        if self.raft.is_none() && self.config.snapshots.interval.is_some() {
+            let engine = Arc::new(self.clone());
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = engine.compact_log_if_due().await {
+                        error!("Error compacting committed log: {}", e);
+                    }
+                }
+            });
+        }
+
+        // Raft tick task: drives elections and heartbeats
+        if let Some(raft) = &self.raft {
+            let engine = Arc::new(self.clone());
+            let raft = Arc::clone(raft);
+            let tick_interval = self.config.raft.tick_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(tick_interval);
+                loop {
+                    interval.tick().await;
+                    let mut node = raft.lock().await;
+                    node.set_election_timeout(engine.timeouts.election_timeout());
+                    node.tick();
+                    if let Err(e) = engine.drive_raft(&mut node).await {
+                        error!("Error driving Raft: {}", e);
+                    }
+                }
+            });
+        }
+
+        // View-change timer: checked at the timeout floor so it fires soon
+        // after rounds have stalled for the RTT-derived election timeout
+        if self.raft.is_none() {
+            let engine = Arc::new(self.clone());
+            let check_interval = self.config.min_timeout.max(std::time::Duration::from_millis(10));
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(check_interval);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = engine.check_view_change().await {
+                        error!("Error checking for a view change: {}", e);
+                    }
+                }
+            });
+        }
+
         // Cleanup task
         let engine = Arc::new(self.clone());
         tokio::spawn(async move {
@@ -430,18 +1481,64 @@ impl ConsensusEngine {
         self.broadcast_message(ConsensusMessage::Heartbeat(heartbeat)).await
     }
 
-    /// Clean up old proposals that have timed out.
+    /// Current PBFT view.
+    pub fn current_view(&self) -> u64 {
+        self.view_timer.lock().unwrap().view
+    }
+
+    /// Request the next view when proposals have waited longer than the
+    /// election timeout without a round committing.
+    async fn check_view_change(&self) -> Result<()> {
+        let last_round = self.get_last_committed_round().await;
+        let pending = !self.proposals.is_empty();
+        if let Some(new_view) = self.claim_view_change(last_round, pending, std::time::Instant::now()) {
+            warn!(
+                "No round committed within {:?} after round {}; requesting view {}",
+                self.timeouts.election_timeout(), last_round, new_view
+            );
+            let view_change = ViewChangeMessage {
+                new_view,
+                peer_id: self.get_local_peer_id().await,
+                last_committed: last_round,
+            };
+            self.broadcast_message(ConsensusMessage::ViewChange(view_change)).await?;
+        }
+        Ok(())
+    }
+
+    /// Advance the view timer to `now` and claim the next view if rounds
+    /// have stalled for the election timeout.
+    fn claim_view_change(&self, last_round: u64, pending: bool, now: std::time::Instant) -> Option<u64> {
+        let mut timer = self.view_timer.lock().unwrap();
+        if last_round != timer.round || !pending {
+            timer.round = last_round;
+            timer.since = now;
+            return None;
+        }
+        if now.saturating_duration_since(timer.since) < self.timeouts.election_timeout() {
+            return None;
+        }
+        // A view counter that cannot advance stays put rather than wrapping to 0
+        timer.view = timer.view.checked_add(1)?;
+        timer.since = now;
+        Some(timer.view)
+    }
+
+    /// Clean up old proposals that have exceeded the current round timeout.
     async fn cleanup_old_proposals(&self) {
-        let cutoff = Utc::now() - chrono::Duration::minutes(10);
+        let round_timeout = chrono::Duration::from_std(self.timeouts.round_timeout())
+            .unwrap_or_else(|_| chrono::Duration::minutes(10));
+        let cutoff = Utc::now() - round_timeout;
         
         self.proposals.retain(|_, proposal| proposal.timestamp > cutoff);
         self.votes.retain(|proposal_id, _| self.proposals.contains_key(proposal_id));
+        self.batcher.release_stale(|proposal_id| self.proposals.contains_key(proposal_id));
     }
 
     // Helper methods for consensus operation
 
     async fn get_current_round(&self) -> u64 {
-        self.committed_log.read().await.len() as u64 + 1
+        self.committed_log.read().await.next_round()
     }
 
     async fn get_local_peer_id(&self) -> PeerId {
@@ -458,11 +1555,14 @@ impl ConsensusEngine {
         3 // Simulated 3-node cluster for development
     }
 
+    /// Peers counted towards quorums: all but the quarantined ones
+    async fn active_peer_count(&self) -> usize {
+        let quarantined = self.byzantine_tolerance.read().await.quarantined_node_ids().len();
+        self.get_peer_count().await.saturating_sub(quarantined).max(1)
+    }
+
     async fn get_last_committed_round(&self) -> u64 {
-        self.committed_log.read().await
-            .last()
-            .map(|entry| entry.consensus_round)
-            .unwrap_or(0)
+        self.committed_log.read().await.last_round()
     }
 
     async fn sign_proposal(&self, proposal_id: &ProposalId) -> Result<String> {
@@ -473,6 +1573,10 @@ impl ConsensusEngine {
     }
 
     async fn sign_vote(&self, proposal_id: &ProposalId, decision: &VoteDecision) -> Result<String> {
+        if let Some(signer) = self.active_threshold_signer().await {
+            return Ok(signer.sign_vote(proposal_id, decision));
+        }
+
         // Current implementation: Deterministic signature for testing and development
         // Security framework integration planned: Use SecurityFramework for cryptographic signing
         // including vote content and voter identity for tamper detection
@@ -499,6 +1603,46 @@ impl ConsensusEngine {
         Ok(true)
     }
 
+    /// Minimum number of votes that can form a quorum.
+    async fn quorum_threshold(&self) -> usize {
+        match &self.topology {
+            Some(topology) => topology.required_votes(),
+            None => (self.active_peer_count().await * 2) / 3 + 1, // 2/3 + 1 majority
+        }
+    }
+
+    /// Decide a proposal once enough votes agree.
+    /// 
+    /// Multi-region clusters apply the per-region quorum rules of the
+    /// topology; otherwise a plain cluster-wide count is used.
+    fn quorum_decision(&self, votes: &HashMap<PeerId, Vote>, threshold: usize) -> Option<VoteDecision> {
+        if let Some(topology) = &self.topology {
+            let voters_for = |decision: VoteDecision| -> Vec<PeerId> {
+                votes.values()
+                    .filter(|vote| std::mem::discriminant(&vote.decision) == std::mem::discriminant(&decision))
+                    .map(|vote| vote.voter.clone())
+                    .collect()
+            };
+
+            return if topology.quorum_reached(&voters_for(VoteDecision::Accept)) {
+                Some(VoteDecision::Accept)
+            } else if topology.quorum_reached(&voters_for(VoteDecision::Reject)) {
+                Some(VoteDecision::Reject)
+            } else {
+                None
+            };
+        }
+
+        let (accept_count, reject_count) = self.count_votes(votes);
+        if accept_count >= threshold {
+            Some(VoteDecision::Accept)
+        } else if reject_count >= threshold {
+            Some(VoteDecision::Reject)
+        } else {
+            None
+        }
+    }
+
     fn count_votes(&self, votes: &HashMap<PeerId, Vote>) -> (usize, usize) {
         let mut accept_count = 0;
         let mut reject_count = 0;
@@ -524,13 +1668,41 @@ impl ConsensusEngine {
 
     async fn handle_commit(&self, commit: CommitMessage) -> Result<()> {
         debug!("Handling commit message for proposal: {}", commit.proposal_id);
-        // Current implementation: Basic logging and validation
-        // Network integration planned:
-        // - Verify commit authenticity and consensus validity
-        // - Apply committed operation to local storage
-        // - Update local consensus state and vector clocks
-        // - Trigger any dependent operations or callbacks
-        Ok(())
+
+        // A single aggregate verification replaces checking every vote
+        // signature, so commits without a certificate are refused
+        if let Some(signer) = self.active_threshold_signer().await {
+            let Some(certificate) = &commit.certificate else {
+                warn!("Rejecting commit for {}: missing aggregate certificate", commit.proposal_id);
+                return Ok(());
+            };
+            let threshold = self.quorum_threshold().await;
+            if !signer.verify_certificate(certificate, threshold)? {
+                warn!("Rejecting commit for {}: invalid aggregate certificate", commit.proposal_id);
+                return Ok(());
+            }
+        }
+
+        // Apply the proposal this node voted on, once, like the proposer did
+        let Some((_, proposal)) = self.proposals.remove(&commit.proposal_id) else {
+            debug!("Commit for unknown proposal {}", commit.proposal_id);
+            return Ok(());
+        };
+        let votes = self.votes.remove(&commit.proposal_id).map(|(_, votes)| votes).unwrap_or(VoteCollection {
+            proposal_id: commit.proposal_id,
+            votes: HashMap::new(),
+            threshold_reached: true,
+        });
+
+        self.execute_operation(&proposal.operation).await?;
+        self.vector_clock.write().await.increment(proposal.proposer.clone());
+        self.committed_log.write().await.push(CommittedEntry {
+            proposal,
+            votes,
+            committed_at: commit.committed_at,
+            consensus_round: commit.round,
+        });
+        self.compact_log_if_due().await
     }
 
     async fn handle_abort(&self, abort: AbortMessage) -> Result<()> {
@@ -546,6 +1718,34 @@ impl ConsensusEngine {
 
     async fn handle_heartbeat(&self, heartbeat: HeartbeatMessage) -> Result<()> {
         debug!("Handling heartbeat from: {}", heartbeat.peer_id);
+
+        if let Some(topology) = &self.topology {
+            topology.record_heartbeat(&heartbeat.peer_id);
+        }
+
+        // Approximate RTT as twice the one-way delay; skip samples distorted by clock skew
+        if let Ok(one_way) = (Utc::now() - heartbeat.timestamp).to_std() {
+            self.timeouts.record_rtt(&heartbeat.peer_id, one_way * 2);
+        }
+
+        // A peer behind our latest snapshot cannot catch up from the log any
+        // more, so it gets the snapshot; Raft transfers its own
+        let local = self.get_local_peer_id().await;
+        if self.raft.is_none() && heartbeat.peer_id != local {
+            let snapshot = self.committed_log.read().await
+                .snapshot()
+                .filter(|snapshot| heartbeat.last_committed_round < snapshot.last_round)
+                .cloned();
+            if let Some(snapshot) = snapshot {
+                info!(
+                    "Sending snapshot at round {} to {}, which is at round {}",
+                    snapshot.last_round, heartbeat.peer_id, heartbeat.last_committed_round
+                );
+                let message = SnapshotMessage { from: local, to: heartbeat.peer_id.clone(), snapshot };
+                self.broadcast_message(ConsensusMessage::InstallSnapshot(message)).await?;
+            }
+        }
+
         // Current implementation: Basic peer tracking
         // Network integration planned:
         // - Update peer liveness and connectivity status
@@ -555,13 +1755,74 @@ impl ConsensusEngine {
         Ok(())
     }
 
+    /// Install a snapshot from a peer this node has fallen behind, replacing
+    /// the state machine and the log entries it covers.
+    async fn handle_install_snapshot(&self, message: SnapshotMessage) -> Result<()> {
+        if self.raft.is_some() || message.to != self.get_local_peer_id().await {
+            return Ok(());
+        }
+        let last_round = self.get_last_committed_round().await;
+        if message.snapshot.last_round <= last_round {
+            debug!(
+                "Ignoring snapshot at round {} from {}: already at round {}",
+                message.snapshot.last_round, message.from, last_round
+            );
+            return Ok(());
+        }
+
+        info!("Installing snapshot at round {} from {}", message.snapshot.last_round, message.from);
+        self.restore_state_machine(&message.snapshot.data).await?;
+        self.committed_log.write().await.install(message.snapshot);
+        Ok(())
+    }
+
     async fn handle_view_change(&self, view_change: ViewChangeMessage) -> Result<()> {
         debug!("Handling view change to: {}", view_change.new_view);
-        // Implementation would handle view change for leader election
+
+        // View changes are unauthenticated, so only the immediate next view is
+        // adopted; jumping further would let one peer skip views (or exhaust the
+        // counter) without the cluster ever stalling in them
+        let mut timer = self.view_timer.lock().unwrap();
+        if timer.view.checked_add(1) != Some(view_change.new_view) {
+            if view_change.new_view > timer.view {
+                warn!("Ignoring view change to {} from {}: current view is {}",
+                      view_change.new_view, view_change.peer_id, timer.view);
+            }
+            return Ok(());
+        }
+        // Give the adopted view a full election timeout to make progress
+        timer.view = view_change.new_view;
+        timer.since = std::time::Instant::now();
         Ok(())
     }
 }
 
+/// Replicates the writes of strongly consistent collections through a
+/// consensus engine and holds their consistent reads at it.
+struct ConsensusReplicator {
+    engine: Weak<ConsensusEngine>,
+}
+
+impl ConsensusReplicator {
+    fn engine(&self) -> Result<Arc<ConsensusEngine>> {
+        self.engine.upgrade().ok_or_else(|| anyhow::anyhow!("Consensus engine has shut down"))
+    }
+}
+
+#[async_trait::async_trait]
+impl WriteReplicator for ConsensusReplicator {
+    async fn replicate(&self, write: ReplicatedWrite) -> Result<StorageResult<()>> {
+        self.engine()?.replicate_write(write).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadBarrier for ConsensusReplicator {
+    async fn wait_for(&self, consistency: ReadConsistency) -> Result<()> {
+        self.engine()?.wait_for_read(consistency).await
+    }
+}
+
 // Clone implementation for Arc usage in spawned tasks
 impl Clone for ConsensusEngine {
     fn clone(&self) -> Self {
@@ -572,12 +1833,391 @@ impl Clone for ConsensusEngine {
             vector_clock: Arc::clone(&self.vector_clock),
             conflict_resolver: Arc::clone(&self.conflict_resolver),
             byzantine_tolerance: Arc::clone(&self.byzantine_tolerance),
+            quarantine_alerts: self.quarantine_alerts.clone(),
             partition_recovery: Arc::clone(&self.partition_recovery),
             proposals: Arc::clone(&self.proposals),
             votes: Arc::clone(&self.votes),
             committed_log: Arc::clone(&self.committed_log),
+            compacting: Arc::clone(&self.compacting),
+            batcher: Arc::clone(&self.batcher),
+            timeouts: Arc::clone(&self.timeouts),
+            view_timer: Arc::clone(&self.view_timer),
+            threshold_signer: self.threshold_signer.clone(),
+            topology: self.topology.clone(),
+            lease_waiters: Arc::clone(&self.lease_waiters),
+            write_waiters: Arc::clone(&self.write_waiters),
+            read_waiters: Arc::clone(&self.read_waiters),
+            feature_flags: Arc::clone(&self.feature_flags),
+            raft: self.raft.clone(),
             message_sender: self.message_sender.clone(),
             message_receiver: Arc::clone(&self.message_receiver),
         }
     }
 }
+
+impl std::fmt::Debug for ConsensusEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConsensusEngine")
+            .field("algorithm", &self.config.algorithm)
+            .field("active_proposals", &self.proposals.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compaction::SnapshotConfig;
+    use crate::raft::RaftRole;
+
+    async fn engine_with(config: ConsensusConfig) -> ConsensusEngine {
+        let storage_config = aerolithdb_storage::StorageConfig {
+            data_dir: std::env::temp_dir().join(format!("aerolith-raft-{}", Uuid::new_v4())),
+            ..Default::default()
+        };
+        let storage = Arc::new(StorageHierarchy::new(&storage_config).await.unwrap());
+        let security = Arc::new(SecurityFramework::new(&Default::default()).await.unwrap());
+        ConsensusEngine::new(&config, security, storage).await.unwrap()
+    }
+
+    async fn raft_engine() -> ConsensusEngine {
+        engine_with(ConsensusConfig {
+            algorithm: ConsensusAlgorithm::Raft,
+            ..Default::default()
+        })
+        .await
+    }
+
+    fn committed_entry(round: u64) -> CommittedEntry {
+        let id = Uuid::new_v4();
+        CommittedEntry {
+            proposal: Proposal {
+                id,
+                round,
+                proposer: "peer_a".to_string(),
+                operation: Operation::CreateCollection { name: format!("c{}", round), schema: None },
+                timestamp: Utc::now(),
+                signature: String::new(),
+            },
+            votes: VoteCollection { proposal_id: id, votes: HashMap::new(), threshold_reached: true },
+            committed_at: Utc::now(),
+            consensus_round: round,
+        }
+    }
+
+    fn strong_collection(engine: &ConsensusEngine) {
+        let strong = aerolithdb_storage::CollectionConfig {
+            consistency: WriteConsistency::Strong,
+            ..Default::default()
+        };
+        engine.storage.create_collection("accounts", strong).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_committed_log_compacts_into_snapshot() {
+        let engine = engine_with(ConsensusConfig {
+            snapshots: SnapshotConfig { max_log_entries: 3, max_log_bytes: u64::MAX, interval: None },
+            ..Default::default()
+        })
+        .await;
+        strong_collection(&engine);
+        engine.storage.store_document("accounts", "a", &serde_json::json!({ "balance": 10 })).await.unwrap();
+
+        for round in 1..=2 {
+            engine.committed_log.write().await.push(committed_entry(round));
+        }
+        engine.compact_log_if_due().await.unwrap();
+        assert_eq!(engine.committed_log_status().await.compacted_entries, 0);
+
+        engine.committed_log.write().await.push(committed_entry(3));
+        engine.compact_log_if_due().await.unwrap();
+        let status = engine.committed_log_status().await;
+        assert_eq!((status.retained_entries, status.compacted_entries, status.snapshot_round), (0, 3, Some(3)));
+        assert_eq!(engine.get_current_round().await, 4);
+        assert_eq!(engine.get_last_committed_round().await, 3);
+
+        let log = engine.committed_log.read().await;
+        let snapshot = log.snapshot().unwrap();
+        assert_eq!(snapshot.data["documents"]["accounts"]["a"], serde_json::json!({ "balance": 10 }));
+        drop(log);
+
+        // Later entries keep numbering on from the compacted ones
+        engine.committed_log.write().await.push(committed_entry(4));
+        let exported = engine.export_operation_log(None).await;
+        assert_eq!(exported.len(), 1);
+        assert_eq!((exported[0].sequence, exported[0].round), (4, 4));
+    }
+
+    #[tokio::test]
+    async fn test_view_change_follows_adaptive_election_timeout() {
+        let engine = engine_with(ConsensusConfig::default()).await;
+        for _ in 0..10 {
+            engine.record_peer_rtt(&"peer-a".to_string(), std::time::Duration::from_millis(150));
+            engine.record_peer_rtt(&"peer-b".to_string(), std::time::Duration::from_millis(150));
+        }
+        let election_timeout = engine.get_timeout_snapshot().election_timeout;
+        assert_eq!(election_timeout, std::time::Duration::from_millis(1500));
+
+        // A new round restarts the timer; stalling past the timeout claims the next view
+        let start = std::time::Instant::now();
+        assert_eq!(engine.claim_view_change(1, true, start), None);
+        assert_eq!(engine.claim_view_change(1, true, start + std::time::Duration::from_millis(1400)), None);
+        assert_eq!(engine.claim_view_change(1, true, start + std::time::Duration::from_millis(1600)), Some(1));
+        assert_eq!(engine.current_view(), 1);
+
+        // Slower links stretch the timeout the next view gets
+        for _ in 0..128 {
+            engine.record_peer_rtt(&"peer-a".to_string(), std::time::Duration::from_millis(400));
+            engine.record_peer_rtt(&"peer-b".to_string(), std::time::Duration::from_millis(400));
+        }
+        let later = start + std::time::Duration::from_millis(1600);
+        assert_eq!(engine.claim_view_change(1, true, later + std::time::Duration::from_millis(3000)), None);
+        assert_eq!(engine.claim_view_change(1, true, later + std::time::Duration::from_millis(4100)), Some(2));
+
+        // Nothing pending means nothing has stalled
+        assert_eq!(engine.claim_view_change(1, false, later + std::time::Duration::from_secs(60)), None);
+
+        // A peer's next view is adopted, but it cannot skip ahead
+        let view_change = |new_view| ViewChangeMessage {
+            new_view,
+            peer_id: "peer-a".to_string(),
+            last_committed: 1,
+        };
+        engine.handle_view_change(view_change(7)).await.unwrap();
+        assert_eq!(engine.current_view(), 2);
+        engine.handle_view_change(view_change(u64::MAX)).await.unwrap();
+        assert_eq!(engine.current_view(), 2);
+        engine.handle_view_change(view_change(3)).await.unwrap();
+        assert_eq!(engine.current_view(), 3);
+
+        // The last view never wraps around
+        engine.view_timer.lock().unwrap().view = u64::MAX;
+        assert_eq!(engine.claim_view_change(1, true, later + std::time::Duration::from_secs(120)), None);
+        assert_eq!(engine.claim_view_change(1, true, later + std::time::Duration::from_secs(180)), None);
+        engine.handle_view_change(view_change(0)).await.unwrap();
+        assert_eq!(engine.current_view(), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_votes_quarantine_voter() {
+        let engine = engine_with(ConsensusConfig::default()).await;
+        let mut alerts = engine.subscribe_quarantine_alerts();
+        assert_eq!(engine.quorum_threshold().await, 3);
+
+        let proposal_id = Uuid::new_v4();
+        let vote = |decision: VoteDecision| Vote {
+            proposal_id,
+            voter: "peer_b".to_string(),
+            decision,
+            timestamp: Utc::now(),
+            signature: "signed".to_string(),
+        };
+        engine.process_vote(vote(VoteDecision::Accept)).await.unwrap();
+        engine.process_vote(vote(VoteDecision::Accept)).await.unwrap();
+        assert!(!engine.is_peer_quarantined("peer_b").await);
+
+        engine.process_vote(vote(VoteDecision::Reject)).await.unwrap();
+        assert!(engine.is_peer_quarantined("peer_b").await);
+        let record = alerts.try_recv().unwrap();
+        assert!(matches!(
+            &record.evidence[0].fault,
+            ByzantineFault::EquivocationAttack { statements, .. } if statements.len() == 2
+        ));
+        let stored = engine.storage.get_document(QUARANTINE_COLLECTION, "peer_b").await.unwrap();
+        assert!(stored.data.is_some());
+
+        // The quarantined peer no longer counts towards quorums
+        assert_eq!(engine.quorum_threshold().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_commits_need_a_valid_certificate_with_threshold_signatures() {
+        let engine = engine_with(ConsensusConfig {
+            threshold_signatures: true,
+            threshold_signature_min_peers: 0,
+            ..Default::default()
+        })
+        .await;
+        let signer = Arc::clone(engine.threshold_signer.as_ref().unwrap());
+        let peers: Vec<ThresholdSignatureAggregator> = ["peer_b", "peer_c"]
+            .iter()
+            .map(|peer| ThresholdSignatureAggregator::new(peer.to_string()).unwrap())
+            .collect();
+        for peer in &peers {
+            engine.register_peer_bls_key(peer.local_peer(), &peer.local_public_key(), &peer.local_proof_of_possession()).unwrap();
+        }
+
+        let entry = committed_entry(1);
+        let proposal_id = entry.proposal.id;
+        engine.proposals.insert(proposal_id, entry.proposal);
+        let commit = |certificate| CommitMessage { proposal_id, round: 1, committed_at: Utc::now(), certificate };
+
+        // Stripping the certificate does not skip its verification
+        engine.handle_commit(commit(None)).await.unwrap();
+        assert!(engine.proposals.contains_key(&proposal_id));
+
+        for voter in peers.iter().chain(std::iter::once(signer.as_ref())) {
+            let signature = voter.sign_vote(&proposal_id, &VoteDecision::Accept);
+            signer.add_partial(proposal_id, voter.local_peer(), &VoteDecision::Accept, &signature).unwrap();
+        }
+        let certificate = match signer.try_aggregate(proposal_id, &VoteDecision::Accept, 3).unwrap().0 {
+            AggregationOutcome::Certified(certificate) => certificate,
+            AggregationOutcome::Pending => panic!("expected certificate"),
+        };
+        engine.handle_commit(commit(Some(certificate))).await.unwrap();
+        assert!(!engine.proposals.contains_key(&proposal_id));
+        assert_eq!(engine.get_last_committed_round().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_peer_installs_snapshot() {
+        let leader = engine_with(ConsensusConfig {
+            snapshots: SnapshotConfig { max_log_entries: 1, max_log_bytes: u64::MAX, interval: None },
+            ..Default::default()
+        })
+        .await;
+        strong_collection(&leader);
+        leader.storage.store_document("accounts", "a", &serde_json::json!({ "balance": 10 })).await.unwrap();
+        for round in 1..=2 {
+            leader.committed_log.write().await.push(committed_entry(round));
+        }
+        leader.compact_log_if_due().await.unwrap();
+        let snapshot = leader.committed_log.read().await.snapshot().cloned().unwrap();
+
+        let lagging = engine_with(ConsensusConfig::default()).await;
+        strong_collection(&lagging);
+        lagging.storage.store_document("accounts", "stale", &serde_json::json!({ "balance": 1 })).await.unwrap();
+
+        // Snapshots meant for another peer are ignored
+        let elsewhere = SnapshotMessage { from: "peer_a".to_string(), to: "peer_b".to_string(), snapshot: snapshot.clone() };
+        lagging.process_message(ConsensusMessage::InstallSnapshot(elsewhere)).await.unwrap();
+        assert_eq!(lagging.get_last_committed_round().await, 0);
+
+        let message = SnapshotMessage { from: "peer_a".to_string(), to: "local_peer".to_string(), snapshot };
+        lagging.process_message(ConsensusMessage::InstallSnapshot(message)).await.unwrap();
+        assert_eq!(lagging.get_last_committed_round().await, 2);
+        assert_eq!(lagging.get_current_round().await, 3);
+        assert_eq!(
+            lagging.storage.get_document("accounts", "a").await.unwrap().data,
+            Some(serde_json::json!({ "balance": 10 }))
+        );
+        assert_eq!(lagging.storage.get_document("accounts", "stale").await.unwrap().data, None);
+    }
+
+    #[tokio::test]
+    async fn test_raft_compaction_truncates_committed_log() {
+        let engine = engine_with(ConsensusConfig {
+            algorithm: ConsensusAlgorithm::Raft,
+            raft: crate::raft::RaftConfig { snapshot_threshold: 2, ..Default::default() },
+            ..Default::default()
+        })
+        .await;
+        engine.start().await.unwrap();
+
+        for name in ["a", "b", "c"] {
+            engine.acquire_lease(name, "node-a", std::time::Duration::from_secs(10)).await.unwrap();
+        }
+
+        let status = engine.committed_log_status().await;
+        assert!(status.compacted_entries > 0);
+        assert!(status.retained_entries < 3);
+        assert_eq!(engine.raft_status().await.unwrap().role, RaftRole::Leader);
+    }
+
+    #[tokio::test]
+    async fn test_single_node_raft_commits_leases() {
+        let engine = raft_engine().await;
+        engine.start().await.unwrap();
+
+        let status = engine.raft_status().await.unwrap();
+        assert_eq!(status.role, RaftRole::Leader);
+
+        let outcome = engine.acquire_lease("leader", "node-a", std::time::Duration::from_secs(10)).await.unwrap();
+        assert!(matches!(outcome, LeaseOutcome::Granted { ref lease } if lease.fencing_token == 1));
+        assert!(engine.get_lease("leader").await.unwrap().is_some());
+
+        // The no-op and the lease are in the log; only the lease is an operation
+        let log = engine.committed_log.read().await;
+        assert_eq!(log.entries().len(), 1);
+        assert_eq!(log.entries()[0].consensus_round, 2);
+        assert!(engine.proposals.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_strong_collection_writes_commit_through_raft() {
+        let engine = Arc::new(raft_engine().await);
+        engine.start().await.unwrap();
+        engine.route_consistent_collections();
+
+        let strong = aerolithdb_storage::CollectionConfig {
+            consistency: WriteConsistency::Strong,
+            ..Default::default()
+        };
+        engine.storage.create_collection("accounts", strong).unwrap();
+
+        let document = serde_json::json!({ "balance": 10 });
+        engine.storage.store_document("accounts", "a", &document).await.unwrap();
+        assert_eq!(engine.storage.get_document("accounts", "a").await.unwrap().data, Some(document.clone()));
+
+        // Conflicts come back to the writer once the write has committed
+        let stale = engine.storage
+            .store_document_with_mode("accounts", "a", &document, WriteMode::Replace { expected_version: Some(7) })
+            .await;
+        assert!(stale.unwrap_err().to_string().contains("Version mismatch"));
+
+        // Eventually consistent collections stay out of the log
+        engine.storage.store_document("notes", "n", &document).await.unwrap();
+        engine.storage.delete_document("accounts", "a").await.unwrap();
+
+        let log = engine.committed_log.read().await;
+        let writes: Vec<&ReplicatedWrite> = log
+            .entries()
+            .iter()
+            .flat_map(|entry| match &entry.proposal.operation {
+                Operation::Batch { operations } => operations.iter().collect(),
+                operation => vec![operation],
+            })
+            .filter_map(|operation| match operation {
+                Operation::Write { write, .. } => Some(write),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(writes.len(), 3);
+        assert!(writes.iter().all(|write| write.collection() == "accounts"));
+        assert!(engine.write_waiters.is_empty());
+        assert_eq!(engine.storage.get_document("accounts", "a").await.unwrap().data, None);
+    }
+
+    #[tokio::test]
+    async fn test_consistent_reads_of_strong_collections() {
+        let engine = Arc::new(raft_engine().await);
+        engine.start().await.unwrap();
+        engine.route_consistent_collections();
+
+        let strong = aerolithdb_storage::CollectionConfig {
+            consistency: WriteConsistency::Strong,
+            ..Default::default()
+        };
+        engine.storage.create_collection("accounts", strong).unwrap();
+        let document = serde_json::json!({ "balance": 10 });
+        engine.storage.store_document("accounts", "a", &document).await.unwrap();
+
+        // A sole voter always holds its lease, so reads need no round
+        let bounded = ReadConsistency::BoundedStaleness { max_lag: std::time::Duration::from_millis(100) };
+        for consistency in [ReadConsistency::Linearizable, bounded] {
+            let read = engine.storage.get_document_with_consistency("accounts", "a", consistency).await.unwrap();
+            assert_eq!(read.data, Some(document.clone()));
+        }
+        let committed = engine.committed_log.read().await.entries().len();
+
+        // Without a lease a barrier goes through the log
+        engine.read_barrier().await.unwrap();
+        let log = engine.committed_log.read().await;
+        assert_eq!(log.entries().len(), committed + 1);
+        assert!(matches!(log.entries()[committed].proposal.operation, Operation::ReadBarrier { .. }));
+        assert!(engine.read_waiters.is_empty());
+
+        let refused = engine.storage.get_document_with_consistency("notes", "n", ReadConsistency::Linearizable).await;
+        assert!(refused.unwrap_err().to_string().starts_with("Invalid read consistency"));
+    }
+}
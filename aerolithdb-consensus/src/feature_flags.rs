@@ -0,0 +1,306 @@
+//! Runtime feature flags for gradual rollout of subsystems.
+//!
+//! Subsystems that are still being stabilized check a named flag at their
+//! integration point instead of being compiled in or out. Each flag resolves,
+//! in order of precedence, from:
+//!
+//! 1. A node-local override, stored on this node only
+//! 2. A cluster-wide setting, proposed through consensus so every node applies
+//!    it in commit order
+//! 3. The flag's built-in default
+//!
+//! Clearing an override or cluster setting falls back to the next level, which
+//! lets operators enable a feature on one canary node before the whole cluster.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use aerolithdb_storage::StorageHierarchy;
+
+/// System collection holding cluster-wide flag settings, written in commit order.
+pub const FEATURE_FLAG_COLLECTION: &str = "_feature_flags";
+
+/// System collection holding this node's local flag overrides. Never replicated.
+pub const NODE_FEATURE_FLAG_COLLECTION: &str = "_feature_flags_node";
+
+/// Names of the flags checked by built-in integration points.
+pub mod features {
+    /// GraphQL API endpoint
+    pub const GRAPHQL: &str = "graphql";
+    /// Plugin loading and event delivery
+    pub const PLUGINS: &str = "plugins";
+    /// SQL query layer
+    pub const SQL: &str = "sql";
+    /// Client sessions and ephemeral documents
+    pub const SESSIONS: &str = "sessions";
+    /// Distributed locks and leases
+    pub const LOCKS: &str = "locks";
+}
+
+/// Flags registered on every node: (name, enabled by default, description).
+const BUILTIN_FEATURES: &[(&str, bool, &str)] = &[
+    (features::GRAPHQL, false, "GraphQL API endpoint"),
+    (features::PLUGINS, false, "Plugin loading and event delivery"),
+    (features::SQL, false, "SQL query layer (not yet available)"),
+    (features::SESSIONS, true, "Client sessions with ephemeral documents"),
+    (features::LOCKS, true, "Consensus-backed locks and leases"),
+];
+
+/// Where a flag's effective value comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagScope {
+    /// Built-in default
+    Default,
+    /// Cluster-wide setting committed through consensus
+    Cluster,
+    /// Override on this node only
+    Node,
+}
+
+/// A cluster-wide flag change as proposed through consensus.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureFlagChange {
+    pub name: String,
+    /// New cluster-wide value, or None to clear it and fall back to the default
+    pub enabled: Option<bool>,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Effective state of one flag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureFlagStatus {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    /// Which level supplied `enabled`
+    pub source: FlagScope,
+    pub default_enabled: bool,
+    /// Cluster-wide setting, if one is committed
+    pub cluster: Option<bool>,
+    /// Local override on this node, if any
+    pub node: Option<bool>,
+}
+
+/// Persisted value of a flag at one scope.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredFlag {
+    enabled: bool,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+struct FeatureSpec {
+    description: String,
+    default_enabled: bool,
+}
+
+/// Registry of known flags and their cluster-wide and node-local settings.
+#[derive(Debug)]
+pub struct FeatureFlagRegistry {
+    storage: Arc<StorageHierarchy>,
+    specs: DashMap<String, FeatureSpec>,
+    cluster: DashMap<String, bool>,
+    node: DashMap<String, bool>,
+}
+
+impl FeatureFlagRegistry {
+    /// Create a registry with the built-in flags registered.
+    pub fn new(storage: Arc<StorageHierarchy>) -> Self {
+        let registry = Self {
+            storage,
+            specs: DashMap::new(),
+            cluster: DashMap::new(),
+            node: DashMap::new(),
+        };
+        for (name, default_enabled, description) in BUILTIN_FEATURES {
+            registry.register(name, *default_enabled, description);
+        }
+        registry
+    }
+
+    /// Register a flag so it can be checked and toggled. Re-registering keeps
+    /// existing settings and replaces the default and description.
+    pub fn register(&self, name: &str, default_enabled: bool, description: &str) {
+        self.specs.insert(name.to_string(), FeatureSpec {
+            description: description.to_string(),
+            default_enabled,
+        });
+    }
+
+    /// Load persisted cluster-wide settings and node overrides after a restart.
+    pub async fn load(&self) -> Result<()> {
+        for (collection, settings) in [
+            (FEATURE_FLAG_COLLECTION, &self.cluster),
+            (NODE_FEATURE_FLAG_COLLECTION, &self.node),
+        ] {
+            for name in self.storage.list_documents(collection, None, None).await? {
+                if let Some(document) = self.storage.get_document(collection, &name).await?.data {
+                    let stored: StoredFlag = serde_json::from_value(document)?;
+                    settings.insert(name, stored.enabled);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a flag is enabled on this node. Unknown flags are disabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.status(name).is_some_and(|status| status.enabled)
+    }
+
+    /// Effective state of a flag, if it is registered.
+    pub fn status(&self, name: &str) -> Option<FeatureFlagStatus> {
+        let spec = self.specs.get(name)?;
+        let cluster = self.cluster.get(name).map(|value| *value);
+        let node = self.node.get(name).map(|value| *value);
+
+        let (enabled, source) = match (node, cluster) {
+            (Some(enabled), _) => (enabled, FlagScope::Node),
+            (None, Some(enabled)) => (enabled, FlagScope::Cluster),
+            (None, None) => (spec.default_enabled, FlagScope::Default),
+        };
+
+        Some(FeatureFlagStatus {
+            name: name.to_string(),
+            description: spec.description.clone(),
+            enabled,
+            source,
+            default_enabled: spec.default_enabled,
+            cluster,
+            node,
+        })
+    }
+
+    /// Effective state of every registered flag, sorted by name.
+    pub fn snapshot(&self) -> Vec<FeatureFlagStatus> {
+        let mut names: Vec<String> = self.specs.iter().map(|spec| spec.key().clone()).collect();
+        names.sort();
+        names.iter().filter_map(|name| self.status(name)).collect()
+    }
+
+    /// Set or clear this node's override for a flag.
+    pub async fn set_node_override(&self, name: &str, enabled: Option<bool>) -> Result<FeatureFlagStatus> {
+        self.ensure_registered(name)?;
+        persist(&self.storage, NODE_FEATURE_FLAG_COLLECTION, name, enabled, Utc::now()).await?;
+        match enabled {
+            Some(enabled) => self.node.insert(name.to_string(), enabled),
+            None => self.node.remove(name).map(|(_, value)| value),
+        };
+        info!("Feature '{}' node override set to {:?}", name, enabled);
+        self.status(name).ok_or_else(|| anyhow::anyhow!("Unknown feature '{}'", name))
+    }
+
+    /// Check a cluster-wide change before proposing it.
+    pub fn validate_change(&self, change: &FeatureFlagChange) -> Result<()> {
+        self.ensure_registered(&change.name)
+    }
+
+    /// Apply a committed cluster-wide change.
+    pub async fn apply_cluster_change(&self, change: &FeatureFlagChange) -> Result<()> {
+        apply_feature_flag_change(&self.storage, change).await?;
+        match change.enabled {
+            Some(enabled) => self.cluster.insert(change.name.clone(), enabled),
+            None => self.cluster.remove(&change.name).map(|(_, value)| value),
+        };
+        info!("Feature '{}' cluster setting set to {:?}", change.name, change.enabled);
+        Ok(())
+    }
+
+    fn ensure_registered(&self, name: &str) -> Result<()> {
+        if self.specs.contains_key(name) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Unknown feature '{}'", name))
+        }
+    }
+}
+
+/// Persist a committed cluster-wide change to storage.
+pub async fn apply_feature_flag_change(storage: &StorageHierarchy, change: &FeatureFlagChange) -> Result<()> {
+    persist(storage, FEATURE_FLAG_COLLECTION, &change.name, change.enabled, change.requested_at).await
+}
+
+async fn persist(
+    storage: &StorageHierarchy,
+    collection: &str,
+    name: &str,
+    enabled: Option<bool>,
+    updated_at: DateTime<Utc>,
+) -> Result<()> {
+    match enabled {
+        Some(enabled) => {
+            let stored = StoredFlag { enabled, updated_at };
+            storage.store_document(collection, name, &serde_json::to_value(&stored)?).await?;
+        }
+        None => {
+            // Clearing a flag that was never set is a no-op
+            if storage.get_document(collection, name).await?.data.is_some() {
+                storage.delete_document(collection, name).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn fresh_storage() -> Arc<StorageHierarchy> {
+        let config = aerolithdb_storage::StorageConfig {
+            data_dir: std::env::temp_dir().join(format!("aerolith-flags-{}", uuid::Uuid::new_v4())),
+            ..Default::default()
+        };
+        Arc::new(StorageHierarchy::new(&config).await.unwrap())
+    }
+
+    fn change(name: &str, enabled: Option<bool>) -> FeatureFlagChange {
+        FeatureFlagChange { name: name.to_string(), enabled, requested_at: Utc::now() }
+    }
+
+    #[tokio::test]
+    async fn test_precedence_node_over_cluster_over_default() {
+        let registry = FeatureFlagRegistry::new(fresh_storage().await);
+        assert!(!registry.is_enabled(features::GRAPHQL));
+
+        registry.apply_cluster_change(&change(features::GRAPHQL, Some(true))).await.unwrap();
+        assert_eq!(registry.status(features::GRAPHQL).unwrap().source, FlagScope::Cluster);
+        assert!(registry.is_enabled(features::GRAPHQL));
+
+        // A canary node can opt out while the cluster setting stays in place
+        let status = registry.set_node_override(features::GRAPHQL, Some(false)).await.unwrap();
+        assert_eq!((status.enabled, status.source, status.cluster), (false, FlagScope::Node, Some(true)));
+
+        registry.set_node_override(features::GRAPHQL, None).await.unwrap();
+        registry.apply_cluster_change(&change(features::GRAPHQL, None)).await.unwrap();
+        assert_eq!(registry.status(features::GRAPHQL).unwrap().source, FlagScope::Default);
+    }
+
+    #[tokio::test]
+    async fn test_settings_survive_reload() {
+        let storage = fresh_storage().await;
+        let registry = FeatureFlagRegistry::new(Arc::clone(&storage));
+        registry.apply_cluster_change(&change(features::PLUGINS, Some(true))).await.unwrap();
+        registry.set_node_override(features::LOCKS, Some(false)).await.unwrap();
+
+        let reloaded = FeatureFlagRegistry::new(storage);
+        reloaded.load().await.unwrap();
+        assert!(reloaded.is_enabled(features::PLUGINS));
+        assert!(!reloaded.is_enabled(features::LOCKS));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_flags_are_rejected() {
+        let registry = FeatureFlagRegistry::new(fresh_storage().await);
+        assert!(!registry.is_enabled("warp_drive"));
+        assert!(registry.set_node_override("warp_drive", Some(true)).await.is_err());
+        assert!(registry.validate_change(&change("warp_drive", Some(true))).is_err());
+        assert_eq!(registry.snapshot().len(), BUILTIN_FEATURES.len());
+    }
+}
@@ -0,0 +1,293 @@
+//! Distributed locks and leases with fencing tokens.
+//!
+//! Applications coordinate leader election and critical sections by holding a
+//! named lease. Every acquire, renew, and release is proposed through consensus
+//! and applied in commit order, so all nodes agree on who holds a lease.
+//!
+//! Each successful acquisition is issued a fencing token that strictly increases
+//! per lease name, including across releases and expiries. Downstream systems
+//! should reject writes carrying a token lower than the highest they have seen,
+//! which protects against a paused holder acting after its lease has expired.
+//!
+//! Expiry is evaluated against the timestamp carried in the committed request
+//! rather than the applying node's clock, keeping the state machine deterministic
+//! for replay.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use aerolithdb_storage::StorageHierarchy;
+
+/// System collection holding the state of every named lease.
+pub const LEASE_COLLECTION: &str = "_consensus_leases";
+
+/// Shortest lease that may be requested
+pub const MIN_LEASE_TTL: Duration = Duration::from_secs(1);
+
+/// Longest lease that may be requested
+pub const MAX_LEASE_TTL: Duration = Duration::from_secs(3600);
+
+/// Longest accepted lease name
+const MAX_LEASE_NAME_LEN: usize = 256;
+
+/// A lease currently granted to a holder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lease {
+    pub name: String,
+    /// Client-chosen identity of the holder
+    pub holder: String,
+    /// Strictly increasing per lease name across every grant
+    pub fencing_token: u64,
+    /// Lease duration applied on acquire and on every renewal
+    pub ttl_ms: u64,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Lease {
+    /// Whether the lease is still held at the given instant
+    pub fn is_live_at(&self, at: DateTime<Utc>) -> bool {
+        self.expires_at > at
+    }
+}
+
+/// What a lease request asks for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LeaseAction {
+    /// Take the lease if it is free or expired
+    Acquire { ttl_ms: u64 },
+    /// Extend a held lease by its TTL
+    Renew { fencing_token: u64 },
+    /// Give the lease up before it expires
+    Release { fencing_token: u64 },
+}
+
+/// A lease request as proposed through consensus.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaseRequest {
+    /// Correlates the committed request with the caller waiting on it
+    pub request_id: Uuid,
+    pub name: String,
+    pub holder: String,
+    /// Proposer's clock when the request was made, used for expiry checks
+    pub requested_at: DateTime<Utc>,
+    pub action: LeaseAction,
+}
+
+/// Result of applying a lease request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LeaseOutcome {
+    /// The lease was acquired or renewed
+    Granted { lease: Lease },
+    /// Another holder has the lease
+    Held { lease: Lease },
+    /// The lease was released
+    Released { name: String },
+    /// The caller does not hold the lease under the given fencing token
+    NotHeld { name: String },
+}
+
+/// Persisted state of one lease name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LeaseRecord {
+    lease: Option<Lease>,
+    /// Last fencing token issued, kept after release so tokens never repeat
+    last_token: u64,
+}
+
+impl LeaseRecord {
+    /// Apply a request to this lease's state.
+    fn apply(&mut self, request: &LeaseRequest) -> LeaseOutcome {
+        let now = request.requested_at;
+        let current = self.lease.clone().filter(|lease| lease.is_live_at(now));
+
+        match &request.action {
+            LeaseAction::Acquire { ttl_ms } => match current {
+                Some(lease) if lease.holder != request.holder => LeaseOutcome::Held { lease },
+                Some(lease) => {
+                    // Re-acquiring a held lease acts as a renewal and keeps the token
+                    self.grant(lease.fencing_token, &request.holder, &request.name, *ttl_ms, lease.acquired_at, now)
+                }
+                None => {
+                    let token = self.last_token + 1;
+                    self.last_token = token;
+                    self.grant(token, &request.holder, &request.name, *ttl_ms, now, now)
+                }
+            },
+            LeaseAction::Renew { fencing_token } => match current {
+                Some(lease) if lease.holder == request.holder && lease.fencing_token == *fencing_token => {
+                    self.grant(lease.fencing_token, &lease.holder, &lease.name, lease.ttl_ms, lease.acquired_at, now)
+                }
+                _ => LeaseOutcome::NotHeld { name: request.name.clone() },
+            },
+            LeaseAction::Release { fencing_token } => match current {
+                Some(lease) if lease.holder == request.holder && lease.fencing_token == *fencing_token => {
+                    self.lease = None;
+                    LeaseOutcome::Released { name: request.name.clone() }
+                }
+                _ => LeaseOutcome::NotHeld { name: request.name.clone() },
+            },
+        }
+    }
+
+    fn grant(
+        &mut self,
+        fencing_token: u64,
+        holder: &str,
+        name: &str,
+        ttl_ms: u64,
+        acquired_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> LeaseOutcome {
+        let lease = Lease {
+            name: name.to_string(),
+            holder: holder.to_string(),
+            fencing_token,
+            ttl_ms,
+            acquired_at,
+            expires_at: now + chrono::Duration::milliseconds(ttl_ms as i64),
+        };
+        self.lease = Some(lease.clone());
+        LeaseOutcome::Granted { lease }
+    }
+}
+
+/// Check a request before proposing it, so invalid requests never reach the log.
+pub fn validate_lease_request(request: &LeaseRequest) -> Result<()> {
+    if request.name.is_empty() || request.name.len() > MAX_LEASE_NAME_LEN {
+        return Err(anyhow::anyhow!(
+            "Lease name must be between 1 and {} characters", MAX_LEASE_NAME_LEN
+        ));
+    }
+    if !request.name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')) {
+        return Err(anyhow::anyhow!(
+            "Lease name '{}' may only contain letters, digits, '-', '_', '.' and ':'", request.name
+        ));
+    }
+    if request.holder.is_empty() {
+        return Err(anyhow::anyhow!("Lease holder must not be empty"));
+    }
+    if let LeaseAction::Acquire { ttl_ms } = request.action {
+        let ttl = Duration::from_millis(ttl_ms);
+        if ttl < MIN_LEASE_TTL || ttl > MAX_LEASE_TTL {
+            return Err(anyhow::anyhow!(
+                "Lease TTL must be between {}s and {}s",
+                MIN_LEASE_TTL.as_secs(), MAX_LEASE_TTL.as_secs()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Apply a committed lease request to storage and return its outcome.
+pub async fn apply_lease_request(storage: &StorageHierarchy, request: &LeaseRequest) -> Result<LeaseOutcome> {
+    let mut record = load_record(storage, &request.name).await?.unwrap_or_default();
+    let outcome = record.apply(request);
+    storage
+        .store_document(LEASE_COLLECTION, &request.name, &serde_json::to_value(&record)?)
+        .await?;
+    Ok(outcome)
+}
+
+/// Read the lease currently held under a name, if it has not expired.
+pub async fn read_lease(storage: &StorageHierarchy, name: &str, at: DateTime<Utc>) -> Result<Option<Lease>> {
+    Ok(load_record(storage, name)
+        .await?
+        .and_then(|record| record.lease)
+        .filter(|lease| lease.is_live_at(at)))
+}
+
+async fn load_record(storage: &StorageHierarchy, name: &str) -> Result<Option<LeaseRecord>> {
+    let result = storage.get_document(LEASE_COLLECTION, name).await?;
+    match result.data {
+        Some(document) => Ok(Some(serde_json::from_value(document)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(holder: &str, at: DateTime<Utc>, action: LeaseAction) -> LeaseRequest {
+        LeaseRequest {
+            request_id: Uuid::new_v4(),
+            name: "leader".to_string(),
+            holder: holder.to_string(),
+            requested_at: at,
+            action,
+        }
+    }
+
+    fn granted_token(outcome: LeaseOutcome) -> u64 {
+        match outcome {
+            LeaseOutcome::Granted { lease } => lease.fencing_token,
+            other => panic!("expected grant, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lease_exclusion_and_expiry() {
+        let start = Utc::now();
+        let mut record = LeaseRecord::default();
+
+        let token = granted_token(record.apply(&request("a", start, LeaseAction::Acquire { ttl_ms: 5_000 })));
+        assert_eq!(token, 1);
+
+        let contended = record.apply(&request("b", start + chrono::Duration::seconds(1), LeaseAction::Acquire { ttl_ms: 5_000 }));
+        assert!(matches!(contended, LeaseOutcome::Held { ref lease } if lease.holder == "a"));
+
+        // Once expired, the next holder gets a higher token and the old one is fenced off
+        let later = start + chrono::Duration::seconds(6);
+        let next = granted_token(record.apply(&request("b", later, LeaseAction::Acquire { ttl_ms: 5_000 })));
+        assert_eq!(next, 2);
+        let stale_renew = record.apply(&request("a", later, LeaseAction::Renew { fencing_token: token }));
+        assert!(matches!(stale_renew, LeaseOutcome::NotHeld { .. }));
+    }
+
+    #[test]
+    fn test_renew_and_release() {
+        let start = Utc::now();
+        let mut record = LeaseRecord::default();
+        let token = granted_token(record.apply(&request("a", start, LeaseAction::Acquire { ttl_ms: 2_000 })));
+
+        let renewed_at = start + chrono::Duration::seconds(1);
+        match record.apply(&request("a", renewed_at, LeaseAction::Renew { fencing_token: token })) {
+            LeaseOutcome::Granted { lease } => {
+                assert_eq!(lease.fencing_token, token);
+                assert_eq!(lease.expires_at, renewed_at + chrono::Duration::seconds(2));
+            }
+            other => panic!("expected grant, got {:?}", other),
+        }
+
+        assert!(matches!(
+            record.apply(&request("b", renewed_at, LeaseAction::Release { fencing_token: token })),
+            LeaseOutcome::NotHeld { .. }
+        ));
+        assert!(matches!(
+            record.apply(&request("a", renewed_at, LeaseAction::Release { fencing_token: token })),
+            LeaseOutcome::Released { .. }
+        ));
+
+        // Tokens keep increasing after a release
+        let next = granted_token(record.apply(&request("b", renewed_at, LeaseAction::Acquire { ttl_ms: 2_000 })));
+        assert_eq!(next, token + 1);
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let now = Utc::now();
+        assert!(validate_lease_request(&request("a", now, LeaseAction::Acquire { ttl_ms: 10_000 })).is_ok());
+        assert!(validate_lease_request(&request("a", now, LeaseAction::Acquire { ttl_ms: 10 })).is_err());
+        assert!(validate_lease_request(&request("", now, LeaseAction::Renew { fencing_token: 1 })).is_err());
+
+        let mut bad_name = request("a", now, LeaseAction::Release { fencing_token: 1 });
+        bad_name.name = "../leader".to_string();
+        assert!(validate_lease_request(&bad_name).is_err());
+    }
+}
@@ -59,6 +59,10 @@
 //! - **Fault Tolerance**: Handles fail-stop failures (up to 1/2 node failures)
 //! - **Use Cases**: Internal clusters, development environments, trusted deployments
 //!
+//! Selecting `ConsensusAlgorithm::Raft` runs the `raft` module's replica:
+//! leader election, log replication, snapshot-based log compaction, and
+//! single-server membership changes via `add_raft_voter`/`remove_raft_voter`.
+//!
 //! ### HoneyBadger BFT
 //! Asynchronous Byzantine consensus for adversarial network conditions:
 //! - **No Timing Assumptions**: True asynchronous operation without timeouts
@@ -71,7 +75,10 @@
 //! ### Byzantine Fault Tolerance
 //! Comprehensive protection against malicious nodes and arbitrary failures:
 //! - **Malicious Actor Detection**: Identifies nodes exhibiting Byzantine behavior
-//! - **Isolation Mechanisms**: Quarantines malicious nodes to prevent damage
+//! - **Equivocation Detection**: Nodes signing conflicting votes or proposals for
+//!   the same proposal are quarantined at once, with both statements as evidence
+//! - **Isolation Mechanisms**: Quarantines malicious nodes, leaving them out of
+//!   quorums, to prevent damage
 //! - **Recovery Procedures**: Restores system integrity after attacks
 //! - **Cryptographic Verification**: Ensures message authenticity and integrity
 //!
@@ -86,7 +93,10 @@
 //! Intelligent handling of concurrent operations and data conflicts:
 //! - **Vector Clock Ordering**: Maintains causal ordering of distributed events
 //! - **Conflict Detection**: Identifies conflicting concurrent operations
-//! - **Resolution Strategies**: Multiple strategies for resolving conflicts
+//! - **Resolution Strategies**: Multiple strategies for resolving conflicts,
+//!   selectable per collection
+//! - **CRDT Merging**: Concurrent edits from partitioned datacenters merge
+//!   field by field, with arrays as observed-remove sets, without losing data
 //! - **Consistency Guarantees**: Ensures deterministic conflict resolution
 //!
 //! ## Performance and Scalability
@@ -117,6 +127,7 @@
 //!     timeout: Duration::from_secs(30),
 //!     max_batch_size: 100,
 //!     conflict_resolution: ConflictResolution::LastWriterWins,
+//!     ..Default::default()
 //! };
 //! 
 //! let engine = ConsensusEngine::new(&config, security, storage).await?;
@@ -134,6 +145,39 @@
 //! };
 //! 
 //! let proposal_id = engine.propose_operation(operation).await?;
+//!
+//! // Or let the engine batch it with other concurrent writes
+//! let batched_id = engine.submit_operation(another_operation).await?;
+//! ```
+//!
+//! ### Log Compaction
+//! Every committed operation is kept in the engine's committed log until it
+//! is compacted into a snapshot of the replicated state. `ConsensusConfig::snapshots`
+//! sets the entry count, size and interval that trigger compaction for the
+//! vote-based algorithms; Raft compacts by `RaftConfig`. Nodes that fall
+//! behind the latest snapshot, or join after it, receive it instead of the
+//! entries it replaced.
+//! ```rust
+//! let status = engine.committed_log_status().await;
+//! println!("{} entries retained, {} compacted", status.retained_entries, status.compacted_entries);
+//! ```
+//!
+//! ### Strongly Consistent Collections
+//! Document writes are eventually consistent unless their collection is
+//! configured with `WriteConsistency::Strong`. Once
+//! `route_consistent_collections` is called, the storage hierarchy hands such
+//! writes to the engine as `Operation::Write`; each is applied on every node
+//! when it commits, and the writer gets its outcome only after a quorum has
+//! acknowledged it.
+//!
+//! Reads of these collections are local unless they ask for a stronger
+//! `ReadConsistency`. Linearizable reads are served under the Raft leader's
+//! lease or wait for a read barrier to commit; bounded-staleness reads are
+//! served by Raft nodes trailing their leader by no more than the bound.
+//! ```rust
+//! let engine = Arc::new(ConsensusEngine::new(&config, security, storage).await?);
+//! engine.route_consistent_collections();
+//! engine.wait_for_read(ReadConsistency::Linearizable).await?;
 //! ```
 //!
 //! ## Security Considerations
@@ -152,15 +196,25 @@
 //! - **Error Propagation**: Clear error reporting and diagnostics
 //! - **State Consistency**: Maintains consistency even during failures
 
+pub mod adaptive_timeout;
+pub mod batching;
 pub mod byzantine_tolerance;
+pub mod compaction;
 pub mod conflict_resolution;
+pub mod crdt;
 pub mod engine;
+pub mod feature_flags;
+pub mod leases;
 pub mod partition_recovery;
+pub mod raft;
+pub mod replay;
+pub mod threshold_signatures;
+pub mod topology;
 pub mod types;
 pub mod vector_clock;
 
 // Re-export main types and functionality for public API
-pub use engine::ConsensusEngine;
+pub use engine::{ConsensusEngine, QUARANTINE_COLLECTION};
 pub use types::{
     ConsensusConfig, ConsensusAlgorithm, ConsensusMessage, Proposal, Vote, VoteDecision,
     VoteCollection, CommittedEntry, Operation, PeerId, ProposalId, CommitMessage,
@@ -168,7 +222,31 @@ pub use types::{
 };
 
 // Re-export supporting systems
-pub use byzantine_tolerance::ByzantineFaultTolerance;
+pub use adaptive_timeout::{AdaptiveTimeoutManager, PeerRttStats, TimeoutSnapshot};
+pub use batching::{BatchMetrics, ProposalBatcher};
+pub use byzantine_tolerance::{
+    ByzantineFault, ByzantineFaultTolerance, EquivocationDetector, FaultEvidence, QuarantineRecord, QuarantineStatus,
+    SignedStatement,
+};
+pub use compaction::{CommittedLogStatus, SnapshotConfig, SnapshotMessage, StateSnapshot};
 pub use conflict_resolution::{ConflictResolution, ConflictResolutionEngine};
+pub use crdt::{CrdtDocument, Stamp, CRDT_STATE_KEY};
+pub use feature_flags::{
+    features, FeatureFlagChange, FeatureFlagRegistry, FeatureFlagStatus, FlagScope,
+    FEATURE_FLAG_COLLECTION, NODE_FEATURE_FLAG_COLLECTION,
+};
+pub use leases::{
+    Lease, LeaseAction, LeaseOutcome, LeaseRequest, LEASE_COLLECTION, MAX_LEASE_TTL, MIN_LEASE_TTL,
+};
 pub use partition_recovery::NetworkPartitionRecovery;
+pub use raft::{
+    EntryPayload, LogEntry, LogIndex, RaftConfig, RaftMessage, RaftNode, RaftPayload, RaftReady,
+    RaftRole, RaftStatus, Snapshot, Term,
+};
+pub use replay::{OperationLogEntry, OperationLogReplayer, ReplayOptions, ReplayReport};
+pub use threshold_signatures::{AggregatedCertificate, ThresholdSignatureAggregator};
+pub use topology::{
+    RegionConfig, RegionEvent, RegionFailoverPolicy, RegionTopology, TopologyConfig, TopologyStatus,
+    WriteQuorum,
+};
 pub use vector_clock::VectorClock;
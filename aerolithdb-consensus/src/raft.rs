@@ -0,0 +1,1453 @@
+//! Raft consensus for clusters of trusted nodes.
+//!
+//! `RaftNode` is a deterministic, IO-free implementation of the Raft protocol:
+//! leader election, log replication, log compaction with snapshots, and
+//! single-server membership changes. It never touches the network, storage,
+//! or a clock. Time advances through `tick`, incoming messages are fed to
+//! `step`, and everything the node wants done (messages to send, a snapshot
+//! to restore, committed entries to apply) is collected with `ready`.
+//!
+//! A leader holds a read lease while a majority of voters has answered it
+//! within the minimum election timeout, since none of them will vote for
+//! another leader before that runs out. Leases are measured from when the
+//! answered message was sent, so they never outlast the followers' own
+//! timers; they let the leader serve linearizable reads without a round.
+//!
+//! The consensus engine drives a node when `ConsensusAlgorithm::Raft` is
+//! selected. Keeping the protocol free of IO is what lets the tests below run
+//! whole clusters in a simulated network with partitions, reproducibly from
+//! a seed.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::compaction;
+use crate::types::{PeerId, Proposal};
+
+/// Raft term number.
+pub type Term = u64;
+
+/// Position of an entry in the replicated log, starting at 1.
+pub type LogIndex = u64;
+
+/// Raft timing, compaction, and initial membership settings.
+#[derive(Debug, Clone)]
+pub struct RaftConfig {
+    /// Voting members the cluster starts with, including this node
+    /// When empty the node forms a single-node cluster on its own
+    pub voters: Vec<PeerId>,
+
+    /// Ticks without hearing from a leader before a follower starts an election
+    /// The actual timeout is randomized between this and twice this value;
+    /// the consensus engine replaces it with one derived from peer RTT
+    pub election_ticks: u32,
+
+    /// Ticks between leader heartbeats; must be well below `election_ticks`
+    pub heartbeat_ticks: u32,
+
+    /// Wall-clock length of one tick when driven by the consensus engine
+    pub tick_interval: std::time::Duration,
+
+    /// Applied entries kept in the log before it is compacted into a snapshot
+    pub snapshot_threshold: u64,
+
+    /// Approximate serialized size of the log that triggers compaction
+    pub snapshot_max_log_bytes: u64,
+
+    /// Compact at least this often while applied entries accumulate, counted
+    /// in ticks of `tick_interval`; `None` compacts on size alone
+    pub snapshot_interval: Option<std::time::Duration>,
+
+    /// Maximum entries carried by a single append message
+    pub max_entries_per_message: usize,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            voters: Vec::new(),
+            election_ticks: 10,
+            heartbeat_ticks: 3,
+            tick_interval: std::time::Duration::from_millis(100),
+            snapshot_threshold: 1000,
+            snapshot_max_log_bytes: 16 * 1024 * 1024,
+            snapshot_interval: Some(std::time::Duration::from_secs(600)),
+            max_entries_per_message: 64,
+        }
+    }
+}
+
+/// Role a node currently plays in the cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaftRole {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+/// Content of a log entry.
+#[derive(Debug, Clone)]
+pub enum EntryPayload {
+    /// Appended by every new leader so entries from earlier terms can commit
+    Noop,
+    /// A proposed operation, applied to the state machine once committed
+    Proposal(Proposal),
+    /// The complete voter set, effective as soon as the entry is appended
+    Membership(Vec<PeerId>),
+}
+
+/// An entry in the replicated log.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub index: LogIndex,
+    pub term: Term,
+    pub payload: EntryPayload,
+}
+
+/// Compacted state machine covering every entry up to `last_index`.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub last_index: LogIndex,
+    pub last_term: Term,
+    /// Voter set in effect at `last_index`
+    pub voters: Vec<PeerId>,
+    /// Opaque state machine contents produced by the caller
+    pub data: serde_json::Value,
+}
+
+/// A message between two Raft nodes.
+#[derive(Debug, Clone)]
+pub struct RaftMessage {
+    pub from: PeerId,
+    pub to: PeerId,
+    /// Sender's current term
+    pub term: Term,
+    pub payload: RaftPayload,
+}
+
+/// Raft RPCs and their responses.
+#[derive(Debug, Clone)]
+pub enum RaftPayload {
+    RequestVote {
+        last_log_index: LogIndex,
+        last_log_term: Term,
+    },
+    RequestVoteResponse {
+        granted: bool,
+    },
+    AppendEntries {
+        prev_log_index: LogIndex,
+        prev_log_term: Term,
+        entries: Vec<LogEntry>,
+        leader_commit: LogIndex,
+    },
+    /// On success `match_index` is the last entry now known to match the
+    /// leader; on failure it hints at where the follower's log may match
+    AppendEntriesResponse {
+        success: bool,
+        match_index: LogIndex,
+    },
+    InstallSnapshot {
+        snapshot: Snapshot,
+    },
+    InstallSnapshotResponse {
+        last_index: LogIndex,
+    },
+}
+
+/// Work produced by a node for its caller to carry out, in order.
+#[derive(Debug, Default)]
+pub struct RaftReady {
+    /// Messages to deliver to other nodes
+    pub messages: Vec<RaftMessage>,
+    /// Snapshot received from the leader; restore it before applying `committed`
+    pub snapshot: Option<Snapshot>,
+    /// Newly committed entries to apply to the state machine
+    pub committed: Vec<LogEntry>,
+}
+
+impl RaftReady {
+    /// Whether there is nothing to do.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.snapshot.is_none() && self.committed.is_empty()
+    }
+}
+
+/// Point-in-time view of a node for monitoring.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaftStatus {
+    pub id: PeerId,
+    pub role: RaftRole,
+    pub term: Term,
+    pub leader: Option<PeerId>,
+    pub voters: Vec<PeerId>,
+    pub commit_index: LogIndex,
+    pub last_applied: LogIndex,
+    pub last_log_index: LogIndex,
+    pub snapshot_index: LogIndex,
+}
+
+/// Replication progress the leader tracks for each peer.
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    next_index: LogIndex,
+    match_index: LogIndex,
+    /// Tick the oldest unanswered message to the peer was sent at
+    probe_sent_at: Option<u64>,
+    /// Tick of the latest message the peer is known to have answered
+    acked_at: Option<u64>,
+}
+
+impl Progress {
+    fn new(next_index: LogIndex) -> Self {
+        Self { next_index, match_index: 0, probe_sent_at: None, acked_at: None }
+    }
+
+    /// Record an answer from the peer, dating it to the oldest message it
+    /// may be answering.
+    fn acknowledge(&mut self) {
+        if let Some(sent_at) = self.probe_sent_at.take() {
+            self.acked_at = Some(sent_at);
+        }
+    }
+}
+
+/// A single Raft participant.
+#[derive(Debug)]
+pub struct RaftNode {
+    id: PeerId,
+    config: RaftConfig,
+    role: RaftRole,
+    term: Term,
+    voted_for: Option<PeerId>,
+    leader: Option<PeerId>,
+
+    /// Entries after the snapshot
+    log: Vec<LogEntry>,
+    /// Approximate serialized size of `log`
+    log_bytes: u64,
+    snapshot: Option<Snapshot>,
+    /// Tick the latest snapshot was taken or installed at
+    snapshot_tick: u64,
+    commit_index: LogIndex,
+    last_applied: LogIndex,
+    /// Commit index of the leader as of its latest append
+    leader_commit: LogIndex,
+
+    /// Voters in effect: those of the latest membership entry in the log
+    voters: Vec<PeerId>,
+    votes_received: HashSet<PeerId>,
+    progress: HashMap<PeerId, Progress>,
+
+    election_elapsed: u32,
+    heartbeat_elapsed: u32,
+    /// Minimum election timeout, initially `config.election_ticks`
+    election_ticks: u32,
+    randomized_election_ticks: u32,
+    /// Ticks since the node was created
+    ticks: u64,
+    rng: u64,
+
+    outbox: Vec<RaftMessage>,
+    pending_snapshot: Option<Snapshot>,
+}
+
+impl RaftNode {
+    /// Create a follower with an empty log.
+    ///
+    /// `seed` drives the randomized election timeout; nodes of one cluster
+    /// should use different seeds (the node ID is mixed in as well).
+    pub fn new(id: PeerId, config: RaftConfig, seed: u64) -> Self {
+        let id_hash = id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+        let election_ticks = config.election_ticks;
+        let mut node = Self {
+            id,
+            config,
+            role: RaftRole::Follower,
+            term: 0,
+            voted_for: None,
+            leader: None,
+            log: Vec::new(),
+            log_bytes: 0,
+            snapshot: None,
+            snapshot_tick: 0,
+            commit_index: 0,
+            last_applied: 0,
+            leader_commit: 0,
+            voters: Vec::new(),
+            votes_received: HashSet::new(),
+            progress: HashMap::new(),
+            election_elapsed: 0,
+            heartbeat_elapsed: 0,
+            election_ticks,
+            randomized_election_ticks: 0,
+            ticks: 0,
+            rng: (seed ^ id_hash) | 1,
+            outbox: Vec::new(),
+            pending_snapshot: None,
+        };
+        node.voters = node.initial_voters();
+        node.reset_election_timeout();
+        node
+    }
+
+    /// This node's ID.
+    pub fn id(&self) -> &PeerId {
+        &self.id
+    }
+
+    /// Current role.
+    pub fn role(&self) -> RaftRole {
+        self.role
+    }
+
+    /// Whether this node is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.role == RaftRole::Leader
+    }
+
+    /// Current term.
+    pub fn term(&self) -> Term {
+        self.term
+    }
+
+    /// The leader this node currently follows, if known.
+    pub fn leader(&self) -> Option<&PeerId> {
+        self.leader.as_ref()
+    }
+
+    /// Voters in effect, sorted.
+    pub fn voters(&self) -> &[PeerId] {
+        &self.voters
+    }
+
+    /// Highest log index known to be committed.
+    pub fn commit_index(&self) -> LogIndex {
+        self.commit_index
+    }
+
+    /// Whether this node is the leader and a majority of voters, itself
+    /// included, has answered it within the minimum election timeout.
+    pub fn has_lease(&self) -> bool {
+        if !self.is_leader() {
+            return false;
+        }
+        let window = self.election_ticks as u64;
+        let current = self
+            .voters
+            .iter()
+            .filter(|peer| {
+                **peer == self.id
+                    || self
+                        .progress
+                        .get(*peer)
+                        .and_then(|progress| progress.acked_at)
+                        .is_some_and(|acked_at| self.ticks - acked_at < window)
+            })
+            .count();
+        current >= self.quorum()
+    }
+
+    /// Index a linearizable read has to wait for before reading locally,
+    /// when this node can serve one under its lease.
+    ///
+    /// A new leader only knows the latest commit once an entry of its own
+    /// term has committed.
+    pub fn read_index(&self) -> Option<LogIndex> {
+        if !self.has_lease() || self.term_at(self.commit_index) != Some(self.term) {
+            return None;
+        }
+        Some(self.commit_index)
+    }
+
+    /// Ticks by which this node's applied state may trail the cluster's,
+    /// if bounded.
+    ///
+    /// A leader serving under its lease is current. A follower that has
+    /// applied everything its leader had committed is behind by no more
+    /// than the time since it last heard from it.
+    pub fn staleness_ticks(&self) -> Option<u64> {
+        match self.role {
+            RaftRole::Leader => self.read_index().filter(|index| self.last_applied >= *index).map(|_| 0),
+            RaftRole::Follower if self.leader.is_some() && self.last_applied >= self.leader_commit => {
+                Some(self.election_elapsed as u64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Point-in-time status for monitoring.
+    pub fn status(&self) -> RaftStatus {
+        RaftStatus {
+            id: self.id.clone(),
+            role: self.role,
+            term: self.term,
+            leader: self.leader.clone(),
+            voters: self.voters.clone(),
+            commit_index: self.commit_index,
+            last_applied: self.last_applied,
+            last_log_index: self.last_index(),
+            snapshot_index: self.snapshot_index(),
+        }
+    }
+
+    /// Minimum election timeout in ticks.
+    pub fn election_ticks(&self) -> u32 {
+        self.election_ticks
+    }
+
+    /// Set the minimum election timeout, such as one derived from measured
+    /// peer RTT. It is rounded up to whole ticks and kept above the heartbeat
+    /// interval, and takes effect when the election timer next resets.
+    pub fn set_election_timeout(&mut self, timeout: std::time::Duration) {
+        let tick_ms = self.config.tick_interval.as_millis().max(1);
+        let ticks = timeout.as_millis().div_ceil(tick_ms).min(u32::MAX as u128) as u32;
+        self.election_ticks = ticks.max(self.config.heartbeat_ticks + 1);
+    }
+
+    /// Advance logical time by one tick.
+    ///
+    /// Leaders send heartbeats every `heartbeat_ticks`; followers and
+    /// candidates start an election once their randomized timeout expires.
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        match self.role {
+            RaftRole::Leader => {
+                self.heartbeat_elapsed += 1;
+                if self.heartbeat_elapsed >= self.config.heartbeat_ticks {
+                    self.heartbeat_elapsed = 0;
+                    self.broadcast_append();
+                }
+            }
+            RaftRole::Follower | RaftRole::Candidate => {
+                self.election_elapsed += 1;
+                if self.election_elapsed >= self.randomized_election_ticks {
+                    self.campaign();
+                }
+            }
+        }
+    }
+
+    /// Append a proposal to the log for replication.
+    ///
+    /// Only the leader accepts proposals. Returns the entry's log index; the
+    /// proposal takes effect when that index shows up in `ready().committed`.
+    pub fn propose(&mut self, proposal: Proposal) -> Result<LogIndex> {
+        self.ensure_leader()?;
+        let index = self.append(EntryPayload::Proposal(proposal));
+        self.broadcast_append();
+        Ok(index)
+    }
+
+    /// Propose adding a voter to the cluster.
+    pub fn add_voter(&mut self, peer: PeerId) -> Result<LogIndex> {
+        if self.voters.contains(&peer) {
+            return Err(anyhow::anyhow!("'{}' is already a Raft voter", peer));
+        }
+        let mut voters = self.voters.clone();
+        voters.push(peer);
+        self.propose_membership(voters)
+    }
+
+    /// Propose removing a voter from the cluster.
+    ///
+    /// A leader that removes itself keeps leading until the change commits,
+    /// then steps down.
+    pub fn remove_voter(&mut self, peer: &PeerId) -> Result<LogIndex> {
+        if !self.voters.contains(peer) {
+            return Err(anyhow::anyhow!("'{}' is not a Raft voter", peer));
+        }
+        if self.voters.len() == 1 {
+            return Err(anyhow::anyhow!("Cannot remove the last Raft voter"));
+        }
+        let voters = self.voters.iter().filter(|voter| *voter != peer).cloned().collect();
+        self.propose_membership(voters)
+    }
+
+    /// Handle a message from another node.
+    pub fn step(&mut self, message: RaftMessage) {
+        if message.to != self.id {
+            return;
+        }
+
+        if message.term > self.term {
+            // A node that heard from its leader within the minimum election
+            // timeout ignores vote requests, so a removed or partitioned node
+            // cannot disrupt a healthy cluster with ever higher terms
+            if matches!(message.payload, RaftPayload::RequestVote { .. })
+                && self.leader.is_some()
+                && self.election_elapsed < self.election_ticks
+            {
+                return;
+            }
+            let leader = match message.payload {
+                RaftPayload::AppendEntries { .. } | RaftPayload::InstallSnapshot { .. } => Some(message.from.clone()),
+                _ => None,
+            };
+            self.become_follower(message.term, leader);
+        } else if message.term < self.term {
+            // Tell stale leaders and candidates about the newer term
+            match message.payload {
+                RaftPayload::AppendEntries { .. } | RaftPayload::InstallSnapshot { .. } => {
+                    self.send(&message.from, RaftPayload::AppendEntriesResponse { success: false, match_index: 0 });
+                }
+                RaftPayload::RequestVote { .. } => {
+                    self.send(&message.from, RaftPayload::RequestVoteResponse { granted: false });
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let from = message.from;
+        match message.payload {
+            RaftPayload::RequestVote { last_log_index, last_log_term } => {
+                self.handle_request_vote(from, last_log_index, last_log_term);
+            }
+            RaftPayload::RequestVoteResponse { granted } => {
+                self.handle_vote_response(from, granted);
+            }
+            RaftPayload::AppendEntries { prev_log_index, prev_log_term, entries, leader_commit } => {
+                self.handle_append_entries(from, prev_log_index, prev_log_term, entries, leader_commit);
+            }
+            RaftPayload::AppendEntriesResponse { success, match_index } => {
+                self.handle_append_response(from, success, match_index);
+            }
+            RaftPayload::InstallSnapshot { snapshot } => {
+                self.handle_install_snapshot(from, snapshot);
+            }
+            RaftPayload::InstallSnapshotResponse { last_index } => {
+                self.handle_snapshot_response(from, last_index);
+            }
+        }
+    }
+
+    /// Collect pending messages, a snapshot to restore, and newly committed
+    /// entries. Committed entries are handed out exactly once.
+    pub fn ready(&mut self) -> RaftReady {
+        let snapshot = self.pending_snapshot.take();
+        let mut committed = Vec::new();
+        while self.last_applied < self.commit_index {
+            self.last_applied += 1;
+            if let Some(entry) = self.entry(self.last_applied) {
+                committed.push(entry.clone());
+            }
+        }
+        RaftReady {
+            messages: std::mem::take(&mut self.outbox),
+            snapshot,
+            committed,
+        }
+    }
+
+    /// Whether enough applied entries have accumulated, the log has grown
+    /// large enough, or enough time has passed to warrant compaction.
+    pub fn should_compact(&self) -> bool {
+        let applied = self.last_applied.saturating_sub(self.snapshot_index());
+        if applied == 0 {
+            return false;
+        }
+        let interval_elapsed = self.config.snapshot_interval.is_some_and(|interval| {
+            let tick_ms = self.config.tick_interval.as_millis().max(1);
+            let interval_ticks = (interval.as_millis() / tick_ms).max(1) as u64;
+            self.ticks - self.snapshot_tick >= interval_ticks
+        });
+        applied >= self.config.snapshot_threshold
+            || self.log_bytes >= self.config.snapshot_max_log_bytes
+            || interval_elapsed
+    }
+
+    /// Approximate serialized size of the entries not yet compacted.
+    pub fn log_bytes(&self) -> u64 {
+        self.log_bytes
+    }
+
+    /// Index of the last entry handed out by `ready`.
+    pub fn last_applied(&self) -> LogIndex {
+        self.last_applied
+    }
+
+    /// Replace the log up to `index` with a snapshot of the state machine.
+    ///
+    /// `data` must reflect exactly the entries applied up to `index`, which
+    /// must already have been handed out by `ready`.
+    pub fn compact(&mut self, index: LogIndex, data: serde_json::Value) -> Result<()> {
+        if index > self.last_applied {
+            return Err(anyhow::anyhow!(
+                "Cannot compact to {}: only {} entries have been applied",
+                index, self.last_applied
+            ));
+        }
+        if index <= self.snapshot_index() {
+            return Ok(());
+        }
+
+        let last_term = self.term_at(index).unwrap_or(0);
+        let voters = self.voters_at(index);
+        let first = self.first_index();
+        self.log.drain(..(index - first + 1) as usize);
+        self.recount_log_bytes();
+        self.snapshot = Some(Snapshot { last_index: index, last_term, voters, data });
+        self.snapshot_tick = self.ticks;
+        Ok(())
+    }
+
+    // Log helpers
+
+    fn entry_size(entry: &LogEntry) -> u64 {
+        match &entry.payload {
+            EntryPayload::Proposal(proposal) => compaction::entry_size(Some(&proposal.operation)),
+            EntryPayload::Noop | EntryPayload::Membership(_) => compaction::entry_size(None),
+        }
+    }
+
+    fn recount_log_bytes(&mut self) {
+        self.log_bytes = self.log.iter().map(Self::entry_size).sum();
+    }
+
+    fn snapshot_index(&self) -> LogIndex {
+        self.snapshot.as_ref().map(|snapshot| snapshot.last_index).unwrap_or(0)
+    }
+
+    fn first_index(&self) -> LogIndex {
+        self.snapshot_index() + 1
+    }
+
+    fn last_index(&self) -> LogIndex {
+        self.log.last().map(|entry| entry.index).unwrap_or_else(|| self.snapshot_index())
+    }
+
+    fn last_term(&self) -> Term {
+        self.term_at(self.last_index()).unwrap_or(0)
+    }
+
+    fn entry(&self, index: LogIndex) -> Option<&LogEntry> {
+        if index < self.first_index() {
+            return None;
+        }
+        self.log.get((index - self.first_index()) as usize)
+    }
+
+    /// Term of the entry at `index`, or `None` if it is compacted or missing.
+    fn term_at(&self, index: LogIndex) -> Option<Term> {
+        if index == 0 {
+            return Some(0);
+        }
+        match &self.snapshot {
+            Some(snapshot) if index == snapshot.last_index => Some(snapshot.last_term),
+            _ => self.entry(index).map(|entry| entry.term),
+        }
+    }
+
+    /// Voter set in effect once the log up to `index` is applied.
+    fn voters_at(&self, index: LogIndex) -> Vec<PeerId> {
+        self.log
+            .iter()
+            .rev()
+            .filter(|entry| entry.index <= index)
+            .find_map(|entry| match &entry.payload {
+                EntryPayload::Membership(voters) => Some(voters.clone()),
+                _ => None,
+            })
+            .or_else(|| self.snapshot.as_ref().map(|snapshot| snapshot.voters.clone()))
+            .unwrap_or_else(|| self.initial_voters())
+    }
+
+    fn initial_voters(&self) -> Vec<PeerId> {
+        if self.config.voters.is_empty() {
+            vec![self.id.clone()]
+        } else {
+            let mut voters = self.config.voters.clone();
+            voters.sort();
+            voters.dedup();
+            voters
+        }
+    }
+
+    /// Recompute the voter set after the log changed and keep leader
+    /// progress tracking in step with it.
+    fn refresh_voters(&mut self) {
+        let mut voters = self.voters_at(self.last_index());
+        voters.sort();
+        self.voters = voters;
+
+        if self.role == RaftRole::Leader {
+            let next_index = self.last_index() + 1;
+            for peer in self.voters.iter().filter(|peer| **peer != self.id) {
+                self.progress.entry(peer.clone()).or_insert(Progress::new(next_index));
+            }
+            let voters = &self.voters;
+            self.progress.retain(|peer, _| voters.contains(peer));
+        }
+    }
+
+    fn append(&mut self, payload: EntryPayload) -> LogIndex {
+        let index = self.last_index() + 1;
+        let membership = matches!(payload, EntryPayload::Membership(_));
+        let entry = LogEntry { index, term: self.term, payload };
+        self.log_bytes += Self::entry_size(&entry);
+        self.log.push(entry);
+        if membership {
+            self.refresh_voters();
+        }
+        self.maybe_commit();
+        index
+    }
+
+    fn quorum(&self) -> usize {
+        self.voters.len() / 2 + 1
+    }
+
+    // Roles and elections
+
+    fn ensure_leader(&self) -> Result<()> {
+        if self.is_leader() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Not the Raft leader (current leader: {:?})", self.leader))
+        }
+    }
+
+    fn propose_membership(&mut self, mut voters: Vec<PeerId>) -> Result<LogIndex> {
+        self.ensure_leader()?;
+        // The leader's no-op must commit first so it knows the latest
+        // committed configuration before changing it
+        if self.term_at(self.commit_index) != Some(self.term) {
+            return Err(anyhow::anyhow!("Raft leader has not committed an entry in its term yet"));
+        }
+        let uncommitted_change = self.log.iter().any(|entry| {
+            entry.index > self.commit_index && matches!(entry.payload, EntryPayload::Membership(_))
+        });
+        if uncommitted_change {
+            return Err(anyhow::anyhow!("A Raft membership change is already in progress"));
+        }
+
+        voters.sort();
+        let index = self.append(EntryPayload::Membership(voters));
+        self.broadcast_append();
+        Ok(index)
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64: deterministic for a given seed
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn reset_election_timeout(&mut self) {
+        self.election_elapsed = 0;
+        let spread = self.election_ticks.max(1) as u64;
+        self.randomized_election_ticks = self.election_ticks + (self.next_random() % spread) as u32;
+    }
+
+    fn become_follower(&mut self, term: Term, leader: Option<PeerId>) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+        }
+        self.role = RaftRole::Follower;
+        self.leader = leader;
+        self.votes_received.clear();
+        self.progress.clear();
+        self.reset_election_timeout();
+    }
+
+    /// Start an election now rather than waiting for the election timeout.
+    pub fn campaign(&mut self) {
+        self.reset_election_timeout();
+        // Nodes outside the voter set (joining or removed) never campaign
+        if !self.voters.contains(&self.id) {
+            return;
+        }
+
+        self.term += 1;
+        self.role = RaftRole::Candidate;
+        self.leader = None;
+        self.voted_for = Some(self.id.clone());
+        self.votes_received = HashSet::from([self.id.clone()]);
+
+        if self.votes_received.len() >= self.quorum() {
+            self.become_leader();
+            return;
+        }
+
+        let (last_log_index, last_log_term) = (self.last_index(), self.last_term());
+        for peer in self.voters.clone() {
+            if peer != self.id {
+                self.send(&peer, RaftPayload::RequestVote { last_log_index, last_log_term });
+            }
+        }
+    }
+
+    fn become_leader(&mut self) {
+        self.role = RaftRole::Leader;
+        self.leader = Some(self.id.clone());
+        self.votes_received.clear();
+        self.heartbeat_elapsed = 0;
+
+        let next_index = self.last_index() + 1;
+        self.progress = self
+            .voters
+            .iter()
+            .filter(|peer| **peer != self.id)
+            .map(|peer| (peer.clone(), Progress::new(next_index)))
+            .collect();
+
+        self.append(EntryPayload::Noop);
+        self.broadcast_append();
+    }
+
+    fn handle_request_vote(&mut self, from: PeerId, last_log_index: LogIndex, last_log_term: Term) {
+        let up_to_date = (last_log_term, last_log_index) >= (self.last_term(), self.last_index());
+        let can_vote = self.voted_for.as_ref().is_none_or(|voted| *voted == from);
+        let granted = can_vote && up_to_date && self.role == RaftRole::Follower;
+        if granted {
+            self.voted_for = Some(from.clone());
+            self.reset_election_timeout();
+        }
+        self.send(&from, RaftPayload::RequestVoteResponse { granted });
+    }
+
+    fn handle_vote_response(&mut self, from: PeerId, granted: bool) {
+        if self.role != RaftRole::Candidate || !granted || !self.voters.contains(&from) {
+            return;
+        }
+        self.votes_received.insert(from);
+        if self.votes_received.len() >= self.quorum() {
+            self.become_leader();
+        }
+    }
+
+    // Replication
+
+    fn send(&mut self, to: &PeerId, payload: RaftPayload) {
+        self.outbox.push(RaftMessage {
+            from: self.id.clone(),
+            to: to.clone(),
+            term: self.term,
+            payload,
+        });
+    }
+
+    fn broadcast_append(&mut self) {
+        let peers: Vec<PeerId> = self.progress.keys().cloned().collect();
+        for peer in peers {
+            self.send_append(&peer);
+        }
+    }
+
+    fn send_append(&mut self, peer: &PeerId) {
+        let ticks = self.ticks;
+        let Some(progress) = self.progress.get_mut(peer) else {
+            return;
+        };
+        progress.probe_sent_at.get_or_insert(ticks);
+        let progress = *progress;
+
+        if progress.next_index < self.first_index() {
+            // The entries the peer needs were compacted away
+            if let Some(snapshot) = self.snapshot.clone() {
+                self.send(peer, RaftPayload::InstallSnapshot { snapshot });
+            }
+            return;
+        }
+
+        let prev_log_index = progress.next_index - 1;
+        let prev_log_term = self.term_at(prev_log_index).unwrap_or(0);
+        let entries: Vec<LogEntry> = self
+            .log
+            .iter()
+            .filter(|entry| entry.index >= progress.next_index)
+            .take(self.config.max_entries_per_message.max(1))
+            .cloned()
+            .collect();
+
+        self.send(peer, RaftPayload::AppendEntries {
+            prev_log_index,
+            prev_log_term,
+            entries,
+            leader_commit: self.commit_index,
+        });
+    }
+
+    fn handle_append_entries(
+        &mut self,
+        from: PeerId,
+        mut prev_log_index: LogIndex,
+        mut prev_log_term: Term,
+        mut entries: Vec<LogEntry>,
+        leader_commit: LogIndex,
+    ) {
+        if self.role != RaftRole::Follower {
+            self.become_follower(self.term, None);
+        }
+        self.leader = Some(from.clone());
+        self.election_elapsed = 0;
+        self.leader_commit = leader_commit;
+
+        // Entries covered by our snapshot are committed and known to match
+        let snapshot_index = self.snapshot_index();
+        if prev_log_index < snapshot_index {
+            entries.retain(|entry| entry.index > snapshot_index);
+            prev_log_index = snapshot_index;
+            prev_log_term = self.term_at(snapshot_index).unwrap_or(0);
+        }
+
+        if self.term_at(prev_log_index) != Some(prev_log_term) {
+            let hint = if self.last_index() < prev_log_index {
+                self.last_index()
+            } else {
+                prev_log_index.saturating_sub(1)
+            };
+            self.send(&from, RaftPayload::AppendEntriesResponse { success: false, match_index: hint });
+            return;
+        }
+
+        let last_new_index = prev_log_index + entries.len() as LogIndex;
+        let mut membership_changed = false;
+        for entry in entries {
+            match self.term_at(entry.index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => {
+                    // Conflicting suffix from a deposed leader; committed
+                    // entries never conflict, so this only drops uncommitted ones
+                    let keep = (entry.index - self.first_index()) as usize;
+                    membership_changed |= self.log[keep..]
+                        .iter()
+                        .any(|dropped| matches!(dropped.payload, EntryPayload::Membership(_)));
+                    self.log.truncate(keep);
+                    self.recount_log_bytes();
+                }
+                None => {}
+            }
+            membership_changed |= matches!(entry.payload, EntryPayload::Membership(_));
+            self.log_bytes += Self::entry_size(&entry);
+            self.log.push(entry);
+        }
+        if membership_changed {
+            self.refresh_voters();
+        }
+
+        if leader_commit > self.commit_index {
+            self.commit_index = leader_commit.min(last_new_index).max(self.commit_index);
+        }
+        self.send(&from, RaftPayload::AppendEntriesResponse { success: true, match_index: last_new_index });
+    }
+
+    fn handle_append_response(&mut self, from: PeerId, success: bool, match_index: LogIndex) {
+        if self.role != RaftRole::Leader {
+            return;
+        }
+        let last_index = self.last_index();
+        let Some(progress) = self.progress.get_mut(&from) else {
+            return;
+        };
+        progress.acknowledge();
+
+        if success {
+            progress.match_index = progress.match_index.max(match_index);
+            progress.next_index = progress.next_index.max(match_index + 1);
+            let behind = progress.next_index <= last_index;
+            self.maybe_commit();
+            if behind {
+                self.send_append(&from);
+            }
+        } else {
+            // Back off towards the follower's hint, always making progress
+            progress.next_index = (match_index + 1).min(progress.next_index.saturating_sub(1)).max(1);
+            self.send_append(&from);
+        }
+    }
+
+    fn handle_install_snapshot(&mut self, from: PeerId, snapshot: Snapshot) {
+        if self.role != RaftRole::Follower {
+            self.become_follower(self.term, None);
+        }
+        self.leader = Some(from.clone());
+        self.election_elapsed = 0;
+
+        let last_index = snapshot.last_index;
+        if last_index <= self.commit_index {
+            // Already have everything the snapshot covers
+            self.send(&from, RaftPayload::InstallSnapshotResponse { last_index: self.commit_index });
+            return;
+        }
+
+        // Keep any log suffix that follows on from the snapshot
+        if self.term_at(last_index) == Some(snapshot.last_term) {
+            let keep_from = (last_index - self.first_index() + 1) as usize;
+            self.log.drain(..keep_from);
+        } else {
+            self.log.clear();
+        }
+        self.recount_log_bytes();
+        self.snapshot_tick = self.ticks;
+
+        self.commit_index = last_index;
+        self.last_applied = last_index;
+        self.snapshot = Some(snapshot.clone());
+        self.pending_snapshot = Some(snapshot);
+        self.refresh_voters();
+
+        self.send(&from, RaftPayload::InstallSnapshotResponse { last_index });
+    }
+
+    fn handle_snapshot_response(&mut self, from: PeerId, last_index: LogIndex) {
+        if self.role != RaftRole::Leader {
+            return;
+        }
+        let leader_last = self.last_index();
+        let Some(progress) = self.progress.get_mut(&from) else {
+            return;
+        };
+        progress.acknowledge();
+        progress.match_index = progress.match_index.max(last_index);
+        progress.next_index = progress.match_index + 1;
+        let behind = progress.next_index <= leader_last;
+        self.maybe_commit();
+        if behind {
+            self.send_append(&from);
+        }
+    }
+
+    /// Advance the commit index to the highest entry of the current term
+    /// replicated on a majority of voters.
+    fn maybe_commit(&mut self) {
+        if self.role != RaftRole::Leader {
+            return;
+        }
+
+        let last_index = self.last_index();
+        let mut committed = self.commit_index;
+        for index in (self.commit_index + 1..=last_index).rev() {
+            // Entries from earlier terms only commit indirectly (Raft §5.4.2)
+            if self.term_at(index) != Some(self.term) {
+                break;
+            }
+            let replicas = self
+                .voters
+                .iter()
+                .filter(|voter| {
+                    **voter == self.id
+                        || self.progress.get(*voter).is_some_and(|progress| progress.match_index >= index)
+                })
+                .count();
+            if replicas >= self.quorum() {
+                committed = index;
+                break;
+            }
+        }
+
+        if committed > self.commit_index {
+            self.commit_index = committed;
+            if !self.voters.contains(&self.id) && self.voters_at(committed) == self.voters {
+                // Our own removal has committed; the remaining voters elect a new leader
+                self.broadcast_append();
+                self.become_follower(self.term, None);
+            } else {
+                // Let followers learn the new commit index without waiting for a heartbeat
+                self.broadcast_append();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Operation;
+    use chrono::Utc;
+    use std::collections::VecDeque;
+
+    /// Deterministic in-memory network of Raft nodes.
+    struct Simulation {
+        nodes: HashMap<PeerId, RaftNode>,
+        /// Messages in flight, delivered in FIFO order
+        network: VecDeque<RaftMessage>,
+        /// Nodes cut off from everyone else
+        isolated: HashSet<PeerId>,
+        /// Operations each node has applied, in order
+        applied: HashMap<PeerId, Vec<String>>,
+    }
+
+    impl Simulation {
+        fn new(size: usize, config: RaftConfig, seed: u64) -> Self {
+            let ids: Vec<PeerId> = (1..=size).map(|n| format!("n{}", n)).collect();
+            let config = RaftConfig { voters: ids.clone(), ..config };
+            let nodes = ids
+                .iter()
+                .enumerate()
+                .map(|(n, id)| (id.clone(), RaftNode::new(id.clone(), config.clone(), seed + n as u64)))
+                .collect();
+            Self {
+                nodes,
+                network: VecDeque::new(),
+                isolated: HashSet::new(),
+                applied: ids.into_iter().map(|id| (id, Vec::new())).collect(),
+            }
+        }
+
+        fn add_node(&mut self, id: &str, config: RaftConfig, seed: u64) {
+            self.nodes.insert(id.to_string(), RaftNode::new(id.to_string(), config, seed));
+            self.applied.insert(id.to_string(), Vec::new());
+        }
+
+        fn node(&mut self, id: &str) -> &mut RaftNode {
+            self.nodes.get_mut(id).unwrap()
+        }
+
+        fn collect(&mut self) {
+            let mut ids: Vec<PeerId> = self.nodes.keys().cloned().collect();
+            ids.sort();
+            for id in ids {
+                let ready = self.nodes.get_mut(&id).unwrap().ready();
+                let applied = self.applied.get_mut(&id).unwrap();
+                if let Some(snapshot) = ready.snapshot {
+                    *applied = serde_json::from_value(snapshot.data).unwrap();
+                }
+                for entry in ready.committed {
+                    if let EntryPayload::Proposal(proposal) = entry.payload {
+                        if let Operation::CreateCollection { name, .. } = proposal.operation {
+                            applied.push(name);
+                        }
+                    }
+                }
+                self.network.extend(ready.messages);
+            }
+        }
+
+        /// Deliver every message until the network is quiet.
+        fn deliver_all(&mut self) {
+            self.collect();
+            while let Some(message) = self.network.pop_front() {
+                if self.isolated.contains(&message.from) || self.isolated.contains(&message.to) {
+                    continue;
+                }
+                if let Some(node) = self.nodes.get_mut(&message.to) {
+                    node.step(message);
+                }
+                self.collect();
+            }
+        }
+
+        fn run(&mut self, ticks: usize) {
+            for _ in 0..ticks {
+                let mut ids: Vec<PeerId> = self.nodes.keys().cloned().collect();
+                ids.sort();
+                for id in ids {
+                    self.node(&id).tick();
+                }
+                self.deliver_all();
+            }
+        }
+
+        fn leaders(&self) -> Vec<PeerId> {
+            let mut leaders: Vec<PeerId> = self
+                .nodes
+                .values()
+                .filter(|node| node.is_leader() && !self.isolated.contains(node.id()))
+                .map(|node| node.id().clone())
+                .collect();
+            leaders.sort();
+            leaders
+        }
+
+        fn elect(&mut self) -> PeerId {
+            for _ in 0..100 {
+                self.run(1);
+                if let [leader] = self.leaders().as_slice() {
+                    return leader.clone();
+                }
+            }
+            panic!("no leader elected");
+        }
+
+        fn propose(&mut self, id: &str, name: &str) -> Result<LogIndex> {
+            self.node(id).propose(proposal(name))
+        }
+    }
+
+    fn proposal(name: &str) -> Proposal {
+        Proposal {
+            id: uuid::Uuid::new_v4(),
+            round: 0,
+            proposer: "client".to_string(),
+            operation: Operation::CreateCollection { name: name.to_string(), schema: None },
+            timestamp: Utc::now(),
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_single_node_commits_immediately() {
+        let mut node = RaftNode::new("solo".to_string(), RaftConfig::default(), 7);
+        assert!(node.propose(proposal("users")).is_err());
+
+        for _ in 0..20 {
+            node.tick();
+        }
+        assert!(node.is_leader());
+
+        let index = node.propose(proposal("users")).unwrap();
+        let ready = node.ready();
+        assert!(ready.messages.is_empty());
+        assert_eq!(ready.committed.last().map(|entry| entry.index), Some(index));
+        assert!(matches!(ready.committed[0].payload, EntryPayload::Noop));
+    }
+
+    #[test]
+    fn test_election_timeout_follows_peer_rtt() {
+        use crate::adaptive_timeout::AdaptiveTimeoutManager;
+        use std::time::Duration;
+
+        let peers: Vec<PeerId> = vec!["n1".to_string(), "n2".to_string(), "n3".to_string()];
+        let config = RaftConfig { voters: peers.clone(), ..RaftConfig::default() };
+        let timeouts = AdaptiveTimeoutManager::new(true, Duration::from_secs(1), Duration::from_millis(200), Duration::from_secs(60));
+        let mut node = RaftNode::new("n1".to_string(), config, 7);
+
+        // Without samples the base timeout applies: 1s of 100ms ticks
+        node.set_election_timeout(timeouts.election_timeout());
+        assert_eq!(node.election_ticks(), 10);
+
+        // A slow WAN link stretches it to ten times the quorum p99 RTT
+        for _ in 0..10 {
+            for peer in &peers[1..] {
+                timeouts.record_rtt(peer, Duration::from_millis(300));
+            }
+        }
+        node.set_election_timeout(timeouts.election_timeout());
+        assert_eq!(node.election_ticks(), 30);
+
+        // The next election waits between 30 and 60 ticks
+        node.campaign();
+        let term = node.term();
+        for _ in 0..29 {
+            node.tick();
+        }
+        assert_eq!(node.term(), term);
+        for _ in 0..31 {
+            node.tick();
+        }
+        assert_eq!(node.term(), term + 1);
+
+        // A fast LAN shrinks it, but never to the heartbeat interval
+        let lan = AdaptiveTimeoutManager::new(true, Duration::from_secs(1), Duration::from_millis(200), Duration::from_secs(60));
+        for _ in 0..10 {
+            lan.record_rtt(&peers[1], Duration::from_millis(1));
+        }
+        node.set_election_timeout(lan.election_timeout());
+        assert_eq!(node.election_ticks(), RaftConfig::default().heartbeat_ticks + 1);
+    }
+
+    #[test]
+    fn test_election_and_reelection_after_leader_isolation() {
+        for seed in 0..20 {
+            let mut sim = Simulation::new(5, RaftConfig::default(), seed * 31);
+            let first = sim.elect();
+            let term = sim.nodes[&first].term();
+            assert!(sim.nodes.values().all(|node| node.leader() == Some(&first)));
+
+            // At most one leader per term, and it stays put while healthy
+            sim.run(50);
+            assert_eq!(sim.leaders(), vec![first.clone()]);
+            assert_eq!(sim.nodes[&first].term(), term);
+
+            sim.isolated.insert(first.clone());
+            let second = sim.elect();
+            assert_ne!(second, first);
+            assert!(sim.nodes[&second].term() > term);
+
+            // The old leader steps down as soon as it hears the newer term
+            sim.isolated.clear();
+            sim.run(10);
+            assert_eq!(sim.leaders(), vec![second.clone()]);
+            assert_eq!(sim.nodes[&first].role(), RaftRole::Follower);
+        }
+    }
+
+    #[test]
+    fn test_replication_and_log_divergence() {
+        let mut sim = Simulation::new(5, RaftConfig::default(), 42);
+        let old_leader = sim.elect();
+        sim.propose(&old_leader, "a").unwrap();
+        sim.propose(&old_leader, "b").unwrap();
+        sim.deliver_all();
+        assert!(sim.applied.values().all(|applied| *applied == ["a", "b"]));
+
+        // Partitioned from the majority, the old leader accepts writes that can never commit
+        sim.isolated.insert(old_leader.clone());
+        sim.propose(&old_leader, "lost-1").unwrap();
+        sim.propose(&old_leader, "lost-2").unwrap();
+        sim.deliver_all();
+        assert_eq!(sim.nodes[&old_leader].status().last_log_index, 5);
+
+        let new_leader = sim.elect();
+        sim.propose(&new_leader, "c").unwrap();
+        sim.deliver_all();
+
+        // After healing, the old leader's divergent suffix is overwritten
+        sim.isolated.clear();
+        sim.run(10);
+        sim.propose(&new_leader, "d").unwrap();
+        sim.run(5);
+
+        for (id, applied) in &sim.applied {
+            assert_eq!(*applied, ["a", "b", "c", "d"], "node {} diverged", id);
+        }
+        let commit = sim.nodes[&new_leader].commit_index();
+        assert!(sim.nodes.values().all(|node| node.commit_index() == commit));
+    }
+
+    #[test]
+    fn test_followers_reject_proposals() {
+        let mut sim = Simulation::new(3, RaftConfig::default(), 5);
+        let leader = sim.elect();
+        let follower = sim.nodes.keys().find(|id| **id != leader).unwrap().clone();
+        let err = sim.propose(&follower, "x").unwrap_err();
+        assert!(err.to_string().starts_with("Not the Raft leader"));
+    }
+
+    #[test]
+    fn test_snapshot_catches_up_new_voter() {
+        let config = RaftConfig { snapshot_threshold: 5, ..RaftConfig::default() };
+        let mut sim = Simulation::new(3, config.clone(), 9);
+        let leader = sim.elect();
+        for n in 0..10 {
+            sim.propose(&leader, &format!("c{}", n)).unwrap();
+        }
+        sim.deliver_all();
+
+        // Compact every node, with the applied operations as the state machine
+        let ids: Vec<PeerId> = sim.nodes.keys().cloned().collect();
+        for id in &ids {
+            let data = serde_json::to_value(&sim.applied[id]).unwrap();
+            let node = sim.node(id);
+            assert!(node.should_compact());
+            let applied = node.last_applied();
+            node.compact(applied, data).unwrap();
+            assert_eq!(node.status().snapshot_index, applied);
+        }
+
+        // A joining node knows the cluster but is not a voter, so it waits for the leader
+        let joining = RaftConfig { voters: ids.clone(), ..config };
+        sim.add_node("n4", joining, 99);
+        sim.run(3);
+        sim.node(&leader).add_voter("n4".to_string()).unwrap();
+        sim.run(5);
+        sim.propose(&leader, "after").unwrap();
+        sim.run(3);
+
+        let expected: Vec<String> = (0..10).map(|n| format!("c{}", n)).chain(["after".to_string()]).collect();
+        assert_eq!(sim.applied["n4"], expected);
+        assert_eq!(sim.nodes["n4"].voters().len(), 4);
+        assert_eq!(sim.nodes[&leader].voters().len(), 4);
+    }
+
+    #[test]
+    fn test_compaction_by_size_and_interval() {
+        let leader = |config: RaftConfig| {
+            let mut node = RaftNode::new("solo".to_string(), config, 7);
+            for _ in 0..20 {
+                node.tick();
+            }
+            assert!(node.is_leader());
+            node.ready();
+            node
+        };
+
+        // Size alone: far below the entry threshold, but over the byte limit
+        let by_size = RaftConfig { snapshot_threshold: u64::MAX, snapshot_interval: None, snapshot_max_log_bytes: 1024, ..RaftConfig::default() };
+        let mut node = leader(by_size);
+        while node.log_bytes() < 1024 {
+            assert!(!node.should_compact());
+            node.propose(proposal("users")).unwrap();
+            node.ready();
+        }
+        assert!(node.should_compact());
+        let applied = node.last_applied();
+        node.compact(applied, serde_json::json!([])).unwrap();
+        assert_eq!(node.log_bytes(), 0);
+        assert!(!node.should_compact());
+
+        // Interval alone: 10 ticks of 100ms, only once something was applied since
+        let by_interval = RaftConfig {
+            snapshot_threshold: u64::MAX,
+            snapshot_max_log_bytes: u64::MAX,
+            snapshot_interval: Some(std::time::Duration::from_secs(1)),
+            ..RaftConfig::default()
+        };
+        let mut node = leader(by_interval);
+        let applied = node.last_applied();
+        node.compact(applied, serde_json::json!([])).unwrap();
+        for _ in 0..10 {
+            node.tick();
+        }
+        assert!(!node.should_compact());
+        node.propose(proposal("users")).unwrap();
+        node.ready();
+        assert!(node.should_compact());
+    }
+
+    #[test]
+    fn test_membership_changes() {
+        let mut sim = Simulation::new(3, RaftConfig::default(), 11);
+        let leader = sim.elect();
+
+        // Only one change may be in flight at a time
+        sim.isolated.extend(sim.nodes.keys().filter(|id| **id != leader).cloned().collect::<Vec<_>>());
+        sim.node(&leader).add_voter("n4".to_string()).unwrap();
+        assert!(sim.node(&leader).remove_voter(&"n2".to_string()).is_err());
+        sim.isolated.clear();
+        sim.add_node("n4", RaftConfig { voters: vec!["n1".into(), "n2".into(), "n3".into()], ..RaftConfig::default() }, 4);
+        sim.run(5);
+        assert!(sim.nodes.values().all(|node| node.voters().len() == 4));
+
+        // A leader that removes itself steps down once the change commits
+        sim.node(&leader).remove_voter(&leader).unwrap();
+        sim.run(5);
+        assert_ne!(sim.nodes[&leader].role(), RaftRole::Leader);
+        let next = sim.elect();
+        assert_ne!(next, leader);
+        assert!(!sim.nodes[&next].voters().contains(&leader));
+
+        // The removed node no longer campaigns, and cannot disrupt the cluster
+        let term = sim.nodes[&next].term();
+        sim.run(100);
+        assert_eq!(sim.leaders(), vec![next.clone()]);
+        assert_eq!(sim.nodes[&next].term(), term);
+        assert!(sim.node(&next).remove_voter(&"n9".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_read_lease_and_follower_staleness() {
+        let mut sim = Simulation::new(3, RaftConfig::default(), 5);
+        let leader = sim.elect();
+        sim.run(1);
+        let index = sim.nodes[&leader].read_index().unwrap();
+        assert_eq!(sim.nodes[&leader].staleness_ticks(), Some(0));
+
+        // A caught-up follower trails by the ticks since it heard from the leader
+        let follower = sim.nodes.keys().find(|id| **id != leader).unwrap().clone();
+        assert!(sim.nodes[&follower].staleness_ticks().unwrap() < RaftConfig::default().heartbeat_ticks as u64);
+        assert_eq!(sim.nodes[&follower].commit_index(), index);
+
+        // Cut off from the majority, the leader's followers fall behind and
+        // it loses its lease once they may elect someone else
+        sim.isolated.insert(leader.clone());
+        sim.run(5);
+        assert!(sim.nodes[&follower].staleness_ticks().unwrap() >= 5);
+        assert!(sim.nodes[&leader].has_lease());
+        sim.run(RaftConfig::default().election_ticks as usize - 5);
+        assert!(!sim.nodes[&leader].has_lease());
+        assert_eq!(sim.nodes[&leader].read_index(), None);
+        assert_eq!(sim.nodes[&leader].staleness_ticks(), None);
+    }
+}
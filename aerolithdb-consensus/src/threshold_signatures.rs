@@ -0,0 +1,438 @@
+//! BLS threshold signature aggregation for consensus votes and commits.
+//!
+//! With individual signatures every node verifies every vote, which grows as
+//! O(n²) across the cluster. When threshold signatures are enabled, votes are
+//! signed with BLS12-381 keys and the partial signatures collected for a
+//! proposal are aggregated into a single certificate once the quorum threshold
+//! is reached. Any node can then check the commit with one aggregate
+//! verification against the signers' public keys.
+//!
+//! Partial signatures are not verified on arrival. If an aggregate fails to
+//! verify, the partials are checked individually so the offending signers can
+//! be dropped and reported as Byzantine.
+//!
+//! Every voter signs the same message, so a peer key is only registered with
+//! a proof of possession of its secret key. Otherwise a peer could register
+//! a key derived from the honest peers' keys and forge a quorum's aggregate
+//! on its own.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use blst::min_pk::{AggregateSignature, PublicKey, SecretKey, Signature};
+use blst::BLST_ERROR;
+use dashmap::DashMap;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::types::{PeerId, ProposalId, VoteDecision};
+
+/// Domain separation tag for consensus vote signatures, in the proof of
+/// possession ciphersuite.
+const CONSENSUS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_AEROLITHDB_CONSENSUS_";
+
+/// Domain separation tag for proofs of possession of a signing key.
+const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_AEROLITHDB_CONSENSUS_";
+
+/// Local BLS key pair used to sign consensus votes.
+pub struct BlsKeyPair {
+    secret_key: SecretKey,
+    public_key: PublicKey,
+}
+
+/// Aggregated quorum certificate proving that `signers` voted for a decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregatedCertificate {
+    /// Proposal the certificate covers
+    pub proposal_id: ProposalId,
+    /// Whether the signers accepted (true) or rejected (false) the proposal
+    pub accepted: bool,
+    /// Peers whose partial signatures are included in the aggregate
+    pub signers: Vec<PeerId>,
+    /// Hex-encoded aggregate BLS signature
+    pub aggregate_signature: String,
+}
+
+/// Result of attempting to aggregate the partial signatures for a proposal.
+#[derive(Debug)]
+pub enum AggregationOutcome {
+    /// Not enough valid partial signatures yet
+    Pending,
+    /// Quorum reached and the aggregate verified
+    Certified(AggregatedCertificate),
+}
+
+/// Collects BLS partial signatures and aggregates them into quorum certificates.
+pub struct ThresholdSignatureAggregator {
+    /// Local peer identifier
+    local_peer: PeerId,
+
+    /// Local signing key pair
+    keypair: BlsKeyPair,
+
+    /// Known BLS public keys of cluster peers
+    peer_keys: DashMap<PeerId, PublicKey>,
+
+    /// Unverified partial signatures per (proposal, decision)
+    partials: DashMap<(ProposalId, bool), HashMap<PeerId, Signature>>,
+}
+
+impl BlsKeyPair {
+    /// Generate a fresh key pair from the operating system's random source.
+    pub fn generate() -> Result<Self> {
+        let mut ikm = [0u8; 32];
+        OsRng.fill_bytes(&mut ikm);
+
+        let secret_key = SecretKey::key_gen(&ikm, &[])
+            .map_err(|e| anyhow::anyhow!("BLS key generation failed: {:?}", e))?;
+        let public_key = secret_key.sk_to_pk();
+
+        Ok(Self { secret_key, public_key })
+    }
+
+    /// Hex-encoded compressed public key for distribution to peers.
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key.to_bytes())
+    }
+
+    /// Hex-encoded proof of possession of the secret key, announced to
+    /// peers together with the public key.
+    pub fn proof_of_possession_hex(&self) -> String {
+        hex::encode(self.secret_key.sign(&self.public_key.to_bytes(), POP_DST, &[]).to_bytes())
+    }
+
+    fn sign(&self, message: &[u8]) -> Signature {
+        self.secret_key.sign(message, CONSENSUS_DST, &[])
+    }
+}
+
+impl ThresholdSignatureAggregator {
+    /// Create an aggregator with a freshly generated local key pair.
+    pub fn new(local_peer: PeerId) -> Result<Self> {
+        let keypair = BlsKeyPair::generate()?;
+        let peer_keys = DashMap::new();
+        peer_keys.insert(local_peer.clone(), keypair.public_key);
+
+        Ok(Self {
+            local_peer,
+            keypair,
+            peer_keys,
+            partials: DashMap::new(),
+        })
+    }
+
+    /// Hex-encoded local public key to announce to peers.
+    pub fn local_public_key(&self) -> String {
+        self.keypair.public_key_hex()
+    }
+
+    /// Hex-encoded proof of possession of the local secret key.
+    pub fn local_proof_of_possession(&self) -> String {
+        self.keypair.proof_of_possession_hex()
+    }
+
+    /// Register a peer's hex-encoded BLS public key, once its proof of
+    /// possession shows the peer holds the matching secret key.
+    pub fn register_peer_key(&self, peer_id: &PeerId, public_key_hex: &str, proof_of_possession_hex: &str) -> Result<()> {
+        let bytes = hex::decode(public_key_hex)?;
+        let public_key = PublicKey::key_validate(&bytes)
+            .map_err(|e| anyhow::anyhow!("Invalid BLS public key for {}: {:?}", peer_id, e))?;
+        let proof = Signature::from_bytes(&hex::decode(proof_of_possession_hex)?)
+            .map_err(|e| anyhow::anyhow!("Malformed BLS proof of possession from {}: {:?}", peer_id, e))?;
+        if proof.verify(true, &bytes, POP_DST, &[], &public_key, true) != BLST_ERROR::BLST_SUCCESS {
+            return Err(anyhow::anyhow!("Invalid BLS proof of possession from {}", peer_id));
+        }
+        self.peer_keys.insert(peer_id.clone(), public_key);
+        Ok(())
+    }
+
+    /// Whether a peer's public key is known, so its votes can be aggregated.
+    pub fn has_peer_key(&self, peer_id: &PeerId) -> bool {
+        self.peer_keys.contains_key(peer_id)
+    }
+
+    /// Sign a vote with the local key, returning the hex-encoded partial signature.
+    pub fn sign_vote(&self, proposal_id: &ProposalId, decision: &VoteDecision) -> String {
+        let message = vote_message(proposal_id, is_accept(decision));
+        hex::encode(self.keypair.sign(&message).to_bytes())
+    }
+
+    /// Record a peer's partial signature without verifying it.
+    pub fn add_partial(
+        &self,
+        proposal_id: ProposalId,
+        voter: &PeerId,
+        decision: &VoteDecision,
+        signature_hex: &str,
+    ) -> Result<()> {
+        let bytes = hex::decode(signature_hex)?;
+        let signature = Signature::from_bytes(&bytes)
+            .map_err(|e| anyhow::anyhow!("Malformed BLS signature from {}: {:?}", voter, e))?;
+
+        self.partials
+            .entry((proposal_id, is_accept(decision)))
+            .or_default()
+            .insert(voter.clone(), signature);
+        Ok(())
+    }
+
+    /// Try to build a certificate once `threshold` partials are available.
+    ///
+    /// Returns the verified certificate together with any signers whose
+    /// partial signatures turned out to be invalid.
+    pub fn try_aggregate(
+        &self,
+        proposal_id: ProposalId,
+        decision: &VoteDecision,
+        threshold: usize,
+    ) -> Result<(AggregationOutcome, Vec<PeerId>)> {
+        let accepted = is_accept(decision);
+        let key = (proposal_id, accepted);
+
+        let partials: Vec<(PeerId, Signature)> = match self.partials.get(&key) {
+            Some(partials) if partials.len() >= threshold => partials
+                .iter()
+                .map(|(peer, signature)| (peer.clone(), *signature))
+                .collect(),
+            _ => return Ok((AggregationOutcome::Pending, Vec::new())),
+        };
+
+        let message = vote_message(&proposal_id, accepted);
+
+        // Optimistic path: aggregate everything and verify once
+        if let Some(certificate) = self.aggregate_and_verify(proposal_id, accepted, &message, &partials)? {
+            self.partials.remove(&key);
+            return Ok((AggregationOutcome::Certified(certificate), Vec::new()));
+        }
+
+        // Slow path: find and drop the invalid partials
+        let mut invalid = Vec::new();
+        let mut valid = Vec::new();
+        for (peer, signature) in partials {
+            match self.peer_keys.get(&peer) {
+                Some(public_key)
+                    if signature.verify(true, &message, CONSENSUS_DST, &[], &public_key, false)
+                        == BLST_ERROR::BLST_SUCCESS =>
+                {
+                    valid.push((peer, signature));
+                }
+                _ => invalid.push(peer),
+            }
+        }
+
+        warn!(
+            "Aggregate signature for proposal {} failed; {} invalid partial(s) dropped",
+            proposal_id,
+            invalid.len()
+        );
+
+        if let Some(mut partials) = self.partials.get_mut(&key) {
+            for peer in &invalid {
+                partials.remove(peer);
+            }
+        }
+
+        if valid.len() >= threshold {
+            if let Some(certificate) = self.aggregate_and_verify(proposal_id, accepted, &message, &valid)? {
+                self.partials.remove(&key);
+                return Ok((AggregationOutcome::Certified(certificate), invalid));
+            }
+        }
+
+        Ok((AggregationOutcome::Pending, invalid))
+    }
+
+    /// Verify a certificate with a single aggregate verification. Only
+    /// distinct signers with registered keys count towards `threshold`.
+    pub fn verify_certificate(&self, certificate: &AggregatedCertificate, threshold: usize) -> Result<bool> {
+        let mut seen = HashSet::new();
+        let signers: Vec<&PeerId> = certificate.signers.iter().filter(|signer| seen.insert(*signer)).collect();
+        if signers.len() < threshold {
+            return Ok(false);
+        }
+
+        let mut public_keys = Vec::with_capacity(signers.len());
+        for signer in signers {
+            match self.peer_keys.get(signer) {
+                Some(public_key) => public_keys.push(*public_key),
+                None => {
+                    debug!("Certificate signer {} has no registered BLS key", signer);
+                    return Ok(false);
+                }
+            }
+        }
+
+        let bytes = hex::decode(&certificate.aggregate_signature)?;
+        let signature = match Signature::from_bytes(&bytes) {
+            Ok(signature) => signature,
+            Err(_) => return Ok(false),
+        };
+
+        let message = vote_message(&certificate.proposal_id, certificate.accepted);
+        let public_key_refs: Vec<&PublicKey> = public_keys.iter().collect();
+        Ok(signature.fast_aggregate_verify(true, &message, CONSENSUS_DST, &public_key_refs)
+            == BLST_ERROR::BLST_SUCCESS)
+    }
+
+    /// Discard partial signatures for a proposal that committed or aborted.
+    pub fn clear(&self, proposal_id: &ProposalId) {
+        self.partials.remove(&(*proposal_id, true));
+        self.partials.remove(&(*proposal_id, false));
+    }
+
+    /// Local peer identifier this aggregator signs for.
+    pub fn local_peer(&self) -> &PeerId {
+        &self.local_peer
+    }
+
+    fn aggregate_and_verify(
+        &self,
+        proposal_id: ProposalId,
+        accepted: bool,
+        message: &[u8],
+        partials: &[(PeerId, Signature)],
+    ) -> Result<Option<AggregatedCertificate>> {
+        let mut public_keys = Vec::with_capacity(partials.len());
+        for (peer, _) in partials {
+            match self.peer_keys.get(peer) {
+                Some(public_key) => public_keys.push(*public_key),
+                None => return Ok(None),
+            }
+        }
+
+        let signature_refs: Vec<&Signature> = partials.iter().map(|(_, signature)| signature).collect();
+        let aggregate = match AggregateSignature::aggregate(&signature_refs, true) {
+            Ok(aggregate) => aggregate.to_signature(),
+            Err(_) => return Ok(None),
+        };
+
+        let public_key_refs: Vec<&PublicKey> = public_keys.iter().collect();
+        if aggregate.fast_aggregate_verify(true, message, CONSENSUS_DST, &public_key_refs)
+            != BLST_ERROR::BLST_SUCCESS
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(AggregatedCertificate {
+            proposal_id,
+            accepted,
+            signers: partials.iter().map(|(peer, _)| peer.clone()).collect(),
+            aggregate_signature: hex::encode(aggregate.to_bytes()),
+        }))
+    }
+}
+
+fn is_accept(decision: &VoteDecision) -> bool {
+    matches!(decision, VoteDecision::Accept)
+}
+
+/// Canonical message signed by voters: the proposal ID and the decision.
+fn vote_message(proposal_id: &ProposalId, accepted: bool) -> Vec<u8> {
+    let mut message = Vec::with_capacity(17);
+    message.extend_from_slice(proposal_id.as_bytes());
+    message.push(accepted as u8);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a cluster of aggregators that all know each other's keys.
+    fn cluster(size: usize) -> Vec<ThresholdSignatureAggregator> {
+        let nodes: Vec<ThresholdSignatureAggregator> = (0..size)
+            .map(|i| ThresholdSignatureAggregator::new(format!("node{}", i)).unwrap())
+            .collect();
+        for node in &nodes {
+            for other in &nodes {
+                node.register_peer_key(other.local_peer(), &other.local_public_key(), &other.local_proof_of_possession())
+                    .unwrap();
+            }
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_aggregate_certificate_verifies_on_other_nodes() {
+        let nodes = cluster(4);
+        let proposal_id = uuid::Uuid::new_v4();
+
+        for node in &nodes[..3] {
+            let signature = node.sign_vote(&proposal_id, &VoteDecision::Accept);
+            nodes[0]
+                .add_partial(proposal_id, node.local_peer(), &VoteDecision::Accept, &signature)
+                .unwrap();
+        }
+
+        let (outcome, invalid) = nodes[0].try_aggregate(proposal_id, &VoteDecision::Accept, 3).unwrap();
+        assert!(invalid.is_empty());
+        let certificate = match outcome {
+            AggregationOutcome::Certified(certificate) => certificate,
+            AggregationOutcome::Pending => panic!("expected certificate"),
+        };
+
+        assert_eq!(certificate.signers.len(), 3);
+        assert!(nodes[3].verify_certificate(&certificate, 3).unwrap());
+        assert!(!nodes[3].verify_certificate(&certificate, 4).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_partial_is_identified() {
+        let nodes = cluster(4);
+        let proposal_id = uuid::Uuid::new_v4();
+
+        for node in &nodes[..3] {
+            let signature = node.sign_vote(&proposal_id, &VoteDecision::Accept);
+            nodes[0]
+                .add_partial(proposal_id, node.local_peer(), &VoteDecision::Accept, &signature)
+                .unwrap();
+        }
+        // node3 submits a signature over a different decision
+        let forged = nodes[3].sign_vote(&proposal_id, &VoteDecision::Reject);
+        nodes[0]
+            .add_partial(proposal_id, nodes[3].local_peer(), &VoteDecision::Accept, &forged)
+            .unwrap();
+
+        let (outcome, invalid) = nodes[0].try_aggregate(proposal_id, &VoteDecision::Accept, 3).unwrap();
+        assert_eq!(invalid, vec!["node3".to_string()]);
+        assert!(matches!(outcome, AggregationOutcome::Certified(ref c) if c.signers.len() == 3));
+    }
+
+    #[test]
+    fn test_repeated_signer_does_not_reach_threshold() {
+        let nodes = cluster(4);
+        let proposal_id = uuid::Uuid::new_v4();
+
+        let signature = nodes[1].sign_vote(&proposal_id, &VoteDecision::Accept);
+        nodes[1].add_partial(proposal_id, nodes[1].local_peer(), &VoteDecision::Accept, &signature).unwrap();
+        let certificate = match nodes[1].try_aggregate(proposal_id, &VoteDecision::Accept, 1).unwrap().0 {
+            AggregationOutcome::Certified(certificate) => certificate,
+            AggregationOutcome::Pending => panic!("expected certificate"),
+        };
+        assert!(nodes[0].verify_certificate(&certificate, 1).unwrap());
+
+        // One signer listed three times is still one signer
+        let forged = AggregatedCertificate { signers: vec!["node1".to_string(); 3], ..certificate };
+        assert!(!nodes[0].verify_certificate(&forged, 3).unwrap());
+    }
+
+    #[test]
+    fn test_peer_keys_need_proof_of_possession() {
+        let nodes = cluster(2);
+        let rogue = ThresholdSignatureAggregator::new("rogue".to_string()).unwrap();
+
+        // A proof made with another key does not cover the announced key
+        let error = nodes[0]
+            .register_peer_key(rogue.local_peer(), &rogue.local_public_key(), &nodes[1].local_proof_of_possession())
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Invalid BLS proof of possession"));
+        assert!(!nodes[0].has_peer_key(rogue.local_peer()));
+
+        nodes[0]
+            .register_peer_key(rogue.local_peer(), &rogue.local_public_key(), &rogue.local_proof_of_possession())
+            .unwrap();
+        assert!(nodes[0].has_peer_key(rogue.local_peer()));
+    }
+}
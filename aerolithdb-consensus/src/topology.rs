@@ -0,0 +1,461 @@
+//! Multi-region cluster topology with per-region quorums.
+//!
+//! A flat peer list treats every node as interchangeable, which hides the
+//! failure domain that matters most in geo-distributed deployments: the
+//! region. This module models regions explicitly so the engine can:
+//!
+//! - **Size replicas per region**: each region declares how many replicas of
+//!   every document it keeps
+//! - **Choose a write quorum**: a cluster-wide quorum, a quorum within the
+//!   local region only, or a quorum inside every region
+//! - **Survive region loss**: a region whose peers all stop responding is
+//!   declared lost and handled according to the configured failover policy
+//!
+//! Region loss and recovery events are surfaced to the engine, which pauses
+//! and resumes cross-datacenter replication to the affected region.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::types::PeerId;
+
+/// Cluster topology configuration grouping peers into regions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyConfig {
+    /// Region this node runs in
+    pub local_region: String,
+
+    /// All regions in the cluster, including the local one
+    pub regions: Vec<RegionConfig>,
+
+    /// Which votes are required before a write commits
+    pub write_quorum: WriteQuorum,
+
+    /// How writes proceed once a whole region is lost
+    pub failover_policy: RegionFailoverPolicy,
+
+    /// How long every peer in a region must be silent before the region is lost
+    pub region_loss_timeout: Duration,
+}
+
+/// A region (or datacenter) and the peers it hosts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionConfig {
+    /// Region identifier; matches `RemoteDatacenter::region` in storage
+    pub region_id: String,
+
+    /// Consensus peers located in this region (the local node is implied for the local region)
+    pub peers: Vec<PeerId>,
+
+    /// Number of replicas of each document kept in this region
+    pub replica_count: usize,
+}
+
+/// Quorum required for a write to commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WriteQuorum {
+    /// 2/3 + 1 of all peers across every region
+    Global,
+
+    /// 2/3 + 1 of the peers in the local region; other regions catch up asynchronously
+    LocalRegion,
+
+    /// 2/3 + 1 of the peers within every region
+    EachRegion,
+}
+
+/// Behaviour once every peer in a region has stopped responding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegionFailoverPolicy {
+    /// Reject writes until the lost region recovers
+    HaltWrites,
+
+    /// Drop the lost region from quorum calculations until it recovers
+    ExcludeLostRegion,
+
+    /// Fall back to a local-region quorum while any region is lost
+    DowngradeToLocalQuorum,
+}
+
+/// Region health transition detected by `RegionTopology::evaluate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegionEvent {
+    /// No peer in the region responded within the loss timeout
+    Lost(String),
+
+    /// A peer in a previously lost region responded again
+    Recovered(String),
+}
+
+/// Current state of a single region.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionStatus {
+    pub region_id: String,
+    pub peers: Vec<PeerId>,
+    pub replica_count: usize,
+    pub lost: bool,
+}
+
+/// Snapshot of the topology and the quorum currently in force.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyStatus {
+    pub local_region: String,
+    pub configured_quorum: WriteQuorum,
+    pub effective_quorum: WriteQuorum,
+    pub failover_policy: RegionFailoverPolicy,
+    pub writes_allowed: bool,
+    pub regions: Vec<RegionStatus>,
+}
+
+/// Runtime view of the region topology used to evaluate quorums and region health.
+pub struct RegionTopology {
+    config: TopologyConfig,
+
+    /// Region membership including the local peer
+    members: HashMap<String, Vec<PeerId>>,
+
+    /// Reverse index from peer to region
+    peer_regions: HashMap<PeerId, String>,
+
+    /// Last time each remote peer was heard from
+    last_seen: RwLock<HashMap<PeerId, Instant>>,
+
+    /// Regions currently considered lost
+    lost_regions: RwLock<HashSet<String>>,
+}
+
+impl RegionTopology {
+    /// Build the runtime topology, placing `local_peer` in the local region.
+    pub fn new(config: TopologyConfig, local_peer: PeerId) -> Result<Self> {
+        if !config.regions.iter().any(|region| region.region_id == config.local_region) {
+            return Err(anyhow::anyhow!(
+                "Local region '{}' is not listed in the topology", config.local_region
+            ));
+        }
+
+        let mut members: HashMap<String, Vec<PeerId>> = HashMap::new();
+        let mut peer_regions = HashMap::new();
+
+        for region in &config.regions {
+            if members.contains_key(&region.region_id) {
+                return Err(anyhow::anyhow!("Region '{}' is defined more than once", region.region_id));
+            }
+
+            let mut peers = region.peers.clone();
+            if region.region_id == config.local_region && !peers.contains(&local_peer) {
+                peers.push(local_peer.clone());
+            }
+
+            if region.replica_count == 0 || region.replica_count > peers.len() {
+                return Err(anyhow::anyhow!(
+                    "Region '{}' requests {} replicas but has {} peers",
+                    region.region_id, region.replica_count, peers.len()
+                ));
+            }
+
+            for peer in &peers {
+                if let Some(existing) = peer_regions.insert(peer.clone(), region.region_id.clone()) {
+                    return Err(anyhow::anyhow!(
+                        "Peer '{}' is assigned to both '{}' and '{}'", peer, existing, region.region_id
+                    ));
+                }
+            }
+
+            members.insert(region.region_id.clone(), peers);
+        }
+
+        // Peers get a full loss timeout of grace before their region can be declared lost
+        let now = Instant::now();
+        let last_seen = peer_regions
+            .keys()
+            .filter(|peer| **peer != local_peer)
+            .map(|peer| (peer.clone(), now))
+            .collect();
+
+        info!(
+            "Cluster topology: {} regions, local region '{}', {:?} write quorum",
+            members.len(), config.local_region, config.write_quorum
+        );
+
+        Ok(Self {
+            config,
+            members,
+            peer_regions,
+            last_seen: RwLock::new(last_seen),
+            lost_regions: RwLock::new(HashSet::new()),
+        })
+    }
+
+    /// Region a peer belongs to.
+    pub fn region_of(&self, peer_id: &PeerId) -> Option<&str> {
+        self.peer_regions.get(peer_id).map(String::as_str)
+    }
+
+    /// Record that a peer is alive.
+    pub fn record_heartbeat(&self, peer_id: &PeerId) {
+        let mut last_seen = self.last_seen.write().unwrap();
+        if let Some(seen) = last_seen.get_mut(peer_id) {
+            *seen = Instant::now();
+        }
+    }
+
+    /// Re-evaluate region health, returning the regions that changed state.
+    pub fn evaluate(&self) -> Vec<RegionEvent> {
+        let timeout = self.config.region_loss_timeout;
+        let last_seen = self.last_seen.read().unwrap();
+        let mut lost_regions = self.lost_regions.write().unwrap();
+        let mut events = Vec::new();
+
+        for (region_id, peers) in &self.members {
+            // The local region always contains this node, so it is never lost
+            if *region_id == self.config.local_region {
+                continue;
+            }
+
+            let alive = peers.iter().any(|peer| {
+                last_seen.get(peer).map(|seen| seen.elapsed() < timeout).unwrap_or(false)
+            });
+
+            if !alive && lost_regions.insert(region_id.clone()) {
+                warn!("Region '{}' lost: no peer responded within {:?}", region_id, timeout);
+                events.push(RegionEvent::Lost(region_id.clone()));
+            } else if alive && lost_regions.remove(region_id) {
+                info!("Region '{}' recovered", region_id);
+                events.push(RegionEvent::Recovered(region_id.clone()));
+            }
+        }
+
+        events
+    }
+
+    /// Regions currently considered lost.
+    pub fn lost_regions(&self) -> Vec<String> {
+        let mut lost: Vec<String> = self.lost_regions.read().unwrap().iter().cloned().collect();
+        lost.sort();
+        lost
+    }
+
+    /// Fail if the failover policy forbids writes in the current state.
+    pub fn check_writes_allowed(&self) -> Result<()> {
+        if self.config.failover_policy == RegionFailoverPolicy::HaltWrites && self.requires_lost_region() {
+            return Err(anyhow::anyhow!(
+                "Writes halted: lost region(s) {:?} are required by the {:?} write quorum",
+                self.lost_regions(), self.config.write_quorum
+            ));
+        }
+        Ok(())
+    }
+
+    /// Quorum in force after applying the failover policy.
+    pub fn effective_quorum(&self) -> WriteQuorum {
+        let any_lost = !self.lost_regions.read().unwrap().is_empty();
+        if any_lost && self.config.failover_policy == RegionFailoverPolicy::DowngradeToLocalQuorum {
+            WriteQuorum::LocalRegion
+        } else {
+            self.config.write_quorum
+        }
+    }
+
+    /// Whether the given voters satisfy the effective write quorum.
+    pub fn quorum_reached(&self, voters: &[PeerId]) -> bool {
+        let voters: HashSet<&PeerId> = voters.iter().collect();
+        let votes_in = |peers: &[PeerId]| peers.iter().filter(|peer| voters.contains(peer)).count();
+
+        match self.effective_quorum() {
+            WriteQuorum::Global => {
+                let peers = self.counted_peers();
+                votes_in(&peers) >= majority(peers.len())
+            }
+            WriteQuorum::LocalRegion => {
+                let peers = &self.members[&self.config.local_region];
+                votes_in(peers) >= majority(peers.len())
+            }
+            WriteQuorum::EachRegion => self
+                .counted_regions()
+                .into_iter()
+                .all(|region| {
+                    let peers = &self.members[region];
+                    votes_in(peers) >= majority(peers.len())
+                }),
+        }
+    }
+
+    /// Minimum number of votes that can satisfy the effective quorum.
+    pub fn required_votes(&self) -> usize {
+        match self.effective_quorum() {
+            WriteQuorum::Global => majority(self.counted_peers().len()),
+            WriteQuorum::LocalRegion => majority(self.members[&self.config.local_region].len()),
+            WriteQuorum::EachRegion => self
+                .counted_regions()
+                .into_iter()
+                .map(|region| majority(self.members[region].len()))
+                .sum(),
+        }
+    }
+
+    /// Configured replica count per region, excluding lost regions.
+    pub fn replica_placement(&self) -> HashMap<String, usize> {
+        let lost_regions = self.lost_regions.read().unwrap();
+        self.config
+            .regions
+            .iter()
+            .filter(|region| !lost_regions.contains(&region.region_id))
+            .map(|region| (region.region_id.clone(), region.replica_count))
+            .collect()
+    }
+
+    /// Snapshot of every region and the quorum currently in force.
+    pub fn status(&self) -> TopologyStatus {
+        let lost_regions = self.lost_regions.read().unwrap().clone();
+        let regions = self
+            .config
+            .regions
+            .iter()
+            .map(|region| RegionStatus {
+                region_id: region.region_id.clone(),
+                peers: self.members[&region.region_id].clone(),
+                replica_count: region.replica_count,
+                lost: lost_regions.contains(&region.region_id),
+            })
+            .collect();
+
+        TopologyStatus {
+            local_region: self.config.local_region.clone(),
+            configured_quorum: self.config.write_quorum,
+            effective_quorum: self.effective_quorum(),
+            failover_policy: self.config.failover_policy,
+            writes_allowed: self.check_writes_allowed().is_ok(),
+            regions,
+        }
+    }
+
+    /// Regions that participate in quorum calculations.
+    fn counted_regions(&self) -> Vec<&String> {
+        let lost_regions = self.lost_regions.read().unwrap();
+        let exclude_lost = self.config.failover_policy == RegionFailoverPolicy::ExcludeLostRegion;
+        self.members
+            .keys()
+            .filter(|region| !(exclude_lost && lost_regions.contains(*region)))
+            .collect()
+    }
+
+    fn counted_peers(&self) -> Vec<PeerId> {
+        self.counted_regions()
+            .into_iter()
+            .flat_map(|region| self.members[region].iter().cloned())
+            .collect()
+    }
+
+    /// Whether the configured quorum depends on votes from a lost region.
+    fn requires_lost_region(&self) -> bool {
+        let lost_regions = self.lost_regions.read().unwrap();
+        match self.config.write_quorum {
+            WriteQuorum::LocalRegion => false,
+            WriteQuorum::Global | WriteQuorum::EachRegion => !lost_regions.is_empty(),
+        }
+    }
+}
+
+/// 2/3 + 1 of `n` peers, matching the engine's cluster-wide threshold.
+fn majority(n: usize) -> usize {
+    (n * 2) / 3 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(write_quorum: WriteQuorum, failover_policy: RegionFailoverPolicy) -> TopologyConfig {
+        TopologyConfig {
+            local_region: "us-east".to_string(),
+            regions: vec![
+                RegionConfig {
+                    region_id: "us-east".to_string(),
+                    peers: vec!["e1".to_string(), "e2".to_string()],
+                    replica_count: 3,
+                },
+                RegionConfig {
+                    region_id: "eu-west".to_string(),
+                    peers: vec!["w1".to_string(), "w2".to_string(), "w3".to_string()],
+                    replica_count: 2,
+                },
+            ],
+            write_quorum,
+            failover_policy,
+            region_loss_timeout: Duration::from_millis(20),
+        }
+    }
+
+    fn peers(ids: &[&str]) -> Vec<PeerId> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_local_and_each_region_quorums() {
+        let local = RegionTopology::new(
+            config(WriteQuorum::LocalRegion, RegionFailoverPolicy::HaltWrites),
+            "local".to_string(),
+        ).unwrap();
+        assert_eq!(local.region_of(&"local".to_string()), Some("us-east"));
+        assert!(!local.quorum_reached(&peers(&["local", "e1"])));
+        assert!(local.quorum_reached(&peers(&["local", "e1", "e2"])));
+
+        let each = RegionTopology::new(
+            config(WriteQuorum::EachRegion, RegionFailoverPolicy::HaltWrites),
+            "local".to_string(),
+        ).unwrap();
+        assert!(!each.quorum_reached(&peers(&["local", "e1", "e2"])));
+        assert!(each.quorum_reached(&peers(&["local", "e1", "e2", "w1", "w2", "w3"])));
+        assert_eq!(each.required_votes(), 6);
+    }
+
+    #[test]
+    fn test_invalid_topology_rejected() {
+        let mut bad = config(WriteQuorum::Global, RegionFailoverPolicy::HaltWrites);
+        bad.regions[1].replica_count = 4;
+        assert!(RegionTopology::new(bad, "local".to_string()).is_err());
+
+        let mut bad = config(WriteQuorum::Global, RegionFailoverPolicy::HaltWrites);
+        bad.local_region = "ap-south".to_string();
+        assert!(RegionTopology::new(bad, "local".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_region_loss_and_failover_policies() {
+        let halt = RegionTopology::new(
+            config(WriteQuorum::Global, RegionFailoverPolicy::HaltWrites),
+            "local".to_string(),
+        ).unwrap();
+        let exclude = RegionTopology::new(
+            config(WriteQuorum::Global, RegionFailoverPolicy::ExcludeLostRegion),
+            "local".to_string(),
+        ).unwrap();
+        let downgrade = RegionTopology::new(
+            config(WriteQuorum::EachRegion, RegionFailoverPolicy::DowngradeToLocalQuorum),
+            "local".to_string(),
+        ).unwrap();
+
+        std::thread::sleep(Duration::from_millis(30));
+        for topology in [&halt, &exclude, &downgrade] {
+            assert_eq!(topology.evaluate(), vec![RegionEvent::Lost("eu-west".to_string())]);
+        }
+
+        assert!(halt.check_writes_allowed().is_err());
+
+        assert!(exclude.check_writes_allowed().is_ok());
+        assert!(exclude.quorum_reached(&peers(&["local", "e1", "e2"])));
+        assert!(!exclude.replica_placement().contains_key("eu-west"));
+
+        assert_eq!(downgrade.effective_quorum(), WriteQuorum::LocalRegion);
+        assert!(downgrade.quorum_reached(&peers(&["local", "e1", "e2"])));
+
+        halt.record_heartbeat(&"w2".to_string());
+        assert_eq!(halt.evaluate(), vec![RegionEvent::Recovered("eu-west".to_string())]);
+        assert!(halt.check_writes_allowed().is_ok());
+    }
+}
@@ -9,7 +9,15 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use aerolithdb_storage::ReplicatedWrite;
+
+use crate::compaction::{SnapshotConfig, SnapshotMessage};
 use crate::conflict_resolution::ConflictResolution;
+use crate::feature_flags::FeatureFlagChange;
+use crate::leases::LeaseRequest;
+use crate::raft::{RaftConfig, RaftMessage};
+use crate::threshold_signatures::AggregatedCertificate;
+use crate::topology::TopologyConfig;
 
 /// Configuration for the consensus engine behavior and algorithms.
 ///
@@ -29,12 +37,54 @@ pub struct ConsensusConfig {
     /// Should account for network latency and processing time
     pub timeout: std::time::Duration,
     
+    /// Whether round and election timeouts adapt to observed peer RTTs
+    /// When disabled, `timeout` is used unchanged
+    pub adaptive_timeouts: bool,
+    
+    /// Lower bound for adaptively derived timeouts
+    pub min_timeout: std::time::Duration,
+    
+    /// Upper bound for adaptively derived timeouts
+    pub max_timeout: std::time::Duration,
+    
     /// Maximum number of operations to batch in a single consensus round
     /// Higher values improve throughput but increase latency
     pub max_batch_size: usize,
     
+    /// Maximum time an operation waits for other operations to join its batch
+    /// Small windows keep latency low while still grouping bursts of writes
+    pub batch_window: std::time::Duration,
+    
+    /// Maximum number of batches that may be awaiting consensus concurrently
+    /// Higher values overlap more rounds at the cost of memory and ordering slack
+    pub pipeline_depth: usize,
+    
+    /// Aggregate vote signatures with BLS threshold signatures
+    /// Replaces per-vote verification with one aggregate check per commit
+    pub threshold_signatures: bool,
+    
+    /// Minimum cluster size at which threshold signatures are used
+    /// Smaller clusters keep individual signatures, which are cheaper at low n
+    pub threshold_signature_min_peers: usize,
+    
+    /// Region layout, write quorum, and region failover policy
+    /// When unset the cluster is treated as a single region with a global quorum
+    pub topology: Option<TopologyConfig>,
+    
+    /// Election, heartbeat, compaction, and membership settings
+    /// Only used when `algorithm` is `ConsensusAlgorithm::Raft`
+    pub raft: RaftConfig,
+    
+    /// When the committed log is compacted into a snapshot
+    /// Only used by the vote-based algorithms; Raft compacts per `raft`
+    pub snapshots: SnapshotConfig,
+    
     /// Strategy for resolving conflicts between concurrent operations
     pub conflict_resolution: ConflictResolution,
+    
+    /// Strategies of collections resolving conflicts differently, such as
+    /// `ConflictResolution::Crdt` for collections written in several regions
+    pub collection_conflict_resolution: HashMap<String, ConflictResolution>,
 }
 
 impl Default for ConsensusConfig {
@@ -44,8 +94,19 @@ impl Default for ConsensusConfig {
             algorithm: ConsensusAlgorithm::ByzantinePBFT,
             byzantine_tolerance: 0.33,
             timeout: Duration::from_secs(30),
+            adaptive_timeouts: true,
+            min_timeout: Duration::from_millis(200),
+            max_timeout: Duration::from_secs(60),
             max_batch_size: 100,
+            batch_window: Duration::from_millis(5),
+            pipeline_depth: 4,
+            threshold_signatures: false,
+            threshold_signature_min_peers: 16,
+            topology: None,
+            raft: RaftConfig::default(),
+            snapshots: SnapshotConfig::default(),
             conflict_resolution: ConflictResolution::LastWriterWins,
+            collection_conflict_resolution: HashMap::new(),
         }
     }
 }
@@ -122,7 +183,7 @@ pub struct Proposal {
 /// These represent all possible state changes that can be made
 /// to the distributed database. Each operation is atomic and
 /// either succeeds completely or fails without side effects.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Operation {
     /// Insert a new document into a collection
     Insert {
@@ -169,6 +230,39 @@ pub enum Operation {
         /// Name of the collection to remove
         name: String,
     },
+    
+    /// Several operations proposed and committed together in one round
+    Batch {
+        /// Operations applied in order when the batch commits
+        operations: Vec<Operation>,
+    },
+    
+    /// Acquire, renew, or release a named lease
+    Lease {
+        /// The lease request, applied against the lease state at commit time
+        request: LeaseRequest,
+    },
+    
+    /// Set or clear a cluster-wide feature flag
+    FeatureFlag {
+        /// The change, applied to every node's flag registry at commit time
+        change: FeatureFlagChange,
+    },
+    
+    /// Store or delete a document of a strongly consistent collection
+    Write {
+        /// Identifies the caller waiting for the write's outcome
+        request_id: Uuid,
+        /// The write, applied to every node's storage at commit time
+        write: ReplicatedWrite,
+    },
+    
+    /// Change nothing; a linearizable read waits for it to be applied so it
+    /// sees every write committed before it
+    ReadBarrier {
+        /// Identifies the read waiting for the barrier
+        request_id: Uuid,
+    },
 }
 
 /// Vote on a proposal from a participating node.
@@ -249,6 +343,10 @@ pub enum ConsensusMessage {
     Heartbeat(HeartbeatMessage),
     /// Request to change view/leader in the consensus protocol
     ViewChange(ViewChangeMessage),
+    /// Snapshot of the committed state for a peer that is behind it
+    InstallSnapshot(SnapshotMessage),
+    /// Raft RPC, used when the engine runs `ConsensusAlgorithm::Raft`
+    Raft(RaftMessage),
 }
 
 /// Message indicating a proposal has been committed.
@@ -260,6 +358,8 @@ pub struct CommitMessage {
     pub round: u64,
     /// When the commit occurred
     pub committed_at: DateTime<Utc>,
+    /// Aggregated quorum certificate when threshold signatures are in use
+    pub certificate: Option<AggregatedCertificate>,
 }
 
 /// Message indicating a proposal has been aborted.
@@ -30,10 +30,16 @@ use std::path::PathBuf;                       // Cross-platform file path handli
 use std::time::Duration;                      // Time duration for timeouts and intervals
 
 // Import from security module to avoid duplication
-use aerolithdb_security::{EncryptionAlgorithm, AuditLevel, ComplianceMode, SecurityConfig};
+use aerolithdb_security::{AdmissionConfig, CaConfig, EncryptionAlgorithm, AuditLevel, ComplianceMode, RedactionConfig, SecurityConfig};
 
 // Import from consensus module
-use aerolithdb_consensus::ConsensusAlgorithm;
+use aerolithdb_consensus::{ConsensusAlgorithm, SnapshotConfig};
+
+// Import from query module
+use aerolithdb_query::ProfilingConfig;
+
+use crate::effective::EffectiveConfig;
+use crate::profile::{redact_config_value, ConfigProfile};
 
 /// Main configuration structure for the entire aerolithsDB system.
 /// 
@@ -90,6 +96,14 @@ pub struct AerolithsConfig {
     
     /// Monitoring, metrics, and observability settings
     pub observability: ObservabilityConfig,
+    
+    /// Environment checks run before the node starts serving
+    #[serde(default)]
+    pub preflight: PreflightConfig,
+    
+    /// Profile preset this configuration was built from (None = plain defaults)
+    #[serde(default)]
+    pub profile: Option<ConfigProfile>,
 }
 
 /// Node-specific configuration including identity and network binding.
@@ -118,6 +132,16 @@ pub struct NodeConfig {
     
     /// External address for NAT traversal and peer discovery (auto-detected if None)
     pub external_address: Option<String>,
+    
+    /// Directory of YAML/NDJSON fixtures seeded into the database at startup (disabled if None)
+    #[serde(default)]
+    pub fixtures_dir: Option<PathBuf>,
+
+    /// Run as a standalone node: the consensus engine and network manager are
+    /// never created, so no peer connections, heartbeats or consensus rounds run
+    /// and writes are only as durable as the write-ahead log (see [`FsyncPolicy`])
+    #[serde(default)]
+    pub local_only: bool,
 }
 
 /// Network cluster configuration for P2P communication and discovery.
@@ -192,6 +216,28 @@ pub struct StorageConfig {
     
     /// Maximum total storage size before triggering archival (None = unlimited)
     pub max_storage_size: Option<u64>,
+
+    /// When write-ahead log appends reach the disk
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+}
+
+impl StorageConfig {
+    /// The guarantee an acknowledged write carries under the fsync policy,
+    /// for a node without replicas to fall back on.
+    pub fn durability_contract(&self) -> &'static str {
+        match self.fsync {
+            FsyncPolicy::Always => {
+                "writes are acknowledged after their write-ahead log record is synced to disk \
+                 and survive both process and operating system crashes"
+            }
+            FsyncPolicy::Never => {
+                "writes are acknowledged once their write-ahead log record reaches the operating \
+                 system and survive process crashes, but the most recent ones can be lost if \
+                 the operating system crashes or power fails"
+            }
+        }
+    }
 }
 
 /// Intelligent caching system configuration with ML-driven optimization.
@@ -273,6 +319,14 @@ pub struct ConsensusConfig {
     
     /// Strategy for resolving conflicting concurrent updates
     pub conflict_resolution: ConflictResolution,
+
+    /// Strategies of collections resolving conflicts differently, by name
+    #[serde(default)]
+    pub collection_conflict_resolution: std::collections::HashMap<String, ConflictResolution>,
+
+    /// Entry count, size and interval at which the consensus log is compacted
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
 }
 
 /// Query processing and optimization engine configuration.
@@ -300,6 +354,10 @@ pub struct QueryConfig {
     
     /// Enable automatic index recommenaerolithons for performance optimization
     pub index_advisor: bool,
+
+    /// Per-collection query statistics and the slow-query log threshold
+    #[serde(default)]
+    pub profiling: ProfilingConfig,
 }
 
 /// Multi-protocol API gateway configuration.
@@ -398,6 +456,38 @@ pub struct ObservabilityConfig {
     pub alerting: AlertingConfig,
 }
 
+/// Pre-flight environment check configuration.
+/// 
+/// Controls the checks run at startup before any subsystem serves traffic.
+/// All failures are reported together with remediation hints; see the
+/// `preflight` module for the individual checks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightConfig {
+    /// Run pre-flight checks at startup (disable only for constrained test environments)
+    pub enabled: bool,
+    
+    /// Minimum soft limit on open file descriptors
+    pub min_open_files: u64,
+    
+    /// Minimum free space required on each data directory's filesystem
+    pub min_free_disk_bytes: u64,
+    
+    /// Verify every bootstrap node accepts a TCP connection (off by default,
+    /// since seed nodes may legitimately start after this one)
+    pub check_bootstrap_nodes: bool,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_open_files: 4096,
+            min_free_disk_bytes: 1024 * 1024 * 1024, // 1GB
+            check_bootstrap_nodes: false,
+        }
+    }
+}
+
 // ================================================================================================
 // ENUMERATION TYPES FOR CONFIGURATION OPTIONS
 // ================================================================================================
@@ -420,6 +510,22 @@ pub enum PluginType {
     Network,
 }
 
+/// When the write-ahead log is synced to disk.
+///
+/// Every store, update and delete is appended to the write-ahead log before
+/// it is acknowledged and replayed on startup. The policy decides whether
+/// the append is also synced, trading write latency for surviving an
+/// operating system crash; a process crash loses nothing either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// Sync every append before acknowledging the write
+    #[default]
+    Always,
+    /// Leave flushing to the operating system
+    Never,
+}
+
 /// Sharding strategies for data distribution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ShardingStrategy {
@@ -477,6 +583,30 @@ pub enum ConflictResolution {
     
     /// Manual intervention required for conflict resolution
     RequireManualIntervention,
+
+    /// CRDT merge of JSON documents: per-field last-writer-wins registers
+    /// and observed-remove sets for arrays, so concurrent edits from
+    /// partitioned datacenters merge deterministically without data loss
+    Crdt,
+}
+
+impl ConflictResolution {
+    /// The consensus engine's equivalent strategy. Semantic merges merge
+    /// field by field, and user-defined resolution uses the custom resolver
+    /// registered as `user_defined`.
+    pub fn to_consensus(&self) -> aerolithdb_consensus::ConflictResolution {
+        match self {
+            Self::LastWriterWins => aerolithdb_consensus::ConflictResolution::LastWriterWins,
+            Self::SemanticMerge => aerolithdb_consensus::ConflictResolution::SemanticMerge(
+                aerolithdb_consensus::conflict_resolution::MergeStrategyType::FieldLevel,
+            ),
+            Self::UserDefinedResolver => {
+                aerolithdb_consensus::ConflictResolution::UserDefinedResolver("user_defined".to_string())
+            }
+            Self::RequireManualIntervention => aerolithdb_consensus::ConflictResolution::RequireManualIntervention,
+            Self::Crdt => aerolithdb_consensus::ConflictResolution::Crdt,
+        }
+    }
 }
 
 // ================================================================================================
@@ -723,7 +853,7 @@ impl AerolithsConfig {
     /// 
     /// # Environment Variable Overrides
     /// Any configuration value can be overridden using environment variables
-    /// with the format: `aerolithSDB_<SECTION>_<FIELD>` (e.g., `aerolithSDB_NODE_PORT=9001`)
+    /// with the format: `AEROLITHDB_<SECTION>_<FIELD>` (e.g., `AEROLITHDB_NODE_PORT=9001`)
     /// 
     /// # Returns
     /// - `Ok(aerolithsConfig)` with loaded and validated configuration
@@ -736,19 +866,32 @@ impl AerolithsConfig {
     /// println!("Loaded configuration for node: {}", config.node.node_id);
     /// ```
     pub async fn load() -> Result<Self> {
-        // Try to load from config file, fallback to default
-        match tokio::fs::read_to_string("config.json").await {
-            Ok(content) => {
-                serde_json::from_str(&content)
-                    .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e))
-            }
-            Err(_) => {
-                // Create default config
-                let default_config = Self::default();
-                default_config.save().await?;
-                Ok(default_config)
-            }
-        }
+        Self::load_with_profile(ConfigProfile::resolve(None)?).await
+    }
+
+    /// Load configuration starting from a named profile preset.
+    /// 
+    /// config.json is treated as a set of explicit overrides: only the fields
+    /// it contains replace the preset's values, so with a profile it should
+    /// hold just the settings that differ from the profile. Without a profile
+    /// or a config.json, a default config.json is written for future use.
+    /// Environment variable overrides are applied on top; see [`EffectiveConfig`]
+    /// for the full precedence order and per-field provenance.
+    /// 
+    /// # Example
+    /// ```rust
+    /// let config = aerolithsConfig::load_with_profile(Some(ConfigProfile::Dev)).await?;
+    /// assert_eq!(config.storage.replication_factor, 1);
+    /// ```
+    pub async fn load_with_profile(profile: Option<ConfigProfile>) -> Result<Self> {
+        Ok(EffectiveConfig::load(profile, Vec::new()).await?.config)
+    }
+
+    /// Serialize the configuration with secrets masked, safe for logging.
+    pub fn redacted(&self) -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self)?;
+        redact_config_value(&mut value);
+        Ok(value)
     }
 
     /// Save configuration to file for persistence and sharing.
@@ -837,6 +980,12 @@ impl Default for AerolithsConfig {
                 
                 // External address auto-detected for NAT traversal
                 external_address: None,
+                
+                // No seed data unless a fixtures directory is configured
+                fixtures_dir: None,
+
+                // Take part in a cluster; the local profile turns this on
+                local_only: false,
             },
             
             // Network configuration for cluster communication
@@ -884,6 +1033,9 @@ impl Default for AerolithsConfig {
                 
                 // No storage size limit (unlimited growth)
                 max_storage_size: None,
+
+                // Sync the write-ahead log before acknowledging each write
+                fsync: FsyncPolicy::Always,
             },
             
             // Intelligent multi-tier cache configuration
@@ -920,6 +1072,21 @@ impl Default for AerolithsConfig {
                 
                 // XChaCha20-Poly1305 for high-performance authenticated encryption
                 encryption_algorithm: EncryptionAlgorithm::XChaCha20Poly1305,
+                
+                // Ciphers an `Auto` algorithm would choose between
+                auto_encryption_candidates: vec![EncryptionAlgorithm::AES256GCM, EncryptionAlgorithm::ChaCha20Poly1305],
+                
+                // Master key for encrypting documents at rest, generated on first start
+                master_key_file: Some(PathBuf::from("./data/keys/master.key")),
+                
+                // Built-in secret and PII patterns scrubbed from logs and errors
+                redaction: RedactionConfig::default(),
+                
+                // Not a cluster certificate authority unless enabled
+                ca: CaConfig::default(),
+                
+                // Every node that is not denied may join without approval
+                admission: AdmissionConfig::default(),
             },
             
             // Byzantine fault-tolerant consensus configuration
@@ -938,6 +1105,10 @@ impl Default for AerolithsConfig {
                 
                 // Last writer wins for conflict resolution
                 conflict_resolution: ConflictResolution::LastWriterWins,
+                collection_conflict_resolution: std::collections::HashMap::new(),
+
+                // Compact every 1000 entries, 16 MiB or 10 minutes
+                snapshots: SnapshotConfig::default(),
             },
             
             // Query engine configuration with optimization
@@ -957,6 +1128,9 @@ impl Default for AerolithsConfig {
                 
                 // Enable automatic index recommenaerolithons
                 index_advisor: true,
+
+                // Log queries taking 100ms or more as slow
+                profiling: ProfilingConfig::default(),
             },
             
             // Multi-protocol API gateway configuration
@@ -1037,6 +1211,12 @@ impl Default for AerolithsConfig {
                     thresholds: std::collections::HashMap::new(), // No thresholds configured
                 },
             },
+            
+            // Pre-flight checks with production-minded thresholds
+            preflight: PreflightConfig::default(),
+            
+            // Plain defaults, not derived from a profile
+            profile: None,
         }
     }
 }
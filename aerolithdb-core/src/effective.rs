@@ -0,0 +1,317 @@
+// aerolithsDB Effective Configuration and Provenance
+//
+// Resolves the configuration a node actually runs with by layering every source
+// in precedence order, and records which source supplied each field:
+// 1. Built-in defaults (lowest precedence)
+// 2. The selected profile preset
+// 3. config.json
+// 4. `AEROLITHDB_<SECTION>_<FIELD>` environment variables
+// 5. Explicit overrides such as `--set node.port=9001` (highest precedence)
+//
+// The resolved configuration can be exported with secrets redacted and a
+// dotted-path → source map, which backs the admin config endpoint.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::config::AerolithsConfig;
+use crate::profile::{merge_config_values, ConfigProfile, PROFILE_ENV_VAR};
+
+/// Prefix of environment variables that override configuration fields
+pub const ENV_OVERRIDE_PREFIX: &str = "AEROLITHDB_";
+
+/// Where a configuration field's effective value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    /// Built-in default value
+    Default,
+    /// Preset of the selected profile
+    Profile,
+    /// config.json
+    File,
+    /// `AEROLITHDB_*` environment variable
+    Env,
+    /// Explicit override, e.g. from the command line
+    Override,
+}
+
+/// Raw inputs to configuration resolution, in precedence order.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLayers {
+    /// Baseline configuration (built-in defaults)
+    pub defaults: AerolithsConfig,
+
+    /// Profile preset applied on top of the defaults
+    pub profile: Option<ConfigProfile>,
+
+    /// Parsed contents of config.json, merged field by field
+    pub file: Option<Value>,
+
+    /// Environment variables; only `AEROLITHDB_*` entries are considered
+    pub env: Vec<(String, String)>,
+
+    /// Explicit `path=value` overrides using dotted paths (e.g. `node.port`)
+    pub overrides: Vec<(String, String)>,
+}
+
+/// Configuration resolved from all sources, with per-field provenance.
+#[derive(Debug, Clone)]
+pub struct EffectiveConfig {
+    /// The resolved configuration
+    pub config: AerolithsConfig,
+
+    /// Source of every leaf field, keyed by dotted path
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+impl EffectiveConfig {
+    /// Load the effective configuration from config.json, the process
+    /// environment, and the given profile and overrides.
+    ///
+    /// As with plain loading, a default config.json is written when neither a
+    /// file nor a profile is present.
+    pub async fn load(profile: Option<ConfigProfile>, overrides: Vec<(String, String)>) -> Result<Self> {
+        let mut layers = ConfigLayers {
+            profile,
+            env: std::env::vars().collect(),
+            overrides,
+            ..Default::default()
+        };
+
+        match tokio::fs::read_to_string("config.json").await {
+            Ok(content) => {
+                layers.file = Some(serde_json::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e))?);
+            }
+            Err(_) if profile.is_none() => layers.defaults.save().await?,
+            Err(_) => {}
+        }
+
+        Self::resolve(layers)
+    }
+
+    /// Resolve configuration layers into an effective configuration.
+    pub fn resolve(layers: ConfigLayers) -> Result<Self> {
+        let defaults = serde_json::to_value(&layers.defaults)?;
+        let mut sources = BTreeMap::new();
+        for (path, _) in leaves(&defaults) {
+            sources.insert(path, ConfigSource::Default);
+        }
+
+        let mut merged = defaults.clone();
+        if let Some(profile) = layers.profile {
+            let mut preset = layers.defaults.clone();
+            profile.apply(&mut preset);
+            merged = serde_json::to_value(&preset)?;
+            for (path, value) in leaves(&merged) {
+                if lookup(&defaults, &path) != Some(value) {
+                    sources.insert(path, ConfigSource::Profile);
+                }
+            }
+        }
+
+        if let Some(file) = layers.file {
+            for (path, _) in leaves(&file) {
+                sources.insert(path, ConfigSource::File);
+            }
+            merge_config_values(&mut merged, file);
+        }
+
+        for (name, raw) in &layers.env {
+            let Some(rest) = name.strip_prefix(ENV_OVERRIDE_PREFIX) else { continue };
+            if name == PROFILE_ENV_VAR {
+                continue;
+            }
+            let segments: Vec<String> = rest.to_ascii_lowercase().split('_').map(str::to_string).collect();
+            match match_env_path(&merged, &segments) {
+                Some(path) => apply_override(&mut merged, &mut sources, &path, raw, ConfigSource::Env)?,
+                None => tracing::debug!("Ignoring {} - no matching configuration field", name),
+            }
+        }
+
+        for (path, raw) in &layers.overrides {
+            let path: Vec<String> = path.split('.').map(str::to_string).collect();
+            if lookup(&merged, &path.join(".")).is_none() {
+                return Err(anyhow::anyhow!("Unknown configuration field '{}'", path.join(".")));
+            }
+            apply_override(&mut merged, &mut sources, &path, raw, ConfigSource::Override)?;
+        }
+
+        let mut config: AerolithsConfig = serde_json::from_value(merged)
+            .map_err(|e| anyhow::anyhow!("Invalid effective configuration: {}", e))?;
+        if layers.profile.is_some() {
+            config.profile = layers.profile;
+        }
+
+        // Report provenance for exactly the fields the final configuration has
+        let resolved = serde_json::to_value(&config)?;
+        let sources = leaves(&resolved)
+            .into_iter()
+            .map(|(path, _)| {
+                let source = sources.get(&path).copied().unwrap_or(ConfigSource::Default);
+                (path, source)
+            })
+            .collect();
+
+        Ok(Self { config, sources })
+    }
+
+    /// Export the configuration for display: secrets redacted, with the
+    /// source of every field.
+    pub fn export(&self) -> Result<Value> {
+        Ok(serde_json::json!({
+            "profile": self.config.profile,
+            "config": self.config.redacted()?,
+            "sources": self.sources,
+        }))
+    }
+}
+
+/// Parse a `path=value` override as given on the command line.
+pub fn parse_override(spec: &str) -> Result<(String, String)> {
+    match spec.split_once('=') {
+        Some((path, value)) if !path.trim().is_empty() => Ok((path.trim().to_string(), value.to_string())),
+        _ => Err(anyhow::anyhow!("Invalid override '{}' (expected path=value)", spec)),
+    }
+}
+
+/// Collect every leaf of a JSON tree with its dotted path. Arrays and empty
+/// objects count as leaves.
+fn leaves(value: &Value) -> Vec<(String, &Value)> {
+    fn walk<'a>(value: &'a Value, prefix: &str, out: &mut Vec<(String, &'a Value)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, child) in map {
+                    let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    walk(child, &path, out);
+                }
+            }
+            _ => out.push((prefix.to_string(), value)),
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(value, "", &mut out);
+    out
+}
+
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |node, key| node.get(key))
+}
+
+/// Map `SECTION_FIELD_NAME` segments onto an existing path. Field names may
+/// themselves contain underscores, so segments are joined greedily against the
+/// keys actually present at each level.
+fn match_env_path(value: &Value, segments: &[String]) -> Option<Vec<String>> {
+    let map = value.as_object()?;
+    for end in 1..=segments.len() {
+        let key = segments[..end].join("_");
+        let Some(child) = map.get(&key) else { continue };
+        if end == segments.len() {
+            return Some(vec![key]);
+        }
+        if let Some(mut rest) = match_env_path(child, &segments[end..]) {
+            rest.insert(0, key);
+            return Some(rest);
+        }
+    }
+    None
+}
+
+/// Set a raw override at `path` and attribute the affected leaves to `source`.
+fn apply_override(
+    root: &mut Value,
+    sources: &mut BTreeMap<String, ConfigSource>,
+    path: &[String],
+    raw: &str,
+    source: ConfigSource,
+) -> Result<()> {
+    let dotted = path.join(".");
+    let target = path
+        .iter()
+        .try_fold(&mut *root, |node, key| node.get_mut(key))
+        .ok_or_else(|| anyhow::anyhow!("Unknown configuration field '{}'", dotted))?;
+
+    // String fields take the raw text; everything else is parsed as JSON so
+    // numbers, booleans, and structured values can be overridden too
+    let value = if target.is_string() {
+        Value::String(raw.to_string())
+    } else {
+        serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+    };
+
+    for (leaf, _) in leaves(&value) {
+        let full = if leaf.is_empty() { dotted.clone() } else { format!("{}.{}", dotted, leaf) };
+        sources.insert(full, source);
+    }
+    *target = value;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn env(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_provenance_by_layer() {
+        let effective = EffectiveConfig::resolve(ConfigLayers {
+            profile: Some(ConfigProfile::Dev),
+            file: Some(json!({"node": {"port": 9100}})),
+            env: vec![
+                env("AEROLITHDB_API_REST_API_PORT", "8181"),
+                env("AEROLITHDB_NODE_BIND_ADDRESS", "10.0.0.5"),
+                env("AEROLITHDB_UNRELATED", "x"),
+                env("HOME", "/root"),
+            ],
+            overrides: vec![env("observability.logging.level", "trace")],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(effective.config.node.port, 9100);
+        assert_eq!(effective.config.api.rest_api.port, 8181);
+        assert_eq!(effective.config.node.bind_address, "10.0.0.5");
+        assert_eq!(effective.config.observability.logging.level, "trace");
+
+        let source = |path: &str| effective.sources[path];
+        assert_eq!(source("node.port"), ConfigSource::File);
+        assert_eq!(source("api.rest_api.port"), ConfigSource::Env);
+        assert_eq!(source("node.bind_address"), ConfigSource::Env);
+        assert_eq!(source("observability.logging.level"), ConfigSource::Override);
+        assert_eq!(source("storage.replication_factor"), ConfigSource::Profile);
+        assert_eq!(source("query.max_concurrent_queries"), ConfigSource::Default);
+    }
+
+    #[test]
+    fn test_unknown_override_is_rejected() {
+        let result = EffectiveConfig::resolve(ConfigLayers {
+            overrides: vec![env("node.no_such_field", "1")],
+            ..Default::default()
+        });
+        assert!(result.is_err());
+        assert!(parse_override("node.port").is_err());
+        assert_eq!(parse_override("node.port=1").unwrap(), env("node.port", "1"));
+    }
+
+    #[test]
+    fn test_export_is_redacted() {
+        let effective = EffectiveConfig::resolve(ConfigLayers {
+            env: vec![env("AEROLITHDB_OBSERVABILITY_ALERTING_WEBHOOK_URL", "https://hooks.example.com/secret")],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let export = effective.export().unwrap();
+        assert_eq!(export["config"]["observability"]["alerting"]["webhook_url"], "***");
+        assert_eq!(export["sources"]["observability.alerting.webhook_url"], "env");
+        assert_eq!(export["sources"]["node.port"], "default");
+    }
+}
@@ -31,6 +31,12 @@
 // 8. Launch API gateway with multi-protocol support
 // 9. Load and activate plugin extensions
 //
+// ### Local-only Mode
+// With `node.local_only` (the `local` profile), steps 5 and 6 are skipped: no
+// consensus engine or network manager is created, so no peer listener,
+// heartbeats or consensus rounds run. Writes then rely on the write-ahead log
+// alone, synced according to `storage.fsync`.
+//
 // ### Lifecycle Management
 // The `aerolithsDB` struct provides comprehensive lifecycle management including:
 // - Graceful startup with dependency resolution
@@ -42,7 +48,7 @@
 use anyhow::Result;                    // Unified error handling with context preservation
 use std::sync::Arc;                    // Thread-safe reference counting for shared state
 use tokio::sync::RwLock;              // Async read-write lock for concurrent access
-use tracing::{info, debug};           // Structured logging for operational observability
+use tracing::{info, debug, warn};     // Structured logging for operational observability
 
 // Import all aerolithsDB subsystem modules for orchestration
 use aerolithdb_consensus::ConsensusEngine;     // Distributed consensus and conflict resolution
@@ -56,12 +62,18 @@ use aerolithdb_query::QueryEngine;             // Distributed query processing a
 
 // Internal core modules for configuration, node management, and type definitions
 mod config;    // Configuration management with environment and file-based loading
+mod effective; // Layered configuration resolution with per-field provenance
 mod node;      // Node identity, metadata, and cluster membership management
+mod preflight; // Environment checks run before the node starts serving
+mod profile;   // Named configuration presets and secret redaction
 mod types;     // Common type definitions and data structures used across modules
 
 // Re-export public interfaces from internal modules for external use
 pub use config::*;  // Configuration structures, loading, and validation functions
+pub use effective::*; // Effective configuration, provenance, and redacted export
 pub use node::*;    // Node identity structures and cluster membership types
+pub use preflight::*; // Pre-flight check results and reports
+pub use profile::*; // Configuration profiles, merging, and redaction helpers
 pub use types::*;   // Common type definitions for external API compatibility
 
 /// Main aerolithsDB instance that orchestrates all subsystems.
@@ -84,14 +96,14 @@ pub struct AerolithsDB {
     /// Local node identity and metadata
     node: Arc<RwLock<Node>>,
     
-    /// Consensus engine for distributed agreement and conflict resolution
-    consensus: Arc<ConsensusEngine>,
+    /// Consensus engine for distributed agreement and conflict resolution (None when local-only)
+    consensus: Option<Arc<ConsensusEngine>>,
     
     /// Multi-tier storage hierarchy with automatic data tiering
     storage: Arc<StorageHierarchy>,
     
-    /// P2P network manager for node communication and discovery
-    network: Arc<NetworkManager>,
+    /// P2P network manager for node communication and discovery (None when local-only)
+    network: Option<Arc<NetworkManager>>,
     
     /// Intelligent cache system with predictive algorithms
     cache: Arc<IntelligentCacheSystem>,
@@ -142,27 +154,49 @@ impl AerolithsDB {
         
         // Initialize security framework first (required by other components)
         // This sets up encryption, authentication, and zero-trust policies
-        let security = Arc::new(SecurityFramework::new(&config.read().await.security).await?);        // Initialize storage hierarchy with default configuration
-        let storage = Arc::new(StorageHierarchy::new(&aerolithdb_storage::StorageConfig::default()).await?);
+        let security = Arc::new(SecurityFramework::new(&config.read().await.security).await?);        // Initialize storage hierarchy with the configured fsync policy
+        let storage = Arc::new(StorageHierarchy::new(&storage_config(&config.read().await.storage)).await?.with_encryption(&security));
 
-        // Initialize intelligent cache system with default configuration
-        let cache = Arc::new(IntelligentCacheSystem::new(&aerolithdb_cache::CacheConfig::default()).await?);
+        // Initialize intelligent cache system, sharded across the cluster
+        // unless the node runs local-only
+        let cache = Arc::new(IntelligentCacheSystem::new(&cache_config(&config.read().await.node, &storage)).await?);
 
-        // Initialize consensus engine with default configuration
-        let consensus = Arc::new(ConsensusEngine::new(
-            &aerolithdb_consensus::ConsensusConfig::default(),
-            Arc::clone(&security),
-            Arc::clone(&storage),
-        ).await?);        // Create a network node for the current instance
-        let network_node = Arc::new(tokio::sync::RwLock::new(aerolithdb_network::Node));        // Initialize network manager with default configuration
-        let network = Arc::new(NetworkManager::new(
-            &aerolithdb_network::NetworkConfig::default(),
-            network_node,
-            Arc::clone(&security),
-            Arc::clone(&consensus),
-        ).await?);        // Initialize query engine with default configuration
+        // Initialize consensus and networking unless the node runs local-only,
+        // in which case neither exists and none of their background tasks run
+        let (consensus, network) = if config.read().await.node.local_only {
+            (None, None)
+        } else {
+            // Initialize consensus engine with the configured algorithm
+            let consensus = Arc::new(ConsensusEngine::new(
+                &aerolithdb_consensus::ConsensusConfig {
+                    algorithm: config.read().await.consensus.algorithm.clone(),
+                    snapshots: config.read().await.consensus.snapshots.clone(),
+                    conflict_resolution: config.read().await.consensus.conflict_resolution.to_consensus(),
+                    collection_conflict_resolution: config.read().await.consensus.collection_conflict_resolution
+                        .iter()
+                        .map(|(collection, strategy)| (collection.clone(), strategy.to_consensus()))
+                        .collect(),
+                    ..Default::default()
+                },
+                Arc::clone(&security),
+                Arc::clone(&storage),
+            ).await?);
+
+            // Strongly consistent collections are written and read through consensus
+            consensus.route_consistent_collections();
+
+            // Initialize network manager with default configuration
+            let network_node = Arc::new(tokio::sync::RwLock::new(aerolithdb_network::Node));
+            let network = Arc::new(NetworkManager::new(
+                &aerolithdb_network::NetworkConfig::default(),
+                network_node,
+                Arc::clone(&security),
+                Arc::clone(&consensus),
+            ).await?);
+            (Some(consensus), Some(network))
+        };        // Initialize query engine with default configuration
         let query = Arc::new(QueryEngine::new(
-            aerolithdb_query::QueryConfig::default(),
+            aerolithdb_query::QueryConfig { profiling: config.read().await.query.profiling.clone(), ..Default::default() },
             Arc::clone(&storage),
             Arc::clone(&cache),
             Arc::clone(&security),        ).await?);
@@ -172,6 +206,7 @@ impl AerolithsDB {
         //     &aerolithdb_api::APIConfig::default(),
         //     Arc::clone(&query),
         //     Arc::clone(&security),
+        //     Arc::clone(&consensus),
         // ).await?);
 
         // Initialize plugin manager with default configuration - temporarily disabled
@@ -218,27 +253,49 @@ impl AerolithsDB {
         
         // Initialize node identity with the provided configuration
         let node = Arc::new(RwLock::new(Node::new(&config.read().await.node).await?));        // Initialize security framework first (required by other components)
-        let security = Arc::new(SecurityFramework::new(&config.read().await.security).await?);        // Initialize storage hierarchy with default configuration
-        let storage = Arc::new(StorageHierarchy::new(&aerolithdb_storage::StorageConfig::default()).await?);
+        let security = Arc::new(SecurityFramework::new(&config.read().await.security).await?);        // Initialize storage hierarchy with the configured fsync policy
+        let storage = Arc::new(StorageHierarchy::new(&storage_config(&config.read().await.storage)).await?.with_encryption(&security));
 
-        // Initialize intelligent cache system with default configuration
-        let cache = Arc::new(IntelligentCacheSystem::new(&aerolithdb_cache::CacheConfig::default()).await?);
+        // Initialize intelligent cache system, sharded across the cluster
+        // unless the node runs local-only
+        let cache = Arc::new(IntelligentCacheSystem::new(&cache_config(&config.read().await.node, &storage)).await?);
 
-        // Initialize consensus engine with default configuration
-        let consensus = Arc::new(ConsensusEngine::new(
-            &aerolithdb_consensus::ConsensusConfig::default(),
-            Arc::clone(&security),
-            Arc::clone(&storage),
-        ).await?);        // Initialize network manager with default configuration
-        let network_node = Arc::new(tokio::sync::RwLock::new(aerolithdb_network::Node));
-        let network = Arc::new(NetworkManager::new(
-            &aerolithdb_network::NetworkConfig::default(),
-            network_node,
-            Arc::clone(&security),
-            Arc::clone(&consensus),
-        ).await?);        // Initialize query engine with default configuration
+        // Initialize consensus and networking unless the node runs local-only,
+        // in which case neither exists and none of their background tasks run
+        let (consensus, network) = if config.read().await.node.local_only {
+            (None, None)
+        } else {
+            // Initialize consensus engine with the configured algorithm
+            let consensus = Arc::new(ConsensusEngine::new(
+                &aerolithdb_consensus::ConsensusConfig {
+                    algorithm: config.read().await.consensus.algorithm.clone(),
+                    snapshots: config.read().await.consensus.snapshots.clone(),
+                    conflict_resolution: config.read().await.consensus.conflict_resolution.to_consensus(),
+                    collection_conflict_resolution: config.read().await.consensus.collection_conflict_resolution
+                        .iter()
+                        .map(|(collection, strategy)| (collection.clone(), strategy.to_consensus()))
+                        .collect(),
+                    ..Default::default()
+                },
+                Arc::clone(&security),
+                Arc::clone(&storage),
+            ).await?);
+
+            // Strongly consistent collections are written and read through consensus
+            consensus.route_consistent_collections();
+
+            // Initialize network manager with default configuration
+            let network_node = Arc::new(tokio::sync::RwLock::new(aerolithdb_network::Node));
+            let network = Arc::new(NetworkManager::new(
+                &aerolithdb_network::NetworkConfig::default(),
+                network_node,
+                Arc::clone(&security),
+                Arc::clone(&consensus),
+            ).await?);
+            (Some(consensus), Some(network))
+        };        // Initialize query engine with default configuration
         let query = Arc::new(QueryEngine::new(
-            aerolithdb_query::QueryConfig::default(),
+            aerolithdb_query::QueryConfig { profiling: config.read().await.query.profiling.clone(), ..Default::default() },
             Arc::clone(&storage),
             Arc::clone(&cache),
             Arc::clone(&security),        ).await?);
@@ -248,6 +305,7 @@ impl AerolithsDB {
         //     &aerolithdb_api::APIConfig::default(),
         //     Arc::clone(&query),
         //     Arc::clone(&security),
+        //     Arc::clone(&consensus),
         // ).await?);
 
         // Initialize plugin manager with default configuration - temporarily disabled
@@ -296,14 +354,54 @@ impl AerolithsDB {
     pub async fn start(&mut self) -> Result<()> {
         info!("Starting aerolithsDB instance");
 
+        // Verify the host environment first, reporting every problem at once
+        let config = self.config.read().await.clone();
+        if config.preflight.enabled {
+            PreflightReport::run(&config).await.into_result()?;
+        }
+
         // Start components in dependency order to avoid initialization conflicts        self.security.start().await?;     // Security must be first for encryption
         self.storage.start().await?;      // Storage needed for persistence
         self.cache.start().await?;        // Cache enhances storage performance
-        self.consensus.start().await?;    // Consensus requires storage and security
-        self.network.start().await?;      // Network needs consensus for coordination
+        if let Some(consensus) = &self.consensus {
+            consensus.start().await?;     // Consensus requires storage and security
+        }
+        if let Some(network) = &self.network {
+            network.start().await?;       // Network needs consensus for coordination
+        }
+        if config.node.local_only {
+            info!("Running local-only: consensus and networking are disabled; {}", config.storage.durability_contract());
+            if config.storage.fsync == FsyncPolicy::Never {
+                warn!("Local-only node with storage.fsync = never has no replica to recover recent writes from");
+            }
+        }
         self.query.start().await?;        // Query engine needs storage and cache
+
+        // Seed known data before serving requests; already-applied fixtures are skipped
+        let fixtures_dir = self.config.read().await.node.fixtures_dir.clone();
+        if let Some(fixtures_dir) = fixtures_dir {
+            self.query.load_fixtures(&fixtures_dir).await?;
+        }
+
+        // A node acting as cluster CA issues its own certificate and keeps it renewed
+        if let Some(ca) = self.security.certificate_authority() {
+            let node = &config.node;
+            let addresses: Vec<String> = std::iter::once(node.bind_address.as_str())
+                .chain(node.external_address.as_deref())
+                .map(|address| match address.rsplit_once(':') {
+                    Some((host, port)) if port.parse::<u16>().is_ok() && !host.contains(':') => host.to_string(),
+                    _ => address.to_string(),
+                })
+                .filter(|address| address.parse::<std::net::IpAddr>().map_or(true, |ip| !ip.is_unspecified()))
+                .collect();
+            ca.local_certificate(&node.node_id, &addresses)?;
+            ca.spawn_local_rotation(node.node_id.clone(), addresses);
+        }
         // self.api.start().await?;          // API gateway needs query engine - temporarily disabled
-        // self.plugins.start().await?;      // Plugins can extend all other systems - temporarily disabled
+        // Plugins can extend all other systems; loading is gated on the runtime `plugins` flag - temporarily disabled
+        // if self.consensus.feature_flags().is_enabled(aerolithdb_consensus::features::PLUGINS) {
+        //     self.plugins.start().await?;
+        // }
 
         info!("aerolithsDB instance started successfully");
         Ok(())
@@ -342,8 +440,12 @@ impl AerolithsDB {
         // self.plugins.stop().await?;       // Stop plugins that might use other systems - temporarily disabled
         // self.api.stop().await?;           // Stop API to prevent new requests - temporarily disabled
         self.query.stop().await?;         // Finish pending queries
-        self.network.stop().await?;       // Close network connections cleanly
-        self.consensus.stop().await?;     // Complete consensus operations
+        if let Some(network) = &self.network {
+            network.stop().await?;        // Close network connections cleanly
+        }
+        if let Some(consensus) = &self.consensus {
+            consensus.stop().await?;      // Complete consensus operations
+        }
         self.cache.stop().await?;         // Flush cache to storage
         self.storage.stop().await?;       // Ensure all data is persisted
         self.security.stop().await?;      // Clear sensitive data last
@@ -411,15 +513,16 @@ impl AerolithsDB {
     /// peer discovery, connection management, and message routing.
     /// 
     /// # Returns
-    /// Arc<NetworkManager> - Network manager reference
+    /// Option<Arc<NetworkManager>> - Network manager reference, None when local-only
     /// 
     /// # Example
     /// ```rust
-    /// let network = db.network_manager();
-    /// let peers = network.get_connected_peers().await?;
+    /// if let Some(network) = db.network_manager() {
+    ///     let peers = network.get_connected_peers().await?;
+    /// }
     /// ```
-    pub fn network_manager(&self) -> Arc<NetworkManager> {
-        Arc::clone(&self.network)
+    pub fn network_manager(&self) -> Option<Arc<NetworkManager>> {
+        self.network.clone()
     }
 
     /// Get a reference to the storage hierarchy for direct storage operations.
@@ -438,4 +541,51 @@ impl AerolithsDB {
     pub fn storage(&self) -> Arc<StorageHierarchy> {
         Arc::clone(&self.storage)
     }
+
+    /// Get a reference to the consensus engine for cluster administration.
+    /// 
+    /// Provides access to consensus metrics and operator workflows such as
+    /// reviewing and reinstating quarantined Byzantine nodes.
+    /// 
+    /// # Returns
+    /// Option<Arc<ConsensusEngine>> - Consensus engine reference, None when local-only
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let db = aerolithdb_core::AerolithsDB::new().await?;
+    /// if let Some(consensus) = db.consensus_engine() {
+    ///     for record in consensus.get_quarantine_records().await {
+    ///         println!("{} quarantined: {}", record.node_id, record.reason);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn consensus_engine(&self) -> Option<Arc<ConsensusEngine>> {
+        self.consensus.clone()
+    }
+}
+
+/// Cache settings derived from the node configuration. Clustered nodes add
+/// the network layer, keyed on the same node id as the storage hash ring so
+/// cluster membership changes carry over to the cache.
+fn cache_config(node: &NodeConfig, storage: &StorageHierarchy) -> aerolithdb_cache::CacheConfig {
+    let mut config = aerolithdb_cache::CacheConfig::default();
+    if !node.local_only {
+        config.hierarchy.push(aerolithdb_cache::CacheLayer::Network);
+        config.network.local_peer_id = storage.node_id().to_string();
+    }
+    config
+}
+
+/// Storage hierarchy settings derived from the node configuration.
+fn storage_config(config: &StorageConfig) -> aerolithdb_storage::StorageConfig {
+    aerolithdb_storage::StorageConfig {
+        wal: aerolithdb_storage::WalConfig {
+            sync: config.fsync == FsyncPolicy::Always,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
 }
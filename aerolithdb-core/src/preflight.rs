@@ -0,0 +1,406 @@
+// aerolithsDB Pre-flight Environment Checks
+//
+// Verifies the host environment before any subsystem starts serving, so that
+// misconfigured machines fail fast with a complete list of problems rather than
+// crashing on the first one part-way through startup.
+//
+// ## Checks
+// - **File descriptors**: soft RLIMIT_NOFILE is high enough for peer and client connections
+// - **Disk space**: data directories have the configured minimum free space
+// - **Clock sync**: the kernel reports the system clock as NTP-synchronized (Linux)
+// - **Ports**: every enabled listener's address and port can be bound (a
+//   local-only node has no node listener)
+// - **Data directories**: data directories exist (or can be created) and are writable
+// - **Bootstrap nodes**: optionally, every bootstrap node accepts a TCP connection
+//
+// Failures abort startup; warnings are logged and startup continues. Every
+// finding carries a remediation hint.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::AerolithsConfig;
+
+/// Outcome of a single pre-flight check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// The environment meets the requirement
+    Pass,
+    /// Startup can continue, but the environment is not recommended
+    Warn,
+    /// Startup must not continue
+    Fail,
+    /// The check does not apply or cannot run on this platform
+    Skipped,
+}
+
+/// Result of one pre-flight check with a remediation hint for problems.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// How to fix the problem, present for warnings and failures
+    pub remediation: Option<String>,
+}
+
+impl PreflightCheck {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Pass, detail: detail.into(), remediation: None }
+    }
+
+    fn skipped(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Skipped, detail: detail.into(), remediation: None }
+    }
+
+    fn problem(name: &str, status: CheckStatus, detail: impl Into<String>, remediation: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status, detail: detail.into(), remediation: Some(remediation.into()) }
+    }
+}
+
+/// All pre-flight check results for one startup.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Run every enabled check against the configuration.
+    pub async fn run(config: &AerolithsConfig) -> Self {
+        let settings = &config.preflight;
+        let mut checks = Vec::new();
+
+        checks.push(check_open_files(settings.min_open_files));
+
+        let data_dirs = data_directories(config);
+        for dir in &data_dirs {
+            checks.push(check_data_dir(dir).await);
+        }
+        for dir in &data_dirs {
+            checks.push(check_disk_space(dir, settings.min_free_disk_bytes));
+        }
+
+        checks.push(check_clock_sync());
+
+        for (service, address) in listen_addresses(config) {
+            checks.push(check_port(&service, &address).await);
+        }
+
+        if settings.check_bootstrap_nodes && !config.node.local_only {
+            for node in &config.network.bootstrap_nodes {
+                checks.push(check_bootstrap_node(node, config.network.connection_timeout).await);
+            }
+        }
+
+        Self { checks }
+    }
+
+    /// Checks that must be resolved before the node can start.
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks.iter().filter(|check| check.status == CheckStatus::Fail)
+    }
+
+    /// Log every finding, then fail with all blocking problems listed together.
+    pub fn into_result(self) -> Result<()> {
+        for check in &self.checks {
+            match check.status {
+                CheckStatus::Pass | CheckStatus::Skipped => debug!("Pre-flight {}: {}", check.name, check.detail),
+                CheckStatus::Warn => warn!(
+                    "Pre-flight {}: {} ({})",
+                    check.name, check.detail, check.remediation.as_deref().unwrap_or_default()
+                ),
+                CheckStatus::Fail => {}
+            }
+        }
+
+        let failures: Vec<String> = self
+            .failures()
+            .map(|check| format!(
+                "  - {}: {}\n    fix: {}",
+                check.name, check.detail, check.remediation.as_deref().unwrap_or("see documentation")
+            ))
+            .collect();
+
+        if failures.is_empty() {
+            info!("Pre-flight checks passed ({} checks)", self.checks.len());
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "{} pre-flight check(s) failed:\n{}", failures.len(), failures.join("\n")
+            ))
+        }
+    }
+}
+
+/// Node and storage data directories, without duplicates.
+fn data_directories(config: &AerolithsConfig) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    [config.node.data_dir.clone(), config.storage.data_dir.clone()]
+        .into_iter()
+        .filter(|dir| seen.insert(dir.clone()))
+        .collect()
+}
+
+/// Addresses of the node and every enabled API listener.
+fn listen_addresses(config: &AerolithsConfig) -> Vec<(String, String)> {
+    let api = &config.api;
+    let mut addresses = Vec::new();
+    if !config.node.local_only {
+        addresses.push(("node".to_string(), format!("{}:{}", config.node.bind_address, config.node.port)));
+    }
+    for (service, enabled, bind_address, port) in [
+        ("REST API", api.rest_api.enabled, &api.rest_api.bind_address, api.rest_api.port),
+        ("GraphQL API", api.graphql_api.enabled, &api.graphql_api.bind_address, api.graphql_api.port),
+        ("gRPC API", api.grpc_api.enabled, &api.grpc_api.bind_address, api.grpc_api.port),
+        ("WebSocket API", api.websocket_api.enabled, &api.websocket_api.bind_address, api.websocket_api.port),
+    ] {
+        if enabled {
+            addresses.push((service.to_string(), format!("{}:{}", bind_address, port)));
+        }
+    }
+    addresses
+}
+
+#[cfg(unix)]
+fn check_open_files(minimum: u64) -> PreflightCheck {
+    const NAME: &str = "open file limit";
+
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    // SAFETY: getrlimit only writes into the provided struct
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return PreflightCheck::skipped(NAME, "could not read RLIMIT_NOFILE");
+    }
+
+    #[allow(clippy::unnecessary_cast)] // rlim_t is narrower than u64 on some platforms
+    let soft = limit.rlim_cur as u64;
+    if soft >= minimum {
+        PreflightCheck::pass(NAME, format!("soft limit {} (minimum {})", soft, minimum))
+    } else {
+        PreflightCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("soft limit is {} but at least {} is required", soft, minimum),
+            format!("raise it with `ulimit -n {}` or LimitNOFILE={} in the systemd unit", minimum, minimum),
+        )
+    }
+}
+
+#[cfg(not(unix))]
+fn check_open_files(_minimum: u64) -> PreflightCheck {
+    PreflightCheck::skipped("open file limit", "not checked on this platform")
+}
+
+async fn check_data_dir(dir: &Path) -> PreflightCheck {
+    let name = format!("data directory {}", dir.display());
+
+    if let Err(e) = tokio::fs::create_dir_all(dir).await {
+        return PreflightCheck::problem(
+            &name,
+            CheckStatus::Fail,
+            format!("cannot be created: {}", e),
+            "create the directory or point data_dir at a location the service user owns",
+        );
+    }
+
+    let probe = dir.join(format!(".preflight-{}", uuid::Uuid::new_v4()));
+    match tokio::fs::write(&probe, b"ok").await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(&probe).await;
+            PreflightCheck::pass(&name, "writable")
+        }
+        Err(e) => PreflightCheck::problem(
+            &name,
+            CheckStatus::Fail,
+            format!("is not writable: {}", e),
+            "grant the service user write access, e.g. `chown -R <user> <dir>`",
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn check_disk_space(dir: &Path, minimum: u64) -> PreflightCheck {
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = format!("disk space for {}", dir.display());
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return PreflightCheck::skipped(&name, "path contains a NUL byte");
+    };
+
+    // SAFETY: statvfs is plain old data and is fully written on success
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return PreflightCheck::skipped(&name, "could not query filesystem statistics");
+    }
+
+    #[allow(clippy::unnecessary_cast)] // statvfs field widths vary by platform
+    let available = stats.f_bavail as u64 * stats.f_frsize as u64;
+    if available >= minimum {
+        PreflightCheck::pass(&name, format!("{} MiB available", available / (1024 * 1024)))
+    } else {
+        PreflightCheck::problem(
+            &name,
+            CheckStatus::Fail,
+            format!(
+                "only {} MiB available, {} MiB required",
+                available / (1024 * 1024), minimum / (1024 * 1024)
+            ),
+            "free up space, move data_dir to a larger volume, or lower preflight.min_free_disk_bytes",
+        )
+    }
+}
+
+#[cfg(not(unix))]
+fn check_disk_space(dir: &Path, _minimum: u64) -> PreflightCheck {
+    PreflightCheck::skipped(&format!("disk space for {}", dir.display()), "not checked on this platform")
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn check_clock_sync() -> PreflightCheck {
+    const NAME: &str = "clock sync";
+
+    // A zeroed timex with modes = 0 only reads the kernel's clock state
+    // SAFETY: timex is plain old data and adjtimex only writes into it
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut timex) };
+
+    if state == -1 {
+        PreflightCheck::skipped(NAME, "could not query kernel clock state")
+    } else if state == libc::TIME_ERROR {
+        PreflightCheck::problem(
+            NAME,
+            CheckStatus::Warn,
+            "system clock is not synchronized; lease expiry and timestamps may drift between nodes",
+            "enable an NTP client such as chronyd or systemd-timesyncd",
+        )
+    } else {
+        PreflightCheck::pass(NAME, "system clock is synchronized")
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn check_clock_sync() -> PreflightCheck {
+    PreflightCheck::skipped("clock sync", "not checked on this platform")
+}
+
+async fn check_port(service: &str, address: &str) -> PreflightCheck {
+    let name = format!("{} port {}", service, address);
+
+    // Bind and immediately release; the service binds for real once it starts
+    match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => {
+            drop(listener);
+            PreflightCheck::pass(&name, "available")
+        }
+        Err(e) => PreflightCheck::problem(
+            &name,
+            CheckStatus::Fail,
+            format!("cannot be bound: {}", e),
+            "stop the process using the port (see `ss -ltnp`) or configure a different port",
+        ),
+    }
+}
+
+async fn check_bootstrap_node(node: &str, timeout: Duration) -> PreflightCheck {
+    let name = format!("bootstrap node {}", node);
+    let Some(address) = bootstrap_socket_address(node) else {
+        return PreflightCheck::problem(
+            &name,
+            CheckStatus::Warn,
+            "address is not in host:port or /ip4|ip6|dns/<host>/tcp/<port> form",
+            "correct the entry in network.bootstrap_nodes",
+        );
+    };
+
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(&address)).await {
+        Ok(Ok(_)) => PreflightCheck::pass(&name, "reachable"),
+        Ok(Err(e)) => PreflightCheck::problem(
+            &name,
+            CheckStatus::Warn,
+            format!("unreachable: {}", e),
+            "check that the node is running and that firewalls allow the connection",
+        ),
+        Err(_) => PreflightCheck::problem(
+            &name,
+            CheckStatus::Warn,
+            format!("no response within {:?}", timeout),
+            "check network routes and firewalls between the nodes",
+        ),
+    }
+}
+
+/// Convert a bootstrap entry (`host:port` or a TCP multiaddr) into `host:port`.
+fn bootstrap_socket_address(node: &str) -> Option<String> {
+    if !node.starts_with('/') {
+        return node.rsplit_once(':').map(|_| node.to_string());
+    }
+
+    let parts: Vec<&str> = node.trim_start_matches('/').split('/').collect();
+    match parts.as_slice() {
+        ["ip6", host, "tcp", port, ..] => Some(format!("[{}]:{}", host, port)),
+        ["ip4" | "dns" | "dns4" | "dns6", host, "tcp", port, ..] => Some(format!("{}:{}", host, port)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bootstrap_address_forms() {
+        assert_eq!(bootstrap_socket_address("10.0.0.1:9000").as_deref(), Some("10.0.0.1:9000"));
+        assert_eq!(bootstrap_socket_address("/ip4/10.0.0.1/tcp/9000/p2p/Qm").as_deref(), Some("10.0.0.1:9000"));
+        assert_eq!(bootstrap_socket_address("/ip6/::1/tcp/9000").as_deref(), Some("[::1]:9000"));
+        assert_eq!(bootstrap_socket_address("/ip4/10.0.0.1/udp/9000/quic"), None);
+        assert_eq!(bootstrap_socket_address("seed-node"), None);
+    }
+
+    #[test]
+    fn test_local_only_has_no_node_listener() {
+        let mut config = AerolithsConfig::default();
+        assert!(listen_addresses(&config).iter().any(|(service, _)| service == "node"));
+
+        config.node.local_only = true;
+        let services: Vec<String> = listen_addresses(&config).into_iter().map(|(service, _)| service).collect();
+        assert!(!services.contains(&"node".to_string()));
+        assert!(services.contains(&"REST API".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_all_failures_reported_together() {
+        let blocker = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = blocker.local_addr().unwrap().port();
+
+        let mut config = AerolithsConfig::default();
+        config.node.bind_address = "127.0.0.1".to_string();
+        config.node.port = port;
+        config.api.rest_api.bind_address = "127.0.0.1".to_string();
+        config.api.rest_api.port = port;
+        config.api.graphql_api.enabled = false;
+        config.api.grpc_api.enabled = false;
+        config.api.websocket_api.enabled = false;
+        config.preflight.min_free_disk_bytes = u64::MAX;
+
+        let dir = std::env::temp_dir().join(format!("aerolithdb-preflight-{}", uuid::Uuid::new_v4()));
+        config.node.data_dir = dir.clone();
+        config.storage.data_dir = dir.clone();
+
+        let report = PreflightReport::run(&config).await;
+        let failed: Vec<&str> = report.failures().map(|check| check.name.as_str()).collect();
+        assert!(failed.iter().any(|name| name.starts_with("node port")));
+        assert!(failed.iter().any(|name| name.starts_with("REST API port")));
+        if cfg!(unix) {
+            assert!(failed.iter().any(|name| name.starts_with("disk space")));
+        }
+
+        let error = report.into_result().unwrap_err().to_string();
+        assert!(error.contains("fix:"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
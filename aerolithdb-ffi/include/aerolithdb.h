@@ -0,0 +1,111 @@
+/*
+ * aerolithsDB C API
+ *
+ * Embeds a single-node aerolithsDB database in the calling process. Link
+ * against the aerolithdb_ffi shared or static library built from the
+ * aerolithdb-ffi crate.
+ *
+ * - Every fallible function returns an AerolithStatus; on failure,
+ *   aerolith_last_error() describes the error on the calling thread
+ * - Collection names and document IDs are NUL-terminated UTF-8 strings
+ * - Documents and queries are JSON passed as a pointer and length; results
+ *   are returned in an AerolithBuffer released with aerolith_buffer_free()
+ * - A database handle may be used from several threads at once, but must
+ *   not be used during or after aerolith_close()
+ */
+
+#ifndef AEROLITHDB_H
+#define AEROLITHDB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define AEROLITH_ABI_VERSION 1
+
+typedef enum AerolithStatus {
+    AEROLITH_OK = 0,
+    /* A required pointer was null, or a string was not valid UTF-8 */
+    AEROLITH_INVALID_ARGUMENT = 1,
+    /* A document, query, or configuration was not valid JSON of the expected shape */
+    AEROLITH_INVALID_JSON = 2,
+    /* The requested document does not exist */
+    AEROLITH_NOT_FOUND = 3,
+    /* The database failed to carry out the operation */
+    AEROLITH_INTERNAL = 4,
+    /* The operation panicked; the handle should be closed */
+    AEROLITH_PANIC = 5
+} AerolithStatus;
+
+/* Opaque handle to an open database */
+typedef struct AerolithDb AerolithDb;
+
+/* Bytes owned by the caller until passed to aerolith_buffer_free() */
+typedef struct AerolithBuffer {
+    uint8_t *data;
+    size_t len;
+} AerolithBuffer;
+
+/* Version of the C ABI implemented by the linked library */
+uint32_t aerolith_abi_version(void);
+
+/*
+ * Open an embedded database. config_json is a JSON object with the optional
+ * keys "data_dir", "compression", "worker_threads", and "sync", or NULL for
+ * the defaults. "sync" is an object with the keys "url" and "collections",
+ * naming the cluster node and the collections to replicate with it.
+ * *out_db is set to the handle, or to NULL on failure.
+ */
+AerolithStatus aerolith_open(const char *config_json, AerolithDb **out_db);
+
+/* Flush the database to disk and release the handle; NULL is ignored */
+AerolithStatus aerolith_close(AerolithDb *db);
+
+/* Store a JSON document under id, replacing any existing version */
+AerolithStatus aerolith_put(const AerolithDb *db, const char *collection, const char *id,
+                            const uint8_t *data, size_t len);
+
+/* Fetch a document as JSON; AEROLITH_NOT_FOUND if it does not exist */
+AerolithStatus aerolith_get(const AerolithDb *db, const char *collection, const char *id,
+                            AerolithBuffer *out);
+
+/* Delete a document; AEROLITH_NOT_FOUND if it does not exist */
+AerolithStatus aerolith_delete(const AerolithDb *db, const char *collection, const char *id);
+
+/*
+ * Query a collection. query is a JSON object with the optional keys
+ * "filter", "sort", "limit", and "offset", as accepted by the REST API, or
+ * NULL to match every document. The result is
+ * {"documents": [...], "total": n}, where total counts matches before limit
+ * and offset were applied.
+ */
+AerolithStatus aerolith_query(const AerolithDb *db, const char *collection,
+                              const uint8_t *query, size_t len, AerolithBuffer *out);
+
+/*
+ * Sync the replicated collections with the configured cluster node, pushing
+ * local writes and pulling remote ones. The report is
+ * {"peer": ..., "sent": {...}, "received": {...}}, each side counting
+ * versions "applied", "merged" with concurrent edits, and "unchanged", and
+ * how many arrived as field "deltas".
+ * Fails while the node is unreachable; local writes are kept for a later call.
+ */
+AerolithStatus aerolith_sync(const AerolithDb *db, AerolithBuffer *out);
+
+/* Release a buffer returned by the library; empty buffers are ignored */
+void aerolith_buffer_free(AerolithBuffer buffer);
+
+/*
+ * Message describing the most recent failure on the calling thread, or NULL.
+ * Valid until the next failing call on the same thread.
+ */
+const char *aerolith_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AEROLITHDB_H */
@@ -0,0 +1,172 @@
+//! Embedded database behind the C API.
+//!
+//! An [`EmbeddedDatabase`] runs a single node's storage hierarchy and query
+//! engine in-process, without networking or consensus. It owns the Tokio
+//! runtime its subsystems run on, so the blocking methods below can be
+//! called from any foreign thread, including several at once.
+//!
+//! Configured with a [`SyncConfig`], the database acts as an edge replica of
+//! a cluster: writes to the synced collections are recorded while offline
+//! and exchanged with a node whenever [`EmbeddedDatabase::sync`] is called.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+use tracing::info;
+
+use aerolithdb_cache::{CacheConfig, IntelligentCacheSystem};
+use aerolithdb_query::{QueryConfig, QueryEngine, QueryRequest, SyncReport};
+use aerolithdb_security::SecurityFramework;
+use aerolithdb_storage::{StorageConfig, StorageHierarchy};
+
+use crate::remote::HttpSyncPeer;
+
+/// How long closing waits for background tasks to wind down
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings accepted by `aerolith_open` as a JSON object.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddedConfig {
+    /// Root directory of the storage tiers and on-disk cache
+    pub data_dir: PathBuf,
+    /// Whether to compress documents at rest
+    pub compression: bool,
+    /// Worker threads of the embedded runtime; defaults to the CPU count
+    pub worker_threads: Option<usize>,
+    /// Cluster node to replicate collections with, if any
+    pub sync: Option<SyncConfig>,
+}
+
+/// Replication of a subset of collections with a cluster node.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Base URL of the node's REST API, e.g. `http://node-1:8080`
+    pub url: String,
+    /// Collections kept on this replica
+    pub collections: Vec<String>,
+}
+
+impl Default for EmbeddedConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("./data"),
+            compression: true,
+            worker_threads: None,
+            sync: None,
+        }
+    }
+}
+
+/// A single-node database running inside the host process.
+#[derive(Debug)]
+pub struct EmbeddedDatabase {
+    storage: Arc<StorageHierarchy>,
+    engine: QueryEngine,
+    remote: Option<HttpSyncPeer>,
+    runtime: Runtime,
+}
+
+impl EmbeddedDatabase {
+    /// Open the database stored under the configured data directory.
+    pub fn open(config: EmbeddedConfig) -> Result<Self> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all().thread_name("aerolithdb-embedded");
+        if let Some(threads) = config.worker_threads {
+            builder.worker_threads(threads.max(1));
+        }
+        let runtime = builder.build()?;
+
+        let (storage, engine) = runtime.block_on(async {
+            let mut storage_config = StorageConfig {
+                data_dir: config.data_dir.clone(),
+                // There is no other node to hold replicas
+                replication_factor: 1,
+                ..Default::default()
+            };
+            if !config.compression {
+                storage_config.compression.algorithm = aerolithdb_storage::CompressionAlgorithm::None;
+            }
+            let cache_config = CacheConfig {
+                nvme_dir: config.data_dir.join("cache"),
+                ..Default::default()
+            };
+
+            let storage = Arc::new(StorageHierarchy::new(&storage_config).await?);
+            storage.start().await?;
+            let cache = Arc::new(IntelligentCacheSystem::new(&cache_config).await?);
+            let security = Arc::new(SecurityFramework::new(&Default::default()).await?);
+            let engine = QueryEngine::new(QueryConfig::default(), Arc::clone(&storage), cache, security).await?;
+            engine.start().await?;
+            if let Some(sync) = &config.sync {
+                for collection in &sync.collections {
+                    engine.sync().track(collection).await?;
+                }
+            }
+            Ok::<_, anyhow::Error>((storage, engine))
+        })?;
+        let remote = config.sync.as_ref().map(|sync| HttpSyncPeer::new(&sync.url)).transpose()?;
+
+        info!("Opened embedded database at {:?}", config.data_dir);
+        Ok(Self { storage, engine, remote, runtime })
+    }
+
+    /// Store a document under `id`, replacing any existing version.
+    pub fn put(&self, collection: &str, id: &str, document: &Value) -> Result<()> {
+        self.runtime.block_on(self.engine.store_document(collection, id, document))
+    }
+
+    /// Fetch a document, or `None` if it does not exist.
+    pub fn get(&self, collection: &str, id: &str) -> Result<Option<Value>> {
+        Ok(self.runtime.block_on(self.storage.get_document(collection, id))?.data)
+    }
+
+    /// Delete a document, returning whether it existed.
+    pub fn delete(&self, collection: &str, id: &str) -> Result<bool> {
+        self.runtime.block_on(async {
+            if self.storage.get_document(collection, id).await?.data.is_none() {
+                return Ok(false);
+            }
+            self.engine.delete_document(collection, id).await?;
+            Ok(true)
+        })
+    }
+
+    /// Run a query, returning `{"documents": [...], "total": n}` where
+    /// `total` counts matches before limit and offset were applied.
+    pub fn query(&self, collection: &str, query: &QueryRequest) -> Result<Value> {
+        let result = self.runtime.block_on(self.engine.query_documents(collection, query))?;
+        Ok(json!({"documents": result.documents, "total": result.total}))
+    }
+
+    /// Exchange changes with the configured cluster node: local writes made
+    /// since the last sync are pushed, then the node's changes are pulled.
+    /// Concurrent edits are merged field by field on both sides.
+    pub fn sync(&self) -> Result<SyncReport> {
+        let remote = self
+            .remote
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Sync is not configured for this database"))?;
+        self.runtime.block_on(self.engine.sync().sync_with(remote))
+    }
+
+    /// Stop the engine and storage tiers, flushing them to disk.
+    pub fn close(self) -> Result<()> {
+        self.runtime.block_on(async {
+            self.engine.stop().await?;
+            self.storage.stop().await
+        })?;
+        // Background tasks still hold the storage, so its files stay locked
+        // until the runtime has dropped them
+        drop(self.engine);
+        drop(self.storage);
+        self.runtime.shutdown_timeout(CLOSE_TIMEOUT);
+        Ok(())
+    }
+}
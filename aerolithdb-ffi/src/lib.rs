@@ -0,0 +1,535 @@
+//! # aerolithsDB C API
+//!
+//! Stable C ABI for embedding aerolithsDB in applications written in other
+//! languages. Go, C#, Swift, Python, and similar bindings wrap these
+//! functions instead of reimplementing the REST protocol; the matching
+//! header is `include/aerolithdb.h`.
+//!
+//! ## Conventions
+//!
+//! - Every fallible function returns an [`AerolithStatus`]. On failure,
+//!   [`aerolith_last_error`] describes the error on the calling thread
+//! - Collection names and document IDs are NUL-terminated UTF-8 strings
+//! - Documents and queries are passed in as JSON in a pointer and length
+//!   pair, and returned in an [`AerolithBuffer`] the caller releases with
+//!   [`aerolith_buffer_free`]
+//! - Panics never unwind into the caller; they are reported as
+//!   [`AerolithStatus::Panic`]
+//! - A database handle may be used from several threads at once, but must
+//!   not be used during or after [`aerolith_close`]
+//!
+//! ## Example
+//!
+//! ```c
+//! AerolithDb *db;
+//! if (aerolith_open("{\"data_dir\": \"./data\"}", &db) != AEROLITH_OK) {
+//!     fprintf(stderr, "%s\n", aerolith_last_error());
+//!     return 1;
+//! }
+//!
+//! const char *user = "{\"name\": \"Ada\", \"age\": 36}";
+//! aerolith_put(db, "users", "ada", (const uint8_t *)user, strlen(user));
+//!
+//! const char *query = "{\"filter\": {\"age\": {\"$gte\": 18}}, \"limit\": 10}";
+//! AerolithBuffer result;
+//! if (aerolith_query(db, "users", (const uint8_t *)query, strlen(query), &result) == AEROLITH_OK) {
+//!     fwrite(result.data, 1, result.len, stdout);
+//!     aerolith_buffer_free(result);
+//! }
+//!
+//! aerolith_close(db);
+//! ```
+
+pub mod embedded;
+pub mod remote;
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use aerolithdb_query::QueryRequest;
+use serde::de::DeserializeOwned;
+use tracing::error;
+
+pub use embedded::{EmbeddedConfig, EmbeddedDatabase, SyncConfig};
+
+/// Version of the C ABI, raised whenever a function or type changes
+/// incompatibly.
+pub const AEROLITH_ABI_VERSION: u32 = 1;
+
+/// Result codes returned by every fallible function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AerolithStatus {
+    Ok = 0,
+    /// A required pointer was null, or a string was not valid UTF-8
+    InvalidArgument = 1,
+    /// A document, query, or configuration was not valid JSON of the expected shape
+    InvalidJson = 2,
+    /// The requested document does not exist
+    NotFound = 3,
+    /// The database failed to carry out the operation
+    Internal = 4,
+    /// The operation panicked; the handle should be closed
+    Panic = 5,
+}
+
+/// Opaque handle to an open database.
+pub struct AerolithDb(EmbeddedDatabase);
+
+/// Bytes allocated by the library and owned by the caller until passed to
+/// [`aerolith_buffer_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct AerolithBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl AerolithBuffer {
+    fn empty() -> Self {
+        Self { data: ptr::null_mut(), len: 0 }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        // A boxed slice has no spare capacity, so the length alone frees it
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// Failure reported to the caller as a status code and last error message.
+struct Failure {
+    status: AerolithStatus,
+    message: String,
+}
+
+impl Failure {
+    fn new(status: AerolithStatus, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(AerolithStatus::Internal, format!("{:#}", e))
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs would truncate the message on the C side anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `operation`, turning errors and panics into a status code.
+fn call(operation: impl FnOnce() -> Result<(), Failure>) -> AerolithStatus {
+    match catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(Ok(())) => AerolithStatus::Ok,
+        Ok(Err(failure)) => {
+            set_last_error(failure.message);
+            failure.status
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            error!("Panic in C API call: {}", message);
+            set_last_error(format!("Panic: {}", message));
+            AerolithStatus::Panic
+        }
+    }
+}
+
+unsafe fn database<'a>(db: *const AerolithDb) -> Result<&'a EmbeddedDatabase, Failure> {
+    db.as_ref()
+        .map(|db| &db.0)
+        .ok_or_else(|| Failure::new(AerolithStatus::InvalidArgument, "Database handle is null"))
+}
+
+unsafe fn string<'a>(value: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if value.is_null() {
+        return Err(Failure::new(AerolithStatus::InvalidArgument, format!("{} is null", name)));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| Failure::new(AerolithStatus::InvalidArgument, format!("{} is not valid UTF-8", name)))
+}
+
+unsafe fn json<T: DeserializeOwned>(data: *const u8, len: usize, name: &str) -> Result<T, Failure> {
+    if data.is_null() {
+        return Err(Failure::new(AerolithStatus::InvalidArgument, format!("{} is null", name)));
+    }
+    let bytes = std::slice::from_raw_parts(data, len);
+    serde_json::from_slice(bytes)
+        .map_err(|e| Failure::new(AerolithStatus::InvalidJson, format!("Invalid {}: {}", name, e)))
+}
+
+/// Reset `out` so callers see an empty buffer if the operation fails.
+unsafe fn output(out: *mut AerolithBuffer) -> Result<*mut AerolithBuffer, Failure> {
+    if out.is_null() {
+        return Err(Failure::new(AerolithStatus::InvalidArgument, "Output buffer is null"));
+    }
+    out.write(AerolithBuffer::empty());
+    Ok(out)
+}
+
+unsafe fn write_json(out: *mut AerolithBuffer, value: &serde_json::Value) -> Result<(), Failure> {
+    let bytes = serde_json::to_vec(value).map_err(anyhow::Error::from)?;
+    out.write(AerolithBuffer::from_vec(bytes));
+    Ok(())
+}
+
+/// Version of the C ABI implemented by this library; see
+/// [`AEROLITH_ABI_VERSION`].
+#[no_mangle]
+pub extern "C" fn aerolith_abi_version() -> u32 {
+    AEROLITH_ABI_VERSION
+}
+
+/// Open an embedded database.
+///
+/// `config_json` is a JSON object with the optional keys `data_dir`,
+/// `compression`, `worker_threads`, and `sync`, or null for the defaults.
+/// `sync` is an object with the keys `url` and `collections`, naming the
+/// cluster node and the collections to replicate with it. On
+/// success the handle is written to `out_db`, which is set to null on
+/// failure.
+///
+/// # Safety
+///
+/// `config_json` must be null or a NUL-terminated string, and `out_db` must
+/// be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_open(config_json: *const c_char, out_db: *mut *mut AerolithDb) -> AerolithStatus {
+    call(|| {
+        if out_db.is_null() {
+            return Err(Failure::new(AerolithStatus::InvalidArgument, "Output handle is null"));
+        }
+        out_db.write(ptr::null_mut());
+        let config = if config_json.is_null() {
+            EmbeddedConfig::default()
+        } else {
+            let config = string(config_json, "Configuration")?;
+            serde_json::from_str(config)
+                .map_err(|e| Failure::new(AerolithStatus::InvalidJson, format!("Invalid configuration: {}", e)))?
+        };
+
+        let db = EmbeddedDatabase::open(config)?;
+        out_db.write(Box::into_raw(Box::new(AerolithDb(db))));
+        Ok(())
+    })
+}
+
+/// Close a database, flushing it to disk and releasing the handle. Closing
+/// a null handle does nothing.
+///
+/// # Safety
+///
+/// `db` must be null or a handle returned by [`aerolith_open`] that has not
+/// been closed, and no other thread may be using it.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_close(db: *mut AerolithDb) -> AerolithStatus {
+    call(|| {
+        if db.is_null() {
+            return Ok(());
+        }
+        let db = Box::from_raw(db);
+        db.0.close()?;
+        Ok(())
+    })
+}
+
+/// Store the JSON document in `data` under `id`, replacing any existing
+/// version.
+///
+/// # Safety
+///
+/// `db` must be an open handle, `collection` and `id` NUL-terminated
+/// strings, and `data` valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_put(
+    db: *const AerolithDb,
+    collection: *const c_char,
+    id: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> AerolithStatus {
+    call(|| {
+        let db = database(db)?;
+        let collection = string(collection, "Collection")?;
+        let id = string(id, "Document ID")?;
+        let document: serde_json::Value = json(data, len, "document")?;
+        db.put(collection, id, &document)?;
+        Ok(())
+    })
+}
+
+/// Fetch a document as JSON into `out`. Returns
+/// [`AerolithStatus::NotFound`] if it does not exist; on any failure `out`
+/// is left empty.
+///
+/// # Safety
+///
+/// `db` must be an open handle, `collection` and `id` NUL-terminated
+/// strings, and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_get(
+    db: *const AerolithDb,
+    collection: *const c_char,
+    id: *const c_char,
+    out: *mut AerolithBuffer,
+) -> AerolithStatus {
+    call(|| {
+        let out = output(out)?;
+        let db = database(db)?;
+        let collection = string(collection, "Collection")?;
+        let id = string(id, "Document ID")?;
+        match db.get(collection, id)? {
+            Some(document) => write_json(out, &document),
+            None => Err(Failure::new(
+                AerolithStatus::NotFound,
+                format!("Document '{}' not found in '{}'", id, collection),
+            )),
+        }
+    })
+}
+
+/// Delete a document. Returns [`AerolithStatus::NotFound`] if it does not
+/// exist.
+///
+/// # Safety
+///
+/// `db` must be an open handle and `collection` and `id` NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_delete(
+    db: *const AerolithDb,
+    collection: *const c_char,
+    id: *const c_char,
+) -> AerolithStatus {
+    call(|| {
+        let db = database(db)?;
+        let collection = string(collection, "Collection")?;
+        let id = string(id, "Document ID")?;
+        if db.delete(collection, id)? {
+            Ok(())
+        } else {
+            Err(Failure::new(
+                AerolithStatus::NotFound,
+                format!("Document '{}' not found in '{}'", id, collection),
+            ))
+        }
+    })
+}
+
+/// Query a collection. `query` is a JSON object with the optional keys
+/// `filter`, `sort`, `limit`, and `offset`, as accepted by the REST API, or
+/// null to match every document. The result written to `out` is
+/// `{"documents": [...], "total": n}`, where `total` counts matches before
+/// `limit` and `offset` were applied; on failure `out` is left empty.
+///
+/// # Safety
+///
+/// `db` must be an open handle, `collection` a NUL-terminated string,
+/// `query` null or valid for reads of `len` bytes, and `out` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_query(
+    db: *const AerolithDb,
+    collection: *const c_char,
+    query: *const u8,
+    len: usize,
+    out: *mut AerolithBuffer,
+) -> AerolithStatus {
+    call(|| {
+        let out = output(out)?;
+        let db = database(db)?;
+        let collection = string(collection, "Collection")?;
+        let query: QueryRequest = if query.is_null() { QueryRequest::new() } else { json(query, len, "query")? };
+        let result = db.query(collection, &query)?;
+        write_json(out, &result)
+    })
+}
+
+/// Sync the replicated collections with the cluster node named in the
+/// configuration, pushing local writes and pulling remote ones. The report
+/// written to `out` is `{"peer": ..., "sent": {...}, "received": {...}}`,
+/// each side counting versions `applied`, `merged` with concurrent edits,
+/// and `unchanged`, and how many arrived as field `deltas`. Fails while the node is unreachable; local writes are
+/// kept and sent by a later call.
+///
+/// # Safety
+///
+/// `db` must be an open handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_sync(db: *const AerolithDb, out: *mut AerolithBuffer) -> AerolithStatus {
+    call(|| {
+        let out = output(out)?;
+        let db = database(db)?;
+        let report = db.sync()?;
+        write_json(out, &serde_json::to_value(report).map_err(anyhow::Error::from)?)
+    })
+}
+
+/// Release a buffer returned by this library. Freeing an empty buffer does
+/// nothing.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by this library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn aerolith_buffer_free(buffer: AerolithBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Message describing the most recent failure on the calling thread, or
+/// null if no call has failed. The string is owned by the library and
+/// stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn aerolith_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn c(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(aerolith_last_error()).to_string_lossy().into_owned() }
+    }
+
+    unsafe fn take(buffer: AerolithBuffer) -> Value {
+        let value = serde_json::from_slice(std::slice::from_raw_parts(buffer.data, buffer.len)).unwrap();
+        aerolith_buffer_free(buffer);
+        value
+    }
+
+    fn open() -> *mut AerolithDb {
+        let dir = std::env::temp_dir().join(format!("aerolith-ffi-{}", uuid::Uuid::new_v4()));
+        let config = c(&json!({"data_dir": dir, "worker_threads": 2}).to_string());
+        let mut db = ptr::null_mut();
+        assert_eq!(unsafe { aerolith_open(config.as_ptr(), &mut db) }, AerolithStatus::Ok);
+        assert!(!db.is_null());
+        db
+    }
+
+    #[test]
+    fn test_crud_and_query() {
+        let db = open();
+        let users = c("users");
+        unsafe {
+            for (id, age) in [("ada", 36), ("alan", 41), ("kid", 9)] {
+                let document = json!({"name": id, "age": age}).to_string();
+                let status = aerolith_put(db, users.as_ptr(), c(id).as_ptr(), document.as_ptr(), document.len());
+                assert_eq!(status, AerolithStatus::Ok);
+            }
+
+            let mut out = AerolithBuffer::empty();
+            assert_eq!(aerolith_get(db, users.as_ptr(), c("ada").as_ptr(), &mut out), AerolithStatus::Ok);
+            assert_eq!(take(out), json!({"name": "ada", "age": 36}));
+
+            let query = json!({"filter": {"age": {"$gte": 18}}, "sort": {"age": -1}, "limit": 1}).to_string();
+            let mut out = AerolithBuffer::empty();
+            let status = aerolith_query(db, users.as_ptr(), query.as_ptr(), query.len(), &mut out);
+            assert_eq!(status, AerolithStatus::Ok);
+            let result = take(out);
+            assert_eq!(result["total"], 2);
+            assert_eq!(result["documents"], json!([{"name": "alan", "age": 41}]));
+
+            let mut out = AerolithBuffer::empty();
+            assert_eq!(aerolith_query(db, users.as_ptr(), ptr::null(), 0, &mut out), AerolithStatus::Ok);
+            assert_eq!(take(out)["total"], 3);
+
+            assert_eq!(aerolith_delete(db, users.as_ptr(), c("kid").as_ptr()), AerolithStatus::Ok);
+            assert_eq!(aerolith_delete(db, users.as_ptr(), c("kid").as_ptr()), AerolithStatus::NotFound);
+            let mut out = AerolithBuffer::empty();
+            assert_eq!(aerolith_get(db, users.as_ptr(), c("kid").as_ptr(), &mut out), AerolithStatus::NotFound);
+            assert!(out.data.is_null());
+            assert!(last_error().contains("'kid' not found"));
+
+            assert_eq!(aerolith_close(db), AerolithStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn test_invalid_arguments_report_errors() {
+        let db = open();
+        let users = c("users");
+        unsafe {
+            let document = b"{not json";
+            let status = aerolith_put(db, users.as_ptr(), c("x").as_ptr(), document.as_ptr(), document.len());
+            assert_eq!(status, AerolithStatus::InvalidJson);
+            assert!(last_error().starts_with("Invalid document"));
+
+            let document = b"{}";
+            let status = aerolith_put(db, users.as_ptr(), ptr::null(), document.as_ptr(), document.len());
+            assert_eq!(status, AerolithStatus::InvalidArgument);
+            assert_eq!(last_error(), "Document ID is null");
+
+            let status = aerolith_put(ptr::null(), users.as_ptr(), c("x").as_ptr(), document.as_ptr(), document.len());
+            assert_eq!(status, AerolithStatus::InvalidArgument);
+
+            let mut handle = ptr::null_mut();
+            let config = c(r#"{"data_directory": "/tmp"}"#);
+            assert_eq!(aerolith_open(config.as_ptr(), &mut handle), AerolithStatus::InvalidJson);
+            assert!(handle.is_null());
+
+            assert_eq!(aerolith_close(ptr::null_mut()), AerolithStatus::Ok);
+            aerolith_buffer_free(AerolithBuffer::empty());
+            assert_eq!(aerolith_close(db), AerolithStatus::Ok);
+        }
+        assert_eq!(aerolith_abi_version(), AEROLITH_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_sync_keeps_offline_writes() {
+        let dir = std::env::temp_dir().join(format!("aerolith-ffi-{}", uuid::Uuid::new_v4()));
+        let config = json!({
+            "data_dir": dir,
+            "worker_threads": 2,
+            // Nothing listens on port 9, so the node is unreachable
+            "sync": {"url": "http://127.0.0.1:9", "collections": ["notes"]},
+        });
+        let mut db = ptr::null_mut();
+        let notes = c("notes");
+        unsafe {
+            assert_eq!(aerolith_open(c(&config.to_string()).as_ptr(), &mut db), AerolithStatus::Ok);
+            let document = json!({"text": "written offline"}).to_string();
+            let status = aerolith_put(db, notes.as_ptr(), c("n1").as_ptr(), document.as_ptr(), document.len());
+            assert_eq!(status, AerolithStatus::Ok);
+
+            let mut out = AerolithBuffer::empty();
+            assert_eq!(aerolith_sync(db, &mut out), AerolithStatus::Internal);
+            assert!(out.data.is_null());
+
+            let mut out = AerolithBuffer::empty();
+            assert_eq!(aerolith_get(db, notes.as_ptr(), c("n1").as_ptr(), &mut out), AerolithStatus::Ok);
+            assert_eq!(take(out), json!({"text": "written offline"}));
+            assert_eq!(aerolith_close(db), AerolithStatus::Ok);
+        }
+
+        let db = open();
+        unsafe {
+            let mut out = AerolithBuffer::empty();
+            assert_eq!(aerolith_sync(db, &mut out), AerolithStatus::Internal);
+            assert!(last_error().contains("not configured"));
+            assert_eq!(aerolith_close(db), AerolithStatus::Ok);
+        }
+    }
+}
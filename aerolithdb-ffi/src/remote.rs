@@ -0,0 +1,70 @@
+//! Sync peer reached over a node's REST API.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use aerolithdb_query::{ApplyReport, ChangeBatch, PullRequest, PushRequest, SyncFuture, SyncPeer};
+
+/// How long a single sync request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+struct SyncInfo {
+    replica_id: String,
+}
+
+/// A cluster node serving the `/api/v1/sync` endpoints
+#[derive(Debug, Clone)]
+pub struct HttpSyncPeer {
+    client: Client,
+    base_url: String,
+}
+
+impl HttpSyncPeer {
+    /// Peer at `base_url`, e.g. `http://node-1:8080`
+    pub fn new(base_url: &str) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let response = self
+            .client
+            .post(format!("{}/api/v1/sync/{}", self.base_url, path))
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+impl SyncPeer for HttpSyncPeer {
+    fn replica_id(&self) -> SyncFuture<'_, String> {
+        Box::pin(async move {
+            let info: SyncInfo = self
+                .client
+                .get(format!("{}/api/v1/sync", self.base_url))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            Ok(info.replica_id)
+        })
+    }
+
+    fn pull(&self, request: PullRequest) -> SyncFuture<'_, ChangeBatch> {
+        Box::pin(async move { self.post("pull", &request).await })
+    }
+
+    fn push(&self, request: PushRequest) -> SyncFuture<'_, ApplyReport> {
+        Box::pin(async move { self.post("push", &request).await })
+    }
+}
@@ -262,6 +262,78 @@ pub enum SystemEvent {
         /// Final decision reached by the consensus algorithm
         decision: String,
     },
+
+    /// Triggered when storage usage reaches the configured storage limit
+    /// Useful for: Capacity alerts, cleanup automation, audit logging
+    StorageQuota {
+        /// What happened: `high_watermark_reached`, `write_rejected`,
+        /// `documents_archived` or `tombstones_purged`
+        kind: String,
+        /// Bytes counted against the limit
+        used_bytes: u64,
+        /// The storage limit in bytes
+        limit_bytes: u64,
+        /// Event details as reported by the storage layer
+        details: serde_json::Value,
+    },
+
+    /// Triggered when consensus quarantines a node for Byzantine behavior,
+    /// or an operator reinstates one
+    /// Useful for: Security alerting, incident response, audit logging
+    NodeQuarantined {
+        /// Unique identifier of the node
+        node_id: String,
+        /// `quarantined` or `reinstated`
+        status: String,
+        /// Why the node was quarantined
+        reason: String,
+        /// Faults observed from the node, such as conflicting signed votes
+        evidence: serde_json::Value,
+    },
+}
+
+impl SystemEvent {
+    /// Build a `StorageQuota` event from a storage quota event serialized to
+    /// JSON, which names its kind in `event` and carries `used` and `limit`.
+    pub fn from_storage_quota(details: serde_json::Value) -> Self {
+        let field = |name: &str| details.get(name).and_then(serde_json::Value::as_u64).unwrap_or(0);
+        SystemEvent::StorageQuota {
+            kind: details.get("event").and_then(serde_json::Value::as_str).unwrap_or("unknown").to_string(),
+            used_bytes: field("used"),
+            limit_bytes: field("limit"),
+            details,
+        }
+    }
+
+    /// Build a `NodeQuarantined` event from a consensus quarantine record
+    /// serialized to JSON, whose `status` is the string `Quarantined` or an
+    /// object keyed by `Reinstated`.
+    pub fn from_quarantine_record(details: &serde_json::Value) -> Option<Self> {
+        let status = match details.get("status")? {
+            serde_json::Value::String(status) => status.to_lowercase(),
+            serde_json::Value::Object(status) => status.keys().next()?.to_lowercase(),
+            _ => return None,
+        };
+        Some(SystemEvent::NodeQuarantined {
+            node_id: details.get("node_id")?.as_str()?.to_string(),
+            status,
+            reason: details.get("reason").and_then(serde_json::Value::as_str).unwrap_or_default().to_string(),
+            evidence: details.get("evidence").cloned().unwrap_or_default(),
+        })
+    }
+
+    /// Build a `DocumentDeleted` event from a storage expiration event
+    /// serialized to JSON. Archived documents are still stored, so their
+    /// events give `None`.
+    pub fn from_document_expiration(details: &serde_json::Value) -> Option<Self> {
+        if details.get("event").and_then(serde_json::Value::as_str) != Some("document_deleted") {
+            return None;
+        }
+        Some(SystemEvent::DocumentDeleted {
+            collection: details.get("collection")?.as_str()?.to_string(),
+            document_id: details.get("document_id")?.as_str()?.to_string(),
+        })
+    }
 }
 
 /// HTTP API endpoint definition for plugin-provided REST services.
@@ -0,0 +1,584 @@
+//! # Aggregation Pipelines
+//!
+//! Analytics queries run as a pipeline of stages over a collection, in the
+//! style of MongoDB's aggregation framework, so counts, sums and averages
+//! are computed next to the data instead of by clients reading every
+//! document:
+//!
+//! - `$match`: keep documents matching a [`CompiledFilter`]
+//! - `$group`: group documents by an `_id` expression and compute
+//!   `$count`, `$sum`, `$avg`, `$min` and `$max` accumulators per group
+//! - `$sort`: order by fields, `1` ascending and `-1` descending
+//! - `$limit`: keep the first documents
+//! - `$project`: include, exclude or compute fields
+//!
+//! Expressions are field references such as `"$customer.city"`, literal
+//! values, or objects and arrays of expressions. A pipeline is parsed once
+//! into an [`AggregationPipeline`]; malformed stages fail with errors
+//! prefixed "Invalid pipeline:". `$match` stages at the start of a pipeline
+//! are applied while the collection is read, and a `$limit` right after
+//! them stops the read early.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::filter::CompiledFilter;
+use crate::processing::DocumentSorter;
+use crate::projection::{insert, lookup, remove};
+
+/// A pipeline parsed and checked once, ready to run over a collection.
+#[derive(Debug, Clone)]
+pub struct AggregationPipeline {
+    stages: Vec<Stage>,
+}
+
+/// Documents an aggregation produced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregationResult {
+    pub documents: Vec<Value>,
+
+    /// Documents read from the collection
+    pub scanned: usize,
+    pub execution_time: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum Stage {
+    Match(CompiledFilter),
+    Group { id: Expression, accumulators: Vec<(String, Accumulator)> },
+    Sort(Value),
+    Limit(usize),
+    Project(Projection),
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    Field(Vec<String>),
+    Literal(Value),
+    Object(Vec<(String, Expression)>),
+    Array(Vec<Expression>),
+}
+
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count,
+    Sum(Expression),
+    Avg(Expression),
+    Min(Expression),
+    Max(Expression),
+}
+
+#[derive(Debug, Clone)]
+enum Projection {
+    /// Keep these fields, and `_id` unless excluded, and add computed ones
+    Include { id: bool, fields: Vec<(Vec<String>, Option<Expression>)> },
+
+    /// Keep everything but these fields
+    Exclude(Vec<Vec<String>>),
+}
+
+impl AggregationPipeline {
+    /// Parse a pipeline, failing on unknown stages, operators and malformed
+    /// operands.
+    pub fn compile(stages: &[Value]) -> Result<Self> {
+        let stages = stages.iter().map(parse_stage).collect::<Result<Vec<_>>>()?;
+        Ok(Self { stages })
+    }
+
+    /// Whether a document read from the collection passes the `$match`
+    /// stages the pipeline starts with.
+    pub fn matches_leading(&self, document: &Value) -> bool {
+        self.stages
+            .iter()
+            .map_while(|stage| match stage {
+                Stage::Match(filter) => Some(filter),
+                _ => None,
+            })
+            .all(|filter| filter.matches(document))
+    }
+
+    /// Documents passing the leading `$match` stages the pipeline needs at
+    /// most, when a `$limit` follows them.
+    pub fn scan_limit(&self) -> Option<usize> {
+        match self.stages.iter().find(|stage| !matches!(stage, Stage::Match(_))) {
+            Some(Stage::Limit(limit)) => Some(*limit),
+            _ => None,
+        }
+    }
+
+    /// Run the stages after the leading `$match` stages over documents that
+    /// passed them.
+    pub fn run(&self, documents: Vec<Value>) -> Vec<Value> {
+        let rest = self.stages.iter().skip_while(|stage| matches!(stage, Stage::Match(_)));
+        rest.fold(documents, |documents, stage| stage.run(documents))
+    }
+
+    /// Run the whole pipeline over documents.
+    pub fn evaluate(&self, documents: Vec<Value>) -> Vec<Value> {
+        let matching = documents.into_iter().filter(|document| self.matches_leading(document)).collect();
+        self.run(matching)
+    }
+}
+
+fn parse_stage(stage: &Value) -> Result<Stage> {
+    let (name, operand) = match stage {
+        Value::Object(stage) if stage.len() == 1 => stage.iter().next().expect("one entry"),
+        _ => return Err(anyhow!("Invalid pipeline: each stage is an object with one stage operator, got {}", stage)),
+    };
+    match name.as_str() {
+        "$match" => Ok(Stage::Match(CompiledFilter::compile(operand).map_err(|e| anyhow!("Invalid pipeline: $match: {}", e))?)),
+        "$group" => parse_group(operand),
+        "$sort" => match operand {
+            Value::Object(fields)
+                if !fields.is_empty() && fields.values().all(|direction| direction == 1 || direction == -1) =>
+            {
+                Ok(Stage::Sort(operand.clone()))
+            }
+            _ => Err(anyhow!("Invalid pipeline: $sort needs fields ordered by 1 or -1")),
+        },
+        "$limit" => match operand.as_u64() {
+            Some(limit) if limit > 0 => Ok(Stage::Limit(limit as usize)),
+            _ => Err(anyhow!("Invalid pipeline: $limit needs a positive integer")),
+        },
+        "$project" => parse_projection(operand),
+        _ => Err(anyhow!("Invalid pipeline: unknown stage {}", name)),
+    }
+}
+
+fn parse_group(operand: &Value) -> Result<Stage> {
+    let Value::Object(spec) = operand else {
+        return Err(anyhow!("Invalid pipeline: $group needs an object"));
+    };
+    let id = spec.get("_id").ok_or_else(|| anyhow!("Invalid pipeline: $group needs an _id, null for one group"))?;
+    let id = parse_expression(id)?;
+
+    let mut accumulators = Vec::new();
+    for (field, accumulator) in spec.iter().filter(|(field, _)| *field != "_id") {
+        if field.contains('.') || field.starts_with('$') {
+            return Err(anyhow!("Invalid pipeline: bad $group field name '{}'", field));
+        }
+        let (operator, argument) = match accumulator {
+            Value::Object(accumulator) if accumulator.len() == 1 => accumulator.iter().next().expect("one entry"),
+            _ => return Err(anyhow!("Invalid pipeline: $group field {} needs one accumulator", field)),
+        };
+        let accumulator = match operator.as_str() {
+            "$count" if argument.as_object().is_some_and(Map::is_empty) => Accumulator::Count,
+            "$count" => return Err(anyhow!("Invalid pipeline: $count takes an empty object")),
+            "$sum" => Accumulator::Sum(parse_expression(argument)?),
+            "$avg" => Accumulator::Avg(parse_expression(argument)?),
+            "$min" => Accumulator::Min(parse_expression(argument)?),
+            "$max" => Accumulator::Max(parse_expression(argument)?),
+            _ => return Err(anyhow!("Invalid pipeline: unknown accumulator {}", operator)),
+        };
+        accumulators.push((field.clone(), accumulator));
+    }
+    Ok(Stage::Group { id, accumulators })
+}
+
+fn parse_projection(operand: &Value) -> Result<Stage> {
+    let Value::Object(spec) = operand else {
+        return Err(anyhow!("Invalid pipeline: $project needs an object"));
+    };
+    if spec.is_empty() {
+        return Err(anyhow!("Invalid pipeline: $project needs at least one field"));
+    }
+
+    let (mut include_id, mut included, mut excluded) = (true, Vec::new(), Vec::new());
+    for (field, value) in spec {
+        let path = parse_path(field)?;
+        let flag = match value {
+            Value::Bool(flag) => Some(*flag),
+            Value::Number(flag) => Some(flag.as_f64() != Some(0.0)),
+            _ => None,
+        };
+        match flag {
+            Some(flag) if field == "_id" => include_id = flag,
+            Some(true) => included.push((path, None)),
+            Some(false) => excluded.push(path),
+            None => included.push((path, Some(parse_expression(value)?))),
+        }
+    }
+
+    match (included.is_empty(), excluded.is_empty()) {
+        (false, false) => Err(anyhow!("Invalid pipeline: $project cannot mix inclusions and exclusions other than _id")),
+        (true, true) if !include_id => Ok(Stage::Project(Projection::Exclude(vec![vec!["_id".to_string()]]))),
+        (true, true) => Err(anyhow!("Invalid pipeline: $project needs at least one field")),
+        (true, false) => {
+            if !include_id {
+                excluded.push(vec!["_id".to_string()]);
+            }
+            Ok(Stage::Project(Projection::Exclude(excluded)))
+        }
+        (false, true) => Ok(Stage::Project(Projection::Include { id: include_id, fields: included })),
+    }
+}
+
+fn parse_path(field: &str) -> Result<Vec<String>> {
+    if field.is_empty() || field.starts_with('$') || field.split('.').any(str::is_empty) {
+        return Err(anyhow!("Invalid pipeline: bad field path '{}'", field));
+    }
+    Ok(field.split('.').map(String::from).collect())
+}
+
+fn parse_expression(expression: &Value) -> Result<Expression> {
+    match expression {
+        Value::String(reference) if reference.starts_with('$') => Ok(Expression::Field(parse_path(&reference[1..])?)),
+        Value::Object(fields) => {
+            if let Some(operator) = fields.keys().find(|key| key.starts_with('$')) {
+                return Err(anyhow!("Invalid pipeline: unknown expression operator {}", operator));
+            }
+            fields
+                .iter()
+                .map(|(field, expression)| Ok((field.clone(), parse_expression(expression)?)))
+                .collect::<Result<_>>()
+                .map(Expression::Object)
+        }
+        Value::Array(elements) => elements.iter().map(parse_expression).collect::<Result<_>>().map(Expression::Array),
+        literal => Ok(Expression::Literal(literal.clone())),
+    }
+}
+
+impl Expression {
+    /// Value of the expression for a document; `None` for a missing field
+    fn evaluate(&self, document: &Value) -> Option<Value> {
+        match self {
+            Expression::Field(path) => lookup(document, path).cloned(),
+            Expression::Literal(value) => Some(value.clone()),
+            Expression::Object(fields) => Some(Value::Object(
+                fields
+                    .iter()
+                    .filter_map(|(field, expression)| Some((field.clone(), expression.evaluate(document)?)))
+                    .collect(),
+            )),
+            Expression::Array(elements) => Some(Value::Array(
+                elements.iter().map(|element| element.evaluate(document).unwrap_or(Value::Null)).collect(),
+            )),
+        }
+    }
+}
+
+impl Stage {
+    fn run(&self, documents: Vec<Value>) -> Vec<Value> {
+        match self {
+            Stage::Match(filter) => documents.into_iter().filter(|document| filter.matches(document)).collect(),
+            Stage::Group { id, accumulators } => group(documents, id, accumulators),
+            Stage::Sort(spec) => {
+                let mut documents = documents;
+                DocumentSorter::sort_documents(&mut documents, spec);
+                documents
+            }
+            Stage::Limit(limit) => documents.into_iter().take(*limit).collect(),
+            Stage::Project(projection) => documents.into_iter().map(|document| projection.apply(document)).collect(),
+        }
+    }
+}
+
+impl Projection {
+    fn apply(&self, document: Value) -> Value {
+        match self {
+            Projection::Include { id, fields } => {
+                let mut projected = Map::new();
+                if *id {
+                    if let Some(value) = document.get("_id") {
+                        projected.insert("_id".to_string(), value.clone());
+                    }
+                }
+                for (path, expression) in fields {
+                    let value = match expression {
+                        Some(expression) => expression.evaluate(&document),
+                        None => lookup(&document, path).cloned(),
+                    };
+                    if let Some(value) = value {
+                        insert(&mut projected, path, value);
+                    }
+                }
+                Value::Object(projected)
+            }
+            Projection::Exclude(paths) => {
+                let mut document = document;
+                for path in paths {
+                    remove(&mut document, path);
+                }
+                document
+            }
+        }
+    }
+}
+
+/// Running state of one accumulator over a group
+#[derive(Debug)]
+enum State {
+    Count(u64),
+    Sum(Total),
+    Avg(Total, u64),
+    Extreme(Ordering, Option<Value>),
+}
+
+/// A sum kept exact while every term is an integer that fits
+#[derive(Debug, Clone, Copy)]
+enum Total {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Total {
+    fn add(self, term: &Number) -> Self {
+        match (self, term.as_i64()) {
+            (Total::Integer(total), Some(term)) => match total.checked_add(term) {
+                Some(total) => Total::Integer(total),
+                None => Total::Float(total as f64 + term as f64),
+            },
+            (Total::Integer(total), None) => Total::Float(total as f64 + term.as_f64().unwrap_or(0.0)),
+            (Total::Float(total), _) => Total::Float(total + term.as_f64().unwrap_or(0.0)),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Total::Integer(total) => total as f64,
+            Total::Float(total) => total,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Total::Integer(total) => Value::from(total),
+            Total::Float(total) => Number::from_f64(total).map_or(Value::Null, Value::Number),
+        }
+    }
+}
+
+impl Accumulator {
+    fn start(&self) -> State {
+        match self {
+            Accumulator::Count => State::Count(0),
+            Accumulator::Sum(_) => State::Sum(Total::Integer(0)),
+            Accumulator::Avg(_) => State::Avg(Total::Integer(0), 0),
+            Accumulator::Min(_) => State::Extreme(Ordering::Less, None),
+            Accumulator::Max(_) => State::Extreme(Ordering::Greater, None),
+        }
+    }
+
+    fn add(&self, state: &mut State, document: &Value) {
+        let value = match self {
+            Accumulator::Count => None,
+            Accumulator::Sum(expression)
+            | Accumulator::Avg(expression)
+            | Accumulator::Min(expression)
+            | Accumulator::Max(expression) => expression.evaluate(document),
+        };
+        match (state, value) {
+            (State::Count(count), _) => *count += 1,
+            // Only numbers are summed and averaged; other values are skipped
+            (State::Sum(total), Some(Value::Number(term))) => *total = total.add(&term),
+            (State::Avg(total, count), Some(Value::Number(term))) => {
+                *total = total.add(&term);
+                *count += 1;
+            }
+            // Missing fields and nulls take no part in the minimum or maximum
+            (State::Extreme(_, _), None | Some(Value::Null)) => {}
+            (State::Extreme(wanted, extreme), Some(value))
+                if extreme.as_ref().is_none_or(|extreme| order(&value, extreme) == *wanted) =>
+            {
+                *extreme = Some(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl State {
+    fn finish(self) -> Value {
+        match self {
+            State::Count(count) => Value::from(count),
+            State::Sum(total) => total.into_value(),
+            State::Avg(_, 0) => Value::Null,
+            State::Avg(total, count) => Number::from_f64(total.as_f64() / count as f64).map_or(Value::Null, Value::Number),
+            State::Extreme(_, extreme) => extreme.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Total order over JSON values: by type, null, numbers, strings, objects,
+/// arrays, booleans, then by value within a type
+fn order(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Number(_) => 1,
+            Value::String(_) => 2,
+            Value::Object(_) => 3,
+            Value::Array(_) => 4,
+            Value::Bool(_) => 5,
+        }
+    }
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal),
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| order(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((a_key, a), (b_key, b))| a_key.cmp(b_key).then_with(|| order(a, b)))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Group documents by key, in the order each key is first seen
+fn group(documents: Vec<Value>, id: &Expression, accumulators: &[(String, Accumulator)]) -> Vec<Value> {
+    let mut groups: Vec<(Value, Vec<State>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for document in &documents {
+        let key = id.evaluate(document).unwrap_or(Value::Null);
+        let position = *positions.entry(key.to_string()).or_insert_with(|| {
+            groups.push((key, accumulators.iter().map(|(_, accumulator)| accumulator.start()).collect()));
+            groups.len() - 1
+        });
+        let states = &mut groups[position].1;
+        for ((_, accumulator), state) in accumulators.iter().zip(states.iter_mut()) {
+            accumulator.add(state, document);
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, states)| {
+            let mut output = Map::new();
+            output.insert("_id".to_string(), key);
+            for ((field, _), state) in accumulators.iter().zip(states) {
+                output.insert(field.clone(), state.finish());
+            }
+            Value::Object(output)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn orders() -> Vec<Value> {
+        vec![
+            json!({ "_id": "o1", "status": "paid", "customer": { "city": "Oslo" }, "total": 40, "items": 2 }),
+            json!({ "_id": "o2", "status": "paid", "customer": { "city": "Bergen" }, "total": 15.5, "items": 1 }),
+            json!({ "_id": "o3", "status": "open", "customer": { "city": "Oslo" }, "total": 100, "items": 5 }),
+            json!({ "_id": "o4", "status": "paid", "customer": { "city": "Oslo" }, "total": 60, "items": 3 }),
+            json!({ "_id": "o5", "status": "paid", "customer": { "city": "Tromsø" }, "items": 1 }),
+        ]
+    }
+
+    fn aggregate(pipeline: Value) -> Vec<Value> {
+        let Value::Array(stages) = pipeline else { panic!("pipelines are arrays") };
+        AggregationPipeline::compile(&stages).unwrap().evaluate(orders())
+    }
+
+    #[test]
+    fn test_group_accumulators() {
+        let revenue = aggregate(json!([
+            { "$match": { "status": "paid" } },
+            { "$group": {
+                "_id": "$customer.city",
+                "orders": { "$count": {} },
+                "revenue": { "$sum": "$total" },
+                "average": { "$avg": "$total" },
+                "smallest": { "$min": "$total" },
+                "largest": { "$max": "$total" },
+                "items": { "$sum": "$items" }
+            } },
+            { "$sort": { "revenue": -1 } }
+        ]));
+        assert_eq!(
+            revenue,
+            vec![
+                json!({ "_id": "Oslo", "orders": 2, "revenue": 100, "average": 50.0, "smallest": 40, "largest": 60, "items": 5 }),
+                json!({ "_id": "Bergen", "orders": 1, "revenue": 15.5, "average": 15.5, "smallest": 15.5, "largest": 15.5, "items": 1 }),
+                // Missing totals are skipped rather than counted as zero
+                json!({ "_id": "Tromsø", "orders": 1, "revenue": 0, "average": null, "smallest": null, "largest": null, "items": 1 }),
+            ]
+        );
+
+        // A null _id puts every document in one group; $sum of 1 counts
+        let overall = aggregate(json!([
+            { "$group": { "_id": null, "documents": { "$sum": 1 }, "largest": { "$max": "$total" } } }
+        ]));
+        assert_eq!(overall, vec![json!({ "_id": null, "documents": 5, "largest": 100 })]);
+
+        // Compound keys
+        let by_status_and_city = aggregate(json!([
+            { "$group": { "_id": { "status": "$status", "city": "$customer.city" }, "n": { "$count": {} } } },
+            { "$match": { "_id.city": "Oslo" } },
+            { "$sort": { "_id.status": 1 } }
+        ]));
+        assert_eq!(
+            by_status_and_city,
+            vec![
+                json!({ "_id": { "status": "open", "city": "Oslo" }, "n": 1 }),
+                json!({ "_id": { "status": "paid", "city": "Oslo" }, "n": 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_limit_and_project() {
+        let top = aggregate(json!([
+            { "$sort": { "items": -1 } },
+            { "$limit": 2 },
+            { "$project": { "_id": 0, "city": "$customer.city", "items": 1, "summary.total": "$total" } }
+        ]));
+        assert_eq!(
+            top,
+            vec![
+                json!({ "city": "Oslo", "items": 5, "summary": { "total": 100 } }),
+                json!({ "city": "Oslo", "items": 3, "summary": { "total": 60 } }),
+            ]
+        );
+
+        let trimmed = aggregate(json!([{ "$match": { "_id": "o2" } }, { "$project": { "customer": 0, "items": false } }]));
+        assert_eq!(trimmed, vec![json!({ "_id": "o2", "status": "paid", "total": 15.5 })]);
+
+        let pipeline = AggregationPipeline::compile(&[json!({ "$match": { "status": "paid" } }), json!({ "$limit": 3 })]).unwrap();
+        assert_eq!(pipeline.scan_limit(), Some(3));
+        assert!(!pipeline.matches_leading(&json!({ "status": "open" })));
+        let pipeline = AggregationPipeline::compile(&[json!({ "$sort": { "total": 1 } }), json!({ "$limit": 3 })]).unwrap();
+        assert_eq!(pipeline.scan_limit(), None);
+    }
+
+    #[test]
+    fn test_invalid_pipelines_are_rejected() {
+        for stage in [
+            json!("$match"),
+            json!({ "$match": { "a": 1 }, "$limit": 1 }),
+            json!({ "$out": "elsewhere" }),
+            json!({ "$match": { "$where": "1" } }),
+            json!({ "$group": { "total": { "$sum": "$total" } } }),
+            json!({ "$group": { "_id": null, "total": { "$median": "$total" } } }),
+            json!({ "$group": { "_id": null, "total": { "$sum": "$total", "$avg": "$total" } } }),
+            json!({ "$group": { "_id": { "$concat": ["$a"] } } }),
+            json!({ "$sort": { "total": "up" } }),
+            json!({ "$limit": 0 }),
+            json!({ "$project": { "a": 1, "b": 0 } }),
+            json!({ "$project": {} }),
+        ] {
+            let error = AggregationPipeline::compile(std::slice::from_ref(&stage)).unwrap_err();
+            assert!(error.to_string().starts_with("Invalid pipeline"), "{}: {}", stage, error);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+//! # Canary Queries
+//!
+//! Before a replacement index or a planner change takes over, a canary
+//! runs a sample of real queries through both the current execution path
+//! and the candidate, and compares their results and latency. Clients are
+//! always answered the usual way; the comparison runs in the background.
+//!
+//! - **Paths**: anything implementing [`QueryPath`]. [`PlannerPath`] runs
+//!   queries with a given [`PlannerMode`]; a replacement index plugs in by
+//!   implementing the trait over its own lookups
+//! - **Comparison**: results must have the same total and the same
+//!   documents, in the same order when the query sorts. Unsorted pages may
+//!   legitimately differ between paths, so only totals are compared for
+//!   unsorted queries with a limit or offset
+//! - **Cutover**: the report is ready for cutover once enough queries were
+//!   compared without a discrepancy or failure and the candidate is not
+//!   slower than allowed
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use aerolithdb_storage::StorageHierarchy;
+
+use crate::engine::execute_query;
+use crate::execution::QueryContext;
+use crate::indexes::SecondaryIndexes;
+use crate::planner::PlannerMode;
+use crate::types::QueryRequest;
+
+/// Discrepancies kept for review
+const RECENT_DISCREPANCIES: usize = 50;
+
+/// Future returned by a [`QueryPath`]
+pub type QueryPathFuture<'a> = Pin<Box<dyn Future<Output = Result<PathResult>> + Send + 'a>>;
+
+/// Documents a query path returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathResult {
+    /// The page of documents returned
+    pub documents: Vec<Value>,
+
+    /// Matches before limit and offset were applied
+    pub total: usize,
+}
+
+/// A way of executing queries that a canary can compare.
+pub trait QueryPath: Send + Sync {
+    /// Name shown in canary reports
+    fn name(&self) -> String;
+
+    /// Run a query without the query result cache
+    fn execute<'a>(&'a self, collection: &'a str, query: &'a QueryRequest) -> QueryPathFuture<'a>;
+}
+
+/// Runs queries against storage, planning filters and choosing indexes
+/// with a given mode.
+pub struct PlannerPath {
+    storage: Arc<StorageHierarchy>,
+    indexes: Arc<SecondaryIndexes>,
+    mode: PlannerMode,
+}
+
+impl PlannerPath {
+    pub fn new(storage: Arc<StorageHierarchy>, indexes: Arc<SecondaryIndexes>, mode: PlannerMode) -> Self {
+        Self { storage, indexes, mode }
+    }
+}
+
+impl QueryPath for PlannerPath {
+    fn name(&self) -> String {
+        format!("planner:{}", serde_json::to_value(self.mode).ok().and_then(|mode| mode.as_str().map(str::to_string)).unwrap_or_default())
+    }
+
+    fn execute<'a>(&'a self, collection: &'a str, query: &'a QueryRequest) -> QueryPathFuture<'a> {
+        Box::pin(async move {
+            let context = QueryContext::unbounded(collection);
+            let execution = execute_query(&self.storage, &self.indexes, collection, query, self.mode, false, &context).await?;
+            Ok(PathResult { documents: execution.documents, total: execution.total })
+        })
+    }
+}
+
+/// How many queries a canary compares and when its candidate is ready.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanaryConfig {
+    /// Fraction of queries compared, from 0.0 to 1.0
+    pub sample_rate: f64,
+
+    /// Comparisons running at once; further samples are dropped
+    pub max_in_flight: usize,
+
+    /// Comparisons needed before the candidate can be ready for cutover
+    pub min_samples: u64,
+
+    /// How much slower than the current path the candidate may be on
+    /// average, as a fraction of its latency
+    pub max_latency_regression: f64,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.05,
+            max_in_flight: 4,
+            min_samples: 100,
+            max_latency_regression: 0.2,
+        }
+    }
+}
+
+/// A query the candidate answered differently, or not at all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryDiscrepancy {
+    pub at: DateTime<Utc>,
+    pub collection: String,
+    pub query: QueryRequest,
+    pub baseline_total: usize,
+    pub candidate_total: Option<usize>,
+
+    /// Documents only the current path returned
+    pub missing: usize,
+
+    /// Documents only the candidate returned
+    pub unexpected: usize,
+
+    /// Why the candidate failed, if it did
+    pub error: Option<String>,
+    pub baseline_ms: f64,
+    pub candidate_ms: f64,
+}
+
+/// Progress of a canary and whether its candidate is ready for cutover.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryReport {
+    pub baseline: String,
+    pub candidate: String,
+    pub config: CanaryConfig,
+    pub started_at: DateTime<Utc>,
+
+    /// Queries compared or being compared
+    pub sampled: u64,
+    pub matched: u64,
+    pub mismatched: u64,
+
+    /// Queries the candidate failed to answer
+    pub failed: u64,
+
+    /// Samples dropped because too many comparisons were running
+    pub dropped: u64,
+
+    /// Mean latency of the current path over compared queries
+    pub baseline_mean_ms: f64,
+
+    /// Mean latency of the candidate over compared queries
+    pub candidate_mean_ms: f64,
+    pub ready_for_cutover: bool,
+
+    /// Most recent discrepancies, newest first
+    pub discrepancies: Vec<CanaryDiscrepancy>,
+}
+
+/// Compares a sample of queries between the current path and a candidate.
+pub struct QueryCanary {
+    config: CanaryConfig,
+    baseline: Arc<dyn QueryPath>,
+    candidate: Arc<dyn QueryPath>,
+    started_at: DateTime<Utc>,
+    seen: AtomicU64,
+    in_flight: AtomicUsize,
+    sampled: AtomicU64,
+    matched: AtomicU64,
+    mismatched: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+    baseline_micros: AtomicU64,
+    candidate_micros: AtomicU64,
+    discrepancies: Mutex<VecDeque<CanaryDiscrepancy>>,
+}
+
+impl std::fmt::Debug for QueryCanary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryCanary")
+            .field("baseline", &self.baseline.name())
+            .field("candidate", &self.candidate.name())
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QueryCanary {
+    pub fn new(config: CanaryConfig, baseline: Arc<dyn QueryPath>, candidate: Arc<dyn QueryPath>) -> Result<Self> {
+        if !(0.0..=1.0).contains(&config.sample_rate) {
+            return Err(anyhow::anyhow!("Canary sample rate must be between 0 and 1, got {}", config.sample_rate));
+        }
+        Ok(Self {
+            config,
+            baseline,
+            candidate,
+            started_at: Utc::now(),
+            seen: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            sampled: AtomicU64::new(0),
+            matched: AtomicU64::new(0),
+            mismatched: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            baseline_micros: AtomicU64::new(0),
+            candidate_micros: AtomicU64::new(0),
+            discrepancies: Mutex::new(VecDeque::with_capacity(RECENT_DISCREPANCIES)),
+        })
+    }
+
+    /// Counters, mean latencies and recent discrepancies.
+    pub fn report(&self) -> CanaryReport {
+        let matched = self.matched.load(Ordering::Relaxed);
+        let mismatched = self.mismatched.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let compared = matched + mismatched + failed;
+        let mean_ms = |micros: &AtomicU64| match compared {
+            0 => 0.0,
+            compared => micros.load(Ordering::Relaxed) as f64 / compared as f64 / 1000.0,
+        };
+        let (baseline_mean_ms, candidate_mean_ms) = (mean_ms(&self.baseline_micros), mean_ms(&self.candidate_micros));
+
+        CanaryReport {
+            baseline: self.baseline.name(),
+            candidate: self.candidate.name(),
+            config: self.config.clone(),
+            started_at: self.started_at,
+            sampled: self.sampled.load(Ordering::Relaxed),
+            matched,
+            mismatched,
+            failed,
+            dropped: self.dropped.load(Ordering::Relaxed),
+            baseline_mean_ms,
+            candidate_mean_ms,
+            ready_for_cutover: compared >= self.config.min_samples
+                && mismatched == 0
+                && failed == 0
+                && candidate_mean_ms <= baseline_mean_ms * (1.0 + self.config.max_latency_regression),
+            discrepancies: self.discrepancies.lock().unwrap().iter().rev().cloned().collect(),
+        }
+    }
+
+    /// Compare a query in the background if it is sampled.
+    pub(crate) fn observe(self: &Arc<Self>, collection: &str, query: &QueryRequest) {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        let rate = self.config.sample_rate;
+        // Samples are spread evenly over the queries seen
+        if ((n + 1) as f64 * rate).floor() <= (n as f64 * rate).floor() {
+            return;
+        }
+        let acquired = self
+            .in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_flight| {
+                (in_flight < self.config.max_in_flight).then_some(in_flight + 1)
+            })
+            .is_ok();
+        if !acquired {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.sampled.fetch_add(1, Ordering::Relaxed);
+
+        let canary = Arc::clone(self);
+        let (collection, query) = (collection.to_string(), query.clone());
+        tokio::spawn(async move {
+            canary.compare(&collection, &query).await;
+            canary.in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+
+    /// Run a query through both paths and record the outcome.
+    async fn compare(&self, collection: &str, query: &QueryRequest) {
+        let started = Instant::now();
+        let baseline = match self.baseline.execute(collection, query).await {
+            Ok(result) => result,
+            Err(e) => {
+                // Without a reference result there is nothing to compare
+                warn!("Canary baseline failed on {}: {}", collection, e);
+                self.sampled.fetch_sub(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        let baseline_elapsed = started.elapsed();
+        let started = Instant::now();
+        let candidate = self.candidate.execute(collection, query).await;
+        let candidate_elapsed = started.elapsed();
+
+        self.baseline_micros.fetch_add(baseline_elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.candidate_micros.fetch_add(candidate_elapsed.as_micros() as u64, Ordering::Relaxed);
+
+        let mut discrepancy = CanaryDiscrepancy {
+            at: Utc::now(),
+            collection: collection.to_string(),
+            query: query.clone(),
+            baseline_total: baseline.total,
+            candidate_total: None,
+            missing: 0,
+            unexpected: 0,
+            error: None,
+            baseline_ms: baseline_elapsed.as_secs_f64() * 1000.0,
+            candidate_ms: candidate_elapsed.as_secs_f64() * 1000.0,
+        };
+        match candidate {
+            Ok(candidate) => {
+                let (missing, unexpected) = differences(query, &baseline, &candidate);
+                if missing == 0 && unexpected == 0 && baseline.total == candidate.total {
+                    self.matched.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                self.mismatched.fetch_add(1, Ordering::Relaxed);
+                discrepancy.candidate_total = Some(candidate.total);
+                discrepancy.missing = missing;
+                discrepancy.unexpected = unexpected;
+                warn!(
+                    "Canary {} differs on {}: {} documents missing, {} unexpected, totals {} vs {}",
+                    self.candidate.name(), collection, missing, unexpected, baseline.total, candidate.total
+                );
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                warn!("Canary {} failed on {}: {}", self.candidate.name(), collection, e);
+                discrepancy.error = Some(e.to_string());
+            }
+        }
+
+        let mut discrepancies = self.discrepancies.lock().unwrap();
+        if discrepancies.len() == RECENT_DISCREPANCIES {
+            discrepancies.pop_front();
+        }
+        discrepancies.push_back(discrepancy);
+    }
+}
+
+/// Documents only the baseline returned and documents only the candidate
+/// returned. Sorted results must agree position by position; unsorted pages
+/// are not compared at all.
+fn differences(query: &QueryRequest, baseline: &PathResult, candidate: &PathResult) -> (usize, usize) {
+    if query.sort.is_some() {
+        let differing = baseline.documents.iter().zip(&candidate.documents).filter(|(a, b)| a != b).count();
+        let missing = differing + baseline.documents.len().saturating_sub(candidate.documents.len());
+        let unexpected = differing + candidate.documents.len().saturating_sub(baseline.documents.len());
+        return (missing, unexpected);
+    }
+    if query.limit.is_some() || query.offset.is_some() {
+        return (0, 0);
+    }
+
+    let canonical = |documents: &[Value]| {
+        let mut documents: Vec<String> = documents.iter().map(Value::to_string).collect();
+        documents.sort();
+        documents
+    };
+    let (baseline, candidate) = (canonical(&baseline.documents), canonical(&candidate.documents));
+    let (mut missing, mut unexpected) = (0, 0);
+    let (mut b, mut c) = (0, 0);
+    while b < baseline.len() || c < candidate.len() {
+        match (baseline.get(b), candidate.get(c)) {
+            (Some(x), Some(y)) if x == y => (b, c) = (b + 1, c + 1),
+            (Some(x), Some(y)) if x < y => (missing, b) = (missing + 1, b + 1),
+            (Some(_), None) => (missing, b) = (missing + 1, b + 1),
+            _ => (unexpected, c) = (unexpected + 1, c + 1),
+        }
+    }
+    (missing, unexpected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Answers every query with fixed documents, optionally failing.
+    struct Fixed(&'static str, Vec<Value>);
+
+    impl QueryPath for Fixed {
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn execute<'a>(&'a self, _collection: &'a str, _query: &'a QueryRequest) -> QueryPathFuture<'a> {
+            Box::pin(async move {
+                if self.1.is_empty() {
+                    return Err(anyhow::anyhow!("index not built"));
+                }
+                Ok(PathResult { documents: self.1.clone(), total: self.1.len() })
+            })
+        }
+    }
+
+    fn query(sort: Option<Value>, limit: Option<usize>) -> QueryRequest {
+        QueryRequest { filter: Some(json!({ "status": "active" })), sort, limit, offset: None, cursor: None, limits: None, projection: None, as_of: None }
+    }
+
+    async fn settled(canary: &QueryCanary) -> CanaryReport {
+        for _ in 0..200 {
+            if canary.in_flight.load(Ordering::Acquire) == 0 {
+                return canary.report();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        panic!("canary comparisons never finished");
+    }
+
+    #[test]
+    fn test_differences_respect_sorting_and_pagination() {
+        let result = |ids: &[u64]| PathResult { documents: ids.iter().map(|id| json!({ "id": id })).collect(), total: ids.len() };
+        let sorted = query(Some(json!({ "id": 1 })), None);
+        let unsorted = query(None, None);
+        let paged = query(None, Some(2));
+
+        assert_eq!(differences(&unsorted, &result(&[1, 2, 3]), &result(&[3, 1, 2])), (0, 0));
+        assert_eq!(differences(&sorted, &result(&[1, 2, 3]), &result(&[3, 1, 2])), (3, 3));
+        assert_eq!(differences(&unsorted, &result(&[1, 2, 3]), &result(&[2, 3, 4, 5])), (1, 2));
+        assert_eq!(differences(&paged, &result(&[1, 2]), &result(&[3, 4])), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_canary_reports_discrepancies_before_cutover() {
+        let documents = vec![json!({ "id": 1 }), json!({ "id": 2 })];
+        let config = CanaryConfig { sample_rate: 0.5, min_samples: 2, max_latency_regression: f64::INFINITY, ..Default::default() };
+
+        // An identical candidate becomes ready once enough queries agree
+        let baseline: Arc<dyn QueryPath> = Arc::new(Fixed("current", documents.clone()));
+        let canary = Arc::new(QueryCanary::new(config.clone(), Arc::clone(&baseline), Arc::new(Fixed("index", documents.clone()))).unwrap());
+        for _ in 0..4 {
+            canary.observe("users", &query(None, None));
+        }
+        let report = settled(&canary).await;
+        assert_eq!((report.sampled, report.matched), (2, 2));
+        assert!(report.ready_for_cutover);
+
+        // A candidate missing documents or failing is reported
+        let canary = Arc::new(QueryCanary::new(config.clone(), Arc::clone(&baseline), Arc::new(Fixed("index", documents[..1].to_vec()))).unwrap());
+        canary.observe("users", &query(None, None));
+        canary.observe("users", &query(None, None));
+        let report = settled(&canary).await;
+        assert_eq!(report.mismatched, 1);
+        assert_eq!(report.discrepancies[0].missing, 1);
+        assert_eq!(report.discrepancies[0].candidate_total, Some(1));
+        assert!(!report.ready_for_cutover);
+
+        let canary = Arc::new(QueryCanary::new(config, baseline, Arc::new(Fixed("index", Vec::new()))).unwrap());
+        canary.observe("users", &query(None, None));
+        canary.observe("users", &query(None, None));
+        let report = settled(&canary).await;
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.discrepancies[0].error.as_deref(), Some("index not built"));
+    }
+}
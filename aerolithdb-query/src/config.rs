@@ -6,6 +6,8 @@
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::profiler::ProfilingConfig;
+
 /// Comprehensive query engine configuration for optimization and execution control.
 ///
 /// This configuration structure provides fine-grained control over query processing
@@ -30,6 +32,9 @@ use std::time::Duration;
 ///     execution_timeout: Duration::from_secs(600),
 ///     max_concurrent_queries: 200,
 ///     index_advisor: true,
+///     max_documents_scanned: Some(1_000_000),
+///     max_query_memory: Some(256 * 1024 * 1024),
+///     profiling: ProfilingConfig { slow_query_threshold_ms: 250, ..Default::default() },
 /// };
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +50,19 @@ pub struct QueryConfig {
     
     /// Enable automatic index recommenaerolithon based on query patterns
     pub index_advisor: bool,
+
+    /// Maximum documents a single query may read, unlimited when unset
+    #[serde(default)]
+    pub max_documents_scanned: Option<usize>,
+
+    /// Maximum estimated bytes of matches a single query may hold before
+    /// sorting and paging them, unlimited when unset
+    #[serde(default)]
+    pub max_query_memory: Option<usize>,
+
+    /// Per-collection query statistics and the slow-query log
+    #[serde(default)]
+    pub profiling: ProfilingConfig,
 }
 
 /// Configuration for the cost-based query optimizer.
@@ -89,6 +107,9 @@ impl Default for QueryConfig {
             execution_timeout: Duration::from_secs(300),
             max_concurrent_queries: 100,
             index_advisor: true,
+            max_documents_scanned: None,
+            max_query_memory: None,
+            profiling: ProfilingConfig::default(),
         }
     }
 }
@@ -0,0 +1,228 @@
+//! # Continuation Cursors
+//!
+//! Offset pagination counts documents from the start of the result, so a
+//! write between two pages shifts everything after it and large offsets
+//! re-read every document before the page. A cursor records where a page
+//! ended instead, and the next page starts right after that position
+//! whatever was written or deleted in between.
+//!
+//! - **Queries**: results are ordered by the query's sort specification,
+//!   ties broken by document ID, so a [`QueryCursor`] holds the sort key
+//!   and ID of the last document of a page
+//! - **Listings**: a [`ListCursor`] holds the last listed document, which
+//!   the listing resumes after
+//! - **Encoding**: cursors are opaque to clients, URL-safe base64 of a
+//!   small JSON document
+//! - **Validation**: a cursor only continues a query or listing ordered
+//!   like the one that issued it; others, and anything that does not
+//!   decode, are refused with an "Invalid cursor" error
+
+use std::cmp::Ordering;
+
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use aerolithdb_storage::{DocumentSortKey, ListOptions, ListedDocument};
+
+use crate::processing::DocumentSorter;
+use crate::types::QueryRequest;
+
+/// Position of a query result page's last document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryCursor {
+    /// Fingerprint of the sort specification the cursor was issued for
+    sort: String,
+
+    /// Values of the sort fields of the last document
+    key: Vec<Value>,
+
+    /// ID of the last document
+    id: String,
+}
+
+impl QueryCursor {
+    /// The cursor of a page ending with `document`.
+    pub(crate) fn after(document: &RankedDocument, sort: Option<&Value>) -> Self {
+        Self { sort: sort_fingerprint(sort), key: document.key.clone(), id: document.id.clone() }
+    }
+
+    /// Decode the cursor `query` continues from, if any, refusing cursors
+    /// issued for another sort specification.
+    pub fn for_query(query: &QueryRequest) -> Result<Option<Self>> {
+        let Some(token) = &query.cursor else {
+            return Ok(None);
+        };
+        let cursor: Self = decode(token)?;
+        if cursor.sort != sort_fingerprint(query.effective_sort().as_ref()) {
+            anyhow::bail!("Invalid cursor: issued for a different sort");
+        }
+        Ok(Some(cursor))
+    }
+
+    pub fn encode(&self) -> String {
+        encode(self)
+    }
+
+    /// Whether `document` comes after this position.
+    pub(crate) fn precedes(&self, document: &RankedDocument, sort: Option<&Value>) -> bool {
+        compare(&self.key, &self.id, &document.key, &document.id, sort) == Ordering::Less
+    }
+}
+
+/// Position of a listing page's last document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListCursor {
+    sort: DocumentSortKey,
+    descending: bool,
+
+    /// The last listed document
+    after: ListedDocument,
+}
+
+impl ListCursor {
+    /// The cursor of a page listed with `options` ending with `document`.
+    pub fn after(document: &ListedDocument, options: &ListOptions) -> Self {
+        Self { sort: options.sort, descending: options.descending, after: document.clone() }
+    }
+
+    /// Decode a listing cursor, refusing cursors issued for a listing
+    /// sorted differently from `options`.
+    pub fn decode(token: &str, options: &ListOptions) -> Result<Self> {
+        let cursor: Self = decode(token)?;
+        if cursor.sort != options.sort || cursor.descending != options.descending {
+            anyhow::bail!("Invalid cursor: issued for a different sort");
+        }
+        Ok(cursor)
+    }
+
+    pub fn encode(&self) -> String {
+        encode(self)
+    }
+
+    /// The document the listing resumes after.
+    pub fn into_document(self) -> ListedDocument {
+        self.after
+    }
+}
+
+/// A matching document with its position in the result.
+#[derive(Debug, Clone)]
+pub(crate) struct RankedDocument {
+    pub(crate) id: String,
+    pub(crate) key: Vec<Value>,
+    pub(crate) document: Value,
+}
+
+impl RankedDocument {
+    pub(crate) fn new(id: String, document: Value, sort: Option<&Value>) -> Self {
+        let key = sort.map(|sort| DocumentSorter::sort_key(&document, sort)).unwrap_or_default();
+        Self { id, key, document }
+    }
+}
+
+/// Order `matches` as `query` sorts them, skip past `after` and the
+/// query's offset, and keep one page of them.
+///
+/// Returns the page with the cursor of the next one when more matches
+/// follow it, here or, with `more_elsewhere`, in results not given.
+pub(crate) fn paginate(
+    mut matches: Vec<RankedDocument>,
+    query: &QueryRequest,
+    after: Option<&QueryCursor>,
+    more_elsewhere: bool,
+) -> (Vec<RankedDocument>, Option<String>) {
+    let sort = query.effective_sort();
+    let sort = sort.as_ref();
+    matches.sort_by(|a, b| compare(&a.key, &a.id, &b.key, &b.id, sort));
+
+    let start = after.map_or(0, |cursor| matches.partition_point(|document| !cursor.precedes(document, sort)));
+    let mut page: Vec<RankedDocument> =
+        matches.into_iter().skip(start.saturating_add(query.offset.unwrap_or(0))).collect();
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let more = more_elsewhere || page.len() > limit;
+    page.truncate(limit);
+
+    let next_cursor = page.last().filter(|_| more).map(|last| QueryCursor::after(last, sort).encode());
+    (page, next_cursor)
+}
+
+/// Order of two documents in a query result: by sort key, then by ID.
+fn compare(a_key: &[Value], a_id: &str, b_key: &[Value], b_id: &str, sort: Option<&Value>) -> Ordering {
+    sort.map_or(Ordering::Equal, |sort| DocumentSorter::compare_keys(a_key, b_key, sort))
+        .then_with(|| a_id.cmp(b_id))
+}
+
+fn sort_fingerprint(sort: Option<&Value>) -> String {
+    let sort = sort.map(Value::to_string).unwrap_or_default();
+    blake3::hash(sort.as_bytes()).to_hex()[..16].to_string()
+}
+
+fn encode<T: Serialize>(cursor: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).expect("cursors serialize to JSON"))
+}
+
+fn decode<T: DeserializeOwned>(token: &str) -> Result<T> {
+    let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|e| anyhow::anyhow!("Invalid cursor: {}", e))?;
+    serde_json::from_slice(&bytes).map_err(|e| anyhow::anyhow!("Invalid cursor: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ranked(documents: &[(&str, i64)], sort: Option<&Value>) -> Vec<RankedDocument> {
+        documents
+            .iter()
+            .map(|(id, rank)| RankedDocument::new(id.to_string(), json!({ "rank": rank }), sort))
+            .collect()
+    }
+
+    fn ids(page: &[RankedDocument]) -> Vec<&str> {
+        page.iter().map(|document| document.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_pages_continue_after_the_cursor_despite_inserts() {
+        let query = QueryRequest::new().with_sort(json!({ "rank": -1 })).with_pagination(2, 0);
+        let sort = query.sort.as_ref();
+
+        let (page, next_cursor) = paginate(ranked(&[("a", 1), ("b", 3), ("c", 2), ("d", 2)], sort), &query, None, false);
+        assert_eq!(ids(&page), ["b", "c"]);
+
+        // A document sorting before the cursor does not shift the next page
+        let next = QueryRequest { cursor: next_cursor, ..query.clone() };
+        let cursor = QueryCursor::for_query(&next).unwrap();
+        let matches = ranked(&[("a", 1), ("b", 3), ("c", 2), ("d", 2), ("e", 5)], sort);
+        let (page, next_cursor) = paginate(matches, &next, cursor.as_ref(), false);
+        assert_eq!(ids(&page), ["d", "a"]);
+        assert!(next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_unsorted_queries_page_by_id() {
+        let query = QueryRequest::new().with_pagination(1, 1);
+        let (page, next_cursor) = paginate(ranked(&[("b", 0), ("c", 0), ("a", 0)], None), &query, None, false);
+        assert_eq!(ids(&page), ["b"]);
+        assert!(next_cursor.is_some());
+
+        let (_, next_cursor) = paginate(ranked(&[("a", 0)], None), &query.clone().with_pagination(1, 0), None, true);
+        assert!(next_cursor.is_some());
+    }
+
+    #[test]
+    fn test_foreign_and_malformed_cursors_are_refused() {
+        let query = QueryRequest::new().with_sort(json!({ "rank": 1 })).with_pagination(1, 0);
+        let (_, next_cursor) = paginate(ranked(&[("a", 1), ("b", 2)], query.sort.as_ref()), &query, None, false);
+
+        let resorted = QueryRequest { cursor: next_cursor, ..QueryRequest::new().with_sort(json!({ "rank": -1 })) };
+        assert!(QueryCursor::for_query(&resorted).unwrap_err().to_string().starts_with("Invalid cursor"));
+
+        let garbled = QueryRequest { cursor: Some("not a cursor".to_string()), ..query };
+        assert!(QueryCursor::for_query(&garbled).unwrap_err().to_string().starts_with("Invalid cursor"));
+    }
+}
@@ -0,0 +1,324 @@
+//! # Distributed Query Execution
+//!
+//! Scatter-gather execution of queries across the cluster. Every member is
+//! asked for the first `offset + limit` matches among the documents it
+//! holds the primary copy of, so replicas are not counted twice. The
+//! answers are merged with this node's, re-sorted, and the query's offset
+//! and limit applied to the merged list.
+//!
+//! - **Transport**: peers are asked through a [`PeerQueryTransport`]; the
+//!   default one posts to [`PEER_QUERY_PATH`] on the REST API of each
+//!   member, which answers from the primary copies it holds
+//! - **Degradation**: members without an address, that fail or that do not
+//!   answer within [`PEER_QUERY_TIMEOUT`] are listed in
+//!   [`DistributedQueryResult::unreachable_nodes`] and the result is marked
+//!   partial instead of failing the query
+//! - **Totals**: the total is the sum of the totals of the members that
+//!   answered
+//! - **Cursors**: members skip past the query's cursor themselves; the
+//!   merged page has a next cursor when matches were left out of it here or
+//!   on any member
+//! - **Text search**: members score `$text` matches against their own
+//!   documents, and the merged page is ordered by those scores
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use aerolithdb_storage::ClusterNode;
+
+use crate::cursor::{self, RankedDocument};
+use crate::types::{QueryRequest, QueryResult};
+
+/// Path of the REST endpoint answering queries from the primary copies a
+/// node holds, followed by the collection
+pub const PEER_QUERY_PATH: &str = "/api/v1/internal/query";
+
+/// How long a member has to answer before it is reported unreachable
+pub const PEER_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Future returned by a [`PeerQueryTransport`]
+pub type PeerQueryFuture<'a> = Pin<Box<dyn Future<Output = Result<PartialQueryResult>> + Send + 'a>>;
+
+/// Matches of a query among the documents of one member.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialQueryResult {
+    /// The page of documents returned
+    pub documents: Vec<Value>,
+
+    /// IDs of the documents, in the same order
+    #[serde(default)]
+    pub ids: Vec<String>,
+
+    /// Matches before limit and offset were applied
+    pub total: usize,
+
+    /// Cursor of the page after this one, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl From<QueryResult> for PartialQueryResult {
+    fn from(result: QueryResult) -> Self {
+        Self { documents: result.documents, ids: result.ids, total: result.total, next_cursor: result.next_cursor }
+    }
+}
+
+/// Asks other cluster members to run a query over their own documents.
+pub trait PeerQueryTransport: Send + Sync {
+    fn query<'a>(&'a self, node: &'a ClusterNode, collection: &'a str, query: &'a QueryRequest) -> PeerQueryFuture<'a>;
+}
+
+/// Posts queries to the REST API of each member.
+#[derive(Debug, Clone)]
+pub struct HttpQueryTransport {
+    client: reqwest::Client,
+}
+
+impl HttpQueryTransport {
+    pub fn new(timeout: Duration) -> Result<Self> {
+        Ok(Self { client: reqwest::Client::builder().timeout(timeout).build()? })
+    }
+}
+
+impl PeerQueryTransport for HttpQueryTransport {
+    fn query<'a>(&'a self, node: &'a ClusterNode, collection: &'a str, query: &'a QueryRequest) -> PeerQueryFuture<'a> {
+        Box::pin(async move {
+            let address = node
+                .address
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Cluster node {} has no address", node.id))?;
+            let response = self
+                .client
+                .post(format!("{}{}/{}", address.trim_end_matches('/'), PEER_QUERY_PATH, collection))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(query)?)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!("{} refused the query: {}", node.id, response.status()));
+            }
+            Ok(serde_json::from_slice(&response.bytes().await?)?)
+        })
+    }
+}
+
+/// Result of a query over the documents of every cluster member.
+#[derive(Debug, Clone, Serialize)]
+pub struct DistributedQueryResult {
+    /// The page of documents returned
+    pub documents: Vec<Value>,
+
+    /// IDs of the documents, in the same order
+    pub ids: Vec<String>,
+
+    /// Matches before limit and offset were applied, on the members that
+    /// answered
+    pub total: usize,
+
+    /// Time spent asking every member and merging their answers
+    pub execution_time: Duration,
+
+    /// Cursor of the page after this one, if any
+    pub next_cursor: Option<String>,
+
+    /// Whether some members did not answer, so matches may be missing
+    pub partial: bool,
+
+    /// Ids of the members that did not answer
+    pub unreachable_nodes: Vec<String>,
+}
+
+/// The transport the engine asks peers through.
+pub(crate) struct PeerQueries {
+    transport: RwLock<Arc<dyn PeerQueryTransport>>,
+}
+
+impl PeerQueries {
+    pub(crate) fn new() -> Result<Self> {
+        let transport: Arc<dyn PeerQueryTransport> = Arc::new(HttpQueryTransport::new(PEER_QUERY_TIMEOUT)?);
+        Ok(Self { transport: RwLock::new(transport) })
+    }
+
+    pub(crate) fn transport(&self) -> Arc<dyn PeerQueryTransport> {
+        Arc::clone(&self.transport.read().unwrap())
+    }
+
+    pub(crate) fn set_transport(&self, transport: Arc<dyn PeerQueryTransport>) {
+        *self.transport.write().unwrap() = transport;
+    }
+}
+
+impl std::fmt::Debug for PeerQueries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeerQueries").finish_non_exhaustive()
+    }
+}
+
+/// The query each member runs: the first `offset + limit` matches after
+/// the cursor, since any of them may end up on the requested page once
+/// merged. Members return whole documents, which are merged by their sort
+/// fields and projected afterwards.
+pub(crate) fn member_query(query: &QueryRequest) -> QueryRequest {
+    QueryRequest {
+        filter: query.filter.clone(),
+        sort: query.sort.clone(),
+        limit: query.limit.map(|limit| limit.saturating_add(query.offset.unwrap_or(0))),
+        offset: None,
+        cursor: query.cursor.clone(),
+        limits: query.limits,
+        projection: None,
+        as_of: query.as_of,
+    }
+}
+
+/// Ask every peer for its matches at once, returning the answers and the
+/// ids of the peers that did not answer.
+pub(crate) async fn scatter(
+    transport: &dyn PeerQueryTransport,
+    peers: &[ClusterNode],
+    collection: &str,
+    query: &QueryRequest,
+) -> (Vec<PartialQueryResult>, Vec<String>) {
+    let answers = futures::future::join_all(peers.iter().map(|node| async move {
+        let answer = match tokio::time::timeout(PEER_QUERY_TIMEOUT, transport.query(node, collection, query)).await {
+            Ok(answer) => answer,
+            Err(_) => Err(anyhow::anyhow!("no answer within {:?}", PEER_QUERY_TIMEOUT)),
+        };
+        (node, answer)
+    }))
+    .await;
+
+    let mut partials = Vec::with_capacity(answers.len());
+    let mut unreachable = Vec::new();
+    for (node, answer) in answers {
+        match answer {
+            Ok(partial) => partials.push(partial),
+            Err(e) => {
+                warn!("Cluster node {} did not answer a query on {}: {}", node.id, collection, e);
+                unreachable.push(node.id.clone());
+            }
+        }
+    }
+    (partials, unreachable)
+}
+
+/// Merge the answers of the members, already past the query's cursor, into
+/// the page `query` asked for, with the combined total.
+pub(crate) fn gather(query: &QueryRequest, partials: Vec<PartialQueryResult>) -> PartialQueryResult {
+    let total = partials.iter().map(|partial| partial.total).sum();
+    let more_elsewhere = partials.iter().any(|partial| partial.next_cursor.is_some());
+    let sort = query.effective_sort();
+    let matches = partials
+        .into_iter()
+        .flat_map(|partial| partial.ids.into_iter().zip(partial.documents))
+        .map(|(id, document)| RankedDocument::new(id, document, sort.as_ref()))
+        .collect();
+
+    let (page, next_cursor) = cursor::paginate(matches, query, None, more_elsewhere);
+    let (ids, documents) = page.into_iter().map(|ranked| (ranked.id, ranked.document)).unzip();
+    PartialQueryResult { documents, ids, total, next_cursor }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::QueryCursor;
+    use chrono::Utc;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Answers with fixed ranks per node, failing for unknown nodes.
+    struct Members(HashMap<&'static str, Vec<u64>>);
+
+    impl PeerQueryTransport for Members {
+        fn query<'a>(&'a self, node: &'a ClusterNode, _collection: &'a str, query: &'a QueryRequest) -> PeerQueryFuture<'a> {
+            Box::pin(async move {
+                let ranks = self.0.get(node.id.as_str()).ok_or_else(|| anyhow::anyhow!("connection refused"))?;
+                Ok(answer(&node.id, ranks, query))
+            })
+        }
+    }
+
+    /// A member's answer over documents `{node}-{rank}` with these ranks.
+    fn answer(node: &str, ranks: &[u64], query: &QueryRequest) -> PartialQueryResult {
+        let matches = ranks
+            .iter()
+            .map(|rank| RankedDocument::new(format!("{}-{}", node, rank), json!({ "rank": rank }), query.sort.as_ref()))
+            .collect::<Vec<_>>();
+        let total = matches.len();
+        let after = QueryCursor::for_query(query).unwrap();
+        let (page, next_cursor) = cursor::paginate(matches, query, after.as_ref(), false);
+        let (ids, documents) = page.into_iter().map(|ranked| (ranked.id, ranked.document)).unzip();
+        PartialQueryResult { documents, ids, total, next_cursor }
+    }
+
+    fn node(id: &str) -> ClusterNode {
+        ClusterNode { id: id.to_string(), address: Some(format!("http://{}:8080", id)), joined_at: Utc::now() }
+    }
+
+    fn ranks(documents: &[Value]) -> Vec<u64> {
+        documents.iter().map(|document| document["rank"].as_u64().unwrap()).collect()
+    }
+
+    fn by_rank() -> QueryRequest {
+        QueryRequest::new().with_sort(json!({ "rank": 1 }))
+    }
+
+    #[test]
+    fn test_member_query_covers_the_requested_page() {
+        let query = by_rank().with_pagination(10, 20).with_cursor("abc");
+        let scoped = member_query(&query);
+        assert_eq!(scoped.limit, Some(30));
+        assert_eq!(scoped.offset, None);
+        assert_eq!(scoped.sort, query.sort);
+        assert_eq!(scoped.cursor, query.cursor);
+
+        let unbounded = QueryRequest { limit: None, ..query };
+        assert_eq!(member_query(&unbounded).limit, None);
+    }
+
+    #[tokio::test]
+    async fn test_results_are_merged_resorted_and_paged_globally() {
+        let members = Members(HashMap::from([("node-b", vec![2, 5, 8]), ("node-c", vec![1, 6, 7])]));
+        let peers = [node("node-b"), node("node-c")];
+
+        let query = by_rank().with_pagination(3, 2);
+        let scoped = member_query(&query);
+        let (mut partials, unreachable) = scatter(&members, &peers, "items", &scoped).await;
+        assert!(unreachable.is_empty());
+        partials.push(answer("node-a", &[3, 4], &scoped));
+
+        let merged = gather(&query, partials);
+        assert_eq!(ranks(&merged.documents), vec![3, 4, 5]);
+        assert_eq!(merged.ids, vec!["node-a-3", "node-a-4", "node-b-5"]);
+        assert_eq!(merged.total, 8);
+
+        // The next page continues after the cursor on every member
+        let next = by_rank().with_pagination(3, 0).with_cursor(merged.next_cursor.unwrap());
+        let scoped = member_query(&next);
+        let (mut partials, _) = scatter(&members, &peers, "items", &scoped).await;
+        partials.push(answer("node-a", &[3, 4], &scoped));
+        let merged = gather(&next, partials);
+        assert_eq!(ranks(&merged.documents), vec![6, 7, 8]);
+        assert!(merged.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_members_are_reported() {
+        let members = Members(HashMap::from([("node-b", vec![1])]));
+        let query = QueryRequest::new();
+
+        let (partials, unreachable) = scatter(&members, &[node("node-b"), node("node-c")], "items", &query).await;
+        assert_eq!(unreachable, vec!["node-c".to_string()]);
+        let merged = gather(&query, partials);
+        assert_eq!(ranks(&merged.documents), vec![1]);
+        assert_eq!(merged.total, 1);
+    }
+}
@@ -4,18 +4,59 @@
 //! Provides high-level interfaces for document operations and query execution.
 
 use anyhow::Result;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
 use serde_json;
 
-use aerolithdb_cache::IntelligentCacheSystem;
+use aerolithdb_cache::{
+    CacheKey, CacheLayer, CachePeerRequest, CachePeerResponse, CachedQueryResult, DocumentEvent, HttpCacheTransport,
+    IntelligentCacheSystem, PrefetchFuture, PrefetchSource, QueryFingerprint,
+};
 use aerolithdb_security::SecurityFramework;
-use aerolithdb_storage::StorageHierarchy;
+use aerolithdb_storage::{
+    BackupKind, BackupManifest, ClusterNode, Collection, CollectionConfig, CollectionInfo, CollectionStatistics, CompressionDictionary, DocumentPage, ExpirationEvent, HeavyHitter, Job, JobState,
+    LegalHold, LegalHoldEvent, ListOptions, MembershipChange, RebalancePlan, TransferredDocument,
+    ReadConsistency, ResidencyViolation, RestoreReport, ScrubReport, StorageHierarchy, StorageUsage, TierMigrationMetrics, TierMigrationReport, TierRule, WormPolicy, WriteOptions,
+};
 
+use crate::aggregation::{AggregationPipeline, AggregationResult};
+use crate::canary::{CanaryConfig, CanaryReport, PlannerPath, QueryCanary, QueryPath};
 use crate::config::QueryConfig;
+use crate::execution::{QueryAborted, QueryContext, QueryLimits, RunningQueries, RunningQuery};
+use crate::distributed::{self, DistributedQueryResult, PartialQueryResult, PeerQueries, PeerQueryTransport};
+use crate::fixtures::{FixtureLoader, FixtureReport, IndexDefinition};
+use crate::indexes::SecondaryIndexes;
+use crate::schema::{CollectionSchema, SchemaRegistry, ValidationMode};
+use crate::planner::{AccessPath, PlannerMode, QueryPlan, QueryPlanner};
+use crate::profiler::{query_shape, QueryProfiler, QuerySample};
+use crate::projection::Projection;
 use crate::types::{QueryRequest, QueryResult};
-use crate::processing::{DocumentFilter, DocumentSorter, DocumentPaginator};
+use crate::filter::CompiledFilter;
+use crate::text::{TextSearch, SCORE_FIELD};
+use crate::cursor::{self, QueryCursor, RankedDocument};
 use crate::stats::QueryStats;
+use crate::sessions::{SessionManager, SESSION_SWEEP_INTERVAL};
+use crate::subscriptions::{ChangeKind, DocumentChange, QueryDelta, SubscriptionManager};
+use crate::sync::SyncManager;
+
+/// How often documents past their collection's retention are deleted
+const COLLECTION_RETENTION_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// How often documents past their time to live are deleted or archived
+const DOCUMENT_EXPIRATION_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often collections are checked for stale compression dictionaries
+const COMPRESSION_DICTIONARY_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Loads documents the cache predicts will be read next from storage.
+struct StorageDocumentSource(Arc<StorageHierarchy>);
+
+impl PrefetchSource for StorageDocumentSource {
+    fn fetch<'a>(&'a self, key: &'a CacheKey) -> PrefetchFuture<'a> {
+        Box::pin(async move { Ok(self.0.get_document(&key.collection, &key.document_id).await?.data) })
+    }
+}
 
 /// Comprehensive distributed query processing engine.
 ///
@@ -61,6 +102,50 @@ pub struct QueryEngine {
     
     /// Security framework for access control and audit logging
     security: Arc<SecurityFramework>,
+    
+    /// Client sessions owning ephemeral documents
+    sessions: Arc<SessionManager>,
+
+    /// Change tracking for collections synced with edge replicas
+    sync: Arc<SyncManager>,
+
+    /// Secondary indexes declared for collections
+    indexes: Arc<SecondaryIndexes>,
+
+    /// JSON Schemas documents written to collections are validated against
+    schemas: SchemaRegistry,
+
+    /// Comparison of sampled queries against a candidate path, if running
+    canary: std::sync::RwLock<Option<Arc<QueryCanary>>>,
+
+    /// How other cluster members are asked for their matches
+    peers: PeerQueries,
+
+    /// How other members' shards of the network cache are reached, if the
+    /// cache has a network layer
+    cache_peers: Option<Arc<HttpCacheTransport>>,
+
+    /// Queries and aggregations being executed, by query ID
+    running: RunningQueries,
+
+    /// Live queries fed the changes written through the engine
+    subscriptions: Arc<SubscriptionManager>,
+
+    /// Per-collection query statistics and the slow-query log
+    profiler: Arc<QueryProfiler>,
+}
+
+/// A live query: the matches it started from and the receiver of the
+/// deltas to them.
+#[derive(Debug)]
+pub struct LiveQuery {
+    pub id: String,
+    pub collection: String,
+
+    /// Matches when the live query was subscribed, as the query would
+    /// return them
+    pub initial: QueryResult,
+    pub deltas: tokio::sync::mpsc::Receiver<QueryDelta>,
 }
 
 impl QueryEngine {
@@ -106,11 +191,39 @@ impl QueryEngine {
             return Err(anyhow::anyhow!("max_concurrent_queries must be greater than 0"));
         }
 
+        // Predicted documents are prefetched from the storage tiers
+        cache.set_prefetch_source(Arc::new(StorageDocumentSource(Arc::clone(&storage))));
+
+        // Other members' shards of the network cache are reached over their
+        // REST API
+        let cache_config = cache.config();
+        let cache_peers = if cache_config.hierarchy.contains(&CacheLayer::Network) {
+            let transport = Arc::new(HttpCacheTransport::new(cache_config.network.request_timeout)?);
+            cache.set_peer_transport(transport.clone())?;
+            Some(transport)
+        } else {
+            None
+        };
+
         let engine = Self {
+            profiler: Arc::new(QueryProfiler::new(config.profiling.clone())),
             config,
+            sessions: Arc::new(SessionManager::new(Arc::clone(&storage))),
+            sync: Arc::new(
+                SyncManager::open(Arc::clone(&storage))
+                    .await?
+                    .with_query_results(Arc::clone(cache.query_results())),
+            ),
+            indexes: Arc::new(SecondaryIndexes::new(Arc::clone(&storage))),
+            schemas: SchemaRegistry::new(Arc::clone(&storage)),
             storage,
             cache,
             security,
+            canary: std::sync::RwLock::new(None),
+            peers: PeerQueries::new()?,
+            cache_peers,
+            running: RunningQueries::default(),
+            subscriptions: Arc::new(SubscriptionManager::new()),
         };        Ok(engine)
     }
 
@@ -119,9 +232,102 @@ impl QueryEngine {
     /// Performs comprehensive startup procedures including subsystem initialization,
     /// optimizer preparation, and performance monitoring setup.
     pub async fn start(&self) -> Result<()> {
+        // The network cache starts out with the members known from before
+        self.sync_cache_peers()?;
+
+        // Delete documents past the retention of their collection
+        let storage = Arc::clone(&self.storage);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COLLECTION_RETENTION_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = storage.enforce_collection_retention().await {
+                    tracing::warn!("Collection retention sweep failed: {}", e);
+                }
+            }
+        });
+
+        // Delete or archive documents past their time to live, dropping
+        // cached results of the deleted ones and telling live queries
+        let storage = Arc::clone(&self.storage);
+        let cache = Arc::clone(&self.cache);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DOCUMENT_EXPIRATION_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                match storage.expire_documents().await {
+                    Ok(report) => {
+                        for event in report.expired {
+                            if let ExpirationEvent::DocumentDeleted { collection, document_id, .. } = event {
+                                cache.query_results().handle_event(&DocumentEvent::DocumentDeleted {
+                                    collection: collection.clone(),
+                                    document_id: document_id.clone(),
+                                });
+                                subscriptions.publish(DocumentChange { collection, document_id, kind: ChangeKind::Deleted, document: None });
+                            }
+                        }
+                    }
+                    Err(e) => tracing::warn!("Document expiration sweep failed: {}", e),
+                }
+            }
+        });
+
+        // Train compression dictionaries for Zstd collections without a
+        // recent one
+        let storage = Arc::clone(&self.storage);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COMPRESSION_DICTIONARY_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = storage.retrain_compression_dictionaries().await {
+                    tracing::warn!("Compression dictionary retraining failed: {}", e);
+                }
+            }
+        });
+
+        // Expire lapsed sessions and delete their ephemeral documents
+        let sessions = Arc::clone(&self.sessions);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = sessions.expire_sessions().await {
+                    tracing::warn!("Session expiry sweep failed: {}", e);
+                }
+            }
+        });
+
         Ok(())
     }
 
+    /// Ask other cluster members for their matches with `transport`
+    /// instead of their REST API.
+    pub fn with_peer_query_transport(self, transport: Arc<dyn PeerQueryTransport>) -> Self {
+        self.peers.set_transport(transport);
+        self
+    }
+
+    /// Session manager for ephemeral documents bound to client heartbeats.
+    pub fn sessions(&self) -> Arc<SessionManager> {
+        Arc::clone(&self.sessions)
+    }
+
+    /// Replica state for offline-first sync with edge replicas.
+    pub fn sync(&self) -> Arc<SyncManager> {
+        Arc::clone(&self.sync)
+    }
+
+    /// Cache the engine reads through, e.g. to export its statistics.
+    pub fn cache(&self) -> Arc<IntelligentCacheSystem> {
+        Arc::clone(&self.cache)
+    }
+
+    /// Seed collections from a fixtures directory, skipping anything already applied.
+    pub async fn load_fixtures(&self, dir: &std::path::Path) -> Result<FixtureReport> {
+        FixtureLoader::new(Arc::clone(&self.storage)).load_dir(dir).await
+    }
+
     /// Gracefully stop the query engine and cleanup resources.
     ///
     /// Performs orderly shutdown of all subsystems and ensures proper
@@ -138,11 +344,11 @@ impl QueryEngine {
     /// # Query Processing Pipeline
     /// 1. **Security Validation**: Verify access permissions and audit logging
     /// 2. **Query Optimization**: Cost-based optimization and execution planning
-    /// 3. **Cache Consultation**: Check for cached results and partial matches
+    /// 3. **Cache Consultation**: Return the cached result of an identical query
     /// 4. **Index Selection**: Choose optimal indices for query execution
     /// 5. **Distributed Execution**: Execute query across cluster nodes if needed
     /// 6. **Result Processing**: Apply sorting, pagination, and transformations
-    /// 7. **Cache Population**: Update cache with results for future queries
+    /// 7. **Cache Population**: Cache the result until its collection changes
     /// 8. **Performance Analysis**: Collect metrics for optimization feedback
     ///
     /// # Arguments
@@ -159,6 +365,10 @@ impl QueryEngine {
     ///     sort: Some(json!({"created_at": -1})),
     ///     limit: Some(50),
     ///     offset: Some(100),
+    ///     cursor: None,
+    ///     limits: None,
+    ///     projection: Some(json!({"name": 1, "email": 1})),
+    ///     as_of: None,
     /// };
     ///    /// let result = engine.query_documents("users", &query).await?;
     /// println!("Found {} active users", result.total);
@@ -168,74 +378,363 @@ impl QueryEngine {
         collection: &str,
         query: &QueryRequest,
     ) -> Result<QueryResult> {
-        let start_time = Instant::now();        // Get all documents in the collection first
-        // Production enhancement: Index-based query execution planned for improved performance
-        let document_ids = match self.storage.list_documents(collection, None, None).await {
-            Ok(ids) => ids,
-            Err(_) => {
-                return Ok(QueryResult::empty(start_time.elapsed()));
-            }
-        };        let mut matching_documents = Vec::new();        let mut from_cache_count = 0;
-        let mut _scanned_count = 0;
-
-        // Fetch and filter documents with optimization
-        // Current implementation: Basic document retrieval with filtering
-        // Future enhancements planned:
-        // - Index scans for filtered fields
-        // - Parallel document retrieval 
-        // - Vectorized filter evaluation
-        // - Early termination for LIMIT queries
-        for doc_id in &document_ids {
-            _scanned_count += 1;
-            
-            match self.storage.get_document(collection, doc_id).await {
-                Ok(storage_result) => {
-                    if let Some(document) = storage_result.data {
-                        // Apply filter if provided
-                        if let Some(filter) = &query.filter {
-                            if !DocumentFilter::matches_filter(&document, filter) {
-                                continue;
-                            }
-                        }
-
-                        matching_documents.push(document);
+        let start_time = Instant::now();
+        if query.as_of.is_some() {
+            return self.query_documents_as_of(collection, query, start_time).await;
+        }
 
-                        // Track cache performance
-                        if storage_result.cache_hit {
-                            from_cache_count += 1;
-                        }
-                    }
-                }
-                Err(_) => {
-                    continue; // Skip documents that can't be retrieved
-                }
-            }
+        // Malformed filters, cursors and projections are refused rather
+        // than matching nothing
+        if let Some(filter) = &query.filter {
+            CompiledFilter::compile(filter)?;
+            self.check_text_index(collection, filter).await?;
         }
+        QueryCursor::for_query(query)?;
+        query.projection.as_ref().map(Projection::compile).transpose()?;
 
-        // Apply sorting if specified
-        if let Some(sort) = &query.sort {
-            DocumentSorter::sort_documents(&mut matching_documents, sort);
+        // Cached answers are sampled too, the canary runs both paths itself
+        if let Some(canary) = self.canary.read().unwrap().as_ref() {
+            canary.observe(collection, query);
         }
 
-        let total = matching_documents.len();
+        // Identical queries are answered from the query result cache until
+        // a write to the collection invalidates them
+        let results = self.cache.query_results();
+        let request = serde_json::to_value(query)?;
+        let fingerprint = QueryFingerprint::new(collection, &request);
+        if let Some(cached) = results.get(&fingerprint) {
+            self.profiler.record(
+                QuerySample {
+                    query_id: uuid::Uuid::new_v4().to_string(),
+                    collection: collection.to_string(),
+                    kind: "query",
+                    duration: start_time.elapsed(),
+                    documents_scanned: 0,
+                    documents_returned: cached.documents.len(),
+                    from_cache: true,
+                    failed: false,
+                    shape: query_shape(&request),
+                },
+                || (request, None),
+            );
+            return Ok(QueryResult {
+                documents: cached.documents,
+                ids: cached.ids,
+                total: cached.total,
+                execution_time: start_time.elapsed(),
+                from_cache: true,
+                next_cursor: cached.next_cursor,
+            });
+        }
+        let generation = results.generation(collection);
 
-        // Apply pagination
-        let paginated_documents = DocumentPaginator::paginate_documents(
-            matching_documents,
-            query.offset,
-            query.limit,
-        );
+        // Queries stopped by their limits fail, other failures match nothing
+        let context = self.running.register(QueryContext::new(collection, "query", self.limits(query.limits.as_ref())));
+        let execution = execute_query(&self.storage, &self.indexes, collection, query, self.planner_mode(), false, &context);
+        let mut execution = match context.run(execution).await {
+            Ok(execution) => execution,
+            Err(e) => {
+                self.profile(&context, start_time, None, None, || request);
+                return match e.is::<QueryAborted>() {
+                    true => Err(e),
+                    false => Ok(QueryResult::empty(start_time.elapsed())),
+                };
+            }
+        };
+        let plan = execution.plan.take();
+        self.profile(&context, start_time, Some(execution.documents.len()), plan, || request);
+        let total = execution.total;
+        let cached = CachedQueryResult {
+            documents: execution.documents,
+            ids: execution.ids,
+            total,
+            next_cursor: execution.next_cursor,
+        };
+        results.put(fingerprint, cached.clone(), generation);
 
         let result = QueryResult {
-            documents: paginated_documents,
+            documents: cached.documents,
+            ids: cached.ids,
             total,
             execution_time: start_time.elapsed(),
-            from_cache: from_cache_count > 0,
+            from_cache: execution.from_cache > 0,
+            next_cursor: cached.next_cursor,
         };
 
         Ok(result)
     }
 
+    /// Run a query against a collection as it was at `as_of`. Its results
+    /// are not cached, since the versions they are read from change as
+    /// documents are written, and reads reaching past the retained history
+    /// fail instead of matching nothing.
+    async fn query_documents_as_of(&self, collection: &str, query: &QueryRequest, start_time: Instant) -> Result<QueryResult> {
+        let context = self.running.register(QueryContext::new(collection, "query", self.limits(query.limits.as_ref())));
+        let execution = context
+            .run(execute_query(&self.storage, &self.indexes, collection, query, self.planner_mode(), false, &context))
+            .await?;
+        Ok(QueryResult {
+            documents: execution.documents,
+            ids: execution.ids,
+            total: execution.total,
+            execution_time: start_time.elapsed(),
+            from_cache: false,
+            next_cursor: execution.next_cursor,
+        })
+    }
+
+    /// Execute a query over the documents this node holds the primary copy
+    /// of, bypassing the query result cache.
+    ///
+    /// This is this node's share of a query spanning the cluster; replicas
+    /// are left to the nodes holding their primary copies so that no
+    /// document is counted twice.
+    pub async fn query_primary_copies(&self, collection: &str, query: &QueryRequest) -> Result<QueryResult> {
+        let start_time = Instant::now();
+        let context = self.running.register(QueryContext::new(collection, "query", self.limits(query.limits.as_ref())));
+        let execution = context
+            .run(execute_query(&self.storage, &self.indexes, collection, query, self.planner_mode(), true, &context))
+            .await;
+        let mut execution = match execution {
+            Ok(execution) => execution,
+            Err(e) => {
+                self.profile(&context, start_time, None, None, || serde_json::to_value(query).unwrap_or_default());
+                return Err(e);
+            }
+        };
+        let plan = execution.plan.take();
+        self.profile(&context, start_time, Some(execution.documents.len()), plan, || {
+            serde_json::to_value(query).unwrap_or_default()
+        });
+        Ok(QueryResult {
+            documents: execution.documents,
+            ids: execution.ids,
+            total: execution.total,
+            execution_time: start_time.elapsed(),
+            from_cache: execution.from_cache > 0,
+            next_cursor: execution.next_cursor,
+        })
+    }
+
+    /// Execute a query over the documents of every cluster member.
+    ///
+    /// Every member runs the query over the documents it holds the primary
+    /// copy of; the matches are merged, re-sorted and paged as if they came
+    /// from one collection. Members that cannot be reached do
+    /// not fail the query, they are reported and the result marked partial.
+    /// On a single node this is [`QueryEngine::query_documents`].
+    pub async fn query_cluster(&self, collection: &str, query: &QueryRequest) -> Result<DistributedQueryResult> {
+        let start_time = Instant::now();
+
+        // Malformed filters, cursors and projections are refused before any
+        // member is asked
+        if let Some(filter) = &query.filter {
+            CompiledFilter::compile(filter)?;
+            self.check_text_index(collection, filter).await?;
+        }
+        QueryCursor::for_query(query)?;
+        let projection = query.projection.as_ref().map(Projection::compile).transpose()?;
+
+        let local_id = self.storage.node_id();
+        let peers: Vec<ClusterNode> = self.storage.cluster_nodes()?.into_iter().filter(|node| node.id != local_id).collect();
+        if peers.is_empty() {
+            let result = self.query_documents(collection, query).await?;
+            return Ok(DistributedQueryResult {
+                documents: result.documents,
+                ids: result.ids,
+                total: result.total,
+                execution_time: start_time.elapsed(),
+                next_cursor: result.next_cursor,
+                partial: false,
+                unreachable_nodes: Vec::new(),
+            });
+        }
+
+        let scoped = distributed::member_query(query);
+        let transport = self.peers.transport();
+        let (local, (mut partials, unreachable_nodes)) = tokio::join!(
+            self.query_primary_copies(collection, &scoped),
+            distributed::scatter(transport.as_ref(), &peers, collection, &scoped),
+        );
+        let local = local?;
+        partials.insert(0, PartialQueryResult::from(local));
+        let mut merged = distributed::gather(query, partials);
+        if let Some(projection) = &projection {
+            merged.documents = merged.documents.into_iter().map(|document| projection.apply(document)).collect();
+        }
+
+        Ok(DistributedQueryResult {
+            documents: merged.documents,
+            ids: merged.ids,
+            total: merged.total,
+            execution_time: start_time.elapsed(),
+            next_cursor: merged.next_cursor,
+            partial: !unreachable_nodes.is_empty(),
+            unreachable_nodes,
+        })
+    }
+
+    /// Wait until this node is current enough for a read of `collection` at
+    /// `consistency`; see [`ReadConsistency`].
+    pub async fn await_read_consistency(&self, collection: &str, consistency: ReadConsistency) -> Result<()> {
+        self.storage.await_read_consistency(collection, consistency).await
+    }
+
+    /// Execute a query once this node is current enough for a read at
+    /// `consistency`.
+    ///
+    /// Local reads run across the cluster as [`QueryEngine::query_cluster`]
+    /// does. Stronger reads are only served for strongly consistent
+    /// collections, every write of which each node applies, so they run
+    /// over this node's documents alone.
+    pub async fn query_with_consistency(
+        &self,
+        collection: &str,
+        query: &QueryRequest,
+        consistency: ReadConsistency,
+    ) -> Result<DistributedQueryResult> {
+        if consistency == ReadConsistency::Local {
+            return self.query_cluster(collection, query).await;
+        }
+        let start_time = Instant::now();
+        self.await_read_consistency(collection, consistency).await?;
+        let result = self.query_documents(collection, query).await?;
+        Ok(DistributedQueryResult {
+            documents: result.documents,
+            ids: result.ids,
+            total: result.total,
+            execution_time: start_time.elapsed(),
+            next_cursor: result.next_cursor,
+            partial: false,
+            unreachable_nodes: Vec::new(),
+        })
+    }
+
+    /// Run an aggregation pipeline over a collection.
+    ///
+    /// The pipeline is checked before anything is read, so malformed
+    /// stages fail with an "Invalid pipeline" error. Leading `$match`
+    /// stages filter documents as they are read; the remaining stages run
+    /// over the matches.
+    pub async fn aggregate(&self, collection: &str, pipeline: &[serde_json::Value]) -> Result<AggregationResult> {
+        self.aggregate_with_limits(collection, pipeline, None).await
+    }
+
+    /// Run an aggregation pipeline within the engine's query limits,
+    /// tightened by `limits`; see [`crate::execution`].
+    pub async fn aggregate_with_limits(
+        &self,
+        collection: &str,
+        pipeline: &[serde_json::Value],
+        limits: Option<&QueryLimits>,
+    ) -> Result<AggregationResult> {
+        let start_time = Instant::now();
+        let stages = pipeline;
+        let pipeline = AggregationPipeline::compile(pipeline)?;
+        let scan_limit = pipeline.scan_limit().unwrap_or(usize::MAX);
+
+        let context = self.running.register(QueryContext::new(collection, "aggregate", self.limits(limits)));
+        let gathered = context
+            .run(async {
+                let mut matching = Vec::new();
+                let mut scanned = 0;
+                for doc_id in self.storage.list_documents(collection, None, None).await? {
+                    if matching.len() >= scan_limit {
+                        break;
+                    }
+                    context.scan()?;
+                    let Some(document) = self.storage.get_document(collection, &doc_id).await?.data else {
+                        continue;
+                    };
+                    scanned += 1;
+                    if pipeline.matches_leading(&document) {
+                        context.hold(&document)?;
+                        matching.push(document);
+                    }
+                }
+                Ok((matching, scanned))
+            })
+            .await;
+        let request = || serde_json::Value::Array(stages.to_vec());
+        let (matching, scanned) = match gathered {
+            Ok(gathered) => gathered,
+            Err(e) => {
+                self.profile(&context, start_time, None, None, request);
+                return Err(e);
+            }
+        };
+
+        let documents = pipeline.run(matching);
+        self.profile(&context, start_time, Some(documents.len()), None, request);
+        Ok(AggregationResult {
+            documents,
+            scanned,
+            execution_time: start_time.elapsed(),
+        })
+    }
+
+    /// Queries and aggregations being executed, longest running first.
+    pub fn running_queries(&self) -> Vec<RunningQuery> {
+        self.running.list()
+    }
+
+    /// Cancel a running query or aggregation; it stops at its next document
+    /// and fails with a [`QueryAborted`] error.
+    ///
+    /// Returns the query if it was running.
+    pub fn cancel_query(&self, query_id: &str) -> Option<RunningQuery> {
+        let cancelled = self.running.cancel(query_id);
+        if let Some(query) = &cancelled {
+            tracing::info!("Cancelled {} {} on {}", query.kind, query.id, query.collection);
+        }
+        cancelled
+    }
+
+    /// Per-collection query statistics and the slow-query log.
+    pub fn profiler(&self) -> Arc<QueryProfiler> {
+        Arc::clone(&self.profiler)
+    }
+
+    /// Record a finished query with the profiler: the documents it
+    /// `returned`, or `None` if it failed, and the plan it ran with.
+    fn profile(
+        &self,
+        context: &QueryContext,
+        start_time: Instant,
+        returned: Option<usize>,
+        plan: Option<QueryPlan>,
+        request: impl FnOnce() -> serde_json::Value,
+    ) {
+        if !self.profiler.config().enabled {
+            return;
+        }
+        let request = request();
+        self.profiler.record(
+            QuerySample {
+                query_id: context.id().to_string(),
+                collection: context.collection().to_string(),
+                kind: context.kind(),
+                duration: start_time.elapsed(),
+                documents_scanned: context.documents_scanned(),
+                documents_returned: returned.unwrap_or(0),
+                from_cache: false,
+                failed: returned.is_none(),
+                shape: query_shape(&request),
+            },
+            || (request, plan),
+        );
+    }
+
+    /// The engine's query limits, tightened by those sent with a query.
+    fn limits(&self, query: Option<&QueryLimits>) -> QueryLimits {
+        QueryLimits {
+            timeout_ms: Some(self.config.execution_timeout.as_millis() as u64),
+            max_documents_scanned: self.config.max_documents_scanned,
+            max_memory_bytes: self.config.max_query_memory,
+        }
+        .tightened(query)
+    }
+
     /// Get database statistics with comprehensive system metrics.
     pub async fn get_stats(&self) -> Result<serde_json::Value> {
         QueryStats::collect_database_stats(
@@ -253,10 +752,35 @@ impl QueryEngine {
         document_id: &str,
         document: &serde_json::Value,
     ) -> Result<()> {
-        match self.storage.store_document(collection, document_id, document).await {
-            Ok(_storage_result) => Ok(()),
-            Err(e) => Err(e),
-        }
+        self.store_document_with_ttl(collection, document_id, document, None).await
+    }
+
+    /// Store a document that expires after `ttl`, if given.
+    pub async fn store_document_with_ttl(
+        &self,
+        collection: &str,
+        document_id: &str,
+        document: &serde_json::Value,
+        ttl: Option<std::time::Duration>,
+    ) -> Result<()> {
+        self.store_document_with_options(collection, document_id, document, WriteOptions { ttl, ..Default::default() }).await
+    }
+
+    /// Store a document as `options` asks.
+    pub async fn store_document_with_options(
+        &self,
+        collection: &str,
+        document_id: &str,
+        document: &serde_json::Value,
+        options: WriteOptions,
+    ) -> Result<()> {
+        self.schemas.validate(collection, document_id, document).await?;
+        self.sync.put_with_options(collection, document_id, document, options).await?;
+        self.emit(DocumentEvent::DocumentCreated {
+            collection: collection.to_string(),
+            document_id: document_id.to_string(),
+        }, Some(document)).await;
+        Ok(())
     }
 
     /// Retrieve a single document by ID.
@@ -265,7 +789,18 @@ impl QueryEngine {
         collection: &str,
         document_id: &str,
     ) -> Result<serde_json::Value> {
-        match self.storage.get_document(collection, document_id).await {
+        self.get_document_with_consistency(collection, document_id, ReadConsistency::Local).await
+    }
+
+    /// Retrieve a single document by ID once this node is current enough
+    /// for a read at `consistency`.
+    pub async fn get_document_with_consistency(
+        &self,
+        collection: &str,
+        document_id: &str,
+        consistency: ReadConsistency,
+    ) -> Result<serde_json::Value> {
+        match self.storage.get_document_with_consistency(collection, document_id, consistency).await {
             Ok(storage_result) => {
                 if let Some(document) = storage_result.data {
                     Ok(document)
@@ -277,6 +812,21 @@ impl QueryEngine {
         }
     }
 
+    /// Retrieve a single document as it was at `as_of`, from the version
+    /// history of its collection.
+    pub async fn get_document_as_of(
+        &self,
+        collection: &str,
+        document_id: &str,
+        as_of: chrono::DateTime<chrono::Utc>,
+    ) -> Result<serde_json::Value> {
+        self.storage
+            .get_document_as_of(collection, document_id, as_of)
+            .await?
+            .and_then(|storage_result| storage_result.data)
+            .ok_or_else(|| anyhow::anyhow!("Document not found"))
+    }
+
     /// Update a document in the collection.
     pub async fn update_document(
         &self,
@@ -284,10 +834,37 @@ impl QueryEngine {
         document_id: &str,
         document: &serde_json::Value,
     ) -> Result<()> {
-        match self.storage.store_document(collection, document_id, document).await {
-            Ok(_storage_result) => Ok(()),
-            Err(e) => Err(e),
-        }
+        self.update_document_with_ttl(collection, document_id, document, None).await
+    }
+
+    /// Update a document, expiring it after `ttl` if given and keeping its
+    /// current expiry otherwise.
+    pub async fn update_document_with_ttl(
+        &self,
+        collection: &str,
+        document_id: &str,
+        document: &serde_json::Value,
+        ttl: Option<std::time::Duration>,
+    ) -> Result<()> {
+        self.update_document_with_options(collection, document_id, document, WriteOptions { ttl, ..Default::default() }).await
+    }
+
+    /// Update a document as `options` asks, keeping its current expiry
+    /// unless they set a time to live.
+    pub async fn update_document_with_options(
+        &self,
+        collection: &str,
+        document_id: &str,
+        document: &serde_json::Value,
+        options: WriteOptions,
+    ) -> Result<()> {
+        self.schemas.validate(collection, document_id, document).await?;
+        self.sync.put_with_options(collection, document_id, document, options).await?;
+        self.emit(DocumentEvent::DocumentUpdated {
+            collection: collection.to_string(),
+            document_id: document_id.to_string(),
+        }, Some(document)).await;
+        Ok(())
     }
 
     /// Delete a document from the collection.
@@ -296,10 +873,489 @@ impl QueryEngine {
         collection: &str,
         document_id: &str,
     ) -> Result<()> {
-        match self.storage.delete_document(collection, document_id).await {
-            Ok(_storage_result) => Ok(()),
-            Err(e) => Err(e),
+        self.sync.delete(collection, document_id).await?;
+        self.emit(DocumentEvent::DocumentDeleted {
+            collection: collection.to_string(),
+            document_id: document_id.to_string(),
+        }, None).await;
+        Ok(())
+    }
+
+    /// Invalidate the cached query results a document change affects,
+    /// bring the built indexes of its collection up to date with it and
+    /// send it to live queries.
+    async fn emit(&self, event: DocumentEvent, document: Option<&serde_json::Value>) {
+        self.cache.query_results().handle_event(&event);
+        let (kind, collection, document_id) = match event {
+            DocumentEvent::DocumentCreated { collection, document_id } => (ChangeKind::Created, collection, document_id),
+            DocumentEvent::DocumentUpdated { collection, document_id } => (ChangeKind::Updated, collection, document_id),
+            DocumentEvent::DocumentDeleted { collection, document_id } => (ChangeKind::Deleted, collection, document_id),
+        };
+        self.indexes.document_changed(&collection, &document_id).await;
+        self.subscriptions.publish(DocumentChange { collection, document_id, kind, document: document.cloned() });
+    }
+
+    /// Subscribe to the matches of `filter` on `collection` as documents
+    /// change; without a filter every document of the collection matches.
+    ///
+    /// Malformed filters, and text searches on collections without a text
+    /// index, are refused with an "Invalid filter" error.
+    pub async fn subscribe_live(&self, collection: &str, filter: Option<&serde_json::Value>) -> Result<LiveQuery> {
+        let filter = filter.cloned().unwrap_or_else(|| serde_json::json!({}));
+        self.check_text_index(collection, &filter).await?;
+
+        // Registered before the initial query so no change falls between
+        let (id, deltas) = self.subscriptions.subscribe(collection, &filter)?;
+        let initial = match self.query_documents(collection, &QueryRequest::with_filter(filter)).await {
+            Ok(initial) => initial,
+            Err(e) => {
+                self.subscriptions.unsubscribe(&id);
+                return Err(e);
+            }
+        };
+        self.subscriptions.seed(&id, initial.ids.iter().cloned());
+        Ok(LiveQuery { id, collection: collection.to_string(), initial, deltas })
+    }
+
+    /// End a live query, returning whether it was subscribed.
+    pub fn unsubscribe_live(&self, id: &str) -> bool {
+        self.subscriptions.unsubscribe(id)
+    }
+
+    /// Live queries of the engine and the feed of every document change
+    /// written through it.
+    pub fn subscriptions(&self) -> Arc<SubscriptionManager> {
+        Arc::clone(&self.subscriptions)
+    }
+
+    /// Refuse text searches on collections without a text index.
+    async fn check_text_index(&self, collection: &str, filter: &serde_json::Value) -> Result<()> {
+        if TextSearch::in_filter(filter) && self.indexes.text_definition(collection).await?.is_none() {
+            return Err(anyhow::anyhow!("Invalid filter: $text needs a text index on collection '{}'", collection));
+        }
+        Ok(())
+    }
+
+    /// Plan a query without running it: the estimated selectivity of its
+    /// filter, the order its predicates are evaluated in, and the access
+    /// path chosen among the collection scan and its indexes, with the
+    /// estimated cost of each.
+    pub async fn explain_query(&self, collection: &str, query: &QueryRequest) -> Result<QueryPlan> {
+        if let Some(filter) = &query.filter {
+            CompiledFilter::compile(filter)?;
+            self.check_text_index(collection, filter).await?;
+        }
+        let documents = self.count_documents(collection).await? as u64;
+        Ok(plan_query(&self.storage, &self.indexes, self.planner_mode(), collection, query.filter.as_ref(), documents).await)
+    }
+
+    /// Secondary indexes declared for a collection.
+    pub async fn list_indexes(&self, collection: &str) -> Result<Vec<IndexDefinition>> {
+        self.indexes.definitions(collection).await
+    }
+
+    /// Declare a secondary index on a collection, replacing one of the same
+    /// name. It is built by the first query that uses it.
+    pub async fn create_index(&self, collection: &str, definition: IndexDefinition) -> Result<IndexDefinition> {
+        self.indexes.create(collection, definition).await
+    }
+
+    /// Remove a secondary index, returning whether it existed.
+    pub async fn drop_index(&self, collection: &str, name: &str) -> Result<bool> {
+        self.indexes.remove(collection, name).await
+    }
+
+    /// Current schema of a collection, or one of its versions.
+    pub async fn get_schema(&self, collection: &str, version: Option<u32>) -> Result<Option<CollectionSchema>> {
+        match version {
+            Some(version) => self.schemas.get_version(collection, version).await,
+            None => self.schemas.get(collection).await,
+        }
+    }
+
+    /// Every version of the schema of a collection, oldest first.
+    pub async fn schema_versions(&self, collection: &str) -> Result<Vec<CollectionSchema>> {
+        self.schemas.versions(collection).await
+    }
+
+    /// Set the JSON Schema documents written to a collection are validated
+    /// against, as its next version. Documents already stored are not
+    /// checked.
+    pub async fn set_schema(&self, collection: &str, schema: serde_json::Value, mode: ValidationMode) -> Result<CollectionSchema> {
+        self.schemas.set(collection, schema, mode).await
+    }
+
+    /// Change how writes to a collection are checked against its schema;
+    /// `None` if it has no schema.
+    pub async fn set_schema_mode(&self, collection: &str, mode: ValidationMode) -> Result<Option<CollectionSchema>> {
+        self.schemas.set_mode(collection, mode).await
+    }
+
+    /// Stop validating the documents of a collection, returning whether it
+    /// had a schema.
+    pub async fn remove_schema(&self, collection: &str) -> Result<bool> {
+        self.schemas.remove(collection).await
+    }
+
+    /// How this engine orders the predicates of filters.
+    pub fn planner_mode(&self) -> PlannerMode {
+        if self.config.optimizer.cost_based {
+            PlannerMode::CostBased
+        } else {
+            PlannerMode::Heuristic
+        }
+    }
+
+    /// The current query path, or the same storage planned with another mode.
+    pub fn planner_path(&self, mode: PlannerMode) -> Arc<dyn QueryPath> {
+        Arc::new(PlannerPath::new(Arc::clone(&self.storage), Arc::clone(&self.indexes), mode))
+    }
+
+    /// Compare a sample of queries between the current path and `candidate`
+    /// until the canary is stopped.
+    pub fn start_canary(&self, candidate: Arc<dyn QueryPath>, config: CanaryConfig) -> Result<CanaryReport> {
+        let mut canary = self.canary.write().unwrap();
+        if let Some(running) = canary.as_ref() {
+            return Err(anyhow::anyhow!("Canary already running against {}", running.report().candidate));
+        }
+        let started = Arc::new(QueryCanary::new(config, self.planner_path(self.planner_mode()), candidate)?);
+        let report = started.report();
+        *canary = Some(started);
+        Ok(report)
+    }
+
+    /// Progress of the running canary.
+    pub fn canary_report(&self) -> Option<CanaryReport> {
+        self.canary.read().unwrap().as_ref().map(|canary| canary.report())
+    }
+
+    /// Stop the running canary, returning its final report.
+    pub fn stop_canary(&self) -> Option<CanaryReport> {
+        self.canary.write().unwrap().take().map(|canary| canary.report())
+    }
+
+    /// Collect the field statistics of every collection now.
+    pub async fn analyze_collections(&self) -> Result<Vec<CollectionStatistics>> {
+        self.storage.analyze_collections().await
+    }
+
+    /// Field statistics of a collection, if they were collected.
+    pub fn collection_statistics(&self, collection: &str) -> Result<Option<CollectionStatistics>> {
+        self.storage.collection_statistics(collection)
+    }
+
+    /// Train a compression dictionary for a Zstd collection now.
+    pub async fn train_compression_dictionary(&self, collection: &str) -> Result<Option<CompressionDictionary>> {
+        self.storage.train_compression_dictionary(collection).await
+    }
+
+    /// Compression dictionary versions of a collection, oldest first.
+    pub fn compression_dictionaries(&self, collection: &str) -> Result<Vec<CompressionDictionary>> {
+        self.storage.compression_dictionaries(collection)
+    }
+
+    /// When a document expires, if it exists and has an expiry.
+    pub fn document_expiry(&self, collection: &str, document_id: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.storage.document_expiry(collection, document_id)
+    }
+
+    /// Storage used per tier and collection, against the storage limit.
+    pub fn storage_usage(&self) -> Result<StorageUsage> {
+        self.storage.storage_usage()
+    }
+
+    /// Directories the storage layer keeps data in.
+    pub fn storage_paths(&self) -> Vec<std::path::PathBuf> {
+        self.storage.storage_paths()
+    }
+
+    /// Number of documents in a collection.
+    pub async fn count_documents(&self, collection: &str) -> Result<usize> {
+        Ok(self.storage.list_documents(collection, None, None).await?.len())
+    }
+
+    /// Register a collection with its own settings.
+    pub fn create_collection(&self, collection: &str, config: CollectionConfig) -> Result<Collection> {
+        self.storage.create_collection(collection, config)
+    }
+
+    /// Replace the settings of a registered collection.
+    pub fn update_collection(&self, collection: &str, config: CollectionConfig) -> Result<Collection> {
+        self.storage.update_collection(collection, config)
+    }
+
+    /// Registered and implicit collections with document counts and sizes.
+    pub fn list_collections(&self) -> Vec<CollectionInfo> {
+        self.storage.list_collections()
+    }
+
+    /// Delete every document in a collection, keeping the collection,
+    /// returning how many were removed.
+    pub async fn truncate_collection(&self, collection: &str) -> Result<usize> {
+        let document_ids = self.storage.list_documents(collection, None, None).await?;
+        for document_id in &document_ids {
+            self.delete_document(collection, document_id).await?;
         }
+        Ok(document_ids.len())
+    }
+
+    /// Delete every document in a collection and the collection itself,
+    /// returning how many documents were removed.
+    pub async fn drop_collection(&self, collection: &str) -> Result<usize> {
+        let removed = self.truncate_collection(collection).await?;
+        self.storage.drop_collection(collection).await?;
+        Ok(removed)
+    }
+
+    /// Place a legal hold on listed documents of a collection.
+    pub fn place_legal_hold(
+        &self,
+        collection: &str,
+        document_ids: Vec<String>,
+        reason: &str,
+        placed_by: &str,
+    ) -> Result<LegalHold> {
+        self.storage.place_legal_hold(LegalHold::new(collection, document_ids, reason, placed_by))
+    }
+
+    /// Place a legal hold on the documents of a collection that currently
+    /// match `filter`. Documents matching it later are not covered.
+    pub async fn place_legal_hold_on_query(
+        &self,
+        collection: &str,
+        filter: &serde_json::Value,
+        reason: &str,
+        placed_by: &str,
+    ) -> Result<LegalHold> {
+        let compiled = CompiledFilter::compile(filter)?;
+        let mut matching = Vec::new();
+        for document_id in self.storage.list_documents(collection, None, None).await? {
+            if let Some(document) = self.storage.get_document(collection, &document_id).await?.data {
+                if compiled.matches(&document) {
+                    matching.push(document_id);
+                }
+            }
+        }
+
+        let hold = LegalHold::new(collection, matching, reason, placed_by).with_query(filter.clone());
+        self.storage.place_legal_hold(hold)
+    }
+
+    /// Release a legal hold.
+    pub fn release_legal_hold(&self, hold_id: &str, released_by: &str) -> Result<LegalHold> {
+        self.storage.release_legal_hold(hold_id, released_by)
+    }
+
+    /// Active legal holds, oldest first.
+    pub fn legal_holds(&self) -> Vec<LegalHold> {
+        self.storage.legal_holds()
+    }
+
+    /// Every legal hold placement and release, oldest first.
+    pub fn legal_hold_audit_log(&self) -> Result<Vec<LegalHoldEvent>> {
+        self.storage.legal_hold_audit_log()
+    }
+
+    /// Make a collection write-once-read-many, or extend its retention.
+    pub fn enable_worm(&self, collection: &str, retention: std::time::Duration) -> Result<WormPolicy> {
+        self.storage.enable_worm(collection, retention)
+    }
+
+    /// WORM policy of a collection, if it has one.
+    pub fn worm_policy(&self, collection: &str) -> Option<WormPolicy> {
+        self.storage.worm_policy(collection)
+    }
+
+    /// Tag a collection with a residency, returning the violations found
+    /// among its stored documents.
+    pub async fn set_collection_residency(&self, collection: &str, residency: &str) -> Result<Vec<ResidencyViolation>> {
+        self.storage.set_collection_residency(collection, residency).await
+    }
+
+    /// Tag a document with a residency, returning the violations found.
+    pub async fn set_document_residency(
+        &self,
+        collection: &str,
+        document_id: &str,
+        residency: &str,
+    ) -> Result<Vec<ResidencyViolation>> {
+        self.storage.set_document_residency(collection, document_id, residency).await
+    }
+
+    /// Every residency violation detected, oldest first.
+    pub fn residency_report(&self) -> Result<Vec<ResidencyViolation>> {
+        self.storage.residency_report()
+    }
+
+    /// Replace the tier migration rules.
+    pub fn set_tier_rules(&self, rules: Vec<TierRule>) -> Result<Vec<TierRule>> {
+        self.storage.set_tier_rules(rules)
+    }
+
+    /// Current tier migration rules, in the order they are checked.
+    pub fn tier_rules(&self) -> Vec<TierRule> {
+        self.storage.tier_rules()
+    }
+
+    /// Apply the tier migration rules now, or only report what they would
+    /// move when `dry_run` is set.
+    pub async fn run_tier_migration(&self, dry_run: bool) -> Result<TierMigrationReport> {
+        self.storage.run_tier_migration(dry_run).await
+    }
+
+    /// Totals of the tier migration runs since startup.
+    pub fn tier_migration_metrics(&self) -> TierMigrationMetrics {
+        self.storage.tier_migration_metrics()
+    }
+
+    /// The `limit` most read documents over the last `window`, keyed by
+    /// `collection:document_id`.
+    pub fn hot_documents(&self, limit: usize, window: std::time::Duration) -> Vec<HeavyHitter> {
+        self.storage.hot_documents(limit, window)
+    }
+
+    /// The `limit` most read collections over the last `window`.
+    pub fn hot_collections(&self, limit: usize, window: std::time::Duration) -> Vec<HeavyHitter> {
+        self.storage.hot_collections(limit, window)
+    }
+
+    /// Verify stored copies against their checksums now, repairing corrupt
+    /// ones from intact copies when `repair` is set.
+    pub async fn scrub(&self, repair: bool) -> Result<ScrubReport> {
+        self.storage.scrub(repair).await
+    }
+
+    /// Report of the last integrity scrub, if any has run.
+    pub fn last_scrub_report(&self) -> Result<Option<ScrubReport>> {
+        self.storage.last_scrub_report()
+    }
+
+    /// Back up the stored documents, all of them or those changed since the
+    /// previous backup.
+    pub async fn create_backup(&self, kind: BackupKind) -> Result<BackupManifest> {
+        self.storage.create_backup(kind).await
+    }
+
+    /// Back up the collections whose names start with `scope`, or all of
+    /// them when it is `None`.
+    pub async fn create_scoped_backup(&self, kind: BackupKind, scope: Option<&str>) -> Result<BackupManifest> {
+        self.storage.create_scoped_backup(kind, scope).await
+    }
+
+    /// Every backup, oldest first.
+    pub async fn list_backups(&self) -> Result<Vec<BackupManifest>> {
+        self.storage.list_backups().await
+    }
+
+    /// Manifest of one backup.
+    pub async fn backup_manifest(&self, id: &str) -> Result<BackupManifest> {
+        self.storage.backup_manifest(id).await
+    }
+
+    /// Delete a backup no incremental backup depends on.
+    pub async fn delete_backup(&self, id: &str) -> Result<()> {
+        self.storage.delete_backup(id).await
+    }
+
+    /// Return the documents to their state when a backup was taken.
+    pub async fn restore_backup(&self, id: &str) -> Result<RestoreReport> {
+        self.storage.restore_backup(id).await
+    }
+
+    /// Copy the documents of a backup into a new namespace.
+    pub async fn restore_backup_into(&self, id: &str, namespace: &str) -> Result<RestoreReport> {
+        self.storage.restore_backup_into(id, namespace).await
+    }
+
+    /// Restore the latest backup taken at or before `at`.
+    pub async fn restore_backup_at(&self, at: chrono::DateTime<chrono::Utc>) -> Result<RestoreReport> {
+        self.storage.restore_backup_at(at).await
+    }
+
+    /// Background jobs, newest first, optionally only those of `tenant` or
+    /// in `state`.
+    pub fn list_jobs(&self, tenant: Option<&str>, state: Option<JobState>) -> Result<Vec<Job>> {
+        self.storage.jobs().list(tenant, state)
+    }
+
+    /// Record of one background job.
+    pub fn job(&self, id: &str) -> Result<Job> {
+        self.storage.jobs().get(id)
+    }
+
+    /// Cancel a queued or running job.
+    pub fn cancel_job(&self, id: &str) -> Result<Job> {
+        self.storage.jobs().cancel(id)
+    }
+
+    /// Take a backup as a background job.
+    pub fn spawn_backup_job(&self, kind: BackupKind, scope: Option<String>, tenant: Option<&str>) -> Result<Job> {
+        self.storage.spawn_backup_job(kind, scope, tenant)
+    }
+
+    /// Restore a backup as a background job, in place or into `namespace`.
+    pub fn spawn_restore_job(&self, id: &str, namespace: Option<String>, tenant: Option<&str>) -> Result<Job> {
+        self.storage.spawn_restore_job(id, namespace, tenant)
+    }
+
+    /// Scrub stored copies as a background job.
+    pub fn spawn_scrub_job(&self, repair: bool) -> Result<Job> {
+        self.storage.spawn_scrub_job(repair)
+    }
+
+    /// Members of the cluster.
+    pub fn cluster_nodes(&self) -> Result<Vec<ClusterNode>> {
+        self.storage.cluster_nodes()
+    }
+
+    /// Record a node joining or leaving, returning the rebalance job it
+    /// started, if any.
+    pub async fn apply_membership_change(&self, change: MembershipChange) -> Result<Option<Job>> {
+        let job = self.storage.apply_membership_change(change).await?;
+        self.sync_cache_peers()?;
+        Ok(job)
+    }
+
+    /// Put the cluster members with a REST address on the network cache's
+    /// hash ring, reached at that address, and take the others off it.
+    fn sync_cache_peers(&self) -> Result<()> {
+        let Some(transport) = &self.cache_peers else { return Ok(()) };
+        let addresses: HashMap<String, String> = self
+            .storage
+            .cluster_nodes()?
+            .into_iter()
+            .filter_map(|node| Some((node.id, node.address?)))
+            .collect();
+        let peers: Vec<String> = addresses.keys().cloned().collect();
+        transport.set_addresses(addresses);
+        self.cache.set_cache_peers(&peers)
+    }
+
+    /// Serve a request from another member against this node's shard of
+    /// the network cache.
+    pub fn handle_cache_peer_request(&self, request: CachePeerRequest) -> Result<CachePeerResponse> {
+        self.cache.handle_peer_request(request)
+    }
+
+    /// Documents on this node that are not where they belong.
+    pub async fn plan_rebalance(&self) -> Result<RebalancePlan> {
+        self.storage.plan_rebalance().await
+    }
+
+    /// Move documents to where they belong as a background job.
+    pub fn spawn_rebalance_job(&self) -> Result<Job> {
+        self.storage.spawn_rebalance_job()
+    }
+
+    /// Store a document another node moved here.
+    pub async fn accept_transferred_document(&self, document: TransferredDocument) -> Result<bool> {
+        self.storage.accept_transferred_document(document).await
+    }
+
+    /// List the documents of a collection with their metadata, filtered,
+    /// sorted and paginated, without reading the documents themselves.
+    pub fn list_documents_with(&self, collection: &str, options: &ListOptions) -> Result<DocumentPage> {
+        self.storage.list_documents_with(collection, options)
     }
 
     /// List all documents in a collection with optional pagination.
@@ -313,13 +1369,15 @@ impl QueryEngine {
         match self.storage.list_documents(collection, limit, offset).await {
             Ok(document_ids) => {
                 let mut documents = Vec::new();
+                let mut ids = Vec::new();
                 let mut from_cache_count = 0;
 
-                for doc_id in &document_ids {
-                    match self.storage.get_document(collection, doc_id).await {
+                for doc_id in document_ids {
+                    match self.storage.get_document(collection, &doc_id).await {
                         Ok(storage_result) => {
                             if let Some(document) = storage_result.data {
                                 documents.push(document);
+                                ids.push(doc_id);
                                 if storage_result.cache_hit {
                                     from_cache_count += 1;
                                 }
@@ -334,8 +1392,10 @@ impl QueryEngine {
                 let result = QueryResult {
                     total: documents.len(),
                     documents,
+                    ids,
                     execution_time: start_time.elapsed(),
                     from_cache: from_cache_count > 0,
+                    next_cursor: None,
                 };
 
                 Ok(result)
@@ -346,3 +1406,220 @@ impl QueryEngine {
         }
     }
 }
+
+/// Documents a query returned, before it was cached.
+pub(crate) struct Execution {
+    /// The page of documents the query returned
+    pub(crate) documents: Vec<serde_json::Value>,
+
+    /// IDs of the documents, in the same order
+    pub(crate) ids: Vec<String>,
+
+    /// Matches before limit and offset were applied
+    pub(crate) total: usize,
+
+    /// Matches read from the hot tier
+    pub(crate) from_cache: usize,
+
+    /// Cursor of the page after this one, if any
+    pub(crate) next_cursor: Option<String>,
+
+    /// Plan the filter was run with, if it was planned
+    pub(crate) plan: Option<QueryPlan>,
+}
+
+/// Plan a filter the way `mode` does, from the collection's statistics
+/// when it is cost-based and they were collected. Unplanned queries always
+/// scan the collection.
+pub(crate) async fn plan_query(
+    storage: &StorageHierarchy,
+    indexes: &SecondaryIndexes,
+    mode: PlannerMode,
+    collection: &str,
+    filter: Option<&serde_json::Value>,
+    documents: u64,
+) -> QueryPlan {
+    let statistics = if mode == PlannerMode::CostBased {
+        storage.collection_statistics(collection).unwrap_or_else(|e| {
+            tracing::warn!("Planning {} without statistics: {}", collection, e);
+            None
+        })
+    } else {
+        None
+    };
+    let definitions = match mode {
+        PlannerMode::Unplanned => Vec::new(),
+        _ => indexes.definitions(collection).await.unwrap_or_else(|e| {
+            tracing::warn!("Planning {} without indexes: {}", collection, e);
+            Vec::new()
+        }),
+    };
+    QueryPlanner::plan(collection, filter, documents, statistics.as_ref(), &definitions)
+}
+
+/// Filter, sort, paginate and project a collection, bypassing the query
+/// result cache. Matches are ordered by the query's sort and then by ID, and
+/// the page starts after the query's cursor, if any. With `primary_only`,
+/// documents whose primary copy is on another node are left out. Every
+/// document read and match kept counts against `context`'s limits.
+pub(crate) async fn execute_query(
+    storage: &StorageHierarchy,
+    indexes: &SecondaryIndexes,
+    collection: &str,
+    query: &QueryRequest,
+    mode: PlannerMode,
+    primary_only: bool,
+    context: &QueryContext,
+) -> Result<Execution> {
+    if let Some(as_of) = query.as_of {
+        return execute_query_as_of(storage, collection, query, as_of, primary_only, context).await;
+    }
+
+    let cursor = QueryCursor::for_query(query)?;
+    let projection = query.projection.as_ref().map(Projection::compile).transpose()?;
+    let sort = query.effective_sort();
+    let mut document_ids = storage.list_documents(collection, None, None).await?;
+
+    // Evaluate the filter's predicates most selective first, unless the
+    // filter is run as written
+    let plan = match (&query.filter, mode) {
+        (Some(_), PlannerMode::Unplanned) | (None, _) => None,
+        (Some(filter), _) => Some(plan_query(storage, indexes, mode, collection, Some(filter), document_ids.len() as u64).await),
+    };
+    let filter = query
+        .filter
+        .as_ref()
+        .map(|filter| plan.as_ref().and_then(QueryPlan::filter).unwrap_or_else(|| filter.clone()));
+
+    // A text search is answered by the text index alone, which finds and
+    // scores the documents the rest of the filter is checked against
+    let (search, filter) = match &filter {
+        Some(filter) => TextSearch::extract(filter)?,
+        None => (None, None),
+    };
+    let filter = filter.as_ref().map(CompiledFilter::compile).transpose()?;
+    let scores = match &search {
+        Some(search) => Some(indexes.search(collection, search).await?),
+        None => None,
+    };
+
+    // Read only the documents the chosen index finds; they are still
+    // checked against the whole filter
+    if let Some(scores) = &scores {
+        document_ids = scores.keys().cloned().collect();
+    } else if let Some(AccessPath::IndexScan { index, predicate, .. }) = plan.as_ref().map(|plan| &plan.access) {
+        match indexes.scan(collection, index, predicate).await {
+            Ok(Some(found)) => document_ids = found,
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Scanning {} instead of index {}: {}", collection, index, e),
+        }
+    }
+
+    // Replicas are answered for by the node holding the primary copy
+    if primary_only {
+        document_ids.retain(|doc_id| storage.is_primary_holder(collection, doc_id));
+    }
+
+    let mut matching_documents = Vec::new();
+    let mut from_cache = 0;
+
+    // Fetch and filter documents with optimization
+    // Current implementation: Basic document retrieval with filtering
+    // Future enhancements planned:
+    // - Parallel document retrieval 
+    // - Vectorized filter evaluation
+    // - Early termination for LIMIT queries
+    for doc_id in document_ids {
+        context.scan()?;
+        match storage.get_document(collection, &doc_id).await {
+            Ok(storage_result) => {
+                if let Some(mut document) = storage_result.data {
+                    // Apply filter if provided
+                    if let Some(filter) = &filter {
+                        if !filter.matches(&document) {
+                            continue;
+                        }
+                    }
+
+                    // Text search matches carry their relevance
+                    if let (Some(score), Some(fields)) =
+                        (scores.as_ref().and_then(|scores| scores.get(&doc_id)), document.as_object_mut())
+                    {
+                        fields.insert(SCORE_FIELD.to_string(), serde_json::json!(score));
+                    }
+
+                    context.hold(&document)?;
+                    matching_documents.push(RankedDocument::new(doc_id, document, sort.as_ref()));
+
+                    // Track cache performance
+                    if storage_result.cache_hit {
+                        from_cache += 1;
+                    }
+                }
+            }
+            Err(_) => {
+                continue; // Skip documents that can't be retrieved
+            }
+        }
+    }
+
+    let total = matching_documents.len();
+
+    // Apply sorting and pagination
+    let (page, next_cursor) = cursor::paginate(matching_documents, query, cursor.as_ref(), false);
+    let (ids, documents): (Vec<_>, Vec<_>) = page.into_iter().map(|ranked| (ranked.id, ranked.document)).unzip();
+    let documents = match &projection {
+        Some(projection) => documents.into_iter().map(|document| projection.apply(document)).collect(),
+        None => documents,
+    };
+
+    Ok(Execution { documents, ids, total, from_cache, next_cursor, plan })
+}
+
+/// Run a query against a collection as it was at `as_of`. The documents
+/// are read from version history and filtered as written, since indexes
+/// only describe the current documents; text searches need a text index
+/// and are refused.
+async fn execute_query_as_of(
+    storage: &StorageHierarchy,
+    collection: &str,
+    query: &QueryRequest,
+    as_of: chrono::DateTime<chrono::Utc>,
+    primary_only: bool,
+    context: &QueryContext,
+) -> Result<Execution> {
+    let cursor = QueryCursor::for_query(query)?;
+    let projection = query.projection.as_ref().map(Projection::compile).transpose()?;
+    let sort = query.effective_sort();
+    let filter = match &query.filter {
+        Some(filter) if TextSearch::in_filter(filter) => {
+            return Err(anyhow::anyhow!("Invalid filter: $text cannot be combined with as_of"));
+        }
+        Some(filter) => Some(CompiledFilter::compile(filter)?),
+        None => None,
+    };
+
+    let mut matching_documents = Vec::new();
+    for (metadata, document) in storage.collection_as_of(collection, as_of).await? {
+        // Replicas are answered for by the node that held the primary copy
+        if primary_only && !storage.is_primary_holder_of(&metadata) {
+            continue;
+        }
+        context.scan()?;
+        if filter.as_ref().is_some_and(|filter| !filter.matches(&document)) {
+            continue;
+        }
+        context.hold(&document)?;
+        matching_documents.push(RankedDocument::new(metadata.id, document, sort.as_ref()));
+    }
+
+    let total = matching_documents.len();
+    let (page, next_cursor) = cursor::paginate(matching_documents, query, cursor.as_ref(), false);
+    let (ids, documents): (Vec<_>, Vec<_>) = page.into_iter().map(|ranked| (ranked.id, ranked.document)).unzip();
+    let documents = match &projection {
+        Some(projection) => documents.into_iter().map(|document| projection.apply(document)).collect(),
+        None => documents,
+    };
+
+    Ok(Execution { documents, ids, total, from_cache: 0, next_cursor, plan: None })
+}
@@ -0,0 +1,413 @@
+//! # Query Execution Limits
+//!
+//! A scan over a large collection can keep a node busy long after its
+//! client stopped waiting. Every query and aggregation runs in a
+//! [`QueryContext`] that bounds it:
+//!
+//! - **Timeout**: wall-clock time from the start of execution,
+//!   [`QueryConfig::execution_timeout`](crate::QueryConfig) by default
+//! - **Documents scanned**: documents read to evaluate the query
+//! - **Memory**: estimated size of the matches held before they are sorted
+//!   and paged
+//! - **Cancellation**: running queries are listed with their ID and can be
+//!   cancelled by it
+//!
+//! Limits are checked as documents are read. A query past one stops and
+//! fails with a [`QueryAborted`] error naming the reason, rather than
+//! returning a partial result. Limits sent with a query can only tighten
+//! the engine's.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Notify;
+
+/// Bounds on the resources a single query may use; unset bounds are not
+/// enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryLimits {
+    /// Wall-clock time the query may run, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+
+    /// Documents the query may read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_documents_scanned: Option<usize>,
+
+    /// Estimated bytes of matches the query may hold at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_bytes: Option<usize>,
+}
+
+impl QueryLimits {
+    /// These limits, tightened field by field by those of a query.
+    pub fn tightened(self, query: Option<&QueryLimits>) -> Self {
+        let Some(query) = query else {
+            return self;
+        };
+        fn tighter<T: Ord>(engine: Option<T>, query: Option<T>) -> Option<T> {
+            match (engine, query) {
+                (Some(engine), Some(query)) => Some(engine.min(query)),
+                (engine, query) => engine.or(query),
+            }
+        }
+        Self {
+            timeout_ms: tighter(self.timeout_ms, query.timeout_ms),
+            max_documents_scanned: tighter(self.max_documents_scanned, query.max_documents_scanned),
+            max_memory_bytes: tighter(self.max_memory_bytes, query.max_memory_bytes),
+        }
+    }
+}
+
+/// Why a query was stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// It ran longer than its timeout
+    Timeout,
+
+    /// An operator cancelled it
+    Cancelled,
+
+    /// It read more documents than allowed
+    ScanLimit,
+
+    /// Its matches took more memory than allowed
+    MemoryLimit,
+}
+
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AbortReason::Timeout => "timed out",
+            AbortReason::Cancelled => "was cancelled",
+            AbortReason::ScanLimit => "exceeded its scan limit",
+            AbortReason::MemoryLimit => "exceeded its memory limit",
+        })
+    }
+}
+
+/// Error of a query stopped by its limits or by cancellation; find it in
+/// an `anyhow::Error` with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryAborted {
+    pub query_id: String,
+    pub collection: String,
+    pub reason: AbortReason,
+
+    /// Documents read before the query stopped
+    pub documents_scanned: usize,
+
+    /// Estimated bytes of matches held when the query stopped
+    pub memory_bytes: usize,
+
+    /// Time the query ran, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+impl std::fmt::Display for QueryAborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Query aborted: query {} on {} {} after scanning {} documents in {} ms",
+            self.query_id, self.collection, self.reason, self.documents_scanned, self.elapsed_ms
+        )
+    }
+}
+
+impl std::error::Error for QueryAborted {}
+
+/// A query being executed, as listed for operators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunningQuery {
+    pub id: String,
+    pub collection: String,
+
+    /// `query` or `aggregate`
+    pub kind: String,
+    pub started_at: DateTime<Utc>,
+    pub elapsed_ms: u64,
+    pub documents_scanned: usize,
+    pub memory_bytes: usize,
+    pub limits: QueryLimits,
+
+    /// Whether it was cancelled and is yet to stop
+    pub cancelled: bool,
+}
+
+/// Execution state of one query, checked against its limits as it reads
+/// documents.
+#[derive(Debug)]
+pub struct QueryContext {
+    id: String,
+    collection: String,
+    kind: &'static str,
+    limits: QueryLimits,
+    started_at: DateTime<Utc>,
+    start: Instant,
+    scanned: AtomicUsize,
+    memory: AtomicUsize,
+    cancelled: AtomicBool,
+    cancellation: Notify,
+}
+
+impl QueryContext {
+    pub fn new(collection: &str, kind: &'static str, limits: QueryLimits) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            collection: collection.to_string(),
+            kind,
+            limits,
+            started_at: Utc::now(),
+            start: Instant::now(),
+            scanned: AtomicUsize::new(0),
+            memory: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+            cancellation: Notify::new(),
+        }
+    }
+
+    /// A context enforcing no limits, for queries run on the engine's own
+    /// behalf.
+    pub fn unbounded(collection: &str) -> Self {
+        Self::new(collection, "query", QueryLimits::default())
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn collection(&self) -> &str {
+        &self.collection
+    }
+
+    /// `query` or `aggregate`
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// Documents read so far.
+    pub fn documents_scanned(&self) -> usize {
+        self.scanned.load(Ordering::Relaxed)
+    }
+
+    /// Fail if the query was cancelled or ran out of time.
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Acquire) {
+            return Err(self.abort(AbortReason::Cancelled));
+        }
+        if self.timeout().is_some_and(|timeout| self.start.elapsed() > timeout) {
+            return Err(self.abort(AbortReason::Timeout));
+        }
+        Ok(())
+    }
+
+    /// Count a document about to be read, failing if it is one too many.
+    pub fn scan(&self) -> Result<()> {
+        self.check()?;
+        let scanned = self.scanned.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.limits.max_documents_scanned.is_some_and(|max| scanned > max) {
+            return Err(self.abort(AbortReason::ScanLimit));
+        }
+        Ok(())
+    }
+
+    /// Count a match kept until the query finishes, failing if the matches
+    /// no longer fit in its memory limit.
+    pub fn hold(&self, document: &Value) -> Result<()> {
+        let size = estimated_size(document);
+        let held = self.memory.fetch_add(size, Ordering::Relaxed) + size;
+        if self.limits.max_memory_bytes.is_some_and(|max| held > max) {
+            return Err(self.abort(AbortReason::MemoryLimit));
+        }
+        Ok(())
+    }
+
+    /// Run `execution` within the query's timeout, stopping it early if the
+    /// query is cancelled.
+    pub async fn run<T>(&self, execution: impl Future<Output = Result<T>>) -> Result<T> {
+        let cancelled = self.cancellation.notified();
+        self.check()?;
+        let timeout = self.timeout().map(|timeout| timeout.saturating_sub(self.start.elapsed()));
+        let execution = async {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, execution)
+                    .await
+                    .unwrap_or_else(|_| Err(self.abort(AbortReason::Timeout))),
+                None => execution.await,
+            }
+        };
+        tokio::select! {
+            result = execution => result,
+            _ = cancelled => Err(self.abort(AbortReason::Cancelled)),
+        }
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        self.cancellation.notify_waiters();
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.limits.timeout_ms.map(Duration::from_millis)
+    }
+
+    fn abort(&self, reason: AbortReason) -> anyhow::Error {
+        anyhow::Error::new(QueryAborted {
+            query_id: self.id.clone(),
+            collection: self.collection.clone(),
+            reason,
+            documents_scanned: self.scanned.load(Ordering::Relaxed),
+            memory_bytes: self.memory.load(Ordering::Relaxed),
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+        })
+    }
+
+    fn summary(&self) -> RunningQuery {
+        RunningQuery {
+            id: self.id.clone(),
+            collection: self.collection.clone(),
+            kind: self.kind.to_string(),
+            started_at: self.started_at,
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+            documents_scanned: self.scanned.load(Ordering::Relaxed),
+            memory_bytes: self.memory.load(Ordering::Relaxed),
+            limits: self.limits,
+            cancelled: self.cancelled.load(Ordering::Acquire),
+        }
+    }
+}
+
+/// Approximate bytes a JSON value takes in memory.
+fn estimated_size(value: &Value) -> usize {
+    const NODE: usize = std::mem::size_of::<Value>();
+    match value {
+        Value::String(string) => NODE + string.len(),
+        Value::Array(items) => NODE + items.iter().map(estimated_size).sum::<usize>(),
+        Value::Object(fields) => {
+            NODE + fields.iter().map(|(name, value)| name.len() + estimated_size(value)).sum::<usize>()
+        }
+        _ => NODE,
+    }
+}
+
+/// Queries being executed by an engine.
+#[derive(Debug, Default)]
+pub(crate) struct RunningQueries {
+    queries: Mutex<HashMap<String, Arc<QueryContext>>>,
+}
+
+impl RunningQueries {
+    /// Track a query until the returned registration is dropped.
+    pub(crate) fn register(&self, context: QueryContext) -> Registration<'_> {
+        let context = Arc::new(context);
+        self.queries.lock().unwrap().insert(context.id.clone(), Arc::clone(&context));
+        Registration { queries: self, context }
+    }
+
+    /// Running queries, longest running first.
+    pub(crate) fn list(&self) -> Vec<RunningQuery> {
+        let mut running: Vec<RunningQuery> = self.queries.lock().unwrap().values().map(|context| context.summary()).collect();
+        running.sort_by_key(|query| query.started_at);
+        running
+    }
+
+    /// Cancel a running query, returning it if it was running.
+    pub(crate) fn cancel(&self, id: &str) -> Option<RunningQuery> {
+        let context = self.queries.lock().unwrap().get(id).cloned()?;
+        context.cancel();
+        Some(context.summary())
+    }
+}
+
+/// A tracked query, untracked when dropped.
+pub(crate) struct Registration<'a> {
+    queries: &'a RunningQueries,
+    context: Arc<QueryContext>,
+}
+
+impl std::ops::Deref for Registration<'_> {
+    type Target = QueryContext;
+
+    fn deref(&self) -> &QueryContext {
+        &self.context
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.queries.queries.lock().unwrap().remove(&self.context.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reason(error: anyhow::Error) -> AbortReason {
+        error.downcast_ref::<QueryAborted>().unwrap().reason
+    }
+
+    #[test]
+    fn test_query_limits_only_tighten() {
+        let engine = QueryLimits { timeout_ms: Some(1000), max_documents_scanned: None, max_memory_bytes: Some(64) };
+        let query = QueryLimits { timeout_ms: Some(5000), max_documents_scanned: Some(10), max_memory_bytes: Some(32) };
+        assert_eq!(
+            engine.tightened(Some(&query)),
+            QueryLimits { timeout_ms: Some(1000), max_documents_scanned: Some(10), max_memory_bytes: Some(32) }
+        );
+        assert_eq!(engine.tightened(None), engine);
+    }
+
+    #[test]
+    fn test_scans_and_matches_past_their_limits_abort() {
+        let limits = QueryLimits { max_documents_scanned: Some(2), max_memory_bytes: Some(200), ..Default::default() };
+        let context = QueryContext::new("users", "query", limits);
+        assert!(context.scan().is_ok() && context.scan().is_ok());
+        assert_eq!(reason(context.scan().unwrap_err()), AbortReason::ScanLimit);
+
+        let context = QueryContext::new("users", "query", limits);
+        assert!(context.hold(&json!({ "name": "ada" })).is_ok());
+        let error = context.hold(&json!({ "bio": "x".repeat(200) })).unwrap_err();
+        assert!(error.to_string().starts_with("Query aborted"));
+        assert_eq!(reason(error), AbortReason::MemoryLimit);
+    }
+
+    #[tokio::test]
+    async fn test_running_queries_time_out_and_cancel() {
+        let limits = QueryLimits { timeout_ms: Some(20), ..Default::default() };
+        let context = QueryContext::new("users", "query", limits);
+        let slow = context.run(async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        });
+        assert_eq!(reason(slow.await.unwrap_err()), AbortReason::Timeout);
+
+        let running = RunningQueries::default();
+        let registration = running.register(QueryContext::unbounded("users"));
+        let id = registration.id().to_string();
+        assert_eq!(running.list()[0].id, id);
+
+        let execution = registration.run(async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        });
+        let (result, cancelled) = tokio::join!(execution, async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            running.cancel(&id)
+        });
+        assert!(cancelled.is_some_and(|query| query.cancelled));
+        assert_eq!(reason(result.unwrap_err()), AbortReason::Cancelled);
+
+        drop(registration);
+        assert!(running.list().is_empty() && running.cancel(&id).is_none());
+    }
+}
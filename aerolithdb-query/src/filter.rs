@@ -0,0 +1,401 @@
+//! # Filter Evaluation
+//!
+//! Query filters use MongoDB's operator syntax. A filter is parsed once
+//! into a [`CompiledFilter`], which rejects unknown operators and invalid
+//! patterns up front, and is then evaluated against each stored document.
+//!
+//! - Comparison: `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`, `$nin`
+//! - Logical: `$and`, `$or`, `$nor`, and `$not` both around a whole filter
+//!   and around the operators of one field
+//! - Element and text: `$exists`, `$regex` with `$options` (`i`, `m`, `s`, `x`)
+//! - Full-text: `$text` with `$search`, in the top-level conjunction only;
+//!   see [`crate::text`]
+//!
+//! Field paths use dots to reach into nested objects (`user.address.city`)
+//! and numeric segments to pick array elements (`tags.0`). A path crossing
+//! an array of objects reaches the field of every element, and a predicate
+//! on an array field matches when the array itself or any element matches,
+//! so `{"tags": "urgent"}` finds documents tagged urgent among others.
+//!
+//! Numbers compare by value whatever their JSON representation. Ordering
+//! operators only compare numbers with numbers and strings with strings;
+//! values of other types never satisfy them. A missing field equals
+//! `null`, so `{"deleted_at": null}` matches documents without the field.
+
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+
+use crate::text::TextSearch;
+
+/// A filter parsed and checked once, ready to be evaluated against many
+/// documents.
+#[derive(Debug, Clone)]
+pub struct CompiledFilter {
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Nor(Vec<Node>),
+    Not(Box<Node>),
+    Field { path: Vec<String>, operators: Vec<Operator> },
+    Text(TextSearch),
+}
+
+#[derive(Debug, Clone)]
+enum Operator {
+    Eq(Value),
+    Ne(Value),
+
+    /// The field orders in this direction from the operand, or equals it
+    /// if the flag is set
+    Compare(Ordering, bool, Value),
+    In(Vec<Value>),
+    Nin(Vec<Value>),
+    Exists(bool),
+    Regex(Regex),
+    Not(Vec<Operator>),
+}
+
+impl CompiledFilter {
+    /// Parse a filter, failing on unknown operators, malformed operands
+    /// and invalid regular expressions.
+    pub fn compile(filter: &Value) -> Result<Self> {
+        match filter {
+            Value::Object(filter) => Ok(Self { root: Node::And(parse_conditions(filter, true)?) }),
+            other => Err(anyhow!("Invalid filter: expected an object, got {}", other)),
+        }
+    }
+
+    /// Whether a document satisfies the filter.
+    pub fn matches(&self, document: &Value) -> bool {
+        self.root.matches(document)
+    }
+}
+
+/// Parse the conditions of one filter object, all of which must hold.
+/// `top_level` is set for the filter's top-level conjunction, where `$text`
+/// may appear.
+fn parse_conditions(filter: &Map<String, Value>, top_level: bool) -> Result<Vec<Node>> {
+    filter.iter().map(|(key, condition)| parse_condition(key, condition, top_level)).collect()
+}
+
+fn parse_condition(key: &str, condition: &Value, top_level: bool) -> Result<Node> {
+    match key {
+        "$and" => Ok(Node::And(parse_filters(key, condition, top_level)?)),
+        "$or" => Ok(Node::Or(parse_filters(key, condition, false)?)),
+        "$nor" => Ok(Node::Nor(parse_filters(key, condition, false)?)),
+        "$not" => match condition {
+            Value::Object(filter) => Ok(Node::Not(Box::new(Node::And(parse_conditions(filter, false)?)))),
+            _ => Err(anyhow!("Invalid filter: $not needs a filter object")),
+        },
+        "$text" if top_level => Ok(Node::Text(TextSearch::parse(condition)?)),
+        "$text" => Err(anyhow!("Invalid filter: $text must be in the top-level conjunction of a filter")),
+        _ if key.starts_with('$') => Err(anyhow!("Invalid filter: unknown operator {}", key)),
+        _ if key.is_empty() || key.split('.').any(str::is_empty) => Err(anyhow!("Invalid filter: bad field path '{}'", key)),
+        _ => Ok(Node::Field {
+            path: key.split('.').map(String::from).collect(),
+            operators: parse_field_condition(condition)?,
+        }),
+    }
+}
+
+/// Parse the operand of `$and`, `$or` or `$nor`: a non-empty array of filters
+fn parse_filters(operator: &str, operand: &Value, top_level: bool) -> Result<Vec<Node>> {
+    match operand {
+        Value::Array(filters) if !filters.is_empty() => filters
+            .iter()
+            .map(|filter| match filter {
+                Value::Object(filter) => Ok(Node::And(parse_conditions(filter, top_level)?)),
+                _ => Err(anyhow!("Invalid filter: {} takes filter objects", operator)),
+            })
+            .collect(),
+        _ => Err(anyhow!("Invalid filter: {} needs a non-empty array", operator)),
+    }
+}
+
+/// Parse what a field is matched against: operators, or a value it must
+/// equal
+fn parse_field_condition(condition: &Value) -> Result<Vec<Operator>> {
+    match condition {
+        Value::Object(operators) if operators.keys().any(|key| key.starts_with('$')) => {
+            if !operators.keys().all(|key| key.starts_with('$')) {
+                return Err(anyhow!("Invalid filter: operators and fields mixed in {}", condition));
+            }
+            parse_operators(operators)
+        }
+        value => Ok(vec![Operator::Eq(value.clone())]),
+    }
+}
+
+fn parse_operators(operators: &Map<String, Value>) -> Result<Vec<Operator>> {
+    let mut parsed = Vec::with_capacity(operators.len());
+    for (operator, operand) in operators {
+        parsed.push(match operator.as_str() {
+            "$eq" => Operator::Eq(operand.clone()),
+            "$ne" => Operator::Ne(operand.clone()),
+            "$gt" => Operator::Compare(Ordering::Greater, false, operand.clone()),
+            "$gte" => Operator::Compare(Ordering::Greater, true, operand.clone()),
+            "$lt" => Operator::Compare(Ordering::Less, false, operand.clone()),
+            "$lte" => Operator::Compare(Ordering::Less, true, operand.clone()),
+            "$in" | "$nin" => {
+                let Value::Array(values) = operand else {
+                    return Err(anyhow!("Invalid filter: {} needs an array", operator));
+                };
+                if operator == "$in" {
+                    Operator::In(values.clone())
+                } else {
+                    Operator::Nin(values.clone())
+                }
+            }
+            "$exists" => match operand {
+                Value::Bool(exists) => Operator::Exists(*exists),
+                _ => return Err(anyhow!("Invalid filter: $exists needs true or false")),
+            },
+            "$regex" => Operator::Regex(parse_regex(operand, operators.get("$options"))?),
+            "$options" if operators.contains_key("$regex") => continue,
+            "$not" => match operand {
+                Value::Object(operators) if !operators.is_empty() && operators.keys().all(|key| key.starts_with('$')) => {
+                    Operator::Not(parse_operators(operators)?)
+                }
+                Value::String(_) => Operator::Not(vec![Operator::Regex(parse_regex(operand, None)?)]),
+                _ => return Err(anyhow!("Invalid filter: $not on a field needs operators or a pattern")),
+            },
+            _ => return Err(anyhow!("Invalid filter: unknown operator {}", operator)),
+        });
+    }
+    Ok(parsed)
+}
+
+fn parse_regex(pattern: &Value, options: Option<&Value>) -> Result<Regex> {
+    let Value::String(pattern) = pattern else {
+        return Err(anyhow!("Invalid filter: $regex needs a string pattern"));
+    };
+    let mut builder = RegexBuilder::new(pattern);
+    match options {
+        None => {}
+        Some(Value::String(options)) => {
+            for option in options.chars() {
+                match option {
+                    'i' => builder.case_insensitive(true),
+                    'm' => builder.multi_line(true),
+                    's' => builder.dot_matches_new_line(true),
+                    'x' => builder.ignore_whitespace(true),
+                    _ => return Err(anyhow!("Invalid filter: unknown $regex option '{}'", option)),
+                };
+            }
+        }
+        Some(_) => return Err(anyhow!("Invalid filter: $options needs a string")),
+    }
+    builder.build().map_err(|e| anyhow!("Invalid filter: bad $regex pattern: {}", e))
+}
+
+impl Node {
+    fn matches(&self, document: &Value) -> bool {
+        match self {
+            Node::And(nodes) => nodes.iter().all(|node| node.matches(document)),
+            Node::Or(nodes) => nodes.iter().any(|node| node.matches(document)),
+            Node::Nor(nodes) => !nodes.iter().any(|node| node.matches(document)),
+            Node::Not(node) => !node.matches(document),
+            Node::Field { path, operators } => {
+                let mut values = Vec::new();
+                resolve(document, path, &mut values);
+                operators.iter().all(|operator| operator.matches(&values))
+            }
+            Node::Text(search) => search.matches(document),
+        }
+    }
+}
+
+/// Collect the values a field path reaches in a document. Missing fields
+/// reach nothing.
+pub(crate) fn resolve<'a>(value: &'a Value, path: &[String], out: &mut Vec<&'a Value>) {
+    let Some((segment, rest)) = path.split_first() else {
+        out.push(value);
+        return;
+    };
+    match value {
+        Value::Object(fields) => {
+            if let Some(field) = fields.get(segment) {
+                resolve(field, rest, out);
+            }
+        }
+        Value::Array(elements) => match segment.parse::<usize>() {
+            Ok(index) => {
+                if let Some(element) = elements.get(index) {
+                    resolve(element, rest, out);
+                }
+            }
+            // The field of every element that is an object
+            Err(_) => {
+                for element in elements.iter().filter(|element| element.is_object()) {
+                    resolve(element, path, out);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+/// The values a predicate is tried against: each reached value, and the
+/// elements of reached arrays
+pub(crate) fn candidates<'a, 'b>(values: &'b [&'a Value]) -> impl Iterator<Item = &'a Value> + 'b {
+    values.iter().flat_map(|value: &'b &'a Value| {
+        let value: &'a Value = value;
+        let elements = match value {
+            Value::Array(elements) => elements.as_slice(),
+            _ => &[],
+        };
+        std::iter::once(value).chain(elements)
+    })
+}
+
+impl Operator {
+    fn matches(&self, values: &[&Value]) -> bool {
+        match self {
+            Operator::Eq(expected) => equals(values, expected),
+            Operator::Ne(expected) => !equals(values, expected),
+            Operator::Compare(direction, or_equal, bound) => candidates(values).any(|value| {
+                compare(value, bound).is_some_and(|order| order == *direction || (*or_equal && order == Ordering::Equal))
+            }),
+            Operator::In(expected) => expected.iter().any(|expected| equals(values, expected)),
+            Operator::Nin(expected) => !expected.iter().any(|expected| equals(values, expected)),
+            Operator::Exists(exists) => values.is_empty() != *exists,
+            Operator::Regex(pattern) => {
+                candidates(values).any(|value| value.as_str().is_some_and(|text| pattern.is_match(text)))
+            }
+            Operator::Not(operators) => !operators.iter().all(|operator| operator.matches(values)),
+        }
+    }
+}
+
+/// Whether a field equals a value, a missing field counting as `null`
+fn equals(values: &[&Value], expected: &Value) -> bool {
+    (values.is_empty() && expected.is_null()) || candidates(values).any(|value| json_eq(value, expected))
+}
+
+/// Equality of JSON values, comparing numbers by value
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => a.as_f64() == b.as_f64(),
+        },
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| json_eq(a, b)),
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| json_eq(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Order of two values of the same kind; values of different kinds are
+/// not ordered
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        },
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn matches(filter: Value, document: &Value) -> bool {
+        CompiledFilter::compile(&filter).unwrap().matches(document)
+    }
+
+    #[test]
+    fn test_comparison_and_logical_operators() {
+        let alice = json!({ "name": "Alice", "age": 30, "score": 91.5, "role": "admin", "deleted_at": null });
+
+        assert!(matches(json!({ "age": { "$gte": 18, "$lt": 65 } }), &alice));
+        assert!(matches(json!({ "age": 30.0 }), &alice));
+        assert!(matches(json!({ "score": { "$gt": 91 } }), &alice));
+        assert!(!matches(json!({ "age": { "$gt": "20" } }), &alice));
+        assert!(!matches(json!({ "missing": { "$lte": 100 } }), &alice));
+        assert!(matches(json!({ "name": { "$ne": "Bob" }, "role": { "$in": ["admin", "owner"] } }), &alice));
+        assert!(matches(json!({ "role": { "$nin": ["guest"] } }), &alice));
+
+        assert!(matches(json!({ "$or": [{ "age": { "$lt": 18 } }, { "role": "admin" }] }), &alice));
+        assert!(matches(json!({ "$and": [{ "age": 30 }, { "$nor": [{ "role": "guest" }] }] }), &alice));
+        assert!(!matches(json!({ "$not": { "name": "Alice" } }), &alice));
+        assert!(matches(json!({ "age": { "$not": { "$gt": 40 } } }), &alice));
+
+        // null and missing fields both exist as null, only missing ones don't exist
+        assert!(matches(json!({ "deleted_at": null, "archived_at": null }), &alice));
+        assert!(matches(json!({ "deleted_at": { "$exists": true }, "archived_at": { "$exists": false } }), &alice));
+
+        assert!(matches(json!({ "name": { "$regex": "^al", "$options": "i" } }), &alice));
+        assert!(!matches(json!({ "name": { "$regex": "^al" } }), &alice));
+        assert!(matches(json!({ "name": { "$not": "^B" } }), &alice));
+    }
+
+    #[test]
+    fn test_nested_field_paths() {
+        let order = json!({
+            "customer": { "address": { "city": "Oslo", "zip": "0150" } },
+            "tags": ["urgent", "gift"],
+            "items": [
+                { "sku": "A-1", "quantity": 2, "options": { "color": "red" } },
+                { "sku": "B-7", "quantity": 10 }
+            ],
+            "matrix": [[1, 2], [3, 4]]
+        });
+
+        assert!(matches(json!({ "customer.address.city": "Oslo" }), &order));
+        assert!(matches(json!({ "customer.address": { "zip": "0150", "city": "Oslo" } }), &order));
+        assert!(!matches(json!({ "customer.address.city.name": { "$exists": true } }), &order));
+        assert!(matches(json!({ "customer.phone.mobile": { "$exists": false } }), &order));
+
+        // Array elements by index, and every element of an array of objects
+        assert!(matches(json!({ "tags.1": "gift", "items.0.sku": "A-1" }), &order));
+        assert!(!matches(json!({ "tags.2": { "$exists": true } }), &order));
+        assert!(matches(json!({ "items.sku": "B-7" }), &order));
+        assert!(matches(json!({ "items.quantity": { "$gt": 5 } }), &order));
+        assert!(!matches(json!({ "items.quantity": { "$gt": 50 } }), &order));
+        assert!(matches(json!({ "items.options.color": { "$regex": "^r" } }), &order));
+        assert!(matches(json!({ "matrix.1": [3, 4], "matrix.0.1": 2 }), &order));
+
+        // A predicate on an array matches the array or any element
+        assert!(matches(json!({ "tags": "urgent" }), &order));
+        assert!(matches(json!({ "tags": ["urgent", "gift"] }), &order));
+        assert!(matches(json!({ "tags": { "$in": ["gift", "sale"] } }), &order));
+        assert!(!matches(json!({ "tags": { "$nin": ["gift"] } }), &order));
+        assert!(!matches(json!({ "tags": { "$ne": "urgent" } }), &order));
+    }
+
+    #[test]
+    fn test_invalid_filters_are_rejected() {
+        for filter in [
+            json!([{ "age": 1 }]),
+            json!({ "$where": "true" }),
+            json!({ "age": { "$between": [1, 2] } }),
+            json!({ "age": { "$in": 5 } }),
+            json!({ "age": { "$exists": "yes" } }),
+            json!({ "$or": [] }),
+            json!({ "$and": { "age": 1 } }),
+            json!({ "name": { "$regex": "(" } }),
+            json!({ "name": { "$regex": "a", "$options": "q" } }),
+            json!({ "name": { "$eq": "a", "first": "b" } }),
+            json!({ "a..b": 1 }),
+            json!({ "$or": [{ "$text": { "$search": "fox" } }, { "age": 1 }] }),
+            json!({ "$text": { "$search": "" } }),
+        ] {
+            assert!(CompiledFilter::compile(&filter).is_err(), "{} was accepted", filter);
+        }
+        assert!(matches(json!({}), &json!({ "any": "document" })));
+        assert!(matches(json!({ "$and": [{ "$text": { "$search": "foxes" } }] }), &json!({ "notes": "a fox" })));
+    }
+}
@@ -0,0 +1,378 @@
+//! Seed data loading from a declarative fixtures directory.
+//!
+//! Demos, tests, and fresh environments can start with known data by placing
+//! fixture files in a directory that is loaded at startup:
+//!
+//! - **YAML** (`.yaml`/`.yml`): either a list of documents, or a mapping with
+//!   optional `collection`, `indexes`, and `documents` keys
+//! - **NDJSON** (`.ndjson`/`.jsonl`): one JSON document per line
+//!
+//! The collection defaults to the file name without its extension, and every
+//! document must carry an `id` field. Files are applied in name order.
+//!
+//! Loading is idempotent. Each applied file is recorded with a checksum and
+//! skipped on later boots while unchanged, and documents or index definitions
+//! that already exist are never overwritten, so data edited after seeding is
+//! left alone. Changing a fixture file only adds its new documents.
+//!
+//! ```yaml
+//! # fixtures/users.yaml
+//! indexes:
+//!   - fields: [email]
+//!     unique: true
+//!   - fields: [bio]
+//!     text: true
+//! documents:
+//!   - id: alice
+//!     email: alice@example.com
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use aerolithdb_storage::StorageHierarchy;
+
+/// System collection recording which fixture files have been applied
+pub const FIXTURES_COLLECTION: &str = "_fixtures";
+
+/// System collection holding declared index definitions
+pub const INDEX_COLLECTION: &str = "_indexes";
+
+/// A secondary index declared for a collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    /// Index name; derived from the fields when omitted
+    #[serde(default)]
+    pub name: Option<String>,
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub unique: bool,
+
+    /// Full-text index over the fields' text, searched with `$text`,
+    /// instead of a lookup on the leading field
+    #[serde(default)]
+    pub text: bool,
+}
+
+impl IndexDefinition {
+    pub fn effective_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| match self.text {
+            true => format!("{}_text", self.fields.join("_")),
+            false => self.fields.join("_"),
+        })
+    }
+}
+
+/// Summary of a fixtures run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FixtureReport {
+    /// Files applied for the first time or since they changed
+    pub files_applied: usize,
+    /// Files skipped because they were already applied unchanged
+    pub files_skipped: usize,
+    pub documents_inserted: usize,
+    /// Documents left untouched because they already existed
+    pub documents_existing: usize,
+    pub indexes_defined: usize,
+}
+
+/// Parsed contents of one fixture file
+#[derive(Debug, Default)]
+struct Fixture {
+    collection: String,
+    indexes: Vec<IndexDefinition>,
+    documents: Vec<(String, serde_json::Value)>,
+}
+
+/// YAML fixtures may be a full description or just a list of documents
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum YamlFixture {
+    Full {
+        collection: Option<String>,
+        #[serde(default)]
+        indexes: Vec<IndexDefinition>,
+        #[serde(default)]
+        documents: Vec<serde_json::Value>,
+    },
+    Documents(Vec<serde_json::Value>),
+}
+
+/// Record of an applied fixture file
+#[derive(Debug, Serialize, Deserialize)]
+struct AppliedFixture {
+    file: String,
+    checksum: String,
+    collection: String,
+    applied_at: DateTime<Utc>,
+}
+
+/// Loads fixture files into storage
+#[derive(Debug)]
+pub struct FixtureLoader {
+    storage: Arc<StorageHierarchy>,
+}
+
+impl FixtureLoader {
+    pub fn new(storage: Arc<StorageHierarchy>) -> Self {
+        Self { storage }
+    }
+
+    /// Apply every fixture file in a directory that has not been applied yet
+    pub async fn load_dir(&self, dir: &Path) -> Result<FixtureReport> {
+        let mut report = FixtureReport::default();
+
+        if !dir.is_dir() {
+            debug!("No fixtures directory at {}", dir.display());
+            return Ok(report);
+        }
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.is_file() && fixture_format(&path).is_some() {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        for path in files {
+            self.load_file(&path, &mut report).await?;
+        }
+
+        info!(
+            "Fixtures loaded from {}: {} files applied, {} skipped, {} documents inserted, {} already present",
+            dir.display(), report.files_applied, report.files_skipped,
+            report.documents_inserted, report.documents_existing
+        );
+        Ok(report)
+    }
+
+    async fn load_file(&self, path: &Path, report: &mut FixtureReport) -> Result<()> {
+        let file_name = path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid fixture file name: {}", path.display()))?
+            .to_string();
+
+        let content = tokio::fs::read_to_string(path).await?;
+        let checksum = blake3::hash(content.as_bytes()).to_hex().to_string();
+
+        if let Some(applied) = self.applied_fixture(&file_name).await? {
+            if applied.checksum == checksum {
+                debug!("Fixture {} already applied", file_name);
+                report.files_skipped += 1;
+                return Ok(());
+            }
+            info!("Fixture {} changed since it was applied; adding new documents", file_name);
+        }
+
+        let fixture = parse_fixture(path, &content)?;
+
+        for index in &fixture.indexes {
+            let key = format!("{}.{}", fixture.collection, index.effective_name());
+            let definition = serde_json::json!({
+                "collection": fixture.collection,
+                "name": index.effective_name(),
+                "fields": index.fields,
+                "unique": index.unique,
+                "text": index.text,
+            });
+            if self.insert_if_absent(INDEX_COLLECTION, &key, &definition).await? {
+                report.indexes_defined += 1;
+            }
+        }
+
+        for (id, document) in &fixture.documents {
+            if self.insert_if_absent(&fixture.collection, id, document).await? {
+                report.documents_inserted += 1;
+            } else {
+                report.documents_existing += 1;
+            }
+        }
+
+        let applied = AppliedFixture {
+            file: file_name.clone(),
+            checksum,
+            collection: fixture.collection.clone(),
+            applied_at: Utc::now(),
+        };
+        self.storage
+            .store_document(FIXTURES_COLLECTION, &file_name, &serde_json::to_value(&applied)?)
+            .await?;
+
+        report.files_applied += 1;
+        debug!("Applied fixture {} to collection {}", file_name, fixture.collection);
+        Ok(())
+    }
+
+    async fn applied_fixture(&self, file_name: &str) -> Result<Option<AppliedFixture>> {
+        match self.storage.get_document(FIXTURES_COLLECTION, file_name).await?.data {
+            Some(document) => Ok(Some(serde_json::from_value(document)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store a document unless one with the same ID exists; returns whether it was stored
+    async fn insert_if_absent(&self, collection: &str, id: &str, document: &serde_json::Value) -> Result<bool> {
+        if self.storage.get_document(collection, id).await?.data.is_some() {
+            return Ok(false);
+        }
+        self.storage.store_document(collection, id, document).await?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FixtureFormat {
+    Yaml,
+    Ndjson,
+}
+
+fn fixture_format(path: &Path) -> Option<FixtureFormat> {
+    if path.file_name()?.to_str()?.starts_with('.') {
+        return None;
+    }
+    match path.extension()?.to_str()? {
+        "yaml" | "yml" => Some(FixtureFormat::Yaml),
+        "ndjson" | "jsonl" => Some(FixtureFormat::Ndjson),
+        _ => None,
+    }
+}
+
+fn parse_fixture(path: &Path, content: &str) -> Result<Fixture> {
+    let file = path.display();
+    let stem = path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut fixture = Fixture {
+        collection: stem,
+        ..Default::default()
+    };
+
+    let documents = match fixture_format(path) {
+        Some(FixtureFormat::Yaml) => {
+            let parsed: YamlFixture = serde_yaml::from_str(content)
+                .map_err(|e| anyhow::anyhow!("Invalid YAML fixture {}: {}", file, e))?;
+            match parsed {
+                YamlFixture::Full { collection, indexes, documents } => {
+                    if let Some(collection) = collection {
+                        fixture.collection = collection;
+                    }
+                    fixture.indexes = indexes;
+                    documents
+                }
+                YamlFixture::Documents(documents) => documents,
+            }
+        }
+        Some(FixtureFormat::Ndjson) => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| anyhow::anyhow!("Invalid JSON in fixture {} line {}: {}", file, number + 1, e))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => return Err(anyhow::anyhow!("Unsupported fixture format: {}", file)),
+    };
+
+    if fixture.collection.is_empty() || fixture.collection.starts_with('_') {
+        return Err(anyhow::anyhow!(
+            "Fixture {} targets invalid collection '{}'; system collections cannot be seeded",
+            file, fixture.collection
+        ));
+    }
+
+    for index in &fixture.indexes {
+        if index.fields.is_empty() {
+            return Err(anyhow::anyhow!("Fixture {} declares an index without fields", file));
+        }
+    }
+
+    for (position, document) in documents.into_iter().enumerate() {
+        let id = match document.get("id") {
+            Some(serde_json::Value::String(id)) if !id.is_empty() => id.clone(),
+            Some(serde_json::Value::Number(id)) => id.to_string(),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Document {} in fixture {} needs a string or numeric 'id' field", position + 1, file
+                ));
+            }
+        };
+        fixture.documents.push((id, document));
+    }
+
+    Ok(fixture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_yaml_and_ndjson() {
+        let yaml = "collection: people\nindexes:\n  - fields: [email]\n    unique: true\ndocuments:\n  - id: alice\n    email: alice@example.com\n";
+        let fixture = parse_fixture(Path::new("users.yaml"), yaml).unwrap();
+        assert_eq!(fixture.collection, "people");
+        assert_eq!(fixture.indexes[0].effective_name(), "email");
+        assert_eq!(fixture.documents[0].0, "alice");
+
+        let list = parse_fixture(Path::new("tags.yml"), "- id: 1\n  label: red\n").unwrap();
+        assert_eq!(list.collection, "tags");
+        assert_eq!(list.documents[0].0, "1");
+
+        let ndjson = "{\"id\": \"o1\", \"total\": 10}\n\n{\"id\": \"o2\", \"total\": 5}\n";
+        let orders = parse_fixture(Path::new("orders.ndjson"), ndjson).unwrap();
+        assert_eq!(orders.collection, "orders");
+        assert_eq!(orders.documents.len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_invalid_fixtures() {
+        assert!(parse_fixture(Path::new("users.ndjson"), "{\"name\": \"no id\"}\n").is_err());
+        assert!(parse_fixture(Path::new("_sessions.yaml"), "- id: x\n").is_err());
+        assert!(parse_fixture(Path::new("users.ndjson"), "{not json}\n").is_err());
+        assert!(fixture_format(Path::new("README.md")).is_none());
+        assert!(fixture_format(Path::new(".hidden.yaml")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_loading_is_idempotent() {
+        let root = std::env::temp_dir().join(format!("aerolith-fixtures-{}", uuid::Uuid::new_v4()));
+        let fixtures = root.join("fixtures");
+        std::fs::create_dir_all(&fixtures).unwrap();
+        std::fs::write(fixtures.join("users.yaml"), "- id: alice\n  name: Alice\n- id: bob\n  name: Bob\n").unwrap();
+
+        let storage_config = aerolithdb_storage::StorageConfig {
+            data_dir: root.join("data"),
+            ..Default::default()
+        };
+        let storage = Arc::new(StorageHierarchy::new(&storage_config).await.unwrap());
+        let loader = FixtureLoader::new(Arc::clone(&storage));
+
+        let first = loader.load_dir(&fixtures).await.unwrap();
+        assert_eq!(first.files_applied, 1);
+        assert_eq!(first.documents_inserted, 2);
+
+        let second = loader.load_dir(&fixtures).await.unwrap();
+        assert_eq!(second.files_skipped, 1);
+        assert_eq!(second.documents_inserted, 0);
+
+        // Edits to seeded data survive a changed fixture file
+        storage.store_document("users", "alice", &serde_json::json!({"id": "alice", "name": "Alicia"})).await.unwrap();
+        std::fs::write(fixtures.join("users.yaml"), "- id: alice\n  name: Alice\n- id: carol\n  name: Carol\n").unwrap();
+        let third = loader.load_dir(&fixtures).await.unwrap();
+        assert_eq!(third.documents_inserted, 1);
+        assert_eq!(third.documents_existing, 1);
+        let alice = storage.get_document("users", "alice").await.unwrap().data.unwrap();
+        assert_eq!(alice["name"], "Alicia");
+    }
+}
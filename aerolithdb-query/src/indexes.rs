@@ -0,0 +1,732 @@
+//! # Secondary Indexes
+//!
+//! Indexes declared for a collection, by fixture files, by
+//! `#[derive(AerolithDocument)]` or through [`SecondaryIndexes::create`],
+//! let a query read only the documents one of its predicates can match
+//! instead of the whole collection. An index maps the values of its leading
+//! field to the documents holding them, with one entry per element when the
+//! field holds an array, so equality, `$in` and range predicates on that
+//! field are answered by a lookup. Further fields of a compound index are
+//! not used for lookups. Documents found through an index are still checked
+//! against the whole filter, so an index only narrows what is read.
+//!
+//! A text index, declared with `text: true`, instead maps the terms of
+//! its fields' text to the documents holding them for `$text` searches; see
+//! [`crate::text`].
+//!
+//! Definitions live in the `_indexes` system collection. Entries are kept in
+//! memory and brought up to date before each lookup by comparing the version
+//! of every document of the collection, known from its metadata, with the
+//! version indexed. Writes made by any path, including restores, sync and
+//! expiration, are therefore seen without hooks into storage; the first
+//! lookup builds the index. Writes through the query engine also update
+//! built entries as they are made, so lookups after them find little to
+//! catch up on.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use aerolithdb_storage::{ListOptions, StorageHierarchy};
+
+use crate::filter::{candidates, resolve};
+use crate::fixtures::{IndexDefinition, INDEX_COLLECTION};
+use crate::text::{TextEntries, TextSearch};
+
+/// What identifies one revision of a document
+pub(crate) type DocumentStamp = (u64, DateTime<Utc>);
+
+/// Entries of one index, locked while they are brought up to date
+type SharedEntries = Arc<Mutex<IndexEntries>>;
+
+/// Entries of one text index
+type SharedTextEntries = Arc<Mutex<TextEntries>>;
+
+/// Entries of an index, kept up to date one document at a time.
+pub(crate) trait Entries {
+    /// Revision of a document indexed, if it is
+    fn stamp(&self, id: &str) -> Option<DocumentStamp>;
+
+    /// IDs of the documents indexed
+    fn ids(&self) -> Vec<String>;
+
+    /// Index a revision of a document, replacing the one indexed
+    fn insert(&mut self, id: &str, stamp: DocumentStamp, document: &Value);
+
+    /// Forget a document
+    fn remove(&mut self, id: &str);
+}
+
+/// A number ordered by value, with negative zero folded into zero
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IndexNumber(f64);
+
+impl PartialEq for IndexNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexNumber {}
+
+impl PartialOrd for IndexNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IndexNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A value held by an indexed field. Keys of one type sort together, so a
+/// range over numbers or over strings is one contiguous run of keys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum IndexKey {
+    Bool(bool),
+    Number(IndexNumber),
+    String(String),
+}
+
+impl IndexKey {
+    /// The key of a scalar value; null, arrays and objects have none
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(value) => Some(IndexKey::Bool(*value)),
+            Value::Number(number) => number.as_f64().map(|number| IndexKey::Number(IndexNumber(number + 0.0))),
+            Value::String(value) => Some(IndexKey::String(value.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// The keys of an index a predicate on its field can match.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum IndexLookup {
+    /// Any of these keys
+    Keys(Vec<IndexKey>),
+
+    /// Keys from the first to the second, inclusive, or to the last key when
+    /// there is no second
+    Range(IndexKey, Option<IndexKey>),
+
+    /// No key; the predicate matches no document
+    Nothing,
+}
+
+impl IndexLookup {
+    /// The lookup answering a condition on an indexed field, if an index
+    /// can: an equality or `$in` on scalar values other than null, or range
+    /// operators. Other operators of the condition are left to the filter.
+    pub(crate) fn for_condition(condition: &Value) -> Option<Self> {
+        let operators = match condition {
+            Value::Object(operators) if operators.keys().any(|key| key.starts_with('$')) => operators,
+            value => return Self::keys(std::slice::from_ref(value)),
+        };
+
+        if let Some(lookup) = operators.get("$eq").and_then(|value| Self::keys(std::slice::from_ref(value))) {
+            return Some(lookup);
+        }
+        if let Some(Value::Array(values)) = operators.get("$in") {
+            if let Some(lookup) = Self::keys(values) {
+                return Some(lookup);
+            }
+        }
+
+        let mut range: Option<(IndexKey, Option<IndexKey>)> = None;
+        for (operator, bound) in operators {
+            let bounds = match (operator.as_str(), bound) {
+                ("$gt" | "$gte", Value::Number(_)) => (IndexKey::of(bound)?, Some(IndexKey::Number(IndexNumber(f64::INFINITY)))),
+                ("$lt" | "$lte", Value::Number(_)) => (IndexKey::Number(IndexNumber(f64::NEG_INFINITY)), Some(IndexKey::of(bound)?)),
+                ("$gt" | "$gte", Value::String(_)) => (IndexKey::of(bound)?, None),
+                ("$lt" | "$lte", Value::String(_)) => (IndexKey::String(String::new()), Some(IndexKey::of(bound)?)),
+                // Other values are never ordered against a field
+                ("$gt" | "$gte" | "$lt" | "$lte", _) => return Some(IndexLookup::Nothing),
+                _ => continue,
+            };
+            range = Some(match range {
+                None => bounds,
+                Some((low, high)) => {
+                    let high = match (high, bounds.1) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                    (low.max(bounds.0), high)
+                }
+            });
+        }
+
+        range.map(|(low, high)| match &high {
+            Some(high) if *high < low => IndexLookup::Nothing,
+            _ => IndexLookup::Range(low, high),
+        })
+    }
+
+    fn keys(values: &[Value]) -> Option<Self> {
+        values.iter().map(IndexKey::of).collect::<Option<Vec<_>>>().map(IndexLookup::Keys)
+    }
+}
+
+/// The entries of one index.
+#[derive(Debug, Default)]
+struct IndexEntries {
+    /// Fields the entries were built for
+    fields: Vec<String>,
+
+    /// Documents holding each key
+    keys: BTreeMap<IndexKey, BTreeSet<String>>,
+
+    /// Revision indexed and keys held, by document ID
+    documents: HashMap<String, (DocumentStamp, Vec<IndexKey>)>,
+}
+
+impl IndexEntries {
+    fn new(fields: &[String]) -> Self {
+        Self { fields: fields.to_vec(), ..Self::default() }
+    }
+
+    /// IDs of the documents a lookup finds, sorted
+    fn lookup(&self, lookup: &IndexLookup) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        match lookup {
+            IndexLookup::Keys(keys) => {
+                for key in keys {
+                    found.extend(self.keys.get(key).into_iter().flatten().cloned());
+                }
+            }
+            IndexLookup::Range(low, high) => {
+                let entries: Box<dyn Iterator<Item = (&IndexKey, &BTreeSet<String>)>> = match high {
+                    Some(high) => Box::new(self.keys.range(low.clone()..=high.clone())),
+                    None => Box::new(self.keys.range(low.clone()..)),
+                };
+                for (_, ids) in entries {
+                    found.extend(ids.iter().cloned());
+                }
+            }
+            IndexLookup::Nothing => {}
+        }
+        found
+    }
+}
+
+impl Entries for IndexEntries {
+    fn stamp(&self, id: &str) -> Option<DocumentStamp> {
+        self.documents.get(id).map(|(stamp, _)| *stamp)
+    }
+
+    fn ids(&self) -> Vec<String> {
+        self.documents.keys().cloned().collect()
+    }
+
+    fn insert(&mut self, id: &str, stamp: DocumentStamp, document: &Value) {
+        self.remove(id);
+
+        let path: Vec<String> = self.fields[0].split('.').map(String::from).collect();
+        let mut values = Vec::new();
+        resolve(document, &path, &mut values);
+        let mut keys: Vec<IndexKey> = candidates(&values).filter_map(IndexKey::of).collect();
+        keys.sort();
+        keys.dedup();
+
+        for key in &keys {
+            self.keys.entry(key.clone()).or_default().insert(id.to_string());
+        }
+        self.documents.insert(id.to_string(), (stamp, keys));
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some((_, keys)) = self.documents.remove(id) else {
+            return;
+        };
+        for key in keys {
+            if let Some(ids) = self.keys.get_mut(&key) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.keys.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// An index definition as stored in the `_indexes` collection.
+#[derive(Debug, Clone, Deserialize)]
+struct DeclaredIndex {
+    collection: String,
+    #[serde(flatten)]
+    definition: IndexDefinition,
+}
+
+/// Secondary indexes of all collections: their declared definitions and
+/// in-memory entries.
+pub struct SecondaryIndexes {
+    storage: Arc<StorageHierarchy>,
+
+    /// Declarations by `_indexes` document ID, with the revision read
+    declared: Mutex<HashMap<String, (DocumentStamp, DeclaredIndex)>>,
+
+    /// Entries by collection and index name
+    entries: Mutex<HashMap<(String, String), SharedEntries>>,
+
+    /// Entries of text indexes by collection and index name
+    text_entries: Mutex<HashMap<(String, String), SharedTextEntries>>,
+}
+
+impl std::fmt::Debug for SecondaryIndexes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecondaryIndexes").finish_non_exhaustive()
+    }
+}
+
+impl SecondaryIndexes {
+    pub fn new(storage: Arc<StorageHierarchy>) -> Self {
+        Self {
+            storage,
+            declared: Mutex::new(HashMap::new()),
+            entries: Mutex::new(HashMap::new()),
+            text_entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Indexes declared for a collection, by name.
+    pub async fn definitions(&self, collection: &str) -> Result<Vec<IndexDefinition>> {
+        let declared = self.refresh_declared().await?;
+        let mut definitions: Vec<IndexDefinition> = declared
+            .values()
+            .filter(|(_, declared)| declared.collection == collection)
+            .map(|(_, declared)| IndexDefinition {
+                name: Some(declared.definition.effective_name()),
+                ..declared.definition.clone()
+            })
+            .collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(definitions)
+    }
+
+    /// Declare an index on a collection, replacing one of the same name.
+    pub async fn create(&self, collection: &str, definition: IndexDefinition) -> Result<IndexDefinition> {
+        if collection.is_empty() || collection.starts_with('_') {
+            return Err(anyhow!("Invalid index: cannot index collection '{}'", collection));
+        }
+        if definition.fields.is_empty() {
+            return Err(anyhow!("Invalid index: no fields given"));
+        }
+        if let Some(field) = definition
+            .fields
+            .iter()
+            .find(|field| field.starts_with('$') || field.is_empty() || field.split('.').any(str::is_empty))
+        {
+            return Err(anyhow!("Invalid index: bad field path '{}'", field));
+        }
+
+        let name = definition.effective_name();
+        if definition.text {
+            if definition.unique {
+                return Err(anyhow!("Invalid index: text indexes cannot be unique"));
+            }
+            if let Some(other) = self.text_definition(collection).await?.filter(|other| other.name.as_deref() != Some(&name)) {
+                return Err(anyhow!(
+                    "Invalid index: collection '{}' already has text index {}",
+                    collection,
+                    other.effective_name()
+                ));
+            }
+        }
+        let document = serde_json::json!({
+            "collection": collection,
+            "name": name,
+            "fields": definition.fields,
+            "unique": definition.unique,
+            "text": definition.text,
+        });
+        self.storage
+            .store_document(INDEX_COLLECTION, &format!("{}.{}", collection, name), &document)
+            .await?;
+        info!("Declared index {} on {} ({})", name, collection, definition.fields.join(", "));
+        Ok(IndexDefinition { name: Some(name), ..definition })
+    }
+
+    /// Remove an index from a collection, returning whether it existed.
+    pub async fn remove(&self, collection: &str, name: &str) -> Result<bool> {
+        let key = format!("{}.{}", collection, name);
+        if self.storage.get_document(INDEX_COLLECTION, &key).await?.data.is_none() {
+            return Ok(false);
+        }
+        self.storage.delete_document(INDEX_COLLECTION, &key).await?;
+        self.entries.lock().await.remove(&(collection.to_string(), name.to_string()));
+        self.text_entries.lock().await.remove(&(collection.to_string(), name.to_string()));
+        info!("Dropped index {} on {}", name, collection);
+        Ok(true)
+    }
+
+    /// IDs of the documents of a collection an index finds for a predicate,
+    /// sorted, or `None` if the index or the predicate cannot be used.
+    pub(crate) async fn scan(&self, collection: &str, index: &str, predicate: &Value) -> Result<Option<Vec<String>>> {
+        let Some(definition) = self
+            .definitions(collection)
+            .await?
+            .into_iter()
+            .find(|definition| !definition.text && definition.name.as_deref() == Some(index))
+        else {
+            return Ok(None);
+        };
+        let Some(lookup) = predicate.get(&definition.fields[0]).and_then(IndexLookup::for_condition) else {
+            return Ok(None);
+        };
+
+        let entries = Arc::clone(
+            self.entries
+                .lock()
+                .await
+                .entry((collection.to_string(), index.to_string()))
+                .or_insert_with(|| Arc::new(Mutex::new(IndexEntries::new(&definition.fields)))),
+        );
+        let mut entries = entries.lock().await;
+        if entries.fields != definition.fields {
+            *entries = IndexEntries::new(&definition.fields);
+        }
+        self.catch_up(collection, &mut *entries).await?;
+        Ok(Some(entries.lookup(&lookup).into_iter().collect()))
+    }
+
+    /// The text index of a collection, if it has one.
+    pub(crate) async fn text_definition(&self, collection: &str) -> Result<Option<IndexDefinition>> {
+        Ok(self.definitions(collection).await?.into_iter().find(|definition| definition.text))
+    }
+
+    /// Documents of a collection a text search finds, with their relevance,
+    /// by ID. Fails when the collection has no text index.
+    pub(crate) async fn search(&self, collection: &str, search: &TextSearch) -> Result<BTreeMap<String, f64>> {
+        let definition = self
+            .text_definition(collection)
+            .await?
+            .ok_or_else(|| anyhow!("Invalid filter: $text needs a text index on collection '{}'", collection))?;
+        let name = definition.effective_name();
+
+        let entries = Arc::clone(
+            self.text_entries
+                .lock()
+                .await
+                .entry((collection.to_string(), name))
+                .or_insert_with(|| Arc::new(Mutex::new(TextEntries::new(&definition.fields)))),
+        );
+        let mut entries = entries.lock().await;
+        if entries.fields != definition.fields {
+            *entries = TextEntries::new(&definition.fields);
+        }
+        self.catch_up(collection, &mut *entries).await?;
+        Ok(entries.search(search))
+    }
+
+    /// Bring the built entries of a collection's indexes up to date with a
+    /// document just written or deleted.
+    pub(crate) async fn document_changed(&self, collection: &str, document_id: &str) {
+        let entries: Vec<SharedEntries> = self
+            .entries
+            .lock()
+            .await
+            .iter()
+            .filter(|((indexed, _), _)| indexed == collection)
+            .map(|(_, entries)| Arc::clone(entries))
+            .collect();
+        let text_entries: Vec<SharedTextEntries> = self
+            .text_entries
+            .lock()
+            .await
+            .iter()
+            .filter(|((indexed, _), _)| indexed == collection)
+            .map(|(_, entries)| Arc::clone(entries))
+            .collect();
+        if entries.is_empty() && text_entries.is_empty() {
+            return;
+        }
+
+        let current = self.storage.get_document(collection, document_id).await.ok().and_then(|result| {
+            let metadata = result.metadata?;
+            Some(((metadata.version, metadata.updated_at), result.data?))
+        });
+        for entries in entries {
+            update(&mut *entries.lock().await, document_id, current.as_ref());
+        }
+        for entries in text_entries {
+            update(&mut *entries.lock().await, document_id, current.as_ref());
+        }
+    }
+
+    /// Index the documents written and forget those removed since the
+    /// entries were last brought up to date.
+    async fn catch_up(&self, collection: &str, entries: &mut impl Entries) -> Result<()> {
+        let current: HashMap<String, DocumentStamp> = self
+            .storage
+            .list_documents_with(collection, &ListOptions::default())?
+            .documents
+            .into_iter()
+            .map(|document| (document.id, (document.version, document.updated_at)))
+            .collect();
+
+        let removed: Vec<String> = entries.ids().into_iter().filter(|id| !current.contains_key(id)).collect();
+        for id in &removed {
+            entries.remove(id);
+        }
+
+        let mut indexed = 0;
+        for (id, stamp) in &current {
+            if entries.stamp(id).as_ref() == Some(stamp) {
+                continue;
+            }
+            match self.storage.get_document(collection, id).await.ok().and_then(|result| result.data) {
+                Some(document) => entries.insert(id, *stamp, &document),
+                None => entries.remove(id),
+            }
+            indexed += 1;
+        }
+
+        if indexed > 0 || !removed.is_empty() {
+            debug!("Index on {} caught up: {} indexed, {} removed", collection, indexed, removed.len());
+        }
+        Ok(())
+    }
+
+    /// Reread the declarations written or removed since they were last read.
+    async fn refresh_declared(&self) -> Result<tokio::sync::MutexGuard<'_, HashMap<String, (DocumentStamp, DeclaredIndex)>>> {
+        let current: HashMap<String, DocumentStamp> = self
+            .storage
+            .list_documents_with(INDEX_COLLECTION, &ListOptions::default())?
+            .documents
+            .into_iter()
+            .map(|document| (document.id, (document.version, document.updated_at)))
+            .collect();
+
+        let mut declared = self.declared.lock().await;
+        let removed: Vec<String> = declared.keys().filter(|id| !current.contains_key(*id)).cloned().collect();
+        for id in removed {
+            if let Some((_, index)) = declared.remove(&id) {
+                let key = (index.collection, index.definition.effective_name());
+                self.entries.lock().await.remove(&key);
+                self.text_entries.lock().await.remove(&key);
+            }
+        }
+
+        for (id, stamp) in current {
+            if declared.get(&id).is_some_and(|(read, _)| *read == stamp) {
+                continue;
+            }
+            let document = self.storage.get_document(INDEX_COLLECTION, &id).await?.data;
+            match document.map(serde_json::from_value::<DeclaredIndex>) {
+                Some(Ok(index)) if !index.definition.fields.is_empty() => {
+                    declared.insert(id, (stamp, index));
+                }
+                Some(Err(e)) => {
+                    tracing::warn!("Ignoring malformed index declaration {}: {}", id, e);
+                    declared.remove(&id);
+                }
+                _ => {
+                    declared.remove(&id);
+                }
+            }
+        }
+        Ok(declared)
+    }
+}
+
+/// Index the current revision of a document, or forget it once deleted.
+fn update(entries: &mut impl Entries, id: &str, current: Option<&(DocumentStamp, Value)>) {
+    match current {
+        Some((stamp, _)) if entries.stamp(id).as_ref() == Some(stamp) => {}
+        Some((stamp, document)) => entries.insert(id, *stamp, document),
+        None => entries.remove(id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::PlannerMode;
+    use crate::text::SCORE_FIELD;
+    use crate::types::QueryRequest;
+    use serde_json::json;
+
+    fn stamp(version: u64) -> DocumentStamp {
+        (version, DateTime::<Utc>::MIN_UTC)
+    }
+
+    fn found(entries: &IndexEntries, condition: Value) -> Vec<String> {
+        let lookup = IndexLookup::for_condition(&condition).unwrap();
+        entries.lookup(&lookup).into_iter().collect()
+    }
+
+    #[test]
+    fn test_lookups_find_every_matching_document() {
+        let mut entries = IndexEntries::new(&["age".to_string()]);
+        entries.insert("a", stamp(1), &json!({ "age": 17 }));
+        entries.insert("b", stamp(1), &json!({ "age": 30.0 }));
+        entries.insert("c", stamp(1), &json!({ "age": [45, "unknown"] }));
+        entries.insert("d", stamp(1), &json!({ "name": "no age" }));
+        entries.insert("e", stamp(1), &json!({ "age": -0.0 }));
+
+        assert_eq!(found(&entries, json!(30)), ["b"]);
+        assert_eq!(found(&entries, json!(0)), ["e"]);
+        assert_eq!(found(&entries, json!({ "$in": [17, 45] })), ["a", "c"]);
+        assert_eq!(found(&entries, json!({ "$gte": 18, "$lt": 50 })), ["b", "c"]);
+        assert_eq!(found(&entries, json!({ "$gt": 18, "$ne": 30 })), ["b", "c"]);
+        assert_eq!(found(&entries, json!({ "$gte": "a" })), ["c"]);
+        assert_eq!(found(&entries, json!({ "$gt": 50, "$lt": 10 })), Vec::<String>::new());
+        assert_eq!(IndexLookup::for_condition(&json!({ "$gt": true })), Some(IndexLookup::Nothing));
+
+        // Rewritten documents lose their old keys
+        entries.insert("b", stamp(2), &json!({ "age": 31 }));
+        assert!(found(&entries, json!(30)).is_empty());
+        entries.remove("c");
+        assert_eq!(found(&entries, json!({ "$gte": 18 })), ["b"]);
+    }
+
+    #[test]
+    fn test_only_some_conditions_use_an_index() {
+        for condition in [
+            json!(null),
+            json!([1, 2]),
+            json!({ "city": "Oslo" }),
+            json!({ "$in": [1, null] }),
+            json!({ "$ne": 5 }),
+            json!({ "$exists": true }),
+            json!({ "$regex": "^a" }),
+        ] {
+            assert_eq!(IndexLookup::for_condition(&condition), None, "{} used an index", condition);
+        }
+        assert!(IndexLookup::for_condition(&json!({ "$regex": "^a", "$eq": "alice" })).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_indexes_follow_writes() {
+        let root = std::env::temp_dir().join(format!("aerolith-indexes-{}", uuid::Uuid::new_v4()));
+        let storage_config = aerolithdb_storage::StorageConfig { data_dir: root.join("data"), ..Default::default() };
+        let storage = Arc::new(StorageHierarchy::new(&storage_config).await.unwrap());
+        let indexes = SecondaryIndexes::new(Arc::clone(&storage));
+
+        for (id, city) in [("a", "Oslo"), ("b", "Bergen"), ("c", "Oslo")] {
+            storage.store_document("users", id, &json!({ "address": { "city": city } })).await.unwrap();
+        }
+        let definition = IndexDefinition { name: None, fields: vec!["address.city".to_string()], unique: false, text: false };
+        assert_eq!(indexes.create("users", definition).await.unwrap().name.as_deref(), Some("address.city"));
+        assert!(indexes.create("_indexes", IndexDefinition { name: None, fields: vec!["x".to_string()], unique: false, text: false }).await.is_err());
+
+        let oslo = json!({ "address.city": "Oslo" });
+        assert_eq!(indexes.scan("users", "address.city", &oslo).await.unwrap(), Some(vec!["a".to_string(), "c".to_string()]));
+
+        // Writes and deletes made straight to storage are picked up
+        storage.store_document("users", "b", &json!({ "address": { "city": "Oslo" } })).await.unwrap();
+        storage.delete_document("users", "a").await.unwrap();
+        assert_eq!(indexes.scan("users", "address.city", &oslo).await.unwrap(), Some(vec!["b".to_string(), "c".to_string()]));
+
+        assert!(indexes.remove("users", "address.city").await.unwrap());
+        assert!(indexes.definitions("users").await.unwrap().is_empty());
+        assert_eq!(indexes.scan("users", "address.city", &oslo).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_text_indexes_rank_and_follow_writes() {
+        let root = std::env::temp_dir().join(format!("aerolith-text-{}", uuid::Uuid::new_v4()));
+        let storage_config = aerolithdb_storage::StorageConfig { data_dir: root.join("data"), ..Default::default() };
+        let storage = Arc::new(StorageHierarchy::new(&storage_config).await.unwrap());
+        let indexes = SecondaryIndexes::new(Arc::clone(&storage));
+
+        let posts = [
+            ("a", "Foxes in the city", "Urban foxes hunt at night", "open"),
+            ("b", "Gardening", "A fox crossed the garden once", "open"),
+            ("c", "Fox watching", "Watching foxes and their cubs", "closed"),
+            ("d", "Dogs", "Dogs sleep all day", "open"),
+        ];
+        for (id, title, body, status) in posts {
+            storage.store_document("posts", id, &json!({ "title": title, "body": body, "status": status })).await.unwrap();
+        }
+        let text = |fields: &[&str]| IndexDefinition {
+            name: None,
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            unique: false,
+            text: true,
+        };
+        assert_eq!(indexes.create("posts", text(&["title", "body"])).await.unwrap().name.as_deref(), Some("title_body_text"));
+        assert!(indexes.create("posts", text(&["body"])).await.is_err());
+
+        // Matches come back best first with their score, and the rest of the
+        // filter still applies
+        let query = QueryRequest::with_filter(json!({ "$text": { "$search": "fox" }, "status": "open" }));
+        let context = crate::execution::QueryContext::unbounded("posts");
+        let execution =
+            crate::engine::execute_query(&storage, &indexes, "posts", &query, PlannerMode::CostBased, false, &context).await.unwrap();
+        assert_eq!(execution.ids, ["a", "b"]);
+        let scores: Vec<f64> = execution.documents.iter().map(|document| document[SCORE_FIELD].as_f64().unwrap()).collect();
+        assert!(scores[0] > scores[1]);
+
+        // Writes reported by the engine update the built index in place
+        storage.store_document("posts", "d", &json!({ "title": "Dogs", "body": "A dog chased a fox", "status": "open" })).await.unwrap();
+        indexes.document_changed("posts", "d").await;
+        storage.delete_document("posts", "a").await.unwrap();
+        indexes.document_changed("posts", "a").await;
+        let search = TextSearch::parse(&json!({ "$search": "fox -garden" })).unwrap();
+        assert_eq!(indexes.search("posts", &search).await.unwrap().keys().collect::<Vec<_>>(), ["c", "d"]);
+
+        assert!(indexes.remove("posts", "title_body_text").await.unwrap());
+        assert!(indexes.search("posts", &search).await.unwrap_err().to_string().starts_with("Invalid filter"));
+    }
+
+    #[tokio::test]
+    async fn test_as_of_queries_read_history_instead_of_indexes() {
+        let root = std::env::temp_dir().join(format!("aerolith-as-of-{}", uuid::Uuid::new_v4()));
+        let history = aerolithdb_storage::HistoryConfig {
+            versioned_collections: ["users".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        let storage_config = aerolithdb_storage::StorageConfig { data_dir: root.join("data"), history, ..Default::default() };
+        let storage = Arc::new(StorageHierarchy::new(&storage_config).await.unwrap());
+        let indexes = SecondaryIndexes::new(Arc::clone(&storage));
+        let definition = IndexDefinition { name: None, fields: vec!["city".to_string()], unique: false, text: false };
+        indexes.create("users", definition).await.unwrap();
+
+        for (id, city) in [("a", "Oslo"), ("b", "Bergen"), ("c", "Oslo")] {
+            storage.store_document("users", id, &json!({ "city": city })).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let before_moves = Utc::now();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        storage.store_document("users", "a", &json!({ "city": "Bergen" })).await.unwrap();
+        storage.delete_document("users", "c").await.unwrap();
+        storage.store_document("users", "d", &json!({ "city": "Oslo" })).await.unwrap();
+        for id in ["a", "c", "d"] {
+            indexes.document_changed("users", id).await;
+        }
+
+        let context = crate::execution::QueryContext::unbounded("users");
+        let run = |query: QueryRequest| {
+            let (storage, indexes, context) = (&storage, &indexes, &context);
+            async move { crate::engine::execute_query(storage, indexes, "users", &query, PlannerMode::CostBased, false, context).await }
+        };
+        let oslo = QueryRequest::with_filter(json!({ "city": "Oslo" }));
+        assert_eq!(run(oslo.clone()).await.unwrap().ids, ["d"]);
+
+        let past = run(oslo.clone().with_as_of(before_moves)).await.unwrap();
+        assert_eq!(past.ids, ["a", "c"]);
+        assert!(past.plan.is_none());
+        let paged = run(QueryRequest::new().with_as_of(before_moves).with_pagination(1, 1)).await.unwrap();
+        assert_eq!((paged.ids, paged.total), (vec!["b".to_string()], 3));
+
+        let search = QueryRequest::with_filter(json!({ "$text": { "$search": "oslo" } })).with_as_of(before_moves);
+        let error = run(search).await.err().expect("text searches cannot read history");
+        assert!(error.to_string().starts_with("Invalid filter"));
+        storage.store_document("logs", "1", &json!({})).await.unwrap();
+        let unversioned = QueryRequest::new().with_as_of(before_moves);
+        let context = crate::execution::QueryContext::unbounded("logs");
+        assert!(crate::engine::execute_query(&storage, &indexes, "logs", &unversioned, PlannerMode::CostBased, false, &context)
+            .await
+            .is_err());
+    }
+}
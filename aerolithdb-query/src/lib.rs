@@ -11,7 +11,20 @@
 //! - **Configuration**: Query engine settings and optimization [`config`] 
 //! - **Types**: Request/response structures and data types [`types`]
 //! - **Processing**: Document filtering, sorting, and pagination [`processing`]
+//! - **Filters**: Compiled evaluation of MongoDB-style filters [`filter`]
+//! - **Aggregation**: Staged `$match`/`$group`/`$sort`/`$limit`/`$project` pipelines [`aggregation`]
 //! - **Statistics**: Performance analytics and metrics collection [`stats`]
+//! - **Profiling**: Per-collection query statistics and the slow-query log [`profiler`]
+//! - **Planning**: Selectivity estimates, predicate ordering and access path choice [`planner`]
+//! - **Indexes**: Secondary indexes answering lookups on a collection's fields [`indexes`]
+//! - **Text Search**: Text indexes, `$text` predicates and BM25 ranking [`text`]
+//! - **Canary**: Comparing queries against a candidate path before cutover [`canary`]
+//! - **Distributed**: Scatter-gather execution across cluster members [`distributed`]
+//! - **Cursors**: Continuation cursors for paging sorted results [`cursor`]
+//! - **Projections**: Trimming query results to the fields a client asks for [`projection`]
+//! - **Execution**: Per-query timeouts, scan and memory limits, and cancellation [`execution`]
+//! - **Subscriptions**: Live queries pushing deltas of their matches as documents change [`subscriptions`]
+//! - **Schemas**: Versioned JSON Schemas validating the documents written to a collection [`schema`]
 //!
 //! ## Key Features
 //! - **Cost-Based Optimization**: Statistics-driven query plan optimization
@@ -43,15 +56,66 @@
 pub mod config;
 pub mod types;
 pub mod processing; 
+pub mod filter;
+pub mod aggregation;
 pub mod stats;
+pub mod profiler;
+pub mod planner;
+pub mod indexes;
+pub mod text;
+pub mod canary;
+pub mod cursor;
+pub mod projection;
+pub mod execution;
+pub mod subscriptions;
+pub mod schema;
+pub mod distributed;
 pub mod engine;
+pub mod sessions;
+pub mod sync;
+pub mod fixtures;
 
 // Re-export main types for convenience
 pub use config::{QueryConfig, OptimizerConfig};
 pub use types::{QueryRequest, QueryResult};
-pub use engine::QueryEngine;
+pub use engine::{LiveQuery, QueryEngine};
 pub use processing::{DocumentFilter, DocumentSorter, DocumentPaginator};
+pub use filter::CompiledFilter;
+pub use aggregation::{AggregationPipeline, AggregationResult};
 pub use stats::QueryStats;
+pub use profiler::{query_shape, CollectionQueryStats, ExpensiveQuery, LatencyPercentiles, ProfilingConfig, QueryProfiler, QuerySample, SlowQuery};
+pub use planner::{AccessCost, AccessPath, PlannerMode, PredicateEstimate, QueryPlan, QueryPlanner};
+pub use indexes::SecondaryIndexes;
+pub use text::{TextSearch, SCORE_FIELD};
+pub use canary::{CanaryConfig, CanaryDiscrepancy, CanaryReport, PathResult, PlannerPath, QueryCanary, QueryPath, QueryPathFuture};
+pub use cursor::{ListCursor, QueryCursor};
+pub use projection::Projection;
+pub use execution::{AbortReason, QueryAborted, QueryContext, QueryLimits, RunningQuery};
+pub use schema::{CollectionSchema, CompiledSchema, SchemaRegistry, SchemaValidationError, SchemaViolation, ValidationMode, SCHEMA_COLLECTION};
+pub use subscriptions::{ChangeKind, DeltaKind, DocumentChange, QueryDelta, SubscriptionManager, LIVE_QUERY_BUFFER};
+pub use distributed::{
+    DistributedQueryResult, HttpQueryTransport, PartialQueryResult, PeerQueryFuture, PeerQueryTransport, PEER_QUERY_PATH,
+    PEER_QUERY_TIMEOUT,
+};
+pub use sessions::{EphemeralDocumentRef, Session, SessionManager};
+pub use sync::{
+    ApplyReport, Change, ChangeBatch, DocumentRef, DocumentVersion, PullRequest, PushRequest, SyncFuture,
+    SyncManager, SyncPeer, SyncReport,
+};
+pub use fixtures::{FixtureLoader, FixtureReport, IndexDefinition};
+pub use aerolithdb_storage::{
+    Collection, CollectionConfig, CollectionInfo, LegalHold, LegalHoldAction, LegalHoldEvent, ResidencyRemediation, ResidencyViolation, WormPolicy,
+    TierMigration, TierMigrationMetrics, TierMigrationReport, TierRule, CorruptCopy, ScrubReport, HeavyHitter, HeavyHitterConfig,
+    BackupKind, BackupManifest, RestoreReport, Job, JobLogEntry, JobProgress, JobState,
+    CollectionStatistics, FieldStatistics, Histogram,
+    CollectionUsage, QuotaAction, QuotaEvent, StorageUsage, TierUsage,
+    ClusterNode, MembershipChange, RebalancePlan, RebalanceReport, ShardMove, TransferredDocument, TRANSFER_PATH,
+    ExpirationAction, ExpirationEvent, ExpirationReport,
+    CompressionDictionary, CompressionStats, WriteOptions,
+    DocumentPage, DocumentSortKey, ListOptions, ListedDocument, StorageTier,
+    ReadConsistency, WriteConsistency,
+};
+pub use aerolithdb_cache::{CachePeerRequest, CachePeerResponse, CACHE_PEER_PATH};
 
 // External dependencies used by the query engine
 pub use anyhow::Result;
@@ -0,0 +1,537 @@
+//! # Query Planning
+//!
+//! Estimates the fraction of a collection's documents each predicate of a
+//! filter keeps, and evaluates the predicates of a conjunction most
+//! selective first so documents are rejected as early as possible.
+//!
+//! With cost-based optimization enabled and statistics collected for the
+//! collection, estimates come from its field statistics: the share of
+//! documents holding a field, its number of distinct values, and the
+//! histogram of numeric fields for range predicates. Otherwise fixed
+//! heuristics are used. Predicates are assumed independent, so the
+//! estimate of a conjunction is the product of its predicates' estimates.
+//!
+//! The planner also chooses how documents are read. A collection scan reads
+//! every document; an index scan reads only those a secondary index finds
+//! for one predicate on its leading field, after checking the versions of
+//! the collection's documents to keep the index current. Costs are counted
+//! in document reads, a read through an index costing more than one in a
+//! scan, and the cheapest path is chosen. Every path considered is reported
+//! with its estimates so `explain` shows why one was chosen. A `$text`
+//! search can only be answered by the collection's text index, which is
+//! then the one path.
+
+use aerolithdb_storage::{CollectionStatistics, FieldStatistics};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::fixtures::IndexDefinition;
+use crate::indexes::IndexLookup;
+
+/// Heuristic share of documents an equality predicate keeps
+const EQUALITY_SELECTIVITY: f64 = 0.1;
+
+/// Heuristic share of documents a range predicate keeps
+const RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+
+/// Heuristic share of documents any other predicate keeps
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+
+/// Heuristic share of documents a text search keeps
+const TEXT_SELECTIVITY: f64 = 0.1;
+
+/// Cost of reading and filtering one document in a collection scan
+const SCAN_READ_COST: f64 = 1.0;
+
+/// Cost of reading and filtering one document found through an index
+const INDEX_READ_COST: f64 = 1.5;
+
+/// Cost of checking one document's version before an index is used
+const INDEX_CHECK_COST: f64 = 0.01;
+
+/// One predicate of a filter with its estimated selectivity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PredicateEstimate {
+    /// The predicate, as a filter of its own
+    pub filter: Value,
+
+    /// Estimated share of documents it keeps, between 0 and 1
+    pub selectivity: f64,
+}
+
+/// How the documents a filter is evaluated against are read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccessPath {
+    /// Every document of the collection is read
+    CollectionScan,
+
+    /// Only the documents an index finds for one predicate are read
+    IndexScan {
+        index: String,
+
+        /// Indexed field the predicate is on
+        field: String,
+
+        /// The predicate looked up, as a filter of its own
+        predicate: Value,
+    },
+
+    /// Only the documents a text index finds for a `$text` search are read
+    TextSearch {
+        index: String,
+
+        /// The search, as a filter of its own
+        predicate: Value,
+    },
+}
+
+/// An access path the planner considered, with its estimates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessCost {
+    pub access: AccessPath,
+
+    /// Estimated documents read
+    pub estimated_reads: u64,
+
+    /// Estimated cost, in collection scan reads
+    pub cost: f64,
+}
+
+/// How a query's filter is evaluated and how many documents it is
+/// expected to return.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryPlan {
+    /// Collection queried
+    pub collection: String,
+
+    /// Documents in the collection
+    pub documents: u64,
+
+    /// Estimated share of the documents the filter keeps
+    pub selectivity: f64,
+
+    /// Estimated documents returned before pagination
+    pub estimated_rows: u64,
+
+    /// Whether the estimates come from collected statistics rather than
+    /// fixed heuristics
+    pub uses_statistics: bool,
+
+    /// When the statistics used were collected
+    pub statistics_collected_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Predicates of the filter's top-level conjunction, in evaluation order
+    pub predicates: Vec<PredicateEstimate>,
+
+    /// How documents are read
+    pub access: AccessPath,
+
+    /// Estimated cost of reading them that way
+    pub estimated_cost: f64,
+
+    /// Every access path considered, cheapest first
+    pub alternatives: Vec<AccessCost>,
+}
+
+impl QueryPlan {
+    /// The filter with its predicates in evaluation order, equivalent to
+    /// the filter planned.
+    pub fn filter(&self) -> Option<Value> {
+        match self.predicates.as_slice() {
+            [] => None,
+            [predicate] => Some(predicate.filter.clone()),
+            predicates => {
+                let filters: Vec<Value> = predicates.iter().map(|predicate| predicate.filter.clone()).collect();
+                Some(serde_json::json!({ "$and": filters }))
+            }
+        }
+    }
+}
+
+/// How the predicates of a filter are ordered before evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannerMode {
+    /// Predicates are evaluated in the order written
+    Unplanned,
+
+    /// Predicates are ordered by fixed selectivity heuristics
+    Heuristic,
+
+    /// Predicates are ordered by selectivity estimated from collection
+    /// statistics, falling back to heuristics without them
+    CostBased,
+}
+
+/// Plans filters from collection statistics.
+pub struct QueryPlanner;
+
+impl QueryPlanner {
+    /// Plan a filter over `documents` documents of a collection that has
+    /// `indexes`; without statistics the estimates fall back to fixed
+    /// heuristics.
+    pub fn plan(
+        collection: &str,
+        filter: Option<&Value>,
+        documents: u64,
+        statistics: Option<&CollectionStatistics>,
+        indexes: &[IndexDefinition],
+    ) -> QueryPlan {
+        let statistics = statistics.filter(|statistics| statistics.documents > 0);
+        let mut predicates: Vec<PredicateEstimate> = filter
+            .map(Self::conjuncts)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|filter| {
+                let selectivity = Self::selectivity(&filter, statistics);
+                PredicateEstimate { filter, selectivity }
+            })
+            .collect();
+        predicates.sort_by(|a, b| a.selectivity.total_cmp(&b.selectivity));
+
+        let selectivity = predicates.iter().map(|predicate| predicate.selectivity).product::<f64>();
+        let alternatives = Self::access_paths(&predicates, documents, indexes);
+        QueryPlan {
+            collection: collection.to_string(),
+            documents,
+            selectivity,
+            estimated_rows: (selectivity * documents as f64).round() as u64,
+            uses_statistics: statistics.is_some(),
+            statistics_collected_at: statistics.map(|statistics| statistics.collected_at),
+            predicates,
+            access: alternatives[0].access.clone(),
+            estimated_cost: alternatives[0].cost,
+            alternatives,
+        }
+    }
+
+    /// Cost the collection scan and a scan of every index that can look up
+    /// one of the predicates, cheapest first; the scan wins ties. A text
+    /// search is only costed through the text index.
+    fn access_paths(predicates: &[PredicateEstimate], documents: u64, indexes: &[IndexDefinition]) -> Vec<AccessCost> {
+        let search = predicates.iter().find(|predicate| predicate.filter.get("$text").is_some());
+        if let (Some(search), Some(index)) = (search, indexes.iter().find(|index| index.text)) {
+            let reads = (search.selectivity * documents as f64).ceil() as u64;
+            return vec![AccessCost {
+                access: AccessPath::TextSearch { index: index.effective_name(), predicate: search.filter.clone() },
+                estimated_reads: reads,
+                cost: documents as f64 * INDEX_CHECK_COST + reads as f64 * INDEX_READ_COST,
+            }];
+        }
+
+        let mut paths = vec![AccessCost {
+            access: AccessPath::CollectionScan,
+            estimated_reads: documents,
+            cost: documents as f64 * SCAN_READ_COST,
+        }];
+
+        for index in indexes.iter().filter(|index| !index.text) {
+            let Some(field) = index.fields.first() else {
+                continue;
+            };
+            for predicate in predicates {
+                let Some(condition) = predicate.filter.get(field) else {
+                    continue;
+                };
+                if IndexLookup::for_condition(condition).is_none() {
+                    continue;
+                }
+                let reads = (predicate.selectivity * documents as f64).ceil() as u64;
+                paths.push(AccessCost {
+                    access: AccessPath::IndexScan {
+                        index: index.effective_name(),
+                        field: field.clone(),
+                        predicate: predicate.filter.clone(),
+                    },
+                    estimated_reads: reads,
+                    cost: documents as f64 * INDEX_CHECK_COST + reads as f64 * INDEX_READ_COST,
+                });
+            }
+        }
+
+        paths.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+        paths
+    }
+
+    /// Estimated share of documents a filter keeps.
+    pub fn selectivity(filter: &Value, statistics: Option<&CollectionStatistics>) -> f64 {
+        match filter {
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(field, condition)| Self::field_selectivity(field, condition, statistics))
+                .product(),
+            _ => 0.0,
+        }
+    }
+
+    /// Split a filter into the predicates that must all hold, flattening
+    /// nested `$and`s.
+    fn conjuncts(filter: &Value) -> Vec<Value> {
+        let Value::Object(fields) = filter else {
+            return vec![filter.clone()];
+        };
+
+        let mut conjuncts = Vec::new();
+        for (field, condition) in fields {
+            match (field.as_str(), condition) {
+                ("$and", Value::Array(filters)) => conjuncts.extend(filters.iter().flat_map(Self::conjuncts)),
+                _ => conjuncts.push(serde_json::json!({ field: condition })),
+            }
+        }
+        conjuncts
+    }
+
+    fn field_selectivity(field: &str, condition: &Value, statistics: Option<&CollectionStatistics>) -> f64 {
+        match (field, condition) {
+            ("$and", Value::Array(filters)) => {
+                filters.iter().map(|filter| Self::selectivity(filter, statistics)).product()
+            }
+            ("$or", Value::Array(filters)) => {
+                1.0 - filters.iter().map(|filter| 1.0 - Self::selectivity(filter, statistics)).product::<f64>()
+            }
+            ("$nor", Value::Array(filters)) => {
+                filters.iter().map(|filter| 1.0 - Self::selectivity(filter, statistics)).product()
+            }
+            ("$not", filter) => 1.0 - Self::selectivity(filter, statistics),
+            ("$text", _) => TEXT_SELECTIVITY,
+            ("$and" | "$or" | "$nor", _) => 0.0,
+            _ => {
+                let estimator = FieldEstimator::new(field, statistics);
+                match condition {
+                    Value::Object(operators) => operators
+                        .iter()
+                        .map(|(operator, operand)| estimator.operator(operator, operand))
+                        .product(),
+                    value => estimator.equal(value),
+                }
+            }
+        }
+    }
+}
+
+/// Estimates predicates on one field, from its statistics if collected.
+struct FieldEstimator<'a> {
+    /// Statistics of the collection and of the field, which is absent if no
+    /// document holds it
+    statistics: Option<(&'a CollectionStatistics, Option<&'a FieldStatistics>)>,
+}
+
+impl<'a> FieldEstimator<'a> {
+    fn new(field: &str, statistics: Option<&'a CollectionStatistics>) -> Self {
+        Self { statistics: statistics.map(|statistics| (statistics, statistics.field(field))) }
+    }
+
+    /// Share of documents holding a non-null value
+    fn present(&self) -> Option<f64> {
+        let (statistics, field) = self.statistics?;
+        Some(field.map_or(0.0, |field| field.non_null as f64 / statistics.documents as f64))
+    }
+
+    fn operator(&self, operator: &str, operand: &Value) -> f64 {
+        match operator {
+            "$eq" => self.equal(operand),
+            "$ne" => 1.0 - self.equal(operand),
+            "$lt" | "$lte" => self.below(operand),
+            "$gt" | "$gte" => self.above(operand),
+            "$in" => self.any_of(operand),
+            "$nin" => 1.0 - self.any_of(operand),
+            "$exists" => match (operand, self.present()) {
+                (Value::Bool(true), Some(present)) => present,
+                (Value::Bool(false), Some(present)) => 1.0 - present,
+                (Value::Bool(_), None) => DEFAULT_SELECTIVITY,
+                _ => 0.0,
+            },
+            "$regex" => self.present().map_or(DEFAULT_SELECTIVITY, |present| present * DEFAULT_SELECTIVITY),
+            // Modifies $regex without narrowing it further
+            "$options" => 1.0,
+            "$not" => match operand {
+                Value::Object(operators) => {
+                    1.0 - operators.iter().map(|(operator, operand)| self.operator(operator, operand)).product::<f64>()
+                }
+                _ => 1.0 - self.operator("$regex", operand),
+            },
+            // Unknown operators match nothing
+            _ => 0.0,
+        }
+    }
+
+    fn equal(&self, value: &Value) -> f64 {
+        let Some((statistics, field)) = self.statistics else {
+            return EQUALITY_SELECTIVITY;
+        };
+        let Some(field) = field else {
+            return if value.is_null() { 1.0 } else { 0.0 };
+        };
+
+        let present = field.non_null as f64 / statistics.documents as f64;
+        if value.is_null() {
+            return 1.0 - present;
+        }
+        if let (Some(number), Some(min), Some(max)) = (value.as_f64(), field.min, field.max) {
+            if number < min || number > max {
+                return 0.0;
+            }
+        }
+        present / field.distinct.max(1) as f64
+    }
+
+    fn any_of(&self, values: &Value) -> f64 {
+        match values {
+            Value::Array(values) => values.iter().map(|value| self.equal(value)).sum::<f64>().min(1.0),
+            _ => 0.0,
+        }
+    }
+
+    fn below(&self, bound: &Value) -> f64 {
+        match self.numeric_fraction_below(bound) {
+            Some((numeric, below)) => numeric * below,
+            None => self.range_heuristic(),
+        }
+    }
+
+    fn above(&self, bound: &Value) -> f64 {
+        match self.numeric_fraction_below(bound) {
+            Some((numeric, below)) => numeric * (1.0 - below),
+            None => self.range_heuristic(),
+        }
+    }
+
+    /// Share of documents holding a number, and the fraction of those
+    /// numbers below `bound`, for numeric bounds on numeric fields
+    fn numeric_fraction_below(&self, bound: &Value) -> Option<(f64, f64)> {
+        let bound = bound.as_f64()?;
+        let (statistics, field) = self.statistics?;
+        let field = field.filter(|field| field.numeric > 0)?;
+        let numeric = field.numeric as f64 / statistics.documents as f64;
+
+        let below = match (&field.histogram, field.min, field.max) {
+            (Some(histogram), _, _) => histogram.fraction_below(bound),
+            (None, Some(min), Some(max)) if max > min => ((bound - min) / (max - min)).clamp(0.0, 1.0),
+            (None, Some(min), _) => if bound > min { 1.0 } else { 0.0 },
+            _ => return None,
+        };
+        Some((numeric, below))
+    }
+
+    fn range_heuristic(&self) -> f64 {
+        self.present().map_or(RANGE_SELECTIVITY, |present| present * RANGE_SELECTIVITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerolithdb_storage::Histogram;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn statistics() -> CollectionStatistics {
+        let mut fields = BTreeMap::new();
+        fields.insert("status".to_string(), FieldStatistics {
+            non_null: 1000,
+            distinct: 4,
+            numeric: 0,
+            min: None,
+            max: None,
+            histogram: None,
+        });
+        fields.insert("age".to_string(), FieldStatistics {
+            non_null: 800,
+            distinct: 80,
+            numeric: 800,
+            min: Some(0.0),
+            max: Some(80.0),
+            histogram: Some(Histogram { bounds: vec![0.0, 20.0, 80.0], counts: vec![400, 400] }),
+        });
+        CollectionStatistics { collection: "users".to_string(), documents: 1000, fields, collected_at: chrono::Utc::now() }
+    }
+
+    #[test]
+    fn test_statistics_drive_selectivity_and_predicate_order() {
+        let statistics = statistics();
+        let filter = json!({ "status": "active", "age": { "$lt": 10 }, "$and": [{ "email": { "$exists": true } }] });
+        let plan = QueryPlanner::plan("users", Some(&filter), 1000, Some(&statistics), &[]);
+
+        assert!(plan.uses_statistics);
+        let estimates: Vec<(String, f64)> = plan
+            .predicates
+            .iter()
+            .map(|predicate| (predicate.filter.as_object().unwrap().keys().next().unwrap().clone(), predicate.selectivity))
+            .collect();
+        // No document holds an email; a quarter of the ages fall below 10
+        assert_eq!(estimates[0], ("email".to_string(), 0.0));
+        assert_eq!(estimates[1].0, "age");
+        assert!((estimates[1].1 - 0.2).abs() < 1e-9);
+        assert_eq!(estimates[2], ("status".to_string(), 0.25));
+        assert_eq!(plan.estimated_rows, 0);
+        assert_eq!(plan.filter().unwrap()["$and"].as_array().unwrap().len(), 3);
+
+        // Ranges outside the values seen, disjunctions and negations
+        assert_eq!(QueryPlanner::selectivity(&json!({ "age": 200 }), Some(&statistics)), 0.0);
+        assert!((QueryPlanner::selectivity(&json!({ "age": { "$gte": 20 } }), Some(&statistics)) - 0.4).abs() < 1e-9);
+        let either = json!({ "$or": [{ "status": "active" }, { "status": "banned" }] });
+        assert!((QueryPlanner::selectivity(&either, Some(&statistics)) - 0.4375).abs() < 1e-9);
+        assert!((QueryPlanner::selectivity(&json!({ "age": null }), Some(&statistics)) - 0.2).abs() < 1e-9);
+        assert_eq!(QueryPlanner::selectivity(&json!({ "$not": { "status": { "$in": ["a", "b", "c", "d"] } } }), Some(&statistics)), 0.0);
+    }
+
+    #[test]
+    fn test_heuristics_without_statistics() {
+        let filter = json!({ "status": "active", "age": { "$gt": 10 } });
+        let plan = QueryPlanner::plan("users", Some(&filter), 300, None, &[]);
+
+        assert!(!plan.uses_statistics);
+        assert_eq!(plan.predicates[0].selectivity, EQUALITY_SELECTIVITY);
+        assert_eq!(plan.predicates[1].selectivity, RANGE_SELECTIVITY);
+        assert_eq!(plan.estimated_rows, 10);
+        assert!(QueryPlanner::plan("users", None, 300, None, &[]).filter().is_none());
+        assert_eq!(plan.access, AccessPath::CollectionScan);
+    }
+
+    #[test]
+    fn test_cheapest_access_path_is_chosen() {
+        let statistics = statistics();
+        let index = |name: &str, fields: &[&str]| IndexDefinition {
+            name: Some(name.to_string()),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            unique: false,
+            text: false,
+        };
+        let indexes = [index("by_status", &["status"]), index("by_age_status", &["age", "status"]), index("by_email", &["email"])];
+
+        // A quarter of the users are active, a fifth are under 10
+        let filter = json!({ "status": "active", "age": { "$lt": 10 } });
+        let plan = QueryPlanner::plan("users", Some(&filter), 1000, Some(&statistics), &indexes);
+        match &plan.access {
+            AccessPath::IndexScan { index, field, predicate } => {
+                assert_eq!((index.as_str(), field.as_str()), ("by_age_status", "age"));
+                assert_eq!(predicate, &json!({ "age": { "$lt": 10 } }));
+            }
+            other => panic!("Unexpected access path: {:?}", other),
+        }
+        assert_eq!(plan.alternatives.len(), 3);
+        assert_eq!(plan.alternatives[0].estimated_reads, 200);
+        assert!((plan.estimated_cost - 310.0).abs() < 1e-9);
+        assert_eq!(plan.alternatives[2].access, AccessPath::CollectionScan);
+
+        // Indexes are not worth it for predicates keeping most documents,
+        // nor usable for operators they cannot look up
+        let broad = json!({ "age": { "$gte": 1 } });
+        assert_eq!(QueryPlanner::plan("users", Some(&broad), 1000, Some(&statistics), &indexes).access, AccessPath::CollectionScan);
+        let regex = json!({ "status": { "$regex": "^act" } });
+        assert_eq!(QueryPlanner::plan("users", Some(&regex), 1000, Some(&statistics), &indexes).alternatives.len(), 1);
+
+        // Text searches go through the text index, however selective the
+        // rest of the filter is
+        let bio = IndexDefinition { text: true, ..index("bio_text", &["bio"]) };
+        let indexes = [index("by_status", &["status"]), bio];
+        let search = json!({ "$text": { "$search": "rust" }, "status": "active" });
+        let plan = QueryPlanner::plan("users", Some(&search), 1000, Some(&statistics), &indexes);
+        assert_eq!(plan.alternatives.len(), 1);
+        assert_eq!(
+            plan.access,
+            AccessPath::TextSearch { index: "bio_text".to_string(), predicate: json!({ "$text": { "$search": "rust" } }) }
+        );
+    }
+}
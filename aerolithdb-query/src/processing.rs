@@ -6,6 +6,8 @@
 use serde_json::Value;
 use std::cmp::Ordering;
 
+use crate::filter::CompiledFilter;
+
 /// Document filtering engine for applying query conditions to documents.
 ///
 /// Provides comprehensive document filtering capabilities using MongoDB-style
@@ -16,7 +18,9 @@ impl DocumentFilter {
     /// Apply a filter to a document and return whether it matches.
     ///
     /// Evaluates the provided filter against the document using MongoDB-style
-    /// query operators. Supports complex nested conditions and boolean logic.
+    /// query operators. Supports complex nested conditions and boolean logic;
+    /// see [`crate::filter`] for the operators. Filters evaluated against
+    /// many documents are better compiled once with [`CompiledFilter`].
     ///
     /// # Arguments
     /// * `document` - The document to evaluate
@@ -41,67 +45,8 @@ impl DocumentFilter {
     /// {"$and": [{"status": "active"}, {"type": "premium"}]}
     /// ```
     pub fn matches_filter(document: &Value, filter: &Value) -> bool {
-        match filter {
-            Value::Object(filter_obj) => {
-                for (key, value) in filter_obj {
-                    if !Self::matches_field(document, key, value) {
-                        return false;
-                    }
-                }
-                true
-            }
-            _ => false,
-        }
-    }
-
-    /// Check if a specific field matches the given filter condition.
-    fn matches_field(document: &Value, field: &str, condition: &Value) -> bool {
-        match field {
-            "$and" => Self::matches_and(document, condition),
-            "$or" => Self::matches_or(document, condition),
-            "$not" => Self::matches_not(document, condition),
-            _ => Self::matches_simple_field(document, field, condition),
-        }
-    }
-
-    /// Handle $and logical operator.
-    fn matches_and(document: &Value, conditions: &Value) -> bool {
-        if let Value::Array(conditions_array) = conditions {
-            conditions_array.iter().all(|cond| Self::matches_filter(document, cond))
-        } else {
-            false
-        }
-    }
-
-    /// Handle $or logical operator.
-    fn matches_or(document: &Value, conditions: &Value) -> bool {
-        if let Value::Array(conditions_array) = conditions {
-            conditions_array.iter().any(|cond| Self::matches_filter(document, cond))
-        } else {
-            false
-        }
-    }
-
-    /// Handle $not logical operator.
-    fn matches_not(document: &Value, condition: &Value) -> bool {
-        !Self::matches_filter(document, condition)
-    }
-
-    /// Handle simple field matching with operators.
-    fn matches_simple_field(document: &Value, field: &str, condition: &Value) -> bool {
-        let field_value = Self::get_nested_field(document, field);
-        
-        match condition {
-            Value::Object(condition_obj) => {
-                for (operator, value) in condition_obj {
-                    if !Self::matches_operator(&field_value, operator, value) {
-                        return false;
-                    }
-                }
-                true
-            }
-            _ => field_value == *condition,
-        }
+        // Invalid filters match nothing; compile them to learn why
+        CompiledFilter::compile(filter).is_ok_and(|filter| filter.matches(document))
     }
 
     /// Get a nested field value from a document using dot notation.
@@ -125,49 +70,10 @@ impl DocumentFilter {
         current.clone()
     }
 
-    /// Apply a comparison operator to field values.
-    fn matches_operator(field_value: &Value, operator: &str, operand: &Value) -> bool {
-        match operator {
-            "$eq" => field_value == operand,
-            "$ne" => field_value != operand,
-            "$gt" => Self::compare_values(field_value, operand) == Ordering::Greater,
-            "$gte" => matches!(Self::compare_values(field_value, operand), Ordering::Greater | Ordering::Equal),
-            "$lt" => Self::compare_values(field_value, operand) == Ordering::Less,
-            "$lte" => matches!(Self::compare_values(field_value, operand), Ordering::Less | Ordering::Equal),
-            "$in" => Self::matches_in(field_value, operand),
-            "$nin" => !Self::matches_in(field_value, operand),
-            "$regex" => Self::matches_regex(field_value, operand),
-            "$exists" => Self::matches_exists(field_value, operand),
-            _ => false,
-        }
-    }
-
-    /// Handle $in operator for array membership.
-    fn matches_in(field_value: &Value, array: &Value) -> bool {
-        if let Value::Array(arr) = array {
-            arr.contains(field_value)
-        } else {
-            false
-        }
-    }    /// Handle $regex operator for pattern matching.
-    fn matches_regex(field_value: &Value, pattern: &Value) -> bool {
-        if let (Value::String(text), Value::String(pattern_str)) = (field_value, pattern) {            // Simple pattern matching - advanced regex support available as enhancement
-            text.contains(pattern_str)
-        } else {
-            false
-        }
-    }
-
-    /// Handle $exists operator for field presence.
-    fn matches_exists(field_value: &Value, should_exist: &Value) -> bool {
-        let exists = !field_value.is_null();
-        match should_exist {
-            Value::Bool(expected) => exists == *expected,
-            _ => false,
-        }
-    }
-
     /// Compare two JSON values for ordering.
+    ///
+    /// Values of different types order null, booleans, numbers, strings,
+    /// arrays and then objects, so missing fields sort first.
     fn compare_values(a: &Value, b: &Value) -> Ordering {
         match (a, b) {
             (Value::Number(a), Value::Number(b)) => {
@@ -179,7 +85,19 @@ impl DocumentFilter {
             }
             (Value::String(a), Value::String(b)) => a.cmp(b),
             (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
-            _ => Ordering::Equal,
+            _ => Self::type_rank(a).cmp(&Self::type_rank(b)),
+        }
+    }
+
+    /// Position of a value's type in the order of mixed-type values.
+    fn type_rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
         }
     }
 
@@ -192,7 +110,10 @@ impl DocumentFilter {
     /// # Returns
     /// A vector containing the documents that match the filter criteria
     pub fn filter_documents(documents: Vec<Value>, filter: &Value) -> Vec<Value> {
-        documents.into_iter().filter(|doc| Self::matches_filter(doc, filter)).collect()
+        let Ok(filter) = CompiledFilter::compile(filter) else {
+            return Vec::new();
+        };
+        documents.into_iter().filter(|doc| filter.matches(doc)).collect()
     }
 }
 
@@ -245,6 +166,33 @@ impl DocumentSorter {
         Ordering::Equal
     }
 
+    /// Values of the fields a sort specification orders by, in its order.
+    ///
+    /// Comparing keys with [`DocumentSorter::compare_keys`] orders documents
+    /// as [`DocumentSorter::sort_documents`] does, without the documents.
+    pub fn sort_key(document: &Value, sort_spec: &Value) -> Vec<Value> {
+        match sort_spec {
+            Value::Object(sort_obj) => {
+                sort_obj.keys().map(|field| DocumentFilter::get_nested_field(document, field)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Compare two sort keys taken with [`DocumentSorter::sort_key`].
+    pub fn compare_keys(a: &[Value], b: &[Value], sort_spec: &Value) -> Ordering {
+        let Value::Object(sort_obj) = sort_spec else {
+            return Ordering::Equal;
+        };
+        for ((a_val, b_val), direction) in a.iter().zip(b).zip(sort_obj.values()) {
+            let comparison = DocumentFilter::compare_values(a_val, b_val);
+            if comparison != Ordering::Equal {
+                return if Self::is_descending(direction) { comparison.reverse() } else { comparison };
+            }
+        }
+        Ordering::Equal
+    }
+
     /// Check if the sort direction is descending.
     fn is_descending(direction: &Value) -> bool {
         match direction {
@@ -502,6 +450,23 @@ mod tests {
         // Test boolean comparisons
         assert!(DocumentFilter::compare_values(&json!(true), &json!(false)) == std::cmp::Ordering::Greater);
         assert!(DocumentFilter::compare_values(&json!(false), &json!(true)) == std::cmp::Ordering::Less);
+
+        // Mixed types order by type, missing fields first
+        assert!(DocumentFilter::compare_values(&Value::Null, &json!(0)) == std::cmp::Ordering::Less);
+        assert!(DocumentFilter::compare_values(&json!("1"), &json!(2)) == std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn test_sort_keys_order_like_documents() {
+        let sort_spec = json!({"department": 1, "level": -1});
+        let alice = json!({"department": "Engineering", "level": 3});
+        let bob = json!({"department": "Engineering", "level": 2});
+        let keys = |document: &Value| DocumentSorter::sort_key(document, &sort_spec);
+
+        assert_eq!(keys(&alice), vec![json!("Engineering"), json!(3)]);
+        assert_eq!(DocumentSorter::compare_keys(&keys(&alice), &keys(&bob), &sort_spec), std::cmp::Ordering::Less);
+        assert_eq!(DocumentSorter::compare_keys(&keys(&alice), &keys(&alice), &sort_spec), std::cmp::Ordering::Equal);
+        assert!(DocumentSorter::sort_key(&alice, &Value::Null).is_empty());
     }
 
     #[test]
@@ -0,0 +1,467 @@
+//! # Query Profiling
+//!
+//! Every query and aggregation the engine runs is recorded against its
+//! collection: how many ran, how long they took, and how many documents
+//! they read for each one they returned. Queries slower than a threshold
+//! are also kept in a slow-query log with the query, the plan it ran with
+//! and its timing, so operators can find the queries worth an index.
+//!
+//! - **Latency**: percentiles over the most recent queries of each
+//!   collection, failed ones included
+//! - **Scan ratio**: documents scanned per document returned; a query
+//!   answered through a selective index reads few more than it returns
+//! - **Slow queries**: the most recent queries that took at least
+//!   `slow_query_threshold_ms`, newest first
+//! - **Expensive queries**: the query shapes taking the most total time over
+//!   sliding windows, tracked as [heavy hitters](aerolithdb_storage::HeavyHitters)
+//!   weighted by latency. A shape is the query with its literal values
+//!   replaced, so queries differing only in the values they look for add up
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use aerolithdb_storage::{HeavyHitterConfig, HeavyHitters};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::planner::QueryPlan;
+
+/// What the profiler records and keeps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfilingConfig {
+    /// Whether queries are recorded at all
+    pub enabled: bool,
+
+    /// Queries taking at least this long, in milliseconds, are logged as
+    /// slow
+    pub slow_query_threshold_ms: u64,
+
+    /// Slow queries kept; the oldest are dropped first
+    pub slow_query_log_size: usize,
+
+    /// Most recent queries of a collection its latency percentiles are
+    /// computed over
+    pub latency_window: usize,
+
+    /// Tracking of the query shapes taking the most time
+    pub expensive_queries: HeavyHitterConfig,
+}
+
+impl Default for ProfilingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            slow_query_threshold_ms: 100,
+            slow_query_log_size: 200,
+            latency_window: 1000,
+            expensive_queries: HeavyHitterConfig::default(),
+        }
+    }
+}
+
+/// One executed query, as handed to the profiler.
+#[derive(Debug, Clone)]
+pub struct QuerySample {
+    pub query_id: String,
+    pub collection: String,
+
+    /// `query` or `aggregate`
+    pub kind: &'static str,
+    pub duration: Duration,
+    pub documents_scanned: usize,
+    pub documents_returned: usize,
+
+    /// Whether it was answered from the query result cache
+    pub from_cache: bool,
+
+    /// Whether it failed, for instance by running past its limits
+    pub failed: bool,
+
+    /// The query with its literal values replaced, see [`query_shape`]
+    pub shape: Value,
+}
+
+/// A query that took at least the slow-query threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowQuery {
+    pub query_id: String,
+    pub collection: String,
+
+    /// `query` or `aggregate`
+    pub kind: String,
+
+    /// The query request, or the pipeline of an aggregation
+    pub query: Value,
+
+    /// Plan the filter was run with; unfiltered queries and aggregations
+    /// scan the collection without one
+    pub plan: Option<QueryPlan>,
+    pub duration_ms: u64,
+    pub documents_scanned: usize,
+    pub documents_returned: usize,
+    pub failed: bool,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// A query shape among those taking the most time over a window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpensiveQuery {
+    pub collection: String,
+
+    /// `query` or `aggregate`
+    pub kind: String,
+    pub shape: Value,
+
+    /// Estimated time spent running queries of the shape, never below the
+    /// true time
+    pub total_ms: f64,
+
+    /// Most `total_ms` can exceed the true time by
+    pub error_ms: f64,
+}
+
+/// The shape of a query: its structure with every literal value replaced
+/// by `"?"`, and runs of array elements of the same shape collapsed into
+/// one.
+pub fn query_shape(query: &Value) -> Value {
+    match query {
+        Value::Object(fields) => {
+            Value::Object(fields.iter().map(|(field, value)| (field.clone(), query_shape(value))).collect())
+        }
+        Value::Array(elements) => {
+            let mut shapes: Vec<Value> = elements.iter().map(query_shape).collect();
+            shapes.dedup();
+            Value::Array(shapes)
+        }
+        _ => Value::String("?".to_string()),
+    }
+}
+
+/// Latency percentiles over a collection's most recent queries, in
+/// milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    /// Queries the percentiles are computed over
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Query statistics of one collection since the profiler started or was
+/// reset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionQueryStats {
+    pub collection: String,
+    pub queries: u64,
+    pub aggregations: u64,
+
+    /// Queries answered from the query result cache
+    pub cache_hits: u64,
+    pub failures: u64,
+    pub slow_queries: u64,
+    pub documents_scanned: u64,
+    pub documents_returned: u64,
+
+    /// Documents scanned per document returned, unset before any was
+    /// returned
+    pub scanned_per_returned: Option<f64>,
+    pub mean_ms: f64,
+    pub latency: LatencyPercentiles,
+    pub since: DateTime<Utc>,
+}
+
+/// Running totals of one collection.
+#[derive(Debug)]
+struct CollectionProfile {
+    queries: u64,
+    aggregations: u64,
+    cache_hits: u64,
+    failures: u64,
+    slow_queries: u64,
+    documents_scanned: u64,
+    documents_returned: u64,
+    total_latency: Duration,
+
+    /// Latencies of the most recent queries, oldest first
+    recent: VecDeque<Duration>,
+    since: DateTime<Utc>,
+}
+
+impl CollectionProfile {
+    fn new() -> Self {
+        Self {
+            queries: 0,
+            aggregations: 0,
+            cache_hits: 0,
+            failures: 0,
+            slow_queries: 0,
+            documents_scanned: 0,
+            documents_returned: 0,
+            total_latency: Duration::ZERO,
+            recent: VecDeque::new(),
+            since: Utc::now(),
+        }
+    }
+
+    fn stats(&self, collection: &str) -> CollectionQueryStats {
+        let mut recent: Vec<Duration> = self.recent.iter().copied().collect();
+        recent.sort();
+        let percentile = |p: f64| {
+            let rank = ((p * recent.len() as f64).ceil() as usize).clamp(1, recent.len());
+            milliseconds(recent[rank - 1])
+        };
+        let latency = if recent.is_empty() {
+            LatencyPercentiles::default()
+        } else {
+            LatencyPercentiles {
+                samples: recent.len(),
+                p50_ms: percentile(0.50),
+                p95_ms: percentile(0.95),
+                p99_ms: percentile(0.99),
+                max_ms: milliseconds(recent[recent.len() - 1]),
+            }
+        };
+        let executed = self.queries + self.aggregations;
+
+        CollectionQueryStats {
+            collection: collection.to_string(),
+            queries: self.queries,
+            aggregations: self.aggregations,
+            cache_hits: self.cache_hits,
+            failures: self.failures,
+            slow_queries: self.slow_queries,
+            documents_scanned: self.documents_scanned,
+            documents_returned: self.documents_returned,
+            scanned_per_returned: (self.documents_returned > 0)
+                .then(|| self.documents_scanned as f64 / self.documents_returned as f64),
+            mean_ms: if executed == 0 { 0.0 } else { milliseconds(self.total_latency) / executed as f64 },
+            latency,
+            since: self.since,
+        }
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Per-collection query statistics and the slow-query log of an engine.
+#[derive(Debug)]
+pub struct QueryProfiler {
+    config: ProfilingConfig,
+    collections: Mutex<HashMap<String, CollectionProfile>>,
+
+    /// Slow queries, newest first
+    slow: Mutex<VecDeque<SlowQuery>>,
+
+    /// Microseconds spent by `[collection, kind, shape]`
+    expensive: HeavyHitters,
+}
+
+impl QueryProfiler {
+    pub fn new(config: ProfilingConfig) -> Self {
+        Self {
+            expensive: HeavyHitters::new(config.expensive_queries.clone()),
+            config,
+            collections: Mutex::new(HashMap::new()),
+            slow: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ProfilingConfig {
+        &self.config
+    }
+
+    /// Record an executed query. `details` gives its request and plan, and
+    /// is only asked for when the query was slow.
+    pub fn record(&self, sample: QuerySample, details: impl FnOnce() -> (Value, Option<QueryPlan>)) {
+        if !self.config.enabled {
+            return;
+        }
+        let slow = sample.duration >= Duration::from_millis(self.config.slow_query_threshold_ms);
+
+        {
+            let mut collections = self.collections.lock().unwrap();
+            let profile = collections.entry(sample.collection.clone()).or_insert_with(CollectionProfile::new);
+            match sample.kind {
+                "aggregate" => profile.aggregations += 1,
+                _ => profile.queries += 1,
+            }
+            profile.cache_hits += sample.from_cache as u64;
+            profile.failures += sample.failed as u64;
+            profile.slow_queries += slow as u64;
+            profile.documents_scanned += sample.documents_scanned as u64;
+            profile.documents_returned += sample.documents_returned as u64;
+            profile.total_latency += sample.duration;
+            profile.recent.push_back(sample.duration);
+            while profile.recent.len() > self.config.latency_window.max(1) {
+                profile.recent.pop_front();
+            }
+        }
+
+        if let Ok(key) = serde_json::to_string(&(&sample.collection, sample.kind, &sample.shape)) {
+            self.expensive.record(&key, (sample.duration.as_micros() as u64).max(1), Utc::now());
+        }
+
+        if slow && self.config.slow_query_log_size > 0 {
+            let (query, plan) = details();
+            tracing::warn!(
+                "Slow {} {} on {} took {} ms, scanning {} documents for {}",
+                sample.kind,
+                sample.query_id,
+                sample.collection,
+                sample.duration.as_millis(),
+                sample.documents_scanned,
+                sample.documents_returned
+            );
+            let mut log = self.slow.lock().unwrap();
+            log.push_front(SlowQuery {
+                query_id: sample.query_id,
+                collection: sample.collection,
+                kind: sample.kind.to_string(),
+                query,
+                plan,
+                duration_ms: sample.duration.as_millis() as u64,
+                documents_scanned: sample.documents_scanned,
+                documents_returned: sample.documents_returned,
+                failed: sample.failed,
+                recorded_at: Utc::now(),
+            });
+            log.truncate(self.config.slow_query_log_size);
+        }
+    }
+
+    /// Statistics of every collection queried, by collection name.
+    pub fn stats(&self) -> Vec<CollectionQueryStats> {
+        let collections = self.collections.lock().unwrap();
+        let mut stats: Vec<_> = collections.iter().map(|(collection, profile)| profile.stats(collection)).collect();
+        stats.sort_by(|a, b| a.collection.cmp(&b.collection));
+        stats
+    }
+
+    /// Statistics of one collection, unset if it was not queried.
+    pub fn collection_stats(&self, collection: &str) -> Option<CollectionQueryStats> {
+        self.collections.lock().unwrap().get(collection).map(|profile| profile.stats(collection))
+    }
+
+    /// Slow queries, newest first, of one collection or all of them.
+    pub fn slow_queries(&self, collection: Option<&str>, limit: Option<usize>) -> Vec<SlowQuery> {
+        self.slow
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|query| collection.is_none_or(|collection| query.collection == collection))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// The `limit` query shapes taking the most total time over the last
+    /// `window`, most expensive first.
+    pub fn expensive_queries(&self, limit: usize, window: Duration) -> Vec<ExpensiveQuery> {
+        self.expensive
+            .top(limit, window, Utc::now())
+            .into_iter()
+            .filter_map(|hitter| {
+                let (collection, kind, shape) = serde_json::from_str::<(String, String, Value)>(&hitter.key).ok()?;
+                Some(ExpensiveQuery {
+                    collection,
+                    kind,
+                    shape,
+                    total_ms: hitter.count as f64 / 1000.0,
+                    error_ms: hitter.error as f64 / 1000.0,
+                })
+            })
+            .collect()
+    }
+
+    /// Forget the statistics and slow queries recorded so far.
+    pub fn reset(&self) {
+        self.collections.lock().unwrap().clear();
+        self.slow.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample(collection: &str, millis: u64, scanned: usize, returned: usize) -> QuerySample {
+        QuerySample {
+            query_id: format!("{}-{}", collection, millis),
+            collection: collection.to_string(),
+            kind: "query",
+            duration: Duration::from_millis(millis),
+            documents_scanned: scanned,
+            documents_returned: returned,
+            from_cache: false,
+            failed: false,
+            shape: json!({ "filter": { "age": "?" } }),
+        }
+    }
+
+    #[test]
+    fn test_collection_stats_report_percentiles_and_scan_ratio() {
+        let profiler = QueryProfiler::new(ProfilingConfig { slow_query_threshold_ms: 1000, ..Default::default() });
+        for millis in 1..=100 {
+            profiler.record(sample("users", millis, 10, 2), || unreachable!("fast queries are not logged"));
+        }
+        profiler.record(QuerySample { kind: "aggregate", from_cache: true, ..sample("users", 5, 0, 0) }, || (json!([]), None));
+
+        let stats = profiler.collection_stats("users").unwrap();
+        assert_eq!((stats.queries, stats.aggregations, stats.cache_hits), (100, 1, 1));
+        assert_eq!((stats.documents_scanned, stats.documents_returned), (1000, 200));
+        assert_eq!(stats.scanned_per_returned, Some(5.0));
+        assert_eq!(stats.latency.samples, 101);
+        assert_eq!((stats.latency.p50_ms, stats.latency.p95_ms, stats.latency.max_ms), (50.0, 95.0, 100.0));
+        assert_eq!(stats.slow_queries, 0);
+        assert!(profiler.collection_stats("orders").is_none());
+    }
+
+    #[test]
+    fn test_slow_queries_are_logged_newest_first_and_bounded() {
+        let config = ProfilingConfig { slow_query_threshold_ms: 50, slow_query_log_size: 2, ..Default::default() };
+        let profiler = QueryProfiler::new(config);
+        profiler.record(sample("users", 10, 1, 1), || unreachable!("fast queries are not logged"));
+        for (collection, millis) in [("users", 60), ("orders", 70), ("users", 80)] {
+            profiler.record(sample(collection, millis, 500, 1), || (json!({ "filter": { "age": 30 } }), None));
+        }
+
+        let slow = profiler.slow_queries(None, None);
+        assert_eq!(slow.iter().map(|query| query.duration_ms).collect::<Vec<_>>(), vec![80, 70]);
+        assert_eq!(slow[0].query, json!({ "filter": { "age": 30 } }));
+        assert_eq!(profiler.slow_queries(Some("orders"), None).len(), 1);
+        assert_eq!(profiler.slow_queries(None, Some(1)).len(), 1);
+        assert_eq!(profiler.collection_stats("users").unwrap().slow_queries, 2);
+
+        profiler.reset();
+        assert!(profiler.stats().is_empty() && profiler.slow_queries(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_expensive_queries_add_up_by_shape() {
+        let profiler = QueryProfiler::new(ProfilingConfig::default());
+        assert_eq!(
+            query_shape(&json!({ "filter": { "age": { "$in": [30, 40] } }, "limit": 10 })),
+            json!({ "filter": { "age": { "$in": ["?"] } }, "limit": "?" })
+        );
+
+        for millis in [20, 30] {
+            profiler.record(sample("users", millis, 1, 1), || (json!({}), None));
+        }
+        profiler.record(QuerySample { shape: json!({}), ..sample("users", 40, 1, 1) }, || (json!({}), None));
+        profiler.record(sample("orders", 5, 1, 1), || (json!({}), None));
+
+        let expensive = profiler.expensive_queries(2, Duration::from_secs(60));
+        assert_eq!(expensive.len(), 2);
+        assert_eq!((expensive[0].collection.as_str(), expensive[0].total_ms), ("users", 50.0));
+        assert_eq!(expensive[0].shape, json!({ "filter": { "age": "?" } }));
+        assert_eq!((expensive[1].shape.clone(), expensive[1].total_ms), (json!({}), 40.0));
+    }
+}
@@ -0,0 +1,200 @@
+//! # Projections
+//!
+//! Queries return whole documents unless they carry a projection, which
+//! trims each returned document to the fields a client asked for before it
+//! is serialized, so fetching a few fields of large documents does not send
+//! the rest over the wire.
+//!
+//! A projection is an object of dotted field paths, all set to `1` (or
+//! `true`) to keep only those fields, or all set to `0` (`false`) to drop
+//! them; the two cannot be mixed:
+//!
+//! ```json
+//! {"title": 1, "author.name": 1}
+//! {"body": 0, "attachments": 0}
+//! ```
+//!
+//! Paths reach into nested objects. Kept fields that a document lacks are
+//! left out rather than returned as `null`. Text search results keep their
+//! `_score` under an inclusion.
+//!
+//! Projections are applied after filtering, sorting and paging, so a query
+//! can sort on fields it does not return.
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+use crate::text::SCORE_FIELD;
+
+/// A compiled projection of query results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Projection {
+    /// Keep only these fields
+    Include(Vec<Vec<String>>),
+
+    /// Keep everything but these fields
+    Exclude(Vec<Vec<String>>),
+}
+
+impl Projection {
+    /// Parse a projection object, failing with an "Invalid projection"
+    /// error on empty, mixed or malformed specifications.
+    pub fn compile(spec: &Value) -> Result<Self> {
+        let Value::Object(fields) = spec else {
+            return Err(anyhow!("Invalid projection: expected an object of field paths"));
+        };
+        if fields.is_empty() {
+            return Err(anyhow!("Invalid projection: needs at least one field"));
+        }
+
+        let (mut included, mut excluded) = (Vec::new(), Vec::new());
+        for (field, flag) in fields {
+            let keep = match flag {
+                Value::Bool(keep) => *keep,
+                Value::Number(flag) if flag.as_f64() == Some(0.0) => false,
+                Value::Number(flag) if flag.as_f64() == Some(1.0) => true,
+                _ => return Err(anyhow!("Invalid projection: '{}' must be 1 or 0", field)),
+            };
+            match keep {
+                true => included.push(parse_path(field)?),
+                false => excluded.push(parse_path(field)?),
+            }
+        }
+
+        match (included.is_empty(), excluded.is_empty()) {
+            (false, false) => Err(anyhow!("Invalid projection: cannot mix included and excluded fields")),
+            (false, true) => Ok(Projection::Include(included)),
+            _ => Ok(Projection::Exclude(excluded)),
+        }
+    }
+
+    /// Keep only the fields at `paths`.
+    pub fn include<'a>(paths: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        Self::from_paths(paths).map(Projection::Include)
+    }
+
+    /// Drop the fields at `paths`.
+    pub fn exclude<'a>(paths: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        Self::from_paths(paths).map(Projection::Exclude)
+    }
+
+    fn from_paths<'a>(paths: impl IntoIterator<Item = &'a str>) -> Result<Vec<Vec<String>>> {
+        let paths = paths.into_iter().map(parse_path).collect::<Result<Vec<_>>>()?;
+        if paths.is_empty() {
+            return Err(anyhow!("Invalid projection: needs at least one field"));
+        }
+        Ok(paths)
+    }
+
+    /// The document trimmed to the projection.
+    pub fn apply(&self, document: Value) -> Value {
+        match self {
+            Projection::Include(paths) => {
+                let mut projected = Map::new();
+                if let Some(score) = document.get(SCORE_FIELD) {
+                    projected.insert(SCORE_FIELD.to_string(), score.clone());
+                }
+                for path in paths {
+                    if let Some(value) = lookup(&document, path) {
+                        insert(&mut projected, path, value.clone());
+                    }
+                }
+                Value::Object(projected)
+            }
+            Projection::Exclude(paths) => {
+                let mut document = document;
+                for path in paths {
+                    remove(&mut document, path);
+                }
+                document
+            }
+        }
+    }
+}
+
+fn parse_path(field: &str) -> Result<Vec<String>> {
+    if field.is_empty() || field.starts_with('$') || field.split('.').any(str::is_empty) {
+        return Err(anyhow!("Invalid projection: bad field path '{}'", field));
+    }
+    Ok(field.split('.').map(String::from).collect())
+}
+
+/// The value at a path through objects, and arrays by numeric segments
+pub(crate) fn lookup<'a>(document: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(document, |value, segment| match value {
+        Value::Object(fields) => fields.get(segment),
+        Value::Array(elements) => elements.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Set the value at a path, creating the objects on the way
+pub(crate) fn insert(document: &mut Map<String, Value>, path: &[String], value: Value) {
+    let (last, parents) = path.split_last().expect("paths are not empty");
+    let mut current = document;
+    for segment in parents {
+        let entry = current.entry(segment.clone()).or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        current = entry.as_object_mut().expect("replaced by an object above");
+    }
+    current.insert(last.clone(), value);
+}
+
+pub(crate) fn remove(document: &mut Value, path: &[String]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let parent = parents.iter().try_fold(document, |value, segment| value.as_object_mut()?.get_mut(segment));
+    if let Some(Value::Object(parent)) = parent {
+        parent.remove(last);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn article() -> Value {
+        json!({
+            "title": "Projections",
+            "body": "a long body",
+            "author": { "name": "Ada", "email": "ada@example.com" },
+            "tags": ["rust", "json"],
+            "_score": 1.5
+        })
+    }
+
+    #[test]
+    fn test_inclusions_keep_nested_fields() {
+        let projection = Projection::compile(&json!({ "title": 1, "author.name": true, "missing": 1 })).unwrap();
+
+        assert_eq!(
+            projection.apply(article()),
+            json!({ "title": "Projections", "author": { "name": "Ada" }, "_score": 1.5 })
+        );
+        assert_eq!(Projection::include(["author.email"]).unwrap().apply(json!({ "author": "Ada" })), json!({}));
+    }
+
+    #[test]
+    fn test_exclusions_drop_nested_fields() {
+        let projection = Projection::compile(&json!({ "body": 0, "author.email": false })).unwrap();
+
+        assert_eq!(
+            projection.apply(article()),
+            json!({ "title": "Projections", "author": { "name": "Ada" }, "tags": ["rust", "json"], "_score": 1.5 })
+        );
+        assert_eq!(projection, Projection::exclude(["author.email", "body"]).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_projections_are_refused() {
+        for spec in [json!({}), json!(["title"]), json!({ "title": 1, "body": 0 }), json!({ "title": 2 }), json!({ "a..b": 1 })] {
+            let error = Projection::compile(&spec).unwrap_err().to_string();
+            assert!(error.starts_with("Invalid projection"), "{}: {}", spec, error);
+        }
+        assert!(Projection::include([]).is_err());
+    }
+}
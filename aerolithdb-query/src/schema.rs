@@ -0,0 +1,832 @@
+//! # Collection Schemas
+//!
+//! A collection may declare a JSON Schema its documents must conform to.
+//! Documents stored or updated through the query engine are validated
+//! against the collection's current schema before they are written.
+//!
+//! - **Modes**: `strict` refuses a non-conforming document with a
+//!   [`SchemaValidationError`] listing every violation with the JSON
+//!   pointer of the offending value; `warn` logs the violations and writes
+//!   the document; `off` keeps the schema without checking writes
+//! - **Versions**: every schema set for a collection is a new version, and
+//!   replaced versions are kept so a document can be traced to the schema
+//!   it was written under; changing only the mode keeps the version
+//! - **Keywords**: `type`, `enum`, `const`, numeric bounds and `multipleOf`,
+//!   string lengths, `pattern` and `format` (`date-time`, `date`, `email`,
+//!   `uuid`, `ipv4` and `ipv6` are checked, other formats are annotations),
+//!   array `items`, lengths and `uniqueItems`, object `properties`,
+//!   `required`, `additionalProperties` and property counts, and `allOf`,
+//!   `anyOf`, `oneOf` and `not`. Annotations such as `title` and
+//!   `description` are accepted; any other keyword, `$ref` included, is
+//!   refused with an "Invalid schema" error rather than silently not
+//!   enforced
+//!
+//! Schemas live in the `_schemas` system collection, the current one under
+//! the collection's name and every version under `<collection>:v<version>`,
+//! so all nodes validate against the same schema. Writes applied by sync,
+//! restores and shard transfers were validated where they were first made
+//! and are not checked again.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use aerolithdb_storage::StorageHierarchy;
+
+/// System collection holding collection schemas
+pub const SCHEMA_COLLECTION: &str = "_schemas";
+
+/// Most violations reported for one document
+const MAX_VIOLATIONS: usize = 100;
+
+/// Keywords carrying no constraint
+const ANNOTATIONS: &[&str] = &[
+    "$schema", "$id", "$comment", "title", "description", "default", "examples", "deprecated", "readOnly", "writeOnly",
+];
+
+/// How writes are checked against a collection's schema.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Non-conforming documents are refused
+    #[default]
+    Strict,
+
+    /// Non-conforming documents are written and their violations logged
+    Warn,
+
+    /// Documents are not checked
+    Off,
+}
+
+/// A version of the schema of a collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSchema {
+    pub collection: String,
+
+    /// Version, starting at 1 and increased each time a schema is set
+    pub version: u32,
+    pub mode: ValidationMode,
+
+    /// The JSON Schema documents are validated against
+    pub schema: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A value of a document failing a schema keyword.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the value, empty for the document itself
+    pub path: String,
+
+    /// Keyword the value fails
+    pub keyword: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Error of a write refused by a strict schema; find it in an
+/// `anyhow::Error` with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaValidationError {
+    pub collection: String,
+    pub document_id: String,
+
+    /// Version of the schema the document failed
+    pub version: u32,
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl std::fmt::Display for SchemaValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid document: {} does not conform to version {} of the schema of {}",
+            self.document_id, self.version, self.collection
+        )?;
+        for (i, violation) in self.violations.iter().take(5).enumerate() {
+            write!(f, "{}{}", if i == 0 { ": " } else { "; " }, violation)?;
+        }
+        if self.violations.len() > 5 {
+            write!(f, "; and {} more", self.violations.len() - 5)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaValidationError {}
+
+/// A JSON Schema parsed and checked once, ready to validate many documents.
+#[derive(Debug, Clone)]
+pub struct CompiledSchema {
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    /// `true`: every value conforms
+    Any,
+
+    /// `false`: no value conforms
+    Never,
+    Checks(Vec<Check>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonType {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    Integer,
+    String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    DateTime,
+    Date,
+    Email,
+    Uuid,
+    Ipv4,
+    Ipv6,
+}
+
+#[derive(Debug, Clone)]
+enum Check {
+    Type(Vec<JsonType>),
+    Enum(Vec<Value>),
+    Const(Value),
+
+    /// The number orders above (or, with the flag, equals) the bound
+    Minimum(f64, bool),
+    Maximum(f64, bool),
+    MultipleOf(f64),
+    MinLength(usize),
+    MaxLength(usize),
+    Pattern(Regex),
+    Format(Format),
+    Items(Box<Node>),
+    MinItems(usize),
+    MaxItems(usize),
+    UniqueItems,
+    Properties(Vec<(String, Node)>),
+    Required(Vec<String>),
+
+    /// Schema of the properties not named by `properties`
+    AdditionalProperties(HashSet<String>, Box<Node>),
+    MinProperties(usize),
+    MaxProperties(usize),
+    AllOf(Vec<Node>),
+    AnyOf(Vec<Node>),
+    OneOf(Vec<Node>),
+    Not(Box<Node>),
+}
+
+impl CompiledSchema {
+    /// Parse a schema, failing on unsupported keywords and malformed
+    /// operands.
+    pub fn compile(schema: &Value) -> Result<Self> {
+        Ok(Self { root: parse(schema, "")? })
+    }
+
+    /// Violations of the schema by a document, empty if it conforms.
+    pub fn validate(&self, document: &Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        self.root.check(document, "", &mut violations);
+        violations.truncate(MAX_VIOLATIONS);
+        violations
+    }
+}
+
+fn parse(schema: &Value, at: &str) -> Result<Node> {
+    let keywords = match schema {
+        Value::Bool(true) => return Ok(Node::Any),
+        Value::Bool(false) => return Ok(Node::Never),
+        Value::Object(keywords) => keywords,
+        other => return Err(anyhow!("Invalid schema: {} must be an object or boolean, got {}", location(at), other)),
+    };
+
+    let mut checks = Vec::new();
+    for (keyword, operand) in keywords {
+        let here = format!("{}/{}", at, keyword);
+        let check = match keyword.as_str() {
+            "type" => Check::Type(parse_types(operand, &here)?),
+            "enum" => match operand {
+                Value::Array(values) => Check::Enum(values.clone()),
+                _ => return Err(anyhow!("Invalid schema: {} must be an array", location(&here))),
+            },
+            "const" => Check::Const(operand.clone()),
+            "minimum" => Check::Minimum(number(operand, &here)?, true),
+            "exclusiveMinimum" => Check::Minimum(number(operand, &here)?, false),
+            "maximum" => Check::Maximum(number(operand, &here)?, true),
+            "exclusiveMaximum" => Check::Maximum(number(operand, &here)?, false),
+            "multipleOf" => match number(operand, &here)? {
+                divisor if divisor > 0.0 => Check::MultipleOf(divisor),
+                _ => return Err(anyhow!("Invalid schema: {} must be greater than 0", location(&here))),
+            },
+            "minLength" => Check::MinLength(count(operand, &here)?),
+            "maxLength" => Check::MaxLength(count(operand, &here)?),
+            "pattern" => match operand {
+                Value::String(pattern) => Check::Pattern(
+                    Regex::new(pattern).map_err(|e| anyhow!("Invalid schema: {} is not a valid pattern: {}", location(&here), e))?,
+                ),
+                _ => return Err(anyhow!("Invalid schema: {} must be a string", location(&here))),
+            },
+            "format" => match operand.as_str() {
+                Some("date-time") => Check::Format(Format::DateTime),
+                Some("date") => Check::Format(Format::Date),
+                Some("email") => Check::Format(Format::Email),
+                Some("uuid") => Check::Format(Format::Uuid),
+                Some("ipv4") => Check::Format(Format::Ipv4),
+                Some("ipv6") => Check::Format(Format::Ipv6),
+                Some(_) => continue,
+                None => return Err(anyhow!("Invalid schema: {} must be a string", location(&here))),
+            },
+            "items" => match operand {
+                Value::Array(_) => return Err(anyhow!("Invalid schema: {} must be a single schema", location(&here))),
+                items => Check::Items(Box::new(parse(items, &here)?)),
+            },
+            "minItems" => Check::MinItems(count(operand, &here)?),
+            "maxItems" => Check::MaxItems(count(operand, &here)?),
+            "uniqueItems" => match operand {
+                Value::Bool(true) => Check::UniqueItems,
+                Value::Bool(false) => continue,
+                _ => return Err(anyhow!("Invalid schema: {} must be a boolean", location(&here))),
+            },
+            "properties" => match operand {
+                Value::Object(properties) => Check::Properties(
+                    properties
+                        .iter()
+                        .map(|(name, schema)| Ok((name.clone(), parse(schema, &format!("{}/{}", here, escape(name)))?)))
+                        .collect::<Result<_>>()?,
+                ),
+                _ => return Err(anyhow!("Invalid schema: {} must be an object", location(&here))),
+            },
+            "required" => match operand.as_array().and_then(|names| names.iter().map(Value::as_str).collect::<Option<Vec<_>>>()) {
+                Some(names) => Check::Required(names.into_iter().map(String::from).collect()),
+                None => return Err(anyhow!("Invalid schema: {} must be an array of property names", location(&here))),
+            },
+            "additionalProperties" => {
+                let named = keywords
+                    .get("properties")
+                    .and_then(Value::as_object)
+                    .map(|properties| properties.keys().cloned().collect())
+                    .unwrap_or_default();
+                Check::AdditionalProperties(named, Box::new(parse(operand, &here)?))
+            }
+            "minProperties" => Check::MinProperties(count(operand, &here)?),
+            "maxProperties" => Check::MaxProperties(count(operand, &here)?),
+            "allOf" => Check::AllOf(parse_all(operand, &here)?),
+            "anyOf" => Check::AnyOf(parse_all(operand, &here)?),
+            "oneOf" => Check::OneOf(parse_all(operand, &here)?),
+            "not" => Check::Not(Box::new(parse(operand, &here)?)),
+            _ if ANNOTATIONS.contains(&keyword.as_str()) => continue,
+            _ => return Err(anyhow!("Invalid schema: unsupported keyword {} at {}", keyword, location(at))),
+        };
+        checks.push(check);
+    }
+    Ok(Node::Checks(checks))
+}
+
+fn parse_types(operand: &Value, at: &str) -> Result<Vec<JsonType>> {
+    let parse_type = |name: &Value| match name.as_str() {
+        Some("null") => Ok(JsonType::Null),
+        Some("boolean") => Ok(JsonType::Boolean),
+        Some("object") => Ok(JsonType::Object),
+        Some("array") => Ok(JsonType::Array),
+        Some("number") => Ok(JsonType::Number),
+        Some("integer") => Ok(JsonType::Integer),
+        Some("string") => Ok(JsonType::String),
+        _ => Err(anyhow!("Invalid schema: {} has unknown type {}", location(at), name)),
+    };
+    match operand {
+        Value::Array(names) if !names.is_empty() => names.iter().map(parse_type).collect(),
+        Value::Array(_) => Err(anyhow!("Invalid schema: {} must name at least one type", location(at))),
+        name => Ok(vec![parse_type(name)?]),
+    }
+}
+
+/// Parse the operand of `allOf`, `anyOf` or `oneOf`: a non-empty array of
+/// schemas
+fn parse_all(operand: &Value, at: &str) -> Result<Vec<Node>> {
+    match operand {
+        Value::Array(schemas) if !schemas.is_empty() => {
+            schemas.iter().enumerate().map(|(i, schema)| parse(schema, &format!("{}/{}", at, i))).collect()
+        }
+        _ => Err(anyhow!("Invalid schema: {} must be a non-empty array of schemas", location(at))),
+    }
+}
+
+fn number(operand: &Value, at: &str) -> Result<f64> {
+    operand.as_f64().ok_or_else(|| anyhow!("Invalid schema: {} must be a number", location(at)))
+}
+
+fn count(operand: &Value, at: &str) -> Result<usize> {
+    operand
+        .as_u64()
+        .map(|count| count as usize)
+        .ok_or_else(|| anyhow!("Invalid schema: {} must be a non-negative integer", location(at)))
+}
+
+fn location(at: &str) -> &str {
+    if at.is_empty() { "the schema" } else { at }
+}
+
+/// Escape a property name for a JSON pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+impl Node {
+    fn check(&self, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+        match self {
+            Node::Any => {}
+            Node::Never => violations.push(violation(path, "false", "no value is allowed here".to_string())),
+            Node::Checks(checks) => {
+                for check in checks {
+                    check.check(value, path, violations);
+                }
+            }
+        }
+    }
+
+    fn conforms(&self, value: &Value) -> bool {
+        let mut violations = Vec::new();
+        self.check(value, "", &mut violations);
+        violations.is_empty()
+    }
+}
+
+impl Check {
+    fn check(&self, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+        if violations.len() >= MAX_VIOLATIONS {
+            return;
+        }
+        let mut fail = |keyword: &str, message: String| violations.push(violation(path, keyword, message));
+        match (self, value) {
+            (Check::Type(types), value) if !types.iter().any(|json_type| json_type.describes(value)) => {
+                let names: Vec<&str> = types.iter().map(JsonType::name).collect();
+                fail("type", format!("expected {}, got {}", names.join(" or "), type_name(value)));
+            }
+            (Check::Enum(values), value) if !values.iter().any(|allowed| equal(allowed, value)) => {
+                fail("enum", format!("{} is not one of the allowed values", value));
+            }
+            (Check::Const(expected), value) if !equal(expected, value) => {
+                fail("const", format!("expected {}", expected));
+            }
+            (Check::Minimum(bound, inclusive), Value::Number(number)) => {
+                let number = number.as_f64().unwrap_or(f64::NAN);
+                if number < *bound || (!inclusive && number == *bound) {
+                    let keyword = if *inclusive { "minimum" } else { "exclusiveMinimum" };
+                    let relation = if *inclusive { "at least" } else { "greater than" };
+                    fail(keyword, format!("{} is not {} {}", number, relation, bound));
+                }
+            }
+            (Check::Maximum(bound, inclusive), Value::Number(number)) => {
+                let number = number.as_f64().unwrap_or(f64::NAN);
+                if number > *bound || (!inclusive && number == *bound) {
+                    let keyword = if *inclusive { "maximum" } else { "exclusiveMaximum" };
+                    let relation = if *inclusive { "at most" } else { "less than" };
+                    fail(keyword, format!("{} is not {} {}", number, relation, bound));
+                }
+            }
+            (Check::MultipleOf(divisor), Value::Number(number)) => {
+                let quotient = number.as_f64().unwrap_or(f64::NAN) / divisor;
+                if (quotient - quotient.round()).abs() > 1e-9 {
+                    fail("multipleOf", format!("{} is not a multiple of {}", number, divisor));
+                }
+            }
+            (Check::MinLength(min), Value::String(string)) if string.chars().count() < *min => {
+                fail("minLength", format!("shorter than {} characters", min));
+            }
+            (Check::MaxLength(max), Value::String(string)) if string.chars().count() > *max => {
+                fail("maxLength", format!("longer than {} characters", max));
+            }
+            (Check::Pattern(pattern), Value::String(string)) if !pattern.is_match(string) => {
+                fail("pattern", format!("does not match the pattern {}", pattern.as_str()));
+            }
+            (Check::Format(format), Value::String(string)) if !format.accepts(string) => {
+                fail("format", format!("is not a valid {}", format.name()));
+            }
+            (Check::Items(schema), Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    schema.check(item, &format!("{}/{}", path, i), violations);
+                }
+            }
+            (Check::MinItems(min), Value::Array(items)) if items.len() < *min => {
+                fail("minItems", format!("has fewer than {} items", min));
+            }
+            (Check::MaxItems(max), Value::Array(items)) if items.len() > *max => {
+                fail("maxItems", format!("has more than {} items", max));
+            }
+            (Check::UniqueItems, Value::Array(items)) => {
+                let duplicate = items.iter().enumerate().find(|(i, item)| items[..*i].iter().any(|earlier| equal(earlier, item)));
+                if let Some((i, _)) = duplicate {
+                    fail("uniqueItems", format!("item {} repeats an earlier item", i));
+                }
+            }
+            (Check::Properties(properties), Value::Object(fields)) => {
+                for (name, schema) in properties {
+                    if let Some(field) = fields.get(name) {
+                        schema.check(field, &format!("{}/{}", path, escape(name)), violations);
+                    }
+                }
+            }
+            (Check::Required(names), Value::Object(fields)) => {
+                for name in names.iter().filter(|name| !fields.contains_key(*name)) {
+                    fail("required", format!("missing required property {}", name));
+                }
+            }
+            (Check::AdditionalProperties(named, schema), Value::Object(fields)) => {
+                for (name, field) in fields.iter().filter(|(name, _)| !named.contains(*name)) {
+                    let field_path = format!("{}/{}", path, escape(name));
+                    match schema.as_ref() {
+                        Node::Never => violations.push(violation(
+                            &field_path,
+                            "additionalProperties",
+                            format!("property {} is not allowed", name),
+                        )),
+                        schema => schema.check(field, &field_path, violations),
+                    }
+                }
+            }
+            (Check::MinProperties(min), Value::Object(fields)) if fields.len() < *min => {
+                fail("minProperties", format!("has fewer than {} properties", min));
+            }
+            (Check::MaxProperties(max), Value::Object(fields)) if fields.len() > *max => {
+                fail("maxProperties", format!("has more than {} properties", max));
+            }
+            (Check::AllOf(schemas), value) => {
+                for schema in schemas {
+                    schema.check(value, path, violations);
+                }
+            }
+            (Check::AnyOf(schemas), value) if !schemas.iter().any(|schema| schema.conforms(value)) => {
+                fail("anyOf", "does not match any of the allowed schemas".to_string());
+            }
+            (Check::OneOf(schemas), value) => {
+                let matching = schemas.iter().filter(|schema| schema.conforms(value)).count();
+                if matching != 1 {
+                    fail("oneOf", format!("matches {} of the schemas instead of exactly one", matching));
+                }
+            }
+            (Check::Not(schema), value) if schema.conforms(value) => {
+                fail("not", "matches a schema it must not".to_string());
+            }
+            // Keywords constrain only values of the type they apply to
+            _ => {}
+        }
+    }
+}
+
+fn violation(path: &str, keyword: &str, message: String) -> SchemaViolation {
+    SchemaViolation { path: path.to_string(), keyword: keyword.to_string(), message }
+}
+
+impl JsonType {
+    fn describes(&self, value: &Value) -> bool {
+        match (self, value) {
+            (JsonType::Null, Value::Null)
+            | (JsonType::Boolean, Value::Bool(_))
+            | (JsonType::Object, Value::Object(_))
+            | (JsonType::Array, Value::Array(_))
+            | (JsonType::Number, Value::Number(_))
+            | (JsonType::String, Value::String(_)) => true,
+            (JsonType::Integer, Value::Number(number)) => {
+                number.is_i64() || number.is_u64() || number.as_f64().is_some_and(|number| number.fract() == 0.0)
+            }
+            _ => false,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Object => "object",
+            JsonType::Array => "array",
+            JsonType::Number => "number",
+            JsonType::Integer => "integer",
+            JsonType::String => "string",
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+    }
+}
+
+/// JSON equality, with numbers compared by value so that `1` equals `1.0`.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b)),
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(name, a)| b.get(name).is_some_and(|b| equal(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+impl Format {
+    fn accepts(&self, string: &str) -> bool {
+        match self {
+            Format::DateTime => DateTime::parse_from_rfc3339(string).is_ok(),
+            Format::Date => chrono::NaiveDate::parse_from_str(string, "%Y-%m-%d").is_ok(),
+            Format::Email => {
+                !string.contains(char::is_whitespace)
+                    && string.split_once('@').is_some_and(|(local, domain)| {
+                        !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+                    })
+            }
+            Format::Uuid => uuid::Uuid::parse_str(string).is_ok() && string.len() == 36,
+            Format::Ipv4 => string.parse::<std::net::Ipv4Addr>().is_ok(),
+            Format::Ipv6 => string.parse::<std::net::Ipv6Addr>().is_ok(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Format::DateTime => "date-time",
+            Format::Date => "date",
+            Format::Email => "email",
+            Format::Uuid => "uuid",
+            Format::Ipv4 => "ipv4",
+            Format::Ipv6 => "ipv6",
+        }
+    }
+}
+
+/// Schemas declared for collections, compiled as they are first used.
+pub struct SchemaRegistry {
+    storage: Arc<StorageHierarchy>,
+
+    /// Compiled schemas by collection and version
+    compiled: Mutex<HashMap<(String, u32), Arc<CompiledSchema>>>,
+}
+
+impl std::fmt::Debug for SchemaRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SchemaRegistry").finish_non_exhaustive()
+    }
+}
+
+impl SchemaRegistry {
+    pub fn new(storage: Arc<StorageHierarchy>) -> Self {
+        Self { storage, compiled: Mutex::new(HashMap::new()) }
+    }
+
+    /// Current schema of a collection, if it has one.
+    pub async fn get(&self, collection: &str) -> Result<Option<CollectionSchema>> {
+        self.read(collection).await
+    }
+
+    /// A version of the schema of a collection, current or replaced.
+    pub async fn get_version(&self, collection: &str, version: u32) -> Result<Option<CollectionSchema>> {
+        self.read(&version_key(collection, version)).await
+    }
+
+    /// Every version of the schema of a collection, oldest first.
+    pub async fn versions(&self, collection: &str) -> Result<Vec<CollectionSchema>> {
+        let prefix = format!("{}:v", collection);
+        let mut versions = Vec::new();
+        for id in self.storage.list_documents(SCHEMA_COLLECTION, None, None).await? {
+            if id.starts_with(&prefix) {
+                versions.extend(self.read(&id).await?);
+            }
+        }
+        versions.sort_by_key(|schema| schema.version);
+        Ok(versions)
+    }
+
+    /// Set the schema of a collection as its next version.
+    pub async fn set(&self, collection: &str, schema: Value, mode: ValidationMode) -> Result<CollectionSchema> {
+        if collection.is_empty() || collection.starts_with('_') {
+            return Err(anyhow!("Invalid schema: cannot declare a schema for collection '{}'", collection));
+        }
+        let compiled = CompiledSchema::compile(&schema)?;
+
+        let version = self.versions(collection).await?.last().map_or(1, |latest| latest.version + 1);
+        let declared = CollectionSchema { collection: collection.to_string(), version, mode, schema, created_at: Utc::now() };
+        self.write(&declared).await?;
+        self.compiled.lock().unwrap().insert((collection.to_string(), version), Arc::new(compiled));
+        info!("Set version {} of the schema of {} ({:?})", version, collection, mode);
+        Ok(declared)
+    }
+
+    /// Change how writes are checked against the current schema of a
+    /// collection, keeping its version; `None` if it has no schema.
+    pub async fn set_mode(&self, collection: &str, mode: ValidationMode) -> Result<Option<CollectionSchema>> {
+        let Some(current) = self.get(collection).await? else {
+            return Ok(None);
+        };
+        let updated = CollectionSchema { mode, ..current };
+        self.write(&updated).await?;
+        info!("Validating writes to {} against its schema in {:?} mode", collection, mode);
+        Ok(Some(updated))
+    }
+
+    /// Stop validating a collection's documents, returning whether it had a
+    /// schema. Its versions are kept, and a schema set later continues
+    /// their numbering.
+    pub async fn remove(&self, collection: &str) -> Result<bool> {
+        if self.storage.get_document(SCHEMA_COLLECTION, collection).await?.data.is_none() {
+            return Ok(false);
+        }
+        self.storage.delete_document(SCHEMA_COLLECTION, collection).await?;
+        info!("Removed the schema of {}", collection);
+        Ok(true)
+    }
+
+    /// Check a document about to be written to a collection against its
+    /// schema, failing with a [`SchemaValidationError`] if the schema is
+    /// strict and the document does not conform.
+    pub async fn validate(&self, collection: &str, document_id: &str, document: &Value) -> Result<()> {
+        if collection.starts_with('_') {
+            return Ok(());
+        }
+        let Some(declared) = self.get(collection).await? else {
+            return Ok(());
+        };
+        if declared.mode == ValidationMode::Off {
+            return Ok(());
+        }
+
+        let key = (collection.to_string(), declared.version);
+        let cached = self.compiled.lock().unwrap().get(&key).cloned();
+        let compiled = match cached {
+            Some(compiled) => compiled,
+            None => {
+                let compiled = Arc::new(CompiledSchema::compile(&declared.schema)?);
+                self.compiled.lock().unwrap().insert(key, Arc::clone(&compiled));
+                compiled
+            }
+        };
+
+        let violations = compiled.validate(document);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let error = SchemaValidationError {
+            collection: collection.to_string(),
+            document_id: document_id.to_string(),
+            version: declared.version,
+            violations,
+        };
+        match declared.mode {
+            ValidationMode::Strict => Err(anyhow::Error::new(error)),
+            _ => {
+                warn!("{}", error);
+                Ok(())
+            }
+        }
+    }
+
+    async fn read(&self, id: &str) -> Result<Option<CollectionSchema>> {
+        match self.storage.get_document(SCHEMA_COLLECTION, id).await?.data {
+            Some(document) => Ok(Some(serde_json::from_value(document)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn write(&self, declared: &CollectionSchema) -> Result<()> {
+        let document = serde_json::to_value(declared)?;
+        self.storage
+            .store_document(SCHEMA_COLLECTION, &version_key(&declared.collection, declared.version), &document)
+            .await?;
+        self.storage.store_document(SCHEMA_COLLECTION, &declared.collection, &document).await?;
+        Ok(())
+    }
+}
+
+/// ID of a version of a collection's schema in the schema collection.
+fn version_key(collection: &str, version: u32) -> String {
+    format!("{}:v{:010}", collection, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(violations: &[SchemaViolation]) -> Vec<(&str, &str)> {
+        violations.iter().map(|violation| (violation.path.as_str(), violation.keyword.as_str())).collect()
+    }
+
+    #[test]
+    fn test_violations_name_the_offending_values() {
+        let schema = CompiledSchema::compile(&json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": "integer", "minimum": 0 },
+                "email": { "type": "string", "format": "email" },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] }, "uniqueItems": true },
+                "address": {
+                    "type": "object",
+                    "properties": { "zip": { "type": "string", "pattern": "^[0-9]{5}$" } },
+                    "additionalProperties": false
+                },
+                "nickname": { "anyOf": [{ "type": "string" }, { "type": "null" }] }
+            },
+            "required": ["name", "age"]
+        }))
+        .unwrap();
+
+        let valid = json!({ "name": "Ada", "age": 36.0, "email": "ada@example.com", "tags": ["a"], "nickname": null });
+        assert!(schema.validate(&valid).is_empty());
+
+        let invalid = json!({
+            "name": "",
+            "email": "ada",
+            "tags": ["a", "c", "a"],
+            "address": { "zip": "1234", "city/town": "Oslo" },
+            "nickname": 7
+        });
+        let violations = schema.validate(&invalid);
+        let mut violations = paths(&violations);
+        violations.sort();
+        assert_eq!(
+            violations,
+            [
+                ("", "required"),
+                ("/address/city~1town", "additionalProperties"),
+                ("/address/zip", "pattern"),
+                ("/email", "format"),
+                ("/name", "minLength"),
+                ("/nickname", "anyOf"),
+                ("/tags", "uniqueItems"),
+                ("/tags/1", "enum"),
+            ]
+        );
+        assert!(schema.validate(&json!([])).iter().any(|violation| violation.message == "expected object, got array"));
+    }
+
+    #[test]
+    fn test_unsupported_and_malformed_schemas_are_refused() {
+        for schema in [
+            json!({ "$ref": "#/definitions/user" }),
+            json!({ "type": "text" }),
+            json!({ "minLength": -1 }),
+            json!({ "properties": { "name": { "pattern": "(" } } }),
+            json!({ "anyOf": [] }),
+            json!("object"),
+        ] {
+            let error = CompiledSchema::compile(&schema).unwrap_err().to_string();
+            assert!(error.starts_with("Invalid schema"), "{} gave {}", schema, error);
+        }
+        assert!(CompiledSchema::compile(&json!({ "title": "User", "format": "hostname" })).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_schemas_are_versioned_and_enforced_by_mode() {
+        let root = std::env::temp_dir().join(format!("aerolith-schemas-{}", uuid::Uuid::new_v4()));
+        let storage_config = aerolithdb_storage::StorageConfig { data_dir: root.join("data"), ..Default::default() };
+        let storage = Arc::new(StorageHierarchy::new(&storage_config).await.unwrap());
+        let schemas = SchemaRegistry::new(storage);
+
+        let user = json!({ "name": "Ada" });
+        assert!(schemas.validate("users", "u1", &user).await.is_ok());
+
+        let v1 = schemas.set("users", json!({ "required": ["name"] }), ValidationMode::Strict).await.unwrap();
+        let v2 = schemas.set("users", json!({ "required": ["name", "email"] }), ValidationMode::Strict).await.unwrap();
+        assert_eq!((v1.version, v2.version), (1, 2));
+        assert!(schemas.set("_schemas", json!({}), ValidationMode::Strict).await.is_err());
+
+        let error = schemas.validate("users", "u1", &user).await.unwrap_err();
+        let refused = error.downcast_ref::<SchemaValidationError>().unwrap();
+        assert_eq!((refused.version, refused.violations[0].keyword.as_str()), (2, "required"));
+        assert!(error.to_string().starts_with("Invalid document"));
+
+        let warned = schemas.set_mode("users", ValidationMode::Warn).await.unwrap().unwrap();
+        assert_eq!(warned.version, 2);
+        assert!(schemas.validate("users", "u1", &user).await.is_ok());
+
+        assert_eq!(schemas.get_version("users", 1).await.unwrap().unwrap().schema, json!({ "required": ["name"] }));
+        assert!(schemas.remove("users").await.unwrap());
+        assert!(schemas.get("users").await.unwrap().is_none());
+        assert_eq!(schemas.versions("users").await.unwrap().len(), 2);
+        assert_eq!(schemas.set("users", json!(true), ValidationMode::Off).await.unwrap().version, 3);
+    }
+}
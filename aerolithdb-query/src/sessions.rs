@@ -0,0 +1,348 @@
+//! Client sessions and ephemeral documents.
+//!
+//! An ephemeral document is an ordinary document bound to a client session.
+//! The session stays alive while the client sends heartbeats; once a
+//! heartbeat is missed for longer than the session TTL, the session expires
+//! and every ephemeral document it owns is deleted. This supports common
+//! coordination patterns on top of the document model:
+//!
+//! - **Presence**: one ephemeral document per connected user in a collection
+//! - **Locks**: an ephemeral document whose existence marks ownership
+//! - **Service discovery**: instances register ephemeral endpoint documents
+//!
+//! Ephemeral documents carry their owning session ID in the `_session` field
+//! so readers can tell them apart from durable documents. Sessions are stored
+//! in a system collection, so any node can expire sessions left behind by a
+//! node that went down.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use aerolithdb_storage::StorageHierarchy;
+
+/// System collection holding session records
+pub const SESSION_COLLECTION: &str = "_sessions";
+
+/// Field added to ephemeral documents naming the owning session
+pub const EPHEMERAL_OWNER_FIELD: &str = "_session";
+
+/// Shortest TTL a session may request
+pub const MIN_SESSION_TTL: Duration = Duration::from_secs(1);
+
+/// Longest TTL a session may request
+pub const MAX_SESSION_TTL: Duration = Duration::from_secs(3600);
+
+/// How often expired sessions are swept
+pub const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A client session owning zero or more ephemeral documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    /// Client or user that opened the session
+    pub owner: String,
+    /// Time without a heartbeat after which the session expires
+    pub ttl: Duration,
+    pub created_at: DateTime<Utc>,
+    pub last_heartbeat: DateTime<Utc>,
+    /// Ephemeral documents owned by this session
+    pub documents: Vec<EphemeralDocumentRef>,
+}
+
+/// Location of an ephemeral document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EphemeralDocumentRef {
+    pub collection: String,
+    pub document_id: String,
+}
+
+impl Session {
+    /// When the session expires unless another heartbeat arrives
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.last_heartbeat + chrono::Duration::from_std(self.ttl).unwrap_or_else(|_| chrono::Duration::zero())
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at()
+    }
+}
+
+/// Manages session lifecycles and the ephemeral documents bound to them
+#[derive(Debug)]
+pub struct SessionManager {
+    storage: Arc<StorageHierarchy>,
+
+    /// Serializes read-modify-write cycles on session records from this node
+    write_lock: Mutex<()>,
+}
+
+impl SessionManager {
+    pub fn new(storage: Arc<StorageHierarchy>) -> Self {
+        Self {
+            storage,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Open a session that expires after `ttl` without a heartbeat
+    pub async fn create_session(&self, owner: &str, ttl: Duration) -> Result<Session> {
+        if ttl < MIN_SESSION_TTL || ttl > MAX_SESSION_TTL {
+            return Err(anyhow::anyhow!(
+                "Session TTL must be between {:?} and {:?}", MIN_SESSION_TTL, MAX_SESSION_TTL
+            ));
+        }
+
+        let now = Utc::now();
+        let session = Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            owner: owner.to_string(),
+            ttl,
+            created_at: now,
+            last_heartbeat: now,
+            documents: Vec::new(),
+        };
+
+        self.save(&session).await?;
+        debug!("Opened session {} for {} with TTL {:?}", session.id, owner, ttl);
+        Ok(session)
+    }
+
+    /// Look up a live session
+    pub async fn get_session(&self, session_id: &str) -> Result<Session> {
+        let session = self.load(session_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Session not found: {}", session_id))?;
+        if session.is_expired(Utc::now()) {
+            return Err(anyhow::anyhow!("Session expired: {}", session_id));
+        }
+        Ok(session)
+    }
+
+    /// Extend a session's lifetime by another TTL
+    pub async fn heartbeat(&self, session_id: &str) -> Result<Session> {
+        let _guard = self.write_lock.lock().await;
+        let mut session = self.get_session(session_id).await?;
+        session.last_heartbeat = Utc::now();
+        self.save(&session).await?;
+        Ok(session)
+    }
+
+    /// Create or replace an ephemeral document owned by the session.
+    ///
+    /// Fails if the document is durable or owned by another live session, so
+    /// the first session to claim a document keeps it until it expires.
+    pub async fn put_ephemeral(
+        &self,
+        session_id: &str,
+        collection: &str,
+        document_id: &str,
+        document: &serde_json::Value,
+    ) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut session = self.get_session(session_id).await?;
+
+        let mut document = document.clone();
+        let fields = document
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("Ephemeral documents must be JSON objects"))?;
+
+        if let Some(existing) = self.storage.get_document(collection, document_id).await?.data {
+            match existing.get(EPHEMERAL_OWNER_FIELD).and_then(|owner| owner.as_str()) {
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Document {}/{} is durable and cannot be replaced by an ephemeral document",
+                        collection, document_id
+                    ));
+                }
+                Some(owner) if owner != session_id && self.is_live(owner).await? => {
+                    return Err(anyhow::anyhow!(
+                        "Document {}/{} is owned by another session", collection, document_id
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+
+        fields.insert(EPHEMERAL_OWNER_FIELD.to_string(), serde_json::Value::String(session.id.clone()));
+        self.storage.store_document(collection, document_id, &document).await?;
+
+        let reference = EphemeralDocumentRef {
+            collection: collection.to_string(),
+            document_id: document_id.to_string(),
+        };
+        if !session.documents.contains(&reference) {
+            session.documents.push(reference);
+            self.save(&session).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete an ephemeral document before its session ends
+    pub async fn delete_ephemeral(&self, session_id: &str, collection: &str, document_id: &str) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut session = self.get_session(session_id).await?;
+
+        let before = session.documents.len();
+        session.documents.retain(|doc| !(doc.collection == collection && doc.document_id == document_id));
+        if session.documents.len() == before {
+            return Err(anyhow::anyhow!(
+                "Document {}/{} is not owned by session {}", collection, document_id, session_id
+            ));
+        }
+
+        self.delete_if_owned(&session.id, collection, document_id).await?;
+        self.save(&session).await
+    }
+
+    /// Close a session, deleting all of its ephemeral documents
+    pub async fn end_session(&self, session_id: &str) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let session = self.load(session_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Session not found: {}", session_id))?;
+        self.remove_session(&session).await
+    }
+
+    /// All sessions that have not yet expired
+    pub async fn list_sessions(&self) -> Result<Vec<Session>> {
+        let now = Utc::now();
+        Ok(self
+            .load_all()
+            .await?
+            .into_iter()
+            .filter(|session| !session.is_expired(now))
+            .collect())
+    }
+
+    /// Remove expired sessions and their ephemeral documents.
+    ///
+    /// Returns the number of sessions expired.
+    pub async fn expire_sessions(&self) -> Result<usize> {
+        let _guard = self.write_lock.lock().await;
+        let now = Utc::now();
+        let mut expired = 0;
+
+        for session in self.load_all().await? {
+            if !session.is_expired(now) {
+                continue;
+            }
+            if let Err(e) = self.remove_session(&session).await {
+                warn!("Failed to expire session {}: {}", session.id, e);
+                continue;
+            }
+            info!(
+                "Session {} of {} expired; removed {} ephemeral document(s)",
+                session.id, session.owner, session.documents.len()
+            );
+            expired += 1;
+        }
+
+        Ok(expired)
+    }
+
+    async fn remove_session(&self, session: &Session) -> Result<()> {
+        for doc in &session.documents {
+            self.delete_if_owned(&session.id, &doc.collection, &doc.document_id).await?;
+        }
+        self.storage.delete_document(SESSION_COLLECTION, &session.id).await?;
+        Ok(())
+    }
+
+    /// Delete a document only if it still belongs to the session (it may have
+    /// been claimed by another session after this one lapsed)
+    async fn delete_if_owned(&self, session_id: &str, collection: &str, document_id: &str) -> Result<()> {
+        if let Some(document) = self.storage.get_document(collection, document_id).await?.data {
+            if document.get(EPHEMERAL_OWNER_FIELD).and_then(|owner| owner.as_str()) == Some(session_id) {
+                self.storage.delete_document(collection, document_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn is_live(&self, session_id: &str) -> Result<bool> {
+        Ok(self
+            .load(session_id)
+            .await?
+            .map(|session| !session.is_expired(Utc::now()))
+            .unwrap_or(false))
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<Session>> {
+        match self.storage.get_document(SESSION_COLLECTION, session_id).await?.data {
+            Some(document) => Ok(Some(serde_json::from_value(document)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_all(&self) -> Result<Vec<Session>> {
+        let mut sessions = Vec::new();
+        for session_id in self.storage.list_documents(SESSION_COLLECTION, None, None).await? {
+            if let Some(session) = self.load(&session_id).await? {
+                sessions.push(session);
+            }
+        }
+        Ok(sessions)
+    }
+
+    async fn save(&self, session: &Session) -> Result<()> {
+        self.storage
+            .store_document(SESSION_COLLECTION, &session.id, &serde_json::to_value(session)?)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn manager() -> (SessionManager, Arc<StorageHierarchy>) {
+        let config = aerolithdb_storage::StorageConfig {
+            data_dir: std::env::temp_dir().join(format!("aerolith-sessions-{}", uuid::Uuid::new_v4())),
+            ..Default::default()
+        };
+        let storage = Arc::new(StorageHierarchy::new(&config).await.unwrap());
+        (SessionManager::new(Arc::clone(&storage)), storage)
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_documents_removed_on_expiry() {
+        let (sessions, storage) = manager().await;
+        let session = sessions.create_session("alice", Duration::from_secs(1)).await.unwrap();
+        sessions
+            .put_ephemeral(&session.id, "presence", "alice", &serde_json::json!({"status": "online"}))
+            .await
+            .unwrap();
+
+        let stored = storage.get_document("presence", "alice").await.unwrap().data.unwrap();
+        assert_eq!(stored[EPHEMERAL_OWNER_FIELD], serde_json::json!(session.id));
+        assert_eq!(sessions.expire_sessions().await.unwrap(), 0);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(sessions.expire_sessions().await.unwrap(), 1);
+        assert!(storage.get_document("presence", "alice").await.unwrap().data.is_none());
+        assert!(sessions.heartbeat(&session.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_document_ownership() {
+        let (sessions, storage) = manager().await;
+        let first = sessions.create_session("a", Duration::from_secs(30)).await.unwrap();
+        let second = sessions.create_session("b", Duration::from_secs(30)).await.unwrap();
+
+        sessions.put_ephemeral(&first.id, "locks", "job", &serde_json::json!({})).await.unwrap();
+        assert!(sessions.put_ephemeral(&second.id, "locks", "job", &serde_json::json!({})).await.is_err());
+
+        storage.store_document("config", "durable", &serde_json::json!({"x": 1})).await.unwrap();
+        assert!(sessions.put_ephemeral(&first.id, "config", "durable", &serde_json::json!({})).await.is_err());
+
+        sessions.end_session(&first.id).await.unwrap();
+        sessions.put_ephemeral(&second.id, "locks", "job", &serde_json::json!({})).await.unwrap();
+        assert_eq!(sessions.list_sessions().await.unwrap().len(), 1);
+    }
+}
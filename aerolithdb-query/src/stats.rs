@@ -48,7 +48,10 @@ impl QueryStats {
     ///     "total_size_bytes": 2147483648,
     ///     "hot_tier_size": 536870912,
     ///     "cache_hit_rate": 0.85,
-    ///     "average_compression_ratio": 0.65
+    ///     "average_compression_ratio": 0.65,
+    ///     "total_reads": 4200000,
+    ///     "reads_per_day": 150000.0,
+    ///     "unread_documents": 250000
     ///   },
     ///   "metadata": {
     ///     "timestamp": "2024-01-15T10:30:00Z",
@@ -72,7 +75,17 @@ impl QueryStats {
                     "cold_tier_size": stats.cold_tier_size,
                     "archive_tier_size": stats.archive_tier_size,
                     "cache_hit_rate": stats.cache_hit_rate,
-                    "average_compression_ratio": stats.compression_ratio
+                    "average_compression_ratio": stats.compression_ratio,
+                    "compression": {
+                        "documents_compressed": stats.compression.documents_compressed,
+                        "documents_skipped": stats.compression.documents_skipped,
+                        "bytes_skipped": stats.compression.bytes_skipped,
+                        "skip_rate": stats.compression.skip_rate()
+                    },
+                    "checksums": stats.checksums,
+                    "total_reads": stats.total_reads,
+                    "reads_per_day": stats.reads_per_day,
+                    "unread_documents": stats.unread_documents
                 })
             }
             Err(e) => {
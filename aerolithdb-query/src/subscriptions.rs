@@ -0,0 +1,299 @@
+//! # Live Query Subscriptions
+//!
+//! A client polling a query to notice changes re-reads its whole result
+//! every time. A live query is subscribed to once instead: the subscriber
+//! receives the current matches, then a delta for every later write that
+//! changes them.
+//!
+//! - **Registration**: a subscription is a compiled filter on one
+//!   collection; filters that do not compile are refused with an
+//!   "Invalid filter" error
+//! - **Deltas**: every created, updated or deleted document of the
+//!   collection is checked against the filter and the matches the
+//!   subscriber holds, and sent as an `Added`, `Changed` or `Removed`
+//!   [`QueryDelta`]; writes to documents matching neither before nor after
+//!   are not sent
+//! - **Backpressure**: deltas are buffered per subscription; a subscriber
+//!   falling [`LIVE_QUERY_BUFFER`] deltas behind is unsubscribed, its
+//!   receiver closing, rather than holding up writes
+//! - **Change feed**: every document change, matching or not, is also
+//!   broadcast to [`SubscriptionManager::changes`] receivers, for APIs
+//!   streaming whole collections
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, warn};
+
+use crate::filter::CompiledFilter;
+
+/// Deltas a subscriber may fall behind by before it is unsubscribed
+pub const LIVE_QUERY_BUFFER: usize = 1024;
+
+/// Document changes buffered for each change feed receiver
+const CHANGE_FEED_BUFFER: usize = 1024;
+
+/// What a write did to a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A document written or deleted through the query engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentChange {
+    pub collection: String,
+    pub document_id: String,
+    pub kind: ChangeKind,
+
+    /// The document as written, `None` for deletions
+    pub document: Option<Value>,
+}
+
+/// What a change did to the matches of a live query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeltaKind {
+    /// The document matches and did not before
+    Added,
+
+    /// The document matched and still does
+    Changed,
+
+    /// The document matched and was deleted or no longer matches
+    Removed,
+}
+
+/// A change to the matches of a live query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryDelta {
+    pub subscription_id: String,
+    pub kind: DeltaKind,
+    pub change: DocumentChange,
+}
+
+struct Subscriber {
+    collection: String,
+    filter: CompiledFilter,
+
+    /// IDs of the documents the subscriber holds as matches
+    matches: HashSet<String>,
+
+    /// Until the subscriber is seeded with its initial matches, the IDs of
+    /// documents deleted or no longer matching since it registered
+    gone_while_seeding: Option<HashSet<String>>,
+    sender: mpsc::Sender<QueryDelta>,
+}
+
+impl std::fmt::Debug for Subscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscriber")
+            .field("collection", &self.collection)
+            .field("matches", &self.matches.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Subscriber {
+    /// The delta `change` makes to the subscriber's matches, if any.
+    fn delta(&mut self, change: &DocumentChange) -> Option<DeltaKind> {
+        let matches = change.document.as_ref().is_some_and(|document| self.filter.matches(document));
+        let matched = self.matches.contains(&change.document_id);
+        if matches {
+            self.matches.insert(change.document_id.clone());
+            if let Some(gone) = &mut self.gone_while_seeding {
+                gone.remove(&change.document_id);
+            }
+            return Some(if matched { DeltaKind::Changed } else { DeltaKind::Added });
+        }
+        self.matches.remove(&change.document_id);
+        match &mut self.gone_while_seeding {
+            // The initial matches may still hold the document
+            Some(gone) => {
+                gone.insert(change.document_id.clone());
+                Some(DeltaKind::Removed)
+            }
+            None => matched.then_some(DeltaKind::Removed),
+        }
+    }
+}
+
+/// Live queries registered with an engine and the changes they are fed.
+#[derive(Debug)]
+pub struct SubscriptionManager {
+    subscribers: Mutex<HashMap<String, Subscriber>>,
+    changes: broadcast::Sender<DocumentChange>,
+}
+
+impl Default for SubscriptionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscriptionManager {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_FEED_BUFFER);
+        Self { subscribers: Mutex::new(HashMap::new()), changes }
+    }
+
+    /// Register a live query of `filter` on `collection`, returning its ID
+    /// and the receiver of its deltas.
+    ///
+    /// Deltas are sent from registration on; [`SubscriptionManager::seed`]
+    /// then gives the subscription the matches it started from.
+    pub fn subscribe(&self, collection: &str, filter: &Value) -> Result<(String, mpsc::Receiver<QueryDelta>)> {
+        let filter = CompiledFilter::compile(filter)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let (sender, receiver) = mpsc::channel(LIVE_QUERY_BUFFER);
+        self.subscribers.lock().unwrap().insert(
+            id.clone(),
+            Subscriber {
+                collection: collection.to_string(),
+                filter,
+                matches: HashSet::new(),
+                gone_while_seeding: Some(HashSet::new()),
+                sender,
+            },
+        );
+        debug!("Registered live query {} on {}", id, collection);
+        Ok((id, receiver))
+    }
+
+    /// Record the matches a subscription started from, leaving out those
+    /// deleted or changed not to match since it registered.
+    pub fn seed(&self, id: &str, matches: impl IntoIterator<Item = String>) {
+        if let Some(subscriber) = self.subscribers.lock().unwrap().get_mut(id) {
+            let gone = subscriber.gone_while_seeding.take().unwrap_or_default();
+            subscriber.matches.extend(matches.into_iter().filter(|id| !gone.contains(id)));
+        }
+    }
+
+    /// End a live query, returning whether it was registered.
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        self.subscribers.lock().unwrap().remove(id).is_some()
+    }
+
+    /// Number of live queries registered.
+    pub fn len(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Receive every document change from now on.
+    pub fn changes(&self) -> broadcast::Receiver<DocumentChange> {
+        self.changes.subscribe()
+    }
+
+    /// Send a document change to the change feed and its deltas to the
+    /// live queries of its collection.
+    pub fn publish(&self, change: DocumentChange) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|id, subscriber| {
+            if subscriber.collection != change.collection {
+                return true;
+            }
+            let Some(kind) = subscriber.delta(&change) else {
+                return true;
+            };
+            let delta = QueryDelta { subscription_id: id.clone(), kind, change: change.clone() };
+            match subscriber.sender.try_send(delta) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!("Live query {} fell {} deltas behind and was unsubscribed", id, LIVE_QUERY_BUFFER);
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    debug!("Live query {} was dropped by its subscriber", id);
+                    false
+                }
+            }
+        });
+        drop(subscribers);
+
+        if self.changes.receiver_count() > 0 {
+            let _ = self.changes.send(change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn change(document_id: &str, kind: ChangeKind, document: Option<Value>) -> DocumentChange {
+        DocumentChange { collection: "orders".to_string(), document_id: document_id.to_string(), kind, document }
+    }
+
+    fn deltas(receiver: &mut mpsc::Receiver<QueryDelta>) -> Vec<(String, DeltaKind)> {
+        std::iter::from_fn(|| receiver.try_recv().ok()).map(|delta| (delta.change.document_id, delta.kind)).collect()
+    }
+
+    #[test]
+    fn test_changes_become_deltas_of_the_matches() {
+        let manager = SubscriptionManager::new();
+        let (id, mut receiver) = manager.subscribe("orders", &json!({ "status": "open" })).unwrap();
+        manager.seed(&id, ["a".to_string()]);
+
+        manager.publish(change("a", ChangeKind::Updated, Some(json!({ "status": "open", "total": 2 }))));
+        manager.publish(change("b", ChangeKind::Created, Some(json!({ "status": "open" }))));
+        manager.publish(change("c", ChangeKind::Created, Some(json!({ "status": "closed" }))));
+        manager.publish(change("a", ChangeKind::Updated, Some(json!({ "status": "closed" }))));
+        manager.publish(change("b", ChangeKind::Deleted, None));
+        manager.publish(DocumentChange { collection: "users".to_string(), ..change("d", ChangeKind::Created, Some(json!({ "status": "open" }))) });
+
+        assert_eq!(
+            deltas(&mut receiver),
+            [
+                ("a".to_string(), DeltaKind::Changed),
+                ("b".to_string(), DeltaKind::Added),
+                ("a".to_string(), DeltaKind::Removed),
+                ("b".to_string(), DeltaKind::Removed),
+            ]
+        );
+        assert!(manager.subscribe("orders", &json!({ "status": { "$bogus": 1 } })).unwrap_err().to_string().starts_with("Invalid filter"));
+    }
+
+    #[test]
+    fn test_documents_gone_while_seeding_are_not_held() {
+        let manager = SubscriptionManager::new();
+        let (id, mut receiver) = manager.subscribe("orders", &json!({})).unwrap();
+
+        // Deleted between registration and the initial query's result
+        manager.publish(change("a", ChangeKind::Deleted, None));
+        manager.seed(&id, ["a".to_string(), "b".to_string()]);
+        manager.publish(change("a", ChangeKind::Deleted, None));
+        manager.publish(change("b", ChangeKind::Deleted, None));
+
+        assert_eq!(
+            deltas(&mut receiver),
+            [("a".to_string(), DeltaKind::Removed), ("b".to_string(), DeltaKind::Removed)]
+        );
+    }
+
+    #[test]
+    fn test_dropped_and_lagging_subscribers_are_unsubscribed() {
+        let manager = SubscriptionManager::new();
+        let mut feed = manager.changes();
+        let (_, receiver) = manager.subscribe("orders", &json!({})).unwrap();
+        let (lagging, _lagging_receiver) = manager.subscribe("orders", &json!({})).unwrap();
+        drop(receiver);
+
+        manager.publish(change("0", ChangeKind::Created, Some(json!({}))));
+        assert_eq!(feed.try_recv().unwrap().document_id, "0");
+        for i in 1..=LIVE_QUERY_BUFFER {
+            manager.publish(change(&i.to_string(), ChangeKind::Created, Some(json!({}))));
+        }
+        assert!(manager.is_empty());
+        assert!(!manager.unsubscribe(&lagging));
+    }
+}
@@ -0,0 +1,389 @@
+//! # Full-Text Search
+//!
+//! A text index covers chosen string fields of a collection's documents.
+//! Their text is split into terms: lowercased runs of letters and digits,
+//! without common English stop words, reduced to a stem so that "running",
+//! "runs" and "run" are one term. The index maps each term to the documents
+//! holding it and how often they do, and records each document's length in
+//! terms.
+//!
+//! A `$text` predicate such as `{"$text": {"$search": "quick fox -lazy"}}`
+//! finds the documents holding any of its terms and none of those prefixed
+//! with `-`. Matches are ranked by BM25, which weighs terms rare in the
+//! collection and frequent in a short document most. Each match carries its
+//! score in [`SCORE_FIELD`], and queries without a sort return the best
+//! matches first.
+//!
+//! A collection has at most one text index, and `$text` needs it. The
+//! predicate must be part of the filter's top-level conjunction, alone or
+//! within `$and`, so the index can answer it. Where no index is at hand,
+//! as in aggregation `$match` stages, `$text` matches documents holding one
+//! of its terms in any string field.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+use crate::filter::{candidates, resolve};
+use crate::indexes::{DocumentStamp, Entries};
+
+/// Field holding the relevance of each document a text search returns
+pub const SCORE_FIELD: &str = "_score";
+
+/// How quickly repeating a term stops adding to a document's score
+const BM25_K1: f64 = 1.2;
+
+/// How much a document's length discounts its term frequencies
+const BM25_B: f64 = 0.75;
+
+/// Words too common to tell documents apart
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "if", "in", "into", "is", "it", "its", "no",
+    "not", "of", "on", "or", "so", "such", "that", "the", "their", "then", "there", "these", "they", "this", "to",
+    "was", "were", "will", "with",
+];
+
+/// Split text into the terms it is indexed and searched under.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| stem(&word))
+        .collect()
+}
+
+/// Reduce a lowercase English word to its stem by stripping plural, past
+/// tense and gerund suffixes. Short words and words with other characters
+/// than ASCII letters are kept as they are.
+fn stem(word: &str) -> String {
+    if word.len() <= 3 || !word.bytes().all(|byte| byte.is_ascii_lowercase()) {
+        return word.to_string();
+    }
+    let is_vowel = |byte: u8| matches!(byte, b'a' | b'e' | b'i' | b'o' | b'u');
+
+    // Plurals
+    let mut stem = word.to_string();
+    if ["sses", "ies", "ches", "shes", "xes", "zes"].iter().any(|suffix| stem.ends_with(suffix)) {
+        stem.truncate(stem.len() - 2);
+    } else if stem.ends_with('s') && !["ss", "us", "is"].iter().any(|suffix| stem.ends_with(suffix)) {
+        stem.pop();
+    }
+
+    // Past tenses and gerunds, undoubling the consonant before them or
+    // restoring the silent e of short stems
+    for suffix in ["ing", "ed"] {
+        let Some(base) = stem.strip_suffix(suffix) else {
+            continue;
+        };
+        let bytes = base.as_bytes();
+        if bytes.len() < 3 || !bytes.iter().any(|byte| is_vowel(*byte)) {
+            break;
+        }
+        let &[.., a, b, c] = bytes else { break };
+        stem = if b == c && !is_vowel(c) && !matches!(c, b'l' | b's' | b'z') {
+            base[..base.len() - 1].to_string()
+        } else if bytes.len() == 3 && !is_vowel(a) && is_vowel(b) && !is_vowel(c) && !matches!(c, b'w' | b'x' | b'y') {
+            format!("{}e", base)
+        } else {
+            base.to_string()
+        };
+        break;
+    }
+
+    if stem.len() > 3 && stem.ends_with('y') {
+        stem.pop();
+        stem.push('i');
+    }
+    if stem.len() > 4 && stem.ends_with('e') {
+        stem.pop();
+    }
+    stem
+}
+
+/// The terms of a `$text` predicate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSearch {
+    /// Terms a document must hold one of
+    pub terms: Vec<String>,
+
+    /// Terms a document must not hold
+    pub excluded: Vec<String>,
+}
+
+impl TextSearch {
+    /// Parse the operand of `$text`: `{"$search": "terms -excluded"}`.
+    pub fn parse(operand: &Value) -> Result<Self> {
+        let Value::Object(options) = operand else {
+            return Err(anyhow!("Invalid filter: $text needs an object with $search"));
+        };
+        if let Some(option) = options.keys().find(|option| option.as_str() != "$search") {
+            return Err(anyhow!("Invalid filter: unknown $text option {}", option));
+        }
+        let Some(Value::String(search)) = options.get("$search") else {
+            return Err(anyhow!("Invalid filter: $text needs a $search string"));
+        };
+
+        let mut terms = Vec::new();
+        let mut excluded = Vec::new();
+        for word in search.split_whitespace() {
+            match word.strip_prefix('-') {
+                Some(word) => excluded.extend(tokenize(word)),
+                None => terms.extend(tokenize(word)),
+            }
+        }
+        if terms.is_empty() {
+            return Err(anyhow!("Invalid filter: $search has no terms to look for"));
+        }
+        terms.sort();
+        terms.dedup();
+        excluded.sort();
+        excluded.dedup();
+        Ok(Self { terms, excluded })
+    }
+
+    /// Take the `$text` predicate out of a filter's top-level conjunction,
+    /// returning it with the rest of the filter, if any is left.
+    pub(crate) fn extract(filter: &Value) -> Result<(Option<Self>, Option<Value>)> {
+        let Value::Object(conditions) = filter else {
+            return Ok((None, Some(filter.clone())));
+        };
+        let mut search = None;
+        let rest = Self::take(conditions, &mut search)?;
+        Ok((search, (!rest.is_empty()).then_some(Value::Object(rest))))
+    }
+
+    fn take(conditions: &Map<String, Value>, search: &mut Option<Self>) -> Result<Map<String, Value>> {
+        let mut rest = Map::new();
+        for (key, condition) in conditions {
+            match (key.as_str(), condition) {
+                ("$text", operand) => {
+                    if search.is_some() {
+                        return Err(anyhow!("Invalid filter: only one $text per filter"));
+                    }
+                    *search = Some(Self::parse(operand)?);
+                }
+                ("$and", Value::Array(filters)) => {
+                    let mut kept = Vec::with_capacity(filters.len());
+                    for filter in filters {
+                        match filter {
+                            Value::Object(filter) => {
+                                let filter = Self::take(filter, search)?;
+                                if !filter.is_empty() {
+                                    kept.push(Value::Object(filter));
+                                }
+                            }
+                            other => kept.push(other.clone()),
+                        }
+                    }
+                    if !kept.is_empty() {
+                        rest.insert(key.clone(), Value::Array(kept));
+                    }
+                }
+                _ => {
+                    rest.insert(key.clone(), condition.clone());
+                }
+            }
+        }
+        Ok(rest)
+    }
+
+    /// Whether a filter searches text in its top-level conjunction.
+    pub fn in_filter(filter: &Value) -> bool {
+        let Value::Object(conditions) = filter else {
+            return false;
+        };
+        conditions.iter().any(|(key, condition)| match (key.as_str(), condition) {
+            ("$text", _) => true,
+            ("$and", Value::Array(filters)) => filters.iter().any(Self::in_filter),
+            _ => false,
+        })
+    }
+
+    /// Whether a document holds one of the terms in any string field and
+    /// none of the excluded ones, for evaluation without an index.
+    pub(crate) fn matches(&self, document: &Value) -> bool {
+        let mut held = HashSet::new();
+        collect_terms(document, &mut held);
+        self.terms.iter().any(|term| held.contains(term)) && !self.excluded.iter().any(|term| held.contains(term))
+    }
+}
+
+fn collect_terms(value: &Value, terms: &mut HashSet<String>) {
+    match value {
+        Value::String(text) => terms.extend(tokenize(text)),
+        Value::Array(values) => values.iter().for_each(|value| collect_terms(value, terms)),
+        Value::Object(fields) => fields.values().for_each(|value| collect_terms(value, terms)),
+        _ => {}
+    }
+}
+
+/// The entries of a text index.
+#[derive(Debug, Default)]
+pub(crate) struct TextEntries {
+    /// Fields the entries were built for
+    pub(crate) fields: Vec<String>,
+
+    /// Documents holding each term, with how often they do
+    postings: HashMap<String, HashMap<String, u32>>,
+
+    /// Revision indexed, length in terms and distinct terms, by document ID
+    documents: HashMap<String, (DocumentStamp, u32, Vec<String>)>,
+
+    /// Sum of the lengths of the documents
+    total_length: u64,
+}
+
+impl TextEntries {
+    pub(crate) fn new(fields: &[String]) -> Self {
+        Self { fields: fields.to_vec(), ..Self::default() }
+    }
+
+    /// Documents a search finds with their relevance, by ID.
+    pub(crate) fn search(&self, search: &TextSearch) -> BTreeMap<String, f64> {
+        let documents = self.documents.len() as f64;
+        let average_length = self.total_length as f64 / documents.max(1.0);
+
+        let mut scores: BTreeMap<String, f64> = BTreeMap::new();
+        for term in &search.terms {
+            let Some(holders) = self.postings.get(term) else {
+                continue;
+            };
+            let held_by = holders.len() as f64;
+            let idf = (1.0 + (documents - held_by + 0.5) / (held_by + 0.5)).ln();
+            for (id, frequency) in holders {
+                let length = self.documents.get(id).map_or(0, |(_, length, _)| *length) as f64;
+                let frequency = *frequency as f64;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length.max(1.0));
+                *scores.entry(id.clone()).or_default() += idf * frequency * (BM25_K1 + 1.0) / (frequency + norm);
+            }
+        }
+
+        for term in &search.excluded {
+            for id in self.postings.get(term).into_iter().flat_map(HashMap::keys) {
+                scores.remove(id);
+            }
+        }
+        // Rounded so scores survive a round trip through JSON, as in cursors
+        scores.values_mut().for_each(|score| *score = (*score * 1e4).round() / 1e4);
+        scores
+    }
+}
+
+impl Entries for TextEntries {
+    fn stamp(&self, id: &str) -> Option<DocumentStamp> {
+        self.documents.get(id).map(|(stamp, _, _)| *stamp)
+    }
+
+    fn ids(&self) -> Vec<String> {
+        self.documents.keys().cloned().collect()
+    }
+
+    fn insert(&mut self, id: &str, stamp: DocumentStamp, document: &Value) {
+        self.remove(id);
+
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        let mut length = 0;
+        for field in &self.fields {
+            let path: Vec<String> = field.split('.').map(String::from).collect();
+            let mut values = Vec::new();
+            resolve(document, &path, &mut values);
+            for text in candidates(&values).filter_map(Value::as_str) {
+                for term in tokenize(text) {
+                    *frequencies.entry(term).or_default() += 1;
+                    length += 1;
+                }
+            }
+        }
+
+        let terms: Vec<String> = frequencies.keys().cloned().collect();
+        for (term, frequency) in frequencies {
+            self.postings.entry(term).or_default().insert(id.to_string(), frequency);
+        }
+        self.total_length += u64::from(length);
+        self.documents.insert(id.to_string(), (stamp, length, terms));
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some((_, length, terms)) = self.documents.remove(id) else {
+            return;
+        };
+        self.total_length -= u64::from(length);
+        for term in terms {
+            if let Some(holders) = self.postings.get_mut(&term) {
+                holders.remove(id);
+                if holders.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    fn stamp() -> DocumentStamp {
+        (1, DateTime::<Utc>::MIN_UTC)
+    }
+
+    fn search(terms: &str) -> TextSearch {
+        TextSearch::parse(&json!({ "$search": terms })).unwrap()
+    }
+
+    #[test]
+    fn test_inflections_share_a_stem() {
+        for words in [
+            ["run", "runs", "running"],
+            ["box", "boxes", "boxed"],
+            ["pony", "ponies", "pony"],
+            ["hope", "hoping", "hoped"],
+            ["class", "classes", "class"],
+            ["index", "indexes", "indexing"],
+        ] {
+            let stems: Vec<String> = words.iter().map(|word| stem(word)).collect();
+            assert!(stems.iter().all(|stem| *stem == stems[0]), "{:?} stem to {:?}", words, stems);
+        }
+        assert_eq!(tokenize("The Quick-Brown fox, and its 2 cubs!"), ["quick", "brown", "fox", "2", "cub"]);
+    }
+
+    #[test]
+    fn test_searches_rank_with_bm25() {
+        let mut entries = TextEntries::new(&["title".to_string(), "body".to_string()]);
+        entries.insert("a", stamp(), &json!({ "title": "Fox sightings", "body": "A fox was seen; foxes are common here" }));
+        entries.insert("b", stamp(), &json!({ "title": "Garden birds", "body": "Robins and a lone fox in a very long and wordy report about the garden and its many visitors" }));
+        entries.insert("c", stamp(), &json!({ "title": "Lazy dogs", "body": "The dog slept" }));
+        entries.insert("d", stamp(), &json!({ "title": ["Fox", "Dog"], "other": "fox fox fox" }));
+
+        let found = entries.search(&search("fox"));
+        assert_eq!(found.keys().collect::<Vec<_>>(), ["a", "b", "d"]);
+        assert!(found["a"] > found["d"] && found["d"] > found["b"]);
+
+        let found = entries.search(&search("foxes -dog"));
+        assert_eq!(found.keys().collect::<Vec<_>>(), ["a", "b"]);
+
+        entries.remove("a");
+        assert_eq!(entries.search(&search("fox")).len(), 2);
+        assert!(entries.search(&search("sightings")).is_empty());
+    }
+
+    #[test]
+    fn test_text_is_taken_from_the_top_level_conjunction() {
+        let filter = json!({ "$and": [{ "$text": { "$search": "fox" } }], "status": "open" });
+        let (found, rest) = TextSearch::extract(&filter).unwrap();
+        assert_eq!(found.unwrap().terms, ["fox"]);
+        assert_eq!(rest, Some(json!({ "status": "open" })));
+        assert!(TextSearch::in_filter(&filter));
+        assert!(!TextSearch::in_filter(&json!({ "$or": [{ "$text": { "$search": "fox" } }] })));
+
+        for operand in [json!("fox"), json!({ "$search": "the" }), json!({ "$search": "fox", "$language": "fr" })] {
+            assert!(TextSearch::parse(&operand).unwrap_err().to_string().starts_with("Invalid filter"));
+        }
+        assert!(search("fox -dog").matches(&json!({ "notes": ["a fox"] })));
+        assert!(!search("fox -dog").matches(&json!({ "notes": "fox and dog" })));
+    }
+}
@@ -3,9 +3,13 @@
 //! Data structures for query requests, responses, and intermediate results.
 //! Provides type-safe interfaces for all query operations in aerolithsDB.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::execution::QueryLimits;
+use crate::text::{TextSearch, SCORE_FIELD};
+
 /// Comprehensive query request structure supporting complex document filtering and sorting.
 ///
 /// This structure represents a complete query request with all necessary parameters
@@ -15,7 +19,8 @@ use std::time::Duration;
 /// ## Query Capabilities
 /// - **Flexible Filtering**: MongoDB-style query operators for complex conditions
 /// - **Multi-Field Sorting**: Sort by multiple fields with ascending/descending order
-/// - **Efficient Pagination**: Offset/limit support for large result sets
+/// - **Efficient Pagination**: Offset/limit, or continuation cursors that are
+///   not shifted by concurrent writes
 /// - **Index Utilization**: Automatic index selection for optimal performance
 ///
 /// ## Query Filter Examples
@@ -50,10 +55,12 @@ pub struct QueryRequest {
     /// - Comparison: `{"field": {"$gt": 10, "$lt": 100}}`
     /// - Array operations: `{"field": {"$in": [1, 2, 3]}}`
     /// - Text matching: `{"field": {"$regex": "pattern"}}`
+    /// - Full-text search: `{"$text": {"$search": "terms"}}`
     /// - Boolean logic: `{"$and": [...], "$or": [...], "$not": {...}}`
     pub filter: Option<serde_json::Value>,
     
-    /// Sort specification with field names and direction (1 for asc, -1 for desc)
+    /// Sort specification with field names and direction (1 for asc, -1 for desc);
+    /// text searches without one are sorted by relevance
     pub sort: Option<serde_json::Value>,
     
     /// Maximum number of documents to return (0 means no limit)
@@ -61,6 +68,25 @@ pub struct QueryRequest {
     
     /// Number of documents to skip for pagination
     pub offset: Option<usize>,
+
+    /// Continue after the page that returned this cursor; see [`crate::cursor`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Limits on the query's execution, tightening the engine's; see
+    /// [`crate::execution`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<QueryLimits>,
+
+    /// Fields to return of each matching document, applied after sorting
+    /// and paging; see [`crate::projection`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<serde_json::Value>,
+
+    /// Query the collection as it was at this time, read from its version
+    /// history instead of its indexes; only versioned collections keep one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<DateTime<Utc>>,
 }
 
 /// Comprehensive query result containing documents and execution metadata.
@@ -91,6 +117,10 @@ pub struct QueryRequest {
 pub struct QueryResult {
     /// Array of matching documents with full content and metadata
     pub documents: Vec<serde_json::Value>,
+
+    /// IDs of the returned documents, in the same order
+    #[serde(default)]
+    pub ids: Vec<String>,
     
     /// Total number of matching documents (may exceed returned documents due to limit)
     pub total: usize,
@@ -100,6 +130,10 @@ pub struct QueryResult {
     
     /// Indicates whether the result was served from cache for performance tracking
     pub from_cache: bool,
+
+    /// Cursor of the next page, set when a limit left matches out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 impl QueryRequest {
@@ -110,6 +144,10 @@ impl QueryRequest {
             sort: None,
             limit: None,
             offset: None,
+            cursor: None,
+            limits: None,
+            projection: None,
+            as_of: None,
         }
     }
 
@@ -120,6 +158,10 @@ impl QueryRequest {
             sort: None,
             limit: None,
             offset: None,
+            cursor: None,
+            limits: None,
+            projection: None,
+            as_of: None,
         }
     }
 
@@ -135,6 +177,39 @@ impl QueryRequest {
         self.offset = Some(offset);
         self
     }
+
+    /// Continue after the page that returned `cursor`.
+    pub fn with_cursor(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    /// Bound the query's execution, within the engine's own limits.
+    pub fn with_limits(mut self, limits: QueryLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Return only the fields `projection` keeps of each document.
+    pub fn with_projection(mut self, projection: serde_json::Value) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    /// Query the collection as it was at `as_of`.
+    pub fn with_as_of(mut self, as_of: DateTime<Utc>) -> Self {
+        self.as_of = Some(as_of);
+        self
+    }
+
+    /// Order of the results: the sort given or, for text searches without
+    /// one, relevance, best first.
+    pub fn effective_sort(&self) -> Option<serde_json::Value> {
+        match (&self.sort, &self.filter) {
+            (None, Some(filter)) if TextSearch::in_filter(filter) => Some(serde_json::json!({ SCORE_FIELD: -1 })),
+            (sort, _) => sort.clone(),
+        }
+    }
 }
 
 impl Default for QueryRequest {
@@ -148,9 +223,11 @@ impl QueryResult {
     pub fn empty(execution_time: Duration) -> Self {
         Self {
             documents: vec![],
+            ids: vec![],
             total: 0,
             execution_time,
             from_cache: false,
+            next_cursor: None,
         }
     }
 
@@ -21,6 +21,7 @@ use axum::{
 
 use crate::tenant::*;
 use crate::errors::{SaaSError, TenantError};
+use crate::impersonation::ImpersonationRegistry;
 
 /// JWT token claims for SaaS authentication
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -129,6 +130,9 @@ pub enum AuthMethod {
     
     /// Service account authentication
     ServiceAccount,
+    
+    /// Platform operator acting within a tenant
+    Impersonation,
 }
 
 /// SaaS authentication manager
@@ -140,13 +144,16 @@ pub struct SaaSAuthManager {
     decoding_key: DecodingKey,
     
     /// Active sessions
-    active_sessions: Arc<RwLock<HashMap<Uuid, UserSession>>>,
+    pub(crate) active_sessions: Arc<RwLock<HashMap<Uuid, UserSession>>>,
     
     /// Tenant manager reference
-    tenant_manager: Arc<TenantManager>,
+    pub(crate) tenant_manager: Arc<TenantManager>,
     
     /// Configuration
-    config: AuthConfig,
+    pub(crate) config: AuthConfig,
+    
+    /// Impersonation grants and their audit trail
+    pub(crate) impersonation: Arc<ImpersonationRegistry>,
     
     /// Background task handles
     background_tasks: Arc<RwLock<Vec<tokio::task::JoinHandle<()>>>>,
@@ -178,6 +185,9 @@ pub struct AuthConfig {
     
     /// JWT audience
     pub jwt_audience: String,
+    
+    /// Longest impersonation grant operators can request
+    pub max_impersonation_duration: Duration,
 }
 
 impl SaaSAuthManager {
@@ -192,6 +202,7 @@ impl SaaSAuthManager {
             active_sessions: Arc::new(RwLock::new(HashMap::new())),
             tenant_manager,
             config,
+            impersonation: Arc::new(ImpersonationRegistry::default()),
             background_tasks: Arc::new(RwLock::new(Vec::new())),
         })
     }
@@ -274,6 +285,7 @@ impl SaaSAuthManager {
             .map_err(|e| SaaSError::Internal(anyhow::anyhow!("Invalid token: {}", e)))?;
         
         let claims = token_data.claims;
+        let impersonated = claims.custom.contains_key("impersonated_by");
         
         // Get session information
         let sessions = self.active_sessions.read().await;
@@ -283,9 +295,17 @@ impl SaaSAuthManager {
         
         // Verify session is still active
         if !session.is_active || session.expires_at < Utc::now() {
+            if impersonated {
+                self.impersonation.expire(Utc::now()).await;
+            }
             return Err(SaaSError::Internal(anyhow::anyhow!("Session expired")).into());
         }
         
+        // Every request made while impersonating is audited
+        if impersonated {
+            self.impersonation.record_use(claims.session_id).await?;
+        }
+        
         // Get tenant information
         let tenant = self.tenant_manager.get_tenant(claims.tenant_id).await?
             .ok_or_else(|| TenantError::NotFound { tenant_id: claims.tenant_id.to_string() })?;
@@ -299,12 +319,12 @@ impl SaaSAuthManager {
             session,
             tenant,
             is_authenticated: true,
-            auth_method: AuthMethod::JWT,
+            auth_method: if impersonated { AuthMethod::Impersonation } else { AuthMethod::JWT },
         })
     }
     
     /// Create user session
-    async fn create_user_session(
+    pub(crate) async fn create_user_session(
         &self,
         tenant_id: Uuid,
         user_id: &str,
@@ -357,8 +377,19 @@ impl SaaSAuthManager {
     
     /// Generate JWT token for session
     async fn generate_jwt_token(&self, session: &UserSession, tenant: &Tenant) -> Result<String> {
+        self.generate_token_with_claims(session, tenant, HashMap::new()).await
+    }
+    
+    /// Generate JWT token for session with custom claims, expiring no later
+    /// than the session
+    pub(crate) async fn generate_token_with_claims(
+        &self,
+        session: &UserSession,
+        tenant: &Tenant,
+        custom: HashMap<String, serde_json::Value>,
+    ) -> Result<String> {
         let now = Utc::now();
-        let exp = now + self.config.token_expiration;
+        let exp = (now + self.config.token_expiration).min(session.expires_at);
         
         let claims = SaaSClaims {
             sub: session.user_id.clone(),
@@ -371,7 +402,7 @@ impl SaaSAuthManager {
             iss: self.config.jwt_issuer.clone(),
             aud: self.config.jwt_audience.clone(),
             session_id: session.session_id,
-            custom: HashMap::new(),
+            custom,
         };
         
         let token = encode(&Header::default(), &claims, &self.encoding_key)
@@ -414,6 +445,7 @@ impl SaaSAuthManager {
     /// Start session cleanup task
     async fn start_session_cleanup(&self) -> Result<()> {
         let active_sessions = Arc::clone(&self.active_sessions);
+        let impersonation = Arc::clone(&self.impersonation);
         let cleanup_interval = self.config.cleanup_interval;
         
         let task = tokio::spawn(async move {
@@ -437,6 +469,10 @@ impl SaaSAuthManager {
                 if initial_count > final_count {
                     debug!("🧹 Cleaned up {} expired sessions", initial_count - final_count);
                 }
+                drop(sessions);
+                
+                // Expired impersonation grants are recorded in the audit trail
+                impersonation.expire(now).await;
             }
         });
         
@@ -502,6 +538,7 @@ mod tests {
             max_sessions_per_user: 5,
             jwt_issuer: "aerolithdb".to_string(),
             jwt_audience: "aerolithdb-api".to_string(),
+            max_impersonation_duration: Duration::hours(4),
         };
         
         let auth_manager = SaaSAuthManager::new(tenant_manager.clone(), config).unwrap();
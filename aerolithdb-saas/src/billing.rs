@@ -4,7 +4,10 @@
 //! integration for SaaS operations.
 
 use crate::config::{BillingConfig, BillingProvider, PricingTier};
+use crate::currency::{AppliedExchangeRate, CurrencyConverter, ExchangeRateSnapshot};
 use crate::errors::{BillingError, BillingResult};
+use crate::plan_estimate::{BillingEstimate, UsageProfile};
+use crate::tax::{round_cents, TaxAssessment};
 use crate::usage::{UsageStatistics, UsageTracker};
 use crate::tenant::{Tenant, TenantManager};
 use anyhow::Result;
@@ -46,12 +49,20 @@ pub struct Invoice {
     /// Tax amount
     pub tax_amount: f64,
     
+    /// How the tax was assessed
+    #[serde(default)]
+    pub tax_details: Option<TaxAssessment>,
+    
     /// Total amount due
     pub total_amount: f64,
     
     /// Currency
     pub currency: String,
     
+    /// Rate charges were converted at from the billing currency
+    #[serde(default)]
+    pub exchange_rate: Option<AppliedExchangeRate>,
+    
     /// Invoice status
     pub status: InvoiceStatus,
     
@@ -279,12 +290,47 @@ pub struct CostBreakdown {
     pub overage_charges: f64,
 }
 
+impl CostBreakdown {
+    /// Charges for `usage` under `pricing_tier`
+    pub fn for_usage(pricing_tier: &PricingTier, usage: &UsageProfile) -> Self {
+        let mut cost_breakdown = Self {
+            base_fee: pricing_tier.base_fee,
+            storage_cost: usage.storage_gb * pricing_tier.storage_price_per_gb,
+            api_cost: (usage.api_calls as f64 / 1000.0) * pricing_tier.api_price_per_1k_calls,
+            compute_cost: usage.compute_hours * pricing_tier.compute_price_per_cpu_hour,
+            network_cost: usage.network_gb * pricing_tier.network_price_per_gb,
+            additional_services: HashMap::new(),
+            overage_charges: 0.0,
+        };
+        
+        // Check for overages
+        let included_storage_gb = pricing_tier.included_quotas.max_storage_bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+        if usage.storage_gb > included_storage_gb {
+            let overage_gb = usage.storage_gb - included_storage_gb;
+            cost_breakdown.overage_charges += overage_gb * pricing_tier.storage_price_per_gb * 2.0; // 2x rate for overage
+        }
+        
+        cost_breakdown
+    }
+    
+    /// Total of all charges
+    pub fn total(&self) -> f64 {
+        self.base_fee +
+            self.storage_cost +
+            self.api_cost +
+            self.compute_cost +
+            self.network_cost +
+            self.overage_charges
+    }
+}
+
 /// Billing engine for automated billing calculations
 pub struct BillingEngine {
     config: BillingConfig,
     db_pool: PgPool,
     usage_tracker: Arc<UsageTracker>,
     tenant_manager: Arc<TenantManager>,
+    currency_converter: Arc<CurrencyConverter>,
     is_running: Arc<tokio::sync::RwLock<bool>>,
 }
 
@@ -296,6 +342,9 @@ impl BillingEngine {
     pub async fn new(config: &BillingConfig) -> Result<Self> {
         info!("💰 Initializing billing engine");
         
+        config.tax.validate()?;
+        let currency_converter = Arc::new(CurrencyConverter::new(config)?);
+        
         // For this implementation, we'll use the same database as usage tracking
         // In production, you might want a separate billing database
         let db_pool = PgPool::connect("postgresql://localhost/aerolithdb_billing").await?;
@@ -312,6 +361,7 @@ impl BillingEngine {
             db_pool,
             usage_tracker,
             tenant_manager,
+            currency_converter,
             is_running: Arc::new(tokio::sync::RwLock::new(false)),
         };
         
@@ -343,6 +393,9 @@ impl BillingEngine {
                 metadata JSONB NOT NULL DEFAULT '{}'
             );
             
+            ALTER TABLE invoices ADD COLUMN IF NOT EXISTS tax_details JSONB;
+            ALTER TABLE invoices ADD COLUMN IF NOT EXISTS exchange_rate JSONB;
+            
             CREATE INDEX IF NOT EXISTS idx_invoices_tenant_id ON invoices(tenant_id);
             CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices((status->>'status'));
             CREATE INDEX IF NOT EXISTS idx_invoices_due_date ON invoices(due_date);
@@ -411,6 +464,7 @@ impl BillingEngine {
             let db_pool = self.db_pool.clone();
             let usage_tracker = Arc::clone(&self.usage_tracker);
             let tenant_manager = Arc::clone(&self.tenant_manager);
+            let currency_converter = Arc::clone(&self.currency_converter);
             let is_running = Arc::clone(&self.is_running);
             
             tokio::spawn(async move {
@@ -428,6 +482,7 @@ impl BillingEngine {
                     if let Err(e) = Self::process_billing_cycle(
                         &db_pool, 
                         &config, 
+                        &currency_converter,
                         &usage_tracker, 
                         &tenant_manager
                     ).await {
@@ -458,6 +513,7 @@ impl BillingEngine {
     async fn process_billing_cycle(
         db_pool: &PgPool,
         config: &BillingConfig,
+        currency_converter: &CurrencyConverter,
         usage_tracker: &UsageTracker,
         tenant_manager: &TenantManager,
     ) -> Result<()> {
@@ -473,6 +529,7 @@ impl BillingEngine {
             if let Err(e) = Self::process_tenant_billing(
                 db_pool,
                 config,
+                currency_converter,
                 usage_tracker,
                 &tenant,
                 start_time,
@@ -490,6 +547,7 @@ impl BillingEngine {
     async fn process_tenant_billing(
         db_pool: &PgPool,
         config: &BillingConfig,
+        currency_converter: &CurrencyConverter,
         usage_tracker: &UsageTracker,
         tenant: &Tenant,
         start_time: DateTime<Utc>,
@@ -533,11 +591,11 @@ impl BillingEngine {
         
         // Generate invoice if amount due
         if calculation.amount_due > 0.0 {
-            let invoice = Self::generate_invoice(config, tenant, &calculation)?;
+            let invoice = Self::generate_invoice(config, currency_converter, tenant, &calculation).await?;
             Self::store_invoice(db_pool, &invoice).await?;
             
-            info!("💰 Generated invoice for tenant {}: ${:.2}", 
-                  tenant.tenant_id, invoice.total_amount);
+            info!("💰 Generated invoice for tenant {}: {:.2} {}", 
+                  tenant.tenant_id, invoice.total_amount, invoice.currency);
         }
         
         Ok(())
@@ -551,48 +609,20 @@ impl BillingEngine {
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
     ) -> BillingResult<BillingCalculation> {
-        let mut cost_breakdown = CostBreakdown {
-            base_fee: pricing_tier.base_fee,
-            storage_cost: 0.0,
-            api_cost: 0.0,
-            compute_cost: 0.0,
-            network_cost: 0.0,
-            additional_services: HashMap::new(),
-            overage_charges: 0.0,
-        };
-        
         // Aggregate usage across all statistics
-        let mut total_storage_gb = 0.0;
-        let mut total_api_calls = 0u64;
-        let mut total_cpu_hours = 0.0;
-        let mut total_network_gb = 0.0;
+        let mut usage = UsageProfile::default();
         
         for stats in usage_stats {
-            total_storage_gb += stats.aggregated_metrics.storage_usage.total_bytes as f64 / (1024.0 * 1024.0 * 1024.0);
-            total_api_calls += stats.aggregated_metrics.api_calls.total_calls;
-            total_cpu_hours += stats.aggregated_metrics.compute_usage.cpu_hours;
-            total_network_gb += (stats.aggregated_metrics.network_usage.bytes_in + 
+            usage.storage_gb += stats.aggregated_metrics.storage_usage.total_bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+            usage.api_calls += stats.aggregated_metrics.api_calls.total_calls;
+            usage.compute_hours += stats.aggregated_metrics.compute_usage.cpu_hours;
+            usage.network_gb += (stats.aggregated_metrics.network_usage.bytes_in + 
                                stats.aggregated_metrics.network_usage.bytes_out) as f64 / (1024.0 * 1024.0 * 1024.0);
         }
         
         // Calculate costs
-        cost_breakdown.storage_cost = total_storage_gb * pricing_tier.storage_price_per_gb;
-        cost_breakdown.api_cost = (total_api_calls as f64 / 1000.0) * pricing_tier.api_price_per_1k_calls;
-        cost_breakdown.compute_cost = total_cpu_hours * pricing_tier.compute_price_per_cpu_hour;
-        cost_breakdown.network_cost = total_network_gb * pricing_tier.network_price_per_gb;
-        
-        // Check for overages
-        if total_storage_gb > pricing_tier.included_quotas.max_storage_bytes as f64 / (1024.0 * 1024.0 * 1024.0) {
-            let overage_gb = total_storage_gb - pricing_tier.included_quotas.max_storage_bytes as f64 / (1024.0 * 1024.0 * 1024.0);
-            cost_breakdown.overage_charges += overage_gb * pricing_tier.storage_price_per_gb * 2.0; // 2x rate for overage
-        }
-        
-        let total_cost = cost_breakdown.base_fee + 
-                        cost_breakdown.storage_cost + 
-                        cost_breakdown.api_cost + 
-                        cost_breakdown.compute_cost + 
-                        cost_breakdown.network_cost + 
-                        cost_breakdown.overage_charges;
+        let cost_breakdown = CostBreakdown::for_usage(pricing_tier, &usage);
+        let total_cost = cost_breakdown.total();
         
         Ok(BillingCalculation {
             tenant_id: tenant.tenant_id,
@@ -607,9 +637,11 @@ impl BillingEngine {
         })
     }
     
-    /// Generate invoice from billing calculation
-    fn generate_invoice(
+    /// Generate invoice from billing calculation, in the tenant's currency
+    /// and with the tax of its country
+    async fn generate_invoice(
         config: &BillingConfig,
+        currency_converter: &CurrencyConverter,
         tenant: &Tenant,
         calculation: &BillingCalculation,
     ) -> BillingResult<Invoice> {
@@ -664,8 +696,17 @@ impl BillingEngine {
             });
         }
         
-        let subtotal = calculation.amount_due;
-        let tax_amount = subtotal * config.tax_rate;
+        // Convert charges from the billing currency
+        let currency = currency_converter.invoice_currency(&tenant.billing_info);
+        let exchange_rate = currency_converter.rate(&currency).await?;
+        for item in &mut line_items {
+            item.unit_price *= exchange_rate.rate;
+            item.total_price = round_cents(item.total_price * exchange_rate.rate);
+        }
+        
+        let subtotal = round_cents(calculation.amount_due * exchange_rate.rate);
+        let tax = config.tax.assess(&tenant.billing_info, config.tax_rate, subtotal);
+        let tax_amount = tax.amount;
         let total_amount = subtotal + tax_amount;
         
         Ok(Invoice {
@@ -677,8 +718,10 @@ impl BillingEngine {
             line_items,
             subtotal,
             tax_amount,
+            tax_details: Some(tax),
             total_amount,
-            currency: config.currency.clone(),
+            exchange_rate: (exchange_rate.currency != exchange_rate.base_currency).then_some(exchange_rate),
+            currency,
             status: InvoiceStatus::Draft,
             due_date: Utc::now() + Duration::days(30), // 30 days due date
             created_at: Utc::now(),
@@ -726,8 +769,8 @@ impl BillingEngine {
             INSERT INTO invoices (
                 invoice_id, tenant_id, invoice_number, period_start, period_end,
                 line_items, subtotal, tax_amount, total_amount, currency,
-                status, due_date, payment_info, metadata
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                status, due_date, payment_info, metadata, tax_details, exchange_rate
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
             "#
         )
         .bind(invoice.invoice_id)
@@ -744,6 +787,8 @@ impl BillingEngine {
         .bind(invoice.due_date)
         .bind(serde_json::to_value(&invoice.payment_info)?)
         .bind(serde_json::to_value(&invoice.metadata)?)
+        .bind(serde_json::to_value(&invoice.tax_details)?)
+        .bind(serde_json::to_value(&invoice.exchange_rate)?)
         .execute(db_pool)
         .await
         .map_err(|e| BillingError::InvoiceGenerationFailed {
@@ -786,8 +831,12 @@ impl BillingEngine {
                 line_items: serde_json::from_value(row.try_get("line_items")?)?,
                 subtotal: row.try_get::<rust_decimal::Decimal, _>("subtotal")?.to_f64().unwrap_or(0.0),
                 tax_amount: row.try_get::<rust_decimal::Decimal, _>("tax_amount")?.to_f64().unwrap_or(0.0),
+                tax_details: row.try_get::<Option<serde_json::Value>, _>("tax_details")?
+                    .map(serde_json::from_value).transpose()?,
                 total_amount: row.try_get::<rust_decimal::Decimal, _>("total_amount")?.to_f64().unwrap_or(0.0),
                 currency: row.try_get("currency")?,
+                exchange_rate: row.try_get::<Option<serde_json::Value>, _>("exchange_rate")?
+                    .map(serde_json::from_value).transpose()?,
                 status: serde_json::from_value(row.try_get("status")?)?,
                 due_date: row.try_get("due_date")?,
                 created_at: row.try_get("created_at")?,
@@ -800,6 +849,51 @@ impl BillingEngine {
         Ok(invoices)
     }
     
+    /// Simulate a tenant's usage over the last `lookback` under every
+    /// pricing tier and recommend the cheapest tier it fits
+    pub async fn estimate_plans(&self, tenant_id: Uuid, lookback: Duration) -> BillingResult<BillingEstimate> {
+        let tenant = self.tenant_manager
+            .get_tenant(tenant_id)
+            .await
+            .map_err(|e| BillingError::CalculationFailed {
+                message: format!("Failed to get tenant: {}", e),
+            })?
+            .ok_or_else(|| BillingError::CalculationFailed {
+                message: format!("Tenant not found: {}", tenant_id),
+            })?;
+        
+        let end_time = Utc::now();
+        let usage_stats = self.usage_tracker
+            .get_usage_statistics(tenant_id, end_time - lookback, end_time)
+            .await
+            .map_err(|e| BillingError::CalculationFailed {
+                message: format!("Failed to get usage statistics: {}", e),
+            })?;
+        
+        Ok(BillingEstimate {
+            tenant_id: Some(tenant_id),
+            ..self.simulate_plans(UsageProfile::from_statistics(&usage_stats, lookback), Some(tenant.subscription_tier))
+        })
+    }
+    
+    /// Simulate a usage profile under every pricing tier
+    pub fn simulate_plans(&self, profile: UsageProfile, current_tier: Option<String>) -> BillingEstimate {
+        BillingEstimate::simulate(profile, &self.config.pricing_tiers, current_tier, &self.config.currency)
+    }
+    
+    /// Current exchange rates of the billing currency into the supported
+    /// invoice currencies
+    pub async fn exchange_rates(&self) -> BillingResult<ExchangeRateSnapshot> {
+        self.currency_converter.snapshot().await
+    }
+    
+    /// Invoices of a tenant as CSV, oldest first, for accounting exports
+    pub async fn export_tenant_invoices(&self, tenant_id: Uuid) -> BillingResult<String> {
+        let mut invoices = self.get_tenant_invoices(tenant_id, Some(10_000), Some(0)).await?;
+        invoices.reverse();
+        Ok(invoices_to_csv(&invoices))
+    }
+    
     /// Get overdue invoices
     pub async fn get_overdue_invoices(&self) -> Result<Vec<Invoice>> {
         let now = Utc::now();
@@ -825,8 +919,12 @@ impl BillingEngine {
                 line_items: serde_json::from_value(row.try_get("line_items")?)?,
                 subtotal: row.try_get::<rust_decimal::Decimal, _>("subtotal")?.to_f64().unwrap_or(0.0),
                 tax_amount: row.try_get::<rust_decimal::Decimal, _>("tax_amount")?.to_f64().unwrap_or(0.0),
+                tax_details: row.try_get::<Option<serde_json::Value>, _>("tax_details")?
+                    .map(serde_json::from_value).transpose()?,
                 total_amount: row.try_get::<rust_decimal::Decimal, _>("total_amount")?.to_f64().unwrap_or(0.0),
                 currency: row.try_get("currency")?,
+                exchange_rate: row.try_get::<Option<serde_json::Value>, _>("exchange_rate")?
+                    .map(serde_json::from_value).transpose()?,
                 status: serde_json::from_value(row.try_get("status")?)?,
                 due_date: row.try_get("due_date")?,
                 created_at: row.try_get("created_at")?,
@@ -897,6 +995,47 @@ impl BillingEngine {
     }
 }
 
+/// Invoices as CSV with their currency, exchange rate and tax details
+pub fn invoices_to_csv(invoices: &[Invoice]) -> String {
+    let mut csv = String::from(
+        "invoice_number,tenant_id,period_start,period_end,currency,exchange_rate,subtotal,\
+         tax_name,tax_country,tax_rate,reverse_charge,customer_tax_id,tax_amount,total_amount,due_date\n",
+    );
+    for invoice in invoices {
+        let tax = invoice.tax_details.as_ref();
+        let fields = [
+            invoice.invoice_number.clone(),
+            invoice.tenant_id.to_string(),
+            invoice.period_start.to_rfc3339(),
+            invoice.period_end.to_rfc3339(),
+            invoice.currency.clone(),
+            invoice.exchange_rate.as_ref().map(|rate| rate.rate.to_string()).unwrap_or_else(|| "1".to_string()),
+            format!("{:.2}", invoice.subtotal),
+            tax.map(|tax| tax.tax_name.clone()).unwrap_or_default(),
+            tax.and_then(|tax| tax.country.clone()).unwrap_or_default(),
+            tax.map(|tax| tax.rate.to_string()).unwrap_or_default(),
+            tax.map(|tax| tax.reverse_charge.to_string()).unwrap_or_default(),
+            tax.and_then(|tax| tax.customer_tax_id.clone()).unwrap_or_default(),
+            format!("{:.2}", invoice.tax_amount),
+            format!("{:.2}", invoice.total_amount),
+            invoice.due_date.to_rfc3339(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// `field` quoted for CSV when it needs to be
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 /// Automated billing enforcement engine
 pub struct BillingEnforcementEngine {
     billing_engine: Arc<BillingEngine>,
@@ -1145,3 +1284,113 @@ pub struct TrialInfo {
     pub plan_id: String,
     pub payment_method_id: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CurrencyConfig, ExchangeRateSource, IsolationLevel, TaxConfig, TaxRule, TenantLimits};
+    use crate::tenant::{BillingAddress, BillingCycle, BillingInfo, TenantStatus, TenantUsage};
+
+    fn tenant(tax_id: Option<&str>) -> Tenant {
+        Tenant {
+            tenant_id: Uuid::nil(),
+            organization_name: "Acme".to_string(),
+            organization_domain: None,
+            isolation_level: IsolationLevel::Shared,
+            limits: TenantLimits::default(),
+            current_usage: TenantUsage::default(),
+            status: TenantStatus::Active,
+            subscription_tier: "starter".to_string(),
+            billing_info: BillingInfo {
+                billing_email: "billing@acme.test".to_string(),
+                billing_address: Some(BillingAddress {
+                    company: Some("Acme SARL".to_string()),
+                    line1: "1 rue de Rivoli".to_string(),
+                    line2: None,
+                    city: "Paris".to_string(),
+                    state: None,
+                    postal_code: "75001".to_string(),
+                    country: "FR".to_string(),
+                }),
+                payment_method: None,
+                billing_cycle: BillingCycle::Monthly,
+                next_billing_date: Utc::now(),
+                outstanding_balance: 0.0,
+                currency: "EUR".to_string(),
+                tax_id: tax_id.map(str::to_string),
+            },
+            metadata: HashMap::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    fn config() -> BillingConfig {
+        BillingConfig {
+            tax_rate: 0.05,
+            currencies: CurrencyConfig {
+                supported: vec!["EUR".to_string()],
+                exchange_rates: ExchangeRateSource::Fixed { rates: HashMap::from([("EUR".to_string(), 0.9)]) },
+                ..CurrencyConfig::default()
+            },
+            tax: TaxConfig {
+                home_country: Some("US".to_string()),
+                rules: vec![TaxRule {
+                    country: "FR".to_string(),
+                    name: "VAT".to_string(),
+                    rate: 0.2,
+                    reverse_charge: true,
+                }],
+            },
+            ..BillingConfig::default()
+        }
+    }
+
+    fn calculation() -> BillingCalculation {
+        let pricing_tier = PricingTier { base_fee: 100.0, ..PricingTier::default() };
+        let cost_breakdown = CostBreakdown::for_usage(&pricing_tier, &UsageProfile::default());
+        BillingCalculation {
+            tenant_id: Uuid::nil(),
+            period_start: Utc::now() - Duration::days(30),
+            period_end: Utc::now(),
+            usage_stats: vec![],
+            pricing_tier,
+            total_cost: cost_breakdown.total(),
+            amount_due: cost_breakdown.total(),
+            cost_breakdown,
+            credits_applied: 0.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invoices_are_converted_and_taxed_by_country() {
+        let config = config();
+        let converter = CurrencyConverter::new(&config).unwrap();
+
+        let invoice = BillingEngine::generate_invoice(&config, &converter, &tenant(None), &calculation()).await.unwrap();
+        assert_eq!(invoice.currency, "EUR");
+        assert_eq!(invoice.exchange_rate.as_ref().unwrap().rate, 0.9);
+        assert_eq!(invoice.line_items[0].total_price, 90.0);
+        assert_eq!((invoice.subtotal, invoice.tax_amount, invoice.total_amount), (90.0, 18.0, 108.0));
+
+        // A registered business abroad is invoiced under reverse charge
+        let invoice = BillingEngine::generate_invoice(&config, &converter, &tenant(Some("FR40303265045")), &calculation())
+            .await
+            .unwrap();
+        let tax = invoice.tax_details.as_ref().unwrap();
+        assert!(tax.reverse_charge);
+        assert_eq!((invoice.tax_amount, invoice.total_amount), (0.0, 90.0));
+
+        let csv = invoices_to_csv(&[invoice]);
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.lines().nth(1).unwrap().contains(",EUR,0.9,90.00,VAT,FR,0,true,FR40303265045,0.00,90.00,"));
+    }
+
+    #[test]
+    fn test_csv_fields_are_quoted() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
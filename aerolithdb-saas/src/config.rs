@@ -4,6 +4,7 @@
 //! usage tracking, billing, quotas, provisioning, SSO, and analytics.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
 /// Main SaaS configuration structure
@@ -29,6 +30,10 @@ pub struct SaaSConfig {
     
     /// Analytics configuration
     pub analytics: AnalyticsConfig,
+
+    /// Tenant backup schedules configuration
+    #[serde(default)]
+    pub backups: TenantBackupConfig,
 }
 
 /// Multi-tenancy configuration
@@ -140,6 +145,71 @@ pub struct BillingConfig {
     
     /// Grace period for overdue payments
     pub payment_grace_period: Duration,
+
+    /// Currencies tenants can be invoiced in and their exchange rates
+    #[serde(default)]
+    pub currencies: CurrencyConfig,
+
+    /// VAT/GST rules by customer country
+    #[serde(default)]
+    pub tax: TaxConfig,
+}
+
+/// Invoice currency configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyConfig {
+    /// Currencies besides the billing currency tenants can be invoiced in
+    pub supported: Vec<String>,
+
+    /// Where exchange rates from the billing currency come from
+    pub exchange_rates: ExchangeRateSource,
+
+    /// Time exchange rates are reused before they are fetched again
+    pub rate_cache_ttl: Duration,
+}
+
+/// Source of exchange rates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExchangeRateSource {
+    /// Fixed rates: units of each currency per unit of the billing currency
+    Fixed {
+        rates: HashMap<String, f64>,
+    },
+    /// JSON rates service; `{base}` in the URL is replaced by the billing
+    /// currency and the response carries a `rates` object
+    Http {
+        url: String,
+        timeout: Duration,
+    },
+}
+
+/// Tax configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaxConfig {
+    /// Country the seller is registered in (ISO 3166-1 alpha-2)
+    pub home_country: Option<String>,
+
+    /// Rates by customer country; customers in other countries are taxed at
+    /// the billing tax rate
+    pub rules: Vec<TaxRule>,
+}
+
+/// VAT/GST rule of one country
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxRule {
+    /// Customer country (ISO 3166-1 alpha-2)
+    pub country: String,
+
+    /// Tax name shown on invoices, e.g. "VAT" or "GST"
+    pub name: String,
+
+    /// Tax rate (as decimal, e.g., 0.2 for 20%)
+    pub rate: f64,
+
+    /// Business customers from other countries with a tax ID account for
+    /// the tax themselves
+    pub reverse_charge: bool,
 }
 
 /// Supported billing providers
@@ -211,6 +281,29 @@ pub struct QuotaConfig {
     
     /// Warning threshold (percentage of quota)
     pub warning_threshold: f64,
+
+    /// Usage alerts sent to tenants approaching their quotas
+    #[serde(default)]
+    pub alerts: UsageAlertConfig,
+}
+
+/// Usage alert configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageAlertConfig {
+    /// Send usage alerts to tenants
+    pub enabled: bool,
+
+    /// Fractions of a quota whose crossing alerts the tenant
+    pub thresholds: Vec<f64>,
+
+    /// Usage history used to forecast quota exhaustion
+    pub forecast_window: Duration,
+
+    /// HTTP endpoint of the mail service email alerts are handed to
+    pub email_relay_url: Option<String>,
+
+    /// Timeout of webhook and mail service requests
+    pub delivery_timeout: Duration,
 }
 
 /// Quota enforcement actions
@@ -454,6 +547,19 @@ pub struct AnalyticsConfig {
     pub usage_pattern_analysis: bool,
 }
 
+/// Tenant backup schedules configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantBackupConfig {
+    /// Let tenants schedule backups and restore them
+    pub enabled: bool,
+
+    /// How often due backups are looked for
+    pub check_interval: Duration,
+
+    /// Most backups a tenant can keep
+    pub max_retention_count: u32,
+}
+
 impl Default for SaaSConfig {
     fn default() -> Self {
         Self {
@@ -464,6 +570,17 @@ impl Default for SaaSConfig {
             provisioning: ProvisioningConfig::default(),
             sso: SSOConfig::default(),
             analytics: AnalyticsConfig::default(),
+            backups: TenantBackupConfig::default(),
+        }
+    }
+}
+
+impl Default for TenantBackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval: Duration::from_secs(15 * 60), // 15 minutes
+            max_retention_count: 30,
         }
     }
 }
@@ -520,6 +637,18 @@ impl Default for BillingConfig {
             pricing_tiers: vec![PricingTier::default()],
             require_payment_method: false,
             payment_grace_period: Duration::from_secs(86400 * 7), // 7 days
+            currencies: CurrencyConfig::default(),
+            tax: TaxConfig::default(),
+        }
+    }
+}
+
+impl Default for CurrencyConfig {
+    fn default() -> Self {
+        Self {
+            supported: vec![],
+            exchange_rates: ExchangeRateSource::Fixed { rates: HashMap::new() },
+            rate_cache_ttl: Duration::from_secs(3600), // 1 hour
         }
     }
 }
@@ -555,6 +684,19 @@ impl Default for QuotaConfig {
             grace_period: Duration::from_secs(3600), // 1 hour
             enable_warnings: true,
             warning_threshold: 0.8, // 80%
+            alerts: UsageAlertConfig::default(),
+        }
+    }
+}
+
+impl Default for UsageAlertConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thresholds: vec![0.8, 0.95],
+            forecast_window: Duration::from_secs(7 * 24 * 3600), // 7 days
+            email_relay_url: None,
+            delivery_timeout: Duration::from_secs(10),
         }
     }
 }
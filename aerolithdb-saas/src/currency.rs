@@ -0,0 +1,337 @@
+//! Invoice currencies and exchange rates
+//!
+//! Charges are calculated in the billing currency and invoiced in the
+//! currency of the tenant's billing information when it is the billing
+//! currency or one of [`CurrencyConfig::supported`]; other tenants are
+//! invoiced in the billing currency. Amounts are converted at rates from an
+//! [`ExchangeRateProvider`]: the fixed rates of the configuration, a JSON
+//! rates service, or any other implementation. Rates are reused for
+//! [`CurrencyConfig::rate_cache_ttl`], and the last rates fetched stand in
+//! while the provider is unavailable.
+
+use crate::config::{BillingConfig, CurrencyConfig, ExchangeRateSource};
+use crate::errors::{BillingError, BillingResult};
+use crate::tenant::BillingInfo;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Source of exchange rates
+#[async_trait]
+pub trait ExchangeRateProvider: Send + Sync {
+    /// Units of each currency one unit of `base` buys
+    async fn rates(&self, base: &str) -> BillingResult<HashMap<String, f64>>;
+}
+
+/// Fixed exchange rates from the configuration
+pub struct FixedExchangeRates {
+    base: String,
+    rates: HashMap<String, f64>,
+}
+
+impl FixedExchangeRates {
+    /// Rates of each currency per unit of `base`
+    pub fn new(base: &str, rates: &HashMap<String, f64>) -> Self {
+        Self {
+            base: base.to_ascii_uppercase(),
+            rates: rates.iter().map(|(currency, rate)| (currency.to_ascii_uppercase(), *rate)).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl ExchangeRateProvider for FixedExchangeRates {
+    async fn rates(&self, base: &str) -> BillingResult<HashMap<String, f64>> {
+        // Cross rates through the configured base
+        let base_rate = if base.eq_ignore_ascii_case(&self.base) {
+            1.0
+        } else {
+            *self.rates.get(&base.to_ascii_uppercase()).ok_or_else(|| BillingError::ExchangeRateUnavailable {
+                from: self.base.clone(),
+                to: base.to_string(),
+                message: "no fixed rate is configured".to_string(),
+            })?
+        };
+        let mut rates: HashMap<String, f64> = self.rates.iter()
+            .map(|(currency, rate)| (currency.clone(), rate / base_rate))
+            .collect();
+        rates.insert(self.base.clone(), 1.0 / base_rate);
+        Ok(rates)
+    }
+}
+
+/// Exchange rates from a JSON rates service
+pub struct HttpExchangeRates {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// Response of a rates service
+#[derive(Deserialize)]
+struct RatesResponse {
+    rates: HashMap<String, f64>,
+}
+
+impl HttpExchangeRates {
+    /// Fetch rates from `url`, in which `{base}` is replaced by the base
+    /// currency
+    pub fn new(url: &str, timeout: std::time::Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            url: url.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl ExchangeRateProvider for HttpExchangeRates {
+    async fn rates(&self, base: &str) -> BillingResult<HashMap<String, f64>> {
+        let url = self.url.replace("{base}", base);
+        let provider_error = |e: reqwest::Error| BillingError::ProviderError {
+            provider: "exchange rates".to_string(),
+            message: e.to_string(),
+        };
+        let response: RatesResponse = self.client.get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+        Ok(response.rates.into_iter().map(|(currency, rate)| (currency.to_ascii_uppercase(), rate)).collect())
+    }
+}
+
+/// Exchange rate an invoice was converted at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedExchangeRate {
+    /// Currency charges were calculated in
+    pub base_currency: String,
+
+    /// Currency of the invoice
+    pub currency: String,
+
+    /// Units of the invoice currency per unit of the base currency
+    pub rate: f64,
+
+    /// When the rate was fetched
+    pub as_of: DateTime<Utc>,
+}
+
+/// Current exchange rates of the billing currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRateSnapshot {
+    /// Billing currency
+    pub base_currency: String,
+
+    /// Units of each supported currency per unit of the billing currency
+    pub rates: HashMap<String, f64>,
+
+    /// When the rates were fetched
+    pub as_of: DateTime<Utc>,
+}
+
+/// Rates of the billing currency and when they were fetched
+type FetchedRates = (DateTime<Utc>, HashMap<String, f64>);
+
+/// Picks invoice currencies and converts charges into them
+pub struct CurrencyConverter {
+    base: String,
+    supported: Vec<String>,
+    provider: Arc<dyn ExchangeRateProvider>,
+    ttl: chrono::Duration,
+
+    /// Last rates fetched and when
+    cache: RwLock<Option<FetchedRates>>,
+}
+
+impl CurrencyConverter {
+    /// Create a converter with the rate source of `config`
+    pub fn new(config: &BillingConfig) -> anyhow::Result<Self> {
+        let provider: Arc<dyn ExchangeRateProvider> = match &config.currencies.exchange_rates {
+            ExchangeRateSource::Fixed { rates } => Arc::new(FixedExchangeRates::new(&config.currency, rates)),
+            ExchangeRateSource::Http { url, timeout } => Arc::new(HttpExchangeRates::new(url, *timeout)?),
+        };
+        Ok(Self::with_provider(&config.currency, &config.currencies, provider))
+    }
+
+    /// Create a converter from the billing currency `base` using `provider`
+    pub fn with_provider(base: &str, config: &CurrencyConfig, provider: Arc<dyn ExchangeRateProvider>) -> Self {
+        Self {
+            base: base.to_ascii_uppercase(),
+            supported: config.supported.iter().map(|currency| currency.to_ascii_uppercase()).collect(),
+            provider,
+            ttl: chrono::Duration::from_std(config.rate_cache_ttl).unwrap_or(chrono::Duration::hours(1)),
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// Billing currency
+    pub fn base_currency(&self) -> &str {
+        &self.base
+    }
+
+    /// Currency a tenant with `billing_info` is invoiced in
+    pub fn invoice_currency(&self, billing_info: &BillingInfo) -> String {
+        let currency = billing_info.currency.trim().to_ascii_uppercase();
+        if currency == self.base || self.supported.contains(&currency) {
+            currency
+        } else {
+            if !currency.is_empty() {
+                warn!("💱 Currency {} is not supported, invoicing in {}", currency, self.base);
+            }
+            self.base.clone()
+        }
+    }
+
+    /// Rate converting the billing currency into `currency`
+    pub async fn rate(&self, currency: &str) -> BillingResult<AppliedExchangeRate> {
+        let currency = currency.to_ascii_uppercase();
+        if currency == self.base {
+            return Ok(AppliedExchangeRate {
+                base_currency: self.base.clone(),
+                currency,
+                rate: 1.0,
+                as_of: Utc::now(),
+            });
+        }
+
+        let (as_of, rates) = self.rates().await?;
+        match rates.get(&currency) {
+            Some(rate) if *rate > 0.0 => Ok(AppliedExchangeRate {
+                base_currency: self.base.clone(),
+                currency,
+                rate: *rate,
+                as_of,
+            }),
+            _ => Err(BillingError::ExchangeRateUnavailable {
+                from: self.base.clone(),
+                to: currency,
+                message: "the provider has no rate".to_string(),
+            }),
+        }
+    }
+
+    /// Rates of the supported currencies
+    pub async fn snapshot(&self) -> BillingResult<ExchangeRateSnapshot> {
+        let (as_of, rates) = self.rates().await?;
+        Ok(ExchangeRateSnapshot {
+            base_currency: self.base.clone(),
+            rates: rates.into_iter().filter(|(currency, _)| self.supported.contains(currency)).collect(),
+            as_of,
+        })
+    }
+
+    /// Rates of the billing currency, fetched when the cached ones expired
+    async fn rates(&self) -> BillingResult<FetchedRates> {
+        let now = Utc::now();
+        if let Some((fetched_at, rates)) = &*self.cache.read().await {
+            if now - *fetched_at < self.ttl {
+                return Ok((*fetched_at, rates.clone()));
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        match self.provider.rates(&self.base).await {
+            Ok(rates) => {
+                debug!("💱 Fetched {} exchange rates for {}", rates.len(), self.base);
+                *cache = Some((now, rates.clone()));
+                Ok((now, rates))
+            }
+            Err(e) => match &*cache {
+                Some((fetched_at, rates)) => {
+                    warn!("💱 Using exchange rates from {}, fetching failed: {}", fetched_at, e);
+                    Ok((*fetched_at, rates.clone()))
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Fixed rates that can be made to fail, counting fetches
+    struct FlakyRates {
+        fetches: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    #[async_trait]
+    impl ExchangeRateProvider for FlakyRates {
+        async fn rates(&self, _base: &str) -> BillingResult<HashMap<String, f64>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(BillingError::ProviderError {
+                    provider: "test".to_string(),
+                    message: "unavailable".to_string(),
+                });
+            }
+            Ok(HashMap::from([("EUR".to_string(), 0.9)]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fixed_rates_cross_through_the_base() {
+        let rates = HashMap::from([("eur".to_string(), 0.9), ("GBP".to_string(), 0.75)]);
+        let provider = FixedExchangeRates::new("USD", &rates);
+
+        let usd = provider.rates("USD").await.unwrap();
+        assert_eq!((usd["EUR"], usd["USD"]), (0.9, 1.0));
+        let eur = provider.rates("eur").await.unwrap();
+        assert!((eur["GBP"] - 0.75 / 0.9).abs() < 1e-12);
+        assert!((eur["USD"] - 1.0 / 0.9).abs() < 1e-12);
+        assert!(provider.rates("JPY").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rates_are_cached_and_survive_provider_outages() {
+        let provider = Arc::new(FlakyRates { fetches: AtomicUsize::new(0), failing: AtomicBool::new(true) });
+        let config = CurrencyConfig {
+            supported: vec!["eur".to_string()],
+            rate_cache_ttl: std::time::Duration::ZERO,
+            ..CurrencyConfig::default()
+        };
+        let converter = CurrencyConverter::with_provider("usd", &config, provider.clone());
+
+        // The billing currency needs no rate
+        assert_eq!(converter.rate("USD").await.unwrap().rate, 1.0);
+        assert!(converter.rate("EUR").await.is_err());
+
+        provider.failing.store(false, Ordering::SeqCst);
+        assert_eq!(converter.rate("eur").await.unwrap().rate, 0.9);
+        provider.failing.store(true, Ordering::SeqCst);
+        assert_eq!(converter.rate("EUR").await.unwrap().rate, 0.9);
+        assert_eq!(provider.fetches.load(Ordering::SeqCst), 3);
+        assert!(converter.rate("GBP").await.is_err());
+    }
+
+    #[test]
+    fn test_unsupported_currencies_are_invoiced_in_the_base() {
+        let config = CurrencyConfig { supported: vec!["EUR".to_string()], ..CurrencyConfig::default() };
+        let provider = Arc::new(FixedExchangeRates::new("USD", &HashMap::new()));
+        let converter = CurrencyConverter::with_provider("USD", &config, provider);
+
+        let mut billing_info = BillingInfo {
+            billing_email: "billing@acme.test".to_string(),
+            billing_address: None,
+            payment_method: None,
+            billing_cycle: crate::tenant::BillingCycle::Monthly,
+            next_billing_date: Utc::now(),
+            outstanding_balance: 0.0,
+            currency: "eur".to_string(),
+            tax_id: None,
+        };
+        assert_eq!(converter.invoice_currency(&billing_info), "EUR");
+        billing_info.currency = "jpy".to_string();
+        assert_eq!(converter.invoice_currency(&billing_info), "USD");
+    }
+}
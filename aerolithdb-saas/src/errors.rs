@@ -33,6 +33,14 @@ pub enum SaaSError {
     #[error("Analytics error: {0}")]
     Analytics(#[from] AnalyticsError),
     
+    /// Tenant impersonation errors
+    #[error("Impersonation error: {0}")]
+    Impersonation(#[from] ImpersonationError),
+    
+    /// Tenant backup errors
+    #[error("Tenant backup error: {0}")]
+    TenantBackup(#[from] TenantBackupError),
+    
     /// Database errors
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
@@ -176,6 +184,14 @@ pub enum BillingError {
     /// Payment method required
     #[error("Payment method required for tenant: {tenant_id}")]
     PaymentMethodRequired { tenant_id: String },
+    
+    /// No exchange rate between two currencies
+    #[error("Exchange rate unavailable from {from} to {to}: {message}")]
+    ExchangeRateUnavailable { from: String, to: String, message: String },
+    
+    /// Invalid tax configuration
+    #[error("Invalid tax configuration: {message}")]
+    InvalidTaxConfig { message: String },
 }
 
 /// Quota management errors
@@ -201,6 +217,10 @@ pub enum QuotaError {
     /// Quota check failed
     #[error("Quota check failed: {message}")]
     CheckFailed { message: String },
+
+    /// Invalid usage alert channel
+    #[error("Invalid alert channel: {message}")]
+    InvalidAlertChannel { message: String },
     
     /// Limit exceeded
     #[error("Limit exceeded for tenant {tenant_id}: {resource} = {current}/{limit}")]
@@ -292,6 +312,54 @@ pub enum AnalyticsError {
     MLModelError { message: String },
 }
 
+/// Tenant impersonation errors
+#[derive(Error, Debug)]
+pub enum ImpersonationError {
+    /// Caller is not a platform operator
+    #[error("Impersonation not permitted for {operator_id}")]
+    NotPermitted { operator_id: String },
+    
+    /// Tenant has not consented to support impersonation
+    #[error("Tenant {tenant_id} has not consented to impersonation")]
+    ConsentRequired { tenant_id: String },
+    
+    /// Missing reason or out-of-range duration
+    #[error("Invalid impersonation request: {message}")]
+    InvalidRequest { message: String },
+    
+    /// Grant not found or no longer active
+    #[error("Impersonation grant not found: {grant_id}")]
+    GrantNotFound { grant_id: String },
+}
+
+/// Tenant backup and restore errors
+#[derive(Error, Debug)]
+pub enum TenantBackupError {
+    /// Tenant backups are disabled on this deployment
+    #[error("Tenant backups are disabled")]
+    Disabled,
+    
+    /// Invalid backup schedule
+    #[error("Invalid backup schedule: {message}")]
+    InvalidSchedule { message: String },
+    
+    /// Invalid restore namespace
+    #[error("Invalid restore namespace: {message}")]
+    InvalidNamespace { message: String },
+    
+    /// Backup not found among the tenant's backups
+    #[error("Backup not found: {backup_id}")]
+    BackupNotFound { backup_id: String },
+    
+    /// Restore namespace already holds documents
+    #[error("Restore namespace is not empty: {namespace}")]
+    NamespaceNotEmpty { namespace: String },
+    
+    /// Backup or restore failed in storage
+    #[error("Backup operation failed: {message}")]
+    Failed { message: String },
+}
+
 /// Result type alias for SaaS operations
 pub type SaaSResult<T> = Result<T, SaaSError>;
 
@@ -316,6 +384,12 @@ pub type SSOResult<T> = Result<T, SSOError>;
 /// Result type alias for analytics operations
 pub type AnalyticsResult<T> = Result<T, AnalyticsError>;
 
+/// Result type alias for impersonation operations
+pub type ImpersonationResult<T> = Result<T, ImpersonationError>;
+
+/// Result type alias for tenant backup operations
+pub type TenantBackupResult<T> = Result<T, TenantBackupError>;
+
 // Additional From implementations for error conversions
 impl From<serde_json::Error> for TenantError {
     fn from(err: serde_json::Error) -> Self {
@@ -0,0 +1,435 @@
+//! Tenant impersonation for support operators
+//!
+//! Lets platform operators act within a tenant to troubleshoot it. An
+//! operator asks for a time-boxed grant stating a reason, and receives a
+//! token for a session inside the tenant that expires with the grant.
+//! Tenants must opt in by setting the `support_impersonation_consent` flag
+//! in their metadata; write access needs the separate
+//! `support_impersonation_write_consent` flag.
+//!
+//! Every grant, refusal, use, revocation and expiry is recorded in an audit
+//! trail that tenants and operators can review.
+
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc, Duration};
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::auth::{has_role, AuthContext, SaaSAuthManager};
+use crate::errors::{ImpersonationError, TenantError};
+use crate::tenant::{Tenant, TenantStatus};
+
+/// Role required to impersonate tenants
+pub const PLATFORM_OPERATOR_ROLE: &str = "platform_operator";
+
+/// Tenant metadata flag consenting to read-only impersonation
+pub const IMPERSONATION_CONSENT_FLAG: &str = "support_impersonation_consent";
+
+/// Tenant metadata flag additionally consenting to write access
+pub const IMPERSONATION_WRITE_CONSENT_FLAG: &str = "support_impersonation_write_consent";
+
+/// Role of impersonation sessions within the tenant
+pub const IMPERSONATION_ROLE: &str = "support_impersonation";
+
+/// Request to impersonate a tenant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationRequest {
+    /// Tenant to act within
+    pub tenant_id: Uuid,
+
+    /// Why access is needed
+    pub reason: String,
+
+    /// Support ticket reference
+    #[serde(default)]
+    pub ticket: Option<String>,
+
+    /// Requested duration in seconds
+    pub duration_secs: i64,
+
+    /// Request write access instead of read-only access
+    #[serde(default)]
+    pub write_access: bool,
+}
+
+/// State of an impersonation grant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImpersonationStatus {
+    /// Grant can be used
+    Active,
+
+    /// Ended early by an operator
+    Revoked {
+        revoked_by: String,
+        revoked_at: DateTime<Utc>,
+    },
+
+    /// Ran out of time
+    Expired,
+}
+
+/// Time-boxed permission for an operator to act within a tenant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationGrant {
+    /// Grant ID, also the ID of the impersonation session
+    pub grant_id: Uuid,
+
+    /// Operator acting within the tenant
+    pub operator_id: String,
+
+    /// Tenant of the operator
+    pub operator_tenant_id: Uuid,
+
+    /// Impersonated tenant
+    pub tenant_id: Uuid,
+
+    /// Why access was needed
+    pub reason: String,
+
+    /// Support ticket reference
+    pub ticket: Option<String>,
+
+    /// Whether the session may write
+    pub write_access: bool,
+
+    /// Grant created at
+    pub created_at: DateTime<Utc>,
+
+    /// Grant expires at
+    pub expires_at: DateTime<Utc>,
+
+    /// Grant status
+    pub status: ImpersonationStatus,
+}
+
+/// Impersonation audit actions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImpersonationAction {
+    /// Grant issued
+    Granted,
+
+    /// Request refused
+    Refused,
+
+    /// Token used to authenticate a request
+    Used,
+
+    /// Grant revoked
+    Revoked,
+
+    /// Grant expired
+    Expired,
+}
+
+/// Entry of the impersonation audit trail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationAuditEvent {
+    /// Event timestamp
+    pub timestamp: DateTime<Utc>,
+
+    /// Grant the event concerns, if one was issued
+    pub grant_id: Option<Uuid>,
+
+    /// Operator involved
+    pub operator_id: String,
+
+    /// Tenant involved
+    pub tenant_id: Uuid,
+
+    /// What happened
+    pub action: ImpersonationAction,
+
+    /// Reason given or refusal cause
+    pub detail: String,
+}
+
+/// Impersonation grants and their audit trail
+#[derive(Debug, Default)]
+pub(crate) struct ImpersonationRegistry {
+    /// Grants by ID
+    grants: RwLock<HashMap<Uuid, ImpersonationGrant>>,
+
+    /// Audit trail, oldest first
+    audit_log: RwLock<Vec<ImpersonationAuditEvent>>,
+}
+
+impl ImpersonationRegistry {
+    /// Append an event to the audit trail
+    async fn record(
+        &self,
+        grant_id: Option<Uuid>,
+        operator_id: &str,
+        tenant_id: Uuid,
+        action: ImpersonationAction,
+        detail: String,
+    ) {
+        warn!(
+            "Audit: impersonation {:?} of tenant {} by {} ({})",
+            action, tenant_id, operator_id, detail
+        );
+        self.audit_log.write().await.push(ImpersonationAuditEvent {
+            timestamp: Utc::now(),
+            grant_id,
+            operator_id: operator_id.to_string(),
+            tenant_id,
+            action,
+            detail,
+        });
+    }
+
+    /// Record a request authenticated with an impersonation token, failing
+    /// if its grant is no longer active
+    pub(crate) async fn record_use(&self, grant_id: Uuid) -> Result<()> {
+        let grant = self.grants.read().await.get(&grant_id).cloned()
+            .ok_or_else(|| ImpersonationError::GrantNotFound { grant_id: grant_id.to_string() })?;
+
+        if grant.status != ImpersonationStatus::Active || grant.expires_at <= Utc::now() {
+            self.expire(Utc::now()).await;
+            return Err(ImpersonationError::GrantNotFound { grant_id: grant_id.to_string() }.into());
+        }
+
+        self.record(Some(grant_id), &grant.operator_id, grant.tenant_id, ImpersonationAction::Used, grant.reason).await;
+        Ok(())
+    }
+
+    /// Mark active grants past their expiry as expired, returning how many
+    pub(crate) async fn expire(&self, now: DateTime<Utc>) -> usize {
+        let expired: Vec<ImpersonationGrant> = {
+            let mut grants = self.grants.write().await;
+            grants.values_mut()
+                .filter(|grant| grant.status == ImpersonationStatus::Active && grant.expires_at <= now)
+                .map(|grant| {
+                    grant.status = ImpersonationStatus::Expired;
+                    grant.clone()
+                })
+                .collect()
+        };
+
+        for grant in &expired {
+            self.record(
+                Some(grant.grant_id),
+                &grant.operator_id,
+                grant.tenant_id,
+                ImpersonationAction::Expired,
+                format!("expired at {}", grant.expires_at.to_rfc3339()),
+            ).await;
+        }
+        expired.len()
+    }
+}
+
+impl SaaSAuthManager {
+    /// Issue a time-boxed token for a platform operator to act within a
+    /// tenant that consented to support impersonation
+    pub async fn start_impersonation(
+        &self,
+        operator: &AuthContext,
+        request: ImpersonationRequest,
+    ) -> Result<(String, ImpersonationGrant)> {
+        let operator_id = operator.session.user_id.clone();
+        info!("🎭 Impersonation of tenant {} requested by {}", request.tenant_id, operator_id);
+
+        let tenant = self.tenant_manager.get_tenant(request.tenant_id).await?
+            .ok_or_else(|| TenantError::NotFound { tenant_id: request.tenant_id.to_string() })?;
+
+        if let Err(refusal) = self.check_impersonation(operator, &tenant, &request) {
+            self.impersonation.record(
+                None,
+                &operator_id,
+                request.tenant_id,
+                ImpersonationAction::Refused,
+                refusal.to_string(),
+            ).await;
+            return Err(refusal.into());
+        }
+
+        let permissions = if request.write_access {
+            vec!["read".to_string(), "write".to_string()]
+        } else {
+            vec!["read".to_string()]
+        };
+        let mut session = self.create_user_session(
+            request.tenant_id,
+            &format!("{}@{}", operator_id, operator.session.tenant_id),
+            vec![IMPERSONATION_ROLE.to_string()],
+            permissions,
+            operator.session.ip_address.clone(),
+            operator.session.user_agent.clone(),
+        ).await?;
+
+        // The session lives exactly as long as the grant
+        session.expires_at = session.created_at + Duration::seconds(request.duration_secs);
+        if let Some(stored) = self.active_sessions.write().await.get_mut(&session.session_id) {
+            stored.expires_at = session.expires_at;
+        }
+
+        let custom = HashMap::from([
+            ("impersonated_by".to_string(), serde_json::json!(operator_id)),
+            ("impersonation_grant".to_string(), serde_json::json!(session.session_id)),
+        ]);
+        let token = self.generate_token_with_claims(&session, &tenant, custom).await?;
+
+        let grant = ImpersonationGrant {
+            grant_id: session.session_id,
+            operator_id: operator_id.clone(),
+            operator_tenant_id: operator.session.tenant_id,
+            tenant_id: request.tenant_id,
+            reason: request.reason.clone(),
+            ticket: request.ticket.clone(),
+            write_access: request.write_access,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            status: ImpersonationStatus::Active,
+        };
+        self.impersonation.grants.write().await.insert(grant.grant_id, grant.clone());
+        self.impersonation.record(
+            Some(grant.grant_id),
+            &operator_id,
+            request.tenant_id,
+            ImpersonationAction::Granted,
+            match &request.ticket {
+                Some(ticket) => format!("{} (ticket {})", request.reason, ticket),
+                None => request.reason.clone(),
+            },
+        ).await;
+
+        info!("✅ Impersonation grant {} issued until {}", grant.grant_id, grant.expires_at);
+        Ok((token, grant))
+    }
+
+    /// Why an operator may not impersonate a tenant, if they may not
+    fn check_impersonation(
+        &self,
+        operator: &AuthContext,
+        tenant: &Tenant,
+        request: &ImpersonationRequest,
+    ) -> Result<(), ImpersonationError> {
+        // Impersonation sessions cannot start further impersonations
+        if !has_role(operator, PLATFORM_OPERATOR_ROLE) || operator.claims.custom.contains_key("impersonated_by") {
+            return Err(ImpersonationError::NotPermitted { operator_id: operator.session.user_id.clone() });
+        }
+        if request.reason.trim().is_empty() {
+            return Err(ImpersonationError::InvalidRequest { message: "a reason is required".to_string() });
+        }
+        let max_secs = self.config.max_impersonation_duration.num_seconds();
+        if request.duration_secs <= 0 || request.duration_secs > max_secs {
+            return Err(ImpersonationError::InvalidRequest {
+                message: format!("duration must be between 1 and {} seconds", max_secs),
+            });
+        }
+        if !matches!(tenant.status, TenantStatus::Active) {
+            return Err(ImpersonationError::InvalidRequest {
+                message: format!("tenant {} is not active", tenant.tenant_id),
+            });
+        }
+
+        let consented = |flag: &str| tenant.metadata.get(flag).and_then(|value| value.as_bool()).unwrap_or(false);
+        if !consented(IMPERSONATION_CONSENT_FLAG)
+            || (request.write_access && !consented(IMPERSONATION_WRITE_CONSENT_FLAG))
+        {
+            return Err(ImpersonationError::ConsentRequired { tenant_id: tenant.tenant_id.to_string() });
+        }
+        Ok(())
+    }
+
+    /// End an impersonation grant before it expires, invalidating its token.
+    /// Platform operators and admins of the impersonated tenant can revoke
+    /// grants.
+    pub async fn revoke_impersonation(&self, operator: &AuthContext, grant_id: Uuid) -> Result<ImpersonationGrant> {
+        let grant = {
+            let mut grants = self.impersonation.grants.write().await;
+            let grant = grants.get_mut(&grant_id)
+                .filter(|grant| grant.status == ImpersonationStatus::Active)
+                .ok_or_else(|| ImpersonationError::GrantNotFound { grant_id: grant_id.to_string() })?;
+
+            // Operators and admins of the impersonated tenant can end a grant
+            let operator_id = &operator.session.user_id;
+            let tenant_admin = has_role(operator, "admin") && operator.session.tenant_id == grant.tenant_id;
+            if !has_role(operator, PLATFORM_OPERATOR_ROLE) && !tenant_admin {
+                return Err(ImpersonationError::NotPermitted { operator_id: operator_id.clone() }.into());
+            }
+            grant.status = ImpersonationStatus::Revoked {
+                revoked_by: operator_id.clone(),
+                revoked_at: Utc::now(),
+            };
+            grant.clone()
+        };
+
+        self.revoke_session(grant_id).await?;
+        self.impersonation.record(
+            Some(grant_id),
+            &operator.session.user_id,
+            grant.tenant_id,
+            ImpersonationAction::Revoked,
+            format!("grant of {}", grant.operator_id),
+        ).await;
+        Ok(grant)
+    }
+
+    /// Impersonation grants, optionally of one tenant, newest first
+    pub async fn list_impersonations(&self, tenant_id: Option<Uuid>) -> Vec<ImpersonationGrant> {
+        self.impersonation.expire(Utc::now()).await;
+
+        let mut grants: Vec<ImpersonationGrant> = self.impersonation.grants.read().await
+            .values()
+            .filter(|grant| tenant_id.is_none_or(|tenant_id| grant.tenant_id == tenant_id))
+            .cloned()
+            .collect();
+        grants.sort_by_key(|grant| std::cmp::Reverse(grant.created_at));
+        grants
+    }
+
+    /// Impersonation audit trail, optionally of one tenant, oldest first
+    pub async fn impersonation_audit_log(&self, tenant_id: Option<Uuid>) -> Vec<ImpersonationAuditEvent> {
+        self.impersonation.expire(Utc::now()).await;
+
+        self.impersonation.audit_log.read().await
+            .iter()
+            .filter(|event| tenant_id.is_none_or(|tenant_id| event.tenant_id == tenant_id))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grant(expires_at: DateTime<Utc>) -> ImpersonationGrant {
+        ImpersonationGrant {
+            grant_id: Uuid::new_v4(),
+            operator_id: "support-1".to_string(),
+            operator_tenant_id: Uuid::new_v4(),
+            tenant_id: Uuid::new_v4(),
+            reason: "investigate failing queries".to_string(),
+            ticket: Some("SUP-42".to_string()),
+            write_access: false,
+            created_at: Utc::now(),
+            expires_at,
+            status: ImpersonationStatus::Active,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grants_expire_and_are_audited() {
+        let registry = ImpersonationRegistry::default();
+        let active = grant(Utc::now() + Duration::minutes(30));
+        let lapsed = grant(Utc::now() - Duration::seconds(1));
+        registry.grants.write().await.insert(active.grant_id, active.clone());
+        registry.grants.write().await.insert(lapsed.grant_id, lapsed.clone());
+
+        registry.record_use(active.grant_id).await.unwrap();
+        assert!(registry.record_use(lapsed.grant_id).await.is_err());
+        assert_eq!(registry.expire(Utc::now()).await, 0);
+
+        let grants = registry.grants.read().await;
+        assert_eq!(grants[&active.grant_id].status, ImpersonationStatus::Active);
+        assert_eq!(grants[&lapsed.grant_id].status, ImpersonationStatus::Expired);
+
+        let actions: Vec<_> = registry.audit_log.read().await.iter().map(|event| event.action.clone()).collect();
+        assert_eq!(actions, [ImpersonationAction::Used, ImpersonationAction::Expired]);
+    }
+}
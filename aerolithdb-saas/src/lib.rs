@@ -43,10 +43,16 @@ pub mod analytics;
 pub mod config;
 pub mod errors;
 pub mod auth;
+pub mod impersonation;
 pub mod tenant_isolation;
 pub mod manager;
 pub mod subscription;
 pub mod production_metering;
+pub mod usage_alerts;
+pub mod plan_estimate;
+pub mod currency;
+pub mod tax;
+pub mod tenant_backups;
 
 // Re-export main types for convenience
 pub use tenant::*;
@@ -60,10 +66,16 @@ pub use analytics::*;
 pub use config::*;
 pub use errors::*;
 pub use auth::*;
+pub use impersonation::*;
 pub use tenant_isolation::*;
 pub use manager::*;
 pub use subscription::*;
 pub use production_metering::*;
+pub use usage_alerts::*;
+pub use plan_estimate::*;
+pub use currency::*;
+pub use tax::*;
+pub use tenant_backups::*;
 
 use anyhow::Result;
 use std::sync::Arc;
@@ -97,6 +109,9 @@ pub struct SaaSManager {
     
     /// Analytics and insights
     analytics_engine: Arc<AnalyticsEngine>,
+    
+    /// Tenant backup schedules, once a backup store is attached
+    tenant_backups: Option<Arc<TenantBackupManager>>,
 }
 
 impl SaaSManager {
@@ -141,9 +156,16 @@ impl SaaSManager {
             provisioning_engine,
             sso_manager,
             analytics_engine,
+            tenant_backups: None,
         })
     }
     
+    /// Let tenants schedule backups of their collections into `store`
+    pub fn with_backup_store(mut self, store: Arc<dyn TenantBackupStore>) -> Self {
+        self.tenant_backups = Some(Arc::new(TenantBackupManager::new(&self.config.backups, store)));
+        self
+    }
+    
     /// Start all SaaS background services
     pub async fn start(&self) -> Result<()> {
         info!("🔄 Starting SaaS background services");
@@ -160,6 +182,11 @@ impl SaaSManager {
         // Start analytics processing
         self.analytics_engine.start_processing().await?;
         
+        // Start scheduled tenant backups
+        if let Some(tenant_backups) = &self.tenant_backups {
+            tenant_backups.start_scheduler().await?;
+        }
+        
         info!("✅ All SaaS services started successfully");
         Ok(())
     }
@@ -172,6 +199,9 @@ impl SaaSManager {
         self.billing_engine.stop_billing_cycle().await?;
         self.quota_manager.stop_monitoring().await?;
         self.analytics_engine.stop_processing().await?;
+        if let Some(tenant_backups) = &self.tenant_backups {
+            tenant_backups.stop_scheduler().await?;
+        }
         
         info!("✅ All SaaS services stopped successfully");
         Ok(())
@@ -210,4 +240,9 @@ impl SaaSManager {
     pub fn analytics_engine(&self) -> &Arc<AnalyticsEngine> {
         &self.analytics_engine
     }
+    
+    /// Get reference to tenant backups, if a backup store is attached
+    pub fn tenant_backups(&self) -> Option<&Arc<TenantBackupManager>> {
+        self.tenant_backups.as_ref()
+    }
 }
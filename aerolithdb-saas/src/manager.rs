@@ -18,7 +18,7 @@ use crate::config::QuotaEnforcementAction;
 use crate::provisioning::{AdvancedProvisioningEngine as ProvisioningManager};
 use crate::sso::{SSOManager, SSOAuthResponse};
 use crate::analytics::{AnalyticsEngine as AnalyticsManager, TenantUsageSummary};
-use crate::config::{SaaSConfig, TenantLimits, TenantConfig, UsageConfig, BillingConfig, QuotaConfig, ProvisioningConfig, SSOConfig, AnalyticsConfig, IsolationLevel};
+use crate::config::{SaaSConfig, TenantLimits, TenantConfig, UsageConfig, BillingConfig, QuotaConfig, ProvisioningConfig, SSOConfig, AnalyticsConfig, IsolationLevel, TenantBackupConfig};
 use crate::auth::{SaaSAuthManager};
 use crate::tenant_isolation::{TenantIsolationManager, IsolationMode};
 use crate::errors::{SaaSError, SaaSResult};
@@ -139,6 +139,7 @@ impl SaaSManager {
             max_sessions_per_user: 5,
             jwt_issuer: "aerolithdb".to_string(),
             jwt_audience: "aerolithdb-api".to_string(),
+            max_impersonation_duration: chrono::Duration::hours(4),
         };
         let auth_manager = Arc::new(SaaSAuthManager::new(Arc::clone(&tenant_manager), auth_config)?);
         
@@ -567,6 +568,7 @@ impl SaaSManagerFactory {
                 export_enabled: true,
                 export_formats: vec!["json".to_string(), "csv".to_string()],
             },
+            backups: TenantBackupConfig::default(),
         }
     }
 }
@@ -0,0 +1,218 @@
+//! Billing plan simulation and cost estimation
+//!
+//! Prices a monthly usage profile under every configured pricing tier, with
+//! the same rates and overage charges the billing cycle applies, and
+//! recommends the cheapest tier whose limits the usage fits. Profiles are
+//! either supplied by the caller to explore "what if" scenarios or derived
+//! from a tenant's recent usage statistics, scaled to a 30-day month.
+
+use crate::billing::CostBreakdown;
+use crate::config::PricingTier;
+use crate::usage::UsageStatistics;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Hours in the 30-day month estimates are made for
+const HOURS_PER_MONTH: f64 = 30.0 * 24.0;
+
+/// Monthly usage of a tenant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageProfile {
+    /// Stored data in GB
+    pub storage_gb: f64,
+
+    /// API calls per month
+    pub api_calls: u64,
+
+    /// CPU hours per month
+    pub compute_hours: f64,
+
+    /// Network transfer in GB per month
+    pub network_gb: f64,
+}
+
+impl UsageProfile {
+    /// Profile of the usage in `stats`, which cover `period`, scaled to a
+    /// month; storage is the largest stored during the period
+    pub fn from_statistics(stats: &[UsageStatistics], period: Duration) -> Self {
+        let hours = (period.num_seconds() as f64 / 3600.0).max(1.0);
+        let scale = HOURS_PER_MONTH / hours;
+
+        let mut profile = Self::default();
+        let mut api_calls = 0u64;
+        for stat in stats {
+            let metrics = &stat.aggregated_metrics;
+            profile.storage_gb = profile.storage_gb.max(metrics.storage_usage.total_bytes as f64 / BYTES_PER_GB);
+            api_calls += metrics.api_calls.total_calls;
+            profile.compute_hours += metrics.compute_usage.cpu_hours;
+            profile.network_gb += (metrics.network_usage.bytes_in + metrics.network_usage.bytes_out) as f64 / BYTES_PER_GB;
+        }
+
+        profile.api_calls = (api_calls as f64 * scale).round() as u64;
+        profile.compute_hours *= scale;
+        profile.network_gb *= scale;
+        profile
+    }
+
+    /// Average API calls per hour
+    pub fn api_calls_per_hour(&self) -> f64 {
+        self.api_calls as f64 / HOURS_PER_MONTH
+    }
+}
+
+/// Simulated monthly charges of one pricing tier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanEstimate {
+    /// Pricing tier name
+    pub tier: String,
+
+    /// Pricing tier description
+    pub description: String,
+
+    /// Charges by kind
+    pub cost_breakdown: CostBreakdown,
+
+    /// Total monthly charges
+    pub monthly_cost: f64,
+
+    /// Whether the usage stays within the tier's limits
+    pub fits: bool,
+
+    /// Limits of the tier the usage exceeds
+    pub limits_exceeded: Vec<String>,
+}
+
+/// Charges of a usage profile across all pricing tiers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillingEstimate {
+    /// Tenant whose usage was simulated, if any
+    pub tenant_id: Option<Uuid>,
+
+    /// Tier the tenant is subscribed to, if known
+    pub current_tier: Option<String>,
+
+    /// Usage the charges were simulated for
+    pub profile: UsageProfile,
+
+    /// Currency of all amounts
+    pub currency: String,
+
+    /// Estimate of each tier, cheapest first
+    pub estimates: Vec<PlanEstimate>,
+
+    /// Cheapest tier the usage fits, if any
+    pub recommended_tier: Option<String>,
+
+    /// Monthly savings of the recommended tier over the current tier
+    pub monthly_savings: Option<f64>,
+
+    /// When the estimate was made
+    pub generated_at: DateTime<Utc>,
+}
+
+impl BillingEstimate {
+    /// Simulate `profile` under every tier in `tiers`
+    pub fn simulate(
+        profile: UsageProfile,
+        tiers: &[PricingTier],
+        current_tier: Option<String>,
+        currency: &str,
+    ) -> Self {
+        let mut estimates: Vec<PlanEstimate> = tiers.iter().map(|tier| estimate_tier(tier, &profile)).collect();
+        estimates.sort_by(|a, b| a.monthly_cost.total_cmp(&b.monthly_cost));
+
+        let recommended = estimates.iter().find(|estimate| estimate.fits);
+        let current = current_tier.as_ref()
+            .and_then(|current| estimates.iter().find(|estimate| &estimate.tier == current));
+        let monthly_savings = recommended.zip(current)
+            .map(|(recommended, current)| current.monthly_cost - recommended.monthly_cost);
+
+        Self {
+            tenant_id: None,
+            recommended_tier: recommended.map(|estimate| estimate.tier.clone()),
+            monthly_savings,
+            current_tier,
+            profile,
+            currency: currency.to_string(),
+            estimates,
+            generated_at: Utc::now(),
+        }
+    }
+}
+
+/// Price `profile` under `tier` and check it against the tier's limits
+fn estimate_tier(tier: &PricingTier, profile: &UsageProfile) -> PlanEstimate {
+    let cost_breakdown = CostBreakdown::for_usage(tier, profile);
+
+    let mut limits_exceeded = Vec::new();
+    if profile.storage_gb * BYTES_PER_GB > tier.max_limits.max_storage_bytes as f64 {
+        limits_exceeded.push("storage".to_string());
+    }
+    if profile.api_calls_per_hour() > tier.max_limits.max_api_calls_per_hour as f64 {
+        limits_exceeded.push("api_calls".to_string());
+    }
+
+    PlanEstimate {
+        tier: tier.name.clone(),
+        description: tier.description.clone(),
+        monthly_cost: cost_breakdown.total(),
+        cost_breakdown,
+        fits: limits_exceeded.is_empty(),
+        limits_exceeded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TenantLimits;
+
+    fn tier(name: &str, base_fee: f64, storage_price_per_gb: f64, max_storage_gb: u64) -> PricingTier {
+        PricingTier {
+            name: name.to_string(),
+            description: String::new(),
+            base_fee,
+            storage_price_per_gb,
+            api_price_per_1k_calls: 0.01,
+            compute_price_per_cpu_hour: 0.0,
+            network_price_per_gb: 0.0,
+            included_quotas: TenantLimits { max_storage_bytes: u64::MAX, ..TenantLimits::default() },
+            max_limits: TenantLimits {
+                max_storage_bytes: max_storage_gb * 1024 * 1024 * 1024,
+                ..TenantLimits::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_recommends_cheapest_tier_that_fits() {
+        let tiers = [
+            tier("starter", 0.0, 0.5, 10),
+            tier("professional", 20.0, 0.2, 100),
+            tier("enterprise", 200.0, 0.1, 1000),
+        ];
+        let profile = UsageProfile { storage_gb: 50.0, api_calls: 100_000, ..Default::default() };
+
+        let estimate = BillingEstimate::simulate(profile, &tiers, Some("enterprise".to_string()), "USD");
+
+        // starter would be cheapest at $26, but 50 GB exceeds its 10 GB limit
+        assert_eq!(estimate.estimates[0].tier, "starter");
+        assert_eq!(estimate.estimates[0].limits_exceeded, vec!["storage".to_string()]);
+        assert_eq!(estimate.recommended_tier.as_deref(), Some("professional"));
+        assert!((estimate.estimates[1].monthly_cost - 31.0).abs() < 1e-9);
+        assert!((estimate.monthly_savings.unwrap() - 175.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_no_recommendation_when_no_tier_fits() {
+        let tiers = [tier("starter", 0.0, 1.0, 10)];
+        let profile = UsageProfile { storage_gb: 50.0, ..Default::default() };
+
+        let estimate = BillingEstimate::simulate(profile, &tiers, None, "USD");
+        assert!(estimate.recommended_tier.is_none());
+        assert!(estimate.monthly_savings.is_none());
+    }
+}
@@ -5,6 +5,7 @@
 use crate::config::{QuotaConfig, QuotaEnforcementAction};
 use crate::errors::{QuotaError, QuotaResult};
 use crate::tenant::{Tenant, TenantManager, TenantUsage};
+use crate::usage_alerts::{HttpAlertNotifier, UsageAlertManager, UsageForecast};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -30,6 +31,7 @@ pub struct QuotaViolation {
 pub struct QuotaManager {
     config: QuotaConfig,
     tenant_manager: Arc<TenantManager>,
+    alerts: Arc<UsageAlertManager>,
     is_running: Arc<tokio::sync::RwLock<bool>>,
 }
 
@@ -41,9 +43,12 @@ impl QuotaManager {
         // Note: In a real implementation, this would be injected
         let tenant_manager = Arc::new(TenantManager::new(&crate::config::TenantConfig::default()).await?);
         
+        let notifier = Arc::new(HttpAlertNotifier::new(&config.alerts)?);
+        
         let manager = Self {
             config: config.clone(),
             tenant_manager,
+            alerts: Arc::new(UsageAlertManager::new(&config.alerts, notifier)),
             is_running: Arc::new(tokio::sync::RwLock::new(false)),
         };
         
@@ -67,6 +72,7 @@ impl QuotaManager {
         if self.config.enabled {
             let config = self.config.clone();
             let tenant_manager = Arc::clone(&self.tenant_manager);
+            let alerts = Arc::clone(&self.alerts);
             let is_running = Arc::clone(&self.is_running);
             
             tokio::spawn(async move {
@@ -81,7 +87,7 @@ impl QuotaManager {
                     }
                     
                     // Check quotas for all tenants
-                    if let Err(e) = Self::check_all_quotas(&config, &tenant_manager).await {
+                    if let Err(e) = Self::check_all_quotas(&config, &tenant_manager, &alerts).await {
                         error!("Quota checking failed: {}", e);
                     }
                 }
@@ -109,12 +115,16 @@ impl QuotaManager {
     async fn check_all_quotas(
         config: &QuotaConfig,
         tenant_manager: &TenantManager,
+        alerts: &UsageAlertManager,
     ) -> Result<()> {
         debug!("📊 Checking quotas for all tenants");
         
         let tenants = tenant_manager.list_tenants(Some(1000), Some(0)).await?;
         
         for tenant in tenants {
+            alerts.record_usage(&tenant).await;
+            alerts.check(&tenant).await;
+            
             if let Err(e) = Self::check_tenant_quotas(config, &tenant).await {
                 error!("Failed to check quotas for tenant {}: {}", tenant.tenant_id, e);
             }
@@ -214,6 +224,26 @@ impl QuotaManager {
         Ok(())
     }
     
+    /// Usage alerts of all tenants
+    pub fn alerts(&self) -> &Arc<UsageAlertManager> {
+        &self.alerts
+    }
+    
+    /// Forecast when each quota of a tenant will be exhausted
+    pub async fn usage_forecast(&self, tenant_id: Uuid) -> QuotaResult<UsageForecast> {
+        let tenant = self.tenant_manager
+            .get_tenant(tenant_id)
+            .await
+            .map_err(|e| QuotaError::CheckFailed {
+                message: format!("Failed to get tenant: {}", e),
+            })?
+            .ok_or_else(|| QuotaError::CheckFailed {
+                message: format!("Tenant not found: {}", tenant_id),
+            })?;
+        
+        Ok(self.alerts.forecast(&tenant).await)
+    }
+    
     /// Check if an operation is allowed for a tenant
     pub async fn check_operation_allowed(
         &self,
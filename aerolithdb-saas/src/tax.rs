@@ -0,0 +1,211 @@
+//! VAT/GST assessment of invoices
+//!
+//! Invoices are taxed at the rate of the customer's country, taken from the
+//! country of its billing address, when a [`TaxRule`] covers that country,
+//! and at the billing tax rate otherwise. Under a rule with reverse charge,
+//! a business customer registered for tax in another country than the
+//! seller is invoiced without tax and accounts for it itself; the invoice
+//! records its tax ID and the reverse-charge note.
+
+use crate::config::{TaxConfig, TaxRule};
+use crate::errors::{BillingError, BillingResult};
+use crate::tenant::BillingInfo;
+use serde::{Deserialize, Serialize};
+
+/// Tax applied to one invoice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxAssessment {
+    /// Customer country the tax was assessed for
+    pub country: Option<String>,
+
+    /// Tax name, e.g. "VAT" or "GST"
+    pub tax_name: String,
+
+    /// Rate applied (as decimal)
+    pub rate: f64,
+
+    /// Tax charged
+    pub amount: f64,
+
+    /// Whether the customer accounts for the tax under reverse charge
+    pub reverse_charge: bool,
+
+    /// Tax ID of the customer
+    pub customer_tax_id: Option<String>,
+
+    /// Note printed on the invoice
+    pub note: Option<String>,
+}
+
+impl TaxConfig {
+    /// Check that countries are two-letter codes, covered at most once, and
+    /// that rates are fractions
+    pub fn validate(&self) -> BillingResult<()> {
+        let invalid = |message: String| Err(BillingError::InvalidTaxConfig { message });
+        if let Some(home_country) = &self.home_country {
+            if !is_country_code(home_country) {
+                return invalid(format!("home country {} is not an ISO 3166-1 alpha-2 code", home_country));
+            }
+        }
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !is_country_code(&rule.country) {
+                return invalid(format!("country {} is not an ISO 3166-1 alpha-2 code", rule.country));
+            }
+            if !(0.0..=1.0).contains(&rule.rate) {
+                return invalid(format!("{} rate {} of {} is not between 0 and 1", rule.name, rule.rate, rule.country));
+            }
+            if self.rules[..index].iter().any(|other| other.country.eq_ignore_ascii_case(&rule.country)) {
+                return invalid(format!("country {} has more than one rule", rule.country));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tax on `subtotal` for the customer described by `billing_info`, at
+    /// `default_rate` when no rule covers its country
+    pub fn assess(&self, billing_info: &BillingInfo, default_rate: f64, subtotal: f64) -> TaxAssessment {
+        let country = billing_info
+            .billing_address
+            .as_ref()
+            .map(|address| address.country.trim().to_ascii_uppercase())
+            .filter(|country| !country.is_empty());
+        let customer_tax_id = billing_info
+            .tax_id
+            .as_ref()
+            .map(|tax_id| tax_id.trim().to_string())
+            .filter(|tax_id| !tax_id.is_empty());
+        let rule = country.as_ref().and_then(|country| self.rule(country));
+
+        let Some(rule) = rule else {
+            return TaxAssessment {
+                country,
+                tax_name: "Tax".to_string(),
+                rate: default_rate,
+                amount: round_cents(subtotal * default_rate),
+                reverse_charge: false,
+                customer_tax_id,
+                note: None,
+            };
+        };
+
+        let cross_border = self
+            .home_country
+            .as_ref()
+            .is_some_and(|home_country| !home_country.eq_ignore_ascii_case(&rule.country));
+        if rule.reverse_charge && cross_border && customer_tax_id.is_some() {
+            return TaxAssessment {
+                country,
+                tax_name: rule.name.clone(),
+                rate: 0.0,
+                amount: 0.0,
+                reverse_charge: true,
+                note: Some(format!("Reverse charge: {} to be accounted for by the recipient", rule.name)),
+                customer_tax_id,
+            };
+        }
+
+        TaxAssessment {
+            country,
+            tax_name: rule.name.clone(),
+            rate: rule.rate,
+            amount: round_cents(subtotal * rule.rate),
+            reverse_charge: false,
+            customer_tax_id,
+            note: None,
+        }
+    }
+
+    fn rule(&self, country: &str) -> Option<&TaxRule> {
+        self.rules.iter().find(|rule| rule.country.eq_ignore_ascii_case(country))
+    }
+}
+
+fn is_country_code(country: &str) -> bool {
+    country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// `amount` rounded to whole cents
+pub(crate) fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::{BillingAddress, BillingCycle};
+    use chrono::Utc;
+
+    fn customer(country: &str, tax_id: Option<&str>) -> BillingInfo {
+        BillingInfo {
+            billing_email: "billing@acme.test".to_string(),
+            billing_address: Some(BillingAddress {
+                company: None,
+                line1: "1 Main Street".to_string(),
+                line2: None,
+                city: "Springfield".to_string(),
+                state: None,
+                postal_code: "12345".to_string(),
+                country: country.to_string(),
+            }),
+            payment_method: None,
+            billing_cycle: BillingCycle::Monthly,
+            next_billing_date: Utc::now(),
+            outstanding_balance: 0.0,
+            currency: "EUR".to_string(),
+            tax_id: tax_id.map(str::to_string),
+        }
+    }
+
+    fn config() -> TaxConfig {
+        let rule = |country: &str, name: &str, rate: f64| TaxRule {
+            country: country.to_string(),
+            name: name.to_string(),
+            rate,
+            reverse_charge: true,
+        };
+        TaxConfig {
+            home_country: Some("DE".to_string()),
+            rules: vec![rule("DE", "VAT", 0.19), rule("FR", "VAT", 0.20), rule("AU", "GST", 0.10)],
+        }
+    }
+
+    #[test]
+    fn test_rates_by_country_and_reverse_charge() {
+        let config = config();
+        assert!(config.validate().is_ok());
+
+        let domestic = config.assess(&customer("de", Some("DE123456789")), 0.05, 100.0);
+        assert_eq!((domestic.tax_name.as_str(), domestic.amount, domestic.reverse_charge), ("VAT", 19.0, false));
+
+        // Cross-border business customers account for the tax themselves
+        let business = config.assess(&customer("FR", Some("FR12345678901")), 0.05, 100.0);
+        assert!(business.reverse_charge);
+        assert_eq!((business.rate, business.amount), (0.0, 0.0));
+        assert_eq!(business.customer_tax_id.as_deref(), Some("FR12345678901"));
+        assert!(business.note.unwrap().contains("Reverse charge"));
+
+        let consumer = config.assess(&customer("FR", None), 0.05, 100.0);
+        assert_eq!((consumer.amount, consumer.reverse_charge), (20.0, false));
+
+        let gst = config.assess(&customer("AU", None), 0.05, 33.33);
+        assert_eq!((gst.tax_name.as_str(), gst.amount), ("GST", 3.33));
+
+        // Countries without a rule pay the billing tax rate
+        let other = config.assess(&customer("US", Some("12-3456789")), 0.05, 100.0);
+        assert_eq!((other.country.as_deref(), other.rate, other.amount), (Some("US"), 0.05, 5.0));
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let mut duplicate = config();
+        duplicate.rules.push(TaxRule { country: "fr".to_string(), ..duplicate.rules[1].clone() });
+        assert!(duplicate.validate().is_err());
+
+        let mut rate = config();
+        rate.rules[0].rate = 19.0;
+        assert!(rate.validate().is_err());
+
+        let country = TaxConfig { home_country: Some("DEU".to_string()), rules: vec![] };
+        assert!(country.validate().is_err());
+    }
+}
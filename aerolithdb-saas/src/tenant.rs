@@ -131,6 +131,10 @@ pub struct BillingInfo {
     
     /// Currency
     pub currency: String,
+    
+    /// VAT/GST registration number of the organization
+    #[serde(default)]
+    pub tax_id: Option<String>,
 }
 
 /// Billing address information
@@ -0,0 +1,493 @@
+//! Tenant backup schedules and self-service restore
+//!
+//! Tenants schedule daily or weekly backups of their own collections and
+//! choose how many to keep. Backups are taken by the storage backup
+//! subsystem, limited to the collections under the tenant's namespace
+//! prefix (`tenant_<id>_`), and each one is a full backup so the oldest can
+//! be pruned without breaking an incremental chain.
+//!
+//! Tenants restore a backup into a new namespace of their own: the backed-up
+//! collections are recreated as `<namespace>_<collection>` next to the
+//! current ones, which stay untouched. Restores in place stay an operator
+//! task.
+//!
+//! Backups and restores taken through [`StorageHierarchy`] are recorded as
+//! background jobs of the tenant, so they show up in its job list.
+
+use crate::config::TenantBackupConfig;
+use crate::errors::{TenantBackupError, TenantBackupResult};
+use aerolithdb_storage::{BackupKind, BackupManifest, Job, RestoreReport, StorageHierarchy};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Longest namespace a tenant can restore into
+const MAX_NAMESPACE_LENGTH: usize = 48;
+
+/// How often scheduled backups run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFrequency {
+    /// Once a day
+    Daily,
+    /// Once a week
+    Weekly,
+}
+
+impl BackupFrequency {
+    /// Time between two scheduled backups
+    pub fn period(self) -> Duration {
+        match self {
+            BackupFrequency::Daily => Duration::days(1),
+            BackupFrequency::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+/// Backup schedule chosen by a tenant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantBackupSchedule {
+    /// How often backups run
+    pub frequency: BackupFrequency,
+
+    /// Backups kept; older ones are deleted after each scheduled backup
+    pub retention_count: u32,
+}
+
+/// Schedule of a tenant and the outcome of its last backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantBackupStatus {
+    /// Tenant backed up
+    pub tenant_id: Uuid,
+
+    /// Schedule, `None` if the tenant has not set one
+    pub schedule: Option<TenantBackupSchedule>,
+
+    /// When the last backup succeeded
+    pub last_backup_at: Option<DateTime<Utc>>,
+
+    /// Why the last backup failed, if it did
+    pub last_error: Option<String>,
+
+    /// When the next scheduled backup is due
+    pub next_backup_at: Option<DateTime<Utc>>,
+}
+
+impl TenantBackupStatus {
+    fn new(tenant_id: Uuid) -> Self {
+        Self { tenant_id, schedule: None, last_backup_at: None, last_error: None, next_backup_at: None }
+    }
+}
+
+/// Restore of a tenant backup into a new namespace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantRestoreRequest {
+    /// Name the restored collections are prefixed with
+    pub namespace: String,
+}
+
+/// Backups of the collections of one tenant
+#[async_trait]
+pub trait TenantBackupStore: Send + Sync {
+    /// Take a full backup of the collections whose names start with `scope`
+    /// on behalf of a tenant
+    async fn create_backup(&self, tenant_id: Uuid, scope: &str) -> Result<BackupManifest>;
+
+    /// Backups of `scope`, oldest first
+    async fn list_backups(&self, scope: &str) -> Result<Vec<BackupManifest>>;
+
+    /// Delete a backup
+    async fn delete_backup(&self, backup_id: &str) -> Result<()>;
+
+    /// Copy the documents of a backup into the collections under `namespace`
+    /// on behalf of a tenant
+    async fn restore_backup_into(&self, tenant_id: Uuid, backup_id: &str, namespace: &str) -> Result<RestoreReport>;
+
+    /// Background jobs run on behalf of a tenant, newest first
+    async fn jobs(&self, tenant_id: Uuid) -> Result<Vec<Job>>;
+}
+
+#[async_trait]
+impl TenantBackupStore for StorageHierarchy {
+    async fn create_backup(&self, tenant_id: Uuid, scope: &str) -> Result<BackupManifest> {
+        self.run_backup_job(BackupKind::Full, Some(scope), Some(&tenant_id.to_string())).await
+    }
+
+    async fn list_backups(&self, scope: &str) -> Result<Vec<BackupManifest>> {
+        let backups = StorageHierarchy::list_backups(self).await?;
+        Ok(backups.into_iter().filter(|backup| backup.scope.as_deref() == Some(scope)).collect())
+    }
+
+    async fn delete_backup(&self, backup_id: &str) -> Result<()> {
+        StorageHierarchy::delete_backup(self, backup_id).await
+    }
+
+    async fn restore_backup_into(&self, tenant_id: Uuid, backup_id: &str, namespace: &str) -> Result<RestoreReport> {
+        self.run_restore_job(backup_id, Some(namespace), Some(&tenant_id.to_string())).await
+    }
+
+    async fn jobs(&self, tenant_id: Uuid) -> Result<Vec<Job>> {
+        StorageHierarchy::jobs(self).list(Some(&tenant_id.to_string()), None)
+    }
+}
+
+/// Tenant backup schedules, scheduled runs and self-service restores
+#[derive(Clone)]
+pub struct TenantBackupManager {
+    config: TenantBackupConfig,
+    store: Arc<dyn TenantBackupStore>,
+
+    /// Schedule and last outcome of each tenant
+    statuses: Arc<RwLock<HashMap<Uuid, TenantBackupStatus>>>,
+
+    is_running: Arc<RwLock<bool>>,
+}
+
+impl TenantBackupManager {
+    /// Create a backup manager taking backups through `store`
+    pub fn new(config: &TenantBackupConfig, store: Arc<dyn TenantBackupStore>) -> Self {
+        Self {
+            config: config.clone(),
+            store,
+            statuses: Arc::new(RwLock::new(HashMap::new())),
+            is_running: Arc::new(RwLock::new(false)),
+        }
+    }
+
+    /// Collection prefix of a tenant, as used by
+    /// [`TenantManager::get_tenant_namespace`](crate::tenant::TenantManager::get_tenant_namespace)
+    pub fn tenant_scope(tenant_id: Uuid) -> String {
+        format!("tenant_{}_", tenant_id.simple())
+    }
+
+    /// Start running due backups every check interval
+    pub async fn start_scheduler(&self) -> Result<()> {
+        {
+            let mut is_running = self.is_running.write().await;
+            if *is_running {
+                warn!("Tenant backup scheduler already running");
+                return Ok(());
+            }
+            *is_running = true;
+        }
+
+        if self.config.enabled {
+            info!("🔄 Starting tenant backup scheduler");
+            let manager = self.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(manager.config.check_interval);
+                loop {
+                    interval.tick().await;
+                    if !*manager.is_running.read().await {
+                        break;
+                    }
+                    manager.run_due_backups(Utc::now()).await;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Stop the scheduler after its current run
+    pub async fn stop_scheduler(&self) -> Result<()> {
+        *self.is_running.write().await = false;
+        info!("✅ Tenant backup scheduler stopped");
+        Ok(())
+    }
+
+    /// Set the backup schedule of a tenant; its first backup runs at the
+    /// next check unless it was backed up within the new period
+    pub async fn set_schedule(
+        &self,
+        tenant_id: Uuid,
+        schedule: TenantBackupSchedule,
+    ) -> TenantBackupResult<TenantBackupStatus> {
+        if !self.config.enabled {
+            return Err(TenantBackupError::Disabled);
+        }
+        if schedule.retention_count == 0 || schedule.retention_count > self.config.max_retention_count {
+            return Err(TenantBackupError::InvalidSchedule {
+                message: format!(
+                    "retention count must be between 1 and {}, got {}",
+                    self.config.max_retention_count, schedule.retention_count
+                ),
+            });
+        }
+
+        let mut statuses = self.statuses.write().await;
+        let status = statuses.entry(tenant_id).or_insert_with(|| TenantBackupStatus::new(tenant_id));
+        status.next_backup_at = Some(match status.last_backup_at {
+            Some(last) => last + schedule.frequency.period(),
+            None => Utc::now(),
+        });
+        info!(
+            "💾 Tenant {} backs up {:?}, keeping {} backups",
+            tenant_id, schedule.frequency, schedule.retention_count
+        );
+        status.schedule = Some(schedule);
+        Ok(status.clone())
+    }
+
+    /// Stop scheduled backups of a tenant; its backups are kept
+    pub async fn remove_schedule(&self, tenant_id: Uuid) {
+        if let Some(status) = self.statuses.write().await.get_mut(&tenant_id) {
+            status.schedule = None;
+            status.next_backup_at = None;
+            info!("💾 Tenant {} no longer backs up on a schedule", tenant_id);
+        }
+    }
+
+    /// Schedule and last outcome of a tenant
+    pub async fn status(&self, tenant_id: Uuid) -> TenantBackupStatus {
+        self.statuses.read().await.get(&tenant_id).cloned().unwrap_or_else(|| TenantBackupStatus::new(tenant_id))
+    }
+
+    /// Backups of a tenant, oldest first
+    pub async fn backups(&self, tenant_id: Uuid) -> TenantBackupResult<Vec<BackupManifest>> {
+        self.store
+            .list_backups(&Self::tenant_scope(tenant_id))
+            .await
+            .map_err(|e| TenantBackupError::Failed { message: e.to_string() })
+    }
+
+    /// Background jobs of a tenant, such as its backups and restores,
+    /// newest first
+    pub async fn jobs(&self, tenant_id: Uuid) -> TenantBackupResult<Vec<Job>> {
+        self.store.jobs(tenant_id).await.map_err(|e| TenantBackupError::Failed { message: e.to_string() })
+    }
+
+    /// Back up a tenant now, then delete the backups beyond its retention
+    /// count if it has a schedule
+    pub async fn run_backup(&self, tenant_id: Uuid) -> TenantBackupResult<BackupManifest> {
+        if !self.config.enabled {
+            return Err(TenantBackupError::Disabled);
+        }
+        let scope = Self::tenant_scope(tenant_id);
+        let created = self.store.create_backup(tenant_id, &scope).await;
+
+        let now = Utc::now();
+        let retention_count = {
+            let mut statuses = self.statuses.write().await;
+            let status = statuses.entry(tenant_id).or_insert_with(|| TenantBackupStatus::new(tenant_id));
+            match &created {
+                Ok(_) => {
+                    status.last_backup_at = Some(now);
+                    status.last_error = None;
+                    if let Some(schedule) = &status.schedule {
+                        status.next_backup_at = Some(now + schedule.frequency.period());
+                    }
+                }
+                Err(e) => status.last_error = Some(e.to_string()),
+            }
+            status.schedule.as_ref().map(|schedule| schedule.retention_count as usize)
+        };
+
+        let manifest = created.map_err(|e| TenantBackupError::Failed { message: e.to_string() })?;
+        info!("💾 Backed up {} documents of tenant {} as {}", manifest.total_documents, tenant_id, manifest.id);
+
+        if let Some(retention_count) = retention_count {
+            self.prune(tenant_id, &scope, retention_count).await;
+        }
+        Ok(manifest)
+    }
+
+    /// Back up every tenant whose scheduled backup is due at `now`
+    pub async fn run_due_backups(&self, now: DateTime<Utc>) -> Vec<Uuid> {
+        let due: Vec<Uuid> = self
+            .statuses
+            .read()
+            .await
+            .values()
+            .filter(|status| status.next_backup_at.is_some_and(|next| next <= now))
+            .map(|status| status.tenant_id)
+            .collect();
+
+        // Failed backups stay due and are retried at the next check
+        for tenant_id in &due {
+            if let Err(e) = self.run_backup(*tenant_id).await {
+                error!("Scheduled backup of tenant {} failed: {}", tenant_id, e);
+            }
+        }
+        due
+    }
+
+    /// Restore a backup of a tenant into the namespace it chose
+    pub async fn restore(
+        &self,
+        tenant_id: Uuid,
+        backup_id: &str,
+        request: &TenantRestoreRequest,
+    ) -> TenantBackupResult<RestoreReport> {
+        if !self.config.enabled {
+            return Err(TenantBackupError::Disabled);
+        }
+        let namespace = &request.namespace;
+        if namespace.is_empty()
+            || namespace.len() > MAX_NAMESPACE_LENGTH
+            || !namespace.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(TenantBackupError::InvalidNamespace {
+                message: format!(
+                    "{:?} must be 1 to {} lowercase letters, digits or underscores",
+                    namespace, MAX_NAMESPACE_LENGTH
+                ),
+            });
+        }
+
+        // Tenants only see their own backups
+        let backups = self.backups(tenant_id).await?;
+        if !backups.iter().any(|backup| backup.id == backup_id) {
+            return Err(TenantBackupError::BackupNotFound { backup_id: backup_id.to_string() });
+        }
+
+        let target = format!("{}{}_", Self::tenant_scope(tenant_id), namespace);
+        let report = self.store.restore_backup_into(tenant_id, backup_id, &target).await.map_err(|e| {
+            if e.to_string().starts_with("Restore namespace is not empty") {
+                TenantBackupError::NamespaceNotEmpty { namespace: namespace.clone() }
+            } else {
+                TenantBackupError::Failed { message: e.to_string() }
+            }
+        })?;
+        info!(
+            "♻️ Restored {} documents of backup {} into namespace {} of tenant {}",
+            report.restored, backup_id, namespace, tenant_id
+        );
+        Ok(report)
+    }
+
+    /// Delete the oldest backups of a tenant beyond `retention_count`
+    async fn prune(&self, tenant_id: Uuid, scope: &str, retention_count: usize) {
+        let backups = match self.store.list_backups(scope).await {
+            Ok(backups) => backups,
+            Err(e) => {
+                warn!("⚠️ Could not list backups of tenant {} to prune them: {}", tenant_id, e);
+                return;
+            }
+        };
+        let excess = backups.len().saturating_sub(retention_count);
+        for backup in &backups[..excess] {
+            match self.store.delete_backup(&backup.id).await {
+                Ok(()) => info!("🗑️ Deleted backup {} of tenant {} beyond retention", backup.id, tenant_id),
+                Err(e) => warn!("⚠️ Could not delete backup {} of tenant {}: {}", backup.id, tenant_id, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps backup manifests in memory
+    #[derive(Default)]
+    struct MemoryStore {
+        backups: std::sync::Mutex<Vec<BackupManifest>>,
+        restores: std::sync::Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl TenantBackupStore for MemoryStore {
+        async fn create_backup(&self, _tenant_id: Uuid, scope: &str) -> Result<BackupManifest> {
+            let mut backups = self.backups.lock().unwrap();
+            let manifest = BackupManifest {
+                id: format!("backup-{}", backups.len()),
+                kind: BackupKind::Full,
+                parent: None,
+                scope: Some(scope.to_string()),
+                created_at: Utc::now(),
+                format_version: 1,
+                documents: 1,
+                total_documents: 1,
+                deleted: 0,
+                unreadable: Vec::new(),
+                size: 0,
+                checksum: String::new(),
+            };
+            backups.push(manifest.clone());
+            Ok(manifest)
+        }
+
+        async fn list_backups(&self, scope: &str) -> Result<Vec<BackupManifest>> {
+            let backups = self.backups.lock().unwrap();
+            Ok(backups.iter().filter(|backup| backup.scope.as_deref() == Some(scope)).cloned().collect())
+        }
+
+        async fn delete_backup(&self, backup_id: &str) -> Result<()> {
+            self.backups.lock().unwrap().retain(|backup| backup.id != backup_id);
+            Ok(())
+        }
+
+        async fn jobs(&self, _tenant_id: Uuid) -> Result<Vec<Job>> {
+            Ok(Vec::new())
+        }
+
+        async fn restore_backup_into(&self, _tenant_id: Uuid, backup_id: &str, namespace: &str) -> Result<RestoreReport> {
+            let mut restores = self.restores.lock().unwrap();
+            if restores.iter().any(|(_, restored)| restored == namespace) {
+                anyhow::bail!("Restore namespace is not empty: {}", namespace);
+            }
+            restores.push((backup_id.to_string(), namespace.to_string()));
+            Ok(RestoreReport { backup_id: backup_id.to_string(), namespace: Some(namespace.to_string()), ..Default::default() })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_backups_run_when_due_and_keep_the_retention_count() {
+        let store = Arc::new(MemoryStore::default());
+        let manager = TenantBackupManager::new(&TenantBackupConfig::default(), store.clone());
+        let tenant_id = Uuid::new_v4();
+        let schedule = TenantBackupSchedule { frequency: BackupFrequency::Daily, retention_count: 2 };
+
+        let invalid = TenantBackupSchedule { retention_count: 0, ..schedule.clone() };
+        assert!(matches!(manager.set_schedule(tenant_id, invalid).await, Err(TenantBackupError::InvalidSchedule { .. })));
+        manager.set_schedule(tenant_id, schedule).await.unwrap();
+
+        // The first backup is due right away, the next one a day later
+        assert_eq!(manager.run_due_backups(Utc::now()).await, [tenant_id]);
+        assert!(manager.run_due_backups(Utc::now()).await.is_empty());
+        let next = manager.status(tenant_id).await.next_backup_at.unwrap();
+        assert_eq!(manager.run_due_backups(next).await, [tenant_id]);
+        assert_eq!(manager.run_due_backups(next + Duration::days(1)).await, [tenant_id]);
+
+        let ids: Vec<String> = manager.backups(tenant_id).await.unwrap().into_iter().map(|backup| backup.id).collect();
+        assert_eq!(ids, ["backup-1", "backup-2"]);
+        assert!(manager.backups(Uuid::new_v4()).await.unwrap().is_empty());
+
+        manager.remove_schedule(tenant_id).await;
+        assert!(manager.run_due_backups(next + Duration::days(7)).await.is_empty());
+        assert_eq!(manager.backups(tenant_id).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tenants_restore_their_own_backups_into_new_namespaces() {
+        let store = Arc::new(MemoryStore::default());
+        let manager = TenantBackupManager::new(&TenantBackupConfig::default(), store.clone());
+        let tenant_id = Uuid::new_v4();
+        let backup = manager.run_backup(tenant_id).await.unwrap();
+        let request = |namespace: &str| TenantRestoreRequest { namespace: namespace.to_string() };
+
+        let report = manager.restore(tenant_id, &backup.id, &request("restored")).await.unwrap();
+        let target = format!("tenant_{}_restored_", tenant_id.simple());
+        assert_eq!(report.namespace, Some(target));
+
+        assert!(matches!(
+            manager.restore(tenant_id, &backup.id, &request("restored")).await,
+            Err(TenantBackupError::NamespaceNotEmpty { .. })
+        ));
+        assert!(matches!(
+            manager.restore(tenant_id, &backup.id, &request("../other")).await,
+            Err(TenantBackupError::InvalidNamespace { .. })
+        ));
+        assert!(matches!(
+            manager.restore(Uuid::new_v4(), &backup.id, &request("stolen")).await,
+            Err(TenantBackupError::BackupNotFound { .. })
+        ));
+        assert_eq!(store.restores.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,574 @@
+//! Usage alerts and quota forecasts for tenants
+//!
+//! Tenants are alerted when their storage or hourly API usage crosses one of
+//! the configured fractions of their quota (80% and 95% by default). Each
+//! threshold alerts once; it is re-armed when usage falls back below it.
+//! Alerts go to the tenant's webhooks and email addresses, or to its
+//! billing email when it has configured none. Webhook requests carry an
+//! HMAC-SHA256 signature of their body when the webhook has a secret; email
+//! alerts are handed to the mail service at
+//! [`UsageAlertConfig::email_relay_url`].
+//!
+//! Usage samples taken by the quota checks also feed a forecast of when each
+//! quota will be exhausted, fitted to the trend over the forecast window.
+
+use crate::config::UsageAlertConfig;
+use crate::errors::{QuotaError, QuotaResult};
+use crate::tenant::Tenant;
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Alerts kept per tenant
+const MAX_ALERTS_PER_TENANT: usize = 100;
+
+/// Quotas that are alerted on and forecast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    /// Stored bytes
+    Storage,
+    /// API calls in the current hour
+    ApiCalls,
+}
+
+/// Where a tenant receives usage alerts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertChannel {
+    /// JSON POST of the alert, signed when a secret is set
+    Webhook {
+        url: String,
+        secret: Option<String>,
+    },
+    /// Email through the configured mail service
+    Email {
+        address: String,
+    },
+}
+
+/// Outcome of delivering an alert to one channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertDelivery {
+    /// Channel the alert was sent to
+    pub channel: AlertChannel,
+
+    /// Why delivery failed, if it did
+    pub error: Option<String>,
+}
+
+/// Alert sent when usage crosses a threshold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageAlert {
+    /// Alert identifier
+    pub alert_id: Uuid,
+
+    /// Tenant alerted
+    pub tenant_id: Uuid,
+
+    /// Quota approaching its limit
+    pub quota: QuotaKind,
+
+    /// Threshold crossed, as a fraction of the limit
+    pub threshold: f64,
+
+    /// Usage when the alert was raised
+    pub current: u64,
+
+    /// Quota limit
+    pub limit: u64,
+
+    /// When the quota is forecast to be exhausted
+    pub exhausted_at: Option<DateTime<Utc>>,
+
+    /// When the alert was raised
+    pub triggered_at: DateTime<Utc>,
+
+    /// Delivery to each channel
+    pub deliveries: Vec<AlertDelivery>,
+}
+
+/// Forecast of one quota
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaForecast {
+    /// Quota forecast
+    pub quota: QuotaKind,
+
+    /// Current usage
+    pub current: u64,
+
+    /// Quota limit
+    pub limit: u64,
+
+    /// Current usage as a fraction of the limit
+    pub utilization: f64,
+
+    /// Usage growth per hour over the samples
+    pub growth_per_hour: f64,
+
+    /// When usage reaches the limit at this growth; `None` if it does not,
+    /// or for API calls not before the hourly quota resets
+    pub exhausted_at: Option<DateTime<Utc>>,
+
+    /// Samples the trend was fitted to
+    pub samples: usize,
+}
+
+/// Forecast of all quotas of a tenant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageForecast {
+    /// Tenant forecast
+    pub tenant_id: Uuid,
+
+    /// When the forecast was made
+    pub generated_at: DateTime<Utc>,
+
+    /// Forecast of each quota
+    pub quotas: Vec<QuotaForecast>,
+}
+
+/// Usage of a tenant at one point in time
+#[derive(Debug, Clone)]
+struct UsageSample {
+    at: DateTime<Utc>,
+    storage_bytes: u64,
+    api_calls_current_hour: u64,
+}
+
+/// Delivery of usage alerts to tenant channels
+#[async_trait]
+pub trait AlertNotifier: Send + Sync {
+    /// Send `alert` about `tenant` to `channel`
+    async fn notify(&self, tenant: &Tenant, channel: &AlertChannel, alert: &UsageAlert) -> Result<()>;
+}
+
+/// Delivers webhook alerts over HTTP and email alerts through a mail service
+pub struct HttpAlertNotifier {
+    client: reqwest::Client,
+    email_relay_url: Option<String>,
+}
+
+impl HttpAlertNotifier {
+    /// Create a notifier for `config`
+    pub fn new(config: &UsageAlertConfig) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(config.delivery_timeout).build()?,
+            email_relay_url: config.email_relay_url.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl AlertNotifier for HttpAlertNotifier {
+    async fn notify(&self, tenant: &Tenant, channel: &AlertChannel, alert: &UsageAlert) -> Result<()> {
+        let request = match channel {
+            AlertChannel::Webhook { url, secret } => {
+                let body = serde_json::to_vec(alert)?;
+                let mut request = self.client.post(url).header("Content-Type", "application/json");
+                if let Some(secret) = secret {
+                    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+                    let signature = ring::hmac::sign(&key, &body);
+                    request = request.header(
+                        "X-AerolithDB-Signature",
+                        format!("sha256={}", base64::engine::general_purpose::STANDARD.encode(signature.as_ref())),
+                    );
+                }
+                request.body(body)
+            }
+            AlertChannel::Email { address } => {
+                let relay = self.email_relay_url.as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("no email relay is configured"))?;
+                self.client.post(relay).json(&serde_json::json!({
+                    "to": address,
+                    "subject": format!(
+                        "{} has used {:.0}% of its {} quota",
+                        tenant.organization_name,
+                        alert.current as f64 / alert.limit as f64 * 100.0,
+                        quota_name(alert.quota),
+                    ),
+                    "body": alert_message(tenant, alert),
+                }))
+            }
+        };
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Usage alert thresholds, channels, history and forecasts
+pub struct UsageAlertManager {
+    config: UsageAlertConfig,
+    notifier: Arc<dyn AlertNotifier>,
+
+    /// Channels configured by each tenant
+    channels: RwLock<HashMap<Uuid, Vec<AlertChannel>>>,
+
+    /// Usage samples within the forecast window
+    samples: RwLock<HashMap<Uuid, VecDeque<UsageSample>>>,
+
+    /// Highest threshold alerted for each quota, until usage drops below it
+    alerted: RwLock<HashMap<(Uuid, QuotaKind), f64>>,
+
+    /// Recent alerts of each tenant, oldest first
+    history: RwLock<HashMap<Uuid, VecDeque<UsageAlert>>>,
+}
+
+impl UsageAlertManager {
+    /// Create an alert manager delivering through `notifier`
+    pub fn new(config: &UsageAlertConfig, notifier: Arc<dyn AlertNotifier>) -> Self {
+        Self {
+            config: config.clone(),
+            notifier,
+            channels: RwLock::new(HashMap::new()),
+            samples: RwLock::new(HashMap::new()),
+            alerted: RwLock::new(HashMap::new()),
+            history: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Replace the alert channels of a tenant; an empty list restores
+    /// alerts to the billing email
+    pub async fn set_channels(&self, tenant_id: Uuid, channels: Vec<AlertChannel>) -> QuotaResult<()> {
+        for channel in &channels {
+            match channel {
+                AlertChannel::Webhook { url, .. } if !url.starts_with("https://") && !url.starts_with("http://") => {
+                    return Err(QuotaError::InvalidAlertChannel {
+                        message: format!("webhook URL {} is not an HTTP(S) URL", url),
+                    });
+                }
+                AlertChannel::Email { address } if !address.contains('@') => {
+                    return Err(QuotaError::InvalidAlertChannel {
+                        message: format!("{} is not an email address", address),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        info!("🔔 Tenant {} receives usage alerts on {} channels", tenant_id, channels.len());
+        self.channels.write().await.insert(tenant_id, channels);
+        Ok(())
+    }
+
+    /// Channels alerts of `tenant` are delivered to
+    pub async fn channels(&self, tenant: &Tenant) -> Vec<AlertChannel> {
+        match self.channels.read().await.get(&tenant.tenant_id) {
+            Some(channels) if !channels.is_empty() => channels.clone(),
+            _ => vec![AlertChannel::Email { address: tenant.billing_info.billing_email.clone() }],
+        }
+    }
+
+    /// Recent alerts of a tenant, oldest first
+    pub async fn alerts(&self, tenant_id: Uuid) -> Vec<UsageAlert> {
+        self.history.read().await
+            .get(&tenant_id)
+            .map(|alerts| alerts.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record the current usage of `tenant` for forecasting
+    pub async fn record_usage(&self, tenant: &Tenant) {
+        let now = Utc::now();
+        let window = Duration::from_std(self.config.forecast_window).unwrap_or(Duration::MAX);
+
+        let mut samples = self.samples.write().await;
+        let tenant_samples = samples.entry(tenant.tenant_id).or_default();
+        tenant_samples.push_back(UsageSample {
+            at: now,
+            storage_bytes: tenant.current_usage.storage_bytes,
+            api_calls_current_hour: tenant.current_usage.api_calls_current_hour,
+        });
+        while tenant_samples.front().is_some_and(|sample| now - sample.at > window) {
+            tenant_samples.pop_front();
+        }
+    }
+
+    /// Alert `tenant` about every quota whose usage crossed a threshold not
+    /// yet alerted, returning the alerts raised
+    pub async fn check(&self, tenant: &Tenant) -> Vec<UsageAlert> {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+
+        let forecast = self.forecast(tenant).await;
+        let mut raised = Vec::new();
+        for quota in &forecast.quotas {
+            let crossed = self.config.thresholds.iter()
+                .copied()
+                .filter(|threshold| quota.utilization >= *threshold)
+                .fold(None, |highest: Option<f64>, threshold| Some(highest.map_or(threshold, |h| h.max(threshold))));
+
+            let key = (tenant.tenant_id, quota.quota);
+            {
+                let mut alerted = self.alerted.write().await;
+                let previous = alerted.get(&key).copied();
+                match crossed {
+                    Some(threshold) => {
+                        alerted.insert(key, threshold);
+                        if previous.is_some_and(|previous| previous >= threshold) {
+                            continue;
+                        }
+                    }
+                    None => {
+                        alerted.remove(&key);
+                        continue;
+                    }
+                }
+            }
+
+            let mut alert = UsageAlert {
+                alert_id: Uuid::new_v4(),
+                tenant_id: tenant.tenant_id,
+                quota: quota.quota,
+                threshold: crossed.unwrap_or_default(),
+                current: quota.current,
+                limit: quota.limit,
+                exhausted_at: quota.exhausted_at,
+                triggered_at: Utc::now(),
+                deliveries: Vec::new(),
+            };
+            warn!("📈 {}", alert_message(tenant, &alert));
+
+            for channel in self.channels(tenant).await {
+                let error = self.notifier.notify(tenant, &channel, &alert).await.err().map(|e| e.to_string());
+                if let Some(error) = &error {
+                    warn!("Failed to deliver usage alert {} to {:?}: {}", alert.alert_id, channel, error);
+                }
+                alert.deliveries.push(AlertDelivery { channel, error });
+            }
+
+            let mut history = self.history.write().await;
+            let alerts = history.entry(tenant.tenant_id).or_default();
+            alerts.push_back(alert.clone());
+            if alerts.len() > MAX_ALERTS_PER_TENANT {
+                alerts.pop_front();
+            }
+            raised.push(alert);
+        }
+
+        raised
+    }
+
+    /// Forecast when each quota of `tenant` will be exhausted from the trend
+    /// of its recorded usage and its current usage
+    pub async fn forecast(&self, tenant: &Tenant) -> UsageForecast {
+        let now = Utc::now();
+        let hour_start = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+        let usage = &tenant.current_usage;
+        let limits = &tenant.limits;
+
+        let samples = self.samples.read().await;
+        let history: Vec<&UsageSample> = samples.get(&tenant.tenant_id).map(|s| s.iter().collect()).unwrap_or_default();
+
+        let storage: Vec<(DateTime<Utc>, u64)> = history.iter()
+            .map(|sample| (sample.at, sample.storage_bytes))
+            .chain(std::iter::once((now, usage.storage_bytes)))
+            .collect();
+        // The API call count restarts every hour, so only this hour's samples count
+        let api_calls: Vec<(DateTime<Utc>, u64)> = history.iter()
+            .filter(|sample| sample.at >= hour_start)
+            .map(|sample| (sample.at, sample.api_calls_current_hour))
+            .chain(std::iter::once((now, usage.api_calls_current_hour)))
+            .collect();
+
+        let mut quotas = vec![quota_forecast(QuotaKind::Storage, &storage, limits.max_storage_bytes, now, None)];
+        quotas.push(quota_forecast(
+            QuotaKind::ApiCalls,
+            &api_calls,
+            limits.max_api_calls_per_hour,
+            now,
+            Some(hour_start + Duration::hours(1)),
+        ));
+
+        UsageForecast { tenant_id: tenant.tenant_id, generated_at: now, quotas }
+    }
+}
+
+/// Forecast one quota from `samples`, the last of which is current, giving
+/// up on exhaustion after `resets_at`
+fn quota_forecast(
+    quota: QuotaKind,
+    samples: &[(DateTime<Utc>, u64)],
+    limit: u64,
+    now: DateTime<Utc>,
+    resets_at: Option<DateTime<Utc>>,
+) -> QuotaForecast {
+    let current = samples.last().map_or(0, |(_, value)| *value);
+    let growth_per_second = trend(samples);
+
+    let exhausted_at = if limit == 0 {
+        None
+    } else if current >= limit {
+        Some(now)
+    } else if growth_per_second > 0.0 {
+        let seconds = (limit - current) as f64 / growth_per_second;
+        Duration::try_milliseconds((seconds * 1000.0).min(i64::MAX as f64) as i64)
+            .and_then(|remaining| now.checked_add_signed(remaining))
+            .filter(|at| resets_at.is_none_or(|resets_at| *at < resets_at))
+    } else {
+        None
+    };
+
+    QuotaForecast {
+        quota,
+        current,
+        limit,
+        utilization: if limit == 0 { 0.0 } else { current as f64 / limit as f64 },
+        growth_per_hour: growth_per_second * 3600.0,
+        exhausted_at,
+        samples: samples.len(),
+    }
+}
+
+/// Least-squares slope of `samples` in units per second
+fn trend(samples: &[(DateTime<Utc>, u64)]) -> f64 {
+    let Some((origin, _)) = samples.first() else {
+        return 0.0;
+    };
+    let points: Vec<(f64, f64)> = samples.iter()
+        .map(|(at, value)| ((*at - *origin).num_milliseconds() as f64 / 1000.0, *value as f64))
+        .collect();
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    if variance == 0.0 { 0.0 } else { covariance / variance }
+}
+
+fn quota_name(quota: QuotaKind) -> &'static str {
+    match quota {
+        QuotaKind::Storage => "storage",
+        QuotaKind::ApiCalls => "hourly API call",
+    }
+}
+
+/// Human-readable text of an alert
+fn alert_message(tenant: &Tenant, alert: &UsageAlert) -> String {
+    let mut message = format!(
+        "{} has used {} of {} ({:.0}%) of its {} quota, crossing the {:.0}% alert threshold.",
+        tenant.organization_name,
+        alert.current,
+        alert.limit,
+        alert.current as f64 / alert.limit as f64 * 100.0,
+        quota_name(alert.quota),
+        alert.threshold * 100.0,
+    );
+    if let Some(exhausted_at) = alert.exhausted_at {
+        message.push_str(&format!(" At the current trend it will be exhausted at {}.", exhausted_at.to_rfc3339()));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{IsolationLevel, TenantLimits};
+    use crate::tenant::{BillingCycle, BillingInfo, TenantStatus, TenantUsage};
+
+    /// Records alerts instead of sending them
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: std::sync::Mutex<Vec<(AlertChannel, f64)>>,
+    }
+
+    #[async_trait]
+    impl AlertNotifier for RecordingNotifier {
+        async fn notify(&self, _tenant: &Tenant, channel: &AlertChannel, alert: &UsageAlert) -> Result<()> {
+            self.sent.lock().unwrap().push((channel.clone(), alert.threshold));
+            Ok(())
+        }
+    }
+
+    fn tenant(storage_bytes: u64) -> Tenant {
+        Tenant {
+            tenant_id: Uuid::nil(),
+            organization_name: "Acme".to_string(),
+            organization_domain: None,
+            isolation_level: IsolationLevel::Shared,
+            limits: TenantLimits {
+                max_storage_bytes: 1000,
+                max_api_calls_per_hour: 100,
+                max_connections: 10,
+                max_collections: 10,
+                max_documents_per_collection: 1000,
+            },
+            current_usage: TenantUsage { storage_bytes, last_updated: Utc::now(), ..Default::default() },
+            status: TenantStatus::Active,
+            subscription_tier: "starter".to_string(),
+            billing_info: BillingInfo {
+                billing_email: "billing@acme.test".to_string(),
+                billing_address: None,
+                payment_method: None,
+                billing_cycle: BillingCycle::Monthly,
+                next_billing_date: Utc::now(),
+                outstanding_balance: 0.0,
+                currency: "USD".to_string(),
+                tax_id: None,
+            },
+            metadata: HashMap::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thresholds_alert_once_until_rearmed() {
+        let notifier = Arc::new(RecordingNotifier::default());
+        let alerts = UsageAlertManager::new(&UsageAlertConfig::default(), notifier.clone());
+
+        assert!(alerts.check(&tenant(790)).await.is_empty());
+        let raised = alerts.check(&tenant(850)).await;
+        assert_eq!(raised.len(), 1);
+        assert_eq!((raised[0].quota, raised[0].threshold), (QuotaKind::Storage, 0.8));
+        assert!(alerts.check(&tenant(900)).await.is_empty());
+        assert_eq!(alerts.check(&tenant(960)).await[0].threshold, 0.95);
+
+        // Dropping below a threshold re-arms it
+        assert!(alerts.check(&tenant(500)).await.is_empty());
+        assert_eq!(alerts.check(&tenant(820)).await.len(), 1);
+
+        let sent = notifier.sent.lock().unwrap().clone();
+        let billing = AlertChannel::Email { address: "billing@acme.test".to_string() };
+        assert_eq!(sent, vec![(billing.clone(), 0.8), (billing.clone(), 0.95), (billing, 0.8)]);
+        assert_eq!(alerts.alerts(Uuid::nil()).await.len(), 3);
+
+        // Configured channels replace the billing email
+        let webhook = AlertChannel::Webhook { url: "https://hooks.acme.test/usage".to_string(), secret: None };
+        assert!(alerts.set_channels(Uuid::nil(), vec![AlertChannel::Email { address: "nobody".to_string() }]).await.is_err());
+        alerts.set_channels(Uuid::nil(), vec![webhook.clone()]).await.unwrap();
+        assert_eq!(alerts.check(&tenant(990)).await[0].deliveries[0].channel, webhook);
+    }
+
+    #[test]
+    fn test_forecast_projects_the_trend_to_the_limit() {
+        let now = Utc::now();
+        let samples: Vec<(DateTime<Utc>, u64)> = (0..=4)
+            .map(|hour| (now - Duration::hours(4 - hour), 200 + 100 * hour as u64))
+            .collect();
+
+        let forecast = quota_forecast(QuotaKind::Storage, &samples, 1000, now, None);
+        assert_eq!(forecast.current, 600);
+        assert!((forecast.growth_per_hour - 100.0).abs() < 1e-6);
+        let exhausted_in = forecast.exhausted_at.unwrap() - now;
+        assert!((exhausted_in.num_minutes() - 240).abs() <= 1);
+
+        // Growth that would exhaust the quota only after it resets does not count
+        let resets_at = Some(now + Duration::hours(1));
+        assert!(quota_forecast(QuotaKind::ApiCalls, &samples, 1000, now, resets_at).exhausted_at.is_none());
+
+        let flat = [(now - Duration::hours(1), 600), (now, 600)];
+        assert!(quota_forecast(QuotaKind::Storage, &flat, 1000, now, None).exhausted_at.is_none());
+    }
+}
@@ -0,0 +1,582 @@
+//! # Cluster Admission Control
+//!
+//! Decides which nodes may join the cluster, before they are added to the
+//! hash ring or issued a certificate:
+//!
+//! - **Denylist**: node ids and public keys that are always refused. A
+//!   public key rule also catches a denied node that rejoins under a new id
+//! - **Allowlist**: node ids and public keys admitted without an operator
+//! - **Approval**: with `require_approval`, any other node waits until an
+//!   operator approves its request, which allowlists it, or rejects it.
+//!   Without it, every node that is not denied is admitted
+//! - **Proof of key**: a node presenting a public key must sign
+//!   [`join_proof_message`] of its node id with it, so no node can claim an
+//!   allowlisted key it does not hold. Keys and signatures are base58
+//!   encoded Ed25519, like the node's signing identity
+//! - **Alerting**: refused attempts are counted per node. Reaching
+//!   `max_denied_attempts` within `denied_attempt_window` raises an alert
+//!   on the alert channel and in the log, once per window
+//!
+//! The lists are persisted to `file` and survive restarts; pending requests
+//! and attempt counters are kept in memory.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use base58::FromBase58;
+use chrono::{DateTime, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// Longest accepted node id or public key
+const MAX_IDENTITY_LEN: usize = 1024;
+
+/// How joining nodes are screened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdmissionConfig {
+    /// Whether nodes that are not allowlisted wait for an operator's approval
+    pub require_approval: bool,
+
+    /// File the allowlist and denylist are persisted in
+    pub file: PathBuf,
+
+    /// Refused attempts by one node that raise an alert
+    pub max_denied_attempts: u32,
+
+    /// Window in which refused attempts are counted
+    pub denied_attempt_window: Duration,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            require_approval: false,
+            file: PathBuf::from("./data/admission.json"),
+            max_denied_attempts: 3,
+            denied_attempt_window: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// Which of a node's identities a rule matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityKind {
+    NodeId,
+    PublicKey,
+}
+
+/// The list a rule belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessList {
+    Allow,
+    Deny,
+}
+
+/// An allowlist or denylist entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessRule {
+    pub kind: IdentityKind,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+/// The lists and approval mode, as reported to operators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessLists {
+    pub require_approval: bool,
+    pub allow: Vec<AccessRule>,
+    pub deny: Vec<AccessRule>,
+}
+
+/// A node asking to join.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinCandidate {
+    pub node_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+
+    /// Signature of [`join_proof_message`] by `public_key`, required with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+/// What a joining node signs with its public key: its node id, bound to
+/// cluster joins.
+pub fn join_proof_message(node_id: &str) -> Vec<u8> {
+    format!("aerolithdb-join:{}", node_id.trim()).into_bytes()
+}
+
+/// A join request waiting for an operator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingJoin {
+    pub id: String,
+    pub node_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub requested_at: DateTime<Utc>,
+
+    /// Times the node has asked while waiting
+    pub attempts: u32,
+}
+
+/// Outcome of screening a joining node that is not denied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AdmissionDecision {
+    Admitted,
+
+    /// Waiting for approval; the node asks again later
+    Pending(PendingJoin),
+}
+
+/// Refused join attempts of one node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeniedJoinAttempts {
+    pub node_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// Attempts within the current window
+    pub attempts: u32,
+    pub total_attempts: u64,
+    pub window_started_at: DateTime<Utc>,
+    pub last_attempt_at: DateTime<Utc>,
+
+    /// Whether this window's attempts raised an alert
+    pub alerted: bool,
+}
+
+/// Contents of the persisted file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoredLists {
+    #[serde(default)]
+    allow: Vec<AccessRule>,
+    #[serde(default)]
+    deny: Vec<AccessRule>,
+}
+
+#[derive(Debug, Default)]
+struct AdmissionState {
+    lists: StoredLists,
+    pending: HashMap<String, PendingJoin>,
+    denied: HashMap<String, DeniedJoinAttempts>,
+}
+
+/// Screens joining nodes against the allowlist, denylist and approvals.
+#[derive(Debug)]
+pub struct AdmissionControl {
+    config: AdmissionConfig,
+    state: Mutex<AdmissionState>,
+    alerts: broadcast::Sender<DeniedJoinAttempts>,
+    rng: SystemRandom,
+}
+
+impl AdmissionControl {
+    /// Load the persisted lists from `config.file`, starting empty when it
+    /// does not exist yet.
+    pub fn load(config: &AdmissionConfig) -> Result<Self> {
+        let lists = match std::fs::read(&config.file) {
+            Ok(bytes) => serde_json::from_slice::<StoredLists>(&bytes)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", config.file.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoredLists::default(),
+            Err(e) => return Err(anyhow!("Failed to read {}: {}", config.file.display(), e)),
+        };
+        if !lists.allow.is_empty() || !lists.deny.is_empty() {
+            info!("Admission control loaded {} allowed and {} denied identities", lists.allow.len(), lists.deny.len());
+        }
+
+        let (alerts, _) = broadcast::channel(64);
+        Ok(Self {
+            config: config.clone(),
+            state: Mutex::new(AdmissionState { lists, ..Default::default() }),
+            alerts,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn require_approval(&self) -> bool {
+        self.config.require_approval
+    }
+
+    /// The allowlist and denylist.
+    pub fn lists(&self) -> AccessLists {
+        let state = self.lock();
+        AccessLists {
+            require_approval: self.config.require_approval,
+            allow: state.lists.allow.clone(),
+            deny: state.lists.deny.clone(),
+        }
+    }
+
+    /// Add a rule to a list, replacing the same identity's rule there.
+    ///
+    /// Denying an identity also drops it from the allowlist and discards its
+    /// pending requests.
+    pub fn add_rule(&self, list: AccessList, kind: IdentityKind, value: &str, note: Option<String>) -> Result<AccessRule> {
+        let value = validate_identity(value, "Invalid access rule")?;
+        let rule = AccessRule { kind, value: value.to_string(), note, added_at: Utc::now() };
+
+        let mut state = self.lock();
+        let mut lists = state.lists.clone();
+        let rules = match list {
+            AccessList::Allow => &mut lists.allow,
+            AccessList::Deny => &mut lists.deny,
+        };
+        rules.retain(|existing| !(existing.kind == kind && existing.value == value));
+        rules.push(rule.clone());
+        if list == AccessList::Deny {
+            lists.allow.retain(|existing| !(existing.kind == kind && existing.value == value));
+        }
+        self.persist(&lists)?;
+        state.lists = lists;
+
+        if list == AccessList::Deny {
+            state.pending.retain(|_, pending| !matches(&rule, &pending.node_id, pending.public_key.as_deref()));
+        }
+        info!("Added {:?} '{}' to the {:?} list", kind, value, list);
+        Ok(rule)
+    }
+
+    /// Remove an identity's rule from a list.
+    pub fn remove_rule(&self, list: AccessList, kind: IdentityKind, value: &str) -> Result<AccessRule> {
+        let mut state = self.lock();
+        let mut lists = state.lists.clone();
+        let rules = match list {
+            AccessList::Allow => &mut lists.allow,
+            AccessList::Deny => &mut lists.deny,
+        };
+        let position = rules
+            .iter()
+            .position(|rule| rule.kind == kind && rule.value == value.trim())
+            .ok_or_else(|| anyhow!("Access rule not found: {:?} '{}'", kind, value))?;
+        let removed = rules.remove(position);
+        self.persist(&lists)?;
+        state.lists = lists;
+        info!("Removed {:?} '{}' from the {:?} list", kind, removed.value, list);
+        Ok(removed)
+    }
+
+    /// Refuse a denied node, counting the attempt. Fails with "Join denied"
+    /// when the node id or public key is on the denylist, and with "Invalid
+    /// join request" when a public key comes without a valid signature.
+    pub fn screen(&self, candidate: &JoinCandidate) -> Result<()> {
+        self.screen_at(candidate, Utc::now())
+    }
+
+    /// Screen a joining node, admitting it when it is allowlisted or no
+    /// approval is required, and queueing it for approval otherwise.
+    pub fn admit(&self, candidate: &JoinCandidate) -> Result<AdmissionDecision> {
+        self.screen(candidate)?;
+
+        let mut state = self.lock();
+        let allowed = state.lists.allow.iter().any(|rule| matches(rule, &candidate.node_id, candidate.public_key.as_deref()));
+        if allowed || !self.config.require_approval {
+            return Ok(AdmissionDecision::Admitted);
+        }
+
+        if let Some(pending) = state.pending.values_mut().find(|pending| {
+            pending.node_id == candidate.node_id && pending.public_key == candidate.public_key
+        }) {
+            pending.attempts += 1;
+            return Ok(AdmissionDecision::Pending(pending.clone()));
+        }
+
+        let pending = PendingJoin {
+            id: self.random_id(),
+            node_id: candidate.node_id.clone(),
+            public_key: candidate.public_key.clone(),
+            address: candidate.address.clone(),
+            requested_at: Utc::now(),
+            attempts: 1,
+        };
+        info!("Join request {} of node {} awaits approval", pending.id, pending.node_id);
+        state.pending.insert(pending.id.clone(), pending.clone());
+        Ok(AdmissionDecision::Pending(pending))
+    }
+
+    /// Join requests waiting for approval, oldest first.
+    pub fn pending(&self) -> Vec<PendingJoin> {
+        let state = self.lock();
+        let mut pending: Vec<PendingJoin> = state.pending.values().cloned().collect();
+        pending.sort_by_key(|pending| pending.requested_at);
+        pending
+    }
+
+    /// Approve a pending request by allowlisting the node: by public key
+    /// when it gave one, by node id otherwise. Its next attempt is admitted.
+    pub fn approve(&self, request_id: &str) -> Result<AccessRule> {
+        let pending = self
+            .lock()
+            .pending
+            .get(request_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Join request not found: {}", request_id))?;
+        let (kind, value) = match &pending.public_key {
+            Some(public_key) => (IdentityKind::PublicKey, public_key.as_str()),
+            None => (IdentityKind::NodeId, pending.node_id.as_str()),
+        };
+        let note = Some(format!("Approved join request {} of node {}", pending.id, pending.node_id));
+        let rule = self.add_rule(AccessList::Allow, kind, value, note)?;
+        self.lock().pending.remove(request_id);
+        info!("Approved join request {} of node {}", request_id, pending.node_id);
+        Ok(rule)
+    }
+
+    /// Reject a pending request. The node may ask again; deny it to refuse
+    /// it for good.
+    pub fn reject(&self, request_id: &str) -> Result<PendingJoin> {
+        let pending = self
+            .lock()
+            .pending
+            .remove(request_id)
+            .ok_or_else(|| anyhow!("Join request not found: {}", request_id))?;
+        info!("Rejected join request {} of node {}", request_id, pending.node_id);
+        Ok(pending)
+    }
+
+    /// Refused join attempts by node, most recent first.
+    pub fn denied_attempts(&self) -> Vec<DeniedJoinAttempts> {
+        let state = self.lock();
+        let mut denied: Vec<DeniedJoinAttempts> = state.denied.values().cloned().collect();
+        denied.sort_by_key(|record| std::cmp::Reverse(record.last_attempt_at));
+        denied
+    }
+
+    /// Alerts raised by repeated join attempts from denied nodes.
+    pub fn subscribe_alerts(&self) -> broadcast::Receiver<DeniedJoinAttempts> {
+        self.alerts.subscribe()
+    }
+
+    fn screen_at(&self, candidate: &JoinCandidate, now: DateTime<Utc>) -> Result<()> {
+        validate_identity(&candidate.node_id, "Invalid join request")?;
+        if let Some(public_key) = &candidate.public_key {
+            let public_key = validate_identity(public_key, "Invalid join request")?;
+            verify_key_proof(&candidate.node_id, public_key, candidate.signature.as_deref())?;
+        }
+
+        let mut state = self.lock();
+        let Some(rule) = state
+            .lists
+            .deny
+            .iter()
+            .find(|rule| matches(rule, &candidate.node_id, candidate.public_key.as_deref()))
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        let window = chrono::Duration::from_std(self.config.denied_attempt_window).unwrap_or(chrono::Duration::MAX);
+        let record = state.denied.entry(candidate.node_id.clone()).or_insert_with(|| DeniedJoinAttempts {
+            node_id: candidate.node_id.clone(),
+            public_key: None,
+            address: None,
+            attempts: 0,
+            total_attempts: 0,
+            window_started_at: now,
+            last_attempt_at: now,
+            alerted: false,
+        });
+        if now - record.window_started_at > window {
+            record.attempts = 0;
+            record.window_started_at = now;
+            record.alerted = false;
+        }
+        record.attempts += 1;
+        record.total_attempts += 1;
+        record.last_attempt_at = now;
+        record.public_key = candidate.public_key.clone();
+        record.address = candidate.address.clone();
+
+        if record.attempts >= self.config.max_denied_attempts.max(1) && !record.alerted {
+            record.alerted = true;
+            warn!(
+                "ALERT: denied node {} tried to join {} times since {} (from {:?})",
+                record.node_id, record.attempts, record.window_started_at, record.address
+            );
+            // Nobody may be listening
+            let _ = self.alerts.send(record.clone());
+        }
+
+        warn!("Refused join of node {}: {:?} '{}' is denied", candidate.node_id, rule.kind, rule.value);
+        Err(anyhow!("Join denied: {:?} '{}' is on the denylist", rule.kind, rule.value))
+    }
+
+    fn persist(&self, lists: &StoredLists) -> Result<()> {
+        if let Some(parent) = self.config.file.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| anyhow!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&self.config.file, serde_json::to_vec_pretty(lists)?)
+            .map_err(|e| anyhow!("Failed to write {}: {}", self.config.file.display(), e))
+    }
+
+    fn random_id(&self) -> String {
+        let mut bytes = [0u8; 8];
+        self.rng.fill(&mut bytes).expect("system random source available");
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, AdmissionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn matches(rule: &AccessRule, node_id: &str, public_key: Option<&str>) -> bool {
+    match rule.kind {
+        IdentityKind::NodeId => rule.value == node_id,
+        IdentityKind::PublicKey => public_key.is_some_and(|public_key| rule.value == public_key.trim()),
+    }
+}
+
+fn validate_identity<'a>(value: &'a str, context: &str) -> Result<&'a str> {
+    let value = value.trim();
+    if value.is_empty() || value.len() > MAX_IDENTITY_LEN || value.chars().any(char::is_whitespace) {
+        return Err(anyhow!("{}: bad identity '{}'", context, value));
+    }
+    Ok(value)
+}
+
+/// Check that the node holds the private half of `public_key`.
+fn verify_key_proof(node_id: &str, public_key: &str, signature: Option<&str>) -> Result<()> {
+    let signature = signature.ok_or_else(|| anyhow!("Invalid join request: public key '{}' is not signed for", public_key))?;
+    let key = public_key.from_base58().map_err(|_| anyhow!("Invalid join request: public key '{}' is not base58", public_key))?;
+    let signature = signature.trim().from_base58().map_err(|_| anyhow!("Invalid join request: signature is not base58"))?;
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(&join_proof_message(node_id), &signature)
+        .map_err(|_| anyhow!("Invalid join request: signature does not prove public key '{}'", public_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base58::ToBase58;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn config(name: &str) -> AdmissionConfig {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-admission-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        AdmissionConfig { file: dir.join("admission.json"), ..Default::default() }
+    }
+
+    fn key(seed: u8) -> Ed25519KeyPair {
+        Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap()
+    }
+
+    fn public(key: &Ed25519KeyPair) -> String {
+        key.public_key().as_ref().to_base58()
+    }
+
+    /// A candidate proving its key, as a joining node does
+    fn candidate(node_id: &str, key: Option<&Ed25519KeyPair>) -> JoinCandidate {
+        JoinCandidate {
+            node_id: node_id.to_string(),
+            public_key: key.map(public),
+            signature: key.map(|key| key.sign(&join_proof_message(node_id)).as_ref().to_base58()),
+            address: Some("http://10.0.0.9:8080".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_approval_allowlists_the_node() {
+        let config = AdmissionConfig { require_approval: true, ..config("approval") };
+        let admission = AdmissionControl::load(&config).unwrap();
+
+        let AdmissionDecision::Pending(pending) = admission.admit(&candidate("node-2", Some(&key(2)))).unwrap() else {
+            panic!("join was not queued");
+        };
+        let AdmissionDecision::Pending(again) = admission.admit(&candidate("node-2", Some(&key(2)))).unwrap() else {
+            panic!("join was not queued");
+        };
+        assert_eq!((again.id.as_str(), again.attempts), (pending.id.as_str(), 2));
+
+        let rule = admission.approve(&pending.id).unwrap();
+        assert_eq!((rule.kind, rule.value), (IdentityKind::PublicKey, public(&key(2))));
+        assert!(admission.pending().is_empty());
+        assert_eq!(admission.admit(&candidate("node-2", Some(&key(2)))).unwrap(), AdmissionDecision::Admitted);
+        // A new id with the approved key is admitted, the old id with another key is not
+        assert_eq!(admission.admit(&candidate("node-2b", Some(&key(2)))).unwrap(), AdmissionDecision::Admitted);
+        assert!(matches!(admission.admit(&candidate("node-2", Some(&key(7)))).unwrap(), AdmissionDecision::Pending(_)));
+
+        let AdmissionDecision::Pending(rejected) = admission.admit(&candidate("node-3", None)).unwrap() else {
+            panic!("join was not queued");
+        };
+        admission.reject(&rejected.id).unwrap();
+        assert!(admission.approve(&rejected.id).unwrap_err().to_string().starts_with("Join request not found"));
+
+        // Lists survive a restart
+        let reloaded = AdmissionControl::load(&config).unwrap();
+        assert_eq!(reloaded.lists().allow, admission.lists().allow);
+        std::fs::remove_dir_all(config.file.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_denylist_wins_and_alerts_on_repeated_attempts() {
+        let config = config("deny");
+        let admission = AdmissionControl::load(&config).unwrap();
+        let mut alerts = admission.subscribe_alerts();
+
+        admission.add_rule(AccessList::Allow, IdentityKind::NodeId, "node-6", None).unwrap();
+        admission.add_rule(AccessList::Deny, IdentityKind::PublicKey, &public(&key(9)), Some("stolen".to_string())).unwrap();
+        assert_eq!(admission.admit(&candidate("node-6", Some(&key(6)))).unwrap(), AdmissionDecision::Admitted);
+
+        let start = Utc::now();
+        for n in 0..3 {
+            let err = admission.screen_at(&candidate("node-6", Some(&key(9))), start + chrono::Duration::seconds(n)).unwrap_err();
+            assert!(err.to_string().starts_with("Join denied"));
+        }
+        let alert = alerts.try_recv().unwrap();
+        assert_eq!((alert.node_id.as_str(), alert.attempts), ("node-6", 3));
+
+        // One alert per window, and counting starts over once it has passed
+        admission.screen_at(&candidate("node-6", Some(&key(9))), start + chrono::Duration::seconds(5)).unwrap_err();
+        assert!(alerts.try_recv().is_err());
+        admission.screen_at(&candidate("node-6", Some(&key(9))), start + chrono::Duration::minutes(20)).unwrap_err();
+        let record = &admission.denied_attempts()[0];
+        assert_eq!((record.attempts, record.total_attempts, record.alerted), (1, 5, false));
+
+        // Denying a node id drops its allow rule
+        admission.add_rule(AccessList::Deny, IdentityKind::NodeId, "node-6", None).unwrap();
+        assert!(admission.lists().allow.is_empty());
+        assert!(admission.admit(&candidate("node-6", None)).is_err());
+        admission.remove_rule(AccessList::Deny, IdentityKind::NodeId, "node-6").unwrap();
+        assert!(admission.remove_rule(AccessList::Deny, IdentityKind::NodeId, "node-6").is_err());
+        assert!(admission.add_rule(AccessList::Deny, IdentityKind::NodeId, "two words", None).is_err());
+        std::fs::remove_dir_all(config.file.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_public_keys_need_a_proof_of_possession() {
+        let config = AdmissionConfig { require_approval: true, ..config("proof") };
+        let admission = AdmissionControl::load(&config).unwrap();
+        admission.add_rule(AccessList::Allow, IdentityKind::PublicKey, &public(&key(2)), None).unwrap();
+
+        // Claiming the allowlisted key without its private half fails
+        let unsigned = JoinCandidate { signature: None, ..candidate("node-5", Some(&key(2))) };
+        let forged = JoinCandidate { signature: candidate("node-5", Some(&key(3))).signature, ..candidate("node-5", Some(&key(2))) };
+        let replayed = JoinCandidate { node_id: "node-5".to_string(), ..candidate("node-2", Some(&key(2))) };
+        for claim in [unsigned, forged, replayed] {
+            let err = admission.admit(&claim).unwrap_err();
+            assert!(err.to_string().starts_with("Invalid join request"), "{}", err);
+        }
+        assert!(admission.pending().is_empty());
+
+        assert_eq!(admission.admit(&candidate("node-5", Some(&key(2)))).unwrap(), AdmissionDecision::Admitted);
+        std::fs::remove_dir_all(config.file.parent().unwrap()).unwrap();
+    }
+}
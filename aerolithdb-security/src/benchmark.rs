@@ -0,0 +1,158 @@
+//! # Encryption Benchmark
+//!
+//! AES-256-GCM is the fastest AEAD on CPUs with AES and carry-less multiply
+//! instructions, and among the slowest without them, where ChaCha20-Poly1305
+//! wins. With [`EncryptionAlgorithm::Auto`] the security framework seals a
+//! test payload with each candidate algorithm at startup and encrypts data
+//! at rest with the fastest.
+//!
+//! The benchmark only picks the algorithm new payloads are sealed with.
+//! Every envelope records its cipher, so nodes that picked differently, or
+//! a node whose hardware changed, still read each other's payloads.
+
+use std::time::{Duration, Instant};
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN};
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::Cipher;
+use crate::EncryptionAlgorithm;
+
+/// Size of the payload sealed by the benchmark, close to a typical document
+/// batch
+pub const BENCHMARK_PAYLOAD_LEN: usize = 64 * 1024;
+
+/// Time spent sealing with each candidate algorithm
+pub const BENCHMARK_DURATION: Duration = Duration::from_millis(25);
+
+/// Throughput of one algorithm on this host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CipherThroughput {
+    pub algorithm: EncryptionAlgorithm,
+
+    /// Plaintext bytes sealed per second
+    pub bytes_per_second: f64,
+}
+
+/// Outcome of the startup benchmark behind [`EncryptionAlgorithm::Auto`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionBenchmark {
+    /// Whether the CPU has instructions accelerating AES-GCM
+    pub hardware_aes: bool,
+
+    /// Throughput of each candidate, in the order they were configured
+    pub results: Vec<CipherThroughput>,
+
+    /// The fastest candidate, used for new payloads
+    pub selected: EncryptionAlgorithm,
+}
+
+impl EncryptionBenchmark {
+    /// Seal a test payload with each of `candidates` for `duration` and
+    /// select the fastest; the first candidate wins ties. Falls back to the
+    /// algorithm the hardware favours when there are no candidates.
+    pub fn run(candidates: &[EncryptionAlgorithm], duration: Duration) -> Self {
+        let hardware_aes = hardware_aes();
+        let candidates: Vec<EncryptionAlgorithm> =
+            candidates.iter().filter(|algorithm| **algorithm != EncryptionAlgorithm::Auto).cloned().collect();
+
+        let results: Vec<CipherThroughput> = candidates
+            .into_iter()
+            .map(|algorithm| CipherThroughput {
+                bytes_per_second: throughput(Cipher::from(&algorithm), duration),
+                algorithm,
+            })
+            .collect();
+
+        let selected = results
+            .iter()
+            .fold(None::<&CipherThroughput>, |fastest, result| match fastest {
+                Some(fastest) if fastest.bytes_per_second >= result.bytes_per_second => Some(fastest),
+                _ => Some(result),
+            })
+            .map(|fastest| fastest.algorithm.clone())
+            .unwrap_or_else(|| EncryptionAlgorithm::preferred(hardware_aes));
+
+        Self { hardware_aes, results, selected }
+    }
+}
+
+/// Plaintext bytes per second `cipher` seals on this host.
+fn throughput(cipher: Cipher, duration: Duration) -> f64 {
+    // The key and nonce only need to be valid; nothing sealed here is kept
+    let key = LessSafeKey::new(UnboundKey::new(cipher.algorithm(), &[0x42; 32]).expect("32-byte AEAD key"));
+    let mut payload = vec![0u8; BENCHMARK_PAYLOAD_LEN];
+
+    let started = Instant::now();
+    let mut sealed_bytes = 0u64;
+    let mut counter = 0u64;
+    while sealed_bytes == 0 || started.elapsed() < duration {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..8].copy_from_slice(&counter.to_le_bytes());
+        let tag = key
+            .seal_in_place_separate_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut payload)
+            .expect("sealing a benchmark payload");
+        let _ = std::hint::black_box(tag);
+        sealed_bytes += BENCHMARK_PAYLOAD_LEN as u64;
+        counter += 1;
+    }
+
+    sealed_bytes as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON)
+}
+
+/// Whether the CPU accelerates AES-GCM.
+#[cfg(target_arch = "x86_64")]
+pub fn hardware_aes() -> bool {
+    is_x86_feature_detected!("aes") && is_x86_feature_detected!("pclmulqdq")
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn hardware_aes() -> bool {
+    std::arch::is_aarch64_feature_detected!("aes") && std::arch::is_aarch64_feature_detected!("pmull")
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn hardware_aes() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_selects_a_measured_candidate() {
+        let candidates = [EncryptionAlgorithm::AES256GCM, EncryptionAlgorithm::ChaCha20Poly1305, EncryptionAlgorithm::Auto];
+        let benchmark = EncryptionBenchmark::run(&candidates, Duration::from_millis(2));
+
+        assert_eq!(benchmark.results.len(), 2);
+        assert!(benchmark.results.iter().all(|result| result.bytes_per_second > 0.0));
+        let fastest = benchmark.results.iter().map(|result| result.bytes_per_second).fold(0.0, f64::max);
+        let selected = benchmark.results.iter().find(|result| result.algorithm == benchmark.selected).unwrap();
+        assert_eq!(selected.bytes_per_second, fastest);
+    }
+
+    #[test]
+    fn test_benchmark_without_candidates_follows_the_hardware() {
+        let benchmark = EncryptionBenchmark::run(&[], Duration::from_millis(2));
+
+        assert!(benchmark.results.is_empty());
+        assert_eq!(benchmark.selected, EncryptionAlgorithm::preferred(hardware_aes()));
+    }
+
+    #[tokio::test]
+    async fn test_auto_resolves_to_the_benchmarked_algorithm() {
+        let config = crate::SecurityConfig {
+            encryption_algorithm: EncryptionAlgorithm::Auto,
+            auto_encryption_candidates: vec![EncryptionAlgorithm::ChaCha20Poly1305],
+            ..Default::default()
+        };
+        let framework = crate::SecurityFramework::new(&config).await.unwrap();
+
+        let status = framework.status();
+        assert_eq!(status.configured_algorithm, EncryptionAlgorithm::Auto);
+        assert_eq!(status.encryption_algorithm, EncryptionAlgorithm::ChaCha20Poly1305);
+        assert_eq!(status.benchmark.unwrap().results.len(), 1);
+        assert!(!status.encryption_at_rest);
+    }
+}
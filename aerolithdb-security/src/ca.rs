@@ -0,0 +1,727 @@
+//! # Cluster Certificate Authority
+//!
+//! Mutual TLS between nodes needs every node to hold a certificate the
+//! others trust. Rather than running separate PKI, one node (usually the
+//! first) can act as the cluster CA:
+//!
+//! - **Built-in CA**: a self-signed CA key and certificate are generated in
+//!   `ca.dir` on first start and reused afterwards
+//! - **External CA**: enterprises point `ca.external` at an intermediate CA
+//!   certificate and key from their own PKI; node certificates chain to it
+//! - **Join**: a joining node requests a certificate for its node id and
+//!   addresses. With `require_approval`, the request waits until an operator
+//!   approves it and the node collects the certificate with the pickup token
+//!   it was given; otherwise it is issued at once
+//! - **Rotation**: node certificates are short-lived (`certificate_ttl`).
+//!   Each issuance returns a renewal token; presenting it renews the
+//!   certificate without another approval. The CA node renews its own
+//!   certificate in the background, `renew_before` ahead of expiry
+//!
+//! Node private keys are generated by the CA and returned once with the
+//! certificate; only the renewal token's SHA-256 digest is kept.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    KeyUsagePurpose, SanType, SerialNumber,
+};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Common name of the built-in CA certificate
+pub const CA_COMMON_NAME: &str = "AerolithDB Cluster CA";
+
+/// Validity of the built-in CA certificate
+const CA_VALIDITY: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// Node certificates are valid from slightly in the past, so peers with
+/// clocks running behind accept them right away
+const CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// How often the CA node checks whether its own certificate is due
+pub const ROTATION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+const ENROLLED_FILE: &str = "enrolled.json";
+
+/// How a node acts as the cluster certificate authority.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaConfig {
+    /// Whether this node issues node certificates
+    pub enabled: bool,
+
+    /// Directory holding the CA key, the CA certificate, this node's
+    /// certificate and the enrolled nodes
+    pub dir: PathBuf,
+
+    /// Lifetime of issued node certificates
+    pub certificate_ttl: Duration,
+
+    /// How long before expiry certificates are renewed
+    pub renew_before: Duration,
+
+    /// Whether join requests wait for an operator's approval
+    pub require_approval: bool,
+
+    /// CA certificate and key imported from an external PKI instead of the
+    /// built-in CA
+    pub external: Option<ExternalCa>,
+}
+
+impl Default for CaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("./data/ca"),
+            certificate_ttl: Duration::from_secs(24 * 60 * 60),
+            renew_before: Duration::from_secs(8 * 60 * 60),
+            require_approval: false,
+            external: None,
+        }
+    }
+}
+
+/// PEM files of a CA from an external PKI, usually an intermediate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalCa {
+    pub certificate_file: PathBuf,
+    pub key_file: PathBuf,
+}
+
+/// Where the CA certificate came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaOrigin {
+    BuiltIn,
+    External,
+}
+
+/// The CA as reported to operators and joining nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaInfo {
+    pub origin: CaOrigin,
+
+    /// Certificate nodes add to their trust store
+    pub certificate_pem: String,
+    pub require_approval: bool,
+    pub certificate_ttl: Duration,
+    pub renew_before: Duration,
+}
+
+/// A node asking for a certificate.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateRequest {
+    pub node_id: String,
+
+    /// DNS names and IP addresses the node is reached at, besides its id
+    #[serde(default)]
+    pub subject_alt_names: Vec<String>,
+
+    /// Token of the node's previous certificate, renewing it without
+    /// another approval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renewal_token: Option<String>,
+
+    /// Pickup token of a request queued for approval, collecting the
+    /// certificate once approved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pickup_token: Option<String>,
+
+    /// Public key identifying the node, screened by admission control
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+
+    /// Signature proving `public_key`, as in a join request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// A certificate issued to a node, with its private key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssuedCertificate {
+    pub node_id: String,
+
+    /// Hex serial number
+    pub serial: String,
+    pub certificate_pem: String,
+    pub private_key_pem: String,
+    pub ca_certificate_pem: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+
+    /// Presented to renew this certificate
+    pub renewal_token: String,
+}
+
+impl IssuedCertificate {
+    /// Whether the certificate is within `renew_before` of its expiry.
+    pub fn needs_renewal(&self, renew_before: Duration, now: DateTime<Utc>) -> bool {
+        let renew_before = chrono::Duration::from_std(renew_before).unwrap_or(chrono::Duration::MAX);
+        now >= self.not_after - renew_before
+    }
+}
+
+/// A join request waiting for an operator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingCertificateRequest {
+    pub id: String,
+    pub node_id: String,
+    pub subject_alt_names: Vec<String>,
+    pub requested_at: DateTime<Utc>,
+
+    /// Secret the requesting node collects its certificate with; only
+    /// returned to that node, when its request is queued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pickup_token: Option<String>,
+}
+
+/// Outcome of a certificate request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CertificateDecision {
+    Issued(IssuedCertificate),
+
+    /// Waiting for approval; the node asks again later
+    Pending(PendingCertificateRequest),
+}
+
+/// A node holding a certificate from this CA.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnrolledNode {
+    pub node_id: String,
+    pub serial: String,
+    pub not_after: DateTime<Utc>,
+    pub renewals: u32,
+
+    /// SHA-256 of the renewal token of the current certificate
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub renewal_token_sha256: String,
+}
+
+#[derive(Debug, Default)]
+struct CaState {
+    /// Requests awaiting approval by id, with the SHA-256 of their pickup
+    /// token
+    pending: HashMap<String, (PendingCertificateRequest, String)>,
+
+    /// Approved certificates awaiting pickup, by SHA-256 of the pickup token
+    approved: HashMap<String, IssuedCertificate>,
+    enrolled: HashMap<String, EnrolledNode>,
+}
+
+/// Issues and renews node certificates for mutual TLS.
+pub struct CertificateAuthority {
+    config: CaConfig,
+    origin: CaOrigin,
+
+    /// The CA certificate rebuilt for signing; carries the subject and key
+    /// of the distributed one
+    issuer: Certificate,
+    issuer_key: KeyPair,
+
+    /// The CA certificate as distributed to nodes
+    certificate_pem: String,
+    state: Mutex<CaState>,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for CertificateAuthority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateAuthority")
+            .field("origin", &self.origin)
+            .field("dir", &self.config.dir)
+            .field("require_approval", &self.config.require_approval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CertificateAuthority {
+    /// Load the CA from `config.dir` or the external files, generating a
+    /// built-in CA on first start.
+    pub fn load_or_create(config: &CaConfig) -> Result<Self> {
+        if config.certificate_ttl.is_zero() || config.renew_before >= config.certificate_ttl {
+            return Err(anyhow!("Invalid CA configuration: renew_before must be shorter than certificate_ttl"));
+        }
+        std::fs::create_dir_all(&config.dir)
+            .map_err(|e| anyhow!("Failed to create CA directory {}: {}", config.dir.display(), e))?;
+
+        let (origin, certificate_pem, key_pem) = match &config.external {
+            Some(external) => (CaOrigin::External, read(&external.certificate_file)?, read(&external.key_file)?),
+            None => {
+                let (certificate_file, key_file) = (config.dir.join("ca.crt"), config.dir.join("ca.key"));
+                if certificate_file.exists() {
+                    (CaOrigin::BuiltIn, read(&certificate_file)?, read(&key_file)?)
+                } else {
+                    let (certificate_pem, key_pem) = generate_ca()?;
+                    write(&key_file, &key_pem, true)?;
+                    write(&certificate_file, &certificate_pem, false)?;
+                    info!("Generated cluster CA at {}", certificate_file.display());
+                    (CaOrigin::BuiltIn, certificate_pem, key_pem)
+                }
+            }
+        };
+
+        let issuer_key = KeyPair::from_pem(&key_pem).map_err(|e| anyhow!("Invalid CA key: {}", e))?;
+        let (_, pem) = x509_parser::pem::parse_x509_pem(certificate_pem.as_bytes())
+            .map_err(|e| anyhow!("Invalid CA certificate: {}", e))?;
+        let parsed = pem.parse_x509().map_err(|e| anyhow!("Invalid CA certificate: {}", e))?;
+        if parsed.public_key().raw != issuer_key.public_key_der().as_slice() {
+            return Err(anyhow!("Invalid CA key: it does not match the CA certificate"));
+        }
+        let params = CertificateParams::from_ca_cert_pem(&certificate_pem)
+            .map_err(|e| anyhow!("Invalid CA certificate: {}", e))?;
+        if !matches!(params.is_ca, IsCa::Ca(_)) {
+            return Err(anyhow!("Invalid CA certificate: it is not a CA certificate"));
+        }
+        let issuer = params.self_signed(&issuer_key).map_err(|e| anyhow!("Invalid CA certificate: {}", e))?;
+
+        let enrolled = match std::fs::read(config.dir.join(ENROLLED_FILE)) {
+            Ok(bytes) => serde_json::from_slice::<Vec<EnrolledNode>>(&bytes)?
+                .into_iter()
+                .map(|node| (node.node_id.clone(), node))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(anyhow!("Failed to read enrolled nodes: {}", e)),
+        };
+        info!("Cluster CA ready ({:?}, {} enrolled nodes)", origin, enrolled.len());
+
+        Ok(Self {
+            config: config.clone(),
+            origin,
+            issuer,
+            issuer_key,
+            certificate_pem,
+            state: Mutex::new(CaState { enrolled, ..Default::default() }),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn info(&self) -> CaInfo {
+        CaInfo {
+            origin: self.origin,
+            certificate_pem: self.certificate_pem.clone(),
+            require_approval: self.config.require_approval,
+            certificate_ttl: self.config.certificate_ttl,
+            renew_before: self.config.renew_before,
+        }
+    }
+
+    /// Issue a certificate for a joining node, or queue the request for
+    /// approval. A valid renewal token always renews at once; an unknown one
+    /// fails with "Certificate renewal refused". A pickup token collects the
+    /// certificate of an approved request.
+    pub fn request_certificate(&self, request: CertificateRequest) -> Result<CertificateDecision> {
+        validate_node_id(&request.node_id)?;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(token) = &request.pickup_token {
+            let pickup = sha256_hex(token);
+            if state.approved.get(&pickup).is_some_and(|issued| issued.node_id == request.node_id) {
+                let issued = state.approved.remove(&pickup).expect("approved certificate present");
+                info!("Node {} collected certificate {}", issued.node_id, issued.serial);
+                return Ok(CertificateDecision::Issued(issued));
+            }
+            let waiting = state.pending.values().find(|(pending, digest)| *digest == pickup && pending.node_id == request.node_id);
+            return match waiting {
+                Some((pending, _)) => Ok(CertificateDecision::Pending(pending.clone())),
+                None => Err(anyhow!("Certificate request not found: rejected or already collected")),
+            };
+        }
+
+        if let Some(token) = &request.renewal_token {
+            let renews = state
+                .enrolled
+                .get(&request.node_id)
+                .is_some_and(|node| !node.renewal_token_sha256.is_empty() && node.renewal_token_sha256 == sha256_hex(token));
+            if !renews {
+                warn!("Refused certificate renewal for node {}", request.node_id);
+                return Err(anyhow!("Certificate renewal refused: unknown renewal token for node {}", request.node_id));
+            }
+            let issued = self.issue(&request.node_id, &request.subject_alt_names)?;
+            self.enroll(&mut state, &issued)?;
+            info!("Renewed certificate {} of node {}", issued.serial, issued.node_id);
+            return Ok(CertificateDecision::Issued(issued));
+        }
+
+        if self.config.require_approval {
+            // A repeated request does not learn the pickup token of the first
+            if let Some((pending, _)) = state.pending.values().find(|(pending, _)| pending.node_id == request.node_id) {
+                return Ok(CertificateDecision::Pending(pending.clone()));
+            }
+            let pickup_token = self.random_hex(32);
+            let pending = PendingCertificateRequest {
+                id: self.random_hex(8),
+                node_id: request.node_id,
+                subject_alt_names: request.subject_alt_names,
+                requested_at: Utc::now(),
+                pickup_token: None,
+            };
+            info!("Certificate request {} of node {} awaits approval", pending.id, pending.node_id);
+            state.pending.insert(pending.id.clone(), (pending.clone(), sha256_hex(&pickup_token)));
+            return Ok(CertificateDecision::Pending(PendingCertificateRequest { pickup_token: Some(pickup_token), ..pending }));
+        }
+
+        let issued = self.issue(&request.node_id, &request.subject_alt_names)?;
+        self.enroll(&mut state, &issued)?;
+        info!("Issued certificate {} to node {}", issued.serial, issued.node_id);
+        Ok(CertificateDecision::Issued(issued))
+    }
+
+    /// Join requests waiting for approval, oldest first.
+    pub fn pending_requests(&self) -> Vec<PendingCertificateRequest> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut pending: Vec<_> = state.pending.values().map(|(pending, _)| pending.clone()).collect();
+        pending.sort_by_key(|pending| pending.requested_at);
+        pending
+    }
+
+    /// Approve a pending request, issuing its certificate for the node to
+    /// collect. The operator only learns the certificate's summary.
+    pub fn approve(&self, request_id: &str) -> Result<EnrolledNode> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (pending, pickup) = state
+            .pending
+            .get(request_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Certificate request not found: {}", request_id))?;
+        let issued = self.issue(&pending.node_id, &pending.subject_alt_names)?;
+        self.enroll(&mut state, &issued)?;
+        state.pending.remove(request_id);
+        info!("Approved certificate request {}; issued {} to node {}", request_id, issued.serial, issued.node_id);
+
+        let enrolled = EnrolledNode { renewal_token_sha256: String::new(), ..state.enrolled[&issued.node_id].clone() };
+        state.approved.insert(pickup, issued);
+        Ok(enrolled)
+    }
+
+    /// Reject a pending request.
+    pub fn reject(&self, request_id: &str) -> Result<PendingCertificateRequest> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (pending, _) = state
+            .pending
+            .remove(request_id)
+            .ok_or_else(|| anyhow!("Certificate request not found: {}", request_id))?;
+        info!("Rejected certificate request {} of node {}", request_id, pending.node_id);
+        Ok(pending)
+    }
+
+    /// Nodes holding a certificate from this CA, by node id.
+    pub fn enrolled_nodes(&self) -> Vec<EnrolledNode> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut nodes: Vec<EnrolledNode> = state
+            .enrolled
+            .values()
+            .map(|node| EnrolledNode { renewal_token_sha256: String::new(), ..node.clone() })
+            .collect();
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        nodes
+    }
+
+    /// This node's own certificate, issued or renewed when missing, for
+    /// another node id, or due; kept as `node.crt` and `node.key` in the CA
+    /// directory for the TLS listeners.
+    pub fn local_certificate(&self, node_id: &str, subject_alt_names: &[String]) -> Result<IssuedCertificate> {
+        let record = self.config.dir.join("node.json");
+        let current = std::fs::read(&record).ok().and_then(|bytes| serde_json::from_slice::<IssuedCertificate>(&bytes).ok());
+        if let Some(current) = current {
+            if current.node_id == node_id && !current.needs_renewal(self.config.renew_before, Utc::now()) {
+                return Ok(current);
+            }
+        }
+
+        let issued = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let issued = self.issue(node_id, subject_alt_names)?;
+            self.enroll(&mut state, &issued)?;
+            issued
+        };
+        write(&self.config.dir.join("node.key"), &issued.private_key_pem, true)?;
+        write(&self.config.dir.join("node.crt"), &issued.certificate_pem, false)?;
+        write(&record, &serde_json::to_string_pretty(&issued)?, true)?;
+        info!("Issued certificate {} to this node, valid until {}", issued.serial, issued.not_after);
+        Ok(issued)
+    }
+
+    /// Keep this node's certificate renewed in the background.
+    pub fn spawn_local_rotation(self: &Arc<Self>, node_id: String, subject_alt_names: Vec<String>) -> tokio::task::JoinHandle<()> {
+        let ca = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ROTATION_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = ca.local_certificate(&node_id, &subject_alt_names) {
+                    warn!("Failed to renew this node's certificate: {}", e);
+                }
+            }
+        })
+    }
+
+    fn issue(&self, node_id: &str, subject_alt_names: &[String]) -> Result<IssuedCertificate> {
+        let now = SystemTime::now();
+        let (not_before, not_after) = (now - CLOCK_SKEW, now + self.config.certificate_ttl);
+
+        let mut params = CertificateParams::default();
+        params.subject_alt_names = std::iter::once(node_id)
+            .chain(subject_alt_names.iter().map(String::as_str))
+            .map(subject_alt_name)
+            .collect::<Result<_>>()?;
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, node_id);
+        name.push(DnType::OrganizationName, "AerolithDB");
+        params.distinguished_name = name;
+        params.not_before = not_before.into();
+        params.not_after = not_after.into();
+        let mut serial = [0u8; 16];
+        self.rng.fill(&mut serial).map_err(|_| anyhow!("Failed to generate a certificate serial"))?;
+        serial[0] &= 0x7f;
+        params.serial_number = Some(SerialNumber::from_slice(&serial));
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature, KeyUsagePurpose::KeyEncipherment];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth, ExtendedKeyUsagePurpose::ClientAuth];
+        params.use_authority_key_identifier_extension = true;
+
+        let key = KeyPair::generate().map_err(|e| anyhow!("Failed to generate a node key: {}", e))?;
+        let certificate = params
+            .signed_by(&key, &self.issuer, &self.issuer_key)
+            .map_err(|e| anyhow!("Failed to sign a node certificate: {}", e))?;
+
+        Ok(IssuedCertificate {
+            node_id: node_id.to_string(),
+            serial: hex(&serial),
+            certificate_pem: certificate.pem(),
+            private_key_pem: key.serialize_pem(),
+            ca_certificate_pem: self.certificate_pem.clone(),
+            not_before: not_before.into(),
+            not_after: not_after.into(),
+            renewal_token: self.random_hex(32),
+        })
+    }
+
+    /// Record `issued` as its node's current certificate
+    fn enroll(&self, state: &mut CaState, issued: &IssuedCertificate) -> Result<()> {
+        let renewals = state.enrolled.get(&issued.node_id).map(|node| node.renewals + 1).unwrap_or(0);
+        state.enrolled.insert(
+            issued.node_id.clone(),
+            EnrolledNode {
+                node_id: issued.node_id.clone(),
+                serial: issued.serial.clone(),
+                not_after: issued.not_after,
+                renewals,
+                renewal_token_sha256: sha256_hex(&issued.renewal_token),
+            },
+        );
+        let mut enrolled: Vec<&EnrolledNode> = state.enrolled.values().collect();
+        enrolled.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        write(&self.config.dir.join(ENROLLED_FILE), &serde_json::to_string_pretty(&enrolled)?, true)
+    }
+
+    fn random_hex(&self, len: usize) -> String {
+        let mut bytes = vec![0u8; len];
+        self.rng.fill(&mut bytes).expect("system randomness");
+        hex(&bytes)
+    }
+}
+
+/// A fresh self-signed CA certificate and key, as PEM
+fn generate_ca() -> Result<(String, String)> {
+    let key = KeyPair::generate().map_err(|e| anyhow!("Failed to generate the CA key: {}", e))?;
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, CA_COMMON_NAME);
+    name.push(DnType::OrganizationName, "AerolithDB");
+    params.distinguished_name = name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+    let now = SystemTime::now();
+    params.not_before = (now - CLOCK_SKEW).into();
+    params.not_after = (now + CA_VALIDITY).into();
+    let certificate = params.self_signed(&key).map_err(|e| anyhow!("Failed to create the CA certificate: {}", e))?;
+    Ok((certificate.pem(), key.serialize_pem()))
+}
+
+fn validate_node_id(node_id: &str) -> Result<()> {
+    let valid = !node_id.is_empty()
+        && node_id.len() <= 253
+        && node_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_'));
+    match valid {
+        true => Ok(()),
+        false => Err(anyhow!("Invalid certificate request: bad node id '{}'", node_id)),
+    }
+}
+
+fn subject_alt_name(name: &str) -> Result<SanType> {
+    if let Ok(ip) = name.parse::<IpAddr>() {
+        return Ok(SanType::IpAddress(ip));
+    }
+    name.to_string()
+        .try_into()
+        .map(SanType::DnsName)
+        .map_err(|_| anyhow!("Invalid certificate request: '{}' is not a DNS name or IP address", name))
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Write `contents` to `path`, readable by the owner only when `private`
+fn write(path: &Path, contents: &str, private: bool) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        if private {
+            options.mode(0o600);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    let mut file = options.open(path).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn sha256_hex(token: &str) -> String {
+    hex(digest::digest(&digest::SHA256, token.as_bytes()).as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> CaConfig {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-ca-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        CaConfig { enabled: true, dir, ..Default::default() }
+    }
+
+    fn issued(decision: CertificateDecision) -> IssuedCertificate {
+        match decision {
+            CertificateDecision::Issued(issued) => issued,
+            CertificateDecision::Pending(pending) => panic!("request {} is pending", pending.id),
+        }
+    }
+
+    fn request(node_id: &str, renewal_token: Option<String>) -> CertificateRequest {
+        CertificateRequest {
+            node_id: node_id.to_string(),
+            subject_alt_names: vec!["10.0.0.7".to_string()],
+            renewal_token,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_issued_certificates_chain_to_the_ca_and_renew_with_their_token() {
+        let config = config("issue");
+        let ca = CertificateAuthority::load_or_create(&config).unwrap();
+        let first = issued(ca.request_certificate(request("node-1", None)).unwrap());
+
+        let (_, pem) = x509_parser::pem::parse_x509_pem(first.certificate_pem.as_bytes()).unwrap();
+        let certificate = pem.parse_x509().unwrap();
+        let (_, ca_pem) = x509_parser::pem::parse_x509_pem(ca.info().certificate_pem.as_bytes()).unwrap();
+        let ca_certificate = ca_pem.parse_x509().unwrap();
+        assert_eq!(certificate.issuer(), ca_certificate.subject());
+        assert!(certificate.verify_signature(Some(ca_certificate.public_key())).is_ok());
+        assert!(first.not_after - first.not_before <= chrono::Duration::hours(25));
+        assert!(!first.needs_renewal(config.renew_before, Utc::now()));
+        assert!(first.needs_renewal(config.renew_before, first.not_after - chrono::Duration::hours(1)));
+
+        let renewed = issued(ca.request_certificate(request("node-1", Some(first.renewal_token.clone()))).unwrap());
+        assert_ne!(renewed.serial, first.serial);
+        // Tokens are single use
+        let refused = ca.request_certificate(request("node-1", Some(first.renewal_token))).unwrap_err();
+        assert!(refused.to_string().starts_with("Certificate renewal refused"));
+        assert!(ca.request_certificate(request("node 1", None)).is_err());
+
+        // The CA and its enrolled nodes survive a restart
+        let reloaded = CertificateAuthority::load_or_create(&config).unwrap();
+        assert_eq!(reloaded.info().certificate_pem, ca.info().certificate_pem);
+        let nodes = reloaded.enrolled_nodes();
+        assert_eq!((nodes.len(), nodes[0].renewals, nodes[0].renewal_token_sha256.as_str()), (1, 1, ""));
+        assert!(reloaded.request_certificate(request("node-1", Some(renewed.renewal_token))).is_ok());
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn test_join_requests_wait_for_approval() {
+        let config = CaConfig { require_approval: true, ..config("approval") };
+        let ca = CertificateAuthority::load_or_create(&config).unwrap();
+
+        let CertificateDecision::Pending(pending) = ca.request_certificate(request("node-2", None)).unwrap() else {
+            panic!("request was not queued");
+        };
+        let CertificateDecision::Pending(again) = ca.request_certificate(request("node-2", None)).unwrap() else {
+            panic!("request was not queued");
+        };
+        assert_eq!(again.id, pending.id);
+        assert!(again.pickup_token.is_none());
+        let pickup = CertificateRequest { pickup_token: pending.pickup_token.clone(), ..request("node-2", None) };
+        assert!(matches!(ca.request_certificate(pickup.clone()).unwrap(), CertificateDecision::Pending(_)));
+        let CertificateDecision::Pending(other) = ca.request_certificate(request("node-3", None)).unwrap() else {
+            panic!("request was not queued");
+        };
+        assert_eq!(ca.pending_requests().len(), 2);
+        assert!(ca.pending_requests().iter().all(|pending| pending.pickup_token.is_none()));
+
+        let enrolled = ca.approve(&pending.id).unwrap();
+        assert_eq!(enrolled.node_id, "node-2");
+        assert!(enrolled.renewal_token_sha256.is_empty());
+        let CertificateDecision::Issued(approved) = ca.request_certificate(pickup.clone()).unwrap() else {
+            panic!("approved certificate was not collected");
+        };
+        assert_eq!(approved.serial, enrolled.serial);
+        assert!(ca.request_certificate(pickup).unwrap_err().to_string().starts_with("Certificate request not found"));
+        ca.reject(&other.id).unwrap();
+        assert!(ca.pending_requests().is_empty());
+        assert!(ca.approve(&other.id).unwrap_err().to_string().starts_with("Certificate request not found"));
+        // Renewals skip the approval
+        assert!(matches!(
+            ca.request_certificate(request("node-2", Some(approved.renewal_token))).unwrap(),
+            CertificateDecision::Issued(_)
+        ));
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_ca_is_imported_and_local_certificate_is_kept() {
+        let config = config("external");
+        std::fs::create_dir_all(&config.dir).unwrap();
+        let (certificate_pem, key_pem) = generate_ca().unwrap();
+        let (certificate_file, key_file) = (config.dir.join("corp-ca.crt"), config.dir.join("corp-ca.key"));
+        std::fs::write(&certificate_file, &certificate_pem).unwrap();
+        std::fs::write(&key_file, &key_pem).unwrap();
+
+        let external = CaConfig { external: Some(ExternalCa { certificate_file, key_file: key_file.clone() }), ..config.clone() };
+        let ca = CertificateAuthority::load_or_create(&external).unwrap();
+        assert_eq!(ca.info().origin, CaOrigin::External);
+        assert_eq!(ca.info().certificate_pem, certificate_pem);
+        assert!(!config.dir.join("ca.crt").exists());
+
+        let local = ca.local_certificate("node-1", &["db1.internal".to_string()]).unwrap();
+        assert_eq!(ca.local_certificate("node-1", &[]).unwrap().serial, local.serial);
+        assert_ne!(ca.local_certificate("node-9", &[]).unwrap().serial, local.serial);
+        assert!(config.dir.join("node.key").exists());
+
+        // A key that does not match the certificate is refused
+        let (_, other_key) = generate_ca().unwrap();
+        std::fs::write(&key_file, other_key).unwrap();
+        let error = CertificateAuthority::load_or_create(&external).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid CA key"), "{}", error);
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+}
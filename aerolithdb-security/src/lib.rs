@@ -28,6 +28,12 @@
 //! - `AuditLevel`: Configurable audit logging granularity
 //! - `ComplianceMode`: Regulatory framework enforcement
 //! - `EncryptionAlgorithm`: Cryptographic algorithm selection
+//! - `DataEncryption`: Payload encryption at rest with rotating derived keys
+//! - `EncryptionBenchmark`: Startup cipher benchmark behind `EncryptionAlgorithm::Auto`
+//! - `CertificateAuthority`: Built-in or imported cluster CA issuing node certificates
+//! - `AdmissionControl`: Allowlist, denylist and operator approval for joining nodes
+//! - `PostureReport`: Scored self-assessment of the configuration with remediation steps
+//! - `Redactor`: Scrubbing of secrets and personal data from logs and error messages
 //! 
 //! ## Operational Considerations
 //! 
@@ -55,6 +61,31 @@
 use anyhow::Result;    // Unified error handling for security operations
 use tracing::info;     // Structured logging for security events and auditing
 use serde::{Serialize, Deserialize};  // Serialization support for configuration
+use std::path::PathBuf;
+use std::sync::Arc;
+
+mod admission;     // Allow/deny lists and approval of joining nodes
+mod benchmark;     // Cipher throughput measured on the host
+mod ca;            // Cluster certificate authority for node mTLS
+mod encryption;    // Authenticated encryption of payloads at rest
+mod posture;       // Self-assessment against deployment best practices
+mod redaction;     // Secret and PII scrubbing of logs and error messages
+
+pub use admission::{
+    join_proof_message, AccessList, AccessLists, AccessRule, AdmissionConfig, AdmissionControl, AdmissionDecision,
+    DeniedJoinAttempts, IdentityKind, JoinCandidate, PendingJoin,
+};
+pub use benchmark::{CipherThroughput, EncryptionBenchmark, BENCHMARK_DURATION, BENCHMARK_PAYLOAD_LEN};
+pub use ca::{
+    CaConfig, CaInfo, CaOrigin, CertificateAuthority, CertificateDecision, CertificateRequest, EnrolledNode, ExternalCa,
+    IssuedCertificate, PendingCertificateRequest, CA_COMMON_NAME, ROTATION_CHECK_INTERVAL,
+};
+pub use encryption::{DataEncryption, MASTER_KEY_LEN};
+pub use posture::{
+    required_audit_level, scan_plugins, CheckStatus, PluginArtifact, PostureCheck, PostureEnvironment, PostureReport,
+    MAX_KEY_ROTATION_INTERVAL, PLUGIN_SIGNATURE_EXTENSION,
+};
+pub use redaction::{install_redactor, redact, RedactingMakeWriter, RedactingWriter, RedactionConfig, Redactor, REDACTED};
 
 /// Comprehensive security configuration for aerolithsDB's zero-trust architecture.
 /// 
@@ -69,6 +100,11 @@ pub struct SecurityConfig {
     
     /// Primary encryption algorithm for data at rest and in transit
     pub encryption_algorithm: EncryptionAlgorithm,
+
+    /// Algorithms `EncryptionAlgorithm::Auto` chooses the fastest of at
+    /// startup; leave out those the deployment must not use
+    #[serde(default = "default_auto_encryption_candidates")]
+    pub auto_encryption_candidates: Vec<EncryptionAlgorithm>,
     
     /// Interval for automatic cryptographic key rotation to maintain security
     /// Shorter intervals improve security but increase operational overhead
@@ -79,13 +115,31 @@ pub struct SecurityConfig {
     
     /// Compliance framework to adhere to (affects data handling and retention)
     pub compliance_mode: ComplianceMode,
+
+    /// File holding the 32-byte master key that data-at-rest keys are derived
+    /// from, generated on first start if missing. Encryption at rest is
+    /// unavailable when unset. Nodes replicating to each other must share it.
+    #[serde(default)]
+    pub master_key_file: Option<PathBuf>,
+
+    /// Secrets and personal data scrubbed from logs and error messages
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+
+    /// Whether and how this node acts as the cluster certificate authority
+    #[serde(default)]
+    pub ca: CaConfig,
+
+    /// Which nodes may join the cluster, and whether they need approval
+    #[serde(default)]
+    pub admission: AdmissionConfig,
 }
 
 /// Audit logging levels for security events and access tracking.
 /// 
 /// Higher levels provide more detailed forensic capabilities but
 /// consume more storage and processing resources.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AuditLevel {
     /// No audit logging - not recommended for production
     None,
@@ -135,7 +189,7 @@ pub enum ComplianceMode {
 /// 
 /// Each algorithm provides different trade-offs between security strength,
 /// performance characteristics, and implementation complexity.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EncryptionAlgorithm {
     /// AES-256 in Galois/Counter Mode - industry standard, hardware acceleration
     /// Best for: General purpose encryption with hardware AES support
@@ -148,6 +202,11 @@ pub enum EncryptionAlgorithm {
     /// XChaCha20-Poly1305 - extended nonce version of ChaCha20-Poly1305
     /// Best for: High-volume encryption, eliminates nonce reuse concerns
     XChaCha20Poly1305,
+
+    /// The fastest of `SecurityConfig::auto_encryption_candidates`, measured
+    /// on the host at startup
+    /// Best for: Fleets mixing hardware with and without AES instructions
+    Auto,
 }
 
 impl Default for EncryptionAlgorithm {
@@ -156,6 +215,20 @@ impl Default for EncryptionAlgorithm {
     }
 }
 
+impl EncryptionAlgorithm {
+    /// AES-256-GCM with hardware AES support, ChaCha20-Poly1305 without.
+    pub fn preferred(hardware_aes: bool) -> Self {
+        match hardware_aes {
+            true => EncryptionAlgorithm::AES256GCM,
+            false => EncryptionAlgorithm::ChaCha20Poly1305,
+        }
+    }
+}
+
+fn default_auto_encryption_candidates() -> Vec<EncryptionAlgorithm> {
+    vec![EncryptionAlgorithm::AES256GCM, EncryptionAlgorithm::ChaCha20Poly1305]
+}
+
 impl Default for SecurityConfig {
     /// Creates a security configuration with conservative defaults suitable for development.
     /// 
@@ -178,9 +251,14 @@ impl Default for SecurityConfig {
         Self {
             zero_trust: false,                                                    // Disabled for development ease
             encryption_algorithm: EncryptionAlgorithm::default(),               // AES-256-GCM - hardware optimized
+            auto_encryption_candidates: default_auto_encryption_candidates(),   // Both ciphers the envelope supports
             key_rotation_interval: std::time::Duration::from_secs(86400),        // 24 hours - balanced security/ops
             audit_level: AuditLevel::default(),                                  // Basic level - essential monitoring
             compliance_mode: ComplianceMode::None,                              // No frameworks - minimize complexity
+            master_key_file: None,                                               // No encryption at rest - no key to manage
+            redaction: RedactionConfig::default(),                               // Built-in secret and PII patterns
+            ca: CaConfig::default(),                                             // Not a certificate authority
+            admission: AdmissionConfig::default(),                               // Admit every node not denied
         }
     }
 }
@@ -226,6 +304,44 @@ impl Default for SecurityConfig {
 pub struct SecurityFramework {
     /// Security configuration defining policies and operational parameters
     config: SecurityConfig,
+
+    /// Payload encryption at rest, present when a master key is configured
+    data_encryption: Option<Arc<DataEncryption>>,
+
+    /// Algorithm in effect, with `Auto` resolved
+    encryption_algorithm: EncryptionAlgorithm,
+
+    /// Startup benchmark that resolved `Auto`
+    encryption_benchmark: Option<EncryptionBenchmark>,
+
+    /// Cluster CA, present when this node issues node certificates
+    certificate_authority: Option<Arc<CertificateAuthority>>,
+
+    /// Allowlist, denylist and pending approvals for joining nodes
+    admission: Arc<AdmissionControl>,
+}
+
+/// Security posture of a running node, as reported by the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityStatus {
+    pub zero_trust: bool,
+    pub audit_level: AuditLevel,
+    pub compliance_mode: ComplianceMode,
+
+    /// Algorithm set in the configuration, possibly `Auto`
+    pub configured_algorithm: EncryptionAlgorithm,
+
+    /// Algorithm new payloads are sealed with
+    pub encryption_algorithm: EncryptionAlgorithm,
+
+    /// Whether a master key is loaded for encryption at rest
+    pub encryption_at_rest: bool,
+
+    /// Key new payloads are sealed with, when encrypting at rest
+    pub active_key_id: Option<String>,
+
+    /// Benchmark that chose the algorithm, when it was `Auto`
+    pub benchmark: Option<EncryptionBenchmark>,
 }
 
 impl SecurityFramework {    /// Initialize a new security framework instance with the specified configuration.
@@ -266,12 +382,98 @@ impl SecurityFramework {    /// Initialize a new security framework instance wit
         // Log security configuration for audit trail
         info!("Security configuration - Key rotation: {:?}, Audit level: {:?}, Compliance: {:?}", 
               config.key_rotation_interval, config.audit_level, config.compliance_mode);
+
+        let encryption_benchmark = match config.encryption_algorithm {
+            EncryptionAlgorithm::Auto => {
+                let benchmark = EncryptionBenchmark::run(&config.auto_encryption_candidates, BENCHMARK_DURATION);
+                for result in &benchmark.results {
+                    info!("Encryption benchmark - {:?}: {:.0} MiB/s", result.algorithm, result.bytes_per_second / (1024.0 * 1024.0));
+                }
+                info!("Selected {:?} for encryption (hardware AES: {})", benchmark.selected, benchmark.hardware_aes);
+                Some(benchmark)
+            }
+            _ => None,
+        };
+        let encryption_algorithm = match &encryption_benchmark {
+            Some(benchmark) => benchmark.selected.clone(),
+            None => config.encryption_algorithm.clone(),
+        };
+
+        let data_encryption = match &config.master_key_file {
+            Some(path) => {
+                let encryption = DataEncryption::load_or_create(
+                    path,
+                    &encryption_algorithm,
+                    config.key_rotation_interval,
+                )?;
+                info!("Data-at-rest encryption enabled with {:?}", encryption_algorithm);
+                Some(Arc::new(encryption))
+            }
+            None => None,
+        };
+
+        let certificate_authority = match config.ca.enabled {
+            true => Some(Arc::new(CertificateAuthority::load_or_create(&config.ca)?)),
+            false => None,
+        };
+
+        let admission = Arc::new(AdmissionControl::load(&config.admission)?);
         
         Ok(Self {
             config: config.clone(),
+            data_encryption,
+            encryption_algorithm,
+            encryption_benchmark,
+            certificate_authority,
+            admission,
         })
     }
 
+    /// Algorithm new payloads are sealed with, `Auto` resolved by the
+    /// startup benchmark.
+    pub fn encryption_algorithm(&self) -> &EncryptionAlgorithm {
+        &self.encryption_algorithm
+    }
+
+    /// Startup benchmark, when the configured algorithm is `Auto`.
+    pub fn encryption_benchmark(&self) -> Option<&EncryptionBenchmark> {
+        self.encryption_benchmark.as_ref()
+    }
+
+    /// Current security posture of the node.
+    pub fn status(&self) -> SecurityStatus {
+        SecurityStatus {
+            zero_trust: self.config.zero_trust,
+            audit_level: self.config.audit_level.clone(),
+            compliance_mode: self.config.compliance_mode.clone(),
+            configured_algorithm: self.config.encryption_algorithm.clone(),
+            encryption_algorithm: self.encryption_algorithm.clone(),
+            encryption_at_rest: self.data_encryption.is_some(),
+            active_key_id: self.data_encryption.as_ref().map(|encryption| encryption.active_key_id()),
+            benchmark: self.encryption_benchmark.clone(),
+        }
+    }
+
+    /// Cluster CA, or `None` when this node does not issue certificates.
+    pub fn certificate_authority(&self) -> Option<&Arc<CertificateAuthority>> {
+        self.certificate_authority.as_ref()
+    }
+
+    /// Admission control screening nodes that join the cluster.
+    pub fn admission(&self) -> &Arc<AdmissionControl> {
+        &self.admission
+    }
+
+    /// Score the running configuration against deployment best practices.
+    pub fn posture(&self, environment: &PostureEnvironment) -> PostureReport {
+        posture::assess(&self.config, &self.encryption_algorithm, self.data_encryption.as_deref(), environment)
+    }
+
+    /// Payload encryption at rest, or `None` when no master key is configured.
+    pub fn data_encryption(&self) -> Option<&Arc<DataEncryption>> {
+        self.data_encryption.as_ref()
+    }
+
     /// Start the security framework and begin active security operations.
     /// 
     /// This method activates all security subsystems and begins enforcing
@@ -302,7 +504,7 @@ impl SecurityFramework {    /// Initialize a new security framework instance wit
         
         // Log security framework startup for compliance and audit requirements
         info!("Security framework active - encryption: {:?}, audit: {:?}", 
-              self.config.encryption_algorithm, self.config.audit_level);
+              self.encryption_algorithm, self.config.audit_level);
         
         Ok(())
     }
@@ -0,0 +1,357 @@
+//! # Security Posture
+//!
+//! A self-assessment of the running configuration against deployment best
+//! practices. Each check passes, warns or fails, and carries the steps that
+//! fix it; the weighted results add up to a score out of 100.
+//!
+//! | Check                | Weight | Passes when                                               |
+//! |----------------------|--------|-----------------------------------------------------------|
+//! | `transport_tls`      | 25     | Clients reach the node over TLS                           |
+//! | `zero_trust`         | 20     | Zero-trust mode is on                                     |
+//! | `encryption_at_rest` | 15     | A master key is loaded                                    |
+//! | `key_rotation`       | 15     | Keys rotate at least every 90 days                        |
+//! | `audit_level`        | 15     | The audit level meets the compliance mode                 |
+//! | `unsigned_plugins`   | 10     | Every plugin library ships a detached signature           |
+//!
+//! The API servers speak plain HTTP, so TLS is terminated by a proxy in
+//! front of them; the caller of the assessment says whether it was. Plugin
+//! libraries count as signed when a `<library>.sig` file sits beside them.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::DataEncryption;
+use crate::{AuditLevel, ComplianceMode, EncryptionAlgorithm, SecurityConfig};
+
+/// Longest key rotation interval considered recent
+pub const MAX_KEY_ROTATION_INTERVAL: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Extension of the detached signature beside a plugin library
+pub const PLUGIN_SIGNATURE_EXTENSION: &str = "sig";
+
+/// Extensions of dynamically loaded plugin libraries
+const PLUGIN_LIBRARY_EXTENSIONS: &[&str] = &["so", "dll", "dylib"];
+
+/// Outcome of one posture check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    /// Acceptable for some deployments, worth reviewing
+    Warn,
+    Fail,
+}
+
+/// One best practice and how the node measures up to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostureCheck {
+    pub id: String,
+    pub title: String,
+    pub status: CheckStatus,
+
+    /// Share of the score this check is worth
+    pub weight: u32,
+
+    /// What was found
+    pub detail: String,
+
+    /// How to fix it, unless the check passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+/// Scored result of a posture assessment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostureReport {
+    /// Weighted score out of 100; warnings count half
+    pub score: u32,
+    pub checks: Vec<PostureCheck>,
+
+    /// Remediation steps of the checks that did not pass, failures first
+    /// and heavier checks before lighter ones
+    pub remediation: Vec<String>,
+}
+
+/// A plugin library found in the plugin directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginArtifact {
+    pub path: PathBuf,
+
+    /// Whether a detached signature sits beside the library
+    pub signed: bool,
+}
+
+/// What the assessment needs to know beyond the security configuration.
+#[derive(Debug, Clone, Default)]
+pub struct PostureEnvironment {
+    /// Whether clients reach the node over TLS
+    pub transport_tls: bool,
+
+    /// Plugin libraries the node would load
+    pub plugins: Vec<PluginArtifact>,
+}
+
+/// Plugin libraries in `dir`, sorted by path; a missing directory holds none.
+pub fn scan_plugins(dir: &Path) -> Result<Vec<PluginArtifact>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow::anyhow!("Failed to read plugin directory {}: {}", dir.display(), e)),
+    };
+
+    let mut plugins = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_library = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| PLUGIN_LIBRARY_EXTENSIONS.contains(&extension));
+        if is_library && path.is_file() {
+            let mut signature = path.clone().into_os_string();
+            signature.push(".");
+            signature.push(PLUGIN_SIGNATURE_EXTENSION);
+            plugins.push(PluginArtifact { signed: Path::new(&signature).is_file(), path });
+        }
+    }
+    plugins.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(plugins)
+}
+
+/// Lowest audit level a compliance framework calls for
+pub fn required_audit_level(mode: &ComplianceMode) -> AuditLevel {
+    match mode {
+        ComplianceMode::None => AuditLevel::Basic,
+        ComplianceMode::GDPR | ComplianceMode::HIPAA | ComplianceMode::SOX | ComplianceMode::PCIDSS => AuditLevel::Full,
+    }
+}
+
+/// Assess `config`, with `encryption` the loaded data-at-rest encryption
+/// sealing with `algorithm`.
+pub(crate) fn assess(
+    config: &SecurityConfig,
+    algorithm: &EncryptionAlgorithm,
+    encryption: Option<&DataEncryption>,
+    environment: &PostureEnvironment,
+) -> PostureReport {
+    let checks = vec![
+        transport_tls(environment),
+        zero_trust(config),
+        encryption_at_rest(algorithm, encryption),
+        key_rotation(encryption),
+        audit_level(config),
+        unsigned_plugins(environment),
+    ];
+
+    let total: u32 = checks.iter().map(|check| check.weight * 2).sum();
+    let earned: u32 = checks
+        .iter()
+        .map(|check| match check.status {
+            CheckStatus::Pass => check.weight * 2,
+            CheckStatus::Warn => check.weight,
+            CheckStatus::Fail => 0,
+        })
+        .sum();
+    let score = (earned * 100 + total / 2) / total.max(1);
+
+    let mut pending: Vec<&PostureCheck> = checks.iter().filter(|check| check.remediation.is_some()).collect();
+    pending.sort_by_key(|check| (check.status != CheckStatus::Fail, std::cmp::Reverse(check.weight)));
+    let remediation = pending.into_iter().filter_map(|check| check.remediation.clone()).collect();
+
+    PostureReport { score, checks, remediation }
+}
+
+fn check(id: &str, title: &str, weight: u32, status: CheckStatus, detail: String, remediation: &str) -> PostureCheck {
+    PostureCheck {
+        id: id.to_string(),
+        title: title.to_string(),
+        status,
+        weight,
+        detail,
+        remediation: (status != CheckStatus::Pass).then(|| remediation.to_string()),
+    }
+}
+
+fn days(duration: Duration) -> String {
+    format!("{:.1} days", duration.as_secs_f64() / 86_400.0)
+}
+
+fn transport_tls(environment: &PostureEnvironment) -> PostureCheck {
+    let (status, detail) = match environment.transport_tls {
+        true => (CheckStatus::Pass, "Clients reach the node over TLS".to_string()),
+        false => (CheckStatus::Fail, "Clients reach the node over plain HTTP".to_string()),
+    };
+    check(
+        "transport_tls",
+        "TLS on client connections",
+        25,
+        status,
+        detail,
+        "Terminate TLS in a proxy or load balancer in front of the API ports and have it set X-Forwarded-Proto: https",
+    )
+}
+
+fn zero_trust(config: &SecurityConfig) -> PostureCheck {
+    let (status, detail) = match config.zero_trust {
+        true => (CheckStatus::Pass, "Every request is authenticated and authorized".to_string()),
+        false => (CheckStatus::Fail, "Zero-trust mode is off".to_string()),
+    };
+    check("zero_trust", "Zero-trust mode", 20, status, detail, "Set security.zero_trust to true")
+}
+
+fn encryption_at_rest(algorithm: &EncryptionAlgorithm, encryption: Option<&DataEncryption>) -> PostureCheck {
+    let (status, detail) = match encryption {
+        Some(_) => (CheckStatus::Pass, format!("Documents are sealed with {:?}", algorithm)),
+        None => (CheckStatus::Fail, "No master key is loaded; documents are stored in the clear".to_string()),
+    };
+    check(
+        "encryption_at_rest",
+        "Encryption at rest",
+        15,
+        status,
+        detail,
+        "Set security.master_key_file so documents are encrypted at rest",
+    )
+}
+
+fn key_rotation(encryption: Option<&DataEncryption>) -> PostureCheck {
+    let (status, detail) = match encryption {
+        None => (CheckStatus::Warn, "No data-at-rest keys to rotate".to_string()),
+        Some(encryption) => {
+            let interval = encryption.rotation_interval();
+            let age = encryption.active_key_age();
+            let detail = format!(
+                "Key {} is {} old and rotates every {}",
+                encryption.active_key_id(),
+                days(age),
+                days(interval)
+            );
+            match interval <= MAX_KEY_ROTATION_INTERVAL && age <= MAX_KEY_ROTATION_INTERVAL {
+                true => (CheckStatus::Pass, detail),
+                false => (CheckStatus::Warn, detail),
+            }
+        }
+    };
+    check(
+        "key_rotation",
+        "Recent key rotation",
+        15,
+        status,
+        detail,
+        "Enable encryption at rest and set security.key_rotation_interval to 90 days or less",
+    )
+}
+
+fn audit_level(config: &SecurityConfig) -> PostureCheck {
+    let required = required_audit_level(&config.compliance_mode);
+    let detail = format!(
+        "Audit level {:?} for compliance mode {:?}, which calls for {:?}",
+        config.audit_level, config.compliance_mode, required
+    );
+    let status = match config.audit_level >= required {
+        true => CheckStatus::Pass,
+        false => CheckStatus::Fail,
+    };
+    let remediation = format!("Set security.audit_level to {:?} or higher", required);
+    check("audit_level", "Audit level for the compliance mode", 15, status, detail, &remediation)
+}
+
+fn unsigned_plugins(environment: &PostureEnvironment) -> PostureCheck {
+    let unsigned: Vec<String> = environment
+        .plugins
+        .iter()
+        .filter(|plugin| !plugin.signed)
+        .map(|plugin| plugin.path.display().to_string())
+        .collect();
+    let (status, detail) = match unsigned.is_empty() {
+        true => (CheckStatus::Pass, format!("{} plugin libraries, all signed", environment.plugins.len())),
+        false => (CheckStatus::Fail, format!("Unsigned plugin libraries: {}", unsigned.join(", "))),
+    };
+    check(
+        "unsigned_plugins",
+        "No unsigned plugins",
+        10,
+        status,
+        detail,
+        "Remove unsigned libraries from the plugin directory or place a detached .sig signature beside each",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(report: &PostureReport, id: &str) -> CheckStatus {
+        report.checks.iter().find(|check| check.id == id).unwrap().status
+    }
+
+    #[test]
+    fn test_default_configuration_scores_low_with_remediation() {
+        let report = assess(&SecurityConfig::default(), &EncryptionAlgorithm::AES256GCM, None, &PostureEnvironment::default());
+
+        assert_eq!(status(&report, "transport_tls"), CheckStatus::Fail);
+        assert_eq!(status(&report, "zero_trust"), CheckStatus::Fail);
+        assert_eq!(status(&report, "encryption_at_rest"), CheckStatus::Fail);
+        assert_eq!(status(&report, "key_rotation"), CheckStatus::Warn);
+        assert_eq!(status(&report, "audit_level"), CheckStatus::Pass);
+        assert_eq!(status(&report, "unsigned_plugins"), CheckStatus::Pass);
+        // (15 + 2 * 15 + 2 * 10) of 200
+        assert_eq!(report.score, 33);
+        assert_eq!(report.remediation.len(), 4);
+        assert!(report.remediation[0].contains("TLS"));
+        assert!(report.remediation[3].contains("key_rotation_interval"));
+    }
+
+    #[test]
+    fn test_hardened_configuration_scores_full() {
+        let config = SecurityConfig {
+            zero_trust: true,
+            audit_level: AuditLevel::Full,
+            compliance_mode: ComplianceMode::HIPAA,
+            key_rotation_interval: Duration::from_secs(7 * 24 * 60 * 60),
+            ..Default::default()
+        };
+        let encryption = DataEncryption::new(&[7u8; crate::MASTER_KEY_LEN], &config.encryption_algorithm, config.key_rotation_interval);
+        let environment = PostureEnvironment { transport_tls: true, plugins: Vec::new() };
+
+        let report = assess(&config, &config.encryption_algorithm, Some(&encryption), &environment);
+        assert_eq!(report.score, 100);
+        assert!(report.remediation.is_empty());
+        assert!(report.checks.iter().all(|check| check.remediation.is_none()));
+
+        let lax = SecurityConfig { audit_level: AuditLevel::Basic, key_rotation_interval: Duration::from_secs(365 * 24 * 60 * 60), ..config };
+        let encryption = DataEncryption::new(&[7u8; crate::MASTER_KEY_LEN], &lax.encryption_algorithm, lax.key_rotation_interval);
+        let report = assess(&lax, &lax.encryption_algorithm, Some(&encryption), &environment);
+        assert_eq!(status(&report, "audit_level"), CheckStatus::Fail);
+        assert_eq!(status(&report, "key_rotation"), CheckStatus::Warn);
+    }
+
+    #[test]
+    fn test_unsigned_plugin_libraries_are_reported() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-posture-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in ["signed.so", "signed.so.sig", "unsigned.dll", "README.md"] {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+
+        let plugins = scan_plugins(&dir).unwrap();
+        assert_eq!(
+            plugins,
+            vec![
+                PluginArtifact { path: dir.join("signed.so"), signed: true },
+                PluginArtifact { path: dir.join("unsigned.dll"), signed: false },
+            ]
+        );
+        let environment = PostureEnvironment { transport_tls: false, plugins };
+        let report = assess(&SecurityConfig::default(), &EncryptionAlgorithm::AES256GCM, None, &environment);
+        let check = report.checks.iter().find(|check| check.id == "unsigned_plugins").unwrap();
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.contains("unsigned.dll") && !check.detail.contains("signed.so"));
+
+        assert!(scan_plugins(&dir.join("missing")).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
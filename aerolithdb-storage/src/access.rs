@@ -0,0 +1,243 @@
+//! # Access Frequency Tracking
+//!
+//! Reads of documents are sampled: one in every
+//! [`AccessTrackingConfig::sample_rate`] reads is recorded, counting for
+//! that many reads, so a read costs no more than an atomic increment unless
+//! it is sampled. Each document keeps its estimated read count, the time of
+//! its last sampled read and a read count decaying with
+//! [`AccessTrackingConfig::half_life`], from which its current reads per day
+//! are estimated.
+//!
+//! Statistics are kept in memory and written to the metadata database every
+//! [`AccessTrackingConfig::persist_interval`] and when storage stops, so a
+//! restart loses at most one interval of reads. Statistics of documents that
+//! no longer exist are dropped when they are persisted.
+//!
+//! Sampled reads also feed two [heavy hitter](crate::HeavyHitters) trackers,
+//! one of documents and one of collections, reporting the hottest of either
+//! over sliding windows of up to an hour by default. They are kept in memory
+//! only.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::{DashMap, DashSet};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{HeavyHitter, HeavyHitterConfig, HeavyHitters, MetadataStore, StorageHierarchy};
+
+/// How reads are sampled and how long their statistics take to fade.
+#[derive(Debug, Clone)]
+pub struct AccessTrackingConfig {
+    /// Record one in this many reads; 1 records every read
+    pub sample_rate: u64,
+
+    /// Time after which a read counts half towards the read rate
+    pub half_life: Duration,
+
+    /// Time between writes of the statistics to disk
+    pub persist_interval: Duration,
+
+    /// Tracking of the most read documents and collections
+    pub heavy_hitters: HeavyHitterConfig,
+}
+
+impl Default for AccessTrackingConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 10,
+            half_life: Duration::from_secs(24 * 3600),
+            persist_interval: Duration::from_secs(60),
+            heavy_hitters: HeavyHitterConfig::default(),
+        }
+    }
+}
+
+/// Read statistics of one document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessStats {
+    /// Estimated reads since tracking began
+    pub reads: u64,
+
+    /// Time of the last sampled read
+    pub last_accessed: DateTime<Utc>,
+
+    /// Estimated reads per day, weighted towards recent reads
+    pub reads_per_day: f64,
+}
+
+/// Persisted read statistics of one document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AccessRecord {
+    reads: u64,
+    last_accessed: DateTime<Utc>,
+
+    /// Read count decayed to `last_accessed`
+    decayed_reads: f64,
+}
+
+/// Sampled read statistics by `collection:document_id`.
+#[derive(Debug)]
+pub(crate) struct AccessTracker {
+    config: AccessTrackingConfig,
+    records: DashMap<String, AccessRecord>,
+
+    /// Keys changed since the last persist
+    dirty: DashSet<String>,
+
+    /// Reads seen, sampled or not
+    counter: AtomicU64,
+
+    /// Most read documents by `collection:document_id`
+    hot_documents: HeavyHitters,
+
+    /// Most read collections
+    hot_collections: HeavyHitters,
+
+    tree: sled::Tree,
+}
+
+impl AccessTracker {
+    pub(crate) fn open(db: &sled::Db, config: &AccessTrackingConfig) -> Result<Self> {
+        let tracker = Self {
+            config: AccessTrackingConfig { sample_rate: config.sample_rate.max(1), ..config.clone() },
+            records: DashMap::new(),
+            dirty: DashSet::new(),
+            counter: AtomicU64::new(0),
+            hot_documents: HeavyHitters::new(config.heavy_hitters.clone()),
+            hot_collections: HeavyHitters::new(config.heavy_hitters.clone()),
+            tree: db.open_tree("access_stats")?,
+        };
+
+        for item in tracker.tree.iter() {
+            let (key, value) = item?;
+            let key = String::from_utf8_lossy(&key).into_owned();
+            match serde_json::from_slice::<AccessRecord>(&value) {
+                Ok(record) => {
+                    tracker.records.insert(key, record);
+                }
+                Err(e) => warn!("Skipping unreadable access statistics of {}: {}", key, e),
+            }
+        }
+
+        Ok(tracker)
+    }
+
+    /// Count a read of `key` if it is sampled.
+    pub(crate) fn record(&self, key: &str) {
+        let sample_rate = self.config.sample_rate;
+        if !self.counter.fetch_add(1, Ordering::Relaxed).is_multiple_of(sample_rate) {
+            return;
+        }
+
+        let now = Utc::now();
+        let mut record = self.records.entry(key.to_string()).or_insert_with(|| AccessRecord {
+            reads: 0,
+            last_accessed: now,
+            decayed_reads: 0.0,
+        });
+        record.decayed_reads = self.decay(record.decayed_reads, record.last_accessed, now) + sample_rate as f64;
+        record.reads += sample_rate;
+        record.last_accessed = now;
+        drop(record);
+        self.dirty.insert(key.to_string());
+
+        self.hot_documents.record(key, sample_rate, now);
+        if let Some((collection, _)) = key.split_once(':') {
+            self.hot_collections.record(collection, sample_rate, now);
+        }
+    }
+
+    /// The `limit` most read documents over `window`, keyed by
+    /// `collection:document_id`.
+    pub(crate) fn hot_documents(&self, limit: usize, window: Duration) -> Vec<HeavyHitter> {
+        self.hot_documents.top(limit, window, Utc::now())
+    }
+
+    /// Longest window the hottest documents can be reported over
+    pub(crate) fn hot_window(&self) -> Duration {
+        self.hot_documents.span()
+    }
+
+    /// Read statistics of `key` as of `now`.
+    pub(crate) fn stats(&self, key: &str, now: DateTime<Utc>) -> Option<AccessStats> {
+        self.records.get(key).map(|record| self.stats_of(&record, now))
+    }
+
+    fn stats_of(&self, record: &AccessRecord, now: DateTime<Utc>) -> AccessStats {
+        let half_life_days = self.config.half_life.as_secs_f64().max(1.0) / 86_400.0;
+        AccessStats {
+            reads: record.reads,
+            last_accessed: record.last_accessed,
+            reads_per_day: self.decay(record.decayed_reads, record.last_accessed, now) * std::f64::consts::LN_2
+                / half_life_days,
+        }
+    }
+
+    /// `reads` counted at `since`, decayed to `now`
+    fn decay(&self, reads: f64, since: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+        let elapsed = (now - since).to_std().unwrap_or_default().as_secs_f64();
+        reads * 0.5f64.powf(elapsed / self.config.half_life.as_secs_f64().max(1.0))
+    }
+
+    /// Write changed statistics to disk, dropping those of documents
+    /// without metadata.
+    pub(crate) fn persist(&self, metadata_store: &MetadataStore) -> Result<()> {
+        let keys: Vec<String> = self.dirty.iter().map(|key| key.clone()).collect();
+        let mut batch = sled::Batch::default();
+        for key in &keys {
+            self.dirty.remove(key);
+            let record = self.records.get(key).map(|record| serde_json::to_vec(&*record)).transpose()?;
+            if let Some(record) = record {
+                batch.insert(key.as_bytes(), record);
+            }
+        }
+
+        let stale: Vec<String> = self
+            .records
+            .iter()
+            .filter(|record| metadata_store.get(record.key()).is_none())
+            .map(|record| record.key().clone())
+            .collect();
+        for key in &stale {
+            self.records.remove(key);
+            batch.remove(key.as_bytes());
+        }
+
+        self.tree.apply_batch(batch)?;
+        debug!("Persisted access statistics of {} documents, dropped {}", keys.len(), stale.len());
+        Ok(())
+    }
+
+    /// Totals over all documents as of `now`: estimated reads, reads per
+    /// day and documents with statistics.
+    pub(crate) fn totals(&self, now: DateTime<Utc>) -> (u64, f64, u64) {
+        self.records.iter().fold((0, 0.0, 0), |(reads, per_day, tracked), record| {
+            let stats = self.stats_of(&record, now);
+            (reads + stats.reads, per_day + stats.reads_per_day, tracked + 1)
+        })
+    }
+}
+
+impl StorageHierarchy {
+    /// Read statistics of a document, if a read of it has been sampled.
+    pub fn access_stats(&self, collection: &str, document_id: &str) -> Option<AccessStats> {
+        self.access.stats(&format!("{}:{}", collection, document_id), Utc::now())
+    }
+
+    /// The `limit` most read documents over the last `window`, heaviest
+    /// first, keyed by `collection:document_id`. Counts are estimated from
+    /// sampled reads and never below the reads sampled.
+    pub fn hot_documents(&self, limit: usize, window: Duration) -> Vec<HeavyHitter> {
+        self.access.hot_documents(limit, window)
+    }
+
+    /// The `limit` most read collections over the last `window`, heaviest
+    /// first.
+    pub fn hot_collections(&self, limit: usize, window: Duration) -> Vec<HeavyHitter> {
+        self.access.hot_collections.top(limit, window, Utc::now())
+    }
+}
@@ -52,6 +52,18 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 use tracing::{debug, info};
+use crate::archive::{ArchiveBackend, ArchiveBackendConfig, ArchiveConfig, LocalArchiveBackend, S3ArchiveBackend};
+
+/// Write all pending changes of a sled database to disk.
+///
+/// sled's `flush_async` can stall forever once many flushes are pending at
+/// once, as happens when a burst of writes replicates in the background, so
+/// the flush runs on the blocking thread pool instead.
+pub(crate) async fn flush_db(db: &sled::Db) -> Result<()> {
+    let db = db.clone();
+    tokio::task::spawn_blocking(move || db.flush()).await??;
+    Ok(())
+}
 
 /// High-performance in-memory cache storage backend (L1 tier).
 /// 
@@ -260,6 +272,30 @@ impl MemoryCache {
         Ok(())
     }
 
+    /// Store several `(shard_id, document_id, data)` entries under a single
+    /// acquisition of the cache lock.
+    pub async fn store_batch(&self, entries: &[(String, String, Vec<u8>)]) -> Result<()> {
+        debug!("Storing {} entries in memory cache", entries.len());
+
+        let mut cache = self.data.write().await;
+        for (shard_id, document_id, data) in entries {
+            cache.insert(format!("{}:{}", shard_id, document_id), data.clone());
+        }
+        Ok(())
+    }
+
+    /// Remove several `(shard_id, document_id)` entries under a single
+    /// acquisition of the cache lock.
+    pub async fn delete_batch(&self, entries: &[(String, String)]) -> Result<()> {
+        debug!("Deleting {} entries from memory cache", entries.len());
+
+        let mut cache = self.data.write().await;
+        for (shard_id, document_id) in entries {
+            cache.remove(&format!("{}:{}", shard_id, document_id));
+        }
+        Ok(())
+    }
+
     /// Evict expired entries from the cache based on TTL policies.
     /// 
     /// Performs periodic cleanup of expired cache entries to:
@@ -370,7 +406,7 @@ impl LocalSSDCache {
     pub async fn stop(&self) -> Result<()> {
         info!("Stopping local SSD cache");
         if let Some(db) = &self.db {
-            db.flush_async().await?;
+            flush_db(db).await?;
         }
         Ok(())
     }
@@ -381,7 +417,7 @@ impl LocalSSDCache {
         
         if let Some(db) = &self.db {
             db.insert(key.as_bytes(), data)?;
-            db.flush_async().await?;
+            flush_db(db).await?;
         }
         Ok(())
     }
@@ -408,6 +444,47 @@ impl LocalSSDCache {
         }
         Ok(())
     }
+
+    /// Store several `(shard_id, document_id, data)` entries with a single
+    /// write and flush
+    pub async fn store_batch(&self, entries: &[(String, String, Vec<u8>)]) -> Result<()> {
+        debug!("Storing {} entries in SSD cache", entries.len());
+
+        if let Some(db) = &self.db {
+            let mut batch = sled::Batch::default();
+            for (shard_id, document_id, data) in entries {
+                batch.insert(format!("{}:{}", shard_id, document_id).as_bytes(), data.as_slice());
+            }
+            db.apply_batch(batch)?;
+            flush_db(db).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete several `(shard_id, document_id)` entries with a single write
+    pub async fn delete_batch(&self, entries: &[(String, String)]) -> Result<()> {
+        debug!("Deleting {} entries from SSD cache", entries.len());
+
+        if let Some(db) = &self.db {
+            let mut batch = sled::Batch::default();
+            for (shard_id, document_id) in entries {
+                batch.remove(format!("{}:{}", shard_id, document_id).as_bytes());
+            }
+            db.apply_batch(batch)?;
+        }
+        Ok(())
+    }
+
+    /// Keys of all entries in SSD cache, formatted as `shard_id:document_id`
+    pub async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        if let Some(db) = &self.db {
+            for key in db.iter().keys() {
+                keys.push(String::from_utf8_lossy(&key?).into_owned());
+            }
+        }
+        Ok(keys)
+    }
 }
 
 /// Distributed storage backend
@@ -439,7 +516,7 @@ impl DistributedStorage {
     pub async fn stop(&self) -> Result<()> {
         info!("Stopping distributed storage");
         if let Some(db) = &self.db {
-            db.flush_async().await?;
+            flush_db(db).await?;
         }
         Ok(())
     }
@@ -450,7 +527,7 @@ impl DistributedStorage {
         
         if let Some(db) = &self.db {
             db.insert(key.as_bytes(), data)?;
-            db.flush_async().await?;
+            flush_db(db).await?;
         }
         Ok(())
     }
@@ -476,6 +553,47 @@ impl DistributedStorage {
             db.remove(key.as_bytes())?;
         }
         Ok(())
+    }
+
+    /// Store several `(shard_id, document_id, data)` entries with a single
+    /// write and flush
+    pub async fn store_batch(&self, entries: &[(String, String, Vec<u8>)]) -> Result<()> {
+        debug!("Storing {} entries in distributed storage", entries.len());
+
+        if let Some(db) = &self.db {
+            let mut batch = sled::Batch::default();
+            for (shard_id, document_id, data) in entries {
+                batch.insert(format!("{}:{}", shard_id, document_id).as_bytes(), data.as_slice());
+            }
+            db.apply_batch(batch)?;
+            flush_db(db).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete several `(shard_id, document_id)` entries with a single write
+    pub async fn delete_batch(&self, entries: &[(String, String)]) -> Result<()> {
+        debug!("Deleting {} entries from distributed storage", entries.len());
+
+        if let Some(db) = &self.db {
+            let mut batch = sled::Batch::default();
+            for (shard_id, document_id) in entries {
+                batch.remove(format!("{}:{}", shard_id, document_id).as_bytes());
+            }
+            db.apply_batch(batch)?;
+        }
+        Ok(())
+    }
+
+    /// Keys of all entries in distributed storage, formatted as `shard_id:document_id`
+    pub async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        if let Some(db) = &self.db {
+            for key in db.iter().keys() {
+                keys.push(String::from_utf8_lossy(&key?).into_owned());
+            }
+        }
+        Ok(keys)
     }    pub async fn compact(&self) -> Result<()> {
         debug!("Compacting distributed storage");
         // Storage compaction enhancement ready for implementation
@@ -486,22 +604,39 @@ impl DistributedStorage {
 /// Object storage backend for archival
 #[derive(Debug)]
 pub struct ObjectStorage {
-    data_dir: std::path::PathBuf,
-    db: Option<Arc<sled::Db>>,
+    backend: Arc<dyn ArchiveBackend>,
+    orphan_sweep_interval: Option<std::time::Duration>,
+    last_sweep: std::sync::Mutex<Option<std::time::Instant>>,
 }
 
 impl ObjectStorage {
     pub async fn new(data_dir: &std::path::Path) -> Result<Self> {
-        info!("Initializing object storage at: {:?}", data_dir);
-        
-        tokio::fs::create_dir_all(data_dir).await?;
-        
-        let db = sled::open(data_dir.join("object_storage"))?;
-        
-        Ok(Self {
-            data_dir: data_dir.to_path_buf(),
-            db: Some(Arc::new(db)),
-        })
+        Self::open(data_dir, &ArchiveConfig::default()).await
+    }
+
+    /// Open the backend chosen in `config`; the local backend lives in
+    /// `data_dir`
+    pub async fn open(data_dir: &std::path::Path, config: &ArchiveConfig) -> Result<Self> {
+        let backend: Arc<dyn ArchiveBackend> = match &config.backend {
+            ArchiveBackendConfig::Local => {
+                info!("Initializing object storage at: {:?}", data_dir);
+                Arc::new(LocalArchiveBackend::open(data_dir).await?)
+            }
+            ArchiveBackendConfig::S3(s3) => {
+                info!("Initializing object storage in bucket {} at {}", s3.bucket, s3.endpoint);
+                Arc::new(S3ArchiveBackend::new(s3)?)
+            }
+        };
+        Ok(Self::with_backend(backend, config))
+    }
+
+    /// Object storage kept in `backend`, swept as `config` says
+    pub fn with_backend(backend: Arc<dyn ArchiveBackend>, config: &ArchiveConfig) -> Self {
+        Self {
+            backend,
+            orphan_sweep_interval: config.orphan_sweep_interval,
+            last_sweep: std::sync::Mutex::new(None),
+        }
     }
 
     pub async fn start(&self) -> Result<()> {
@@ -511,43 +646,70 @@ impl ObjectStorage {
 
     pub async fn stop(&self) -> Result<()> {
         info!("Stopping object storage");
-        if let Some(db) = &self.db {
-            db.flush_async().await?;
-        }
-        Ok(())
+        self.backend.flush().await
     }
 
     pub async fn store(&self, shard_id: &str, document_id: &str, data: &[u8]) -> Result<()> {
         let key = format!("{}:{}", shard_id, document_id);
         debug!("Storing in object storage: {}", key);
-        
-        if let Some(db) = &self.db {
-            db.insert(key.as_bytes(), data)?;
-            db.flush_async().await?;
-        }
-        Ok(())
+        self.backend.put(&key, data).await
     }
 
     pub async fn get(&self, shard_id: &str, document_id: &str) -> Result<Vec<u8>> {
         let key = format!("{}:{}", shard_id, document_id);
         debug!("Getting from object storage: {}", key);
 
-        if let Some(db) = &self.db {
-            if let Some(data) = db.get(key.as_bytes())? {
-                return Ok(data.to_vec());
-            }
-        }
-        
-        Err(anyhow::anyhow!("Key not found in object storage"))
+        self.backend
+            .get(&key)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Key not found in object storage"))
     }
 
     pub async fn delete(&self, shard_id: &str, document_id: &str) -> Result<()> {
         let key = format!("{}:{}", shard_id, document_id);
         debug!("Deleting from object storage: {}", key);
-        
-        if let Some(db) = &self.db {
-            db.remove(key.as_bytes())?;
+        self.backend.delete(&key).await
+    }
+
+    /// Store several `(shard_id, document_id, data)` entries; the local
+    /// backend writes and flushes them once
+    pub async fn store_batch(&self, entries: &[(String, String, Vec<u8>)]) -> Result<()> {
+        debug!("Storing {} entries in object storage", entries.len());
+
+        let entries: Vec<(String, Vec<u8>)> = entries
+            .iter()
+            .map(|(shard_id, document_id, data)| (format!("{}:{}", shard_id, document_id), data.clone()))
+            .collect();
+        self.backend.put_batch(&entries).await
+    }
+
+    /// Delete several `(shard_id, document_id)` entries; the local backend
+    /// deletes them with a single write
+    pub async fn delete_batch(&self, entries: &[(String, String)]) -> Result<()> {
+        debug!("Deleting {} entries from object storage", entries.len());
+
+        let keys: Vec<String> = entries
+            .iter()
+            .map(|(shard_id, document_id)| format!("{}:{}", shard_id, document_id))
+            .collect();
+        self.backend.delete_batch(&keys).await
+    }
+
+    /// Keys of all entries in object storage, formatted as `shard_id:document_id`
+    pub async fn keys(&self) -> Result<Vec<String>> {
+        self.backend.keys().await
+    }
+
+    /// Whether an orphan sweep is due, recording it as started if so
+    pub(crate) fn start_sweep(&self) -> bool {
+        let Some(interval) = self.orphan_sweep_interval else {
+            return false;
+        };
+        let mut last_sweep = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+        if last_sweep.is_some_and(|last| last.elapsed() < interval) {
+            return false;
         }
-        Ok(())
+        *last_sweep = Some(std::time::Instant::now());
+        true
     }
 }
@@ -0,0 +1,921 @@
+//! # Backup and Restore
+//!
+//! [`StorageHierarchy::create_backup`] writes the stored payloads of all
+//! documents, with their metadata and chunk manifests, into a backup
+//! bundle. A bundle is a directory under
+//! [`StorageConfig::backup_dir`](crate::StorageConfig) holding
+//! `manifest.json`, `index.json` with the checksum of every document that
+//! existed when it was taken, and `documents.bin` with the documents
+//! themselves. Payloads are copied as stored, so documents encrypted at
+//! rest stay encrypted and need the same master key to be read after a
+//! restore, and documents compressed with a collection dictionary need the
+//! dictionaries of the node they were written on. Bundles can be copied to the backup directory of another node
+//! and restored there.
+//!
+//! A full backup holds every document; an incremental one holds only the
+//! documents written since the previous backup, and depends on it. A
+//! backup is not a consistent snapshot: every document is captured at one
+//! version, but writes made while it runs may or may not be included.
+//!
+//! [`StorageHierarchy::restore_backup`] returns the documents to their
+//! state when a backup was taken, reading the chain of bundles from the
+//! last full backup: documents that changed since are rewritten, and
+//! documents created since are deleted. Documents under legal hold and
+//! within WORM retention are left as they are, and reported.
+//! [`StorageHierarchy::restore_backup_at`] restores the last backup taken at
+//! or before a point in time. Revisions, tombstones, collection settings,
+//! compression dictionaries and legal holds are not part of backups.
+//!
+//! [`StorageHierarchy::create_scoped_backup`] limits a backup to the
+//! collections whose names start with a prefix, such as the collections of
+//! one tenant. Incremental backups build on the latest backup of the same
+//! scope, and restoring a scoped backup leaves other collections alone.
+//! [`StorageHierarchy::restore_backup_into`] copies the documents of a
+//! backup into a new namespace instead, replacing the scope prefix of their
+//! collections, so the current documents stay untouched.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tracing::{debug, info, warn};
+
+use crate::jobs::JobHandle;
+use crate::streaming::{load_chunk, open_chunk};
+use crate::{
+    ChunkManifest, CompressionConfig, CompressionEngine, DocumentMetadata, StorageHierarchy, StorageTier, WriteMode,
+};
+
+/// Version of the bundle layout written by this build
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const INDEX_FILE: &str = "index.json";
+const DOCUMENTS_FILE: &str = "documents.bin";
+
+/// Documents handled between two progress reports of a backup job
+const PROGRESS_INTERVAL: usize = 100;
+
+/// Whether a backup holds every document or only the changed ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// Every document
+    Full,
+
+    /// Documents written since the previous backup
+    Incremental,
+}
+
+/// Description of one backup bundle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Identifier of the backup, sortable by creation time
+    pub id: String,
+
+    /// Full or incremental
+    pub kind: BackupKind,
+
+    /// Backup an incremental backup builds on
+    pub parent: Option<String>,
+
+    /// Collection prefix the backup is limited to, `None` for all
+    /// collections
+    #[serde(default)]
+    pub scope: Option<String>,
+
+    /// When the backup started
+    pub created_at: DateTime<Utc>,
+
+    /// Layout version of the bundle
+    pub format_version: u32,
+
+    /// Documents held in the bundle
+    pub documents: usize,
+
+    /// Documents that existed when the backup was taken
+    pub total_documents: usize,
+
+    /// Documents deleted since the parent backup
+    pub deleted: usize,
+
+    /// Documents left out because no intact copy of them could be read
+    pub unreadable: Vec<String>,
+
+    /// Size of the documents file in bytes
+    pub size: u64,
+
+    /// BLAKE3 checksum of the documents file
+    pub checksum: String,
+}
+
+/// Outcome of a restore.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestoreReport {
+    /// Backup that was restored
+    pub backup_id: String,
+
+    /// Bundles read, from the full backup to the restored one
+    pub chain: Vec<String>,
+
+    /// Namespace the documents were copied into, `None` for a restore in
+    /// place
+    #[serde(default)]
+    pub namespace: Option<String>,
+
+    /// Documents rewritten from the backup
+    pub restored: usize,
+
+    /// Documents already in their backed-up state
+    pub unchanged: usize,
+
+    /// Documents created after the backup that were deleted
+    pub deleted: usize,
+
+    /// Documents, as `collection:document_id`, left as they are because a
+    /// legal hold or WORM retention protects them
+    pub retained: Vec<String>,
+
+    /// Documents whose payload is missing from the backup and were left as
+    /// they are
+    pub missing: Vec<String>,
+}
+
+/// One document in the documents file, followed by its payload or chunks.
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupEntry {
+    key: String,
+    metadata: DocumentMetadata,
+    manifest: Option<ChunkManifest>,
+}
+
+/// Checksum of every document when a backup was taken, `None` for
+/// documents that could not be read.
+type BackupIndex = BTreeMap<String, Option<String>>;
+
+/// Backup bundles kept in one directory.
+#[derive(Debug)]
+pub struct BackupManager {
+    dir: PathBuf,
+
+    /// Serializes backups and restores
+    running: tokio::sync::Mutex<()>,
+}
+
+impl BackupManager {
+    /// Manage the bundles in `dir`, which is created with the first backup.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), running: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Directory holding the bundles.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every complete backup, oldest first.
+    pub async fn list(&self) -> Result<Vec<BackupManifest>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(id) = entry.file_name().into_string() else { continue };
+            // Bundles being written have no manifest yet
+            if validate_id(&id).is_err() {
+                continue;
+            }
+            match self.read_manifest(&id).await {
+                Ok(manifest) => backups.push(manifest),
+                Err(e) => warn!("Skipping unreadable backup {}: {}", id, e),
+            }
+        }
+        backups.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(backups)
+    }
+
+    /// Manifest of one backup.
+    pub async fn manifest(&self, id: &str) -> Result<BackupManifest> {
+        validate_id(id)?;
+        match self.read_manifest(id).await {
+            Ok(manifest) => Ok(manifest),
+            Err(e) if is_not_found(&e) => Err(anyhow::anyhow!("Backup not found: {}", id)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Delete a backup no incremental backup depends on.
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let _running = self.running.lock().await;
+        self.manifest(id).await?;
+        if let Some(child) = self.list().await?.into_iter().find(|backup| backup.parent.as_deref() == Some(id)) {
+            return Err(anyhow::anyhow!("Cannot delete backup {}: backup {} depends on it", id, child.id));
+        }
+
+        tokio::fs::remove_dir_all(self.dir.join(id)).await?;
+        info!("Deleted backup {}", id);
+        Ok(())
+    }
+
+    /// The full backup `id` builds on and the incremental ones in between,
+    /// ending with `id`.
+    async fn chain(&self, id: &str) -> Result<Vec<BackupManifest>> {
+        let mut chain = vec![self.manifest(id).await?];
+        while let Some(parent) = chain.last().and_then(|backup| backup.parent.clone()) {
+            if chain.len() > 10_000 || chain.iter().any(|backup| backup.id == parent) {
+                return Err(anyhow::anyhow!("Backup {} has a cyclic parent chain", id));
+            }
+            let manifest = self.manifest(&parent).await.map_err(|e| {
+                anyhow::anyhow!("Backup {} depends on backup {} which cannot be read: {}", id, parent, e)
+            })?;
+            chain.push(manifest);
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+
+    async fn read_manifest(&self, id: &str) -> Result<BackupManifest> {
+        let manifest: BackupManifest = serde_json::from_slice(&tokio::fs::read(self.dir.join(id).join(MANIFEST_FILE)).await?)?;
+        if manifest.format_version > FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Backup {} has format version {}, newer than the supported {}",
+                id,
+                manifest.format_version,
+                FORMAT_VERSION
+            ));
+        }
+        Ok(manifest)
+    }
+
+    async fn read_index(&self, id: &str) -> Result<BackupIndex> {
+        Ok(serde_json::from_slice(&tokio::fs::read(self.dir.join(id).join(INDEX_FILE)).await?)?)
+    }
+
+    /// The chain of backup `id`, every bundle checked against its checksum,
+    /// and the index of `id`.
+    async fn verified_chain(&self, id: &str) -> Result<(Vec<BackupManifest>, BackupIndex)> {
+        let chain = self.chain(id).await?;
+        for manifest in &chain {
+            self.verify(manifest).await?;
+        }
+        Ok((chain, self.read_index(id).await?))
+    }
+
+    /// Check the documents file of a backup against its manifest.
+    async fn verify(&self, manifest: &BackupManifest) -> Result<()> {
+        let mut file = tokio::fs::File::open(self.dir.join(&manifest.id).join(DOCUMENTS_FILE)).await?;
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        if hasher.finalize().to_hex().as_str() != manifest.checksum {
+            return Err(anyhow::anyhow!("Backup {} is corrupt: its documents do not match their checksum", manifest.id));
+        }
+        Ok(())
+    }
+}
+
+/// Backup ids name directories, so only letters, digits and dashes are
+/// accepted.
+fn validate_id(id: &str) -> Result<()> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(anyhow::anyhow!("Invalid backup id: {}", id));
+    }
+    Ok(())
+}
+
+/// Scopes and namespaces are collection name prefixes, so they cannot be
+/// empty or reach into the document id part of a key.
+fn validate_prefix(prefix: &str, what: &str) -> Result<()> {
+    if prefix.is_empty() || prefix.contains(':') {
+        return Err(anyhow::anyhow!("Invalid {}: {:?}", what, prefix));
+    }
+    Ok(())
+}
+
+/// Whether `collection` is covered by a backup of `scope`.
+fn in_scope(scope: Option<&str>, collection: &str) -> bool {
+    scope.is_none_or(|scope| collection.starts_with(scope))
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Writes the documents file, hashing it on the way.
+struct BundleWriter {
+    file: BufWriter<tokio::fs::File>,
+    hasher: blake3::Hasher,
+    size: u64,
+}
+
+impl BundleWriter {
+    async fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(tokio::fs::File::create(path).await?),
+            hasher: blake3::Hasher::new(),
+            size: 0,
+        })
+    }
+
+    async fn write_entry(&mut self, entry: &BackupEntry) -> Result<()> {
+        let header = serde_json::to_vec(entry)?;
+        self.write(&(header.len() as u32).to_le_bytes()).await?;
+        self.write(&header).await
+    }
+
+    async fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        self.write(&(blob.len() as u64).to_le_bytes()).await?;
+        self.write(blob).await
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file.write_all(bytes).await?;
+        self.hasher.update(bytes);
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Flush the file to disk, returning its size and checksum.
+    async fn finish(mut self) -> Result<(u64, String)> {
+        self.file.flush().await?;
+        self.file.get_ref().sync_all().await?;
+        Ok((self.size, self.hasher.finalize().to_hex().to_string()))
+    }
+}
+
+/// Reads the documents file written by [`BundleWriter`].
+struct BundleReader {
+    file: BufReader<tokio::fs::File>,
+}
+
+impl BundleReader {
+    async fn open(path: &Path) -> Result<Self> {
+        Ok(Self { file: BufReader::new(tokio::fs::File::open(path).await?) })
+    }
+
+    /// Next document, `None` at the end of the file.
+    async fn next_entry(&mut self) -> Result<Option<BackupEntry>> {
+        let length = match self.file.read_u32_le().await {
+            Ok(length) => length,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut header = vec![0; length as usize];
+        self.file.read_exact(&mut header).await?;
+        Ok(Some(serde_json::from_slice(&header)?))
+    }
+
+    async fn blob(&mut self) -> Result<Vec<u8>> {
+        let length = self.file.read_u64_le().await?;
+        let mut blob = vec![0; length as usize];
+        self.file.read_exact(&mut blob).await?;
+        Ok(blob)
+    }
+}
+
+impl StorageHierarchy {
+    /// Back up the stored documents into a new bundle.
+    ///
+    /// An incremental backup builds on the latest backup and fails if
+    /// there is none. Documents without any intact stored copy are left out
+    /// and listed in the manifest; the next incremental backup tries them
+    /// again.
+    pub async fn create_backup(&self, kind: BackupKind) -> Result<BackupManifest> {
+        self.create_scoped_backup(kind, None).await
+    }
+
+    /// Back up the documents of the collections whose names start with
+    /// `scope`, or of all collections when it is `None`.
+    ///
+    /// An incremental backup builds on the latest backup of the same scope.
+    pub async fn create_scoped_backup(&self, kind: BackupKind, scope: Option<&str>) -> Result<BackupManifest> {
+        self.run_backup(kind, scope, None).await
+    }
+
+    /// Take a backup, reporting progress to `job` and stopping when it is
+    /// cancelled.
+    pub(crate) async fn run_backup(&self, kind: BackupKind, scope: Option<&str>, job: Option<&JobHandle>) -> Result<BackupManifest> {
+        if let Some(scope) = scope {
+            validate_prefix(scope, "backup scope")?;
+        }
+        let _running = self.backups.running.lock().await;
+        let created_at = Utc::now();
+
+        let parent = match kind {
+            BackupKind::Full => None,
+            BackupKind::Incremental => {
+                let parent = self
+                    .backups
+                    .list()
+                    .await?
+                    .into_iter()
+                    .rev()
+                    .find(|backup| backup.scope.as_deref() == scope)
+                    .ok_or_else(|| anyhow::anyhow!("Incremental backup needs a previous backup"))?;
+                Some(parent)
+            }
+        };
+        let parent_index = match &parent {
+            Some(parent) => self.backups.read_index(&parent.id).await?,
+            None => BackupIndex::new(),
+        };
+
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        let id = format!("{}-{}", created_at.format("%Y%m%dT%H%M%S%3fZ"), &suffix[..8]);
+        tokio::fs::create_dir_all(&self.backups.dir).await?;
+        let partial = self.backups.dir.join(format!("{}.partial", id));
+        tokio::fs::create_dir_all(&partial).await?;
+
+        let manifest = BackupManifest {
+            id: id.clone(),
+            kind,
+            parent: parent.map(|parent| parent.id),
+            scope: scope.map(str::to_string),
+            created_at,
+            format_version: FORMAT_VERSION,
+            documents: 0,
+            total_documents: 0,
+            deleted: 0,
+            unreadable: Vec::new(),
+            size: 0,
+            checksum: String::new(),
+        };
+        let written = self.write_bundle(&partial, manifest, &parent_index, job).await;
+        let manifest = match written {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let _ = tokio::fs::remove_dir_all(&partial).await;
+                return Err(e);
+            }
+        };
+        tokio::fs::rename(&partial, self.backups.dir.join(&id)).await?;
+
+        info!(
+            "Created {:?} backup {} with {} of {} documents ({} bytes)",
+            kind, id, manifest.documents, manifest.total_documents, manifest.size
+        );
+        Ok(manifest)
+    }
+
+    /// Write the index, documents and manifest of the backup described by
+    /// `manifest` into `dir`, filling in its counts and checksum.
+    async fn write_bundle(
+        &self,
+        dir: &Path,
+        mut manifest: BackupManifest,
+        parent_index: &BackupIndex,
+        job: Option<&JobHandle>,
+    ) -> Result<BackupManifest> {
+        let id = manifest.id.clone();
+        let keys: Vec<String> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| in_scope(manifest.scope.as_deref(), &entry.value().collection))
+            .map(|entry| entry.key().clone())
+            .collect();
+        let total = keys.len() as u64;
+        let mut index = BackupIndex::new();
+        let mut unreadable = Vec::new();
+        let mut documents = 0;
+        let mut writer = BundleWriter::create(&dir.join(DOCUMENTS_FILE)).await?;
+
+        for (done, key) in keys.into_iter().enumerate() {
+            if let Some(job) = job {
+                if done.is_multiple_of(PROGRESS_INTERVAL) {
+                    job.check_cancelled()?;
+                    job.set_progress(done as u64, Some(total));
+                }
+            }
+            let mut attempts = 0;
+            while let Some(metadata) = self.metadata_store.get(&key) {
+                let checksum = metadata.checksum.clone();
+                if parent_index.get(&key) == Some(&Some(checksum.clone())) {
+                    index.insert(key.clone(), Some(checksum));
+                    break;
+                }
+                if self.write_document(&mut writer, &key, &metadata).await? {
+                    documents += 1;
+                    index.insert(key.clone(), Some(checksum));
+                    break;
+                }
+
+                // A document rewritten while it was read is read again
+                let rewritten = self.metadata_store.get(&key).is_some_and(|current| current.checksum != checksum);
+                if rewritten && attempts < 3 {
+                    attempts += 1;
+                    continue;
+                }
+                warn!("Leaving {} out of backup {}: no intact copy is stored", key, id);
+                index.insert(key.clone(), None);
+                unreadable.push(key.clone());
+                break;
+            }
+        }
+
+        let (size, checksum) = writer.finish().await?;
+        manifest.documents = documents;
+        manifest.total_documents = index.len();
+        manifest.deleted = parent_index.keys().filter(|key| !index.contains_key(*key)).count();
+        manifest.unreadable = unreadable;
+        manifest.size = size;
+        manifest.checksum = checksum;
+        if let Some(job) = job {
+            job.set_progress(total, Some(total));
+        }
+        tokio::fs::write(dir.join(INDEX_FILE), serde_json::to_vec(&index)?).await?;
+        tokio::fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?).await?;
+        Ok(manifest)
+    }
+
+    /// Append one document to the bundle; returns false when no intact copy
+    /// of its current version is stored.
+    ///
+    /// Chunks are copied one at a time. Once a chunk cannot be read, it
+    /// and the remaining ones are written empty, and the entry is left for
+    /// restores to skip since the index does not point to it.
+    async fn write_document(&self, writer: &mut BundleWriter, key: &str, metadata: &DocumentMetadata) -> Result<bool> {
+        let Some(manifest) = self.chunks.get(key)? else {
+            let Some(payload) = self.stored_payload(key, metadata).await else { return Ok(false) };
+            writer.write_entry(&BackupEntry { key: key.to_string(), metadata: metadata.clone(), manifest: None }).await?;
+            writer.write_blob(&payload).await?;
+            return Ok(true);
+        };
+
+        let chunks: Vec<_> = manifest.chunks.iter().enumerate().map(|(index, chunk)| (manifest.chunk_key(key, index), chunk.clone())).collect();
+        let algorithm = manifest.checksum_algorithm;
+        writer
+            .write_entry(&BackupEntry { key: key.to_string(), metadata: metadata.clone(), manifest: Some(manifest) })
+            .await?;
+        let mut intact = true;
+        for (chunk_key, chunk) in chunks {
+            let sealed = match intact {
+                true => load_chunk(&self.warm_layer, &self.cold_layer, &metadata.shard_id, &chunk_key, &chunk, algorithm).await,
+                false => None,
+            };
+            match sealed {
+                Some(sealed) => writer.write_blob(&sealed).await?,
+                None => {
+                    intact = false;
+                    writer.write_blob(&[]).await?;
+                }
+            }
+        }
+        Ok(intact)
+    }
+
+    /// Every backup, oldest first.
+    pub async fn list_backups(&self) -> Result<Vec<BackupManifest>> {
+        self.backups.list().await
+    }
+
+    /// Manifest of one backup.
+    pub async fn backup_manifest(&self, id: &str) -> Result<BackupManifest> {
+        self.backups.manifest(id).await
+    }
+
+    /// Delete a backup, unless an incremental backup depends on it.
+    pub async fn delete_backup(&self, id: &str) -> Result<()> {
+        self.backups.delete(id).await
+    }
+
+    /// Return the documents to their state when backup `id` was taken.
+    ///
+    /// Every bundle of the chain is checked against its checksum before any
+    /// document is changed. Documents outside the scope of the backup are
+    /// left alone. Writes made while a restore runs may be overwritten by it.
+    pub async fn restore_backup(&self, id: &str) -> Result<RestoreReport> {
+        self.run_restore(id, None).await
+    }
+
+    /// Restore backup `id` in place, reporting progress to `job`.
+    ///
+    /// A cancelled job stops before the first document is changed; once
+    /// the restore has started it runs to the end.
+    pub(crate) async fn run_restore(&self, id: &str, job: Option<&JobHandle>) -> Result<RestoreReport> {
+        let _running = self.backups.running.lock().await;
+        let (chain, index) = self.backups.verified_chain(id).await?;
+        if let Some(job) = job {
+            job.check_cancelled()?;
+        }
+        let scope = chain.last().and_then(|backup| backup.scope.clone());
+        info!("Restoring backup {} from {} bundles", id, chain.len());
+
+        let mut report = RestoreReport {
+            backup_id: id.to_string(),
+            chain: chain.iter().map(|manifest| manifest.id.clone()).collect(),
+            ..Default::default()
+        };
+        self.restore_entries(&chain, &index, None, job, &mut report).await?;
+
+        // Documents created after the backup go, unless they are protected
+        let created: Vec<DocumentMetadata> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| in_scope(scope.as_deref(), &entry.value().collection) && !index.contains_key(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        for metadata in created {
+            match self.delete_document(&metadata.collection, &metadata.id).await {
+                Ok(_) => report.deleted += 1,
+                Err(e) => {
+                    debug!("Keeping {}:{} during restore: {}", metadata.collection, metadata.id, e);
+                    report.retained.push(format!("{}:{}", metadata.collection, metadata.id));
+                }
+            }
+        }
+
+        self.metadata_store.flush().await?;
+        info!(
+            "Restored backup {}: {} restored, {} unchanged, {} deleted, {} retained, {} missing",
+            id,
+            report.restored,
+            report.unchanged,
+            report.deleted,
+            report.retained.len(),
+            report.missing.len()
+        );
+        Ok(report)
+    }
+
+    /// Copy the documents of backup `id` into the collections named by
+    /// replacing the scope prefix of their collection with `namespace`, or
+    /// prefixing it for backups of all collections.
+    ///
+    /// The namespace must not hold any documents yet. Documents are written
+    /// as new documents of their target collections, so they are encrypted
+    /// and placed as those collections require; the current documents are
+    /// not touched.
+    pub async fn restore_backup_into(&self, id: &str, namespace: &str) -> Result<RestoreReport> {
+        self.run_restore_into(id, namespace, None).await
+    }
+
+    /// Copy backup `id` into `namespace`, reporting progress to `job`, which
+    /// can be cancelled until the first document is written.
+    pub(crate) async fn run_restore_into(&self, id: &str, namespace: &str, job: Option<&JobHandle>) -> Result<RestoreReport> {
+        validate_prefix(namespace, "restore namespace")?;
+        let _running = self.backups.running.lock().await;
+        if self.metadata_store.iter().any(|entry| entry.value().collection.starts_with(namespace)) {
+            return Err(anyhow::anyhow!("Restore namespace is not empty: {}", namespace));
+        }
+        let (chain, index) = self.backups.verified_chain(id).await?;
+        if let Some(job) = job {
+            job.check_cancelled()?;
+        }
+        info!("Restoring backup {} from {} bundles into namespace {}", id, chain.len(), namespace);
+
+        let mut report = RestoreReport {
+            backup_id: id.to_string(),
+            chain: chain.iter().map(|manifest| manifest.id.clone()).collect(),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        };
+        self.restore_entries(&chain, &index, Some(namespace), job, &mut report).await?;
+
+        self.metadata_store.flush().await?;
+        info!(
+            "Restored backup {} into namespace {}: {} restored, {} missing",
+            id,
+            namespace,
+            report.restored,
+            report.missing.len()
+        );
+        Ok(report)
+    }
+
+    /// Restore the latest backup taken at or before `at`.
+    pub async fn restore_backup_at(&self, at: DateTime<Utc>) -> Result<RestoreReport> {
+        let backup = self
+            .backups
+            .list()
+            .await?
+            .into_iter()
+            .rev()
+            .find(|backup| backup.created_at <= at)
+            .ok_or_else(|| anyhow::anyhow!("Backup not found: none was taken at or before {}", at.to_rfc3339()))?;
+        self.restore_backup(&backup.id).await
+    }
+
+    /// Restore the version of every document in `index` from the newest
+    /// bundle of `chain` holding it, in place or into `namespace`.
+    async fn restore_entries(
+        &self,
+        chain: &[BackupManifest],
+        index: &BackupIndex,
+        namespace: Option<&str>,
+        job: Option<&JobHandle>,
+        report: &mut RestoreReport,
+    ) -> Result<()> {
+        let scope = chain.last().and_then(|backup| backup.scope.as_deref()).unwrap_or("");
+
+        // The newest bundle holding a document's backed-up version restores it
+        let mut pending: HashSet<&String> = index.iter().filter(|(_, checksum)| checksum.is_some()).map(|(key, _)| key).collect();
+        let total = pending.len();
+        for manifest in chain.iter().rev() {
+            if pending.is_empty() {
+                break;
+            }
+            let mut reader = BundleReader::open(&self.backups.dir.join(&manifest.id).join(DOCUMENTS_FILE)).await?;
+            while let Some(entry) = reader.next_entry().await? {
+                let blobs = entry.manifest.as_ref().map_or(1, |manifest| manifest.chunks.len());
+                let wanted = index.get(&entry.key) == Some(&Some(entry.metadata.checksum.clone()));
+                if !wanted || !pending.remove(&entry.key) {
+                    for _ in 0..blobs {
+                        reader.blob().await?;
+                    }
+                    continue;
+                }
+                if let Some(job) = job {
+                    let done = total - pending.len() - 1;
+                    if done.is_multiple_of(PROGRESS_INTERVAL) {
+                        job.set_progress(done as u64, Some(total as u64));
+                    }
+                }
+                match namespace {
+                    None => self.restore_document(&mut reader, entry, report).await?,
+                    Some(namespace) => {
+                        let suffix = entry.metadata.collection.strip_prefix(scope).unwrap_or(&entry.metadata.collection);
+                        let collection = format!("{}{}", namespace, suffix);
+                        self.copy_document(&mut reader, entry, &collection).await?;
+                        report.restored += 1;
+                    }
+                }
+            }
+        }
+        if let Some(job) = job {
+            job.set_progress((total - pending.len()) as u64, Some(total as u64));
+        }
+        report.missing = index
+            .iter()
+            .filter(|(key, checksum)| checksum.is_none() || pending.contains(key))
+            .map(|(key, _)| key.clone())
+            .collect();
+        Ok(())
+    }
+
+    /// Bring one document back to the version in `entry`, reading its
+    /// payload or chunks from `reader`.
+    async fn restore_document(&self, reader: &mut BundleReader, entry: BackupEntry, report: &mut RestoreReport) -> Result<()> {
+        let BackupEntry { key, metadata, manifest } = entry;
+        let blobs = manifest.as_ref().map_or(1, |manifest| manifest.chunks.len());
+        let existing = self.metadata_store.get(&key);
+
+        let skip = match &existing {
+            Some(existing) if existing.checksum == metadata.checksum => {
+                report.unchanged += 1;
+                true
+            }
+            Some(existing) if self.legal_holds.is_held(&key) || self.check_worm(existing, "restore").is_err() => {
+                report.retained.push(key.clone());
+                true
+            }
+            _ => false,
+        };
+        if skip {
+            for _ in 0..blobs {
+                reader.blob().await?;
+            }
+            return Ok(());
+        }
+
+        let shard_id = &metadata.shard_id;
+        let keep_cold_copy = self.collection_replication_factor(&metadata.collection) >= 2;
+        if let Some(existing) = &existing {
+            self.retain_replaced(&key, existing).await;
+        }
+
+        match &manifest {
+            None => {
+                let payload = reader.blob().await?;
+                if !metadata.checksum_matches(&payload) {
+                    return Err(anyhow::anyhow!("Backup copy of {} does not match its checksum", key));
+                }
+
+                let tiers = match metadata.storage_tier {
+                    StorageTier::Hot if keep_cold_copy => vec![StorageTier::Hot, StorageTier::Warm, StorageTier::Cold],
+                    StorageTier::Hot => vec![StorageTier::Hot, StorageTier::Warm],
+                    StorageTier::Warm if keep_cold_copy => vec![StorageTier::Warm, StorageTier::Cold],
+                    StorageTier::Warm => vec![StorageTier::Warm],
+                    StorageTier::Cold => vec![StorageTier::Cold],
+                    StorageTier::Archive => vec![StorageTier::Archive],
+                };
+                for tier in &tiers {
+                    self.tier_store(tier, shard_id, &key, &payload).await?;
+                }
+                // Copies of the version being replaced must not be served
+                for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold, StorageTier::Archive] {
+                    if !tiers.contains(&tier) {
+                        let _ = self.tier_delete(&tier, shard_id, &key).await;
+                    }
+                }
+                if let Some(existing) = &existing {
+                    self.discard_chunks(&key, &existing.shard_id).await;
+                }
+            }
+            Some(manifest) => {
+                for (index, chunk) in manifest.chunks.iter().enumerate() {
+                    let sealed = reader.blob().await?;
+                    if !manifest.chunk_intact(chunk, &sealed) {
+                        return Err(anyhow::anyhow!("Backup copy of chunk {} of {} does not match its checksum", index, key));
+                    }
+                    let chunk_key = manifest.chunk_key(&key, index);
+                    self.warm_layer.store(shard_id, &chunk_key, &sealed).await?;
+                    if keep_cold_copy {
+                        self.cold_layer.store(shard_id, &chunk_key, &sealed).await?;
+                    }
+                }
+
+                match self.chunks.insert(&key, manifest)? {
+                    Some(replaced) if replaced.generation != manifest.generation => {
+                        self.remove_chunks(shard_id, &key, &replaced).await;
+                    }
+                    Some(_) => {}
+                    None => {
+                        for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold, StorageTier::Archive] {
+                            let _ = self.tier_delete(&tier, shard_id, &key).await;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.metadata_store.insert(key.clone(), metadata)?;
+        self.clear_tombstone(&key);
+        report.restored += 1;
+        Ok(())
+    }
+
+    /// Write the document in `entry` as a new document of `collection`,
+    /// reading its payload or chunks from `reader`.
+    ///
+    /// Payloads are bound to the collection they were stored in, so they
+    /// are opened and stored again rather than copied. Chunks are piped
+    /// into [`StorageHierarchy::store_document_stream`] one at a time.
+    async fn copy_document(&self, reader: &mut BundleReader, entry: BackupEntry, collection: &str) -> Result<()> {
+        let BackupEntry { key, metadata, manifest } = entry;
+
+        let Some(manifest) = manifest else {
+            let payload = reader.blob().await?;
+            if !metadata.checksum_matches(&payload) {
+                return Err(anyhow::anyhow!("Backup copy of {} does not match its checksum", key));
+            }
+            let document = self.decompress_and_deserialize(&metadata.collection, &metadata.id, &payload).await?;
+            self.store_document_with_mode(collection, &metadata.id, &document, WriteMode::CreateOnly).await?;
+            return Ok(());
+        };
+
+        let compression = std::sync::Arc::new(CompressionEngine::new(&CompressionConfig {
+            algorithm: manifest.compression.clone(),
+            ..self.config.compression.clone()
+        }));
+        let (sender, receiver) = tokio::io::duplex(64 * 1024);
+        let feed = async {
+            let mut sender = sender;
+            let mut hasher = manifest.checksum_algorithm.hasher();
+            for (index, chunk) in manifest.chunks.iter().enumerate() {
+                let sealed = reader.blob().await?;
+                if !manifest.chunk_intact(chunk, &sealed) {
+                    return Err(anyhow::anyhow!("Backup copy of chunk {} of {} does not match its checksum", index, key));
+                }
+                let raw = open_chunk(
+                    std::sync::Arc::clone(&compression),
+                    self.encryption.clone(),
+                    sealed,
+                    manifest.chunk_key(&key, index),
+                )
+                .await?;
+                hasher.update(&raw);
+                sender.write_all(&raw).await?;
+            }
+            Ok(hasher.finalize())
+        };
+        let (fed, stored) = tokio::join!(feed, self.store_document_stream(collection, &metadata.id, receiver));
+
+        // A feed that broke off ends the stream early, which stores a
+        // truncated document. The restored copy is checksummed with the
+        // current algorithm, so the feed is checked against the backup
+        stored?.metadata.ok_or_else(|| anyhow::anyhow!("Restored {} without metadata", key))?;
+        let complete = fed.as_ref().is_ok_and(|checksum| *checksum == manifest.checksum);
+        if fed.is_err() || !complete {
+            let _ = self.delete_document(collection, &metadata.id).await;
+        }
+        fed?;
+        if !complete {
+            return Err(anyhow::anyhow!("Restored copy of {} does not match its checksum", key));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+//! # Batch Document Operations
+//!
+//! Storing, reading or deleting many documents one call at a time pays for a
+//! hot-layer lock, a metadata write and a replication task per document. The
+//! batch operations here share that work across the whole batch: payloads are
+//! written to each tier with one write, metadata with one write, the
+//! write-ahead log with one append, and replication runs as a single
+//! background task.
+//!
+//! Every operation returns one result per input document, in input order, so
+//! callers such as bulk imports can report exactly which documents failed.
+//! Batches written to strongly consistent collections are replicated one
+//! document at a time, concurrently, rather than written in one go.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::future::join_all;
+use tracing::{debug, error, warn};
+
+use crate::wal::WalOperation;
+use crate::{
+    datacenter_replication, DocumentMetadata, ReplicatedWrite, StorageHierarchy, StorageResult, StorageTier,
+    WriteMode,
+};
+
+impl StorageHierarchy {
+    /// Store several documents in one collection.
+    ///
+    /// Documents are serialized, compressed and encrypted individually, then
+    /// written to the hot layer and the metadata store in one write each and
+    /// replicated to the warm and cold layers by a single background task.
+    /// Like [`StorageHierarchy::store_document`], existing documents are
+    /// replaced with their next version. Documents that fail to serialize,
+    /// or that a WORM collection still retains, are reported without
+    /// affecting the rest of the batch; a failed
+    /// hot-layer or metadata write fails every document that reached it.
+    pub async fn store_documents_batch(
+        &self,
+        collection: &str,
+        documents: &[(String, serde_json::Value)],
+    ) -> Vec<Result<StorageResult<()>>> {
+        if let Some(replicator) = self.write_replicator(collection) {
+            return join_all(documents.iter().map(|(document_id, data)| {
+                replicator.replicate(ReplicatedWrite::Store {
+                    collection: collection.to_string(),
+                    document_id: document_id.clone(),
+                    data: data.clone(),
+                    mode: WriteMode::Upsert,
+                    expires_at: None,
+                    skip_compression: false,
+                })
+            }))
+            .await;
+        }
+
+        let start_time = std::time::Instant::now();
+        debug!("Storing batch of {} documents in {}", documents.len(), collection);
+
+        let mut results: Vec<Option<Result<StorageResult<()>>>> = Vec::with_capacity(documents.len());
+        let mut prepared = Vec::new();
+
+        for (index, (document_id, data)) in documents.iter().enumerate() {
+            match self.prepare_document(collection, document_id, data).await {
+                Ok((key, serialized, metadata)) => {
+                    results.push(None);
+                    prepared.push((index, key, serialized, metadata));
+                }
+                Err(e) => results.push(Some(Err(e))),
+            }
+        }
+
+        // The batch fits in the storage limit as a whole or not at all
+        let requested: u64 = prepared
+            .iter()
+            .map(|(_, key, serialized, _)| {
+                let replaced = self.metadata_store.get(key).map_or(0, |existing| existing.size);
+                serialized.len().saturating_sub(replaced) as u64
+            })
+            .sum();
+        if let Err(e) = self.reserve_storage(collection, requested).await {
+            for (index, _, _, _) in &prepared {
+                results[*index] = Some(Err(anyhow::anyhow!("{}", e)));
+            }
+            return results.into_iter().flatten().collect();
+        }
+
+        let entries: Vec<(String, String, Vec<u8>)> = prepared
+            .iter()
+            .map(|(_, key, serialized, metadata)| (metadata.shard_id.clone(), key.clone(), serialized.clone()))
+            .collect();
+
+        // The whole batch is logged with one append before anything is
+        // written, unless the collection is volatile
+        let volatile = self.is_volatile_collection(collection);
+        let operations: Vec<(WalOperation, &[u8])> = prepared
+            .iter()
+            .filter(|_| !volatile)
+            .map(|(_, key, serialized, metadata)| {
+                (WalOperation::Store { key: key.clone(), metadata: metadata.clone() }, serialized.as_slice())
+            })
+            .collect();
+        let mut wal_seqs = Vec::new();
+        let written = match self.wal.append(&operations) {
+            Ok(seqs) => {
+                wal_seqs = seqs;
+                match self.hot_layer.store_batch(&entries).await {
+                    Ok(()) => self.metadata_store.insert_batch(
+                        prepared.iter().map(|(_, key, _, metadata)| (key.clone(), metadata.clone())).collect(),
+                    ),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = written {
+            error!("Failed to store batch in {}: {}", collection, e);
+            self.wal.complete(&wal_seqs);
+            for (index, key, _, _) in &prepared {
+                results[*index] = Some(Err(anyhow::anyhow!("Failed to store document {}: {}", key, e)));
+            }
+            return results.into_iter().flatten().collect();
+        }
+
+        // Replaced streamed documents leave their chunks behind
+        for (_, key, _, metadata) in &prepared {
+            self.discard_chunks(key, &metadata.shard_id).await;
+        }
+
+        // Make room in capped collections, which may evict documents of the
+        // batch itself but never its last one
+        let evicted = match prepared.last() {
+            Some((_, _, _, last)) => self.evict_capped(collection, &last.id).await,
+            None => Vec::new(),
+        };
+
+        // Volatile documents live in the hot layer only, and evicted ones
+        // are not copied anywhere
+        if !volatile {
+            if evicted.is_empty() {
+                self.replicate_batch(collection, &prepared, entries, wal_seqs);
+            } else {
+                let kept: Vec<_> =
+                    prepared.iter().filter(|(_, _, _, metadata)| !evicted.contains(&metadata.id)).cloned().collect();
+                let entries = entries.into_iter().filter(|(_, key, _)| kept.iter().any(|(_, kept, _, _)| kept == key)).collect();
+                self.replicate_batch(collection, &kept, entries, wal_seqs);
+            }
+        }
+
+        let operation_time = start_time.elapsed();
+        for (index, _, _, metadata) in prepared {
+            results[index] = Some(Ok(StorageResult {
+                data: Some(()),
+                metadata: Some(metadata),
+                operation_time,
+                storage_tier: StorageTier::Hot,
+                cache_hit: false,
+            }));
+        }
+
+        results.into_iter().flatten().collect()
+    }
+
+    /// Retrieve several documents from one collection.
+    ///
+    /// Reads run concurrently and follow the same tier fallback and promotion
+    /// as [`StorageHierarchy::get_document`]; missing documents come back with
+    /// no data rather than as errors.
+    pub async fn get_documents_batch(
+        &self,
+        collection: &str,
+        document_ids: &[String],
+    ) -> Vec<Result<StorageResult<serde_json::Value>>> {
+        debug!("Retrieving batch of {} documents from {}", document_ids.len(), collection);
+
+        join_all(document_ids.iter().map(|document_id| self.get_document(collection, document_id))).await
+    }
+
+    /// Delete several documents from one collection.
+    ///
+    /// Metadata is removed in one write and every tier is cleared with one
+    /// write per tier. Documents that do not exist are reported as not found,
+    /// and documents under legal hold or still retained by a WORM collection
+    /// are left in place and reported as such.
+    pub async fn delete_documents_batch(
+        &self,
+        collection: &str,
+        document_ids: &[String],
+    ) -> Vec<Result<StorageResult<()>>> {
+        if let Some(replicator) = self.write_replicator(collection) {
+            return join_all(document_ids.iter().map(|document_id| {
+                replicator.replicate(ReplicatedWrite::Delete {
+                    collection: collection.to_string(),
+                    document_id: document_id.clone(),
+                })
+            }))
+            .await;
+        }
+
+        let start_time = std::time::Instant::now();
+        debug!("Deleting batch of {} documents from {}", document_ids.len(), collection);
+
+        let keys: Vec<String> = document_ids
+            .iter()
+            .map(|document_id| format!("{}:{}", collection, document_id))
+            .collect();
+        let refusals: Vec<Option<anyhow::Error>> = keys.iter().map(|key| self.refuse_deletion(key)).collect();
+        let deletable: Vec<String> = keys
+            .iter()
+            .zip(&refusals)
+            .filter(|(_, refusal)| refusal.is_none())
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let volatile = self.is_volatile_collection(collection);
+        let operations: Vec<(WalOperation, &[u8])> = deletable
+            .iter()
+            .filter(|_| !volatile)
+            .filter_map(|key| {
+                let existing = self.metadata_store.get(key)?;
+                let operation = WalOperation::Delete { key: key.clone(), shard_id: existing.shard_id, version: existing.version };
+                Some((operation, &[][..]))
+            })
+            .collect();
+        let wal_seqs = match self.wal.append(&operations) {
+            Ok(seqs) => seqs,
+            Err(e) => {
+                error!("Failed to log batch delete from {}: {}", collection, e);
+                return keys
+                    .iter()
+                    .map(|key| Err(anyhow::anyhow!("Failed to delete document {}: {}", key, e)))
+                    .collect();
+            }
+        };
+
+        let removed = match self.metadata_store.remove_batch(&deletable) {
+            Ok(removed) => removed,
+            Err(e) => {
+                error!("Failed to delete batch from {}: {}", collection, e);
+                self.wal.complete(&wal_seqs);
+                return keys
+                    .iter()
+                    .map(|key| Err(anyhow::anyhow!("Failed to delete document {}: {}", key, e)))
+                    .collect();
+            }
+        };
+
+        let entries: Vec<(String, String)> = deletable
+            .iter()
+            .zip(&removed)
+            .filter_map(|(key, metadata)| metadata.as_ref().map(|metadata| (metadata.shard_id.clone(), key.clone())))
+            .collect();
+
+        // Keep tombstones or last revisions before the payloads go
+        for (key, metadata) in deletable.iter().zip(&removed) {
+            if let Some(metadata) = metadata {
+                self.retain_deleted(key, metadata).await;
+                self.discard_chunks(key, &metadata.shard_id).await;
+            }
+        }
+
+        if !entries.is_empty() {
+            let _ = self.hot_layer.delete_batch(&entries).await;
+            let _ = self.warm_layer.delete_batch(&entries).await;
+            let _ = self.cold_layer.delete_batch(&entries).await;
+            let _ = self.archive_layer.delete_batch(&entries).await;
+        }
+        self.wal.complete(&wal_seqs);
+
+        let operation_time = start_time.elapsed();
+        let mut removed = removed.into_iter();
+        keys.iter()
+            .zip(refusals)
+            .map(|(key, refusal)| {
+                if let Some(refusal) = refusal {
+                    return Err(refusal);
+                }
+                match removed.next().flatten() {
+                    Some(metadata) => Ok(StorageResult {
+                        data: Some(()),
+                        metadata: Some(metadata),
+                        operation_time,
+                        storage_tier: StorageTier::Hot,
+                        cache_hit: false,
+                    }),
+                    None => Err(anyhow::anyhow!("Document not found: {}", key)),
+                }
+            })
+            .collect()
+    }
+
+    /// Why a document of a batch delete must stay, if it must.
+    fn refuse_deletion(&self, key: &str) -> Option<anyhow::Error> {
+        if self.legal_holds.is_held(key) {
+            return Some(anyhow::anyhow!("Document {} is under legal hold", key));
+        }
+        let existing = self.metadata_store.get(key)?;
+        self.check_worm(&existing, "delete").err()
+    }
+
+    /// Serialize one document of a batch and build its metadata, returning
+    /// the tier key, the stored payload and the metadata.
+    async fn prepare_document(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+    ) -> Result<(String, Vec<u8>, DocumentMetadata)> {
+        self.check_placement(collection, document_id)?;
+        let skip_compression = self.collection_skips_compression(collection);
+        let (serialized, encryption_key_id) =
+            self.serialize_and_compress(collection, document_id, data, skip_compression).await?;
+        self.check_capped_size(collection, document_id, serialized.len())?;
+        let key = format!("{}:{}", collection, document_id);
+
+        // Existing documents keep their shard and continue their versions
+        let existing = self.metadata_store.get(&key);
+        if let Some(existing) = &existing {
+            self.check_worm(existing, "update")?;
+        }
+        let shard_id = match &existing {
+            Some(existing) => existing.shard_id.clone(),
+            None => self.sharding_engine.read().await.get_shard(collection, document_id).await,
+        };
+        match &existing {
+            Some(existing) => self.retain_replaced(&key, existing).await,
+            None => self.clear_tombstone(&key),
+        }
+
+        let uncompressed_size = serde_json::to_vec(data)?.len();
+        let now = chrono::Utc::now();
+        let metadata = DocumentMetadata {
+            id: document_id.to_string(),
+            collection: collection.to_string(),
+            size: serialized.len(),
+            compression_ratio: uncompressed_size as f32 / serialized.len() as f32,
+            created_at: existing.as_ref().map_or(now, |existing| existing.created_at),
+            updated_at: now,
+            version: existing.as_ref().map_or_else(|| self.next_new_version(&key), |existing| existing.version + 1),
+            checksum: self.config.checksum.algorithm.digest(&serialized),
+            checksum_algorithm: self.config.checksum.algorithm,
+            storage_tier: StorageTier::Hot,
+            shard_id,
+            replica_locations: existing.as_ref().map_or_else(Vec::new, |existing| existing.replica_locations.clone()),
+            encryption_key_id,
+            expires_at: existing.as_ref().and_then(|existing| existing.expires_at),
+            compression_skipped: skip_compression,
+        };
+
+        Ok((key, serialized, metadata))
+    }
+
+    /// Replicate a stored batch to the warm and cold layers and, if
+    /// configured, to other datacenters, each in one background task. The
+    /// logged writes of the batch complete once the layers hold it.
+    fn replicate_batch(
+        &self,
+        collection: &str,
+        prepared: &[(usize, String, Vec<u8>, DocumentMetadata)],
+        entries: Vec<(String, String, Vec<u8>)>,
+        wal_seqs: Vec<u64>,
+    ) {
+        if entries.is_empty() {
+            self.wal.complete(&wal_seqs);
+            return;
+        }
+
+        if let Some(dc_replication) = &self.datacenter_replication_manager {
+            let dc_replication = Arc::clone(dc_replication);
+            let collection = collection.to_string();
+            let documents: Vec<(String, Vec<u8>, Option<HashSet<String>>)> = prepared
+                .iter()
+                .map(|(_, _, serialized, metadata)| {
+                    let allowed_regions = self.allowed_regions(&collection, &metadata.id);
+                    (metadata.id.clone(), serialized.clone(), allowed_regions)
+                })
+                .collect();
+
+            tokio::spawn(async move {
+                for (document_id, data, allowed_regions) in documents {
+                    if let Err(e) = dc_replication
+                        .replicate_document_within(
+                            &collection,
+                            &document_id,
+                            &data,
+                            datacenter_replication::ReplicationOperation::Create,
+                            allowed_regions.as_ref(),
+                        )
+                        .await
+                    {
+                        error!("Cross-datacenter replication failed for {}:{}: {}", collection, document_id, e);
+                    }
+                }
+            });
+        }
+
+        let replication_manager = Arc::clone(&self.replication_manager);
+        let warm_layer = Arc::clone(&self.warm_layer);
+        let cold_layer = Arc::clone(&self.cold_layer);
+        let replication_factor = self.collection_replication_factor(collection);
+        let wal = Arc::clone(&self.wal);
+
+        tokio::spawn(async move {
+            match replication_manager
+                .replicate_batch_to_layers(&entries, replication_factor, &warm_layer, &cold_layer)
+                .await
+            {
+                Ok(result) if result.failed_replicas == 0 => wal.complete(&wal_seqs),
+                Ok(_) => warn!("Keeping {} logged writes until restart after failed replication", wal_seqs.len()),
+                Err(e) => error!("Failed to replicate document batch: {}", e),
+            }
+        });
+    }
+}
@@ -0,0 +1,236 @@
+//! # Checksum Algorithms
+//!
+//! Every stored payload and chunk is checksummed when it is written and
+//! verified when it is read back, scrubbed, restored or transferred. The
+//! algorithm is a trade-off:
+//!
+//! - **BLAKE3**: cryptographic and, with SIMD, the fastest of the three on
+//!   large payloads; the default
+//! - **xxHash64**: not cryptographic, cheapest on small documents where
+//!   integrity against bit rot is all that is needed
+//! - **SHA-256**: for compliance regimes that name it
+//!
+//! [`ChecksumConfig::algorithm`] applies to new writes. The algorithm a
+//! checksum was computed with is recorded next to it, in the document's
+//! metadata and in the chunk manifest of streamed documents, so changing
+//! the configured algorithm leaves existing documents verifiable. With
+//! [`ChecksumConfig::migrate`], scrubs then move documents stored whole to
+//! the configured algorithm once all their copies are verified intact;
+//! streamed documents move when they are rewritten.
+//!
+//! BLAKE3 and SHA-256 pick the fastest implementation for the CPU at
+//! runtime; [`HashAcceleration`] reports which.
+
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+
+use serde::{Deserialize, Serialize};
+
+/// Algorithm of a stored checksum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    /// BLAKE3, 256-bit
+    #[default]
+    Blake3,
+
+    /// xxHash64, 64-bit, not collision resistant
+    XxHash64,
+
+    /// SHA-256
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Hex-encoded checksum of `data`.
+    pub fn digest(self, data: &[u8]) -> String {
+        match self {
+            ChecksumAlgorithm::Blake3 => blake3::hash(data).to_hex().to_string(),
+            ChecksumAlgorithm::XxHash64 => format!("{:016x}", twox_hash::XxHash64::oneshot(0, data)),
+            ChecksumAlgorithm::Sha256 => hex(ring::digest::digest(&ring::digest::SHA256, data).as_ref()),
+        }
+    }
+
+    /// Whether `data` has the checksum `expected`.
+    pub fn verify(self, data: &[u8], expected: &str) -> bool {
+        self.digest(data) == expected
+    }
+
+    /// A hasher computing the checksum of data given in pieces.
+    pub fn hasher(self) -> ChecksumHasher {
+        match self {
+            ChecksumAlgorithm::Blake3 => ChecksumHasher::Blake3(Box::new(blake3::Hasher::new())),
+            ChecksumAlgorithm::XxHash64 => ChecksumHasher::XxHash64(twox_hash::XxHash64::with_seed(0)),
+            ChecksumAlgorithm::Sha256 => ChecksumHasher::Sha256(ring::digest::Context::new(&ring::digest::SHA256)),
+        }
+    }
+}
+
+impl std::fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::XxHash64 => "xxhash64",
+            ChecksumAlgorithm::Sha256 => "sha256",
+        })
+    }
+}
+
+/// Incremental checksum of one of the [`ChecksumAlgorithm`]s.
+pub enum ChecksumHasher {
+    Blake3(Box<blake3::Hasher>),
+    XxHash64(twox_hash::XxHash64),
+    Sha256(ring::digest::Context),
+}
+
+impl ChecksumHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            ChecksumHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            ChecksumHasher::XxHash64(hasher) => hasher.write(data),
+            ChecksumHasher::Sha256(context) => context.update(data),
+        }
+    }
+
+    /// Hex-encoded checksum of everything given to [`ChecksumHasher::update`].
+    pub fn finalize(self) -> String {
+        match self {
+            ChecksumHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            ChecksumHasher::XxHash64(hasher) => format!("{:016x}", hasher.finish()),
+            ChecksumHasher::Sha256(context) => hex(context.finish().as_ref()),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Checksums of new writes and migration of existing ones.
+#[derive(Debug, Clone)]
+pub struct ChecksumConfig {
+    /// Algorithm new payloads and chunks are checksummed with
+    pub algorithm: ChecksumAlgorithm,
+
+    /// Whether scrubs re-checksum intact documents recorded under another
+    /// algorithm
+    pub migrate: bool,
+}
+
+impl Default for ChecksumConfig {
+    fn default() -> Self {
+        Self { algorithm: ChecksumAlgorithm::Blake3, migrate: true }
+    }
+}
+
+/// Implementations the checksum algorithms use on this CPU.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashAcceleration {
+    /// Widest SIMD instruction set BLAKE3 uses: `avx512`, `avx2`, `sse41`,
+    /// `sse2` or `portable`
+    pub blake3: String,
+
+    /// `sha-ni` or `armv8-sha2` when SHA-256 runs on dedicated
+    /// instructions, `software` otherwise
+    pub sha256: String,
+}
+
+impl HashAcceleration {
+    /// Detect the instruction sets available at runtime.
+    pub fn detect() -> Self {
+        Self { blake3: blake3_implementation().to_string(), sha256: sha256_implementation().to_string() }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn blake3_implementation() -> &'static str {
+    if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512vl") {
+        "avx512"
+    } else if is_x86_feature_detected!("avx2") {
+        "avx2"
+    } else if is_x86_feature_detected!("sse4.1") {
+        "sse41"
+    } else {
+        "sse2"
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn blake3_implementation() -> &'static str {
+    "portable"
+}
+
+#[cfg(target_arch = "x86_64")]
+fn sha256_implementation() -> &'static str {
+    match is_x86_feature_detected!("sha") {
+        true => "sha-ni",
+        false => "software",
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn sha256_implementation() -> &'static str {
+    match std::arch::is_aarch64_feature_detected!("sha2") {
+        true => "armv8-sha2",
+        false => "software",
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn sha256_implementation() -> &'static str {
+    "software"
+}
+
+/// Checksum settings of a node and how far its documents have moved to the
+/// configured algorithm.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecksumStats {
+    /// Algorithm of new writes
+    pub algorithm: ChecksumAlgorithm,
+
+    pub acceleration: HashAcceleration,
+
+    /// Documents by the algorithm of their checksum
+    pub documents: BTreeMap<ChecksumAlgorithm, u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [ChecksumAlgorithm; 3] =
+        [ChecksumAlgorithm::Blake3, ChecksumAlgorithm::XxHash64, ChecksumAlgorithm::Sha256];
+
+    #[test]
+    fn test_checksums_match_known_digests() {
+        assert_eq!(
+            ChecksumAlgorithm::Sha256.digest(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(ChecksumAlgorithm::XxHash64.digest(b""), "ef46db3751d8e999");
+        assert_eq!(ChecksumAlgorithm::Blake3.digest(b"abc"), blake3::hash(b"abc").to_hex().to_string());
+    }
+
+    #[test]
+    fn test_incremental_checksums_equal_oneshot_ones() {
+        let data = b"a payload checksummed in pieces";
+        for algorithm in ALGORITHMS {
+            let mut hasher = algorithm.hasher();
+            for piece in data.chunks(7) {
+                hasher.update(piece);
+            }
+            let checksum = hasher.finalize();
+            assert!(algorithm.verify(data, &checksum), "{}", algorithm);
+            assert!(!algorithm.verify(b"another payload", &checksum), "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_algorithms_serialize_by_name() {
+        assert_eq!(serde_json::to_value(ChecksumAlgorithm::XxHash64).unwrap(), "xxhash64");
+        assert_eq!(serde_json::from_value::<ChecksumAlgorithm>("sha256".into()).unwrap(), ChecksumAlgorithm::Sha256);
+        assert!(!HashAcceleration::detect().blake3.is_empty());
+    }
+}
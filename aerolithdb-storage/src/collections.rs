@@ -0,0 +1,532 @@
+//! # Collections
+//!
+//! A collection exists implicitly as soon as a document is stored under its
+//! name. Creating it explicitly registers it even while empty and attaches
+//! settings that override the storage-wide defaults for its documents:
+//! compression algorithm, replication factor, retention and encryption at
+//! rest. Registered collections are persisted in the metadata database.
+//!
+//! A volatile collection keeps its documents in the hot tier only, for
+//! session stores and transient state where speed matters more than
+//! durability. Its writes skip the write-ahead log, the warm and cold
+//! copies, cross-datacenter replication and document history, and its
+//! documents are never migrated off the hot tier; expired ones are deleted
+//! rather than archived. They are gone after a restart. Copies on other
+//! cluster nodes are only placed when the collection sets a replication
+//! factor.
+//!
+//! A capped collection holds at most a number of documents or bytes of
+//! stored payloads. A write that takes it past either limit evicts its
+//! oldest documents, by creation time, until it fits again, so logs and
+//! recent-activity feeds stay bounded without a retention sweep. Updates
+//! keep a document's place in that order, and listings sorted by creation
+//! time read a capped collection in insertion order. Documents under legal
+//! hold are never evicted, and a document larger than the size limit is
+//! refused.
+//!
+//! Payloads do not record how they were compressed, so a collection's
+//! compression can only change while it holds no documents and no retained
+//! revisions or tombstones. Payloads that skipped compression are marked
+//! as such, so a collection can start or stop skipping it at any time.
+//!
+//! A strongly consistent collection agrees on every write through
+//! consensus before acknowledging it; see the consistency module. Volatile
+//! and system collections cannot be strongly consistent.
+//!
+//! Dropping or truncating a collection refuses to remove documents protected by a legal
+//! hold or a WORM retention period.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use aerolithdb_security::DataEncryption;
+
+use crate::{CompressionAlgorithm, CompressionConfig, CompressionEngine, StorageHierarchy, WriteConsistency};
+
+/// Settings of a collection; unset fields follow the storage configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionConfig {
+    /// Compression algorithm of the collection's payloads
+    pub compression: Option<CompressionAlgorithm>,
+
+    /// Persistent copies of each document: 1 keeps a warm copy, 2 or more
+    /// a warm and a cold copy
+    pub replication_factor: Option<usize>,
+
+    /// Documents not updated for this long are deleted
+    pub retention: Option<Duration>,
+
+    /// Whether payloads are encrypted at rest. `Some(true)` refuses writes
+    /// while no encryption key is available; `Some(false)` stores plaintext
+    /// even with encryption at rest enabled.
+    pub encrypted: Option<bool>,
+
+    /// Whether documents are kept in memory only and lost on restart
+    #[serde(default)]
+    pub volatile: bool,
+
+    /// Most documents the collection holds before its oldest are evicted
+    pub max_documents: Option<usize>,
+
+    /// Most bytes of stored payloads the collection holds before its
+    /// oldest documents are evicted
+    pub max_size_bytes: Option<u64>,
+
+    /// Whether payloads are stored without compression, for collections
+    /// of already-compressed or incompressible documents such as images or
+    /// encrypted blobs
+    #[serde(default)]
+    pub skip_compression: bool,
+
+    /// Whether stores, updates and deletes are agreed through consensus
+    /// before they are acknowledged
+    #[serde(default)]
+    pub consistency: WriteConsistency,
+}
+
+impl CollectionConfig {
+    /// Whether the collection evicts its oldest documents on overflow.
+    pub fn is_capped(&self) -> bool {
+        self.max_documents.is_some() || self.max_size_bytes.is_some()
+    }
+}
+
+/// A registered collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Collection {
+    /// Collection name
+    pub name: String,
+
+    /// Settings overriding the storage configuration
+    pub config: CollectionConfig,
+
+    /// When the collection was created
+    pub created_at: DateTime<Utc>,
+}
+
+/// A collection with its current contents, as reported by
+/// [`StorageHierarchy::list_collections`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionInfo {
+    /// Collection name
+    pub name: String,
+
+    /// Settings of the collection; defaults for implicit collections
+    pub config: CollectionConfig,
+
+    /// Whether the collection was created explicitly rather than implied by
+    /// its documents
+    pub registered: bool,
+
+    /// Number of documents
+    pub document_count: usize,
+
+    /// Stored size of the documents in bytes
+    pub size_bytes: u64,
+
+    /// When the collection was created, or its oldest document for implicit
+    /// collections
+    pub created_at: DateTime<Utc>,
+
+    /// When a document was last written
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Registered collections and the statistics of every collection,
+/// persisted in the metadata database.
+#[derive(Debug)]
+pub(crate) struct CollectionRegistry {
+    collections: DashMap<String, Collection>,
+    tree: sled::Tree,
+
+    /// Field statistics by collection name
+    pub(crate) statistics: sled::Tree,
+}
+
+impl CollectionRegistry {
+    pub(crate) fn open(db: &sled::Db) -> Result<Self> {
+        let registry = Self {
+            collections: DashMap::new(),
+            tree: db.open_tree("collections")?,
+            statistics: db.open_tree("collection_statistics")?,
+        };
+
+        for item in registry.tree.iter() {
+            let (key, value) = item?;
+            match serde_json::from_slice::<Collection>(&value) {
+                Ok(collection) => {
+                    registry.collections.insert(collection.name.clone(), collection);
+                }
+                Err(e) => warn!("Skipping unreadable collection {}: {}", String::from_utf8_lossy(&key), e),
+            }
+        }
+
+        Ok(registry)
+    }
+
+    fn save(&self, collection: Collection) -> Result<Collection> {
+        self.tree.insert(collection.name.as_bytes(), serde_json::to_vec(&collection)?)?;
+        self.collections.insert(collection.name.clone(), collection.clone());
+        Ok(collection)
+    }
+
+    pub(crate) fn config(&self, name: &str) -> Option<CollectionConfig> {
+        self.collections.get(name).map(|collection| collection.config.clone())
+    }
+
+    /// Whether a collection keeps its documents in memory only.
+    pub(crate) fn is_volatile(&self, name: &str) -> bool {
+        self.collections.get(name).is_some_and(|collection| collection.config.volatile)
+    }
+}
+
+impl StorageHierarchy {
+    /// Register a collection with its settings.
+    ///
+    /// Collections that already hold documents can be registered too, as
+    /// long as their compression is left unchanged.
+    pub fn create_collection(&self, name: &str, config: CollectionConfig) -> Result<Collection> {
+        if name.is_empty() || name.contains(':') {
+            return Err(anyhow::anyhow!("Invalid collection name: {:?}", name));
+        }
+        if self.collections.collections.contains_key(name) {
+            return Err(anyhow::anyhow!("Collection already exists: {}", name));
+        }
+        self.validate_collection_config(name, &CollectionConfig::default(), &config)?;
+
+        let collection = self.collections.save(Collection {
+            name: name.to_string(),
+            config,
+            created_at: Utc::now(),
+        })?;
+        info!("Created collection {}", name);
+        Ok(collection)
+    }
+
+    /// Registered collection by name.
+    pub fn collection(&self, name: &str) -> Option<Collection> {
+        self.collections.collections.get(name).map(|collection| collection.clone())
+    }
+
+    /// Replace the settings of a registered collection. New settings apply
+    /// to documents written from now on.
+    pub fn update_collection(&self, name: &str, config: CollectionConfig) -> Result<Collection> {
+        let mut collection = self
+            .collection(name)
+            .ok_or_else(|| anyhow::anyhow!("Collection not found: {}", name))?;
+        self.validate_collection_config(name, &collection.config, &config)?;
+
+        collection.config = config;
+        let collection = self.collections.save(collection)?;
+        info!("Updated settings of collection {}", name);
+        Ok(collection)
+    }
+
+    fn validate_collection_config(&self, name: &str, current: &CollectionConfig, config: &CollectionConfig) -> Result<()> {
+        if config.replication_factor == Some(0) {
+            return Err(anyhow::anyhow!("Replication factor of collection {} must be at least 1", name));
+        }
+
+        let algorithm = |config: &CollectionConfig| {
+            config.compression.clone().unwrap_or_else(|| self.config.compression.algorithm.clone())
+        };
+        let stored = self.collection_document_count(name) > 0 || self.history.has_collection_records(name);
+        if algorithm(current) != algorithm(config) && stored {
+            return Err(anyhow::anyhow!(
+                "Cannot change compression of collection {} while it holds documents or their history",
+                name
+            ));
+        }
+        // Documents would be left without the copies the new setting expects
+        if current.volatile != config.volatile && stored {
+            return Err(anyhow::anyhow!(
+                "Cannot make collection {} {} while it holds documents or their history",
+                name,
+                if config.volatile { "volatile" } else { "persistent" }
+            ));
+        }
+        if config.volatile && self.worm.policy(name).is_some() {
+            return Err(anyhow::anyhow!("WORM collection {} cannot be volatile", name));
+        }
+        if config.max_documents == Some(0) || config.max_size_bytes == Some(0) {
+            return Err(anyhow::anyhow!("Limits of capped collection {} must be at least 1", name));
+        }
+        if config.is_capped() && self.worm.policy(name).is_some() {
+            return Err(anyhow::anyhow!("WORM collection {} cannot be capped", name));
+        }
+        if config.consistency == WriteConsistency::Strong {
+            if name.starts_with('_') {
+                return Err(anyhow::anyhow!("System collection {} cannot be strongly consistent", name));
+            }
+            // Quorum acknowledgment means nothing for documents lost on restart
+            if config.volatile {
+                return Err(anyhow::anyhow!("Volatile collection {} cannot be strongly consistent", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete every document of a collection and its history, and
+    /// unregister it. Returns how many documents were deleted; dropping a
+    /// collection that does not exist deletes nothing.
+    pub async fn drop_collection(&self, name: &str) -> Result<usize> {
+        let document_ids = self.list_documents(name, None, None).await?;
+        let deleted = self.remove_collection_documents(name, &document_ids).await?;
+        let purged = self.history.purge_collection(name, &self.legal_holds)?;
+
+        self.collections.tree.remove(name.as_bytes())?;
+        self.collections.collections.remove(name);
+        self.collections.statistics.remove(name.as_bytes())?;
+        info!("Dropped collection {} ({} documents, {} history records)", name, deleted, purged);
+        Ok(deleted)
+    }
+
+    /// Delete every document of a collection, keeping the collection and
+    /// its settings. Returns how many documents were deleted.
+    pub async fn truncate_collection(&self, name: &str) -> Result<usize> {
+        let document_ids = self.list_documents(name, None, None).await?;
+        let deleted = self.remove_collection_documents(name, &document_ids).await?;
+        self.collections.statistics.remove(name.as_bytes())?;
+        info!("Truncated collection {} ({} documents)", name, deleted);
+        Ok(deleted)
+    }
+
+    /// Delete the given documents of a collection, or none of them if any is
+    /// protected from deletion.
+    async fn remove_collection_documents(&self, name: &str, document_ids: &[String]) -> Result<usize> {
+        for document_id in document_ids {
+            let key = format!("{}:{}", name, document_id);
+            if self.legal_holds.is_held(&key) {
+                return Err(anyhow::anyhow!("Document {} is under legal hold", key));
+            }
+            if let Some(existing) = self.metadata_store.get(&key) {
+                self.check_worm(&existing, "delete")?;
+            }
+        }
+
+        let results = self.delete_documents_batch(name, document_ids).await;
+        Ok(results.iter().filter(|result| result.is_ok()).count())
+    }
+
+    /// Registered and implicit collections with their document counts and
+    /// sizes, sorted by name.
+    pub fn list_collections(&self) -> Vec<CollectionInfo> {
+        let mut collections: HashMap<String, CollectionInfo> = self
+            .collections
+            .collections
+            .iter()
+            .map(|collection| {
+                let info = CollectionInfo {
+                    name: collection.name.clone(),
+                    config: collection.config.clone(),
+                    registered: true,
+                    document_count: 0,
+                    size_bytes: 0,
+                    created_at: collection.created_at,
+                    updated_at: None,
+                };
+                (collection.name.clone(), info)
+            })
+            .collect();
+
+        for entry in self.metadata_store.iter() {
+            let metadata = entry.value();
+            let info = collections.entry(metadata.collection.clone()).or_insert_with(|| CollectionInfo {
+                name: metadata.collection.clone(),
+                config: CollectionConfig::default(),
+                registered: false,
+                document_count: 0,
+                size_bytes: 0,
+                created_at: metadata.created_at,
+                updated_at: None,
+            });
+            info.document_count += 1;
+            info.size_bytes += metadata.size as u64;
+            if !info.registered {
+                info.created_at = info.created_at.min(metadata.created_at);
+            }
+            info.updated_at = info.updated_at.max(Some(metadata.updated_at));
+        }
+
+        let mut collections: Vec<CollectionInfo> = collections.into_values().collect();
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+        collections
+    }
+
+    fn collection_document_count(&self, name: &str) -> usize {
+        self.metadata_store.iter().filter(|entry| entry.value().collection == name).count()
+    }
+
+    /// Delete documents of collections with a retention that have not been
+    /// updated within it, returning how many were deleted. Documents under
+    /// legal hold or WORM retention are kept.
+    pub async fn enforce_collection_retention(&self) -> Result<usize> {
+        let now = Utc::now();
+        let retentions: HashMap<String, Duration> = self
+            .collections
+            .collections
+            .iter()
+            .filter_map(|collection| collection.config.retention.map(|retention| (collection.name.clone(), retention)))
+            .collect();
+        if retentions.is_empty() {
+            return Ok(0);
+        }
+
+        let expired: Vec<(String, String)> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| {
+                let metadata = entry.value();
+                retentions.get(&metadata.collection).is_some_and(|retention| {
+                    chrono::Duration::from_std(*retention)
+                        .ok()
+                        .and_then(|retention| metadata.updated_at.checked_add_signed(retention))
+                        .is_some_and(|expires_at| expires_at <= now)
+                })
+            })
+            .map(|entry| (entry.value().collection.clone(), entry.value().id.clone()))
+            .collect();
+
+        let mut deleted = 0;
+        for (collection, document_id) in expired {
+            match self.delete_document_local(&collection, &document_id).await {
+                Ok(_) => deleted += 1,
+                Err(e) => warn!("Keeping expired document {}:{}: {}", collection, document_id, e),
+            }
+        }
+
+        if deleted > 0 {
+            info!("Deleted {} documents past their collection retention", deleted);
+        }
+        Ok(deleted)
+    }
+
+    /// Refuse a document whose stored size alone exceeds the size limit of
+    /// its capped collection.
+    pub(crate) fn check_capped_size(&self, collection: &str, document_id: &str, size: usize) -> Result<()> {
+        match self.collections.config(collection).and_then(|config| config.max_size_bytes) {
+            Some(limit) if size as u64 > limit => Err(anyhow::anyhow!(
+                "Document {}:{} of {} bytes exceeds the {} byte limit of capped collection {}",
+                collection,
+                document_id,
+                size,
+                limit,
+                collection
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete the oldest documents of a capped collection until it is
+    /// within its limits again, sparing `keep`, the document just written.
+    /// Returns the IDs of the evicted documents, oldest first.
+    pub(crate) async fn evict_capped(&self, collection: &str, keep: &str) -> Vec<String> {
+        let Some(config) = self.collections.config(collection).filter(CollectionConfig::is_capped) else {
+            return Vec::new();
+        };
+
+        let mut documents: Vec<(DateTime<Utc>, String, u64)> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| entry.value().collection == collection)
+            .map(|entry| (entry.value().created_at, entry.value().id.clone(), entry.value().size as u64))
+            .collect();
+        let mut count = documents.len();
+        let mut size: u64 = documents.iter().map(|(_, _, size)| size).sum();
+        let over = |count: usize, size: u64| {
+            config.max_documents.is_some_and(|max| count > max) || config.max_size_bytes.is_some_and(|max| size > max)
+        };
+        if !over(count, size) {
+            return Vec::new();
+        }
+        documents.sort();
+
+        let mut evicted = Vec::new();
+        for (_, document_id, document_size) in documents {
+            if !over(count, size) {
+                break;
+            }
+            if document_id == keep {
+                continue;
+            }
+            match self.delete_document_local(collection, &document_id).await {
+                Ok(_) => {
+                    count -= 1;
+                    size = size.saturating_sub(document_size);
+                    evicted.push(document_id);
+                }
+                Err(e) => warn!("Keeping document {}:{} of capped collection: {}", collection, document_id, e),
+            }
+        }
+
+        debug!("Evicted {} documents from capped collection {}", evicted.len(), collection);
+        evicted
+    }
+
+    /// Compression engine of a collection with its own algorithm.
+    pub(crate) fn collection_compression(&self, collection: &str) -> Option<CompressionEngine> {
+        let algorithm = self.collections.config(collection)?.compression?;
+        Some(CompressionEngine::new(&CompressionConfig { algorithm, ..self.config.compression.clone() }))
+    }
+
+    /// Whether a collection's payloads are stored without compression.
+    pub(crate) fn collection_skips_compression(&self, collection: &str) -> bool {
+        self.collections.config(collection).is_some_and(|config| config.skip_compression)
+    }
+
+    /// Compression algorithm of a collection's payloads.
+    pub(crate) fn collection_compression_algorithm(&self, collection: &str) -> CompressionAlgorithm {
+        self.collections
+            .config(collection)
+            .and_then(|config| config.compression)
+            .unwrap_or_else(|| self.config.compression.algorithm.clone())
+    }
+
+    /// Persistent copies to keep of the documents of a collection. Volatile
+    /// collections keep a single copy unless they set their own factor.
+    pub(crate) fn collection_replication_factor(&self, collection: &str) -> usize {
+        match self.collections.config(collection) {
+            Some(config) if config.volatile => config.replication_factor.unwrap_or(1),
+            config => config.and_then(|config| config.replication_factor).unwrap_or(self.config.replication_factor),
+        }
+    }
+
+    /// Whether the documents of a collection are kept in memory only.
+    pub fn is_volatile_collection(&self, collection: &str) -> bool {
+        self.collections.is_volatile(collection)
+    }
+
+    /// Forget documents of volatile collections whose metadata outlived
+    /// the restart that lost their payloads, returning how many.
+    pub(crate) fn discard_volatile_documents(&self) -> Result<usize> {
+        let keys: Vec<String> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| self.collections.is_volatile(&entry.value().collection))
+            .map(|entry| entry.key().clone())
+            .collect();
+        self.metadata_store.remove_batch(&keys)?;
+        if !keys.is_empty() {
+            info!("Discarded {} documents of volatile collections lost on restart", keys.len());
+        }
+        Ok(keys.len())
+    }
+
+    /// Encryption to apply to payloads of a collection, if any.
+    pub(crate) fn collection_encryption(&self, collection: &str) -> Result<Option<&Arc<DataEncryption>>> {
+        match self.collections.config(collection).and_then(|config| config.encrypted) {
+            Some(false) => Ok(None),
+            Some(true) => self.encryption.as_ref().map(Some).ok_or_else(|| {
+                anyhow::anyhow!("Collection {} requires encryption at rest but no key is configured", collection)
+            }),
+            None => Ok(self.encryption.as_ref()),
+        }
+    }
+}
@@ -56,11 +56,23 @@
 //! - Balance CPU usage vs. storage/bandwidth savings
 //! - Consider decompression overhead for frequently accessed data
 //! - Plan for compression algorithm migration and compatibility
+//!
+//! ## Skipping Compression
+//!
+//! Images, encrypted blobs and other already-compressed payloads only cost
+//! CPU to compress again. Collections and single writes can be marked to
+//! skip compression; such payloads are stored as they are behind a short
+//! marker, and every engine passes them through on decompression whatever
+//! its algorithm.
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use tracing::debug;
 use std::io::Write;
 
+/// Marks a payload stored without compression; the payload follows
+const PASSTHROUGH_MAGIC: &[u8; 4] = b"AZpt";
+
 /// Configuration for the data compression system with algorithm selection and tuning options.
 /// 
 /// This configuration enables fine-tuning of compression behavior based on workload
@@ -83,7 +95,7 @@ pub struct CompressionConfig {
 /// 
 /// Each algorithm represents a different trade-off between compression speed,
 /// decompression speed, compression ratio, and CPU usage.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CompressionAlgorithm {
     /// LZ4 - Ultra-fast compression optimized for real-time applications
     /// 
@@ -163,8 +175,27 @@ impl CompressionEngine {
                data.len(), compressed.len(), 
                data.len() as f32 / compressed.len() as f32);
         Ok(compressed)
-    }    /// Decompress data
+    }
+
+    /// Store data without compressing it, marked so that any engine passes
+    /// it through on decompression.
+    pub fn passthrough(data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(PASSTHROUGH_MAGIC.len() + data.len());
+        payload.extend_from_slice(PASSTHROUGH_MAGIC);
+        payload.extend_from_slice(data);
+        payload
+    }
+
+    /// Whether a payload was stored with [`Self::passthrough`].
+    pub fn is_passthrough(payload: &[u8]) -> bool {
+        payload.starts_with(PASSTHROUGH_MAGIC)
+    }
+
+    /// Decompress data
     pub async fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>> {
+        if let Some(data) = compressed_data.strip_prefix(PASSTHROUGH_MAGIC.as_slice()) {
+            return Ok(data.to_vec());
+        }
         debug!("Decompressing {} bytes with {:?}", compressed_data.len(), self.config.algorithm);
 
         let decompressed = match self.config.algorithm {
@@ -272,11 +303,54 @@ impl CompressionEngine {
 }
 
 /// Compression statistics
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompressionStats {
     pub total_bytes_compressed: u64,
     pub total_bytes_decompressed: u64,
     pub total_compression_ratio: f32,
     pub compression_time_ms: u64,
     pub decompression_time_ms: u64,
+
+    /// Documents whose payloads were compressed
+    pub documents_compressed: u64,
+
+    /// Documents stored without compression because they were marked as
+    /// already compressed
+    pub documents_skipped: u64,
+
+    /// Stored bytes of the skipped documents
+    pub bytes_skipped: u64,
+}
+
+impl CompressionStats {
+    /// Share of documents that skipped compression, from 0 to 1.
+    pub fn skip_rate(&self) -> f64 {
+        let total = self.documents_compressed + self.documents_skipped;
+        if total == 0 {
+            return 0.0;
+        }
+        self.documents_skipped as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_passthrough_payloads_decompress_with_any_algorithm() {
+        let data = b"\x89PNG already compressed";
+        let payload = CompressionEngine::passthrough(data);
+        assert!(CompressionEngine::is_passthrough(&payload));
+
+        for algorithm in [CompressionAlgorithm::LZ4, CompressionAlgorithm::Zstd, CompressionAlgorithm::None] {
+            let engine = CompressionEngine::new(&CompressionConfig { algorithm, level: 3, adaptive: false });
+            assert_eq!(engine.decompress(&payload).await.unwrap(), data);
+            assert!(!CompressionEngine::is_passthrough(&engine.compress(data).await.unwrap()));
+        }
+
+        let stats = CompressionStats { documents_compressed: 3, documents_skipped: 1, ..Default::default() };
+        assert_eq!(stats.skip_rate(), 0.25);
+        assert_eq!(CompressionStats::default().skip_rate(), 0.0);
+    }
 }
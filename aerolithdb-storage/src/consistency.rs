@@ -0,0 +1,239 @@
+//! # Write Consistency
+//!
+//! Document writes are eventually consistent by default: the node applies
+//! them, acknowledges them, and copies reach other nodes in the background.
+//! A collection whose [`CollectionConfig::consistency`] is
+//! [`WriteConsistency::Strong`] instead hands its stores, updates and
+//! deletes, single or batched, to a [`WriteReplicator`]. The consensus
+//! engine is one: it proposes each write, applies it on every node with
+//! [`StorageHierarchy::apply_replicated_write`] when it commits, and returns
+//! only once a quorum has acknowledged it. Conflicts such as a version
+//! mismatch are detected when the write is applied, so every node reaches
+//! the same outcome and the writer gets it back.
+//!
+//! Without a replicator, as on a local-only node, strong collections are
+//! written like any other. Deletes made by maintenance (retention, expiry
+//! and the eviction of capped collections) stay local, since every node
+//! runs the same policies, and system collections, whose names start with
+//! an underscore, are always eventually consistent.
+//!
+//! Reads are served from the node's own copy by default, which may trail
+//! the cluster. Reads of strongly consistent collections can ask for more
+//! with a [`ReadConsistency`]: a bounded-staleness read waits until the node
+//! is at most a given lag behind, and a linearizable read waits until it
+//! has applied every write committed before the read began. A
+//! [`ReadBarrier`] decides when that is; the consensus engine is one. Reads
+//! of eventually consistent collections are always local, and without a
+//! barrier, as on a local-only node, every read is already current.
+//!
+//! [`CollectionConfig::consistency`]: crate::CollectionConfig::consistency
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{StorageHierarchy, StorageResult, WriteMode};
+
+/// How the document writes of a collection reach the cluster.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteConsistency {
+    /// Applied locally and acknowledged; other nodes catch up in the background
+    #[default]
+    Eventual,
+
+    /// Agreed through consensus and acknowledged once a quorum has it
+    Strong,
+}
+
+/// How current a read has to be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// Read this node's copy as it is
+    #[default]
+    Local,
+
+    /// Read once this node trails the cluster by at most `max_lag`
+    BoundedStaleness { max_lag: Duration },
+
+    /// Read once this node has applied every write committed before the read
+    Linearizable,
+}
+
+impl ReadConsistency {
+    /// Parse a level named `local`, `bounded_staleness` or `linearizable`.
+    /// Bounded staleness needs `max_lag_ms`, which the other levels refuse.
+    pub fn parse(level: &str, max_lag_ms: Option<u64>) -> Result<Self> {
+        match (level, max_lag_ms) {
+            ("local", None) => Ok(ReadConsistency::Local),
+            ("linearizable", None) => Ok(ReadConsistency::Linearizable),
+            ("bounded_staleness", Some(max_lag_ms)) => {
+                Ok(ReadConsistency::BoundedStaleness { max_lag: Duration::from_millis(max_lag_ms) })
+            }
+            ("bounded_staleness", None) => {
+                Err(anyhow::anyhow!("Invalid read consistency: bounded_staleness needs a maximum lag"))
+            }
+            ("local" | "linearizable", Some(_)) => {
+                Err(anyhow::anyhow!("Invalid read consistency: only bounded_staleness takes a maximum lag"))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Invalid read consistency: {} (expected local, bounded_staleness or linearizable)",
+                level
+            )),
+        }
+    }
+}
+
+/// A document write of a strongly consistent collection, as replicated to
+/// every node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplicatedWrite {
+    /// Store a document as `store_document_with_mode` does
+    Store {
+        collection: String,
+        document_id: String,
+        data: serde_json::Value,
+        mode: WriteMode,
+        /// New expiry of the document; `None` keeps the current one
+        expires_at: Option<DateTime<Utc>>,
+        skip_compression: bool,
+    },
+
+    /// Delete a document
+    Delete { collection: String, document_id: String },
+}
+
+impl ReplicatedWrite {
+    /// Collection the write targets.
+    pub fn collection(&self) -> &str {
+        match self {
+            ReplicatedWrite::Store { collection, .. } | ReplicatedWrite::Delete { collection, .. } => collection,
+        }
+    }
+
+    /// Document the write targets.
+    pub fn document_id(&self) -> &str {
+        match self {
+            ReplicatedWrite::Store { document_id, .. } | ReplicatedWrite::Delete { document_id, .. } => document_id,
+        }
+    }
+}
+
+/// Replicates the writes of strongly consistent collections.
+#[async_trait]
+pub trait WriteReplicator: Send + Sync {
+    /// Replicate `write` and return its outcome once it has committed and
+    /// been applied to this node.
+    async fn replicate(&self, write: ReplicatedWrite) -> Result<StorageResult<()>>;
+}
+
+/// Holds reads of strongly consistent collections back until this node is
+/// current enough to serve them.
+#[async_trait]
+pub trait ReadBarrier: Send + Sync {
+    /// Return once a read at `consistency` can be served from this node,
+    /// or fail if it cannot be.
+    async fn wait_for(&self, consistency: ReadConsistency) -> Result<()>;
+}
+
+/// The replicator writes of strongly consistent collections go through and
+/// the barrier their reads wait at, if attached.
+#[derive(Default)]
+pub(crate) struct ReplicatorSlot {
+    writes: std::sync::RwLock<Option<Arc<dyn WriteReplicator>>>,
+    reads: std::sync::RwLock<Option<Arc<dyn ReadBarrier>>>,
+}
+
+impl std::fmt::Debug for ReplicatorSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicatorSlot")
+            .field("writes", &self.writes.read().unwrap().is_some())
+            .field("reads", &self.reads.read().unwrap().is_some())
+            .finish()
+    }
+}
+
+impl StorageHierarchy {
+    /// Send the writes of strongly consistent collections through
+    /// `replicator` from now on.
+    pub fn set_write_replicator(&self, replicator: Arc<dyn WriteReplicator>) {
+        *self.replicator.writes.write().unwrap() = Some(replicator);
+    }
+
+    /// Hold reads of strongly consistent collections that ask for more than
+    /// a local read at `barrier` from now on.
+    pub fn set_read_barrier(&self, barrier: Arc<dyn ReadBarrier>) {
+        *self.replicator.reads.write().unwrap() = Some(barrier);
+    }
+
+    /// Write consistency of a collection. System collections are always
+    /// eventually consistent.
+    pub fn collection_consistency(&self, collection: &str) -> WriteConsistency {
+        if collection.starts_with('_') {
+            return WriteConsistency::Eventual;
+        }
+        self.collection(collection)
+            .map(|collection| collection.config.consistency)
+            .unwrap_or_default()
+    }
+
+    /// Replicator a write to `collection` has to go through, if any.
+    pub(crate) fn write_replicator(&self, collection: &str) -> Option<Arc<dyn WriteReplicator>> {
+        if self.collection_consistency(collection) != WriteConsistency::Strong {
+            return None;
+        }
+        self.replicator.writes.read().unwrap().clone()
+    }
+
+    /// Wait until a read of `collection` at `consistency` can be served
+    /// from this node.
+    ///
+    /// Reads above local fail for eventually consistent collections when a
+    /// barrier is attached, since their writes are not ordered by it.
+    pub async fn await_read_consistency(&self, collection: &str, consistency: ReadConsistency) -> Result<()> {
+        if consistency == ReadConsistency::Local {
+            return Ok(());
+        }
+        let Some(barrier) = self.replicator.reads.read().unwrap().clone() else {
+            return Ok(());
+        };
+        if self.collection_consistency(collection) != WriteConsistency::Strong {
+            return Err(anyhow::anyhow!(
+                "Invalid read consistency: collection {} is eventually consistent and only supports local reads",
+                collection
+            ));
+        }
+        barrier.wait_for(consistency).await
+    }
+
+    /// Retrieve a document once this node is current enough for a read at
+    /// `consistency`.
+    pub async fn get_document_with_consistency(
+        &self,
+        collection: &str,
+        document_id: &str,
+        consistency: ReadConsistency,
+    ) -> Result<StorageResult<serde_json::Value>> {
+        self.await_read_consistency(collection, consistency).await?;
+        self.get_document(collection, document_id).await
+    }
+
+    /// Apply a committed write of a strongly consistent collection to this
+    /// node, without replicating it again.
+    pub async fn apply_replicated_write(&self, write: &ReplicatedWrite) -> Result<StorageResult<()>> {
+        match write {
+            ReplicatedWrite::Store { collection, document_id, data, mode, expires_at, skip_compression } => {
+                self.store_document_local(collection, document_id, data, *mode, *expires_at, *skip_compression)
+                    .await
+            }
+            ReplicatedWrite::Delete { collection, document_id } => {
+                self.delete_document_local(collection, document_id).await
+            }
+        }
+    }
+}
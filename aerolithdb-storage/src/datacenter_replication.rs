@@ -34,12 +34,13 @@
 //! - ✅ Configurable based on data criticality
 
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
-use tracing::{debug, info, error};
+use tracing::{debug, info, error, warn};
 use chrono::{DateTime, Utc};
+use dashmap::DashSet;
 use serde::{Serialize, Deserialize};
 
 /// Configuration for cross-datacenter replication
@@ -118,6 +119,8 @@ pub struct DatacenterReplicationManager {
     remote_connections: Arc<RwLock<HashMap<String, RemoteDatacenterConnection>>>,
     replication_stats: Arc<RwLock<ReplicationStatistics>>,
     conflict_resolver: Arc<CrossDatacenterConflictResolver>,
+    /// Regions declared lost by consensus; their datacenters are skipped until resumed
+    suspended_regions: DashSet<String>,
 }
 
 /// Connection to a remote datacenter
@@ -266,6 +269,7 @@ impl DatacenterReplicationManager {
             remote_connections: Arc::new(RwLock::new(HashMap::new())),
             replication_stats: Arc::new(RwLock::new(ReplicationStatistics::default())),
             conflict_resolver,
+            suspended_regions: DashSet::new(),
         };
 
         // Initialize connections to remote datacenters
@@ -304,6 +308,19 @@ impl DatacenterReplicationManager {
         document_id: &str,
         data: &[u8],
         operation_type: ReplicationOperation,
+    ) -> Result<ReplicationResult> {
+        self.replicate_document_within(collection, document_id, data, operation_type, None).await
+    }
+
+    /// Replicate a document to the remote datacenters in `allowed_regions`,
+    /// or to all of them when no restriction is given
+    pub async fn replicate_document_within(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &[u8],
+        operation_type: ReplicationOperation,
+        allowed_regions: Option<&HashSet<String>>,
     ) -> Result<ReplicationResult> {
         if !self.config.enabled {
             debug!("Cross-datacenter replication is disabled");
@@ -325,7 +342,12 @@ impl DatacenterReplicationManager {
         let connections = self.remote_connections.read().await;
 
         for datacenter in &self.config.remote_datacenters {
-            if !datacenter.active {
+            if !datacenter.active || self.suspended_regions.contains(&datacenter.region) {
+                continue;
+            }
+            if allowed_regions.is_some_and(|allowed| !allowed.contains(&datacenter.region)) {
+                debug!("Not replicating {}:{} to datacenter {} in region {} outside its residency",
+                       collection, document_id, datacenter.datacenter_id, datacenter.region);
                 continue;
             }
 
@@ -469,6 +491,70 @@ impl DatacenterReplicationManager {
         }
     }
 
+    /// Delete a document from every active remote datacenter outside
+    /// `allowed_regions` that it may have been replicated to, i.e. within
+    /// `previously_allowed` if the document was already restricted.
+    /// Returns the datacenters it was deleted from.
+    pub async fn purge_outside_regions(
+        &self,
+        collection: &str,
+        document_id: &str,
+        allowed_regions: &HashSet<String>,
+        previously_allowed: Option<&HashSet<String>>,
+    ) -> Result<Vec<RemoteDatacenter>> {
+        if !self.config.enabled {
+            return Ok(Vec::new());
+        }
+
+        let metadata = self.create_replication_metadata(ReplicationOperation::Delete, &[]).await?;
+        let connections = self.remote_connections.read().await;
+        let mut purged = Vec::new();
+
+        for datacenter in &self.config.remote_datacenters {
+            if !datacenter.active || allowed_regions.contains(&datacenter.region) {
+                continue;
+            }
+            if previously_allowed.is_some_and(|previous| !previous.contains(&datacenter.region)) {
+                continue;
+            }
+
+            if let Some(connection) = connections.get(&datacenter.datacenter_id) {
+                let request = ReplicationRequest {
+                    source_datacenter: self.config.local_datacenter_id.clone(),
+                    target_datacenter: datacenter.datacenter_id.clone(),
+                    collection: collection.to_string(),
+                    document_id: document_id.to_string(),
+                    data: Vec::new(),
+                    metadata: metadata.clone(),
+                    replication_mode: self.config.default_replication_mode.clone(),
+                };
+                self.execute_replication_request(&request, connection).await?;
+                purged.push(datacenter.clone());
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Stop replicating to every datacenter in a region (e.g. after the region is lost)
+    pub fn suspend_region(&self, region: &str) {
+        if self.suspended_regions.insert(region.to_string()) {
+            warn!("Cross-datacenter replication to region {} suspended", region);
+        }
+    }
+
+    /// Resume replicating to a previously suspended region
+    pub fn resume_region(&self, region: &str) {
+        if self.suspended_regions.remove(region).is_some() {
+            info!("Cross-datacenter replication to region {} resumed", region);
+        }
+    }
+
+    /// Regions currently excluded from cross-datacenter replication
+    pub fn suspended_regions(&self) -> Vec<String> {
+        self.suspended_regions.iter().map(|region| region.clone()).collect()
+    }
+
     /// Get current replication statistics
     pub async fn get_replication_statistics(&self) -> ReplicationStatistics {
         self.replication_stats.read().await.clone()
@@ -0,0 +1,334 @@
+//! # Compression Dictionaries
+//!
+//! Documents are compressed one at a time, and small JSON documents give a
+//! compressor too little to learn from: field names and common values are
+//! repeated in every document but found in none twice. A ZSTD dictionary
+//! trained on a sample of a collection's documents carries those repeats,
+//! so each document only stores what sets it apart.
+//!
+//! Collections compressed with [`CompressionAlgorithm::Zstd`] can have
+//! dictionaries. [`StorageHierarchy::train_compression_dictionary`] trains
+//! one from the most recently written documents of a collection and adopts
+//! it as a new version when it compresses the sample better than plain
+//! compression does. From then on payloads of the collection are
+//! compressed with the latest version, and each payload starts with the
+//! version it needs, so documents written under older versions stay
+//! readable. Every version is kept in the metadata database for as long as
+//! the node runs; like encrypted payloads, payloads copied into backups
+//! need the dictionaries of the node they came from.
+//!
+//! The query engine calls
+//! [`StorageHierarchy::retrain_compression_dictionaries`] periodically,
+//! which retrains collections whose dictionary is older than
+//! [`DictionaryConfig::retrain_interval`].
+
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{CompressionAlgorithm, CompressionEngine, StorageHierarchy};
+
+/// Marks a payload compressed with a dictionary; the version follows
+const MAGIC: &[u8; 4] = b"AZdc";
+
+/// Magic followed by the big-endian dictionary version
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+/// When and how collection dictionaries are trained.
+#[derive(Debug, Clone)]
+pub struct DictionaryConfig {
+    /// Compress Zstd collections with their latest dictionary; payloads
+    /// written with a dictionary are read back either way
+    pub enabled: bool,
+
+    /// Most recently written documents a dictionary is trained on
+    pub sample_size: usize,
+
+    /// Documents a collection needs before a dictionary is trained for it
+    pub min_samples: usize,
+
+    /// Largest dictionary trained, in bytes
+    pub max_size: usize,
+
+    /// Age after which the periodic retrain replaces a dictionary
+    pub retrain_interval: Duration,
+
+    /// ZSTD level payloads are compressed at with a dictionary
+    pub level: i32,
+}
+
+impl Default for DictionaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sample_size: 1000,
+            min_samples: 100,
+            max_size: 16 * 1024,
+            retrain_interval: Duration::from_secs(24 * 3600),
+            level: 3,
+        }
+    }
+}
+
+/// A trained dictionary version of a collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionDictionary {
+    pub collection: String,
+
+    /// Version stored at the start of payloads compressed with it
+    pub version: u32,
+    pub trained_at: DateTime<Utc>,
+
+    /// Size of the dictionary in bytes
+    pub size: usize,
+
+    /// Documents it was trained on
+    pub samples: usize,
+
+    /// Serialized size of the sample
+    pub sample_bytes: usize,
+
+    /// Size of the sample compressed with the dictionary
+    pub compressed_bytes: usize,
+
+    /// Size of the sample compressed without it
+    pub plain_compressed_bytes: usize,
+}
+
+/// Dictionary versions of every collection, persisted in the metadata
+/// database.
+#[derive(Debug)]
+pub(crate) struct DictionaryStore {
+    config: DictionaryConfig,
+
+    /// Description of each version, keyed by collection and version
+    versions: sled::Tree,
+
+    /// Trained bytes of each version, under the same keys
+    data: sled::Tree,
+
+    /// Latest version of each collection
+    latest: DashMap<String, u32>,
+
+    /// Dictionaries read since startup
+    loaded: DashMap<(String, u32), Arc<Vec<u8>>>,
+}
+
+impl DictionaryStore {
+    pub(crate) fn open(db: &sled::Db, config: &DictionaryConfig) -> Result<Self> {
+        let versions = db.open_tree("compression_dictionaries")?;
+        let latest = DashMap::new();
+        for item in versions.iter() {
+            let (_, value) = item?;
+            let dictionary: CompressionDictionary = serde_json::from_slice(&value)?;
+            let mut version = latest.entry(dictionary.collection).or_insert(0);
+            *version = dictionary.version.max(*version);
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            versions,
+            data: db.open_tree("compression_dictionary_data")?,
+            latest,
+            loaded: DashMap::new(),
+        })
+    }
+
+    fn key(collection: &str, version: u32) -> Vec<u8> {
+        let mut key = Self::prefix(collection);
+        key.extend_from_slice(&version.to_be_bytes());
+        key
+    }
+
+    fn prefix(collection: &str) -> Vec<u8> {
+        let mut prefix = collection.as_bytes().to_vec();
+        prefix.push(0);
+        prefix
+    }
+
+    fn latest(&self, collection: &str) -> Option<u32> {
+        self.latest.get(collection).map(|version| *version)
+    }
+
+    fn list(&self, collection: &str) -> Result<Vec<CompressionDictionary>> {
+        self.versions
+            .scan_prefix(Self::prefix(collection))
+            .map(|item| Ok(serde_json::from_slice(&item?.1)?))
+            .collect()
+    }
+
+    fn insert(&self, dictionary: &CompressionDictionary, data: Vec<u8>) -> Result<()> {
+        let key = Self::key(&dictionary.collection, dictionary.version);
+        self.data.insert(&key, data.as_slice())?;
+        self.versions.insert(&key, serde_json::to_vec(dictionary)?)?;
+        self.loaded.insert((dictionary.collection.clone(), dictionary.version), Arc::new(data));
+        self.latest.insert(dictionary.collection.clone(), dictionary.version);
+        Ok(())
+    }
+
+    fn dictionary(&self, collection: &str, version: u32) -> Result<Arc<Vec<u8>>> {
+        if let Some(data) = self.loaded.get(&(collection.to_string(), version)) {
+            return Ok(Arc::clone(&data));
+        }
+        let data = self
+            .data
+            .get(Self::key(collection, version))?
+            .ok_or_else(|| anyhow::anyhow!("Compression dictionary {} of {} not found", version, collection))?;
+        let data = Arc::new(data.to_vec());
+        self.loaded.insert((collection.to_string(), version), Arc::clone(&data));
+        Ok(data)
+    }
+
+    /// Compress a payload with the latest dictionary of its collection,
+    /// if dictionaries are enabled and the collection has one.
+    pub(crate) fn compress(&self, collection: &str, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let Some(version) = self.latest(collection).filter(|_| self.config.enabled) else {
+            return Ok(None);
+        };
+        let dictionary = self.dictionary(collection, version)?;
+        let frame = zstd::bulk::Compressor::with_dictionary(self.config.level, &dictionary)?.compress(data)?;
+
+        let mut payload = Vec::with_capacity(HEADER_LEN + frame.len());
+        payload.extend_from_slice(MAGIC);
+        payload.extend_from_slice(&version.to_be_bytes());
+        payload.extend_from_slice(&frame);
+        Ok(Some(payload))
+    }
+
+    /// Decompress a payload with the dictionary version it names, or with
+    /// `engine` if it was compressed without one.
+    pub(crate) async fn decompress(&self, collection: &str, payload: &[u8], engine: &CompressionEngine) -> Result<Vec<u8>> {
+        let Some(header) = payload.strip_prefix(MAGIC.as_slice()).filter(|rest| rest.len() >= 4) else {
+            return engine.decompress(payload).await;
+        };
+        let version = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let dictionary = self.dictionary(collection, version)?;
+
+        let mut decompressed = Vec::new();
+        zstd::stream::Decoder::with_dictionary(&payload[HEADER_LEN..], &dictionary)?.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+}
+
+impl StorageHierarchy {
+    /// Train a dictionary for a Zstd collection from its most recently
+    /// written documents, adopting it as the collection's next version if
+    /// it compresses them better than plain compression.
+    ///
+    /// Returns `None` when the collection has too few documents or the
+    /// dictionary would not help.
+    pub async fn train_compression_dictionary(&self, collection: &str) -> Result<Option<CompressionDictionary>> {
+        let algorithm = self.collection_compression_algorithm(collection);
+        if algorithm != CompressionAlgorithm::Zstd {
+            return Err(anyhow::anyhow!(
+                "Compression dictionaries need Zstd compression, {} uses {:?}",
+                collection,
+                algorithm
+            ));
+        }
+        let config = &self.config.dictionaries;
+
+        let mut recent: Vec<_> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| entry.collection == collection && !self.chunks.contains(entry.key()))
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        if recent.len() < config.min_samples {
+            return Ok(None);
+        }
+        recent.sort_by_key(|(_, metadata)| std::cmp::Reverse(metadata.updated_at));
+        recent.truncate(config.sample_size);
+
+        let reader = self.statistics_collector();
+        let compression = self.collection_compression(collection).unwrap_or_else(|| CompressionEngine::new(&self.config.compression));
+        let mut samples = Vec::with_capacity(recent.len());
+        for (key, metadata) in &recent {
+            match reader.decode(key, metadata, &compression).await {
+                Ok(document) => samples.push(serde_json::to_vec(&document)?),
+                Err(e) => warn!("Leaving {} out of the dictionary sample: {}", key, e),
+            }
+        }
+        if samples.len() < config.min_samples {
+            return Ok(None);
+        }
+
+        let (max_size, level) = (config.max_size, config.level);
+        let (data, samples) = tokio::task::spawn_blocking(move || {
+            zstd::dict::from_samples(&samples, max_size).map(|data| (data, samples))
+        })
+        .await??;
+
+        let mut compressor = zstd::bulk::Compressor::with_dictionary(level, &data)?;
+        let (mut compressed_bytes, mut plain_compressed_bytes) = (0, 0);
+        for sample in &samples {
+            compressed_bytes += HEADER_LEN + compressor.compress(sample)?.len();
+            plain_compressed_bytes += compression.compress(sample).await?.len();
+        }
+        if compressed_bytes >= plain_compressed_bytes {
+            info!(
+                "Not adopting a dictionary for {}: {} bytes compressed with it, {} without",
+                collection, compressed_bytes, plain_compressed_bytes
+            );
+            return Ok(None);
+        }
+
+        let dictionary = CompressionDictionary {
+            collection: collection.to_string(),
+            version: self.dictionaries.latest(collection).unwrap_or(0) + 1,
+            trained_at: Utc::now(),
+            size: data.len(),
+            samples: samples.len(),
+            sample_bytes: samples.iter().map(Vec::len).sum(),
+            compressed_bytes,
+            plain_compressed_bytes,
+        };
+        self.dictionaries.insert(&dictionary, data)?;
+        info!(
+            "Adopted dictionary {} of {}: sample compresses to {} bytes instead of {}",
+            dictionary.version, collection, compressed_bytes, plain_compressed_bytes
+        );
+        Ok(Some(dictionary))
+    }
+
+    /// Train dictionaries for Zstd collections without one, or whose latest
+    /// one is older than the retrain interval. Collections skipping
+    /// compression are left alone.
+    pub async fn retrain_compression_dictionaries(&self) -> Result<Vec<CompressionDictionary>> {
+        if !self.config.dictionaries.enabled {
+            return Ok(Vec::new());
+        }
+        let retrain_after = chrono::Duration::from_std(self.config.dictionaries.retrain_interval)?;
+        let collections: BTreeSet<String> = self.metadata_store.iter().map(|entry| entry.collection.clone()).collect();
+
+        let mut trained = Vec::new();
+        for collection in collections {
+            if self.collection_compression_algorithm(&collection) != CompressionAlgorithm::Zstd
+                || self.collection_skips_compression(&collection)
+            {
+                continue;
+            }
+            let latest = self.compression_dictionaries(&collection)?.pop();
+            if latest.is_some_and(|latest| Utc::now() - latest.trained_at < retrain_after) {
+                continue;
+            }
+            match self.train_compression_dictionary(&collection).await {
+                Ok(dictionary) => trained.extend(dictionary),
+                Err(e) => warn!("Failed to train a compression dictionary for {}: {}", collection, e),
+            }
+        }
+        Ok(trained)
+    }
+
+    /// Dictionary versions of a collection, oldest first.
+    pub fn compression_dictionaries(&self, collection: &str) -> Result<Vec<CompressionDictionary>> {
+        self.dictionaries.list(collection)
+    }
+}
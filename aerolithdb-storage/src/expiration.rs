@@ -0,0 +1,195 @@
+//! # Document Expiration
+//!
+//! A document can be stored with a time to live, recorded as `expires_at`
+//! in its metadata. Later writes keep the expiry unless they set a new one,
+//! and [`StorageHierarchy::set_document_expiry`] changes or clears it.
+//!
+//! Expired documents are removed by [`StorageHierarchy::expire_documents`],
+//! which the query engine runs periodically. Depending on
+//! [`ExpirationConfig::action`] each one is deleted, leaving a tombstone
+//! when soft delete is enabled, or moved to the Archive tier with its
+//! expiry cleared. Documents of volatile collections are always deleted.
+//! Documents under legal hold or WORM retention are kept until they are
+//! released. Every removal is published as an [`ExpirationEvent`] so
+//! caches and plugins can drop what they hold for the document.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::{StorageHierarchy, StorageResult, StorageTier, WriteOptions};
+
+/// Expiration events buffered for slow subscribers
+pub(crate) const EVENT_CAPACITY: usize = 256;
+
+/// What happens to a document once it expires.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpirationAction {
+    /// Delete the document
+    #[default]
+    Delete,
+
+    /// Move the document to the Archive tier and clear its expiry
+    Archive,
+}
+
+/// How expired documents are removed.
+#[derive(Debug, Clone)]
+pub struct ExpirationConfig {
+    /// Action taken on expired documents
+    pub action: ExpirationAction,
+
+    /// Most documents removed per sweep; the rest wait for the next one
+    pub max_per_sweep: usize,
+}
+
+impl Default for ExpirationConfig {
+    fn default() -> Self {
+        Self {
+            action: ExpirationAction::Delete,
+            max_per_sweep: 10_000,
+        }
+    }
+}
+
+/// Removal of an expired document, published to subscribers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ExpirationEvent {
+    /// The document was deleted
+    DocumentDeleted { collection: String, document_id: String, expires_at: DateTime<Utc> },
+
+    /// The document was moved to the Archive tier
+    DocumentArchived { collection: String, document_id: String, expires_at: DateTime<Utc> },
+}
+
+/// Outcome of an expiration sweep.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpirationReport {
+    /// Documents deleted or archived, in order of expiry
+    pub expired: Vec<ExpirationEvent>,
+
+    /// Expired documents kept because of a legal hold, WORM retention or
+    /// an error
+    pub kept: usize,
+}
+
+/// When a document written now with a time to live of `ttl` expires.
+pub(crate) fn expiry_after(ttl: std::time::Duration) -> Result<DateTime<Utc>> {
+    chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .ok_or_else(|| anyhow::anyhow!("Invalid time to live: {:?}", ttl))
+}
+
+impl StorageHierarchy {
+    /// Store a document that expires `ttl` from now, creating it or
+    /// replacing the current version.
+    pub async fn store_document_with_ttl(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+        ttl: std::time::Duration,
+    ) -> Result<StorageResult<()>> {
+        self.store_document_with_options(collection, document_id, data, WriteOptions { ttl: Some(ttl), ..Default::default() })
+            .await
+    }
+
+    /// Set when a stored document expires, or with `None` keep it until it
+    /// is deleted.
+    pub fn set_document_expiry(&self, collection: &str, document_id: &str, expires_at: Option<DateTime<Utc>>) -> Result<()> {
+        let key = format!("{}:{}", collection, document_id);
+        let mut metadata = self
+            .metadata_store
+            .get_mut(&key)
+            .ok_or_else(|| anyhow::anyhow!("Document not found: {}", key))?;
+        metadata.expires_at = expires_at;
+        Ok(())
+    }
+
+    /// When a stored document expires, if it exists and has an expiry.
+    pub fn document_expiry(&self, collection: &str, document_id: &str) -> Option<DateTime<Utc>> {
+        self.metadata_store.get(&format!("{}:{}", collection, document_id))?.expires_at
+    }
+
+    /// Receive expiration events from now on.
+    pub fn subscribe_expirations(&self) -> broadcast::Receiver<ExpirationEvent> {
+        self.expirations.subscribe()
+    }
+
+    /// Delete or archive documents whose expiry has passed, oldest expiry
+    /// first and at most `max_per_sweep` of them.
+    pub async fn expire_documents(&self) -> Result<ExpirationReport> {
+        let now = Utc::now();
+        let mut expired: Vec<(String, DateTime<Utc>)> = self
+            .metadata_store
+            .iter()
+            .filter_map(|entry| entry.expires_at.filter(|expires_at| *expires_at <= now).map(|at| (entry.key().clone(), at)))
+            .collect();
+        expired.sort_by_key(|(_, expires_at)| *expires_at);
+        expired.truncate(self.config.expiration.max_per_sweep);
+
+        let mut report = ExpirationReport::default();
+        for (key, expires_at) in expired {
+            // Skip documents rewritten with a later expiry since the scan
+            let Some(metadata) = self.metadata_store.get(&key).filter(|metadata| metadata.expires_at == Some(expires_at)) else {
+                continue;
+            };
+            let (collection, document_id) = (metadata.collection.clone(), metadata.id.clone());
+
+            // Volatile documents have no tier to be archived to
+            let action = if self.is_volatile_collection(&collection) {
+                ExpirationAction::Delete
+            } else {
+                self.config.expiration.action
+            };
+            let outcome = match action {
+                ExpirationAction::Delete => self
+                    .delete_document_local(&collection, &document_id)
+                    .await
+                    .map(|_| ExpirationEvent::DocumentDeleted { collection, document_id, expires_at }),
+                ExpirationAction::Archive => self
+                    .archive_expired(&key, &metadata)
+                    .await
+                    .map(|_| ExpirationEvent::DocumentArchived { collection, document_id, expires_at }),
+            };
+            match outcome {
+                Ok(event) => {
+                    // Nobody listening is not an error
+                    let _ = self.expirations.send(event.clone());
+                    report.expired.push(event);
+                }
+                Err(e) => {
+                    warn!("Keeping expired document {}: {}", key, e);
+                    report.kept += 1;
+                }
+            }
+        }
+
+        if !report.expired.is_empty() {
+            info!("Removed {} expired documents, kept {}", report.expired.len(), report.kept);
+        }
+        Ok(report)
+    }
+
+    /// Move an expired document to the Archive tier and clear its expiry.
+    async fn archive_expired(&self, key: &str, metadata: &crate::DocumentMetadata) -> Result<()> {
+        if self.legal_holds.is_held(key) {
+            return Err(anyhow::anyhow!("Document {} is under legal hold", key));
+        }
+        if self.chunks.contains(key) {
+            return Err(anyhow::anyhow!("streamed documents are not archived"));
+        }
+        if metadata.storage_tier != StorageTier::Archive {
+            self.tier_migrator().migrate(key, metadata, &StorageTier::Archive).await?;
+        }
+        if let Some(mut current) = self.metadata_store.get_mut(key).filter(|current| current.checksum == metadata.checksum) {
+            current.expires_at = None;
+        }
+        Ok(())
+    }
+}
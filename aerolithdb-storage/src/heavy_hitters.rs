@@ -0,0 +1,244 @@
+//! # Heavy Hitters
+//!
+//! The keys seen most often in a stream, such as the most read documents or
+//! the most expensive queries, are tracked with the space-saving algorithm:
+//! a summary keeps at most [`HeavyHitterConfig::capacity`] counters, and a
+//! key arriving at a full summary replaces the key with the smallest count,
+//! inheriting that count as its possible overestimate. Any key occurring more
+//! often than the stream total divided by the capacity is guaranteed to be
+//! kept, and no count is low by more than its reported error.
+//!
+//! Counts are weighted, so a sampled read can count for the reads it stands
+//! for and a query for its latency. Events are kept in one summary per
+//! [`HeavyHitterConfig::bucket`] of time, the last
+//! [`HeavyHitterConfig::buckets`] of them, so the heaviest keys of any
+//! sliding window up to their span are found by merging the summaries of
+//! the buckets it covers.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How many keys are counted and over which time they are kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeavyHitterConfig {
+    /// Keys counted per bucket; the least counted is replaced by a new one
+    pub capacity: usize,
+
+    /// Time span of one bucket, the granularity of windows
+    pub bucket: Duration,
+
+    /// Buckets kept, so windows span up to `bucket * buckets`
+    pub buckets: usize,
+}
+
+impl Default for HeavyHitterConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            bucket: Duration::from_secs(60),
+            buckets: 60,
+        }
+    }
+}
+
+/// A key among the heaviest of a window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeavyHitter {
+    pub key: String,
+
+    /// Estimated weight of the key, never below its true weight
+    pub count: u64,
+
+    /// Most the estimate can exceed the true weight by
+    pub error: u64,
+}
+
+/// Space-saving summary of one bucket.
+#[derive(Debug, Default)]
+struct Summary {
+    /// Count and error by key
+    counters: HashMap<String, (u64, u64)>,
+
+    /// Keys ordered by count, smallest first
+    by_count: BTreeSet<(u64, String)>,
+}
+
+impl Summary {
+    fn add(&mut self, key: &str, weight: u64, capacity: usize) {
+        if let Some((count, _)) = self.counters.get_mut(key) {
+            self.by_count.remove(&(*count, key.to_string()));
+            *count += weight;
+            self.by_count.insert((*count, key.to_string()));
+            return;
+        }
+
+        let mut error = 0;
+        if self.counters.len() >= capacity {
+            if let Some((min, evicted)) = self.by_count.pop_first() {
+                self.counters.remove(&evicted);
+                error = min;
+            }
+        }
+        self.counters.insert(key.to_string(), (error + weight, error));
+        self.by_count.insert((error + weight, key.to_string()));
+    }
+
+    /// Most a key missing from the summary can have been seen
+    fn missing_bound(&self, capacity: usize) -> u64 {
+        if self.counters.len() < capacity {
+            0
+        } else {
+            self.by_count.first().map_or(0, |(count, _)| *count)
+        }
+    }
+}
+
+/// Windowed heavy hitters of a weighted stream of keys.
+#[derive(Debug)]
+pub struct HeavyHitters {
+    config: HeavyHitterConfig,
+
+    /// Summaries by bucket number, oldest first
+    buckets: Mutex<VecDeque<(i64, Summary)>>,
+}
+
+impl HeavyHitters {
+    pub fn new(config: HeavyHitterConfig) -> Self {
+        Self {
+            config: HeavyHitterConfig {
+                capacity: config.capacity.max(1),
+                bucket: config.bucket.max(Duration::from_secs(1)),
+                buckets: config.buckets.max(1),
+            },
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn config(&self) -> &HeavyHitterConfig {
+        &self.config
+    }
+
+    /// Longest window the kept buckets cover
+    pub fn span(&self) -> Duration {
+        self.config.bucket * self.config.buckets as u32
+    }
+
+    /// Count `weight` occurrences of `key` at `now`.
+    pub fn record(&self, key: &str, weight: u64, now: DateTime<Utc>) {
+        if weight == 0 {
+            return;
+        }
+        let number = self.bucket_of(now);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.back().is_none_or(|(last, _)| *last < number) {
+            buckets.push_back((number, Summary::default()));
+        }
+        while buckets.front().is_some_and(|(first, _)| *first <= number - self.config.buckets as i64) {
+            buckets.pop_front();
+        }
+        // An event older than the newest bucket counts towards the newest
+        if let Some((_, summary)) = buckets.back_mut() {
+            summary.add(key, weight, self.config.capacity);
+        }
+    }
+
+    /// The `limit` heaviest keys of the `window` up to `now`, heaviest
+    /// first. Windows are rounded up to whole buckets and capped at
+    /// [`span`](Self::span).
+    pub fn top(&self, limit: usize, window: Duration, now: DateTime<Utc>) -> Vec<HeavyHitter> {
+        let bucket_secs = self.config.bucket.as_secs().max(1);
+        let covered = window.as_secs().div_ceil(bucket_secs).clamp(1, self.config.buckets as u64) as i64;
+        let newest = self.bucket_of(now);
+        let buckets = self.buckets.lock().unwrap();
+        let summaries: Vec<&Summary> = buckets
+            .iter()
+            .filter(|(number, _)| *number > newest - covered && *number <= newest)
+            .map(|(_, summary)| summary)
+            .collect();
+
+        // A key missing from a summary may have been evicted from it, so it
+        // is counted as seen up to that summary's smallest count
+        let mut merged: HashMap<&str, (u64, u64)> = HashMap::new();
+        for summary in &summaries {
+            for (key, (count, error)) in &summary.counters {
+                let entry = merged.entry(key.as_str()).or_default();
+                entry.0 += count;
+                entry.1 += error;
+            }
+        }
+        let mut hitters: Vec<HeavyHitter> = merged
+            .into_iter()
+            .map(|(key, (mut count, mut error))| {
+                for summary in &summaries {
+                    if !summary.counters.contains_key(key) {
+                        let bound = summary.missing_bound(self.config.capacity);
+                        count += bound;
+                        error += bound;
+                    }
+                }
+                HeavyHitter { key: key.to_string(), count, error }
+            })
+            .collect();
+        hitters.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.error.cmp(&b.error)).then_with(|| a.key.cmp(&b.key)));
+        hitters.truncate(limit);
+        hitters
+    }
+
+    fn bucket_of(&self, time: DateTime<Utc>) -> i64 {
+        time.timestamp().div_euclid(self.config.bucket.as_secs().max(1) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(capacity: usize) -> HeavyHitters {
+        HeavyHitters::new(HeavyHitterConfig { capacity, bucket: Duration::from_secs(60), buckets: 10 })
+    }
+
+    #[test]
+    fn test_frequent_keys_survive_eviction() {
+        let hitters = tracker(3);
+        let now = Utc::now();
+        for i in 0..100 {
+            hitters.record("hot", 5, now);
+            hitters.record(&format!("cold-{}", i), 1, now);
+        }
+
+        let top = hitters.top(2, Duration::from_secs(60), now);
+        assert_eq!(top[0].key, "hot");
+        assert!(top[0].count >= 500 && top[0].count - top[0].error <= 500);
+        assert_eq!(top.len(), 2);
+        assert!(top[1].count - top[1].error <= 1);
+    }
+
+    #[test]
+    fn test_windows_merge_recent_buckets() {
+        let hitters = tracker(10);
+        let now = Utc::now();
+        let earlier = now - chrono::Duration::minutes(5);
+        hitters.record("old", 10, earlier);
+        hitters.record("new", 3, now);
+        hitters.record("old", 1, now);
+
+        let recent = hitters.top(10, Duration::from_secs(60), now);
+        assert_eq!(recent, vec![
+            HeavyHitter { key: "new".to_string(), count: 3, error: 0 },
+            HeavyHitter { key: "old".to_string(), count: 1, error: 0 },
+        ]);
+        let all = hitters.top(10, Duration::from_secs(3600), now);
+        assert_eq!((all[0].key.as_str(), all[0].count), ("old", 11));
+
+        // Buckets older than the span are dropped
+        hitters.record("new", 1, now + chrono::Duration::minutes(10));
+        let all = hitters.top(10, Duration::from_secs(3600), now + chrono::Duration::minutes(10));
+        assert_eq!(all.len(), 1);
+        assert_eq!((all[0].key.as_str(), all[0].count), ("new", 1));
+    }
+}
@@ -0,0 +1,635 @@
+//! # Soft Delete and Version History
+//!
+//! Two opt-in safety nets against losing data, both configured through
+//! [`HistoryConfig`]:
+//!
+//! - **Soft delete**: with a retention window set, deleting a document keeps
+//!   its last revision as a tombstone that can be restored until the window
+//!   passes, after which a background task purges it.
+//! - **Version history**: documents of versioned collections keep their
+//!   previous revisions whenever they are replaced or deleted. Any revision
+//!   can be read back or restored as the newest version, and the history of
+//!   a document can be purged explicitly.
+//! - **Point-in-time reads**: a versioned document or collection can be read
+//!   as it was at an earlier time. Each version counts as current from its
+//!   `updated_at` until the next version was written or the document was
+//!   deleted, so reads are only as precise as the clock of the node that
+//!   wrote them, and only reach back as far as revisions are retained.
+//!
+//! Tombstones and revisions hold the payload exactly as it was stored, so
+//! they stay compressed and, with encryption at rest, encrypted. Both live in
+//! the metadata database. Documents under legal hold are never purged.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::legal_hold::LegalHoldRegistry;
+use crate::{DocumentMetadata, StorageHierarchy, StorageResult, StorageTier, WriteMode};
+
+/// How deleted and replaced documents are retained.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryConfig {
+    /// How long deleted documents stay restorable. `None` deletes documents
+    /// permanently.
+    pub soft_delete_retention: Option<Duration>,
+
+    /// Collections keeping previous revisions of their documents; `*`
+    /// versions every collection
+    pub versioned_collections: HashSet<String>,
+
+    /// Previous revisions kept per document, oldest dropped first; 0 keeps
+    /// every revision
+    pub max_revisions: usize,
+}
+
+impl HistoryConfig {
+    /// Whether documents of `collection` keep previous revisions.
+    pub fn is_versioned(&self, collection: &str) -> bool {
+        self.versioned_collections.contains(collection) || self.versioned_collections.contains("*")
+    }
+}
+
+/// A deleted document that can still be restored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedDocument {
+    /// Metadata of the document when it was deleted
+    pub metadata: DocumentMetadata,
+
+    /// When the document was deleted
+    pub deleted_at: DateTime<Utc>,
+
+    /// When the tombstone becomes eligible for purging
+    pub purge_after: DateTime<Utc>,
+}
+
+/// A retained revision or tombstone: metadata plus the stored payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RetainedPayload<T> {
+    #[serde(flatten)]
+    record: T,
+    payload: Vec<u8>,
+}
+
+/// A retained revision and when it stopped being current.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Revision {
+    #[serde(flatten)]
+    metadata: DocumentMetadata,
+
+    /// When the revision was replaced or deleted; not recorded on revisions
+    /// kept by earlier releases
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retired_at: Option<DateTime<Utc>>,
+}
+
+/// Which version of a document was current at a point in time.
+#[derive(Debug, PartialEq)]
+enum VersionAsOf {
+    /// The retained revision at this index
+    Revision(usize),
+    /// The current version
+    Current,
+    /// The document did not exist
+    Absent,
+}
+
+/// Revisions and tombstones, persisted in the metadata database.
+#[derive(Debug)]
+pub(crate) struct HistoryStore {
+    config: HistoryConfig,
+
+    /// Previous revisions keyed by `collection:document_id` and version
+    revisions: sled::Tree,
+
+    /// Deleted documents keyed by `collection:document_id`
+    tombstones: sled::Tree,
+}
+
+impl HistoryStore {
+    pub(crate) fn open(db: &sled::Db, config: &HistoryConfig) -> Result<Self> {
+        Ok(Self {
+            config: config.clone(),
+            revisions: db.open_tree("document_revisions")?,
+            tombstones: db.open_tree("document_tombstones")?,
+        })
+    }
+
+    /// Revision keys sort by version within a document
+    fn revision_key(key: &str, version: u64) -> Vec<u8> {
+        let mut revision_key = Self::revision_prefix(key);
+        revision_key.extend_from_slice(&version.to_be_bytes());
+        revision_key
+    }
+
+    fn revision_prefix(key: &str) -> Vec<u8> {
+        let mut prefix = key.as_bytes().to_vec();
+        prefix.push(0);
+        prefix
+    }
+
+    fn revisions_of(&self, key: &str) -> Result<Vec<RetainedPayload<Revision>>> {
+        self.revisions
+            .scan_prefix(Self::revision_prefix(key))
+            .map(|item| Ok(serde_json::from_slice(&item?.1)?))
+            .collect()
+    }
+
+    fn revision(&self, key: &str, version: u64) -> Result<Option<RetainedPayload<Revision>>> {
+        match self.revisions.get(Self::revision_key(key, version))? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Keep a revision that is about to be replaced or deleted, dropping the
+    /// oldest revisions beyond the configured limit.
+    fn retain_revision(&self, key: &str, metadata: &DocumentMetadata, payload: &[u8], held: bool) -> Result<()> {
+        let revision = RetainedPayload {
+            record: Revision { metadata: metadata.clone(), retired_at: Some(Utc::now()) },
+            payload: payload.to_vec(),
+        };
+        self.revisions
+            .insert(Self::revision_key(key, metadata.version), serde_json::to_vec(&revision)?)?;
+
+        if self.config.max_revisions > 0 && !held {
+            let keys: Vec<sled::IVec> = self
+                .revisions
+                .scan_prefix(Self::revision_prefix(key))
+                .keys()
+                .collect::<std::result::Result<_, _>>()?;
+            for stale in keys.iter().take(keys.len().saturating_sub(self.config.max_revisions)) {
+                self.revisions.remove(stale)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// IDs of the documents of a collection with retained revisions.
+    fn revised_documents(&self, collection: &str) -> Result<BTreeSet<String>> {
+        let prefix = format!("{}:", collection);
+        let mut documents = BTreeSet::new();
+        for key in self.revisions.scan_prefix(prefix.as_bytes()).keys() {
+            let key = key?;
+            // Revision keys end in a NUL and the version after the document key
+            let document_key = key.split(|&byte| byte == 0).next().unwrap_or(&key);
+            documents.insert(String::from_utf8_lossy(&document_key[prefix.len()..]).into_owned());
+        }
+        Ok(documents)
+    }
+
+    /// Find which of the retained `revisions` of `key`, oldest first, or the
+    /// `current` version was current at `as_of`. A revision stays current
+    /// until the next version was written or, if the document was deleted
+    /// in between, until it was retired.
+    fn version_as_of(
+        &self,
+        key: &str,
+        revisions: &[RetainedPayload<Revision>],
+        current: Option<&DocumentMetadata>,
+        as_of: DateTime<Utc>,
+    ) -> Result<VersionAsOf> {
+        let Some(oldest) = revisions.first().map(|revision| &revision.record.metadata).or(current) else {
+            return Ok(VersionAsOf::Absent);
+        };
+        if as_of < oldest.updated_at {
+            if oldest.version > 1 {
+                return Err(anyhow::anyhow!(
+                    "History of {} before version {} is no longer retained",
+                    key,
+                    oldest.version
+                ));
+            }
+            return Ok(VersionAsOf::Absent);
+        }
+
+        for (index, revision) in revisions.iter().enumerate() {
+            let next = revisions
+                .get(index + 1)
+                .map(|next| next.record.metadata.updated_at)
+                .or(current.map(|current| current.updated_at));
+            let until = match (next, revision.record.retired_at) {
+                (Some(next), Some(retired_at)) => next.min(retired_at),
+                (Some(until), None) | (None, Some(until)) => until,
+                (None, None) => match self.tombstone(key)? {
+                    Some(tombstone) => tombstone.record.deleted_at,
+                    None => {
+                        return Err(anyhow::anyhow!(
+                            "Deletion time of version {} of {} is not recorded",
+                            revision.record.metadata.version,
+                            key
+                        ))
+                    }
+                },
+            };
+            if revision.record.metadata.updated_at <= as_of && as_of < until {
+                return Ok(VersionAsOf::Revision(index));
+            }
+        }
+
+        match current {
+            Some(current) if current.updated_at <= as_of => Ok(VersionAsOf::Current),
+            _ => Ok(VersionAsOf::Absent),
+        }
+    }
+
+    fn tombstone(&self, key: &str) -> Result<Option<RetainedPayload<DeletedDocument>>> {
+        match self.tombstones.get(key.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn bury(&self, key: &str, metadata: &DocumentMetadata, payload: &[u8], retention: Duration) -> Result<()> {
+        let deleted_at = Utc::now();
+        let purge_after = chrono::Duration::from_std(retention)
+            .ok()
+            .and_then(|retention| deleted_at.checked_add_signed(retention))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let tombstone = RetainedPayload {
+            record: DeletedDocument { metadata: metadata.clone(), deleted_at, purge_after },
+            payload: payload.to_vec(),
+        };
+        self.tombstones.insert(key.as_bytes(), serde_json::to_vec(&tombstone)?)?;
+        Ok(())
+    }
+
+    /// Highest version ever recorded for a document that no longer has
+    /// metadata, so a recreated document continues its version sequence
+    pub(crate) fn last_version(&self, key: &str) -> Result<u64> {
+        let tombstone = self.tombstone(key)?.map_or(0, |tombstone| tombstone.record.metadata.version);
+        let revision = match self.revisions.scan_prefix(Self::revision_prefix(key)).keys().next_back() {
+            Some(revision_key) => {
+                let revision_key = revision_key?;
+                let version = revision_key[revision_key.len() - 8..].try_into().map(u64::from_be_bytes);
+                version.unwrap_or(0)
+            }
+            None => 0,
+        };
+        Ok(tombstone.max(revision))
+    }
+
+    /// Whether any revision or tombstone of a collection is retained.
+    pub(crate) fn has_collection_records(&self, collection: &str) -> bool {
+        let prefix = format!("{}:", collection);
+        [&self.revisions, &self.tombstones].iter().any(|tree| tree.scan_prefix(prefix.as_bytes()).next().is_some())
+    }
+
+    /// Remove the revisions and tombstones of every document of a
+    /// collection, skipping held documents.
+    pub(crate) fn purge_collection(&self, collection: &str, legal_holds: &LegalHoldRegistry) -> Result<usize> {
+        let prefix = format!("{}:", collection);
+        let mut purged = 0;
+
+        for tree in [&self.revisions, &self.tombstones] {
+            let keys: Vec<sled::IVec> = tree.scan_prefix(prefix.as_bytes()).keys().collect::<std::result::Result<_, _>>()?;
+            for key in keys {
+                // Revision keys end in a NUL and the version after the document key
+                let document_key = key.split(|&byte| byte == 0).next().unwrap_or(&key);
+                if legal_holds.is_held(&String::from_utf8_lossy(document_key)) {
+                    continue;
+                }
+                tree.remove(&key)?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Purge tombstones whose retention window has passed, skipping held
+    /// documents.
+    pub(crate) fn purge_expired_tombstones(&self, legal_holds: &LegalHoldRegistry) -> Result<usize> {
+        let now = Utc::now();
+        let mut purged = 0;
+
+        for item in self.tombstones.iter() {
+            let (key, value) = item?;
+            let tombstone: RetainedPayload<DeletedDocument> = match serde_json::from_slice(&value) {
+                Ok(tombstone) => tombstone,
+                Err(e) => {
+                    warn!("Skipping unreadable tombstone {}: {}", String::from_utf8_lossy(&key), e);
+                    continue;
+                }
+            };
+            if tombstone.record.purge_after > now || legal_holds.is_held(&String::from_utf8_lossy(&key)) {
+                continue;
+            }
+            self.tombstones.remove(&key)?;
+            purged += 1;
+        }
+
+        if purged > 0 {
+            info!("Purged {} expired tombstones", purged);
+        }
+        Ok(purged)
+    }
+
+    /// Bytes of retained revisions and tombstones per collection, as stored.
+    pub(crate) fn retained_bytes(&self) -> Result<HashMap<String, u64>> {
+        let mut bytes = HashMap::new();
+        for tree in [&self.revisions, &self.tombstones] {
+            for item in tree.iter() {
+                let (key, value) = item?;
+                let collection = key.split(|&byte| byte == b':').next().unwrap_or(&key);
+                *bytes.entry(String::from_utf8_lossy(collection).into_owned()).or_insert(0) += value.len() as u64;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Purge the oldest tombstones, retention window or not, until `bytes`
+    /// are freed, skipping held documents. Returns how many tombstones and
+    /// bytes were purged.
+    pub(crate) fn purge_oldest_tombstones(&self, bytes: u64, legal_holds: &LegalHoldRegistry) -> Result<(usize, u64)> {
+        let mut tombstones = Vec::new();
+        for item in self.tombstones.iter() {
+            let (key, value) = item?;
+            if legal_holds.is_held(&String::from_utf8_lossy(&key)) {
+                continue;
+            }
+            match serde_json::from_slice::<RetainedPayload<DeletedDocument>>(&value) {
+                Ok(tombstone) => tombstones.push((tombstone.record.deleted_at, key, value.len() as u64)),
+                Err(e) => warn!("Skipping unreadable tombstone {}: {}", String::from_utf8_lossy(&key), e),
+            }
+        }
+        tombstones.sort_by_key(|(deleted_at, _, _)| *deleted_at);
+
+        let (mut purged, mut freed) = (0, 0);
+        for (_, key, size) in tombstones {
+            if freed >= bytes {
+                break;
+            }
+            self.tombstones.remove(&key)?;
+            purged += 1;
+            freed += size;
+        }
+        Ok((purged, freed))
+    }
+}
+
+impl StorageHierarchy {
+    /// Whether deleted documents are kept as restorable tombstones.
+    pub fn is_soft_delete_enabled(&self) -> bool {
+        self.config.history.soft_delete_retention.is_some()
+    }
+
+    /// Whether documents of `collection` keep previous revisions.
+    pub fn is_versioned(&self, collection: &str) -> bool {
+        self.config.history.is_versioned(collection)
+    }
+
+    /// Read the stored payload of a document from the first tier holding it.
+    pub(crate) async fn stored_payload(&self, key: &str, metadata: &DocumentMetadata) -> Option<Vec<u8>> {
+        for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold, StorageTier::Archive] {
+            if let Ok(payload) = self.tier_get(&tier, &metadata.shard_id, key).await {
+                if metadata.checksum_matches(&payload) {
+                    return Some(payload);
+                }
+            }
+        }
+        None
+    }
+
+    /// Keep the revision described by `previous` before it is replaced, if
+    /// its collection is versioned. Streamed documents are not kept.
+    pub(crate) async fn retain_replaced(&self, key: &str, previous: &DocumentMetadata) {
+        if !self.is_versioned(&previous.collection) || self.is_chunked(key) || self.is_volatile_collection(&previous.collection) {
+            return;
+        }
+
+        let retained = match self.stored_payload(key, previous).await {
+            Some(payload) => {
+                self.history.retain_revision(key, previous, &payload, self.legal_holds.is_held(key))
+            }
+            None => Err(anyhow::anyhow!("payload of version {} is no longer stored", previous.version)),
+        };
+        if let Err(e) = retained {
+            warn!("Failed to keep previous revision of {}: {}", key, e);
+        }
+    }
+
+    /// Keep a document that is being deleted, as a revision of a versioned
+    /// collection and as a tombstone when soft delete is enabled. Must run
+    /// before the payload is removed from the tiers. Streamed documents are
+    /// not kept.
+    pub(crate) async fn retain_deleted(&self, key: &str, metadata: &DocumentMetadata) {
+        let retention = self.config.history.soft_delete_retention;
+        let versioned = self.is_versioned(&metadata.collection);
+        if (retention.is_none() && !versioned) || self.is_chunked(key) || self.is_volatile_collection(&metadata.collection) {
+            return;
+        }
+
+        let Some(payload) = self.stored_payload(key, metadata).await else {
+            warn!("Deleted document {} had no stored payload to retain", key);
+            return;
+        };
+        if versioned {
+            if let Err(e) = self.history.retain_revision(key, metadata, &payload, false) {
+                warn!("Failed to keep last revision of deleted document {}: {}", key, e);
+            }
+        }
+        if let Some(retention) = retention {
+            if let Err(e) = self.history.bury(key, metadata, &payload, retention) {
+                warn!("Failed to keep tombstone of deleted document {}: {}", key, e);
+            }
+        }
+    }
+
+    /// Version a document created under `key` should start from, continuing
+    /// the sequence of a deleted predecessor.
+    pub(crate) fn next_new_version(&self, key: &str) -> u64 {
+        self.history.last_version(key).unwrap_or(0) + 1
+    }
+
+    /// Drop the tombstone of a document that has been written again.
+    pub(crate) fn clear_tombstone(&self, key: &str) {
+        if let Err(e) = self.history.tombstones.remove(key.as_bytes()) {
+            warn!("Failed to clear tombstone of {}: {}", key, e);
+        }
+    }
+
+    /// Versions of a document, oldest first: retained revisions followed by
+    /// the current version if the document exists.
+    pub fn list_versions(&self, collection: &str, document_id: &str) -> Result<Vec<DocumentMetadata>> {
+        let key = format!("{}:{}", collection, document_id);
+        let mut versions: Vec<DocumentMetadata> =
+            self.history.revisions_of(&key)?.into_iter().map(|revision| revision.record.metadata).collect();
+        if let Some(current) = self.metadata_store.get(&key) {
+            versions.push(current);
+        }
+        Ok(versions)
+    }
+
+    /// Read one version of a document, current or retained.
+    pub async fn get_document_version(
+        &self,
+        collection: &str,
+        document_id: &str,
+        version: u64,
+    ) -> Result<StorageResult<serde_json::Value>> {
+        let start_time = std::time::Instant::now();
+        let key = format!("{}:{}", collection, document_id);
+
+        if let Some(current) = self.metadata_store.get(&key) {
+            if current.version == version {
+                return self.get_document(collection, document_id).await;
+            }
+        }
+
+        let revision = self
+            .history
+            .revision(&key, version)?
+            .ok_or_else(|| anyhow::anyhow!("Version {} of {} not found", version, key))?;
+        let document = self.decompress_and_deserialize(collection, document_id, &revision.payload).await?;
+
+        Ok(StorageResult {
+            data: Some(document),
+            metadata: Some(revision.record.metadata),
+            operation_time: start_time.elapsed(),
+            storage_tier: StorageTier::Archive,
+            cache_hit: false,
+        })
+    }
+
+    /// Read a document as it was at `as_of`, or `None` if it did not exist
+    /// then. Only documents of versioned collections can be read at a past
+    /// time, and only as far back as their revisions are retained.
+    pub async fn get_document_as_of(
+        &self,
+        collection: &str,
+        document_id: &str,
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<StorageResult<serde_json::Value>>> {
+        let start_time = std::time::Instant::now();
+        if !self.is_versioned(collection) {
+            return Err(anyhow::anyhow!("Collection {} does not keep version history", collection));
+        }
+
+        let key = format!("{}:{}", collection, document_id);
+        let revisions = self.history.revisions_of(&key)?;
+        let current = self.metadata_store.get(&key);
+        match self.history.version_as_of(&key, &revisions, current.as_ref(), as_of)? {
+            VersionAsOf::Current => self.get_document(collection, document_id).await.map(Some),
+            VersionAsOf::Revision(index) => {
+                let revision = &revisions[index];
+                let document = self.decompress_and_deserialize(collection, document_id, &revision.payload).await?;
+                Ok(Some(StorageResult {
+                    data: Some(document),
+                    metadata: Some(revision.record.metadata.clone()),
+                    operation_time: start_time.elapsed(),
+                    storage_tier: StorageTier::Archive,
+                    cache_hit: false,
+                }))
+            }
+            VersionAsOf::Absent => Ok(None),
+        }
+    }
+
+    /// Documents of a versioned collection as they were at `as_of`, with
+    /// the metadata of that version, sorted by ID. Fails if the history of
+    /// any document no longer reaches back that far.
+    pub async fn collection_as_of(
+        &self,
+        collection: &str,
+        as_of: DateTime<Utc>,
+    ) -> Result<Vec<(DocumentMetadata, serde_json::Value)>> {
+        if !self.is_versioned(collection) {
+            return Err(anyhow::anyhow!("Collection {} does not keep version history", collection));
+        }
+
+        let mut document_ids = self.history.revised_documents(collection)?;
+        document_ids.extend(self.list_documents(collection, None, None).await?);
+
+        let mut documents = Vec::new();
+        for document_id in document_ids {
+            if let Some(document) = self.get_document_as_of(collection, &document_id, as_of).await? {
+                if let (Some(metadata), Some(data)) = (document.metadata, document.data) {
+                    documents.push((metadata, data));
+                }
+            }
+        }
+        Ok(documents)
+    }
+
+    /// Make a previous version the current content of a document, stored as
+    /// a new version. Works for deleted documents whose history is retained.
+    pub async fn restore_document_version(
+        &self,
+        collection: &str,
+        document_id: &str,
+        version: u64,
+    ) -> Result<StorageResult<()>> {
+        let document = self
+            .get_document_version(collection, document_id, version)
+            .await?
+            .data
+            .ok_or_else(|| anyhow::anyhow!("Version {} of {}:{} not found", version, collection, document_id))?;
+
+        debug!("Restoring {}:{} to version {}", collection, document_id, version);
+        self.store_document_with_mode(collection, document_id, &document, WriteMode::Upsert).await
+    }
+
+    /// Soft-deleted documents of a collection that can still be restored.
+    pub fn list_deleted_documents(&self, collection: &str) -> Result<Vec<DeletedDocument>> {
+        self.history
+            .tombstones
+            .scan_prefix(format!("{}:", collection).as_bytes())
+            .map(|item| {
+                let tombstone: RetainedPayload<DeletedDocument> = serde_json::from_slice(&item?.1)?;
+                Ok(tombstone.record)
+            })
+            .collect()
+    }
+
+    /// Bring back a soft-deleted document as a new version. Fails if the
+    /// document has been written again since it was deleted.
+    pub async fn restore_deleted_document(&self, collection: &str, document_id: &str) -> Result<StorageResult<()>> {
+        let key = format!("{}:{}", collection, document_id);
+        let tombstone = self
+            .history
+            .tombstone(&key)?
+            .ok_or_else(|| anyhow::anyhow!("No deleted document to restore: {}", key))?;
+        let document = self.decompress_and_deserialize(collection, document_id, &tombstone.payload).await?;
+
+        let restored = self.store_document_with_mode(collection, document_id, &document, WriteMode::CreateOnly).await?;
+        info!("Restored deleted document {}", key);
+        Ok(restored)
+    }
+
+    /// Permanently remove the retained revisions and tombstone of a
+    /// document, returning how many records were removed. The current
+    /// version, if any, is kept.
+    pub fn purge_history(&self, collection: &str, document_id: &str) -> Result<usize> {
+        let key = format!("{}:{}", collection, document_id);
+        if self.legal_holds.is_held(&key) {
+            return Err(anyhow::anyhow!("Document {} is under legal hold", key));
+        }
+
+        let mut purged = 0;
+        let revision_keys: Vec<sled::IVec> = self
+            .history
+            .revisions
+            .scan_prefix(HistoryStore::revision_prefix(&key))
+            .keys()
+            .collect::<std::result::Result<_, _>>()?;
+        for revision_key in revision_keys {
+            self.history.revisions.remove(revision_key)?;
+            purged += 1;
+        }
+        if self.history.tombstones.remove(key.as_bytes())?.is_some() {
+            purged += 1;
+        }
+
+        info!("Purged {} history records of {}", purged, key);
+        Ok(purged)
+    }
+
+    /// Purge tombstones whose retention window has passed.
+    pub fn purge_expired_tombstones(&self) -> Result<usize> {
+        self.history.purge_expired_tombstones(&self.legal_holds)
+    }
+}
@@ -0,0 +1,543 @@
+//! # Background Jobs
+//!
+//! Long-running operations such as backups, restores and scrubs can run as
+//! jobs instead of keeping a request open. Every job is recorded in the
+//! metadata database with its state, progress, log and outcome, so it can
+//! be listed, inspected and cancelled while it runs, and reviewed after it
+//! finishes. Jobs started on behalf of a tenant carry its id, so each
+//! tenant can be shown only its own jobs.
+//!
+//! At most [`JobConfig::max_concurrent`] jobs run at a time; the others
+//! wait, queued in the order they were submitted. Cancelling a queued job
+//! keeps it from starting. A running job is asked to stop and ends as
+//! cancelled the next time it checks, which each kind of job does at points
+//! where stopping leaves no partial changes behind. Jobs that were queued
+//! or running when the node stopped are marked failed when it starts
+//! again. Finished jobs are kept for [`JobConfig::retention`].
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::{BackupKind, BackupManifest, RestoreReport, StorageHierarchy};
+
+/// How many jobs run at once and how long their records are kept.
+#[derive(Debug, Clone)]
+pub struct JobConfig {
+    /// Jobs running at the same time; further jobs wait in the queue
+    pub max_concurrent: usize,
+
+    /// How long finished jobs are kept
+    pub retention: Duration,
+
+    /// Log entries kept per job, oldest dropped first
+    pub max_log_entries: usize,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 2,
+            retention: Duration::from_secs(7 * 24 * 3600),
+            max_log_entries: 200,
+        }
+    }
+}
+
+/// Lifecycle state of a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for a free slot
+    Queued,
+
+    /// Running
+    Running,
+
+    /// Finished with a result
+    Succeeded,
+
+    /// Finished with an error
+    Failed,
+
+    /// Stopped on request
+    Cancelled,
+}
+
+impl JobState {
+    /// Whether the job has ended.
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+/// How far a job has come, in units of its kind such as documents.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// Units done
+    pub completed: u64,
+
+    /// Units in total, when known
+    pub total: Option<u64>,
+}
+
+/// One line of a job's log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobLogEntry {
+    /// When the line was written
+    pub at: DateTime<Utc>,
+
+    /// What happened
+    pub message: String,
+}
+
+/// Record of one job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    /// Identifier of the job
+    pub id: String,
+
+    /// What the job does, such as `backup`, `restore` or `scrub`
+    pub kind: String,
+
+    /// Tenant the job runs for, `None` for jobs of the operator
+    pub tenant: Option<String>,
+
+    /// Current state
+    pub state: JobState,
+
+    /// Progress reported by the job
+    pub progress: JobProgress,
+
+    /// Whether cancellation was requested
+    pub cancel_requested: bool,
+
+    /// When the job was submitted
+    pub created_at: DateTime<Utc>,
+
+    /// When the job started running
+    pub started_at: Option<DateTime<Utc>>,
+
+    /// When the job ended
+    pub finished_at: Option<DateTime<Utc>>,
+
+    /// Why the job failed
+    pub error: Option<String>,
+
+    /// Outcome of a succeeded job, such as a backup manifest
+    pub result: Option<serde_json::Value>,
+
+    /// Log lines, oldest first
+    pub logs: Vec<JobLogEntry>,
+}
+
+/// Persistent job records and the queue running them.
+#[derive(Debug)]
+pub struct JobRegistry {
+    tree: sled::Tree,
+    config: JobConfig,
+
+    /// Slots of running jobs; waiters are served in order
+    slots: Arc<Semaphore>,
+
+    /// Cancellation flags of queued and running jobs
+    active: DashMap<String, Arc<AtomicBool>>,
+}
+
+impl JobRegistry {
+    pub(crate) fn open(db: &sled::Db, config: &JobConfig) -> Result<Self> {
+        let registry = Self {
+            tree: db.open_tree("jobs")?,
+            config: config.clone(),
+            slots: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            active: DashMap::new(),
+        };
+
+        // Jobs do not survive a restart
+        for job in registry.all()? {
+            if !job.state.is_finished() {
+                let was = if job.state == JobState::Queued { "queued" } else { "running" };
+                registry.update(&job.id, |job| {
+                    job.state = JobState::Failed;
+                    job.finished_at = Some(Utc::now());
+                    job.error = Some(format!("Interrupted: the node stopped while the job was {}", was));
+                })?;
+            }
+        }
+        registry.prune();
+        Ok(registry)
+    }
+
+    /// Jobs, newest first, optionally only those of `tenant` or in `state`.
+    pub fn list(&self, tenant: Option<&str>, state: Option<JobState>) -> Result<Vec<Job>> {
+        let mut jobs: Vec<Job> = self
+            .all()?
+            .into_iter()
+            .filter(|job| tenant.is_none_or(|tenant| job.tenant.as_deref() == Some(tenant)))
+            .filter(|job| state.is_none_or(|state| job.state == state))
+            .collect();
+        jobs.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+        Ok(jobs)
+    }
+
+    /// Record of one job.
+    pub fn get(&self, id: &str) -> Result<Job> {
+        match self.tree.get(id.as_bytes())? {
+            Some(value) => Ok(serde_json::from_slice(&value)?),
+            None => Err(anyhow::anyhow!("Job not found: {}", id)),
+        }
+    }
+
+    /// Cancel a job: a queued job ends at once, a running one when it next
+    /// checks for cancellation.
+    pub fn cancel(&self, id: &str) -> Result<Job> {
+        if self.get(id)?.state.is_finished() {
+            return Err(anyhow::anyhow!("Job already finished: {}", id));
+        }
+        if let Some(flag) = self.active.get(id) {
+            flag.store(true, Ordering::SeqCst);
+        }
+        let job = self
+            .update(id, |job| {
+                if job.state.is_finished() {
+                    return;
+                }
+                job.cancel_requested = true;
+                if job.state == JobState::Queued {
+                    job.state = JobState::Cancelled;
+                    job.finished_at = Some(Utc::now());
+                }
+            })?
+            .ok_or_else(|| anyhow::anyhow!("Job not found: {}", id))?;
+        info!("Requested cancellation of job {} ({})", id, job.kind);
+        Ok(job)
+    }
+
+    /// Queue a job running `body` and return its record without waiting
+    /// for it.
+    pub fn spawn<T, F, Fut>(self: &Arc<Self>, kind: &str, tenant: Option<&str>, body: F) -> Result<Job>
+    where
+        T: Serialize + Send + 'static,
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let handle = self.submit(kind, tenant)?;
+        let job = self.get(&handle.id)?;
+        let registry = Arc::clone(self);
+        tokio::spawn(async move {
+            let _ = registry.execute(handle, body).await;
+        });
+        Ok(job)
+    }
+
+    /// Queue a job running `body` and wait for its outcome.
+    ///
+    /// The job is recorded as failed if the returned future is dropped
+    /// before it finishes.
+    pub async fn run<T, F, Fut>(self: &Arc<Self>, kind: &str, tenant: Option<&str>, body: F) -> Result<T>
+    where
+        T: Serialize,
+        F: FnOnce(JobHandle) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let handle = self.submit(kind, tenant)?;
+        self.execute(handle, body).await
+    }
+
+    /// Record a queued job and track its cancellation flag.
+    fn submit(self: &Arc<Self>, kind: &str, tenant: Option<&str>) -> Result<JobHandle> {
+        self.prune();
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            tenant: tenant.map(str::to_string),
+            state: JobState::Queued,
+            progress: JobProgress::default(),
+            cancel_requested: false,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            error: None,
+            result: None,
+            logs: Vec::new(),
+        };
+        self.tree.insert(job.id.as_bytes(), serde_json::to_vec(&job)?)?;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.active.insert(job.id.clone(), Arc::clone(&cancelled));
+        Ok(JobHandle { id: job.id, registry: Arc::clone(self), cancelled })
+    }
+
+    /// Wait for a slot, run `body` and record how it ended.
+    async fn execute<T, F, Fut>(&self, handle: JobHandle, body: F) -> Result<T>
+    where
+        T: Serialize,
+        F: FnOnce(JobHandle) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut finish = Finish { registry: self, id: handle.id.clone(), done: false };
+        let _slot = Arc::clone(&self.slots).acquire_owned().await?;
+        if handle.is_cancelled() {
+            finish.done = true;
+            self.active.remove(&handle.id);
+            return Err(anyhow::anyhow!("Job cancelled: {}", handle.id));
+        }
+        self.record(&handle.id, |job| {
+            job.state = JobState::Running;
+            job.started_at = Some(Utc::now());
+        });
+        info!("Started job {}", handle.id);
+
+        let cancelled = Arc::clone(&handle.cancelled);
+        let outcome = body(handle).await;
+        let cancelled = cancelled.load(Ordering::SeqCst);
+        let result = outcome.as_ref().ok().and_then(|value| serde_json::to_value(value).ok());
+        finish.done = true;
+        self.active.remove(&finish.id);
+        self.record(&finish.id, |job| {
+            job.finished_at = Some(Utc::now());
+            match &outcome {
+                Ok(_) => {
+                    job.state = JobState::Succeeded;
+                    job.result = result.clone();
+                }
+                Err(_) if cancelled => job.state = JobState::Cancelled,
+                Err(e) => {
+                    job.state = JobState::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        });
+        match &outcome {
+            Ok(_) => info!("Job {} succeeded", finish.id),
+            Err(e) if cancelled => info!("Job {} cancelled: {}", finish.id, e),
+            Err(e) => warn!("Job {} failed: {}", finish.id, e),
+        }
+        outcome
+    }
+
+    /// Delete finished jobs older than the retention period.
+    fn prune(&self) {
+        let Ok(retention) = chrono::Duration::from_std(self.config.retention) else { return };
+        let cutoff = Utc::now() - retention;
+        match self.all() {
+            Ok(jobs) => {
+                for job in jobs {
+                    if job.finished_at.is_some_and(|finished_at| finished_at < cutoff) {
+                        let _ = self.tree.remove(job.id.as_bytes());
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to prune finished jobs: {}", e),
+        }
+    }
+
+    fn all(&self) -> Result<Vec<Job>> {
+        self.tree
+            .iter()
+            .values()
+            .map(|value| Ok(serde_json::from_slice(&value?)?))
+            .collect()
+    }
+
+    /// Apply `change` to a job atomically; returns the updated job, or
+    /// `None` if there is no such job.
+    fn update(&self, id: &str, mut change: impl FnMut(&mut Job)) -> Result<Option<Job>> {
+        let updated = self.tree.update_and_fetch(id.as_bytes(), |old| {
+            let old = old?;
+            let Ok(mut job) = serde_json::from_slice::<Job>(old) else { return Some(old.to_vec()) };
+            change(&mut job);
+            Some(serde_json::to_vec(&job).unwrap_or_else(|_| old.to_vec()))
+        })?;
+        Ok(updated.map(|value| serde_json::from_slice(&value)).transpose()?)
+    }
+
+    /// Update a job, logging instead of failing, so a job's outcome is not
+    /// lost to an error recording it.
+    fn record(&self, id: &str, change: impl FnMut(&mut Job)) {
+        if let Err(e) = self.update(id, change) {
+            warn!("Failed to update job {}: {}", id, e);
+        }
+    }
+}
+
+/// Marks a job that stopped without an outcome, because its future was
+/// dropped, as failed.
+struct Finish<'a> {
+    registry: &'a JobRegistry,
+    id: String,
+    done: bool,
+}
+
+impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        self.registry.active.remove(&self.id);
+        self.registry.record(&self.id, |job| {
+            if !job.state.is_finished() {
+                job.state = JobState::Failed;
+                job.finished_at = Some(Utc::now());
+                job.error = Some("Abandoned before it finished".to_string());
+            }
+        });
+    }
+}
+
+/// Passed to a running job to report progress and notice cancellation.
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    id: String,
+    registry: Arc<JobRegistry>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    /// Identifier of the job.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fail with a cancellation error if cancellation was requested.
+    pub fn check_cancelled(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(anyhow::anyhow!("Job cancelled: {}", self.id)),
+            false => Ok(()),
+        }
+    }
+
+    /// Record how far the job has come.
+    pub fn set_progress(&self, completed: u64, total: Option<u64>) {
+        self.registry.record(&self.id, |job| job.progress = JobProgress { completed, total });
+    }
+
+    /// Append a line to the job's log.
+    pub fn log(&self, message: impl Into<String>) {
+        let entry = JobLogEntry { at: Utc::now(), message: message.into() };
+        let max = self.registry.config.max_log_entries;
+        self.registry.record(&self.id, |job| {
+            job.logs.push(entry.clone());
+            if job.logs.len() > max {
+                let excess = job.logs.len() - max;
+                job.logs.drain(..excess);
+            }
+        });
+    }
+}
+
+impl StorageHierarchy {
+    /// Job records and queue.
+    pub fn jobs(&self) -> &Arc<JobRegistry> {
+        &self.jobs
+    }
+
+    /// Take a backup as a job, limited to `scope` like
+    /// [`StorageHierarchy::create_scoped_backup`].
+    pub fn spawn_backup_job(
+        self: &Arc<Self>,
+        kind: BackupKind,
+        scope: Option<String>,
+        tenant: Option<&str>,
+    ) -> Result<Job> {
+        let storage = Arc::clone(self);
+        self.jobs.spawn("backup", tenant, move |job| async move {
+            storage.backup_in_job(kind, scope.as_deref(), &job).await
+        })
+    }
+
+    /// Take a backup as a job and wait for it.
+    pub async fn run_backup_job(
+        &self,
+        kind: BackupKind,
+        scope: Option<&str>,
+        tenant: Option<&str>,
+    ) -> Result<BackupManifest> {
+        self.jobs
+            .run("backup", tenant, |job| async move { self.backup_in_job(kind, scope, &job).await })
+            .await
+    }
+
+    /// Restore backup `id` as a job, in place or into `namespace` like
+    /// [`StorageHierarchy::restore_backup_into`].
+    pub fn spawn_restore_job(
+        self: &Arc<Self>,
+        id: &str,
+        namespace: Option<String>,
+        tenant: Option<&str>,
+    ) -> Result<Job> {
+        let storage = Arc::clone(self);
+        let id = id.to_string();
+        self.jobs.spawn("restore", tenant, move |job| async move {
+            storage.restore_in_job(&id, namespace.as_deref(), &job).await
+        })
+    }
+
+    /// Restore backup `id` as a job and wait for it.
+    pub async fn run_restore_job(
+        &self,
+        id: &str,
+        namespace: Option<&str>,
+        tenant: Option<&str>,
+    ) -> Result<RestoreReport> {
+        self.jobs
+            .run("restore", tenant, |job| async move { self.restore_in_job(id, namespace, &job).await })
+            .await
+    }
+
+    async fn backup_in_job(&self, kind: BackupKind, scope: Option<&str>, job: &JobHandle) -> Result<BackupManifest> {
+        let what = match kind {
+            BackupKind::Full => "full",
+            BackupKind::Incremental => "incremental",
+        };
+        job.log(format!("Taking a {} backup of {}", what, scope.unwrap_or("all collections")));
+        let manifest = self.run_backup(kind, scope, Some(job)).await?;
+        job.log(format!("Wrote backup {} with {} of {} documents", manifest.id, manifest.documents, manifest.total_documents));
+        Ok(manifest)
+    }
+
+    async fn restore_in_job(&self, id: &str, namespace: Option<&str>, job: &JobHandle) -> Result<RestoreReport> {
+        let report = match namespace {
+            Some(namespace) => {
+                job.log(format!("Restoring backup {} into namespace {}", id, namespace));
+                self.run_restore_into(id, namespace, Some(job)).await?
+            }
+            None => {
+                job.log(format!("Restoring backup {}", id));
+                self.run_restore(id, Some(job)).await?
+            }
+        };
+        job.log(format!("Restored {} documents, {} missing", report.restored, report.missing.len()));
+        Ok(report)
+    }
+
+    /// Scrub stored copies as a job.
+    pub fn spawn_scrub_job(self: &Arc<Self>, repair: bool) -> Result<Job> {
+        let scrubber = self.scrubber();
+        self.jobs.spawn("scrub", None, move |job| async move {
+            job.log(if repair { "Scrubbing and repairing stored copies" } else { "Scrubbing stored copies" });
+            let report = scrubber.run(repair).await?;
+            job.set_progress(report.documents as u64, Some((report.documents + report.skipped) as u64));
+            job.log(format!(
+                "Checked {} documents: {} corrupt copies, {} repaired, {} unrecoverable",
+                report.documents,
+                report.corrupt.len(),
+                report.repaired,
+                report.unrecoverable.len()
+            ));
+            Ok(report)
+        })
+    }
+}
@@ -0,0 +1,276 @@
+//! # Legal Holds
+//!
+//! A legal hold preserves a set of documents for litigation or an
+//! investigation, regardless of retention policy. While any hold covers a
+//! document, every removal path refuses to delete it: single and batch
+//! deletes, and with them ephemeral-document expiry and erasure requests, as
+//! well as tier migration, which leaves held documents on their current tier.
+//! Reads and updates are unaffected.
+//!
+//! A hold names documents of one collection, either listed explicitly or
+//! resolved from a query when the hold is placed; documents matching the
+//! query later are not covered. Holds are persisted next to the document
+//! metadata, and every placement and release is appended to an audit log
+//! that is never pruned.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::StorageHierarchy;
+
+/// A set of documents preserved until the hold is released.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegalHold {
+    /// Unique hold identifier
+    pub id: String,
+
+    /// Collection the held documents belong to
+    pub collection: String,
+
+    /// Identifiers of the held documents
+    pub document_ids: Vec<String>,
+
+    /// Filter the documents were selected with, for query-defined holds
+    pub query: Option<serde_json::Value>,
+
+    /// Why the documents are held, e.g. a case or ticket reference
+    pub reason: String,
+
+    /// Who placed the hold
+    pub placed_by: String,
+
+    /// When the hold was placed
+    pub placed_at: DateTime<Utc>,
+}
+
+impl LegalHold {
+    /// Create a hold over explicitly listed documents.
+    pub fn new(collection: &str, document_ids: Vec<String>, reason: &str, placed_by: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            collection: collection.to_string(),
+            document_ids,
+            query: None,
+            reason: reason.to_string(),
+            placed_by: placed_by.to_string(),
+            placed_at: Utc::now(),
+        }
+    }
+
+    /// Record the filter the held documents were selected with.
+    pub fn with_query(mut self, query: serde_json::Value) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.document_ids.iter().map(|id| format!("{}:{}", self.collection, id))
+    }
+}
+
+/// Operation recorded in the legal hold audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegalHoldAction {
+    Placed,
+    Released,
+}
+
+/// One entry of the legal hold audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegalHoldEvent {
+    /// When the operation happened
+    pub at: DateTime<Utc>,
+
+    /// Whether the hold was placed or released
+    pub action: LegalHoldAction,
+
+    /// Who performed the operation
+    pub actor: String,
+
+    /// The hold as it was when the operation happened
+    pub hold: LegalHold,
+}
+
+/// Active holds and their audit log, persisted in the metadata database.
+#[derive(Debug)]
+pub(crate) struct LegalHoldRegistry {
+    /// Active holds by id
+    holds: DashMap<String, LegalHold>,
+
+    /// Ids of the holds covering each `collection:document_id`
+    held: DashMap<String, HashSet<String>>,
+
+    db: sled::Db,
+    holds_tree: sled::Tree,
+    audit_tree: sled::Tree,
+}
+
+impl LegalHoldRegistry {
+    /// Load active holds from `db`.
+    pub(crate) fn open(db: &sled::Db) -> Result<Self> {
+        let registry = Self {
+            holds: DashMap::new(),
+            held: DashMap::new(),
+            db: db.clone(),
+            holds_tree: db.open_tree("legal_holds")?,
+            audit_tree: db.open_tree("legal_hold_audit")?,
+        };
+
+        for item in registry.holds_tree.iter() {
+            let (key, value) = item?;
+            match serde_json::from_slice::<LegalHold>(&value) {
+                Ok(hold) => registry.index(hold),
+                Err(e) => warn!("Skipping unreadable legal hold {}: {}", String::from_utf8_lossy(&key), e),
+            }
+        }
+
+        Ok(registry)
+    }
+
+    fn index(&self, hold: LegalHold) {
+        for key in hold.keys() {
+            self.held.entry(key).or_default().insert(hold.id.clone());
+        }
+        self.holds.insert(hold.id.clone(), hold);
+    }
+
+    fn place(&self, hold: LegalHold) -> Result<()> {
+        self.holds_tree.insert(hold.id.as_bytes(), serde_json::to_vec(&hold)?)?;
+        self.audit(LegalHoldAction::Placed, &hold.placed_by, &hold)?;
+        self.index(hold);
+        Ok(())
+    }
+
+    fn release(&self, hold_id: &str, released_by: &str) -> Result<LegalHold> {
+        let hold = self
+            .holds
+            .get(hold_id)
+            .map(|hold| hold.clone())
+            .ok_or_else(|| anyhow::anyhow!("Legal hold not found: {}", hold_id))?;
+
+        self.holds_tree.remove(hold_id.as_bytes())?;
+        self.audit(LegalHoldAction::Released, released_by, &hold)?;
+
+        self.holds.remove(hold_id);
+        for key in hold.keys() {
+            self.held.remove_if_mut(&key, |_, ids| {
+                ids.remove(hold_id);
+                ids.is_empty()
+            });
+        }
+        Ok(hold)
+    }
+
+    fn audit(&self, action: LegalHoldAction, actor: &str, hold: &LegalHold) -> Result<()> {
+        let event = LegalHoldEvent { at: Utc::now(), action, actor: actor.to_string(), hold: hold.clone() };
+        let sequence = self.db.generate_id()?;
+        self.audit_tree.insert(sequence.to_be_bytes(), serde_json::to_vec(&event)?)?;
+
+        info!(
+            "Audit: legal hold {} on {} document(s) of {} {:?} by {} ({})",
+            hold.id, hold.document_ids.len(), hold.collection, action, actor, hold.reason
+        );
+        Ok(())
+    }
+
+    pub(crate) fn is_held(&self, key: &str) -> bool {
+        self.held.contains_key(key)
+    }
+}
+
+impl StorageHierarchy {
+    /// Place a legal hold, preventing its documents from being removed until
+    /// it is released.
+    pub fn place_legal_hold(&self, hold: LegalHold) -> Result<LegalHold> {
+        if hold.reason.trim().is_empty() {
+            return Err(anyhow::anyhow!("Legal hold reason must not be empty"));
+        }
+        if hold.document_ids.is_empty() {
+            return Err(anyhow::anyhow!("Legal hold must cover at least one document"));
+        }
+
+        self.legal_holds.place(hold.clone())?;
+        Ok(hold)
+    }
+
+    /// Release a legal hold. Its documents stay protected while another hold
+    /// covers them.
+    pub fn release_legal_hold(&self, hold_id: &str, released_by: &str) -> Result<LegalHold> {
+        self.legal_holds.release(hold_id, released_by)
+    }
+
+    /// Active legal holds, oldest first.
+    pub fn legal_holds(&self) -> Vec<LegalHold> {
+        let mut holds: Vec<LegalHold> = self.legal_holds.holds.iter().map(|hold| hold.clone()).collect();
+        holds.sort_by(|a, b| a.placed_at.cmp(&b.placed_at).then_with(|| a.id.cmp(&b.id)));
+        holds
+    }
+
+    /// Whether any legal hold covers a document.
+    pub fn is_under_legal_hold(&self, collection: &str, document_id: &str) -> bool {
+        self.legal_holds.is_held(&format!("{}:{}", collection, document_id))
+    }
+
+    /// Every legal hold placement and release, oldest first.
+    pub fn legal_hold_audit_log(&self) -> Result<Vec<LegalHoldEvent>> {
+        self.legal_holds
+            .audit_tree
+            .iter()
+            .map(|item| Ok(serde_json::from_slice(&item?.1)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_holds_survive_reopening_and_overlap() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-legal-hold-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let first = LegalHold::new("mail", vec!["1".to_string(), "2".to_string()], "case 7", "alice");
+        let second = LegalHold::new("mail", vec!["2".to_string()], "case 9", "bob");
+        let db = sled::open(&dir).unwrap();
+        {
+            let registry = LegalHoldRegistry::open(&db).unwrap();
+            registry.place(first.clone()).unwrap();
+            registry.place(second.clone()).unwrap();
+        }
+
+        // Holds are loaded back from the database
+        let registry = LegalHoldRegistry::open(&db).unwrap();
+        assert!(registry.is_held("mail:1") && registry.is_held("mail:2"));
+
+        registry.release(&first.id, "carol").unwrap();
+        assert!(!registry.is_held("mail:1"));
+        assert!(registry.is_held("mail:2"));
+        assert!(registry.release(&first.id, "carol").is_err());
+
+        let actions: Vec<_> = registry
+            .audit_tree
+            .iter()
+            .map(|item| serde_json::from_slice::<LegalHoldEvent>(&item.unwrap().1).unwrap())
+            .map(|event| (event.action, event.actor))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (LegalHoldAction::Placed, "alice".to_string()),
+                (LegalHoldAction::Placed, "bob".to_string()),
+                (LegalHoldAction::Released, "carol".to_string()),
+            ]
+        );
+
+        drop(registry);
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,8 +2,8 @@
 use anyhow::Result;              // Unified error handling
 use std::sync::Arc;              // Thread-safe reference counting
 use std::path::PathBuf;          // File system path operations
-use tracing::{info, debug, error}; // Structured logging
-use dashmap::DashMap;            // Concurrent hash map for metadata storage
+use tracing::{info, debug, error, warn}; // Structured logging
+use aerolithdb_security::{DataEncryption, SecurityFramework}; // Payload encryption at rest
 
 // Internal storage subsystem modules
 mod sharding;      // Consistent hashing and data distribution
@@ -11,6 +11,30 @@ mod replication;   // Data replication across nodes and tiers
 mod backends;      // Storage backend implementations
 mod compression;   // Data compression algorithms and optimization
 mod datacenter_replication; // Cross-datacenter replication and global consistency
+mod metadata;      // Persistent document metadata with startup recovery
+mod batch;         // Batch store, get and delete operations
+mod legal_hold;    // Legal holds preventing document removal
+mod history;       // Soft delete tombstones and document version history
+mod worm;          // Write-once-read-many collections
+mod residency;     // Data residency tags and placement enforcement
+mod collections;   // Registered collections and their settings
+mod streaming;     // Chunked storage of large documents
+mod tiering;       // Rule-based migration of documents between tiers
+mod access;        // Sampled read statistics of documents
+mod heavy_hitters; // Windowed space-saving counts of the heaviest keys of a stream
+mod archive;       // Local and S3-compatible backends of the Archive tier
+mod scrub;         // Verification and repair of stored copies against their checksums
+mod backup;        // Full and incremental backup bundles and restores
+mod jobs;          // Persistent background jobs with progress and cancellation
+mod wal;           // Write-ahead log of hot-tier writes awaiting replication
+mod statistics;    // Field statistics of collections for query planning
+mod quota;         // Storage accounting and enforcement of the storage limit
+mod rebalance;     // Cluster membership and movement of documents between nodes
+mod expiration;    // Per-document time to live and removal of expired documents
+mod dictionaries;  // Trained ZSTD dictionaries of collections
+mod listing;       // Filtered and sorted listings of documents with their metadata
+mod checksum;      // Configurable checksum algorithms of payloads and chunks
+mod consistency;   // Per-collection write and read consistency through consensus
 
 // Re-export public interfaces from internal modules
 pub use sharding::*;      // Sharding strategies and hash ring management
@@ -18,6 +42,29 @@ pub use replication::{ReplicationManager, ReplicationStatus}; // Replication pol
 pub use backends::*;      // Memory, SSD, distributed, and archival storage
 pub use compression::*;   // LZ4, Zstd, and adaptive compression
 pub use datacenter_replication::*; // Cross-datacenter replication capabilities
+pub use metadata::{MetadataGuard, MetadataStore}; // Durable document metadata
+pub use legal_hold::{LegalHold, LegalHoldAction, LegalHoldEvent}; // Legal hold records and audit log
+pub use history::{DeletedDocument, HistoryConfig}; // Retention of deleted and replaced documents
+pub use worm::WormPolicy; // Retention of write-once-read-many collections
+pub use residency::{ResidencyConfig, ResidencyRemediation, ResidencyViolation}; // Data residency
+pub use collections::{Collection, CollectionConfig, CollectionInfo}; // Collection management
+pub use streaming::{ChunkInfo, ChunkManifest, DocumentStream}; // Streaming of large documents
+pub use tiering::{TierMigration, TierMigrationMetrics, TierMigrationReport, TierPolicyConfig, TierRule}; // Tier migration policies
+pub use access::{AccessStats, AccessTrackingConfig}; // Access frequency tracking
+pub use heavy_hitters::{HeavyHitter, HeavyHitterConfig, HeavyHitters}; // Hottest keys over sliding windows
+pub use archive::{ArchiveBackend, ArchiveBackendConfig, ArchiveConfig, LocalArchiveBackend, S3ArchiveBackend, S3ArchiveConfig}; // Archive tier backends
+pub use scrub::{CorruptCopy, ScrubConfig, ScrubReport}; // Data integrity scrubbing
+pub use backup::{BackupKind, BackupManager, BackupManifest, RestoreReport}; // Backup and restore
+pub use jobs::{Job, JobConfig, JobHandle, JobLogEntry, JobProgress, JobRegistry, JobState}; // Background jobs
+pub use wal::{WalConfig, WalReplayReport}; // Write-ahead logging
+pub use statistics::{CollectionStatistics, FieldStatistics, Histogram, StatisticsConfig}; // Collection statistics
+pub use quota::{CollectionUsage, QuotaAction, QuotaConfig, QuotaEvent, StorageUsage, TierUsage}; // Storage quota
+pub use rebalance::{ClusterNode, HttpShardTransport, MembershipChange, RebalanceConfig, RebalancePlan, RebalanceReport, ShardMove, ShardTransport, TransferredDocument, TRANSFER_PATH}; // Shard rebalancing
+pub use expiration::{ExpirationAction, ExpirationConfig, ExpirationEvent, ExpirationReport}; // Document expiration
+pub use dictionaries::{CompressionDictionary, DictionaryConfig}; // Compression dictionaries
+pub use listing::{DocumentPage, DocumentSortKey, ListOptions, ListedDocument}; // Document listings
+pub use checksum::{ChecksumAlgorithm, ChecksumConfig, ChecksumHasher, ChecksumStats, HashAcceleration}; // Payload checksums
+pub use consistency::{ReadBarrier, ReadConsistency, ReplicatedWrite, WriteConsistency, WriteReplicator}; // Strongly consistent writes and reads
 
 /// Configuration for the hierarchical storage system.
 /// 
@@ -36,18 +83,73 @@ pub struct StorageConfig {
     /// Compression settings for reducing storage footprint
     pub compression: CompressionConfig,
     
-    /// Whether to encrypt data at rest for security compliance
+    /// Whether to encrypt data at rest for security compliance. Takes effect
+    /// once a security framework with a master key is attached through
+    /// [`StorageHierarchy::with_encryption`].
     pub encryption_at_rest: bool,
     
     /// Root directory for local storage tiers (warm, cold, archive)
     pub data_dir: PathBuf,
     
-    /// Optional maximum storage size limit in bytes
-    /// When reached, triggers automatic data archival or cleanup
+    /// Optional maximum storage size limit in bytes, counting documents
+    /// outside the Archive tier and retained revisions and tombstones.
+    /// How it is enforced is set by `quota`.
     pub max_storage_size: Option<u64>,
     
     /// Cross-datacenter replication configuration for global consistency
     pub datacenter_replication: Option<DatacenterReplicationConfig>,
+
+    /// Soft delete and version history; both are off by default
+    pub history: HistoryConfig,
+
+    /// Collections made write-once-read-many at startup, with the retention
+    /// during which their documents cannot be updated or deleted
+    pub worm_collections: std::collections::HashMap<String, std::time::Duration>,
+
+    /// Region of this node and the regions residency tags allow
+    pub residency: ResidencyConfig,
+
+    /// Uncompressed size in bytes of the chunks documents are streamed in
+    pub stream_chunk_size: usize,
+
+    /// Rules moving documents between tiers and how often they run
+    pub tiering: TierPolicyConfig,
+
+    /// Sampling and persistence of document read statistics
+    pub access_tracking: AccessTrackingConfig,
+
+    /// Backend of the Archive tier and how often orphaned objects are swept
+    pub archive: ArchiveConfig,
+
+    /// How often stored copies are verified against their checksums
+    pub scrub: ScrubConfig,
+
+    /// Directory holding backup bundles, `<data_dir>/backups` if unset
+    pub backup_dir: Option<PathBuf>,
+
+    /// How many background jobs run at once and how long they are kept
+    pub jobs: JobConfig,
+
+    /// Logging of writes until they are replicated beyond the hot tier
+    pub wal: WalConfig,
+
+    /// Sampling of the field statistics used for query planning
+    pub statistics: StatisticsConfig,
+
+    /// What happens as usage approaches `max_storage_size`
+    pub quota: QuotaConfig,
+
+    /// Id of this node and how documents move when membership changes
+    pub rebalance: RebalanceConfig,
+
+    /// What happens to documents once their time to live has passed
+    pub expiration: ExpirationConfig,
+
+    /// Training and use of compression dictionaries for Zstd collections
+    pub dictionaries: DictionaryConfig,
+
+    /// Algorithm of new checksums and migration of existing ones
+    pub checksum: ChecksumConfig,
 }
 
 impl Default for StorageConfig {
@@ -64,6 +166,23 @@ impl Default for StorageConfig {
             data_dir: std::path::PathBuf::from("./data"),
             max_storage_size: None,
             datacenter_replication: None, // Disabled by default
+            history: HistoryConfig::default(),
+            worm_collections: std::collections::HashMap::new(),
+            residency: ResidencyConfig::default(),
+            stream_chunk_size: 4 * 1024 * 1024,
+            tiering: TierPolicyConfig::default(),
+            access_tracking: AccessTrackingConfig::default(),
+            archive: ArchiveConfig::default(),
+            scrub: ScrubConfig::default(),
+            backup_dir: None,
+            jobs: JobConfig::default(),
+            wal: WalConfig::default(),
+            statistics: StatisticsConfig::default(),
+            quota: QuotaConfig::default(),
+            rebalance: RebalanceConfig::default(),
+            expiration: ExpirationConfig::default(),
+            dictionaries: DictionaryConfig::default(),
+            checksum: ChecksumConfig::default(),
         }
     }
 }
@@ -136,7 +255,7 @@ pub struct StorageHierarchy {
     archive_layer: Arc<ObjectStorage>,
     
     /// Sharding engine for data distribution and load balancing
-    sharding_engine: Arc<ShardingEngine>,
+    sharding_engine: Arc<tokio::sync::RwLock<ShardingEngine>>,
       /// Replication manager for data durability and availability
     replication_manager: Arc<ReplicationManager>,
     
@@ -146,8 +265,62 @@ pub struct StorageHierarchy {
     /// Compression engine for storage efficiency
     compression_engine: Arc<CompressionEngine>,
     
-    /// Concurrent metadata store for document information
-    metadata_store: Arc<DashMap<String, DocumentMetadata>>,
+    /// Concurrent metadata store for document information, persisted to disk
+    metadata_store: Arc<MetadataStore>,
+
+    /// Legal holds protecting documents from removal
+    legal_holds: Arc<legal_hold::LegalHoldRegistry>,
+
+    /// Tombstones and previous revisions of documents
+    history: Arc<history::HistoryStore>,
+
+    /// Retention policies of write-once-read-many collections
+    worm: Arc<worm::WormRegistry>,
+
+    /// Residency tags of collections and documents, and their violations
+    residency: Arc<residency::ResidencyRegistry>,
+
+    /// Registered collections and their settings
+    collections: Arc<collections::CollectionRegistry>,
+
+    /// Chunk manifests of streamed documents
+    chunks: Arc<streaming::ChunkManifests>,
+
+    /// Tier migration rules, read counts and migration metrics
+    tiering: Arc<tiering::TierPolicy>,
+
+    /// Sampled read statistics of documents
+    access: Arc<access::AccessTracker>,
+
+    /// Report of the last integrity scrub
+    scrub_log: Arc<scrub::ScrubLog>,
+
+    /// Backup bundles
+    backups: Arc<BackupManager>,
+
+    /// Background job records and queue
+    jobs: Arc<JobRegistry>,
+
+    /// Log of writes awaiting replication beyond the hot tier
+    wal: Arc<wal::WriteAheadLog>,
+
+    /// Quota events and the state of the background quota check
+    quota: Arc<quota::QuotaState>,
+
+    /// Cluster members and the transport moving documents between them
+    membership: Arc<rebalance::ClusterMembership>,
+
+    /// Publishes the deletion and archival of expired documents
+    expirations: tokio::sync::broadcast::Sender<ExpirationEvent>,
+
+    /// Trained compression dictionaries of collections
+    dictionaries: Arc<dictionaries::DictionaryStore>,
+
+    /// Payload encryption, set when encryption at rest is active
+    encryption: Option<Arc<DataEncryption>>,
+
+    /// Replicator of the writes of strongly consistent collections
+    replicator: consistency::ReplicatorSlot,
 }
 
 /// Comprehensive metadata for stored documents.
@@ -179,8 +352,13 @@ pub struct DocumentMetadata {
     /// Document version for optimistic concurrency control
     pub version: u64,
     
-    /// SHA-256 checksum for data integrity verification
+    /// Checksum of the stored payload for data integrity verification
     pub checksum: String,
+
+    /// Algorithm `checksum` was computed with; metadata written before
+    /// algorithms were configurable is BLAKE3
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
     
     /// Current storage tier where primary copy resides
     pub storage_tier: StorageTier,
@@ -193,6 +371,22 @@ pub struct DocumentMetadata {
     
     /// Optional encryption key identifier for encrypted documents
     pub encryption_key_id: Option<String>,
+
+    /// When the document expires and is deleted or archived, if ever
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Whether the payload was stored without compression because the
+    /// document or its collection was marked as already compressed
+    #[serde(default)]
+    pub compression_skipped: bool,
+}
+
+impl DocumentMetadata {
+    /// Whether `payload` is the stored payload this metadata describes.
+    pub fn checksum_matches(&self, payload: &[u8]) -> bool {
+        self.checksum_algorithm.verify(payload, &self.checksum)
+    }
 }
 
 /// Storage tier classification for data placement optimization.
@@ -227,6 +421,54 @@ pub enum StorageTier {
     Archive,
 }
 
+/// Outcome of rebuilding document metadata from the storage tiers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MetadataRepairReport {
+    /// Documents found in the persistent tiers
+    pub scanned: usize,
+
+    /// Documents without metadata whose metadata was recreated
+    pub recovered: usize,
+
+    /// Metadata entries corrected to match the stored payload
+    pub repaired: usize,
+
+    /// Metadata entries dropped because no tier holds the document
+    pub removed: usize,
+}
+
+/// How `store_document_with_mode` treats an existing document.
+///
+/// Existence and version checks are atomic with the metadata write, so two
+/// racing writers can never both satisfy the same expectation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Create the document, failing if it already exists
+    CreateOnly,
+
+    /// Create the document or replace it, continuing its version history
+    #[default]
+    Upsert,
+
+    /// Replace an existing document, failing if it does not exist or, when
+    /// an expected version is given, if its version differs
+    Replace { expected_version: Option<u64> },
+}
+
+/// Options of a single document write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Expire the document this long after the write; without it the
+    /// document keeps its current expiry
+    pub ttl: Option<std::time::Duration>,
+
+    /// Store the payload without compressing it, for documents that are
+    /// already compressed or incompressible such as images or encrypted
+    /// blobs
+    pub skip_compression: bool,
+}
+
 /// Result wrapper for storage operations with performance metrics.
 /// 
 /// Provides detailed information about where data was retrieved from,
@@ -257,7 +499,8 @@ impl StorageHierarchy {
     /// - Setting up storage layer backends
     /// - Configuring sharding and replication
     /// - Initializing compression engine
-    /// - Creating metadata tracking structures
+    /// - Loading persisted document metadata, rebuilding it from the
+    ///   persistent tiers when none is found
     /// 
     /// # Arguments
     /// * `config` - Storage configuration specifying behavior and limits
@@ -277,6 +520,11 @@ impl StorageHierarchy {
     /// ```
     pub async fn new(config: &StorageConfig) -> Result<Self> {
         info!("Initializing storage hierarchy");
+        let acceleration = HashAcceleration::detect();
+        info!(
+            "Checksumming with {} (BLAKE3 on {}, SHA-256 on {})",
+            config.checksum.algorithm, acceleration.blake3, acceleration.sha256
+        );
 
         // Create the root data directory and ensure proper permissions
         tokio::fs::create_dir_all(&config.data_dir).await?;
@@ -285,8 +533,32 @@ impl StorageHierarchy {
         let hot_layer = Arc::new(MemoryCache::new().await?);
         let warm_layer = Arc::new(LocalSSDCache::new(&config.data_dir.join("warm")).await?);
         let cold_layer = Arc::new(DistributedStorage::new(&config.data_dir.join("cold")).await?);
-        let archive_layer = Arc::new(ObjectStorage::new(&config.data_dir.join("archive")).await?);        // Initialize supporting engines for data management
-        let sharding_engine = Arc::new(ShardingEngine::new(&sharding::ShardingStrategy::ConsistentHash, config.replication_factor));
+        let archive_layer = Arc::new(ObjectStorage::open(&config.data_dir.join("archive"), &config.archive).await?);
+        let metadata_store = Arc::new(MetadataStore::open(&config.data_dir.join("metadata"))?);
+        let legal_holds = Arc::new(legal_hold::LegalHoldRegistry::open(metadata_store.db())?);
+        let history = Arc::new(history::HistoryStore::open(metadata_store.db(), &config.history)?);
+        let worm = Arc::new(worm::WormRegistry::open(metadata_store.db())?);
+        let residency = Arc::new(residency::ResidencyRegistry::open(metadata_store.db(), &config.residency)?);
+        let collections = Arc::new(collections::CollectionRegistry::open(metadata_store.db())?);
+        let chunks = Arc::new(streaming::ChunkManifests::open(metadata_store.db())?);
+        let tiering = Arc::new(tiering::TierPolicy::open(metadata_store.db(), &config.tiering)?);
+        let access = Arc::new(access::AccessTracker::open(metadata_store.db(), &config.access_tracking)?);
+        let scrub_log = Arc::new(scrub::ScrubLog::open(metadata_store.db())?);
+        let backup_dir = config.backup_dir.clone().unwrap_or_else(|| config.data_dir.join("backups"));
+        let backups = Arc::new(BackupManager::new(backup_dir));
+        let jobs = Arc::new(JobRegistry::open(metadata_store.db(), &config.jobs)?);
+        let (wal, wal_recovery) = wal::WriteAheadLog::open(&config.data_dir.join("wal"), &config.wal)?;
+        let wal = Arc::new(wal);
+
+        // Initialize supporting engines for data management
+        let membership = Arc::new(rebalance::ClusterMembership::open(metadata_store.db(), &config.rebalance)?);
+        let dictionaries = Arc::new(dictionaries::DictionaryStore::open(metadata_store.db(), &config.dictionaries)?);
+        let nodes: Vec<String> = membership.list()?.into_iter().map(|node| node.id).collect();
+        let sharding_engine = Arc::new(tokio::sync::RwLock::new(ShardingEngine::with_nodes(
+            &sharding::ShardingStrategy::ConsistentHash,
+            config.replication_factor,
+            &nodes,
+        )));
         let replication_manager = Arc::new(ReplicationManager::new(config.replication_factor));
         let compression_engine = Arc::new(CompressionEngine::new(&config.compression));
 
@@ -302,7 +574,7 @@ impl StorageHierarchy {
             None
         };
 
-        Ok(Self {
+        let storage = Self {
             config: config.clone(),
             hot_layer,
             warm_layer,
@@ -312,8 +584,206 @@ impl StorageHierarchy {
             replication_manager,
             datacenter_replication_manager,
             compression_engine,
-            metadata_store: Arc::new(DashMap::new()),
-        })
+            metadata_store,
+            legal_holds,
+            history,
+            worm,
+            residency,
+            collections,
+            chunks,
+            tiering,
+            access,
+            scrub_log,
+            backups,
+            jobs,
+            wal,
+            quota: Arc::new(quota::QuotaState::new()),
+            membership,
+            expirations: tokio::sync::broadcast::channel(expiration::EVENT_CAPACITY).0,
+            dictionaries,
+            encryption: None,
+            replicator: consistency::ReplicatorSlot::default(),
+        };
+
+        for (collection, retention) in &config.worm_collections {
+            if let Err(e) = storage.enable_worm(collection, *retention) {
+                warn!("Keeping persisted WORM policy: {}", e);
+            }
+        }
+
+        // Redo writes acknowledged before a crash but never replicated, then
+        // recover metadata for documents persisted before it was tracked, or
+        // after the metadata database was lost
+        let metadata_lost = storage.metadata_store.is_empty();
+        storage.discard_volatile_documents()?;
+        storage.replay_wal(wal_recovery).await?;
+        if metadata_lost {
+            let report = storage.rebuild_metadata().await?;
+            if report.recovered > 0 {
+                info!("Recovered metadata for {} documents from storage tiers", report.recovered);
+            }
+        }
+
+        Ok(storage)
+    }
+
+    /// Encrypt document payloads at rest with the keys of `security`.
+    ///
+    /// Has no effect unless `encryption_at_rest` is configured. Documents
+    /// written earlier in plaintext, or under a key that has since rotated,
+    /// are re-encrypted with the active key the next time they are read.
+    pub fn with_encryption(mut self, security: &SecurityFramework) -> Self {
+        if !self.config.encryption_at_rest {
+            return self;
+        }
+
+        match security.data_encryption() {
+            Some(encryption) => {
+                info!("Encrypting documents at rest with key {}", encryption.active_key_id());
+                self.encryption = Some(Arc::clone(encryption));
+            }
+            None => warn!("encryption_at_rest is set but no master key is configured; documents are stored unencrypted"),
+        }
+        self
+    }
+
+    /// Whether document payloads are encrypted before they are stored.
+    pub fn is_encrypted_at_rest(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// Directories this node keeps data in: the data directory and the
+    /// backup directory, which may be on another disk.
+    pub fn storage_paths(&self) -> Vec<PathBuf> {
+        vec![self.config.data_dir.clone(), self.backups.dir().to_path_buf()]
+    }
+
+    /// Rebuild document metadata from the persistent tiers.
+    ///
+    /// Scans the warm, cold and archive tiers, preferring the first copy found
+    /// in that order (or the hot copy when one is cached), and:
+    /// - recreates metadata for documents that have none. Their version
+    ///   restarts at 1 and their timestamps at the time of the rebuild, since
+    ///   the tiers do not record them
+    /// - corrects the checksum, size and key id of entries that no longer
+    ///   match the stored payload, e.g. after a crash between a metadata write
+    ///   and its replication
+    /// - drops entries for documents that no tier holds, except streamed
+    ///   documents, whose chunks are not scanned
+    ///
+    /// Runs on startup when no metadata is found.
+    pub async fn rebuild_metadata(&self) -> Result<MetadataRepairReport> {
+        info!("Rebuilding document metadata from storage tiers");
+        let mut report = MetadataRepairReport::default();
+        let mut seen = std::collections::HashSet::new();
+
+        let tiers = [
+            (StorageTier::Warm, self.warm_layer.keys().await?),
+            (StorageTier::Cold, self.cold_layer.keys().await?),
+            (StorageTier::Archive, self.archive_layer.keys().await?),
+        ];
+        for (tier, tier_keys) in tiers {
+            for tier_key in tier_keys {
+                // Tier keys are `shard_id:collection:document_id`
+                let Some((shard_id, key)) = tier_key.split_once(':') else { continue };
+                let Some((collection, document_id)) = key.split_once(':') else { continue };
+                // Chunks of streamed documents are described by their manifest
+                if key.contains('\0') {
+                    continue;
+                }
+                if !seen.insert(key.to_string()) {
+                    continue;
+                }
+                report.scanned += 1;
+
+                let data = match self.hot_layer.get(shard_id, key).await {
+                    Ok(data) => data,
+                    Err(_) => self.tier_get(&tier, shard_id, key).await?,
+                };
+                let algorithm = self.config.checksum.algorithm;
+                let encryption_key_id = DataEncryption::key_id(&data).map(str::to_string);
+
+                if let Some(mut metadata) = self.metadata_store.get_mut(key) {
+                    if !metadata.checksum_matches(&data) {
+                        metadata.size = data.len();
+                        metadata.checksum = algorithm.digest(&data);
+                        metadata.checksum_algorithm = algorithm;
+                        metadata.encryption_key_id = encryption_key_id;
+                        report.repaired += 1;
+                    }
+                    continue;
+                }
+
+                let now = chrono::Utc::now();
+                self.metadata_store.insert(key.to_string(), DocumentMetadata {
+                    id: document_id.to_string(),
+                    collection: collection.to_string(),
+                    size: data.len(),
+                    compression_ratio: 1.0,
+                    created_at: now,
+                    updated_at: now,
+                    version: 1,
+                    checksum: algorithm.digest(&data),
+                    checksum_algorithm: algorithm,
+                    storage_tier: tier.clone(),
+                    shard_id: shard_id.to_string(),
+                    replica_locations: Vec::new(),
+                    encryption_key_id,
+                    expires_at: None,
+                    compression_skipped: CompressionEngine::is_passthrough(&data),
+                })?;
+                report.recovered += 1;
+            }
+        }
+
+        let unseen: Vec<(String, String)> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| !seen.contains(entry.key()) && !self.is_chunked(entry.key()))
+            .map(|entry| (entry.key().clone(), entry.shard_id.clone()))
+            .collect();
+        for (key, shard_id) in unseen {
+            if self.hot_layer.get(&shard_id, &key).await.is_err() {
+                self.metadata_store.remove(&key)?;
+                report.removed += 1;
+            }
+        }
+
+        if report.recovered + report.repaired + report.removed > 0 {
+            self.metadata_store.flush().await?;
+        }
+        info!("Metadata rebuild complete: {:?}", report);
+        Ok(report)
+    }
+
+    /// Read a payload from one persistent tier
+    async fn tier_get(&self, tier: &StorageTier, shard_id: &str, key: &str) -> Result<Vec<u8>> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.get(shard_id, key).await,
+            StorageTier::Warm => self.warm_layer.get(shard_id, key).await,
+            StorageTier::Cold => self.cold_layer.get(shard_id, key).await,
+            StorageTier::Archive => self.archive_layer.get(shard_id, key).await,
+        }
+    }
+
+    /// Write a payload to one tier
+    async fn tier_store(&self, tier: &StorageTier, shard_id: &str, key: &str, data: &[u8]) -> Result<()> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.store(shard_id, key, data).await,
+            StorageTier::Warm => self.warm_layer.store(shard_id, key, data).await,
+            StorageTier::Cold => self.cold_layer.store(shard_id, key, data).await,
+            StorageTier::Archive => self.archive_layer.store(shard_id, key, data).await,
+        }
+    }
+
+    /// Remove a payload from one tier
+    async fn tier_delete(&self, tier: &StorageTier, shard_id: &str, key: &str) -> Result<()> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.delete(shard_id, key).await,
+            StorageTier::Warm => self.warm_layer.delete(shard_id, key).await,
+            StorageTier::Cold => self.cold_layer.delete(shard_id, key).await,
+            StorageTier::Archive => self.archive_layer.delete(shard_id, key).await,
+        }
     }
 
     /// Start the storage hierarchy and all background processes.
@@ -369,6 +839,8 @@ impl StorageHierarchy {
         self.warm_layer.stop().await?;
         self.cold_layer.stop().await?;
         self.archive_layer.stop().await?;
+        self.access.persist(&self.metadata_store)?;
+        self.checkpoint_wal().await?;
 
         info!("Storage hierarchy stopped successfully");
         Ok(())
@@ -377,39 +849,83 @@ impl StorageHierarchy {
     /// This method serializes JSON documents to bytes and applies the configured
     /// compression algorithm to reduce storage footprint and network transfer costs.
     /// The compression ratio and algorithm choice are optimized based on data
-    /// characteristics and performance requirements.
+    /// characteristics and performance requirements. When encryption at rest is
+    /// active, the compressed bytes are then sealed under the active key and
+    /// bound to the document's collection and id.
     /// 
     /// # Arguments
+    /// * `collection` - Collection the document belongs to
+    /// * `document_id` - Identifier of the document
     /// * `data` - JSON document data to serialize and compress
+    /// * `skip_compression` - Store the serialized bytes as they are
     /// 
     /// # Returns
-    /// Compressed byte vector or error if serialization/compression fails
-    async fn serialize_and_compress(&self, data: &serde_json::Value) -> Result<Vec<u8>> {
+    /// Stored bytes plus the id of the key that encrypted them, or error if
+    /// serialization/compression/encryption fails
+    async fn serialize_and_compress(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+        skip_compression: bool,
+    ) -> Result<(Vec<u8>, Option<String>)> {
         // First serialize to JSON bytes
         let serialized = serde_json::to_vec(data)?;
         
-        // Then compress using the configured algorithm
-        let compressed = self.compression_engine.compress(&serialized).await?;
+        // Then compress with the collection's dictionary, or with the
+        // collection's or the configured algorithm, unless skipped
+        let trained = match self.collection_compression_algorithm(collection) {
+            CompressionAlgorithm::Zstd if !skip_compression => self.dictionaries.compress(collection, &serialized)?,
+            _ => None,
+        };
+        let compressed = match (trained, self.collection_compression(collection)) {
+            _ if skip_compression => CompressionEngine::passthrough(&serialized),
+            (Some(compressed), _) => compressed,
+            (None, Some(compression_engine)) => compression_engine.compress(&serialized).await?,
+            (None, None) => self.compression_engine.compress(&serialized).await?,
+        };
         
         debug!("Serialized and compressed {} bytes to {} bytes (ratio: {:.2}x)", 
                serialized.len(), compressed.len(),
                serialized.len() as f32 / compressed.len() as f32);
-        
-        Ok(compressed)
+
+        // Finally encrypt if encryption at rest is active for the collection
+        match self.collection_encryption(collection)? {
+            Some(encryption) => {
+                let context = Self::encryption_context(collection, document_id);
+                let (encrypted, key_id) = encryption.encrypt(&compressed, context.as_bytes())?;
+                Ok((encrypted, Some(key_id)))
+            }
+            None => Ok((compressed, None)),
+        }
     }    /// Decompress and deserialize document data from storage.
     /// 
-    /// This method decompresses stored data using the appropriate algorithm
-    /// and deserializes it back to JSON format. The decompression algorithm
-    /// is automatically detected from the data format markers.
+    /// This method decrypts encrypted payloads, decompresses the data using the
+    /// appropriate algorithm and deserializes it back to JSON format. The
+    /// decompression algorithm is automatically detected from the data format
+    /// markers; payloads stored before encryption was enabled are read as they are.
     /// 
     /// # Arguments
-    /// * `data` - Compressed byte data to decompress and deserialize
+    /// * `collection` - Collection the document belongs to
+    /// * `document_id` - Identifier of the document
+    /// * `data` - Stored byte data to decrypt, decompress and deserialize
     /// 
     /// # Returns
-    /// Deserialized JSON value or error if decompression/deserialization fails
-    async fn decompress_and_deserialize(&self, data: &[u8]) -> Result<serde_json::Value> {
-        // First decompress the data
-        let decompressed = self.compression_engine.decompress(data).await?;
+    /// Deserialized JSON value or error if decryption/decompression/deserialization fails
+    async fn decompress_and_deserialize(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &[u8],
+    ) -> Result<serde_json::Value> {
+        // First decrypt the data if it was encrypted
+        let compressed = self.decrypt_payload(collection, document_id, data)?;
+
+        // Then decompress the data, with the dictionary it names if any
+        let decompressed = match self.collection_compression(collection) {
+            Some(compression_engine) => self.dictionaries.decompress(collection, &compressed, &compression_engine).await?,
+            None => self.dictionaries.decompress(collection, &compressed, &self.compression_engine).await?,
+        };
         
         // Then deserialize from JSON bytes
         let document = serde_json::from_slice(&decompressed)?;
@@ -418,62 +934,304 @@ impl StorageHierarchy {
                data.len(), decompressed.len());
         
         Ok(document)
-    }/// Store a document
+    }
+
+    /// Additional authenticated data binding a payload to its document
+    fn encryption_context(collection: &str, document_id: &str) -> String {
+        format!("{}:{}", collection, document_id)
+    }
+
+    /// Decrypt a stored payload, passing plaintext payloads through unchanged.
+    fn decrypt_payload(&self, collection: &str, document_id: &str, data: &[u8]) -> Result<Vec<u8>> {
+        if !DataEncryption::is_encrypted(data) {
+            return Ok(data.to_vec());
+        }
+
+        let encryption = self.encryption.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Document {}:{} is encrypted but encryption at rest is not configured",
+                collection,
+                document_id
+            )
+        })?;
+        let context = Self::encryption_context(collection, document_id);
+        encryption.decrypt(data, context.as_bytes())
+    }
+
+    /// Rewrite a payload under the active key if it was stored in plaintext or
+    /// under a rotated key, returning the refreshed metadata.
+    ///
+    /// Failures are logged rather than returned: the document was already read
+    /// successfully and will be retried on its next read.
+    async fn reencrypt_if_stale(&self, collection: &str, document_id: &str, data: &[u8]) -> Option<DocumentMetadata> {
+        let encryption = self.collection_encryption(collection).ok().flatten()?;
+        if DataEncryption::key_id(data) == Some(encryption.active_key_id().as_str()) {
+            return None;
+        }
+
+        let context = Self::encryption_context(collection, document_id);
+        let (encrypted, key_id) = match self
+            .decrypt_payload(collection, document_id, data)
+            .and_then(|compressed| encryption.encrypt(&compressed, context.as_bytes()))
+        {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                error!("Failed to re-encrypt document {}: {}", context, e);
+                return None;
+            }
+        };
+
+        // Skip the rewrite if the document changed since it was read
+        let mut metadata = self.metadata_store.get_mut(&context)?;
+        if !metadata.checksum_matches(data) {
+            return None;
+        }
+
+        let shard_id = metadata.shard_id.clone();
+        if let Err(e) = self.hot_layer.store(&shard_id, &context, &encrypted).await {
+            error!("Failed to re-encrypt document {}: {}", context, e);
+            return None;
+        }
+
+        metadata.size = encrypted.len();
+        metadata.checksum = metadata.checksum_algorithm.digest(&encrypted);
+        metadata.encryption_key_id = Some(key_id.clone());
+
+        let updated = metadata.clone();
+        drop(metadata);
+
+        let warm_layer = Arc::clone(&self.warm_layer);
+        let cold_layer = Arc::clone(&self.cold_layer);
+        tokio::spawn(async move {
+            let _ = warm_layer.store(&shard_id, &context, &encrypted).await;
+            let _ = cold_layer.store(&shard_id, &context, &encrypted).await;
+        });
+
+        debug!("Re-encrypted document {}:{} with key {}", collection, document_id, key_id);
+        Some(updated)
+    }
+
+    /// Store a document, creating it or replacing the current version
     pub async fn store_document(
         &self,
         collection: &str,
         document_id: &str,
         data: &serde_json::Value,
+    ) -> Result<StorageResult<()>> {
+        self.store_document_with_mode(collection, document_id, data, WriteMode::Upsert).await
+    }
+
+    /// Store a document, honouring the existence and version expectations
+    /// of `mode`.
+    ///
+    /// A document that already exists keeps its creation time and gets the
+    /// next version; a new document starts at version 1. Conflicts are
+    /// reported as `Document already exists`, `Document not found` or
+    /// `Version mismatch` errors and leave the stored document untouched.
+    /// The document keeps the expiry it had, if any.
+    pub async fn store_document_with_mode(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+        mode: WriteMode,
+    ) -> Result<StorageResult<()>> {
+        self.store_document_expiring(collection, document_id, data, mode, None, false).await
+    }
+
+    /// Store a document, creating it or replacing the current version, as
+    /// `options` asks.
+    pub async fn store_document_with_options(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+        options: WriteOptions,
+    ) -> Result<StorageResult<()>> {
+        let expires_at = options.ttl.map(expiration::expiry_after).transpose()?;
+        self.store_document_expiring(collection, document_id, data, WriteMode::Upsert, expires_at, options.skip_compression)
+            .await
+    }
+
+    /// Store a document as `store_document_with_mode` does, setting its
+    /// expiry when `expires_at` is given and keeping the current one
+    /// otherwise. The payload is stored uncompressed with
+    /// `skip_compression` or when its collection skips compression.
+    ///
+    /// Writes to strongly consistent collections are replicated first.
+    pub(crate) async fn store_document_expiring(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+        mode: WriteMode,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        skip_compression: bool,
+    ) -> Result<StorageResult<()>> {
+        if let Some(replicator) = self.write_replicator(collection) {
+            let write = ReplicatedWrite::Store {
+                collection: collection.to_string(),
+                document_id: document_id.to_string(),
+                data: data.clone(),
+                mode,
+                expires_at,
+                skip_compression,
+            };
+            return replicator.replicate(write).await;
+        }
+        self.store_document_local(collection, document_id, data, mode, expires_at, skip_compression).await
+    }
+
+    /// Store a document on this node only, as `store_document_expiring`
+    /// does for eventually consistent collections.
+    pub(crate) async fn store_document_local(
+        &self,
+        collection: &str,
+        document_id: &str,
+        data: &serde_json::Value,
+        mode: WriteMode,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        skip_compression: bool,
     ) -> Result<StorageResult<()>> {
         let start_time = std::time::Instant::now();
-          debug!("Storing document {}:{}", collection, document_id);
+        debug!("Storing document {}:{} ({:?})", collection, document_id, mode);
 
-        // Serialize and compress data
-        let serialized = self.serialize_and_compress(data).await?;
+        // Refuse placement outside the document's residency
+        let allowed_regions = self.check_placement(collection, document_id)?;
 
-        // Determine shard
-        let shard_id = self.sharding_engine.get_shard(collection, document_id).await;
+        // Serialize, compress and encrypt data
+        let skip_compression = skip_compression || self.collection_skips_compression(collection);
+        let (serialized, encryption_key_id) =
+            self.serialize_and_compress(collection, document_id, data, skip_compression).await?;
+        self.check_capped_size(collection, document_id, serialized.len())?;
+
+        // Determine shard for new documents
+        let new_shard_id = self.sharding_engine.read().await.get_shard(collection, document_id).await;
+
+        // Refuse writes beyond the storage limit; a replaced document frees
+        // its own bytes
+        let replaced = self.metadata_store.get(&format!("{}:{}", collection, document_id)).map_or(0, |existing| existing.size);
+        self.reserve_storage(collection, serialized.len().saturating_sub(replaced) as u64).await?;
 
         // Calculate compression ratio
         let uncompressed_size = serde_json::to_vec(data)?.len();
         let compression_ratio = uncompressed_size as f32 / serialized.len() as f32;
 
-        // Create metadata
-        let metadata = DocumentMetadata {
-            id: document_id.to_string(),
-            collection: collection.to_string(),
-            size: serialized.len(),
-            compression_ratio,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-            version: 1,
-            checksum: blake3::hash(&serialized).to_hex().to_string(),
-            storage_tier: StorageTier::Hot,
-            shard_id: shard_id.clone(),
-            replica_locations: Vec::new(),
-            encryption_key_id: None,
+        // Check the expectations of the write mode and record the new
+        // metadata in one step, keyed by collection so the persistent tiers
+        // hold enough to rebuild metadata
+        let key = format!("{}:{}", collection, document_id);
+        let now = chrono::Utc::now();
+        let mut previous = None;
+        let metadata = self.metadata_store.write_with(key.clone(), |existing| {
+            match (mode, existing) {
+                (WriteMode::CreateOnly, Some(_)) => {
+                    return Err(anyhow::anyhow!("Document already exists: {}:{}", collection, document_id));
+                }
+                (WriteMode::Replace { .. }, None) => {
+                    return Err(anyhow::anyhow!("Document not found: {}:{}", collection, document_id));
+                }
+                (WriteMode::Replace { expected_version: Some(expected) }, Some(existing))
+                    if existing.version != expected =>
+                {
+                    return Err(anyhow::anyhow!(
+                        "Version mismatch: expected {}, got {}",
+                        expected,
+                        existing.version
+                    ));
+                }
+                _ => {}
+            }
+            if let Some(existing) = existing {
+                self.check_worm(existing, "update")?;
+            }
+            previous = existing.cloned();
+
+            Ok(DocumentMetadata {
+                id: document_id.to_string(),
+                collection: collection.to_string(),
+                size: serialized.len(),
+                compression_ratio,
+                created_at: existing.map_or(now, |existing| existing.created_at),
+                updated_at: now,
+                version: existing.map_or_else(|| self.next_new_version(&key), |existing| existing.version + 1),
+                checksum: self.config.checksum.algorithm.digest(&serialized),
+                checksum_algorithm: self.config.checksum.algorithm,
+                storage_tier: StorageTier::Hot,
+                shard_id: existing.map_or_else(|| new_shard_id.clone(), |existing| existing.shard_id.clone()),
+                replica_locations: existing.map_or_else(Vec::new, |existing| existing.replica_locations.clone()),
+                encryption_key_id,
+                expires_at: expires_at.or_else(|| existing.and_then(|existing| existing.expires_at)),
+                compression_skipped: skip_compression,
+            })
+        })?;
+        let shard_id = metadata.shard_id.clone();
+
+        // Log the write before it is acknowledged, undoing the metadata
+        // write if that fails; volatile writes are not logged
+        let volatile = self.is_volatile_collection(collection);
+        let operation = wal::WalOperation::Store { key: key.clone(), metadata: metadata.clone() };
+        let logged = if volatile { Ok(Vec::new()) } else { self.wal.append(&[(operation, &serialized)]) };
+        let wal_seqs = match logged {
+            Ok(seqs) => seqs,
+            Err(e) => {
+                match &previous {
+                    Some(previous) => self.metadata_store.insert(key.clone(), previous.clone())?,
+                    None => {
+                        self.metadata_store.remove(&key)?;
+                    }
+                }
+                return Err(anyhow::anyhow!("Failed to log write of {}: {}", key, e));
+            }
         };
 
+        // Keep the replaced revision of versioned collections, or forget the
+        // tombstone of a document written again after deletion
+        match &previous {
+            Some(previous) => self.retain_replaced(&key, previous).await,
+            None => self.clear_tombstone(&key),
+        }
+
         // Store in hot layer first
-        self.hot_layer.store(&shard_id, document_id, &serialized).await?;
+        self.hot_layer.store(&shard_id, &key, &serialized).await?;
+        if previous.is_some() {
+            self.discard_chunks(&key, &shard_id).await;
+        }
 
-        // Store metadata
-        let key = format!("{}:{}", collection, document_id);
-        self.metadata_store.insert(key, metadata.clone());        // Asynchronously replicate to other layers
+        // Make room in capped collections by evicting their oldest documents
+        self.evict_capped(collection, document_id).await;
+
+        // Volatile documents live in the hot layer only
+        if volatile {
+            return Ok(StorageResult {
+                data: Some(()),
+                metadata: Some(metadata),
+                operation_time: start_time.elapsed(),
+                storage_tier: StorageTier::Hot,
+                cache_hit: false,
+            });
+        }
+
+        // Asynchronously replicate to other layers
         let replication_manager = Arc::clone(&self.replication_manager);
-        let warm_layer = Arc::clone(&self.warm_layer);        let cold_layer = Arc::clone(&self.cold_layer);
+        let warm_layer = Arc::clone(&self.warm_layer);
+        let cold_layer = Arc::clone(&self.cold_layer);
         let data_copy = serialized.clone();
         let shard_id_copy = shard_id.clone();
-        let document_id_copy = document_id.to_string();
-        let _collection_copy = collection.to_string();
+        let key_copy = key;
+        let replication_factor = self.collection_replication_factor(collection);
+        let wal = Arc::clone(&self.wal);
 
-        // Start local replication
+        // Start local replication; the logged write is kept for replay
+        // until every copy is written
         tokio::spawn(async move {
-            if let Err(e) = replication_manager
-                .replicate_to_layers(&shard_id_copy, &document_id_copy, &data_copy, &warm_layer, &cold_layer)
+            match replication_manager
+                .replicate_to_layers(&shard_id_copy, &key_copy, &data_copy, replication_factor, &warm_layer, &cold_layer)
                 .await
             {
-                error!("Failed to replicate document: {}", e);
+                Ok(result) if result.failed_replicas == 0 => wal.complete(&wal_seqs),
+                Ok(_) => warn!("Keeping logged write of {} until restart after failed replication", key_copy),
+                Err(e) => error!("Failed to replicate document: {}", e),
             }
         });
 
@@ -483,16 +1241,22 @@ impl StorageHierarchy {
             let data_copy = serialized.clone();
             let collection_copy = collection.to_string();
             let document_id_copy = document_id.to_string();
+            let operation = if metadata.version == 1 {
+                datacenter_replication::ReplicationOperation::Create
+            } else {
+                datacenter_replication::ReplicationOperation::Update
+            };
 
             tokio::spawn(async move {
-                match dc_replication_copy.replicate_document(
+                match dc_replication_copy.replicate_document_within(
                     &collection_copy,
                     &document_id_copy,
                     &data_copy,
-                    datacenter_replication::ReplicationOperation::Create,
+                    operation,
+                    allowed_regions.as_ref(),
                 ).await {
                     Ok(result) => {
-                        debug!("Cross-datacenter replication completed: {}/{} datacenters successful", 
+                        debug!("Cross-datacenter replication completed: {}/{} datacenters successful",
                                result.successful_replications, result.total_datacenters);
                     }
                     Err(e) => {
@@ -524,13 +1288,21 @@ impl StorageHierarchy {
         let key = format!("{}:{}", collection, document_id);
         
         // Get metadata
-        let metadata = self.metadata_store.get(&key)
-            .map(|entry| entry.clone());
+        let metadata = self.metadata_store.get(&key);
+
+        if metadata.is_some() && self.is_chunked(&key) {
+            return Err(anyhow::anyhow!(
+                "Document {} is stored in chunks; read it with get_document_stream",
+                key
+            ));
+        }
 
         if let Some(meta) = &metadata {
+            self.access.record(&key);
             let shard_id = &meta.shard_id;            // Try hot layer first
-            if let Ok(data) = self.hot_layer.get(shard_id, document_id).await {
-                let document = self.decompress_and_deserialize(&data).await?;
+            if let Ok(data) = self.hot_layer.get(shard_id, &key).await {
+                let document = self.decompress_and_deserialize(collection, document_id, &data).await?;
+                let metadata = self.reencrypt_if_stale(collection, document_id, &data).await.or(metadata);
                 
                 return Ok(StorageResult {
                     data: Some(document),
@@ -540,11 +1312,12 @@ impl StorageHierarchy {
                     cache_hit: true,
                 });
             }            // Try warm layer
-            if let Ok(data) = self.warm_layer.get(shard_id, document_id).await {
-                let document = self.decompress_and_deserialize(&data).await?;
+            if let Ok(data) = self.warm_layer.get(shard_id, &key).await {
+                let document = self.decompress_and_deserialize(collection, document_id, &data).await?;
                 
                 // Promote to hot layer
-                let _ = self.hot_layer.store(shard_id, document_id, &data).await;
+                let _ = self.hot_layer.store(shard_id, &key, &data).await;
+                let metadata = self.reencrypt_if_stale(collection, document_id, &data).await.or(metadata);
                 
                 return Ok(StorageResult {
                     data: Some(document),
@@ -554,11 +1327,12 @@ impl StorageHierarchy {
                     cache_hit: false,
                 });
             }            // Try cold layer
-            if let Ok(data) = self.cold_layer.get(shard_id, document_id).await {
-                let document = self.decompress_and_deserialize(&data).await?;
+            if let Ok(data) = self.cold_layer.get(shard_id, &key).await {
+                let document = self.decompress_and_deserialize(collection, document_id, &data).await?;
                 
                 // Promote to warm layer
-                let _ = self.warm_layer.store(shard_id, document_id, &data).await;
+                let _ = self.warm_layer.store(shard_id, &key, &data).await;
+                let metadata = self.reencrypt_if_stale(collection, document_id, &data).await.or(metadata);
                 
                 return Ok(StorageResult {
                     data: Some(document),
@@ -568,8 +1342,9 @@ impl StorageHierarchy {
                     cache_hit: false,
                 });
             }            // Try archive layer
-            if let Ok(data) = self.archive_layer.get(shard_id, document_id).await {
-                let document = self.decompress_and_deserialize(&data).await?;
+            if let Ok(data) = self.archive_layer.get(shard_id, &key).await {
+                let document = self.decompress_and_deserialize(collection, document_id, &data).await?;
+                let metadata = self.reencrypt_if_stale(collection, document_id, &data).await.or(metadata);
                 
                 return Ok(StorageResult {
                     data: Some(document),
@@ -591,7 +1366,8 @@ impl StorageHierarchy {
         })
     }
 
-    /// Update a document
+    /// Update an existing document, optionally requiring its current
+    /// version to match `expected_version`
     pub async fn update_document(
         &self,
         collection: &str,
@@ -599,68 +1375,31 @@ impl StorageHierarchy {
         data: &serde_json::Value,
         expected_version: Option<u64>,
     ) -> Result<StorageResult<()>> {
-        let start_time = std::time::Instant::now();
-        
-        debug!("Upaerolithng document {}:{}", collection, document_id);
-
-        let key = format!("{}:{}", collection, document_id);
-
-        // Check version if specified
-        if let Some(expected) = expected_version {
-            if let Some(metadata) = self.metadata_store.get(&key) {
-                if metadata.version != expected {
-                    return Err(anyhow::anyhow!(
-                        "Version mismatch: expected {}, got {}",
-                        expected,
-                        metadata.version
-                    ));
-                }
-            }
-        }        // Serialize and compress data
-        let serialized = self.serialize_and_compress(data).await?;
-
-        // Calculate compression ratio
-        let uncompressed_size = serde_json::to_vec(data)?.len();
-        let compression_ratio = uncompressed_size as f32 / serialized.len() as f32;
-
-        // Update metadata
-        if let Some(mut metadata) = self.metadata_store.get_mut(&key) {            metadata.size = serialized.len();
-            metadata.compression_ratio = compression_ratio;
-            metadata.updated_at = chrono::Utc::now();
-            metadata.version += 1;
-            metadata.checksum = blake3::hash(&serialized).to_hex().to_string();
-
-            let shard_id = metadata.shard_id.clone();
-
-            // Update in all layers
-            self.hot_layer.store(&shard_id, document_id, &serialized).await?;
-            
-            // Asynchronously update other layers
-            let warm_layer = Arc::clone(&self.warm_layer);
-            let cold_layer = Arc::clone(&self.cold_layer);
-            let data_copy = serialized.clone();
-            let shard_id_copy = shard_id.clone();
-            let document_id_copy = document_id.to_string();
+        debug!("Updating document {}:{}", collection, document_id);
 
-            tokio::spawn(async move {
-                let _ = warm_layer.store(&shard_id_copy, &document_id_copy, &data_copy).await;
-                let _ = cold_layer.store(&shard_id_copy, &document_id_copy, &data_copy).await;
-            });
+        self.store_document_with_mode(collection, document_id, data, WriteMode::Replace { expected_version })
+            .await
+    }
 
-            Ok(StorageResult {
-                data: Some(()),
-                metadata: Some(metadata.clone()),
-                operation_time: start_time.elapsed(),
-                storage_tier: StorageTier::Hot,
-                cache_hit: false,
-            })
-        } else {
-            Err(anyhow::anyhow!("Document not found: {}:{}", collection, document_id))
+    /// Delete a document; deletes from strongly consistent collections are
+    /// replicated first
+    pub async fn delete_document(
+        &self,
+        collection: &str,
+        document_id: &str,
+    ) -> Result<StorageResult<()>> {
+        if let Some(replicator) = self.write_replicator(collection) {
+            let write = ReplicatedWrite::Delete {
+                collection: collection.to_string(),
+                document_id: document_id.to_string(),
+            };
+            return replicator.replicate(write).await;
         }
+        self.delete_document_local(collection, document_id).await
     }
 
-    /// Delete a document
-    pub async fn delete_document(
+    /// Delete a document from this node only
+    pub(crate) async fn delete_document_local(
         &self,
         collection: &str,
         document_id: &str,
@@ -671,14 +1410,41 @@ impl StorageHierarchy {
 
         let key = format!("{}:{}", collection, document_id);
 
-        if let Some((_, metadata)) = self.metadata_store.remove(&key) {
+        if self.legal_holds.is_held(&key) {
+            return Err(anyhow::anyhow!("Document {} is under legal hold", key));
+        }
+        let mut wal_seqs = Vec::new();
+        if let Some(existing) = self.metadata_store.get(&key) {
+            self.check_worm(&existing, "delete")?;
+            // Deletes of volatile documents are not logged either
+            if !self.is_volatile_collection(collection) {
+                let operation = wal::WalOperation::Delete {
+                    key: key.clone(),
+                    shard_id: existing.shard_id.clone(),
+                    version: existing.version,
+                };
+                wal_seqs = self.wal.append(&[(operation, &[])])
+                    .map_err(|e| anyhow::anyhow!("Failed to log delete of {}: {}", key, e))?;
+            }
+        }
+
+        let removed = self.metadata_store.remove(&key);
+        if !matches!(removed, Ok(Some(_))) {
+            self.wal.complete(&wal_seqs);
+        }
+        if let Some(metadata) = removed? {
             let shard_id = &metadata.shard_id;
 
+            // Keep a tombstone or last revision before the payload goes
+            self.retain_deleted(&key, &metadata).await;
+            self.discard_chunks(&key, shard_id).await;
+
             // Delete from all layers
-            let _ = self.hot_layer.delete(shard_id, document_id).await;
-            let _ = self.warm_layer.delete(shard_id, document_id).await;
-            let _ = self.cold_layer.delete(shard_id, document_id).await;
-            let _ = self.archive_layer.delete(shard_id, document_id).await;
+            let _ = self.hot_layer.delete(shard_id, &key).await;
+            let _ = self.warm_layer.delete(shard_id, &key).await;
+            let _ = self.cold_layer.delete(shard_id, &key).await;
+            let _ = self.archive_layer.delete(shard_id, &key).await;
+            self.wal.complete(&wal_seqs);
 
             Ok(StorageResult {
                 data: Some(()),
@@ -692,7 +1458,7 @@ impl StorageHierarchy {
         }
     }
 
-    /// List documents in a collection
+    /// List documents in a collection, sorted by ID
     pub async fn list_documents(
         &self,
         collection: &str,
@@ -701,27 +1467,9 @@ impl StorageHierarchy {
     ) -> Result<Vec<String>> {
         debug!("Listing documents in collection: {}", collection);
 
-        let mut documents = Vec::new();
-        let prefix = format!("{}:", collection);
-
-        for entry in self.metadata_store.iter() {
-            if entry.key().starts_with(&prefix) {
-                let document_id = entry.key().strip_prefix(&prefix).unwrap_or("");
-                documents.push(document_id.to_string());
-            }
-        }
-
-        documents.sort();
-
-        if let Some(offset) = offset {
-            documents = documents.into_iter().skip(offset).collect();
-        }
-
-        if let Some(limit) = limit {
-            documents.truncate(limit);
-        }
-
-        Ok(documents)
+        let options = ListOptions { limit, offset, ..ListOptions::default() };
+        let page = self.list_documents_with(collection, &options)?;
+        Ok(page.documents.into_iter().map(|document| document.id).collect())
     }
 
     /// Get storage statistics
@@ -740,12 +1488,28 @@ impl StorageHierarchy {
                 StorageTier::Cold => stats.cold_tier_size += metadata.size as u64,
                 StorageTier::Archive => stats.archive_tier_size += metadata.size as u64,
             }
+
+            if metadata.compression_skipped {
+                stats.compression.documents_skipped += 1;
+                stats.compression.bytes_skipped += metadata.size as u64;
+            } else {
+                stats.compression.documents_compressed += 1;
+            }
+            *stats.checksums.documents.entry(metadata.checksum_algorithm).or_default() += 1;
         }
+        stats.checksums.algorithm = self.config.checksum.algorithm;
+        stats.checksums.acceleration = HashAcceleration::detect();
 
         // Get cache stats
         stats.cache_hit_rate = self.hot_layer.get_hit_rate().await;
         stats.compression_ratio = self.calculate_average_compression_ratio().await;
 
+        // Get sampled read statistics
+        let (total_reads, reads_per_day, tracked) = self.access.totals(chrono::Utc::now());
+        stats.total_reads = total_reads;
+        stats.reads_per_day = reads_per_day;
+        stats.unread_documents = stats.total_documents.saturating_sub(tracked);
+
         Ok(stats)
     }
 
@@ -759,9 +1523,50 @@ impl StorageHierarchy {
         // Start tier migration
         self.start_tier_migration_task().await?;
 
+        // Start persisting access statistics
+        self.start_access_persist_task().await?;
+
+        // Start dropping replicated writes from the write-ahead log
+        self.start_wal_checkpoint_task().await?;
+
+        // Start integrity scrubbing
+        if let Some(period) = self.config.scrub.interval {
+            self.start_scrub_task(period).await?;
+        }
+
         // Start compaction
         self.start_compaction_task().await?;
 
+        // Start tombstone purging
+        if self.is_soft_delete_enabled() {
+            self.start_tombstone_purge_task().await?;
+        }
+
+        // Start checking usage against the storage limit
+        if self.config.max_storage_size.is_some() {
+            self.start_quota_task().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Start the task checkpointing the write-ahead log
+    async fn start_wal_checkpoint_task(&self) -> Result<()> {
+        let wal = Arc::clone(&self.wal);
+        let metadata_store = Arc::clone(&self.metadata_store);
+        let period = self.config.wal.checkpoint_interval;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = wal.checkpoint(&metadata_store).await {
+                    error!("Write-ahead log checkpoint failed: {}", e);
+                }
+            }
+        });
+
         Ok(())
     }
 
@@ -781,33 +1586,104 @@ impl StorageHierarchy {
         });
 
         Ok(())
-    }    /// Start tier migration task
+    }    /// Start the task applying the tier migration rules
     async fn start_tier_migration_task(&self) -> Result<()> {
-        let metadata_store = Arc::clone(&self.metadata_store);
-        let _hot_layer = Arc::clone(&self.hot_layer);
-        let _warm_layer = Arc::clone(&self.warm_layer);
-        let cold_layer = Arc::clone(&self.cold_layer);
-        let archive_layer = Arc::clone(&self.archive_layer);
+        let migrator = self.tier_migrator();
+        let period = self.config.tiering.interval;
+        let dry_run = self.config.tiering.dry_run;
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(300)); // 5 minutes
-            
+            let mut interval = tokio::time::interval(period);
+
             loop {
                 interval.tick().await;
-                
-                // Move cold data to archive
-                for entry in metadata_store.iter() {
-                    let metadata = entry.value();
-                    if metadata.storage_tier == StorageTier::Cold {
-                        let age = chrono::Utc::now() - metadata.updated_at;
-                        if age > chrono::Duration::days(30) {
-                            // Migrate to archive
-                            if let Ok(data) = cold_layer.get(&metadata.shard_id, &metadata.id).await {
-                                let _ = archive_layer.store(&metadata.shard_id, &metadata.id, &data).await;
-                                let _ = cold_layer.delete(&metadata.shard_id, &metadata.id).await;
-                            }
-                        }
+                match migrator.run(dry_run).await {
+                    Ok(report) if dry_run && !report.migrations.is_empty() => {
+                        info!("Tier migration dry run would move {} documents", report.migrations.len());
                     }
+                    Ok(report) if !report.migrations.is_empty() => {
+                        info!("Tier migration moved {} documents", report.migrations.len());
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("Tier migration failed: {}", e),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Start the task verifying stored copies against their checksums
+    async fn start_scrub_task(&self, period: std::time::Duration) -> Result<()> {
+        let scrubber = self.scrubber();
+        let repair = self.config.scrub.repair;
+
+        tokio::spawn(async move {
+            // The first scrub waits a full period rather than slowing startup
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = scrubber.run(repair).await {
+                    error!("Integrity scrub failed: {}", e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Start the task writing access statistics to disk
+    async fn start_access_persist_task(&self) -> Result<()> {
+        let access = Arc::clone(&self.access);
+        let metadata_store = Arc::clone(&self.metadata_store);
+        let period = self.config.access_tracking.persist_interval;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = access.persist(&metadata_store) {
+                    error!("Persisting access statistics failed: {}", e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Start the task checking usage against the storage limit
+    async fn start_quota_task(&self) -> Result<()> {
+        let enforcer = self.quota_enforcer();
+        let period = self.config.quota.check_interval;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = enforcer.enforce().await {
+                    error!("Storage quota check failed: {}", e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Start the task purging tombstones past their retention window
+    async fn start_tombstone_purge_task(&self) -> Result<()> {
+        let history = Arc::clone(&self.history);
+        let legal_holds = Arc::clone(&self.legal_holds);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600)); // 1 hour
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = history.purge_expired_tombstones(&legal_holds) {
+                    error!("Tombstone purge failed: {}", e);
                 }
             }
         });
@@ -818,6 +1694,7 @@ impl StorageHierarchy {
     /// Start compaction task
     async fn start_compaction_task(&self) -> Result<()> {
         let cold_layer = Arc::clone(&self.cold_layer);
+        let statistics = self.statistics_collector();
         
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600)); // 1 hour
@@ -827,6 +1704,10 @@ impl StorageHierarchy {
                 if let Err(e) = cold_layer.compact().await {
                     error!("Storage compaction failed: {}", e);
                 }
+                // Refresh the query planner's statistics after each compaction
+                if let Err(e) = statistics.run().await {
+                    error!("Collecting collection statistics failed: {}", e);
+                }
             }
         });
 
@@ -855,6 +1736,43 @@ impl StorageHierarchy {
         }
     }
 
+    /// Exclude a peer from replication targets (e.g. while it is quarantined)
+    pub fn exclude_replication_peer(&self, peer: &str) {
+        self.replication_manager.exclude_peer(peer);
+    }
+
+    /// Restore a previously excluded peer as a replication target
+    pub fn restore_replication_peer(&self, peer: &str) {
+        self.replication_manager.include_peer(peer);
+    }
+
+    /// Peers currently excluded from replication targets
+    pub fn excluded_replication_peers(&self) -> Vec<String> {
+        self.replication_manager.excluded_peers()
+    }
+
+    /// Stop cross-datacenter replication to a region that has been lost
+    pub fn suspend_datacenter_region(&self, region: &str) {
+        if let Some(dc_replication) = &self.datacenter_replication_manager {
+            dc_replication.suspend_region(region);
+        }
+    }
+
+    /// Resume cross-datacenter replication to a recovered region
+    pub fn resume_datacenter_region(&self, region: &str) {
+        if let Some(dc_replication) = &self.datacenter_replication_manager {
+            dc_replication.resume_region(region);
+        }
+    }
+
+    /// Regions currently excluded from cross-datacenter replication
+    pub fn suspended_datacenter_regions(&self) -> Vec<String> {
+        self.datacenter_replication_manager
+            .as_ref()
+            .map(|dc_replication| dc_replication.suspended_regions())
+            .unwrap_or_default()
+    }
+
     /// Check if cross-datacenter replication is enabled
     pub fn is_datacenter_replication_enabled(&self) -> bool {
         self.datacenter_replication_manager.is_some()
@@ -872,4 +1790,1765 @@ pub struct StorageStats {
     pub archive_tier_size: u64,
     pub cache_hit_rate: f32,
     pub compression_ratio: f32,
+
+    /// Documents compressed and skipped by compression
+    pub compression: CompressionStats,
+
+    /// Checksum algorithm of new writes and of existing documents
+    pub checksums: ChecksumStats,
+    pub total_reads: u64,
+    pub reads_per_day: f64,
+    pub unread_documents: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerolithdb_security::SecurityConfig;
+
+    #[tokio::test]
+    async fn test_encryption_at_rest_with_lazy_reencryption() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-encryption-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let security = SecurityFramework::new(&SecurityConfig {
+            master_key_file: Some(dir.join("master.key")),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let config = StorageConfig { data_dir: dir.join("encrypted"), ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap().with_encryption(&security);
+        assert!(storage.is_encrypted_at_rest());
+
+        let document = serde_json::json!({ "name": "aerolith" });
+        let metadata = storage.store_document("users", "1", &document).await.unwrap().metadata.unwrap();
+        let first_key = metadata.encryption_key_id.clone().unwrap();
+        let stored = storage.hot_layer.get(&metadata.shard_id, "users:1").await.unwrap();
+        assert_eq!(DataEncryption::key_id(&stored), Some(first_key.as_str()));
+        assert!(!stored.windows(8).any(|window| window == b"aerolith"));
+
+        let read = storage.get_document("users", "1").await.unwrap();
+        assert_eq!(read.data.unwrap(), document);
+        assert_eq!(read.metadata.unwrap().encryption_key_id, Some(first_key));
+
+        // The first read after rotation rewrites the payload under the new key
+        let rotated_key = security.data_encryption().unwrap().rotate_key();
+        let read = storage.get_document("users", "1").await.unwrap();
+        assert_eq!(read.data.unwrap(), document);
+        assert_eq!(read.metadata.unwrap().encryption_key_id.as_deref(), Some(rotated_key.as_str()));
+        let stored = storage.hot_layer.get(&metadata.shard_id, "users:1").await.unwrap();
+        assert_eq!(DataEncryption::key_id(&stored), Some(rotated_key.as_str()));
+
+        // Without a master key documents stay readable in plaintext
+        let plain = StorageHierarchy::new(&StorageConfig { data_dir: dir.join("plain"), ..Default::default() })
+            .await
+            .unwrap()
+            .with_encryption(&SecurityFramework::new(&SecurityConfig::default()).await.unwrap());
+        assert!(!plain.is_encrypted_at_rest());
+        let metadata = plain.store_document("users", "1", &document).await.unwrap().metadata.unwrap();
+        assert_eq!(metadata.encryption_key_id, None);
+        assert_eq!(plain.get_document("users", "1").await.unwrap().data.unwrap(), document);
+
+        // Sled trees can deadlock on drop against in-flight replication tasks
+        std::mem::forget(storage);
+        std::mem::forget(plain);
+    }
+
+    /// Wait until no background replication task holds the persistent tiers
+    async fn settle(storage: &StorageHierarchy) {
+        for _ in 0..500 {
+            if Arc::strong_count(&storage.warm_layer) == 1 && Arc::strong_count(&storage.cold_layer) == 1 {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("background replication never finished");
+    }
+
+    #[tokio::test]
+    async fn test_metadata_survives_restart_and_rebuilds_from_tiers() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-metadata-restart-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir.clone(), ..Default::default() };
+        let document = serde_json::json!({ "name": "aerolith" });
+
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        for id in ["1", "2", "3"] {
+            storage.store_document("users", id, &document).await.unwrap();
+        }
+        settle(&storage).await;
+        storage.update_document("users", "1", &serde_json::json!({ "name": "v2" }), None).await.unwrap();
+        settle(&storage).await;
+        storage.stop().await.unwrap();
+        drop(storage);
+
+        // Versions and checksums are loaded back on startup
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        let read = storage.get_document("users", "1").await.unwrap();
+        assert_eq!(read.data.unwrap(), serde_json::json!({ "name": "v2" }));
+        assert_eq!(read.metadata.unwrap().version, 2);
+        assert_eq!(storage.list_documents("users", None, None).await.unwrap(), ["1", "2", "3"]);
+
+        // Lost, stale and orphaned entries are repaired from the tiers
+        storage.metadata_store.remove("users:2").unwrap();
+        storage.metadata_store.get_mut("users:3").unwrap().checksum = "stale".to_string();
+        let mut orphan = storage.metadata_store.get("users:1").unwrap();
+        orphan.id = "4".to_string();
+        storage.metadata_store.insert("users:4".to_string(), orphan).unwrap();
+
+        let report = storage.rebuild_metadata().await.unwrap();
+        assert_eq!(report, MetadataRepairReport { scanned: 3, recovered: 1, repaired: 1, removed: 1 });
+        assert_eq!(storage.get_document("users", "2").await.unwrap().data.unwrap(), document);
+        assert_eq!(storage.get_document("users", "3").await.unwrap().data.unwrap(), document);
+        assert!(storage.get_document("users", "4").await.unwrap().data.is_none());
+        assert_eq!(storage.get_document("users", "1").await.unwrap().metadata.unwrap().version, 2);
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_reads_as_of_past_times() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-as-of-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let history = HistoryConfig {
+            versioned_collections: ["notes".to_string()].into_iter().collect(),
+            max_revisions: 3,
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, history, ..Default::default() })
+            .await
+            .unwrap();
+        async fn mark() -> chrono::DateTime<chrono::Utc> {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            let now = chrono::Utc::now();
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            now
+        }
+
+        let before = mark().await;
+        storage.store_document("notes", "1", &serde_json::json!({ "n": 1 })).await.unwrap();
+        let first = mark().await;
+        storage.store_document("notes", "1", &serde_json::json!({ "n": 2 })).await.unwrap();
+        storage.store_document("notes", "2", &serde_json::json!({ "n": 1 })).await.unwrap();
+        let second = mark().await;
+        settle(&storage).await;
+        storage.delete_document("notes", "1").await.unwrap();
+        let deleted = mark().await;
+        storage.store_document("notes", "1", &serde_json::json!({ "n": 3 })).await.unwrap();
+        let recreated = mark().await;
+        settle(&storage).await;
+
+        let as_of = |at| {
+            let storage = &storage;
+            async move { storage.get_document_as_of("notes", "1", at).await.unwrap().map(|result| result.data.unwrap()) }
+        };
+        assert_eq!(as_of(before).await, None);
+        assert_eq!(as_of(first).await, Some(serde_json::json!({ "n": 1 })));
+        assert_eq!(as_of(second).await, Some(serde_json::json!({ "n": 2 })));
+        assert_eq!(as_of(deleted).await, None);
+        assert_eq!(as_of(recreated).await, Some(serde_json::json!({ "n": 3 })));
+
+        let ids = |documents: Vec<(DocumentMetadata, serde_json::Value)>| {
+            documents.into_iter().map(|(metadata, _)| metadata.id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(storage.collection_as_of("notes", first).await.unwrap()), ["1"]);
+        assert_eq!(ids(storage.collection_as_of("notes", deleted).await.unwrap()), ["2"]);
+        assert_eq!(ids(storage.collection_as_of("notes", recreated).await.unwrap()), ["1", "2"]);
+
+        // Unversioned collections have no past to read
+        storage.store_document("misc", "1", &serde_json::json!({ "n": 1 })).await.unwrap();
+        assert!(storage.get_document_as_of("misc", "1", recreated).await.is_err());
+        settle(&storage).await;
+
+        // Once the oldest revision is dropped, earlier times cannot be read
+        for n in 4..=5 {
+            storage.store_document("notes", "1", &serde_json::json!({ "n": n })).await.unwrap();
+        }
+        settle(&storage).await;
+        let error = storage.get_document_as_of("notes", "1", first).await.unwrap_err();
+        assert!(error.to_string().contains("no longer retained"), "{}", error);
+        assert_eq!(as_of(second).await, Some(serde_json::json!({ "n": 2 })));
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_batch_operations_report_per_document_results() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-batch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() })
+            .await
+            .unwrap();
+
+        let documents: Vec<(String, serde_json::Value)> = (1..=3)
+            .map(|i| (i.to_string(), serde_json::json!({ "n": i })))
+            .collect();
+        let stored = storage.store_documents_batch("items", &documents).await;
+        assert_eq!(stored.len(), 3);
+        assert!(stored.iter().all(|result| result.as_ref().unwrap().metadata.as_ref().unwrap().version == 1));
+        settle(&storage).await;
+
+        // Replicated copies are readable once the hot layer is cleared
+        let metadata = storage.metadata_store.get("items:2").unwrap();
+        storage.hot_layer.delete(&metadata.shard_id, "items:2").await.unwrap();
+
+        let ids: Vec<String> = ["1", "2", "missing"].iter().map(|id| id.to_string()).collect();
+        let read = storage.get_documents_batch("items", &ids).await;
+        assert_eq!(read[0].as_ref().unwrap().data, Some(serde_json::json!({ "n": 1 })));
+        assert_eq!(read[1].as_ref().unwrap().data, Some(serde_json::json!({ "n": 2 })));
+        assert_eq!(read[1].as_ref().unwrap().storage_tier, StorageTier::Warm);
+        assert!(read[2].as_ref().unwrap().data.is_none());
+
+        let deleted = storage.delete_documents_batch("items", &ids).await;
+        assert!(deleted[0].is_ok() && deleted[1].is_ok());
+        assert!(deleted[2].as_ref().unwrap_err().to_string().contains("not found"));
+        assert_eq!(storage.list_documents("items", None, None).await.unwrap(), ["3"]);
+        assert!(storage.warm_layer.get(&metadata.shard_id, "items:2").await.is_err());
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_legal_hold_blocks_deletion_until_released() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-legal-hold-storage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() })
+            .await
+            .unwrap();
+
+        let documents: Vec<(String, serde_json::Value)> =
+            ["1", "2"].iter().map(|id| (id.to_string(), serde_json::json!({ "id": id }))).collect();
+        storage.store_documents_batch("mail", &documents).await;
+        settle(&storage).await;
+
+        let hold = storage
+            .place_legal_hold(LegalHold::new("mail", vec!["1".to_string()], "case 7", "alice"))
+            .unwrap();
+        assert!(storage.place_legal_hold(LegalHold::new("mail", Vec::new(), "case 8", "alice")).is_err());
+
+        let error = storage.delete_document("mail", "1").await.unwrap_err();
+        assert!(error.to_string().contains("legal hold"));
+        let ids = vec!["1".to_string(), "2".to_string()];
+        let deleted = storage.delete_documents_batch("mail", &ids).await;
+        assert!(deleted[0].as_ref().unwrap_err().to_string().contains("legal hold"));
+        assert!(deleted[1].is_ok());
+        assert!(storage.get_document("mail", "1").await.unwrap().data.is_some());
+
+        storage.release_legal_hold(&hold.id, "bob").unwrap();
+        assert!(storage.legal_holds().is_empty());
+        storage.delete_document("mail", "1").await.unwrap();
+
+        let log = storage.legal_hold_audit_log().unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!((log[1].action, log[1].actor.as_str()), (LegalHoldAction::Released, "bob"));
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_write_modes_detect_conflicts() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-write-modes-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() })
+            .await
+            .unwrap();
+        let v1 = serde_json::json!({ "n": 1 });
+        let v2 = serde_json::json!({ "n": 2 });
+
+        // Of two racing creates exactly one wins
+        let (first, second) = tokio::join!(
+            storage.store_document_with_mode("users", "1", &v1, WriteMode::CreateOnly),
+            storage.store_document_with_mode("users", "1", &v2, WriteMode::CreateOnly),
+        );
+        assert!(first.is_ok() != second.is_ok());
+        let created = storage.get_document("users", "1").await.unwrap().metadata.unwrap();
+        assert_eq!(created.version, 1);
+
+        let missing = storage
+            .store_document_with_mode("users", "2", &v1, WriteMode::Replace { expected_version: None })
+            .await;
+        assert!(missing.unwrap_err().to_string().contains("Document not found"));
+
+        let stale = storage
+            .store_document_with_mode("users", "1", &v2, WriteMode::Replace { expected_version: Some(5) })
+            .await;
+        assert!(stale.unwrap_err().to_string().contains("Version mismatch"));
+
+        let replaced = storage
+            .store_document_with_mode("users", "1", &v2, WriteMode::Replace { expected_version: Some(1) })
+            .await
+            .unwrap()
+            .metadata
+            .unwrap();
+        assert_eq!(replaced.version, 2);
+        assert_eq!(replaced.created_at, created.created_at);
+
+        // Plain stores upsert and continue the version history
+        let upserted = storage.store_document("users", "1", &v1).await.unwrap().metadata.unwrap();
+        assert_eq!(upserted.version, 3);
+        assert_eq!(storage.get_document("users", "1").await.unwrap().data.unwrap(), v1);
+
+        settle(&storage).await;
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_and_version_history() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let history = HistoryConfig {
+            soft_delete_retention: Some(std::time::Duration::from_secs(3600)),
+            versioned_collections: ["notes".to_string()].into_iter().collect(),
+            max_revisions: 2,
+        };
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, history, ..Default::default() })
+            .await
+            .unwrap();
+
+        for n in 1..=4 {
+            storage.store_document("notes", "1", &serde_json::json!({ "n": n })).await.unwrap();
+        }
+        settle(&storage).await;
+
+        // Only the newest two previous revisions are kept
+        let versions: Vec<u64> = storage.list_versions("notes", "1").unwrap().iter().map(|m| m.version).collect();
+        assert_eq!(versions, [2, 3, 4]);
+        let old = storage.get_document_version("notes", "1", 2).await.unwrap();
+        assert_eq!(old.data.unwrap(), serde_json::json!({ "n": 2 }));
+        assert!(storage.get_document_version("notes", "1", 1).await.is_err());
+
+        let restored = storage.restore_document_version("notes", "1", 2).await.unwrap();
+        assert_eq!(restored.metadata.unwrap().version, 5);
+        assert_eq!(storage.get_document("notes", "1").await.unwrap().data.unwrap(), serde_json::json!({ "n": 2 }));
+        settle(&storage).await;
+
+        // Deleted documents leave a restorable tombstone and continue their versions
+        storage.delete_document("notes", "1").await.unwrap();
+        assert!(storage.get_document("notes", "1").await.unwrap().data.is_none());
+        let deleted = storage.list_deleted_documents("notes").unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].metadata.version, 5);
+        assert_eq!(storage.purge_expired_tombstones().unwrap(), 0);
+
+        let revived = storage.restore_deleted_document("notes", "1").await.unwrap();
+        assert_eq!(revived.metadata.unwrap().version, 6);
+        assert_eq!(storage.get_document("notes", "1").await.unwrap().data.unwrap(), serde_json::json!({ "n": 2 }));
+        assert!(storage.list_deleted_documents("notes").unwrap().is_empty());
+        assert!(storage.restore_deleted_document("notes", "1").await.is_err());
+        settle(&storage).await;
+
+        // Unversioned collections still get tombstones but no revisions
+        storage.store_document("misc", "1", &serde_json::json!({ "n": 1 })).await.unwrap();
+        storage.store_document("misc", "1", &serde_json::json!({ "n": 2 })).await.unwrap();
+        assert_eq!(storage.list_versions("misc", "1").unwrap().len(), 1);
+        settle(&storage).await;
+        storage.delete_document("misc", "1").await.unwrap();
+        assert_eq!(storage.list_deleted_documents("misc").unwrap().len(), 1);
+
+        assert_eq!(storage.purge_history("misc", "1").unwrap(), 1);
+        assert!(storage.list_deleted_documents("misc").unwrap().is_empty());
+        assert!(storage.purge_history("notes", "1").unwrap() > 0);
+        assert_eq!(storage.list_versions("notes", "1").unwrap().len(), 1);
+
+        std::mem::forget(storage);
+    }
+    #[tokio::test]
+    async fn test_worm_collections_refuse_changes_within_retention() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-worm-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let hour = std::time::Duration::from_secs(3600);
+        let worm_collections = [("ledger".to_string(), hour)].into_iter().collect();
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, worm_collections, ..Default::default() })
+            .await
+            .unwrap();
+
+        let entry = serde_json::json!({ "amount": 10 });
+        storage.store_document("ledger", "1", &entry).await.unwrap();
+        settle(&storage).await;
+
+        // Documents can be written once, then neither changed nor removed
+        let changed = serde_json::json!({ "amount": 20 });
+        assert!(storage.store_document("ledger", "1", &changed).await.unwrap_err().to_string().contains("WORM"));
+        assert!(storage.update_document("ledger", "1", &changed, None).await.is_err());
+        assert!(storage.delete_document("ledger", "1").await.unwrap_err().to_string().contains("WORM"));
+        let stored = storage.store_documents_batch("ledger", &[("1".to_string(), changed.clone())]).await;
+        assert!(stored[0].is_err());
+        let deleted = storage.delete_documents_batch("ledger", &["1".to_string(), "2".to_string()]).await;
+        assert!(deleted[0].as_ref().unwrap_err().to_string().contains("WORM"));
+        assert!(deleted[1].as_ref().unwrap_err().to_string().contains("not found"));
+        assert_eq!(storage.get_document("ledger", "1").await.unwrap().data.unwrap(), entry);
+
+        // Retention can be extended but never shortened
+        assert!(storage.enable_worm("ledger", std::time::Duration::from_secs(60)).is_err());
+        assert_eq!(storage.enable_worm("ledger", hour * 2).unwrap().retention, hour * 2);
+        assert_eq!(storage.worm_policy("ledger").unwrap().retention, hour * 2);
+
+        // Once the retention period has elapsed documents can change again
+        storage.enable_worm("journal", std::time::Duration::ZERO).unwrap();
+        storage.store_document("journal", "1", &entry).await.unwrap();
+        settle(&storage).await;
+        storage.store_document("journal", "1", &changed).await.unwrap();
+        settle(&storage).await;
+        storage.delete_document("journal", "1").await.unwrap();
+        assert!(storage.worm_policy("notes").is_none());
+
+        std::mem::forget(storage);
+    }
+    #[tokio::test]
+    async fn test_residency_restricts_and_remediates_placement() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-residency-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let datacenter = |id: &str, region: &str| RemoteDatacenter {
+            datacenter_id: id.to_string(),
+            endpoints: vec![format!("https://{}.example.com", id)],
+            region: region.to_string(),
+            priority: 1,
+            active: true,
+        };
+        let config = StorageConfig {
+            data_dir: dir,
+            datacenter_replication: Some(DatacenterReplicationConfig {
+                enabled: true,
+                local_datacenter_id: "dc-local".to_string(),
+                remote_datacenters: vec![datacenter("dc-us", "us-east"), datacenter("dc-eu", "eu-central")],
+                default_replication_mode: ReplicationMode::Asynchronous { max_delay_ms: 100 },
+                max_replication_lag_ms: 1000,
+                retry_attempts: 1,
+                batch_size: 10,
+                compression_enabled: false,
+            }),
+            residency: ResidencyConfig {
+                local_region: Some("eu-west".to_string()),
+                allowed_regions: [("eu".to_string(), ["eu-west".to_string(), "eu-central".to_string()].into())]
+                    .into_iter()
+                    .collect(),
+            },
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        let profile = serde_json::json!({ "name": "Ada" });
+
+        // Tagging stored data purges the copy replicated outside the region
+        storage.store_document("users", "1", &profile).await.unwrap();
+        settle(&storage).await;
+        let found = storage.set_collection_residency("users", "eu").await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].datacenter_id.as_deref(), Some("dc-us"));
+        assert_eq!(found[0].remediation, ResidencyRemediation::CopyPurged);
+        assert_eq!(storage.residency_of("users", "2").as_deref(), Some("eu"));
+        storage.store_document("users", "2", &profile).await.unwrap();
+        settle(&storage).await;
+
+        // A stricter document tag flags the local copy and purges the other
+        // allowed datacenter, but not one that never received a copy
+        let found = storage.set_document_residency("users", "1", "us-east").await.unwrap();
+        let remediations: Vec<_> = found.iter().map(|v| (v.datacenter_id.clone(), v.remediation)).collect();
+        assert_eq!(
+            remediations,
+            [
+                (None, ResidencyRemediation::CopyFlagged),
+                (Some("dc-eu".to_string()), ResidencyRemediation::CopyPurged),
+            ]
+        );
+
+        // New copies outside the allowed regions are refused
+        let refused = storage.store_document("users", "1", &profile).await.unwrap_err();
+        assert!(refused.to_string().contains("does not allow placement in region eu-west"));
+        assert!(storage.store_documents_batch("users", &[("1".to_string(), profile.clone())]).await[0].is_err());
+
+        let report = storage.residency_report().unwrap();
+        assert_eq!(report.len(), 5);
+        assert_eq!(report[4].remediation, ResidencyRemediation::PlacementRefused);
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_collection_lifecycle_and_settings() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-collections-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() })
+            .await
+            .unwrap();
+
+        let logs = CollectionConfig {
+            compression: Some(CompressionAlgorithm::Snappy),
+            replication_factor: Some(1),
+            retention: Some(std::time::Duration::ZERO),
+            encrypted: None,
+            volatile: false,
+            max_documents: None,
+            max_size_bytes: None,
+            skip_compression: false,
+            consistency: WriteConsistency::Eventual,
+        };
+        storage.create_collection("logs", logs.clone()).unwrap();
+        assert!(storage.create_collection("logs", CollectionConfig::default()).is_err());
+        assert!(storage.create_collection("a:b", CollectionConfig::default()).is_err());
+
+        // Documents round-trip through the collection's own compression
+        let line = serde_json::json!({ "message": "started" });
+        storage.store_document("logs", "1", &line).await.unwrap();
+        storage.store_document("logs", "2", &line).await.unwrap();
+        storage.store_document("events", "1", &line).await.unwrap();
+        settle(&storage).await;
+        assert_eq!(storage.get_document("logs", "1").await.unwrap().data.unwrap(), line);
+
+        // Compression is fixed while documents exist
+        let other = CollectionConfig { compression: Some(CompressionAlgorithm::None), ..logs.clone() };
+        assert!(storage.update_collection("logs", other).is_err());
+
+        let listed: Vec<_> = storage.list_collections().iter().map(|c| (c.name.clone(), c.document_count, c.registered)).collect();
+        assert_eq!(listed, [("events".to_string(), 1, false), ("logs".to_string(), 2, true)]);
+        assert!(storage.list_collections().iter().all(|c| c.size_bytes > 0));
+
+        // Documents past retention are deleted; the collection stays
+        assert_eq!(storage.enforce_collection_retention().await.unwrap(), 2);
+        assert_eq!(storage.list_collections()[1].document_count, 0);
+
+        assert_eq!(storage.truncate_collection("events").await.unwrap(), 1);
+        assert_eq!(storage.list_collections().len(), 1);
+
+        // Collections requiring encryption refuse writes without a key
+        let sealed = CollectionConfig { encrypted: Some(true), ..Default::default() };
+        storage.create_collection("secrets", sealed).unwrap();
+        assert!(storage.store_document("secrets", "1", &line).await.is_err());
+
+        assert_eq!(storage.drop_collection("logs").await.unwrap(), 0);
+        assert!(storage.collection("logs").is_none());
+        assert_eq!(storage.list_collections().len(), 1);
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_volatile_collections_stay_in_memory() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-volatile-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir, ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        storage.create_collection("sessions", CollectionConfig { volatile: true, ..Default::default() }).unwrap();
+        assert!(storage.enable_worm("sessions", std::time::Duration::from_secs(60)).is_err());
+
+        let session = serde_json::json!({ "user": 1 });
+        storage.store_document("sessions", "a", &session).await.unwrap();
+        storage.store_documents_batch("sessions", &[("b".to_string(), session.clone())]).await[0].as_ref().unwrap();
+        storage.store_document("users", "1", &session).await.unwrap();
+        settle(&storage).await;
+
+        // Only the hot layer holds volatile documents, and they stay there
+        let metadata = storage.metadata_store.get("sessions:a").unwrap();
+        assert!(storage.hot_layer.get(&metadata.shard_id, "sessions:a").await.is_ok());
+        assert!(storage.warm_layer.get(&metadata.shard_id, "sessions:a").await.is_err());
+        assert!(storage.cold_layer.get(&metadata.shard_id, "sessions:a").await.is_err());
+        assert!(storage.tier_migrator().migrate("sessions:a", &metadata, &StorageTier::Warm).await.is_err());
+        assert_eq!(storage.get_document("sessions", "b").await.unwrap().data.unwrap(), session);
+
+        let usage = storage.storage_usage().unwrap();
+        assert!(usage.collections["sessions"].volatile && !usage.collections["users"].volatile);
+        assert_eq!(usage.volatile, usage.collections["sessions"].tiers.hot);
+
+        // The setting is fixed while documents exist
+        assert!(storage.update_collection("sessions", CollectionConfig::default()).is_err());
+
+        // Volatile documents are gone after a restart, persistent ones remain
+        storage.stop().await.unwrap();
+        drop(storage);
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        assert!(storage.list_documents("sessions", None, None).await.unwrap().is_empty());
+        assert_eq!(storage.get_document("users", "1").await.unwrap().data.unwrap(), session);
+        assert!(storage.is_volatile_collection("sessions"));
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_capped_collections_evict_oldest_documents() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-capped-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() }).await.unwrap();
+        let capped = CollectionConfig { max_documents: Some(3), ..Default::default() };
+        assert!(storage.create_collection("feed", CollectionConfig { max_documents: Some(0), ..Default::default() }).is_err());
+        storage.create_collection("feed", capped).unwrap();
+        assert!(storage.enable_worm("feed", std::time::Duration::from_secs(60)).is_err());
+
+        let in_order = ListOptions { sort: DocumentSortKey::CreatedAt, ..Default::default() };
+        let ids = |storage: &StorageHierarchy| {
+            let page = storage.list_documents_with("feed", &in_order).unwrap();
+            page.documents.into_iter().map(|document| document.id).collect::<Vec<_>>()
+        };
+        for id in ["a", "b", "c", "d", "e"] {
+            storage.store_document("feed", id, &serde_json::json!({ "event": id })).await.unwrap();
+        }
+        assert_eq!(ids(&storage), ["c", "d", "e"]);
+
+        // Updates keep their place, and held documents are never evicted
+        storage.store_document("feed", "c", &serde_json::json!({ "event": "c2" })).await.unwrap();
+        storage.place_legal_hold(LegalHold::new("feed", vec!["c".to_string()], "case 9", "alice")).unwrap();
+        storage.store_document("feed", "f", &serde_json::json!({ "event": "f" })).await.unwrap();
+        assert_eq!(ids(&storage), ["c", "e", "f"]);
+
+        // A batch larger than the collection keeps its newest documents
+        let batch: Vec<(String, serde_json::Value)> =
+            ["w", "x", "y"].iter().map(|id| (id.to_string(), serde_json::json!({ "event": id }))).collect();
+        assert!(storage.store_documents_batch("feed", &batch).await.iter().all(Result::is_ok));
+        assert_eq!(ids(&storage), ["c", "x", "y"]);
+
+        // Size limits evict by stored bytes and refuse oversized documents
+        let sized = CollectionConfig { max_size_bytes: Some(1000), ..Default::default() };
+        storage.create_collection("log", sized).unwrap();
+        let line = |i: usize| serde_json::json!({ "text": (0..40).map(|j| ((i + j) * 7919 % 10007).to_string()).collect::<String>() });
+        for i in 0..20 {
+            storage.store_document("log", &i.to_string(), &line(i)).await.unwrap();
+        }
+        let usage = storage.list_collections().into_iter().find(|collection| collection.name == "log").unwrap();
+        assert!(usage.size_bytes <= 1000 && usage.document_count < 20);
+        assert!(storage.get_document("log", "19").await.unwrap().data.is_some());
+        let huge = serde_json::json!({ "text": (0..2000).map(|i| (i * 7919 % 10007).to_string()).collect::<String>() });
+        assert!(storage.store_document("log", "huge", &huge).await.unwrap_err().to_string().contains("limit of capped"));
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_skipped_compression_is_recorded_and_counted() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-skip-compression-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() }).await.unwrap();
+        let document = serde_json::json!({ "text": "compressible ".repeat(50) });
+
+        let compressed = storage.store_document("notes", "1", &document).await.unwrap().metadata.unwrap();
+        assert!(!compressed.compression_skipped && compressed.compression_ratio > 2.0);
+
+        // A single write skips compression, and its payload reads back
+        let options = WriteOptions { skip_compression: true, ..Default::default() };
+        let skipped = storage.store_document_with_options("notes", "2", &document, options).await.unwrap().metadata.unwrap();
+        assert!(skipped.compression_skipped && skipped.compression_ratio < 1.0);
+        let stored = storage.hot_layer.get(&skipped.shard_id, "notes:2").await.unwrap();
+        assert!(CompressionEngine::is_passthrough(&stored));
+        assert_eq!(storage.get_document("notes", "2").await.unwrap().data, Some(document.clone()));
+
+        // So does every write to a collection marked as already compressed,
+        // which can change while it holds documents
+        storage.create_collection("images", CollectionConfig { skip_compression: true, ..Default::default() }).unwrap();
+        let batch = vec![("a".to_string(), document.clone()), ("b".to_string(), document.clone())];
+        assert!(storage.store_documents_batch("images", &batch).await.iter().all(Result::is_ok));
+        assert!(storage.metadata_store.get("images:a").unwrap().compression_skipped);
+        storage.update_collection("images", CollectionConfig::default()).unwrap();
+        assert_eq!(storage.get_document("images", "b").await.unwrap().data, Some(document));
+
+        let stats = storage.get_storage_stats().await.unwrap().compression;
+        assert_eq!((stats.documents_compressed, stats.documents_skipped), (1, 3));
+        assert_eq!(stats.skip_rate(), 0.75);
+
+        std::mem::forget(storage);
+    }
+
+    /// Records replicated writes and applies them as a committed log would
+    struct RecordingReplicator {
+        storage: std::sync::Weak<StorageHierarchy>,
+        writes: std::sync::Mutex<Vec<ReplicatedWrite>>,
+    }
+
+    #[async_trait::async_trait]
+    impl WriteReplicator for RecordingReplicator {
+        async fn replicate(&self, write: ReplicatedWrite) -> Result<StorageResult<()>> {
+            self.writes.lock().unwrap().push(write.clone());
+            self.storage.upgrade().unwrap().apply_replicated_write(&write).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_strong_collections_route_writes_through_replicator() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-consistency-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = Arc::new(
+            StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() }).await.unwrap(),
+        );
+        let strong = CollectionConfig { consistency: WriteConsistency::Strong, ..Default::default() };
+        storage.create_collection("accounts", strong.clone()).unwrap();
+        assert!(storage.create_collection("_system", strong.clone()).is_err());
+        assert!(storage
+            .create_collection("cache", CollectionConfig { volatile: true, ..strong.clone() })
+            .is_err());
+
+        // Without a replicator strong collections are written locally
+        let document = serde_json::json!({ "balance": 10 });
+        storage.store_document("accounts", "a", &document).await.unwrap();
+        assert_eq!(storage.get_document("accounts", "a").await.unwrap().data, Some(document.clone()));
+
+        let replicator = Arc::new(RecordingReplicator {
+            storage: Arc::downgrade(&storage),
+            writes: std::sync::Mutex::new(Vec::new()),
+        });
+        storage.set_write_replicator(replicator.clone());
+
+        // Stores, conflicting stores, batches and deletes are all replicated
+        let updated = serde_json::json!({ "balance": 20 });
+        storage.store_document("accounts", "a", &updated).await.unwrap();
+        let conflict = storage
+            .store_document_with_mode("accounts", "a", &document, WriteMode::Replace { expected_version: Some(1) })
+            .await;
+        assert!(conflict.is_err());
+        let batch = vec![("b".to_string(), document.clone()), ("c".to_string(), document.clone())];
+        assert!(storage.store_documents_batch("accounts", &batch).await.iter().all(Result::is_ok));
+        storage.delete_document("accounts", "b").await.unwrap();
+        assert_eq!(storage.get_document("accounts", "a").await.unwrap().data, Some(updated));
+        assert_eq!(storage.get_document("accounts", "b").await.unwrap().data, None);
+
+        // Eventually consistent collections are written locally
+        storage.store_document("notes", "n", &document).await.unwrap();
+
+        let writes = replicator.writes.lock().unwrap().clone();
+        assert_eq!(writes.len(), 5);
+        assert!(writes.iter().all(|write| write.collection() == "accounts"));
+        assert_eq!(writes[4], ReplicatedWrite::Delete { collection: "accounts".into(), document_id: "b".into() });
+        assert_eq!(storage.collection_consistency("notes"), WriteConsistency::Eventual);
+
+        std::mem::forget(storage);
+    }
+
+    /// Records the consistency of every read it holds back
+    #[derive(Default)]
+    struct RecordingBarrier {
+        waits: std::sync::Mutex<Vec<ReadConsistency>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ReadBarrier for RecordingBarrier {
+        async fn wait_for(&self, consistency: ReadConsistency) -> Result<()> {
+            self.waits.lock().unwrap().push(consistency);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consistent_reads_wait_at_barrier() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-read-consistency-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() }).await.unwrap();
+        let strong = CollectionConfig { consistency: WriteConsistency::Strong, ..Default::default() };
+        storage.create_collection("accounts", strong).unwrap();
+        let document = serde_json::json!({ "balance": 10 });
+        storage.store_document("accounts", "a", &document).await.unwrap();
+        storage.store_document("notes", "n", &document).await.unwrap();
+
+        // Without a barrier every read is current
+        let linearizable = ReadConsistency::parse("linearizable", None).unwrap();
+        let read = storage.get_document_with_consistency("notes", "n", linearizable).await.unwrap();
+        assert_eq!(read.data, Some(document.clone()));
+
+        let barrier = Arc::new(RecordingBarrier::default());
+        storage.set_read_barrier(barrier.clone());
+        let bounded = ReadConsistency::parse("bounded_staleness", Some(250)).unwrap();
+        for consistency in [ReadConsistency::Local, bounded, linearizable] {
+            let read = storage.get_document_with_consistency("accounts", "a", consistency).await.unwrap();
+            assert_eq!(read.data, Some(document.clone()));
+        }
+        assert_eq!(
+            *barrier.waits.lock().unwrap(),
+            vec![ReadConsistency::BoundedStaleness { max_lag: std::time::Duration::from_millis(250) }, linearizable]
+        );
+
+        // Eventually consistent collections only read locally
+        let refused = storage.get_document_with_consistency("notes", "n", linearizable).await;
+        assert!(refused.unwrap_err().to_string().starts_with("Invalid read consistency"));
+        assert!(storage.get_document_with_consistency("notes", "n", ReadConsistency::Local).await.is_ok());
+        assert!(ReadConsistency::parse("bounded_staleness", None).is_err());
+        assert!(ReadConsistency::parse("linearizable", Some(5)).is_err());
+        assert!(ReadConsistency::parse("strong", None).is_err());
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_documents_round_trip_in_chunks() {
+        use futures::TryStreamExt;
+
+        let dir = std::env::temp_dir().join(format!("aerolithdb-streaming-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir, stream_chunk_size: 1024, ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        let blob: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let metadata = storage.store_document_stream("blobs", "1", &blob[..]).await.unwrap().metadata.unwrap();
+        assert_eq!(metadata.checksum, blake3::hash(&blob).to_hex().to_string());
+
+        let manifest = storage.chunk_manifest("blobs", "1").unwrap().unwrap();
+        assert_eq!(manifest.chunks.len(), 10);
+        assert_eq!(manifest.total_size, blob.len() as u64);
+        assert!(storage.get_document("blobs", "1").await.is_err());
+
+        let stream = storage.get_document_stream("blobs", "1").await.unwrap();
+        let chunks: Vec<Vec<u8>> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.len(), 10);
+        assert_eq!(chunks.concat(), blob);
+
+        // Rewriting replaces every chunk of the previous generation
+        let shorter = &blob[..3000];
+        let metadata = storage.store_document_stream("blobs", "1", shorter).await.unwrap().metadata.unwrap();
+        assert_eq!(metadata.version, 2);
+        let chunks: Vec<Vec<u8>> = storage.get_document_stream("blobs", "1").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), shorter);
+        let chunk_keys = |storage: &StorageHierarchy| {
+            let warm_layer = Arc::clone(&storage.warm_layer);
+            async move { warm_layer.keys().await.unwrap().into_iter().filter(|key| key.contains('\0')).count() }
+        };
+        assert_eq!(chunk_keys(&storage).await, 3);
+
+        // A regular write over a streamed document drops its chunks
+        let document = serde_json::json!({ "replaced": true });
+        storage.store_document("blobs", "1", &document).await.unwrap();
+        assert_eq!(chunk_keys(&storage).await, 0);
+        let chunks: Vec<Vec<u8>> = storage.get_document_stream("blobs", "1").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks, [serde_json::to_vec(&document).unwrap()]);
+
+        storage.store_document_stream("blobs", "2", &blob[..]).await.unwrap();
+        storage.delete_document("blobs", "2").await.unwrap();
+        assert!(storage.chunk_manifest("blobs", "2").unwrap().is_none());
+        assert_eq!(chunk_keys(&storage).await, 0);
+        assert!(storage.get_document_stream("blobs", "2").await.is_err());
+
+        settle(&storage).await;
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_tier_rules_move_documents_between_tiers() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-tiering-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir.clone(),
+            access_tracking: AccessTrackingConfig { sample_rate: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        assert_eq!(storage.tier_rules(), TierPolicyConfig::default().rules);
+
+        let document = serde_json::json!({ "event": "login" });
+        for id in ["1", "2"] {
+            storage.store_document("logs", id, &document).await.unwrap();
+        }
+        storage.store_document("users", "1", &document).await.unwrap();
+        settle(&storage).await;
+
+        let rule = |name: &str, from: StorageTier, to: StorageTier| TierRule {
+            name: name.to_string(),
+            collection: Some("logs".to_string()),
+            from,
+            to,
+            min_age: None,
+            min_idle: None,
+            min_size: None,
+            max_size: None,
+            min_reads_per_day: None,
+            max_reads_per_day: None,
+            hottest: None,
+        };
+        assert!(storage.set_tier_rules(vec![rule("noop", StorageTier::Hot, StorageTier::Hot)]).is_err());
+        storage
+            .set_tier_rules(vec![
+                TierRule { min_reads_per_day: Some(1.0), ..rule("keep-read", StorageTier::Hot, StorageTier::Warm) },
+                rule("demote-logs", StorageTier::Hot, StorageTier::Cold),
+            ])
+            .unwrap();
+
+        // A dry run reports the migrations without moving anything
+        let report = storage.run_tier_migration(true).await.unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.evaluated, 3);
+        assert_eq!(report.migrations.len(), 2);
+        assert!(report.migrations.iter().all(|migration| migration.rule == "demote-logs"));
+        assert_eq!(storage.metadata_store.get("logs:1").unwrap().storage_tier, StorageTier::Hot);
+
+        let report = storage.run_tier_migration(false).await.unwrap();
+        assert_eq!((report.migrations.len(), report.failed), (2, 0));
+        let metadata = storage.metadata_store.get("logs:1").unwrap();
+        assert_eq!(metadata.storage_tier, StorageTier::Cold);
+        assert!(storage.hot_layer.get(&metadata.shard_id, "logs:1").await.is_err());
+        assert!(storage.warm_layer.get(&metadata.shard_id, "logs:1").await.is_err());
+        assert_eq!(storage.metadata_store.get("users:1").unwrap().storage_tier, StorageTier::Hot);
+        let read = storage.get_document("logs", "1").await.unwrap();
+        assert_eq!((read.data.unwrap(), read.storage_tier), (document.clone(), StorageTier::Cold));
+
+        // Frequently read documents are promoted again
+        storage
+            .set_tier_rules(vec![TierRule {
+                min_reads_per_day: Some(0.5),
+                ..rule("promote-read", StorageTier::Cold, StorageTier::Warm)
+            }])
+            .unwrap();
+        let report = storage.run_tier_migration(false).await.unwrap();
+        assert_eq!(report.migrations.len(), 1);
+        assert_eq!(report.migrations[0].document_id, "1");
+        assert_eq!(storage.metadata_store.get("logs:1").unwrap().storage_tier, StorageTier::Warm);
+        assert_eq!(storage.metadata_store.get("logs:2").unwrap().storage_tier, StorageTier::Cold);
+
+        let metrics = storage.tier_migration_metrics();
+        assert_eq!((metrics.runs, metrics.dry_runs, metrics.documents_migrated), (2, 1, 3));
+        assert_eq!(metrics.migrated_by_rule["demote-logs"], 2);
+        assert!(metrics.bytes_migrated > 0);
+
+        // Rules set at runtime outlive a restart
+        settle(&storage).await;
+        storage.stop().await.unwrap();
+        drop(storage);
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        assert_eq!(storage.tier_rules()[0].name, "promote-read");
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_archive_follows_tier_migrations() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-archive-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir,
+            access_tracking: AccessTrackingConfig { sample_rate: 1, ..Default::default() },
+            archive: ArchiveConfig { orphan_sweep_interval: Some(std::time::Duration::ZERO), ..Default::default() },
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        let document = serde_json::json!({ "event": "login" });
+        for id in ["1", "2"] {
+            storage.store_document("logs", id, &document).await.unwrap();
+        }
+        settle(&storage).await;
+        storage.archive_layer.store("0", "logs:deleted", b"stale").await.unwrap();
+
+        let rule = |name: &str, from: StorageTier, to: StorageTier| TierRule {
+            name: name.to_string(),
+            collection: Some("logs".to_string()),
+            from,
+            to,
+            min_age: None,
+            min_idle: None,
+            min_size: None,
+            max_size: None,
+            min_reads_per_day: None,
+            max_reads_per_day: None,
+            hottest: None,
+        };
+        storage.set_tier_rules(vec![rule("archive-logs", StorageTier::Hot, StorageTier::Archive)]).unwrap();
+
+        // Archiving sweeps out the object of the deleted document
+        let report = storage.run_tier_migration(false).await.unwrap();
+        assert_eq!((report.migrations.len(), report.archive_objects_removed), (2, 1));
+        let archived = storage.archive_layer.keys().await.unwrap();
+        assert_eq!(archived.len(), 2);
+        let read = storage.get_document("logs", "1").await.unwrap();
+        assert_eq!((read.data.unwrap(), read.storage_tier), (document.clone(), StorageTier::Archive));
+
+        // A promoted document leaves the archive
+        storage
+            .set_tier_rules(vec![TierRule {
+                min_reads_per_day: Some(0.5),
+                ..rule("restore-read", StorageTier::Archive, StorageTier::Warm)
+            }])
+            .unwrap();
+        let report = storage.run_tier_migration(false).await.unwrap();
+        assert_eq!((report.migrations.len(), report.archive_objects_removed), (1, 0));
+        let archived = storage.archive_layer.keys().await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].ends_with(":logs:2"));
+        let read = storage.get_document("logs", "1").await.unwrap();
+        assert_eq!((read.data.unwrap(), read.storage_tier), (document, StorageTier::Warm));
+        assert_eq!(storage.tier_migration_metrics().archive_objects_removed, 1);
+
+        settle(&storage).await;
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_hottest_documents_are_promoted() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-hottest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir,
+            access_tracking: AccessTrackingConfig { sample_rate: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        let document = serde_json::json!({ "event": "login" });
+        for id in ["1", "2", "3"] {
+            storage.store_document("logs", id, &document).await.unwrap();
+        }
+        settle(&storage).await;
+
+        let rule = |name: &str, from: StorageTier, to: StorageTier| TierRule {
+            name: name.to_string(),
+            collection: Some("logs".to_string()),
+            from,
+            to,
+            min_age: None,
+            min_idle: None,
+            min_size: None,
+            max_size: None,
+            min_reads_per_day: None,
+            max_reads_per_day: None,
+            hottest: None,
+        };
+        storage.set_tier_rules(vec![rule("demote-logs", StorageTier::Hot, StorageTier::Cold)]).unwrap();
+        storage.run_tier_migration(false).await.unwrap();
+        for _ in 0..3 {
+            storage.get_document("logs", "2").await.unwrap();
+        }
+        storage.get_document("logs", "3").await.unwrap();
+        storage.get_document("logs", "3").await.unwrap();
+        storage.get_document("logs", "1").await.unwrap();
+
+        let promote = TierRule { hottest: Some(0), ..rule("promote-hot", StorageTier::Cold, StorageTier::Hot) };
+        assert!(storage.set_tier_rules(vec![promote.clone()]).is_err());
+        storage.set_tier_rules(vec![TierRule { hottest: Some(2), ..promote }]).unwrap();
+
+        // Only the two most read documents are promoted
+        let report = storage.run_tier_migration(false).await.unwrap();
+        let mut promoted: Vec<&str> = report.migrations.iter().map(|migration| migration.document_id.as_str()).collect();
+        promoted.sort();
+        assert_eq!(promoted, ["2", "3"]);
+        assert_eq!(storage.metadata_store.get("logs:1").unwrap().storage_tier, StorageTier::Cold);
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_scrub_detects_and_repairs_corrupt_copies() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-scrub-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir,
+            stream_chunk_size: 1024,
+            scrub: ScrubConfig { interval: None, min_age: std::time::Duration::ZERO, ..Default::default() },
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        assert!(storage.last_scrub_report().unwrap().is_none());
+
+        for id in ["1", "2", "3"] {
+            storage.store_document("users", id, &serde_json::json!({ "id": id })).await.unwrap();
+        }
+        let blob: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        storage.store_document_stream("blobs", "1", &blob[..]).await.unwrap();
+        settle(&storage).await;
+
+        let clean = storage.scrub(true).await.unwrap();
+        assert_eq!((clean.documents, clean.copies_verified, clean.corrupt.len()), (4, 15, 0));
+
+        // Rot one copy of users:1, every copy of users:2 and a cold chunk
+        let shard = |key: &str| storage.metadata_store.get(key).unwrap().shard_id;
+        let intact = storage.warm_layer.get(&shard("users:1"), "users:1").await.unwrap();
+        storage.warm_layer.store(&shard("users:1"), "users:1", b"rotten").await.unwrap();
+        storage.hot_layer.store(&shard("users:2"), "users:2", b"rotten").await.unwrap();
+        storage.warm_layer.store(&shard("users:2"), "users:2", b"rotten").await.unwrap();
+        storage.cold_layer.store(&shard("users:2"), "users:2", b"rotten").await.unwrap();
+        let manifest = storage.chunk_manifest("blobs", "1").unwrap().unwrap();
+        let chunk_key = manifest.chunk_key("blobs:1", 0);
+        storage.cold_layer.store(&shard("blobs:1"), &chunk_key, b"rotten").await.unwrap();
+
+        // Without repair corruption is only reported
+        let report = storage.scrub(false).await.unwrap();
+        assert_eq!((report.corrupt.len(), report.repaired), (5, 0));
+        assert_eq!(report.unrecoverable, ["users:2"]);
+        assert!(report.corrupt.iter().all(|copy| copy.repaired_from.is_none()));
+
+        let report = storage.scrub(true).await.unwrap();
+        assert_eq!((report.corrupt.len(), report.repaired), (5, 2));
+        let warm = report.corrupt.iter().find(|copy| copy.document_id == "1" && copy.collection == "users").unwrap();
+        assert_eq!((&warm.tier, &warm.repaired_from), (&StorageTier::Warm, &Some(StorageTier::Hot)));
+        let chunk = report.corrupt.iter().find(|copy| copy.collection == "blobs").unwrap();
+        assert_eq!((chunk.chunk, &chunk.tier, &chunk.repaired_from), (Some(0), &StorageTier::Cold, &Some(StorageTier::Warm)));
+        assert_eq!(storage.warm_layer.get(&shard("users:1"), "users:1").await.unwrap(), intact);
+        assert_eq!(storage.last_scrub_report().unwrap().unwrap(), report);
+
+        // Only the unrecoverable document is left corrupt
+        let report = storage.scrub(true).await.unwrap();
+        assert_eq!((report.corrupt.len(), report.repaired), (3, 0));
+        assert_eq!(report.unrecoverable, ["users:2"]);
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_documents_stay_verifiable_across_checksum_algorithms() {
+        use futures::TryStreamExt;
+
+        let dir = std::env::temp_dir().join(format!("aerolithdb-checksums-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir,
+            stream_chunk_size: 1024,
+            scrub: ScrubConfig { interval: None, min_age: std::time::Duration::ZERO, ..Default::default() },
+            ..Default::default()
+        };
+        let mut storage = StorageHierarchy::new(&config).await.unwrap();
+        storage.store_document("users", "1", &serde_json::json!({ "id": 1 })).await.unwrap();
+        let blob: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        storage.store_document_stream("blobs", "1", &blob[..]).await.unwrap();
+
+        // New writes use the new default, existing documents keep theirs
+        storage.config.checksum.algorithm = ChecksumAlgorithm::Sha256;
+        storage.store_document("users", "2", &serde_json::json!({ "id": 2 })).await.unwrap();
+        settle(&storage).await;
+        let algorithm = |key: &str| storage.metadata_store.get(key).unwrap().checksum_algorithm;
+        assert_eq!((algorithm("users:1"), algorithm("users:2")), (ChecksumAlgorithm::Blake3, ChecksumAlgorithm::Sha256));
+        let stats = storage.get_storage_stats().await.unwrap();
+        assert_eq!(stats.checksums.documents[&ChecksumAlgorithm::Blake3], 2);
+        assert_eq!(stats.checksums.documents[&ChecksumAlgorithm::Sha256], 1);
+
+        // Scrubs find every copy intact and move whole documents over
+        let report = storage.scrub(true).await.unwrap();
+        assert_eq!((report.corrupt.len(), report.checksums_migrated), (0, 1));
+        assert_eq!(algorithm("users:1"), ChecksumAlgorithm::Sha256);
+        assert_eq!(storage.scrub(true).await.unwrap().corrupt.len(), 0);
+
+        // Streamed documents are read against their manifest's algorithm
+        let streamed: Vec<Vec<u8>> = storage.get_document_stream("blobs", "1").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(streamed.concat(), blob);
+        assert_eq!(algorithm("blobs:1"), ChecksumAlgorithm::Blake3);
+        assert_eq!(storage.get_document("users", "1").await.unwrap().data.unwrap(), serde_json::json!({ "id": 1 }));
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_full_and_incremental_backups_restore_points_in_time() {
+        use futures::TryStreamExt;
+
+        let dir = std::env::temp_dir().join(format!("aerolithdb-backup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir, stream_chunk_size: 1024, ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        let read = |id: &'static str| {
+            let storage = &storage;
+            async move { storage.get_document("users", id).await.unwrap().data }
+        };
+
+        assert!(storage.create_backup(BackupKind::Incremental).await.is_err());
+        for id in ["1", "2"] {
+            storage.store_document("users", id, &serde_json::json!({ "v": 1 })).await.unwrap();
+        }
+        let blob: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        storage.store_document_stream("blobs", "1", &blob[..]).await.unwrap();
+        settle(&storage).await;
+        let full = storage.create_backup(BackupKind::Full).await.unwrap();
+        assert_eq!((full.documents, full.total_documents, full.parent.as_deref()), (3, 3, None));
+
+        // The incremental backup only holds what changed
+        storage.update_document("users", "1", &serde_json::json!({ "v": 2 }), None).await.unwrap();
+        storage.delete_document("users", "2").await.unwrap();
+        storage.store_document("users", "3", &serde_json::json!({ "v": 1 })).await.unwrap();
+        settle(&storage).await;
+        let incremental = storage.create_backup(BackupKind::Incremental).await.unwrap();
+        assert_eq!(incremental.parent.as_deref(), Some(full.id.as_str()));
+        assert_eq!((incremental.documents, incremental.total_documents, incremental.deleted), (2, 3, 1));
+        assert_eq!(storage.list_backups().await.unwrap(), [full.clone(), incremental.clone()]);
+
+        storage.update_document("users", "1", &serde_json::json!({ "v": 3 }), None).await.unwrap();
+        storage.store_document("users", "4", &serde_json::json!({ "v": 1 })).await.unwrap();
+        storage.store_document_stream("blobs", "1", &blob[..100]).await.unwrap();
+        settle(&storage).await;
+
+        let report = storage.restore_backup(&incremental.id).await.unwrap();
+        assert_eq!(report.chain, [full.id.clone(), incremental.id.clone()]);
+        assert_eq!((report.restored, report.unchanged, report.deleted), (2, 1, 1));
+        assert_eq!(read("1").await, Some(serde_json::json!({ "v": 2 })));
+        assert_eq!(read("2").await, None);
+        assert_eq!(read("4").await, None);
+        let chunks: Vec<Vec<u8>> = storage.get_document_stream("blobs", "1").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), blob);
+
+        // Restoring the time of the full backup brings back the deleted document
+        let report = storage.restore_backup_at(full.created_at).await.unwrap();
+        assert_eq!((report.backup_id.as_str(), report.restored, report.deleted), (full.id.as_str(), 2, 1));
+        assert_eq!(read("1").await, Some(serde_json::json!({ "v": 1 })));
+        assert_eq!(read("2").await, Some(serde_json::json!({ "v": 1 })));
+        assert_eq!(read("3").await, None);
+        assert!(storage.restore_backup_at(full.created_at - chrono::Duration::seconds(1)).await.is_err());
+
+        // Backups others depend on stay; corrupt bundles are not restored
+        assert!(storage.delete_backup(&full.id).await.is_err());
+        let documents = storage.backups.dir().join(&incremental.id).join("documents.bin");
+        let mut bytes = std::fs::read(&documents).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&documents, bytes).unwrap();
+        assert!(storage.restore_backup(&incremental.id).await.is_err());
+        storage.delete_backup(&incremental.id).await.unwrap();
+        storage.delete_backup(&full.id).await.unwrap();
+        assert!(storage.list_backups().await.unwrap().is_empty());
+        assert!(storage.backup_manifest("../metadata").await.is_err());
+
+        settle(&storage).await;
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_backups_restore_in_place_and_into_namespace() {
+        use futures::TryStreamExt;
+
+        let dir = std::env::temp_dir().join(format!("aerolithdb-scoped-backup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir, stream_chunk_size: 1024, ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        storage.store_document("acme_users", "1", &serde_json::json!({ "v": 1 })).await.unwrap();
+        storage.store_document("other_users", "1", &serde_json::json!({ "v": 1 })).await.unwrap();
+        let blob: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        storage.store_document_stream("acme_blobs", "1", &blob[..]).await.unwrap();
+        settle(&storage).await;
+
+        assert!(storage.create_scoped_backup(BackupKind::Full, Some("acme:")).await.is_err());
+        let scoped = storage.create_scoped_backup(BackupKind::Full, Some("acme_")).await.unwrap();
+        assert_eq!((scoped.scope.as_deref(), scoped.total_documents), (Some("acme_"), 2));
+        // Incremental backups only build on backups of their own scope
+        assert!(storage.create_backup(BackupKind::Incremental).await.is_err());
+
+        // Restoring in place leaves other collections alone
+        storage.update_document("acme_users", "1", &serde_json::json!({ "v": 2 }), None).await.unwrap();
+        storage.store_document("acme_users", "2", &serde_json::json!({ "v": 1 })).await.unwrap();
+        storage.store_document("other_users", "2", &serde_json::json!({ "v": 1 })).await.unwrap();
+        settle(&storage).await;
+        let report = storage.restore_backup(&scoped.id).await.unwrap();
+        assert_eq!((report.restored, report.unchanged, report.deleted), (1, 1, 1));
+        assert!(storage.get_document("other_users", "2").await.unwrap().data.is_some());
+
+        // Restoring into a namespace copies the documents next to the current ones
+        storage.update_document("acme_users", "1", &serde_json::json!({ "v": 3 }), None).await.unwrap();
+        settle(&storage).await;
+        let report = storage.restore_backup_into(&scoped.id, "acme_restored_").await.unwrap();
+        assert_eq!((report.namespace.as_deref(), report.restored), (Some("acme_restored_"), 2));
+        let restored = storage.get_document("acme_restored_users", "1").await.unwrap().data;
+        assert_eq!(restored, Some(serde_json::json!({ "v": 1 })));
+        assert_eq!(storage.get_document("acme_users", "1").await.unwrap().data, Some(serde_json::json!({ "v": 3 })));
+        let chunks: Vec<Vec<u8>> =
+            storage.get_document_stream("acme_restored_blobs", "1").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), blob);
+        let error = storage.restore_backup_into(&scoped.id, "acme_restored_").await.unwrap_err();
+        assert!(error.to_string().starts_with("Restore namespace is not empty"));
+
+        settle(&storage).await;
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_access_statistics_are_sampled_and_persisted() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-access-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir.clone(),
+            access_tracking: AccessTrackingConfig { sample_rate: 2, ..Default::default() },
+            ..Default::default()
+        };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        let document = serde_json::json!({ "name": "aerolith" });
+        for id in ["1", "2", "3"] {
+            storage.store_document("users", id, &document).await.unwrap();
+        }
+        for _ in 0..4 {
+            storage.get_document("users", "1").await.unwrap();
+        }
+        assert!(storage.access_stats("users", "2").is_none());
+
+        // Every second read is recorded and counts twice
+        let stats = storage.access_stats("users", "1").unwrap();
+        assert_eq!(stats.reads, 4);
+        assert!(stats.reads_per_day > 2.0 && stats.reads_per_day <= 4.0 * std::f64::consts::LN_2);
+
+        let storage_stats = storage.get_storage_stats().await.unwrap();
+        assert_eq!((storage_stats.total_reads, storage_stats.unread_documents), (4, 2));
+        let hot = storage.hot_documents(10, std::time::Duration::from_secs(60));
+        assert_eq!(hot, vec![HeavyHitter { key: "users:1".to_string(), count: 4, error: 0 }]);
+        assert_eq!(storage.hot_collections(10, std::time::Duration::from_secs(60))[0].count, 4);
+
+        // Statistics outlive a restart, except those of deleted documents
+        storage.get_document("users", "3").await.unwrap();
+        storage.get_document("users", "3").await.unwrap();
+        storage.delete_document("users", "3").await.unwrap();
+        settle(&storage).await;
+        storage.stop().await.unwrap();
+        drop(storage);
+
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        assert_eq!(storage.access_stats("users", "1").unwrap().reads, 4);
+        assert!(storage.access_stats("users", "3").is_none());
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_background_jobs_queue_cancel_and_restart() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-jobs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            data_dir: dir.clone(),
+            jobs: JobConfig { max_concurrent: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let storage = Arc::new(StorageHierarchy::new(&config).await.unwrap());
+        storage.store_document("tenant_a_users", "1", &serde_json::json!({ "name": "a" })).await.unwrap();
+        settle(&storage).await;
+
+        async fn finished(storage: &StorageHierarchy, id: &str) -> Job {
+            for _ in 0..500 {
+                let job = storage.jobs().get(id).unwrap();
+                if job.state.is_finished() {
+                    return job;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("job {} never finished", id);
+        }
+
+        // A backup job records its progress, log and manifest
+        let job = storage.spawn_backup_job(BackupKind::Full, Some("tenant_a_".to_string()), Some("a")).unwrap();
+        assert_eq!((job.kind.as_str(), job.tenant.as_deref()), ("backup", Some("a")));
+        let job = finished(&storage, &job.id).await;
+        assert_eq!(job.state, JobState::Succeeded);
+        assert_eq!(job.progress, JobProgress { completed: 1, total: Some(1) });
+        let manifest: BackupManifest = serde_json::from_value(job.result.unwrap()).unwrap();
+        assert_eq!(manifest.documents, 1);
+        assert_eq!(job.logs.len(), 2);
+        assert!(storage.jobs().cancel(&job.id).unwrap_err().to_string().starts_with("Job already finished"));
+
+        // With one slot the second job waits, and cancelling it keeps it from starting
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let blocking = storage
+            .jobs()
+            .spawn("test", None, |job| async move {
+                let _ = released.await;
+                job.check_cancelled()?;
+                Ok(())
+            })
+            .unwrap();
+        while storage.jobs().get(&blocking.id).unwrap().state == JobState::Queued {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let queued = storage.spawn_scrub_job(false).unwrap();
+        assert_eq!(storage.jobs().get(&queued.id).unwrap().state, JobState::Queued);
+        assert_eq!(storage.jobs().cancel(&queued.id).unwrap().state, JobState::Cancelled);
+        assert_eq!(storage.jobs().cancel(&blocking.id).unwrap().state, JobState::Running);
+        release.send(()).unwrap();
+        assert_eq!(finished(&storage, &blocking.id).await.state, JobState::Cancelled);
+        assert!(storage.last_scrub_report().unwrap().is_none());
+
+        // Jobs waited on report their outcome, and failures are recorded
+        let answer = storage.jobs().run("test", Some("b"), |_| async { Ok(42) }).await.unwrap();
+        assert_eq!(answer, 42);
+        let failed = storage.jobs().run("test", Some("b"), |_| async { Err::<(), _>(anyhow::anyhow!("boom")) }).await;
+        assert!(failed.is_err());
+        let tenant_jobs = storage.jobs().list(Some("b"), None).unwrap();
+        assert_eq!(tenant_jobs.len(), 2);
+        assert!(tenant_jobs.iter().any(|job| job.state == JobState::Failed && job.error.as_deref() == Some("boom")));
+        assert!(tenant_jobs.iter().any(|job| job.result == Some(serde_json::json!(42))));
+        assert_eq!(storage.jobs().list(None, Some(JobState::Cancelled)).unwrap().len(), 2);
+        assert!(storage.jobs().get("missing").unwrap_err().to_string().starts_with("Job not found"));
+
+        // Jobs left unfinished by a restart are marked failed
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let registry = Arc::new(JobRegistry::open(&db, &JobConfig::default()).unwrap());
+        let (_hold, held) = tokio::sync::oneshot::channel::<()>();
+        let job = registry.spawn("test", None, |_| async move { held.await.map_err(anyhow::Error::from) }).unwrap();
+        tokio::task::yield_now().await;
+        let reopened = JobRegistry::open(&db, &JobConfig::default()).unwrap();
+        let job = reopened.get(&job.id).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert!(job.error.unwrap().starts_with("Interrupted"));
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_write_ahead_log_replays_unreplicated_writes() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-wal-{}", std::process::id()));
+        let crashed = std::env::temp_dir().join(format!("aerolithdb-wal-crashed-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&crashed);
+        let config = StorageConfig { data_dir: dir.clone(), ..Default::default() };
+        let document = serde_json::json!({ "name": "aerolith" });
+
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        storage.store_document("users", "1", &document).await.unwrap();
+        storage.update_document("users", "1", &serde_json::json!({ "name": "v2" }), None).await.unwrap();
+        storage.store_document("users", "2", &document).await.unwrap();
+        storage.delete_document("users", "2").await.unwrap();
+        let batch = storage.store_documents_batch("users", &[("3".to_string(), document.clone())]).await;
+        assert!(batch[0].is_ok());
+        settle(&storage).await;
+        assert_eq!(storage.pending_wal_records(), 0);
+
+        // A node that kept only its log, as if it crashed before replicating
+        // or flushing metadata, redoes every acknowledged write; a record
+        // torn by the crash is ignored
+        std::fs::create_dir_all(crashed.join("wal")).unwrap();
+        let mut segments: Vec<_> = std::fs::read_dir(dir.join("wal")).unwrap().map(|entry| entry.unwrap().path()).collect();
+        segments.sort();
+        for segment in &segments {
+            std::fs::copy(segment, crashed.join("wal").join(segment.file_name().unwrap())).unwrap();
+        }
+        let last = crashed.join("wal").join(segments.last().unwrap().file_name().unwrap());
+        let mut bytes = std::fs::read(&last).unwrap();
+        bytes.extend_from_slice(&[42, 0, 0, 0, 1, 2, 3]);
+        std::fs::write(&last, bytes).unwrap();
+
+        let recovered = StorageHierarchy::new(&StorageConfig { data_dir: crashed.clone(), ..Default::default() }).await.unwrap();
+        let read = recovered.get_document("users", "1").await.unwrap();
+        assert_eq!(read.data.unwrap(), serde_json::json!({ "name": "v2" }));
+        assert_eq!(read.metadata.unwrap().version, 2);
+        assert!(recovered.get_document("users", "2").await.unwrap().data.is_none());
+        assert_eq!(recovered.get_document("users", "3").await.unwrap().data.unwrap(), document);
+        let shard_id = recovered.metadata_store.get("users:3").unwrap().shard_id;
+        assert!(recovered.warm_layer.get(&shard_id, "users:3").await.is_ok());
+        assert_eq!(std::fs::read_dir(crashed.join("wal")).unwrap().count(), 0);
+
+        // Checkpoints drop the segments once their writes are replicated
+        assert_eq!(storage.checkpoint_wal().await.unwrap(), segments.len());
+        assert_eq!(std::fs::read_dir(dir.join("wal")).unwrap().count(), 0);
+        storage.store_document("users", "4", &document).await.unwrap();
+        settle(&storage).await;
+        storage.stop().await.unwrap();
+        assert_eq!(std::fs::read_dir(dir.join("wal")).unwrap().count(), 0);
+
+        std::mem::forget(storage);
+        std::mem::forget(recovered);
+    }
+
+    #[tokio::test]
+    async fn test_collection_statistics_describe_fields() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-statistics-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir.clone(), ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        let documents: Vec<(String, serde_json::Value)> = (0..2000)
+            .map(|i| {
+                let document = serde_json::json!({
+                    "age": i % 100,
+                    "status": if i % 4 == 0 { "inactive" } else { "active" },
+                    "profile": { "email": format!("user{}@example.com", i) },
+                    "nickname": if i % 2 == 0 { serde_json::Value::Null } else { serde_json::json!("n") },
+                });
+                (i.to_string(), document)
+            })
+            .collect();
+        for result in storage.store_documents_batch("users", &documents).await {
+            result.unwrap();
+        }
+        storage.store_document("orders", "1", &serde_json::json!({ "total": 5 })).await.unwrap();
+        settle(&storage).await;
+        assert!(storage.collection_statistics("users").unwrap().is_none());
+
+        let collected = storage.analyze_collections().await.unwrap();
+        assert_eq!(collected.len(), 2);
+        let statistics = storage.collection_statistics("users").unwrap().unwrap();
+        assert_eq!(statistics.documents, 2000);
+
+        let age = statistics.field("age").unwrap();
+        assert_eq!((age.non_null, age.numeric, age.distinct), (2000, 2000, 100));
+        assert_eq!((age.min, age.max), (Some(0.0), Some(99.0)));
+        let below = age.histogram.as_ref().unwrap().fraction_below(25.0);
+        assert!((below - 0.25).abs() < 0.05, "fraction below 25 was {}", below);
+        assert_eq!(statistics.field("status").unwrap().distinct, 2);
+        assert!(statistics.field("status").unwrap().histogram.is_none());
+        assert_eq!(statistics.field("nickname").unwrap().non_null, 1000);
+
+        // Beyond the sketch size, distinct values are estimated
+        let emails = statistics.field("profile.email").unwrap().distinct;
+        assert!((1800..=2000).contains(&emails), "estimated {} distinct emails", emails);
+
+        // Statistics go with their collection
+        storage.truncate_collection("orders").await.unwrap();
+        assert!(storage.collection_statistics("orders").unwrap().is_none());
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_storage_quota_rejects_or_archives() {
+        let document = |i: usize| serde_json::json!({ "id": i, "token": uuid::Uuid::new_v4().to_string(), "note": uuid::Uuid::new_v4().to_string() });
+
+        // Rejecting writes keeps usage under the limit
+        let dir = std::env::temp_dir().join(format!("aerolithdb-quota-reject-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir.clone(), max_storage_size: Some(1000), ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        let mut events = storage.subscribe_quota_events();
+
+        let mut stored = 0;
+        let rejection = loop {
+            match storage.store_document("notes", &stored.to_string(), &document(stored)).await {
+                Ok(_) => stored += 1,
+                Err(e) => break e,
+            }
+            assert!(stored < 100, "the limit was never reached");
+        };
+        assert!(rejection.to_string().starts_with("Storage quota exceeded"));
+        assert!(stored > 0);
+        let usage = storage.storage_usage().unwrap();
+        assert!(usage.used <= 1000);
+        assert_eq!(usage.collections["notes"].documents, stored as u64);
+        assert_eq!(usage.tiers.hot, usage.used);
+        assert!(matches!(events.try_recv().unwrap(), QuotaEvent::WriteRejected { collection, .. } if collection == "notes"));
+
+        // Deleting frees room again
+        settle(&storage).await;
+        storage.delete_document("notes", "0").await.unwrap();
+        storage.store_document("notes", "0", &document(0)).await.unwrap();
+        settle(&storage).await;
+        std::mem::forget(storage);
+
+        // Archiving the oldest documents makes room instead
+        let dir = std::env::temp_dir().join(format!("aerolithdb-quota-archive-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let quota = QuotaConfig { action: QuotaAction::ArchiveOldest, ..Default::default() };
+        let config = StorageConfig { data_dir: dir.clone(), max_storage_size: Some(1000), quota, ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        let mut events = storage.subscribe_quota_events();
+
+        for i in 0..20 {
+            storage.store_document("notes", &i.to_string(), &document(i)).await.unwrap();
+            settle(&storage).await;
+        }
+        let usage = storage.storage_usage().unwrap();
+        assert!(usage.used <= 1000);
+        assert!(usage.tiers.archive > 0);
+        assert_eq!(usage.collections["notes"].documents, 20);
+        assert!(matches!(events.try_recv().unwrap(), QuotaEvent::DocumentsArchived { .. }));
+
+        let oldest = storage.get_document("notes", "0").await.unwrap();
+        assert_eq!(oldest.storage_tier, StorageTier::Archive);
+        assert_eq!(oldest.data.unwrap()["id"], 0);
+        let newest = storage.get_document("notes", "19").await.unwrap();
+        assert_ne!(newest.storage_tier, StorageTier::Archive);
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_expired_documents_are_deleted_or_archived() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-expiration-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = StorageConfig { data_dir: dir.clone(), ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+        let mut events = storage.subscribe_expirations();
+        let ttl = std::time::Duration::from_secs(3600);
+
+        storage.store_document_with_ttl("sessions", "live", &serde_json::json!({ "user": 1 }), ttl).await.unwrap();
+        storage.store_document_with_ttl("sessions", "lapsed", &serde_json::json!({ "user": 2 }), std::time::Duration::ZERO).await.unwrap();
+        storage.store_document("sessions", "held", &serde_json::json!({ "user": 3 })).await.unwrap();
+        settle(&storage).await;
+
+        // Plain writes keep the expiry, which can also be set afterwards
+        let expires_at = storage.document_expiry("sessions", "live").unwrap();
+        storage.store_document("sessions", "live", &serde_json::json!({ "user": 1, "seen": true })).await.unwrap();
+        assert_eq!(storage.document_expiry("sessions", "live"), Some(expires_at));
+        assert_eq!(storage.document_expiry("sessions", "held"), None);
+        storage.set_document_expiry("sessions", "held", Some(chrono::Utc::now())).unwrap();
+        storage.place_legal_hold(LegalHold::new("sessions", vec!["held".to_string()], "audit", "legal")).unwrap();
+        settle(&storage).await;
+
+        let report = storage.expire_documents().await.unwrap();
+        assert_eq!(report.kept, 1);
+        assert!(matches!(
+            report.expired.as_slice(),
+            [ExpirationEvent::DocumentDeleted { document_id, .. }] if document_id == "lapsed"
+        ));
+        assert_eq!(events.try_recv().unwrap(), report.expired[0]);
+        assert!(storage.get_document("sessions", "lapsed").await.unwrap().data.is_none());
+        assert!(storage.get_document("sessions", "live").await.unwrap().data.is_some());
+        assert!(storage.get_document("sessions", "held").await.unwrap().data.is_some());
+        std::mem::forget(storage);
+
+        // Archiving keeps expired documents readable from the Archive tier
+        let dir = std::env::temp_dir().join(format!("aerolithdb-expiration-archive-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let expiration = ExpirationConfig { action: ExpirationAction::Archive, ..Default::default() };
+        let config = StorageConfig { data_dir: dir.clone(), expiration, ..Default::default() };
+        let storage = StorageHierarchy::new(&config).await.unwrap();
+
+        storage.store_document_with_ttl("sessions", "lapsed", &serde_json::json!({ "user": 2 }), std::time::Duration::ZERO).await.unwrap();
+        settle(&storage).await;
+        let report = storage.expire_documents().await.unwrap();
+        assert!(matches!(report.expired.as_slice(), [ExpirationEvent::DocumentArchived { .. }]));
+        let archived = storage.get_document("sessions", "lapsed").await.unwrap();
+        assert_eq!(archived.storage_tier, StorageTier::Archive);
+        assert_eq!(archived.data.unwrap()["user"], 2);
+        assert_eq!(storage.document_expiry("sessions", "lapsed"), None);
+        assert!(storage.expire_documents().await.unwrap().expired.is_empty());
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_rebalancing_moves_documents_to_joining_nodes() {
+        struct Loopback(Arc<StorageHierarchy>);
+
+        #[async_trait::async_trait]
+        impl ShardTransport for Loopback {
+            async fn send(&self, node: &ClusterNode, document: &TransferredDocument) -> Result<()> {
+                assert_eq!(node.id, self.0.node_id());
+                self.0.accept_transferred_document(document.clone()).await.map(|_| ())
+            }
+        }
+
+        let node = |name: &str| {
+            let data_dir = std::env::temp_dir().join(format!("aerolithdb-rebalance-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&data_dir);
+            let rebalance = RebalanceConfig { node_id: name.to_string(), max_bytes_per_second: None, ..Default::default() };
+            StorageConfig { data_dir, replication_factor: 1, rebalance, ..Default::default() }
+        };
+        let node_b = Arc::new(StorageHierarchy::new(&node("node-b")).await.unwrap());
+        let node_a = StorageHierarchy::new(&node("node-a")).await.unwrap().with_shard_transport(Arc::new(Loopback(Arc::clone(&node_b))));
+        let node_a = Arc::new(node_a);
+
+        for i in 0..50 {
+            node_a.store_document("items", &i.to_string(), &serde_json::json!({ "n": i })).await.unwrap();
+        }
+        settle(&node_a).await;
+        assert!(node_a.plan_rebalance().await.unwrap().moves.is_empty());
+
+        // Half the documents belong on the new node
+        let change = MembershipChange::NodeJoined { node_id: "node-b".to_string(), address: None };
+        let job = node_a.apply_membership_change(change).await.unwrap().unwrap();
+        let job = loop {
+            let job = node_a.jobs().get(&job.id).unwrap();
+            if job.state.is_finished() {
+                break job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        assert_eq!(job.state, JobState::Succeeded, "{:?}", job.error);
+        let report: RebalanceReport = serde_json::from_value(job.result.unwrap()).unwrap();
+        assert!(report.moved > 0 && report.moved < 50);
+        assert_eq!((report.released, report.failed), (report.moved, 0));
+        assert_eq!(node_a.metadata_store.len() + node_b.metadata_store.len(), 50);
+        assert!(node_a.plan_rebalance().await.unwrap().moves.is_empty());
+
+        for i in 0..50 {
+            let id = i.to_string();
+            let (holder, other) = if node_a.metadata_store.get(&format!("items:{}", id)).is_some() { (&node_a, &node_b) } else { (&node_b, &node_a) };
+            let document = holder.get_document("items", &id).await.unwrap();
+            assert_eq!(document.data.unwrap()["n"], i);
+            assert!(holder.is_primary_holder("items", &id) && !other.is_primary_holder("items", &id));
+        }
+        let moved = node_b.metadata_store.iter().next().unwrap().value().clone();
+        assert_eq!(moved.replica_locations, vec!["node-b".to_string()]);
+        assert_eq!(node_a.cluster_nodes().unwrap().len(), 2);
+
+        // Neither the local node nor an unknown node can leave
+        assert!(node_a.apply_membership_change(MembershipChange::NodeLeft { node_id: "node-a".to_string() }).await.is_err());
+        assert!(node_a.apply_membership_change(MembershipChange::NodeLeft { node_id: "node-c".to_string() }).await.is_err());
+
+        std::mem::forget(node_a);
+        std::mem::forget(node_b);
+    }
+
+    #[tokio::test]
+    async fn test_zstd_collections_compress_with_trained_dictionaries() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-dictionaries-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() }).await.unwrap();
+        let events = CollectionConfig { compression: Some(CompressionAlgorithm::Zstd), replication_factor: None, retention: None, encrypted: None, volatile: false, max_documents: None, max_size_bytes: None, skip_compression: false, consistency: WriteConsistency::Eventual };
+        storage.create_collection("events", events).unwrap();
+        let event = |i: usize| serde_json::json!({
+            "type": "page_view",
+            "user_agent": "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0",
+            "path": format!("/articles/{}", i),
+            "status": if i.is_multiple_of(3) { "bounced" } else { "engaged" },
+            "duration_ms": i * 37 % 5000,
+        });
+        let payload = |id: &str| {
+            let key = format!("events:{}", id);
+            let shard_id = storage.metadata_store.get(&key).unwrap().shard_id.clone();
+            let hot_layer = Arc::clone(&storage.hot_layer);
+            async move { hot_layer.get(&shard_id, &key).await.unwrap() }
+        };
+
+        // Only Zstd collections with enough documents get a dictionary
+        storage.store_document("notes", "1", &event(1)).await.unwrap();
+        assert!(storage.train_compression_dictionary("notes").await.is_err());
+        for i in 0..50 {
+            storage.store_document("events", &i.to_string(), &event(i)).await.unwrap();
+        }
+        assert_eq!(storage.train_compression_dictionary("events").await.unwrap(), None);
+        for i in 50..200 {
+            storage.store_document("events", &i.to_string(), &event(i)).await.unwrap();
+        }
+        let first = storage.train_compression_dictionary("events").await.unwrap().unwrap();
+        assert_eq!((first.version, first.samples), (1, 200));
+        assert!(first.compressed_bytes < first.plain_compressed_bytes);
+
+        // New writes name the dictionary they were compressed with
+        storage.store_document("events", "200", &event(200)).await.unwrap();
+        assert!(payload("200").await.starts_with(b"AZdc\0\0\0\x01"));
+        assert!(!payload("0").await.starts_with(b"AZdc"));
+
+        // Documents stay readable after the dictionary is replaced
+        let second = storage.train_compression_dictionary("events").await.unwrap().unwrap();
+        assert_eq!(second.version, 2);
+        storage.store_document("events", "201", &event(201)).await.unwrap();
+        assert!(payload("201").await.starts_with(b"AZdc\0\0\0\x02"));
+        for i in [0, 199, 200, 201] {
+            assert_eq!(storage.get_document("events", &i.to_string()).await.unwrap().data.unwrap(), event(i));
+        }
+        let versions: Vec<u32> = storage.compression_dictionaries("events").unwrap().iter().map(|d| d.version).collect();
+        assert_eq!(versions, [1, 2]);
+
+        // Fresh dictionaries are not retrained
+        assert!(storage.retrain_compression_dictionaries().await.unwrap().is_empty());
+        assert_eq!(storage.analyze_collections().await.unwrap().iter().find(|s| s.collection == "events").unwrap().documents, 202);
+
+        std::mem::forget(storage);
+    }
+
+    #[tokio::test]
+    async fn test_list_documents_with_metadata_filters_and_sorting() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-listing-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = StorageHierarchy::new(&StorageConfig { data_dir: dir, ..Default::default() }).await.unwrap();
+        let now = chrono::Utc::now();
+        for (id, days_old, padding) in [("a", 10, 300), ("b", 1, 10), ("c", 40, 100), ("d", 5, 100)] {
+            let text: String = (0..padding).map(|i: usize| (i * 7919 % 10007).to_string()).collect();
+            storage.store_document("logs", id, &serde_json::json!({ "text": text })).await.unwrap();
+            storage.metadata_store.get_mut(&format!("logs:{}", id)).unwrap().created_at = now - chrono::Duration::days(days_old);
+        }
+        storage.store_document("logs", "b", &serde_json::json!({ "text": "y" })).await.unwrap();
+        storage.metadata_store.get_mut("logs:c").unwrap().storage_tier = StorageTier::Archive;
+        let ids = |page: DocumentPage| page.documents.into_iter().map(|document| document.id).collect::<Vec<_>>();
+
+        let page = storage.list_documents_with("logs", &ListOptions::default()).unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(page.documents[1].version, 2);
+        assert_eq!(page.documents[2].storage_tier, StorageTier::Archive);
+
+        let newest_first = ListOptions { sort: DocumentSortKey::CreatedAt, descending: true, ..Default::default() };
+        assert_eq!(ids(storage.list_documents_with("logs", &newest_first).unwrap()), ["b", "d", "a", "c"]);
+
+        // Equal sizes stay in ID order either way
+        let by_size = ListOptions { sort: DocumentSortKey::Size, ..Default::default() };
+        assert_eq!(ids(storage.list_documents_with("logs", &by_size).unwrap()), ["b", "c", "d", "a"]);
+        let largest_first = ListOptions { descending: true, limit: Some(2), ..by_size };
+        let page = storage.list_documents_with("logs", &largest_first).unwrap();
+        assert!(page.has_more);
+        let after = page.documents.last().cloned();
+        assert_eq!((page.total, ids(page)), (4, vec!["a".to_string(), "c".to_string()]));
+
+        // Resuming after the last document is not shifted by earlier writes
+        let text: String = (0..1000).map(|i: usize| (i * 7919 % 10007).to_string()).collect();
+        storage.store_document("logs", "e", &serde_json::json!({ "text": text })).await.unwrap();
+        let next_page = ListOptions { after, ..largest_first };
+        let page = storage.list_documents_with("logs", &next_page).unwrap();
+        assert!(!page.has_more);
+        assert_eq!(ids(page), ["d", "b"]);
+        storage.delete_document("logs", "e").await.unwrap();
+
+        let week = std::time::Duration::from_secs(7 * 24 * 3600);
+        let older = ListOptions { min_age: Some(week), ..Default::default() };
+        assert_eq!(ids(storage.list_documents_with("logs", &older).unwrap()), ["a", "c"]);
+        let recent = ListOptions { max_age: Some(week), tier: Some(StorageTier::Hot), ..Default::default() };
+        assert_eq!(ids(storage.list_documents_with("logs", &recent).unwrap()), ["b", "d"]);
+        let archived = ListOptions { tier: Some(StorageTier::Archive), offset: Some(1), ..Default::default() };
+        assert_eq!(storage.list_documents_with("logs", &archived).unwrap(), DocumentPage { documents: Vec::new(), total: 1, has_more: false });
+
+        std::mem::forget(storage);
+    }
 }
@@ -0,0 +1,161 @@
+//! # Document Listings
+//!
+//! Listing a collection answers from the in-memory metadata, so sizes,
+//! versions, timestamps and tiers come with each document ID at no extra
+//! cost. [`StorageHierarchy::list_documents_with`] filters a collection's
+//! documents by tier and age, sorts them by ID, creation or update time or
+//! size, and returns one page of them along with the number of matches,
+//! sparing callers a read per document just to decide what to look at.
+//!
+//! Ties are broken by ID, so every document has one position in a listing.
+//! A listing can resume [`ListOptions::after`] the last document of a
+//! previous page, which unlike an offset is not shifted by documents
+//! written or deleted in between.
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{DocumentMetadata, StorageHierarchy, StorageTier};
+
+/// Order of a document listing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSortKey {
+    /// Document ID, lexically
+    #[default]
+    Id,
+    CreatedAt,
+    UpdatedAt,
+
+    /// Stored size
+    Size,
+}
+
+/// Which documents of a collection are listed, and in what order.
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub sort: DocumentSortKey,
+
+    /// Largest first instead of smallest first; ties stay in ID order
+    pub descending: bool,
+
+    /// Only documents whose primary copy is on this tier
+    pub tier: Option<StorageTier>,
+
+    /// Only documents created at least this long ago
+    pub min_age: Option<Duration>,
+
+    /// Only documents created at most this long ago
+    pub max_age: Option<Duration>,
+
+    /// Only documents listed after this one, as a previous page ended
+    pub after: Option<ListedDocument>,
+}
+
+impl ListOptions {
+    /// Order of two documents in this listing.
+    fn order(&self, a: &ListedDocument, b: &ListedDocument) -> Ordering {
+        let order = match self.sort {
+            DocumentSortKey::Id => a.id.cmp(&b.id),
+            DocumentSortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+            DocumentSortKey::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            DocumentSortKey::Size => a.size.cmp(&b.size),
+        };
+        let order = if self.descending { order.reverse() } else { order };
+        order.then_with(|| a.id.cmp(&b.id))
+    }
+}
+
+/// A listed document and its metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListedDocument {
+    pub id: String,
+
+    /// Stored size in bytes, after compression
+    pub size: usize,
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub storage_tier: StorageTier,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<&DocumentMetadata> for ListedDocument {
+    fn from(metadata: &DocumentMetadata) -> Self {
+        Self {
+            id: metadata.id.clone(),
+            size: metadata.size,
+            version: metadata.version,
+            created_at: metadata.created_at,
+            updated_at: metadata.updated_at,
+            storage_tier: metadata.storage_tier.clone(),
+            expires_at: metadata.expires_at,
+        }
+    }
+}
+
+/// One page of a document listing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentPage {
+    pub documents: Vec<ListedDocument>,
+
+    /// Documents matching the filters, before limit and offset
+    pub total: usize,
+
+    /// Whether more documents follow this page
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+impl StorageHierarchy {
+    /// List the documents of a collection with their metadata, filtered,
+    /// sorted and paginated as `options` asks.
+    pub fn list_documents_with(&self, collection: &str, options: &ListOptions) -> Result<DocumentPage> {
+        let now = Utc::now();
+        let age_bound = |age: Option<Duration>| -> Result<Option<DateTime<Utc>>> {
+            age.map(|age| {
+                chrono::Duration::from_std(age)
+                    .ok()
+                    .and_then(|age| now.checked_sub_signed(age))
+                    .ok_or_else(|| anyhow::anyhow!("Invalid document age: {:?}", age))
+            })
+            .transpose()
+        };
+        let (created_before, created_after) = (age_bound(options.min_age)?, age_bound(options.max_age)?);
+
+        let prefix = format!("{}:", collection);
+        let mut documents: Vec<ListedDocument> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| entry.key().starts_with(&prefix))
+            .filter(|entry| options.tier.as_ref().is_none_or(|tier| entry.storage_tier == *tier))
+            .filter(|entry| created_before.is_none_or(|before| entry.created_at <= before))
+            .filter(|entry| created_after.is_none_or(|after| entry.created_at >= after))
+            .map(|entry| ListedDocument {
+                id: entry.key()[prefix.len()..].to_string(),
+                ..ListedDocument::from(entry.value())
+            })
+            .collect();
+
+        documents.sort_by(|a, b| options.order(a, b));
+
+        let total = documents.len();
+        let start = options
+            .after
+            .as_ref()
+            .map_or(0, |after| documents.partition_point(|document| options.order(document, after).is_le()));
+        let mut documents: Vec<ListedDocument> =
+            documents.into_iter().skip(start.saturating_add(options.offset.unwrap_or(0))).collect();
+        let limit = options.limit.unwrap_or(usize::MAX);
+        let has_more = documents.len() > limit;
+        documents.truncate(limit);
+        Ok(DocumentPage { documents, total, has_more })
+    }
+}
@@ -0,0 +1,298 @@
+//! # Persistent Document Metadata
+//!
+//! Document metadata (versions, checksums, shard assignments and key ids) is
+//! served from memory and written through to a sled database under
+//! `<data_dir>/metadata`, which is loaded back when the storage hierarchy
+//! starts. sled flushes writes in the background and on
+//! [`MetadataStore::flush`]; entries lost in a crash before a flush, or a lost
+//! metadata database, can be recovered from the persistent tiers with
+//! `StorageHierarchy::rebuild_metadata`.
+
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+use anyhow::Result;
+use dashmap::mapref::entry::Entry;
+use dashmap::mapref::one::RefMut;
+use dashmap::DashMap;
+use tracing::{error, info, warn};
+
+use crate::quota::UsageCounters;
+use crate::DocumentMetadata;
+
+/// Document metadata keyed by `collection:document_id`, persisted to disk.
+#[derive(Debug)]
+pub struct MetadataStore {
+    /// In-memory copy serving all reads
+    entries: DashMap<String, DocumentMetadata>,
+
+    /// Durable copy, one JSON-encoded entry per document
+    db: sled::Db,
+
+    /// Stored bytes per collection and tier
+    usage: UsageCounters,
+}
+
+impl MetadataStore {
+    /// Open the metadata database in `dir` and load its entries.
+    ///
+    /// Entries that can no longer be decoded are skipped with a warning and
+    /// left for the rebuild path to recover.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let db = sled::open(dir)?;
+        let entries = DashMap::new();
+        let usage = UsageCounters::default();
+
+        for item in db.iter() {
+            let (key, value) = item?;
+            let key = String::from_utf8_lossy(&key).into_owned();
+            match serde_json::from_slice::<DocumentMetadata>(&value) {
+                Ok(metadata) => {
+                    usage.add(&metadata);
+                    entries.insert(key, metadata);
+                }
+                Err(e) => warn!("Skipping unreadable metadata for {}: {}", key, e),
+            }
+        }
+
+        info!("Loaded metadata for {} documents from {:?}", entries.len(), dir);
+        Ok(Self { entries, db, usage })
+    }
+
+    /// Metadata of one document.
+    pub fn get(&self, key: &str) -> Option<DocumentMetadata> {
+        self.entries.get(key).map(|entry| entry.clone())
+    }
+
+    /// Exclusive access to one document's metadata. Changes are written to
+    /// disk when the guard is dropped.
+    pub fn get_mut(&self, key: &str) -> Option<MetadataGuard<'_>> {
+        self.entries
+            .get_mut(key)
+            .map(|entry| MetadataGuard { entry, db: &self.db, usage: &self.usage, before: None })
+    }
+
+    /// Insert or replace one document's metadata.
+    pub fn insert(&self, key: String, metadata: DocumentMetadata) -> Result<()> {
+        self.db.insert(key.as_bytes(), serde_json::to_vec(&metadata)?)?;
+        self.usage.add(&metadata);
+        if let Some(previous) = self.entries.insert(key, metadata) {
+            self.usage.remove(&previous);
+        }
+        Ok(())
+    }
+
+    /// Atomically replace one document's metadata with the result of `write`,
+    /// which receives the current metadata, if any, and may refuse the write.
+    ///
+    /// Concurrent writers of the same document are serialized, so `write`
+    /// always sees the metadata of the last successful write.
+    pub fn write_with<F>(&self, key: String, write: F) -> Result<DocumentMetadata>
+    where
+        F: FnOnce(Option<&DocumentMetadata>) -> Result<DocumentMetadata>,
+    {
+        match self.entries.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let metadata = write(Some(entry.get()))?;
+                self.db.insert(entry.key().as_bytes(), serde_json::to_vec(&metadata)?)?;
+                self.usage.replace(Some(entry.get()), &metadata);
+                entry.insert(metadata.clone());
+                Ok(metadata)
+            }
+            Entry::Vacant(entry) => {
+                let metadata = write(None)?;
+                self.db.insert(entry.key().as_bytes(), serde_json::to_vec(&metadata)?)?;
+                self.usage.add(&metadata);
+                entry.insert(metadata.clone());
+                Ok(metadata)
+            }
+        }
+    }
+
+    /// Remove one document's metadata, returning it if it existed.
+    pub fn remove(&self, key: &str) -> Result<Option<DocumentMetadata>> {
+        self.db.remove(key.as_bytes())?;
+        let removed = self.entries.remove(key).map(|(_, metadata)| metadata);
+        if let Some(metadata) = &removed {
+            self.usage.remove(metadata);
+        }
+        Ok(removed)
+    }
+
+    /// Insert or replace the metadata of several documents with one write.
+    pub fn insert_batch(&self, entries: Vec<(String, DocumentMetadata)>) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for (key, metadata) in &entries {
+            batch.insert(key.as_bytes(), serde_json::to_vec(metadata)?);
+        }
+        self.db.apply_batch(batch)?;
+
+        for (key, metadata) in entries {
+            self.usage.add(&metadata);
+            if let Some(previous) = self.entries.insert(key, metadata) {
+                self.usage.remove(&previous);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the metadata of several documents with one write, returning
+    /// the entries that existed in the order of `keys`.
+    pub fn remove_batch(&self, keys: &[String]) -> Result<Vec<Option<DocumentMetadata>>> {
+        let mut batch = sled::Batch::default();
+        for key in keys {
+            batch.remove(key.as_bytes());
+        }
+        self.db.apply_batch(batch)?;
+
+        Ok(keys
+            .iter()
+            .map(|key| {
+                let removed = self.entries.remove(key).map(|(_, metadata)| metadata);
+                if let Some(metadata) = &removed {
+                    self.usage.remove(metadata);
+                }
+                removed
+            })
+            .collect())
+    }
+
+    /// Iterate over the metadata of all documents.
+    pub fn iter(&self) -> dashmap::iter::Iter<'_, String, DocumentMetadata> {
+        self.entries.iter()
+    }
+
+    /// Number of documents with metadata.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no document has metadata.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Stored bytes per collection and tier.
+    pub(crate) fn usage(&self) -> &UsageCounters {
+        &self.usage
+    }
+
+    /// Database holding the metadata, shared by other persisted storage state.
+    pub(crate) fn db(&self) -> &sled::Db {
+        &self.db
+    }
+
+    /// Write all pending changes to disk.
+    pub async fn flush(&self) -> Result<()> {
+        crate::backends::flush_db(&self.db).await?;
+        Ok(())
+    }
+}
+
+/// Mutable metadata of one document, persisted when dropped if modified.
+pub struct MetadataGuard<'a> {
+    entry: RefMut<'a, String, DocumentMetadata>,
+    db: &'a sled::Db,
+    usage: &'a UsageCounters,
+
+    /// Metadata before the first change, if any
+    before: Option<DocumentMetadata>,
+}
+
+impl Deref for MetadataGuard<'_> {
+    type Target = DocumentMetadata;
+
+    fn deref(&self) -> &DocumentMetadata {
+        self.entry.value()
+    }
+}
+
+impl DerefMut for MetadataGuard<'_> {
+    fn deref_mut(&mut self) -> &mut DocumentMetadata {
+        if self.before.is_none() {
+            self.before = Some(self.entry.value().clone());
+        }
+        self.entry.value_mut()
+    }
+}
+
+impl Drop for MetadataGuard<'_> {
+    fn drop(&mut self) {
+        let Some(before) = self.before.take() else {
+            return;
+        };
+        self.usage.replace(Some(&before), self.entry.value());
+
+        let persisted = serde_json::to_vec(self.entry.value())
+            .map_err(anyhow::Error::from)
+            .and_then(|value| Ok(self.db.insert(self.entry.key().as_bytes(), value)?));
+        if let Err(e) = persisted {
+            error!("Failed to persist metadata for {}: {}", self.entry.key(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StorageTier;
+
+    fn metadata(collection: &str, id: &str) -> DocumentMetadata {
+        DocumentMetadata {
+            id: id.to_string(),
+            collection: collection.to_string(),
+            size: 10,
+            compression_ratio: 1.0,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            version: 1,
+            checksum: "abc".to_string(),
+            checksum_algorithm: crate::ChecksumAlgorithm::Blake3,
+            storage_tier: StorageTier::Hot,
+            shard_id: "local_node".to_string(),
+            replica_locations: Vec::new(),
+            encryption_key_id: None,
+            expires_at: None,
+            compression_skipped: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_survive_reopening() {
+        let dir = std::env::temp_dir().join(format!("aerolithdb-metadata-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let store = MetadataStore::open(&dir).unwrap();
+        store.insert("users:1".to_string(), metadata("users", "1")).unwrap();
+        store.insert("users:2".to_string(), metadata("users", "2")).unwrap();
+        store.get_mut("users:1").unwrap().version = 5;
+        store.remove("users:2").unwrap();
+
+        // Restart on the same directory: close the store, flush what its
+        // last handle wrote, and let that handle go before reopening
+        let db = store.db().clone();
+        drop(store);
+        crate::backends::flush_db(&db).await.unwrap();
+        drop(db);
+        wait_for_release(&dir);
+
+        let store = MetadataStore::open(&dir).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get("users:1").unwrap().version, 5);
+        assert!(store.get("users:2").is_none());
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Block until sled lets go of the database's file lock. Its worker
+    /// threads can still hold the file for a moment after a flush returns
+    /// and the last handle is dropped.
+    fn wait_for_release(dir: &Path) {
+        use fs2::FileExt;
+
+        let file = std::fs::File::open(dir.join("db")).unwrap();
+        file.lock_exclusive().unwrap();
+        file.unlock().unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+//! # Storage Quota
+//!
+//! `StorageConfig::max_storage_size` caps the bytes a node keeps outside
+//! the Archive tier: the stored size of every document on the hot, warm
+//! and cold tiers, counted once on the tier it resides on, plus the
+//! payloads of retained revisions and tombstones. Document bytes are
+//! accounted per collection and tier as metadata changes; retained bytes
+//! are measured when usage is reported and on every quota check.
+//!
+//! A write that would exceed the limit is handled according to
+//! [`QuotaConfig::action`]: it is rejected, or room is first made by moving
+//! the least recently updated documents to the Archive tier, or by purging
+//! the oldest tombstones. A background check also runs the action once
+//! usage passes [`QuotaConfig::high_watermark`] of the limit, bringing it
+//! back to [`QuotaConfig::low_watermark`]. Documents under legal hold are
+//! neither archived nor purged. Every threshold crossing, rejected write,
+//! archival and purge is published as a [`QuotaEvent`] that plugins and
+//! other subscribers receive through
+//! [`StorageHierarchy::subscribe_quota_events`].
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::collections::CollectionRegistry;
+use crate::history::HistoryStore;
+use crate::legal_hold::LegalHoldRegistry;
+use crate::streaming::ChunkManifests;
+use crate::tiering::TierMigrator;
+use crate::{DocumentMetadata, MetadataStore, StorageHierarchy, StorageTier};
+
+/// Quota events buffered for slow subscribers
+const EVENT_CAPACITY: usize = 256;
+
+/// What happens when the storage limit is reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Refuse writes that would exceed the limit
+    #[default]
+    Reject,
+
+    /// Move the least recently updated documents to the Archive tier
+    ArchiveOldest,
+
+    /// Purge the oldest tombstones of soft-deleted documents
+    PurgeTombstones,
+}
+
+/// How the storage limit is enforced.
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    /// Action taken when a write would exceed the limit, and in the
+    /// background once usage passes the high watermark
+    pub action: QuotaAction,
+
+    /// Share of the limit at which a warning is published and the action
+    /// starts making room
+    pub high_watermark: f64,
+
+    /// Share of the limit the action brings usage back down to
+    pub low_watermark: f64,
+
+    /// Time between background quota checks
+    pub check_interval: Duration,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            action: QuotaAction::Reject,
+            high_watermark: 0.9,
+            low_watermark: 0.8,
+            check_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Bytes stored on each tier.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierUsage {
+    pub hot: u64,
+    pub warm: u64,
+    pub cold: u64,
+    pub archive: u64,
+}
+
+impl TierUsage {
+    fn tier_mut(&mut self, tier: &StorageTier) -> &mut u64 {
+        match tier {
+            StorageTier::Hot => &mut self.hot,
+            StorageTier::Warm => &mut self.warm,
+            StorageTier::Cold => &mut self.cold,
+            StorageTier::Archive => &mut self.archive,
+        }
+    }
+
+    /// Bytes outside the Archive tier
+    fn local(&self) -> u64 {
+        self.hot + self.warm + self.cold
+    }
+}
+
+/// Storage used by one collection.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionUsage {
+    /// Documents of the collection
+    pub documents: u64,
+
+    /// Stored bytes of its documents by the tier they reside on
+    pub tiers: TierUsage,
+
+    /// Bytes of its retained revisions and tombstones
+    pub retained: u64,
+
+    /// Whether its documents are kept in memory only
+    #[serde(default)]
+    pub volatile: bool,
+}
+
+/// Storage used by the node against its limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// `max_storage_size`, if set
+    pub limit: Option<u64>,
+
+    /// Bytes counted against the limit
+    pub used: u64,
+
+    /// Stored bytes of all documents by the tier they reside on
+    pub tiers: TierUsage,
+
+    /// Bytes of retained revisions and tombstones
+    pub retained: u64,
+
+    /// Bytes of documents of volatile collections, lost on restart
+    #[serde(default)]
+    pub volatile: u64,
+
+    /// Usage of each collection
+    pub collections: BTreeMap<String, CollectionUsage>,
+}
+
+/// A change in storage usage against the limit, published to subscribers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum QuotaEvent {
+    /// Usage passed the high watermark
+    HighWatermarkReached { used: u64, limit: u64 },
+
+    /// A write was refused because it would exceed the limit
+    WriteRejected { collection: String, requested: u64, used: u64, limit: u64 },
+
+    /// Documents were moved to the Archive tier to make room
+    DocumentsArchived { documents: usize, bytes: u64, used: u64, limit: u64 },
+
+    /// Tombstones were purged to make room
+    TombstonesPurged { tombstones: usize, bytes: u64, used: u64, limit: u64 },
+}
+
+/// Stored document bytes per collection and tier, kept up to date by the
+/// metadata store.
+#[derive(Debug, Default)]
+pub(crate) struct UsageCounters {
+    collections: DashMap<String, CollectionUsage>,
+}
+
+impl UsageCounters {
+    pub(crate) fn add(&self, metadata: &DocumentMetadata) {
+        let mut usage = self.collections.entry(metadata.collection.clone()).or_default();
+        usage.documents += 1;
+        *usage.tiers.tier_mut(&metadata.storage_tier) += metadata.size as u64;
+    }
+
+    pub(crate) fn remove(&self, metadata: &DocumentMetadata) {
+        if let Some(mut usage) = self.collections.get_mut(&metadata.collection) {
+            usage.documents = usage.documents.saturating_sub(1);
+            let bytes = usage.tiers.tier_mut(&metadata.storage_tier);
+            *bytes = bytes.saturating_sub(metadata.size as u64);
+        }
+        self.collections.remove_if(&metadata.collection, |_, usage| usage.documents == 0);
+    }
+
+    pub(crate) fn replace(&self, previous: Option<&DocumentMetadata>, current: &DocumentMetadata) {
+        if let Some(previous) = previous {
+            self.remove(previous);
+        }
+        self.add(current);
+    }
+
+    /// Document bytes outside the Archive tier
+    fn local(&self) -> u64 {
+        self.collections.iter().map(|usage| usage.tiers.local()).sum()
+    }
+}
+
+/// Quota events and the state of the background check.
+#[derive(Debug)]
+pub(crate) struct QuotaState {
+    events: broadcast::Sender<QuotaEvent>,
+
+    /// Whether usage was above the high watermark at the last check
+    above_high_watermark: AtomicBool,
+
+    /// Bytes of retained revisions and tombstones at the last measurement
+    retained: AtomicU64,
+}
+
+impl QuotaState {
+    pub(crate) fn new() -> Self {
+        Self {
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            above_high_watermark: AtomicBool::new(false),
+            retained: AtomicU64::new(0),
+        }
+    }
+
+    fn publish(&self, event: QuotaEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+}
+
+/// Storage components quota checks work on, detached from the hierarchy
+/// so checks can be spawned.
+#[derive(Clone)]
+pub(crate) struct QuotaEnforcer {
+    config: QuotaConfig,
+    limit: Option<u64>,
+    state: Arc<QuotaState>,
+    metadata_store: Arc<MetadataStore>,
+    history: Arc<HistoryStore>,
+    legal_holds: Arc<LegalHoldRegistry>,
+    chunks: Arc<ChunkManifests>,
+    collections: Arc<CollectionRegistry>,
+    migrator: TierMigrator,
+}
+
+impl QuotaEnforcer {
+    /// Storage used per tier and collection, measuring retained bytes anew.
+    pub(crate) fn usage(&self) -> Result<StorageUsage> {
+        let mut collections: BTreeMap<String, CollectionUsage> = self
+            .metadata_store
+            .usage()
+            .collections
+            .iter()
+            .map(|usage| (usage.key().clone(), usage.value().clone()))
+            .collect();
+        let retained = self.history.retained_bytes()?;
+        for (collection, bytes) in &retained {
+            collections.entry(collection.clone()).or_default().retained = *bytes;
+        }
+        let retained: u64 = retained.values().sum();
+        self.state.retained.store(retained, Ordering::Relaxed);
+
+        let mut tiers = TierUsage::default();
+        let mut volatile = 0;
+        for (name, usage) in collections.iter_mut() {
+            tiers.hot += usage.tiers.hot;
+            tiers.warm += usage.tiers.warm;
+            tiers.cold += usage.tiers.cold;
+            tiers.archive += usage.tiers.archive;
+            usage.volatile = self.collections.is_volatile(name);
+            if usage.volatile {
+                volatile += usage.tiers.local();
+            }
+        }
+        Ok(StorageUsage { limit: self.limit, used: tiers.local() + retained, tiers, retained, volatile, collections })
+    }
+
+    /// Bytes counted against the limit, with retained bytes as last measured
+    fn used(&self) -> u64 {
+        self.metadata_store.usage().local() + self.state.retained.load(Ordering::Relaxed)
+    }
+
+    /// Make sure `requested` more bytes fit in the limit, making room as
+    /// configured, or refuse the write.
+    pub(crate) async fn reserve(&self, collection: &str, requested: u64) -> Result<()> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+        if self.used() + requested <= limit {
+            return Ok(());
+        }
+
+        // Retained bytes may have changed since they were last measured
+        let mut used = self.usage()?.used;
+        if used + requested > limit && self.config.action != QuotaAction::Reject {
+            // Room for the write, down to the low watermark if possible
+            let target = self.watermark(limit, self.config.low_watermark).min(limit.saturating_sub(requested));
+            self.make_room(limit, (used + requested).saturating_sub(target)).await?;
+            used = self.usage()?.used;
+        }
+        if used + requested <= limit {
+            return Ok(());
+        }
+
+        self.state.publish(QuotaEvent::WriteRejected { collection: collection.to_string(), requested, used, limit });
+        Err(anyhow::anyhow!(
+            "Storage quota exceeded: {} of {} bytes used, {} more requested for {}",
+            used,
+            limit,
+            requested,
+            collection
+        ))
+    }
+
+    /// Check usage against the high watermark, publishing an event when it
+    /// is passed and making room down to the low watermark unless writes
+    /// are only rejected.
+    pub(crate) async fn enforce(&self) -> Result<StorageUsage> {
+        let usage = self.usage()?;
+        let Some(limit) = self.limit else {
+            return Ok(usage);
+        };
+
+        let above = usage.used >= self.watermark(limit, self.config.high_watermark);
+        if !above {
+            self.state.above_high_watermark.store(false, Ordering::Relaxed);
+            return Ok(usage);
+        }
+        if !self.state.above_high_watermark.swap(true, Ordering::Relaxed) {
+            warn!("Storage usage of {} bytes passed the high watermark of the {} byte limit", usage.used, limit);
+            self.state.publish(QuotaEvent::HighWatermarkReached { used: usage.used, limit });
+        }
+        if self.config.action == QuotaAction::Reject {
+            return Ok(usage);
+        }
+
+        let target = self.watermark(limit, self.config.low_watermark);
+        self.make_room(limit, usage.used.saturating_sub(target)).await?;
+        self.usage()
+    }
+
+    fn watermark(&self, limit: u64, share: f64) -> u64 {
+        (limit as f64 * share.clamp(0.0, 1.0)) as u64
+    }
+
+    /// Free at least `bytes` with the configured action, as far as possible.
+    async fn make_room(&self, limit: u64, bytes: u64) -> Result<()> {
+        match self.config.action {
+            QuotaAction::Reject => {}
+            QuotaAction::ArchiveOldest => {
+                let (documents, freed) = self.archive_oldest(bytes).await;
+                if documents > 0 {
+                    info!("Archived {} documents ({} bytes) to stay within the storage limit", documents, freed);
+                    let used = self.used();
+                    self.state.publish(QuotaEvent::DocumentsArchived { documents, bytes: freed, used, limit });
+                }
+            }
+            QuotaAction::PurgeTombstones => {
+                let (tombstones, freed) = self.history.purge_oldest_tombstones(bytes, &self.legal_holds)?;
+                if tombstones > 0 {
+                    info!("Purged {} tombstones ({} bytes) to stay within the storage limit", tombstones, freed);
+                    let used = self.usage()?.used;
+                    self.state.publish(QuotaEvent::TombstonesPurged { tombstones, bytes: freed, used, limit });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the least recently updated documents to the Archive tier until
+    /// `bytes` are freed, returning how many documents and bytes moved.
+    async fn archive_oldest(&self, bytes: u64) -> (usize, u64) {
+        let mut candidates: Vec<(String, DocumentMetadata)> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| entry.storage_tier != StorageTier::Archive)
+            .filter(|entry| !self.legal_holds.is_held(entry.key()) && !self.chunks.contains(entry.key()))
+            .filter(|entry| !self.collections.is_volatile(&entry.collection))
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        candidates.sort_by_key(|(_, metadata)| metadata.updated_at);
+
+        let (mut documents, mut freed) = (0, 0);
+        for (key, metadata) in candidates {
+            if freed >= bytes {
+                break;
+            }
+            match self.migrator.migrate(&key, &metadata, &StorageTier::Archive).await {
+                Ok(()) => {
+                    documents += 1;
+                    freed += metadata.size as u64;
+                }
+                Err(e) => warn!("Failed to archive {} for the storage limit: {}", key, e),
+            }
+        }
+        (documents, freed)
+    }
+}
+
+impl StorageHierarchy {
+    /// Storage used per tier and collection, against `max_storage_size`.
+    pub fn storage_usage(&self) -> Result<StorageUsage> {
+        self.quota_enforcer().usage()
+    }
+
+    /// Check usage against the limit now instead of at the next background
+    /// check, making room as configured.
+    pub async fn enforce_storage_quota(&self) -> Result<StorageUsage> {
+        self.quota_enforcer().enforce().await
+    }
+
+    /// Receive quota events from now on.
+    pub fn subscribe_quota_events(&self) -> broadcast::Receiver<QuotaEvent> {
+        self.quota.events.subscribe()
+    }
+
+    /// Make sure `requested` more bytes of `collection` fit in the limit;
+    /// fails with a `Storage quota exceeded` error otherwise.
+    pub(crate) async fn reserve_storage(&self, collection: &str, requested: u64) -> Result<()> {
+        if self.config.max_storage_size.is_none() {
+            return Ok(());
+        }
+        self.quota_enforcer().reserve(collection, requested).await
+    }
+
+    pub(crate) fn quota_enforcer(&self) -> QuotaEnforcer {
+        QuotaEnforcer {
+            config: self.config.quota.clone(),
+            limit: self.config.max_storage_size,
+            state: Arc::clone(&self.quota),
+            metadata_store: Arc::clone(&self.metadata_store),
+            history: Arc::clone(&self.history),
+            legal_holds: Arc::clone(&self.legal_holds),
+            chunks: Arc::clone(&self.chunks),
+            collections: Arc::clone(&self.collections),
+            migrator: self.tier_migrator(),
+        }
+    }
+}
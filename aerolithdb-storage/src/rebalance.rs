@@ -0,0 +1,540 @@
+//! # Shard Rebalancing
+//!
+//! The nodes of the cluster are kept in the metadata database and make up
+//! the sharding engine's hash ring. Each document belongs on the node
+//! owning its shard plus the next distinct nodes along the ring, as many
+//! as its collection's replication factor; the nodes holding it are
+//! recorded in its metadata as `replica_locations`, with an empty list
+//! meaning only this node.
+//!
+//! When a node joins or leaves ([`MembershipChange`]), the ring is updated
+//! and, with [`RebalanceConfig::auto_rebalance`], a `rebalance` job is
+//! started. It compares where every local document is with where it now
+//! belongs, sends it to nodes that should hold it but do not, throttled to
+//! [`RebalanceConfig::max_bytes_per_second`], and records the new holders.
+//! Documents this node should no longer hold are released once every new
+//! holder has them, unless they are under legal hold. Streamed documents
+//! are not moved.
+//!
+//! Documents travel through a [`ShardTransport`]; the default one posts
+//! them to the REST API of the receiving node, which stores them with
+//! [`StorageHierarchy::accept_transferred_document`].
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::jobs::JobHandle;
+use crate::{DocumentMetadata, Job, StorageHierarchy, StorageTier};
+
+/// Documents planned between yields to other tasks
+const YIELD_EVERY: usize = 64;
+
+/// Path of the REST endpoint receiving transferred documents
+pub const TRANSFER_PATH: &str = "/api/v1/internal/rebalance/documents";
+
+/// Identity of this node and how documents are moved between nodes.
+#[derive(Debug, Clone)]
+pub struct RebalanceConfig {
+    /// Id of this node in the hash ring
+    pub node_id: String,
+
+    /// Bytes sent per second while rebalancing; `None` sends as fast as
+    /// the receiving nodes accept them
+    pub max_bytes_per_second: Option<u64>,
+
+    /// Whether a membership change starts a rebalance job
+    pub auto_rebalance: bool,
+
+    /// How long sending one document to a node may take
+    pub transfer_timeout: Duration,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            node_id: "local_node".to_string(),
+            max_bytes_per_second: Some(16 * 1024 * 1024),
+            auto_rebalance: true,
+            transfer_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A member of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterNode {
+    /// Node id, as used in the hash ring
+    pub id: String,
+
+    /// Base URL of the node's REST API, if documents can be sent to it
+    pub address: Option<String>,
+
+    /// When the node joined
+    pub joined_at: DateTime<Utc>,
+}
+
+/// A change in cluster membership.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MembershipChange {
+    /// A node joined, or changed its address
+    NodeJoined { node_id: String, address: Option<String> },
+
+    /// A node left the cluster
+    NodeLeft { node_id: String },
+}
+
+/// A document that has to move for the cluster to be balanced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardMove {
+    /// Document key, `collection:document_id`
+    pub key: String,
+
+    /// Shard of the document
+    pub shard_id: String,
+
+    /// Stored size in bytes
+    pub size: usize,
+
+    /// Nodes holding the document now
+    pub from: Vec<String>,
+
+    /// Nodes that should hold it, owner first
+    pub to: Vec<String>,
+}
+
+impl ShardMove {
+    /// Nodes that should receive a copy
+    pub fn added(&self) -> Vec<&String> {
+        self.to.iter().filter(|node| !self.from.contains(node)).collect()
+    }
+
+    /// Nodes that should drop their copy
+    pub fn removed(&self) -> Vec<&String> {
+        self.from.iter().filter(|node| !self.to.contains(node)).collect()
+    }
+}
+
+/// Documents out of place on this node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RebalancePlan {
+    /// Nodes in the hash ring
+    pub nodes: Vec<String>,
+
+    /// Documents compared
+    pub evaluated: usize,
+
+    /// Streamed documents left where they are
+    pub skipped: usize,
+
+    /// Documents to move
+    pub moves: Vec<ShardMove>,
+
+    /// Stored bytes of the documents to move
+    pub bytes: u64,
+}
+
+/// Outcome of one rebalance.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RebalanceReport {
+    /// Documents moved to all of their new holders
+    pub moved: usize,
+
+    /// Copies sent to other nodes
+    pub transferred: usize,
+
+    /// Documents this node released after moving them away
+    pub released: usize,
+
+    /// Documents that changed, or were held, while being moved
+    pub skipped: usize,
+
+    /// Documents that could not be sent to every new holder
+    pub failed: usize,
+
+    /// Bytes sent to other nodes
+    pub bytes: u64,
+}
+
+/// A document on its way to another node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferredDocument {
+    /// Document key, `collection:document_id`
+    pub key: String,
+
+    /// Metadata of the document, listing its new holders
+    pub metadata: DocumentMetadata,
+
+    /// Payload exactly as stored
+    pub payload: Vec<u8>,
+}
+
+/// Sends documents to other nodes while rebalancing.
+#[async_trait]
+pub trait ShardTransport: Send + Sync {
+    /// Deliver one document to `node`.
+    async fn send(&self, node: &ClusterNode, document: &TransferredDocument) -> Result<()>;
+}
+
+/// Posts documents to the REST API of the receiving node.
+#[derive(Debug, Clone)]
+pub struct HttpShardTransport {
+    client: reqwest::Client,
+}
+
+impl HttpShardTransport {
+    pub fn new(timeout: Duration) -> Result<Self> {
+        Ok(Self { client: reqwest::Client::builder().timeout(timeout).build()? })
+    }
+}
+
+#[async_trait]
+impl ShardTransport for HttpShardTransport {
+    async fn send(&self, node: &ClusterNode, document: &TransferredDocument) -> Result<()> {
+        let address = node
+            .address
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Cluster node {} has no address", node.id))?;
+        let response = self
+            .client
+            .post(format!("{}{}", address.trim_end_matches('/'), TRANSFER_PATH))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(document)?)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("{} refused {}: {}", node.id, document.key, response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Cluster members, persisted in the metadata database.
+pub(crate) struct ClusterMembership {
+    nodes: sled::Tree,
+
+    /// Held by the running rebalance
+    pub(crate) running: tokio::sync::Mutex<()>,
+
+    transport: std::sync::RwLock<Arc<dyn ShardTransport>>,
+}
+
+impl ClusterMembership {
+    /// Open the member list, adding this node to a new one.
+    pub(crate) fn open(db: &sled::Db, config: &RebalanceConfig) -> Result<Self> {
+        let nodes = db.open_tree("cluster_nodes")?;
+        if nodes.is_empty() {
+            let local = ClusterNode { id: config.node_id.clone(), address: None, joined_at: Utc::now() };
+            nodes.insert(local.id.as_bytes(), serde_json::to_vec(&local)?)?;
+        }
+        let transport: Arc<dyn ShardTransport> = Arc::new(HttpShardTransport::new(config.transfer_timeout)?);
+        Ok(Self { nodes, running: tokio::sync::Mutex::new(()), transport: std::sync::RwLock::new(transport) })
+    }
+
+    pub(crate) fn list(&self) -> Result<Vec<ClusterNode>> {
+        self.nodes.iter().values().map(|value| Ok(serde_json::from_slice(&value?)?)).collect()
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ClusterNode>> {
+        match self.nodes.get(id.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn transport(&self) -> Arc<dyn ShardTransport> {
+        Arc::clone(&self.transport.read().unwrap())
+    }
+}
+
+impl std::fmt::Debug for ClusterMembership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClusterMembership").field("nodes", &self.nodes.len()).finish_non_exhaustive()
+    }
+}
+
+impl StorageHierarchy {
+    /// Move documents between nodes with `transport` instead of the REST
+    /// API of the receiving nodes.
+    pub fn with_shard_transport(self, transport: Arc<dyn ShardTransport>) -> Self {
+        *self.membership.transport.write().unwrap() = transport;
+        self
+    }
+
+    /// Id of this node in the hash ring.
+    pub fn node_id(&self) -> &str {
+        &self.config.rebalance.node_id
+    }
+
+    /// Members of the cluster, sorted by id.
+    pub fn cluster_nodes(&self) -> Result<Vec<ClusterNode>> {
+        self.membership.list()
+    }
+
+    /// Update the member list and the hash ring, and start a rebalance job
+    /// if [`RebalanceConfig::auto_rebalance`] is set.
+    pub async fn apply_membership_change(self: &Arc<Self>, change: MembershipChange) -> Result<Option<Job>> {
+        match &change {
+            MembershipChange::NodeJoined { node_id, address } => {
+                if node_id.is_empty() {
+                    return Err(anyhow::anyhow!("Invalid cluster node id"));
+                }
+                let previous = self.membership.get(node_id)?;
+                let node = ClusterNode {
+                    id: node_id.clone(),
+                    address: address.clone(),
+                    joined_at: previous.as_ref().map_or_else(Utc::now, |previous| previous.joined_at),
+                };
+                self.membership.nodes.insert(node_id.as_bytes(), serde_json::to_vec(&node)?)?;
+                if previous.is_some() {
+                    info!("Cluster node {} is now at {:?}", node_id, address);
+                    return Ok(None);
+                }
+                self.sharding_engine.write().await.add_node(node_id.clone());
+                info!("Cluster node {} joined", node_id);
+            }
+            MembershipChange::NodeLeft { node_id } => {
+                if node_id == self.node_id() {
+                    return Err(anyhow::anyhow!("Cannot remove the local node {}", node_id));
+                }
+                if self.membership.nodes.remove(node_id.as_bytes())?.is_none() {
+                    return Err(anyhow::anyhow!("Cluster node not found: {}", node_id));
+                }
+                self.sharding_engine.write().await.remove_node(node_id);
+                info!("Cluster node {} left", node_id);
+            }
+        }
+
+        if !self.config.rebalance.auto_rebalance {
+            return Ok(None);
+        }
+        self.spawn_rebalance_job().map(Some)
+    }
+
+    /// Documents on this node that are not where they belong.
+    pub async fn plan_rebalance(&self) -> Result<RebalancePlan> {
+        let ring = self.sharding_engine.read().await;
+        let entries: Vec<(String, DocumentMetadata)> =
+            self.metadata_store.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect();
+
+        let mut plan = RebalancePlan { nodes: ring.nodes(), evaluated: 0, skipped: 0, moves: Vec::new(), bytes: 0 };
+        for (index, (key, metadata)) in entries.into_iter().enumerate() {
+            if index % YIELD_EVERY == YIELD_EVERY - 1 {
+                tokio::task::yield_now().await;
+            }
+            plan.evaluated += 1;
+            if self.is_chunked(&key) {
+                plan.skipped += 1;
+                continue;
+            }
+
+            let count = self.collection_replication_factor(&metadata.collection).clamp(1, plan.nodes.len().max(1));
+            let to = ring.placement(&metadata.collection, &metadata.id, count).await;
+            let from = self.holders(&metadata);
+            if to.is_empty() || (to.iter().all(|node| from.contains(node)) && from.iter().all(|node| to.contains(node))) {
+                continue;
+            }
+            plan.bytes += metadata.size as u64;
+            plan.moves.push(ShardMove { key, shard_id: metadata.shard_id.clone(), size: metadata.size, from, to });
+        }
+        Ok(plan)
+    }
+
+    /// Rebalance as a job.
+    pub fn spawn_rebalance_job(self: &Arc<Self>) -> Result<Job> {
+        let storage = Arc::clone(self);
+        self.jobs.spawn("rebalance", None, move |job| async move { storage.rebalance(Some(&job)).await })
+    }
+
+    /// Move every document on this node to where it belongs; only one
+    /// rebalance runs at a time.
+    pub async fn rebalance(&self, job: Option<&JobHandle>) -> Result<RebalanceReport> {
+        let _running = self.membership.running.lock().await;
+        let plan = self.plan_rebalance().await?;
+        if let Some(job) = job {
+            job.log(format!(
+                "Moving {} of {} documents ({} bytes) across {} nodes",
+                plan.moves.len(),
+                plan.evaluated,
+                plan.bytes,
+                plan.nodes.len()
+            ));
+            job.set_progress(0, Some(plan.moves.len() as u64));
+        }
+
+        let nodes = self.membership.list()?;
+        let transport = self.membership.transport();
+        let started = Instant::now();
+        let mut report = RebalanceReport::default();
+        for (index, shard_move) in plan.moves.iter().enumerate() {
+            if let Some(job) = job {
+                job.check_cancelled()?;
+            }
+            match self.move_document(shard_move, &nodes, transport.as_ref(), &mut report).await {
+                Ok(true) => report.moved += 1,
+                Ok(false) => report.skipped += 1,
+                Err(e) => {
+                    warn!("Failed to move {}: {}", shard_move.key, e);
+                    if let Some(job) = job {
+                        job.log(format!("Failed to move {}: {}", shard_move.key, e));
+                    }
+                    report.failed += 1;
+                }
+            }
+            if let Some(job) = job {
+                job.set_progress(index as u64 + 1, Some(plan.moves.len() as u64));
+            }
+            self.throttle(started, report.bytes).await;
+        }
+
+        info!(
+            "Rebalanced {} documents: {} copies sent, {} released, {} skipped, {} failed",
+            report.moved, report.transferred, report.released, report.skipped, report.failed
+        );
+        if let Some(job) = job {
+            job.log(format!(
+                "Moved {} documents, released {}, {} skipped, {} failed",
+                report.moved, report.released, report.skipped, report.failed
+            ));
+        }
+        Ok(report)
+    }
+
+    /// Store a document sent by another node while rebalancing, unless a
+    /// newer version is already here. Returns whether it was stored.
+    pub async fn accept_transferred_document(&self, document: TransferredDocument) -> Result<bool> {
+        let TransferredDocument { key, mut metadata, payload } = document;
+        if key != format!("{}:{}", metadata.collection, metadata.id) {
+            return Err(anyhow::anyhow!("Invalid transferred document: key {} does not match its metadata", key));
+        }
+        if !metadata.checksum_matches(&payload) {
+            return Err(anyhow::anyhow!("Invalid transferred document: checksum mismatch for {}", key));
+        }
+        if self.metadata_store.get(&key).is_some_and(|current| current.version >= metadata.version) {
+            return Ok(false);
+        }
+
+        self.reserve_storage(&metadata.collection, payload.len() as u64).await?;
+        let shard_id = metadata.shard_id.clone();
+        if self.is_volatile_collection(&metadata.collection) {
+            self.hot_layer.store(&shard_id, &key, &payload).await?;
+            metadata.storage_tier = StorageTier::Hot;
+            self.metadata_store.insert(key.clone(), metadata)?;
+            debug!("Accepted transferred volatile document {}", key);
+            return Ok(true);
+        }
+        self.warm_layer.store(&shard_id, &key, &payload).await?;
+        if self.collection_replication_factor(&metadata.collection) >= 2 {
+            self.cold_layer.store(&shard_id, &key, &payload).await?;
+        }
+        metadata.storage_tier = StorageTier::Warm;
+        self.metadata_store.insert(key.clone(), metadata)?;
+        debug!("Accepted transferred document {}", key);
+        Ok(true)
+    }
+
+    /// Whether this node holds the primary copy of a document, the first of
+    /// its holders, which answers for it in queries spanning the cluster.
+    pub fn is_primary_holder(&self, collection: &str, document_id: &str) -> bool {
+        self.metadata_store
+            .get(&format!("{}:{}", collection, document_id))
+            .is_some_and(|metadata| self.is_primary_holder_of(&metadata))
+    }
+
+    /// Whether this node holds the primary copy of the document version
+    /// described by `metadata`, which may have been replaced or deleted since.
+    pub fn is_primary_holder_of(&self, metadata: &DocumentMetadata) -> bool {
+        self.holders(metadata).first().is_some_and(|node| node == self.node_id())
+    }
+
+    /// Nodes holding a document according to its metadata
+    fn holders(&self, metadata: &DocumentMetadata) -> Vec<String> {
+        if metadata.replica_locations.is_empty() {
+            vec![self.node_id().to_string()]
+        } else {
+            metadata.replica_locations.clone()
+        }
+    }
+
+    /// Send one document to its new holders, then record them and release
+    /// the local copy if this node is no longer one. Returns `false` if the
+    /// document changed meanwhile.
+    async fn move_document(
+        &self,
+        shard_move: &ShardMove,
+        nodes: &[ClusterNode],
+        transport: &dyn ShardTransport,
+        report: &mut RebalanceReport,
+    ) -> Result<bool> {
+        let Some(mut metadata) = self.metadata_store.get(&shard_move.key) else {
+            return Ok(false);
+        };
+        if self.holders(&metadata) != shard_move.from {
+            return Ok(false);
+        }
+
+        let recipients: Vec<&String> = shard_move.added().into_iter().filter(|node| *node != self.node_id()).collect();
+        if !recipients.is_empty() {
+            let payload = self
+                .stored_payload(&shard_move.key, &metadata)
+                .await
+                .ok_or_else(|| anyhow::anyhow!("no tier holds the current payload"))?;
+            metadata.replica_locations = shard_move.to.clone();
+            let document = TransferredDocument { key: shard_move.key.clone(), metadata: metadata.clone(), payload };
+            for recipient in recipients {
+                let node = nodes
+                    .iter()
+                    .find(|node| &node.id == recipient)
+                    .ok_or_else(|| anyhow::anyhow!("{} is no longer a cluster node", recipient))?;
+                transport.send(node, &document).await?;
+                report.transferred += 1;
+                report.bytes += document.payload.len() as u64;
+            }
+        }
+
+        // Record the new holders unless the document changed meanwhile
+        {
+            let Some(mut current) = self.metadata_store.get_mut(&shard_move.key) else {
+                return Ok(false);
+            };
+            if current.version != metadata.version || current.checksum != metadata.checksum {
+                return Ok(false);
+            }
+            current.replica_locations = shard_move.to.clone();
+        }
+
+        if shard_move.to.iter().any(|node| node == self.node_id()) {
+            return Ok(true);
+        }
+        if self.legal_holds.is_held(&shard_move.key) {
+            info!("Keeping held document {} on {} after moving it", shard_move.key, self.node_id());
+            return Ok(true);
+        }
+        if self.metadata_store.get(&shard_move.key).is_some_and(|current| current.version == metadata.version) {
+            self.metadata_store.remove(&shard_move.key)?;
+            for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold, StorageTier::Archive] {
+                let _ = self.tier_delete(&tier, &shard_move.shard_id, &shard_move.key).await;
+            }
+            report.released += 1;
+        }
+        Ok(true)
+    }
+
+    /// Wait until `bytes` sent since `started` are within the rate limit
+    async fn throttle(&self, started: Instant, bytes: u64) {
+        let Some(rate) = self.config.rebalance.max_bytes_per_second.filter(|rate| *rate > 0) else {
+            return;
+        };
+        let due = Duration::from_secs_f64(bytes as f64 / rate as f64);
+        let elapsed = started.elapsed();
+        if due > elapsed {
+            tokio::time::sleep(due - elapsed).await;
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use std::sync::Arc;
+use dashmap::DashSet;
 use tracing::{debug, error, warn};
 
 use super::backends::{LocalSSDCache, DistributedStorage};
@@ -8,6 +9,8 @@ use super::backends::{LocalSSDCache, DistributedStorage};
 #[derive(Debug)]
 pub struct ReplicationManager {
     replication_factor: usize,
+    /// Peers that must not receive replicas (e.g. quarantined Byzantine nodes)
+    excluded_peers: DashSet<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -22,15 +25,38 @@ impl ReplicationManager {
         debug!("Initializing replication manager with factor: {}", replication_factor);
         Self {
             replication_factor,
+            excluded_peers: DashSet::new(),
         }
     }
 
-    /// Replicate data to multiple storage layers
+    /// Stop selecting a peer as a replication target
+    pub fn exclude_peer(&self, peer: &str) {
+        if self.excluded_peers.insert(peer.to_string()) {
+            warn!("Peer {} excluded from replication targets", peer);
+        }
+    }
+
+    /// Allow a previously excluded peer to receive replicas again
+    pub fn include_peer(&self, peer: &str) {
+        if self.excluded_peers.remove(peer).is_some() {
+            debug!("Peer {} restored as a replication target", peer);
+        }
+    }
+
+    /// Peers currently excluded from replication
+    pub fn excluded_peers(&self) -> Vec<String> {
+        self.excluded_peers.iter().map(|peer| peer.clone()).collect()
+    }
+
+    /// Replicate data to multiple storage layers. The warm layer always
+    /// receives a copy and the cold layer a second one when
+    /// `replication_factor` asks for more than one.
     pub async fn replicate_to_layers(
         &self,
         shard_id: &str,
         document_id: &str,
         data: &[u8],
+        replication_factor: usize,
         warm_layer: &Arc<LocalSSDCache>,
         cold_layer: &Arc<DistributedStorage>,
     ) -> Result<ReplicationResult> {
@@ -54,15 +80,67 @@ impl ReplicationManager {
         }
 
         // Replicate to cold layer
-        match cold_layer.store(shard_id, document_id, data).await {
+        if replication_factor < 2 {
+            debug!("Replication factor {} keeps no cold copy", replication_factor);
+        } else {
+            match cold_layer.store(shard_id, document_id, data).await {
+                Ok(_) => {
+                    successful_replicas += 1;
+                    replica_locations.push("cold".to_string());
+                    debug!("Successfully replicated to cold layer");
+                }
+                Err(e) => {
+                    failed_replicas += 1;
+                    warn!("Failed to replicate to cold layer: {}", e);
+                }
+            }
+        }
+
+        Ok(ReplicationResult {
+            successful_replicas,
+            failed_replicas,
+            replica_locations,
+        })
+    }
+
+    /// Replicate several `(shard_id, document_id, data)` entries to the warm
+    /// and cold layers, writing each layer once for the whole batch. Like
+    /// [`ReplicationManager::replicate_to_layers`], the cold layer is only
+    /// written when `replication_factor` exceeds one.
+    pub async fn replicate_batch_to_layers(
+        &self,
+        entries: &[(String, String, Vec<u8>)],
+        replication_factor: usize,
+        warm_layer: &Arc<LocalSSDCache>,
+        cold_layer: &Arc<DistributedStorage>,
+    ) -> Result<ReplicationResult> {
+        debug!("Replicating batch of {} documents to layers", entries.len());
+
+        let mut successful_replicas = 0;
+        let mut failed_replicas = 0;
+        let mut replica_locations = Vec::new();
+
+        match warm_layer.store_batch(entries).await {
             Ok(_) => {
                 successful_replicas += 1;
-                replica_locations.push("cold".to_string());
-                debug!("Successfully replicated to cold layer");
+                replica_locations.push("warm".to_string());
             }
             Err(e) => {
                 failed_replicas += 1;
-                warn!("Failed to replicate to cold layer: {}", e);
+                warn!("Failed to replicate batch to warm layer: {}", e);
+            }
+        }
+
+        if replication_factor >= 2 {
+            match cold_layer.store_batch(entries).await {
+                Ok(_) => {
+                    successful_replicas += 1;
+                    replica_locations.push("cold".to_string());
+                }
+                Err(e) => {
+                    failed_replicas += 1;
+                    warn!("Failed to replicate batch to cold layer: {}", e);
+                }
             }
         }
 
@@ -88,7 +166,11 @@ impl ReplicationManager {
         let mut failed_replicas = 0;
         let mut replica_locations = Vec::new();
 
-        for peer in peer_nodes.iter().take(self.replication_factor) {
+        let eligible_peers = peer_nodes
+            .iter()
+            .filter(|peer| !self.excluded_peers.contains(peer.as_str()));
+
+        for peer in eligible_peers.take(self.replication_factor) {
             match self.replicate_to_peer(shard_id, document_id, data, peer).await {
                 Ok(_) => {
                     successful_replicas += 1;
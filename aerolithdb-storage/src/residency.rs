@@ -0,0 +1,307 @@
+//! # Data Residency
+//!
+//! Regulations such as GDPR can require data about certain people to stay
+//! within certain regions. A collection or an individual document can be
+//! tagged with a residency, e.g. `eu`, which names the regions its copies
+//! may be placed in: the regions configured for the tag in
+//! [`ResidencyConfig::allowed_regions`], or only the region of the same name.
+//! A document tag takes precedence over the tag of its collection.
+//!
+//! Placement is enforced in the storage layer. A node whose region the
+//! residency does not allow refuses to store the document, and
+//! cross-datacenter replication skips datacenters in other regions.
+//!
+//! Tagging data that is already stored can reveal copies outside the
+//! allowed regions. Remote copies are deleted from the offending
+//! datacenters; a copy on this node is flagged for an operator, since
+//! removing it would lose data. Every violation, including refused
+//! placements, is recorded with the remediation taken in a compliance log
+//! that is never pruned.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::StorageHierarchy;
+
+/// Where this node stores data and which regions residency tags allow.
+#[derive(Debug, Clone, Default)]
+pub struct ResidencyConfig {
+    /// Region of this node; `None` leaves local placement unchecked
+    pub local_region: Option<String>,
+
+    /// Regions allowed by each residency tag. A tag without an entry allows
+    /// only the region of the same name.
+    pub allowed_regions: HashMap<String, HashSet<String>>,
+}
+
+impl ResidencyConfig {
+    /// Regions a residency tag allows copies in.
+    pub fn regions_for(&self, residency: &str) -> HashSet<String> {
+        self.allowed_regions
+            .get(residency)
+            .cloned()
+            .unwrap_or_else(|| [residency.to_string()].into_iter().collect())
+    }
+}
+
+/// How a residency violation was dealt with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResidencyRemediation {
+    /// The document was not stored in the region
+    PlacementRefused,
+
+    /// The copy was deleted from the region
+    CopyPurged,
+
+    /// The copy is still in the region and needs an operator
+    CopyFlagged,
+}
+
+/// A copy of a document that was, or would have been, placed outside the
+/// regions its residency allows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResidencyViolation {
+    /// When the violation was detected
+    pub at: DateTime<Utc>,
+
+    /// Collection of the document
+    pub collection: String,
+
+    /// Identifier of the document
+    pub document_id: String,
+
+    /// Residency tag of the document
+    pub residency: String,
+
+    /// Region of the copy
+    pub region: String,
+
+    /// Remote datacenter of the copy, `None` for this node
+    pub datacenter_id: Option<String>,
+
+    /// What was done about it
+    pub remediation: ResidencyRemediation,
+}
+
+/// Residency tags and the violation log, persisted in the metadata database.
+#[derive(Debug)]
+pub(crate) struct ResidencyRegistry {
+    config: ResidencyConfig,
+
+    /// Residency tags by collection
+    collections: DashMap<String, String>,
+
+    /// Residency tags by `collection:document_id`
+    documents: DashMap<String, String>,
+
+    db: sled::Db,
+    collections_tree: sled::Tree,
+    documents_tree: sled::Tree,
+    violations_tree: sled::Tree,
+}
+
+impl ResidencyRegistry {
+    /// Load residency tags from `db`.
+    pub(crate) fn open(db: &sled::Db, config: &ResidencyConfig) -> Result<Self> {
+        let registry = Self {
+            config: config.clone(),
+            collections: DashMap::new(),
+            documents: DashMap::new(),
+            db: db.clone(),
+            collections_tree: db.open_tree("collection_residency")?,
+            documents_tree: db.open_tree("document_residency")?,
+            violations_tree: db.open_tree("residency_violations")?,
+        };
+
+        for (tree, tags) in [
+            (&registry.collections_tree, &registry.collections),
+            (&registry.documents_tree, &registry.documents),
+        ] {
+            for item in tree.iter() {
+                let (key, value) = item?;
+                tags.insert(
+                    String::from_utf8_lossy(&key).into_owned(),
+                    String::from_utf8_lossy(&value).into_owned(),
+                );
+            }
+        }
+
+        Ok(registry)
+    }
+
+    /// Residency of a document: its own tag, else its collection's.
+    fn residency_of(&self, collection: &str, key: &str) -> Option<String> {
+        self.documents
+            .get(key)
+            .or_else(|| self.collections.get(collection))
+            .map(|residency| residency.clone())
+    }
+
+    fn record(&self, violation: &ResidencyViolation) -> Result<()> {
+        let sequence = self.db.generate_id()?;
+        self.violations_tree.insert(sequence.to_be_bytes(), serde_json::to_vec(violation)?)?;
+
+        warn!(
+            "Audit: residency {} of {}:{} violated in region {} ({:?})",
+            violation.residency, violation.collection, violation.document_id, violation.region, violation.remediation
+        );
+        Ok(())
+    }
+}
+
+impl StorageHierarchy {
+    /// Tag a collection with a residency, remediating copies of its
+    /// documents that are already outside the allowed regions.
+    ///
+    /// Returns the violations found. Documents with a residency of their own
+    /// are unaffected.
+    pub async fn set_collection_residency(&self, collection: &str, residency: &str) -> Result<Vec<ResidencyViolation>> {
+        let prefix = format!("{}:", collection);
+        let previous = self.residency.collections.get(collection).map(|previous| previous.clone());
+        let document_ids: Vec<String> = self
+            .metadata_store
+            .iter()
+            .filter(|entry| entry.key().starts_with(&prefix) && !self.residency.documents.contains_key(entry.key()))
+            .map(|entry| entry.value().id.clone())
+            .collect();
+
+        self.residency.collections_tree.insert(collection.as_bytes(), residency.as_bytes())?;
+        self.residency.collections.insert(collection.to_string(), residency.to_string());
+        info!("Collection {} has residency {}", collection, residency);
+
+        let mut violations = Vec::new();
+        for document_id in document_ids {
+            violations.extend(self.remediate_residency(collection, &document_id, residency, previous.as_deref()).await?);
+        }
+        Ok(violations)
+    }
+
+    /// Tag a document with a residency, taking precedence over its
+    /// collection's, and remediate copies already outside the allowed
+    /// regions.
+    pub async fn set_document_residency(
+        &self,
+        collection: &str,
+        document_id: &str,
+        residency: &str,
+    ) -> Result<Vec<ResidencyViolation>> {
+        let key = format!("{}:{}", collection, document_id);
+        let previous = self.residency.residency_of(collection, &key);
+
+        self.residency.documents_tree.insert(key.as_bytes(), residency.as_bytes())?;
+        self.residency.documents.insert(key.clone(), residency.to_string());
+        info!("Document {} has residency {}", key, residency);
+
+        if self.metadata_store.get(&key).is_none() {
+            return Ok(Vec::new());
+        }
+        self.remediate_residency(collection, document_id, residency, previous.as_deref()).await
+    }
+
+    /// Residency of a document, from its own tag or its collection's.
+    pub fn residency_of(&self, collection: &str, document_id: &str) -> Option<String> {
+        self.residency.residency_of(collection, &format!("{}:{}", collection, document_id))
+    }
+
+    /// Every residency violation detected, oldest first.
+    pub fn residency_report(&self) -> Result<Vec<ResidencyViolation>> {
+        self.residency
+            .violations_tree
+            .iter()
+            .map(|item| Ok(serde_json::from_slice(&item?.1)?))
+            .collect()
+    }
+
+    /// Regions a document may be placed in, `None` if it is unrestricted.
+    pub(crate) fn allowed_regions(&self, collection: &str, document_id: &str) -> Option<HashSet<String>> {
+        self.residency_of(collection, document_id)
+            .map(|residency| self.residency.config.regions_for(&residency))
+    }
+
+    /// Regions a document may be replicated to, refusing and recording the
+    /// placement if its residency does not allow this node's region.
+    /// `None` means the document is unrestricted.
+    pub(crate) fn check_placement(&self, collection: &str, document_id: &str) -> Result<Option<HashSet<String>>> {
+        let key = format!("{}:{}", collection, document_id);
+        let Some(residency) = self.residency.residency_of(collection, &key) else {
+            return Ok(None);
+        };
+        let allowed = self.residency.config.regions_for(&residency);
+
+        if let Some(local_region) = &self.residency.config.local_region {
+            if !allowed.contains(local_region) {
+                self.residency.record(&ResidencyViolation {
+                    at: Utc::now(),
+                    collection: collection.to_string(),
+                    document_id: document_id.to_string(),
+                    residency: residency.clone(),
+                    region: local_region.clone(),
+                    datacenter_id: None,
+                    remediation: ResidencyRemediation::PlacementRefused,
+                })?;
+                return Err(anyhow::anyhow!(
+                    "Residency {} of {} does not allow placement in region {}",
+                    residency,
+                    key,
+                    local_region
+                ));
+            }
+        }
+        Ok(Some(allowed))
+    }
+
+    /// Find and remediate copies of a stored document outside the regions
+    /// of its new residency, given the residency it had before.
+    async fn remediate_residency(
+        &self,
+        collection: &str,
+        document_id: &str,
+        residency: &str,
+        previous: Option<&str>,
+    ) -> Result<Vec<ResidencyViolation>> {
+        let allowed = self.residency.config.regions_for(residency);
+        let previously_allowed = previous.map(|previous| self.residency.config.regions_for(previous));
+        let was_allowed = |region: &str| previously_allowed.as_ref().is_none_or(|previous| previous.contains(region));
+
+        let violation = |region: &str, datacenter_id: Option<String>, remediation| ResidencyViolation {
+            at: Utc::now(),
+            collection: collection.to_string(),
+            document_id: document_id.to_string(),
+            residency: residency.to_string(),
+            region: region.to_string(),
+            datacenter_id,
+            remediation,
+        };
+        let mut violations = Vec::new();
+
+        if let Some(local_region) = &self.residency.config.local_region {
+            if !allowed.contains(local_region) && was_allowed(local_region) {
+                violations.push(violation(local_region, None, ResidencyRemediation::CopyFlagged));
+            }
+        }
+
+        if let Some(dc_replication) = &self.datacenter_replication_manager {
+            let purged = dc_replication
+                .purge_outside_regions(collection, document_id, &allowed, previously_allowed.as_ref())
+                .await?;
+            for datacenter in purged {
+                violations.push(violation(
+                    &datacenter.region,
+                    Some(datacenter.datacenter_id),
+                    ResidencyRemediation::CopyPurged,
+                ));
+            }
+        }
+
+        for violation in &violations {
+            self.residency.record(violation)?;
+        }
+        Ok(violations)
+    }
+}
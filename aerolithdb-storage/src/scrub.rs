@@ -0,0 +1,437 @@
+//! # Data Integrity Scrubbing
+//!
+//! Every stored copy of a document is checked against the checksum kept in
+//! its metadata, with the algorithm recorded next to it: the copies in the
+//! hot, warm and cold tiers, and the archived copy of archived documents. Chunks of streamed documents
+//! are checked against the checksums in their manifest, in the warm and
+//! cold tiers holding them.
+//!
+//! A copy that no longer matches is corrupt. Unless repair is turned off,
+//! it is overwritten with an intact copy from another tier of this node,
+//! provided the document was not rewritten or moved meanwhile. Documents
+//! without any intact copy cannot be repaired and are reported as
+//! unrecoverable. Documents written within [`ScrubConfig::min_age`] are
+//! skipped, since their copies may still be replicating between tiers.
+//!
+//! Documents stored whole whose copies are all intact but whose checksum
+//! was computed with an algorithm other than the configured one are
+//! re-checksummed with it, unless
+//! [`ChecksumConfig::migrate`](crate::ChecksumConfig) is off.
+//!
+//! Scrubs run every [`ScrubConfig::interval`] and on demand through
+//! [`StorageHierarchy::scrub`]; only one runs at a time. The report of the
+//! last scrub is kept in the metadata database.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::streaming::ChunkManifests;
+use crate::{
+    ChecksumAlgorithm, DistributedStorage, DocumentMetadata, LocalSSDCache, MemoryCache, MetadataStore, ObjectStorage,
+    StorageHierarchy, StorageTier,
+};
+
+/// Key of the last report in its tree
+const LAST_REPORT_KEY: &[u8] = b"last_report";
+
+/// Documents checked between yields to other tasks
+const YIELD_EVERY: usize = 64;
+
+/// How often documents are scrubbed and whether corruption is repaired.
+#[derive(Debug, Clone)]
+pub struct ScrubConfig {
+    /// Time between background scrubs; `None` only scrubs on demand
+    pub interval: Option<Duration>,
+
+    /// Whether background scrubs overwrite corrupt copies with intact ones
+    pub repair: bool,
+
+    /// Documents written more recently are not checked
+    pub min_age: Duration,
+}
+
+impl Default for ScrubConfig {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(24 * 3600)),
+            repair: true,
+            min_age: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A stored copy that did not match its checksum.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorruptCopy {
+    /// Collection of the document
+    pub collection: String,
+
+    /// Identifier of the document
+    pub document_id: String,
+
+    /// Tier holding the copy
+    pub tier: StorageTier,
+
+    /// Index of the chunk, for streamed documents
+    pub chunk: Option<usize>,
+
+    /// Checksum recorded when the copy was written
+    pub expected: String,
+
+    /// Checksum of the stored bytes
+    pub actual: String,
+
+    /// Tier of the intact copy the corrupt one was replaced with
+    pub repaired_from: Option<StorageTier>,
+}
+
+/// Outcome of one scrub.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// When the scrub started
+    pub started_at: DateTime<Utc>,
+
+    /// When the scrub finished
+    pub finished_at: DateTime<Utc>,
+
+    /// Whether corrupt copies were repaired
+    pub repair: bool,
+
+    /// Documents checked
+    pub documents: usize,
+
+    /// Documents skipped because they were written too recently
+    pub skipped: usize,
+
+    /// Copies and chunks checked
+    pub copies_verified: usize,
+
+    /// Copies and chunks that did not match their checksum
+    pub corrupt: Vec<CorruptCopy>,
+
+    /// Corrupt copies replaced with intact ones
+    pub repaired: usize,
+
+    /// Documents, as `collection:document_id`, without any intact copy of
+    /// their payload or of one of their chunks
+    pub unrecoverable: Vec<String>,
+
+    /// Documents re-checksummed with the configured algorithm
+    #[serde(default)]
+    pub checksums_migrated: usize,
+}
+
+/// The last scrub report, persisted in the metadata database.
+#[derive(Debug)]
+pub(crate) struct ScrubLog {
+    /// Serializes scrubs
+    running: tokio::sync::Mutex<()>,
+
+    tree: sled::Tree,
+}
+
+impl ScrubLog {
+    pub(crate) fn open(db: &sled::Db) -> Result<Self> {
+        Ok(Self { running: tokio::sync::Mutex::new(()), tree: db.open_tree("scrub")? })
+    }
+
+    fn record(&self, report: &ScrubReport) -> Result<()> {
+        self.tree.insert(LAST_REPORT_KEY, serde_json::to_vec(report)?)?;
+        Ok(())
+    }
+
+    fn last(&self) -> Result<Option<ScrubReport>> {
+        match self.tree.get(LAST_REPORT_KEY)? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Storage components a scrub works on, detached from the hierarchy so
+/// scrubs can be spawned.
+#[derive(Clone)]
+pub(crate) struct Scrubber {
+    log: Arc<ScrubLog>,
+    metadata_store: Arc<MetadataStore>,
+    chunks: Arc<ChunkManifests>,
+    hot_layer: Arc<MemoryCache>,
+    warm_layer: Arc<LocalSSDCache>,
+    cold_layer: Arc<DistributedStorage>,
+    archive_layer: Arc<ObjectStorage>,
+    min_age: Duration,
+
+    /// Algorithm intact documents are re-checksummed with, if migrating
+    migrate_to: Option<ChecksumAlgorithm>,
+}
+
+impl Scrubber {
+    /// Check every stored copy of every document, replacing corrupt copies
+    /// with intact ones when `repair` is set.
+    pub(crate) async fn run(&self, repair: bool) -> Result<ScrubReport> {
+        let _running = self.log.running.lock().await;
+        let started_at = Utc::now();
+        let min_age = chrono::Duration::from_std(self.min_age).unwrap_or_else(|_| chrono::Duration::zero());
+
+        let mut report = ScrubReport {
+            started_at,
+            finished_at: started_at,
+            repair,
+            documents: 0,
+            skipped: 0,
+            copies_verified: 0,
+            corrupt: Vec::new(),
+            repaired: 0,
+            unrecoverable: Vec::new(),
+            checksums_migrated: 0,
+        };
+        let documents: Vec<(String, DocumentMetadata)> = self
+            .metadata_store
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        for (index, (key, metadata)) in documents.into_iter().enumerate() {
+            if index % YIELD_EVERY == YIELD_EVERY - 1 {
+                tokio::task::yield_now().await;
+            }
+            if started_at - metadata.updated_at < min_age {
+                report.skipped += 1;
+                continue;
+            }
+
+            report.documents += 1;
+            let intact = match self.chunks.get(&key)? {
+                Some(manifest) => self.scrub_chunks(&key, &metadata, &manifest, repair, &mut report).await,
+                None => self.scrub_document(&key, &metadata, repair, &mut report).await,
+            };
+            if !intact {
+                error!("No intact copy of {} is left to repair it from", key);
+                report.unrecoverable.push(key);
+            }
+        }
+
+        report.finished_at = Utc::now();
+        self.log.record(&report)?;
+        if report.checksums_migrated > 0 {
+            info!("Re-checksummed {} documents with {}", report.checksums_migrated, self.migrate_to.unwrap_or_default());
+        }
+        if report.corrupt.is_empty() {
+            info!("Scrubbed {} documents, no corruption found", report.documents);
+        } else {
+            warn!(
+                "Scrubbed {} documents: {} corrupt copies, {} repaired, {} unrecoverable documents",
+                report.documents,
+                report.corrupt.len(),
+                report.repaired,
+                report.unrecoverable.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Check the copies of a document stored whole; returns whether an
+    /// intact copy exists.
+    async fn scrub_document(
+        &self,
+        key: &str,
+        metadata: &DocumentMetadata,
+        repair: bool,
+        report: &mut ScrubReport,
+    ) -> bool {
+        let shard_id = &metadata.shard_id;
+        let mut tiers = vec![StorageTier::Hot, StorageTier::Warm, StorageTier::Cold];
+        // Only archived documents keep a copy in the archive
+        if metadata.storage_tier == StorageTier::Archive {
+            tiers.push(StorageTier::Archive);
+        }
+
+        let mut intact = None;
+        let mut corrupt = Vec::new();
+        for tier in tiers {
+            let Ok(data) = self.get(&tier, shard_id, key).await else { continue };
+            report.copies_verified += 1;
+            if metadata.checksum_matches(&data) {
+                intact.get_or_insert((tier, data));
+            } else {
+                corrupt.push((tier, metadata.checksum_algorithm.digest(&data)));
+            }
+        }
+
+        if let (true, Some((_, data))) = (corrupt.is_empty(), &intact) {
+            if self.migrate_checksum(key, metadata, data) {
+                report.checksums_migrated += 1;
+            }
+        }
+        for (tier, actual) in corrupt {
+            // A rewrite or migration since the metadata was read is not
+            // corruption
+            if !self.unchanged(key, metadata) {
+                return true;
+            }
+            warn!("Copy of {} in the {:?} tier does not match its checksum", key, tier);
+
+            let mut repaired_from = None;
+            if let (true, Some((source, data))) = (repair, &intact) {
+                match self.store(&tier, shard_id, key, data).await {
+                    Ok(()) => {
+                        info!("Repaired copy of {} in the {:?} tier from the {:?} tier", key, tier, source);
+                        repaired_from = Some(source.clone());
+                        report.repaired += 1;
+                    }
+                    Err(e) => error!("Failed to repair copy of {} in the {:?} tier: {}", key, tier, e),
+                }
+            }
+            report.corrupt.push(CorruptCopy {
+                collection: metadata.collection.clone(),
+                document_id: metadata.id.clone(),
+                tier,
+                chunk: None,
+                expected: metadata.checksum.clone(),
+                actual,
+                repaired_from,
+            });
+        }
+
+        intact.is_some() || !self.unchanged(key, metadata)
+    }
+
+    /// Check the chunks of a streamed document in the warm and cold tiers;
+    /// returns whether every chunk has an intact copy.
+    async fn scrub_chunks(
+        &self,
+        key: &str,
+        metadata: &DocumentMetadata,
+        manifest: &crate::ChunkManifest,
+        repair: bool,
+        report: &mut ScrubReport,
+    ) -> bool {
+        let shard_id = &metadata.shard_id;
+        let mut recoverable = true;
+
+        for (index, chunk) in manifest.chunks.iter().enumerate() {
+            let chunk_key = manifest.chunk_key(key, index);
+            let mut intact = None;
+            let mut corrupt = Vec::new();
+            for tier in [StorageTier::Warm, StorageTier::Cold] {
+                let Ok(data) = self.get(&tier, shard_id, &chunk_key).await else { continue };
+                report.copies_verified += 1;
+                if manifest.chunk_intact(chunk, &data) {
+                    intact.get_or_insert((tier, data));
+                } else {
+                    corrupt.push((tier, manifest.checksum_algorithm.digest(&data)));
+                }
+            }
+
+            // A rewrite drops the chunks of the previous generation
+            if !corrupt.is_empty() && !self.unchanged(key, metadata) {
+                return true;
+            }
+            for (tier, actual) in corrupt {
+                warn!("Chunk {} of {} in the {:?} tier does not match its checksum", index, key, tier);
+
+                let mut repaired_from = None;
+                if let (true, Some((source, data))) = (repair, &intact) {
+                    match self.store(&tier, shard_id, &chunk_key, data).await {
+                        Ok(()) => {
+                            info!("Repaired chunk {} of {} in the {:?} tier from the {:?} tier", index, key, tier, source);
+                            repaired_from = Some(source.clone());
+                            report.repaired += 1;
+                        }
+                        Err(e) => error!("Failed to repair chunk {} of {} in the {:?} tier: {}", index, key, tier, e),
+                    }
+                }
+                report.corrupt.push(CorruptCopy {
+                    collection: metadata.collection.clone(),
+                    document_id: metadata.id.clone(),
+                    tier,
+                    chunk: Some(index),
+                    expected: chunk.checksum.clone(),
+                    actual,
+                    repaired_from,
+                });
+            }
+            if intact.is_none() {
+                recoverable = false;
+            }
+        }
+
+        recoverable || !self.unchanged(key, metadata)
+    }
+
+    /// Re-checksum an intact document with the configured algorithm, unless
+    /// it already uses it or was rewritten since `metadata` was read.
+    /// Returns whether its checksum was migrated.
+    fn migrate_checksum(&self, key: &str, metadata: &DocumentMetadata, payload: &[u8]) -> bool {
+        let Some(algorithm) = self.migrate_to.filter(|algorithm| *algorithm != metadata.checksum_algorithm) else {
+            return false;
+        };
+        let Some(mut current) = self.metadata_store.get_mut(key) else {
+            return false;
+        };
+        if current.checksum != metadata.checksum || current.checksum_algorithm != metadata.checksum_algorithm {
+            return false;
+        }
+        current.checksum = algorithm.digest(payload);
+        current.checksum_algorithm = algorithm;
+        true
+    }
+
+    /// Whether the document still has the payload and tier of `metadata`.
+    fn unchanged(&self, key: &str, metadata: &DocumentMetadata) -> bool {
+        self.metadata_store.get(key).is_some_and(|current| {
+            current.checksum == metadata.checksum && current.storage_tier == metadata.storage_tier
+        })
+    }
+
+    async fn get(&self, tier: &StorageTier, shard_id: &str, key: &str) -> Result<Vec<u8>> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.get(shard_id, key).await,
+            StorageTier::Warm => self.warm_layer.get(shard_id, key).await,
+            StorageTier::Cold => self.cold_layer.get(shard_id, key).await,
+            StorageTier::Archive => self.archive_layer.get(shard_id, key).await,
+        }
+    }
+
+    async fn store(&self, tier: &StorageTier, shard_id: &str, key: &str, data: &[u8]) -> Result<()> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.store(shard_id, key, data).await,
+            StorageTier::Warm => self.warm_layer.store(shard_id, key, data).await,
+            StorageTier::Cold => self.cold_layer.store(shard_id, key, data).await,
+            StorageTier::Archive => self.archive_layer.store(shard_id, key, data).await,
+        }
+    }
+}
+
+impl StorageHierarchy {
+    /// Verify every stored copy of every document against its checksum now,
+    /// replacing corrupt copies with intact ones from other tiers when
+    /// `repair` is set. Waits for a scrub in progress to finish first.
+    pub async fn scrub(&self, repair: bool) -> Result<ScrubReport> {
+        self.scrubber().run(repair).await
+    }
+
+    /// Report of the last scrub, if any has run.
+    pub fn last_scrub_report(&self) -> Result<Option<ScrubReport>> {
+        self.scrub_log.last()
+    }
+
+    pub(crate) fn scrubber(&self) -> Scrubber {
+        Scrubber {
+            log: Arc::clone(&self.scrub_log),
+            metadata_store: Arc::clone(&self.metadata_store),
+            chunks: Arc::clone(&self.chunks),
+            hot_layer: Arc::clone(&self.hot_layer),
+            warm_layer: Arc::clone(&self.warm_layer),
+            cold_layer: Arc::clone(&self.cold_layer),
+            archive_layer: Arc::clone(&self.archive_layer),
+            min_age: self.config.scrub.min_age,
+            migrate_to: self.config.checksum.migrate.then_some(self.config.checksum.algorithm),
+        }
+    }
+}
@@ -187,6 +187,19 @@ impl ShardingEngine {
         engine
     }
 
+    /// Create an engine whose ring holds `nodes` instead of the default
+    /// local node
+    pub fn with_nodes(strategy: &ShardingStrategy, replication_factor: usize, nodes: &[String]) -> Self {
+        let mut engine = Self::new(strategy, replication_factor);
+        if !nodes.is_empty() {
+            engine.remove_node("local_node");
+            for node in nodes {
+                engine.add_node(node.clone());
+            }
+        }
+        engine
+    }
+
     /// Add a physical node to the hash ring
     pub fn add_node(&mut self, node_id: String) {
         debug!("Adding node to hash ring: {}", node_id);
@@ -232,6 +245,37 @@ impl ShardingEngine {
         }
     }
 
+    /// Physical nodes in the ring, sorted by id
+    pub fn nodes(&self) -> Vec<String> {
+        let nodes: std::collections::BTreeSet<&String> = self.virtual_nodes.iter().map(|vnode| &vnode.physical_node).collect();
+        nodes.into_iter().cloned().collect()
+    }
+
+    /// Nodes that should hold a document: the node owning its shard first,
+    /// then the next distinct nodes along the ring, `count` nodes at most
+    pub async fn placement(&self, collection: &str, document_id: &str, count: usize) -> Vec<String> {
+        let mut placement = Vec::with_capacity(count);
+        if self.hash_ring.is_empty() || count == 0 {
+            return placement;
+        }
+        placement.push(self.get_shard(collection, document_id).await);
+
+        let hash = self.hash_key(&format!("{}:{}", collection, document_id));
+        let start = self.hash_ring.binary_search(&hash).unwrap_or_else(|x| x);
+        for i in 0..self.hash_ring.len() {
+            if placement.len() >= count {
+                break;
+            }
+            let position = (start + i) % self.hash_ring.len();
+            if let Some(node) = self.node_map.get(&self.hash_ring[position]) {
+                if !placement.contains(node) {
+                    placement.push(node.clone());
+                }
+            }
+        }
+        placement
+    }
+
     /// Get replica nodes for a shard
     pub fn get_replica_nodes(&self, primary_shard: &str) -> Vec<String> {
         let mut replicas = Vec::new();
@@ -0,0 +1,418 @@
+//! # Collection Statistics
+//!
+//! The query planner estimates how many documents a filter keeps from
+//! statistics of each field of a collection: how many documents hold a
+//! value, an estimate of the number of distinct values, and for numeric
+//! fields their range and an equi-depth histogram. Nested fields are
+//! described under their dotted path, as filters name them.
+//!
+//! Statistics are collected after each hourly compaction and on demand
+//! through [`StorageHierarchy::analyze_collections`], by decoding every
+//! document once, and are kept with the collection catalog in the metadata
+//! database. Distinct values are counted with a fixed-size sketch of the
+//! smallest value hashes, and histograms are built from a sample of the
+//! values chosen by document hash, so memory stays bounded however large a
+//! collection grows. Streamed documents are not decoded and not counted.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use aerolithdb_security::DataEncryption;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::collections::CollectionRegistry;
+use crate::dictionaries::DictionaryStore;
+use crate::streaming::ChunkManifests;
+use crate::{
+    CompressionConfig, CompressionEngine, DistributedStorage, DocumentMetadata, LocalSSDCache, MemoryCache,
+    MetadataStore, ObjectStorage, StorageHierarchy, StorageTier,
+};
+
+/// Value hashes kept to estimate the number of distinct values of a field
+const DISTINCT_SKETCH_SIZE: usize = 1024;
+
+/// Fields tracked per collection; fields first seen after these are ignored
+const MAX_FIELDS: usize = 256;
+
+/// Documents decoded between yields to other tasks
+const YIELD_EVERY: usize = 64;
+
+/// How much of each collection is sampled for its histograms.
+#[derive(Debug, Clone)]
+pub struct StatisticsConfig {
+    /// Numeric values sampled per field to build its histogram
+    pub sample_size: usize,
+
+    /// Buckets of each histogram
+    pub buckets: usize,
+}
+
+impl Default for StatisticsConfig {
+    fn default() -> Self {
+        Self { sample_size: 4096, buckets: 32 }
+    }
+}
+
+/// Equi-depth histogram of the numeric values of a field: bucket `i`
+/// covers `bounds[i]..=bounds[i + 1]` and holds about `counts[i]` values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Bucket boundaries, one more than the buckets
+    pub bounds: Vec<f64>,
+
+    /// Estimated values per bucket
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    /// Build a histogram of a sample of `total` values.
+    fn build(mut sample: Vec<f64>, buckets: usize, total: u64) -> Option<Self> {
+        if sample.is_empty() || buckets == 0 {
+            return None;
+        }
+        sample.sort_by(f64::total_cmp);
+
+        let n = sample.len();
+        let buckets = buckets.min(n);
+        let scale = total as f64 / n as f64;
+        let mut bounds = Vec::with_capacity(buckets + 1);
+        let mut counts = Vec::with_capacity(buckets);
+        bounds.push(sample[0]);
+        for bucket in 0..buckets {
+            let start = bucket * n / buckets;
+            let end = (bucket + 1) * n / buckets;
+            bounds.push(sample[end - 1]);
+            counts.push(((end - start) as f64 * scale).round() as u64);
+        }
+        Some(Self { bounds, counts })
+    }
+
+    /// Estimated fraction of the values below `value`, interpolating
+    /// linearly within its bucket.
+    pub fn fraction_below(&self, value: f64) -> f64 {
+        let total: u64 = self.counts.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let mut below = 0.0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            let (low, high) = (self.bounds[bucket], self.bounds[bucket + 1]);
+            if value > high {
+                below += *count as f64;
+            } else {
+                if value > low {
+                    below += *count as f64 * (value - low) / (high - low);
+                }
+                break;
+            }
+        }
+        below / total as f64
+    }
+}
+
+/// Statistics of one field of a collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldStatistics {
+    /// Documents holding a non-null value
+    pub non_null: u64,
+
+    /// Estimated number of distinct non-null values
+    pub distinct: u64,
+
+    /// Documents holding a number
+    pub numeric: u64,
+
+    /// Smallest number held
+    pub min: Option<f64>,
+
+    /// Largest number held
+    pub max: Option<f64>,
+
+    /// Distribution of the numbers held
+    pub histogram: Option<Histogram>,
+}
+
+/// Field statistics of one collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionStatistics {
+    /// Collection name
+    pub collection: String,
+
+    /// Documents analyzed
+    pub documents: u64,
+
+    /// Statistics by dotted field path
+    pub fields: BTreeMap<String, FieldStatistics>,
+
+    /// When the statistics were collected
+    pub collected_at: DateTime<Utc>,
+}
+
+impl CollectionStatistics {
+    /// Statistics of a field, by dotted path.
+    pub fn field(&self, path: &str) -> Option<&FieldStatistics> {
+        self.fields.get(path)
+    }
+}
+
+/// Accumulates the statistics of one field.
+#[derive(Debug, Default)]
+struct FieldBuilder {
+    non_null: u64,
+    numeric: u64,
+    min: Option<f64>,
+    max: Option<f64>,
+
+    /// Smallest hashes of the values seen
+    hashes: BTreeSet<u64>,
+
+    /// Numbers of the documents with the smallest key hashes
+    sample: BTreeMap<u64, f64>,
+}
+
+impl FieldBuilder {
+    fn add(&mut self, document_hash: u64, value: &Value, config: &StatisticsConfig) {
+        if value.is_null() {
+            return;
+        }
+        self.non_null += 1;
+
+        self.hashes.insert(hash64(value.to_string().as_bytes()));
+        if self.hashes.len() > DISTINCT_SKETCH_SIZE {
+            self.hashes.pop_last();
+        }
+
+        if let Some(number) = value.as_f64() {
+            self.numeric += 1;
+            self.min = Some(self.min.map_or(number, |min| min.min(number)));
+            self.max = Some(self.max.map_or(number, |max| max.max(number)));
+            self.sample.insert(document_hash, number);
+            if self.sample.len() > config.sample_size {
+                self.sample.pop_last();
+            }
+        }
+    }
+
+    fn finish(self, config: &StatisticsConfig) -> FieldStatistics {
+        // With a full sketch, the largest kept hash tells how densely the
+        // distinct values fill the hash space
+        let distinct = match self.hashes.last() {
+            Some(largest) if self.hashes.len() == DISTINCT_SKETCH_SIZE => {
+                let estimate = (DISTINCT_SKETCH_SIZE - 1) as f64 * (u64::MAX as f64 / *largest as f64);
+                (estimate.round() as u64).min(self.non_null)
+            }
+            _ => self.hashes.len() as u64,
+        };
+
+        FieldStatistics {
+            non_null: self.non_null,
+            distinct,
+            numeric: self.numeric,
+            min: self.min,
+            max: self.max,
+            histogram: Histogram::build(self.sample.into_values().collect(), config.buckets, self.numeric),
+        }
+    }
+}
+
+/// Accumulates the statistics of one collection.
+#[derive(Debug, Default)]
+struct CollectionBuilder {
+    documents: u64,
+    fields: BTreeMap<String, FieldBuilder>,
+}
+
+impl CollectionBuilder {
+    fn add(&mut self, key: &str, document: &Value, config: &StatisticsConfig) {
+        self.documents += 1;
+        let document_hash = hash64(key.as_bytes());
+        let mut leaves = Vec::new();
+        flatten(None, document, &mut leaves);
+
+        for (path, value) in leaves {
+            if !self.fields.contains_key(&path) && self.fields.len() >= MAX_FIELDS {
+                continue;
+            }
+            self.fields.entry(path).or_default().add(document_hash, value, config);
+        }
+    }
+
+    fn finish(self, collection: &str, config: &StatisticsConfig) -> CollectionStatistics {
+        CollectionStatistics {
+            collection: collection.to_string(),
+            documents: self.documents,
+            fields: self.fields.into_iter().map(|(path, field)| (path, field.finish(config))).collect(),
+            collected_at: Utc::now(),
+        }
+    }
+}
+
+/// Collect the fields of a document with their dotted paths; objects are
+/// descended into, arrays and scalars are values.
+fn flatten<'a>(prefix: Option<&str>, value: &'a Value, leaves: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                let path = match prefix {
+                    Some(prefix) => format!("{}.{}", prefix, name),
+                    None => name.clone(),
+                };
+                flatten(Some(&path), value, leaves);
+            }
+        }
+        _ => {
+            if let Some(prefix) = prefix {
+                leaves.push((prefix.to_string(), value));
+            }
+        }
+    }
+}
+
+/// First eight bytes of the BLAKE3 hash of `bytes`
+fn hash64(bytes: &[u8]) -> u64 {
+    let hash = blake3::hash(bytes);
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&hash.as_bytes()[..8]);
+    u64::from_le_bytes(prefix)
+}
+
+/// Collects statistics of every collection, independently of the storage
+/// hierarchy so it can run from a background task.
+pub(crate) struct StatisticsCollector {
+    config: StatisticsConfig,
+    metadata_store: Arc<MetadataStore>,
+    collections: Arc<CollectionRegistry>,
+    chunks: Arc<ChunkManifests>,
+    hot_layer: Arc<MemoryCache>,
+    warm_layer: Arc<LocalSSDCache>,
+    cold_layer: Arc<DistributedStorage>,
+    archive_layer: Arc<ObjectStorage>,
+    compression: CompressionConfig,
+    dictionaries: Arc<DictionaryStore>,
+    encryption: Option<Arc<DataEncryption>>,
+}
+
+impl StatisticsCollector {
+    /// Decode every document and replace the statistics of each collection.
+    pub(crate) async fn run(&self) -> Result<Vec<CollectionStatistics>> {
+        let mut by_collection: BTreeMap<String, Vec<(String, DocumentMetadata)>> = BTreeMap::new();
+        for entry in self.metadata_store.iter() {
+            by_collection
+                .entry(entry.collection.clone())
+                .or_default()
+                .push((entry.key().clone(), entry.value().clone()));
+        }
+
+        let mut collected = Vec::with_capacity(by_collection.len());
+        for (collection, documents) in by_collection {
+            let compression = self.compression_engine(&collection);
+            let mut builder = CollectionBuilder::default();
+            for (index, (key, metadata)) in documents.iter().enumerate() {
+                if index % YIELD_EVERY == YIELD_EVERY - 1 {
+                    tokio::task::yield_now().await;
+                }
+                if self.chunks.contains(key) {
+                    continue;
+                }
+                match self.decode(key, metadata, &compression).await {
+                    Ok(document) => builder.add(key, &document, &self.config),
+                    Err(e) => debug!("Leaving {} out of statistics: {}", key, e),
+                }
+            }
+
+            let statistics = builder.finish(&collection, &self.config);
+            self.collections
+                .statistics
+                .insert(collection.as_bytes(), serde_json::to_vec(&statistics)?)?;
+            collected.push(statistics);
+        }
+
+        // Collections emptied since the last run keep no statistics
+        for item in self.collections.statistics.iter() {
+            let (name, _) = item?;
+            if !collected.iter().any(|statistics| statistics.collection.as_bytes() == name.as_ref()) {
+                self.collections.statistics.remove(name)?;
+            }
+        }
+
+        info!("Collected statistics of {} collections", collected.len());
+        Ok(collected)
+    }
+
+    fn compression_engine(&self, collection: &str) -> CompressionEngine {
+        let algorithm = self
+            .collections
+            .config(collection)
+            .and_then(|config| config.compression)
+            .unwrap_or_else(|| self.compression.algorithm.clone());
+        CompressionEngine::new(&CompressionConfig { algorithm, ..self.compression.clone() })
+    }
+
+    /// Read a document from the fastest tier holding it and decode it.
+    pub(crate) async fn decode(&self, key: &str, metadata: &DocumentMetadata, compression: &CompressionEngine) -> Result<Value> {
+        let shard_id = &metadata.shard_id;
+        let data = match self.hot_layer.get(shard_id, key).await {
+            Ok(data) => data,
+            Err(_) => match metadata.storage_tier {
+                StorageTier::Hot | StorageTier::Warm => self.warm_layer.get(shard_id, key).await?,
+                StorageTier::Cold => self.cold_layer.get(shard_id, key).await?,
+                StorageTier::Archive => self.archive_layer.get(shard_id, key).await?,
+            },
+        };
+
+        let compressed = if DataEncryption::is_encrypted(&data) {
+            let encryption = self
+                .encryption
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("encrypted but encryption at rest is not configured"))?;
+            encryption.decrypt(&data, key.as_bytes())?
+        } else {
+            data
+        };
+        let collection = &metadata.collection;
+        Ok(serde_json::from_slice(&self.dictionaries.decompress(collection, &compressed, compression).await?)?)
+    }
+}
+
+impl StorageHierarchy {
+    /// Collect the field statistics of every collection now, replacing the
+    /// stored ones.
+    pub async fn analyze_collections(&self) -> Result<Vec<CollectionStatistics>> {
+        self.statistics_collector().run().await
+    }
+
+    /// Field statistics of a collection, if they were collected.
+    pub fn collection_statistics(&self, collection: &str) -> Result<Option<CollectionStatistics>> {
+        match self.collections.statistics.get(collection.as_bytes())? {
+            Some(value) => match serde_json::from_slice(&value) {
+                Ok(statistics) => Ok(Some(statistics)),
+                Err(e) => {
+                    warn!("Ignoring unreadable statistics of {}: {}", collection, e);
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn statistics_collector(&self) -> StatisticsCollector {
+        StatisticsCollector {
+            config: self.config.statistics.clone(),
+            metadata_store: Arc::clone(&self.metadata_store),
+            collections: Arc::clone(&self.collections),
+            chunks: Arc::clone(&self.chunks),
+            hot_layer: Arc::clone(&self.hot_layer),
+            warm_layer: Arc::clone(&self.warm_layer),
+            cold_layer: Arc::clone(&self.cold_layer),
+            archive_layer: Arc::clone(&self.archive_layer),
+            compression: self.config.compression.clone(),
+            dictionaries: Arc::clone(&self.dictionaries),
+            encryption: self.encryption.clone(),
+        }
+    }
+}
@@ -0,0 +1,514 @@
+//! # Streaming Large Documents
+//!
+//! Documents stored with [`StorageHierarchy::store_document`] are held in
+//! memory whole, which does not scale to blobs of hundreds of megabytes.
+//! [`StorageHierarchy::store_document_stream`] instead reads its payload
+//! from an [`AsyncRead`] one chunk at a time, compresses and encrypts each
+//! chunk on a blocking thread, and writes it to the warm tier, plus the
+//! cold tier when the collection keeps two or more copies. Chunks never
+//! enter the hot tier.
+//!
+//! The chunks of a document are described by a [`ChunkManifest`] kept next
+//! to its metadata. A rewrite stores its chunks under a new generation and
+//! swaps the manifest only once every chunk is written, so readers never
+//! see a mix of two versions. [`StorageHierarchy::get_document_stream`]
+//! verifies, decrypts and decompresses the chunks one at a time as the
+//! returned stream is polled.
+//!
+//! Streamed documents are opaque bytes: they are not readable through
+//! [`StorageHierarchy::get_document`], not kept as revisions or tombstones,
+//! and not replicated to other datacenters. Legal holds, WORM retention and
+//! residency placement apply to them as to any other document.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, error, info};
+
+use aerolithdb_security::DataEncryption;
+
+use crate::{
+    ChecksumAlgorithm, CompressionAlgorithm, CompressionConfig, CompressionEngine, DistributedStorage, DocumentMetadata,
+    LocalSSDCache, StorageHierarchy, StorageResult, StorageTier,
+};
+
+/// Layout of a document stored in chunks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    /// Generation the chunks were written under, unique per write
+    pub generation: String,
+
+    /// Size of the uncompressed chunks in bytes; the last may be shorter
+    pub chunk_size: usize,
+
+    /// Size of the uncompressed document in bytes
+    pub total_size: u64,
+
+    /// Checksum of the uncompressed document
+    pub checksum: String,
+
+    /// Algorithm of the document's and the chunks' checksums; manifests
+    /// written before algorithms were configurable are BLAKE3
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
+
+    /// Algorithm the chunks were compressed with
+    pub compression: CompressionAlgorithm,
+
+    /// Chunks in document order
+    pub chunks: Vec<ChunkInfo>,
+}
+
+/// One stored chunk of a document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkInfo {
+    /// Stored size in bytes, after compression and encryption
+    pub size: usize,
+
+    /// Uncompressed size in bytes
+    pub raw_size: usize,
+
+    /// Checksum of the stored bytes, with the manifest's algorithm
+    pub checksum: String,
+}
+
+impl ChunkManifest {
+    /// Stored size of all chunks in bytes.
+    pub fn stored_size(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.size).sum()
+    }
+
+    /// Tier key of a chunk, which also binds its encryption to the
+    /// document, generation and position.
+    pub(crate) fn chunk_key(&self, key: &str, index: usize) -> String {
+        format!("{}\0{}\0{:08}", key, self.generation, index)
+    }
+
+    /// Whether `sealed` are the stored bytes of `chunk`.
+    pub(crate) fn chunk_intact(&self, chunk: &ChunkInfo, sealed: &[u8]) -> bool {
+        self.checksum_algorithm.verify(sealed, &chunk.checksum)
+    }
+}
+
+/// A document read chunk by chunk, yielding its uncompressed bytes.
+///
+/// Documents that were not streamed in are yielded as their JSON
+/// serialization in a single chunk.
+pub struct DocumentStream {
+    /// Metadata of the document
+    pub metadata: DocumentMetadata,
+
+    /// Chunk layout, `None` for documents that were not streamed in
+    pub manifest: Option<ChunkManifest>,
+
+    chunks: BoxStream<'static, Result<Vec<u8>>>,
+}
+
+impl Stream for DocumentStream {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.chunks.poll_next_unpin(cx)
+    }
+}
+
+/// Chunk manifests by `collection:document_id`, persisted in the metadata
+/// database.
+#[derive(Debug)]
+pub(crate) struct ChunkManifests {
+    tree: sled::Tree,
+}
+
+impl ChunkManifests {
+    pub(crate) fn open(db: &sled::Db) -> Result<Self> {
+        Ok(Self { tree: db.open_tree("chunk_manifests")? })
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.tree.contains_key(key.as_bytes()).unwrap_or(false)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Result<Option<ChunkManifest>> {
+        match self.tree.get(key.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn insert(&self, key: &str, manifest: &ChunkManifest) -> Result<Option<ChunkManifest>> {
+        match self.tree.insert(key.as_bytes(), serde_json::to_vec(manifest)?)? {
+            Some(previous) => Ok(Some(serde_json::from_slice(&previous)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<ChunkManifest>> {
+        match self.tree.remove(key.as_bytes())? {
+            Some(previous) => Ok(Some(serde_json::from_slice(&previous)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl StorageHierarchy {
+    /// Store a document read from `reader` in chunks, creating it or
+    /// replacing the current version.
+    ///
+    /// At most one chunk of
+    /// [`StorageConfig::stream_chunk_size`](crate::StorageConfig) bytes is
+    /// held in memory at a time. If reading or storing any chunk fails, the
+    /// chunks written so far are removed and the current version is left
+    /// untouched.
+    pub async fn store_document_stream<R>(
+        &self,
+        collection: &str,
+        document_id: &str,
+        mut reader: R,
+    ) -> Result<StorageResult<()>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let start_time = std::time::Instant::now();
+        debug!("Streaming document {}:{} into chunks", collection, document_id);
+
+        let key = format!("{}:{}", collection, document_id);
+        self.check_placement(collection, document_id)?;
+        let existing = self.metadata_store.get(&key);
+        if let Some(existing) = &existing {
+            self.check_worm(existing, "update")?;
+        }
+
+        let encryption = self.collection_encryption(collection)?.cloned();
+        // Collections skipping compression store their chunks as they are
+        let algorithm = match self.collection_skips_compression(collection) {
+            true => CompressionAlgorithm::None,
+            false => self.collection_compression_algorithm(collection),
+        };
+        let compression = Arc::new(CompressionEngine::new(&CompressionConfig {
+            algorithm: algorithm.clone(),
+            ..self.config.compression.clone()
+        }));
+        let shard_id = match &existing {
+            Some(existing) => existing.shard_id.clone(),
+            None => self.sharding_engine.read().await.get_shard(collection, document_id).await,
+        };
+        let chunk_size = self.config.stream_chunk_size.max(1);
+        let keep_cold_copy = self.collection_replication_factor(collection) >= 2;
+
+        let mut manifest = ChunkManifest {
+            generation: uuid::Uuid::new_v4().to_string(),
+            chunk_size,
+            total_size: 0,
+            checksum: String::new(),
+            checksum_algorithm: self.config.checksum.algorithm,
+            compression: algorithm,
+            chunks: Vec::new(),
+        };
+        let mut hasher = manifest.checksum_algorithm.hasher();
+        let mut encryption_key_id = None;
+
+        loop {
+            let written = async {
+                let mut raw = Vec::with_capacity(chunk_size);
+                (&mut reader).take(chunk_size as u64).read_to_end(&mut raw).await?;
+                if raw.is_empty() {
+                    return Ok(None);
+                }
+                hasher.update(&raw);
+
+                let chunk_key = manifest.chunk_key(&key, manifest.chunks.len());
+                let raw_size = raw.len();
+                let (sealed, key_id) =
+                    seal_chunk(Arc::clone(&compression), encryption.clone(), raw, chunk_key.clone()).await?;
+
+                // The document so far has to fit in the storage limit
+                let streamed: usize = manifest.chunks.iter().map(|chunk| chunk.size).sum::<usize>() + sealed.len();
+                let replaced = existing.as_ref().map_or(0, |existing| existing.size);
+                self.reserve_storage(collection, streamed.saturating_sub(replaced) as u64).await?;
+
+                self.warm_layer.store(&shard_id, &chunk_key, &sealed).await?;
+                if keep_cold_copy {
+                    self.cold_layer.store(&shard_id, &chunk_key, &sealed).await?;
+                }
+                let checksum = manifest.checksum_algorithm.digest(&sealed);
+                let chunk = ChunkInfo { size: sealed.len(), raw_size, checksum };
+                Ok::<_, anyhow::Error>(Some((chunk, key_id)))
+            }
+            .await;
+
+            match written {
+                Ok(Some((chunk, key_id))) => {
+                    manifest.total_size += chunk.raw_size as u64;
+                    manifest.chunks.push(chunk);
+                    encryption_key_id = key_id;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    self.remove_chunks(&shard_id, &key, &manifest).await;
+                    return Err(e);
+                }
+            }
+        }
+        manifest.checksum = hasher.finalize();
+
+        // Keep the replaced revision of versioned collections while its
+        // payload is still stored
+        match &existing {
+            Some(existing) => self.retain_replaced(&key, existing).await,
+            None => self.clear_tombstone(&key),
+        }
+
+        let committed = self.commit_manifest(collection, document_id, &key, &shard_id, &manifest, encryption_key_id);
+        let (metadata, replaced) = match committed {
+            Ok(committed) => committed,
+            Err(e) => {
+                self.remove_chunks(&shard_id, &key, &manifest).await;
+                return Err(e);
+            }
+        };
+
+        // Remove whatever the previous version left in the tiers
+        match replaced {
+            Some(replaced) => self.remove_chunks(&shard_id, &key, &replaced).await,
+            None if existing.is_some() => {
+                let _ = self.hot_layer.delete(&shard_id, &key).await;
+                let _ = self.warm_layer.delete(&shard_id, &key).await;
+                let _ = self.cold_layer.delete(&shard_id, &key).await;
+                let _ = self.archive_layer.delete(&shard_id, &key).await;
+            }
+            None => {}
+        }
+
+        info!(
+            "Stored document {} in {} chunks ({} bytes)",
+            key,
+            manifest.chunks.len(),
+            manifest.total_size
+        );
+        Ok(StorageResult {
+            data: Some(()),
+            metadata: Some(metadata),
+            operation_time: start_time.elapsed(),
+            storage_tier: StorageTier::Warm,
+            cache_hit: false,
+        })
+    }
+
+    /// Read a document as a stream of its uncompressed chunks.
+    ///
+    /// Chunks are read from the warm tier, falling back to the cold tier,
+    /// and checked against their checksums as the stream is polled; a chunk
+    /// that is missing or corrupt in both ends the stream with an error.
+    pub async fn get_document_stream(&self, collection: &str, document_id: &str) -> Result<DocumentStream> {
+        let key = format!("{}:{}", collection, document_id);
+        let metadata = self
+            .metadata_store
+            .get(&key)
+            .ok_or_else(|| anyhow::anyhow!("Document not found: {}:{}", collection, document_id))?;
+
+        let Some(manifest) = self.chunks.get(&key)? else {
+            let document = self
+                .get_document(collection, document_id)
+                .await?
+                .data
+                .ok_or_else(|| anyhow::anyhow!("Document not found: {}:{}", collection, document_id))?;
+            let bytes = serde_json::to_vec(&document)?;
+            return Ok(DocumentStream { metadata, manifest: None, chunks: stream::once(async { Ok(bytes) }).boxed() });
+        };
+        self.access.record(&key);
+
+        let warm_layer = Arc::clone(&self.warm_layer);
+        let cold_layer = Arc::clone(&self.cold_layer);
+        let encryption = self.encryption.clone();
+        let compression = Arc::new(CompressionEngine::new(&CompressionConfig {
+            algorithm: manifest.compression.clone(),
+            ..self.config.compression.clone()
+        }));
+        let shard_id = metadata.shard_id.clone();
+        let algorithm = manifest.checksum_algorithm;
+        let chunks: Vec<(String, ChunkInfo)> = manifest
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| (manifest.chunk_key(&key, index), chunk.clone()))
+            .collect();
+
+        let chunks = stream::iter(chunks.into_iter().enumerate())
+            .then(move |(index, (chunk_key, chunk))| {
+                let warm_layer = Arc::clone(&warm_layer);
+                let cold_layer = Arc::clone(&cold_layer);
+                let encryption = encryption.clone();
+                let compression = Arc::clone(&compression);
+                let shard_id = shard_id.clone();
+                let key = key.clone();
+                async move {
+                    let sealed = load_chunk(&warm_layer, &cold_layer, &shard_id, &chunk_key, &chunk, algorithm)
+                        .await
+                        .ok_or_else(|| anyhow::anyhow!("Chunk {} of {} is missing or corrupt", index, key))?;
+                    open_chunk(compression, encryption, sealed, chunk_key).await
+                }
+            })
+            .boxed();
+
+        Ok(DocumentStream { metadata, manifest: Some(manifest), chunks })
+    }
+
+    /// Chunk layout of a document, `None` if it was not streamed in.
+    pub fn chunk_manifest(&self, collection: &str, document_id: &str) -> Result<Option<ChunkManifest>> {
+        self.chunks.get(&format!("{}:{}", collection, document_id))
+    }
+
+    /// Whether the document under `key` is stored in chunks.
+    pub(crate) fn is_chunked(&self, key: &str) -> bool {
+        self.chunks.contains(key)
+    }
+
+    /// Remove the chunks of a document that is being replaced or deleted,
+    /// if it was stored in chunks.
+    pub(crate) async fn discard_chunks(&self, key: &str, shard_id: &str) {
+        match self.chunks.remove(key) {
+            Ok(Some(manifest)) => self.remove_chunks(shard_id, key, &manifest).await,
+            Ok(None) => {}
+            Err(e) => error!("Failed to remove chunk manifest of {}: {}", key, e),
+        }
+    }
+
+    /// Record the manifest and metadata of a streamed document, returning
+    /// the metadata and the manifest it replaced.
+    fn commit_manifest(
+        &self,
+        collection: &str,
+        document_id: &str,
+        key: &str,
+        shard_id: &str,
+        manifest: &ChunkManifest,
+        encryption_key_id: Option<String>,
+    ) -> Result<(DocumentMetadata, Option<ChunkManifest>)> {
+        let replaced = self.chunks.insert(key, manifest)?;
+        let stored_size = manifest.stored_size();
+        let now = chrono::Utc::now();
+
+        let metadata = self.metadata_store.write_with(key.to_string(), |existing| {
+            if let Some(existing) = existing {
+                self.check_worm(existing, "update")?;
+            }
+            Ok(DocumentMetadata {
+                id: document_id.to_string(),
+                collection: collection.to_string(),
+                size: stored_size,
+                compression_ratio: manifest.total_size as f32 / stored_size.max(1) as f32,
+                created_at: existing.map_or(now, |existing| existing.created_at),
+                updated_at: now,
+                version: existing.map_or_else(|| self.next_new_version(key), |existing| existing.version + 1),
+                checksum: manifest.checksum.clone(),
+                checksum_algorithm: manifest.checksum_algorithm,
+                storage_tier: StorageTier::Warm,
+                shard_id: shard_id.to_string(),
+                replica_locations: Vec::new(),
+                encryption_key_id,
+                expires_at: existing.and_then(|existing| existing.expires_at),
+                compression_skipped: self.collection_skips_compression(collection),
+            })
+        });
+
+        match metadata {
+            Ok(metadata) => Ok((metadata, replaced)),
+            Err(e) => {
+                // Put back the manifest of the version that stays
+                let restored = match &replaced {
+                    Some(replaced) => self.chunks.insert(key, replaced).map(drop),
+                    None => self.chunks.remove(key).map(drop),
+                };
+                if let Err(restore_error) = restored {
+                    error!("Failed to restore chunk manifest of {}: {}", key, restore_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Delete the chunks described by `manifest` from every tier holding
+    /// them.
+    pub(crate) async fn remove_chunks(&self, shard_id: &str, key: &str, manifest: &ChunkManifest) {
+        let entries: Vec<(String, String)> = (0..manifest.chunks.len())
+            .map(|index| (shard_id.to_string(), manifest.chunk_key(key, index)))
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.warm_layer.delete_batch(&entries).await {
+            error!("Failed to remove chunks of {} from the warm tier: {}", key, e);
+        }
+        if let Err(e) = self.cold_layer.delete_batch(&entries).await {
+            error!("Failed to remove chunks of {} from the cold tier: {}", key, e);
+        }
+    }
+}
+
+/// Compress and, if `encryption` is set, encrypt one chunk on a blocking
+/// thread, returning the sealed bytes and the id of the key used.
+async fn seal_chunk(
+    compression: Arc<CompressionEngine>,
+    encryption: Option<Arc<DataEncryption>>,
+    raw: Vec<u8>,
+    context: String,
+) -> Result<(Vec<u8>, Option<String>)> {
+    tokio::task::spawn_blocking(move || {
+        let compressed = futures::executor::block_on(compression.compress(&raw))?;
+        match encryption {
+            Some(encryption) => {
+                let (encrypted, key_id) = encryption.encrypt(&compressed, context.as_bytes())?;
+                Ok((encrypted, Some(key_id)))
+            }
+            None => Ok((compressed, None)),
+        }
+    })
+    .await?
+}
+
+/// Decrypt and decompress one chunk on a blocking thread.
+pub(crate) async fn open_chunk(
+    compression: Arc<CompressionEngine>,
+    encryption: Option<Arc<DataEncryption>>,
+    sealed: Vec<u8>,
+    context: String,
+) -> Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let compressed = if DataEncryption::is_encrypted(&sealed) {
+            let encryption = encryption.ok_or_else(|| {
+                anyhow::anyhow!("Chunk is encrypted but encryption at rest is not configured")
+            })?;
+            encryption.decrypt(&sealed, context.as_bytes())?
+        } else {
+            sealed
+        };
+        futures::executor::block_on(compression.decompress(&compressed))
+    })
+    .await?
+}
+
+/// Read a stored chunk from the first tier holding an intact copy, as
+/// checked with the `algorithm` of its manifest.
+pub(crate) async fn load_chunk(
+    warm_layer: &LocalSSDCache,
+    cold_layer: &DistributedStorage,
+    shard_id: &str,
+    chunk_key: &str,
+    chunk: &ChunkInfo,
+    algorithm: ChecksumAlgorithm,
+) -> Option<Vec<u8>> {
+    let intact = |sealed: &Vec<u8>| algorithm.verify(sealed, &chunk.checksum);
+
+    if let Ok(sealed) = warm_layer.get(shard_id, chunk_key).await {
+        if intact(&sealed) {
+            return Some(sealed);
+        }
+    }
+    cold_layer.get(shard_id, chunk_key).await.ok().filter(intact)
+}
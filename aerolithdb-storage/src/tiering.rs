@@ -0,0 +1,571 @@
+//! # Tier Migration Policies
+//!
+//! Documents are moved between the storage tiers by rules. Each rule names
+//! the tier it applies to and the tier it moves documents to, optionally
+//! limited to one collection, and conditions on the time since the document
+//! was last written or read, its stored size and how often it is read.
+//! Rules are checked in order and the first one matching a document decides
+//! its migration, so more specific rules go first.
+//!
+//! Demoting a document writes it to the slower tier and removes its copies
+//! from the faster ones; promoting it writes it to the faster tier and keeps
+//! the slower copies, except the archived one. Documents under legal hold,
+//! streamed documents and documents of volatile collections stay where they
+//! are.
+//!
+//! Runs also sweep the archive every
+//! [`ArchiveConfig::orphan_sweep_interval`](crate::ArchiveConfig), removing
+//! archived objects of documents that no longer exist or are no longer
+//! archived, which a failed or interrupted migration can leave behind.
+//!
+//! Read rates and idle times come from the sampled access statistics of
+//! each document; a document without statistics counts as unread since it
+//! was last written. Rules may also require a document to rank among the
+//! most read documents over the heavy-hitter window, which promotes bursts
+//! of reads faster than a read rate decaying over a day does. A dry run reports the migrations a run would perform
+//! without moving any document. Rules set at runtime are persisted in the
+//! metadata database and take precedence over
+//! [`TierPolicyConfig::rules`](crate::TierPolicyConfig).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::access::AccessTracker;
+use crate::collections::CollectionRegistry;
+use crate::legal_hold::LegalHoldRegistry;
+use crate::streaming::ChunkManifests;
+use crate::{
+    AccessStats, DistributedStorage, DocumentMetadata, LocalSSDCache, MemoryCache, MetadataStore, ObjectStorage,
+    StorageHierarchy, StorageTier,
+};
+
+/// Key of the persisted rules in their tree
+const RULES_KEY: &[u8] = b"rules";
+
+/// Tier migration rules and how often they are applied.
+#[derive(Debug, Clone)]
+pub struct TierPolicyConfig {
+    /// Rules applied until rules are set at runtime
+    pub rules: Vec<TierRule>,
+
+    /// Time between migration runs
+    pub interval: Duration,
+
+    /// Whether the background runs only report what they would migrate
+    pub dry_run: bool,
+}
+
+impl Default for TierPolicyConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![TierRule {
+                name: "archive-cold".to_string(),
+                collection: None,
+                from: StorageTier::Cold,
+                to: StorageTier::Archive,
+                min_age: Some(Duration::from_secs(30 * 24 * 3600)),
+                min_idle: None,
+                min_size: None,
+                max_size: None,
+                min_reads_per_day: None,
+                max_reads_per_day: None,
+                hottest: None,
+            }],
+            interval: Duration::from_secs(300),
+            dry_run: false,
+        }
+    }
+}
+
+/// A rule moving documents from one tier to another.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TierRule {
+    /// Name of the rule, unique among the rules
+    pub name: String,
+
+    /// Collection the rule applies to; `None` applies it to all collections
+    pub collection: Option<String>,
+
+    /// Tier of the documents the rule applies to
+    pub from: StorageTier,
+
+    /// Tier the rule moves documents to
+    pub to: StorageTier,
+
+    /// Documents not written for at least this long
+    pub min_age: Option<Duration>,
+
+    /// Documents neither read nor written for at least this long
+    pub min_idle: Option<Duration>,
+
+    /// Documents with at least this stored size in bytes
+    pub min_size: Option<usize>,
+
+    /// Documents with at most this stored size in bytes
+    pub max_size: Option<usize>,
+
+    /// Documents read at least this many times per day
+    pub min_reads_per_day: Option<f64>,
+
+    /// Documents read at most this many times per day
+    pub max_reads_per_day: Option<f64>,
+
+    /// Documents among this many most read over the heavy-hitter window
+    pub hottest: Option<usize>,
+}
+
+impl TierRule {
+    /// Whether the rule moves a document with `metadata` and `access`
+    /// statistics at time `now`, `rank` being its position among the most
+    /// read documents, counted from 1, if it is among them.
+    fn matches(
+        &self,
+        metadata: &DocumentMetadata,
+        access: Option<&AccessStats>,
+        rank: Option<usize>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if metadata.storage_tier != self.from {
+            return false;
+        }
+        if self.collection.as_ref().is_some_and(|collection| *collection != metadata.collection) {
+            return false;
+        }
+        if let Some(min_age) = self.min_age {
+            let age = (now - metadata.updated_at).to_std().unwrap_or_default();
+            if age < min_age {
+                return false;
+            }
+        }
+        if let Some(min_idle) = self.min_idle {
+            let last_used = access.map_or(metadata.updated_at, |access| access.last_accessed.max(metadata.updated_at));
+            if (now - last_used).to_std().unwrap_or_default() < min_idle {
+                return false;
+            }
+        }
+        let reads_per_day = access.map_or(0.0, |access| access.reads_per_day);
+        self.min_size.is_none_or(|min| metadata.size >= min)
+            && self.max_size.is_none_or(|max| metadata.size <= max)
+            && self.min_reads_per_day.is_none_or(|min| reads_per_day >= min)
+            && self.max_reads_per_day.is_none_or(|max| reads_per_day <= max)
+            && self.hottest.is_none_or(|hottest| rank.is_some_and(|rank| rank <= hottest))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow::anyhow!("Invalid tier rule: rules need a name"));
+        }
+        if self.from == self.to {
+            return Err(anyhow::anyhow!("Invalid tier rule {}: it moves documents to the tier they are in", self.name));
+        }
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min > max {
+                return Err(anyhow::anyhow!("Invalid tier rule {}: min_size exceeds max_size", self.name));
+            }
+        }
+        for rate in [self.min_reads_per_day, self.max_reads_per_day].into_iter().flatten() {
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(anyhow::anyhow!("Invalid tier rule {}: read rates must be non-negative", self.name));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_reads_per_day, self.max_reads_per_day) {
+            if min > max {
+                return Err(anyhow::anyhow!(
+                    "Invalid tier rule {}: min_reads_per_day exceeds max_reads_per_day",
+                    self.name
+                ));
+            }
+        }
+        if self.hottest == Some(0) {
+            return Err(anyhow::anyhow!("Invalid tier rule {}: hottest must be at least 1", self.name));
+        }
+        Ok(())
+    }
+}
+
+/// A document moved, or to be moved in a dry run, by a rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TierMigration {
+    /// Collection of the document
+    pub collection: String,
+
+    /// Identifier of the document
+    pub document_id: String,
+
+    /// Name of the rule that matched
+    pub rule: String,
+
+    /// Tier the document was in
+    pub from: StorageTier,
+
+    /// Tier the document was moved to
+    pub to: StorageTier,
+
+    /// Stored size of the document in bytes
+    pub size: usize,
+}
+
+/// Outcome of one migration run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TierMigrationReport {
+    /// Whether documents were only evaluated, not moved
+    pub dry_run: bool,
+
+    /// Documents checked against the rules
+    pub evaluated: usize,
+
+    /// Documents moved, or that would be moved in a dry run
+    pub migrations: Vec<TierMigration>,
+
+    /// Documents a rule matched that could not be moved
+    pub failed: usize,
+
+    /// Orphaned objects removed from the archive
+    pub archive_objects_removed: usize,
+}
+
+/// Totals of the migration runs since startup.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TierMigrationMetrics {
+    /// Runs that moved documents
+    pub runs: u64,
+
+    /// Dry runs
+    pub dry_runs: u64,
+
+    /// Documents moved
+    pub documents_migrated: u64,
+
+    /// Stored bytes moved
+    pub bytes_migrated: u64,
+
+    /// Migrations that failed
+    pub failures: u64,
+
+    /// Orphaned objects removed from the archive
+    pub archive_objects_removed: u64,
+
+    /// Documents moved by each rule
+    pub migrated_by_rule: HashMap<String, u64>,
+
+    /// When the last run, dry or not, finished
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+/// Migration rules and metrics; the rules are persisted in the metadata
+/// database.
+#[derive(Debug)]
+pub(crate) struct TierPolicy {
+    rules: RwLock<Vec<TierRule>>,
+    metrics: Mutex<TierMigrationMetrics>,
+
+    /// Serializes migration runs
+    running: tokio::sync::Mutex<()>,
+
+    tree: sled::Tree,
+}
+
+impl TierPolicy {
+    pub(crate) fn open(db: &sled::Db, config: &TierPolicyConfig) -> Result<Self> {
+        let tree = db.open_tree("tier_rules")?;
+        let rules = match tree.get(RULES_KEY)? {
+            Some(value) => match serde_json::from_slice(&value) {
+                Ok(rules) => rules,
+                Err(e) => {
+                    warn!("Using configured tier rules; persisted rules are unreadable: {}", e);
+                    config.rules.clone()
+                }
+            },
+            None => config.rules.clone(),
+        };
+
+        Ok(Self {
+            rules: RwLock::new(rules),
+            metrics: Mutex::new(TierMigrationMetrics::default()),
+            running: tokio::sync::Mutex::new(()),
+            tree,
+        })
+    }
+
+    fn set_rules(&self, rules: Vec<TierRule>) -> Result<()> {
+        for (index, rule) in rules.iter().enumerate() {
+            rule.validate()?;
+            if rules[..index].iter().any(|other| other.name == rule.name) {
+                return Err(anyhow::anyhow!("Invalid tier rule {}: names must be unique", rule.name));
+            }
+        }
+
+        self.tree.insert(RULES_KEY, serde_json::to_vec(&rules)?)?;
+        *self.rules.write().unwrap() = rules;
+        Ok(())
+    }
+
+    fn rules(&self) -> Vec<TierRule> {
+        self.rules.read().unwrap().clone()
+    }
+
+    fn metrics(&self) -> TierMigrationMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+}
+
+/// Storage components a migration run works on, detached from the
+/// hierarchy so runs can be spawned.
+#[derive(Clone)]
+pub(crate) struct TierMigrator {
+    policy: Arc<TierPolicy>,
+    access: Arc<AccessTracker>,
+    metadata_store: Arc<MetadataStore>,
+    legal_holds: Arc<LegalHoldRegistry>,
+    chunks: Arc<ChunkManifests>,
+    collections: Arc<CollectionRegistry>,
+    hot_layer: Arc<MemoryCache>,
+    warm_layer: Arc<LocalSSDCache>,
+    cold_layer: Arc<DistributedStorage>,
+    archive_layer: Arc<ObjectStorage>,
+}
+
+impl TierMigrator {
+    /// Check every document against the rules and move those a rule
+    /// matches, or only report them when `dry_run` is set.
+    pub(crate) async fn run(&self, dry_run: bool) -> Result<TierMigrationReport> {
+        let _running = self.policy.running.lock().await;
+        let rules = self.policy.rules();
+        let now = Utc::now();
+
+        // Ranks are only looked up as deep as the deepest rule needs
+        let ranks: HashMap<String, usize> = match rules.iter().filter_map(|rule| rule.hottest).max() {
+            Some(depth) => self
+                .access
+                .hot_documents(depth, self.access.hot_window())
+                .into_iter()
+                .enumerate()
+                .map(|(i, hitter)| (hitter.key, i + 1))
+                .collect(),
+            None => HashMap::new(),
+        };
+
+        let mut report = TierMigrationReport { dry_run, ..Default::default() };
+        let mut candidates = Vec::new();
+        for entry in self.metadata_store.iter() {
+            report.evaluated += 1;
+            let access = self.access.stats(entry.key(), now);
+            let rank = ranks.get(entry.key()).copied();
+            if let Some(rule) = rules.iter().find(|rule| rule.matches(entry.value(), access.as_ref(), rank, now)) {
+                candidates.push((entry.key().clone(), entry.value().clone(), rule));
+            }
+        }
+
+        for (key, metadata, rule) in candidates {
+            if self.legal_holds.is_held(&key) || self.chunks.contains(&key) || self.collections.is_volatile(&metadata.collection) {
+                continue;
+            }
+
+            if !dry_run {
+                if let Err(e) = self.migrate(&key, &metadata, &rule.to).await {
+                    warn!("Failed to move {} from {:?} to {:?}: {}", key, rule.from, rule.to, e);
+                    report.failed += 1;
+                    continue;
+                }
+            }
+            report.migrations.push(TierMigration {
+                collection: metadata.collection.clone(),
+                document_id: metadata.id.clone(),
+                rule: rule.name.clone(),
+                from: rule.from.clone(),
+                to: rule.to.clone(),
+                size: metadata.size,
+            });
+        }
+
+        if !dry_run && self.archive_layer.start_sweep() {
+            match self.sweep_archive().await {
+                Ok(removed) => report.archive_objects_removed = removed,
+                Err(e) => warn!("Failed to sweep orphaned archive objects: {}", e),
+            }
+        }
+
+        let mut metrics = self.policy.metrics.lock().unwrap();
+        if dry_run {
+            metrics.dry_runs += 1;
+        } else {
+            metrics.runs += 1;
+            metrics.documents_migrated += report.migrations.len() as u64;
+            metrics.failures += report.failed as u64;
+            metrics.archive_objects_removed += report.archive_objects_removed as u64;
+            for migration in &report.migrations {
+                metrics.bytes_migrated += migration.size as u64;
+                *metrics.migrated_by_rule.entry(migration.rule.clone()).or_insert(0) += 1;
+            }
+        }
+        metrics.last_run = Some(Utc::now());
+        drop(metrics);
+
+        debug!(
+            "Tier migration {}: {} evaluated, {} migrations, {} failed",
+            if dry_run { "dry run" } else { "run" },
+            report.evaluated,
+            report.migrations.len(),
+            report.failed
+        );
+        Ok(report)
+    }
+
+    /// Move one document to `to`, unless it changed since `metadata` was read.
+    pub(crate) async fn migrate(&self, key: &str, metadata: &DocumentMetadata, to: &StorageTier) -> Result<()> {
+        if *to != StorageTier::Hot && self.collections.is_volatile(&metadata.collection) {
+            return Err(anyhow::anyhow!("documents of volatile collection {} stay in memory", metadata.collection));
+        }
+        let shard_id = &metadata.shard_id;
+        let mut payload = None;
+        for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold, StorageTier::Archive] {
+            if let Ok(data) = self.get(&tier, shard_id, key).await {
+                if metadata.checksum_matches(&data) {
+                    payload = Some(data);
+                    break;
+                }
+            }
+        }
+        let payload = payload.ok_or_else(|| anyhow::anyhow!("no tier holds the current payload"))?;
+        self.store(to, shard_id, key, &payload).await?;
+
+        // Skip the move if the document was rewritten or moved meanwhile
+        let Some(mut current) = self.metadata_store.get_mut(key) else {
+            return Ok(());
+        };
+        if current.checksum != metadata.checksum || current.storage_tier != metadata.storage_tier {
+            return Ok(());
+        }
+        current.storage_tier = to.clone();
+
+        // A demoted document keeps no copies in faster tiers
+        for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold] {
+            if tier_rank(&tier) < tier_rank(to) {
+                let _ = self.delete(&tier, shard_id, key).await;
+            }
+        }
+        // nor a promoted one an archived copy
+        if metadata.storage_tier == StorageTier::Archive {
+            let _ = self.archive_layer.delete(shard_id, key).await;
+        }
+        Ok(())
+    }
+
+    /// Remove archived objects of documents without metadata or outside
+    /// the Archive tier; returns how many were removed.
+    async fn sweep_archive(&self) -> Result<usize> {
+        let orphans: Vec<(String, String)> = self
+            .archive_layer
+            .keys()
+            .await?
+            .into_iter()
+            .filter_map(|tier_key| {
+                // Tier keys are `shard_id:collection:document_id`; chunks of
+                // streamed documents go with their manifest
+                let (shard_id, key) = tier_key.split_once(':')?;
+                if key.contains('\0') || self.legal_holds.is_held(key) {
+                    return None;
+                }
+                let archived = self
+                    .metadata_store
+                    .get(key)
+                    .is_some_and(|metadata| metadata.storage_tier == StorageTier::Archive);
+                (!archived).then(|| (shard_id.to_string(), key.to_string()))
+            })
+            .collect();
+
+        if !orphans.is_empty() {
+            self.archive_layer.delete_batch(&orphans).await?;
+            info!("Removed {} orphaned objects from the archive", orphans.len());
+        }
+        Ok(orphans.len())
+    }
+
+    async fn get(&self, tier: &StorageTier, shard_id: &str, key: &str) -> Result<Vec<u8>> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.get(shard_id, key).await,
+            StorageTier::Warm => self.warm_layer.get(shard_id, key).await,
+            StorageTier::Cold => self.cold_layer.get(shard_id, key).await,
+            StorageTier::Archive => self.archive_layer.get(shard_id, key).await,
+        }
+    }
+
+    async fn store(&self, tier: &StorageTier, shard_id: &str, key: &str, data: &[u8]) -> Result<()> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.store(shard_id, key, data).await,
+            StorageTier::Warm => self.warm_layer.store(shard_id, key, data).await,
+            StorageTier::Cold => self.cold_layer.store(shard_id, key, data).await,
+            StorageTier::Archive => self.archive_layer.store(shard_id, key, data).await,
+        }
+    }
+
+    async fn delete(&self, tier: &StorageTier, shard_id: &str, key: &str) -> Result<()> {
+        match tier {
+            StorageTier::Hot => self.hot_layer.delete(shard_id, key).await,
+            StorageTier::Warm => self.warm_layer.delete(shard_id, key).await,
+            StorageTier::Cold => self.cold_layer.delete(shard_id, key).await,
+            StorageTier::Archive => self.archive_layer.delete(shard_id, key).await,
+        }
+    }
+}
+
+/// Position of a tier from fastest to slowest
+fn tier_rank(tier: &StorageTier) -> u8 {
+    match tier {
+        StorageTier::Hot => 0,
+        StorageTier::Warm => 1,
+        StorageTier::Cold => 2,
+        StorageTier::Archive => 3,
+    }
+}
+
+impl StorageHierarchy {
+    /// Replace the tier migration rules, which are checked in order.
+    ///
+    /// Fails without changing the rules if a rule is unnamed, shares its
+    /// name with another, moves documents to the tier they are in or has
+    /// contradictory thresholds.
+    pub fn set_tier_rules(&self, rules: Vec<TierRule>) -> Result<Vec<TierRule>> {
+        self.tiering.set_rules(rules)?;
+        let rules = self.tiering.rules();
+        info!("Tier migration now follows {} rules", rules.len());
+        Ok(rules)
+    }
+
+    /// Current tier migration rules, in the order they are checked.
+    pub fn tier_rules(&self) -> Vec<TierRule> {
+        self.tiering.rules()
+    }
+
+    /// Apply the tier migration rules now, or with `dry_run` only report
+    /// the migrations they call for.
+    pub async fn run_tier_migration(&self, dry_run: bool) -> Result<TierMigrationReport> {
+        self.tier_migrator().run(dry_run).await
+    }
+
+    /// Totals of the tier migration runs since startup.
+    pub fn tier_migration_metrics(&self) -> TierMigrationMetrics {
+        self.tiering.metrics()
+    }
+
+    pub(crate) fn tier_migrator(&self) -> TierMigrator {
+        TierMigrator {
+            policy: Arc::clone(&self.tiering),
+            access: Arc::clone(&self.access),
+            metadata_store: Arc::clone(&self.metadata_store),
+            legal_holds: Arc::clone(&self.legal_holds),
+            chunks: Arc::clone(&self.chunks),
+            collections: Arc::clone(&self.collections),
+            hot_layer: Arc::clone(&self.hot_layer),
+            warm_layer: Arc::clone(&self.warm_layer),
+            cold_layer: Arc::clone(&self.cold_layer),
+            archive_layer: Arc::clone(&self.archive_layer),
+        }
+    }
+}
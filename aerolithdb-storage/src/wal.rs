@@ -0,0 +1,406 @@
+//! # Write-Ahead Log
+//!
+//! Writes are acknowledged once they reach the hot tier, while their copies
+//! in the warm and cold tiers are written by a background replication task.
+//! A crash in between would lose an acknowledged write, along with any
+//! metadata sled had not flushed yet. To prevent that, every store, update
+//! and delete is first appended to a log under `<data_dir>/wal`, with the
+//! new metadata and stored payload, and synced to disk before it is
+//! acknowledged.
+//!
+//! A record is complete once its replication to the warm and cold tiers
+//! succeeded, or, for deletes, once every tier dropped the document.
+//! Checkpoints close the active segment, flush the metadata database and
+//! remove the segments holding only complete records. On startup, the
+//! records left in the log are replayed in order and the log is cleared.
+//! A record whose document was written again since, by a change the log
+//! does not track, is skipped. A torn record at the end of a segment, left
+//! by a crash during its append, ends the replay of that segment.
+
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{DocumentMetadata, MetadataStore, StorageHierarchy, StorageTier};
+
+/// Bytes before the body of a record: its length and checksum
+const FRAME_HEADER: usize = 12;
+
+/// Whether writes are logged and how the log is synced and checkpointed.
+#[derive(Debug, Clone)]
+pub struct WalConfig {
+    /// Log writes before acknowledging them
+    pub enabled: bool,
+
+    /// Sync each append to disk; without it, writes acknowledged shortly
+    /// before an operating system crash can still be lost
+    pub sync: bool,
+
+    /// Size in bytes after which a new segment is started
+    pub segment_size: u64,
+
+    /// Time between background checkpoints
+    pub checkpoint_interval: Duration,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sync: true,
+            segment_size: 64 * 1024 * 1024,
+            checkpoint_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Outcome of replaying the log on startup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalReplayReport {
+    /// Records read from the log
+    pub records: usize,
+
+    /// Records applied to the metadata and tiers
+    pub replayed: usize,
+
+    /// Records superseded by later changes to their document
+    pub skipped: usize,
+
+    /// Segments whose replay ended at a torn or corrupt record
+    pub torn: usize,
+}
+
+/// A logged change to one document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum WalOperation {
+    /// A document was stored with this metadata; the payload follows
+    Store { key: String, metadata: DocumentMetadata },
+
+    /// A document was deleted at this version
+    Delete { key: String, shard_id: String, version: u64 },
+}
+
+/// A record read back from the log.
+#[derive(Debug)]
+pub(crate) struct WalRecord {
+    seq: u64,
+    operation: WalOperation,
+    payload: Vec<u8>,
+}
+
+/// Records left in the log by the previous run.
+#[derive(Debug, Default)]
+pub(crate) struct WalRecovery {
+    records: Vec<WalRecord>,
+    torn: usize,
+}
+
+impl WalRecovery {
+    fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.torn == 0
+    }
+}
+
+/// Append-only log of document writes, split into segments named after
+/// the sequence number of their first record.
+#[derive(Debug)]
+pub(crate) struct WriteAheadLog {
+    dir: PathBuf,
+    config: WalConfig,
+    state: Mutex<WalState>,
+}
+
+#[derive(Debug)]
+struct WalState {
+    /// Segment receiving appends, opened by the first append after a rotation
+    active: Option<Segment>,
+
+    /// Sequence number of the next record
+    next_seq: u64,
+
+    /// Records appended but not yet complete
+    pending: BTreeSet<u64>,
+}
+
+#[derive(Debug)]
+struct Segment {
+    file: File,
+    first_seq: u64,
+    len: u64,
+}
+
+impl WriteAheadLog {
+    /// Open the log in `dir`, returning the records of the previous run.
+    pub(crate) fn open(dir: &Path, config: &WalConfig) -> Result<(Self, WalRecovery)> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut recovery = WalRecovery::default();
+        let mut next_seq = 1;
+        for (first_seq, path) in segments(dir)? {
+            next_seq = next_seq.max(first_seq + 1);
+            let bytes = std::fs::read(&path)?;
+            let (records, torn) = decode_segment(&bytes);
+            if torn {
+                warn!("Write-ahead log segment {:?} ends with a torn record", path);
+                recovery.torn += 1;
+            }
+            if let Some(last) = records.last() {
+                next_seq = next_seq.max(last.seq + 1);
+            }
+            recovery.records.extend(records);
+        }
+
+        let log = Self {
+            dir: dir.to_path_buf(),
+            config: config.clone(),
+            state: Mutex::new(WalState { active: None, next_seq, pending: BTreeSet::new() }),
+        };
+        Ok((log, recovery))
+    }
+
+    /// Append records and sync them to disk, returning their sequence
+    /// numbers. Nothing is logged, and no numbers are returned, when the
+    /// log is disabled.
+    pub(crate) fn append(&self, entries: &[(WalOperation, &[u8])]) -> Result<Vec<u64>> {
+        if !self.config.enabled || entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.active.as_ref().is_some_and(|segment| segment.len >= self.config.segment_size) {
+            state.active = None;
+        }
+
+        let first_seq = state.next_seq;
+        let mut buffer = Vec::new();
+        for (offset, (operation, payload)) in entries.iter().enumerate() {
+            encode_record(&mut buffer, first_seq + offset as u64, operation, payload)?;
+        }
+
+        if state.active.is_none() {
+            let path = self.dir.join(segment_name(first_seq));
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            state.active = Some(Segment { file, first_seq, len: 0 });
+        }
+        let segment = state.active.as_mut().expect("active segment was just opened");
+        segment.file.write_all(&buffer)?;
+        if self.config.sync {
+            segment.file.sync_data()?;
+        }
+        segment.len += buffer.len() as u64;
+
+        let seqs: Vec<u64> = (first_seq..first_seq + entries.len() as u64).collect();
+        state.next_seq += entries.len() as u64;
+        state.pending.extend(&seqs);
+        Ok(seqs)
+    }
+
+    /// Mark records complete so checkpoints can drop them.
+    pub(crate) fn complete(&self, seqs: &[u64]) {
+        if seqs.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for seq in seqs {
+            state.pending.remove(seq);
+        }
+    }
+
+    /// Close the active segment and remove the segments holding only
+    /// complete records, once the metadata they describe is flushed.
+    /// Returns the number of segments removed.
+    pub(crate) async fn checkpoint(&self, metadata_store: &MetadataStore) -> Result<usize> {
+        // Records below the watermark are complete; the segment holding
+        // the rest stays behind
+        let watermark = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.active = None;
+            state.pending.first().copied().unwrap_or(state.next_seq)
+        };
+
+        metadata_store.flush().await?;
+
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let active_seq = state.active.as_ref().map(|segment| segment.first_seq);
+        let segments = segments(&self.dir)?;
+        let mut removed = 0;
+        for (index, (first_seq, path)) in segments.iter().enumerate() {
+            if Some(*first_seq) == active_seq {
+                continue;
+            }
+            let end = segments.get(index + 1).map_or(state.next_seq, |(next_seq, _)| *next_seq);
+            if end <= watermark {
+                std::fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Records appended but not yet complete.
+    pub(crate) fn pending(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).pending.len()
+    }
+}
+
+impl StorageHierarchy {
+    /// Drop the log segments whose writes have all been replicated.
+    ///
+    /// Runs every [`WalConfig::checkpoint_interval`] and on shutdown;
+    /// returns the number of segments removed.
+    pub async fn checkpoint_wal(&self) -> Result<usize> {
+        self.wal.checkpoint(&self.metadata_store).await
+    }
+
+    /// Logged writes whose replication has not yet succeeded.
+    pub fn pending_wal_records(&self) -> usize {
+        self.wal.pending()
+    }
+
+    /// Apply the records left in the log by the previous run, then clear it.
+    pub(crate) async fn replay_wal(&self, recovery: WalRecovery) -> Result<WalReplayReport> {
+        let mut report = WalReplayReport { records: recovery.records.len(), torn: recovery.torn, ..Default::default() };
+        if recovery.is_empty() {
+            return Ok(report);
+        }
+
+        info!("Replaying {} write-ahead log records", report.records);
+        for record in recovery.records {
+            let applied = match record.operation {
+                WalOperation::Store { key, metadata } => self.replay_store(key, metadata, &record.payload).await?,
+                WalOperation::Delete { key, shard_id, version } => self.replay_delete(&key, &shard_id, version).await?,
+            };
+            if applied {
+                report.replayed += 1;
+            } else {
+                report.skipped += 1;
+            }
+        }
+
+        self.checkpoint_wal().await?;
+        info!("Write-ahead log replay complete: {:?}", report);
+        Ok(report)
+    }
+
+    /// Restore the metadata and persistent copies of a logged store.
+    async fn replay_store(&self, key: String, metadata: DocumentMetadata, payload: &[u8]) -> Result<bool> {
+        let current = self.metadata_store.get(&key);
+        match &current {
+            Some(current) if current.version > metadata.version => return Ok(false),
+            // Same version rewritten in place, or already moved off the
+            // hot tier after its replication
+            Some(current)
+                if current.version == metadata.version
+                    && (current.checksum != metadata.checksum || current.storage_tier != StorageTier::Hot) =>
+            {
+                return Ok(false)
+            }
+            _ => {}
+        }
+
+        let shard_id = metadata.shard_id.clone();
+        let replication_factor = self.collection_replication_factor(&metadata.collection);
+        if current.as_ref().is_none_or(|current| current.version < metadata.version) {
+            self.metadata_store.insert(key.clone(), metadata)?;
+        }
+        self.warm_layer.store(&shard_id, &key, payload).await?;
+        if replication_factor >= 2 {
+            self.cold_layer.store(&shard_id, &key, payload).await?;
+        }
+        Ok(true)
+    }
+
+    /// Redo a logged delete unless the document was written again since.
+    async fn replay_delete(&self, key: &str, shard_id: &str, version: u64) -> Result<bool> {
+        if self.metadata_store.get(key).is_some_and(|current| current.version > version) {
+            return Ok(false);
+        }
+
+        self.metadata_store.remove(key)?;
+        for tier in [StorageTier::Hot, StorageTier::Warm, StorageTier::Cold, StorageTier::Archive] {
+            let _ = self.tier_delete(&tier, shard_id, key).await;
+        }
+        Ok(true)
+    }
+}
+
+/// File name of the segment starting at `first_seq`
+fn segment_name(first_seq: u64) -> String {
+    format!("wal-{:020}.log", first_seq)
+}
+
+/// Segments in `dir` with their first sequence numbers, oldest first
+fn segments(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let first_seq = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("wal-")?.strip_suffix(".log"))
+            .and_then(|seq| seq.parse::<u64>().ok());
+        if let Some(first_seq) = first_seq {
+            segments.push((first_seq, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+/// Frame one record: body length, the first eight bytes of the body's
+/// BLAKE3 hash, then the body of sequence number, operation and payload.
+fn encode_record(buffer: &mut Vec<u8>, seq: u64, operation: &WalOperation, payload: &[u8]) -> Result<()> {
+    let header = serde_json::to_vec(operation)?;
+    let mut body = Vec::with_capacity(12 + header.len() + payload.len());
+    body.extend_from_slice(&seq.to_le_bytes());
+    body.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    body.extend_from_slice(&header);
+    body.extend_from_slice(payload);
+
+    buffer.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&blake3::hash(&body).as_bytes()[..8]);
+    buffer.extend_from_slice(&body);
+    Ok(())
+}
+
+/// Decode the records of a segment, stopping at the first torn or corrupt
+/// one; the flag tells whether one was found.
+fn decode_segment(mut bytes: &[u8]) -> (Vec<WalRecord>, bool) {
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        match decode_record(bytes) {
+            Some((record, rest)) => {
+                records.push(record);
+                bytes = rest;
+            }
+            None => return (records, true),
+        }
+    }
+    (records, false)
+}
+
+fn decode_record(bytes: &[u8]) -> Option<(WalRecord, &[u8])> {
+    if bytes.len() < FRAME_HEADER {
+        return None;
+    }
+    let len = u32::from_le_bytes(bytes[..4].try_into().ok()?) as usize;
+    let body = bytes.get(FRAME_HEADER..FRAME_HEADER + len)?;
+    if blake3::hash(body).as_bytes()[..8] != bytes[4..FRAME_HEADER] || body.len() < 12 {
+        return None;
+    }
+
+    let seq = u64::from_le_bytes(body[..8].try_into().ok()?);
+    let header_len = u32::from_le_bytes(body[8..12].try_into().ok()?) as usize;
+    let header = body.get(12..12 + header_len)?;
+    let operation = serde_json::from_slice(header).ok()?;
+    let payload = body[12 + header_len..].to_vec();
+    Some((WalRecord { seq, operation, payload }, &bytes[FRAME_HEADER + len..]))
+}
@@ -0,0 +1,136 @@
+//! # Write-Once-Read-Many Collections
+//!
+//! Records kept for regulatory retention, such as SEC 17a-4 style
+//! broker-dealer records under `ComplianceMode::SOX`, must stay unaltered
+//! for a fixed period. A WORM collection refuses to update or delete any of
+//! its documents until the collection's retention period has elapsed since
+//! the document was first written. The check runs inside the storage layer,
+//! below every API and role, so no caller can bypass it.
+//!
+//! WORM policies are persisted in the metadata database and cannot be
+//! removed or shortened, only extended. Policies listed in
+//! [`StorageConfig::worm_collections`](crate::StorageConfig) are applied at
+//! startup; dropping one from the configuration leaves it in force.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{DocumentMetadata, StorageHierarchy};
+
+/// Retention policy of a WORM collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WormPolicy {
+    /// How long documents stay immutable after they are first written
+    pub retention: Duration,
+
+    /// When the collection became WORM
+    pub enabled_at: DateTime<Utc>,
+}
+
+impl WormPolicy {
+    /// Time until which a document created at `created_at` is immutable.
+    pub fn retained_until(&self, created_at: DateTime<Utc>) -> DateTime<Utc> {
+        chrono::Duration::from_std(self.retention)
+            .ok()
+            .and_then(|retention| created_at.checked_add_signed(retention))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// WORM policies by collection, persisted in the metadata database.
+#[derive(Debug)]
+pub(crate) struct WormRegistry {
+    policies: DashMap<String, WormPolicy>,
+    tree: sled::Tree,
+}
+
+impl WormRegistry {
+    pub(crate) fn open(db: &sled::Db) -> Result<Self> {
+        let registry = Self { policies: DashMap::new(), tree: db.open_tree("worm_collections")? };
+
+        for item in registry.tree.iter() {
+            let (key, value) = item?;
+            let collection = String::from_utf8_lossy(&key).into_owned();
+            match serde_json::from_slice::<WormPolicy>(&value) {
+                Ok(policy) => {
+                    registry.policies.insert(collection, policy);
+                }
+                Err(e) => warn!("Skipping unreadable WORM policy of {}: {}", collection, e),
+            }
+        }
+
+        Ok(registry)
+    }
+
+    fn enable(&self, collection: &str, retention: Duration) -> Result<WormPolicy> {
+        let mut entry = self
+            .policies
+            .entry(collection.to_string())
+            .or_insert_with(|| WormPolicy { retention, enabled_at: Utc::now() });
+        if entry.retention > retention {
+            return Err(anyhow::anyhow!(
+                "WORM retention of {} can only be extended: currently {:?}, requested {:?}",
+                collection,
+                entry.retention,
+                retention
+            ));
+        }
+        entry.retention = retention;
+
+        self.tree.insert(collection.as_bytes(), serde_json::to_vec(&*entry)?)?;
+        Ok(entry.clone())
+    }
+
+    pub(crate) fn policy(&self, collection: &str) -> Option<WormPolicy> {
+        self.policies.get(collection).map(|policy| policy.clone())
+    }
+}
+
+impl StorageHierarchy {
+    /// Make a collection write-once-read-many, or extend the retention of a
+    /// collection that already is.
+    ///
+    /// Fails if the collection already has a longer retention. There is no
+    /// way to turn WORM off again.
+    pub fn enable_worm(&self, collection: &str, retention: Duration) -> Result<WormPolicy> {
+        if self.is_volatile_collection(collection) {
+            return Err(anyhow::anyhow!("Volatile collection {} cannot be WORM", collection));
+        }
+        if self.collections.config(collection).is_some_and(|config| config.is_capped()) {
+            return Err(anyhow::anyhow!("Capped collection {} cannot be WORM", collection));
+        }
+        let policy = self.worm.enable(collection, retention)?;
+        info!("Collection {} is WORM with retention {:?}", collection, policy.retention);
+        Ok(policy)
+    }
+
+    /// WORM policy of a collection, if it has one.
+    pub fn worm_policy(&self, collection: &str) -> Option<WormPolicy> {
+        self.worm.policy(collection)
+    }
+
+    /// Refuse to `action` a document of a WORM collection that is still
+    /// within its retention period.
+    pub(crate) fn check_worm(&self, existing: &DocumentMetadata, action: &str) -> Result<()> {
+        let Some(policy) = self.worm.policy(&existing.collection) else {
+            return Ok(());
+        };
+
+        let retained_until = policy.retained_until(existing.created_at);
+        if Utc::now() < retained_until {
+            return Err(anyhow::anyhow!(
+                "Cannot {} {}:{}: WORM collection retains it until {}",
+                action,
+                existing.collection,
+                existing.id,
+                retained_until.to_rfc3339()
+            ));
+        }
+        Ok(())
+    }
+}
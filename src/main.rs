@@ -19,7 +19,9 @@
 
 // Import essential dependencies for error handling, core database functionality, and logging
 use anyhow::Result;                    // Unified error handling with context preservation
-use aerolithdb_core::AerolithsDB;            // Main database orchestration engine
+use aerolithdb_core::{AerolithsDB, ConfigProfile, EffectiveConfig, parse_override}; // Database engine and configuration
+use aerolithdb_security::{install_redactor, RedactingMakeWriter, Redactor}; // Secret scrubbing of log output
+use std::sync::Arc;
 use tracing::{info, error};           // Structured logging for operational observability
 use tracing_subscriber;               // Logging configuration and output formatting
 use tokio::signal;                    // Async signal handling for graceful shutdown
@@ -45,16 +47,39 @@ use tokio::signal;                    // Async signal handling for graceful shut
 /// - Network communication with peer nodes
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Resolve the configuration profile from `--profile` or AEROLITHDB_PROFILE and
+    // load the effective configuration before logging starts, so the profile's
+    // log level and format apply from the first line
+    let args = LaunchArgs::parse()?;
+    let profile = ConfigProfile::resolve(args.profile.as_deref())?;
+    let effective = EffectiveConfig::load(profile, args.overrides).await?;
+    let config = effective.config.clone();
+
     // Initialize structured logging with JSON output for production deployments
     // Supports environment-based log level configuration (RUST_LOG=debug,aerolithsdb=trace)
-    // Default level is 'info' for aerolithsDB modules, with structured JSON formatting
+    // The aerolithsDB level comes from the configuration ('info' by default, 'debug'
+    // for the dev profile), with JSON formatting when structured logging is enabled
     // for integration with log aggregation systems (ELK stack, Datadog, etc.)
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env().add_directive("aerolithsdb=info".parse()?))
-        .json()  // JSON format for structured logging and log aggregation
-        .init();
+    // Every line passes through the configured redactor, which also scrubs the
+    // error messages the APIs return, so secrets and PII never reach the output
+    let logging = &config.observability.logging;
+    let filter = tracing_subscriber::EnvFilter::from_default_env()
+        .add_directive(format!("aerolithdb={}", logging.level).parse()?);
+    let redactor = Arc::new(Redactor::new(&config.security.redaction)?);
+    install_redactor(Arc::clone(&redactor));
+    let writer = RedactingMakeWriter::new(std::io::stdout, redactor);
+    if logging.structured {
+        tracing_subscriber::fmt().with_env_filter(filter).with_writer(writer).json().init();
+    } else {
+        tracing_subscriber::fmt().with_env_filter(filter).with_writer(writer).init();
+    }
 
     info!("Starting aerolithsDB distributed database");
+    info!(
+        profile = %profile.map(|p| p.to_string()).unwrap_or_else(|| "default".to_string()),
+        config = %effective.export()?,
+        "Effective configuration"
+    );
 
     // Initialize the complete database system with all subsystems
     // This creates and configures:
@@ -65,7 +90,7 @@ async fn main() -> Result<()> {
     // - Query processing engine with optimization
     // - API gateway supporting multiple protocols
     // - Plugin manager for extensibility
-    let mut db = match AerolithsDB::new().await {
+    let mut db = match AerolithsDB::new_with_config(config).await {
         Ok(db) => {
             info!("aerolithsDB initialized successfully");
             db
@@ -124,3 +149,38 @@ async fn main() -> Result<()> {
     info!("aerolithsDB stopped successfully");
     Ok(())
 }
+
+/// Command-line flags accepted by the server binary.
+#[derive(Default)]
+struct LaunchArgs {
+    /// `--profile <name>`: configuration preset to start from
+    profile: Option<String>,
+
+    /// `--set <path>=<value>`: explicit configuration overrides, repeatable
+    overrides: Vec<(String, String)>,
+}
+
+impl LaunchArgs {
+    fn parse() -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline.clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow::anyhow!("{} requires a value", flag))
+            };
+
+            match flag.as_str() {
+                "--profile" => parsed.profile = Some(value()?),
+                "--set" => parsed.overrides.push(parse_override(&value()?)?),
+                _ => return Err(anyhow::anyhow!("Unknown argument '{}'", arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
@@ -38,6 +38,9 @@ async fn main() -> anyhow::Result<()> {
         execution_timeout: std::time::Duration::from_secs(30),
         max_concurrent_queries: 100,
         index_advisor: true,
+        max_documents_scanned: None,
+        max_query_memory: None,
+        profiling: Default::default(),
     };
 
     // Initialize storage
@@ -134,6 +137,8 @@ async fn main() -> anyhow::Result<()> {
         limit: Some(5),
         offset: None,
         sort: Some(serde_json::json!({"value": 1})), // ascending
+        cursor: None,
+        as_of: None,
     };
 
     match query_engine.query_documents(test_collection, &query_request).await {
@@ -347,6 +347,8 @@ impl NetworkBattleTest {
                 bind_address: "127.0.0.1".to_string(),
                 port,
                 external_address: Some(format!("127.0.0.1:{}", port)),
+                fixtures_dir: None,
+                local_only: false,
             },
             network: NetworkConfig {
                 network_id: "test-network".to_string(),
@@ -368,6 +370,7 @@ impl NetworkBattleTest {
                 encryption_at_rest: true,
                 data_dir: data_dir.join("storage"),
                 max_storage_size: Some(1024 * 1024 * 1024), // 1GB limit for test
+                fsync: FsyncPolicy::Always,
             },
             cache: CacheConfig {
                 hierarchy: vec![CacheLayer::Memory],